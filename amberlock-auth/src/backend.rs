@@ -0,0 +1,209 @@
+//! 保险库加密后端
+//!
+//! Windows 上启用 `dpapi` 特性时用 DPAPI 加密；其他平台或未启用该特性时改用
+//! 由密码派生的密钥加密（Argon2id + ChaCha20-Poly1305，与 `portable` 模块相同的格式）。
+//!
+//! # 格式
+//! - DPAPI：DPAPI 输出原样保存，不加头部（与已有保险库及旧版本兼容）
+//! - 密码后端：`MAGIC`(4 字节) + 后端标识(1 字节) + `portable` 格式的密文
+//!
+//! DPAPI 输出总以版本号 `01 00 00 00` 开头，不会与 `MAGIC` 混淆。
+//!
+//! # 注意
+//! 密码后端以 `default` 凭据的密码加密整个保险库：
+//! - 打开与写回都需要该密码，不带密码的函数（`vault_metadata`、`list_credentials` 等）
+//!   返回 `PasswordRequired`
+//! - 其他凭据的密码与恢复密钥都解不开保险库：恢复密钥与修改其他凭据的密码返回
+//!   `UnsupportedOnPasswordBackend`，管理操作只能以 `default` 的密码授权
+//! - 密码错误与保险库被改动无法区分，校验时均按密码错误处理
+//! - 密码错误时无法写回失败计数，持久化锁定不生效（单次调用的退避照常）
+
+use crate::vault::Argon2Config;
+use crate::{binary, portable};
+use amberlock_types::{AmberlockError, Result};
+use zeroize::Zeroizing;
+
+/// 非 DPAPI 后端保险库的前缀
+pub const MAGIC: &[u8; 4] = b"ALVB";
+/// 头部长度：前缀与后端标识
+const HEADER_LEN: usize = MAGIC.len() + 1;
+
+/// 保险库使用的加密后端
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Backend {
+    /// Windows DPAPI（绑定用户或计算机）
+    Dpapi,
+    /// 由密码派生的密钥（DPAPI 不可用时的回退）
+    Password,
+}
+
+impl Backend {
+    /// 头部中的后端标识
+    const PASSWORD_ID: u8 = 1;
+
+    /// 本机创建与写回保险库时使用的后端
+    pub fn available() -> Self {
+        if cfg!(all(windows, feature = "dpapi")) {
+            Backend::Dpapi
+        } else {
+            Backend::Password
+        }
+    }
+
+    /// 识别保险库字节使用的后端（没有头部的视为 DPAPI）
+    pub fn of(blob: &[u8]) -> Self {
+        if blob.starts_with(MAGIC) {
+            Backend::Password
+        } else {
+            Backend::Dpapi
+        }
+    }
+}
+
+/// 是否带有有效的密码后端头部（前缀与已知的后端标识）
+///
+/// 只检查头部，不解密：不带密码时无法进一步确认密文完整
+pub fn has_password_header(blob: &[u8]) -> bool {
+    blob.len() > HEADER_LEN && blob.starts_with(MAGIC) && blob[MAGIC.len()] == Backend::PASSWORD_ID
+}
+
+/// 不带密码检查密码后端保险库的结构
+///
+/// 依次核对头部、导出格式的版本与 Argon2 参数，以及盐与随机数之后的密文（含认证标签）
+/// 不短于最短的保险库明文。截断后仍长于下限、或密文被改动，只有解密时才能发现
+///
+/// # 返回
+/// - `Err(VaultCorrupted)`: 头部无效或长度不足
+pub fn check_structure(blob: &[u8]) -> Result<()> {
+    if !has_password_header(blob) {
+        return Err(AmberlockError::VaultCorrupted(
+            "缺少密码后端头部".to_string(),
+        ));
+    }
+    let sealed = &blob[HEADER_LEN..];
+    portable::check_header(sealed).map_err(|e| match e {
+        AmberlockError::PortableFormat(reason) => AmberlockError::VaultCorrupted(reason),
+        other => other,
+    })?;
+    let min_len = portable::HEADER_LEN + binary::MIN_ENCODED_LEN + portable::TAG_LEN;
+    if sealed.len() < min_len {
+        return Err(AmberlockError::VaultCorrupted(format!(
+            "密文长度 {} 字节，不足 {} 字节",
+            sealed.len() - portable::HEADER_LEN,
+            min_len - portable::HEADER_LEN
+        )));
+    }
+    Ok(())
+}
+
+/// 以密码后端加密保险库明文
+pub(crate) fn protect(plain: &[u8], password: &str) -> Result<Vec<u8>> {
+    let sealed = portable::seal(plain, password, &Argon2Config::default())?;
+    let mut out = Vec::with_capacity(HEADER_LEN + sealed.len());
+    out.extend_from_slice(MAGIC);
+    out.push(Backend::PASSWORD_ID);
+    out.extend_from_slice(&sealed);
+    Ok(out)
+}
+
+/// 以密码解密密码后端的保险库
+///
+/// # 返回
+/// - `Err(WrongPassword)`: 密码错误或保险库被改动
+/// - `Err(VaultCorrupted)`: 后端标识未知或内部格式无效
+pub(crate) fn unprotect(blob: &[u8], password: &str) -> Result<Zeroizing<Vec<u8>>> {
+    match blob.get(MAGIC.len()) {
+        Some(&Backend::PASSWORD_ID) => {}
+        Some(other) => {
+            return Err(AmberlockError::VaultCorrupted(format!(
+                "未知的加密后端 {}",
+                other
+            )));
+        }
+        None => return Err(AmberlockError::VaultCorrupted("缺少后端标识".to_string())),
+    }
+    portable::open(&blob[HEADER_LEN..], password).map_err(|e| match e {
+        AmberlockError::PortableDecryptFailed => AmberlockError::WrongPassword,
+        AmberlockError::PortableFormat(reason) => AmberlockError::VaultCorrupted(reason),
+        other => other,
+    })
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_password_backend_round_trip() {
+        let blob = protect(b"vault plaintext", "secret").expect("加密失败");
+        assert_eq!(Backend::of(&blob), Backend::Password);
+        assert_eq!(
+            &unprotect(&blob, "secret").expect("解密失败")[..],
+            b"vault plaintext"
+        );
+        assert!(matches!(
+            unprotect(&blob, "wrong"),
+            Err(AmberlockError::WrongPassword)
+        ));
+
+        let mut tampered = blob.clone();
+        let last = tampered.len() - 1;
+        tampered[last] ^= 0x01;
+        assert!(matches!(
+            unprotect(&tampered, "secret"),
+            Err(AmberlockError::WrongPassword)
+        ));
+        assert!(has_password_header(&blob));
+        assert!(!has_password_header(&blob[..HEADER_LEN]));
+        let mut unknown = blob;
+        unknown[MAGIC.len()] = 9;
+        assert!(!has_password_header(&unknown));
+        assert!(matches!(
+            unprotect(&unknown, "secret"),
+            Err(AmberlockError::VaultCorrupted(_))
+        ));
+        println!("✅ 密码后端往返");
+    }
+
+    #[test]
+    fn test_check_structure_rejects_truncation() {
+        let vault = crate::vault::VaultBlob::with_default("secret").expect("创建保险库失败");
+        let blob = protect(&vault.encode().unwrap(), "secret").expect("加密失败");
+        check_structure(&blob).expect("完整的保险库应通过检查");
+
+        // 截断到头部、随机数或明文下限以内，以及头部被改动，都不必解密即可发现
+        let min_len =
+            HEADER_LEN + portable::HEADER_LEN + binary::MIN_ENCODED_LEN + portable::TAG_LEN;
+        for len in [
+            HEADER_LEN,
+            HEADER_LEN + portable::HEADER_LEN - 1,
+            min_len - 1,
+        ] {
+            assert!(
+                matches!(
+                    check_structure(&blob[..len]),
+                    Err(AmberlockError::VaultCorrupted(_))
+                ),
+                "截断为 {} 字节后仍通过检查",
+                len
+            );
+        }
+        let mut future = blob.clone();
+        future[HEADER_LEN + portable::MAGIC.len()] += 1;
+        assert!(check_structure(&future).is_err());
+        assert!(check_structure(&[1, 0, 0, 0, 0xd0, 0x8c]).is_err());
+        println!("✅ 不带密码检查密码后端保险库的结构");
+    }
+
+    #[test]
+    fn test_backend_detection() {
+        // DPAPI 输出以版本号 1 开头，没有头部
+        assert_eq!(Backend::of(&[1, 0, 0, 0, 0xd0, 0x8c]), Backend::Dpapi);
+        assert_eq!(Backend::of(b""), Backend::Dpapi);
+        assert_eq!(
+            Backend::available() == Backend::Dpapi,
+            cfg!(all(windows, feature = "dpapi"))
+        );
+        println!("✅ 按头部识别加密后端");
+    }
+}
@@ -0,0 +1,243 @@
+//! 密码校验退避
+//!
+//! 校验失败后补齐延迟到随机化的目标时长，使脚本化的暴力尝试无法按固定节奏流水线化，
+//! 也使“解析即失败”与“完整执行 Argon2 后失败”在耗时上无法区分。
+//!
+//! # 规则
+//! - 目标时长 = 基础延迟 × (1 ± 20% 随机抖动)
+//! - 实际等待 = max(目标时长 − 已耗时, 最小附加延迟)，即每次失败至少再等待 100 ms
+//! - 基础延迟不低于 250 ms，无法通过配置关闭
+//! - 可选对成功路径应用同样的补齐（高安全部署）
+//! - 带退避的校验函数以 `TimedGuard` 计时，守卫创建后的任何出口都按失败补齐，仅显式标记成功的除外
+
+use amberlock_types::Settings;
+use std::time::{Duration, Instant};
+
+/// 基础延迟下限
+pub const MIN_BASE_DELAY: Duration = Duration::from_millis(250);
+/// 默认基础延迟
+pub const DEFAULT_BASE_DELAY: Duration = Duration::from_millis(500);
+/// 每次退避至少附加的延迟
+pub const MIN_EXTRA_DELAY: Duration = Duration::from_millis(100);
+/// 抖动幅度（相对基础延迟的比例）
+pub const JITTER_RATIO: f64 = 0.2;
+
+/// 退避配置
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct BackoffConfig {
+    base_delay: Duration,
+    pad_success: bool,
+}
+
+impl Default for BackoffConfig {
+    fn default() -> Self {
+        Self::new(DEFAULT_BASE_DELAY)
+    }
+}
+
+impl BackoffConfig {
+    /// 创建配置（基础延迟低于 `MIN_BASE_DELAY` 时按下限处理）
+    pub fn new(base_delay: Duration) -> Self {
+        Self {
+            base_delay: base_delay.max(MIN_BASE_DELAY),
+            pad_success: false,
+        }
+    }
+
+    /// 从应用设置构造
+    pub fn from_settings(settings: &Settings) -> Self {
+        Self::new(Duration::from_millis(settings.backoff_base_ms))
+            .with_pad_success(settings.backoff_pad_success)
+    }
+
+    /// 设置是否对成功路径同样补齐延迟
+    pub fn with_pad_success(mut self, pad_success: bool) -> Self {
+        self.pad_success = pad_success;
+        self
+    }
+
+    /// 生效的基础延迟
+    pub fn base_delay(&self) -> Duration {
+        self.base_delay
+    }
+
+    /// 是否对成功路径补齐延迟
+    pub fn pad_success(&self) -> bool {
+        self.pad_success
+    }
+}
+
+/// 计算还需等待的时长
+///
+/// # 参数
+/// - `elapsed`: 校验本身已耗时
+/// - `config`: 退避配置
+/// - `jitter`: 抖动系数，取值 [-1.0, 1.0]（超出范围时截断）
+pub fn backoff_delay(elapsed: Duration, config: &BackoffConfig, jitter: f64) -> Duration {
+    let factor = 1.0 + JITTER_RATIO * jitter.clamp(-1.0, 1.0);
+    let target = config.base_delay.mul_f64(factor);
+    target.saturating_sub(elapsed).max(MIN_EXTRA_DELAY)
+}
+
+/// 按配置补齐校验耗时
+///
+/// # 参数
+/// - `started`: 校验开始时刻
+/// - `succeeded`: 校验是否成功（成功且未启用 `pad_success` 时立即返回）
+/// - `config`: 退避配置
+pub fn apply_backoff(started: Instant, succeeded: bool, config: &BackoffConfig) {
+    if succeeded && !config.pad_success {
+        return;
+    }
+    let jitter = rand::random_range(-1.0..=1.0);
+    std::thread::sleep(backoff_delay(started.elapsed(), config, jitter));
+}
+
+/// 校验计时守卫
+///
+/// 创建时记录开始时刻，释放时按 `apply_backoff` 补齐延迟。未调用 `finish(true)` 的守卫
+/// 一律按失败处理，守卫创建之后的提前返回（包括 `?`）同样会补齐延迟
+#[must_use = "守卫立即释放时不会覆盖之后的校验"]
+pub struct TimedGuard<'a> {
+    started: Instant,
+    succeeded: bool,
+    config: &'a BackoffConfig,
+}
+
+impl<'a> TimedGuard<'a> {
+    /// 开始计时
+    pub fn start(config: &'a BackoffConfig) -> Self {
+        Self {
+            started: Instant::now(),
+            succeeded: false,
+            config,
+        }
+    }
+
+    /// 记录校验结果并补齐延迟
+    pub fn finish(mut self, succeeded: bool) {
+        self.succeeded = succeeded;
+    }
+}
+
+impl Drop for TimedGuard<'_> {
+    fn drop(&mut self) {
+        apply_backoff(self.started, self.succeeded, self.config);
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    const ITERATIONS: usize = 2000;
+
+    /// 对给定已耗时采样，返回 (最小, 最大, 平均) 等待时长
+    fn sample(elapsed: Duration, config: &BackoffConfig) -> (Duration, Duration, Duration) {
+        let delays: Vec<Duration> = (0..ITERATIONS)
+            .map(|_| backoff_delay(elapsed, config, rand::random_range(-1.0..=1.0)))
+            .collect();
+        let min = *delays.iter().min().unwrap();
+        let max = *delays.iter().max().unwrap();
+        let mean = delays.iter().sum::<Duration>() / ITERATIONS as u32;
+        (min, max, mean)
+    }
+
+    #[test]
+    fn test_delay_bounds_per_failure_class() {
+        let config = BackoffConfig::default();
+        let base = config.base_delay();
+
+        // 失败类别：解析即失败（几乎不耗时）、Argon2 完整执行后失败
+        for elapsed in [Duration::from_millis(1), Duration::from_millis(200)] {
+            let (min, max, mean) = sample(elapsed, &config);
+            let low = base.mul_f64(1.0 - JITTER_RATIO) - elapsed;
+            let high = base.mul_f64(1.0 + JITTER_RATIO) - elapsed;
+
+            assert!(min >= low, "{:?} 低于下界 {:?}", min, low);
+            assert!(max <= high, "{:?} 高于上界 {:?}", max, high);
+            // 抖动确实生效，且大致以目标为中心
+            assert!(max - min > base.mul_f64(JITTER_RATIO));
+            let expected = base - elapsed;
+            assert!(mean.abs_diff(expected) < base.mul_f64(0.05));
+            println!(
+                "✅ 已耗时 {:?}：等待 {:?} ~ {:?}，平均 {:?}",
+                elapsed, min, max, mean
+            );
+        }
+    }
+
+    #[test]
+    fn test_minimum_extra_delay_when_operation_was_slow() {
+        let config = BackoffConfig::default();
+
+        // 冷启动的 DPAPI/Argon2 已超过目标时长，仍需附加最小延迟
+        let (min, max, _) = sample(Duration::from_secs(2), &config);
+        assert_eq!(min, MIN_EXTRA_DELAY);
+        assert_eq!(max, MIN_EXTRA_DELAY);
+
+        // 刚好接近目标时长时同样不低于下限
+        let (min, _, _) = sample(config.base_delay(), &config);
+        assert!(min >= MIN_EXTRA_DELAY);
+    }
+
+    #[test]
+    fn test_base_delay_floor() {
+        assert_eq!(
+            BackoffConfig::new(Duration::ZERO).base_delay(),
+            MIN_BASE_DELAY
+        );
+        assert_eq!(
+            BackoffConfig::new(Duration::from_millis(800)).base_delay(),
+            Duration::from_millis(800)
+        );
+
+        let config = BackoffConfig::new(Duration::from_millis(10));
+        let (min, _, _) = sample(Duration::ZERO, &config);
+        assert!(min >= MIN_BASE_DELAY.mul_f64(1.0 - JITTER_RATIO));
+    }
+
+    #[test]
+    fn test_success_path_padding_is_optional() {
+        let config = BackoffConfig::default();
+        let started = Instant::now();
+        apply_backoff(started, true, &config);
+        assert!(started.elapsed() < MIN_EXTRA_DELAY);
+
+        let padded = BackoffConfig::new(MIN_BASE_DELAY).with_pad_success(true);
+        let started = Instant::now();
+        apply_backoff(started, true, &padded);
+        assert!(started.elapsed() >= MIN_BASE_DELAY.mul_f64(1.0 - JITTER_RATIO));
+    }
+
+    #[test]
+    fn test_timed_guard_pads_early_returns() {
+        let config = BackoffConfig::default();
+        let floor = config.base_delay().mul_f64(1.0 - JITTER_RATIO);
+
+        // 守卫创建后经 `?` 提前返回，未调用 finish
+        fn parse(config: &BackoffConfig, text: &str) -> Result<u32, std::num::ParseIntError> {
+            let guard = TimedGuard::start(config);
+            let value = text.parse()?;
+            guard.finish(true);
+            Ok(value)
+        }
+
+        let started = Instant::now();
+        assert!(parse(&config, "not a number").is_err());
+        assert!(
+            started.elapsed() >= floor,
+            "提前返回未补齐延迟: {:?}",
+            started.elapsed()
+        );
+
+        let started = Instant::now();
+        TimedGuard::start(&config).finish(false);
+        assert!(started.elapsed() >= floor);
+
+        let started = Instant::now();
+        assert_eq!(parse(&config, "42"), Ok(42));
+        assert!(started.elapsed() < MIN_EXTRA_DELAY);
+        println!("✅ 计时守卫对所有失败出口补齐延迟");
+    }
+}
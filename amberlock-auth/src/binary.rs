@@ -0,0 +1,470 @@
+//! 保险库 v5 二进制编码
+//!
+//! 明文以 `MAGIC` 开头，其后是 4 字节小端版本号与一串带标签的字段：
+//! `标签(1 字节) + 长度(4 字节小端) + 内容`。字段名不出现在明文中，
+//! 内存转储里也就看不出哪段字节是盐、哪段是哈希。
+//!
+//! # 字段
+//! - 顶层：创建时间、修改时间、DPAPI 范围、凭据（可重复，内容为嵌套字段）、
+//!   连续失败次数、最近失败时间、恢复密钥哈希（内容与凭据相同）、密码修改时间、密码提示、
+//!   TOTP 密钥
+//! - 凭据：名称、参数字符串、结构化参数、输出长度、盐、哈希、是否需要密钥文件、角色（管理员不写入）
+//! - 完整性校验：固定为最后一个字段，内容是此前全部字节的 SHA-256
+//!
+//! 读取时先核对完整性校验（常量时间比较），不一致或缺失时返回 `VaultTampered`，
+//! 之后才解析字段。未知标签被跳过，较新版本增加的字段不影响旧版本读取；
+//! 长度越界、重复的单值字段或缺少必需字段时返回 `VaultCorrupted`。
+
+use crate::dpapi::DpapiScope;
+use crate::lockout::LockoutState;
+use crate::totp::TotpSecret;
+use crate::vault::{
+    Argon2Params, Credential, RECOVERY_CREDENTIAL, Role, VAULT_VERSION_V5, VaultBlob,
+    constant_time_eq,
+};
+use amberlock_types::{AmberlockError, Result};
+use sha2::{Digest, Sha256};
+use std::collections::BTreeMap;
+use zeroize::Zeroizing;
+
+/// 二进制明文的前缀（JSON 明文总以 `{` 开头，不会与之混淆）
+pub const MAGIC: &[u8; 4] = b"ALV\0";
+
+const TAG_CREATED_AT: u8 = 1;
+const TAG_LAST_CHANGED_AT: u8 = 2;
+const TAG_DPAPI_SCOPE: u8 = 3;
+const TAG_CREDENTIAL: u8 = 4;
+const TAG_INTEGRITY: u8 = 5;
+const TAG_FAILED_ATTEMPTS: u8 = 6;
+const TAG_LAST_FAILURE_AT: u8 = 7;
+const TAG_RECOVERY: u8 = 8;
+const TAG_PASSWORD_CHANGED_AT: u8 = 9;
+const TAG_HINT: u8 = 10;
+const TAG_TOTP: u8 = 11;
+
+const TAG_NAME: u8 = 1;
+const TAG_PARAMS: u8 = 2;
+const TAG_ARGON2: u8 = 3;
+const TAG_OUTPUT_LEN: u8 = 4;
+const TAG_SALT: u8 = 5;
+const TAG_HASH: u8 = 6;
+const TAG_KEYFILE: u8 = 7;
+const TAG_ROLE: u8 = 8;
+
+/// 字段头长度（标签 + 长度）
+const FIELD_HEADER_LEN: usize = 1 + 4;
+/// 完整性校验字段的总长度（字段头 + SHA-256）
+const INTEGRITY_FIELD_LEN: usize = FIELD_HEADER_LEN + 32;
+/// 编码结果的长度下限：前缀、版本、DPAPI 范围与完整性校验（不计凭据）
+pub const MIN_ENCODED_LEN: usize = MAGIC.len() + 4 + FIELD_HEADER_LEN + 1 + INTEGRITY_FIELD_LEN;
+
+/// 明文是否为二进制格式
+pub fn is_binary(plain: &[u8]) -> bool {
+    plain.starts_with(MAGIC)
+}
+
+/// 读取二进制明文的版本号
+pub fn version(plain: &[u8]) -> Result<u32> {
+    let bytes = plain
+        .get(MAGIC.len()..MAGIC.len() + 4)
+        .ok_or_else(|| corrupted("缺少版本号"))?;
+    Ok(u32::from_le_bytes(bytes.try_into().unwrap()))
+}
+
+/// 编码为 v5 明文
+///
+/// # 注意
+/// 缓冲区按编码后的长度预先分配，写入过程中不会扩容，盐与哈希不会残留在
+/// 扩容时释放的旧缓冲区里；返回的明文由调用方负责清零
+pub fn encode(vault: &VaultBlob) -> Result<Vec<u8>> {
+    let mut out = Vec::with_capacity(encoded_len(vault));
+    out.extend_from_slice(MAGIC);
+    out.extend_from_slice(&VAULT_VERSION_V5.to_le_bytes());
+    if let Some(created_at) = &vault.created_at {
+        put(&mut out, TAG_CREATED_AT, created_at.as_bytes())?;
+    }
+    if let Some(last_changed_at) = &vault.last_changed_at {
+        put(&mut out, TAG_LAST_CHANGED_AT, last_changed_at.as_bytes())?;
+    }
+    let scope = match vault.dpapi_scope {
+        DpapiScope::CurrentUser => 0u8,
+        DpapiScope::LocalMachine => 1u8,
+    };
+    put(&mut out, TAG_DPAPI_SCOPE, &[scope])?;
+    if vault.lockout.failed_attempts > 0 {
+        put(
+            &mut out,
+            TAG_FAILED_ATTEMPTS,
+            &vault.lockout.failed_attempts.to_le_bytes(),
+        )?;
+    }
+    if let Some(last_failure_at) = &vault.lockout.last_failure_at {
+        put(&mut out, TAG_LAST_FAILURE_AT, last_failure_at.as_bytes())?;
+    }
+    if let Some(password_changed_at) = &vault.password_changed_at {
+        put(
+            &mut out,
+            TAG_PASSWORD_CHANGED_AT,
+            password_changed_at.as_bytes(),
+        )?;
+    }
+    if let Some(hint) = &vault.hint {
+        put(&mut out, TAG_HINT, hint.as_bytes())?;
+    }
+    if let Some(totp) = &vault.totp {
+        put(&mut out, TAG_TOTP, totp.as_bytes())?;
+    }
+
+    let recovery = vault
+        .recovery
+        .as_ref()
+        .map(|credential| (RECOVERY_CREDENTIAL, credential, TAG_RECOVERY));
+    let credentials = vault
+        .credentials
+        .iter()
+        .map(|(name, credential)| (name.as_str(), credential, TAG_CREDENTIAL));
+    for (name, credential, tag) in credentials.chain(recovery) {
+        // 嵌套字段的缓冲区同样含有盐与哈希，用完清零
+        let mut inner = Zeroizing::new(Vec::with_capacity(credential_len(name, credential)));
+        put(&mut inner, TAG_NAME, name.as_bytes())?;
+        put(&mut inner, TAG_PARAMS, credential.params.as_bytes())?;
+        if let Some(params) = credential.argon2 {
+            let mut bytes = Vec::with_capacity(12);
+            for value in [params.m_cost, params.t_cost, params.p_cost] {
+                bytes.extend_from_slice(&value.to_le_bytes());
+            }
+            put(&mut inner, TAG_ARGON2, &bytes)?;
+        }
+        if let Some(output_len) = credential.output_len {
+            let output_len = u32::try_from(output_len).map_err(|_| corrupted("输出长度过大"))?;
+            put(&mut inner, TAG_OUTPUT_LEN, &output_len.to_le_bytes())?;
+        }
+        put(&mut inner, TAG_SALT, &credential.salt)?;
+        put(&mut inner, TAG_HASH, &credential.hash)?;
+        if credential.keyfile {
+            put(&mut inner, TAG_KEYFILE, &[1])?;
+        }
+        if credential.role == Role::Operator {
+            put(&mut inner, TAG_ROLE, &[1])?;
+        }
+        put(&mut out, tag, &inner)?;
+    }
+    let digest = Sha256::digest(&out);
+    put(&mut out, TAG_INTEGRITY, &digest)?;
+    debug_assert_eq!(out.len(), encoded_len(vault));
+    Ok(out)
+}
+
+/// 编码后的总长度
+fn encoded_len(vault: &VaultBlob) -> usize {
+    let text_len =
+        |value: &Option<String>| value.as_ref().map_or(0, |v| FIELD_HEADER_LEN + v.len());
+    MAGIC.len()
+        + 4
+        + text_len(&vault.created_at)
+        + text_len(&vault.last_changed_at)
+        + FIELD_HEADER_LEN
+        + 1
+        + if vault.lockout.failed_attempts > 0 {
+            FIELD_HEADER_LEN + 4
+        } else {
+            0
+        }
+        + text_len(&vault.lockout.last_failure_at)
+        + text_len(&vault.password_changed_at)
+        + text_len(&vault.hint)
+        + vault
+            .totp
+            .as_ref()
+            .map_or(0, |totp| FIELD_HEADER_LEN + totp.as_bytes().len())
+        + vault.recovery.as_ref().map_or(0, |credential| {
+            FIELD_HEADER_LEN + credential_len(RECOVERY_CREDENTIAL, credential)
+        })
+        + vault
+            .credentials
+            .iter()
+            .map(|(name, credential)| FIELD_HEADER_LEN + credential_len(name, credential))
+            .sum::<usize>()
+        + INTEGRITY_FIELD_LEN
+}
+
+/// 单个凭据嵌套字段的长度
+fn credential_len(name: &str, credential: &Credential) -> usize {
+    let mut len = FIELD_HEADER_LEN * 4
+        + name.len()
+        + credential.params.len()
+        + credential.salt.len()
+        + credential.hash.len();
+    if credential.argon2.is_some() {
+        len += FIELD_HEADER_LEN + 12;
+    }
+    if credential.output_len.is_some() {
+        len += FIELD_HEADER_LEN + 4;
+    }
+    if credential.keyfile {
+        len += FIELD_HEADER_LEN + 1;
+    }
+    if credential.role == Role::Operator {
+        len += FIELD_HEADER_LEN + 1;
+    }
+    len
+}
+
+/// 解析 v5 明文
+///
+/// # 参数
+/// - `with_secrets`: 为 false 时跳过盐与哈希（只读取元数据），返回的凭据中二者为空
+///
+/// # 返回
+/// - `Err(VaultTampered)`: 完整性校验缺失或不一致
+/// - `Err(VaultCorrupted)`: 版本不支持，或校验通过但字段无效
+pub fn decode(plain: &[u8], with_secrets: bool) -> Result<VaultBlob> {
+    if !is_binary(plain) {
+        return Err(corrupted("缺少二进制格式前缀"));
+    }
+    let version = version(plain)?;
+    if version != VAULT_VERSION_V5 {
+        return Err(AmberlockError::VaultCorrupted(format!(
+            "不支持的保险库版本: {}",
+            version
+        )));
+    }
+
+    let fields = check_integrity(plain)?;
+
+    let mut created_at = None;
+    let mut last_changed_at = None;
+    let mut dpapi_scope = None;
+    let mut failed_attempts = None;
+    let mut last_failure_at = None;
+    let mut recovery = None;
+    let mut password_changed_at = None;
+    let mut hint = None;
+    let mut totp = None;
+    let mut credentials = BTreeMap::new();
+    for field in Fields::new(fields) {
+        let (tag, value) = field?;
+        match tag {
+            TAG_CREATED_AT => set_once(&mut created_at, text(value)?, "创建时间")?,
+            TAG_LAST_CHANGED_AT => set_once(&mut last_changed_at, text(value)?, "修改时间")?,
+            TAG_DPAPI_SCOPE => {
+                let scope = match value {
+                    [0] => DpapiScope::CurrentUser,
+                    [1] => DpapiScope::LocalMachine,
+                    _ => return Err(corrupted("无效的 DPAPI 范围")),
+                };
+                set_once(&mut dpapi_scope, scope, "DPAPI 范围")?;
+            }
+            TAG_FAILED_ATTEMPTS => {
+                if value.len() != 4 {
+                    return Err(corrupted("失败次数字段长度错误"));
+                }
+                set_once(&mut failed_attempts, u32_at(value, 0), "失败次数")?;
+            }
+            TAG_LAST_FAILURE_AT => set_once(&mut last_failure_at, text(value)?, "最近失败时间")?,
+            TAG_PASSWORD_CHANGED_AT => {
+                set_once(&mut password_changed_at, text(value)?, "密码修改时间")?
+            }
+            TAG_HINT => set_once(&mut hint, text(value)?, "密码提示")?,
+            TAG_TOTP => {
+                let secret = if with_secrets { value } else { &[] };
+                set_once(&mut totp, TotpSecret::from_bytes(secret), "TOTP 密钥")?;
+            }
+            TAG_RECOVERY => {
+                let (_, credential) = decode_credential(value, with_secrets)?;
+                set_once(&mut recovery, credential, "恢复密钥")?;
+            }
+            TAG_CREDENTIAL => {
+                let (name, credential) = decode_credential(value, with_secrets)?;
+                if credentials.insert(name.clone(), credential).is_some() {
+                    return Err(corrupted(&format!("重复的凭据 {:?}", name)));
+                }
+            }
+            _ => {}
+        }
+    }
+    if credentials.is_empty() {
+        return Err(corrupted("凭据表为空"));
+    }
+
+    Ok(VaultBlob {
+        version,
+        credentials,
+        created_at,
+        last_changed_at,
+        dpapi_scope: dpapi_scope.unwrap_or_default(),
+        lockout: LockoutState {
+            failed_attempts: failed_attempts.unwrap_or_default(),
+            last_failure_at,
+        },
+        recovery,
+        password_changed_at,
+        hint,
+        totp,
+    })
+}
+
+/// 核对末尾的完整性校验字段
+///
+/// # 返回
+/// 版本号之后、校验字段之前的字段字节
+fn check_integrity(plain: &[u8]) -> Result<&[u8]> {
+    let header_len = MAGIC.len() + 4;
+    let Some(split) = plain
+        .len()
+        .checked_sub(INTEGRITY_FIELD_LEN)
+        .filter(|&split| split >= header_len)
+    else {
+        return Err(tampered("缺少完整性校验"));
+    };
+    let (body, trailer) = plain.split_at(split);
+    if trailer[0] != TAG_INTEGRITY || u32_at(trailer, 1) != 32 {
+        return Err(tampered("缺少完整性校验"));
+    }
+    if !constant_time_eq(&Sha256::digest(body), &trailer[5..]) {
+        return Err(tampered("完整性校验不一致"));
+    }
+    Ok(&body[header_len..])
+}
+
+/// 解析单个凭据的嵌套字段
+fn decode_credential(bytes: &[u8], with_secrets: bool) -> Result<(String, Credential)> {
+    let mut name = None;
+    let mut params = None;
+    let mut argon2 = None;
+    let mut output_len = None;
+    let mut salt = None;
+    let mut hash = None;
+    let mut keyfile = None;
+    let mut role = None;
+    for field in Fields::new(bytes) {
+        let (tag, value) = field?;
+        match tag {
+            TAG_NAME => set_once(&mut name, text(value)?, "凭据名称")?,
+            TAG_PARAMS => set_once(&mut params, text(value)?, "参数字符串")?,
+            TAG_ARGON2 => {
+                if value.len() != 12 {
+                    return Err(corrupted("结构化参数长度错误"));
+                }
+                let parsed = Argon2Params {
+                    m_cost: u32_at(value, 0),
+                    t_cost: u32_at(value, 4),
+                    p_cost: u32_at(value, 8),
+                };
+                set_once(&mut argon2, parsed, "结构化参数")?;
+            }
+            TAG_OUTPUT_LEN => {
+                if value.len() != 4 {
+                    return Err(corrupted("输出长度字段长度错误"));
+                }
+                set_once(&mut output_len, u32_at(value, 0) as usize, "输出长度")?;
+            }
+            TAG_KEYFILE => {
+                if value != [1] {
+                    return Err(corrupted("无效的密钥文件标记"));
+                }
+                set_once(&mut keyfile, true, "密钥文件标记")?;
+            }
+            TAG_ROLE => {
+                let parsed = match value {
+                    [0] => Role::Admin,
+                    [1] => Role::Operator,
+                    _ => return Err(corrupted("无效的凭据角色")),
+                };
+                set_once(&mut role, parsed, "凭据角色")?;
+            }
+            TAG_SALT if with_secrets => set_once(&mut salt, Zeroizing::new(value.to_vec()), "盐")?,
+            TAG_HASH if with_secrets => {
+                set_once(&mut hash, Zeroizing::new(value.to_vec()), "哈希")?
+            }
+            _ => {}
+        }
+    }
+
+    let name = name.ok_or_else(|| corrupted("凭据缺少名称"))?;
+    let params = params.ok_or_else(|| corrupted(&format!("凭据 {:?} 缺少参数", name)))?;
+    // 解析中途出错时已读出的盐与哈希随 Zeroizing 清零；成功时移交给凭据
+    let (salt, hash) = if with_secrets {
+        let mut salt = salt.ok_or_else(|| corrupted(&format!("凭据 {:?} 缺少盐", name)))?;
+        let mut hash = hash.ok_or_else(|| corrupted(&format!("凭据 {:?} 缺少哈希", name)))?;
+        (std::mem::take(&mut *salt), std::mem::take(&mut *hash))
+    } else {
+        (Vec::new(), Vec::new())
+    };
+    Ok((
+        name,
+        Credential {
+            params,
+            argon2,
+            output_len,
+            salt,
+            hash,
+            keyfile: keyfile.unwrap_or_default(),
+            role: role.unwrap_or_default(),
+        },
+    ))
+}
+
+/// 依次读取 `标签 + 长度 + 内容` 字段
+struct Fields<'a> {
+    rest: &'a [u8],
+}
+
+impl<'a> Fields<'a> {
+    fn new(bytes: &'a [u8]) -> Self {
+        Self { rest: bytes }
+    }
+}
+
+impl<'a> Iterator for Fields<'a> {
+    type Item = Result<(u8, &'a [u8])>;
+
+    fn next(&mut self) -> Option<Self::Item> {
+        let (&tag, rest) = self.rest.split_first()?;
+        let Some(len) = rest.get(..4) else {
+            self.rest = &[];
+            return Some(Err(corrupted("字段长度被截断")));
+        };
+        let len = u32_at(len, 0) as usize;
+        let Some(value) = rest.get(4..4 + len) else {
+            self.rest = &[];
+            return Some(Err(corrupted("字段内容被截断")));
+        };
+        self.rest = &rest[4 + len..];
+        Some(Ok((tag, value)))
+    }
+}
+
+/// 写入一个字段
+fn put(out: &mut Vec<u8>, tag: u8, value: &[u8]) -> Result<()> {
+    let len = u32::try_from(value.len()).map_err(|_| corrupted("字段过长"))?;
+    out.push(tag);
+    out.extend_from_slice(&len.to_le_bytes());
+    out.extend_from_slice(value);
+    Ok(())
+}
+
+/// 单值字段只允许出现一次
+fn set_once<T>(slot: &mut Option<T>, value: T, field: &str) -> Result<()> {
+    if slot.is_some() {
+        return Err(corrupted(&format!("重复的{}", field)));
+    }
+    *slot = Some(value);
+    Ok(())
+}
+
+fn text(value: &[u8]) -> Result<String> {
+    String::from_utf8(value.to_vec()).map_err(|_| corrupted("文本字段不是 UTF-8"))
+}
+
+fn u32_at(bytes: &[u8], offset: usize) -> u32 {
+    u32::from_le_bytes(bytes[offset..offset + 4].try_into().unwrap())
+}
+
+fn corrupted(reason: &str) -> AmberlockError {
+    AmberlockError::VaultCorrupted(format!("二进制保险库无效: {}", reason))
+}
+
+fn tampered(reason: &str) -> AmberlockError {
+    AmberlockError::VaultTampered(reason.to_string())
+}
@@ -0,0 +1,423 @@
+//! 保险库诊断
+//!
+//! 保险库无法使用时，`vault_metadata` 等函数只返回第一处错误。`diagnose_vault` 不需要密码，
+//! 逐步检查保险库并记录每一步的结果，界面据此区分“文件来自其他用户或计算机”与
+//! “文件损坏或被截断”。
+//!
+//! # 步骤
+//! 1. 文件长度与加密后端（按头部识别，见 `backend` 模块）
+//! 2. DPAPI 解密（密码后端需要密码，跳过其后的步骤）
+//! 3. 明文格式（二进制或 JSON）、版本号与解析（二进制格式含完整性校验）
+//! 4. 各凭据的 Argon2 参数（见 `Credential::validated_params`）
+//!
+//! 支持人员的脚本不以创建者身份运行时无法解密，`describe_blob` 按头部识别字节的种类，
+//! `try_parse_plaintext_vault` 解析已在别处解密的明文，两者都不需要 DPAPI。
+//!
+//! # 注意
+//! - 诊断结果不含盐、哈希或密码提示；解密得到的明文用完清零
+//! - 本模块的函数都不需要也不会得到密码
+
+use crate::backend::{self, Backend};
+use crate::binary;
+use crate::dpapi;
+use crate::outcome::VerifyOutcome;
+use crate::portable;
+use crate::vault::{Argon2Params, RECOVERY_CREDENTIAL, VaultBlob};
+use amberlock_types::{AmberlockError, Result};
+use serde::Deserialize;
+use serde::de::IgnoredAny;
+use std::collections::BTreeMap;
+use zeroize::Zeroizing;
+
+/// 单个诊断步骤的结果
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum Check {
+    /// 通过
+    Passed,
+    /// 未通过（附原因）
+    Failed(String),
+    /// 前一步未通过或不适用，未执行
+    Skipped,
+}
+
+impl Check {
+    /// 是否通过
+    pub fn is_passed(&self) -> bool {
+        *self == Check::Passed
+    }
+}
+
+/// 保险库明文格式
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum PlainFormat {
+    /// 带字段标签的二进制编码（v5）
+    Binary,
+    /// JSON（v1、v3/v4）
+    Json,
+}
+
+/// DPAPI 输出的头部：版本号 1 与 DPAPI 提供者 GUID（df9d8cd0-1501-11d1-8c7a-00c04fc297eb）
+const DPAPI_HEADER: [u8; 20] = [
+    0x01, 0x00, 0x00, 0x00, 0xd0, 0x8c, 0x9d, 0xdf, 0x01, 0x15, 0xd1, 0x11, 0x8c, 0x7a, 0x00, 0xc0,
+    0x4f, 0xc2, 0x97, 0xeb,
+];
+
+/// 字节的种类（按头部识别，不解密）
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum BlobKind {
+    /// DPAPI 加密的保险库
+    Dpapi,
+    /// 密码后端加密的保险库（见 `backend` 模块）
+    PasswordSealed,
+    /// 可迁移的导出文件（见 `portable` 模块）
+    Portable,
+    /// 保险库明文
+    Plain(PlainFormat),
+    /// 无法识别
+    Unknown,
+}
+
+impl std::fmt::Display for BlobKind {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        let text = match self {
+            BlobKind::Dpapi => "DPAPI 加密的保险库",
+            BlobKind::PasswordSealed => "密码加密的保险库",
+            BlobKind::Portable => "导出文件",
+            BlobKind::Plain(PlainFormat::Binary) => "二进制保险库明文",
+            BlobKind::Plain(PlainFormat::Json) => "JSON 保险库明文",
+            BlobKind::Unknown => "无法识别的数据",
+        };
+        f.write_str(text)
+    }
+}
+
+/// 识别字节的种类
+///
+/// 只检查头部（JSON 另外检查语法），不解密、不校验内容，识别为明文不代表能解析为保险库
+pub fn describe_blob(bytes: &[u8]) -> BlobKind {
+    if bytes.starts_with(&DPAPI_HEADER) {
+        BlobKind::Dpapi
+    } else if bytes.starts_with(backend::MAGIC) {
+        BlobKind::PasswordSealed
+    } else if bytes.starts_with(portable::MAGIC) {
+        BlobKind::Portable
+    } else if binary::is_binary(bytes) {
+        BlobKind::Plain(PlainFormat::Binary)
+    } else if bytes.trim_ascii_start().starts_with(b"{")
+        && serde_json::from_slice::<IgnoredAny>(bytes).is_ok()
+    {
+        BlobKind::Plain(PlainFormat::Json)
+    } else {
+        BlobKind::Unknown
+    }
+}
+
+/// 解析已解密的保险库明文（不经过 DPAPI）
+///
+/// 用于检查在别处解密或由导出文件导入得到的明文：版本、参数、创建时间等
+///
+/// # 返回
+/// - `Ok(VaultBlob)`: 解析结果（含盐与哈希，不含密码）
+/// - `Err(VaultCorrupted)`: 不是保险库明文（加密的保险库、导出文件或无法识别的数据），或无法解析
+/// - `Err(VaultTampered)`: 二进制明文的完整性校验失败
+pub fn try_parse_plaintext_vault(bytes: &[u8]) -> Result<VaultBlob> {
+    match describe_blob(bytes) {
+        BlobKind::Plain(_) => VaultBlob::decode(bytes),
+        kind => Err(AmberlockError::VaultCorrupted(format!(
+            "{}不是保险库明文",
+            kind
+        ))),
+    }
+}
+
+/// 保险库诊断结果
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct VaultDiagnostics {
+    /// 保险库文件长度（字节）
+    pub blob_len: usize,
+    /// 加密后端
+    pub backend: Backend,
+    /// 解密
+    pub decryption: Check,
+    /// 明文格式（解密失败或无法识别时为 None）
+    pub format: Option<PlainFormat>,
+    /// 存储的格式版本（无法读取时为 None）
+    pub version: Option<u32>,
+    /// 明文解析（二进制格式含完整性校验）
+    pub parsing: Check,
+    /// 各凭据的 Argon2 参数（凭据名称 → 参数或参数无效的原因）
+    pub params: BTreeMap<String, std::result::Result<Argon2Params, String>>,
+    /// 保险库无法使用的归类（损坏，或属于其他用户或其他计算机）
+    pub problem: Option<VerifyOutcome>,
+}
+
+impl VaultDiagnostics {
+    /// 全部步骤均通过
+    pub fn is_healthy(&self) -> bool {
+        self.decryption.is_passed()
+            && self.parsing.is_passed()
+            && self.params.values().all(|params| params.is_ok())
+    }
+
+    /// 记录失败的步骤与归类
+    fn fail(&mut self, error: AmberlockError) -> Check {
+        Check::Failed(self.classify(error))
+    }
+
+    /// 按错误归类，返回给用户的原因
+    fn classify(&mut self, error: AmberlockError) -> String {
+        let reason = error.to_string();
+        if let Ok(problem) = VerifyOutcome::classify(Err(error)) {
+            self.problem = Some(problem);
+        }
+        reason
+    }
+
+    /// 检查解密后的明文
+    fn check_plain(&mut self, plain: &[u8]) {
+        #[derive(Deserialize)]
+        struct VersionProbe {
+            version: u32,
+        }
+
+        if binary::is_binary(plain) {
+            self.format = Some(PlainFormat::Binary);
+            self.version = binary::version(plain).ok();
+        } else if plain.trim_ascii_start().starts_with(b"{") {
+            self.format = Some(PlainFormat::Json);
+            self.version = serde_json::from_slice::<VersionProbe>(plain)
+                .ok()
+                .map(|probe| probe.version);
+        }
+
+        let vault = match VaultBlob::decode_unchecked(plain) {
+            Ok(vault) => vault,
+            Err(e) => {
+                self.parsing = self.fail(e);
+                return;
+            }
+        };
+        self.parsing = Check::Passed;
+
+        let credentials = vault.credentials.iter().map(|(n, c)| (n.as_str(), c));
+        let recovery = vault.recovery.iter().map(|c| (RECOVERY_CREDENTIAL, c));
+        for (name, credential) in credentials.chain(recovery) {
+            let params = credential
+                .validated_params(name)
+                .map_err(|e| self.classify(e));
+            self.params.insert(name.to_string(), params);
+        }
+    }
+}
+
+/// 诊断保险库（不需要密码）
+///
+/// # 参数
+/// - `blob`: 保险库文件的全部字节
+pub fn diagnose_vault(blob: &[u8]) -> VaultDiagnostics {
+    let mut diagnostics = VaultDiagnostics {
+        blob_len: blob.len(),
+        backend: Backend::of(blob),
+        decryption: Check::Skipped,
+        format: None,
+        version: None,
+        parsing: Check::Skipped,
+        params: BTreeMap::new(),
+        problem: None,
+    };
+    if blob.is_empty() {
+        diagnostics.decryption =
+            diagnostics.fail(AmberlockError::VaultCorrupted("文件为空".to_string()));
+        return diagnostics;
+    }
+    if diagnostics.backend == Backend::Password {
+        return diagnostics;
+    }
+
+    match dpapi::unprotect(blob, None) {
+        Ok(plain) => {
+            let plain = Zeroizing::new(plain);
+            diagnostics.decryption = Check::Passed;
+            diagnostics.check_plain(&plain);
+        }
+        Err(e) => diagnostics.decryption = diagnostics.fail(e),
+    }
+    diagnostics
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::vault::{Credential, DEFAULT_CREDENTIAL, VAULT_VERSION, VAULT_VERSION_V1};
+    use serde_json::json;
+
+    /// 解密成功后的诊断（DPAPI 之后的步骤与平台无关）
+    fn diagnose_plain(plain: &[u8]) -> VaultDiagnostics {
+        let mut diagnostics = VaultDiagnostics {
+            blob_len: plain.len(),
+            backend: Backend::Dpapi,
+            decryption: Check::Passed,
+            format: None,
+            version: None,
+            parsing: Check::Skipped,
+            params: BTreeMap::new(),
+            problem: None,
+        };
+        diagnostics.check_plain(plain);
+        diagnostics
+    }
+
+    #[test]
+    fn test_valid_vaults() {
+        let vault = VaultBlob::with_default("secret").expect("创建保险库失败");
+        let diagnostics = diagnose_plain(&vault.encode().expect("编码失败"));
+        assert!(diagnostics.is_healthy());
+        assert_eq!(diagnostics.format, Some(PlainFormat::Binary));
+        assert_eq!(diagnostics.version, Some(VAULT_VERSION));
+        assert_eq!(diagnostics.problem, None);
+        assert_eq!(
+            diagnostics.params[DEFAULT_CREDENTIAL],
+            Ok(vault.credentials[DEFAULT_CREDENTIAL].argon2.unwrap())
+        );
+
+        // 旧版 JSON 保险库同样可以诊断
+        let credential = Credential::new("secret").expect("生成凭据失败");
+        let legacy = serde_json::to_vec(&json!({
+            "version": VAULT_VERSION_V1,
+            "params": credential.params,
+            "salt": credential.salt,
+            "hash": credential.hash,
+        }))
+        .unwrap();
+        let diagnostics = diagnose_plain(&legacy);
+        assert!(diagnostics.is_healthy());
+        assert_eq!(diagnostics.format, Some(PlainFormat::Json));
+        assert_eq!(diagnostics.version, Some(VAULT_VERSION_V1));
+        println!("✅ 完好的保险库通过全部诊断");
+    }
+
+    #[test]
+    fn test_truncated_plaintext() {
+        let plain = VaultBlob::with_default("secret")
+            .expect("创建保险库失败")
+            .encode()
+            .expect("编码失败");
+        for len in [plain.len() - 1, plain.len() / 2, 6] {
+            let diagnostics = diagnose_plain(&plain[..len]);
+            assert!(!diagnostics.is_healthy());
+            assert_eq!(diagnostics.format, Some(PlainFormat::Binary));
+            assert!(matches!(diagnostics.parsing, Check::Failed(_)));
+            assert_eq!(diagnostics.problem, Some(VerifyOutcome::VaultCorrupted));
+            assert!(diagnostics.params.is_empty());
+        }
+        println!("✅ 截断的保险库归为损坏");
+    }
+
+    #[test]
+    fn test_foreign_and_malformed_json() {
+        // 不是保险库的 JSON
+        let diagnostics = diagnose_plain(br#"{"name": "something else"}"#);
+        assert_eq!(diagnostics.format, Some(PlainFormat::Json));
+        assert_eq!(diagnostics.version, None);
+        assert!(matches!(diagnostics.parsing, Check::Failed(_)));
+        assert_eq!(diagnostics.problem, Some(VerifyOutcome::VaultCorrupted));
+
+        // 未知版本
+        let diagnostics = diagnose_plain(br#"{"version": 99}"#);
+        assert_eq!(diagnostics.version, Some(99));
+        assert!(matches!(diagnostics.parsing, Check::Failed(_)));
+
+        // 格式正确，但凭据参数无效：解析通过，参数单独报告
+        let credential = Credential::new("secret").expect("生成凭据失败");
+        let plain = serde_json::to_vec(&json!({
+            "version": VAULT_VERSION_V1,
+            "params": "m=bogus",
+            "salt": credential.salt,
+            "hash": credential.hash,
+        }))
+        .unwrap();
+        let diagnostics = diagnose_plain(&plain);
+        assert!(diagnostics.parsing.is_passed());
+        assert!(diagnostics.params[DEFAULT_CREDENTIAL].is_err());
+        assert!(!diagnostics.is_healthy());
+        assert_eq!(diagnostics.problem, Some(VerifyOutcome::VaultCorrupted));
+
+        // 既不是二进制也不是 JSON
+        let diagnostics = diagnose_plain(b"\x00garbage");
+        assert_eq!(diagnostics.format, None);
+        assert!(matches!(diagnostics.parsing, Check::Failed(_)));
+        println!("✅ 非保险库 JSON 与无效参数被逐项报告");
+    }
+
+    #[test]
+    fn test_describe_blob() {
+        let vault = VaultBlob::with_default("secret").expect("创建保险库失败");
+        let binary = vault.encode().expect("编码失败");
+        assert_eq!(describe_blob(&binary), BlobKind::Plain(PlainFormat::Binary));
+        let json = serde_json::to_vec(&json!({"version": VAULT_VERSION_V1})).unwrap();
+        assert_eq!(describe_blob(&json), BlobKind::Plain(PlainFormat::Json));
+
+        let mut dpapi_output = DPAPI_HEADER.to_vec();
+        dpapi_output.extend_from_slice(&[0x5a; 64]);
+        assert_eq!(describe_blob(&dpapi_output), BlobKind::Dpapi);
+        let sealed = crate::backend::protect(&binary, "secret").expect("加密失败");
+        assert_eq!(describe_blob(&sealed), BlobKind::PasswordSealed);
+        let config = crate::vault::Argon2Config {
+            mem_kib: crate::vault::MIN_MEM_KIB,
+            time_cost: 1,
+            parallelism: 1,
+            output_len: 32,
+        };
+        let exported = portable::seal(&binary, "secret", &config).expect("导出失败");
+        assert_eq!(describe_blob(&exported), BlobKind::Portable);
+
+        // 随机字节、截断的头部与不完整的 JSON 无法识别
+        for _ in 0..32 {
+            let mut random = [0u8; 64];
+            rand::fill(&mut random[..]);
+            assert_eq!(describe_blob(&random), BlobKind::Unknown);
+        }
+        assert_eq!(describe_blob(&DPAPI_HEADER[..8]), BlobKind::Unknown);
+        assert_eq!(describe_blob(b""), BlobKind::Unknown);
+        assert_eq!(describe_blob(br#"{"version": 1"#), BlobKind::Unknown);
+        println!("✅ 按头部识别保险库、导出文件与明文");
+    }
+
+    #[test]
+    fn test_try_parse_plaintext_vault() {
+        let vault = VaultBlob::with_default("secret").expect("创建保险库失败");
+        let plain = vault.encode().expect("编码失败");
+        let parsed = try_parse_plaintext_vault(&plain).expect("解析失败");
+        assert_eq!(parsed.version, VAULT_VERSION);
+        assert_eq!(parsed.created_at, vault.created_at);
+        assert!(parsed.verify(DEFAULT_CREDENTIAL, "secret").unwrap());
+
+        // 加密的数据与随机字节不当作明文解析
+        let sealed = crate::backend::protect(&plain, "secret").expect("加密失败");
+        for bytes in [&sealed[..], &[0x42; 64][..], b""] {
+            assert!(matches!(
+                try_parse_plaintext_vault(bytes),
+                Err(AmberlockError::VaultCorrupted(_))
+            ));
+        }
+        println!("✅ 不经过 DPAPI 解析保险库明文");
+    }
+
+    #[test]
+    fn test_blob_level_checks() {
+        let empty = diagnose_vault(b"");
+        assert_eq!(empty.blob_len, 0);
+        assert!(matches!(empty.decryption, Check::Failed(_)));
+        assert_eq!(empty.parsing, Check::Skipped);
+        assert_eq!(empty.problem, Some(VerifyOutcome::VaultCorrupted));
+
+        // 密码后端无法在没有密码时解密
+        let sealed = crate::backend::protect(b"plain", "secret").expect("加密失败");
+        let diagnostics = diagnose_vault(&sealed);
+        assert_eq!(diagnostics.backend, Backend::Password);
+        assert_eq!(diagnostics.blob_len, sealed.len());
+        assert_eq!(diagnostics.decryption, Check::Skipped);
+        assert_eq!(diagnostics.problem, None);
+        println!("✅ 文件层面的诊断");
+    }
+}
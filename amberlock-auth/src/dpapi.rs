@@ -0,0 +1,246 @@
+//! DPAPI 封装
+//!
+//! 使用 `CryptProtectData`/`CryptUnprotectData` 将保险库明文绑定到当前用户
+//! （`DpapiScope::CurrentUser`，其他账户或其他机器上无法解密），或绑定到本机
+//! （`DpapiScope::LocalMachine`，本机任何账户均可解密，供以 SYSTEM 运行的计划任务
+//! 与管理员共用同一个保险库）。解密时由 DPAPI 数据自身决定范围，无需指定。
+//!
+//! 可选的附加熵（`pOptionalEntropy`）把数据进一步绑定到应用持有的密钥：
+//! 加密时提供了熵，解密时必须提供相同的熵，否则与数据损坏一样解密失败。
+//!
+//! 解密失败按原因区分：数据属于其他用户或其他机器（`NTE_BAD_KEY_STATE`）时返回
+//! `VaultWrongUserOrMachine`；提供了附加熵时返回 `VaultCorrupted`；其余情况视为数据被篡改，
+//! 返回 `VaultTampered`。
+//!
+//! 非 Windows 平台或未启用 `dpapi` 特性时，`protect`/`unprotect`
+//! 返回 `AmberlockError::Unsupported`。
+
+use amberlock_types::{AmberlockError, Result};
+use serde::{Deserialize, Serialize};
+#[cfg(all(windows, feature = "dpapi"))]
+use windows::Win32::Foundation::{HLOCAL, LocalFree, NTE_BAD_KEY_STATE};
+#[cfg(all(windows, feature = "dpapi"))]
+use windows::Win32::Security::Cryptography::{
+    CRYPT_INTEGER_BLOB, CRYPTPROTECT_LOCAL_MACHINE, CRYPTPROTECT_UI_FORBIDDEN, CryptProtectData,
+    CryptUnprotectData,
+};
+#[cfg(all(windows, feature = "dpapi"))]
+use windows::core::PCWSTR;
+#[cfg(all(windows, feature = "dpapi"))]
+use zeroize::Zeroize;
+
+/// DPAPI 密钥的范围
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash, Default, Serialize, Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub enum DpapiScope {
+    /// 当前用户（默认，仅创建者的账户可以解密）
+    #[default]
+    CurrentUser,
+    /// 本机（本机任何账户均可解密，包括 SYSTEM）
+    LocalMachine,
+}
+
+impl std::fmt::Display for DpapiScope {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self {
+            DpapiScope::CurrentUser => write!(f, "当前用户"),
+            DpapiScope::LocalMachine => write!(f, "本机"),
+        }
+    }
+}
+
+/// 使用指定范围的 DPAPI 密钥加密数据
+///
+/// # 参数
+/// - `entropy`: 附加熵（None 时不使用；解密时须提供相同的值）
+#[cfg(all(windows, feature = "dpapi"))]
+pub fn protect(plain: &[u8], scope: DpapiScope, entropy: Option<&[u8]>) -> Result<Vec<u8>> {
+    let input = borrow_blob(plain);
+    let entropy = entropy.map(borrow_blob);
+    let mut output = CRYPT_INTEGER_BLOB::default();
+    let flags = match scope {
+        DpapiScope::CurrentUser => CRYPTPROTECT_UI_FORBIDDEN,
+        DpapiScope::LocalMachine => CRYPTPROTECT_UI_FORBIDDEN | CRYPTPROTECT_LOCAL_MACHINE,
+    };
+
+    unsafe {
+        CryptProtectData(
+            &input,
+            PCWSTR::null(),
+            entropy.as_ref().map(|blob| blob as *const _),
+            None,
+            None,
+            flags,
+            &mut output,
+        )
+        .map_err(|e| AmberlockError::Win32 {
+            code: e.code().0 as u32,
+            msg: format!("DPAPI 加密失败: {}", e),
+        })?;
+
+        Ok(take_blob(output))
+    }
+}
+
+/// 解密 DPAPI 数据（当前用户与本机范围的数据均可）
+///
+/// # 参数
+/// - `entropy`: 加密时使用的附加熵（未使用时为 None）
+///
+/// # 返回
+/// - `Err(VaultWrongUserOrMachine)`: 数据属于其他用户或其他机器
+/// - `Err(VaultCorrupted)`: 附加熵不一致
+/// - `Err(VaultTampered)`: 未使用附加熵时数据被篡改或根本不是 DPAPI 数据
+///
+/// # 注意
+/// - DPAPI 无法区分附加熵不一致与数据被篡改，提供了附加熵时一律按 `VaultCorrupted` 返回
+/// - 系统分配的输出缓冲区在释放前清零；返回的明文由调用方负责清零（如以 `Zeroizing` 包装）
+#[cfg(all(windows, feature = "dpapi"))]
+pub fn unprotect(blob: &[u8], entropy: Option<&[u8]>) -> Result<Vec<u8>> {
+    let input = borrow_blob(blob);
+    let entropy = entropy.map(borrow_blob);
+    let mut output = CRYPT_INTEGER_BLOB::default();
+
+    unsafe {
+        CryptUnprotectData(
+            &input,
+            None,
+            entropy.as_ref().map(|blob| blob as *const _),
+            None,
+            None,
+            CRYPTPROTECT_UI_FORBIDDEN,
+            &mut output,
+        )
+        .map_err(|e| {
+            if e.code() == NTE_BAD_KEY_STATE {
+                AmberlockError::VaultWrongUserOrMachine(format!("DPAPI 解密失败: {}", e))
+            } else if entropy.is_some() {
+                AmberlockError::VaultCorrupted(format!("DPAPI 解密失败: {}", e))
+            } else {
+                AmberlockError::VaultTampered(format!("DPAPI 解密失败: {}", e))
+            }
+        })?;
+
+        Ok(take_blob(output))
+    }
+}
+
+/// 以切片构造只读的 DPAPI 输入（DPAPI 不会写入输入缓冲区）
+#[cfg(all(windows, feature = "dpapi"))]
+fn borrow_blob(data: &[u8]) -> CRYPT_INTEGER_BLOB {
+    CRYPT_INTEGER_BLOB {
+        cbData: data.len() as u32,
+        pbData: data.as_ptr() as *mut u8,
+    }
+}
+
+/// 复制 DPAPI 输出，清零并释放系统分配的缓冲区（解密输出即保险库明文）
+#[cfg(all(windows, feature = "dpapi"))]
+unsafe fn take_blob(blob: CRYPT_INTEGER_BLOB) -> Vec<u8> {
+    unsafe {
+        let buffer = std::slice::from_raw_parts_mut(blob.pbData, blob.cbData as usize);
+        let data = buffer.to_vec();
+        buffer.zeroize();
+        let _ = LocalFree(Some(HLOCAL(blob.pbData as *mut _)));
+        data
+    }
+}
+
+/// DPAPI 不可用时的加密占位实现
+#[cfg(not(all(windows, feature = "dpapi")))]
+pub fn protect(_plain: &[u8], _scope: DpapiScope, _entropy: Option<&[u8]>) -> Result<Vec<u8>> {
+    Err(AmberlockError::Unsupported)
+}
+
+/// DPAPI 不可用时的解密占位实现
+#[cfg(not(all(windows, feature = "dpapi")))]
+pub fn unprotect(_blob: &[u8], _entropy: Option<&[u8]>) -> Result<Vec<u8>> {
+    Err(AmberlockError::Unsupported)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    #[cfg(not(all(windows, feature = "dpapi")))]
+    fn test_unavailable_without_dpapi() {
+        assert!(matches!(
+            protect(b"amberlock", DpapiScope::LocalMachine, None),
+            Err(AmberlockError::Unsupported)
+        ));
+        assert!(matches!(
+            unprotect(b"blob", Some(&b"entropy"[..])),
+            Err(AmberlockError::Unsupported)
+        ));
+    }
+
+    #[test]
+    #[cfg(all(windows, feature = "dpapi"))]
+    fn test_protect_round_trip() {
+        let blob = protect(b"amberlock", DpapiScope::CurrentUser, None).expect("DPAPI 加密失败");
+        assert_ne!(blob, b"amberlock");
+        assert_eq!(
+            unprotect(&blob, None).expect("DPAPI 解密失败"),
+            b"amberlock"
+        );
+    }
+
+    #[test]
+    #[cfg(all(windows, feature = "dpapi"))]
+    fn test_local_machine_round_trip() {
+        let user = protect(b"amberlock", DpapiScope::CurrentUser, None).expect("DPAPI 加密失败");
+        let machine =
+            protect(b"amberlock", DpapiScope::LocalMachine, None).expect("DPAPI 加密失败");
+        assert_ne!(machine, user);
+        assert_eq!(
+            unprotect(&machine, None).expect("DPAPI 解密失败"),
+            b"amberlock"
+        );
+    }
+
+    #[test]
+    #[cfg(all(windows, feature = "dpapi"))]
+    fn test_entropy_round_trip() {
+        let blob = protect(
+            b"amberlock",
+            DpapiScope::CurrentUser,
+            Some(&b"app-secret"[..]),
+        )
+        .expect("DPAPI 加密失败");
+        assert_eq!(
+            unprotect(&blob, Some(&b"app-secret"[..])).expect("DPAPI 解密失败"),
+            b"amberlock"
+        );
+        assert!(matches!(
+            unprotect(&blob, Some(&b"other-secret"[..])),
+            Err(AmberlockError::VaultCorrupted(_))
+        ));
+        // 未提供附加熵时与篡改无法区分
+        assert!(matches!(
+            unprotect(&blob, None),
+            Err(AmberlockError::VaultTampered(_))
+        ));
+    }
+
+    #[test]
+    #[cfg(all(windows, feature = "dpapi"))]
+    fn test_unprotect_garbage_is_tampered() {
+        let err = unprotect(b"not a dpapi blob", None).expect_err("应拒绝非 DPAPI 数据");
+        assert!(matches!(err, AmberlockError::VaultTampered(_)));
+    }
+
+    #[test]
+    #[cfg(all(windows, feature = "dpapi"))]
+    fn test_flipped_ciphertext_is_tampered() {
+        let blob = protect(b"amberlock", DpapiScope::CurrentUser, None).expect("DPAPI 加密失败");
+        for offset in [blob.len() / 2, blob.len() - 1] {
+            let mut modified = blob.clone();
+            modified[offset] ^= 0x01;
+            assert!(matches!(
+                unprotect(&modified, None),
+                Err(AmberlockError::VaultTampered(_))
+            ));
+        }
+    }
+}
@@ -0,0 +1,1790 @@
+//! AmberLock 密码保险库
+//!
+//! 保险库是一段经 DPAPI 加密的字节串，内部保存若干命名凭据（Argon2id 哈希）。
+//! 调用方负责保险库字节的持久化，本 crate 只负责加解密、校验与凭据管理。
+//!
+//! 单一密码 API（`create_vault`/`verify_password`）作用于名为 `default` 的凭据，
+//! 与旧版保险库保持兼容。
+//!
+//! 保险库默认以当前用户的 DPAPI 密钥加密；需要与以 SYSTEM 运行的计划任务共用时，
+//! 用 `create_vault_scoped` 以本机范围创建。范围记录在保险库中，之后的修改沿用。
+//!
+//! `create_vault_with_entropy` 额外以应用持有的密钥（DPAPI 附加熵）加密，只复制
+//! 保险库文件而没有该密钥时无法打开。附加熵不记录在保险库中，这类保险库须经
+//! `*_with_entropy` 函数访问，其他函数会按保险库被篡改处理。
+//!
+//! 保险库被篡改（密文或明文被改动、完整性校验不一致）时返回 `VaultTampered`，
+//! 应提示用户从备份恢复；保险库属于其他用户或其他计算机时返回
+//! `VaultWrongUserOrMachine`；格式无法识别时返回 `VaultCorrupted`。
+//! 需要据此提示用户时使用 `verify_password_detailed`，结果已归类为 `VerifyOutcome`。
+//!
+//! 保险库无法使用时，`diagnose_vault` 不需要密码，逐步报告文件长度、解密、明文格式与版本、
+//! 各凭据的 Argon2 参数，据此区分文件来自其他用户或计算机与文件损坏或被截断。
+//! 支持工具可用 `describe_blob` 识别字节的种类，用 `try_parse_plaintext_vault` 解析已在别处解密的明文。
+//!
+//! 默认 Argon2 参数提高后，已有凭据不会自动跟进。`verify_and_upgrade` 在密码正确且
+//! `default` 凭据的参数低于当前默认值时，以当前参数重新计算哈希并返回更新后的保险库，
+//! 调用方写回即可；`verify_password_tracked` 同样会顺带升级。
+//!
+//! `verify_password_tracked` 另把连续失败次数记录在保险库中，按失败次数递增锁定时长
+//! （见 `lockout` 模块），重启程序无法绕过；锁定情况可用 `get_lockout_state` 读取。
+//!
+//! 每个凭据带有角色（`Role`）：管理员可执行全部操作并管理凭据，操作员只读。
+//! `add_slot`/`remove_slot` 增删凭据（需管理员密码），`verify_slot` 识别密码属于哪个凭据，
+//! 全部凭据按固定顺序校验，退避只补齐一次。
+//!
+//! `create_vault_with_keyfile` 创建需要“密码 + 密钥文件”的保险库，须经
+//! `verify_password_with_keyfile` 校验；`verify_password` 等不带密钥文件的函数
+//! 对这类保险库一律按密码错误返回。
+//!
+//! 保险库记录创建时间、密码修改时间与可选的密码提示（`create_vault_with_hint`、
+//! `set_password_hint`），均可经 `get_vault_info` 在不输入密码的情况下读取。
+//! 提示以明文保存在保险库中，不能包含密码本身。
+//!
+//! 创建保险库的函数不检查密码强度；界面应先用 `estimate_password_strength` 评估，
+//! 低于 `MIN_RECOMMENDED_SCORE` 时要求用户确认。
+//!
+//! `create_vault_with_recovery` 同时生成恢复密钥，忘记密码时可凭它经
+//! `reset_password_with_recovery` 重设 `default` 凭据的密码（见 `recovery` 模块）。
+//!
+//! `enable_totp` 为保险库启用 TOTP 第二因素（见 `totp` 模块），返回的密钥应立即添加到
+//! 验证器应用；启用后以 `verify_totp` 同时校验密码与验证码，`disable_totp` 停用。
+//! `VaultMetadata::totp_enabled` 无需密码即可判断是否需要验证码。启用后只凭密码的校验
+//! （`verify_password`、`verify_password_tracked` 等）一律不通过，与需要密钥文件的保险库
+//! 未提供密钥文件时相同。
+//!
+//! 管理操作（增删改凭据、修改密码与提示、启停 TOTP、迁移、导出与导入）带 `code` 参数，
+//! 统一经 `VaultBlob::authorize` 授权：先检查锁定，再校验密码，启用 TOTP 时再校验验证码。
+//! 失败与 `verify_password_tracked` 一样计入失败次数，以 `AuthorizationFailed` 返回记录了
+//! 本次失败的保险库，调用方应写回；密码后端的保险库密码错误时无法解密，只返回 `WrongPassword`。
+//!
+//! 保险库绑定于 DPAPI，换用户或换计算机后无法打开。`export_portable` 以密码派生的密钥
+//! 重新加密为可迁移的导出文件（见 `portable` 模块），在新计算机上用 `import_portable`
+//! 凭同一密码导入，得到新的 DPAPI 保险库。
+//!
+//! Argon2id 计算会阻塞调用线程数百毫秒。界面线程上改用 `create_vault_async` 与
+//! `verify_password_async`，在后台线程中执行并返回 `VaultTask`（见 `task` 模块），
+//! 可阻塞等待或作为 Future 在事件循环中等待。
+//!
+//! 解密得到的明文、序列化后的明文与计算出的哈希在用完后清零；调用方持有的密码
+//! 可用 `SecretString` 包装，释放时同样清零。
+//!
+//! # 特性
+//! - `dpapi`（默认）：在 Windows 上使用 DPAPI 加解密保险库。未启用或非 Windows 平台上，
+//!   创建与写回保险库改用由 `default` 凭据密码派生的密钥加密（见 `backend` 模块），
+//!   带密码的函数签名与行为不变；不带密码的函数（`vault_metadata`、`list_credentials`）
+//!   对这类保险库返回 `PasswordRequired`，附加熵与 DPAPI 范围不适用。
+//!   保险库头部记录所用后端（`Backend`），写回时改用本机可用的后端。
+//!   只有 `default` 凭据的密码能解密这类保险库：其他凭据的密码校验不通过，管理操作须以
+//!   `default` 的密码授权，修改其他凭据的密码与恢复密钥（无法不凭密码解密）返回
+//!   `UnsupportedOnPasswordBackend`
+
+pub mod backend;
+pub mod backoff;
+mod binary;
+pub mod diagnose;
+pub mod dpapi;
+pub mod lockout;
+mod outcome;
+pub mod portable;
+pub mod recovery;
+mod secret;
+pub mod strength;
+pub mod task;
+pub mod totp;
+pub mod vault;
+
+pub use backend::Backend;
+pub use backoff::{BackoffConfig, TimedGuard, apply_backoff};
+pub use diagnose::{
+    BlobKind, Check, PlainFormat, VaultDiagnostics, describe_blob, diagnose_vault,
+    try_parse_plaintext_vault,
+};
+pub use dpapi::DpapiScope;
+pub use lockout::{LockoutInfo, LockoutState};
+pub use outcome::VerifyOutcome;
+pub use recovery::RecoveryKey;
+pub use secret::SecretString;
+pub use strength::{MIN_RECOMMENDED_SCORE, StrengthReport, estimate_password_strength};
+pub use task::VaultTask;
+pub use totp::TotpSecret;
+pub use vault::{
+    Argon2Config, Argon2Params, Credential, DEFAULT_CREDENTIAL, Role, SlotInfo, VAULT_VERSION,
+    VaultBlob, VaultInfo, VaultMetadata, calibrate, needs_rehash, parse_params,
+};
+
+use amberlock_types::{AmberlockError, Result};
+use time::OffsetDateTime;
+use zeroize::Zeroizing;
+
+/// 创建仅含 `default` 凭据的新保险库
+///
+/// # 返回
+/// DPAPI 加密后的保险库字节（DPAPI 不可用时以密码加密，见 `backend` 模块）
+pub fn create_vault(password: &str) -> Result<Vec<u8>> {
+    seal_for(&VaultBlob::with_default(password)?, password)
+}
+
+/// 创建带密码提示的新保险库
+///
+/// # 返回
+/// - `Err(InvalidHint)`: 提示超过 64 个字符、含有控制字符或包含密码本身
+pub fn create_vault_with_hint(password: &str, hint: Option<&str>) -> Result<Vec<u8>> {
+    let mut vault = VaultBlob::with_default(password)?;
+    vault.set_hint(password, None, hint)?;
+    vault.last_changed_at = vault.created_at.clone();
+    seal_for(&vault, password)
+}
+
+/// 以指定的 DPAPI 范围创建仅含 `default` 凭据的新保险库
+///
+/// # 返回
+/// DPAPI 加密后的保险库字节；`DpapiScope::LocalMachine` 时本机任何账户均可打开
+pub fn create_vault_scoped(password: &str, scope: DpapiScope) -> Result<Vec<u8>> {
+    let mut vault = VaultBlob::with_default(password)?;
+    vault.dpapi_scope = scope;
+    seal_for(&vault, password)
+}
+
+/// 以附加熵加密、仅含 `default` 凭据的新保险库
+///
+/// # 参数
+/// - `entropy`: 应用持有的密钥（校验时须提供相同的值）
+pub fn create_vault_with_entropy(password: &str, entropy: &[u8]) -> Result<Vec<u8>> {
+    seal_with(&VaultBlob::with_default(password)?, Some(entropy))
+}
+
+/// 创建仅含 `default` 凭据的新保险库，并生成恢复密钥
+///
+/// # 返回
+/// - `Ok((blob, key))`: DPAPI 加密后的保险库字节与恢复密钥。恢复密钥只在此时返回一次，
+///   须提示用户抄写保管；保险库中只保存它的 Argon2id 哈希
+/// - `Err(UnsupportedOnPasswordBackend)`: 本机只能使用密码后端，忘记密码后恢复密钥也无法解密保险库
+pub fn create_vault_with_recovery(password: &str) -> Result<(Vec<u8>, RecoveryKey)> {
+    require_dpapi_for(Backend::available(), "恢复密钥")?;
+    let mut vault = VaultBlob::with_default(password)?;
+    let key = vault.set_recovery()?;
+    Ok((seal_for(&vault, password)?, key))
+}
+
+/// 创建仅含 `default` 凭据、需要密码与密钥文件的新保险库
+///
+/// # 参数
+/// - `keyfile`: 密钥文件内容（任意非空字节，如 U 盘上的随机文件），只保存其摘要参与哈希
+///
+/// # 返回
+/// - `Ok(blob)`: DPAPI 加密后的保险库字节
+/// - `Err(InvalidKeyfile)`: 密钥文件为空
+pub fn create_vault_with_keyfile(password: &str, keyfile: &[u8]) -> Result<Vec<u8>> {
+    seal_for(
+        &VaultBlob::with_default_keyfile(password, keyfile)?,
+        password,
+    )
+}
+
+/// 使用指定的 Argon2 配置创建仅含 `default` 凭据的新保险库
+///
+/// # 返回
+/// - `Ok(blob)`: DPAPI 加密后的保险库字节
+/// - `Err(UnsafeArgon2Config)`: 配置不安全（如内存低于 8 MiB）
+pub fn create_vault_with_config(password: &str, config: &Argon2Config) -> Result<Vec<u8>> {
+    seal_for(&VaultBlob::with_default_config(password, config)?, password)
+}
+
+/// 在后台线程中创建保险库（见 `create_vault`）
+///
+/// 密码由任务接管，计算完成后清零
+pub fn create_vault_async(password: SecretString) -> VaultTask<Vec<u8>> {
+    VaultTask::spawn(move || create_vault(password.as_str()))
+}
+
+/// 校验 `default` 凭据的密码
+pub fn verify_password(blob: &[u8], password: &str) -> Result<bool> {
+    verify_password_for(blob, DEFAULT_CREDENTIAL, password)
+}
+
+/// 校验指定凭据的密码
+///
+/// # 返回
+/// - `Ok(true/false)`: 密码是否匹配；启用 TOTP 的保险库总是 false（须改用 `verify_totp`）
+/// - `Err(CredentialNotFound)`: 凭据不存在
+/// - `Err(VaultTampered)`: 保险库被篡改（应从备份恢复）
+/// - `Err(VaultWrongUserOrMachine)`: 保险库属于其他用户或其他计算机
+/// - `Err(VaultCorrupted)`: 保险库无法解析
+pub fn verify_password_for(blob: &[u8], name: &str, password: &str) -> Result<bool> {
+    match open_for(blob, password) {
+        // 密码后端：密码错误时无法解密
+        Err(AmberlockError::WrongPassword) => Ok(false),
+        vault => {
+            let vault = vault?;
+            Ok(vault.verify(name, password)? && vault.totp.is_none())
+        }
+    }
+}
+
+/// 校验指定凭据的密码并按配置退避
+///
+/// # 注意
+/// 任何失败（密码错误、凭据不存在、保险库损坏）都会补齐延迟后再返回，
+/// 调用方无法通过耗时区分失败原因；成功路径仅在 `pad_success` 时补齐
+pub fn verify_password_with_backoff(
+    blob: &[u8],
+    name: &str,
+    password: &str,
+    config: &BackoffConfig,
+) -> Result<bool> {
+    let guard = TimedGuard::start(config);
+    let result = verify_password_for(blob, name, password);
+    guard.finish(matches!(result, Ok(true)));
+    result
+}
+
+/// 校验 `default` 凭据的密码，参数低于当前默认值时一并升级
+///
+/// # 返回
+/// - `Ok((true, Some(blob)))`: 密码正确且已按当前默认参数重新计算哈希，调用方须写回
+/// - `Ok((true, None))`: 密码正确，参数无需升级
+/// - `Ok((false, None))`: 密码错误
+///
+/// # 注意
+/// 新参数逐项取原参数与默认值中较强的一方，不会降低任何一项；
+/// 需要密钥文件的凭据不在此升级；启用 TOTP 的保险库按密码错误处理
+pub fn verify_and_upgrade(blob: &[u8], password: &str) -> Result<(bool, Option<Vec<u8>>)> {
+    let mut vault = match open_for(blob, password) {
+        Err(AmberlockError::WrongPassword) => return Ok((false, None)),
+        vault => vault?,
+    };
+    if !vault.verify(DEFAULT_CREDENTIAL, password)? || vault.totp.is_some() {
+        return Ok((false, None));
+    }
+    let upgraded = upgrade_default(&mut vault, password)?;
+    Ok((
+        true,
+        upgraded.then(|| seal_for(&vault, password)).transpose()?,
+    ))
+}
+
+/// 密码已校验通过后按当前默认参数升级 `default` 凭据
+fn upgrade_default(vault: &mut VaultBlob, password: &str) -> Result<bool> {
+    let target = Argon2Config::default();
+    if !needs_rehash(vault, &target) {
+        return Ok(false);
+    }
+    vault.upgrade_credential(DEFAULT_CREDENTIAL, password, &target)
+}
+
+/// 校验 `default` 凭据的密码并按配置退避，返回归类后的结果
+///
+/// # 返回
+/// - `Ok(outcome)`: 密码正确、错误，或保险库损坏、属于其他用户或其他计算机
+/// - `Err`: 与保险库内容无关的错误（平台不支持、`default` 凭据不存在等）
+///
+/// # 注意
+/// 除 `Valid` 外的所有结果与 `verify_password_with_backoff` 一样补齐延迟，
+/// 调用方无法通过耗时区分失败原因
+pub fn verify_password_detailed(
+    blob: &[u8],
+    password: &str,
+    config: &BackoffConfig,
+) -> Result<VerifyOutcome> {
+    VerifyOutcome::classify(verify_password_with_backoff(
+        blob,
+        DEFAULT_CREDENTIAL,
+        password,
+        config,
+    ))
+}
+
+/// 记录失败次数的校验结果
+#[derive(Debug)]
+pub struct TrackedVerification {
+    /// 校验结果
+    pub outcome: VerifyOutcome,
+    /// 失败计数变化或参数升级后的保险库字节（调用方须写回；均未变化时为 None）
+    pub blob: Option<Vec<u8>>,
+}
+
+/// 校验 `default` 凭据的密码，把连续失败次数记录在保险库中并按配置退避
+///
+/// # 返回
+/// - `Ok(verification)`: 归类后的结果；密码错误（计数加一）、正确且此前有失败记录
+///   （计数清零）或正确且参数低于当前默认值（已升级）时附带更新后的保险库字节
+/// - `Err(LockedOut)`: 仍在锁定中，未校验密码
+/// - `Err`: 与保险库内容无关的错误（平台不支持、`default` 凭据不存在等）
+///
+/// # 注意
+/// - 更新后的保险库只改动失败计数与凭据参数，`last_changed_at` 不变
+/// - 启用 TOTP 的保险库只凭密码不能通过，按密码错误处理并计入失败次数（须改用 `verify_totp`）
+pub fn verify_password_tracked(
+    blob: &[u8],
+    password: &str,
+    config: &BackoffConfig,
+) -> Result<TrackedVerification> {
+    let guard = TimedGuard::start(config);
+    let result = verify_tracked(blob, password, None, OffsetDateTime::now_utc());
+    guard.finish(matches!(&result, Ok(v) if v.outcome.is_valid()));
+    result
+}
+
+/// 在后台线程中校验 `default` 凭据的密码（见 `verify_password_tracked`）
+///
+/// # 注意
+/// 失败计数、锁定与退避延迟与同步调用相同，退避在工作线程中补齐，任务完成前不会返回结果；
+/// 保险库字节与密码由任务接管，密码在校验完成后清零
+pub fn verify_password_async(
+    blob: Vec<u8>,
+    password: SecretString,
+    config: BackoffConfig,
+) -> VaultTask<TrackedVerification> {
+    VaultTask::spawn(move || verify_password_tracked(&blob, password.as_str(), &config))
+}
+
+/// 读取锁定情况（失败次数、锁定时长与剩余时长），无需密码
+pub fn get_lockout_state(blob: &[u8]) -> Result<LockoutInfo> {
+    Ok(vault_metadata(blob)?
+        .lockout
+        .info(OffsetDateTime::now_utc()))
+}
+
+/// 校验（启用 TOTP 时连同验证码）并在失败计数变化或参数升级时重新加密
+fn verify_tracked(
+    blob: &[u8],
+    password: &str,
+    code: Option<&str>,
+    now: OffsetDateTime,
+) -> Result<TrackedVerification> {
+    let mut vault = match open_for(blob, password) {
+        Ok(vault) => vault,
+        Err(e) => {
+            return Ok(TrackedVerification {
+                outcome: VerifyOutcome::classify(Err(e))?,
+                blob: None,
+            });
+        }
+    };
+    let before = vault.lockout.clone();
+    let outcome = VerifyOutcome::classify(vault.verify_tracked_with_code(
+        DEFAULT_CREDENTIAL,
+        password,
+        code,
+        now,
+    ))?;
+    let upgraded = outcome.is_valid() && upgrade_default(&mut vault, password)?;
+    let blob = if vault.lockout != before || upgraded {
+        Some(seal_for(&vault, password)?)
+    } else {
+        None
+    };
+    Ok(TrackedVerification { outcome, blob })
+}
+
+/// 校验 `default` 凭据的密码与密钥文件，并按配置退避
+///
+/// # 返回
+/// - `Ok(true/false)`: 是否匹配（密钥文件不一致、保险库启用了 TOTP 时同样为 false）
+/// - `Err(VaultCorrupted)` 等: 保险库无法解密或解析
+///
+/// # 注意
+/// 不需要密钥文件的保险库忽略 `keyfile`，只校验密码
+pub fn verify_password_with_keyfile(
+    blob: &[u8],
+    password: &str,
+    keyfile: &[u8],
+    config: &BackoffConfig,
+) -> Result<bool> {
+    let guard = TimedGuard::start(config);
+    let result = match open_for(blob, password) {
+        Err(AmberlockError::WrongPassword) => Ok(false),
+        vault => vault.and_then(|v| {
+            Ok(
+                v.verify_with_keyfile(DEFAULT_CREDENTIAL, password, Some(keyfile))?
+                    && v.totp.is_none(),
+            )
+        }),
+    };
+    guard.finish(matches!(result, Ok(true)));
+    result
+}
+
+/// 校验恢复密钥并按配置退避
+///
+/// # 参数
+/// - `key`: 用户输入的恢复密钥文本（忽略大小写、空白与 `-`）
+///
+/// # 返回
+/// - `Ok(true/false)`: 恢复密钥是否匹配
+/// - `Err(InvalidRecoveryKey)`: 文本不是有效的恢复密钥
+/// - `Err(RecoveryNotConfigured)`: 保险库未设置恢复密钥
+/// - `Err(UnsupportedOnPasswordBackend)`: 保险库以密码后端加密（立即返回，不退避）
+///
+/// # 注意
+/// 与 `verify_password_with_backoff` 相同，任何失败都会补齐延迟后再返回
+pub fn verify_recovery_key(blob: &[u8], key: &str, config: &BackoffConfig) -> Result<bool> {
+    require_dpapi_for(Backend::of(blob), "恢复密钥")?;
+    let guard = TimedGuard::start(config);
+    let result = RecoveryKey::parse(key).and_then(|key| open(blob)?.verify_recovery(&key));
+    guard.finish(matches!(result, Ok(true)));
+    result
+}
+
+/// 凭恢复密钥重设 `default` 凭据的密码，并按配置退避
+///
+/// # 参数
+/// - `keyfile`: 新密码使用的密钥文件内容；原凭据需要密钥文件时必须提供
+///
+/// # 返回
+/// - `Ok(blob)`: 更新后的保险库字节（失败计数清零，恢复密钥保持不变）
+/// - `Err(WrongRecoveryKey)`: 恢复密钥不匹配
+/// - `Err(InvalidRecoveryKey)`: 文本不是有效的恢复密钥
+/// - `Err(RecoveryNotConfigured)`: 保险库未设置恢复密钥
+/// - `Err(InvalidKeyfile)`: 原凭据需要密钥文件而未提供
+/// - `Err(UnsupportedOnPasswordBackend)`: 保险库以密码后端加密（立即返回，不退避）
+///
+/// # 注意
+/// 与 `verify_password_with_backoff` 相同，任何失败都会补齐延迟后再返回
+pub fn reset_password_with_recovery(
+    blob: &[u8],
+    key: &str,
+    new_password: &str,
+    keyfile: Option<&[u8]>,
+    config: &BackoffConfig,
+) -> Result<Vec<u8>> {
+    require_dpapi_for(Backend::of(blob), "恢复密钥")?;
+    let guard = TimedGuard::start(config);
+    let result = RecoveryKey::parse(key).and_then(|key| {
+        let mut vault = open(blob)?;
+        vault.reset_password_with_recovery(&key, new_password, keyfile)?;
+        seal(&vault)
+    });
+    guard.finish(result.is_ok());
+    result
+}
+
+/// 校验以附加熵加密的保险库中指定凭据的密码，并按配置退避
+///
+/// # 返回
+/// - `Ok(true/false)`: 密码是否匹配（保险库启用了 TOTP 时为 false）
+/// - `Err(VaultCorrupted)`: 附加熵不一致，或保险库无法解密或解析
+///
+/// # 注意
+/// 附加熵不一致与密码错误一样补齐延迟后再返回，调用方无法通过耗时区分
+pub fn verify_password_with_entropy(
+    blob: &[u8],
+    name: &str,
+    password: &str,
+    entropy: &[u8],
+    config: &BackoffConfig,
+) -> Result<bool> {
+    let guard = TimedGuard::start(config);
+    let result = open_with(blob, Some(entropy))
+        .and_then(|vault| Ok(vault.verify(name, password)? && vault.totp.is_none()));
+    guard.finish(matches!(result, Ok(true)));
+    result
+}
+
+/// 列出凭据名称（不含任何哈希信息）
+pub fn list_credentials(blob: &[u8]) -> Result<Vec<String>> {
+    Ok(open(blob)?.names())
+}
+
+/// 添加命名凭据
+///
+/// # 参数
+/// - `blob`: 现有保险库
+/// - `admin_password`: 任一管理员凭据的密码
+/// - `code`: 启用 TOTP 时的当前验证码
+/// - `name`: 新凭据名称
+/// - `new_password`: 新凭据密码
+///
+/// # 返回
+/// 更新后的保险库字节（旧版保险库同时升级为当前格式）
+///
+/// # 注意
+/// 密码后端的保险库只能以 `default` 凭据的密码打开，其他管理员凭据的密码返回 `WrongPassword`
+pub fn create_credential(
+    blob: &[u8],
+    admin_password: &str,
+    code: Option<&str>,
+    name: &str,
+    new_password: &str,
+) -> Result<Vec<u8>> {
+    let (vault, ()) = with_authorized(blob, admin_password, |vault| {
+        vault.add_credential(admin_password, code, name, new_password)
+    })?;
+    seal_for(&vault, admin_password)
+}
+
+/// 添加指定角色的命名凭据
+///
+/// # 参数
+/// - `admin_password`: 任一管理员凭据的密码（操作员的密码不能添加凭据）
+/// - `code`: 启用 TOTP 时的当前验证码
+/// - `role`: 新凭据的角色
+///
+/// # 返回
+/// - `Ok(blob)`: 更新后的保险库字节
+/// - `Err(AuthorizationFailed)`: 密码与所有管理员凭据都不匹配，或验证码错误
+/// - `Err(CredentialExists)`: 同名凭据已存在
+///
+/// # 注意
+/// 密码后端的保险库只能以 `default` 凭据的密码打开，其他管理员凭据的密码返回 `WrongPassword`
+pub fn add_slot(
+    blob: &[u8],
+    admin_password: &str,
+    code: Option<&str>,
+    new_name: &str,
+    new_password: &str,
+    role: Role,
+) -> Result<Vec<u8>> {
+    let (vault, ()) = with_authorized(blob, admin_password, |vault| {
+        vault.add_slot(admin_password, code, new_name, new_password, role)
+    })?;
+    seal_for(&vault, admin_password)
+}
+
+/// 删除命名凭据（不允许删除最后一个凭据或最后一个管理员凭据；授权同 `add_slot`，
+/// 密码后端的保险库同样只能以 `default` 凭据的密码授权）
+pub fn remove_credential(
+    blob: &[u8],
+    admin_password: &str,
+    code: Option<&str>,
+    name: &str,
+) -> Result<Vec<u8>> {
+    let (vault, ()) = with_authorized(blob, admin_password, |vault| {
+        vault.remove_credential(admin_password, code, name)
+    })?;
+    seal_for(&vault, admin_password)
+}
+
+/// 删除命名凭据（同 `remove_credential`）
+///
+/// # 返回
+/// - `Err(LastCredential)`: 只剩这一个凭据
+/// - `Err(LastAdmin)`: 这是最后一个管理员凭据
+pub fn remove_slot(
+    blob: &[u8],
+    admin_password: &str,
+    code: Option<&str>,
+    name: &str,
+) -> Result<Vec<u8>> {
+    remove_credential(blob, admin_password, code, name)
+}
+
+/// 识别密码属于哪个凭据，并按配置退避
+///
+/// # 返回
+/// - `Ok(Some(slot))`: 匹配到的凭据名称与角色
+/// - `Ok(None)`: 密码与所有凭据都不匹配，或保险库启用了 TOTP（只凭密码不能通过）
+///
+/// # 注意
+/// 全部凭据按名称顺序逐一校验，匹配后不提前结束；退避只在全部校验结束后补齐一次。
+/// 密码后端的保险库只能以 `default` 凭据的密码打开，其他密码一律返回 `Ok(None)`
+pub fn verify_slot(
+    blob: &[u8],
+    password: &str,
+    config: &BackoffConfig,
+) -> Result<Option<SlotInfo>> {
+    let guard = TimedGuard::start(config);
+    let result = match open_for(blob, password) {
+        Err(AmberlockError::WrongPassword) => Ok(None),
+        vault => vault.and_then(|vault| {
+            let slot = vault.match_slot(password)?;
+            Ok(slot.filter(|_| vault.totp.is_none()))
+        }),
+    };
+    guard.finish(matches!(result, Ok(Some(_))));
+    result
+}
+
+/// 重命名凭据（授权同 `add_slot`，密码后端的保险库只能以 `default` 凭据的密码授权）
+pub fn rename_credential(
+    blob: &[u8],
+    admin_password: &str,
+    code: Option<&str>,
+    old_name: &str,
+    new_name: &str,
+) -> Result<Vec<u8>> {
+    let (vault, ()) = with_authorized(blob, admin_password, |vault| {
+        vault.rename_credential(admin_password, code, old_name, new_name)
+    })?;
+    seal_for(&vault, admin_password)
+}
+
+/// 修改 `default` 凭据的密码
+///
+/// 校验原密码后以新的随机盐和当前 Argon2id 参数重新计算哈希，
+/// 版本、创建时间与其他凭据原样保留
+///
+/// # 返回
+/// - `Ok(blob)`: 更新后的保险库字节（`last_changed_at` 已更新）
+/// - `Err(AuthorizationFailed)`: 原密码不匹配或验证码错误（附带记录了失败的保险库）
+/// - `Err(WrongPassword)`: 密码后端的保险库无法以原密码解密
+/// - `Err(VaultCorrupted)`: 保险库无法解密或解析
+pub fn change_password(
+    blob: &[u8],
+    old_password: &str,
+    code: Option<&str>,
+    new_password: &str,
+) -> Result<Vec<u8>> {
+    change_password_for(blob, DEFAULT_CREDENTIAL, old_password, code, new_password)
+}
+
+/// 修改指定凭据的密码
+///
+/// # 返回
+/// - `Ok(blob)`: 更新后的保险库字节（`last_changed_at` 已更新）
+/// - `Err(AuthorizationFailed)`: 原密码不匹配或验证码错误（附带记录了失败的保险库）
+/// - `Err(CredentialNotFound)`: 凭据不存在
+/// - `Err(VaultCorrupted)`: 保险库无法解密或解析
+/// - `Err(UnsupportedOnPasswordBackend)`: 密码后端的保险库只能修改 `default` 凭据的密码
+///
+/// # 注意
+/// 解密得到的保险库在加密写回后立即清零，不等到函数返回
+pub fn change_password_for(
+    blob: &[u8],
+    name: &str,
+    old_password: &str,
+    code: Option<&str>,
+    new_password: &str,
+) -> Result<Vec<u8>> {
+    if name != DEFAULT_CREDENTIAL {
+        // 其他凭据的密码解不开保险库
+        require_dpapi_for(Backend::of(blob), "修改其他凭据的密码")?;
+    }
+    let (vault, ()) = with_authorized(blob, old_password, |vault| {
+        vault.change_password(name, old_password, code, new_password)
+    })?;
+    // 密码后端以 `default` 凭据的密码加密
+    let key_password = if name == DEFAULT_CREDENTIAL {
+        new_password
+    } else {
+        old_password
+    };
+    let sealed = seal_for(&vault, key_password);
+    drop(vault);
+    sealed
+}
+
+/// 将保险库改写为当前格式（v5 二进制）
+///
+/// # 参数
+/// - `password`: 任一管理员凭据的密码
+/// - `code`: 启用 TOTP 时的当前验证码
+///
+/// # 返回
+/// - `Ok(blob)`: 改写后的保险库字节（凭据、时间戳与 DPAPI 范围不变）
+/// - `Err(AuthorizationFailed)`: 密码与所有管理员凭据都不匹配，或验证码错误
+/// - `Err(VaultCorrupted)`: 保险库无法解密或解析
+///
+/// # 注意
+/// 其他修改操作写回时同样使用当前格式，本函数用于不改动凭据时主动升级
+pub fn migrate_vault(blob: &[u8], password: &str, code: Option<&str>) -> Result<Vec<u8>> {
+    let (vault, ()) = with_authorized(blob, password, |vault| vault.authorize(password, code))?;
+    seal_for(&vault, password)
+}
+
+/// 导出为不绑定 DPAPI 的可迁移格式
+///
+/// # 参数
+/// - `code`: 启用 TOTP 时的当前验证码（导出文件中含 TOTP 密钥，只凭密码不能导出）
+///
+/// # 返回
+/// - `Ok(portable)`: 以密码派生的密钥加密的导出文件内容
+/// - `Err(AuthorizationFailed)`: 密码与所有管理员凭据都不匹配，或验证码错误
+///
+/// # 注意
+/// 导出文件只受密码保护；需要密钥文件的凭据仍需原密钥文件才能校验。
+/// 以附加熵创建的保险库无法经本函数打开
+pub fn export_portable(blob: &[u8], password: &str, code: Option<&str>) -> Result<Vec<u8>> {
+    let (vault, ()) = with_authorized(blob, password, |vault| vault.authorize(password, code))?;
+    let plain = Zeroizing::new(vault.encode()?);
+    portable::seal(&plain, password, &Argon2Config::default())
+}
+
+/// 导入 `export_portable` 的导出文件，在本机重新以 DPAPI 加密
+///
+/// # 参数
+/// - `code`: 导出的保险库启用了 TOTP 时的当前验证码
+///
+/// # 返回
+/// - `Ok(blob)`: 新的 DPAPI 保险库字节（沿用导出时记录的范围）
+/// - `Err(PortableFormat)`: 不是导出文件或版本不受支持
+/// - `Err(PortableDecryptFailed)`: 密码错误或导出文件被改动
+/// - `Err(WrongPassword)`: 密码不属于管理员凭据，或验证码错误（导出文件不写回，不记录失败）
+/// - `Err(LockedOut)`: 导出时保险库仍在锁定中
+pub fn import_portable(portable: &[u8], password: &str, code: Option<&str>) -> Result<Vec<u8>> {
+    let plain = portable::open(portable, password)?;
+    let mut vault = VaultBlob::decode(&plain)?;
+    vault.authorize(password, code)?;
+    seal_for(&vault, password)
+}
+
+/// 设置或清除密码提示（`hint` 为 None 或空白时清除）
+///
+/// # 返回
+/// - `Err(InvalidHint)`: 提示超过 64 个字符、含有控制字符或包含密码本身
+/// - `Err(AuthorizationFailed)`: 密码与所有管理员凭据都不匹配，或验证码错误
+pub fn set_password_hint(
+    blob: &[u8],
+    password: &str,
+    code: Option<&str>,
+    hint: Option<&str>,
+) -> Result<Vec<u8>> {
+    let (vault, ()) =
+        with_authorized(blob, password, |vault| vault.set_hint(password, code, hint))?;
+    seal_for(&vault, password)
+}
+
+/// 启用 TOTP 第二因素（已启用时换用新的密钥，旧密钥失效）
+///
+/// # 参数
+/// - `code`: 已启用时须为旧密钥的当前验证码；未启用时忽略
+///
+/// # 返回
+/// - `Ok((blob, secret))`: 更新后的保险库字节与新密钥（用 `otpauth_uri` 添加到验证器应用）
+/// - `Err(AuthorizationFailed)`: 密码与所有管理员凭据都不匹配，或验证码错误
+pub fn enable_totp(
+    blob: &[u8],
+    password: &str,
+    code: Option<&str>,
+) -> Result<(Vec<u8>, TotpSecret)> {
+    let (vault, secret) =
+        with_authorized(blob, password, |vault| vault.enable_totp(password, code))?;
+    Ok((seal_for(&vault, password)?, secret))
+}
+
+/// 校验 `default` 凭据的密码与当前的 TOTP 验证码，记录失败次数并按配置退避
+///
+/// # 返回
+/// - `Ok(verification)`: 与 `verify_password_tracked` 相同；密码与验证码均正确时为 `Valid`，
+///   任一项错误时为 `InvalidPassword` 并计入失败次数
+/// - `Err(LockedOut)`: 仍在锁定中，未校验密码与验证码
+///
+/// # 注意
+/// - 密码错误与验证码错误返回同一结果，调用方无法区分是哪一项错误
+/// - 保险库未启用 TOTP 时忽略验证码，只校验密码
+/// - 失败时与 `verify_password_tracked` 一样补齐延迟
+pub fn verify_totp(
+    blob: &[u8],
+    password: &str,
+    code: &str,
+    config: &BackoffConfig,
+) -> Result<TrackedVerification> {
+    let guard = TimedGuard::start(config);
+    let result = verify_tracked(blob, password, Some(code), OffsetDateTime::now_utc());
+    guard.finish(matches!(&result, Ok(v) if v.outcome.is_valid()));
+    result
+}
+
+/// 在后台线程中校验密码与 TOTP 验证码（见 `verify_totp`）
+///
+/// 退避在工作线程中补齐；保险库字节、密码与验证码由任务接管，密码在校验完成后清零
+pub fn verify_totp_async(
+    blob: Vec<u8>,
+    password: SecretString,
+    code: String,
+    config: BackoffConfig,
+) -> VaultTask<TrackedVerification> {
+    VaultTask::spawn(move || verify_totp(&blob, password.as_str(), &code, &config))
+}
+
+/// 停用 TOTP 第二因素（未启用时保险库内容不变）
+///
+/// # 参数
+/// - `code`: 当前验证码；只凭密码不能停用
+///
+/// # 返回
+/// - `Err(AuthorizationFailed)`: 密码与所有管理员凭据都不匹配，或验证码错误
+pub fn disable_totp(blob: &[u8], password: &str, code: Option<&str>) -> Result<Vec<u8>> {
+    let (vault, ()) = with_authorized(blob, password, |vault| vault.disable_totp(password, code))?;
+    seal_for(&vault, password)
+}
+
+/// 读取保险库信息（创建时间、密码修改时间、密码提示、失败次数等），无需密码
+///
+/// 与 `vault_metadata` 相同
+pub fn get_vault_info(blob: &[u8]) -> Result<VaultInfo> {
+    vault_metadata(blob)
+}
+
+/// 确认保险库能解密并解析（密码后端以 `password` 解密），不校验凭据、不计入失败次数
+///
+/// 用于写入或恢复保险库后读回确认。启用 TOTP 的保险库只凭密码的校验一律不通过，
+/// 读回时改用本函数；DPAPI 保险库只能确认解密与解析成功，密码后端能解密即说明密码正确
+///
+/// # 返回
+/// - `Err(WrongPassword)`: 密码后端的保险库无法以 `password` 解密（含被截断或改动）
+/// - `Err(VaultCorrupted)` / `Err(VaultTampered)`: 保险库无法解析或完整性校验不一致
+pub fn check_vault_opens(blob: &[u8], password: &str) -> Result<()> {
+    open_for(blob, password).map(drop)
+}
+
+/// 读取保险库元数据（版本、时间戳、DPAPI 范围、Argon2 参数），无需密码
+///
+/// # 注意
+/// 只解析元数据字段，盐与哈希不会被解析或返回；解密得到的明文在返回前清零
+pub fn vault_metadata(blob: &[u8]) -> Result<VaultMetadata> {
+    require_dpapi(blob)?;
+    let plain = Zeroizing::new(dpapi::unprotect(blob, None)?);
+    VaultMetadata::from_plain(&plain)
+}
+
+/// 解密并解析保险库
+fn open(blob: &[u8]) -> Result<VaultBlob> {
+    open_with(blob, None)
+}
+
+/// 以附加熵解密并解析保险库
+fn open_with(blob: &[u8], entropy: Option<&[u8]>) -> Result<VaultBlob> {
+    require_dpapi(blob)?;
+    let plain = Zeroizing::new(dpapi::unprotect(blob, entropy)?);
+    VaultBlob::decode(&plain)
+}
+
+/// 按保险库的后端解密并解析（密码后端以 `password` 解密）
+fn open_for(blob: &[u8], password: &str) -> Result<VaultBlob> {
+    match Backend::of(blob) {
+        Backend::Dpapi => open(blob),
+        Backend::Password => VaultBlob::decode(&backend::unprotect(blob, password)?),
+    }
+}
+
+/// 不带密码的函数只能打开 DPAPI 保险库
+fn require_dpapi(blob: &[u8]) -> Result<()> {
+    match Backend::of(blob) {
+        Backend::Dpapi => Ok(()),
+        Backend::Password => Err(AmberlockError::PasswordRequired),
+    }
+}
+
+/// 密码后端不支持的功能
+fn require_dpapi_for(backend: Backend, feature: &'static str) -> Result<()> {
+    match backend {
+        Backend::Dpapi => Ok(()),
+        Backend::Password => Err(AmberlockError::UnsupportedOnPasswordBackend(feature)),
+    }
+}
+
+/// 序列化并按保险库记录的范围加密
+fn seal(vault: &VaultBlob) -> Result<Vec<u8>> {
+    seal_with(vault, None)
+}
+
+/// 序列化并以本机可用的后端加密（密码后端以 `password` 加密）
+fn seal_for(vault: &VaultBlob, password: &str) -> Result<Vec<u8>> {
+    seal_as(vault, password, Backend::available())
+}
+
+/// 序列化并以指定后端加密
+fn seal_as(vault: &VaultBlob, password: &str, backend: Backend) -> Result<Vec<u8>> {
+    match backend {
+        Backend::Dpapi => seal(vault),
+        Backend::Password => {
+            let plain = Zeroizing::new(vault.encode()?);
+            backend::protect(&plain, password)
+        }
+    }
+}
+
+/// 以 `password` 打开保险库并执行管理操作（操作内经 `VaultBlob::authorize` 授权）
+///
+/// # 返回
+/// - `Ok((vault, value))`: 操作完成，调用方加密写回
+/// - `Err(AuthorizationFailed)`: 密码或验证码错误，附带以原后端重新加密、记录了本次失败的保险库
+/// - 其他 `Err`: 保险库无法打开或操作本身失败
+fn with_authorized<T>(
+    blob: &[u8],
+    password: &str,
+    op: impl FnOnce(&mut VaultBlob) -> Result<T>,
+) -> Result<(VaultBlob, T)> {
+    let mut vault = open_for(blob, password)?;
+    match op(&mut vault) {
+        // 密码后端能解密即说明 `password` 是加密用的密码，以原后端写回不会换用密钥
+        Err(AmberlockError::WrongPassword) => Err(AmberlockError::AuthorizationFailed {
+            updated: seal_as(&vault, password, Backend::of(blob))?,
+        }),
+        result => result.map(|value| (vault, value)),
+    }
+}
+
+/// 序列化并按保险库记录的范围、以附加熵加密
+fn seal_with(vault: &VaultBlob, entropy: Option<&[u8]>) -> Result<Vec<u8>> {
+    let plain = Zeroizing::new(vault.encode()?);
+    dpapi::protect(&plain, vault.dpapi_scope, entropy)
+}
+
+#[cfg(all(test, windows, feature = "dpapi"))]
+mod tests {
+    use super::*;
+    use amberlock_types::AmberlockError;
+
+    #[test]
+    fn test_named_credentials_round_trip() {
+        let blob = create_vault("admin").expect("创建保险库失败");
+        assert!(verify_password(&blob, "admin").unwrap());
+
+        let blob = create_credential(&blob, "admin", None, "work", "w").expect("添加凭据失败");
+        assert_eq!(list_credentials(&blob).unwrap(), vec!["default", "work"]);
+        assert!(verify_password_for(&blob, "work", "w").unwrap());
+        assert!(!verify_password(&blob, "w").unwrap());
+
+        let blob = rename_credential(&blob, "w", None, "work", "office").expect("重命名失败");
+        let blob = remove_credential(&blob, "w", None, DEFAULT_CREDENTIAL).expect("删除失败");
+        assert_eq!(list_credentials(&blob).unwrap(), vec!["office"]);
+        assert!(matches!(
+            verify_password(&blob, "admin"),
+            Err(AmberlockError::CredentialNotFound(_))
+        ));
+        println!("✅ 命名凭据增删改查正常");
+    }
+
+    #[test]
+    fn test_slots_verify_and_remove() {
+        let backoff = BackoffConfig::default();
+        let blob = create_vault("admin").expect("创建保险库失败");
+        let blob =
+            add_slot(&blob, "admin", None, "operator", "view", Role::Operator).expect("添加失败");
+
+        let slot = verify_slot(&blob, "view", &backoff)
+            .unwrap()
+            .expect("应匹配操作员");
+        assert_eq!(
+            (slot.name.as_str(), slot.role),
+            ("operator", Role::Operator)
+        );
+        let slot = verify_slot(&blob, "admin", &backoff)
+            .unwrap()
+            .expect("应匹配管理员");
+        assert_eq!(slot.role, Role::Admin);
+
+        // 密码不属于任何凭据：补齐一次退避，而不是每个凭据一次
+        let started = std::time::Instant::now();
+        assert_eq!(verify_slot(&blob, "nobody", &backoff).unwrap(), None);
+        let elapsed = started.elapsed();
+        assert!(elapsed >= backoff::MIN_EXTRA_DELAY);
+        assert!(elapsed < backoff.base_delay() * 2 + std::time::Duration::from_secs(2));
+
+        // 操作员不能删除凭据；删除后其密码不再匹配
+        assert!(matches!(
+            remove_slot(&blob, "view", None, "operator"),
+            Err(AmberlockError::AuthorizationFailed { .. })
+        ));
+        let blob = remove_slot(&blob, "admin", None, "operator").expect("删除失败");
+        assert_eq!(verify_slot(&blob, "view", &backoff).unwrap(), None);
+        assert!(matches!(
+            remove_slot(&blob, "admin", None, DEFAULT_CREDENTIAL),
+            Err(AmberlockError::LastCredential)
+        ));
+        println!("✅ 按密码识别凭据及其角色");
+    }
+
+    #[test]
+    fn test_vault_metadata_and_change_password() {
+        let blob = create_vault("old").expect("创建保险库失败");
+        let before = vault_metadata(&blob).expect("读取元数据失败");
+        assert_eq!(before.version, VAULT_VERSION);
+        assert!(before.created_at.is_some());
+
+        let blob = change_password_for(&blob, DEFAULT_CREDENTIAL, "old", None, "new")
+            .expect("修改密码失败");
+        let after = vault_metadata(&blob).expect("读取元数据失败");
+        assert_eq!(after.created_at, before.created_at);
+        assert!(verify_password(&blob, "new").unwrap());
+    }
+
+    #[test]
+    fn test_entropy_round_trip() {
+        let backoff = BackoffConfig::default();
+        let blob = create_vault_with_entropy("p", b"app-secret").expect("创建保险库失败");
+        assert!(
+            verify_password_with_entropy(&blob, DEFAULT_CREDENTIAL, "p", b"app-secret", &backoff)
+                .unwrap()
+        );
+        assert!(
+            !verify_password_with_entropy(&blob, DEFAULT_CREDENTIAL, "q", b"app-secret", &backoff)
+                .unwrap()
+        );
+
+        // 附加熵不一致或缺失时解密失败，且同样补齐延迟
+        let started = std::time::Instant::now();
+        assert!(matches!(
+            verify_password_with_entropy(&blob, DEFAULT_CREDENTIAL, "p", b"other", &backoff),
+            Err(AmberlockError::VaultCorrupted(_))
+        ));
+        assert!(started.elapsed() >= backoff::MIN_EXTRA_DELAY);
+        assert!(matches!(
+            verify_password(&blob, "p"),
+            Err(AmberlockError::VaultTampered(_))
+        ));
+
+        // 不使用附加熵的保险库照旧可用
+        let plain = create_vault("p").expect("创建保险库失败");
+        assert!(verify_password(&plain, "p").unwrap());
+        println!("✅ 附加熵保险库往返正常");
+    }
+
+    #[test]
+    fn test_tampered_vault() {
+        let blob = create_vault("p").expect("创建保险库失败");
+
+        // 密文被改动
+        let mut modified = blob.clone();
+        let middle = modified.len() / 2;
+        modified[middle] ^= 0x01;
+        assert!(matches!(
+            verify_password(&modified, "p"),
+            Err(AmberlockError::VaultTampered(_))
+        ));
+
+        // 明文被改动后重新加密（字段内容与校验值本身）
+        let plain = dpapi::unprotect(&blob, None).unwrap();
+        for offset in [plain.len() / 2, plain.len() - 1] {
+            let mut modified = plain.clone();
+            modified[offset] ^= 0x01;
+            let resealed = dpapi::protect(&modified, DpapiScope::CurrentUser, None).unwrap();
+            assert!(matches!(
+                verify_password(&resealed, "p"),
+                Err(AmberlockError::VaultTampered(_))
+            ));
+            assert!(matches!(
+                vault_metadata(&resealed),
+                Err(AmberlockError::VaultTampered(_))
+            ));
+        }
+
+        assert!(verify_password(&blob, "p").unwrap());
+        println!("✅ 篡改的保险库返回 VaultTampered");
+    }
+
+    #[test]
+    fn test_verify_password_detailed() {
+        let backoff = BackoffConfig::default();
+        let blob = create_vault("p").expect("创建保险库失败");
+        assert_eq!(
+            verify_password_detailed(&blob, "p", &backoff).unwrap(),
+            VerifyOutcome::Valid
+        );
+        assert_eq!(
+            verify_password_detailed(&blob, "q", &backoff).unwrap(),
+            VerifyOutcome::InvalidPassword
+        );
+
+        // 密文被改动、明文无法解析：均归为保险库损坏，且同样补齐延迟
+        let mut modified = blob.clone();
+        let middle = modified.len() / 2;
+        modified[middle] ^= 0x01;
+        let garbage = dpapi::protect(b"{not json", DpapiScope::CurrentUser, None).unwrap();
+        for damaged in [&modified, &garbage] {
+            let started = std::time::Instant::now();
+            assert_eq!(
+                verify_password_detailed(damaged, "p", &backoff).unwrap(),
+                VerifyOutcome::VaultCorrupted
+            );
+            assert!(started.elapsed() >= backoff::MIN_EXTRA_DELAY);
+        }
+        println!("✅ 校验结果按原因归类");
+    }
+
+    #[test]
+    fn test_verify_password_tracked() {
+        let backoff = BackoffConfig::default();
+        let mut blob = create_vault("p").expect("创建保险库失败");
+        assert!(!get_lockout_state(&blob).unwrap().is_locked());
+
+        let mut previous = std::time::Duration::ZERO;
+        for attempt in 1..=2 {
+            let result = verify_password_tracked(&blob, "wrong", &backoff).unwrap();
+            assert_eq!(result.outcome, VerifyOutcome::InvalidPassword);
+            blob = result.blob.expect("失败后应写回保险库");
+
+            let info = get_lockout_state(&blob).unwrap();
+            assert_eq!(info.failed_attempts, attempt);
+            assert!(info.delay > previous);
+            previous = info.delay;
+
+            // 锁定期间即使密码正确也被拒绝
+            if info.is_locked() {
+                assert!(matches!(
+                    verify_password_tracked(&blob, "p", &backoff),
+                    Err(AmberlockError::LockedOut { .. })
+                ));
+            }
+            std::thread::sleep(info.delay);
+        }
+
+        let result = verify_password_tracked(&blob, "p", &backoff).unwrap();
+        assert_eq!(result.outcome, VerifyOutcome::Valid);
+        let blob = result.blob.expect("成功后应写回清零的计数");
+        assert_eq!(get_lockout_state(&blob).unwrap().failed_attempts, 0);
+        assert!(
+            verify_password_tracked(&blob, "p", &backoff)
+                .unwrap()
+                .blob
+                .is_none()
+        );
+        println!("✅ 失败次数随保险库持久化");
+    }
+
+    #[test]
+    fn test_vault_info_and_hint() {
+        let blob = create_vault_with_hint("secret", Some("最喜欢的颜色")).expect("创建保险库失败");
+        let info = get_vault_info(&blob).unwrap();
+        assert_eq!(info.hint.as_deref(), Some("最喜欢的颜色"));
+        assert!(info.created_at.is_some());
+        assert_eq!(info.password_changed_at, info.created_at);
+        assert_eq!(info.last_changed_at, info.created_at);
+        assert!(matches!(
+            create_vault_with_hint("secret", Some("my secret")),
+            Err(AmberlockError::InvalidHint(_))
+        ));
+
+        let blob = set_password_hint(&blob, "secret", None, None).expect("清除提示失败");
+        assert_eq!(get_vault_info(&blob).unwrap().hint, None);
+        assert!(matches!(
+            set_password_hint(&blob, "wrong", None, Some("x")),
+            Err(AmberlockError::AuthorizationFailed { .. })
+        ));
+
+        let changed = change_password(&blob, "secret", None, "new").unwrap();
+        let after = get_vault_info(&changed).unwrap();
+        assert_eq!(after.created_at, info.created_at);
+        assert_ne!(after.password_changed_at, info.password_changed_at);
+        println!("✅ 无需密码读取保险库信息与密码提示");
+    }
+
+    #[test]
+    fn test_verify_and_upgrade() {
+        let weak = Argon2Config {
+            mem_kib: vault::MIN_MEM_KIB,
+            time_cost: 1,
+            parallelism: 1,
+            output_len: vault::HASH_LEN,
+        };
+        let blob = create_vault_with_config("secret", &weak).expect("创建保险库失败");
+        assert_eq!(verify_and_upgrade(&blob, "wrong").unwrap(), (false, None));
+
+        // 密码正确且参数较弱：返回以当前默认参数重新计算的保险库
+        let (valid, upgraded) = verify_and_upgrade(&blob, "secret").unwrap();
+        assert!(valid);
+        let upgraded = upgraded.expect("应返回升级后的保险库");
+        assert_eq!(
+            vault_metadata(&upgraded).unwrap().params[DEFAULT_CREDENTIAL],
+            Argon2Params::CURRENT
+        );
+        assert!(verify_password(&upgraded, "secret").unwrap());
+        assert_eq!(
+            verify_and_upgrade(&upgraded, "secret").unwrap(),
+            (true, None)
+        );
+
+        // 记录失败次数的校验同样顺带升级
+        let verification =
+            verify_password_tracked(&blob, "secret", &BackoffConfig::default()).unwrap();
+        assert!(verification.outcome.is_valid());
+        let upgraded = verification.blob.expect("应返回升级后的保险库");
+        assert!(!needs_rehash(
+            &open(&upgraded).unwrap(),
+            &Argon2Config::default()
+        ));
+        println!("✅ 校验成功后升级较弱的 Argon2 参数");
+    }
+
+    #[test]
+    fn test_keyfile_second_factor() {
+        let backoff = BackoffConfig::default();
+        let blob = create_vault_with_keyfile("p", b"usb-keyfile").expect("创建保险库失败");
+
+        assert!(verify_password_with_keyfile(&blob, "p", b"usb-keyfile", &backoff).unwrap());
+        assert!(!verify_password_with_keyfile(&blob, "q", b"usb-keyfile", &backoff).unwrap());
+        assert!(!verify_password_with_keyfile(&blob, "p", b"other-file", &backoff).unwrap());
+        // 未提供密钥文件：按密码错误处理
+        assert!(!verify_password(&blob, "p").unwrap());
+        assert_eq!(
+            verify_password_detailed(&blob, "p", &backoff).unwrap(),
+            VerifyOutcome::InvalidPassword
+        );
+        assert!(matches!(
+            create_vault_with_keyfile("p", b""),
+            Err(AmberlockError::InvalidKeyfile(_))
+        ));
+        println!("✅ 密钥文件作为第二因素");
+    }
+
+    #[test]
+    fn test_recovery_key_reset() {
+        let backoff = BackoffConfig::default();
+        let (blob, key) = create_vault_with_recovery("forgotten").expect("创建保险库失败");
+        let text = key.to_string();
+        assert!(verify_recovery_key(&blob, &text, &backoff).unwrap());
+
+        // 错误的恢复密钥与格式错误的输入同样补齐延迟
+        let wrong = RecoveryKey::generate().to_string();
+        for (input, expect_wrong_key) in [(wrong.as_str(), true), ("not-a-key", false)] {
+            let started = std::time::Instant::now();
+            let result = reset_password_with_recovery(&blob, input, "new", None, &backoff);
+            assert!(started.elapsed() >= backoff::MIN_EXTRA_DELAY);
+            if expect_wrong_key {
+                assert!(matches!(result, Err(AmberlockError::WrongRecoveryKey)));
+            } else {
+                assert!(matches!(result, Err(AmberlockError::InvalidRecoveryKey(_))));
+            }
+        }
+        let started = std::time::Instant::now();
+        assert!(!verify_recovery_key(&blob, &wrong, &backoff).unwrap());
+        assert!(started.elapsed() >= backoff::MIN_EXTRA_DELAY);
+
+        let reset =
+            reset_password_with_recovery(&blob, &text.to_lowercase(), "new", None, &backoff)
+                .expect("重设密码失败");
+        assert!(verify_password(&reset, "new").unwrap());
+        assert!(!verify_password(&reset, "forgotten").unwrap());
+        assert!(verify_recovery_key(&reset, &text, &backoff).unwrap());
+
+        // 未设置恢复密钥的保险库
+        assert!(matches!(
+            verify_recovery_key(&create_vault("p").unwrap(), &text, &backoff),
+            Err(AmberlockError::RecoveryNotConfigured)
+        ));
+        println!("✅ 凭恢复密钥重设密码");
+    }
+
+    #[test]
+    fn test_migrate_vault() {
+        // 旧版 JSON 保险库
+        let mut legacy = VaultBlob::with_default("p").unwrap();
+        legacy.version = vault::VAULT_VERSION_V4;
+        let plain = serde_json::to_vec(&legacy).unwrap();
+        let blob = dpapi::protect(&plain, DpapiScope::CurrentUser, None).unwrap();
+        assert_eq!(
+            vault_metadata(&blob).unwrap().version,
+            vault::VAULT_VERSION_V4
+        );
+
+        assert!(matches!(
+            migrate_vault(&blob, "wrong", None),
+            Err(AmberlockError::AuthorizationFailed { .. })
+        ));
+        let migrated = migrate_vault(&blob, "p", None).expect("迁移失败");
+        let metadata = vault_metadata(&migrated).unwrap();
+        assert_eq!(metadata.version, VAULT_VERSION);
+        assert_eq!(metadata.created_at, legacy.created_at);
+        assert!(verify_password(&migrated, "p").unwrap());
+        println!("✅ 保险库迁移为 v5");
+    }
+
+    #[test]
+    fn test_portable_export_import() {
+        let blob = create_credential(
+            &create_vault("secret").unwrap(),
+            "secret",
+            None,
+            "work",
+            "w",
+        )
+        .expect("添加凭据失败");
+        assert!(matches!(
+            export_portable(&blob, "wrong", None),
+            Err(AmberlockError::AuthorizationFailed { .. })
+        ));
+        let portable = export_portable(&blob, "secret", None).expect("导出失败");
+        assert!(portable.starts_with(portable::MAGIC));
+
+        // 导出文件被改动：认证失败
+        let mut corrupted = portable.clone();
+        let last = corrupted.len() - 1;
+        corrupted[last] ^= 0x01;
+        assert!(matches!(
+            import_portable(&corrupted, "secret", None),
+            Err(AmberlockError::PortableDecryptFailed)
+        ));
+        assert!(matches!(
+            import_portable(&blob, "secret", None),
+            Err(AmberlockError::PortableFormat(_))
+        ));
+
+        // 导入得到新的 DPAPI 保险库，凭据与元数据保持不变
+        let imported = import_portable(&portable, "secret", None).expect("导入失败");
+        assert_ne!(imported, blob);
+        assert!(verify_password(&imported, "secret").unwrap());
+        assert!(verify_password_for(&imported, "work", "w").unwrap());
+        let before = vault_metadata(&blob).unwrap();
+        let after = vault_metadata(&imported).unwrap();
+        assert_eq!(after.created_at, before.created_at);
+        assert_eq!(after.dpapi_scope, DpapiScope::CurrentUser);
+
+        // 本机范围的保险库导入后沿用本机范围
+        let machine = create_vault_scoped("secret", DpapiScope::LocalMachine).unwrap();
+        let portable = export_portable(&machine, "secret", None).unwrap();
+        let imported = import_portable(&portable, "secret", None).expect("导入失败");
+        assert_eq!(
+            vault_metadata(&imported).unwrap().dpapi_scope,
+            DpapiScope::LocalMachine
+        );
+        println!("✅ 导出为可迁移格式并重新以 DPAPI 导入");
+    }
+
+    #[test]
+    fn test_scoped_vaults() {
+        let machine = create_vault_scoped("p", DpapiScope::LocalMachine).expect("创建保险库失败");
+        assert!(verify_password(&machine, "p").unwrap());
+        assert_eq!(
+            vault_metadata(&machine).unwrap().dpapi_scope,
+            DpapiScope::LocalMachine
+        );
+        // 修改后仍以本机范围加密
+        let changed = change_password(&machine, "p", None, "n").expect("修改密码失败");
+        assert_eq!(
+            vault_metadata(&changed).unwrap().dpapi_scope,
+            DpapiScope::LocalMachine
+        );
+
+        // 当前用户范围的保险库照旧可用
+        let user = create_vault("p").expect("创建保险库失败");
+        assert!(verify_password(&user, "p").unwrap());
+        assert_eq!(
+            vault_metadata(&user).unwrap().dpapi_scope,
+            DpapiScope::CurrentUser
+        );
+        println!("✅ 本机范围保险库往返正常");
+    }
+
+    #[test]
+    fn test_change_password_rehashes_with_fresh_salt() {
+        let blob = create_credential(&create_vault("old").unwrap(), "old", None, "work", "w")
+            .expect("添加凭据失败");
+
+        assert!(matches!(
+            change_password(&blob, "wrong", None, "new"),
+            Err(AmberlockError::AuthorizationFailed { .. })
+        ));
+        assert!(matches!(
+            change_password(b"not a vault", "old", None, "new"),
+            Err(AmberlockError::VaultTampered(_))
+        ));
+
+        let changed = change_password(&blob, "old", None, "new").expect("修改密码失败");
+        assert!(verify_password(&changed, "new").unwrap());
+        assert!(!verify_password(&changed, "old").unwrap());
+        assert!(verify_password_for(&changed, "work", "w").unwrap());
+
+        let before = open(&blob).unwrap();
+        let after = open(&changed).unwrap();
+        assert_ne!(
+            before.credentials[DEFAULT_CREDENTIAL].salt,
+            after.credentials[DEFAULT_CREDENTIAL].salt
+        );
+        assert_eq!(before.credentials["work"], after.credentials["work"]);
+        assert_eq!(after.version, VAULT_VERSION);
+        assert_eq!(after.created_at, before.created_at);
+        println!("✅ 修改密码使用新的盐重新计算哈希");
+    }
+}
+
+#[cfg(test)]
+mod fallback_tests {
+    use super::*;
+
+    /// 以密码后端加密的保险库（任何平台都可构造）
+    fn password_vault(password: &str) -> Vec<u8> {
+        let vault = VaultBlob::with_default(password).expect("创建保险库失败");
+        seal_as(&vault, password, Backend::Password).expect("加密失败")
+    }
+
+    #[test]
+    fn test_password_backend_verify_and_change() {
+        let blob = password_vault("secret");
+        assert_eq!(Backend::of(&blob), Backend::Password);
+        assert!(verify_password(&blob, "secret").unwrap());
+        assert!(!verify_password(&blob, "wrong").unwrap());
+        assert_eq!(verify_and_upgrade(&blob, "wrong").unwrap(), (false, None));
+
+        // 密码错误无法解密，也就无法写回失败计数
+        let verification = verify_tracked(&blob, "wrong", None, OffsetDateTime::now_utc()).unwrap();
+        assert_eq!(verification.outcome, VerifyOutcome::InvalidPassword);
+        assert!(verification.blob.is_none());
+
+        // 写回时改用本机可用的后端，密码照常生效
+        let changed = change_password(&blob, "secret", None, "new").expect("修改密码失败");
+        assert_eq!(Backend::of(&changed), Backend::available());
+        assert!(verify_password(&changed, "new").unwrap());
+        assert!(!verify_password(&changed, "secret").unwrap());
+        assert!(matches!(
+            change_password(&blob, "wrong", None, "new"),
+            Err(AmberlockError::WrongPassword)
+        ));
+        println!("✅ 密码后端的校验与修改密码");
+    }
+
+    #[test]
+    fn test_password_backend_requires_password() {
+        let blob = password_vault("secret");
+        assert!(matches!(
+            vault_metadata(&blob),
+            Err(AmberlockError::PasswordRequired)
+        ));
+        assert!(matches!(
+            list_credentials(&blob),
+            Err(AmberlockError::PasswordRequired)
+        ));
+        assert!(matches!(
+            verify_password_with_entropy(
+                &blob,
+                DEFAULT_CREDENTIAL,
+                "secret",
+                b"k",
+                &BackoffConfig::default()
+            ),
+            Err(AmberlockError::PasswordRequired)
+        ));
+
+        // 被改动的保险库按密码错误处理
+        let mut tampered = blob;
+        let last = tampered.len() - 1;
+        tampered[last] ^= 0x01;
+        assert!(!verify_password(&tampered, "secret").unwrap());
+        println!("✅ 密码后端不带密码时无法读取");
+    }
+
+    #[test]
+    fn test_check_vault_opens() {
+        let blob = password_vault("secret");
+        check_vault_opens(&blob, "secret").expect("应能打开");
+        assert!(matches!(
+            check_vault_opens(&blob, "wrong"),
+            Err(AmberlockError::WrongPassword)
+        ));
+        assert!(check_vault_opens(&blob[..blob.len() - 1], "secret").is_err());
+
+        // 启用 TOTP 后只凭密码的校验不通过，读回确认照常
+        let (enabled, _) = enable_totp(&blob, "secret", None).expect("启用 TOTP 失败");
+        assert!(!verify_password(&enabled, "secret").unwrap());
+        check_vault_opens(&enabled, "secret").expect("应能打开");
+        println!("✅ 读回确认保险库能够打开");
+    }
+
+    #[test]
+    fn test_password_backend_rejects_recovery() {
+        let mut vault = VaultBlob::with_default("secret").expect("创建保险库失败");
+        let key = vault.set_recovery().expect("生成恢复密钥失败").to_string();
+        let blob = seal_as(&vault, "secret", Backend::Password).expect("加密失败");
+        let backoff = BackoffConfig::default();
+
+        assert!(matches!(
+            verify_recovery_key(&blob, &key, &backoff),
+            Err(AmberlockError::UnsupportedOnPasswordBackend(_))
+        ));
+        assert!(matches!(
+            reset_password_with_recovery(&blob, &key, "new", None, &backoff),
+            Err(AmberlockError::UnsupportedOnPasswordBackend(_))
+        ));
+        // 本机只能用密码后端时不生成用不上的恢复密钥
+        if Backend::available() == Backend::Password {
+            assert!(matches!(
+                create_vault_with_recovery("secret"),
+                Err(AmberlockError::UnsupportedOnPasswordBackend(_))
+            ));
+        }
+        println!("✅ 密码后端明确拒绝恢复密钥");
+    }
+
+    #[test]
+    fn test_password_backend_other_credentials() {
+        let mut vault = VaultBlob::with_default("secret").expect("创建保险库失败");
+        vault.add_credential("secret", None, "work", "w").unwrap();
+        let blob = seal_as(&vault, "secret", Backend::Password).expect("加密失败");
+
+        // 其他凭据的密码解不开保险库：校验不通过，也不能授权管理操作
+        assert!(!verify_password_for(&blob, "work", "w").unwrap());
+        assert!(matches!(
+            remove_credential(&blob, "w", None, DEFAULT_CREDENTIAL),
+            Err(AmberlockError::WrongPassword)
+        ));
+        assert!(matches!(
+            change_password_for(&blob, "work", "w", None, "x"),
+            Err(AmberlockError::UnsupportedOnPasswordBackend(_))
+        ));
+
+        // 以 default 的密码照常管理
+        let removed = remove_credential(&blob, "secret", None, "work").expect("删除失败");
+        assert!(verify_password(&removed, "secret").unwrap());
+        println!("✅ 密码后端只能以 default 凭据的密码管理");
+    }
+
+    #[test]
+    fn test_parse_failure_takes_full_backoff() {
+        use std::time::Instant;
+
+        // 凭据参数无法解析：计算密码哈希之前即失败，耗时仍不低于退避下限
+        let mut vault = VaultBlob::with_default("secret").expect("创建保险库失败");
+        vault
+            .credentials
+            .get_mut(DEFAULT_CREDENTIAL)
+            .expect("缺少 default 凭据")
+            .params = "m=bogus".to_string();
+        let blob = seal_as(&vault, "secret", Backend::Password).expect("加密失败");
+        let config = BackoffConfig::default();
+
+        let started = Instant::now();
+        let result = verify_password_with_backoff(&blob, DEFAULT_CREDENTIAL, "secret", &config);
+        assert!(matches!(result, Err(AmberlockError::VaultCorrupted(_))));
+        assert!(
+            started.elapsed() >= config.base_delay().mul_f64(1.0 - backoff::JITTER_RATIO),
+            "解析失败的路径未补齐退避延迟: {:?}",
+            started.elapsed()
+        );
+        println!("✅ 解析失败与密码错误耗时一致");
+    }
+
+    #[test]
+    fn test_async_verification_enforces_backoff() {
+        use std::time::{Duration, Instant};
+
+        let blob = password_vault("secret");
+        let config = BackoffConfig::new(Duration::from_millis(400));
+        let started = Instant::now();
+        let task = verify_password_async(blob.clone(), "wrong".into(), config);
+        // 调用方不被阻塞，退避在工作线程中补齐
+        assert!(!task.is_finished());
+        let verification = task::tests::block_on(task).expect("校验失败");
+        assert_eq!(verification.outcome, VerifyOutcome::InvalidPassword);
+        assert!(
+            started.elapsed() >= config.base_delay().mul_f64(1.0 - backoff::JITTER_RATIO),
+            "后台校验失败后未补齐退避延迟: {:?}",
+            started.elapsed()
+        );
+
+        let verification = verify_password_async(blob, "secret".into(), config)
+            .join()
+            .expect("校验失败");
+        assert!(verification.outcome.is_valid());
+        println!("✅ 后台校验同样按配置退避");
+    }
+
+    #[test]
+    fn test_totp_enable_verify_disable() {
+        let at = |secs: i64| OffsetDateTime::from_unix_timestamp(secs).unwrap();
+        let blob = password_vault("secret");
+        assert!(matches!(
+            enable_totp(&blob, "wrong", None),
+            Err(AmberlockError::WrongPassword)
+        ));
+        // 未启用时忽略验证码
+        assert!(
+            verify_tracked(&blob, "secret", Some("000000"), at(0))
+                .unwrap()
+                .outcome
+                .is_valid()
+        );
+
+        let (enabled, secret) = enable_totp(&blob, "secret", None).expect("启用 TOTP 失败");
+        let time = 1_700_000_000;
+        let code = secret.code_at(time as u64);
+        let valid = |blob: &[u8], password: &str, code: &str, time: i64| {
+            verify_tracked(blob, password, Some(code), at(time))
+                .unwrap()
+                .outcome
+                .is_valid()
+        };
+        assert!(valid(&enabled, "secret", &code, time));
+        assert!(valid(&enabled, "secret", &code, time + 30));
+        assert!(!valid(&enabled, "secret", &code, time + 90));
+        assert!(!valid(&enabled, "wrong", &code, time));
+        let other = if code == "000000" { "000001" } else { "000000" };
+        assert!(!valid(&enabled, "secret", other, time));
+        let now = OffsetDateTime::now_utc().unix_timestamp() as u64;
+        let config = BackoffConfig::default();
+        assert!(
+            verify_totp(&enabled, "secret", &secret.code_at(now), &config)
+                .unwrap()
+                .outcome
+                .is_valid()
+        );
+
+        // 重新启用须带旧密钥的验证码，之后旧密钥失效
+        assert!(matches!(
+            enable_totp(&enabled, "secret", None),
+            Err(AmberlockError::AuthorizationFailed { .. })
+        ));
+        let (rotated, new_secret) =
+            enable_totp(&enabled, "secret", Some(&secret.code_at(now))).expect("启用 TOTP 失败");
+        assert_ne!(secret, new_secret);
+        assert!(valid(
+            &rotated,
+            "secret",
+            &new_secret.code_at(time as u64),
+            time
+        ));
+
+        // 停用同样须带验证码，停用后不再需要
+        assert!(matches!(
+            disable_totp(&rotated, "wrong", None),
+            Err(AmberlockError::WrongPassword)
+        ));
+        let disabled = disable_totp(&rotated, "secret", Some(&new_secret.code_at(now)))
+            .expect("停用 TOTP 失败");
+        assert!(verify_password(&disabled, "secret").unwrap());
+        println!("✅ 启用、校验与停用 TOTP");
+    }
+
+    #[test]
+    fn test_password_only_checks_refuse_totp_vault() {
+        let blob = password_vault("secret");
+        let (enabled, _) = enable_totp(&blob, "secret", None).expect("启用 TOTP 失败");
+        let config = BackoffConfig::default();
+
+        assert!(!verify_password(&enabled, "secret").unwrap());
+        assert!(!verify_password_for(&enabled, DEFAULT_CREDENTIAL, "secret").unwrap());
+        assert_eq!(
+            verify_and_upgrade(&enabled, "secret").unwrap(),
+            (false, None)
+        );
+        assert_eq!(verify_slot(&enabled, "secret", &config).unwrap(), None);
+
+        // 计入失败次数，与密码错误无法区分
+        let verification = verify_password_tracked(&enabled, "secret", &config).expect("校验失败");
+        assert_eq!(verification.outcome, VerifyOutcome::InvalidPassword);
+        let updated = verification.blob.expect("失败计数应写回");
+        let now = OffsetDateTime::now_utc().unix_timestamp() as u64;
+        let vault = open_for(&updated, "secret").unwrap();
+        assert_eq!(vault.lockout.failed_attempts, 1);
+        let code = vault.totp.as_ref().unwrap().code_at(now);
+
+        // 带验证码校验通过后清零
+        let verification = verify_totp(&updated, "secret", &code, &config).expect("校验失败");
+        assert!(verification.outcome.is_valid());
+        let cleared = verification.blob.expect("清零后应写回");
+        assert_eq!(
+            open_for(&cleared, "secret")
+                .unwrap()
+                .lockout
+                .failed_attempts,
+            0
+        );
+        println!("✅ 启用 TOTP 后只凭密码的校验一律不通过");
+    }
+
+    #[test]
+    fn test_admin_actions_require_totp_code() {
+        let blob = password_vault("secret");
+        let (blob, secret) = enable_totp(&blob, "secret", None).expect("启用 TOTP 失败");
+        let now = OffsetDateTime::now_utc().unix_timestamp() as u64;
+        let code = secret.code_at(now);
+        let wrong = if code == "000000" { "000001" } else { "000000" };
+
+        // 只凭密码不能停用 TOTP，失败计入次数并返回应写回的保险库
+        let Err(AmberlockError::AuthorizationFailed { updated }) =
+            disable_totp(&blob, "secret", None)
+        else {
+            panic!("只凭密码不应停用 TOTP");
+        };
+        let vault = open_for(&updated, "secret").unwrap();
+        assert!(vault.totp.is_some());
+        assert_eq!(vault.lockout.failed_attempts, 1);
+
+        // 导出文件含 TOTP 密钥，同样须带验证码
+        for result in [
+            export_portable(&blob, "secret", None),
+            export_portable(&blob, "secret", Some(wrong)),
+            migrate_vault(&blob, "secret", None),
+            set_password_hint(&blob, "secret", None, Some("提示")),
+            change_password(&blob, "secret", None, "new"),
+            create_credential(&blob, "secret", None, "office", "office-pw"),
+        ] {
+            assert!(matches!(
+                result,
+                Err(AmberlockError::AuthorizationFailed { .. })
+            ));
+        }
+
+        let code = secret.code_at(OffsetDateTime::now_utc().unix_timestamp() as u64);
+        let disabled = disable_totp(&blob, "secret", Some(&code)).expect("停用 TOTP 失败");
+        assert!(verify_password(&disabled, "secret").unwrap());
+        println!("✅ 管理操作启用 TOTP 后须带验证码");
+    }
+
+    #[test]
+    fn test_totp_failures_are_padded_and_locked_out() {
+        use std::time::{Duration, Instant};
+
+        let blob = password_vault("secret");
+        let (blob, secret) = enable_totp(&blob, "secret", None).expect("启用 TOTP 失败");
+        let now = OffsetDateTime::now_utc().unix_timestamp() as u64;
+        let code = secret.code_at(now);
+        let wrong = if code == "000000" { "000001" } else { "000000" };
+        let config = BackoffConfig::new(Duration::from_millis(300));
+
+        // 验证码错误：补齐退避延迟并计入失败次数
+        let started = Instant::now();
+        let verification = verify_totp(&blob, "secret", wrong, &config).expect("校验失败");
+        assert!(
+            started.elapsed() >= config.base_delay().mul_f64(1.0 - backoff::JITTER_RATIO),
+            "验证码错误的路径未补齐退避延迟: {:?}",
+            started.elapsed()
+        );
+        assert_eq!(verification.outcome, VerifyOutcome::InvalidPassword);
+        let blob = verification.blob.expect("失败计数应写回");
+        let lockout = open_for(&blob, "secret").unwrap().lockout;
+        assert_eq!(lockout.failed_attempts, 1);
+
+        // 锁定期间正确的验证码同样被拒绝
+        let failed_at = OffsetDateTime::parse(
+            lockout.last_failure_at.as_deref().expect("缺少失败时间"),
+            &time::format_description::well_known::Rfc3339,
+        )
+        .unwrap();
+        assert!(matches!(
+            verify_tracked(&blob, "secret", Some(&code), failed_at),
+            Err(AmberlockError::LockedOut { .. })
+        ));
+        println!("✅ 验证码错误同样退避并计入锁定");
+    }
+
+    #[test]
+    fn test_totp_secret_round_trips_and_stays_out_of_metadata() {
+        let mut vault = VaultBlob::with_default("secret").expect("创建保险库失败");
+        let secret = vault.enable_totp("secret", None).expect("启用 TOTP 失败");
+        let plain = vault.encode().expect("编码失败");
+        assert_eq!(
+            VaultBlob::decode(&plain).unwrap().totp,
+            Some(secret.clone())
+        );
+
+        let metadata = VaultMetadata::from_plain(&plain).expect("读取元数据失败");
+        assert!(metadata.totp_enabled);
+        assert!(!format!("{:?}", metadata).contains(&secret.to_base32()));
+        assert!(!format!("{:?}", vault).contains(&format!("{:?}", secret.as_bytes())));
+
+        let code = secret.code_at(OffsetDateTime::now_utc().unix_timestamp() as u64);
+        vault.disable_totp("secret", Some(&code)).unwrap();
+        let plain = vault.encode().expect("编码失败");
+        assert!(!VaultMetadata::from_plain(&plain).unwrap().totp_enabled);
+        println!("✅ TOTP 密钥随保险库保存，元数据只标明是否启用");
+    }
+
+    #[test]
+    fn test_create_vault_async() {
+        let blob = create_vault_async("secret".into())
+            .join()
+            .expect("创建保险库失败");
+        assert_eq!(Backend::of(&blob), Backend::available());
+        assert!(verify_password(&blob, "secret").unwrap());
+        println!("✅ 后台创建保险库");
+    }
+
+    #[cfg(not(all(windows, feature = "dpapi")))]
+    #[test]
+    fn test_fallback_when_dpapi_unavailable() {
+        let blob = create_vault("secret").expect("创建保险库失败");
+        assert_eq!(Backend::of(&blob), Backend::Password);
+        assert!(verify_password(&blob, "secret").unwrap());
+        assert!(!verify_password(&blob, "wrong").unwrap());
+
+        let blob =
+            set_password_hint(&blob, "secret", None, Some("猫的名字")).expect("设置提示失败");
+        let portable = export_portable(&blob, "secret", None).expect("导出失败");
+        let imported = import_portable(&portable, "secret", None).expect("导入失败");
+        assert_eq!(Backend::of(&imported), Backend::Password);
+        assert!(verify_password(&imported, "secret").unwrap());
+        println!("✅ DPAPI 不可用时以密码加密保险库");
+    }
+}
@@ -0,0 +1,158 @@
+//! 持久化的失败计数与递增锁定
+//!
+//! `apply_backoff` 只延迟单次调用，重启程序即可绕过。失败次数与最近一次失败的时间
+//! 记录在保险库中（随保险库一同经 DPAPI 加密写回），重启后依然有效。
+//!
+//! # 规则
+//! - 第 n 次连续失败后锁定 `500 ms × 2^(n-1)`，上限 30 秒（500 ms、1 s、2 s……30 s）
+//! - 锁定期间的校验直接拒绝，不计入失败次数
+//! - 校验成功后清零
+//! - 最近失败时间无法解析或晚于当前时间（时钟回拨）时按整段锁定处理
+
+use serde::{Deserialize, Serialize};
+use std::time::Duration;
+use time::OffsetDateTime;
+use time::format_description::well_known::Rfc3339;
+
+/// 首次失败后的锁定时长
+pub const BASE_LOCKOUT: Duration = Duration::from_millis(500);
+/// 锁定时长上限
+pub const MAX_LOCKOUT: Duration = Duration::from_secs(30);
+
+/// 保险库中记录的失败状态
+#[derive(Debug, Clone, Default, PartialEq, Eq, Serialize, Deserialize)]
+pub struct LockoutState {
+    /// 连续失败次数
+    #[serde(default)]
+    pub failed_attempts: u32,
+    /// 最近一次失败的时间（RFC3339）
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub last_failure_at: Option<String>,
+}
+
+impl LockoutState {
+    /// 是否没有失败记录
+    pub fn is_clear(&self) -> bool {
+        self.failed_attempts == 0 && self.last_failure_at.is_none()
+    }
+
+    /// 记录一次失败
+    pub fn record_failure(&mut self, now: OffsetDateTime) {
+        self.failed_attempts = self.failed_attempts.saturating_add(1);
+        self.last_failure_at = now.format(&Rfc3339).ok();
+    }
+
+    /// 校验成功后清零
+    pub fn reset(&mut self) {
+        *self = Self::default();
+    }
+
+    /// 计算指定时刻的锁定情况
+    pub fn info(&self, now: OffsetDateTime) -> LockoutInfo {
+        let delay = lockout_delay(self.failed_attempts);
+        let elapsed = self
+            .last_failure_at
+            .as_deref()
+            .and_then(|at| OffsetDateTime::parse(at, &Rfc3339).ok())
+            .and_then(|at| Duration::try_from(now - at).ok())
+            .unwrap_or(Duration::ZERO);
+        LockoutInfo {
+            failed_attempts: self.failed_attempts,
+            delay,
+            remaining: delay.saturating_sub(elapsed),
+        }
+    }
+}
+
+/// 锁定情况
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct LockoutInfo {
+    /// 连续失败次数
+    pub failed_attempts: u32,
+    /// 当前失败次数对应的锁定时长
+    pub delay: Duration,
+    /// 剩余锁定时长（为零时可以校验）
+    pub remaining: Duration,
+}
+
+impl LockoutInfo {
+    /// 是否仍在锁定中
+    pub fn is_locked(&self) -> bool {
+        !self.remaining.is_zero()
+    }
+
+    /// 剩余锁定秒数（向上取整，用于提示）
+    pub fn remaining_secs(&self) -> u64 {
+        self.remaining.as_millis().div_ceil(1000) as u64
+    }
+}
+
+/// 连续失败 `failed_attempts` 次后的锁定时长
+pub fn lockout_delay(failed_attempts: u32) -> Duration {
+    match failed_attempts {
+        0 => Duration::ZERO,
+        n => BASE_LOCKOUT
+            .checked_mul(1u32.checked_shl(n - 1).unwrap_or(u32::MAX))
+            .map_or(MAX_LOCKOUT, |delay| delay.min(MAX_LOCKOUT)),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_lockout_delay_escalates() {
+        let delays: Vec<_> = (0..=8).map(lockout_delay).collect();
+        assert_eq!(
+            delays,
+            [0, 500, 1000, 2000, 4000, 8000, 16000, 30000, 30000].map(Duration::from_millis)
+        );
+        assert_eq!(lockout_delay(40), MAX_LOCKOUT);
+        assert_eq!(lockout_delay(u32::MAX), MAX_LOCKOUT);
+    }
+
+    #[test]
+    fn test_record_failure_and_reset() {
+        let start = OffsetDateTime::UNIX_EPOCH + Duration::from_secs(1_700_000_000);
+        let mut state = LockoutState::default();
+        assert!(state.is_clear());
+        assert!(!state.info(start).is_locked());
+
+        let mut previous = Duration::ZERO;
+        for attempt in 1..=4 {
+            state.record_failure(start);
+            let info = state.info(start);
+            assert_eq!(info.failed_attempts, attempt);
+            assert!(
+                info.delay > previous,
+                "第 {} 次失败后锁定时长未增长",
+                attempt
+            );
+            assert_eq!(info.remaining, info.delay);
+            previous = info.delay;
+        }
+
+        // 4 次失败锁定 4 秒：3 秒后仍锁定 1 秒，4 秒后解除
+        let info = state.info(start + Duration::from_secs(3));
+        assert!(info.is_locked());
+        assert_eq!(info.remaining_secs(), 1);
+        assert!(!state.info(start + Duration::from_secs(4)).is_locked());
+
+        state.reset();
+        assert!(state.is_clear());
+        println!("✅ 连续失败后锁定时长递增，成功后清零");
+    }
+
+    #[test]
+    fn test_clock_skew_keeps_full_lockout() {
+        let now = OffsetDateTime::UNIX_EPOCH + Duration::from_secs(1_700_000_000);
+        let mut state = LockoutState::default();
+        state.record_failure(now + Duration::from_secs(3600));
+        assert_eq!(state.info(now).remaining, BASE_LOCKOUT);
+
+        state.last_failure_at = Some("not a timestamp".to_string());
+        assert_eq!(state.info(now).remaining, BASE_LOCKOUT);
+        println!("✅ 时钟回拨或时间无法解析时保持锁定");
+    }
+}
@@ -0,0 +1,102 @@
+//! 密码校验结果分类
+//!
+//! `verify_password` 以 `Ok(false)` 表示密码错误，以各种 `Err` 表示保险库无法使用，
+//! 调用方只能靠错误文本区分。`VerifyOutcome` 把保险库层面的失败归为几类，
+//! 界面据此给出针对性的提示（重新输入密码、从备份恢复、换用创建保险库的账户）。
+
+use amberlock_types::{AmberlockError, Result};
+
+/// 密码校验结果
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum VerifyOutcome {
+    /// 密码正确
+    Valid,
+    /// 密码错误
+    InvalidPassword,
+    /// 保险库损坏或被篡改（应从备份恢复）
+    VaultCorrupted,
+    /// 保险库属于其他用户或其他计算机（DPAPI 密钥不可用）
+    WrongUserOrMachine,
+}
+
+impl VerifyOutcome {
+    /// 将底层校验结果归类
+    ///
+    /// # 返回
+    /// - `Ok(outcome)`: 密码正确、错误，或保险库层面的失败
+    /// - `Err`: 与保险库内容无关的错误（平台不支持、凭据不存在等），原样返回
+    pub fn classify(result: Result<bool>) -> Result<Self> {
+        match result {
+            Ok(true) => Ok(VerifyOutcome::Valid),
+            Ok(false) | Err(AmberlockError::WrongPassword) => Ok(VerifyOutcome::InvalidPassword),
+            Err(AmberlockError::VaultWrongUserOrMachine(_)) => {
+                Ok(VerifyOutcome::WrongUserOrMachine)
+            }
+            Err(
+                AmberlockError::VaultTampered(_)
+                | AmberlockError::VaultCorrupted(_)
+                | AmberlockError::InconsistentVaultParams { .. },
+            ) => Ok(VerifyOutcome::VaultCorrupted),
+            Err(e) => Err(e),
+        }
+    }
+
+    /// 密码是否正确
+    pub fn is_valid(self) -> bool {
+        self == VerifyOutcome::Valid
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_classify() {
+        let cases = [
+            (Ok(true), VerifyOutcome::Valid),
+            (Ok(false), VerifyOutcome::InvalidPassword),
+            (
+                Err(AmberlockError::WrongPassword),
+                VerifyOutcome::InvalidPassword,
+            ),
+            (
+                Err(AmberlockError::VaultTampered("完整性校验不一致".into())),
+                VerifyOutcome::VaultCorrupted,
+            ),
+            (
+                Err(AmberlockError::VaultCorrupted("EOF while parsing".into())),
+                VerifyOutcome::VaultCorrupted,
+            ),
+            (
+                Err(AmberlockError::InconsistentVaultParams {
+                    name: "default".into(),
+                    reason: "哈希长度不一致".into(),
+                }),
+                VerifyOutcome::VaultCorrupted,
+            ),
+            (
+                Err(AmberlockError::VaultWrongUserOrMachine(
+                    "NTE_BAD_KEY_STATE".into(),
+                )),
+                VerifyOutcome::WrongUserOrMachine,
+            ),
+        ];
+        for (result, expected) in cases {
+            assert_eq!(VerifyOutcome::classify(result).unwrap(), expected);
+        }
+        assert!(VerifyOutcome::Valid.is_valid());
+        assert!(!VerifyOutcome::VaultCorrupted.is_valid());
+
+        // 与保险库内容无关的错误原样返回
+        assert!(matches!(
+            VerifyOutcome::classify(Err(AmberlockError::Unsupported)),
+            Err(AmberlockError::Unsupported)
+        ));
+        assert!(matches!(
+            VerifyOutcome::classify(Err(AmberlockError::CredentialNotFound("x".into()))),
+            Err(AmberlockError::CredentialNotFound(_))
+        ));
+        println!("✅ 校验结果按原因归类");
+    }
+}
@@ -0,0 +1,244 @@
+//! 可迁移的保险库导出格式
+//!
+//! DPAPI 加密的保险库只能在原用户、原计算机上打开，换机后无法使用。导出格式改用
+//! 由密码派生的密钥加密（Argon2id + ChaCha20-Poly1305），在新计算机上凭同一密码导入，
+//! 重新以 DPAPI 加密。
+//!
+//! # 格式
+//! 依次为 `MAGIC`(4 字节)、版本(1 字节)、Argon2 参数（m、t、p，各 4 字节小端）、
+//! 盐(16 字节)、随机数(12 字节)、密文（含 16 字节认证标签）。密文是保险库的 v5 二进制明文；
+//! 密文之前的全部字节作为附加认证数据，头部被改动同样无法解密。
+//!
+//! # 注意
+//! - 密码错误与文件被改动无法区分，均返回 `PortableDecryptFailed`
+//! - 导出文件只受密码保护，应像备份一样妥善保管
+
+use crate::vault::{Argon2Config, Argon2Params, MAX_CALIBRATE_MEM_KIB, SALT_LEN, hash_password};
+use amberlock_types::{AmberlockError, Result};
+use chacha20poly1305::aead::{Aead, KeyInit, Payload};
+use chacha20poly1305::{ChaCha20Poly1305, Key, Nonce};
+use zeroize::Zeroizing;
+
+/// 导出文件的前缀
+pub const MAGIC: &[u8; 4] = b"ALVP";
+/// 当前导出格式版本
+pub const PORTABLE_VERSION: u8 = 1;
+/// 派生密钥长度（字节）
+const KEY_LEN: usize = 32;
+/// 随机数长度（字节）
+const NONCE_LEN: usize = 12;
+/// 认证标签长度（字节）
+pub(crate) const TAG_LEN: usize = 16;
+/// 头部长度：前缀、版本、三个参数、盐、随机数
+pub(crate) const HEADER_LEN: usize = MAGIC.len() + 1 + 12 + SALT_LEN + NONCE_LEN;
+
+/// 以密码加密保险库明文
+///
+/// # 参数
+/// - `plain`: 保险库的二进制明文
+/// - `config`: 派生密钥使用的 Argon2id 配置（记录在头部）
+pub fn seal(plain: &[u8], password: &str, config: &Argon2Config) -> Result<Vec<u8>> {
+    config.validate()?;
+    let params = config.params();
+    let mut salt = [0u8; SALT_LEN];
+    rand::fill(&mut salt[..]);
+    let mut nonce = [0u8; NONCE_LEN];
+    rand::fill(&mut nonce[..]);
+
+    let mut out = Vec::with_capacity(HEADER_LEN + plain.len() + TAG_LEN);
+    out.extend_from_slice(MAGIC);
+    out.push(PORTABLE_VERSION);
+    for value in [params.m_cost, params.t_cost, params.p_cost] {
+        out.extend_from_slice(&value.to_le_bytes());
+    }
+    out.extend_from_slice(&salt);
+    out.extend_from_slice(&nonce);
+
+    let cipher = cipher(password, &salt, params)?;
+    let ciphertext = cipher
+        .encrypt(
+            Nonce::from_slice(&nonce),
+            Payload {
+                msg: plain,
+                aad: &out,
+            },
+        )
+        .map_err(|_| AmberlockError::PortableFormat("加密失败".to_string()))?;
+    out.extend_from_slice(&ciphertext);
+    Ok(out)
+}
+
+/// 以密码解密导出文件，返回保险库明文
+///
+/// # 返回
+/// - `Err(PortableFormat)`: 不是导出文件、版本不受支持或头部参数无效
+/// - `Err(PortableDecryptFailed)`: 密码错误或文件被改动
+pub fn open(portable: &[u8], password: &str) -> Result<Zeroizing<Vec<u8>>> {
+    let params = check_header(portable)?;
+    let (header, ciphertext) = portable.split_at(HEADER_LEN);
+    let salt_at = HEADER_LEN - NONCE_LEN - SALT_LEN;
+    let salt = &header[salt_at..salt_at + SALT_LEN];
+    let nonce = &header[salt_at + SALT_LEN..];
+    let cipher = cipher(password, salt, params)?;
+    let plain = cipher
+        .decrypt(
+            Nonce::from_slice(nonce),
+            Payload {
+                msg: ciphertext,
+                aad: header,
+            },
+        )
+        .map_err(|_| AmberlockError::PortableDecryptFailed)?;
+    Ok(Zeroizing::new(plain))
+}
+
+/// 不带密码检查头部：前缀、版本、Argon2 参数，以及长度至少容纳头部与认证标签
+///
+/// # 返回
+/// - `Ok(params)`: 头部记录的 Argon2 参数
+/// - `Err(PortableFormat)`: 不是导出文件、版本不受支持或头部参数无效
+pub(crate) fn check_header(portable: &[u8]) -> Result<Argon2Params> {
+    if portable.len() < HEADER_LEN + TAG_LEN || !portable.starts_with(MAGIC) {
+        return Err(AmberlockError::PortableFormat(
+            "不是 AmberLock 导出文件".to_string(),
+        ));
+    }
+    let version = portable[MAGIC.len()];
+    if version != PORTABLE_VERSION {
+        return Err(AmberlockError::PortableFormat(format!(
+            "不支持的版本 {}（当前版本 {}）",
+            version, PORTABLE_VERSION
+        )));
+    }
+
+    let header = &portable[..HEADER_LEN];
+    let u32_at = |offset: usize| {
+        u32::from_le_bytes(
+            header[offset..offset + 4]
+                .try_into()
+                .expect("头部长度已校验"),
+        )
+    };
+    let params_at = MAGIC.len() + 1;
+    let params = Argon2Params {
+        m_cost: u32_at(params_at),
+        t_cost: u32_at(params_at + 4),
+        p_cost: u32_at(params_at + 8),
+    };
+    // 头部参数来自文件本身，限制内存上限，避免构造的文件耗尽内存
+    if params.m_cost > MAX_CALIBRATE_MEM_KIB {
+        return Err(AmberlockError::PortableFormat(format!(
+            "Argon2 参数 {} 超出上限",
+            params
+        )));
+    }
+    let config = Argon2Config {
+        mem_kib: params.m_cost,
+        time_cost: params.t_cost,
+        parallelism: params.p_cost,
+        output_len: KEY_LEN,
+    };
+    config
+        .validate()
+        .map_err(|e| AmberlockError::PortableFormat(e.to_string()))?;
+    Ok(params)
+}
+
+/// 由密码派生密钥并构造加密器（派生出的密钥用完清零）
+fn cipher(password: &str, salt: &[u8], params: Argon2Params) -> Result<ChaCha20Poly1305> {
+    let key = Zeroizing::new(hash_password(password, salt, None, params, KEY_LEN)?);
+    Ok(ChaCha20Poly1305::new(Key::from_slice(&key)))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::vault::{MIN_MEM_KIB, VaultBlob};
+
+    /// 测试用的低开销配置
+    fn fast_config() -> Argon2Config {
+        Argon2Config {
+            mem_kib: MIN_MEM_KIB,
+            time_cost: 1,
+            parallelism: 1,
+            output_len: KEY_LEN,
+        }
+    }
+
+    #[test]
+    fn test_round_trip() {
+        let vault = VaultBlob::with_default("secret").expect("创建保险库失败");
+        let plain = Zeroizing::new(vault.encode().expect("编码失败"));
+        let portable = seal(&plain, "secret", &fast_config()).expect("导出失败");
+        assert!(portable.starts_with(MAGIC));
+        assert_eq!(portable[MAGIC.len()], PORTABLE_VERSION);
+        assert_eq!(portable.len(), HEADER_LEN + plain.len() + TAG_LEN);
+
+        let opened = open(&portable, "secret").expect("导入失败");
+        assert_eq!(&opened[..], &plain[..]);
+        let restored = VaultBlob::decode(&opened).expect("解析失败");
+        assert!(restored.verify("default", "secret").unwrap());
+
+        // 每次导出的盐与随机数不同
+        let again = seal(&plain, "secret", &fast_config()).expect("导出失败");
+        assert_ne!(again, portable);
+        println!("✅ 导出文件往返");
+    }
+
+    #[test]
+    fn test_wrong_password_and_tampering_fail_authentication() {
+        let portable = seal(b"vault plaintext", "secret", &fast_config()).expect("导出失败");
+        assert!(matches!(
+            open(&portable, "wrong"),
+            Err(AmberlockError::PortableDecryptFailed)
+        ));
+
+        // 密文、标签或头部中的盐、随机数任一字节被改动都无法通过认证
+        for index in [
+            portable.len() - 1,
+            HEADER_LEN,
+            HEADER_LEN - 1,
+            HEADER_LEN - NONCE_LEN - 1,
+        ] {
+            let mut corrupted = portable.clone();
+            corrupted[index] ^= 0x01;
+            assert!(
+                matches!(
+                    open(&corrupted, "secret"),
+                    Err(AmberlockError::PortableDecryptFailed)
+                ),
+                "改动第 {} 字节后仍能解密",
+                index
+            );
+        }
+        println!("✅ 密码错误或文件被改动时认证失败");
+    }
+
+    #[test]
+    fn test_rejects_malformed_header() {
+        let portable = seal(b"vault plaintext", "secret", &fast_config()).expect("导出失败");
+        let format_error = |bytes: &[u8]| {
+            matches!(
+                open(bytes, "secret"),
+                Err(AmberlockError::PortableFormat(_))
+            )
+        };
+
+        assert!(format_error(b""));
+        assert!(format_error(&portable[..HEADER_LEN]));
+        let mut wrong_magic = portable.clone();
+        wrong_magic[0] = b'X';
+        assert!(format_error(&wrong_magic));
+        let mut future = portable.clone();
+        future[MAGIC.len()] = PORTABLE_VERSION + 1;
+        assert!(format_error(&future));
+        // 内存开销被改为超大值：拒绝而不是尝试分配
+        let mut huge = portable.clone();
+        huge[MAGIC.len() + 1..MAGIC.len() + 5].copy_from_slice(&u32::MAX.to_le_bytes());
+        assert!(format_error(&huge));
+        let mut zero_time = portable;
+        zero_time[MAGIC.len() + 5..MAGIC.len() + 9].copy_from_slice(&0u32.to_le_bytes());
+        assert!(format_error(&zero_time));
+        println!("✅ 拒绝格式错误的导出文件");
+    }
+}
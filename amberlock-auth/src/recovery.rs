@@ -0,0 +1,196 @@
+//! 恢复密钥
+//!
+//! 忘记主密码时凭恢复密钥重设 `default` 凭据的密码。恢复密钥是 256 位随机值，
+//! 以分组的 base32 文本（RFC 4648 字母表，无填充，每 4 个字符一组，以 `-` 分隔）
+//! 交给用户保管；保险库中只保存它的 Argon2id 哈希（与普通凭据相同的格式），
+//! 密钥本身从不写入保险库。
+//!
+//! 解析时忽略大小写、空白与 `-`，并把易混淆的 `0`/`1`/`8` 视为 `O`/`I`/`B`。
+
+use amberlock_types::{AmberlockError, Result};
+use std::fmt;
+use zeroize::{Zeroize, ZeroizeOnDrop, Zeroizing};
+
+/// 恢复密钥长度（字节）
+pub const RECOVERY_KEY_LEN: usize = 32;
+/// 文本形式每组字符数
+pub const GROUP_LEN: usize = 4;
+
+const ALPHABET: &[u8; 32] = b"ABCDEFGHIJKLMNOPQRSTUVWXYZ234567";
+
+/// 恢复密钥（释放时清零）
+///
+/// `Display` 输出分组文本，仅用于创建时展示给用户；`Debug` 不含内容
+#[derive(Clone, PartialEq, Eq)]
+pub struct RecoveryKey(Zeroizing<[u8; RECOVERY_KEY_LEN]>);
+
+impl RecoveryKey {
+    /// 生成新的随机恢复密钥
+    pub fn generate() -> Self {
+        let mut bytes = Zeroizing::new([0u8; RECOVERY_KEY_LEN]);
+        rand::fill(&mut bytes[..]);
+        Self(bytes)
+    }
+
+    /// 解析用户输入的恢复密钥
+    ///
+    /// # 返回
+    /// - `Err(InvalidRecoveryKey)`: 含有 base32 以外的字符，或长度不是 256 位
+    pub fn parse(text: &str) -> Result<Self> {
+        let mut bits: u64 = 0;
+        let mut bit_count = 0;
+        let mut bytes = Zeroizing::new(Vec::with_capacity(RECOVERY_KEY_LEN + 1));
+        for c in text.chars() {
+            if c.is_whitespace() || c == '-' {
+                continue;
+            }
+            let c = match c.to_ascii_uppercase() {
+                '0' => 'O',
+                '1' => 'I',
+                '8' => 'B',
+                other => other,
+            };
+            let value = ALPHABET
+                .iter()
+                .position(|&a| a as char == c)
+                .ok_or_else(|| AmberlockError::InvalidRecoveryKey(format!("无效字符 {:?}", c)))?;
+            bits = (bits << 5) | value as u64;
+            bit_count += 5;
+            if bit_count >= 8 {
+                bit_count -= 8;
+                bytes.push((bits >> bit_count) as u8);
+            }
+        }
+        bits.zeroize();
+
+        let bytes: [u8; RECOVERY_KEY_LEN] = bytes[..].try_into().map_err(|_| {
+            AmberlockError::InvalidRecoveryKey(format!(
+                "长度应为 {} 位，实际为 {} 位",
+                RECOVERY_KEY_LEN * 8,
+                bytes.len() * 8
+            ))
+        })?;
+        Ok(Self(Zeroizing::new(bytes)))
+    }
+
+    /// 不分组的 base32 文本（作为 Argon2id 的输入）
+    pub(crate) fn canonical(&self) -> Zeroizing<String> {
+        let mut out = Zeroizing::new(String::with_capacity(encoded_len()));
+        let mut bits: u64 = 0;
+        let mut bit_count = 0;
+        for &byte in self.0.iter() {
+            bits = (bits << 8) | byte as u64;
+            bit_count += 8;
+            while bit_count >= 5 {
+                bit_count -= 5;
+                out.push(ALPHABET[((bits >> bit_count) & 0x1f) as usize] as char);
+            }
+        }
+        if bit_count > 0 {
+            out.push(ALPHABET[((bits << (5 - bit_count)) & 0x1f) as usize] as char);
+        }
+        bits.zeroize();
+        out
+    }
+}
+
+/// 不分组文本的长度（256 位 → 52 个字符）
+const fn encoded_len() -> usize {
+    (RECOVERY_KEY_LEN * 8).div_ceil(5)
+}
+
+impl fmt::Display for RecoveryKey {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        for (i, group) in self.canonical().as_bytes().chunks(GROUP_LEN).enumerate() {
+            if i > 0 {
+                f.write_str("-")?;
+            }
+            // 字母表均为 ASCII
+            f.write_str(std::str::from_utf8(group).map_err(|_| fmt::Error)?)?;
+        }
+        Ok(())
+    }
+}
+
+impl fmt::Debug for RecoveryKey {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.write_str("RecoveryKey(***)")
+    }
+}
+
+impl Zeroize for RecoveryKey {
+    fn zeroize(&mut self) {
+        self.0.zeroize();
+    }
+}
+
+impl ZeroizeOnDrop for RecoveryKey {}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_display_and_parse_round_trip() {
+        let key = RecoveryKey::generate();
+        let text = key.to_string();
+        let groups: Vec<_> = text.split('-').collect();
+        assert_eq!(groups.len(), encoded_len().div_ceil(GROUP_LEN));
+        assert!(
+            groups[..groups.len() - 1]
+                .iter()
+                .all(|g| g.len() == GROUP_LEN)
+        );
+        assert!(
+            text.chars()
+                .all(|c| c == '-' || ALPHABET.contains(&(c as u8)))
+        );
+
+        assert_eq!(RecoveryKey::parse(&text).unwrap(), key);
+        // 小写、空格分隔同样接受
+        let relaxed = text.to_lowercase().replace('-', " ");
+        assert_eq!(RecoveryKey::parse(&relaxed).unwrap(), key);
+        assert_ne!(RecoveryKey::generate(), key);
+        assert_eq!(format!("{:?}", key), "RecoveryKey(***)");
+        println!("✅ 恢复密钥文本往返");
+    }
+
+    #[test]
+    fn test_known_encoding() {
+        let key = RecoveryKey(Zeroizing::new([0xff; RECOVERY_KEY_LEN]));
+        let text = key.to_string();
+        assert!(text.starts_with("7777-7777-"));
+        // 最后一个字符只含 1 位有效数据，低位补零
+        assert!(text.ends_with("777Q"));
+
+        let zero = RecoveryKey(Zeroizing::new([0; RECOVERY_KEY_LEN]));
+        assert_eq!(zero.to_string().replace('-', ""), "A".repeat(encoded_len()));
+
+        // 易混淆的数字按字母处理
+        let key = RecoveryKey::generate();
+        let lookalike = key
+            .to_string()
+            .replace('O', "0")
+            .replace('I', "1")
+            .replace('B', "8");
+        assert_eq!(RecoveryKey::parse(&lookalike).unwrap(), key);
+        println!("✅ 恢复密钥编码符合 RFC 4648");
+    }
+
+    #[test]
+    fn test_parse_rejects_malformed() {
+        let text = RecoveryKey::generate().to_string();
+        for bad in [
+            "",
+            &text[..text.len() - 5],
+            &format!("{}-AAAA", text),
+            &text.replacen(|c: char| c.is_ascii_alphabetic(), "!", 1),
+        ] {
+            assert!(matches!(
+                RecoveryKey::parse(bad),
+                Err(AmberlockError::InvalidRecoveryKey(_))
+            ));
+        }
+        println!("✅ 拒绝格式错误的恢复密钥");
+    }
+}
@@ -0,0 +1,89 @@
+//! 释放时清零的密码字符串
+//!
+//! 调用方持有的密码（界面输入、标准输入读取的行）用 `SecretString` 包装，
+//! 离开作用域时缓冲区被清零。本 crate 的 API 仍接受 `&str`，可通过 `as_str` 传入。
+
+use std::fmt;
+use zeroize::{Zeroize, ZeroizeOnDrop, Zeroizing};
+
+/// 释放时清零的字符串
+///
+/// `Debug` 输出不含内容；不实现 `Display`/`Clone`，避免密码被无意复制或写入日志
+#[derive(Default)]
+pub struct SecretString(Zeroizing<String>);
+
+impl SecretString {
+    /// 接管已有字符串的缓冲区（不复制）
+    pub fn new(value: String) -> Self {
+        Self(Zeroizing::new(value))
+    }
+
+    /// 取得字符串内容
+    pub fn as_str(&self) -> &str {
+        &self.0
+    }
+
+    /// 是否为空字符串
+    pub fn is_empty(&self) -> bool {
+        self.0.is_empty()
+    }
+}
+
+impl From<String> for SecretString {
+    fn from(value: String) -> Self {
+        Self::new(value)
+    }
+}
+
+impl From<&str> for SecretString {
+    fn from(value: &str) -> Self {
+        Self::new(value.to_string())
+    }
+}
+
+impl Zeroize for SecretString {
+    fn zeroize(&mut self) {
+        self.0.zeroize();
+    }
+}
+
+impl ZeroizeOnDrop for SecretString {}
+
+impl fmt::Debug for SecretString {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.write_str("SecretString(***)")
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_zeroize_clears_buffer() {
+        // 释放时执行的正是这一步（`Zeroizing` 的 Drop 先清零再释放缓冲区）
+        let mut secret = SecretString::from("correct horse");
+        assert_eq!(secret.as_str(), "correct horse");
+        let ptr = secret.as_str().as_ptr();
+        let len = secret.as_str().len();
+
+        // 清零后缓冲区仍归字符串所有（容量不变），可以安全读取原位置
+        secret.zeroize();
+        assert!(secret.is_empty());
+        let bytes = unsafe { std::slice::from_raw_parts(ptr, len) };
+        assert!(bytes.iter().all(|&b| b == 0));
+        println!("✅ SecretString 清零缓冲区");
+    }
+
+    #[test]
+    fn test_takes_buffer_without_copy() {
+        // 包装前后是同一块内存，没有留下未清零的副本
+        let value = String::from("hunter2");
+        let ptr = value.as_ptr();
+        let secret = SecretString::new(value);
+        assert_eq!(secret.as_str().as_ptr(), ptr);
+        assert_eq!(format!("{:?}", secret), "SecretString(***)");
+        drop(secret);
+        println!("✅ SecretString 接管缓冲区且不泄露内容");
+    }
+}
@@ -0,0 +1,310 @@
+//! 密码强度估计
+//!
+//! 创建保险库前给出强度参考，不联网、不依赖外部词典。`create_vault` 本身不拒绝弱密码，
+//! 由界面根据报告决定是否要求用户确认。
+//!
+//! # 估计方法
+//! - 字符集：按出现的字符类别（小写、大写、数字、ASCII 符号、空格、非 ASCII）累加候选数
+//! - 有效长度：与前一个字符相同或相邻（如 `aaa`、`abc`、`321`）的字符只计 1/4
+//! - 常见密码与键盘序列：内置的常见词按整体计 `DICTIONARY_WORD_BITS` 位，
+//!   整个密码就是常见密码（允许末尾附加数字与符号）时熵不超过 `COMMON_PASSWORD_BITS`
+//! - 熵 = 有效长度 × log2(字符集大小) + 常见词位数，按阈值折算为 0–4 分
+
+/// 建议的最低分数（低于此分数时界面应要求确认）
+pub const MIN_RECOMMENDED_SCORE: u8 = 3;
+/// 建议的最短长度（字符数）
+pub const MIN_RECOMMENDED_LEN: usize = 12;
+/// 每个命中的常见词计入的熵
+pub const DICTIONARY_WORD_BITS: f64 = 8.0;
+/// 整体为常见密码时熵的上限
+pub const COMMON_PASSWORD_BITS: f64 = 10.0;
+
+/// 分数阈值：熵达到 `SCORE_THRESHOLDS[i]` 位时得 `i + 1` 分
+const SCORE_THRESHOLDS: [f64; 4] = [28.0, 36.0, 60.0, 80.0];
+
+/// 非 ASCII 字符计入的候选数（保守估计，常用汉字远多于此）
+const NON_ASCII_POOL: u32 = 100;
+
+/// AmberLock 首次运行时的默认密码
+const DEFAULT_PASSWORD: &str = "amberlock";
+
+/// 常见密码与键盘序列（小写）
+const COMMON_WORDS: &[&str] = &[
+    "amberlock",
+    "password",
+    "passw0rd",
+    "p@ssw0rd",
+    "admin",
+    "administrator",
+    "welcome",
+    "letmein",
+    "iloveyou",
+    "monkey",
+    "dragon",
+    "master",
+    "sunshine",
+    "princess",
+    "football",
+    "baseball",
+    "shadow",
+    "superman",
+    "trustno1",
+    "login",
+    "secret",
+    "windows",
+    "qwerty",
+    "qwertyuiop",
+    "asdfgh",
+    "asdfghjkl",
+    "zxcvbn",
+    "1qaz2wsx",
+    "qazwsx",
+    "123456",
+    "12345678",
+    "123456789",
+    "1234567890",
+    "111111",
+    "000000",
+    "666666",
+    "888888",
+    "123123",
+    "654321",
+    "abc123",
+    "woaini",
+    "5201314",
+    "密码",
+    "我爱你",
+    "пароль",
+];
+
+/// 密码强度报告
+#[derive(Debug, Clone, PartialEq)]
+pub struct StrengthReport {
+    /// 分数（0–4，越高越强）
+    pub score: u8,
+    /// 估计的熵（位）
+    pub entropy_bits: f64,
+    /// 给用户的提示（按发现顺序）
+    pub warnings: Vec<String>,
+}
+
+impl StrengthReport {
+    /// 是否达到建议强度
+    pub fn is_recommended(&self) -> bool {
+        self.score >= MIN_RECOMMENDED_SCORE
+    }
+}
+
+/// 估计密码强度
+pub fn estimate_password_strength(password: &str) -> StrengthReport {
+    let chars: Vec<char> = password.chars().collect();
+    let lower: Vec<char> = password.to_lowercase().chars().collect();
+    let mut warnings = Vec::new();
+
+    if chars.is_empty() {
+        return StrengthReport {
+            score: 0,
+            entropy_bits: 0.0,
+            warnings: vec!["密码为空".to_string()],
+        };
+    }
+
+    // 常见词覆盖的字符不再按字符计熵（大小写转换后长度不变时才逐字符对应）
+    let mut covered = vec![false; chars.len()];
+    let mut dictionary_hits = 0usize;
+    if lower.len() == chars.len() {
+        for word in COMMON_WORDS {
+            let word: Vec<char> = word.chars().collect();
+            for start in 0..=lower.len().saturating_sub(word.len()) {
+                if lower[start..].starts_with(&word)
+                    && !covered[start..start + word.len()].iter().all(|&c| c)
+                {
+                    covered[start..start + word.len()].fill(true);
+                    dictionary_hits += 1;
+                }
+            }
+        }
+    }
+
+    let mut effective_len = 0.0;
+    for (i, &c) in chars.iter().enumerate() {
+        if covered[i] {
+            continue;
+        }
+        let predictable = i > 0 && (c as u32).abs_diff(chars[i - 1] as u32) <= 1;
+        effective_len += if predictable { 0.25 } else { 1.0 };
+    }
+
+    let pool = charset_pool(&chars);
+    let mut entropy_bits =
+        effective_len * f64::from(pool).log2() + dictionary_hits as f64 * DICTIONARY_WORD_BITS;
+
+    let lowered: String = lower.iter().collect();
+    let core = lowered.trim_end_matches(|c: char| c.is_ascii_digit() || c.is_ascii_punctuation());
+    if core == DEFAULT_PASSWORD {
+        warnings.push("这是 AmberLock 的默认密码，请务必更换".to_string());
+    }
+    if COMMON_WORDS.contains(&core) || core.is_empty() {
+        warnings.push("这是常见密码，极易被猜中".to_string());
+        entropy_bits = entropy_bits.min(COMMON_PASSWORD_BITS);
+    } else if dictionary_hits > 0 {
+        warnings.push("包含常见密码或键盘序列".to_string());
+    }
+    if chars.len() < MIN_RECOMMENDED_LEN {
+        warnings.push(format!("长度不足 {} 个字符", MIN_RECOMMENDED_LEN));
+    }
+    if effective_len < chars.len() as f64 * 0.75 && dictionary_hits == 0 {
+        warnings.push("包含重复或连续的字符".to_string());
+    }
+    if class_count(&chars) == 1 && chars.len() < 20 {
+        warnings.push("只包含一种字符，建议混合字母、数字与符号，或改用更长的短语".to_string());
+    }
+
+    let score = SCORE_THRESHOLDS
+        .iter()
+        .filter(|&&threshold| entropy_bits >= threshold)
+        .count() as u8;
+    StrengthReport {
+        score,
+        entropy_bits,
+        warnings,
+    }
+}
+
+/// 字符类别
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+enum CharClass {
+    Lower,
+    Upper,
+    Digit,
+    Symbol,
+    Space,
+    NonAscii,
+}
+
+impl CharClass {
+    fn of(c: char) -> Self {
+        match c {
+            'a'..='z' => CharClass::Lower,
+            'A'..='Z' => CharClass::Upper,
+            '0'..='9' => CharClass::Digit,
+            ' ' => CharClass::Space,
+            c if c.is_ascii() => CharClass::Symbol,
+            _ => CharClass::NonAscii,
+        }
+    }
+
+    fn pool(self) -> u32 {
+        match self {
+            CharClass::Lower | CharClass::Upper => 26,
+            CharClass::Digit => 10,
+            CharClass::Symbol => 33,
+            CharClass::Space => 1,
+            CharClass::NonAscii => NON_ASCII_POOL,
+        }
+    }
+}
+
+fn classes(chars: &[char]) -> Vec<CharClass> {
+    let mut classes = Vec::new();
+    for &c in chars {
+        let class = CharClass::of(c);
+        if !classes.contains(&class) {
+            classes.push(class);
+        }
+    }
+    classes
+}
+
+fn class_count(chars: &[char]) -> usize {
+    classes(chars).len()
+}
+
+/// 字符集大小（至少为 2，避免单字符集的熵为零后仍按长度区分）
+fn charset_pool(chars: &[char]) -> u32 {
+    classes(chars)
+        .into_iter()
+        .map(CharClass::pool)
+        .sum::<u32>()
+        .max(2)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_strength_table() {
+        // (密码, 最低分, 最高分)
+        let cases: &[(&str, u8, u8)] = &[
+            // 明显很弱
+            ("", 0, 0),
+            ("amberlock", 0, 0),
+            ("Amberlock2024!", 0, 1),
+            ("password", 0, 0),
+            ("123456", 0, 0),
+            ("aaaaaaaaaaaa", 0, 0),
+            ("abcdefghijkl", 0, 0),
+            ("qwerty123", 0, 0),
+            ("密码", 0, 0),
+            ("пароль", 0, 0),
+            ("🔒🔑", 0, 1),
+            // 中等
+            ("correcthorse", 2, 2),
+            ("sunflower42", 2, 2),
+            ("猫咪爱晒太阳", 2, 2),
+            ("Żółw-mały", 2, 3),
+            // 强
+            ("Xk9#mQ2$vL7!", 3, 4),
+            ("Summer-lake7", 3, 4),
+            ("correct horse battery staple", 4, 4),
+            ("我的猫叫小橘子喜欢晒太阳", 3, 4),
+            ("Zażółć gęślą jaźń 42", 4, 4),
+        ];
+        for &(password, min, max) in cases {
+            let report = estimate_password_strength(password);
+            assert!(
+                (min..=max).contains(&report.score),
+                "{:?} 得分 {}（熵 {:.1} 位），应在 {}–{} 之间",
+                password,
+                report.score,
+                report.entropy_bits,
+                min,
+                max
+            );
+            assert_eq!(
+                report.is_recommended(),
+                report.score >= MIN_RECOMMENDED_SCORE
+            );
+            if report.score < MIN_RECOMMENDED_SCORE {
+                assert!(!report.warnings.is_empty(), "{:?} 缺少提示", password);
+            }
+        }
+        println!("✅ 密码强度分级");
+    }
+
+    #[test]
+    fn test_warnings() {
+        let report = estimate_password_strength("amberlock");
+        assert!(report.warnings.iter().any(|w| w.contains("默认密码")));
+        assert!(report.entropy_bits <= COMMON_PASSWORD_BITS);
+
+        let report = estimate_password_strength("horse-password-lake");
+        assert!(
+            report
+                .warnings
+                .iter()
+                .any(|w| w.contains("常见密码或键盘序列"))
+        );
+
+        let report = estimate_password_strength("zzzzzz");
+        assert!(report.warnings.iter().any(|w| w.contains("重复或连续")));
+        assert!(report.warnings.iter().any(|w| w.contains("长度不足")));
+
+        // 更长的密码不会更弱
+        let short = estimate_password_strength("Summer-lake7");
+        let long = estimate_password_strength("Summer-lake7-river-stone");
+        assert!(long.entropy_bits > short.entropy_bits);
+        println!("✅ 密码强度提示");
+    }
+}
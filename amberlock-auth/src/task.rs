@@ -0,0 +1,165 @@
+//! 后台执行保险库操作
+//!
+//! Argon2id 在默认参数下单次计算即需数百毫秒，界面线程上直接调用会卡住窗口。
+//! 本模块把创建与校验放到独立线程中执行，通过 `VaultTask` 取得结果：
+//! 可以 `join()` 阻塞等待，也可以作为 Future 在界面的事件循环中等待（不依赖异步运行时）。
+//!
+//! # 注意
+//! - 退避延迟同样在工作线程中补齐，`VaultTask` 完成的时刻与同步调用返回的时刻一致
+//! - 丢弃 `VaultTask` 不会终止工作线程，结果被丢弃
+
+use amberlock_types::{AmberlockError, Result};
+use std::future::Future;
+use std::panic::AssertUnwindSafe;
+use std::pin::Pin;
+use std::sync::{Arc, Mutex};
+use std::task::{Context, Poll, Waker};
+use std::thread::JoinHandle;
+
+/// 工作线程与等待方共享的结果槽
+struct Slot<T> {
+    result: Option<Result<T>>,
+    waker: Option<Waker>,
+}
+
+/// 后台保险库操作的句柄
+pub struct VaultTask<T> {
+    slot: Arc<Mutex<Slot<T>>>,
+    worker: JoinHandle<()>,
+}
+
+impl<T: Send + 'static> VaultTask<T> {
+    /// 在独立线程中执行 `run`（崩溃时结果为 `WorkerPanicked`）
+    pub(crate) fn spawn(run: impl FnOnce() -> Result<T> + Send + 'static) -> Self {
+        let slot = Arc::new(Mutex::new(Slot {
+            result: None,
+            waker: None,
+        }));
+        let worker = {
+            let slot = slot.clone();
+            std::thread::Builder::new()
+                .name("amberlock-vault".to_string())
+                .spawn(move || {
+                    let result = std::panic::catch_unwind(AssertUnwindSafe(run))
+                        .unwrap_or(Err(AmberlockError::WorkerPanicked));
+                    let waker = {
+                        let mut slot = slot.lock().unwrap_or_else(|e| e.into_inner());
+                        slot.result = Some(result);
+                        slot.waker.take()
+                    };
+                    if let Some(waker) = waker {
+                        waker.wake();
+                    }
+                })
+                .expect("无法创建保险库工作线程")
+        };
+        Self { slot, worker }
+    }
+}
+
+impl<T> VaultTask<T> {
+    /// 工作线程是否已结束（结束后 `join()` 不会阻塞）
+    pub fn is_finished(&self) -> bool {
+        self.worker.is_finished()
+    }
+
+    /// 阻塞等待操作结束
+    ///
+    /// # 注意
+    /// 已作为 Future 完成的句柄结果已被取走，此时返回 `WorkerPanicked`
+    pub fn join(self) -> Result<T> {
+        let _ = self.worker.join();
+        take(&self.slot).unwrap_or(Err(AmberlockError::WorkerPanicked))
+    }
+}
+
+impl<T> Future for VaultTask<T> {
+    type Output = Result<T>;
+
+    fn poll(self: Pin<&mut Self>, cx: &mut Context<'_>) -> Poll<Self::Output> {
+        let mut slot = self.slot.lock().unwrap_or_else(|e| e.into_inner());
+        match slot.result.take() {
+            Some(result) => Poll::Ready(result),
+            None => {
+                slot.waker = Some(cx.waker().clone());
+                Poll::Pending
+            }
+        }
+    }
+}
+
+impl<T> std::fmt::Debug for VaultTask<T> {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        f.debug_struct("VaultTask")
+            .field("finished", &self.is_finished())
+            .finish()
+    }
+}
+
+fn take<T>(slot: &Mutex<Slot<T>>) -> Option<Result<T>> {
+    slot.lock().unwrap_or_else(|e| e.into_inner()).result.take()
+}
+
+#[cfg(test)]
+pub(crate) mod tests {
+    use super::*;
+    use std::sync::mpsc;
+    use std::task::Wake;
+    use std::thread::Thread;
+    use std::time::Duration;
+
+    /// 唤醒时恢复被挂起的线程
+    struct Unpark(Thread);
+
+    impl Wake for Unpark {
+        fn wake(self: Arc<Self>) {
+            self.0.unpark();
+        }
+    }
+
+    /// 在当前线程上等待 Future 完成（测试用的最小执行器）
+    pub(crate) fn block_on<F: Future>(future: F) -> F::Output {
+        let mut future = std::pin::pin!(future);
+        let waker = Waker::from(Arc::new(Unpark(std::thread::current())));
+        let mut cx = Context::from_waker(&waker);
+        loop {
+            if let Poll::Ready(output) = future.as_mut().poll(&mut cx) {
+                return output;
+            }
+            std::thread::park();
+        }
+    }
+
+    #[test]
+    fn test_join_and_await() {
+        let task = VaultTask::spawn(|| Ok(42));
+        assert_eq!(task.join().expect("操作失败"), 42);
+
+        // 工作线程等到测试线程先挂起后才完成，覆盖 Pending 后被唤醒的路径
+        let (resume_tx, resume_rx) = mpsc::channel::<()>();
+        let task = VaultTask::spawn(move || {
+            resume_rx.recv().expect("测试线程已退出");
+            Ok("done")
+        });
+        assert!(!task.is_finished());
+        std::thread::spawn(move || {
+            std::thread::sleep(Duration::from_millis(50));
+            resume_tx.send(()).expect("工作线程已退出");
+        });
+        assert_eq!(block_on(task).expect("操作失败"), "done");
+        println!("✅ 后台任务可阻塞等待或作为 Future 等待");
+    }
+
+    #[test]
+    fn test_worker_panic_is_reported() {
+        let task = VaultTask::<()>::spawn(|| panic!("模拟工作线程崩溃"));
+        assert!(matches!(task.join(), Err(AmberlockError::WorkerPanicked)));
+
+        let task = VaultTask::<()>::spawn(|| panic!("模拟工作线程崩溃"));
+        assert!(matches!(
+            block_on(task),
+            Err(AmberlockError::WorkerPanicked)
+        ));
+        println!("✅ 工作线程崩溃时返回 WorkerPanicked");
+    }
+}
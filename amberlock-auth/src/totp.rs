@@ -0,0 +1,316 @@
+//! TOTP 第二因素（RFC 6238）
+//!
+//! 保险库可附带一个 TOTP 密钥，解锁时除密码外还需输入验证器应用显示的验证码。
+//! 密钥保存在保险库明文中，随保险库一同加密，只有校验密码之后才能读取。
+//!
+//! # 规则
+//! - 算法为 HMAC-SHA256（RFC 6238 允许的变体），6 位验证码，30 秒一步
+//! - 校验时接受当前步及前后各 `TOTP_SKEW_STEPS` 步，容忍验证器与本机的时钟偏差
+//!
+//! # 注意
+//! - 验证器应用须支持 SHA256，添加时使用 `otpauth_uri` 生成的链接：链接中的 `algorithm`
+//!   参数为 `TOTP_ALGORITHM`。多数验证器省略该参数时按 SHA1 计算，手动输入 Base32 密钥时
+//!   须同时选择 SHA256，否则显示的验证码不会通过
+//! - 同一验证码在有效期内可重复使用
+
+use serde::{Deserialize, Serialize};
+use sha2::{Digest, Sha256};
+use zeroize::{Zeroize, Zeroizing};
+
+/// 每一步的时长（秒）
+pub const TOTP_STEP_SECS: u64 = 30;
+/// 验证码位数
+pub const TOTP_DIGITS: u32 = 6;
+/// `otpauth://` 链接中的算法参数（与 `hotp` 使用的 HMAC-SHA256 对应）
+pub const TOTP_ALGORITHM: &str = "SHA256";
+/// 校验时前后各容忍的步数
+pub const TOTP_SKEW_STEPS: u64 = 1;
+/// 新密钥的长度（字节，与 HMAC-SHA256 的输出等长）
+pub const TOTP_SECRET_LEN: usize = 32;
+/// 验证器应用中显示的签发方
+const ISSUER: &str = "AmberLock";
+/// HMAC-SHA256 的分组长度
+const HMAC_BLOCK_LEN: usize = 64;
+
+/// TOTP 密钥（释放时清零，`Debug` 不显示内容）
+#[derive(Clone, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(transparent)]
+pub struct TotpSecret(Vec<u8>);
+
+impl TotpSecret {
+    /// 生成新的随机密钥
+    pub fn generate() -> Self {
+        let mut bytes = vec![0u8; TOTP_SECRET_LEN];
+        rand::fill(&mut bytes[..]);
+        Self(bytes)
+    }
+
+    /// 以已有的密钥字节构造
+    pub fn from_bytes(bytes: &[u8]) -> Self {
+        Self(bytes.to_vec())
+    }
+
+    /// 密钥字节
+    pub fn as_bytes(&self) -> &[u8] {
+        &self.0
+    }
+
+    /// Base32 编码的密钥（RFC 4648，不带填充，供手动输入验证器应用）
+    pub fn to_base32(&self) -> String {
+        base32(&self.0)
+    }
+
+    /// 验证器应用可识别的 `otpauth://` 链接（通常生成二维码供扫描）
+    ///
+    /// # 参数
+    /// - `account`: 在验证器应用中显示的账户名
+    pub fn otpauth_uri(&self, account: &str) -> String {
+        format!(
+            "otpauth://totp/{issuer}:{account}?secret={secret}&issuer={issuer}&algorithm={algorithm}&digits={digits}&period={period}",
+            issuer = ISSUER,
+            account = percent_encode(account),
+            secret = self.to_base32(),
+            algorithm = TOTP_ALGORITHM,
+            digits = TOTP_DIGITS,
+            period = TOTP_STEP_SECS,
+        )
+    }
+
+    /// 指定时刻（Unix 秒）的验证码
+    pub fn code_at(&self, unix_secs: u64) -> String {
+        format_code(hotp(&self.0, unix_secs / TOTP_STEP_SECS, TOTP_DIGITS))
+    }
+
+    /// 校验指定时刻（Unix 秒）输入的验证码
+    ///
+    /// # 返回
+    /// 验证码是 `TOTP_DIGITS` 位数字，且与当前步或前后 `TOTP_SKEW_STEPS` 步之一相符
+    ///
+    /// # 注意
+    /// 各步依次比较，不提前返回，耗时与匹配的是哪一步无关
+    pub fn verify_at(&self, code: &str, unix_secs: u64) -> bool {
+        let code = code.trim();
+        if code.len() != TOTP_DIGITS as usize || !code.bytes().all(|b| b.is_ascii_digit()) {
+            return false;
+        }
+        let Ok(expected) = code.parse::<u32>() else {
+            return false;
+        };
+        let step = unix_secs / TOTP_STEP_SECS;
+        let first = step.saturating_sub(TOTP_SKEW_STEPS);
+        let mut matched = false;
+        for counter in first..=step + TOTP_SKEW_STEPS {
+            matched |= hotp(&self.0, counter, TOTP_DIGITS) == expected;
+        }
+        matched
+    }
+}
+
+impl Drop for TotpSecret {
+    fn drop(&mut self) {
+        self.0.zeroize();
+    }
+}
+
+impl std::fmt::Debug for TotpSecret {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        f.write_str("TotpSecret(..)")
+    }
+}
+
+/// HOTP（RFC 4226，以 HMAC-SHA256 代替 HMAC-SHA1）
+fn hotp(key: &[u8], counter: u64, digits: u32) -> u32 {
+    let mac = hmac_sha256(key, &counter.to_be_bytes());
+    let offset = usize::from(mac[mac.len() - 1] & 0x0f);
+    let truncated = u32::from_be_bytes(mac[offset..offset + 4].try_into().expect("偏移不超过 15"));
+    (truncated & 0x7fff_ffff) % 10u32.pow(digits)
+}
+
+/// HMAC-SHA256（RFC 2104）
+fn hmac_sha256(key: &[u8], message: &[u8]) -> [u8; 32] {
+    let mut block = Zeroizing::new([0u8; HMAC_BLOCK_LEN]);
+    if key.len() > HMAC_BLOCK_LEN {
+        block[..32].copy_from_slice(&Sha256::digest(key));
+    } else {
+        block[..key.len()].copy_from_slice(key);
+    }
+    let inner_pad = Zeroizing::new(block.map(|b| b ^ 0x36));
+    let outer_pad = Zeroizing::new(block.map(|b| b ^ 0x5c));
+
+    let inner = Sha256::new()
+        .chain_update(&inner_pad[..])
+        .chain_update(message)
+        .finalize();
+    Sha256::new()
+        .chain_update(&outer_pad[..])
+        .chain_update(inner)
+        .finalize()
+        .into()
+}
+
+/// 补零到 `TOTP_DIGITS` 位
+fn format_code(code: u32) -> String {
+    format!("{:0width$}", code, width = TOTP_DIGITS as usize)
+}
+
+/// Base32 编码（RFC 4648 字母表，不带填充）
+fn base32(bytes: &[u8]) -> String {
+    const ALPHABET: &[u8; 32] = b"ABCDEFGHIJKLMNOPQRSTUVWXYZ234567";
+    let mut out = String::with_capacity(bytes.len().div_ceil(5) * 8);
+    let mut buffer = 0u16;
+    let mut bits = 0;
+    for &byte in bytes {
+        buffer = (buffer << 8) | u16::from(byte);
+        bits += 8;
+        while bits >= 5 {
+            bits -= 5;
+            out.push(ALPHABET[usize::from((buffer >> bits) & 0x1f)] as char);
+        }
+    }
+    if bits > 0 {
+        out.push(ALPHABET[usize::from((buffer << (5 - bits)) & 0x1f)] as char);
+    }
+    out
+}
+
+/// 对 URI 路径中的账户名做百分号编码（保留 RFC 3986 的非保留字符）
+fn percent_encode(text: &str) -> String {
+    let mut out = String::with_capacity(text.len());
+    for byte in text.bytes() {
+        if byte.is_ascii_alphanumeric() || b"-._~".contains(&byte) {
+            out.push(byte as char);
+        } else {
+            out.push_str(&format!("%{:02X}", byte));
+        }
+    }
+    out
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn hex(bytes: &[u8]) -> String {
+        bytes.iter().map(|b| format!("{:02x}", b)).collect()
+    }
+
+    #[test]
+    fn test_hmac_sha256_rfc4231() {
+        // RFC 4231 测试用例 1、2 与 6（密钥长于分组，先做哈希）
+        let cases: [(&[u8], &[u8], &str); 3] = [
+            (
+                &[0x0b; 20],
+                b"Hi There",
+                "b0344c61d8db38535ca8afceaf0bf12b881dc200c9833da726e9376c2e32cff7",
+            ),
+            (
+                b"Jefe",
+                b"what do ya want for nothing?",
+                "5bdcc146bf60754e6a042426089575c75a003f089d2739839dec58b964ec3843",
+            ),
+            (
+                &[0xaa; 131],
+                b"Test Using Larger Than Block-Size Key - Hash Key First",
+                "60e431591ee0b67f0d8a26aacbf5b77f8e0bc6213728c5140546040f0ee37f54",
+            ),
+        ];
+        for (key, message, expected) in cases {
+            assert_eq!(hex(&hmac_sha256(key, message)), expected);
+        }
+        println!("✅ HMAC-SHA256 符合 RFC 4231");
+    }
+
+    #[test]
+    fn test_rfc6238_sha256_vectors() {
+        // RFC 6238 附录 B 中 SHA256 的测试向量（8 位）
+        let secret = TotpSecret::from_bytes(b"12345678901234567890123456789012");
+        let vectors = [
+            (59, 46119246),
+            (1111111109, 68084774),
+            (1111111111, 67062674),
+            (1234567890, 91819424),
+            (2000000000, 90698825),
+            (20000000000, 77737706),
+        ];
+        for (time, expected) in vectors {
+            assert_eq!(hotp(secret.as_bytes(), time / TOTP_STEP_SECS, 8), expected);
+            // 6 位验证码是同一截断值的低 6 位
+            assert_eq!(secret.code_at(time), format_code(expected % 1_000_000));
+        }
+        println!("✅ TOTP 符合 RFC 6238 测试向量");
+    }
+
+    #[test]
+    fn test_otpauth_uri_declares_algorithm() {
+        let secret = TotpSecret::from_bytes(b"12345678901234567890123456789012");
+        let uri = secret.otpauth_uri("admin");
+        let (_, query) = uri.split_once('?').expect("链接缺少参数");
+        let params: std::collections::HashMap<&str, &str> = query
+            .split('&')
+            .map(|pair| pair.split_once('=').expect("参数格式错误"))
+            .collect();
+        assert_eq!(params["algorithm"], TOTP_ALGORITHM);
+        assert_eq!(params["algorithm"], "SHA256");
+        assert_eq!(params["digits"], TOTP_DIGITS.to_string());
+        assert_eq!(params["period"], TOTP_STEP_SECS.to_string());
+        assert_eq!(params["secret"], secret.to_base32());
+
+        // 链接中的参数与实际计算一致：RFC 6238 附录 B 中 SHA256 的向量相符，SHA1 的不符
+        // （SHA1 向量以 20 字节种子计算，前 20 字节与 SHA256 种子相同）
+        assert_eq!(hotp(secret.as_bytes(), 59 / TOTP_STEP_SECS, 8), 46119246);
+        let sha1_seed = TotpSecret::from_bytes(b"12345678901234567890");
+        assert_ne!(hotp(sha1_seed.as_bytes(), 59 / TOTP_STEP_SECS, 8), 94287082);
+        println!("✅ otpauth 链接注明的算法与验证码计算一致");
+    }
+
+    #[test]
+    fn test_verify_window() {
+        let secret = TotpSecret::from_bytes(b"12345678901234567890123456789012");
+        let time = 1111111109;
+        let code = secret.code_at(time);
+        assert_eq!(code, "084774");
+
+        // 前后各一步之内有效
+        for offset in [-30i64, 0, 30] {
+            assert!(secret.verify_at(&code, time.saturating_add_signed(offset)));
+        }
+        assert!(secret.verify_at(" 084774 ", time));
+        // 超出窗口无效
+        for offset in [-60i64, 60] {
+            assert!(!secret.verify_at(&code, time.saturating_add_signed(offset)));
+        }
+        // 格式错误
+        for bad in ["", "84774", "0847740", "08477a", "+84774", "08 774"] {
+            assert!(!secret.verify_at(bad, time), "{:?} 应被拒绝", bad);
+        }
+        // 时间起点附近不下溢
+        assert!(secret.verify_at(&secret.code_at(0), 0));
+        println!("✅ 验证码在前后一步之内有效");
+    }
+
+    #[test]
+    fn test_encoding() {
+        // RFC 4648 的 Base32 测试向量（去掉填充）
+        for (plain, encoded) in [
+            ("", ""),
+            ("f", "MY"),
+            ("fo", "MZXQ"),
+            ("foo", "MZXW6"),
+            ("foob", "MZXW6YQ"),
+            ("fooba", "MZXW6YTB"),
+            ("foobar", "MZXW6YTBOI"),
+        ] {
+            assert_eq!(base32(plain.as_bytes()), encoded);
+        }
+
+        let secret = TotpSecret::from_bytes(b"foobar");
+        assert_eq!(
+            secret.otpauth_uri("张三 admin"),
+            "otpauth://totp/AmberLock:%E5%BC%A0%E4%B8%89%20admin?secret=MZXW6YTBOI&issuer=AmberLock&algorithm=SHA256&digits=6&period=30"
+        );
+        assert_eq!(format!("{:?}", secret), "TotpSecret(..)");
+        assert_eq!(TotpSecret::generate().as_bytes().len(), TOTP_SECRET_LEN);
+        assert_ne!(TotpSecret::generate(), TotpSecret::generate());
+        println!("✅ 密钥编码与 otpauth 链接");
+    }
+}
@@ -0,0 +1,2316 @@
+//! 保险库格式与凭据管理
+//!
+//! 本模块只处理保险库明文（DPAPI 解密之后的数据），不涉及任何 Win32 调用。
+//!
+//! # 格式版本
+//! - v1：单一密码 JSON `{ version, params, salt, hash }`
+//! - v3：命名凭据表 JSON `{ version, credentials: { name: { params, salt, hash } } }`
+//! - v4：v3 基础上增加 `created_at`/`last_changed_at`（RFC3339）与
+//!   `dpapi_scope`（加密时使用的 DPAPI 范围）
+//! - v5：带字段标签的二进制编码（见 `binary` 模块），内容与 v4 相同，末尾附 SHA-256
+//!   完整性校验，为当前写入的格式
+//!
+//! 版本号全局递增，JSON 与二进制格式不会共用同一个版本号。读取时先按前缀（`binary::MAGIC`）
+//! 区分二进制与 JSON，再按版本号分派；JSON 格式的保险库下次写入时自动升级为 v5。v1 映射为名为 `default` 的凭据；
+//! 旧版本缺少的时间戳保持为 None，缺少的 DPAPI 范围视为当前用户。
+//!
+//! # Argon2 参数
+//! 每个凭据的 `params` 字符串（`m=19456,t=2,p=1`）是校验密码时实际使用的参数。
+//! 读取时严格解析（只接受 m/t/p 三个键，数值须在 argon2 库允许的范围内；PHC 形式的
+//! `$argon2id$v=19$m=..,t=..,p=..` 同样接受，其他算法或版本一律拒绝），
+//! 并与结构化字段 `argon2` 及哈希长度交叉校验，不一致时返回
+//! `InconsistentVaultParams`，不会带着错误的元数据继续校验。新写入的凭据同时
+//! 写入结构化字段与参数字符串，旧版本仍可读取。
+//!
+//! 新凭据默认使用 `Argon2Config::default()`（即 `ARGON2_*` 常量）；部署时可通过
+//! `Argon2Config`（或 `calibrate` 按本机耗时选出的参数）改用其他参数，实际使用的
+//! 参数与输出长度随凭据一同保存，校验时按凭据自身的参数计算，不受默认值变化影响。
+//!
+//! # 密钥文件
+//! 标记了 `keyfile` 的凭据以密钥文件内容的 SHA-256 作为 Argon2 的密钥（secret 输入）
+//! 计算哈希，校验时须同时提供密码与同一个密钥文件。未提供密钥文件时按密码错误处理。
+//!
+//! # 角色
+//! 每个凭据带有角色（`Role`）。管理员可执行全部操作并管理凭据；操作员只读（如查看日志），
+//! 其密码不能用于添加、删除、重命名凭据或设置密码提示。旧版保险库中的凭据均为管理员，
+//! 保险库中至少保留一个管理员凭据。
+
+use crate::binary;
+use crate::dpapi::DpapiScope;
+use crate::lockout::LockoutState;
+use crate::recovery::RecoveryKey;
+use crate::totp::TotpSecret;
+use amberlock_types::{AmberlockError, Result};
+use argon2::{Algorithm, Argon2, Params, Version};
+use serde::de::IgnoredAny;
+use serde::{Deserialize, Serialize};
+use sha2::{Digest, Sha256};
+use std::collections::BTreeMap;
+use std::fmt;
+use std::time::{Duration, Instant};
+use time::OffsetDateTime;
+use time::format_description::well_known::Rfc3339;
+use zeroize::{Zeroize, Zeroizing};
+
+/// 单一密码格式版本
+pub const VAULT_VERSION_V1: u32 = 1;
+/// 命名凭据格式版本
+pub const VAULT_VERSION_V3: u32 = 3;
+/// 带时间戳的命名凭据格式版本
+pub const VAULT_VERSION_V4: u32 = 4;
+/// 二进制格式版本
+pub const VAULT_VERSION_V5: u32 = 5;
+/// 当前写入的格式版本
+pub const VAULT_VERSION: u32 = VAULT_VERSION_V5;
+
+/// 单一密码 API 对应的保留凭据名称
+pub const DEFAULT_CREDENTIAL: &str = "default";
+/// 恢复密钥哈希在错误信息与二进制编码中使用的名称（不在凭据表中）
+pub const RECOVERY_CREDENTIAL: &str = "recovery";
+/// 凭据名称最大长度（字符数）
+pub const MAX_CREDENTIAL_NAME_LEN: usize = 64;
+/// 密码提示最大长度（字符数）
+pub const MAX_HINT_LEN: usize = 64;
+
+/// Argon2id 内存开销（KiB）
+pub const ARGON2_M_COST: u32 = 19456;
+/// Argon2id 迭代次数
+pub const ARGON2_T_COST: u32 = 2;
+/// Argon2id 并行度
+pub const ARGON2_P_COST: u32 = 1;
+/// 哈希输出长度（字节）
+pub const HASH_LEN: usize = 32;
+/// 盐长度（字节）
+pub const SALT_LEN: usize = 16;
+
+/// 允许的最小内存开销（KiB，即 8 MiB）
+pub const MIN_MEM_KIB: u32 = 8 * 1024;
+/// 校准时尝试的最大内存开销（KiB，即 1 GiB）
+pub const MAX_CALIBRATE_MEM_KIB: u32 = 1024 * 1024;
+/// 校准时尝试的最大迭代次数
+pub const MAX_CALIBRATE_TIME_COST: u32 = 16;
+/// 允许的最小哈希输出长度（字节）
+pub const MIN_HASH_LEN: usize = 16;
+/// 允许的最大哈希输出长度（字节）
+pub const MAX_HASH_LEN: usize = 64;
+
+/// 结构化的 Argon2id 参数
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+pub struct Argon2Params {
+    /// 内存开销（KiB）
+    pub m_cost: u32,
+    /// 迭代次数
+    pub t_cost: u32,
+    /// 并行度
+    pub p_cost: u32,
+}
+
+impl Argon2Params {
+    /// 新凭据使用的参数
+    pub const CURRENT: Self = Self {
+        m_cost: ARGON2_M_COST,
+        t_cost: ARGON2_T_COST,
+        p_cost: ARGON2_P_COST,
+    };
+
+    /// 转换为 argon2 库的参数（同时校验取值范围）
+    fn to_argon2(self, output_len: usize) -> Result<Params> {
+        Params::new(self.m_cost, self.t_cost, self.p_cost, Some(output_len)).map_err(|e| {
+            AmberlockError::VaultCorrupted(format!("无效的 Argon2 参数 {}: {}", self, e))
+        })
+    }
+}
+
+impl std::fmt::Display for Argon2Params {
+    /// 旧版参数字符串格式（`m=19456,t=2,p=1`）
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        write!(f, "m={},t={},p={}", self.m_cost, self.t_cost, self.p_cost)
+    }
+}
+
+/// 创建凭据时使用的 Argon2id 配置
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct Argon2Config {
+    /// 内存开销（KiB，至少 `MIN_MEM_KIB`）
+    pub mem_kib: u32,
+    /// 迭代次数（至少 1）
+    pub time_cost: u32,
+    /// 并行度（至少 1）
+    pub parallelism: u32,
+    /// 哈希输出长度（字节，`MIN_HASH_LEN..=MAX_HASH_LEN`）
+    pub output_len: usize,
+}
+
+impl Default for Argon2Config {
+    fn default() -> Self {
+        Self {
+            mem_kib: ARGON2_M_COST,
+            time_cost: ARGON2_T_COST,
+            parallelism: ARGON2_P_COST,
+            output_len: HASH_LEN,
+        }
+    }
+}
+
+impl Argon2Config {
+    /// 对应的结构化参数（不含输出长度）
+    pub fn params(&self) -> Argon2Params {
+        Argon2Params {
+            m_cost: self.mem_kib,
+            t_cost: self.time_cost,
+            p_cost: self.parallelism,
+        }
+    }
+
+    /// 校验配置是否安全可用
+    ///
+    /// # 返回
+    /// - `Err(UnsafeArgon2Config)`: 内存低于 8 MiB、迭代次数或并行度为 0、
+    ///   输出长度越界，或超出 argon2 库允许的范围
+    pub fn validate(&self) -> Result<()> {
+        let unsafe_config = |reason: String| AmberlockError::UnsafeArgon2Config(reason);
+        if self.mem_kib < MIN_MEM_KIB {
+            return Err(unsafe_config(format!(
+                "内存开销 {} KiB 低于下限 {} KiB",
+                self.mem_kib, MIN_MEM_KIB
+            )));
+        }
+        if self.time_cost == 0 || self.parallelism == 0 {
+            return Err(unsafe_config("迭代次数与并行度至少为 1".to_string()));
+        }
+        if !(MIN_HASH_LEN..=MAX_HASH_LEN).contains(&self.output_len) {
+            return Err(unsafe_config(format!(
+                "输出长度 {} 字节不在 {}..={} 之间",
+                self.output_len, MIN_HASH_LEN, MAX_HASH_LEN
+            )));
+        }
+        Params::new(
+            self.mem_kib,
+            self.time_cost,
+            self.parallelism,
+            Some(self.output_len),
+        )
+        .map_err(|e| unsafe_config(format!("{}: {}", self.params(), e)))?;
+        Ok(())
+    }
+}
+
+impl Argon2Config {
+    /// 逐项取两者中更强的一方（升级参数时不降低任何一项）
+    pub fn max(&self, other: &Self) -> Self {
+        Self {
+            mem_kib: self.mem_kib.max(other.mem_kib),
+            time_cost: self.time_cost.max(other.time_cost),
+            parallelism: self.parallelism.max(other.parallelism),
+            output_len: self.output_len.max(other.output_len),
+        }
+    }
+}
+
+/// `default` 凭据的参数是否低于 `target`（凭据不存在时为 false）
+///
+/// 以参数字符串为准逐项比较内存、迭代次数、并行度与输出长度，任一项较低即需重新计算
+pub fn needs_rehash(vault: &VaultBlob, target: &Argon2Config) -> bool {
+    vault
+        .credentials
+        .get(DEFAULT_CREDENTIAL)
+        .is_some_and(|credential| credential.needs_rehash(target))
+}
+
+/// 测量本机后选出耗时接近 `target` 的参数
+///
+/// 从 `MIN_MEM_KIB`、单次迭代开始，先倍增内存（不超过 `MAX_CALIBRATE_MEM_KIB`），
+/// 再按单次迭代的耗时增加迭代次数（不超过 `MAX_CALIBRATE_TIME_COST`）。
+/// 本机过慢时返回允许的最小参数，不会低于下限
+pub fn calibrate(target: Duration) -> Argon2Config {
+    calibrate_with(target, measure)
+}
+
+/// 以给定的测量函数校准（测试中替换为模拟耗时）
+fn calibrate_with(
+    target: Duration,
+    mut measure: impl FnMut(&Argon2Config) -> Duration,
+) -> Argon2Config {
+    let mut config = Argon2Config {
+        mem_kib: MIN_MEM_KIB,
+        time_cost: 1,
+        parallelism: ARGON2_P_COST,
+        output_len: HASH_LEN,
+    };
+    let mut elapsed = measure(&config);
+    // 耗时与内存大致成正比：翻倍后仍不超过目标时继续翻倍
+    while elapsed * 2 <= target && config.mem_kib * 2 <= MAX_CALIBRATE_MEM_KIB {
+        config.mem_kib *= 2;
+        elapsed = measure(&config);
+    }
+    // 剩余的预算用迭代次数补足
+    if !elapsed.is_zero() {
+        let passes = target.as_secs_f64() / elapsed.as_secs_f64();
+        config.time_cost = (passes.floor() as u32).clamp(1, MAX_CALIBRATE_TIME_COST);
+    }
+    config
+}
+
+/// 以固定的密码与盐计算一次哈希，返回耗时
+fn measure(config: &Argon2Config) -> Duration {
+    let started = Instant::now();
+    let _ = hash_password(
+        "amberlock-calibration",
+        &[0u8; SALT_LEN],
+        None,
+        config.params(),
+        config.output_len,
+    );
+    started.elapsed()
+}
+
+/// 严格解析 Argon2 参数字符串（`m=..,t=..,p=..`，或 PHC 形式 `$argon2id$v=19$m=..,t=..,p=..`）
+///
+/// # 返回
+/// - `Ok(Argon2Params)`: 三个键齐全、无重复，且取值在 argon2 库允许的范围内
+/// - `Err(VaultCorrupted)`: 格式错误、未知键、重复键、缺少键或取值越界；
+///   PHC 形式的算法不是 Argon2id、版本不是 0x13，或带有盐与哈希
+pub fn parse_params(params: &str) -> Result<Argon2Params> {
+    let invalid = |reason: &str| {
+        AmberlockError::VaultCorrupted(format!("无效的 Argon2 参数 {:?}: {}", params, reason))
+    };
+
+    let fields = match params.strip_prefix('$') {
+        Some(phc) => {
+            let mut segments = phc.split('$');
+            let algorithm = segments.next().unwrap_or_default();
+            if algorithm.parse::<Algorithm>().ok() != Some(Algorithm::Argon2id) {
+                return Err(invalid(&format!(
+                    "不支持的算法 {:?}，只接受 {}",
+                    algorithm,
+                    Algorithm::Argon2id.as_str()
+                )));
+            }
+            let version = segments.next().unwrap_or_default();
+            if version != format!("v={}", u32::from(Version::V0x13)) {
+                return Err(invalid(&format!("不支持的版本 {:?}", version)));
+            }
+            let fields = segments.next().unwrap_or_default();
+            if segments.next().is_some() {
+                return Err(invalid("只能包含算法、版本与参数，盐与哈希单独保存"));
+            }
+            fields
+        }
+        None => params,
+    };
+
+    let mut m_cost = None;
+    let mut t_cost = None;
+    let mut p_cost = None;
+
+    for part in fields.split(',') {
+        let (key, value) = part.split_once('=').ok_or_else(|| invalid("缺少 '='"))?;
+        let slot = match key {
+            "m" => &mut m_cost,
+            "t" => &mut t_cost,
+            "p" => &mut p_cost,
+            other => return Err(invalid(&format!("未知的键 {:?}", other))),
+        };
+        if slot.is_some() {
+            return Err(invalid(&format!("重复的键 {:?}", key)));
+        }
+        let value: u32 = value
+            .parse()
+            .map_err(|_| invalid(&format!("{} 的取值不是整数", key)))?;
+        *slot = Some(value);
+    }
+
+    let (Some(m_cost), Some(t_cost), Some(p_cost)) = (m_cost, t_cost, p_cost) else {
+        return Err(invalid("缺少 m、t 或 p"));
+    };
+    let parsed = Argon2Params {
+        m_cost,
+        t_cost,
+        p_cost,
+    };
+    parsed.to_argon2(HASH_LEN)?;
+    Ok(parsed)
+}
+
+/// 解析参数字符串并与结构化参数比对（结构化参数缺失时以字符串为准）
+fn reconcile_params(
+    name: &str,
+    params: &str,
+    structured: Option<Argon2Params>,
+) -> Result<Argon2Params> {
+    let parsed = parse_params(params)?;
+    match structured {
+        Some(structured) if structured != parsed => Err(AmberlockError::InconsistentVaultParams {
+            name: name.to_string(),
+            reason: format!("结构化参数为 {}，参数字符串为 {}", structured, parsed),
+        }),
+        _ => Ok(parsed),
+    }
+}
+
+/// 凭据角色
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "lowercase")]
+pub enum Role {
+    /// 管理员：可执行全部操作，包括管理凭据
+    #[default]
+    Admin,
+    /// 操作员：只读，不能管理凭据
+    Operator,
+}
+
+impl Role {
+    /// 是否为管理员
+    pub fn is_admin(&self) -> bool {
+        *self == Role::Admin
+    }
+}
+
+impl fmt::Display for Role {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.write_str(match self {
+            Role::Admin => "管理员",
+            Role::Operator => "操作员",
+        })
+    }
+}
+
+/// 密码匹配到的凭据
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct SlotInfo {
+    /// 凭据名称
+    pub name: String,
+    /// 凭据角色
+    pub role: Role,
+}
+
+/// 单个凭据
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+pub struct Credential {
+    /// Argon2 参数字符串（如 `m=19456,t=2,p=1`，校验密码时使用）
+    pub params: String,
+    /// 结构化的 Argon2 参数（旧版保险库缺失，读取后补齐）
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub argon2: Option<Argon2Params>,
+    /// 哈希输出长度（字节，缺失时为 `HASH_LEN`）
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub output_len: Option<usize>,
+    /// 随机盐
+    pub salt: Vec<u8>,
+    /// Argon2id 哈希
+    pub hash: Vec<u8>,
+    /// 是否需要密钥文件（哈希以密钥文件摘要作为 Argon2 密钥计算）
+    #[serde(default, skip_serializing_if = "std::ops::Not::not")]
+    pub keyfile: bool,
+    /// 角色（旧版保险库缺失，视为管理员）
+    #[serde(default, skip_serializing_if = "Role::is_admin")]
+    pub role: Role,
+}
+
+impl Credential {
+    /// 使用默认参数和新随机盐为密码生成凭据
+    pub fn new(password: &str) -> Result<Self> {
+        Self::with_config(password, &Argon2Config::default())
+    }
+
+    /// 使用指定参数和新随机盐为密码生成凭据
+    ///
+    /// # 返回
+    /// - `Err(UnsafeArgon2Config)`: 配置未通过 `Argon2Config::validate`
+    pub fn with_config(password: &str, config: &Argon2Config) -> Result<Self> {
+        Self::build(password, None, config)
+    }
+
+    /// 使用指定参数和新随机盐为“密码 + 密钥文件”生成凭据
+    ///
+    /// # 返回
+    /// - `Err(InvalidKeyfile)`: 密钥文件为空
+    /// - `Err(UnsafeArgon2Config)`: 配置未通过 `Argon2Config::validate`
+    pub fn with_keyfile(password: &str, keyfile: &[u8], config: &Argon2Config) -> Result<Self> {
+        if keyfile.is_empty() {
+            return Err(AmberlockError::InvalidKeyfile("密钥文件为空".to_string()));
+        }
+        Self::build(password, Some(&keyfile_digest(keyfile)[..]), config)
+    }
+
+    fn build(password: &str, secret: Option<&[u8]>, config: &Argon2Config) -> Result<Self> {
+        config.validate()?;
+        let mut salt = vec![0u8; SALT_LEN];
+        rand::fill(&mut salt[..]);
+
+        let params = config.params();
+        let hash = hash_password(password, &salt, secret, params, config.output_len)?;
+
+        Ok(Self {
+            params: params.to_string(),
+            argon2: Some(params),
+            output_len: Some(config.output_len),
+            salt,
+            hash,
+            keyfile: secret.is_some(),
+            role: Role::Admin,
+        })
+    }
+
+    /// 改为指定角色
+    pub fn with_role(mut self, role: Role) -> Self {
+        self.role = role;
+        self
+    }
+
+    /// 凭据创建时使用的配置（旧凭据的输出长度按 `HASH_LEN`）
+    pub fn config(&self) -> Result<Argon2Config> {
+        let params = parse_params(&self.params)?;
+        Ok(Argon2Config {
+            mem_kib: params.m_cost,
+            time_cost: params.t_cost,
+            parallelism: params.p_cost,
+            output_len: self.output_len.unwrap_or(HASH_LEN),
+        })
+    }
+
+    /// 参数是否低于 `target`（参数字符串无效时为 false，校验时另行报错）
+    pub fn needs_rehash(&self, target: &Argon2Config) -> bool {
+        let Ok(params) = parse_params(&self.params) else {
+            return false;
+        };
+        params.m_cost < target.mem_kib
+            || params.t_cost < target.time_cost
+            || params.p_cost < target.parallelism
+            || self.output_len.unwrap_or(HASH_LEN) < target.output_len
+    }
+
+    /// 校验密码（常量时间比较）
+    ///
+    /// 需要密钥文件的凭据在此返回 `Ok(false)`，须改用 `verify_with_keyfile`
+    pub fn verify(&self, password: &str) -> Result<bool> {
+        self.verify_with_keyfile(password, None)
+    }
+
+    /// 校验密码与密钥文件（常量时间比较）
+    ///
+    /// # 返回
+    /// - `Ok(false)`: 密码不匹配，或凭据需要密钥文件而 `keyfile` 为 None、密钥文件不一致
+    ///
+    /// # 注意
+    /// 凭据不需要密钥文件时忽略 `keyfile`
+    pub fn verify_with_keyfile(&self, password: &str, keyfile: Option<&[u8]>) -> Result<bool> {
+        let digest = match (self.keyfile, keyfile) {
+            (true, None) => return Ok(false),
+            (true, Some(keyfile)) => Some(keyfile_digest(keyfile)),
+            (false, _) => None,
+        };
+        let computed = Zeroizing::new(hash_password(
+            password,
+            &self.salt,
+            digest.as_ref().map(|d| &d[..]),
+            parse_params(&self.params)?,
+            self.output_len.unwrap_or(HASH_LEN),
+        )?);
+        Ok(constant_time_eq(&computed, &self.hash))
+    }
+
+    /// 校验并返回凭据实际使用的 Argon2 参数
+    ///
+    /// # 返回
+    /// - `Err(VaultCorrupted)`: 参数字符串无效
+    /// - `Err(InconsistentVaultParams)`: 结构化参数与参数字符串不一致，或哈希长度与记录的输出长度不符
+    pub fn validated_params(&self, name: &str) -> Result<Argon2Params> {
+        let params = reconcile_params(name, &self.params, self.argon2)?;
+        let output_len = self.output_len.unwrap_or(HASH_LEN);
+        if !(MIN_HASH_LEN..=MAX_HASH_LEN).contains(&output_len) || self.hash.len() != output_len {
+            return Err(AmberlockError::InconsistentVaultParams {
+                name: name.to_string(),
+                reason: format!(
+                    "哈希长度为 {} 字节，应为 {} 字节",
+                    self.hash.len(),
+                    output_len
+                ),
+            });
+        }
+        Ok(params)
+    }
+}
+
+impl Drop for Credential {
+    /// 盐与哈希随凭据释放一同清零（包括修改密码时被替换的旧凭据）
+    fn drop(&mut self) {
+        self.salt.zeroize();
+        self.hash.zeroize();
+    }
+}
+
+/// 保险库明文（v3/v4）
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+pub struct VaultBlob {
+    /// 格式版本
+    pub version: u32,
+    /// 命名凭据表（按名称排序）
+    pub credentials: BTreeMap<String, Credential>,
+    /// 创建时间（RFC3339，v4 之前的保险库为 None）
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub created_at: Option<String>,
+    /// 最近一次修改凭据的时间（RFC3339，v4 之前的保险库为 None）
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub last_changed_at: Option<String>,
+    /// 加密保险库时使用的 DPAPI 范围（写回时沿用）
+    #[serde(default)]
+    pub dpapi_scope: DpapiScope,
+    /// 连续失败次数与最近失败时间（见 `lockout` 模块）
+    #[serde(default, skip_serializing_if = "LockoutState::is_clear")]
+    pub lockout: LockoutState,
+    /// 恢复密钥的哈希（未设置时为 None，见 `recovery` 模块）
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub recovery: Option<Credential>,
+    /// `default` 凭据密码最近一次设置的时间（RFC3339，早于此字段的保险库为 None）
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub password_changed_at: Option<String>,
+    /// 密码提示（明文保存，无需密码即可读取）
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub hint: Option<String>,
+    /// TOTP 第二因素的密钥（未启用时为 None，见 `totp` 模块）
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub totp: Option<TotpSecret>,
+}
+
+/// 保险库元数据（不含任何盐或哈希）
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct VaultMetadata {
+    /// 存储的格式版本（未升级的旧保险库保留原版本号）
+    pub version: u32,
+    /// 创建时间
+    pub created_at: Option<String>,
+    /// 最近修改时间
+    pub last_changed_at: Option<String>,
+    /// 创建时选择的 DPAPI 范围
+    pub dpapi_scope: DpapiScope,
+    /// 各凭据已校验的 Argon2 参数（凭据名称 → 参数，即校验密码时实际使用的参数）
+    pub params: BTreeMap<String, Argon2Params>,
+    /// 连续失败次数与最近失败时间
+    pub lockout: LockoutState,
+    /// `default` 凭据密码最近一次设置的时间
+    pub password_changed_at: Option<String>,
+    /// 密码提示
+    pub hint: Option<String>,
+    /// 是否启用了 TOTP 第二因素
+    pub totp_enabled: bool,
+}
+
+/// `get_vault_info` 返回的保险库信息（即 `VaultMetadata`）
+pub type VaultInfo = VaultMetadata;
+
+impl VaultMetadata {
+    /// 从保险库明文中只提取元数据
+    ///
+    /// # 注意
+    /// 反序列化目标不包含盐与哈希字段，这些字段被直接跳过，不会被复制出来
+    pub fn from_plain(plain: &[u8]) -> Result<Self> {
+        if binary::is_binary(plain) {
+            let vault = binary::decode(plain, false)?;
+            let params = vault
+                .credentials
+                .iter()
+                .map(|(name, c)| Ok((name.clone(), reconcile_params(name, &c.params, c.argon2)?)))
+                .collect::<Result<_>>()?;
+            return Ok(Self {
+                version: vault.version,
+                created_at: vault.created_at.clone(),
+                last_changed_at: vault.last_changed_at.clone(),
+                dpapi_scope: vault.dpapi_scope,
+                params,
+                lockout: vault.lockout,
+                password_changed_at: vault.password_changed_at,
+                hint: vault.hint,
+                totp_enabled: vault.totp.is_some(),
+            });
+        }
+
+        #[derive(Deserialize)]
+        struct ParamsOnly {
+            params: String,
+            #[serde(default)]
+            argon2: Option<Argon2Params>,
+        }
+
+        #[derive(Deserialize)]
+        struct MetadataProbe {
+            version: u32,
+            #[serde(default)]
+            params: Option<String>,
+            #[serde(default)]
+            credentials: BTreeMap<String, ParamsOnly>,
+            #[serde(default)]
+            created_at: Option<String>,
+            #[serde(default)]
+            last_changed_at: Option<String>,
+            #[serde(default)]
+            dpapi_scope: DpapiScope,
+            #[serde(default)]
+            lockout: LockoutState,
+            #[serde(default)]
+            password_changed_at: Option<String>,
+            #[serde(default)]
+            hint: Option<String>,
+            #[serde(default)]
+            totp: Option<IgnoredAny>,
+        }
+
+        let probe: MetadataProbe = serde_json::from_slice(plain)
+            .map_err(|e| AmberlockError::VaultCorrupted(e.to_string()))?;
+
+        let params = match probe.version {
+            VAULT_VERSION_V1 => {
+                let params = probe.params.ok_or_else(|| {
+                    AmberlockError::VaultCorrupted("缺少 Argon2 参数".to_string())
+                })?;
+                let params = reconcile_params(DEFAULT_CREDENTIAL, &params, None)?;
+                BTreeMap::from([(DEFAULT_CREDENTIAL.to_string(), params)])
+            }
+            VAULT_VERSION_V3 | VAULT_VERSION_V4 => probe
+                .credentials
+                .into_iter()
+                .map(|(name, c)| {
+                    let params = reconcile_params(&name, &c.params, c.argon2)?;
+                    Ok((name, params))
+                })
+                .collect::<Result<_>>()?,
+            other => {
+                return Err(AmberlockError::VaultCorrupted(format!(
+                    "不支持的保险库版本: {}",
+                    other
+                )));
+            }
+        };
+
+        Ok(Self {
+            version: probe.version,
+            created_at: probe.created_at,
+            last_changed_at: probe.last_changed_at,
+            dpapi_scope: probe.dpapi_scope,
+            params,
+            lockout: probe.lockout,
+            password_changed_at: probe.password_changed_at,
+            hint: probe.hint,
+            totp_enabled: probe.totp.is_some(),
+        })
+    }
+}
+
+/// v1 单一密码格式（只读，用于迁移）
+#[derive(Debug, Deserialize)]
+struct LegacyVaultBlob {
+    params: String,
+    salt: Vec<u8>,
+    hash: Vec<u8>,
+}
+
+/// 仅用于读取版本号
+#[derive(Deserialize)]
+struct VersionProbe {
+    version: u32,
+}
+
+impl VaultBlob {
+    /// 创建只含 `default` 凭据的保险库
+    pub fn with_default(password: &str) -> Result<Self> {
+        Self::with_default_config(password, &Argon2Config::default())
+    }
+
+    /// 创建只含 `default` 凭据的保险库，凭据使用指定的 Argon2 配置
+    pub fn with_default_config(password: &str, config: &Argon2Config) -> Result<Self> {
+        Ok(Self::with_default_credential(Credential::with_config(
+            password, config,
+        )?))
+    }
+
+    /// 创建只含 `default` 凭据的保险库，凭据需要密码与密钥文件
+    ///
+    /// # 返回
+    /// - `Err(InvalidKeyfile)`: 密钥文件为空
+    pub fn with_default_keyfile(password: &str, keyfile: &[u8]) -> Result<Self> {
+        Ok(Self::with_default_credential(Credential::with_keyfile(
+            password,
+            keyfile,
+            &Argon2Config::default(),
+        )?))
+    }
+
+    fn with_default_credential(credential: Credential) -> Self {
+        let mut credentials = BTreeMap::new();
+        credentials.insert(DEFAULT_CREDENTIAL.to_string(), credential);
+        let now = now_rfc3339();
+        Self {
+            version: VAULT_VERSION,
+            credentials,
+            created_at: Some(now.clone()),
+            last_changed_at: Some(now.clone()),
+            dpapi_scope: DpapiScope::CurrentUser,
+            lockout: LockoutState::default(),
+            recovery: None,
+            password_changed_at: Some(now),
+            hint: None,
+            totp: None,
+        }
+    }
+
+    /// 解析保险库明文（支持 v5 二进制与 v1、v3/v4 JSON）
+    ///
+    /// 每个凭据的 Argon2 参数都经过校验（见 `Credential::validated_params`），
+    /// 缺失的结构化参数在此补齐，下次写入时一并保存
+    pub fn decode(plain: &[u8]) -> Result<Self> {
+        let mut blob = Self::decode_unchecked(plain)?;
+        for (name, credential) in blob.credentials.iter_mut() {
+            credential.argon2 = Some(credential.validated_params(name)?);
+        }
+        if let Some(recovery) = blob.recovery.as_mut() {
+            recovery.argon2 = Some(recovery.validated_params(RECOVERY_CREDENTIAL)?);
+        }
+        Ok(blob)
+    }
+
+    /// 按格式与版本解析明文（不校验参数）
+    pub(crate) fn decode_unchecked(plain: &[u8]) -> Result<Self> {
+        if binary::is_binary(plain) {
+            return binary::decode(plain, true);
+        }
+        let probe: VersionProbe = serde_json::from_slice(plain)
+            .map_err(|e| AmberlockError::VaultCorrupted(e.to_string()))?;
+
+        match probe.version {
+            VAULT_VERSION_V1 => {
+                let legacy: LegacyVaultBlob = serde_json::from_slice(plain)
+                    .map_err(|e| AmberlockError::VaultCorrupted(e.to_string()))?;
+                let mut credentials = BTreeMap::new();
+                credentials.insert(
+                    DEFAULT_CREDENTIAL.to_string(),
+                    Credential {
+                        params: legacy.params,
+                        argon2: None,
+                        output_len: None,
+                        salt: legacy.salt,
+                        hash: legacy.hash,
+                        keyfile: false,
+                        role: Role::Admin,
+                    },
+                );
+                Ok(Self {
+                    version: VAULT_VERSION,
+                    credentials,
+                    created_at: None,
+                    last_changed_at: None,
+                    dpapi_scope: DpapiScope::CurrentUser,
+                    lockout: LockoutState::default(),
+                    recovery: None,
+                    password_changed_at: None,
+                    hint: None,
+                    totp: None,
+                })
+            }
+            VAULT_VERSION_V3 | VAULT_VERSION_V4 => {
+                let blob: VaultBlob = serde_json::from_slice(plain)
+                    .map_err(|e| AmberlockError::VaultCorrupted(e.to_string()))?;
+                if blob.credentials.is_empty() {
+                    return Err(AmberlockError::VaultCorrupted("凭据表为空".to_string()));
+                }
+                Ok(blob)
+            }
+            other => Err(AmberlockError::VaultCorrupted(format!(
+                "不支持的保险库版本: {}",
+                other
+            ))),
+        }
+    }
+
+    /// 序列化为明文（始终写入当前版本，即 v5 二进制格式）
+    pub fn encode(&self) -> Result<Vec<u8>> {
+        binary::encode(self)
+    }
+
+    /// 凭据名称列表（不暴露哈希）
+    pub fn names(&self) -> Vec<String> {
+        self.credentials.keys().cloned().collect()
+    }
+
+    /// 校验指定凭据的密码
+    ///
+    /// # 返回
+    /// - `Ok(true/false)`: 密码是否匹配
+    /// - `Err(CredentialNotFound)`: 凭据不存在
+    pub fn verify(&self, name: &str, password: &str) -> Result<bool> {
+        self.credentials
+            .get(name)
+            .ok_or_else(|| AmberlockError::CredentialNotFound(name.to_string()))?
+            .verify(password)
+    }
+
+    /// 校验指定凭据的密码与密钥文件
+    ///
+    /// # 返回
+    /// - `Ok(true/false)`: 是否匹配（需要密钥文件而未提供时为 false）
+    /// - `Err(CredentialNotFound)`: 凭据不存在
+    pub fn verify_with_keyfile(
+        &self,
+        name: &str,
+        password: &str,
+        keyfile: Option<&[u8]>,
+    ) -> Result<bool> {
+        self.credentials
+            .get(name)
+            .ok_or_else(|| AmberlockError::CredentialNotFound(name.to_string()))?
+            .verify_with_keyfile(password, keyfile)
+    }
+
+    /// 只凭密码校验指定凭据并更新失败计数（见 `verify_tracked_with_code`）
+    ///
+    /// 启用 TOTP 的保险库在此一律按失败处理，须改用 `verify_tracked_with_code`
+    pub fn verify_tracked(
+        &mut self,
+        name: &str,
+        password: &str,
+        now: OffsetDateTime,
+    ) -> Result<bool> {
+        self.verify_tracked_with_code(name, password, None, now)
+    }
+
+    /// 校验指定凭据的密码与 TOTP 验证码并更新失败计数
+    ///
+    /// 锁定期间直接拒绝（不计入失败次数）；校验失败时记录失败，通过时清零
+    ///
+    /// # 参数
+    /// - `code`: 启用 TOTP 时须为 `now` 时刻有效的验证码，为 None 时按失败处理
+    ///   （与需要密钥文件的凭据未提供密钥文件相同）；未启用 TOTP 时忽略
+    ///
+    /// # 返回
+    /// - `Ok(true/false)`: 密码（与验证码）是否匹配
+    /// - `Err(LockedOut)`: 仍在锁定中
+    /// - `Err(CredentialNotFound)`: 凭据不存在
+    pub fn verify_tracked_with_code(
+        &mut self,
+        name: &str,
+        password: &str,
+        code: Option<&str>,
+        now: OffsetDateTime,
+    ) -> Result<bool> {
+        self.check_tracked(code, now, |vault| vault.verify(name, password))
+    }
+
+    /// 锁定、密码与验证码的共用校验流程
+    ///
+    /// 锁定期间直接拒绝（不计入失败次数）；`password_ok` 为 false 或验证码错误时记录失败，
+    /// 均通过时清零
+    fn check_tracked(
+        &mut self,
+        code: Option<&str>,
+        now: OffsetDateTime,
+        password_ok: impl FnOnce(&Self) -> Result<bool>,
+    ) -> Result<bool> {
+        let info = self.lockout.info(now);
+        if info.is_locked() {
+            return Err(AmberlockError::LockedOut {
+                remaining_secs: info.remaining_secs(),
+            });
+        }
+        let matched = password_ok(self)? && self.verify_code(code, now);
+        if matched {
+            self.lockout.reset();
+        } else {
+            self.lockout.record_failure(now);
+        }
+        Ok(matched)
+    }
+
+    /// 添加管理员凭据
+    ///
+    /// # 参数
+    /// - `admin_password`: 任一管理员凭据的密码
+    /// - `code`: 启用 TOTP 时的验证码（见 `authorize`）
+    /// - `name`: 新凭据名称
+    /// - `password`: 新凭据密码
+    pub fn add_credential(
+        &mut self,
+        admin_password: &str,
+        code: Option<&str>,
+        name: &str,
+        password: &str,
+    ) -> Result<()> {
+        self.add_slot(admin_password, code, name, password, Role::Admin)
+    }
+
+    /// 添加指定角色的凭据
+    ///
+    /// # 参数
+    /// - `admin_password`: 任一管理员凭据的密码
+    /// - `code`: 启用 TOTP 时的验证码（见 `authorize`）
+    /// - `name`: 新凭据名称
+    /// - `password`: 新凭据密码
+    /// - `role`: 新凭据的角色
+    pub fn add_slot(
+        &mut self,
+        admin_password: &str,
+        code: Option<&str>,
+        name: &str,
+        password: &str,
+        role: Role,
+    ) -> Result<()> {
+        self.authorize(admin_password, code)?;
+        validate_name(name)?;
+        if self.credentials.contains_key(name) {
+            return Err(AmberlockError::CredentialExists(name.to_string()));
+        }
+        self.credentials
+            .insert(name.to_string(), Credential::new(password)?.with_role(role));
+        self.touch();
+        Ok(())
+    }
+
+    /// 删除凭据（拒绝删除最后一个凭据或最后一个管理员凭据；授权见 `authorize`）
+    pub fn remove_credential(
+        &mut self,
+        admin_password: &str,
+        code: Option<&str>,
+        name: &str,
+    ) -> Result<()> {
+        self.authorize(admin_password, code)?;
+        let credential = self
+            .credentials
+            .get(name)
+            .ok_or_else(|| AmberlockError::CredentialNotFound(name.to_string()))?;
+        if self.credentials.len() == 1 {
+            return Err(AmberlockError::LastCredential);
+        }
+        let admins = self
+            .credentials
+            .values()
+            .filter(|c| c.role.is_admin())
+            .count();
+        if credential.role.is_admin() && admins == 1 {
+            return Err(AmberlockError::LastAdmin);
+        }
+        self.credentials.remove(name);
+        self.touch();
+        Ok(())
+    }
+
+    /// 重命名凭据（保留原有哈希与盐；授权见 `authorize`）
+    pub fn rename_credential(
+        &mut self,
+        admin_password: &str,
+        code: Option<&str>,
+        old_name: &str,
+        new_name: &str,
+    ) -> Result<()> {
+        self.authorize(admin_password, code)?;
+        validate_name(new_name)?;
+        if self.credentials.contains_key(new_name) {
+            return Err(AmberlockError::CredentialExists(new_name.to_string()));
+        }
+        let credential = self
+            .credentials
+            .remove(old_name)
+            .ok_or_else(|| AmberlockError::CredentialNotFound(old_name.to_string()))?;
+        self.credentials.insert(new_name.to_string(), credential);
+        self.touch();
+        Ok(())
+    }
+
+    /// 修改指定凭据的密码（沿用该凭据的 Argon2 配置与角色，以新的随机盐重新计算哈希）
+    ///
+    /// 原密码与验证码的校验与 `verify_tracked_with_code` 相同：检查锁定，失败时记录失败次数
+    ///
+    /// # 返回
+    /// - `Err(WrongPassword)`: 原密码与该凭据不匹配，或验证码错误（已记录失败）
+    /// - `Err(LockedOut)`: 仍在锁定中
+    /// - `Err(CredentialNotFound)`: 凭据不存在
+    pub fn change_password(
+        &mut self,
+        name: &str,
+        old_password: &str,
+        code: Option<&str>,
+        new_password: &str,
+    ) -> Result<()> {
+        if !self.verify_tracked_with_code(name, old_password, code, OffsetDateTime::now_utc())? {
+            return Err(AmberlockError::WrongPassword);
+        }
+        let credential = &self.credentials[name];
+        let (config, role) = (credential.config()?, credential.role);
+        self.credentials.insert(
+            name.to_string(),
+            Credential::with_config(new_password, &config)?.with_role(role),
+        );
+        self.touch();
+        if name == DEFAULT_CREDENTIAL {
+            self.password_changed_at = self.last_changed_at.clone();
+        }
+        Ok(())
+    }
+
+    /// 设置或清除密码提示
+    ///
+    /// # 参数
+    /// - `admin_password`: 任一管理员凭据的密码
+    /// - `code`: 启用 TOTP 时的验证码（见 `authorize`）
+    /// - `hint`: 新的提示；None 或只含空白时清除
+    ///
+    /// # 返回
+    /// - `Err(InvalidHint)`: 超过 `MAX_HINT_LEN` 个字符、含有控制字符或包含密码本身
+    /// - `Err(WrongPassword)`: 密码与所有管理员凭据都不匹配，或验证码错误
+    pub fn set_hint(
+        &mut self,
+        admin_password: &str,
+        code: Option<&str>,
+        hint: Option<&str>,
+    ) -> Result<()> {
+        self.authorize(admin_password, code)?;
+        let hint = hint.map(str::trim).filter(|hint| !hint.is_empty());
+        if let Some(hint) = hint {
+            validate_hint(hint, admin_password)?;
+        }
+        self.hint = hint.map(str::to_string);
+        self.touch();
+        Ok(())
+    }
+
+    /// 启用 TOTP 第二因素（已启用时换用新的密钥）
+    ///
+    /// # 参数
+    /// - `admin_password`: 任一管理员凭据的密码
+    /// - `code`: 已启用时须为旧密钥的当前验证码，否则只知道密码就能换成自己掌握的密钥
+    ///
+    /// # 返回
+    /// - `Ok(secret)`: 新生成的密钥，调用方应立即展示给用户添加到验证器应用
+    /// - `Err(WrongPassword)`: 密码与所有管理员凭据都不匹配，或验证码错误
+    pub fn enable_totp(&mut self, admin_password: &str, code: Option<&str>) -> Result<TotpSecret> {
+        self.authorize(admin_password, code)?;
+        let secret = TotpSecret::generate();
+        self.totp = Some(secret.clone());
+        self.touch();
+        Ok(secret)
+    }
+
+    /// 第二因素是否满足：未启用 TOTP 时总是满足，启用时须提供 `now` 时刻有效的验证码
+    pub fn verify_code(&self, code: Option<&str>, now: OffsetDateTime) -> bool {
+        match &self.totp {
+            None => true,
+            Some(secret) => {
+                code.is_some_and(|code| secret.verify_at(code, now.unix_timestamp().max(0) as u64))
+            }
+        }
+    }
+
+    /// 停用 TOTP 第二因素（未启用时不做任何修改）
+    ///
+    /// # 参数
+    /// - `code`: 当前验证码；只凭密码不能停用第二因素
+    ///
+    /// # 返回
+    /// - `Err(WrongPassword)`: 密码与所有管理员凭据都不匹配，或验证码错误
+    pub fn disable_totp(&mut self, admin_password: &str, code: Option<&str>) -> Result<()> {
+        self.authorize(admin_password, code)?;
+        if self.totp.take().is_some() {
+            self.touch();
+        }
+        Ok(())
+    }
+
+    /// 参数低于 `target` 时以新的随机盐重新计算指定凭据的哈希
+    ///
+    /// 新参数逐项取原参数与 `target` 中较强的一方；密码不变，`last_changed_at` 不变
+    ///
+    /// # 返回
+    /// - `Ok(true)`: 已升级
+    /// - `Ok(false)`: 参数已达到 `target`，或凭据需要密钥文件（无法仅凭密码重新计算）
+    /// - `Err(WrongPassword)`: 密码与该凭据不匹配
+    /// - `Err(CredentialNotFound)`: 凭据不存在
+    pub fn upgrade_credential(
+        &mut self,
+        name: &str,
+        password: &str,
+        target: &Argon2Config,
+    ) -> Result<bool> {
+        let credential = self
+            .credentials
+            .get(name)
+            .ok_or_else(|| AmberlockError::CredentialNotFound(name.to_string()))?;
+        if credential.keyfile || !credential.needs_rehash(target) {
+            return Ok(false);
+        }
+        if !credential.verify(password)? {
+            return Err(AmberlockError::WrongPassword);
+        }
+        let config = credential.config()?.max(target);
+        let role = credential.role;
+        self.credentials.insert(
+            name.to_string(),
+            Credential::with_config(password, &config)?.with_role(role),
+        );
+        Ok(true)
+    }
+
+    /// 生成新的恢复密钥并保存其哈希（替换已有的恢复密钥）
+    ///
+    /// 哈希使用与 `default` 凭据相同的 Argon2 配置；返回的密钥须交给用户保管，
+    /// 保险库中不保存密钥本身
+    pub fn set_recovery(&mut self) -> Result<RecoveryKey> {
+        let key = RecoveryKey::generate();
+        let config = self.default_config()?;
+        self.recovery = Some(Credential::with_config(&key.canonical(), &config)?);
+        self.touch();
+        Ok(key)
+    }
+
+    /// 校验恢复密钥（常量时间比较）
+    ///
+    /// # 返回
+    /// - `Ok(true/false)`: 恢复密钥是否匹配
+    /// - `Err(RecoveryNotConfigured)`: 保险库未设置恢复密钥
+    pub fn verify_recovery(&self, key: &RecoveryKey) -> Result<bool> {
+        self.recovery
+            .as_ref()
+            .ok_or(AmberlockError::RecoveryNotConfigured)?
+            .verify(&key.canonical())
+    }
+
+    /// 凭恢复密钥重设 `default` 凭据的密码
+    ///
+    /// 新密码沿用原凭据的 Argon2 配置并使用新的随机盐；失败计数清零，恢复密钥保持不变
+    ///
+    /// # 参数
+    /// - `keyfile`: 新凭据使用的密钥文件；原凭据需要密钥文件时必须提供（可以换用新的密钥文件）
+    ///
+    /// # 返回
+    /// - `Err(InvalidKeyfile)`: 原凭据需要密钥文件而未提供，或密钥文件为空
+    /// - `Err(WrongRecoveryKey)`: 恢复密钥不匹配
+    /// - `Err(RecoveryNotConfigured)`: 保险库未设置恢复密钥
+    pub fn reset_password_with_recovery(
+        &mut self,
+        key: &RecoveryKey,
+        new_password: &str,
+        keyfile: Option<&[u8]>,
+    ) -> Result<()> {
+        let (role, requires_keyfile) = self
+            .credentials
+            .get(DEFAULT_CREDENTIAL)
+            .map_or((Role::Admin, false), |credential| {
+                (credential.role, credential.keyfile)
+            });
+        // 只换密码不换密钥文件：否则恢复密钥会顺带去掉第二因素
+        if requires_keyfile && keyfile.is_none() {
+            return Err(AmberlockError::InvalidKeyfile(
+                "该凭据需要密钥文件，重设密码时须一并提供".to_string(),
+            ));
+        }
+        if !self.verify_recovery(key)? {
+            return Err(AmberlockError::WrongRecoveryKey);
+        }
+        let config = self.default_config()?;
+        let credential = match keyfile {
+            Some(keyfile) => Credential::with_keyfile(new_password, keyfile, &config)?,
+            None => Credential::with_config(new_password, &config)?,
+        };
+        self.credentials
+            .insert(DEFAULT_CREDENTIAL.to_string(), credential.with_role(role));
+        self.lockout.reset();
+        self.touch();
+        self.password_changed_at = self.last_changed_at.clone();
+        Ok(())
+    }
+
+    /// `default` 凭据的 Argon2 配置（凭据不存在时为默认配置）
+    fn default_config(&self) -> Result<Argon2Config> {
+        self.credentials
+            .get(DEFAULT_CREDENTIAL)
+            .map_or_else(|| Ok(Argon2Config::default()), Credential::config)
+    }
+
+    /// 更新修改时间（创建时间保持不变）
+    fn touch(&mut self) {
+        self.last_changed_at = Some(now_rfc3339());
+    }
+
+    /// 识别密码属于哪个凭据
+    ///
+    /// 按名称顺序逐一校验全部凭据，匹配后不提前结束，耗时与匹配到哪个凭据无关；
+    /// 多个凭据使用同一密码时返回名称最靠前的一个。需要密钥文件的凭据不参与匹配
+    ///
+    /// # 返回
+    /// - `Ok(Some(slot))`: 匹配到的凭据名称与角色
+    /// - `Ok(None)`: 密码与所有凭据都不匹配
+    pub fn match_slot(&self, password: &str) -> Result<Option<SlotInfo>> {
+        let mut matched = None;
+        for (name, credential) in &self.credentials {
+            let ok = credential.verify(password)?;
+            if ok && matched.is_none() {
+                matched = Some(SlotInfo {
+                    name: name.clone(),
+                    role: credential.role,
+                });
+            }
+        }
+        Ok(matched)
+    }
+
+    /// 管理操作授权：密码须与任一管理员凭据匹配，启用 TOTP 时还须提供有效的验证码
+    ///
+    /// 与 `verify_tracked_with_code` 共用锁定与失败计数：锁定期间直接拒绝，失败时记录，
+    /// 通过时清零。全部管理员凭据逐一校验，匹配后不提前结束
+    ///
+    /// # 返回
+    /// - `Err(WrongPassword)`: 密码与所有管理员凭据都不匹配，或验证码错误（已记录失败）
+    /// - `Err(LockedOut)`: 仍在锁定中
+    pub(crate) fn authorize(&mut self, admin_password: &str, code: Option<&str>) -> Result<()> {
+        let matched = self.check_tracked(code, OffsetDateTime::now_utc(), |vault| {
+            let mut matched = false;
+            for credential in vault.credentials.values().filter(|c| c.role.is_admin()) {
+                matched |= credential.verify(admin_password)?;
+            }
+            Ok(matched)
+        })?;
+        if !matched {
+            return Err(AmberlockError::WrongPassword);
+        }
+        Ok(())
+    }
+}
+
+/// 当前 UTC 时间（RFC3339）
+fn now_rfc3339() -> String {
+    OffsetDateTime::now_utc()
+        .format(&Rfc3339)
+        .unwrap_or_default()
+}
+
+/// 校验凭据名称：非空、长度受限、不含控制字符
+fn validate_name(name: &str) -> Result<()> {
+    if name.trim().is_empty() {
+        return Err(AmberlockError::InvalidCredentialName(
+            "名称不能为空".to_string(),
+        ));
+    }
+    if name.chars().count() > MAX_CREDENTIAL_NAME_LEN {
+        return Err(AmberlockError::InvalidCredentialName(format!(
+            "名称长度不能超过 {} 个字符",
+            MAX_CREDENTIAL_NAME_LEN
+        )));
+    }
+    if name.chars().any(char::is_control) {
+        return Err(AmberlockError::InvalidCredentialName(
+            "名称不能包含控制字符".to_string(),
+        ));
+    }
+    Ok(())
+}
+
+/// 校验密码提示（已去除首尾空白且非空）
+fn validate_hint(hint: &str, password: &str) -> Result<()> {
+    if hint.chars().count() > MAX_HINT_LEN {
+        return Err(AmberlockError::InvalidHint(format!(
+            "长度不能超过 {} 个字符",
+            MAX_HINT_LEN
+        )));
+    }
+    if hint.chars().any(char::is_control) {
+        return Err(AmberlockError::InvalidHint("不能包含控制字符".to_string()));
+    }
+    if !password.is_empty() && hint.to_lowercase().contains(&password.to_lowercase()) {
+        return Err(AmberlockError::InvalidHint("不能包含密码本身".to_string()));
+    }
+    Ok(())
+}
+
+/// 使用 Argon2id 计算密码哈希
+///
+/// # 参数
+/// - `secret`: Argon2 密钥输入（密钥文件摘要，不需要密钥文件时为 None）
+pub(crate) fn hash_password(
+    password: &str,
+    salt: &[u8],
+    secret: Option<&[u8]>,
+    params: Argon2Params,
+    output_len: usize,
+) -> Result<Vec<u8>> {
+    let params = params.to_argon2(output_len)?;
+    let argon2 = match secret {
+        Some(secret) => {
+            Argon2::new_with_secret(secret, Algorithm::Argon2id, Version::V0x13, params)
+                .map_err(|e| AmberlockError::VaultCorrupted(format!("哈希计算失败: {}", e)))?
+        }
+        None => Argon2::new(Algorithm::Argon2id, Version::V0x13, params),
+    };
+    let mut out = vec![0u8; output_len];
+    argon2
+        .hash_password_into(password.as_bytes(), salt, &mut out)
+        .map_err(|e| AmberlockError::VaultCorrupted(format!("哈希计算失败: {}", e)))?;
+    Ok(out)
+}
+
+/// 密钥文件摘要（SHA-256），作为 Argon2 的密钥输入
+fn keyfile_digest(keyfile: &[u8]) -> Zeroizing<[u8; 32]> {
+    Zeroizing::new(Sha256::digest(keyfile).into())
+}
+
+/// 常量时间比较，避免通过耗时泄露匹配前缀长度
+pub(crate) fn constant_time_eq(a: &[u8], b: &[u8]) -> bool {
+    if a.len() != b.len() {
+        return false;
+    }
+    a.iter().zip(b).fold(0u8, |acc, (x, y)| acc | (x ^ y)) == 0
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use serde_json::json;
+
+    /// 构造 v1 格式明文
+    fn legacy_plain(password: &str) -> Vec<u8> {
+        let credential = Credential::new(password).expect("生成凭据失败");
+        serde_json::to_vec(&json!({
+            "version": VAULT_VERSION_V1,
+            "params": credential.params,
+            "salt": credential.salt,
+            "hash": credential.hash,
+        }))
+        .unwrap()
+    }
+
+    #[test]
+    fn test_upgrade_from_v1() {
+        let vault = VaultBlob::decode(&legacy_plain("old-pass")).expect("解析 v1 失败");
+
+        assert_eq!(vault.names(), vec![DEFAULT_CREDENTIAL]);
+        assert!(vault.verify(DEFAULT_CREDENTIAL, "old-pass").unwrap());
+
+        // 写入后为 v5 二进制格式，且可再次读取
+        let encoded = vault.encode().expect("序列化失败");
+        assert!(binary::is_binary(&encoded));
+        assert_eq!(binary::version(&encoded).unwrap(), VAULT_VERSION);
+        assert_eq!(VaultBlob::decode(&encoded).unwrap(), vault);
+        println!("✅ v1 保险库透明升级为 v5");
+    }
+
+    #[test]
+    fn test_independent_verification_per_credential() {
+        let mut vault = VaultBlob::with_default("admin").expect("创建保险库失败");
+        vault
+            .add_credential("admin", None, "work", "work-pass")
+            .expect("添加凭据失败");
+
+        assert!(vault.verify("work", "work-pass").unwrap());
+        assert!(!vault.verify("work", "admin").unwrap());
+        assert!(vault.verify(DEFAULT_CREDENTIAL, "admin").unwrap());
+        assert!(!vault.verify(DEFAULT_CREDENTIAL, "work-pass").unwrap());
+        assert!(matches!(
+            vault.verify("personal", "admin"),
+            Err(AmberlockError::CredentialNotFound(_))
+        ));
+
+        // 任一管理员凭据持有者都可以管理
+        vault
+            .add_credential("work-pass", None, "personal", "p")
+            .expect("添加凭据失败");
+        assert_eq!(vault.names(), vec!["default", "personal", "work"]);
+
+        // 列表中不包含任何哈希信息
+        let listed = serde_json::to_string(&vault.names()).unwrap();
+        assert!(!listed.contains("hash"));
+    }
+
+    #[test]
+    fn test_rename_and_remove() {
+        let mut vault = VaultBlob::with_default("admin").expect("创建保险库失败");
+        vault.add_credential("admin", None, "work", "w").unwrap();
+
+        vault
+            .rename_credential("admin", None, "work", "office")
+            .unwrap();
+        assert!(vault.verify("office", "w").unwrap());
+        assert!(matches!(
+            vault.rename_credential("admin", None, "office", DEFAULT_CREDENTIAL),
+            Err(AmberlockError::CredentialExists(_))
+        ));
+
+        assert!(matches!(
+            vault.remove_credential("wrong", None, "office"),
+            Err(AmberlockError::WrongPassword)
+        ));
+        assert_eq!(vault.lockout.failed_attempts, 1);
+        vault.lockout.reset();
+        vault
+            .remove_credential("w", None, DEFAULT_CREDENTIAL)
+            .unwrap();
+        assert_eq!(vault.names(), vec!["office"]);
+
+        // 拒绝删除最后一个凭据
+        assert!(matches!(
+            vault.remove_credential("w", None, "office"),
+            Err(AmberlockError::LastCredential)
+        ));
+    }
+
+    #[test]
+    fn test_slots_with_roles() {
+        let mut vault = VaultBlob::with_default("admin").expect("创建保险库失败");
+        vault
+            .add_slot("admin", None, "operator", "view-only", Role::Operator)
+            .expect("添加凭据失败");
+
+        // 两个凭据各自匹配，互不串用
+        let admin = vault.match_slot("admin").unwrap().expect("应匹配管理员");
+        assert_eq!(
+            (admin.name.as_str(), admin.role),
+            (DEFAULT_CREDENTIAL, Role::Admin)
+        );
+        let operator = vault
+            .match_slot("view-only")
+            .unwrap()
+            .expect("应匹配操作员");
+        assert_eq!(
+            (operator.name.as_str(), operator.role),
+            ("operator", Role::Operator)
+        );
+        assert_eq!(vault.match_slot("wrong").unwrap(), None);
+        assert!(!vault.verify(DEFAULT_CREDENTIAL, "view-only").unwrap());
+        assert!(!vault.verify("operator", "admin").unwrap());
+
+        // 操作员不能管理凭据
+        assert!(matches!(
+            vault.add_slot("view-only", None, "other", "p", Role::Admin),
+            Err(AmberlockError::WrongPassword)
+        ));
+        assert!(matches!(
+            vault.remove_credential("view-only", None, "operator"),
+            Err(AmberlockError::WrongPassword)
+        ));
+        vault.lockout.reset();
+
+        // 角色随编码往返，修改密码后保持不变
+        let decoded = VaultBlob::decode(&vault.encode().unwrap()).expect("解析失败");
+        assert_eq!(decoded.credentials["operator"].role, Role::Operator);
+        assert_eq!(decoded.credentials[DEFAULT_CREDENTIAL].role, Role::Admin);
+        vault
+            .change_password("operator", "view-only", None, "new-view")
+            .expect("修改密码失败");
+        assert_eq!(vault.credentials["operator"].role, Role::Operator);
+
+        // 不能删除最后一个管理员；删除操作员后其密码失效
+        assert!(matches!(
+            vault.remove_credential("admin", None, DEFAULT_CREDENTIAL),
+            Err(AmberlockError::LastAdmin)
+        ));
+        vault.remove_credential("admin", None, "operator").unwrap();
+        assert_eq!(vault.match_slot("new-view").unwrap(), None);
+        assert_eq!(vault.names(), vec![DEFAULT_CREDENTIAL]);
+        println!("✅ 多个凭据按角色区分");
+    }
+
+    #[test]
+    fn test_invalid_names_and_duplicates() {
+        let mut vault = VaultBlob::with_default("admin").expect("创建保险库失败");
+
+        for bad in ["", "   ", "a\tb", &"x".repeat(MAX_CREDENTIAL_NAME_LEN + 1)] {
+            assert!(matches!(
+                vault.add_credential("admin", None, bad, "p"),
+                Err(AmberlockError::InvalidCredentialName(_))
+            ));
+        }
+        assert!(matches!(
+            vault.add_credential("admin", None, DEFAULT_CREDENTIAL, "p"),
+            Err(AmberlockError::CredentialExists(_))
+        ));
+    }
+
+    #[test]
+    fn test_corrupted_plaintext() {
+        assert!(matches!(
+            VaultBlob::decode(b"not json"),
+            Err(AmberlockError::VaultCorrupted(_))
+        ));
+        assert!(matches!(
+            VaultBlob::decode(br#"{"version":99}"#),
+            Err(AmberlockError::VaultCorrupted(_))
+        ));
+        assert!(matches!(
+            VaultBlob::decode(br#"{"version":3,"credentials":{}}"#),
+            Err(AmberlockError::VaultCorrupted(_))
+        ));
+    }
+
+    #[test]
+    fn test_vault_version_check() {
+        let mut vault = VaultBlob::with_default("p").expect("创建保险库失败");
+        vault.add_credential("p", None, "work", "w").unwrap();
+
+        // v5：二进制明文中不出现字段名，读取后与原保险库一致
+        let v5 = vault.encode().unwrap();
+        for name in ["credentials", "salt", "hash", "params", "created_at"] {
+            assert!(
+                !v5.windows(name.len()).any(|w| w == name.as_bytes()),
+                "明文中出现了字段名 {}",
+                name
+            );
+        }
+        let decoded = VaultBlob::decode(&v5).expect("解析 v5 失败");
+        assert_eq!(decoded, vault);
+        assert!(decoded.verify("work", "w").unwrap());
+        assert_eq!(
+            VaultMetadata::from_plain(&v5).unwrap().version,
+            VAULT_VERSION_V5
+        );
+
+        // v1 与 v4 JSON 照常读取
+        assert!(
+            VaultBlob::decode(&legacy_plain("old"))
+                .unwrap()
+                .verify(DEFAULT_CREDENTIAL, "old")
+                .unwrap()
+        );
+        let v4 = serde_json::to_vec(&VaultBlob {
+            version: VAULT_VERSION_V4,
+            ..vault.clone()
+        })
+        .unwrap();
+        let decoded = VaultBlob::decode(&v4).expect("解析 v4 失败");
+        assert_eq!(decoded.version, VAULT_VERSION_V4);
+        assert!(decoded.verify("work", "w").unwrap());
+        assert_eq!(
+            VaultMetadata::from_plain(&v4).unwrap().version,
+            VAULT_VERSION_V4
+        );
+
+        // 未来的版本号，以及与前缀不符的版本号（二进制的 v2、JSON 的 v5）：均拒绝
+        let with_version = |version: u32| {
+            let mut plain = v5.clone();
+            plain[binary::MAGIC.len()..binary::MAGIC.len() + 4]
+                .copy_from_slice(&version.to_le_bytes());
+            plain
+        };
+        let (future, old_binary) = (with_version(9), with_version(2));
+        for plain in [
+            &br#"{"version":9}"#[..],
+            &br#"{"version":5,"credentials":{}}"#[..],
+            &future,
+            &old_binary,
+        ] {
+            assert!(matches!(
+                VaultBlob::decode(plain),
+                Err(AmberlockError::VaultCorrupted(_))
+            ));
+            assert!(matches!(
+                VaultMetadata::from_plain(plain),
+                Err(AmberlockError::VaultCorrupted(_))
+            ));
+        }
+
+        // 截断的二进制明文无法通过完整性校验
+        assert!(matches!(
+            VaultBlob::decode(&v5[..v5.len() - 1]),
+            Err(AmberlockError::VaultTampered(_))
+        ));
+        println!("✅ 按格式与版本分派读取");
+    }
+
+    #[test]
+    fn test_integrity_tag() {
+        let vault = VaultBlob::with_default("p").expect("创建保险库失败");
+        let plain = vault.encode().unwrap();
+        let tag_start = plain.len() - 32;
+        let is_tampered = |plain: &[u8]| {
+            matches!(
+                VaultBlob::decode(plain),
+                Err(AmberlockError::VaultTampered(_))
+            ) && matches!(
+                VaultMetadata::from_plain(plain),
+                Err(AmberlockError::VaultTampered(_))
+            )
+        };
+
+        // 篡改字段内容（含盐与哈希所在的凭据字段）
+        for offset in [binary::MAGIC.len() + 4, tag_start / 2, tag_start - 6] {
+            let mut modified = plain.clone();
+            modified[offset] ^= 0x01;
+            assert!(is_tampered(&modified), "偏移 {} 被篡改后未被发现", offset);
+        }
+
+        // 篡改校验值本身，或整个去掉校验字段
+        let mut modified = plain.clone();
+        modified[plain.len() - 1] ^= 0x80;
+        assert!(is_tampered(&modified));
+        assert!(is_tampered(&plain[..tag_start - 5]));
+        assert!(is_tampered(&plain[..binary::MAGIC.len() + 4]));
+
+        // 校验值与内容一致但字段结构无效：属于格式错误而非篡改
+        let mut malformed = plain[..tag_start - 5].to_vec();
+        // 声明长度越界的未知字段
+        malformed.extend_from_slice(&[9, 0xff, 0xff, 0xff, 0xff]);
+        let digest = <sha2::Sha256 as sha2::Digest>::digest(&malformed);
+        malformed.extend_from_slice(&[5, 32, 0, 0, 0]);
+        malformed.extend_from_slice(&digest);
+        assert!(matches!(
+            VaultBlob::decode(&malformed),
+            Err(AmberlockError::VaultCorrupted(_))
+        ));
+
+        assert_eq!(VaultBlob::decode(&plain).unwrap(), vault);
+        println!("✅ 完整性校验区分篡改与格式错误");
+    }
+
+    #[test]
+    fn test_failed_attempts_persist_and_escalate() {
+        let start = OffsetDateTime::now_utc();
+        let mut vault = VaultBlob::with_default("p").expect("创建保险库失败");
+
+        // 每次失败后写回再读取，模拟每次校验都重启程序
+        let mut now = start;
+        let mut previous = Duration::ZERO;
+        for attempt in 1..=3 {
+            assert!(
+                !vault
+                    .verify_tracked(DEFAULT_CREDENTIAL, "wrong", now)
+                    .unwrap()
+            );
+            vault = VaultBlob::decode(&vault.encode().unwrap()).expect("重新读取失败");
+            assert_eq!(vault.lockout.failed_attempts, attempt);
+            let metadata = VaultMetadata::from_plain(&vault.encode().unwrap()).unwrap();
+            assert_eq!(metadata.lockout, vault.lockout);
+
+            // 锁定期内直接拒绝，且不计入失败次数
+            let info = vault.lockout.info(now);
+            assert!(info.delay > previous);
+            previous = info.delay;
+            assert!(matches!(
+                vault.verify_tracked(DEFAULT_CREDENTIAL, "p", now),
+                Err(AmberlockError::LockedOut { .. })
+            ));
+            assert_eq!(vault.lockout.failed_attempts, attempt);
+            now += info.delay;
+        }
+        assert_eq!(previous, Duration::from_secs(2));
+
+        // 锁定结束后密码正确，计数清零且不再写入明文
+        assert!(vault.verify_tracked(DEFAULT_CREDENTIAL, "p", now).unwrap());
+        assert!(vault.lockout.is_clear());
+        let plain = vault.encode().unwrap();
+        assert_eq!(
+            VaultBlob::decode(&plain).unwrap().lockout,
+            LockoutState::default()
+        );
+        println!("✅ 失败次数写入保险库，锁定时长随失败递增");
+    }
+
+    #[test]
+    fn test_keyfile_credential() {
+        let vault = VaultBlob::with_default_keyfile("p", b"keyfile-bytes").expect("创建保险库失败");
+        let decoded = VaultBlob::decode(&vault.encode().unwrap()).expect("重新读取失败");
+        assert_eq!(decoded, vault);
+        assert!(decoded.credentials[DEFAULT_CREDENTIAL].keyfile);
+
+        let verify = |password: &str, keyfile: Option<&[u8]>| {
+            decoded
+                .verify_with_keyfile(DEFAULT_CREDENTIAL, password, keyfile)
+                .unwrap()
+        };
+        // 密码与密钥文件都正确
+        assert!(verify("p", Some(b"keyfile-bytes")));
+        // 密码正确但密钥文件错误、密码错误、缺少密钥文件
+        assert!(!verify("p", Some(b"keyfile-byteS")));
+        assert!(!verify("p", Some(b"")));
+        assert!(!verify("q", Some(b"keyfile-bytes")));
+        assert!(!verify("p", None));
+        assert!(!decoded.verify(DEFAULT_CREDENTIAL, "p").unwrap());
+
+        // 不需要密钥文件的凭据忽略提供的密钥文件，且编码中不带标记
+        let plain = VaultBlob::with_default("p").unwrap();
+        assert!(
+            plain
+                .verify_with_keyfile(DEFAULT_CREDENTIAL, "p", Some(b"anything"))
+                .unwrap()
+        );
+        assert!(
+            !VaultBlob::decode(&plain.encode().unwrap())
+                .unwrap()
+                .credentials[DEFAULT_CREDENTIAL]
+                .keyfile
+        );
+        assert!(matches!(
+            VaultBlob::with_default_keyfile("p", b""),
+            Err(AmberlockError::InvalidKeyfile(_))
+        ));
+        println!("✅ 密钥文件参与哈希计算");
+    }
+
+    #[test]
+    fn test_recovery_key_hash_only() {
+        let mut vault = VaultBlob::with_default("p").expect("创建保险库失败");
+        assert!(matches!(
+            vault.verify_recovery(&RecoveryKey::generate()),
+            Err(AmberlockError::RecoveryNotConfigured)
+        ));
+        let key = vault.set_recovery().expect("生成恢复密钥失败");
+
+        // 保险库中只有哈希：明文里找不到密钥的原始字节或文本
+        let plain = vault.encode().unwrap();
+        let canonical = key.canonical();
+        assert!(
+            !plain
+                .windows(canonical.len())
+                .any(|w| w == canonical.as_bytes())
+        );
+        let decoded = VaultBlob::decode(&plain).expect("重新读取失败");
+        assert_eq!(decoded, vault);
+        assert!(decoded.verify_recovery(&key).unwrap());
+        assert!(!decoded.verify_recovery(&RecoveryKey::generate()).unwrap());
+        assert_eq!(decoded.names(), vec![DEFAULT_CREDENTIAL.to_string()]);
+        // 恢复密钥不能充当管理密码
+        assert!(decoded.clone().authorize(&canonical, None).is_err());
+
+        // 重设密码：恢复密钥错误时不改动，正确时替换 default 并清零失败计数
+        let mut vault = decoded;
+        vault.lockout.record_failure(OffsetDateTime::now_utc());
+        assert!(matches!(
+            vault.reset_password_with_recovery(&RecoveryKey::generate(), "new", None),
+            Err(AmberlockError::WrongRecoveryKey)
+        ));
+        assert!(vault.verify(DEFAULT_CREDENTIAL, "p").unwrap());
+        vault
+            .reset_password_with_recovery(&key, "new", None)
+            .expect("重设密码失败");
+        assert!(vault.verify(DEFAULT_CREDENTIAL, "new").unwrap());
+        assert!(vault.lockout.is_clear());
+        assert!(vault.verify_recovery(&key).unwrap());
+        println!("✅ 保险库只保存恢复密钥的哈希");
+    }
+
+    #[test]
+    fn test_recovery_reset_keeps_keyfile() {
+        let mut vault = VaultBlob::with_default_keyfile("p", b"usb key").expect("创建保险库失败");
+        let key = vault.set_recovery().expect("生成恢复密钥失败");
+
+        // 未提供密钥文件：拒绝重设，凭据不变
+        assert!(matches!(
+            vault.reset_password_with_recovery(&key, "new", None),
+            Err(AmberlockError::InvalidKeyfile(_))
+        ));
+        assert!(
+            vault
+                .verify_with_keyfile(DEFAULT_CREDENTIAL, "p", Some(b"usb key"))
+                .unwrap()
+        );
+
+        // 提供密钥文件：新密码仍需密钥文件，可换用新的密钥文件
+        vault
+            .reset_password_with_recovery(&key, "new", Some(b"new key"))
+            .expect("重设密码失败");
+        assert!(vault.credentials[DEFAULT_CREDENTIAL].keyfile);
+        assert!(!vault.verify(DEFAULT_CREDENTIAL, "new").unwrap());
+        assert!(
+            vault
+                .verify_with_keyfile(DEFAULT_CREDENTIAL, "new", Some(b"new key"))
+                .unwrap()
+        );
+        assert!(
+            !vault
+                .verify_with_keyfile(DEFAULT_CREDENTIAL, "new", Some(b"usb key"))
+                .unwrap()
+        );
+        println!("✅ 凭恢复密钥重设密码不去掉密钥文件");
+    }
+
+    #[test]
+    fn test_timestamps_round_trip() {
+        let vault = VaultBlob::with_default("admin").expect("创建保险库失败");
+        assert!(vault.created_at.is_some());
+        assert_eq!(vault.created_at, vault.last_changed_at);
+
+        let decoded = VaultBlob::decode(&vault.encode().unwrap()).expect("解析失败");
+        assert_eq!(decoded.created_at, vault.created_at);
+        assert_eq!(decoded.last_changed_at, vault.last_changed_at);
+    }
+
+    #[test]
+    fn test_dpapi_scope_recorded() {
+        let mut vault = VaultBlob::with_default("p").expect("创建保险库失败");
+        assert_eq!(vault.dpapi_scope, DpapiScope::CurrentUser);
+        vault.dpapi_scope = DpapiScope::LocalMachine;
+
+        let encoded = vault.encode().unwrap();
+        let decoded = VaultBlob::decode(&encoded).expect("解析失败");
+        assert_eq!(decoded.dpapi_scope, DpapiScope::LocalMachine);
+        let metadata = VaultMetadata::from_plain(&encoded).unwrap();
+        assert_eq!(metadata.dpapi_scope, DpapiScope::LocalMachine);
+
+        // 未记录范围的旧保险库视为当前用户
+        let legacy = VaultBlob::decode(&legacy_plain("p")).unwrap();
+        assert_eq!(legacy.dpapi_scope, DpapiScope::CurrentUser);
+        let v4 = plain_with(json!(Credential::new("w").unwrap()));
+        assert_eq!(
+            VaultMetadata::from_plain(&v4).unwrap().dpapi_scope,
+            DpapiScope::CurrentUser
+        );
+        println!("✅ DPAPI 范围随保险库保存");
+    }
+
+    #[test]
+    fn test_change_password_updates_last_changed_only() {
+        let mut vault = VaultBlob::with_default("old").expect("创建保险库失败");
+        let created = "2024-11-02T08:00:00Z".to_string();
+        vault.created_at = Some(created.clone());
+        vault.last_changed_at = Some(created.clone());
+
+        assert!(matches!(
+            vault.change_password(DEFAULT_CREDENTIAL, "wrong", None, "new"),
+            Err(AmberlockError::WrongPassword)
+        ));
+        vault.lockout.reset();
+        assert_eq!(vault.last_changed_at.as_deref(), Some(created.as_str()));
+
+        let old_salt = vault.credentials[DEFAULT_CREDENTIAL].salt.clone();
+        vault
+            .change_password(DEFAULT_CREDENTIAL, "old", None, "new")
+            .expect("修改密码失败");
+        assert!(vault.verify(DEFAULT_CREDENTIAL, "new").unwrap());
+        assert!(!vault.verify(DEFAULT_CREDENTIAL, "old").unwrap());
+        assert_ne!(vault.credentials[DEFAULT_CREDENTIAL].salt, old_salt);
+        assert_eq!(vault.created_at.as_deref(), Some(created.as_str()));
+        assert_ne!(vault.last_changed_at.as_deref(), Some(created.as_str()));
+    }
+
+    #[test]
+    fn test_password_changed_at_and_hint() {
+        let mut vault = VaultBlob::with_default("old").expect("创建保险库失败");
+        assert_eq!(vault.password_changed_at, vault.created_at);
+        assert_eq!(vault.hint, None);
+
+        // 提示长度、控制字符与包含密码均被拒绝，原提示不变
+        vault
+            .set_hint("old", None, Some("  猫的名字  "))
+            .expect("设置提示失败");
+        assert_eq!(vault.hint.as_deref(), Some("猫的名字"));
+        for bad in [
+            "提".repeat(MAX_HINT_LEN + 1),
+            "a\nb".to_string(),
+            "是 OLD 吗".to_string(),
+        ] {
+            assert!(matches!(
+                vault.set_hint("old", None, Some(&bad)),
+                Err(AmberlockError::InvalidHint(_))
+            ));
+        }
+        assert!(
+            vault
+                .set_hint("old", None, Some(&"提".repeat(MAX_HINT_LEN)))
+                .is_ok()
+        );
+        assert!(matches!(
+            vault.set_hint("wrong", None, None),
+            Err(AmberlockError::WrongPassword)
+        ));
+        vault.lockout.reset();
+        vault.set_hint("old", None, Some("猫的名字")).unwrap();
+
+        // 只有 `default` 凭据的密码变化才更新密码修改时间
+        let created = "2024-11-02T08:00:00Z".to_string();
+        vault.password_changed_at = Some(created.clone());
+        vault.add_credential("old", None, "work", "w").unwrap();
+        vault.change_password("work", "w", None, "x").unwrap();
+        assert_eq!(vault.password_changed_at.as_deref(), Some(created.as_str()));
+        vault
+            .change_password(DEFAULT_CREDENTIAL, "old", None, "new")
+            .unwrap();
+        assert_ne!(vault.password_changed_at.as_deref(), Some(created.as_str()));
+        assert_eq!(vault.password_changed_at, vault.last_changed_at);
+
+        let encoded = vault.encode().unwrap();
+        let decoded = VaultBlob::decode(&encoded).expect("解析失败");
+        assert_eq!(decoded.password_changed_at, vault.password_changed_at);
+        assert_eq!(decoded.hint.as_deref(), Some("猫的名字"));
+        let metadata = VaultMetadata::from_plain(&encoded).unwrap();
+        assert_eq!(metadata.password_changed_at, vault.password_changed_at);
+        assert_eq!(metadata.hint.as_deref(), Some("猫的名字"));
+
+        // 旧保险库没有这两个字段
+        let metadata = VaultMetadata::from_plain(&legacy_plain("p")).unwrap();
+        assert_eq!((metadata.password_changed_at, metadata.hint), (None, None));
+        let v4 = plain_with(json!(Credential::new("w").unwrap()));
+        let vault = VaultBlob::decode(&v4).unwrap();
+        assert_eq!((vault.password_changed_at, vault.hint), (None, None));
+        println!("✅ 密码修改时间与提示随保险库保存");
+    }
+
+    #[test]
+    fn test_authorize_checks_totp_and_lockout() {
+        let mut vault = VaultBlob::with_default("admin").expect("创建保险库失败");
+        let secret = vault.enable_totp("admin", None).expect("启用 TOTP 失败");
+        let now = OffsetDateTime::now_utc();
+        let code = secret.code_at(now.unix_timestamp() as u64);
+
+        // 只凭密码不能停用，失败计入锁定
+        assert!(matches!(
+            vault.disable_totp("admin", None),
+            Err(AmberlockError::WrongPassword)
+        ));
+        assert!(vault.totp.is_some());
+        assert_eq!(vault.lockout.failed_attempts, 1);
+
+        // 锁定期间正确的密码与验证码同样被拒绝（失败时间按授权开始计，调试构建中哈希较慢）
+        let failed_at = OffsetDateTime::now_utc().format(&Rfc3339).unwrap();
+        vault.lockout.last_failure_at = Some(failed_at);
+        assert!(matches!(
+            vault.authorize("admin", Some(&code)),
+            Err(AmberlockError::LockedOut { .. })
+        ));
+
+        vault.lockout.reset();
+        vault
+            .disable_totp("admin", Some(&code))
+            .expect("停用 TOTP 失败");
+        assert!(vault.totp.is_none());
+        assert_eq!(vault.lockout.failed_attempts, 0);
+        println!("✅ 管理操作的授权同时检查锁定与验证码");
+    }
+
+    #[test]
+    fn test_metadata_without_hash() {
+        // 旧格式：时间戳为 None，不报错
+        let metadata = VaultMetadata::from_plain(&legacy_plain("p")).expect("读取元数据失败");
+        assert_eq!(metadata.version, VAULT_VERSION_V1);
+        assert_eq!(metadata.created_at, None);
+        assert_eq!(metadata.last_changed_at, None);
+        assert_eq!(metadata.params[DEFAULT_CREDENTIAL], Argon2Params::CURRENT);
+
+        // v3 同样没有时间戳
+        let v3 = br#"{"version":3,"credentials":{"work":{"params":"m=8,t=1,p=1","salt":[1],"hash":[2]}}}"#;
+        let metadata = VaultMetadata::from_plain(v3).expect("读取元数据失败");
+        assert_eq!(metadata.created_at, None);
+        assert_eq!(metadata.params["work"].to_string(), "m=8,t=1,p=1");
+
+        let vault = VaultBlob::with_default("p").unwrap();
+        let metadata = VaultMetadata::from_plain(&vault.encode().unwrap()).unwrap();
+        assert_eq!(metadata.version, VAULT_VERSION);
+        assert_eq!(metadata.created_at, vault.created_at);
+        assert!(!format!("{:?}", metadata).contains("hash"));
+        println!("✅ 元数据：{:?}", metadata);
+    }
+
+    /// 构造只含一个 `work` 凭据的 v4 明文
+    fn plain_with(credential: serde_json::Value) -> Vec<u8> {
+        serde_json::to_vec(&json!({
+            "version": VAULT_VERSION_V4,
+            "credentials": { "work": credential },
+        }))
+        .unwrap()
+    }
+
+    #[test]
+    fn test_parse_params_strict() {
+        assert_eq!(
+            parse_params("m=19456,t=2,p=1").unwrap(),
+            Argon2Params::CURRENT
+        );
+        assert_eq!(Argon2Params::CURRENT.to_string(), "m=19456,t=2,p=1");
+        assert_eq!(
+            parse_params("$argon2id$v=19$m=19456,t=2,p=1").unwrap(),
+            Argon2Params::CURRENT
+        );
+
+        for bad in [
+            "",
+            "m=19456,t=2",
+            "m=19456,t=2,p=1,v=19",
+            "m=19456,t=2,p=1,m=8",
+            "m=19456,t=2,p",
+            "m=19456,t=-1,p=1",
+            "m = 19456,t=2,p=1",
+            // 超出 argon2 库的范围：t 至少为 1，m 至少为 8*p，p 至多 2^24-1
+            "m=19456,t=0,p=1",
+            "m=7,t=2,p=1",
+            "m=64,t=2,p=16",
+            "m=4294967295,t=2,p=16777216",
+            // PHC 形式只接受 Argon2id 0x13，且不带盐与哈希
+            "$argon2i$v=19$m=19456,t=2,p=1",
+            "$argon2i$v=19$m=19456,t=2,p=1$c29tZXNhbHQ$aGFzaGhhc2hoYXNoaGFzaA",
+            "$argon2d$v=19$m=19456,t=2,p=1",
+            "$argon2id$v=16$m=19456,t=2,p=1",
+            "$argon2id$m=19456,t=2,p=1",
+            "$argon2id$v=19$m=19456,t=2,p=1$c29tZXNhbHQ$aGFzaGhhc2hoYXNoaGFzaA",
+            "$scrypt$ln=15,r=8,p=1",
+        ] {
+            assert!(
+                matches!(parse_params(bad), Err(AmberlockError::VaultCorrupted(_))),
+                "{:?} 应被拒绝",
+                bad
+            );
+        }
+        println!("✅ Argon2 参数严格解析");
+    }
+
+    #[test]
+    fn test_structured_params_written_and_cross_checked() {
+        let vault = VaultBlob::with_default("p").expect("创建保险库失败");
+        let written = binary::decode(&vault.encode().unwrap(), true).unwrap();
+        let credential = &written.credentials[DEFAULT_CREDENTIAL];
+        // 同时写入结构化参数与旧版参数字符串
+        assert_eq!(credential.params, "m=19456,t=2,p=1");
+        assert_eq!(credential.argon2.map(|p| p.m_cost), Some(ARGON2_M_COST));
+
+        // 一致的凭据可以读取并校验
+        let consistent = Credential::new("w").unwrap();
+        let vault = VaultBlob::decode(&plain_with(json!(consistent))).expect("读取失败");
+        assert!(vault.verify("work", "w").unwrap());
+
+        // 结构化参数与参数字符串不一致
+        let mut mismatched = json!(consistent);
+        mismatched["argon2"]["t_cost"] = json!(3);
+        assert!(matches!(
+            VaultBlob::decode(&plain_with(mismatched.clone())),
+            Err(AmberlockError::InconsistentVaultParams { name, .. }) if name == "work"
+        ));
+        assert!(matches!(
+            VaultMetadata::from_plain(&plain_with(mismatched)),
+            Err(AmberlockError::InconsistentVaultParams { .. })
+        ));
+
+        // 哈希长度与参数要求的输出长度不符
+        let mut truncated = json!(consistent);
+        truncated["hash"] = json!(&consistent.hash[..16]);
+        assert!(matches!(
+            VaultBlob::decode(&plain_with(truncated)),
+            Err(AmberlockError::InconsistentVaultParams { .. })
+        ));
+
+        // 参数字符串含未知键、越界，或是 Argon2i 的 PHC 字符串
+        for bad in [
+            "m=19456,t=2,p=1,x=1",
+            "m=19456,t=0,p=1",
+            "$argon2i$v=19$m=19456,t=2,p=1",
+        ] {
+            let mut invalid = json!(consistent);
+            invalid["params"] = json!(bad);
+            invalid["argon2"] = serde_json::Value::Null;
+            assert!(matches!(
+                VaultBlob::decode(&plain_with(invalid)),
+                Err(AmberlockError::VaultCorrupted(_))
+            ));
+        }
+
+        // Argon2id 的 PHC 参数与结构化参数一致时照常校验
+        let mut phc = json!(consistent);
+        phc["params"] = json!("$argon2id$v=19$m=19456,t=2,p=1");
+        let vault = VaultBlob::decode(&plain_with(phc)).expect("读取失败");
+        assert!(vault.verify("work", "w").unwrap());
+    }
+
+    #[test]
+    fn test_custom_config_recorded_and_honored() {
+        let config = Argon2Config {
+            mem_kib: MIN_MEM_KIB,
+            time_cost: 1,
+            parallelism: 1,
+            output_len: MAX_HASH_LEN,
+        };
+        let mut vault = VaultBlob::with_default_config("p", &config).expect("创建保险库失败");
+        let decoded = VaultBlob::decode(&vault.encode().unwrap()).expect("解析失败");
+        assert!(decoded.verify(DEFAULT_CREDENTIAL, "p").unwrap());
+        assert!(!decoded.verify(DEFAULT_CREDENTIAL, "q").unwrap());
+        assert_eq!(
+            decoded.credentials[DEFAULT_CREDENTIAL].hash.len(),
+            MAX_HASH_LEN
+        );
+        assert_eq!(
+            decoded.credentials[DEFAULT_CREDENTIAL].config().unwrap(),
+            config
+        );
+
+        let metadata = VaultMetadata::from_plain(&vault.encode().unwrap()).unwrap();
+        assert_eq!(
+            metadata.params[DEFAULT_CREDENTIAL].to_string(),
+            "m=8192,t=1,p=1"
+        );
+
+        // 新凭据仍用默认参数，修改密码沿用原凭据的参数
+        vault.add_credential("p", None, "work", "w").unwrap();
+        assert_eq!(
+            vault.credentials["work"].config().unwrap(),
+            Argon2Config::default()
+        );
+        vault
+            .change_password(DEFAULT_CREDENTIAL, "p", None, "n")
+            .unwrap();
+        assert_eq!(
+            vault.credentials[DEFAULT_CREDENTIAL].config().unwrap(),
+            config
+        );
+        println!("✅ 自定义 Argon2 参数随凭据保存并用于校验");
+    }
+
+    #[test]
+    fn test_weak_params_upgraded() {
+        let weak = Argon2Config {
+            mem_kib: MIN_MEM_KIB,
+            time_cost: 1,
+            parallelism: 1,
+            output_len: HASH_LEN,
+        };
+        let target = Argon2Config::default();
+        let mut vault = VaultBlob::with_default_config("p", &weak).expect("创建保险库失败");
+        vault.last_changed_at = Some("2024-01-01T00:00:00Z".to_string());
+        assert!(needs_rehash(&vault, &target));
+        assert!(!needs_rehash(&vault, &weak));
+
+        assert!(matches!(
+            vault.upgrade_credential(DEFAULT_CREDENTIAL, "q", &target),
+            Err(AmberlockError::WrongPassword)
+        ));
+        let old_salt = vault.credentials[DEFAULT_CREDENTIAL].salt.clone();
+        assert!(
+            vault
+                .upgrade_credential(DEFAULT_CREDENTIAL, "p", &target)
+                .unwrap()
+        );
+
+        // 升级后写回的保险库使用目标参数，密码与修改时间不变
+        let decoded = VaultBlob::decode(&vault.encode().unwrap()).expect("解析失败");
+        let credential = &decoded.credentials[DEFAULT_CREDENTIAL];
+        assert_eq!(credential.config().unwrap(), target);
+        assert_ne!(credential.salt, old_salt);
+        assert!(decoded.verify(DEFAULT_CREDENTIAL, "p").unwrap());
+        assert_eq!(
+            decoded.last_changed_at.as_deref(),
+            Some("2024-01-01T00:00:00Z")
+        );
+        assert!(!needs_rehash(&decoded, &target));
+        assert!(
+            !vault
+                .upgrade_credential(DEFAULT_CREDENTIAL, "p", &target)
+                .unwrap()
+        );
+        println!("✅ 低于目标的参数在校验成功后升级");
+    }
+
+    #[test]
+    fn test_upgrade_never_lowers_params() {
+        // 内存高于目标、迭代次数低于目标：只提高迭代次数
+        let mixed = Argon2Config {
+            mem_kib: ARGON2_M_COST * 2,
+            time_cost: 1,
+            parallelism: 1,
+            output_len: MAX_HASH_LEN,
+        };
+        let target = Argon2Config::default();
+        let mut vault = VaultBlob::with_default_config("p", &mixed).expect("创建保险库失败");
+        assert!(needs_rehash(&vault, &target));
+        assert!(
+            vault
+                .upgrade_credential(DEFAULT_CREDENTIAL, "p", &target)
+                .unwrap()
+        );
+        assert_eq!(
+            vault.credentials[DEFAULT_CREDENTIAL].config().unwrap(),
+            Argon2Config {
+                time_cost: ARGON2_T_COST,
+                ..mixed
+            }
+        );
+
+        // 需要密钥文件的凭据无法仅凭密码升级
+        vault.credentials.insert(
+            DEFAULT_CREDENTIAL.to_string(),
+            Credential::with_keyfile("p", b"key", &mixed).expect("创建凭据失败"),
+        );
+        assert!(needs_rehash(&vault, &target));
+        assert!(
+            !vault
+                .upgrade_credential(DEFAULT_CREDENTIAL, "p", &target)
+                .unwrap()
+        );
+        println!("✅ 升级参数时不降低任何一项");
+    }
+
+    #[test]
+    fn test_unsafe_config_rejected() {
+        let base = Argon2Config::default();
+        for bad in [
+            Argon2Config {
+                mem_kib: MIN_MEM_KIB - 1,
+                ..base
+            },
+            Argon2Config {
+                time_cost: 0,
+                ..base
+            },
+            Argon2Config {
+                parallelism: 0,
+                ..base
+            },
+            Argon2Config {
+                output_len: MIN_HASH_LEN - 1,
+                ..base
+            },
+            Argon2Config {
+                output_len: MAX_HASH_LEN + 1,
+                ..base
+            },
+            // 内存须至少为并行度的 8 倍
+            Argon2Config {
+                parallelism: MIN_MEM_KIB,
+                ..base
+            },
+        ] {
+            assert!(
+                matches!(
+                    VaultBlob::with_default_config("p", &bad),
+                    Err(AmberlockError::UnsafeArgon2Config(_))
+                ),
+                "{:?} 应被拒绝",
+                bad
+            );
+        }
+        assert!(base.validate().is_ok());
+    }
+
+    #[test]
+    fn test_calibrate_scales_memory_then_passes() {
+        // 模拟：每 8 MiB 每次迭代耗时 10ms
+        let per_pass = |config: &Argon2Config| {
+            Duration::from_millis(10 * u64::from(config.mem_kib / MIN_MEM_KIB))
+        };
+        let config = calibrate_with(Duration::from_millis(500), per_pass);
+        assert_eq!(config.mem_kib, 32 * MIN_MEM_KIB);
+        assert_eq!(config.time_cost, 1);
+
+        // 内存到达上限后用迭代次数补足
+        let config = calibrate_with(Duration::from_secs(6), per_pass);
+        assert_eq!(config.mem_kib, MAX_CALIBRATE_MEM_KIB);
+        assert_eq!(config.time_cost, 4);
+
+        // 本机过慢时不低于下限
+        let config = calibrate_with(Duration::from_millis(100), |_| Duration::from_secs(2));
+        assert_eq!(config.mem_kib, MIN_MEM_KIB);
+        assert_eq!(config.time_cost, 1);
+        assert!(config.validate().is_ok());
+
+        // 实际测量选出的参数同样可用
+        assert!(calibrate(Duration::from_millis(1)).validate().is_ok());
+        println!("✅ 校准按目标耗时选取参数");
+    }
+
+    #[test]
+    fn test_legacy_params_string_still_loads() {
+        // v1 与不含结构化参数的 v3 保险库均使用当前的参数字符串
+        let vault = VaultBlob::decode(&legacy_plain("old")).expect("解析 v1 失败");
+        assert!(vault.verify(DEFAULT_CREDENTIAL, "old").unwrap());
+
+        let credential = Credential::new("w").unwrap();
+        let mut legacy = json!(credential);
+        legacy.as_object_mut().unwrap().remove("argon2");
+        assert_eq!(legacy["params"], "m=19456,t=2,p=1");
+        let vault = VaultBlob::decode(&plain_with(legacy)).expect("解析旧凭据失败");
+        assert!(vault.verify("work", "w").unwrap());
+
+        // 读取时补齐结构化参数，下次写入一并保存
+        assert_eq!(
+            vault.credentials["work"].argon2,
+            Some(Argon2Params::CURRENT)
+        );
+        println!("✅ 旧版参数字符串继续可用");
+    }
+}
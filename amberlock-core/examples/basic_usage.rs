@@ -0,0 +1,34 @@
+//! 最小嵌入示例：探测能力 → 计算有效级别 → 批量只读上锁 → 写入审计日志
+//!
+//! 只从 `amberlock_core::prelude` 导入。
+//!
+//! 用法：`cargo run -p amberlock-core --example basic_usage -- <路径>...`
+
+use amberlock_core::prelude::*;
+use std::path::PathBuf;
+
+fn main() -> Result<(), Box<dyn std::error::Error>> {
+    let paths: Vec<PathBuf> = std::env::args().skip(1).map(PathBuf::from).collect();
+    if paths.is_empty() {
+        eprintln!("用法: basic_usage <路径>...");
+        return Ok(());
+    }
+
+    let probe: CapabilityProbe = probe_capability()?;
+    let opts = LockOptions {
+        desired_level: LabelLevel::High,
+        mode: ProtectMode::ReadOnly,
+        comment: Some("basic_usage 示例".to_string()),
+        ..Default::default()
+    };
+    let effective = compute_effective_level(opts.desired_level, probe.has_se_relabel);
+    let user_sid = read_user_sid()?;
+    let logger = OperationLog::open("amberlock-example.ndjson")?;
+
+    let result: BatchResult = batch_process_lock(&paths, &opts, effective, &user_sid, &logger)?;
+    println!("{}", result);
+    for failure in &result.failed_paths {
+        println!("  {}", failure);
+    }
+    std::process::exit(result.outcome().exit_code());
+}
@@ -0,0 +1,439 @@
+//! 告警模块
+//!
+//! 批量操作失败或校验发现漂移时，按阈值生成汇总告警并投递到可插拔的 `AlertSink`。
+//!
+//! # 设计
+//! - 是否告警、告警内容由纯函数 `AlertPolicy::batch_alert`/`drift_alert` 决定
+//! - 投递通过 `AlertSink` trait 进行，内置 `WebhookSink`（`alerts-webhook` feature）与 `TestSink`
+//! - 投递失败不影响操作结果，只写入一条 `alert_failed` 日志记录
+
+use crate::registry::OperationKind;
+use crate::{APP_VERSION, BatchResult, now_iso8601};
+use amberlock_storage::OperationLog;
+use amberlock_types::{
+    LabelLevel, LockRecord, OperationOrigin, ProtectMode, Result, Settings, TargetKind,
+};
+use serde::Serialize;
+use std::fmt::{Display, Formatter};
+use std::sync::Mutex;
+
+/// 告警中最多列出的路径数
+pub const MAX_ALERT_PATHS: usize = 20;
+
+/// 告警类型
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize)]
+#[serde(rename_all = "snake_case")]
+pub enum AlertKind {
+    /// 批量操作存在失败项
+    BatchFailures,
+    /// 校验发现标签漂移
+    Drift,
+}
+
+impl Display for AlertKind {
+    fn fmt(&self, f: &mut Formatter<'_>) -> std::fmt::Result {
+        match self {
+            AlertKind::BatchFailures => write!(f, "批量操作失败"),
+            AlertKind::Drift => write!(f, "保护漂移"),
+        }
+    }
+}
+
+/// 汇总告警（序列化后即 Webhook 负载）
+#[derive(Debug, Clone, PartialEq, Eq, Serialize)]
+pub struct Alert {
+    /// 告警类型
+    pub kind: AlertKind,
+    /// 批次 ID（调用方提供时）
+    pub batch_id: Option<String>,
+    /// 操作名称（上锁/解锁/校验）
+    pub operation: String,
+    /// 处理总数
+    pub total: usize,
+    /// 失败数量
+    pub failed: usize,
+    /// 漂移数量
+    pub drifted: usize,
+    /// 失败或漂移的路径（最多 `MAX_ALERT_PATHS` 个）
+    pub top_paths: Vec<String>,
+    /// 主机名
+    pub hostname: String,
+    /// 生成时间（UTC ISO8601）
+    pub timestamp: String,
+}
+
+impl Alert {
+    /// 单行摘要（用于日志记录）
+    pub fn summary(&self) -> String {
+        format!(
+            "{}：{} 共 {} 个，失败 {} 个，漂移 {} 个",
+            self.kind, self.operation, self.total, self.failed, self.drifted
+        )
+    }
+}
+
+/// 告警投递接口
+pub trait AlertSink: Send + Sync {
+    /// 投递告警
+    fn send(&self, alert: &Alert) -> Result<()>;
+}
+
+/// 告警阈值
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct AlertPolicy {
+    min_failures: usize,
+}
+
+impl AlertPolicy {
+    /// 创建阈值（0 按 1 处理，无失败时从不告警）
+    pub fn new(min_failures: usize) -> Self {
+        Self {
+            min_failures: min_failures.max(1),
+        }
+    }
+
+    /// 从应用设置构造
+    pub fn from_settings(settings: &Settings) -> Self {
+        Self::new(settings.alert_min_failures)
+    }
+
+    /// 生效的阈值
+    pub fn min_failures(&self) -> usize {
+        self.min_failures
+    }
+
+    /// 批量操作完成后决定是否告警
+    ///
+    /// # 参数
+    /// - `kind`: 操作类型
+    /// - `result`: 批量操作结果
+    /// - `batch_id`: 批次 ID（可选）
+    ///
+    /// # 返回
+    /// 失败数量达到阈值时返回告警，否则返回 None
+    pub fn batch_alert(
+        &self,
+        kind: OperationKind,
+        result: &BatchResult,
+        batch_id: Option<&str>,
+    ) -> Option<Alert> {
+        if result.failed_count < self.min_failures {
+            return None;
+        }
+        Some(new_alert(
+            AlertKind::BatchFailures,
+            batch_id,
+            kind.to_string(),
+            result.total_count,
+            result.failed_count,
+            0,
+            &result.failed_paths,
+        ))
+    }
+
+    /// 校验扫描完成后决定是否告警
+    ///
+    /// # 参数
+    /// - `scanned`: 扫描的对象数量
+    /// - `drifted_paths`: 标签与记录不一致的路径
+    ///
+    /// # 返回
+    /// 漂移数量达到阈值时返回告警，否则返回 None
+    pub fn drift_alert(&self, scanned: usize, drifted_paths: &[String]) -> Option<Alert> {
+        if drifted_paths.len() < self.min_failures {
+            return None;
+        }
+        Some(new_alert(
+            AlertKind::Drift,
+            None,
+            "校验".to_string(),
+            scanned,
+            0,
+            drifted_paths.len(),
+            drifted_paths,
+        ))
+    }
+}
+
+/// 构造告警并截断路径列表
+fn new_alert(
+    kind: AlertKind,
+    batch_id: Option<&str>,
+    operation: String,
+    total: usize,
+    failed: usize,
+    drifted: usize,
+    paths: &[String],
+) -> Alert {
+    Alert {
+        kind,
+        batch_id: batch_id.map(str::to_string),
+        operation,
+        total,
+        failed,
+        drifted,
+        top_paths: paths.iter().take(MAX_ALERT_PATHS).cloned().collect(),
+        hostname: hostname(),
+        timestamp: now_iso8601(),
+    }
+}
+
+/// 当前主机名
+fn hostname() -> String {
+    std::env::var("COMPUTERNAME").unwrap_or_else(|_| "unknown".to_string())
+}
+
+/// 投递告警，失败时写入警告记录
+///
+/// # 注意
+/// 本函数从不返回错误：告警投递失败不得影响操作结果
+pub fn deliver_alert(sink: &dyn AlertSink, alert: &Alert, logger: &OperationLog, user_sid: &str) {
+    let Err(e) = sink.send(alert) else {
+        return;
+    };
+
+    let record = LockRecord {
+        id: logger.stamper().new_id(),
+        path: alert.top_paths.first().cloned().unwrap_or_default(),
+        kind: TargetKind::File,
+        mode: ProtectMode::ReadOnly,
+        level_applied: LabelLevel::Medium,
+        time_utc: logger.stamper().now(),
+        seq_no: logger.stamper().next_seq(),
+        user_sid: user_sid.to_string(),
+        owner_before: None,
+        sddl_before: None,
+        sddl_after: None,
+        status: "alert_failed".to_string(),
+        errors: vec![e.to_string()],
+        details: Some(alert.summary()),
+        authorized_by: None,
+        comment: None,
+        batch_id: None,
+        volume_root_acknowledged: false,
+        downgrade_reason: None,
+        origin: OperationOrigin::Unknown,
+        app_version: Some(APP_VERSION.to_string()),
+        file_id: None,
+        warnings: vec![],
+    };
+    let _ = logger.append(&record);
+}
+
+/// 内存告警收集器（测试与演练用）
+#[derive(Debug, Default)]
+pub struct TestSink {
+    alerts: Mutex<Vec<Alert>>,
+    fail: bool,
+}
+
+impl TestSink {
+    /// 创建总是投递失败的收集器
+    pub fn failing() -> Self {
+        Self {
+            fail: true,
+            ..Default::default()
+        }
+    }
+
+    /// 已收到的告警
+    pub fn alerts(&self) -> Vec<Alert> {
+        self.alerts.lock().unwrap().clone()
+    }
+}
+
+impl AlertSink for TestSink {
+    fn send(&self, alert: &Alert) -> Result<()> {
+        if self.fail {
+            return Err(amberlock_types::AmberlockError::AlertDelivery(
+                "TestSink 模拟失败".to_string(),
+            ));
+        }
+        self.alerts.lock().unwrap().push(alert.clone());
+        Ok(())
+    }
+}
+
+/// Webhook 告警：以 JSON 负载 POST 到配置的 URL
+#[cfg(feature = "alerts-webhook")]
+pub struct WebhookSink {
+    url: String,
+    agent: ureq::Agent,
+}
+
+#[cfg(feature = "alerts-webhook")]
+impl WebhookSink {
+    /// 请求超时
+    const TIMEOUT: std::time::Duration = std::time::Duration::from_secs(10);
+
+    /// 创建 Webhook 告警
+    ///
+    /// # 注意
+    /// TLS 初始化失败时仍可创建，HTTPS 地址的错误在 `send` 时返回
+    pub fn new(url: &str) -> Self {
+        let mut builder = ureq::AgentBuilder::new().timeout(Self::TIMEOUT);
+        if let Ok(tls) = native_tls::TlsConnector::new() {
+            builder = builder.tls_connector(std::sync::Arc::new(tls));
+        }
+        Self {
+            url: url.to_string(),
+            agent: builder.build(),
+        }
+    }
+}
+
+#[cfg(feature = "alerts-webhook")]
+impl AlertSink for WebhookSink {
+    fn send(&self, alert: &Alert) -> Result<()> {
+        let body = serde_json::to_string(alert)
+            .map_err(|e| amberlock_types::AmberlockError::AlertDelivery(e.to_string()))?;
+        self.agent
+            .post(&self.url)
+            .set("Content-Type", "application/json")
+            .send_string(&body)
+            .map_err(|e| amberlock_types::AmberlockError::AlertDelivery(e.to_string()))?;
+        Ok(())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use amberlock_storage::NdjsonReader;
+    use tempfile::TempDir;
+
+    fn result(total: usize, failed: usize) -> BatchResult {
+        BatchResult {
+            success_count: total - failed,
+            failed_count: failed,
+            total_count: total,
+            failed_paths: (0..failed.min(crate::MAX_FAILED_PATHS))
+                .map(|i| format!(r"C:\data\f{}.txt", i))
+                .collect(),
+            ..Default::default()
+        }
+    }
+
+    #[test]
+    fn test_batch_alert_threshold() {
+        let policy = AlertPolicy::new(3);
+
+        assert!(
+            policy
+                .batch_alert(OperationKind::Lock, &result(10, 0), None)
+                .is_none()
+        );
+        assert!(
+            policy
+                .batch_alert(OperationKind::Lock, &result(10, 2), None)
+                .is_none()
+        );
+
+        let alert = policy
+            .batch_alert(OperationKind::Lock, &result(10, 3), Some("b-1"))
+            .expect("达到阈值应告警");
+        assert_eq!(alert.kind, AlertKind::BatchFailures);
+        assert_eq!(alert.batch_id.as_deref(), Some("b-1"));
+        assert_eq!((alert.total, alert.failed, alert.drifted), (10, 3, 0));
+        assert_eq!(alert.top_paths.len(), 3);
+
+        // 阈值 0 按 1 处理，全部成功时从不告警
+        let zero = AlertPolicy::new(0);
+        assert_eq!(zero.min_failures(), 1);
+        assert!(
+            zero.batch_alert(OperationKind::Unlock, &result(5, 0), None)
+                .is_none()
+        );
+        println!("✅ 告警阈值判定正确");
+    }
+
+    #[test]
+    fn test_payload_caps_paths_and_serializes() {
+        let policy = AlertPolicy::new(1);
+        let drifted: Vec<String> = (0..50).map(|i| format!(r"C:\d\{}", i)).collect();
+        let alert = policy.drift_alert(1000, &drifted).expect("有漂移应告警");
+        assert_eq!(alert.top_paths.len(), MAX_ALERT_PATHS);
+        assert_eq!(alert.drifted, 50);
+
+        let json: serde_json::Value = serde_json::to_value(&alert).unwrap();
+        assert_eq!(json["kind"], "drift");
+        assert_eq!(json["operation"], "校验");
+        assert_eq!(json["top_paths"].as_array().unwrap().len(), MAX_ALERT_PATHS);
+        assert!(json["batch_id"].is_null());
+        assert!(json["hostname"].is_string());
+        assert!(json["timestamp"].is_string());
+    }
+
+    #[test]
+    fn test_delivery_failure_is_logged_not_propagated() {
+        let dir = TempDir::new().expect("创建临时目录失败");
+        let log_path = dir.path().join("log.ndjson");
+        let logger = OperationLog::open(&log_path).expect("打开日志失败");
+        let alert = AlertPolicy::new(1)
+            .batch_alert(OperationKind::Lock, &result(4, 2), None)
+            .unwrap();
+
+        let sink = TestSink::default();
+        deliver_alert(&sink, &alert, &logger, "S-1-5-21");
+        assert_eq!(sink.alerts(), vec![alert.clone()]);
+
+        deliver_alert(&TestSink::failing(), &alert, &logger, "S-1-5-21");
+        logger.flush().unwrap();
+
+        let mut reader = NdjsonReader::open(&log_path).expect("打开日志失败");
+        let records = reader.read_last_n(10).expect("读取日志失败");
+        assert_eq!(records.len(), 1);
+        assert_eq!(records[0]["status"], "alert_failed");
+        assert_eq!(records[0]["details"], alert.summary());
+        println!("✅ 投递失败只写入警告记录");
+    }
+
+    #[cfg(feature = "alerts-webhook")]
+    #[test]
+    fn test_webhook_posts_json_payload() {
+        use std::io::{BufRead, BufReader, Read, Write};
+        use std::net::TcpListener;
+
+        let listener = TcpListener::bind("127.0.0.1:0").expect("监听失败");
+        let url = format!("http://{}/hook", listener.local_addr().unwrap());
+
+        let server = std::thread::spawn(move || {
+            let (stream, _) = listener.accept().expect("接受连接失败");
+            let mut reader = BufReader::new(stream);
+            let mut request_line = String::new();
+            reader.read_line(&mut request_line).unwrap();
+
+            let mut length = 0;
+            loop {
+                let mut line = String::new();
+                reader.read_line(&mut line).unwrap();
+                if line == "\r\n" {
+                    break;
+                }
+                if let Some(value) = line.to_ascii_lowercase().strip_prefix("content-length:") {
+                    length = value.trim().parse().unwrap();
+                }
+            }
+            let mut body = vec![0; length];
+            reader.read_exact(&mut body).unwrap();
+            reader
+                .get_mut()
+                .write_all(b"HTTP/1.1 200 OK\r\nContent-Length: 0\r\n\r\n")
+                .unwrap();
+            (request_line, body)
+        });
+
+        let alert = AlertPolicy::new(1)
+            .batch_alert(OperationKind::Unlock, &result(3, 1), Some("b-2"))
+            .unwrap();
+        WebhookSink::new(&url).send(&alert).expect("投递失败");
+
+        let (request_line, body) = server.join().unwrap();
+        assert!(request_line.starts_with("POST /hook "));
+        let json: serde_json::Value = serde_json::from_slice(&body).expect("负载不是 JSON");
+        assert_eq!(json["kind"], "batch_failures");
+        assert_eq!(json["batch_id"], "b-2");
+        assert_eq!(json["failed"], 1);
+        println!("✅ Webhook 以 JSON 负载投递");
+    }
+}
@@ -0,0 +1,249 @@
+//! 审计日志自锁诊断与恢复
+//!
+//! 用户（或自我保护）把日志所在目录设为 High 后，以 Medium 重新启动的
+//! 进程将无法再写入日志。`OperationLog` 连续写入失败时，本模块比较
+//! 日志目录的完整性标签与当前进程级别，识别为 `AuditBlockedBySelfLock`，
+//! 并提供两种恢复方式：
+//! - 将日志切换到新的可写路径（同时原子更新设置）
+//! - 已提权时临时移除日志目录的标签，补写积压记录后恢复原标签
+
+use amberlock_storage::OperationLog;
+use amberlock_types::{AmberlockError, LabelLevel};
+use std::path::Path;
+
+/// 根据日志目录标签与进程级别判断写入失败是否由自身锁定引起
+///
+/// # 参数
+/// - `log_dir`: 日志所在目录
+/// - `dir_level`: 目录的显式标签级别（无标签为 None，即默认 Medium）
+/// - `process_level`: 当前进程完整性级别
+///
+/// # 返回
+/// 目录级别高于进程级别时返回 `AuditBlockedBySelfLock`，否则 None
+pub fn classify_log_blockage(
+    log_dir: &Path,
+    dir_level: Option<LabelLevel>,
+    process_level: LabelLevel,
+) -> Option<AmberlockError> {
+    let dir_level = dir_level?;
+    (dir_level > process_level).then(|| AmberlockError::AuditBlockedBySelfLock {
+        log_dir: log_dir.to_string_lossy().to_string(),
+        dir_level,
+        process_level,
+    })
+}
+
+/// 诊断日志持续写入失败的原因
+///
+/// # 返回
+/// - `Some(AuditBlockedBySelfLock)`: 日志目录被高于进程的标签锁定
+/// - `None`: 日志未处于持续失败状态，或失败原因不是标签锁定
+#[cfg(feature = "winsec")]
+pub fn diagnose_log_blockage(logger: &OperationLog) -> Option<AmberlockError> {
+    if !logger.is_persistently_failing() {
+        return None;
+    }
+    let path = logger.path();
+    let log_dir = path.parent()?;
+    let dir_level = amberlock_winsec::get_object_label(&log_dir.to_string_lossy())
+        .ok()
+        .map(|label| label.level);
+    let process_level = amberlock_winsec::read_process_il().ok()?;
+    classify_log_blockage(log_dir, dir_level, process_level)
+}
+
+/// 将日志切换到新的可写路径，并原子更新设置中的 `log_path`
+///
+/// # 参数
+/// - `logger`: 操作日志
+/// - `settings`: 当前设置（成功后更新 `log_path`）
+/// - `settings_path`: 设置文件路径
+/// - `new_path`: 新日志文件路径
+///
+/// # 返回
+/// 补写的积压记录数；切换失败时设置保持不变
+pub fn relocate_log(
+    logger: &OperationLog,
+    settings: &mut amberlock_types::Settings,
+    settings_path: &Path,
+    new_path: &Path,
+) -> amberlock_types::Result<usize> {
+    let written = logger.relocate(new_path)?;
+    settings.log_path = new_path.to_string_lossy().to_string();
+    amberlock_storage::save_settings(settings_path, settings)?;
+    Ok(written)
+}
+
+/// 当前进程能否移除并恢复指定级别的标签
+///
+/// 进程级别不低于标签级别，或拥有 SeRelabelPrivilege 时可以
+#[cfg(feature = "winsec")]
+pub fn can_lift_label(level: LabelLevel) -> bool {
+    let process_at_least = amberlock_winsec::read_process_il().is_ok_and(|il| il >= level);
+    process_at_least
+        || amberlock_winsec::probe_capability().is_ok_and(|probe| probe.has_se_relabel)
+}
+
+/// 临时移除日志目录的标签，补写积压记录后恢复原标签（需要提权）
+///
+/// # 返回
+/// - `Ok(n)`: 补写的记录数
+/// - `Err(ElevationRequired)`: 当前进程无法修改日志目录的标签（见 `can_lift_label`）
+///
+/// # 注意
+/// 无论补写是否成功都会尝试恢复原标签；恢复失败时返回该错误
+#[cfg(feature = "winsec")]
+pub fn flush_backlog_with_label_lifted(logger: &OperationLog) -> amberlock_types::Result<usize> {
+    let path = logger.path();
+    let log_dir = path
+        .parent()
+        .ok_or(AmberlockError::Unsupported)?
+        .to_string_lossy()
+        .to_string();
+
+    let original = amberlock_winsec::get_object_label(&log_dir)?;
+    if !can_lift_label(original.level) {
+        return Err(AmberlockError::ElevationRequired);
+    }
+
+    amberlock_winsec::remove_mandatory_label(&log_dir)?;
+    let written = logger.retry_backlog();
+    amberlock_winsec::set_mandatory_label(&log_dir, original.level)?;
+    Ok(written?)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use amberlock_types::{
+        LockRecord, OperationOrigin, ParallelismMode, ProtectMode, Schedule, Settings, TargetKind,
+    };
+    use tempfile::TempDir;
+
+    #[test]
+    fn test_classify_log_blockage() {
+        let dir = Path::new("C:\\ProgramData\\AmberLock");
+
+        let blocked = classify_log_blockage(dir, Some(LabelLevel::High), LabelLevel::Medium);
+        match blocked {
+            Some(AmberlockError::AuditBlockedBySelfLock {
+                log_dir,
+                dir_level,
+                process_level,
+            }) => {
+                assert_eq!(log_dir, "C:\\ProgramData\\AmberLock");
+                assert_eq!(dir_level, LabelLevel::High);
+                assert_eq!(process_level, LabelLevel::Medium);
+            }
+            other => panic!("应识别为自身锁定，实际为 {:?}", other),
+        }
+
+        // 无标签、同级或进程更高时都不是自身锁定
+        assert!(classify_log_blockage(dir, None, LabelLevel::Medium).is_none());
+        assert!(classify_log_blockage(dir, Some(LabelLevel::High), LabelLevel::High).is_none());
+        assert!(classify_log_blockage(dir, Some(LabelLevel::High), LabelLevel::System).is_none());
+        assert!(classify_log_blockage(dir, Some(LabelLevel::System), LabelLevel::High).is_some());
+        println!("✅ 日志目录自身锁定识别正确");
+    }
+
+    #[test]
+    fn test_relocate_log_updates_settings() {
+        let dir = TempDir::new().expect("创建临时目录失败");
+        let logger = OperationLog::open(dir.path().join("old.ndjson")).expect("打开日志失败");
+        let settings_path = dir.path().join("settings.json");
+        let new_path = dir.path().join("new.ndjson");
+        let mut settings = Settings {
+            parallelism: ParallelismMode::Fixed(4),
+            default_mode: ProtectMode::ReadOnly,
+            default_level: LabelLevel::High,
+            log_path: dir.path().join("old.ndjson").to_string_lossy().to_string(),
+            vault_path: dir.path().join("vault.bin").to_string_lossy().to_string(),
+            shell_integration: false,
+            backoff_base_ms: 500,
+            backoff_pad_success: false,
+            notifications_enabled: true,
+            alert_webhook_url: None,
+            alert_min_failures: 1,
+            read_only: false,
+            check_updates: false,
+            update_manifest_url: None,
+            last_update_check: None,
+            slow_volume_max_concurrent: 2,
+            slow_volume_min_interval_ms: 5,
+            replica_path: None,
+            schedule: Schedule::default(),
+            compress_sddl: false,
+            theme: Default::default(),
+            allow_hello_unlock: false,
+            on_cancel: Default::default(),
+            session: None,
+        };
+
+        let written =
+            relocate_log(&logger, &mut settings, &settings_path, &new_path).expect("切换失败");
+        assert_eq!(written, 0);
+        assert_eq!(settings.log_path, new_path.to_string_lossy());
+        let saved = amberlock_storage::load_settings(&settings_path).expect("读取设置失败");
+        assert_eq!(saved.log_path, settings.log_path);
+
+        logger
+            .append(&LockRecord {
+                id: logger.stamper().new_id(),
+                path: "C:\\a.txt".to_string(),
+                kind: TargetKind::File,
+                mode: ProtectMode::ReadOnly,
+                level_applied: LabelLevel::High,
+                time_utc: logger.stamper().now(),
+                seq_no: logger.stamper().next_seq(),
+                user_sid: "S-1-5-21-0".to_string(),
+                owner_before: None,
+                sddl_before: None,
+                sddl_after: None,
+                status: "success".to_string(),
+                errors: vec![],
+                details: None,
+                authorized_by: None,
+                comment: None,
+                batch_id: None,
+                volume_root_acknowledged: false,
+                downgrade_reason: None,
+                origin: OperationOrigin::Unknown,
+                app_version: None,
+                file_id: None,
+                warnings: vec![],
+            })
+            .expect("写入失败");
+        assert!(
+            std::fs::read_to_string(&new_path)
+                .unwrap()
+                .contains("C:\\\\a.txt")
+        );
+    }
+
+    #[cfg(all(windows, feature = "winsec"))]
+    fn check_elevated() -> bool {
+        amberlock_winsec::read_process_il().is_ok_and(|level| level >= LabelLevel::High)
+    }
+
+    #[test]
+    #[ignore] // 需要管理员权限
+    #[cfg(all(windows, feature = "winsec"))]
+    fn test_flush_backlog_with_label_lifted() {
+        if !check_elevated() {
+            println!("⚠️ 跳过测试：需要管理员权限");
+            return;
+        }
+
+        let dir = TempDir::new().expect("创建临时目录失败");
+        let log_dir = dir.path().to_string_lossy().to_string();
+        let logger = OperationLog::open(dir.path().join("log.ndjson")).expect("打开日志失败");
+
+        amberlock_winsec::set_mandatory_label(&log_dir, LabelLevel::High).expect("上锁失败");
+        let written = flush_backlog_with_label_lifted(&logger).expect("补写失败");
+        assert_eq!(written, 0);
+
+        let restored = amberlock_winsec::get_object_label(&log_dir).expect("读取标签失败");
+        assert_eq!(restored.level, LabelLevel::High, "补写后应恢复原标签");
+        amberlock_winsec::remove_mandatory_label(&log_dir).ok();
+    }
+}
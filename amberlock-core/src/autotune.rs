@@ -0,0 +1,501 @@
+//! 并发度自动调整
+//!
+//! `parallelism: "auto"` 时，每个卷有独立的 `AutoTuner`：从保守的并发度开始，
+//! 工作线程每处理完一个对象就送入一个样本（耗时与结果），攒满一个窗口后按 AIMD 调整：
+//! - 窗口平均延迟不超过基线的 `spike_factor` 倍：并发度加一
+//! - 延迟超过基线的 `spike_factor` 倍，或暂时性错误占比达到 `error_burst_ratio`：并发度乘以 `decrease_factor`
+//!
+//! 基线是迄今为止最低的窗口平均延迟；已降到下限仍然超标时以当前延迟为新基线，
+//! 避免持续变慢的设备把并发度永远钉在下限。窗口大小不小于当前并发度，
+//! 使每次调整后的判断基于新并发度下完成的对象。
+//!
+//! 调整器本身不涉及线程与时钟，按卷的并发上限由 `throttle` 模块的调度器读取。
+//! 批量操作结束后每个卷写入一条 `TUNING_STATUS` 记录，`details` 为调整历史。
+
+use crate::build_info::APP_VERSION;
+use crate::{BatchResult, LockOptions, LockOutcome, LockResult};
+use amberlock_storage::OperationLog;
+use amberlock_types::{AmberlockError, LockRecord, TargetKind};
+use std::collections::VecDeque;
+use std::fmt::{Display, Formatter};
+use std::time::Duration;
+
+/// 并发度调整摘要记录的状态
+pub const TUNING_STATUS: &str = "parallelism_tuned";
+
+/// 调整历史最多保留的条目数（超出后只更新计数与最终值）
+pub const MAX_TUNING_HISTORY: usize = 64;
+
+/// 自动调整参数
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct AutoTuneConfig {
+    /// 起始并发度
+    pub initial: usize,
+    /// 并发度下限
+    pub min: usize,
+    /// 并发度上限（同时也是工作线程数）
+    pub max: usize,
+    /// 每次调整前至少收集的样本数
+    pub window: usize,
+    /// 窗口平均延迟超过基线的倍数时视为延迟尖峰
+    pub spike_factor: f64,
+    /// 乘性减小的系数
+    pub decrease_factor: f64,
+    /// 窗口内暂时性错误占比达到该值时视为错误突增
+    pub error_burst_ratio: f64,
+}
+
+impl Default for AutoTuneConfig {
+    fn default() -> Self {
+        Self {
+            initial: 2,
+            min: 1,
+            max: 32,
+            window: 8,
+            spike_factor: 2.0,
+            decrease_factor: 0.5,
+            error_burst_ratio: 0.25,
+        }
+    }
+}
+
+/// 单个对象的处理结果（用于调整）
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum SampleOutcome {
+    /// 成功（含降级、跳过）
+    Success,
+    /// 暂时性错误（网络中断、共享冲突、重试后才成功）
+    Transient,
+    /// 与负载无关的失败（如拒绝访问），不参与延迟统计
+    Failure,
+}
+
+impl SampleOutcome {
+    /// 由对象的处理结果分类
+    pub fn of(outcome: &amberlock_types::Result<LockOutcome>) -> Self {
+        match outcome {
+            Ok(outcome) if outcome.result == LockResult::Recovered => SampleOutcome::Transient,
+            Ok(_) => SampleOutcome::Success,
+            Err(e) if is_transient(e) => SampleOutcome::Transient,
+            Err(_) => SampleOutcome::Failure,
+        }
+    }
+}
+
+/// 是否为负载过高时常见的暂时性错误
+///
+/// - `NetworkUnreachable`：共享暂时无响应
+/// - Win32 32/33：共享冲突、锁定冲突
+/// - Win32 64/121：网络名不再可用、信号灯超时
+pub fn is_transient(error: &AmberlockError) -> bool {
+    match error {
+        AmberlockError::NetworkUnreachable { .. } => true,
+        AmberlockError::Win32 { code, .. } => matches!(code, 32 | 33 | 64 | 121),
+        _ => false,
+    }
+}
+
+/// 最近一次调整的方向
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq)]
+pub enum ConcurrencyTrend {
+    #[default]
+    Steady,
+    Rising,
+    Falling,
+}
+
+impl Display for ConcurrencyTrend {
+    fn fmt(&self, f: &mut Formatter<'_>) -> std::fmt::Result {
+        match self {
+            ConcurrencyTrend::Steady => Ok(()),
+            ConcurrencyTrend::Rising => write!(f, "↑"),
+            ConcurrencyTrend::Falling => write!(f, "↓"),
+        }
+    }
+}
+
+/// 一次并发度变化：完成第 `completed` 个样本后变为 `concurrency`
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct TuningStep {
+    pub completed: usize,
+    pub concurrency: usize,
+}
+
+/// 按实测延迟与错误率调整并发度
+#[derive(Debug, Clone)]
+pub struct AutoTuner {
+    config: AutoTuneConfig,
+    concurrency: usize,
+    trend: ConcurrencyTrend,
+    /// 当前窗口的样本（`Failure` 的耗时不计入）
+    window: VecDeque<(Duration, SampleOutcome)>,
+    /// 最低的窗口平均延迟
+    baseline: Option<Duration>,
+    completed: usize,
+    history: Vec<TuningStep>,
+    adjustments: usize,
+    peak: usize,
+}
+
+impl AutoTuner {
+    /// 创建调整器（参数按 `min <= initial <= max` 修正）
+    pub fn new(config: AutoTuneConfig) -> Self {
+        let min = config.min.max(1);
+        let max = config.max.max(min);
+        let config = AutoTuneConfig {
+            min,
+            max,
+            initial: config.initial.clamp(min, max),
+            window: config.window.max(1),
+            ..config
+        };
+        Self {
+            config,
+            concurrency: config.initial,
+            trend: ConcurrencyTrend::Steady,
+            window: VecDeque::new(),
+            baseline: None,
+            completed: 0,
+            history: vec![TuningStep {
+                completed: 0,
+                concurrency: config.initial,
+            }],
+            adjustments: 0,
+            peak: config.initial,
+        }
+    }
+
+    /// 当前并发度
+    pub fn concurrency(&self) -> usize {
+        self.concurrency
+    }
+
+    /// 最近一次调整的方向
+    pub fn trend(&self) -> ConcurrencyTrend {
+        self.trend
+    }
+
+    /// 当前基线延迟（尚未攒满第一个窗口时为 None）
+    pub fn baseline(&self) -> Option<Duration> {
+        self.baseline
+    }
+
+    /// 并发度变化历史（首项为起始值，最多 `MAX_TUNING_HISTORY` 条）
+    pub fn history(&self) -> &[TuningStep] {
+        &self.history
+    }
+
+    /// 送入一个样本，返回（可能已调整的）并发度
+    pub fn record(&mut self, latency: Duration, outcome: SampleOutcome) -> usize {
+        self.completed += 1;
+        self.window.push_back((latency, outcome));
+        if self.window.len() >= self.config.window.max(self.concurrency) {
+            self.adjust();
+        }
+        self.concurrency
+    }
+
+    fn adjust(&mut self) {
+        let samples: Vec<(Duration, SampleOutcome)> = self.window.drain(..).collect();
+        let transient = samples
+            .iter()
+            .filter(|(_, outcome)| *outcome == SampleOutcome::Transient)
+            .count();
+        let timed: Vec<Duration> = samples
+            .iter()
+            .filter(|(_, outcome)| *outcome != SampleOutcome::Failure)
+            .map(|(latency, _)| *latency)
+            .collect();
+
+        let error_burst = transient as f64 >= self.config.error_burst_ratio * samples.len() as f64;
+        let mean = (!timed.is_empty()).then(|| timed.iter().sum::<Duration>() / timed.len() as u32);
+        let spike = match (mean, self.baseline) {
+            (Some(mean), Some(baseline)) => {
+                mean.as_secs_f64() > baseline.as_secs_f64() * self.config.spike_factor
+            }
+            _ => false,
+        };
+        if let Some(mean) = mean
+            && self.baseline.is_none_or(|baseline| mean < baseline)
+        {
+            self.baseline = Some(mean);
+        }
+
+        let next = if error_burst || spike {
+            if self.concurrency == self.config.min && spike {
+                // 已在下限：接受设备变慢的事实，以当前延迟为新基线
+                self.baseline = mean;
+            }
+            ((self.concurrency as f64 * self.config.decrease_factor) as usize).max(self.config.min)
+        } else {
+            (self.concurrency + 1).min(self.config.max)
+        };
+        self.set(next);
+    }
+
+    fn set(&mut self, next: usize) {
+        self.trend = match next.cmp(&self.concurrency) {
+            std::cmp::Ordering::Greater => ConcurrencyTrend::Rising,
+            std::cmp::Ordering::Less => ConcurrencyTrend::Falling,
+            std::cmp::Ordering::Equal => ConcurrencyTrend::Steady,
+        };
+        if next == self.concurrency {
+            return;
+        }
+        self.concurrency = next;
+        self.peak = self.peak.max(next);
+        self.adjustments += 1;
+        if self.history.len() < MAX_TUNING_HISTORY {
+            self.history.push(TuningStep {
+                completed: self.completed,
+                concurrency: next,
+            });
+        }
+    }
+
+    /// 本次运行的调整摘要
+    pub fn summary(&self, root: &str) -> VolumeTuning {
+        VolumeTuning {
+            root: root.to_string(),
+            history: self.history.clone(),
+            adjustments: self.adjustments,
+            peak: self.peak,
+            last: self.concurrency,
+        }
+    }
+}
+
+/// 单个卷的并发度调整摘要（写入批量结果与日志记录的 `details`）
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct VolumeTuning {
+    /// 卷根
+    pub root: String,
+    /// 并发度变化历史（首项为起始值）
+    pub history: Vec<TuningStep>,
+    /// 调整次数（含超出历史上限未保留的）
+    pub adjustments: usize,
+    /// 最高并发度
+    pub peak: usize,
+    /// 结束时的并发度
+    pub last: usize,
+}
+
+impl Display for VolumeTuning {
+    /// 如 `auto concurrency D:\: 2@0 3@8 4@17 2@27 (adjustments 3, peak 4, final 2)`
+    fn fmt(&self, f: &mut Formatter<'_>) -> std::fmt::Result {
+        let root = if self.root.is_empty() {
+            "?"
+        } else {
+            &self.root
+        };
+        write!(f, "auto concurrency {}:", root)?;
+        for step in &self.history {
+            write!(f, " {}@{}", step.concurrency, step.completed)?;
+        }
+        if self.adjustments + 1 > self.history.len() {
+            write!(f, " …")?;
+        }
+        write!(
+            f,
+            " (adjustments {}, peak {}, final {})",
+            self.adjustments, self.peak, self.last
+        )
+    }
+}
+
+/// 为每个自动调整过的卷写入一条摘要记录
+///
+/// # 注意
+/// 摘要写入失败不影响批量结果
+#[cfg_attr(not(feature = "winsec"), allow(dead_code))]
+pub(crate) fn log_tuning(
+    result: &BatchResult,
+    opts: &LockOptions,
+    user_sid: &str,
+    logger: &OperationLog,
+) {
+    for tuning in &result.tuning {
+        let _ = logger.append(&tuning_record(tuning, opts, user_sid, logger));
+    }
+}
+
+/// 构造调整摘要记录
+#[cfg_attr(not(feature = "winsec"), allow(dead_code))]
+fn tuning_record(
+    tuning: &VolumeTuning,
+    opts: &LockOptions,
+    user_sid: &str,
+    logger: &OperationLog,
+) -> LockRecord {
+    LockRecord {
+        id: logger.stamper().new_id(),
+        path: tuning.root.clone(),
+        kind: TargetKind::Directory,
+        mode: opts.mode,
+        level_applied: opts.desired_level,
+        time_utc: logger.stamper().now(),
+        seq_no: logger.stamper().next_seq(),
+        user_sid: user_sid.to_string(),
+        owner_before: None,
+        sddl_before: None,
+        sddl_after: None,
+        status: TUNING_STATUS.to_string(),
+        errors: vec![],
+        details: Some(tuning.to_string()),
+        authorized_by: None,
+        comment: None,
+        batch_id: opts.batch_id.clone(),
+        volume_root_acknowledged: false,
+        downgrade_reason: None,
+        origin: opts.origin,
+        app_version: Some(APP_VERSION.to_string()),
+        file_id: None,
+        warnings: vec![],
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn ms(value: u64) -> Duration {
+        Duration::from_millis(value)
+    }
+
+    /// 按脚本化的负载运行调整器：`latency(concurrency, completed)` 给出每个样本的耗时
+    fn drive(
+        tuner: &mut AutoTuner,
+        samples: usize,
+        latency: impl Fn(usize, usize) -> (Duration, SampleOutcome),
+    ) -> Vec<usize> {
+        (0..samples)
+            .map(|i| {
+                let (elapsed, outcome) = latency(tuner.concurrency(), i);
+                tuner.record(elapsed, outcome)
+            })
+            .collect()
+    }
+
+    #[test]
+    fn test_fast_volume_climbs_to_max() {
+        let mut tuner = AutoTuner::new(AutoTuneConfig {
+            max: 16,
+            ..Default::default()
+        });
+        // 本地 NVMe：延迟与并发度无关
+        let trace = drive(&mut tuner, 400, |_, _| (ms(2), SampleOutcome::Success));
+
+        assert_eq!(tuner.concurrency(), 16);
+        assert!(trace.windows(2).all(|pair| pair[1] >= pair[0]), "不应减小");
+        assert_eq!(tuner.history().first().unwrap().concurrency, 2);
+        assert_eq!(tuner.summary("c:\\").peak, 16);
+        println!("✅ 快速卷收敛到上限");
+    }
+
+    #[test]
+    fn test_slow_nas_settles_near_capacity() {
+        let mut tuner = AutoTuner::new(AutoTuneConfig::default());
+        // 小型 NAS：并发超过 4 后请求排队，单个对象的耗时随并发度线性增长
+        let trace = drive(&mut tuner, 2000, |concurrency, _| {
+            let queued = concurrency.max(4) as u64;
+            (ms(10 * queued / 4), SampleOutcome::Success)
+        });
+
+        let tail = &trace[1000..];
+        assert!(
+            tail.iter().all(|&c| (4..=9).contains(&c)),
+            "应在容量附近振荡：{:?}",
+            &tail[..50]
+        );
+        assert!(tuner.summary("\\\\nas\\share").peak <= 9);
+        println!("✅ 慢速 NAS 收敛到容量附近");
+    }
+
+    #[test]
+    fn test_degrading_disk_backs_off() {
+        let mut tuner = AutoTuner::new(AutoTuneConfig::default());
+        // 磁盘能同时服务的请求数从 16 逐渐降到 2，超出部分排队
+        let capacity = |i: usize| 16usize.saturating_sub(i / 20).max(2);
+        let disk = |concurrency: usize, cap: usize| {
+            let queued = concurrency.max(cap) as u64;
+            (ms(5 * queued / cap as u64), SampleOutcome::Success)
+        };
+        drive(&mut tuner, 300, |c, _| disk(c, capacity(0)));
+        let healthy = tuner.concurrency();
+        assert!(healthy > 8);
+
+        let trace = drive(&mut tuner, 600, |c, i| disk(c, capacity(i)));
+        assert!(
+            trace[400..].iter().all(|&c| c <= 5),
+            "{:?}",
+            tuner.history()
+        );
+        assert!(tuner.concurrency() < healthy / 2);
+        println!("✅ 设备变慢时降低并发度");
+    }
+
+    #[test]
+    fn test_decrease_on_spike_and_error_burst() {
+        let config = AutoTuneConfig {
+            initial: 8,
+            window: 4,
+            ..Default::default()
+        };
+
+        // 先建立基线，再送入一个延迟尖峰窗口：立即减半
+        let mut tuner = AutoTuner::new(config);
+        drive(&mut tuner, 8, |_, _| (ms(10), SampleOutcome::Success));
+        let before = tuner.concurrency();
+        drive(&mut tuner, before, |_, _| (ms(50), SampleOutcome::Success));
+        assert_eq!(tuner.concurrency(), before / 2);
+        assert_eq!(tuner.trend(), ConcurrencyTrend::Falling);
+        assert_eq!(tuner.trend().to_string(), "↓");
+
+        // 延迟正常但暂时性错误突增：同样减半
+        let mut tuner = AutoTuner::new(config);
+        drive(&mut tuner, 8, |_, i| match i % 4 {
+            0 => (ms(10), SampleOutcome::Transient),
+            _ => (ms(10), SampleOutcome::Success),
+        });
+        assert_eq!(tuner.concurrency(), 4);
+
+        // 与负载无关的失败不触发减小
+        let mut tuner = AutoTuner::new(config);
+        drive(&mut tuner, 8, |_, i| match i % 2 {
+            0 => (ms(1), SampleOutcome::Failure),
+            _ => (ms(10), SampleOutcome::Success),
+        });
+        assert_eq!(tuner.concurrency(), 9);
+        assert_eq!(tuner.trend(), ConcurrencyTrend::Rising);
+        println!("✅ 延迟尖峰与错误突增时乘性减小");
+    }
+
+    #[test]
+    fn test_summary_format_and_history_cap() {
+        let mut tuner = AutoTuner::new(AutoTuneConfig {
+            initial: 1,
+            max: 3,
+            window: 2,
+            ..Default::default()
+        });
+        drive(&mut tuner, 6, |_, _| (ms(1), SampleOutcome::Success));
+        assert_eq!(
+            tuner.summary(r"D:\").to_string(),
+            r"auto concurrency D:\: 1@0 2@2 3@4 (adjustments 2, peak 3, final 3)"
+        );
+
+        // 持续振荡时历史有上限，摘要以省略号标出
+        let mut tuner = AutoTuner::new(AutoTuneConfig {
+            window: 1,
+            ..Default::default()
+        });
+        drive(&mut tuner, 1000, |c, _| {
+            (ms(c as u64 * c as u64), SampleOutcome::Success)
+        });
+        let summary = tuner.summary("");
+        assert_eq!(summary.history.len(), MAX_TUNING_HISTORY);
+        assert!(summary.adjustments >= MAX_TUNING_HISTORY);
+        assert!(summary.to_string().contains(" … (adjustments"));
+        println!("✅ 调整摘要格式正确");
+    }
+}
@@ -0,0 +1,412 @@
+//! 标签读写后端
+//!
+//! 单对象上锁/解锁对标签的读写都经过 `LabelBackend`，实际运行时为
+//! `WinsecBackend`（Win32 API），测试与跟踪回放时替换为内存中的 `FakeBackend`，
+//! 两者执行完全相同的调用序列（见 `lock_object` / `unlock_object`）。
+
+use crate::interference::AccessPrecheck;
+use amberlock_types::{AmberlockError, FileId, LabelLevel, Result};
+use serde::{Deserialize, Serialize};
+use std::collections::{HashMap, VecDeque};
+use std::sync::Mutex;
+
+/// 对象当前的标签
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct ObjectLabel {
+    /// 完整 SDDL 字符串
+    pub sddl: String,
+    /// 完整性级别（无显式标签时为 Medium）
+    pub level: LabelLevel,
+}
+
+/// 后端调用类型
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash, Serialize, Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub enum LabelOp {
+    /// 读取标签
+    Get,
+    /// 设置标签
+    Set,
+    /// 移除标签
+    Remove,
+    /// 恢复 DACL 继承
+    ResetInheritance,
+    /// 设置所有者
+    SetOwner,
+}
+
+/// 标签读写后端
+pub trait LabelBackend: Send + Sync {
+    /// 读取对象标签
+    fn get_label(&self, target: &str) -> Result<ObjectLabel>;
+    /// 设置对象标签
+    fn set_label(&self, target: &str, level: LabelLevel) -> Result<()>;
+    /// 移除对象标签
+    fn remove_label(&self, target: &str) -> Result<()>;
+    /// 写入被拒绝后的权限预检（见 `interference` 模块）
+    ///
+    /// 默认视为未通过，被拒绝的写入按真实的权限错误处理
+    fn precheck_access(&self, _target: &str) -> AccessPrecheck {
+        AccessPrecheck::default()
+    }
+    /// 读取对象的文件标识（见 `moves` 模块）
+    ///
+    /// 默认不支持，记录中不写入标识，移动检测只能使用名称与大小
+    fn file_id(&self, _target: &str) -> Option<FileId> {
+        None
+    }
+    /// 读取对象的安全描述符 SDDL（所有者、组、DACL 与强制标签，见 `seal` 模块）
+    ///
+    /// 默认不支持
+    fn read_descriptor(&self, _target: &str) -> Result<String> {
+        Err(AmberlockError::Unsupported)
+    }
+    /// 恢复对象 DACL 的继承（见 `repair` 模块）
+    ///
+    /// 默认不支持
+    fn reset_dacl_inheritance(&self, _target: &str) -> Result<()> {
+        Err(AmberlockError::Unsupported)
+    }
+    /// 设置对象的所有者（见 `repair` 模块）
+    ///
+    /// 默认不支持
+    fn set_owner(&self, _target: &str, _owner_sid: &str) -> Result<()> {
+        Err(AmberlockError::Unsupported)
+    }
+}
+
+/// Win32 API 后端
+#[cfg(feature = "winsec")]
+#[derive(Debug, Clone, Copy, Default)]
+pub struct WinsecBackend;
+
+#[cfg(feature = "winsec")]
+impl LabelBackend for WinsecBackend {
+    fn get_label(&self, target: &str) -> Result<ObjectLabel> {
+        let label = amberlock_winsec::get_object_label(target)?;
+        Ok(ObjectLabel {
+            sddl: label.sddl,
+            level: label.level,
+        })
+    }
+
+    fn set_label(&self, target: &str, level: LabelLevel) -> Result<()> {
+        amberlock_winsec::set_mandatory_label(target, level)
+    }
+
+    fn remove_label(&self, target: &str) -> Result<()> {
+        amberlock_winsec::remove_mandatory_label(target)
+    }
+
+    fn precheck_access(&self, target: &str) -> AccessPrecheck {
+        AccessPrecheck {
+            has_se_security: amberlock_winsec::probe_capability()
+                .is_ok_and(|capability| capability.has_se_security),
+            label_access: amberlock_winsec::probe_label_access(target),
+        }
+    }
+    fn file_id(&self, target: &str) -> Option<FileId> {
+        amberlock_winsec::file_id_of(target).ok()
+    }
+
+    fn read_descriptor(&self, target: &str) -> Result<String> {
+        amberlock_winsec::read_security_descriptor(target)
+    }
+
+    fn reset_dacl_inheritance(&self, target: &str) -> Result<()> {
+        amberlock_winsec::reset_dacl_inheritance(target)
+    }
+
+    fn set_owner(&self, target: &str, owner_sid: &str) -> Result<()> {
+        amberlock_winsec::set_object_owner(target, owner_sid)
+    }
+}
+
+/// 单对象标签变更的结果
+#[derive(Debug)]
+pub struct LabelChange {
+    /// 变更前的标签（读取失败为 None）
+    pub before: Option<ObjectLabel>,
+    /// 变更后的标签（仅上锁成功后读取）
+    pub after: Option<ObjectLabel>,
+    /// 设置或移除调用的结果
+    pub result: Result<()>,
+}
+
+/// 上锁单个对象：读取 → 设置 → 成功时再次读取
+pub fn lock_object(backend: &dyn LabelBackend, target: &str, level: LabelLevel) -> LabelChange {
+    let before = backend.get_label(target).ok();
+    let result = backend.set_label(target, level);
+    let after = match result {
+        Ok(()) => backend.get_label(target).ok(),
+        Err(_) => None,
+    };
+    LabelChange {
+        before,
+        after,
+        result,
+    }
+}
+
+/// 解锁单个对象：读取 → 移除
+pub fn unlock_object(backend: &dyn LabelBackend, target: &str) -> LabelChange {
+    let before = backend.get_label(target).ok();
+    let result = backend.remove_label(target);
+    LabelChange {
+        before,
+        after: None,
+        result,
+    }
+}
+
+/// 内存后端对象的默认 DACL
+pub const DEFAULT_FAKE_DACL: &str = "D:PAI(A;;FA;;;SY)(A;;FA;;;BA)(A;;0x1200a9;;;BU)";
+
+/// 按级别生成只含强制标签的 SDDL（用于内存后端）
+fn label_sddl(level: LabelLevel) -> String {
+    let token = match level {
+        LabelLevel::Medium => "ME",
+        LabelLevel::High => "HI",
+        LabelLevel::System => "SI",
+    };
+    format!("S:(ML;;NW;;;{})", token)
+}
+
+/// 按（调用类别, 对象）排入的结果队列（`None` 表示正常执行）
+type Script = HashMap<(LabelOp, String), VecDeque<Option<u32>>>;
+
+/// 内存中的标签后端（测试与回放用）
+///
+/// 未显式设置的对象视为 Medium；可为某个对象的某类调用预先排入结果，
+/// 排入的结果按调用顺序逐个消耗（`None` 表示正常执行），耗尽后恢复正常行为
+#[derive(Debug, Default)]
+pub struct FakeBackend {
+    labels: Mutex<HashMap<String, LabelLevel>>,
+    scripted: Mutex<Script>,
+    calls: Mutex<Vec<(LabelOp, String)>>,
+    prechecks: Mutex<HashMap<String, AccessPrecheck>>,
+    file_ids: Mutex<HashMap<String, FileId>>,
+    dacls: Mutex<HashMap<String, String>>,
+    owners: Mutex<HashMap<String, String>>,
+}
+
+impl FakeBackend {
+    /// 创建空后端
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// 设置对象的初始标签
+    pub fn with_label(self, target: &str, level: LabelLevel) -> Self {
+        self.insert_label(target, level);
+        self
+    }
+
+    /// 设置对象当前的标签
+    pub fn insert_label(&self, target: &str, level: LabelLevel) {
+        self.labels
+            .lock()
+            .unwrap()
+            .insert(target.to_string(), level);
+    }
+
+    /// 让对象的下一次指定调用以 Win32 错误码失败
+    pub fn fail_next(&self, op: LabelOp, target: &str, code: u32) {
+        self.push_script(op, target, Some(code));
+    }
+
+    /// 为对象的下一次指定调用排入结果（`None` 为正常执行）
+    pub fn push_script(&self, op: LabelOp, target: &str, code: Option<u32>) {
+        self.scripted
+            .lock()
+            .unwrap()
+            .entry((op, target.to_string()))
+            .or_default()
+            .push_back(code);
+    }
+
+    /// 设置对象的权限预检结果（未设置时视为未通过）
+    pub fn set_precheck(&self, target: &str, precheck: AccessPrecheck) {
+        self.prechecks
+            .lock()
+            .unwrap()
+            .insert(target.to_string(), precheck);
+    }
+
+    /// 设置对象的文件标识（未设置时为 None）
+    pub fn set_file_id(&self, target: &str, file_id: FileId) {
+        self.file_ids
+            .lock()
+            .unwrap()
+            .insert(target.to_string(), file_id);
+    }
+
+    /// 设置对象的 DACL（SDDL 的 `D:` 段，未设置时为 `DEFAULT_FAKE_DACL`）
+    pub fn set_dacl(&self, target: &str, dacl: &str) {
+        self.dacls
+            .lock()
+            .unwrap()
+            .insert(target.to_string(), dacl.to_string());
+    }
+
+    /// 对象当前的所有者（未设置时为 None，描述符中显示为 `BA`）
+    pub fn owner_of(&self, target: &str) -> Option<String> {
+        self.owners.lock().unwrap().get(target).cloned()
+    }
+
+    /// 对象当前的标签级别（未设置时为 None）
+    pub fn label_of(&self, target: &str) -> Option<LabelLevel> {
+        self.labels.lock().unwrap().get(target).copied()
+    }
+
+    /// 已发生的调用（按顺序）
+    pub fn calls(&self) -> Vec<(LabelOp, String)> {
+        self.calls.lock().unwrap().clone()
+    }
+
+    fn begin(&self, op: LabelOp, target: &str) -> Result<()> {
+        self.calls.lock().unwrap().push((op, target.to_string()));
+        let scripted = self
+            .scripted
+            .lock()
+            .unwrap()
+            .get_mut(&(op, target.to_string()))
+            .and_then(VecDeque::pop_front)
+            .flatten();
+        match scripted {
+            Some(code) => Err(AmberlockError::Win32 {
+                code,
+                msg: format!("模拟的 Win32 错误 {}", code),
+            }),
+            None => Ok(()),
+        }
+    }
+}
+
+impl LabelBackend for FakeBackend {
+    fn get_label(&self, target: &str) -> Result<ObjectLabel> {
+        self.begin(LabelOp::Get, target)?;
+        let level = self.label_of(target).unwrap_or(LabelLevel::Medium);
+        Ok(ObjectLabel {
+            sddl: label_sddl(level),
+            level,
+        })
+    }
+
+    fn set_label(&self, target: &str, level: LabelLevel) -> Result<()> {
+        self.begin(LabelOp::Set, target)?;
+        self.insert_label(target, level);
+        Ok(())
+    }
+
+    fn remove_label(&self, target: &str) -> Result<()> {
+        self.begin(LabelOp::Remove, target)?;
+        self.labels.lock().unwrap().remove(target);
+        Ok(())
+    }
+
+    fn precheck_access(&self, target: &str) -> AccessPrecheck {
+        self.prechecks
+            .lock()
+            .unwrap()
+            .get(target)
+            .copied()
+            .unwrap_or_default()
+    }
+
+    fn file_id(&self, target: &str) -> Option<FileId> {
+        self.file_ids.lock().unwrap().get(target).copied()
+    }
+
+    fn read_descriptor(&self, target: &str) -> Result<String> {
+        self.begin(LabelOp::Get, target)?;
+        let dacl = self
+            .dacls
+            .lock()
+            .unwrap()
+            .get(target)
+            .cloned()
+            .unwrap_or_else(|| DEFAULT_FAKE_DACL.to_string());
+        let label = self.label_of(target).map(label_sddl).unwrap_or_default();
+        let owner = self.owner_of(target).unwrap_or_else(|| "BA".to_string());
+        Ok(format!("O:{}G:SY{}{}", owner, dacl, label))
+    }
+
+    fn reset_dacl_inheritance(&self, target: &str) -> Result<()> {
+        self.begin(LabelOp::ResetInheritance, target)?;
+        let mut dacls = self.dacls.lock().unwrap();
+        let dacl = dacls
+            .entry(target.to_string())
+            .or_insert_with(|| DEFAULT_FAKE_DACL.to_string());
+        *dacl = dacl.replacen("D:P", "D:", 1);
+        Ok(())
+    }
+
+    fn set_owner(&self, target: &str, owner_sid: &str) -> Result<()> {
+        self.begin(LabelOp::SetOwner, target)?;
+        self.owners
+            .lock()
+            .unwrap()
+            .insert(target.to_string(), owner_sid.to_string());
+        Ok(())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_lock_object_call_sequence() {
+        let backend = FakeBackend::new();
+        let change = lock_object(&backend, "C:\\a.txt", LabelLevel::High);
+
+        assert!(change.result.is_ok());
+        assert_eq!(change.before.unwrap().level, LabelLevel::Medium);
+        assert_eq!(change.after.unwrap().sddl, "S:(ML;;NW;;;HI)");
+        assert_eq!(
+            backend.calls(),
+            vec![
+                (LabelOp::Get, "C:\\a.txt".to_string()),
+                (LabelOp::Set, "C:\\a.txt".to_string()),
+                (LabelOp::Get, "C:\\a.txt".to_string()),
+            ]
+        );
+    }
+
+    #[test]
+    fn test_scripted_failure_is_consumed_once() {
+        let backend = FakeBackend::new().with_label("C:\\b", LabelLevel::High);
+        backend.fail_next(LabelOp::Remove, "C:\\b", 5);
+
+        let first = unlock_object(&backend, "C:\\b");
+        assert!(matches!(
+            first.result,
+            Err(AmberlockError::Win32 { code: 5, .. })
+        ));
+        assert_eq!(backend.label_of("C:\\b"), Some(LabelLevel::High));
+
+        let second = unlock_object(&backend, "C:\\b");
+        assert!(second.result.is_ok());
+        assert_eq!(backend.label_of("C:\\b"), None);
+        println!("✅ 预设失败只作用于一次调用");
+    }
+
+    #[test]
+    fn test_every_level_round_trips_through_fake_backend() {
+        let backend = FakeBackend::new();
+        let mut tokens = std::collections::BTreeSet::new();
+        for level in LabelLevel::ALL {
+            let target = format!("C:\\{:?}", level);
+            backend.set_label(&target, level).unwrap();
+            let label = backend.get_label(&target).unwrap();
+            assert_eq!(label.level, level);
+            assert!(
+                tokens.insert(label.sddl),
+                "{:?} 的 SDDL 与其他级别重复",
+                level
+            );
+        }
+        println!("✅ 每个完整性级别都有独立的 SDDL 标记");
+    }
+}
@@ -0,0 +1,53 @@
+//! 构建信息
+//!
+//! 构建脚本嵌入的 git 提交与构建时间。版本号写入启动报告与每条审计记录
+//! （`LockRecord.app_version`），便于把记录与发布的二进制对应起来。
+
+use time::OffsetDateTime;
+use time::format_description::well_known::Rfc3339;
+
+/// 构建时的 git 提交（12 位短哈希，不在 git 仓库中构建时为 `unknown`）
+pub const GIT_COMMIT: &str = env!("AMBERLOCK_GIT_COMMIT");
+
+/// 程序版本（`<包版本>+<git 提交>`，semver 构建元数据形式）
+pub const APP_VERSION: &str = concat!(env!("CARGO_PKG_VERSION"), "+", env!("AMBERLOCK_GIT_COMMIT"));
+
+/// 构建时间的 Unix 时间戳（秒）
+const BUILD_EPOCH: &str = env!("AMBERLOCK_BUILD_EPOCH");
+
+/// 构建时间（UTC，RFC 3339）
+///
+/// # 返回
+/// 如 `2025-06-01T08:30:00Z`；嵌入的时间戳无效时为 `unknown`
+pub fn build_time() -> String {
+    BUILD_EPOCH
+        .parse::<i64>()
+        .ok()
+        .and_then(|secs| OffsetDateTime::from_unix_timestamp(secs).ok())
+        .and_then(|time| time.format(&Rfc3339).ok())
+        .unwrap_or_else(|| "unknown".to_string())
+}
+
+/// 界面展示用的版本字符串（含构建时间）
+pub fn version_string() -> String {
+    format!("{}（构建于 {}）", APP_VERSION, build_time())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_version_embeds_commit_and_build_time() {
+        assert!(APP_VERSION.starts_with(env!("CARGO_PKG_VERSION")));
+        assert!(APP_VERSION.ends_with(&format!("+{}", GIT_COMMIT)));
+        assert!(
+            GIT_COMMIT == "unknown" || GIT_COMMIT.chars().all(|c| c.is_ascii_hexdigit()),
+            "提交哈希格式异常: {}",
+            GIT_COMMIT
+        );
+        assert_ne!(build_time(), "unknown", "构建时间应可解析");
+        assert!(version_string().contains(APP_VERSION));
+        println!("✅ 版本号: {}", version_string());
+    }
+}
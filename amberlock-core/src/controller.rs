@@ -0,0 +1,453 @@
+//! 启动阶段编排
+//!
+//! 日志与保护清单很大时，启动自检、活动趋势和最近日志的读取可能耗时数秒。
+//! `AppController` 在窗口显示后发出 `StartupEvent::WindowReady`，随后在工作线程中
+//! 按 `StartupComponent` 的顺序逐个运行启动组件，结果经事件通道逐个送回 UI 线程：
+//! - 组件在预算内完成：结果到达后再开始下一个组件，保证界面按顺序填充
+//! - 超出预算：发出 `OverBudget` 并继续下一个组件，超时的组件在后台完成后照常送回结果
+//!
+//! UI 线程将收到的事件交给 `AppController::handle` 推进 `StartupPhase`，
+//! 在全部组件完成前界面保持可用，状态栏显示仍在加载的内容。
+
+use amberlock_types::{AmberlockError, Result};
+use std::collections::BTreeSet;
+use std::fmt::{Display, Formatter};
+use std::panic::AssertUnwindSafe;
+use std::sync::mpsc::{self, Sender};
+use std::time::{Duration, Instant};
+
+/// 启动组件（按声明顺序加载）
+#[derive(Debug, Clone, Copy, PartialEq, Eq, PartialOrd, Ord, Hash)]
+pub enum StartupComponent {
+    /// 能力探测与启动自检报告
+    Capability,
+    /// 依赖设置的内容（活动趋势卡片）
+    Settings,
+    /// 最近的日志记录
+    LogTail,
+}
+
+impl StartupComponent {
+    /// 默认时间预算
+    pub fn default_budget(&self) -> Duration {
+        match self {
+            StartupComponent::Capability => Duration::from_millis(1500),
+            StartupComponent::Settings => Duration::from_millis(1000),
+            StartupComponent::LogTail => Duration::from_millis(1000),
+        }
+    }
+}
+
+impl Display for StartupComponent {
+    fn fmt(&self, f: &mut Formatter<'_>) -> std::fmt::Result {
+        match self {
+            StartupComponent::Capability => write!(f, "启动自检"),
+            StartupComponent::Settings => write!(f, "活动趋势"),
+            StartupComponent::LogTail => write!(f, "最近日志"),
+        }
+    }
+}
+
+/// 启动阶段
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum StartupPhase {
+    /// 窗口尚未显示
+    Created,
+    /// 窗口已显示，正在等待指定组件
+    Loading(StartupComponent),
+    /// 不再等待任何组件，超出预算的组件仍在后台加载
+    Partial,
+    /// 全部组件加载完成
+    Ready,
+}
+
+/// 启动组件
+pub struct StartupTask<T> {
+    component: StartupComponent,
+    budget: Duration,
+    run: Box<dyn FnOnce() -> T + Send>,
+}
+
+impl<T> StartupTask<T> {
+    /// 创建使用默认预算的组件
+    pub fn new(component: StartupComponent, run: impl FnOnce() -> T + Send + 'static) -> Self {
+        Self {
+            component,
+            budget: component.default_budget(),
+            run: Box::new(run),
+        }
+    }
+
+    /// 设置时间预算
+    pub fn with_budget(mut self, budget: Duration) -> Self {
+        self.budget = budget;
+        self
+    }
+}
+
+/// 送回 UI 线程的启动事件
+#[derive(Debug)]
+pub enum StartupEvent<T> {
+    /// 窗口已显示（在任何组件开始之前发出）
+    WindowReady,
+    /// 开始加载组件
+    Started(StartupComponent),
+    /// 组件超出预算，转入后台继续加载
+    OverBudget(StartupComponent),
+    /// 组件加载结束（组件 panic 或无法创建线程时为 `WorkerPanicked`）
+    Loaded {
+        component: StartupComponent,
+        elapsed: Duration,
+        data: Result<T>,
+    },
+}
+
+/// 启动阶段状态机（仅在 UI 线程中使用）
+#[derive(Debug)]
+pub struct AppController {
+    phase: StartupPhase,
+    /// 尚未开始的组件
+    queued: BTreeSet<StartupComponent>,
+    /// 尚未加载完成的组件
+    pending: BTreeSet<StartupComponent>,
+    /// 工作线程正在等待的组件
+    waiting: Option<StartupComponent>,
+}
+
+impl Default for AppController {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+impl AppController {
+    /// 创建状态机
+    pub fn new() -> Self {
+        Self {
+            phase: StartupPhase::Created,
+            queued: BTreeSet::new(),
+            pending: BTreeSet::new(),
+            waiting: None,
+        }
+    }
+
+    /// 当前阶段
+    pub fn phase(&self) -> StartupPhase {
+        self.phase
+    }
+
+    /// 尚未加载完成的组件
+    pub fn pending(&self) -> impl Iterator<Item = StartupComponent> + '_ {
+        self.pending.iter().copied()
+    }
+
+    /// 开始启动流程（窗口显示后调用）
+    ///
+    /// # 参数
+    /// - `tasks`: 启动组件（按组件顺序运行，与传入顺序无关）
+    /// - `sink`: 事件通道，UI 线程取出后交给 `handle`
+    ///
+    /// # 注意
+    /// 立即发出 `WindowReady` 后返回，组件在名为 `amberlock-startup` 的工作线程中运行；
+    /// 超出预算的组件各自占用一个后台线程直到完成
+    pub fn start<T: Send + 'static>(
+        &mut self,
+        mut tasks: Vec<StartupTask<T>>,
+        sink: Sender<StartupEvent<T>>,
+    ) -> Result<()> {
+        tasks.sort_by_key(|task| task.component);
+        self.queued = tasks.iter().map(|task| task.component).collect();
+        self.pending = self.queued.clone();
+
+        let _ = sink.send(StartupEvent::WindowReady);
+        std::thread::Builder::new()
+            .name("amberlock-startup".to_string())
+            .spawn(move || run_tasks(tasks, sink))
+            .map_err(|_| AmberlockError::WorkerPanicked)?;
+        Ok(())
+    }
+
+    /// 处理一个启动事件，返回新的阶段
+    pub fn handle<T>(&mut self, event: &StartupEvent<T>) -> StartupPhase {
+        match event {
+            StartupEvent::WindowReady => {}
+            StartupEvent::Started(component) => {
+                self.queued.remove(component);
+                self.waiting = Some(*component);
+            }
+            StartupEvent::OverBudget(component) => {
+                if self.waiting == Some(*component) {
+                    self.waiting = None;
+                }
+            }
+            StartupEvent::Loaded { component, .. } => {
+                self.pending.remove(component);
+                if self.waiting == Some(*component) {
+                    self.waiting = None;
+                }
+            }
+        }
+        self.phase = if self.pending.is_empty() {
+            StartupPhase::Ready
+        } else if let Some(component) = self.waiting.or(self.queued.first().copied()) {
+            StartupPhase::Loading(component)
+        } else {
+            StartupPhase::Partial
+        };
+        self.phase
+    }
+
+    /// 状态栏文本（全部加载完成后为 None）
+    pub fn status_text(&self) -> Option<String> {
+        match self.phase {
+            StartupPhase::Created => Some("正在加载…".to_string()),
+            StartupPhase::Loading(component) => Some(format!("正在加载{}…", component)),
+            StartupPhase::Partial => {
+                let names: Vec<String> = self.pending().map(|c| c.to_string()).collect();
+                Some(format!("⏳ 仍在后台加载：{}", names.join("、")))
+            }
+            StartupPhase::Ready => None,
+        }
+    }
+}
+
+/// 按顺序运行组件，每个组件最多等待其预算
+fn run_tasks<T: Send + 'static>(tasks: Vec<StartupTask<T>>, sink: Sender<StartupEvent<T>>) {
+    for task in tasks {
+        let component = task.component;
+        if sink.send(StartupEvent::Started(component)).is_err() {
+            return;
+        }
+
+        let (done_tx, done_rx) = mpsc::channel();
+        let events = sink.clone();
+        let run = task.run;
+        let spawned = std::thread::Builder::new()
+            .name(format!("amberlock-startup-{:?}", component).to_lowercase())
+            .spawn(move || {
+                let started = Instant::now();
+                let data = std::panic::catch_unwind(AssertUnwindSafe(run))
+                    .map_err(|_| AmberlockError::WorkerPanicked);
+                // 先送回结果再通知等待方，保证预算内完成的组件先于下一个组件开始
+                let _ = events.send(StartupEvent::Loaded {
+                    component,
+                    elapsed: started.elapsed(),
+                    data,
+                });
+                let _ = done_tx.send(());
+            });
+        if spawned.is_err() {
+            let _ = sink.send(StartupEvent::Loaded {
+                component,
+                elapsed: Duration::ZERO,
+                data: Err(AmberlockError::WorkerPanicked),
+            });
+            continue;
+        }
+
+        if done_rx.recv_timeout(task.budget).is_err() {
+            let _ = sink.send(StartupEvent::OverBudget(component));
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::sync::mpsc::Receiver;
+
+    /// 收集事件直到全部组件加载完成
+    fn drain(
+        controller: &mut AppController,
+        rx: &Receiver<StartupEvent<&'static str>>,
+    ) -> Vec<StartupEvent<&'static str>> {
+        let mut events = Vec::new();
+        while controller.phase() != StartupPhase::Ready {
+            let event = rx
+                .recv_timeout(Duration::from_secs(5))
+                .expect("启动事件超时");
+            controller.handle(&event);
+            events.push(event);
+        }
+        events
+    }
+
+    fn loaded(events: &[StartupEvent<&'static str>]) -> Vec<StartupComponent> {
+        events
+            .iter()
+            .filter_map(|event| match event {
+                StartupEvent::Loaded { component, .. } => Some(*component),
+                _ => None,
+            })
+            .collect()
+    }
+
+    #[test]
+    fn test_components_load_in_order_within_budget() {
+        let (tx, rx) = mpsc::channel();
+        let mut controller = AppController::new();
+        assert_eq!(controller.status_text().as_deref(), Some("正在加载…"));
+
+        // 传入顺序与加载顺序无关
+        controller
+            .start(
+                vec![
+                    StartupTask::new(StartupComponent::LogTail, || "logs"),
+                    StartupTask::new(StartupComponent::Capability, || "status"),
+                    StartupTask::new(StartupComponent::Settings, || "dashboard"),
+                ],
+                tx,
+            )
+            .expect("启动失败");
+        let events = drain(&mut controller, &rx);
+
+        assert!(matches!(events[0], StartupEvent::WindowReady));
+        assert_eq!(
+            loaded(&events),
+            vec![
+                StartupComponent::Capability,
+                StartupComponent::Settings,
+                StartupComponent::LogTail,
+            ]
+        );
+        assert!(
+            !events
+                .iter()
+                .any(|e| matches!(e, StartupEvent::OverBudget(_)))
+        );
+        assert_eq!(controller.status_text(), None);
+        println!("✅ 预算内的组件按顺序加载");
+    }
+
+    #[test]
+    fn test_window_ready_before_component_over_budget() {
+        let delay = Duration::from_millis(400);
+        let (tx, rx) = mpsc::channel();
+        let mut controller = AppController::new();
+
+        let started = Instant::now();
+        controller
+            .start(
+                vec![
+                    StartupTask::new(StartupComponent::Capability, move || {
+                        std::thread::sleep(delay);
+                        "status"
+                    })
+                    .with_budget(Duration::from_millis(20)),
+                    StartupTask::new(StartupComponent::Settings, || "dashboard"),
+                    StartupTask::new(StartupComponent::LogTail, || "logs"),
+                ],
+                tx,
+            )
+            .expect("启动失败");
+        // 不等待任何组件
+        assert!(started.elapsed() < delay);
+
+        let first = rx.recv().expect("应收到窗口就绪事件");
+        assert!(matches!(first, StartupEvent::WindowReady));
+        assert_eq!(
+            controller.handle(&first),
+            StartupPhase::Loading(StartupComponent::Capability)
+        );
+
+        let mut phases = Vec::new();
+        let mut events = vec![first];
+        while controller.phase() != StartupPhase::Ready {
+            let event = rx
+                .recv_timeout(Duration::from_secs(5))
+                .expect("启动事件超时");
+            phases.push(controller.handle(&event));
+            events.push(event);
+        }
+
+        // 超出预算的组件不阻塞后续组件，最后才完成
+        assert_eq!(
+            loaded(&events),
+            vec![
+                StartupComponent::Settings,
+                StartupComponent::LogTail,
+                StartupComponent::Capability,
+            ]
+        );
+        assert!(
+            events
+                .iter()
+                .any(|e| matches!(e, StartupEvent::OverBudget(StartupComponent::Capability)))
+        );
+        let slow = events
+            .iter()
+            .find_map(|event| match event {
+                StartupEvent::Loaded {
+                    component: StartupComponent::Capability,
+                    elapsed,
+                    data,
+                } => Some((*elapsed, data.as_ref().ok().copied())),
+                _ => None,
+            })
+            .expect("慢组件应完成");
+        assert!(slow.0 >= delay);
+        assert_eq!(slow.1, Some("status"));
+
+        // 其余组件完成后进入部分可用状态
+        assert!(phases.contains(&StartupPhase::Partial));
+        println!("✅ 窗口就绪早于超出预算的组件完成");
+    }
+
+    #[test]
+    fn test_panicking_component_does_not_stall_startup() {
+        let (tx, rx) = mpsc::channel();
+        let mut controller = AppController::new();
+        controller
+            .start(
+                vec![
+                    StartupTask::new(StartupComponent::Settings, || -> &'static str {
+                        panic!("模拟组件崩溃")
+                    }),
+                    StartupTask::new(StartupComponent::LogTail, || "logs"),
+                ],
+                tx,
+            )
+            .expect("启动失败");
+        let events = drain(&mut controller, &rx);
+
+        let failed = events.iter().any(|event| {
+            matches!(
+                event,
+                StartupEvent::Loaded {
+                    component: StartupComponent::Settings,
+                    data: Err(AmberlockError::WorkerPanicked),
+                    ..
+                }
+            )
+        });
+        assert!(failed);
+        assert_eq!(controller.phase(), StartupPhase::Ready);
+    }
+
+    #[test]
+    fn test_partial_status_lists_pending_components() {
+        let mut controller = AppController::new();
+        controller.queued = [StartupComponent::Capability, StartupComponent::LogTail].into();
+        controller.pending = controller.queued.clone();
+
+        controller.handle(&StartupEvent::<()>::Started(StartupComponent::Capability));
+        assert_eq!(
+            controller.status_text().as_deref(),
+            Some("正在加载启动自检…")
+        );
+        controller.handle(&StartupEvent::<()>::OverBudget(
+            StartupComponent::Capability,
+        ));
+        assert_eq!(
+            controller.phase(),
+            StartupPhase::Loading(StartupComponent::LogTail)
+        );
+        controller.handle(&StartupEvent::<()>::Started(StartupComponent::LogTail));
+        controller.handle(&StartupEvent::<()>::OverBudget(StartupComponent::LogTail));
+        assert_eq!(controller.phase(), StartupPhase::Partial);
+        assert_eq!(
+            controller.status_text().as_deref(),
+            Some("⏳ 仍在后台加载：启动自检、最近日志")
+        );
+    }
+}
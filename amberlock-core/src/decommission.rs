@@ -0,0 +1,720 @@
+//! 全部解除保护（停用）
+//!
+//! 停用机器或不再使用 AmberLock 时，需要移除工具施加过的全部标签，
+//! 否则卸载后遗漏的对象会永久受限。`unprotect_all` 按所选来源收集目标：
+//! - `FromInventory`：保护清单（附属文件过期或缺失时从日志重建）
+//! - `FromLog`：日志中曾经成功施加过标签的全部路径（含清单已不再跟踪的对象）
+//! - `ScanRoots`：逐个扫描目录树中带标签的对象（见 `moves::scan_labeled_objects`）
+//!
+//! 开始前必须输入确认短语 `DECOMMISSION_CONFIRM_PHRASE`，保险库密码由调用方校验。
+//! 当前为 System 级别的对象、以及以普通权限移除被拒绝的对象改用 SYSTEM 权限后端处理。
+//! 每个对象写入一条记录（同一新批次，`details` 以 `DECOMMISSION_MARKER` 开头），
+//! 结束后重新读取全部目标（扫描来源时再次扫描），仍带标签的对象列入报告。
+
+use crate::backend::LabelBackend;
+use crate::interference::is_access_denied;
+use crate::moves::{self, LabeledObject, RELOCATED_STATUS, is_not_found};
+use crate::progress::{CancelToken, ProgressTracker};
+use crate::registry::{ActiveOperations, OperationKind};
+use crate::repair::REPAIRED_STATUS;
+use crate::{
+    BatchResult, LockOutcome, LockResult, OperationContext, accumulate_outcome, inventory,
+    pathutil, readonly,
+};
+use amberlock_storage::OperationLog;
+use amberlock_storage::query::QueryBuilder;
+use amberlock_types::{
+    AmberlockError, LabelLevel, LockRecord, OperationOrigin, ProtectMode, Result, TargetKind,
+};
+use std::collections::{BTreeMap, HashSet};
+use std::fmt::{Display, Formatter};
+use std::path::{Path, PathBuf};
+
+/// 开始全部解除保护前必须输入的确认短语
+pub const DECOMMISSION_CONFIRM_PHRASE: &str = "全部解除保护";
+/// 全部解除保护记录 `details` 的前缀
+pub const DECOMMISSION_MARKER: &str = "decommission";
+/// 对象不存在或已不带标签、无需移除时的日志状态（保护清单同样视为已解除）
+pub const ALREADY_UNPROTECTED_STATUS: &str = "already_unprotected";
+
+/// 日志中表示标签已施加的状态
+const APPLIED_STATUSES: [&str; 4] = [
+    "success",
+    "success_elevated",
+    RELOCATED_STATUS,
+    REPAIRED_STATUS,
+];
+
+/// 目标来源
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum UnprotectScope {
+    /// 保护清单（推荐）
+    FromInventory,
+    /// 日志中曾经上锁的全部路径
+    FromLog,
+    /// 扫描指定目录树中带标签的对象
+    ScanRoots(Vec<PathBuf>),
+}
+
+impl Display for UnprotectScope {
+    fn fmt(&self, f: &mut Formatter<'_>) -> std::fmt::Result {
+        match self {
+            UnprotectScope::FromInventory => write!(f, "保护清单"),
+            UnprotectScope::FromLog => write!(f, "操作日志"),
+            UnprotectScope::ScanRoots(roots) => write!(f, "扫描 {} 个目录", roots.len()),
+        }
+    }
+}
+
+/// 一个待解除保护的对象
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct UnprotectTarget {
+    /// 对象路径（规范化后）
+    pub path: String,
+    /// 对象类型
+    pub kind: TargetKind,
+}
+
+/// 按来源收集待解除保护的对象
+///
+/// # 返回
+/// 按路径去重（不区分大小写），子对象排在父目录之前
+pub fn unprotect_targets(
+    backend: &dyn LabelBackend,
+    scope: &UnprotectScope,
+    log_path: impl AsRef<Path>,
+) -> Result<Vec<UnprotectTarget>> {
+    let log_path = log_path.as_ref();
+    let targets = match scope {
+        UnprotectScope::FromInventory => {
+            let (inventory, _) =
+                inventory::load_or_rebuild(inventory::inventory_path(log_path), log_path)?;
+            inventory
+                .entries()
+                .map(|entry| UnprotectTarget {
+                    path: entry.path.clone(),
+                    kind: entry.kind,
+                })
+                .collect()
+        }
+        UnprotectScope::FromLog => applied_in_log(log_path)?,
+        UnprotectScope::ScanRoots(roots) => roots
+            .iter()
+            .flat_map(|root| moves::scan_labeled_objects(backend, root))
+            .map(|object| UnprotectTarget {
+                path: object.path,
+                kind: object.kind,
+            })
+            .collect(),
+    };
+
+    let mut unique: BTreeMap<String, UnprotectTarget> = BTreeMap::new();
+    for target in targets {
+        let path = pathutil::normalized_str(&target.path);
+        unique
+            .entry(path.to_lowercase())
+            .or_insert(UnprotectTarget {
+                path,
+                kind: target.kind,
+            });
+    }
+    Ok(unique.into_values().rev().collect())
+}
+
+/// 日志中曾经成功施加过标签的路径
+fn applied_in_log(log_path: &Path) -> Result<Vec<UnprotectTarget>> {
+    if !log_path.exists() {
+        return Ok(Vec::new());
+    }
+    let records = QueryBuilder::new(log_path).execute()?;
+    Ok(records
+        .into_iter()
+        .filter_map(|value| serde_json::from_value::<LockRecord>(value).ok())
+        .filter(|record| {
+            record.level_applied != LabelLevel::Medium
+                && APPLIED_STATUSES.contains(&record.status.as_str())
+        })
+        .map(|record| UnprotectTarget {
+            path: record.path,
+            kind: record.kind,
+        })
+        .collect())
+}
+
+/// 校验确认短语（忽略首尾空白）
+///
+/// # 返回
+/// - `Err(ConfirmPhraseMismatch)`: 与 `DECOMMISSION_CONFIRM_PHRASE` 不一致
+pub fn check_confirm_phrase(phrase: &str) -> Result<()> {
+    if phrase.trim() == DECOMMISSION_CONFIRM_PHRASE {
+        Ok(())
+    } else {
+        Err(AmberlockError::ConfirmPhraseMismatch(
+            DECOMMISSION_CONFIRM_PHRASE,
+        ))
+    }
+}
+
+/// 一次全部解除保护操作
+#[derive(Debug, Clone)]
+pub struct UnprotectAll {
+    /// 目标来源
+    pub scope: UnprotectScope,
+    /// 本次操作的批次 ID
+    pub batch_id: String,
+    /// 用户 SID
+    pub user_sid: String,
+    /// 授权本次操作的保险库凭据名称
+    pub authorized_by: String,
+    /// 发起本次操作的入口
+    pub origin: OperationOrigin,
+}
+
+impl UnprotectAll {
+    /// 创建操作（批次 ID 由 `logger` 的记录 ID 来源生成）
+    ///
+    /// # 参数
+    /// - `credential`: 校验通过的保险库凭据名称
+    pub fn new(
+        scope: UnprotectScope,
+        user_sid: &str,
+        credential: &str,
+        logger: &OperationLog,
+    ) -> Self {
+        Self {
+            scope,
+            batch_id: logger.stamper().new_id(),
+            user_sid: user_sid.to_string(),
+            authorized_by: credential.to_string(),
+            origin: OperationOrigin::Unknown,
+        }
+    }
+
+    /// 记录发起本次操作的入口
+    pub fn with_origin(mut self, origin: OperationOrigin) -> Self {
+        self.origin = origin;
+        self
+    }
+
+    /// 解除单个对象的保护
+    ///
+    /// # 返回
+    /// 处理结果（对象不存在或已不带标签时为 `Skipped`），以及是否使用了 SYSTEM 权限后端
+    fn unprotect_entry(
+        &self,
+        backend: &dyn LabelBackend,
+        elevated: Option<&dyn LabelBackend>,
+        target: &UnprotectTarget,
+        logger: &OperationLog,
+    ) -> (Result<LockOutcome>, bool) {
+        let path = Path::new(&target.path);
+        if let Err(e) = pathutil::ensure_not_reserved(path) {
+            return (Err(e), false);
+        }
+        let ctx = OperationContext::new(path, &self.user_sid, logger)
+            .with_details(Some(DECOMMISSION_MARKER))
+            .with_authorized_by(Some(&self.authorized_by))
+            .with_batch_id(Some(&self.batch_id))
+            .with_origin(self.origin);
+
+        let current = backend.get_label(&ctx.target);
+        let absent = match &current {
+            Err(e) if is_not_found(e) => Some("对象不存在"),
+            Ok(label) if label.level == LabelLevel::Medium => Some("对象未带标签"),
+            _ => None,
+        };
+        if let Some(reason) = absent {
+            ctx.log_and_track(
+                ProtectMode::ReadOnly,
+                LabelLevel::Medium,
+                current.ok().map(|label| label.sddl),
+                None,
+                ALREADY_UNPROTECTED_STATUS,
+                vec![reason.to_string()],
+            );
+            return (Ok(ctx.outcome(LockResult::Skipped)), false);
+        }
+
+        let system = current
+            .as_ref()
+            .is_ok_and(|label| label.level == LabelLevel::System);
+        let sddl_before = current.ok().map(|label| label.sddl);
+        let (result, escalated) = match elevated {
+            Some(elevated) if system => (elevated.remove_label(&ctx.target), true),
+            _ => match (backend.remove_label(&ctx.target), elevated) {
+                (Err(e), Some(elevated)) if is_access_denied(&e) => {
+                    (elevated.remove_label(&ctx.target), true)
+                }
+                (result, _) => (result, false),
+            },
+        };
+
+        let ctx = if escalated {
+            let details = format!("{} (SYSTEM)", DECOMMISSION_MARKER);
+            ctx.with_details(Some(&details))
+        } else {
+            ctx
+        };
+        (ctx.log_unlock(sddl_before, result), escalated)
+    }
+}
+
+/// 全部解除保护的结果
+#[derive(Debug, Clone)]
+pub struct UnprotectReport {
+    /// 目标来源
+    pub scope: UnprotectScope,
+    /// 本次操作的批次 ID（导出记录时按此筛选）
+    pub batch_id: String,
+    /// 批量统计（不存在或已不带标签的对象计为跳过）
+    pub result: BatchResult,
+    /// 使用 SYSTEM 权限处理的对象数
+    pub escalated_count: usize,
+    /// 验证时仍带标签的对象
+    pub leftovers: Vec<LabeledObject>,
+}
+
+impl UnprotectReport {
+    /// 是否全部成功且验证没有发现残留的标签
+    pub fn is_clean(&self) -> bool {
+        self.result.failed_count == 0 && self.leftovers.is_empty()
+    }
+}
+
+impl Display for UnprotectReport {
+    fn fmt(&self, f: &mut Formatter<'_>) -> std::fmt::Result {
+        write!(f, "{}（来源：{}）", self.result, self.scope)?;
+        if self.escalated_count > 0 {
+            write!(f, "，{} 个使用 SYSTEM 权限", self.escalated_count)?;
+        }
+        if self.leftovers.is_empty() {
+            write!(f, "；验证通过，没有残留的标签")
+        } else {
+            write!(f, "；验证发现 {} 个对象仍带标签", self.leftovers.len())
+        }
+    }
+}
+
+/// 全部解除保护
+///
+/// # 参数
+/// - `backend`: 标签读写后端
+/// - `elevated`: SYSTEM 权限后端（无法提权时为 None，System 级对象仍以普通权限尝试）
+/// - `request`: 本次操作（来源、批次、授权凭据与入口）
+/// - `confirm_phrase`: 用户输入的确认短语
+/// - `logger`: 日志记录器（先刷新，确保读到全部记录）
+/// - `progress`: 进度跟踪器（收集目标后设置总数）
+///
+/// # 返回
+/// - `Ok(UnprotectReport)`: 批量统计与验证结果
+/// - `Err(ConfirmPhraseMismatch)`: 确认短语不一致（不写入任何记录）
+/// - `Err(ReadOnlyMode)`: 处于只读审计模式（每个目标都写入被拒绝记录）
+/// - `Err(ConflictingOperation)`: 与进行中的操作路径重叠
+///
+/// # 注意
+/// 本函数不校验密码，调用方须先通过 `amberlock_auth::verify_password_for` 校验；
+/// 一旦开始即处理完全部目标，不响应取消
+pub fn unprotect_all(
+    backend: &dyn LabelBackend,
+    elevated: Option<&dyn LabelBackend>,
+    request: &UnprotectAll,
+    confirm_phrase: &str,
+    logger: &OperationLog,
+    progress: &ProgressTracker,
+) -> Result<UnprotectReport> {
+    check_confirm_phrase(confirm_phrase)?;
+    logger.flush()?;
+    let targets = unprotect_targets(backend, &request.scope, logger.path())?;
+    let paths: Vec<PathBuf> = targets.iter().map(|t| PathBuf::from(&t.path)).collect();
+    readonly::ensure_batch_writable(
+        &paths,
+        ProtectMode::ReadOnly,
+        LabelLevel::Medium,
+        &request.user_sid,
+        logger,
+        None,
+    )?;
+    let _guard = ActiveOperations::global().register(
+        &paths,
+        OperationKind::Unlock,
+        CancelToken::new(),
+        false,
+    )?;
+    progress.update_total(targets.len());
+
+    let mut result = BatchResult {
+        total_count: targets.len(),
+        ..Default::default()
+    };
+    let mut escalated_count = 0;
+    for (target, path) in targets.iter().zip(&paths) {
+        let (outcome, escalated) = request.unprotect_entry(backend, elevated, target, logger);
+        escalated_count += usize::from(escalated);
+        accumulate_outcome(&mut result, path, &outcome);
+        progress.record(outcome.is_ok());
+    }
+    logger.flush()?;
+
+    Ok(UnprotectReport {
+        scope: request.scope.clone(),
+        batch_id: request.batch_id.clone(),
+        result,
+        escalated_count,
+        leftovers: still_labeled(backend, &request.scope, &targets),
+    })
+}
+
+/// 验证：重新读取全部目标，扫描来源时再次扫描各目录
+fn still_labeled(
+    backend: &dyn LabelBackend,
+    scope: &UnprotectScope,
+    targets: &[UnprotectTarget],
+) -> Vec<LabeledObject> {
+    let mut leftovers: Vec<LabeledObject> = targets
+        .iter()
+        .filter_map(|target| {
+            let canonical = pathutil::canonical_path(Path::new(&target.path));
+            let label = backend.get_label(&canonical.to_string_lossy()).ok()?;
+            (label.level != LabelLevel::Medium).then(|| LabeledObject {
+                path: target.path.clone(),
+                kind: target.kind,
+                level: label.level,
+                file_id: None,
+            })
+        })
+        .collect();
+
+    if let UnprotectScope::ScanRoots(roots) = scope {
+        let mut seen: HashSet<String> = leftovers
+            .iter()
+            .map(|object| pathutil::normalized_str(&object.path).to_lowercase())
+            .collect();
+        for root in roots {
+            for object in moves::scan_labeled_objects(backend, root) {
+                if seen.insert(pathutil::normalized_str(&object.path).to_lowercase()) {
+                    leftovers.push(object);
+                }
+            }
+        }
+    }
+    leftovers
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::backend::{FakeBackend, LabelOp, ObjectLabel};
+    use std::sync::Mutex;
+    use tempfile::TempDir;
+
+    const SID: &str = "S-1-5-21-test";
+
+    /// 与普通后端共享标签状态、记录移除调用的 SYSTEM 权限后端
+    struct Elevated<'a> {
+        inner: &'a FakeBackend,
+        removed: Mutex<Vec<String>>,
+    }
+
+    impl<'a> Elevated<'a> {
+        fn new(inner: &'a FakeBackend) -> Self {
+            Self {
+                inner,
+                removed: Mutex::new(Vec::new()),
+            }
+        }
+    }
+
+    impl LabelBackend for Elevated<'_> {
+        fn get_label(&self, target: &str) -> Result<ObjectLabel> {
+            self.inner.get_label(target)
+        }
+
+        fn set_label(&self, target: &str, level: LabelLevel) -> Result<()> {
+            self.inner.set_label(target, level)
+        }
+
+        fn remove_label(&self, target: &str) -> Result<()> {
+            self.removed.lock().unwrap().push(target.to_string());
+            self.inner.remove_label(target)
+        }
+    }
+
+    /// 对指定对象报告移除成功、实际保留标签的后端（模拟验证应发现的残留）
+    struct Stubborn<'a> {
+        inner: &'a FakeBackend,
+        keep: &'a str,
+    }
+
+    impl LabelBackend for Stubborn<'_> {
+        fn get_label(&self, target: &str) -> Result<ObjectLabel> {
+            self.inner.get_label(target)
+        }
+
+        fn set_label(&self, target: &str, level: LabelLevel) -> Result<()> {
+            self.inner.set_label(target, level)
+        }
+
+        fn remove_label(&self, target: &str) -> Result<()> {
+            if target == self.keep {
+                return Ok(());
+            }
+            self.inner.remove_label(target)
+        }
+    }
+
+    fn record(logger: &OperationLog, path: &str, status: &str, level: LabelLevel) -> LockRecord {
+        LockRecord {
+            id: logger.stamper().new_id(),
+            path: path.to_string(),
+            kind: TargetKind::File,
+            mode: ProtectMode::ReadOnly,
+            level_applied: level,
+            time_utc: logger.stamper().now(),
+            seq_no: logger.stamper().next_seq(),
+            user_sid: SID.to_string(),
+            owner_before: None,
+            sddl_before: None,
+            sddl_after: None,
+            status: status.to_string(),
+            errors: vec![],
+            details: None,
+            authorized_by: None,
+            comment: None,
+            batch_id: None,
+            volume_root_acknowledged: false,
+            downgrade_reason: None,
+            origin: OperationOrigin::GuiButton,
+            app_version: None,
+            file_id: None,
+            warnings: vec![],
+        }
+    }
+
+    /// 写入上锁历史：返回日志
+    fn history(temp_dir: &TempDir, entries: &[(&str, &str, LabelLevel)]) -> OperationLog {
+        let logger = OperationLog::open(temp_dir.path().join("log.ndjson")).expect("打开日志失败");
+        for (path, status, level) in entries {
+            logger
+                .append(&record(&logger, path, status, *level))
+                .unwrap();
+        }
+        logger.flush().unwrap();
+        logger
+    }
+
+    fn paths(targets: &[UnprotectTarget]) -> Vec<&str> {
+        targets.iter().map(|t| t.path.as_str()).collect()
+    }
+
+    #[test]
+    fn test_targets_from_each_scope() {
+        let temp_dir = TempDir::new().expect("创建临时目录失败");
+        let logger = history(
+            &temp_dir,
+            &[
+                (r"D:\data\a.txt", "success", LabelLevel::High),
+                (r"D:\data", "success", LabelLevel::High),
+                (r"D:\data\b.txt\", "success", LabelLevel::System),
+                (r"D:\data\b.txt", "unlocked", LabelLevel::Medium),
+                (r"D:\data\c.txt", "error", LabelLevel::High),
+            ],
+        );
+        let backend = FakeBackend::new();
+
+        // 没有附属文件时从日志重建清单；子对象在父目录之前
+        let targets = unprotect_targets(&backend, &UnprotectScope::FromInventory, logger.path())
+            .expect("读取保护清单失败");
+        assert_eq!(paths(&targets), vec![r"D:\data\a.txt", r"D:\data"]);
+
+        // 日志来源包括已解锁的对象，不包括从未成功的对象
+        let targets = unprotect_targets(&backend, &UnprotectScope::FromLog, logger.path())
+            .expect("读取日志失败");
+        assert_eq!(
+            paths(&targets),
+            vec![r"D:\data\b.txt", r"D:\data\a.txt", r"D:\data"]
+        );
+
+        // 扫描来源只包含当前带标签的对象
+        let root = temp_dir.path().join("tree");
+        std::fs::create_dir_all(root.join("sub")).expect("创建目录失败");
+        for name in ["x.txt", "sub/y.txt", "plain.txt"] {
+            std::fs::write(root.join(name), b"x").expect("创建文件失败");
+        }
+        let x = root.join("x.txt").to_string_lossy().to_string();
+        let y = root.join("sub").join("y.txt").to_string_lossy().to_string();
+        backend.insert_label(&x, LabelLevel::High);
+        backend.insert_label(&y, LabelLevel::System);
+        let scope = UnprotectScope::ScanRoots(vec![root.clone(), root.join("sub")]);
+        let targets = unprotect_targets(&backend, &scope, logger.path()).expect("扫描失败");
+        assert_eq!(paths(&targets), vec![x.as_str(), y.as_str()]);
+        assert_eq!(scope.to_string(), "扫描 2 个目录");
+        println!("✅ 三种来源收集到的目标正确");
+    }
+
+    #[test]
+    fn test_confirm_phrase_gating() {
+        let temp_dir = TempDir::new().expect("创建临时目录失败");
+        let logger = history(
+            &temp_dir,
+            &[(r"D:\gate\a.txt", "success", LabelLevel::High)],
+        );
+        let backend = FakeBackend::new().with_label(r"D:\gate\a.txt", LabelLevel::High);
+        let request = UnprotectAll::new(UnprotectScope::FromLog, SID, "default", &logger);
+        let progress = ProgressTracker::indeterminate();
+
+        for phrase in ["", "全部解除", "解除全部保护", "yes"] {
+            let result = unprotect_all(&backend, None, &request, phrase, &logger, &progress);
+            assert!(
+                matches!(result, Err(AmberlockError::ConfirmPhraseMismatch(_))),
+                "{:?} 不应通过确认",
+                phrase
+            );
+        }
+        assert!(backend.calls().is_empty(), "确认失败时不应访问任何对象");
+        assert_eq!(backend.label_of(r"D:\gate\a.txt"), Some(LabelLevel::High));
+
+        let phrase = format!("  {}\n", DECOMMISSION_CONFIRM_PHRASE);
+        let report = unprotect_all(&backend, None, &request, &phrase, &logger, &progress)
+            .expect("确认短语正确时应执行");
+        assert_eq!(report.result.success_count, 1);
+        assert_eq!(backend.label_of(r"D:\gate\a.txt"), None);
+        println!("✅ 确认短语不一致时不执行任何操作");
+    }
+
+    #[test]
+    fn test_escalation_fallback_selection() {
+        let (sys, denied, plain) = (r"D:\esc\sys.txt", r"D:\esc\denied.txt", r"D:\esc\plain.txt");
+        let temp_dir = TempDir::new().expect("创建临时目录失败");
+        let logger = history(
+            &temp_dir,
+            &[
+                (sys, "success_elevated", LabelLevel::System),
+                (denied, "success", LabelLevel::High),
+                (plain, "success", LabelLevel::High),
+            ],
+        );
+        let removes = |backend: &FakeBackend, target: &str| {
+            backend
+                .calls()
+                .iter()
+                .filter(|(op, t)| *op == LabelOp::Remove && t == target)
+                .count()
+        };
+
+        let backend = FakeBackend::new()
+            .with_label(sys, LabelLevel::System)
+            .with_label(denied, LabelLevel::High)
+            .with_label(plain, LabelLevel::High);
+        backend.fail_next(LabelOp::Remove, denied, 5);
+        let elevated = Elevated::new(&backend);
+        let request = UnprotectAll::new(UnprotectScope::FromLog, SID, "default", &logger)
+            .with_origin(OperationOrigin::GuiButton);
+        let report = unprotect_all(
+            &backend,
+            Some(&elevated),
+            &request,
+            DECOMMISSION_CONFIRM_PHRASE,
+            &logger,
+            &ProgressTracker::indeterminate(),
+        )
+        .expect("全部解除保护失败");
+
+        // System 级对象直接提权，普通移除被拒绝的对象再提权，其他对象不提权
+        assert_eq!(*elevated.removed.lock().unwrap(), vec![sys, denied]);
+        assert_eq!(removes(&backend, sys), 1);
+        assert_eq!(removes(&backend, denied), 2);
+        assert_eq!(removes(&backend, plain), 1);
+        assert_eq!(report.escalated_count, 2);
+        assert!(report.is_clean(), "{}", report);
+
+        let records: Vec<LockRecord> = QueryBuilder::new(logger.path())
+            .filter_custom("batch_id", &report.batch_id)
+            .execute()
+            .unwrap()
+            .into_iter()
+            .map(|value| serde_json::from_value(value).unwrap())
+            .collect();
+        assert_eq!(records.len(), 3);
+        for record in &records {
+            assert_eq!(record.status, "unlocked");
+            assert_eq!(record.origin, OperationOrigin::GuiButton);
+            assert_eq!(record.authorized_by.as_deref(), Some("default"));
+            let details = record.details.as_deref().unwrap_or_default();
+            assert!(details.starts_with(DECOMMISSION_MARKER));
+            assert_eq!(details.ends_with("(SYSTEM)"), record.path != plain);
+        }
+
+        // 无法提权时 System 级对象以普通权限尝试，失败如实计入
+        let backend = FakeBackend::new().with_label(sys, LabelLevel::System);
+        backend.fail_next(LabelOp::Remove, sys, 5);
+        let request = UnprotectAll::new(UnprotectScope::FromLog, SID, "default", &logger);
+        let report = unprotect_all(
+            &backend,
+            None,
+            &request,
+            DECOMMISSION_CONFIRM_PHRASE,
+            &logger,
+            &ProgressTracker::indeterminate(),
+        )
+        .unwrap();
+        assert_eq!(removes(&backend, sys), 1);
+        assert_eq!(report.escalated_count, 0);
+        assert_eq!(report.result.failed_count, 1);
+        assert_eq!(report.result.skipped_count, 2);
+        assert_eq!(report.leftovers.len(), 1);
+        assert_eq!(report.leftovers[0].level, LabelLevel::System);
+        println!("✅ 按当前级别与拒绝访问选择是否提权");
+    }
+
+    #[test]
+    fn test_verification_catches_leftover() {
+        let (kept, gone, done) = (r"E:\v\kept.txt", r"E:\v\gone.txt", r"E:\v\done.txt");
+        let temp_dir = TempDir::new().expect("创建临时目录失败");
+        let logger = history(
+            &temp_dir,
+            &[
+                (kept, "success", LabelLevel::High),
+                (gone, "success", LabelLevel::High),
+                (done, "success", LabelLevel::High),
+            ],
+        );
+        let backend = FakeBackend::new()
+            .with_label(kept, LabelLevel::High)
+            .with_label(done, LabelLevel::High);
+        backend.fail_next(LabelOp::Get, gone, 2);
+        let stubborn = Stubborn {
+            inner: &backend,
+            keep: kept,
+        };
+        let progress = ProgressTracker::indeterminate();
+        let request = UnprotectAll::new(UnprotectScope::FromInventory, SID, "default", &logger);
+        let report = unprotect_all(
+            &stubborn,
+            None,
+            &request,
+            DECOMMISSION_CONFIRM_PHRASE,
+            &logger,
+            &progress,
+        )
+        .expect("全部解除保护失败");
+
+        assert_eq!(report.result.success_count, 2);
+        assert_eq!(report.result.skipped_count, 1, "不存在的对象计为跳过");
+        assert_eq!(progress.snapshot().completed, 3);
+        let leftovers: Vec<&str> = report.leftovers.iter().map(|o| o.path.as_str()).collect();
+        assert_eq!(leftovers, vec![kept]);
+        assert!(!report.is_clean());
+        assert!(report.to_string().contains("验证发现 1 个对象仍带标签"));
+
+        // 不存在的对象也从保护清单中移除，残留对象按记录已解锁
+        let inventory = inventory::rebuild_inventory_from_log(logger.path()).unwrap();
+        assert!(inventory.is_empty());
+        let absent = QueryBuilder::new(logger.path())
+            .filter_status(ALREADY_UNPROTECTED_STATUS)
+            .execute()
+            .unwrap();
+        assert_eq!(absent.len(), 1);
+        assert_eq!(absent[0]["path"], gone);
+        println!("✅ 验证阶段发现未被移除的标签");
+    }
+}
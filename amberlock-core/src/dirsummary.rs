@@ -0,0 +1,706 @@
+//! 按目录汇总批量结果
+//!
+//! 递归上锁（预设、`**` 通配符）失败成千上万个时，失败往往集中在一两棵拒绝访问的子树。
+//! `LockOptions.collect_dir_summary` 开启后，`BatchResult` 按对象所在目录累计成功/失败/跳过数
+//! 与错误类型，结束时组装为 `DirSummary` 树，界面可以逐层展开并跳转到该子树的日志。
+//!
+//! 开销：关闭时每个对象只多一次 `Option` 判断；开启时计数器按目录而不是按对象分配，
+//! 且目录层级超过 `max_depth` 的对象计入第 `max_depth` 层的祖先目录，
+//! 内存只与该深度以内的目录数成正比。
+//!
+//! 有失败时批次结束写入一条 `DIR_SUMMARY_STATUS` 记录，`details` 为失败最多的目录
+//! （JSON，不超过 `DIR_SUMMARY_MAX_BYTES` 字节）。
+
+use crate::build_info::APP_VERSION;
+use crate::failures::format_count;
+use crate::trace::{UNKNOWN_ERROR_CODE, error_code};
+use crate::{BatchResult, LockOptions, LockResult};
+use amberlock_storage::OperationLog;
+use amberlock_types::{AmberlockError, LockRecord, TargetKind};
+use serde::{Deserialize, Serialize};
+use std::collections::{BTreeMap, HashMap};
+use std::fmt::{Display, Formatter};
+use std::path::Path;
+
+/// 目录汇总记录的状态
+pub const DIR_SUMMARY_STATUS: &str = "dir_summary";
+
+/// 默认的汇总深度（从盘符或共享名算起的路径段数）
+pub const DEFAULT_DIR_SUMMARY_DEPTH: usize = 6;
+
+/// 汇总记录 `details` 的最大字节数
+pub const DIR_SUMMARY_MAX_BYTES: usize = 2048;
+
+/// 单个目录（或子树）的计数
+#[derive(Debug, Clone, Default, PartialEq, Eq)]
+pub struct DirCounts {
+    /// 成功数（含降级与重试后成功）
+    pub succeeded: usize,
+    /// 失败数
+    pub failed: usize,
+    /// 跳过数
+    pub skipped: usize,
+    /// 各类错误的出现次数（见 `error_kind`）
+    pub errors: BTreeMap<String, usize>,
+}
+
+impl DirCounts {
+    fn observe(&mut self, outcome: std::result::Result<&LockResult, &AmberlockError>) {
+        match outcome {
+            Ok(LockResult::Skipped) => self.skipped += 1,
+            Ok(_) => self.succeeded += 1,
+            Err(e) => {
+                self.failed += 1;
+                *self.errors.entry(error_kind(e)).or_default() += 1;
+            }
+        }
+    }
+
+    fn merge(&mut self, other: &DirCounts) {
+        self.succeeded += other.succeeded;
+        self.failed += other.failed;
+        self.skipped += other.skipped;
+        for (kind, count) in &other.errors {
+            *self.errors.entry(kind.clone()).or_default() += count;
+        }
+    }
+
+    /// 出现次数最多的错误类型（次数相同时取名称较小者）
+    pub fn dominant_error(&self) -> Option<&str> {
+        self.errors
+            .iter()
+            .max_by(|a, b| a.1.cmp(b.1).then_with(|| b.0.cmp(a.0)))
+            .map(|(kind, _)| kind.as_str())
+    }
+}
+
+/// 批量操作中的目录计数器
+///
+/// 对象按父目录计数；父目录超过 `max_depth` 段时计入其第 `max_depth` 段的祖先
+#[derive(Debug, Clone)]
+pub struct DirAggregator {
+    max_depth: usize,
+    dirs: HashMap<String, DirCounts>,
+}
+
+impl DirAggregator {
+    /// 创建计数器（`max_depth` 至少为 1）
+    pub fn new(max_depth: usize) -> Self {
+        Self {
+            max_depth: max_depth.max(1),
+            dirs: HashMap::new(),
+        }
+    }
+
+    /// 汇总深度
+    pub fn max_depth(&self) -> usize {
+        self.max_depth
+    }
+
+    /// 已有计数器的目录数
+    pub fn dir_count(&self) -> usize {
+        self.dirs.len()
+    }
+
+    /// 累计单个对象的处理结果
+    pub fn record(
+        &mut self,
+        path: &Path,
+        outcome: std::result::Result<&LockResult, &AmberlockError>,
+    ) {
+        let text = path.to_string_lossy();
+        let key = summary_dir(&text, self.max_depth);
+        // 已有目录不再为键分配内存
+        match self.dirs.get_mut(key) {
+            Some(counts) => counts.observe(outcome),
+            None => self
+                .dirs
+                .entry(key.to_string())
+                .or_default()
+                .observe(outcome),
+        }
+    }
+
+    /// 组装为目录树（没有任何对象时为 None）
+    ///
+    /// 根节点为所有目录的最长公共祖先，每个节点的计数包含整棵子树
+    pub fn summary(&self) -> Option<DirSummary> {
+        let mut keys: Vec<(&str, Vec<usize>)> = self
+            .dirs
+            .keys()
+            .map(|key| (key.as_str(), segment_ends(key)))
+            .collect();
+        keys.sort();
+        let (first, first_ends) = keys.first()?;
+        let common = keys
+            .iter()
+            .map(|(key, ends)| {
+                ends.iter()
+                    .zip(first_ends)
+                    .take_while(|(a, b)| key[..**a] == first[..**b])
+                    .count()
+            })
+            .min()
+            .unwrap_or(0);
+
+        let root_path = match common {
+            0 => String::new(),
+            n => first[..first_ends[n - 1]].to_string(),
+        };
+        let mut root = TreeBuilder::new(root_path);
+        for (key, ends) in &keys {
+            let mut node = &mut root;
+            let counts = &self.dirs[*key];
+            node.counts.merge(counts);
+            for end in &ends[common..] {
+                node = node
+                    .children
+                    .entry(key[..*end].to_string())
+                    .or_insert_with(|| TreeBuilder::new(key[..*end].to_string()));
+                node.counts.merge(counts);
+            }
+        }
+        Some(root.build())
+    }
+
+    /// 失败最多的目录（按失败数降序，相同时按路径）
+    ///
+    /// 只统计对象直接所在（或被汇总到）的目录，不含上层目录的累计值
+    pub fn worst_offenders(&self) -> Vec<DirOffender> {
+        let mut offenders: Vec<DirOffender> = self
+            .dirs
+            .iter()
+            .filter(|(_, counts)| counts.failed > 0)
+            .map(|(path, counts)| DirOffender {
+                path: path.clone(),
+                failed: counts.failed,
+                succeeded: counts.succeeded,
+                skipped: counts.skipped,
+                error: counts.dominant_error().map(str::to_string),
+            })
+            .collect();
+        offenders.sort_by(|a, b| b.failed.cmp(&a.failed).then_with(|| a.path.cmp(&b.path)));
+        offenders
+    }
+
+    /// 失败最多的目录的 JSON（不超过 `max_bytes` 字节，放不下的目录只计入 `omitted`）
+    pub fn offenders_json(&self, max_bytes: usize) -> String {
+        let offenders = self.worst_offenders();
+        let mut report = OffenderReport {
+            dirs: Vec::new(),
+            omitted: offenders.len(),
+        };
+        for offender in offenders {
+            report.dirs.push(offender);
+            report.omitted -= 1;
+            if to_json(&report).len() > max_bytes {
+                report.dirs.pop();
+                report.omitted += 1;
+                break;
+            }
+        }
+        to_json(&report)
+    }
+}
+
+/// 失败最多的目录之一（写入汇总记录）
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+pub struct DirOffender {
+    /// 目录路径
+    pub path: String,
+    /// 失败数
+    pub failed: usize,
+    /// 成功数
+    pub succeeded: usize,
+    /// 跳过数
+    pub skipped: usize,
+    /// 主要错误类型
+    pub error: Option<String>,
+}
+
+/// 汇总记录 `details` 的内容
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+pub struct OffenderReport {
+    /// 失败最多的目录（按失败数降序）
+    pub dirs: Vec<DirOffender>,
+    /// 超出大小上限未列出的目录数
+    pub omitted: usize,
+}
+
+fn to_json(report: &OffenderReport) -> String {
+    serde_json::to_string(report).unwrap_or_default()
+}
+
+/// 按目录汇总的结果树
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct DirSummary {
+    /// 目录路径（所有对象都没有父目录时为空）
+    pub path: String,
+    /// 整棵子树的计数
+    pub counts: DirCounts,
+    /// 子目录（按失败数降序，相同时按路径）
+    pub children: Vec<DirSummary>,
+}
+
+impl DirSummary {
+    /// 目录名（路径的最后一段）
+    pub fn name(&self) -> &str {
+        self.path
+            .trim_end_matches(['\\', '/'])
+            .rsplit(['\\', '/'])
+            .next()
+            .unwrap_or("")
+    }
+
+    /// 按路径查找子树中的节点
+    pub fn find(&self, path: &str) -> Option<&DirSummary> {
+        if self.path == path {
+            return Some(self);
+        }
+        self.children.iter().find_map(|child| child.find(path))
+    }
+
+    /// 树形视图中的一行（如 `dir1\ ✗ 2,398 个失败 — ACCESS_DENIED`）
+    pub fn label(&self) -> String {
+        let name = match self.name() {
+            "" => "（根）".to_string(),
+            name => format!("{}\\", name),
+        };
+        let counts = &self.counts;
+        if counts.failed > 0 {
+            let mut text = format!("{} ✗ {} 个失败", name, format_count(counts.failed));
+            if let Some(kind) = counts.dominant_error() {
+                text.push_str(&format!(" — {}", kind));
+            }
+            text
+        } else {
+            format!("{} ✓ {} 个成功", name, format_count(counts.succeeded))
+        }
+    }
+}
+
+impl Display for DirSummary {
+    fn fmt(&self, f: &mut Formatter<'_>) -> std::fmt::Result {
+        write!(f, "{}", self.label())
+    }
+}
+
+/// 组装目录树时的中间节点（子目录按路径索引）
+struct TreeBuilder {
+    path: String,
+    counts: DirCounts,
+    children: BTreeMap<String, TreeBuilder>,
+}
+
+impl TreeBuilder {
+    fn new(path: String) -> Self {
+        Self {
+            path,
+            counts: DirCounts::default(),
+            children: BTreeMap::new(),
+        }
+    }
+
+    fn build(self) -> DirSummary {
+        let mut children: Vec<DirSummary> = self
+            .children
+            .into_values()
+            .map(TreeBuilder::build)
+            .collect();
+        children.sort_by(|a, b| {
+            b.counts
+                .failed
+                .cmp(&a.counts.failed)
+                .then_with(|| a.path.cmp(&b.path))
+        });
+        DirSummary {
+            path: self.path,
+            counts: self.counts,
+            children,
+        }
+    }
+}
+
+/// 每个路径段在原始字符串中的结束位置（`\` 与 `/` 均视为分隔符，忽略空段）
+fn segment_ends(path: &str) -> Vec<usize> {
+    let mut ends = Vec::new();
+    let mut in_segment = false;
+    for (i, c) in path.char_indices() {
+        if c == '\\' || c == '/' {
+            if in_segment {
+                ends.push(i);
+            }
+            in_segment = false;
+        } else {
+            in_segment = true;
+        }
+    }
+    if in_segment {
+        ends.push(path.len());
+    }
+    ends
+}
+
+/// 对象计入的目录：父目录，超过 `max_depth` 段时截断到第 `max_depth` 段
+fn summary_dir(path: &str, max_depth: usize) -> &str {
+    let ends = segment_ends(path);
+    let parent_segments = ends.len().saturating_sub(1).min(max_depth);
+    match parent_segments {
+        0 => "",
+        n => &path[..ends[n - 1]],
+    }
+}
+
+/// 目录的日志筛选语句（匹配该目录下的所有记录，见 `amberlock_storage::querylang`）
+pub fn subtree_query(path: &str) -> String {
+    let separator = if path.contains('/') && !path.contains('\\') {
+        '/'
+    } else {
+        '\\'
+    };
+    if path.is_empty() || path.ends_with(['\\', '/']) {
+        format!("path:\"{}\"", path)
+    } else {
+        format!("path:\"{}{}\"", path, separator)
+    }
+}
+
+/// 错误类型的简短名称（用于按目录汇总）
+///
+/// 常见 Win32 错误码使用其常量名，其余错误码为 `WIN32_<十进制>`，
+/// 非 Win32 错误使用错误种类名
+pub fn error_kind(error: &AmberlockError) -> String {
+    let code = error_code(error);
+    if code != UNKNOWN_ERROR_CODE {
+        // HRESULT_FROM_WIN32 形式按原错误码归类
+        let code = if code & 0xFFFF_0000 == 0x8007_0000 {
+            code & 0xFFFF
+        } else {
+            code
+        };
+        return match code {
+            2 => "FILE_NOT_FOUND".to_string(),
+            3 => "PATH_NOT_FOUND".to_string(),
+            5 => "ACCESS_DENIED".to_string(),
+            32 => "SHARING_VIOLATION".to_string(),
+            53 => "BAD_NETPATH".to_string(),
+            1314 => "PRIVILEGE_NOT_HELD".to_string(),
+            code => format!("WIN32_{}", code),
+        };
+    }
+    match error {
+        AmberlockError::PrivilegeMissing(_) => "PRIVILEGE_MISSING",
+        AmberlockError::Unsupported => "UNSUPPORTED",
+        AmberlockError::ReservedName(_) => "RESERVED_NAME",
+        AmberlockError::ReadOnlyMode => "READ_ONLY_MODE",
+        AmberlockError::VolumeRootModeNotAllowed(_)
+        | AmberlockError::VolumeRootNotAcknowledged(_) => "VOLUME_ROOT",
+        AmberlockError::NetworkAuthRequired { .. } => "NETWORK_AUTH_REQUIRED",
+        AmberlockError::NetworkUnreachable { .. } => "NETWORK_UNREACHABLE",
+        AmberlockError::SuspectedFilterInterference { .. } => "FILTER_INTERFERENCE",
+        AmberlockError::PlanStale { .. } => "PLAN_STALE",
+        _ => "OTHER",
+    }
+    .to_string()
+}
+
+/// 批次有失败且开启了目录汇总时写入汇总记录
+#[cfg_attr(not(feature = "winsec"), allow(dead_code))]
+pub(crate) fn log_dir_summary(
+    result: &BatchResult,
+    opts: &LockOptions,
+    user_sid: &str,
+    logger: &OperationLog,
+) {
+    if let Some(record) = dir_summary_record(result, opts, user_sid, logger) {
+        let _ = logger.append(&record);
+    }
+}
+
+/// 构造汇总记录（未开启目录汇总或没有失败时为 None）
+fn dir_summary_record(
+    result: &BatchResult,
+    opts: &LockOptions,
+    user_sid: &str,
+    logger: &OperationLog,
+) -> Option<LockRecord> {
+    let dirs = result.dirs.as_ref()?;
+    if result.failed_count == 0 {
+        return None;
+    }
+    let root = dirs.summary()?;
+    Some(LockRecord {
+        id: logger.stamper().new_id(),
+        path: root.path,
+        kind: TargetKind::Directory,
+        mode: opts.mode,
+        level_applied: opts.desired_level,
+        time_utc: logger.stamper().now(),
+        seq_no: logger.stamper().next_seq(),
+        user_sid: user_sid.to_string(),
+        owner_before: None,
+        sddl_before: None,
+        sddl_after: None,
+        status: DIR_SUMMARY_STATUS.to_string(),
+        errors: vec![],
+        details: Some(dirs.offenders_json(DIR_SUMMARY_MAX_BYTES)),
+        authorized_by: None,
+        comment: None,
+        batch_id: opts.batch_id.clone(),
+        volume_root_acknowledged: false,
+        downgrade_reason: None,
+        origin: opts.origin,
+        app_version: Some(APP_VERSION.to_string()),
+        file_id: None,
+        warnings: vec![],
+    })
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::accumulate;
+    use amberlock_storage::NdjsonReader;
+    use tempfile::TempDir;
+
+    fn denied() -> AmberlockError {
+        AmberlockError::Win32 {
+            code: 5,
+            msg: "拒绝访问".to_string(),
+        }
+    }
+
+    fn sharing() -> AmberlockError {
+        AmberlockError::Win32 {
+            code: 32,
+            msg: "文件被占用".to_string(),
+        }
+    }
+
+    /// 按脚本送入结果：`ok` 成功，`skip` 跳过，其余为错误
+    fn feed(aggregator: &mut DirAggregator, items: &[(&str, &str)]) {
+        for (path, outcome) in items {
+            let error = match *outcome {
+                "ok" => None,
+                "skip" => None,
+                "denied" => Some(denied()),
+                "sharing" => Some(sharing()),
+                other => panic!("未知结果 {}", other),
+            };
+            let result = match (*outcome, &error) {
+                ("skip", _) => Ok(&LockResult::Skipped),
+                (_, Some(e)) => Err(e),
+                _ => Ok(&LockResult::Success),
+            };
+            aggregator.record(Path::new(path), result);
+        }
+    }
+
+    fn shape(node: &DirSummary, depth: usize, out: &mut Vec<String>) {
+        out.push(format!(
+            "{}{} {}/{}/{}",
+            "  ".repeat(depth),
+            node.path,
+            node.counts.succeeded,
+            node.counts.failed,
+            node.counts.skipped
+        ));
+        for child in &node.children {
+            shape(child, depth + 1, out);
+        }
+    }
+
+    #[test]
+    fn test_tree_shape_rolls_up_subtree_counts() {
+        let mut aggregator = DirAggregator::new(DEFAULT_DIR_SUMMARY_DEPTH);
+        feed(
+            &mut aggregator,
+            &[
+                (r"D:\data\a\1.txt", "ok"),
+                (r"D:\data\a\2.txt", "ok"),
+                (r"D:\data\b\x\1.txt", "denied"),
+                (r"D:\data\b\x\2.txt", "denied"),
+                (r"D:\data\b\3.txt", "skip"),
+                (r"D:\data\top.txt", "ok"),
+            ],
+        );
+
+        let summary = aggregator.summary().expect("应有汇总");
+        let mut lines = Vec::new();
+        shape(&summary, 0, &mut lines);
+        // 根为公共祖先，失败多的子目录排在前面
+        assert_eq!(
+            lines,
+            vec![
+                r"D:\data 3/2/1",
+                r"  D:\data\b 0/2/1",
+                r"    D:\data\b\x 0/2/0",
+                r"  D:\data\a 2/0/0",
+            ]
+        );
+        assert_eq!(summary.name(), "data");
+        assert_eq!(summary.find(r"D:\data\b\x").unwrap().counts.failed, 2);
+        println!("✅ 目录树形状与子树累计计数正确");
+    }
+
+    #[test]
+    fn test_depth_cap_aggregates_into_ancestor() {
+        let mut aggregator = DirAggregator::new(3);
+        feed(
+            &mut aggregator,
+            &[
+                (r"C:\a\b\c\d\e\1.txt", "denied"),
+                (r"C:\a\b\c\2.txt", "denied"),
+                (r"C:\a\b\x\y\3.txt", "ok"),
+                (r"C:\a\4.txt", "ok"),
+            ],
+        );
+
+        // 超过 3 段的目录计入 C:\a\b
+        assert_eq!(aggregator.dir_count(), 2);
+        let summary = aggregator.summary().unwrap();
+        assert_eq!(summary.path, r"C:\a");
+        assert_eq!(summary.children.len(), 1);
+        let capped = &summary.children[0];
+        assert_eq!(capped.path, r"C:\a\b");
+        assert_eq!((capped.counts.succeeded, capped.counts.failed), (1, 2));
+        assert!(capped.children.is_empty());
+
+        // 深度不影响浅层对象与公共祖先的计算
+        assert_eq!(summary_dir("file.txt", 3), "");
+        assert_eq!(
+            summary_dir(r"\\server\share\dir\f.txt", 2),
+            r"\\server\share"
+        );
+        assert_eq!(summary_dir("/mnt/data/f.txt", 6), "/mnt/data");
+        println!("✅ 超过深度的对象计入第 N 层祖先目录");
+    }
+
+    #[test]
+    fn test_dominant_error_selection() {
+        let mut aggregator = DirAggregator::new(DEFAULT_DIR_SUMMARY_DEPTH);
+        feed(
+            &mut aggregator,
+            &[
+                (r"D:\a\1", "sharing"),
+                (r"D:\a\2", "denied"),
+                (r"D:\a\3", "denied"),
+                (r"D:\b\1", "sharing"),
+                (r"D:\b\2", "sharing"),
+                (r"D:\b\3", "sharing"),
+                (r"D:\c\1", "denied"),
+                (r"D:\c\2", "sharing"),
+            ],
+        );
+
+        let summary = aggregator.summary().unwrap();
+        assert_eq!(
+            summary.find(r"D:\a").unwrap().counts.dominant_error(),
+            Some("ACCESS_DENIED")
+        );
+        // 上层按整棵子树统计
+        assert_eq!(summary.counts.dominant_error(), Some("SHARING_VIOLATION"));
+        // 次数相同时取名称较小者，结果稳定
+        assert_eq!(
+            summary.find(r"D:\c").unwrap().counts.dominant_error(),
+            Some("ACCESS_DENIED")
+        );
+        assert_eq!(
+            summary.find(r"D:\b").unwrap().label(),
+            r"b\ ✗ 3 个失败 — SHARING_VIOLATION"
+        );
+
+        assert_eq!(
+            error_kind(&AmberlockError::Win32 {
+                code: 0x8007_0005,
+                msg: String::new()
+            }),
+            "ACCESS_DENIED"
+        );
+        assert_eq!(error_kind(&AmberlockError::Unsupported), "UNSUPPORTED");
+        println!("✅ 主要错误类型按次数选取，平局结果稳定");
+    }
+
+    #[test]
+    fn test_offenders_json_respects_size_cap() {
+        let mut aggregator = DirAggregator::new(DEFAULT_DIR_SUMMARY_DEPTH);
+        for dir in 0..200 {
+            for file in 0..=dir % 7 {
+                let path = format!(
+                    r"D:\very\long\directory\name\number_{:03}\f{}.txt",
+                    dir, file
+                );
+                aggregator.record(Path::new(&path), Err(&denied()));
+            }
+        }
+
+        let json = aggregator.offenders_json(DIR_SUMMARY_MAX_BYTES);
+        assert!(json.len() <= DIR_SUMMARY_MAX_BYTES, "{} 字节", json.len());
+        let report: OffenderReport = serde_json::from_str(&json).expect("应为合法 JSON");
+        assert!(!report.dirs.is_empty());
+        assert_eq!(report.dirs.len() + report.omitted, 200);
+        // 列出的是失败最多的目录
+        assert!(report.dirs.iter().all(|d| d.failed == 7));
+        assert_eq!(report.dirs[0].error.as_deref(), Some("ACCESS_DENIED"));
+
+        let tiny: OffenderReport = serde_json::from_str(&aggregator.offenders_json(10)).unwrap();
+        assert_eq!((tiny.dirs.len(), tiny.omitted), (0, 200));
+        println!("✅ 汇总 JSON 不超过大小上限");
+    }
+
+    #[test]
+    fn test_batch_result_collects_only_when_enabled() {
+        let path = Path::new(r"D:\data\a\1.txt");
+        let mut plain = BatchResult::default();
+        accumulate(&mut plain, path, &Err(denied()));
+        assert!(plain.dir_summary().is_none());
+
+        let mut result = BatchResult::default().with_dir_summary(DEFAULT_DIR_SUMMARY_DEPTH);
+        accumulate(&mut result, path, &Err(denied()));
+        accumulate(&mut result, path, &Ok(LockResult::Downgraded));
+        let summary = result.dir_summary().expect("应有汇总");
+        assert_eq!(summary.path, r"D:\data\a");
+        assert_eq!((summary.counts.succeeded, summary.counts.failed), (1, 1));
+
+        assert_eq!(subtree_query(r"D:\data\a"), r#"path:"D:\data\a\""#);
+        assert_eq!(subtree_query("/mnt/x/"), r#"path:"/mnt/x/""#);
+        println!("✅ 仅在开启时按目录汇总");
+    }
+
+    #[test]
+    fn test_summary_record_written_on_failure() {
+        let temp_dir = TempDir::new().expect("创建临时目录失败");
+        let log_path = temp_dir.path().join("test.log");
+        let logger = OperationLog::open(&log_path).expect("打开日志失败");
+        let opts = LockOptions {
+            collect_dir_summary: true,
+            batch_id: Some("batch-1".to_string()),
+            ..Default::default()
+        };
+
+        let mut result = opts.batch_result(2);
+        accumulate(
+            &mut result,
+            Path::new(r"D:\data\ok.txt"),
+            &Ok(LockResult::Success),
+        );
+        log_dir_summary(&result, &opts, "S-1-5-21-test", &logger);
+        accumulate(&mut result, Path::new(r"D:\data\x\bad.txt"), &Err(denied()));
+        log_dir_summary(&result, &opts, "S-1-5-21-test", &logger);
+        logger.flush().unwrap();
+
+        // 没有失败时不写入
+        let records = NdjsonReader::open(&log_path)
+            .unwrap()
+            .read_last_n(10)
+            .unwrap();
+        assert_eq!(records.len(), 1);
+        assert_eq!(records[0]["status"], DIR_SUMMARY_STATUS);
+        assert_eq!(records[0]["path"], r"D:\data");
+        assert_eq!(records[0]["batch_id"], "batch-1");
+        let report: OffenderReport =
+            serde_json::from_str(records[0]["details"].as_str().unwrap()).unwrap();
+        assert_eq!(report.dirs[0].path, r"D:\data\x");
+        println!("✅ 有失败时写入目录汇总记录");
+    }
+}
@@ -0,0 +1,192 @@
+//! 失败明细的内存上限与旁路文件
+//!
+//! 大批量操作（如整棵不可达网络共享）可能产生数十万个失败。
+//! `BatchResult` 只保留前 `failure_cap` 条明细，其余仅计数；
+//! 需要完整列表时通过 `FailureSpill` 写入与日志并列的 NDJSON 旁路文件，
+//! 内存占用只与上限成正比。
+
+use amberlock_storage::{NdjsonWriter, path_limit, safe_derived_filename};
+use amberlock_types::Result;
+use serde::{Deserialize, Serialize};
+use std::path::{Path, PathBuf};
+use std::sync::atomic::{AtomicUsize, Ordering};
+
+/// `BatchResult.failures` 默认保留的最大明细数
+pub const DEFAULT_FAILURE_CAP: usize = 1000;
+
+/// 单个失败对象的明细
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+pub struct BatchFailure {
+    /// 对象路径
+    pub path: String,
+    /// 错误描述
+    pub error: String,
+}
+
+/// 失败明细旁路文件（每行一个 `BatchFailure`）
+///
+/// 内部写入器自带互斥锁，可在工作线程间共享
+pub struct FailureSpill {
+    path: PathBuf,
+    writer: NdjsonWriter,
+    written: AtomicUsize,
+}
+
+impl FailureSpill {
+    /// 创建旁路文件（已存在时追加）
+    pub fn create<P: AsRef<Path>>(path: P) -> Result<Self> {
+        Ok(Self {
+            path: path.as_ref().to_path_buf(),
+            writer: NdjsonWriter::open_append(&path)?,
+            written: AtomicUsize::new(0),
+        })
+    }
+
+    /// 与日志文件并列的旁路文件路径：`<日志名>.failures-<批次>.ndjson`
+    ///
+    /// 批次 id 来自外部数据，经 `safe_derived_filename` 编码并限制路径长度
+    pub fn sidecar_path(log_path: &Path, batch: &str) -> Result<PathBuf> {
+        let stem = log_path
+            .file_stem()
+            .map(|s| s.to_string_lossy().to_string())
+            .unwrap_or_else(|| "amberlock".to_string());
+        let dir = log_path.parent().unwrap_or(Path::new(""));
+        Ok(safe_derived_filename(
+            dir,
+            &[&stem, &format!("failures-{}", batch)],
+            "ndjson",
+            path_limit(dir),
+        )?)
+    }
+
+    /// 旁路文件路径（写入汇总记录的 `details` 以便追溯）
+    pub fn path(&self) -> &Path {
+        &self.path
+    }
+
+    /// 已写入的失败数
+    pub fn written(&self) -> usize {
+        self.written.load(Ordering::SeqCst)
+    }
+
+    /// 追加一条失败明细
+    pub fn record(&self, path: &Path, error: &dyn std::fmt::Display) -> Result<()> {
+        self.writer.write_record(&BatchFailure {
+            path: path.to_string_lossy().to_string(),
+            error: error.to_string(),
+        })?;
+        self.written.fetch_add(1, Ordering::SeqCst);
+        Ok(())
+    }
+
+    /// 刷新到磁盘
+    pub fn flush(&self) -> Result<()> {
+        Ok(self.writer.flush()?)
+    }
+}
+
+/// 按千位分组格式化数量（如 `398,712`）
+pub(crate) fn format_count(n: usize) -> String {
+    let digits = n.to_string();
+    let mut out = String::with_capacity(digits.len() + digits.len() / 3);
+    for (i, c) in digits.chars().enumerate() {
+        if i > 0 && (digits.len() - i).is_multiple_of(3) {
+            out.push(',');
+        }
+        out.push(c);
+    }
+    out
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::{BatchResult, LockResult, MAX_FAILED_PATHS, accumulate};
+    use amberlock_storage::NdjsonReader;
+    use amberlock_types::AmberlockError;
+    use tempfile::TempDir;
+
+    const SYNTHETIC_FAILURES: usize = 400_000;
+
+    #[test]
+    fn test_failures_are_capped_with_overflow_count() {
+        let mut result = BatchResult::default().with_failure_cap(100);
+        let failure: amberlock_types::Result<LockResult> = Err(AmberlockError::Unsupported);
+        let path = PathBuf::from("\\\\nas\\share\\dead\\file.bin");
+
+        for _ in 0..SYNTHETIC_FAILURES {
+            accumulate(&mut result, &path, &failure);
+        }
+        accumulate(&mut result, &path, &Ok(LockResult::Success));
+
+        assert_eq!(result.failed_count, SYNTHETIC_FAILURES);
+        assert_eq!(result.failures.len(), 100);
+        assert!(result.failures.capacity() < 1000, "明细容量应与上限成正比");
+        assert_eq!(result.failures_omitted, SYNTHETIC_FAILURES - 100);
+        assert_eq!(result.failed_paths.len(), MAX_FAILED_PATHS);
+        assert_eq!(
+            result.failure_overflow_line().as_deref(),
+            Some("…以及另外 399,900 个失败")
+        );
+        assert_eq!(
+            result.failures[0].error,
+            AmberlockError::Unsupported.to_string()
+        );
+        println!("✅ 40 万个失败只保留 100 条明细");
+    }
+
+    #[test]
+    fn test_default_cap_and_no_overflow_line() {
+        let mut result = BatchResult::default();
+        assert_eq!(result.failure_cap, DEFAULT_FAILURE_CAP);
+        accumulate(
+            &mut result,
+            Path::new("a"),
+            &Err(AmberlockError::Unsupported),
+        );
+        assert_eq!(result.failures.len(), 1);
+        assert_eq!(result.failure_overflow_line(), None);
+    }
+
+    #[test]
+    fn test_spill_receives_every_failure() {
+        let dir = TempDir::new().expect("创建临时目录失败");
+        let log_path = dir.path().join("amberlock-log.ndjson");
+        let spill_path = FailureSpill::sidecar_path(&log_path, "b1").expect("派生旁路文件名失败");
+        assert_eq!(
+            spill_path.file_name().unwrap(),
+            "amberlock-log.failures-b1.ndjson"
+        );
+        // 批次 id 中的路径分隔符不会逃出日志目录
+        let escaped = FailureSpill::sidecar_path(&log_path, "../b1").expect("派生旁路文件名失败");
+        assert_eq!(escaped.parent(), Some(dir.path()));
+
+        let spill = FailureSpill::create(&spill_path).expect("创建旁路文件失败");
+        let mut result = BatchResult::default().with_failure_cap(10);
+        let error = AmberlockError::Unsupported;
+        for i in 0..200_000 {
+            let path = PathBuf::from(format!("D:\\dead\\{}.txt", i));
+            spill.record(&path, &error).expect("写入旁路文件失败");
+            accumulate(&mut result, &path, &Err(AmberlockError::Unsupported));
+        }
+        spill.flush().unwrap();
+
+        assert_eq!(spill.written(), 200_000);
+        assert_eq!(result.failures.len(), 10);
+
+        let mut reader = NdjsonReader::open(spill.path()).expect("打开旁路文件失败");
+        assert_eq!(reader.count_records().unwrap(), 200_000);
+        let last: BatchFailure =
+            serde_json::from_value(reader.read_last_n(1).unwrap().remove(0)).unwrap();
+        assert_eq!(last.path, "D:\\dead\\199999.txt");
+    }
+
+    #[test]
+    fn test_format_count() {
+        assert_eq!(format_count(0), "0");
+        assert_eq!(format_count(999), "999");
+        assert_eq!(format_count(1000), "1,000");
+        assert_eq!(format_count(398_712), "398,712");
+        assert_eq!(format_count(1_234_567), "1,234,567");
+    }
+}
@@ -0,0 +1,544 @@
+//! 通配符批量操作
+//!
+//! 将 `D:\Builds\**\*.dll` 之类的通配符模式展开为具体路径，再委托给批量上锁/解锁逻辑。
+//! 模式匹配遵循 Windows 语义：大小写不敏感，`\` 与 `/` 均视为路径分隔符。
+
+use crate::{BatchResult, LockOutcome, accumulate_outcome};
+#[cfg(feature = "winsec")]
+use crate::{
+    LockOptions, OperationContext,
+    ops::{lock_with_details, unlock_with_context},
+};
+#[cfg(feature = "winsec")]
+use amberlock_storage::OperationLog;
+use amberlock_types::{AmberlockError, Result};
+#[cfg(feature = "winsec")]
+use amberlock_types::LabelLevel;
+use globset::{Glob, GlobBuilder, GlobSetBuilder};
+use std::collections::{BTreeMap, HashSet};
+use std::path::{Path, PathBuf};
+use walkdir::WalkDir;
+
+/// 默认匹配数量上限
+pub const DEFAULT_MAX_MATCHES: usize = 10_000;
+
+/// 通配符展开选项
+#[derive(Debug, Clone)]
+pub struct ExpandOptions {
+    /// 相对模式的基准目录（None 表示当前工作目录）
+    pub base_dir: Option<PathBuf>,
+    /// 遍历时是否跟随符号链接/目录联接
+    pub follow_symlinks: bool,
+    /// 排除模式（语法与匹配模式相同）
+    pub exclude: Vec<String>,
+    /// 匹配数量上限，超过时返回 `TooManyMatches`
+    pub max_matches: usize,
+}
+
+impl Default for ExpandOptions {
+    fn default() -> Self {
+        Self {
+            base_dir: None,
+            follow_symlinks: false,
+            exclude: Vec::new(),
+            max_matches: DEFAULT_MAX_MATCHES,
+        }
+    }
+}
+
+/// 通配符批量操作结果
+#[derive(Debug, Clone, Default)]
+pub struct GlobBatchResult {
+    /// 批量操作统计
+    pub batch: BatchResult,
+    /// 未匹配到任何对象的模式
+    pub unmatched_patterns: Vec<String>,
+}
+
+impl GlobBatchResult {
+    /// 是否存在未匹配任何对象的模式（需要向用户提示）
+    pub fn has_unmatched(&self) -> bool {
+        !self.unmatched_patterns.is_empty()
+    }
+}
+
+/// 单个模式编译后的信息
+struct CompiledPattern {
+    /// 原始模式文本（写入审计日志）
+    original: String,
+    /// 遍历起点（模式中第一个通配段之前的字面前缀）
+    root: PathBuf,
+    /// 相对于起点的最大深度（含 `**` 时为 None）
+    max_depth: Option<usize>,
+    /// 不含通配符时为字面路径
+    literal: bool,
+}
+
+/// 展开通配符模式
+///
+/// # 参数
+/// - `patterns`: 通配符模式列表（绝对路径或相对于 `base_dir`）
+/// - `opts`: 展开选项
+///
+/// # 返回
+/// 去重后的匹配路径；未匹配任何对象时返回空列表而非错误
+///
+/// # 注意
+/// 匹配数量超过 `opts.max_matches` 时立即停止遍历并返回 `TooManyMatches`，
+/// 避免误写的 `**` 枚举整个卷
+pub fn expand_glob(patterns: &[String], opts: &ExpandOptions) -> Result<Vec<PathBuf>> {
+    Ok(expand_grouped(patterns, opts)?
+        .into_iter()
+        .flat_map(|(_, paths)| paths)
+        .collect())
+}
+
+/// 按模式分组展开，返回 `(原始模式, 匹配路径)` 列表（保持模式顺序）
+///
+/// 同一路径被多个模式匹配时只归属于第一个模式
+fn expand_grouped(
+    patterns: &[String],
+    opts: &ExpandOptions,
+) -> Result<Vec<(String, Vec<PathBuf>)>> {
+    let base_dir = match &opts.base_dir {
+        Some(dir) => dir.clone(),
+        None => std::env::current_dir().map_err(|e| AmberlockError::Storage(e.into()))?,
+    };
+
+    let mut compiled = Vec::with_capacity(patterns.len());
+    let mut builder = GlobSetBuilder::new();
+    for pattern in patterns {
+        let (normalized, info) = compile_pattern(pattern, &base_dir);
+        builder.add(build_glob(&normalized)?);
+        compiled.push(info);
+    }
+    let set = builder
+        .build()
+        .map_err(|e| AmberlockError::InvalidPattern(e.to_string()))?;
+
+    let mut exclude_builder = GlobSetBuilder::new();
+    for pattern in &opts.exclude {
+        // 以 `**` 开头的排除模式不锚定到基准目录
+        let normalized = if pattern.starts_with("**") {
+            normalize(pattern)
+        } else {
+            compile_pattern(pattern, &base_dir).0
+        };
+        exclude_builder.add(build_glob(&normalized)?);
+    }
+    let exclude = exclude_builder
+        .build()
+        .map_err(|e| AmberlockError::InvalidPattern(e.to_string()))?;
+
+    let mut groups: Vec<Vec<PathBuf>> = vec![Vec::new(); compiled.len()];
+    let mut seen = HashSet::new();
+    let mut total = 0usize;
+
+    // 按遍历起点合并，每个起点只遍历一次
+    let mut roots: BTreeMap<PathBuf, Option<usize>> = BTreeMap::new();
+    for info in compiled.iter().filter(|c| !c.literal) {
+        roots
+            .entry(info.root.clone())
+            .and_modify(|depth| {
+                *depth = match (*depth, info.max_depth) {
+                    (Some(a), Some(b)) => Some(a.max(b)),
+                    _ => None,
+                }
+            })
+            .or_insert(info.max_depth);
+    }
+
+    // 字面路径：存在即匹配
+    for (index, info) in compiled.iter().enumerate().filter(|(_, c)| c.literal) {
+        let normalized = normalize(&info.root.to_string_lossy());
+        if info.root.exists() && !exclude.is_match(&normalized) {
+            push_match(&mut groups[index], &mut seen, &mut total, &info.root, opts)?;
+        }
+    }
+
+    for (root, max_depth) in roots {
+        let mut walker = WalkDir::new(&root).follow_links(opts.follow_symlinks);
+        if let Some(depth) = max_depth {
+            walker = walker.max_depth(depth);
+        }
+
+        let entries = walker
+            .into_iter()
+            .filter_entry(|entry| !exclude.is_match(normalize(&entry.path().to_string_lossy())));
+
+        // 遍历中的访问错误（权限不足等）直接跳过
+        for entry in entries.flatten() {
+            let normalized = normalize(&entry.path().to_string_lossy());
+            let matched = set
+                .matches(&normalized)
+                .into_iter()
+                .find(|&i| !compiled[i].literal);
+            if let Some(index) = matched {
+                push_match(
+                    &mut groups[index],
+                    &mut seen,
+                    &mut total,
+                    entry.path(),
+                    opts,
+                )?;
+            }
+        }
+    }
+
+    Ok(compiled
+        .into_iter()
+        .map(|c| c.original)
+        .zip(groups)
+        .collect())
+}
+
+/// 记录一个匹配项（大小写不敏感去重），超过上限时返回错误
+fn push_match(
+    group: &mut Vec<PathBuf>,
+    seen: &mut HashSet<String>,
+    total: &mut usize,
+    path: &Path,
+    opts: &ExpandOptions,
+) -> Result<()> {
+    if seen.insert(normalize(&path.to_string_lossy()).to_lowercase()) {
+        *total += 1;
+        if *total > opts.max_matches {
+            return Err(AmberlockError::TooManyMatches {
+                limit: opts.max_matches,
+            });
+        }
+        group.push(path.to_path_buf());
+    }
+    Ok(())
+}
+
+/// 规范化模式：统一分隔符、补全相对路径，并计算遍历起点
+fn compile_pattern(pattern: &str, base_dir: &Path) -> (String, CompiledPattern) {
+    let pattern_path = Path::new(pattern);
+    let normalized = if pattern_path.is_absolute() || pattern.starts_with(['\\', '/']) {
+        normalize(pattern)
+    } else {
+        // 基准目录中的字符按字面处理
+        let base = globset::escape(&normalize(&base_dir.to_string_lossy()));
+        format!("{}/{}", base.trim_end_matches('/'), normalize(pattern))
+    };
+
+    let components: Vec<&str> = normalized.split('/').collect();
+    let first_wild = components.iter().position(|c| is_wild(c));
+
+    let info = match first_wild {
+        None => CompiledPattern {
+            original: pattern.to_string(),
+            root: native_path(&unescape(&normalized)),
+            max_depth: Some(0),
+            literal: true,
+        },
+        Some(index) => {
+            let mut root = unescape(&components[..index].join("/"));
+            // "D:" 需补全为 "D:/"，否则表示驱动器当前目录
+            if root.ends_with(':') || root.is_empty() {
+                root.push('/');
+            }
+            let rest = &components[index..];
+            CompiledPattern {
+                original: pattern.to_string(),
+                root: native_path(&root),
+                max_depth: if rest.iter().any(|c| c.contains("**")) {
+                    None
+                } else {
+                    Some(rest.len())
+                },
+                literal: false,
+            }
+        }
+    };
+
+    (normalized, info)
+}
+
+/// 转换回系统分隔符（`\\?\` 前缀下 `/` 不被视为分隔符）
+fn native_path(path: &str) -> PathBuf {
+    PathBuf::from(path.replace('/', std::path::MAIN_SEPARATOR_STR))
+}
+
+/// 编译单个模式（大小写不敏感，`*` 不跨越分隔符）
+fn build_glob(normalized: &str) -> Result<Glob> {
+    GlobBuilder::new(normalized)
+        .case_insensitive(true)
+        .literal_separator(true)
+        .backslash_escape(false)
+        .build()
+        .map_err(|e| AmberlockError::InvalidPattern(e.to_string()))
+}
+
+/// 统一为 `/` 分隔符，并转义扩展长度前缀 `\\?\` 中的 `?`
+fn normalize(path: &str) -> String {
+    let normalized = path.replace('\\', "/");
+    match normalized.strip_prefix("//?/") {
+        Some(rest) => format!("//[?]/{}", rest),
+        None => normalized,
+    }
+}
+
+/// 判断路径段是否包含通配符（忽略 `globset::escape` 生成的 `[x]` 转义）
+fn is_wild(component: &str) -> bool {
+    let chars: Vec<char> = component.chars().collect();
+    let mut i = 0;
+    while i < chars.len() {
+        if chars[i] == '[' && i + 2 < chars.len() && chars[i + 2] == ']' {
+            i += 3;
+        } else if matches!(chars[i], '*' | '?' | '[' | '{') {
+            return true;
+        } else {
+            i += 1;
+        }
+    }
+    false
+}
+
+/// 还原 `globset::escape` 生成的单字符类（`[*]` → `*`）
+fn unescape(pattern: &str) -> String {
+    let mut out = String::with_capacity(pattern.len());
+    let chars: Vec<char> = pattern.chars().collect();
+    let mut i = 0;
+    while i < chars.len() {
+        if chars[i] == '[' && i + 2 < chars.len() && chars[i + 2] == ']' {
+            out.push(chars[i + 1]);
+            i += 3;
+        } else {
+            out.push(chars[i]);
+            i += 1;
+        }
+    }
+    out
+}
+
+/// 按通配符批量上锁
+///
+/// # 参数
+/// - `patterns`: 通配符模式列表
+/// - `expand`: 展开选项
+/// - `opts`: 锁定选项
+/// - `effective_level`: 有效完整性级别
+/// - `user_sid`: 用户 SID
+/// - `logger`: 日志记录器
+///
+/// # 返回
+/// 批量统计及未匹配的模式；每条日志记录的 `details` 为 `glob: <原始模式>`，
+/// `opts.collect_dir_summary` 开启且有失败时最后写入一条目录汇总记录
+#[cfg(feature = "winsec")]
+pub fn batch_lock_glob(
+    patterns: &[String],
+    expand: &ExpandOptions,
+    opts: &LockOptions,
+    effective_level: LabelLevel,
+    user_sid: &str,
+    logger: &OperationLog,
+) -> Result<GlobBatchResult> {
+    let opts = opts.in_batch(logger);
+    let result = run_glob(patterns, expand, opts.batch_result(0), |path, details| {
+        lock_with_details(path, &opts, effective_level, user_sid, logger, Some(details))
+    })?;
+    crate::dirsummary::log_dir_summary(&result.batch, &opts, user_sid, logger);
+    Ok(result)
+}
+
+/// 按通配符批量解锁
+///
+/// # 参数
+/// - `patterns`: 通配符模式列表
+/// - `expand`: 展开选项
+/// - `user_sid`: 用户 SID
+/// - `logger`: 日志记录器
+/// - `comment`: 操作备注，写入每条记录
+#[cfg(feature = "winsec")]
+pub fn batch_unlock_glob(
+    patterns: &[String],
+    expand: &ExpandOptions,
+    user_sid: &str,
+    logger: &OperationLog,
+    comment: Option<&str>,
+) -> Result<GlobBatchResult> {
+    let batch_id = logger.stamper().new_id();
+    run_glob(patterns, expand, BatchResult::default(), |path, details| {
+        unlock_with_context(
+            OperationContext::new(path, user_sid, logger)
+                .with_details(Some(details))
+                .with_comment(comment)
+                .with_batch_id(Some(&batch_id)),
+        )
+    })
+}
+
+/// 展开模式并对每个匹配项执行操作，结果累计到 `batch`
+#[cfg_attr(not(feature = "winsec"), allow(dead_code))]
+fn run_glob<F, O>(
+    patterns: &[String],
+    expand: &ExpandOptions,
+    batch: BatchResult,
+    mut op: F,
+) -> Result<GlobBatchResult>
+where
+    F: FnMut(&Path, &str) -> Result<O>,
+    O: Into<LockOutcome>,
+{
+    let mut result = GlobBatchResult {
+        batch,
+        ..Default::default()
+    };
+
+    for (pattern, paths) in expand_grouped(patterns, expand)? {
+        if paths.is_empty() {
+            result.unmatched_patterns.push(pattern);
+            continue;
+        }
+
+        let details = format!("glob: {}", pattern);
+        for path in paths {
+            result.batch.total_count += 1;
+            let outcome = op(&path, &details).map(Into::into);
+            accumulate_outcome(&mut result.batch, &path, &outcome);
+        }
+    }
+
+    Ok(result)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::fs::{self, File};
+    use tempfile::TempDir;
+
+    /// 构建测试目录树：
+    /// ```text
+    /// root/
+    ///   app.dll
+    ///   readme.txt
+    ///   bin/core.DLL
+    ///   bin/deep/extra.dll
+    ///   node_modules/dep.dll
+    /// ```
+    fn fixture() -> TempDir {
+        let dir = TempDir::new().expect("创建临时目录失败");
+        let root = dir.path();
+        fs::create_dir_all(root.join("bin/deep")).expect("创建目录失败");
+        fs::create_dir_all(root.join("node_modules")).expect("创建目录失败");
+        for file in [
+            "app.dll",
+            "readme.txt",
+            "bin/core.DLL",
+            "bin/deep/extra.dll",
+            "node_modules/dep.dll",
+        ] {
+            File::create(root.join(file)).expect("创建文件失败");
+        }
+        dir
+    }
+
+    fn opts_for(dir: &TempDir) -> ExpandOptions {
+        ExpandOptions {
+            base_dir: Some(dir.path().to_path_buf()),
+            ..Default::default()
+        }
+    }
+
+    fn names(paths: &[PathBuf]) -> Vec<String> {
+        let mut names: Vec<String> = paths
+            .iter()
+            .map(|p| p.file_name().unwrap().to_string_lossy().to_lowercase())
+            .collect();
+        names.sort();
+        names
+    }
+
+    #[test]
+    fn test_single_star_does_not_cross_directories() {
+        let dir = fixture();
+        let paths = expand_glob(&["*.dll".to_string()], &opts_for(&dir)).expect("展开失败");
+
+        assert_eq!(names(&paths), vec!["app.dll"]);
+    }
+
+    #[test]
+    fn test_recursive_case_insensitive_with_backslashes() {
+        let dir = fixture();
+        let paths = expand_glob(&["**\\*.dll".to_string()], &opts_for(&dir)).expect("展开失败");
+
+        assert_eq!(
+            names(&paths),
+            vec!["app.dll", "core.dll", "dep.dll", "extra.dll"]
+        );
+        println!("✅ 反斜杠与大小写不敏感匹配正常");
+    }
+
+    #[test]
+    fn test_absolute_pattern_and_exclusion() {
+        let dir = fixture();
+        let pattern = format!("{}\\**\\*.dll", dir.path().display());
+        let opts = ExpandOptions {
+            exclude: vec!["**/node_modules".to_string(), "**/BIN/deep/**".to_string()],
+            ..Default::default()
+        };
+
+        let paths = expand_glob(&[pattern], &opts).expect("展开失败");
+
+        assert_eq!(names(&paths), vec!["app.dll", "core.dll"]);
+    }
+
+    #[test]
+    fn test_overlapping_patterns_are_deduplicated() {
+        let dir = fixture();
+        let patterns = vec!["bin/*.dll".to_string(), "**/*.dll".to_string()];
+        let grouped = expand_grouped(&patterns, &opts_for(&dir)).expect("展开失败");
+
+        assert_eq!(names(&grouped[0].1), vec!["core.dll"]);
+        assert_eq!(
+            names(&grouped[1].1),
+            vec!["app.dll", "dep.dll", "extra.dll"]
+        );
+    }
+
+    #[test]
+    fn test_match_cap() {
+        let dir = fixture();
+        let opts = ExpandOptions {
+            max_matches: 2,
+            ..opts_for(&dir)
+        };
+
+        let err = expand_glob(&["**/*".to_string()], &opts).expect_err("应超过匹配上限");
+        assert!(matches!(err, AmberlockError::TooManyMatches { limit: 2 }));
+    }
+
+    #[test]
+    fn test_no_match_is_empty_not_error() {
+        let dir = fixture();
+        let patterns = vec!["**/*.exe".to_string(), "missing.txt".to_string()];
+        let grouped = expand_grouped(&patterns, &opts_for(&dir)).expect("展开失败");
+
+        assert!(grouped.iter().all(|(_, paths)| paths.is_empty()));
+
+        // 未匹配的模式不会触发任何操作，只在结果中标记
+        let result = run_glob(
+            &patterns,
+            &opts_for(&dir),
+            BatchResult::default(),
+            |_, _| -> Result<crate::LockResult> { panic!("不应对任何路径执行操作") },
+        )
+        .expect("展开失败");
+        assert!(result.has_unmatched());
+        assert_eq!(result.unmatched_patterns, patterns);
+        assert_eq!(result.batch.total_count, 0);
+    }
+
+    #[test]
+    fn test_literal_path_and_invalid_pattern() {
+        let dir = fixture();
+        let paths = expand_glob(&["readme.txt".to_string()], &opts_for(&dir)).expect("展开失败");
+        assert_eq!(paths.len(), 1);
+
+        let err = expand_glob(&["bin/[".to_string()], &opts_for(&dir)).expect_err("应拒绝无效模式");
+        assert!(matches!(err, AmberlockError::InvalidPattern(_)));
+    }
+}
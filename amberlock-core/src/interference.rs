@@ -0,0 +1,289 @@
+//! 安全软件拦截诊断
+//!
+//! 部分终端安全产品的文件系统过滤驱动会间歇性拒绝 SACL 写入：
+//! 标签写入返回拒绝访问，同一进程片刻之后却又能成功。本模块在写入被拒绝时
+//! 做保守的判定，避免把这类失败误当作权限问题排查：
+//! - 预检：进程持有 SeSecurityPrivilege，且能以写入标签所需的访问权限打开对象。
+//!   预检未通过时一律视为真实的权限错误，不重新分类
+//! - 预检通过时短暂等待后重试一次；重试成功即判定为疑似拦截（`LockResult::Recovered`）
+//! - 重试仍失败时返回 `SuspectedFilterInterference`，但只有整批中这类失败零散出现
+//!   （占比低、没有成片连续失败）时才计入批量摘要；成片出现的失败更可能是策略性的
+
+use crate::LockResult;
+use crate::backend::{LabelBackend, LabelChange, lock_object};
+use crate::trace::error_code;
+use amberlock_types::{AmberlockError, LabelLevel};
+use std::time::Duration;
+
+/// 预检通过后重试前的等待时间
+pub const INTERFERENCE_RETRY_DELAY: Duration = Duration::from_millis(200);
+/// 计入摘要时，重试仍失败的对象占已处理对象的最大百分比
+pub const SCATTERED_MAX_PERCENT: usize = 20;
+/// 计入摘要时，允许的最长连续失败数
+pub const SCATTERED_MAX_RUN: usize = 2;
+
+/// ERROR_ACCESS_DENIED
+const ACCESS_DENIED: u32 = 5;
+
+/// 写入被拒绝后的权限预检结果
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq)]
+pub struct AccessPrecheck {
+    /// 进程持有 SeSecurityPrivilege
+    pub has_se_security: bool,
+    /// 能以 `WRITE_OWNER | ACCESS_SYSTEM_SECURITY` 打开对象
+    pub label_access: bool,
+}
+
+impl AccessPrecheck {
+    /// 两项检查都通过
+    pub fn passed(&self) -> bool {
+        self.has_se_security && self.label_access
+    }
+}
+
+/// 单个对象写入标签的拦截判定
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Interference {
+    /// 没有被拒绝，或预检未通过（真实的权限错误）
+    None,
+    /// 预检通过，重试后成功
+    Recovered,
+    /// 预检通过，重试仍被拒绝
+    Suspected,
+}
+
+/// 错误是否为拒绝访问（含 HRESULT 形式）
+pub fn is_access_denied(error: &AmberlockError) -> bool {
+    let code = error_code(error);
+    code == ACCESS_DENIED || code == 0x8007_0000 | ACCESS_DENIED
+}
+
+/// 上锁单个对象，写入被拒绝且预检通过时等待后重试一次
+///
+/// # 参数
+/// - `backend`: 标签后端（提供预检）
+/// - `target`: 对象路径
+/// - `level`: 完整性级别
+/// - `delay`: 重试前的等待时间
+///
+/// # 返回
+/// 标签变化与判定；重试成功时 `before` 取自第一次尝试
+pub(crate) fn lock_with_retry(
+    backend: &dyn LabelBackend,
+    target: &str,
+    level: LabelLevel,
+    delay: Duration,
+) -> (LabelChange, Interference) {
+    let first = lock_object(backend, target, level);
+    let denied = matches!(&first.result, Err(e) if is_access_denied(e));
+    if !denied || !backend.precheck_access(target).passed() {
+        return (first, Interference::None);
+    }
+
+    std::thread::sleep(delay);
+    let retry = lock_object(backend, target, level);
+    match retry.result {
+        Ok(()) => (
+            LabelChange {
+                before: first.before,
+                ..retry
+            },
+            Interference::Recovered,
+        ),
+        Err(_) => (first, Interference::Suspected),
+    }
+}
+
+/// 按判定改写失败（`Suspected` 时替换为 `SuspectedFilterInterference`）
+pub(crate) fn classify(path: &str, error: AmberlockError, verdict: Interference) -> AmberlockError {
+    match verdict {
+        Interference::Suspected => AmberlockError::SuspectedFilterInterference {
+            path: path.to_string(),
+            code: error_code(&error),
+        },
+        _ => error,
+    }
+}
+
+/// 批量操作中的拦截统计
+#[derive(Debug, Clone, Default, PartialEq, Eq)]
+pub struct InterferenceTally {
+    /// 已处理的对象数
+    pub processed: usize,
+    /// 重试后成功的对象数
+    pub recovered: usize,
+    /// 重试仍被拒绝的对象数
+    pub suspected: usize,
+    /// 最长的连续 `suspected` 数
+    pub longest_run: usize,
+    current_run: usize,
+}
+
+impl InterferenceTally {
+    /// 计入一个对象的处理结果
+    pub fn observe(&mut self, outcome: Result<&LockResult, &AmberlockError>) {
+        self.processed += 1;
+        match outcome {
+            Err(AmberlockError::SuspectedFilterInterference { .. }) => {
+                self.suspected += 1;
+                self.current_run += 1;
+                self.longest_run = self.longest_run.max(self.current_run);
+                return;
+            }
+            Ok(LockResult::Recovered) => self.recovered += 1,
+            _ => {}
+        }
+        self.current_run = 0;
+    }
+
+    /// 重试仍失败的对象是否零散分布（而非成片出现）
+    pub fn is_scattered(&self) -> bool {
+        self.suspected > 0
+            && self.longest_run <= SCATTERED_MAX_RUN
+            && self.suspected * 100 <= self.processed * SCATTERED_MAX_PERCENT
+    }
+
+    /// 疑似被拦截的失败次数
+    ///
+    /// 重试后成功的总是计入；重试仍失败的只在零散分布时计入
+    pub fn suspected_count(&self) -> usize {
+        self.recovered
+            + if self.is_scattered() {
+                self.suspected
+            } else {
+                0
+            }
+    }
+
+    /// 批量摘要中的提示行（如 `12 次失败疑似被安全软件拦截`）
+    pub fn summary_line(&self) -> Option<String> {
+        let count = self.suspected_count();
+        (count > 0).then(|| format!("{} 次失败疑似被安全软件拦截", count))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::backend::{FakeBackend, LabelOp};
+
+    const TARGET: &str = "C:\\Data\\a.txt";
+
+    fn passed() -> AccessPrecheck {
+        AccessPrecheck {
+            has_se_security: true,
+            label_access: true,
+        }
+    }
+
+    /// (首次错误码, 预检, 重试错误码) → 期望的判定与最终级别
+    #[test]
+    fn test_retry_classification() {
+        let no_access = AccessPrecheck {
+            has_se_security: true,
+            label_access: false,
+        };
+        let no_privilege = AccessPrecheck {
+            has_se_security: false,
+            label_access: true,
+        };
+        let cases = [
+            // 预检通过，重试成功
+            (Some(5), passed(), None, Interference::Recovered, Some(LabelLevel::High)),
+            (Some(0x8007_0005), passed(), None, Interference::Recovered, Some(LabelLevel::High)),
+            // 预检通过，重试仍失败
+            (Some(5), passed(), Some(5), Interference::Suspected, None),
+            // DACL 预检未通过：真实的权限错误，不重试
+            (Some(5), no_access, None, Interference::None, None),
+            (Some(5), no_privilege, None, Interference::None, None),
+            // 其他错误不重试
+            (Some(32), passed(), None, Interference::None, None),
+            // 首次即成功
+            (None, passed(), None, Interference::None, Some(LabelLevel::High)),
+        ];
+
+        for (first, precheck, retry, want, label) in cases {
+            let backend = FakeBackend::new();
+            backend.set_precheck(TARGET, precheck);
+            backend.push_script(LabelOp::Set, TARGET, first);
+            backend.push_script(LabelOp::Set, TARGET, retry);
+
+            let (change, verdict) =
+                lock_with_retry(&backend, TARGET, LabelLevel::High, Duration::ZERO);
+            let case = format!("{:?} {:?} {:?}", first, precheck, retry);
+            assert_eq!(verdict, want, "{}", case);
+            assert_eq!(backend.label_of(TARGET), label, "{}", case);
+            assert_eq!(change.result.is_ok(), label.is_some(), "{}", case);
+
+            let sets = backend
+                .calls()
+                .iter()
+                .filter(|(op, _)| *op == LabelOp::Set)
+                .count();
+            let retried = precheck.passed() && matches!(first, Some(5) | Some(0x8007_0005));
+            assert_eq!(sets, if retried { 2 } else { 1 }, "{}: 重试次数", case);
+        }
+        println!("✅ 只有预检通过的拒绝访问才会重试并重新分类");
+    }
+
+    #[test]
+    fn test_genuine_denial_keeps_original_error() {
+        let backend = FakeBackend::new();
+        backend.fail_next(LabelOp::Set, TARGET, 5);
+
+        let (change, verdict) = lock_with_retry(&backend, TARGET, LabelLevel::High, Duration::ZERO);
+        let error = classify(TARGET, change.result.unwrap_err(), verdict);
+        assert!(matches!(error, AmberlockError::Win32 { code: 5, .. }));
+
+        let error = classify(TARGET, AmberlockError::Unsupported, Interference::Suspected);
+        assert!(matches!(
+            error,
+            AmberlockError::SuspectedFilterInterference { .. }
+        ));
+    }
+
+    fn tally(pattern: &str) -> InterferenceTally {
+        let mut tally = InterferenceTally::default();
+        for c in pattern.chars() {
+            let outcome = match c {
+                's' => Err(AmberlockError::SuspectedFilterInterference {
+                    path: TARGET.to_string(),
+                    code: 5,
+                }),
+                'r' => Ok(LockResult::Recovered),
+                'e' => Err(AmberlockError::Unsupported),
+                _ => Ok(LockResult::Success),
+            };
+            tally.observe(outcome.as_ref());
+        }
+        tally
+    }
+
+    #[test]
+    fn test_batch_scatter() {
+        // 零散分布：计入摘要
+        let scattered = tally("..s.......s.....r...s.........s...");
+        assert!(scattered.is_scattered());
+        assert_eq!(scattered.suspected_count(), 5);
+        assert_eq!(
+            scattered.summary_line().as_deref(),
+            Some("5 次失败疑似被安全软件拦截")
+        );
+
+        // 成片连续失败：只计入重试成功的
+        let run = tally("....r.....sss.........");
+        assert!(!run.is_scattered());
+        assert_eq!(run.longest_run, 3);
+        assert_eq!(run.suspected_count(), 1);
+
+        // 占比过高：系统性失败
+        let dense = tally("s.s.s.s.");
+        assert!(!dense.is_scattered());
+        assert_eq!(dense.suspected_count(), 0);
+        assert_eq!(dense.summary_line(), None);
+
+        // 其他失败不影响判定，也不计入
+        let other = tally("eeee..........");
+        assert_eq!(other.summary_line(), None);
+    }
+}
@@ -0,0 +1,712 @@
+//! 保护清单
+//!
+//! 验证、启动报告与仪表板都需要“当前哪些对象处于保护状态”。从审计日志推导
+//! 需要重放全部记录，日志增长到数百万行后启动时间随之变长。`ProtectionInventory`
+//! 保存推导结果：
+//! - 作为 `RecordObserver` 登记到操作日志，每条追加的记录都在内存中更新清单
+//! - 日志每次刷新（批量操作结束、验证每块结束等）后原子写入日志旁的附属文件
+//!   （`{日志文件名}.inventory.json`），保证附属文件不会领先于日志
+//! - 附属文件缺失、损坏、版本不一致，或保存时的日志长度与当前不同（异常退出、
+//!   日志被轮转）时，`load_or_rebuild` 从日志重新推导
+//!
+//! 清单只是日志的缓存，日志仍是唯一的权威来源。`check_inventory` 随机抽样
+//! 若干条目与实际标签比对，发现不一致时由启动报告提示重新验证。
+//!
+//! 附属文件还保存最近一次退出保存的 `SessionStamp`，与设置中的标记比对见 `session` 模块。
+
+use crate::backend::LabelBackend;
+use crate::decommission::ALREADY_UNPROTECTED_STATUS;
+use crate::moves::{self, RELOCATED_STATUS, is_not_found};
+use crate::pathutil;
+use crate::repair::REPAIRED_STATUS;
+use crate::rollback::ROLLED_BACK_STATUS;
+use crate::session::{SessionReconciliation, reconcile_session};
+use crate::verify::ExpectedLabel;
+use amberlock_storage::query::QueryBuilder;
+use amberlock_storage::{AtomicWriteOptions, RecordObserver, atomic_write};
+use amberlock_types::{
+    AmberlockError, FileId, LabelLevel, LockRecord, ProtectMode, Result, SessionStamp, Settings,
+    TargetKind,
+};
+use serde::{Deserialize, Serialize};
+use std::collections::{BTreeMap, BTreeSet};
+use std::path::{Path, PathBuf};
+use std::sync::Mutex;
+
+/// 附属文件格式版本
+pub const INVENTORY_VERSION: u32 = 1;
+/// 一致性检查的默认抽样数
+pub const DEFAULT_INVENTORY_SAMPLE: usize = 20;
+
+/// 清单中的一个受保护对象
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+pub struct InventoryEntry {
+    /// 对象路径（与日志记录一致）
+    pub path: String,
+    /// 对象类型
+    pub kind: TargetKind,
+    /// 保护模式
+    pub mode: ProtectMode,
+    /// 期望的完整性级别
+    pub level: LabelLevel,
+    /// 最近一次上锁（或移动）的时间
+    pub time_utc: String,
+    /// 上锁时的文件标识
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub file_id: Option<FileId>,
+}
+
+impl InventoryEntry {
+    fn from_record(record: &LockRecord) -> Self {
+        Self {
+            path: pathutil::normalized_str(&record.path),
+            kind: record.kind,
+            mode: record.mode,
+            level: record.level_applied,
+            time_utc: record.time_utc.clone(),
+            file_id: record.file_id,
+        }
+    }
+}
+
+/// 附属文件内容
+#[derive(Serialize, Deserialize)]
+struct InventoryFile {
+    version: u32,
+    /// 保存时日志文件的长度
+    log_len: u64,
+    entries: Vec<InventoryEntry>,
+    /// 最近一次退出保存的标记（旧版本写入的文件没有）
+    #[serde(default)]
+    session: Option<SessionStamp>,
+}
+
+/// 当前处于保护状态的对象
+#[derive(Debug, Clone, Default, PartialEq, Eq)]
+pub struct ProtectionInventory {
+    entries: BTreeMap<String, InventoryEntry>,
+}
+
+impl ProtectionInventory {
+    /// 按一条日志记录更新清单
+    ///
+    /// 记录路径先经规范化（末尾与混合分隔符），规范化之前写入的记录与之后的记录对应同一条目。
+    /// `success` 加入或覆盖，`unlocked`、`rolled_back` 与 `already_unprotected` 移除，
+    /// `relocated` 只在原路径仍在清单中时移到新路径；其他状态（失败、漂移等）忽略
+    pub fn apply(&mut self, record: &LockRecord) {
+        let key = pathutil::normalized_str(&record.path);
+        match record.status.as_str() {
+            "success" => {
+                self.entries
+                    .insert(key, InventoryEntry::from_record(record));
+            }
+            "unlocked" | ROLLED_BACK_STATUS | ALREADY_UNPROTECTED_STATUS => {
+                self.entries.remove(&key);
+            }
+            RELOCATED_STATUS => {
+                let Some(from) = moves::relocated_from(record) else {
+                    return;
+                };
+                if self
+                    .entries
+                    .remove(&pathutil::normalized_str(from))
+                    .is_some()
+                {
+                    self.entries
+                        .insert(key, InventoryEntry::from_record(record));
+                }
+            }
+            // 完全成功的修复按修复后的级别更新（修复为 Medium 即不再受保护）
+            REPAIRED_STATUS if record.errors.is_empty() => {
+                if record.level_applied == LabelLevel::Medium {
+                    self.entries.remove(&key);
+                } else {
+                    self.entries
+                        .insert(key, InventoryEntry::from_record(record));
+                }
+            }
+            _ => {}
+        }
+    }
+
+    /// 受保护对象数
+    pub fn len(&self) -> usize {
+        self.entries.len()
+    }
+
+    /// 清单是否为空
+    pub fn is_empty(&self) -> bool {
+        self.entries.is_empty()
+    }
+
+    /// 查找路径对应的条目（路径先经规范化）
+    pub fn get(&self, path: &str) -> Option<&InventoryEntry> {
+        self.entries.get(&pathutil::normalized_str(path))
+    }
+
+    /// 按路径排序的全部条目
+    pub fn entries(&self) -> impl Iterator<Item = &InventoryEntry> {
+        self.entries.values()
+    }
+
+    /// 受保护对象所在的卷或网络共享（如 `C:\`、`\\server\share`）
+    pub fn roots(&self) -> BTreeSet<String> {
+        self.entries.keys().map(|path| root_of(path)).collect()
+    }
+
+    /// 摘要（如 `当前保护 1234 个对象，分布在 2 个卷`）
+    pub fn summary_line(&self) -> String {
+        format!(
+            "当前保护 {} 个对象，分布在 {} 个卷",
+            self.len(),
+            self.roots().len()
+        )
+    }
+
+    /// 验证使用的期望列表（按路径排序）
+    pub fn expected(&self) -> Vec<ExpectedLabel> {
+        self.entries
+            .values()
+            .map(|entry| ExpectedLabel {
+                path: entry.path.clone(),
+                kind: entry.kind,
+                mode: entry.mode,
+                level: entry.level,
+                file_id: entry.file_id,
+            })
+            .collect()
+    }
+
+    /// 读取附属文件
+    ///
+    /// # 返回
+    /// 清单及保存时的日志长度；文件不存在、损坏或版本不一致时返回错误，
+    /// 调用方应改为从日志重建
+    pub fn load<P: AsRef<Path>>(path: P) -> Result<(Self, u64)> {
+        let bytes = std::fs::read(path.as_ref()).map_err(|e| AmberlockError::Storage(e.into()))?;
+        let file: InventoryFile =
+            serde_json::from_slice(&bytes).map_err(|e| AmberlockError::Storage(e.into()))?;
+        if file.version != INVENTORY_VERSION {
+            let message = format!("不支持的保护清单版本: {}", file.version);
+            return Err(AmberlockError::Storage(
+                std::io::Error::new(std::io::ErrorKind::InvalidData, message).into(),
+            ));
+        }
+        let inventory = Self {
+            entries: file
+                .entries
+                .into_iter()
+                .map(|entry| (pathutil::normalized_str(&entry.path), entry))
+                .collect(),
+        };
+        Ok((inventory, file.log_len))
+    }
+
+    /// 原子写入附属文件
+    ///
+    /// # 参数
+    /// - `path`: 附属文件路径
+    /// - `log_len`: 清单对应的日志文件长度（读取时据此判断清单是否过期）
+    pub fn save<P: AsRef<Path>>(&self, path: P, log_len: u64) -> Result<()> {
+        self.save_with_session(path, log_len, None)
+    }
+
+    /// 原子写入附属文件，同时写入退出保存的标记
+    fn save_with_session<P: AsRef<Path>>(
+        &self,
+        path: P,
+        log_len: u64,
+        session: Option<&SessionStamp>,
+    ) -> Result<()> {
+        let file = InventoryFile {
+            version: INVENTORY_VERSION,
+            log_len,
+            entries: self.entries.values().cloned().collect(),
+            session: session.cloned(),
+        };
+        let bytes = serde_json::to_vec(&file).map_err(|e| AmberlockError::Storage(e.into()))?;
+        let options = AtomicWriteOptions {
+            fsync: false,
+            ..Default::default()
+        };
+        atomic_write(path, &bytes, &options)?;
+        Ok(())
+    }
+}
+
+/// 对象所在的卷或网络共享
+pub(crate) fn root_of(path: &str) -> String {
+    if let Some(share) = pathutil::unc_share(Path::new(path)) {
+        return share;
+    }
+    let rest = path.strip_prefix(r"\\?\").unwrap_or(path);
+    match rest.as_bytes() {
+        [drive, b':', ..] if drive.is_ascii_alphabetic() => {
+            format!("{}:\\", drive.to_ascii_uppercase() as char)
+        }
+        _ => rest
+            .split(['\\', '/'])
+            .next()
+            .unwrap_or_default()
+            .to_string(),
+    }
+}
+
+/// 日志的默认保护清单路径（`{日志文件名}.inventory.json`）
+pub fn inventory_path<P: AsRef<Path>>(log_path: P) -> PathBuf {
+    let mut name = log_path.as_ref().as_os_str().to_os_string();
+    name.push(".inventory.json");
+    PathBuf::from(name)
+}
+
+/// 从审计日志重新推导保护清单
+///
+/// # 参数
+/// - `log_path`: 日志文件路径
+///
+/// # 返回
+/// 重放全部记录后的清单；无法解析为 `LockRecord` 的行被跳过
+pub fn rebuild_inventory_from_log(log_path: impl AsRef<Path>) -> Result<ProtectionInventory> {
+    let records = QueryBuilder::new(log_path).execute()?;
+    let mut inventory = ProtectionInventory::default();
+    for value in records {
+        if let Ok(record) = serde_json::from_value::<LockRecord>(value) {
+            inventory.apply(&record);
+        }
+    }
+    Ok(inventory)
+}
+
+/// 读取保护清单，不可用时从日志重建
+///
+/// # 参数
+/// - `path`: 附属文件路径（通常为 `inventory_path(log_path)`）
+/// - `log_path`: 日志文件路径
+///
+/// # 返回
+/// 清单，以及是否从日志重建（重建后调用方应保存附属文件）
+///
+/// # 注意
+/// - 附属文件记录的日志长度与当前日志不同时同样重建
+/// - 日志尚不存在（首次运行）时得到空清单
+pub fn load_or_rebuild(
+    path: impl AsRef<Path>,
+    log_path: impl AsRef<Path>,
+) -> Result<(ProtectionInventory, bool)> {
+    match ProtectionInventory::load(path) {
+        Ok((inventory, log_len)) if log_len == log_len_of(&log_path) => Ok((inventory, false)),
+        _ if !log_path.as_ref().exists() => Ok((ProtectionInventory::default(), true)),
+        _ => Ok((rebuild_inventory_from_log(log_path)?, true)),
+    }
+}
+
+/// 读取附属文件中的退出保存标记（文件不存在、损坏或没有标记时为 None）
+pub fn inventory_session(path: impl AsRef<Path>) -> Option<SessionStamp> {
+    #[derive(Deserialize)]
+    struct SessionOnly {
+        #[serde(default)]
+        session: Option<SessionStamp>,
+    }
+
+    let bytes = std::fs::read(path).ok()?;
+    serde_json::from_slice::<SessionOnly>(&bytes).ok()?.session
+}
+
+/// 日志文件当前的长度（不存在时为 0）
+fn log_len_of(log_path: impl AsRef<Path>) -> u64 {
+    std::fs::metadata(log_path).map(|m| m.len()).unwrap_or(0)
+}
+
+/// 一致性检查结果
+#[derive(Debug, Clone, Default, PartialEq, Eq)]
+pub struct InventoryCheck {
+    /// 抽样的条目数
+    pub sampled: usize,
+    /// 实际标签与清单不一致（级别不同或对象不存在）的路径
+    pub divergent: Vec<String>,
+    /// 无法读取标签的条目数（不计为不一致）
+    pub unreadable: usize,
+}
+
+impl InventoryCheck {
+    /// 抽样中是否发现不一致
+    pub fn is_consistent(&self) -> bool {
+        self.divergent.is_empty()
+    }
+}
+
+/// 随机抽样检查清单与实际标签是否一致
+///
+/// # 参数
+/// - `inventory`: 保护清单
+/// - `backend`: 标签读取后端
+/// - `sample`: 抽样数（超过清单大小时检查全部条目）
+/// - `seed`: 随机种子（相同种子抽取相同的条目）
+///
+/// # 返回
+/// 抽样结果；对象不存在计为不一致，其他读取错误只计数
+pub fn check_inventory(
+    inventory: &ProtectionInventory,
+    backend: &dyn LabelBackend,
+    sample: usize,
+    seed: u64,
+) -> InventoryCheck {
+    let mut entries: Vec<&InventoryEntry> = inventory.entries().collect();
+    let sample = sample.min(entries.len());
+
+    // 部分 Fisher-Yates 洗牌：前 `sample` 项为无放回的随机抽样
+    let mut state = seed;
+    for i in 0..sample {
+        let j = i + (splitmix64(&mut state) % (entries.len() - i) as u64) as usize;
+        entries.swap(i, j);
+    }
+
+    let mut check = InventoryCheck {
+        sampled: sample,
+        ..Default::default()
+    };
+    for entry in &entries[..sample] {
+        let target = pathutil::canonical_path(Path::new(&entry.path));
+        match backend.get_label(&target.to_string_lossy()) {
+            Ok(label) if label.level == entry.level => {}
+            Ok(_) => check.divergent.push(entry.path.clone()),
+            Err(e) if is_not_found(&e) => check.divergent.push(entry.path.clone()),
+            Err(_) => check.unreadable += 1,
+        }
+    }
+    check.divergent.sort();
+    check
+}
+
+/// splitmix64 伪随机数
+fn splitmix64(state: &mut u64) -> u64 {
+    *state = state.wrapping_add(0x9E37_79B9_7F4A_7C15);
+    let mut z = *state;
+    z = (z ^ (z >> 30)).wrapping_mul(0xBF58_476D_1CE4_E5B9);
+    z = (z ^ (z >> 27)).wrapping_mul(0x94D0_49BB_1331_11EB);
+    z ^ (z >> 31)
+}
+
+/// 跟随操作日志维护的保护清单
+///
+/// 通过 `OperationLog::with_observer` 登记后，每条追加的记录都更新内存中的清单，
+/// 日志刷新后写入附属文件
+pub struct InventoryTracker {
+    inventory: Mutex<ProtectionInventory>,
+    session: Mutex<Option<SessionStamp>>,
+    reconciliation: Mutex<SessionReconciliation>,
+    path: PathBuf,
+    log_path: PathBuf,
+    rebuilt: bool,
+}
+
+impl InventoryTracker {
+    /// 读取保护清单（不可用时从日志重建）并开始跟随
+    ///
+    /// # 参数
+    /// - `log_path`: 日志文件路径（附属文件位于 `inventory_path(log_path)`）
+    ///
+    /// # 注意
+    /// 从日志重建后立即保存附属文件
+    pub fn open(log_path: impl AsRef<Path>) -> Result<Self> {
+        let path = inventory_path(&log_path);
+        let (inventory, rebuilt) = load_or_rebuild(&path, &log_path)?;
+        let tracker = Self {
+            inventory: Mutex::new(inventory),
+            session: Mutex::new(inventory_session(&path)),
+            reconciliation: Mutex::new(SessionReconciliation::InSync),
+            path,
+            log_path: log_path.as_ref().to_path_buf(),
+            rebuilt,
+        };
+        if rebuilt {
+            let _ = tracker.persist();
+        }
+        Ok(tracker)
+    }
+
+    /// 打开时是否从日志重建
+    pub fn rebuilt(&self) -> bool {
+        self.rebuilt
+    }
+
+    /// 附属文件路径
+    pub fn path(&self) -> &Path {
+        &self.path
+    }
+
+    /// 当前清单的副本
+    pub fn snapshot(&self) -> ProtectionInventory {
+        self.inventory.lock().unwrap().clone()
+    }
+
+    /// 写入附属文件
+    ///
+    /// # 注意
+    /// 应在日志刷新之后、没有操作进行时调用，保证清单与记录的日志长度一致
+    pub fn persist(&self) -> Result<()> {
+        let inventory = self.inventory.lock().unwrap();
+        let session = self.session.lock().unwrap();
+        inventory.save_with_session(&self.path, log_len_of(&self.log_path), session.as_ref())
+    }
+
+    /// 附属文件中的退出保存标记
+    pub fn session(&self) -> Option<SessionStamp> {
+        self.session.lock().unwrap().clone()
+    }
+
+    /// 以新的退出保存标记写入附属文件（由 `session::save_session_pair` 调用）
+    pub(crate) fn persist_session(&self, stamp: SessionStamp) -> Result<()> {
+        *self.session.lock().unwrap() = Some(stamp);
+        self.persist()
+    }
+
+    /// 比对设置与清单的退出保存标记，不一致时恢复
+    ///
+    /// # 参数
+    /// - `settings`: 刚读取的设置（设置较旧时 `session` 改为沿用清单的标记）
+    ///
+    /// # 返回
+    /// 比对结论（同时保存在本对象中，供启动报告读取）
+    ///
+    /// # 注意
+    /// 应在打开后、开始记录操作之前调用。清单较旧时从日志重建并以设置的标记保存
+    pub fn reconcile_session(&self, settings: &mut Settings) -> Result<SessionReconciliation> {
+        let outcome = reconcile_session(settings.session.as_ref(), self.session().as_ref());
+        match &outcome {
+            SessionReconciliation::InSync => {}
+            SessionReconciliation::InventoryStale { .. } => {
+                if !self.rebuilt {
+                    let rebuilt = if self.log_path.exists() {
+                        rebuild_inventory_from_log(&self.log_path)?
+                    } else {
+                        ProtectionInventory::default()
+                    };
+                    *self.inventory.lock().unwrap() = rebuilt;
+                }
+                *self.session.lock().unwrap() = settings.session.clone();
+                self.persist()?;
+            }
+            SessionReconciliation::SettingsStale { .. } => settings.session = self.session(),
+        }
+        *self.reconciliation.lock().unwrap() = outcome.clone();
+        Ok(outcome)
+    }
+
+    /// 最近一次 `reconcile_session` 的结论（未调用时为 `InSync`）
+    pub fn session_reconciliation(&self) -> SessionReconciliation {
+        self.reconciliation.lock().unwrap().clone()
+    }
+}
+
+impl RecordObserver for InventoryTracker {
+    fn observe(&self, record: &LockRecord) {
+        self.inventory.lock().unwrap().apply(record);
+    }
+
+    fn flushed(&self) {
+        let _ = self.persist();
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::backend::{FakeBackend, LabelOp};
+    use crate::moves::{LabeledObject, MoveConfidence, MovePair, record_relocations};
+    use crate::verify::expected_from_log;
+    use amberlock_storage::OperationLog;
+    use amberlock_types::OperationOrigin;
+    use std::sync::Arc;
+    use tempfile::TempDir;
+
+    fn record(logger: &OperationLog, path: &str, status: &str) -> LockRecord {
+        LockRecord {
+            id: logger.stamper().new_id(),
+            path: path.to_string(),
+            kind: TargetKind::File,
+            mode: ProtectMode::ReadOnly,
+            level_applied: LabelLevel::High,
+            time_utc: logger.stamper().now(),
+            seq_no: logger.stamper().next_seq(),
+            user_sid: "S-1-5-21-test".to_string(),
+            owner_before: None,
+            sddl_before: None,
+            sddl_after: None,
+            status: status.to_string(),
+            errors: vec![],
+            details: None,
+            authorized_by: None,
+            comment: None,
+            batch_id: None,
+            volume_root_acknowledged: false,
+            downgrade_reason: None,
+            origin: OperationOrigin::GuiButton,
+            app_version: None,
+            file_id: None,
+            warnings: vec![],
+        }
+    }
+
+    /// 写入一组上锁、解锁、失败与移动记录
+    fn write_history(logger: &OperationLog) {
+        for path in [
+            r"C:\Data\a.txt",
+            r"C:\Data\b.txt",
+            r"D:\c.txt",
+            r"\\srv\share\d.txt",
+        ] {
+            logger.append(&record(logger, path, "success")).unwrap();
+        }
+        logger
+            .append(&record(logger, r"C:\Data\e.txt", "error"))
+            .unwrap();
+        logger
+            .append(&record(logger, r"C:\Data\b.txt", "unlocked"))
+            .unwrap();
+
+        let expected = ExpectedLabel {
+            path: r"D:\c.txt".to_string(),
+            kind: TargetKind::File,
+            mode: ProtectMode::ReadOnly,
+            level: LabelLevel::High,
+            file_id: None,
+        };
+        let found = LabeledObject {
+            path: r"D:\moved\c.txt".to_string(),
+            kind: TargetKind::File,
+            level: LabelLevel::High,
+            file_id: None,
+        };
+        let pair = MovePair {
+            expected,
+            found,
+            confidence: MoveConfidence::High,
+        };
+        record_relocations(logger, "S-1-5-21-test", &[pair], OperationOrigin::GuiButton)
+            .expect("写入移动记录失败");
+        logger.flush().unwrap();
+    }
+
+    #[test]
+    fn test_tracker_follows_log() {
+        let temp_dir = TempDir::new().expect("创建临时目录失败");
+        let log_path = temp_dir.path().join("log.ndjson");
+        let tracker = Arc::new(InventoryTracker::open(&log_path).expect("打开保护清单失败"));
+        assert!(tracker.rebuilt(), "附属文件不存在时应从日志重建");
+        let logger = OperationLog::open(&log_path)
+            .expect("打开日志失败")
+            .with_observer(tracker.clone());
+
+        write_history(&logger);
+
+        let inventory = tracker.snapshot();
+        let paths: Vec<&str> = inventory.entries().map(|e| e.path.as_str()).collect();
+        assert_eq!(
+            paths,
+            vec![r"C:\Data\a.txt", r"D:\moved\c.txt", r"\\srv\share\d.txt"]
+        );
+        assert_eq!(inventory.summary_line(), "当前保护 3 个对象，分布在 3 个卷");
+        assert_eq!(inventory.expected(), expected_from_log(&log_path).unwrap());
+        assert_eq!(inventory, rebuild_inventory_from_log(&log_path).unwrap());
+        println!("✅ 内存中的保护清单与日志推导结果一致");
+    }
+
+    #[test]
+    fn test_history_matches_records_before_and_after_normalization() {
+        let temp_dir = TempDir::new().expect("创建临时目录失败");
+        let log_path = temp_dir.path().join("log.ndjson");
+        let logger = OperationLog::open(&log_path).expect("打开日志失败");
+
+        // 规范化之前写入的记录可能带末尾分隔符或混合分隔符
+        for path in [r"D:\Data\a.txt\", "D:/Data//b.txt", r"\\nas\share"] {
+            logger.append(&record(&logger, path, "success")).unwrap();
+        }
+        logger
+            .append(&record(&logger, r"D:\Data\b.txt", "unlocked"))
+            .unwrap();
+        logger.flush().unwrap();
+
+        let inventory = rebuild_inventory_from_log(&log_path).expect("重建保护清单失败");
+        let paths: Vec<&str> = inventory.entries().map(|e| e.path.as_str()).collect();
+        assert_eq!(paths, vec![r"D:\Data\a.txt", r"\\nas\share\"]);
+        for lookup in [r"D:\Data\a.txt", r"D:\Data\a.txt\", "D:/Data/a.txt"] {
+            assert!(inventory.get(lookup).is_some(), "{} 应命中旧记录", lookup);
+        }
+        assert!(inventory.get(r"\\nas\share\").is_some());
+        assert!(inventory.get(r"D:\Data\b.txt").is_none());
+        println!("✅ 规范化前后写入的记录对应同一清单条目");
+    }
+
+    #[test]
+    fn test_persist_round_trip_and_recovery() {
+        let temp_dir = TempDir::new().expect("创建临时目录失败");
+        let log_path = temp_dir.path().join("log.ndjson");
+        let path = inventory_path(&log_path);
+        let tracker = Arc::new(InventoryTracker::open(&log_path).unwrap());
+        let logger = OperationLog::open(&log_path)
+            .unwrap()
+            .with_observer(tracker.clone());
+        // 日志刷新时自动保存
+        write_history(&logger);
+
+        let (loaded, rebuilt) = load_or_rebuild(&path, &log_path).unwrap();
+        assert!(!rebuilt, "附属文件有效时不应重建");
+        assert_eq!(loaded, tracker.snapshot());
+
+        // 保存后日志又追加了记录、未及刷新即异常退出：清单已过期
+        logger
+            .append(&record(&logger, r"C:\Data\f.txt", "success"))
+            .unwrap();
+        let (loaded, rebuilt) = load_or_rebuild(&path, &log_path).unwrap();
+        assert!(rebuilt, "日志长度变化时应重建");
+        assert!(loaded.get(r"C:\Data\f.txt").is_some());
+
+        // 附属文件损坏
+        std::fs::write(&path, b"{\"version\":1,\"entr").unwrap();
+        let (loaded, rebuilt) = load_or_rebuild(&path, &log_path).unwrap();
+        assert!(rebuilt, "附属文件损坏时应重建");
+        assert_eq!(loaded, rebuild_inventory_from_log(&log_path).unwrap());
+
+        // 版本不一致
+        let stale = format!(
+            "{{\"version\":{},\"log_len\":0,\"entries\":[]}}",
+            INVENTORY_VERSION + 1
+        );
+        std::fs::write(&path, stale).unwrap();
+        assert!(ProtectionInventory::load(&path).is_err());
+        println!("✅ 附属文件无效或过期时从日志重建");
+    }
+
+    #[test]
+    fn test_check_samples_live_labels() {
+        let temp_dir = TempDir::new().expect("创建临时目录失败");
+        let logger = OperationLog::open(temp_dir.path().join("log.ndjson")).unwrap();
+        let mut inventory = ProtectionInventory::default();
+        let backend = FakeBackend::new();
+        for i in 0..50 {
+            let path = format!(r"C:\Data\{:02}.txt", i);
+            inventory.apply(&record(&logger, &path, "success"));
+            backend.insert_label(&path, LabelLevel::High);
+        }
+
+        let check = check_inventory(&inventory, &backend, 10, 42);
+        assert_eq!(check.sampled, 10);
+        assert!(check.is_consistent());
+        assert_eq!(
+            check_inventory(&inventory, &backend, 10, 42),
+            check,
+            "相同种子应抽取相同的条目"
+        );
+
+        // 标签在 AmberLock 之外被移除：全量抽样必然发现
+        backend.insert_label(r"C:\Data\07.txt", LabelLevel::Medium);
+        backend.fail_next(LabelOp::Get, r"C:\Data\31.txt", 2);
+        backend.fail_next(LabelOp::Get, r"C:\Data\44.txt", 5);
+        let check = check_inventory(&inventory, &backend, 100, 7);
+        assert_eq!(check.sampled, 50);
+        assert_eq!(check.divergent, vec![r"C:\Data\07.txt", r"C:\Data\31.txt"]);
+        assert_eq!(check.unreadable, 1);
+        println!("✅ 抽样检查发现清单与实际标签不一致");
+    }
+}
@@ -1,22 +1,357 @@
+//! AmberLock 核心库
+//!
+//! # 特性
+//! - `winsec`（默认）：通过 `amberlock-winsec` 实际读写 Windows 强制完整性标签。
+//!   关闭后 `ops`、`privileged` 模块及依赖它们的批量/后台/通配符入口不参与编译，
+//!   进度、冲突登记、告警、路径处理、结果分类等纯逻辑仍可在任意平台构建
+//! - `tokio`：为 `OperationHandle` 提供 `into_future()`
+//! - `alerts-webhook`：内置 `WebhookSink`
+
 use std::fmt::{Display, Formatter};
 use std::path::Path;
-use uuid::Uuid;
-use amberlock_storage::NdjsonWriter;
-use amberlock_types::{LabelLevel, LockRecord, ProtectMode, TargetKind};
+use std::sync::Arc;
+use amberlock_storage::{OperationLog, RecordStamper, SystemStamper};
+use amberlock_types::{
+    AmberlockError, CancelBehavior, DowngradeReason, FileId, LabelLevel, LockRecord, MaybeCompressed,
+    OperationOrigin, ParallelismMode, ProtectMode, SDDL_COMPRESS_THRESHOLD, TargetKind, Warning,
+    WarningKind,
+};
+use std::collections::BTreeMap;
 
+pub mod alerts;
+pub mod audit;
+pub mod autotune;
+pub mod backend;
+pub mod build_info;
+pub mod controller;
+pub mod decommission;
+pub mod dirsummary;
+pub mod failures;
+pub mod glob;
+pub mod interference;
+pub mod inventory;
+pub mod lifecycle;
+pub mod moves;
+pub mod network;
+#[cfg(feature = "winsec")]
 pub mod ops;
+pub mod pathutil;
+pub mod prelude;
+pub mod presets;
+#[cfg(feature = "winsec")]
 pub mod privileged;
+pub mod progress;
+pub mod readonly;
+pub mod registry;
+pub mod repair;
+pub mod rescan;
+pub mod revert;
+pub mod rollback;
+pub mod schedule;
+pub mod seal;
+pub mod session;
+pub mod startup;
+pub mod task;
+pub mod throttle;
+pub mod trace;
+pub mod verify;
+pub mod volume;
 
+pub use alerts::{
+    Alert,
+    AlertKind,
+    AlertPolicy,
+    AlertSink,
+    TestSink,
+    deliver_alert,
+};
+#[cfg(feature = "alerts-webhook")]
+pub use alerts::WebhookSink;
+pub use audit::{
+    classify_log_blockage,
+    relocate_log,
+};
+#[cfg(feature = "winsec")]
+pub use audit::{
+    can_lift_label,
+    diagnose_log_blockage,
+    flush_backlog_with_label_lifted,
+};
+pub use autotune::{
+    AutoTuneConfig,
+    AutoTuner,
+    ConcurrencyTrend,
+    SampleOutcome,
+    TuningStep,
+    VolumeTuning,
+};
+pub use backend::{
+    FakeBackend,
+    LabelBackend,
+    LabelOp,
+    ObjectLabel,
+};
+#[cfg(feature = "winsec")]
+pub use backend::WinsecBackend;
+pub use build_info::{
+    APP_VERSION,
+    version_string,
+};
+pub use controller::{
+    AppController,
+    StartupComponent,
+    StartupEvent,
+    StartupPhase,
+    StartupTask,
+};
+pub use decommission::{
+    ALREADY_UNPROTECTED_STATUS,
+    DECOMMISSION_CONFIRM_PHRASE,
+    DECOMMISSION_MARKER,
+    UnprotectAll,
+    UnprotectReport,
+    UnprotectScope,
+    UnprotectTarget,
+    check_confirm_phrase,
+    unprotect_all,
+    unprotect_targets,
+};
+pub use dirsummary::{
+    DEFAULT_DIR_SUMMARY_DEPTH,
+    DIR_SUMMARY_MAX_BYTES,
+    DIR_SUMMARY_STATUS,
+    DirAggregator,
+    DirCounts,
+    DirOffender,
+    DirSummary,
+    OffenderReport,
+    error_kind,
+    subtree_query,
+};
+pub use failures::{
+    BatchFailure,
+    DEFAULT_FAILURE_CAP,
+    FailureSpill,
+};
+pub use glob::{
+    ExpandOptions,
+    GlobBatchResult,
+    expand_glob,
+};
+#[cfg(feature = "winsec")]
+pub use glob::{
+    batch_lock_glob,
+    batch_unlock_glob,
+};
+pub use interference::{
+    AccessPrecheck,
+    InterferenceTally,
+};
+pub use inventory::{
+    DEFAULT_INVENTORY_SAMPLE,
+    InventoryCheck,
+    InventoryEntry,
+    InventoryTracker,
+    ProtectionInventory,
+    check_inventory,
+    inventory_path,
+    inventory_session,
+    load_or_rebuild,
+    rebuild_inventory_from_log,
+};
+pub use lifecycle::{
+    PANIC_STATUS,
+    install_panic_hook,
+    panic_record,
+    shutdown_in_flight,
+};
+#[cfg(feature = "winsec")]
+pub use lifecycle::install_console_ctrl_handler;
+pub use moves::{
+    LabeledObject,
+    MoveConfidence,
+    MovePair,
+    MoveReport,
+    RELOCATED_STATUS,
+    reconcile_moves,
+    record_relocations,
+    scan_labeled_objects,
+};
+pub use network::{
+    NETWORK_PROBE_TIMEOUT,
+    ShareProbeCache,
+    explain_failure,
+};
+#[cfg(feature = "winsec")]
+pub use network::probe_network_path;
+#[cfg(feature = "winsec")]
 pub use ops::{
     process_lock,
+    process_lock_outcome,
     process_unlock,
+    process_unlock_authorized,
+    process_unlock_outcome,
     batch_process_lock,
     batch_process_unlock,
+    unlock_batch,
+    unprotect_all_now,
 };
+pub use pathutil::{
+    canonical_path,
+    ensure_not_reserved,
+    is_reserved_device_name,
+    is_volume_root,
+    resolves_through_reparse_point,
+    unc_share,
+};
+#[cfg(feature = "winsec")]
 pub use privileged::{
+    SystemBackend,
     force_lock,
+    force_repair,
     force_unlock,
-    repair_file_permissions,
+};
+pub use presets::{
+    KnownFolder,
+    LockProfile,
+    PresetPlan,
+    ProtectionPreset,
+    plan_preset,
+    resolve_known_folder,
+};
+#[cfg(feature = "winsec")]
+pub use presets::{
+    apply_preset,
+    spawn_preset,
+};
+pub use progress::{
+    CancelToken,
+    ProgressSnapshot,
+    ProgressTracker,
+};
+pub use readonly::{
+    is_read_only,
+    set_read_only,
+};
+pub use registry::{
+    ActiveOperations,
+    OperationKind,
+};
+pub use repair::{
+    REPAIRED_STATUS,
+    RepairOptions,
+    RepairReport,
+    RepairState,
+    RepairStep,
+    StepOutcome,
+    explain_logged_sddl,
+    explain_sddl,
+    repair_object,
+};
+pub use rescan::{
+    RESCAN_CHUNK_PAUSE,
+    RescanPriority,
+    RescanQueue,
+    RescanRequest,
+    VolumeEvent,
+    expected_under_root,
+    rescan_root,
+    roots_for_event,
+};
+pub use revert::{
+    BatchEntry,
+    BatchRevert,
+    LABEL_CHANGED_STATUS,
+    batch_entries,
+};
+pub use rollback::{
+    CANCELLED_KEPT_STATUS,
+    CANCELLED_ROLLED_BACK_STATUS,
+    CancelledBatch,
+    ROLLED_BACK_STATUS,
+    RollbackResult,
+    roll_back_batch,
+};
+pub use schedule::{
+    BackendExecutor,
+    FixedZone,
+    LocalZone,
+    SCHEDULE_GRACE,
+    SCHEDULE_SKIPPED_STATUS,
+    ScheduleExecutor,
+    ScheduleOutcome,
+    ScheduleRun,
+    TimeSpec,
+    due_entries,
+    due_occurrence,
+    run_due,
+};
+#[cfg(feature = "winsec")]
+pub use schedule::{
+    SystemZone,
+    WinsecExecutor,
+    run_due_now,
+};
+pub use seal::{
+    Ace,
+    AceChange,
+    Acl,
+    Descriptor,
+    SealPlan,
+    apply_seal,
+    diff_aces,
+    plan_seal,
+    preview_seal,
+};
+pub use session::{
+    SessionReconciliation,
+    next_session_stamp,
+    reconcile_session,
+    save_session_pair,
+};
+pub use startup::{
+    Health,
+    Issue,
+    Severity,
+    StartupReport,
+};
+#[cfg(feature = "winsec")]
+pub use startup::gather_startup_report;
+pub use task::{
+    OperationHandle,
+    spawn_operation,
+};
+#[cfg(feature = "winsec")]
+pub use task::{
+    spawn_batch_lock,
+    spawn_batch_unlock,
+    spawn_unlock_batch,
+};
+pub use throttle::VolumeThrottle;
+pub use trace::{
+    Anonymizer,
+    ReplayReport,
+    TraceHeader,
+    TraceRecorder,
+    replay_trace,
+};
+pub use verify::{
+    DRIFT_STATUS,
+    ExpectedLabel,
+    VerifyOptions,
+    VerifyProgress,
+    VerifyScope,
+    VerifyState,
+    expected_from_log,
+    verify_protection_chunked,
+};
+pub use volume::VolumeRootPolicy;
+#[cfg(feature = "winsec")]
+pub use amberlock_winsec::{
+    compute_effective_level,
+    probe_capability,
+    read_process_il,
+    read_user_sid,
 };
 
 /// 上锁结果类型
@@ -28,6 +363,8 @@ pub enum LockResult {
     Downgraded,
     /// 已跳过
     Skipped,
+    /// 首次写入被拒绝、重试后成功（疑似被安全软件拦截，见 `interference` 模块）
+    Recovered,
 }
 
 impl Display for LockResult {
@@ -36,12 +373,41 @@ impl Display for LockResult {
             LockResult::Success => write!(f, "操作成功"),
             LockResult::Downgraded => write!(f, "已降级处理"),
             LockResult::Skipped => write!(f, "已跳过"),
+            LockResult::Recovered => write!(f, "重试后成功"),
+        }
+    }
+}
+
+/// 单对象操作的结果与附带的警告
+///
+/// 警告在产生处设置（如重试、写后验证、路径解析），与结果一同写入
+/// `LockRecord.warnings` 并按类型累计到 `BatchResult.warnings`
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct LockOutcome {
+    /// 操作结果
+    pub result: LockResult,
+    /// 成功但需要用户知晓的情况
+    pub warnings: Vec<Warning>,
+}
+
+impl LockOutcome {
+    /// 是否带有警告
+    pub fn has_warnings(&self) -> bool {
+        !self.warnings.is_empty()
+    }
+}
+
+impl From<LockResult> for LockOutcome {
+    fn from(result: LockResult) -> Self {
+        Self {
+            result,
+            warnings: Vec::new(),
         }
     }
 }
 
 /// 批量操作结果统计
-#[derive(Debug, Clone, Default)]
+#[derive(Debug, Clone)]
 pub struct BatchResult {
     /// 成功数量
     pub success_count: usize,
@@ -49,17 +415,234 @@ pub struct BatchResult {
     pub failed_count: usize,
     /// 降级数量
     pub downgraded_count: usize,
+    /// 跳过数量
+    pub skipped_count: usize,
     /// 总数量
     pub total_count: usize,
+    /// 失败路径样本（最多 `MAX_FAILED_PATHS` 个，用于告警与摘要）
+    pub failed_paths: Vec<String>,
+    /// 失败明细（最多 `failure_cap` 条）
+    pub failures: Vec<BatchFailure>,
+    /// 超出上限、未保留明细的失败数
+    pub failures_omitted: usize,
+    /// 失败明细上限（默认 `DEFAULT_FAILURE_CAP`）
+    pub failure_cap: usize,
+    /// 疑似被安全软件拦截的统计
+    pub interference: InterferenceTally,
+    /// 带有警告的对象数
+    pub warned_count: usize,
+    /// 各类警告的出现次数
+    pub warnings: BTreeMap<WarningKind, usize>,
+    /// 自动调整并发度时每个卷的调整摘要（固定并发度时为空）
+    pub tuning: Vec<VolumeTuning>,
+    /// 批次被取消时的处理结果（未取消或取消前已处理完全部对象时为 None）
+    pub cancelled: Option<CancelledBatch>,
+    /// 按目录的计数（仅在开启目录汇总时存在，见 `dirsummary` 模块）
+    pub dirs: Option<DirAggregator>,
+}
+
+impl Default for BatchResult {
+    fn default() -> Self {
+        Self {
+            success_count: 0,
+            failed_count: 0,
+            downgraded_count: 0,
+            skipped_count: 0,
+            total_count: 0,
+            failed_paths: Vec::new(),
+            failures: Vec::new(),
+            failures_omitted: 0,
+            failure_cap: DEFAULT_FAILURE_CAP,
+            interference: InterferenceTally::default(),
+            warned_count: 0,
+            warnings: BTreeMap::new(),
+            tuning: Vec::new(),
+            cancelled: None,
+            dirs: None,
+        }
+    }
+}
+
+/// `BatchResult.failed_paths` 保留的最大路径数
+pub const MAX_FAILED_PATHS: usize = 20;
+
+impl BatchResult {
+    /// 设置失败明细上限
+    pub fn with_failure_cap(mut self, cap: usize) -> Self {
+        self.failure_cap = cap;
+        self
+    }
+
+    /// 开启按目录汇总（`max_depth` 见 `DirAggregator`）
+    pub fn with_dir_summary(mut self, max_depth: usize) -> Self {
+        self.dirs = Some(DirAggregator::new(max_depth));
+        self
+    }
+
+    /// 按目录汇总的结果树（未开启或没有处理任何对象时为 None）
+    pub fn dir_summary(&self) -> Option<DirSummary> {
+        self.dirs.as_ref()?.summary()
+    }
+
+    /// 明细被截断时的摘要行（如 `…以及另外 398,712 个失败`）
+    pub fn failure_overflow_line(&self) -> Option<String> {
+        (self.failures_omitted > 0)
+            .then(|| format!("…以及另外 {} 个失败", failures::format_count(self.failures_omitted)))
+    }
+
+    /// 是否有对象完成但带有警告
+    pub fn has_warnings(&self) -> bool {
+        self.warned_count > 0
+    }
+
+    /// 按类型汇总的警告（如 `遇到暂时性错误，重试后成功：3 次`）
+    pub fn warning_lines(&self) -> Vec<String> {
+        self.warnings
+            .iter()
+            .map(|(kind, count)| format!("{}：{} 次", kind.label(), count))
+            .collect()
+    }
+
+    /// 对批量结果进行分类
+    ///
+    /// # 规则
+    /// - 未处理任何对象（空列表，或开始前即被取消）：`Empty`
+    /// - 没有失败（含降级与跳过）：`AllSucceeded`
+    /// - 有失败且没有成功或跳过的对象：`AllFailed`
+    /// - 其余情况：`Partial`
+    pub fn outcome(&self) -> BatchOutcome {
+        let processed = self.success_count + self.failed_count + self.skipped_count;
+        if processed == 0 {
+            BatchOutcome::Empty
+        } else if self.failed_count == 0 {
+            BatchOutcome::AllSucceeded
+        } else if self.success_count == 0 && self.skipped_count == 0 {
+            BatchOutcome::AllFailed
+        } else {
+            BatchOutcome::Partial
+        }
+    }
 }
 
 impl Display for BatchResult {
     fn fmt(&self, f: &mut Formatter<'_>) -> std::fmt::Result {
         write!(
             f,
-            "操作完成：成功 {} 个，失败 {} 个，降级 {} 个（共 {} 个）",
-            self.success_count, self.failed_count, self.downgraded_count, self.total_count
-        )
+            "{}：成功 {} 个，失败 {} 个，降级 {} 个，跳过 {} 个（共 {} 个）",
+            self.outcome(),
+            self.success_count,
+            self.failed_count,
+            self.downgraded_count,
+            self.skipped_count,
+            self.total_count
+        )?;
+        if self.has_warnings() {
+            write!(f, "，{} 个有警告", self.warned_count)?;
+        }
+        Ok(())
+    }
+}
+
+/// 将单个对象的处理结果累计到批量统计中
+pub(crate) fn accumulate(result: &mut BatchResult, path: &Path, outcome: &amberlock_types::Result<LockResult>) {
+    tally(result, path, outcome.as_ref());
+}
+
+/// 将单个对象的处理结果累计到批量统计中，同时按类型计入警告
+pub(crate) fn accumulate_outcome(
+    result: &mut BatchResult,
+    path: &Path,
+    outcome: &amberlock_types::Result<LockOutcome>,
+) {
+    if let Ok(outcome) = outcome
+        && outcome.has_warnings()
+    {
+        result.warned_count += 1;
+        for warning in &outcome.warnings {
+            *result.warnings.entry(warning.kind()).or_default() += 1;
+        }
+    }
+    tally(
+        result,
+        path,
+        outcome.as_ref().map(|outcome| &outcome.result),
+    );
+}
+
+fn tally(
+    result: &mut BatchResult,
+    path: &Path,
+    outcome: std::result::Result<&LockResult, &AmberlockError>,
+) {
+    match outcome {
+        Ok(LockResult::Success) => result.success_count += 1,
+        Ok(LockResult::Downgraded) => {
+            result.success_count += 1;
+            result.downgraded_count += 1;
+        }
+        Ok(LockResult::Skipped) => result.skipped_count += 1,
+        Ok(LockResult::Recovered) => result.success_count += 1,
+        Err(e) => {
+            result.failed_count += 1;
+            if result.failed_paths.len() < MAX_FAILED_PATHS {
+                result.failed_paths.push(path.to_string_lossy().to_string());
+            }
+            // 超过上限后只计数，不再为路径和错误描述分配内存
+            if result.failures.len() < result.failure_cap {
+                result.failures.push(BatchFailure {
+                    path: path.to_string_lossy().to_string(),
+                    error: e.to_string(),
+                });
+            } else {
+                result.failures_omitted += 1;
+            }
+        }
+    }
+    result.interference.observe(outcome);
+    if let Some(dirs) = &mut result.dirs {
+        dirs.record(path, outcome);
+    }
+}
+
+/// 批量操作结果分类
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum BatchOutcome {
+    /// 没有可处理的对象
+    Empty,
+    /// 全部成功
+    AllSucceeded,
+    /// 部分失败
+    Partial,
+    /// 全部失败
+    AllFailed,
+}
+
+impl BatchOutcome {
+    /// 批处理模式的进程退出码
+    ///
+    /// # 返回
+    /// - `0`: 全部成功
+    /// - `1`: 全部失败
+    /// - `2`: 部分失败
+    /// - `3`: 没有匹配到任何对象（便于脚本发现路径拼写错误）
+    pub fn exit_code(self) -> i32 {
+        match self {
+            BatchOutcome::AllSucceeded => 0,
+            BatchOutcome::AllFailed => 1,
+            BatchOutcome::Partial => 2,
+            BatchOutcome::Empty => 3,
+        }
+    }
+}
+
+impl Display for BatchOutcome {
+    fn fmt(&self, f: &mut Formatter<'_>) -> std::fmt::Result {
+        match self {
+            BatchOutcome::Empty => write!(f, "没有可处理的对象"),
+            BatchOutcome::AllSucceeded => write!(f, "全部成功"),
+            BatchOutcome::Partial => write!(f, "部分失败"),
+            BatchOutcome::AllFailed => write!(f, "全部失败"),
+        }
     }
 }
 
@@ -70,8 +653,30 @@ pub struct LockOptions {
     pub desired_level: LabelLevel,
     /// 保护模式
     pub mode: ProtectMode,
-    /// 并发度上限
-    pub parallelism: usize,
+    /// 并发度：固定的工作线程数，或按每个卷的实测延迟自动调整（见 `autotune` 模块）
+    pub parallelism: ParallelismMode,
+    /// 可移动/网络卷的节流参数（固定磁盘不限，见 `throttle` 模块）
+    pub slow_volume_throttle: VolumeThrottle,
+    /// 与进行中的操作路径重叠时是否等待其结束（否则立即返回冲突错误）
+    pub wait_for_conflicts: bool,
+    /// 操作备注，写入本次操作的每条记录（见 `sanitize_comment`）
+    pub comment: Option<String>,
+    /// 诊断跟踪记录器（仅对本次操作生效，见 `trace` 模块）
+    pub trace: Option<Arc<TraceRecorder>>,
+    /// 批次 ID，写入本次操作的每条记录（批量入口未设置时自动生成）
+    pub batch_id: Option<String>,
+    /// 用户已确认锁定卷根的风险（仅在 GUI/命令行确认后设置，见 `VolumeRootPolicy`）
+    pub acknowledged_volume_root: bool,
+    /// 发起操作的入口，写入本次操作的每条记录
+    pub origin: OperationOrigin,
+    /// 用户已确认的封印计划（见 `seal` 模块）
+    ///
+    /// 路径有对应计划时，写入前检查描述符自预览后未被修改，否则以 `PlanStale` 失败
+    pub seal_plans: Vec<SealPlan>,
+    /// 批次被取消时对已上锁对象的处理方式（见 `rollback` 模块）
+    pub on_cancel: CancelBehavior,
+    /// 是否按目录汇总结果（见 `dirsummary` 模块），递归操作建议开启
+    pub collect_dir_summary: bool,
 }
 
 impl Default for LockOptions {
@@ -79,37 +684,190 @@ impl Default for LockOptions {
         Self {
             desired_level: LabelLevel::High,
             mode: ProtectMode::ReadOnly,
-            parallelism: 4,
+            parallelism: ParallelismMode::default(),
+            slow_volume_throttle: VolumeThrottle::SLOW_VOLUME_DEFAULT,
+            wait_for_conflicts: false,
+            comment: None,
+            trace: None,
+            batch_id: None,
+            acknowledged_volume_root: false,
+            origin: OperationOrigin::Unknown,
+            seal_plans: Vec::new(),
+            on_cancel: CancelBehavior::default(),
+            collect_dir_summary: false,
         }
     }
 }
 
+impl LockOptions {
+    /// 返回用于一次批量操作的副本
+    ///
+    /// 已设置 `batch_id` 时原样保留，否则由 `logger` 的记录 ID 来源生成
+    pub fn in_batch(&self, logger: &OperationLog) -> LockOptions {
+        let mut opts = self.clone();
+        if opts.batch_id.is_none() {
+            opts.batch_id = Some(logger.stamper().new_id());
+        }
+        opts
+    }
+
+    /// 本次批量操作的初始统计（按 `collect_dir_summary` 开启目录汇总）
+    #[cfg_attr(not(feature = "winsec"), allow(dead_code))]
+    pub(crate) fn batch_result(&self, total_count: usize) -> BatchResult {
+        let result = BatchResult {
+            total_count,
+            ..Default::default()
+        };
+        if self.collect_dir_summary {
+            result.with_dir_summary(DEFAULT_DIR_SUMMARY_DEPTH)
+        } else {
+            result
+        }
+    }
+
+    /// 路径对应的封印计划
+    pub fn seal_plan_for(&self, path: &str) -> Option<&SealPlan> {
+        self.seal_plans.iter().find(|plan| plan.path == path)
+    }
+}
+
 /// 操作上下文
-pub struct OperationContext<'a> {
+#[cfg_attr(not(feature = "winsec"), allow(dead_code))]
+pub(crate) struct OperationContext<'a> {
     pub path_str: String,
+    /// 传给 Win32 API 的路径（末尾带点/空格时为 `\\?\` 形式）
+    pub target: String,
     pub target_kind: TargetKind,
     pub user_sid: &'a str,
-    pub logger: &'a NdjsonWriter,
+    pub logger: &'a OperationLog,
+    /// 附加审计信息（写入 `LockRecord.details`）
+    pub details: Option<String>,
+    /// 授权凭据名称（写入 `LockRecord.authorized_by`）
+    pub authorized_by: Option<String>,
+    /// 操作备注（写入 `LockRecord.comment`，已规范化）
+    pub comment: Option<String>,
+    /// 所属批次（写入 `LockRecord.batch_id`）
+    pub batch_id: Option<String>,
+    /// 目标为已确认的卷根（写入 `LockRecord.volume_root_acknowledged`）
+    pub volume_root_acknowledged: bool,
+    /// 降级原因（写入 `LockRecord.downgrade_reason`）
+    pub downgrade_reason: Option<DowngradeReason>,
+    /// 发起操作的入口（写入 `LockRecord.origin`）
+    pub origin: OperationOrigin,
+    /// 上锁后读取的文件标识（写入 `LockRecord.file_id`）
+    pub file_id: Option<FileId>,
+    /// 操作附带的警告（写入 `LockRecord.warnings`，并随结果返回）
+    pub warnings: Vec<Warning>,
+    /// 记录时间戳与 ID 来源（默认取自 `logger`）
+    pub stamper: &'a dyn RecordStamper,
 }
 
+#[cfg_attr(not(feature = "winsec"), allow(dead_code))]
 impl<'a> OperationContext<'a> {
     pub fn new(
         path: &Path,
         user_sid: &'a str,
-        logger: &'a NdjsonWriter,
+        logger: &'a OperationLog,
     ) -> Self {
+        // 末尾分隔符与混合分隔符在此统一，Win32 调用、日志记录与清单键都使用规范化后的路径
+        let path = &pathutil::normalize_path(path);
+        let target = pathutil::canonical_path(path);
+        let warnings = if pathutil::resolves_through_reparse_point(path) {
+            vec![Warning::ResolvedThroughReparsePoint]
+        } else {
+            Vec::new()
+        };
         Self {
             path_str: path.to_string_lossy().to_string(),
-            target_kind: if path.is_dir() {
+            target_kind: if target.is_dir() {
                 TargetKind::Directory
             } else {
                 TargetKind::File
             },
+            target: target.to_string_lossy().to_string(),
             user_sid,
             logger,
+            details: None,
+            authorized_by: None,
+            comment: None,
+            batch_id: None,
+            volume_root_acknowledged: false,
+            downgrade_reason: None,
+            origin: OperationOrigin::Unknown,
+            file_id: None,
+            warnings,
+            stamper: logger.stamper(),
         }
     }
 
+    /// 替换记录时间戳与 ID 来源
+    #[cfg(test)]
+    pub fn with_stamper(mut self, stamper: &'a dyn RecordStamper) -> Self {
+        self.stamper = stamper;
+        self
+    }
+
+    /// 附加审计信息（如通配符来源模式）
+    pub fn with_details(mut self, details: Option<&str>) -> Self {
+        self.details = details.map(str::to_string);
+        self
+    }
+
+    /// 记录授权本次操作的保险库凭据名称
+    pub fn with_authorized_by(mut self, credential: Option<&str>) -> Self {
+        self.authorized_by = credential.map(str::to_string);
+        self
+    }
+
+    /// 附加用户填写的操作备注（经 `sanitize_comment` 规范化）
+    pub fn with_comment(mut self, comment: Option<&str>) -> Self {
+        self.comment = comment.and_then(sanitize_comment);
+        self
+    }
+
+    /// 标记所属批次
+    pub fn with_batch_id(mut self, batch_id: Option<&str>) -> Self {
+        self.batch_id = batch_id.map(str::to_string);
+        self
+    }
+
+    /// 记录发起操作的入口
+    pub fn with_origin(mut self, origin: OperationOrigin) -> Self {
+        self.origin = origin;
+        self
+    }
+
+    /// 记录上锁后的文件标识（供移动检测，见 `moves` 模块）
+    pub fn with_file_id(mut self, file_id: Option<FileId>) -> Self {
+        self.file_id = file_id;
+        self
+    }
+
+    /// 追加警告
+    pub fn with_warnings(mut self, warnings: impl IntoIterator<Item = Warning>) -> Self {
+        self.warnings.extend(warnings);
+        self
+    }
+
+    /// 以上下文中的警告组成单对象结果
+    pub fn outcome(&self, result: LockResult) -> LockOutcome {
+        LockOutcome {
+            result,
+            warnings: self.warnings.clone(),
+        }
+    }
+
+    /// 记录卷根确认与降级原因
+    pub fn with_policy(
+        mut self,
+        volume_root_acknowledged: bool,
+        downgrade: Option<DowngradeReason>,
+    ) -> Self {
+        self.volume_root_acknowledged = volume_root_acknowledged;
+        self.downgrade_reason = downgrade;
+        self
+    }
+
     /// 记录日志
     pub fn log_and_track(
         &self,
@@ -121,27 +879,478 @@ impl<'a> OperationContext<'a> {
         errors: Vec<String>,
     ) {
         let record = LockRecord {
-            id: Uuid::new_v4().to_string(),
+            id: self.stamper.new_id(),
             path: self.path_str.clone(),
             kind: self.target_kind,
             mode,
             level_applied,
-            time_utc: now_iso8601(),
+            time_utc: self.stamper.now(),
+            seq_no: self.stamper.next_seq(),
             user_sid: self.user_sid.to_string(),
             owner_before: None,
-            sddl_before,
-            sddl_after,
+            sddl_before: sddl_before.map(|sddl| self.sddl_field(sddl)),
+            sddl_after: sddl_after.map(|sddl| self.sddl_field(sddl)),
             status: status.to_string(),
             errors,
+            details: self.details.clone(),
+            authorized_by: self.authorized_by.clone(),
+            comment: self.comment.clone(),
+            batch_id: self.batch_id.clone(),
+            volume_root_acknowledged: self.volume_root_acknowledged,
+            downgrade_reason: self.downgrade_reason,
+            origin: self.origin,
+            app_version: Some(APP_VERSION.to_string()),
+            file_id: self.file_id,
+            warnings: self.warnings.clone(),
         };
-        let _ = self.logger.write_record(&record);
+        let _ = self.logger.append(&record);
+    }
+
+    /// 按日志的 `compress_sddl` 设置决定 SDDL 字段是否压缩
+    fn sddl_field(&self, sddl: String) -> MaybeCompressed<String> {
+        if self.logger.compresses_sddl() {
+            MaybeCompressed::compress(sddl, SDDL_COMPRESS_THRESHOLD)
+        } else {
+            sddl.into()
+        }
+    }
+
+    /// 记录移除标签的结果
+    ///
+    /// # 参数
+    /// - `sddl_before`: 移除前的标签
+    /// - `result`: 移除调用的结果
+    pub fn log_unlock(
+        &self,
+        sddl_before: Option<String>,
+        result: amberlock_types::Result<()>,
+    ) -> amberlock_types::Result<LockOutcome> {
+        match result {
+            Ok(()) => {
+                self.log_and_track(
+                    ProtectMode::ReadOnly,
+                    LabelLevel::Medium,
+                    sddl_before,
+                    None,
+                    "unlocked",
+                    vec![],
+                );
+                Ok(self.outcome(LockResult::Success))
+            }
+            Err(e) => {
+                self.log_and_track(
+                    ProtectMode::ReadOnly,
+                    LabelLevel::Medium,
+                    sddl_before,
+                    None,
+                    "error",
+                    vec![format!("{:?}", e)],
+                );
+                Err(e)
+            }
+        }
+    }
+}
+
+/// 操作备注最大长度（字符数，不含截断标记）
+pub const MAX_COMMENT_CHARS: usize = 512;
+/// 备注被截断时附加的标记
+pub const COMMENT_TRUNCATED_MARKER: &str = "…[已截断]";
+
+/// 规范化用户填写的操作备注
+///
+/// # 规则
+/// - 控制字符（含换行、制表符）替换为空格，并去除首尾空白
+/// - 超过 `MAX_COMMENT_CHARS` 个字符时截断并附加 `COMMENT_TRUNCATED_MARKER`
+///
+/// # 返回
+/// 规范化后为空时返回 None
+pub fn sanitize_comment(raw: &str) -> Option<String> {
+    let cleaned: String = raw
+        .chars()
+        .map(|c| if c.is_control() { ' ' } else { c })
+        .collect();
+    let trimmed = cleaned.trim();
+    if trimmed.is_empty() {
+        return None;
     }
+    if trimmed.chars().count() <= MAX_COMMENT_CHARS {
+        return Some(trimmed.to_string());
+    }
+    let mut truncated: String = trimmed.chars().take(MAX_COMMENT_CHARS).collect();
+    truncated.push_str(COMMENT_TRUNCATED_MARKER);
+    Some(truncated)
 }
 
 /// 获取当前 UTC 时间戳（ISO8601）
-pub fn now_iso8601() -> String {
-    use time::OffsetDateTime;
-    OffsetDateTime::now_utc()
-        .format(&time::format_description::well_known::Rfc3339)
-        .unwrap()
-}
\ No newline at end of file
+///
+/// # 注意
+/// 构造 `LockRecord` 时请使用 `OperationLog::stamper()`，以便测试注入确定性时间
+pub(crate) fn now_iso8601() -> String {
+    SystemStamper.now()
+}
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use amberlock_storage::{FixedStamper, NdjsonReader, SequenceStamper};
+    use std::sync::Arc;
+    use tempfile::TempDir;
+
+    #[test]
+    fn test_sanitize_comment() {
+        assert_eq!(sanitize_comment("   \t\n "), None);
+        assert_eq!(
+            sanitize_comment("  发布冻结\r\n工单 INC-1234\u{7}  ").as_deref(),
+            Some("发布冻结  工单 INC-1234")
+        );
+
+        let long = "备".repeat(MAX_COMMENT_CHARS + 10);
+        let sanitized = sanitize_comment(&long).expect("非空备注");
+        assert!(sanitized.ends_with(COMMENT_TRUNCATED_MARKER));
+        assert_eq!(
+            sanitized.chars().count(),
+            MAX_COMMENT_CHARS + COMMENT_TRUNCATED_MARKER.chars().count()
+        );
+        println!("✅ 操作备注规范化与截断正常");
+    }
+
+    #[test]
+    fn test_comment_is_written_to_record() {
+        let temp_dir = TempDir::new().expect("创建临时目录失败");
+        let log_path = temp_dir.path().join("log.ndjson");
+        let logger = OperationLog::open(&log_path)
+            .expect("打开日志失败")
+            .with_stamper(Arc::new(SequenceStamper::new("2025-01-01T00:00:00Z")));
+        let target = temp_dir.path().join("target.txt");
+
+        let ctx = OperationContext::new(&target, "S-1-5-21-test", &logger)
+            .with_comment(Some("  INC-1234\n发布冻结 "));
+        ctx.log_and_track(
+            ProtectMode::ReadOnly,
+            LabelLevel::High,
+            None,
+            None,
+            "success",
+            vec![],
+        );
+        OperationContext::new(&target, "S-1-5-21-test", &logger)
+            .with_comment(Some("   "))
+            .log_and_track(
+                ProtectMode::ReadOnly,
+                LabelLevel::High,
+                None,
+                None,
+                "success",
+                vec![],
+            );
+
+        logger.flush().unwrap();
+        let mut reader = NdjsonReader::open(&log_path).expect("打开日志失败");
+        let records = reader.read_last_n(10).expect("读取日志失败");
+        assert_eq!(records[0]["comment"], "INC-1234 发布冻结");
+        assert!(records[1].get("comment").is_none(), "空备注不应写入");
+        assert_eq!(records[0]["id"], "00000000-0000-4000-8000-000000000001");
+        assert_eq!(records[1]["time_utc"], "2025-01-01T00:00:01.000000Z");
+        assert_eq!(records[1]["seq_no"], 2);
+        println!("✅ 操作备注写入日志记录");
+    }
+
+    #[test]
+    fn test_origin_is_stamped_on_records() {
+        use crate::backend::{FakeBackend, lock_object};
+        use crate::revert::{BatchEntry, BatchRevert};
+
+        let temp_dir = TempDir::new().expect("创建临时目录失败");
+        let log_path = temp_dir.path().join("log.ndjson");
+        let logger = OperationLog::open(&log_path).expect("打开日志失败");
+        let backend = FakeBackend::new();
+        let target = temp_dir.path().join("target.txt");
+
+        // 快捷键触发的上锁
+        let ctx = OperationContext::new(&target, "S-1-5-21-test", &logger)
+            .with_origin(OperationOrigin::GuiShortcut);
+        let change = lock_object(&backend, &ctx.target, LabelLevel::High);
+        ctx.log_and_track(
+            ProtectMode::ReadOnly,
+            LabelLevel::High,
+            None,
+            change.after.as_ref().map(|label| label.sddl.clone()),
+            "success",
+            vec![],
+        );
+        // 按钮触发的批次撤销
+        let revert = BatchRevert::new("batch-a", "S-1-5-21-test", &logger)
+            .with_origin(OperationOrigin::GuiButton);
+        let entry = BatchEntry {
+            path: target.clone(),
+            level: LabelLevel::High,
+            sddl_after: change.after.map(|label| label.sddl),
+        };
+        revert.revert_entry(&backend, &entry, &logger).expect("撤销失败");
+        // 未指定来源
+        OperationContext::new(&target, "S-1-5-21-test", &logger).log_and_track(
+            ProtectMode::ReadOnly,
+            LabelLevel::Medium,
+            None,
+            None,
+            "unlocked",
+            vec![],
+        );
+
+        logger.flush().unwrap();
+        let records = NdjsonReader::open(&log_path)
+            .unwrap()
+            .read_last_n(3)
+            .unwrap();
+        assert_eq!(records[0]["origin"], "gui_shortcut");
+        assert_eq!(records[1]["origin"], "gui_button");
+        assert!(records[2].get("origin").is_none(), "未知来源不应写入");
+
+        // 旧记录没有 origin 字段，反序列化为 Unknown
+        let record: LockRecord = serde_json::from_value(records[2].clone()).unwrap();
+        assert_eq!(record.origin, OperationOrigin::Unknown);
+        println!("✅ 各入口的来源写入日志记录");
+    }
+
+    /// 完整 `LockRecord` 的序列化结果必须与黄金文件逐字一致；
+    /// 有意修改记录结构时请同步更新 `testdata/lock_record.golden.json`
+    #[test]
+    fn test_lock_record_golden() {
+        let temp_dir = TempDir::new().expect("创建临时目录失败");
+        let log_path = temp_dir.path().join("log.ndjson");
+        let logger = OperationLog::open(&log_path).expect("打开日志失败");
+        let stamper = FixedStamper::new(
+            "2025-06-01T08:30:00.000000Z",
+            "6f1c2a9e-0b7d-4e55-9a3c-2d4b8e7f1a00",
+        );
+
+        OperationContext::new(Path::new("C:\\Data\\report.docx"), "S-1-5-21-1000", &logger)
+            .with_stamper(&stamper)
+            .with_details(Some("preset: documents_safe"))
+            .with_authorized_by(Some("admin"))
+            .with_comment(Some("INC-1234"))
+            .with_batch_id(Some("3b9d0c4e-7a21-4f6b-8e15-c0d2a9f4b7e3"))
+            .with_origin(OperationOrigin::GuiButton)
+            .log_and_track(
+                ProtectMode::Seal,
+                LabelLevel::High,
+                Some("S:(ML;;NW;;;ME)".to_string()),
+                Some("S:(ML;;NWNRNX;;;HI)".to_string()),
+                "error",
+                vec!["拒绝访问".to_string()],
+            );
+        logger.flush().unwrap();
+
+        let mut reader = NdjsonReader::open(&log_path).expect("打开日志失败");
+        let mut records = reader.read_last_n(1).expect("读取日志失败");
+        // 版本号随提交变化，确认已写入后替换为固定值
+        assert_eq!(records[0]["app_version"], APP_VERSION);
+        records[0]["app_version"] = "0.1.0+0123456789ab".into();
+        let actual = serde_json::to_string_pretty(&records[0]).unwrap();
+        let expected = include_str!("../testdata/lock_record.golden.json");
+        assert_eq!(
+            actual.trim_end(),
+            expected.trim_end(),
+            "LockRecord 序列化结构发生变化"
+        );
+    }
+
+    #[test]
+    fn test_sddl_compression_shrinks_recursive_batch() {
+        let temp_dir = TempDir::new().expect("创建临时目录失败");
+        // 共享文件夹上常见的大 DACL：数百条显式 ACE
+        let aces: String = (0..300)
+            .map(|i| {
+                format!(
+                    "(A;OICI;0x1301bf;;;S-1-5-21-1004336348-1177238915-682003330-{})",
+                    1000 + i
+                )
+            })
+            .collect();
+        let before = format!("O:BAG:SYD:PAI{}", aces);
+        let after = format!("{}S:(ML;OICI;NW;;;HI)", before);
+
+        let write_batch = |name: &str, compress: bool| {
+            let log_path = temp_dir.path().join(name);
+            let logger = OperationLog::open(&log_path)
+                .expect("打开日志失败")
+                .with_sddl_compression(compress);
+            for i in 0..200 {
+                let target = temp_dir
+                    .path()
+                    .join(format!("share\\dir{}\\file{}.txt", i / 20, i));
+                OperationContext::new(&target, "S-1-5-21-test", &logger)
+                    .with_batch_id(Some("batch-a"))
+                    .log_and_track(
+                        ProtectMode::ReadOnly,
+                        LabelLevel::High,
+                        Some(before.clone()),
+                        Some(after.clone()),
+                        "success",
+                        vec![],
+                    );
+            }
+            logger.flush().unwrap();
+            log_path
+        };
+        let plain = write_batch("plain.ndjson", false);
+        let compressed = write_batch("compressed.ndjson", true);
+
+        let plain_size = std::fs::metadata(&plain).unwrap().len();
+        let compressed_size = std::fs::metadata(&compressed).unwrap().len();
+        assert!(
+            compressed_size * 10 < plain_size,
+            "压缩后 {} 字节，未压缩 {} 字节",
+            compressed_size,
+            plain_size
+        );
+
+        // 读取时透明解压
+        let mut reader = NdjsonReader::open(&compressed).expect("打开日志失败");
+        for value in reader.read_last_n(200).expect("读取日志失败") {
+            let record: LockRecord = serde_json::from_value(value).expect("应为 LockRecord");
+            assert!(record.sddl_after.as_ref().unwrap().is_compressed());
+            assert_eq!(record.sddl_before_text().as_deref(), Some(before.as_str()));
+            assert_eq!(record.sddl_after_text().as_deref(), Some(after.as_str()));
+        }
+        println!(
+            "✅ SDDL 压缩将日志从 {} 字节减小到 {} 字节",
+            plain_size, compressed_size
+        );
+    }
+
+    #[test]
+    fn test_warnings_propagate_to_batch_and_records() {
+        use amberlock_storage::query::QueryBuilder;
+
+        let temp_dir = TempDir::new().expect("创建临时目录失败");
+        let log_path = temp_dir.path().join("log.ndjson");
+        let logger = OperationLog::open(&log_path).expect("打开日志失败");
+        let backend = FakeBackend::new();
+        let (retried, unverified, clean, failed) = (
+            r"C:\Data\retried.txt",
+            r"C:\Data\unverified.txt",
+            r"C:\Data\clean.txt",
+            r"C:\Data\failed.txt",
+        );
+
+        // 首次写入被拒绝、预检通过，重试成功
+        backend.fail_next(LabelOp::Set, retried, 5);
+        backend.set_precheck(
+            retried,
+            AccessPrecheck {
+                has_se_security: true,
+                label_access: true,
+            },
+        );
+        // 写入前的读取正常，写入后的读取失败
+        backend.push_script(LabelOp::Get, unverified, None);
+        backend.fail_next(LabelOp::Get, unverified, 32);
+        backend.fail_next(LabelOp::Set, failed, 2);
+
+        let opts = LockOptions::default();
+        let mut result = BatchResult::default();
+        for target in [retried, unverified, clean, failed] {
+            let ctx = OperationContext::new(Path::new(target), "S-1-5-21-test", &logger);
+            let outcome =
+                volume::lock_with_policy(&backend, ctx, &opts, LabelLevel::High, |_| Ok(()));
+            result.total_count += 1;
+            accumulate_outcome(&mut result, Path::new(target), &outcome);
+        }
+        logger.flush().unwrap();
+
+        assert_eq!(result.success_count, 3);
+        assert_eq!(result.failed_count, 1);
+        assert_eq!(result.warned_count, 2);
+        assert_eq!(
+            result.warnings,
+            BTreeMap::from([
+                (WarningKind::RetriedAfterTransientError, 1),
+                (WarningKind::VerificationSkipped, 1),
+            ])
+        );
+        assert_eq!(result.outcome(), BatchOutcome::Partial);
+        assert!(result.to_string().ends_with("，2 个有警告"));
+        assert_eq!(result.warning_lines().len(), 2);
+
+        // 写入日志后按类型反序列化，警告原样还原
+        let records: Vec<LockRecord> = NdjsonReader::open(&log_path)
+            .unwrap()
+            .read_last_n(4)
+            .unwrap()
+            .into_iter()
+            .map(|value| serde_json::from_value(value).expect("应为 LockRecord"))
+            .collect();
+        assert_eq!(
+            records[0].warnings,
+            vec![Warning::RetriedAfterTransientError { attempts: 2 }]
+        );
+        assert!(records[0].errors.is_empty(), "重试不再以文本写入 errors");
+        assert_eq!(records[1].warnings, vec![Warning::VerificationSkipped]);
+        assert!(records[2].warnings.is_empty());
+        assert_eq!(records[3].status, "error");
+
+        // 没有警告的记录不写入该字段
+        let raw = std::fs::read_to_string(&log_path).unwrap();
+        let clean_line = raw.lines().find(|line| line.contains("clean.txt")).unwrap();
+        assert!(!clean_line.contains("warnings"));
+
+        let hits = QueryBuilder::new(&log_path)
+            .filter_has_warning(WarningKind::VerificationSkipped)
+            .execute()
+            .expect("查询失败");
+        assert_eq!(hits.len(), 1);
+        assert_eq!(hits[0]["path"], unverified);
+        println!("✅ 警告从后端传递到批量统计与日志记录");
+    }
+
+    #[cfg(unix)]
+    #[test]
+    fn test_reparse_point_warning_is_recorded() {
+        let temp_dir = TempDir::new().expect("创建临时目录失败");
+        let real = temp_dir.path().join("real");
+        std::fs::create_dir(&real).expect("创建目录失败");
+        let link = temp_dir.path().join("link");
+        std::os::unix::fs::symlink(&real, &link).expect("创建符号链接失败");
+        let logger = OperationLog::open(temp_dir.path().join("log.ndjson")).expect("打开日志失败");
+        let backend = FakeBackend::new();
+
+        let target = link.join("a.txt");
+        let ctx = OperationContext::new(&target, "S-1-5-21-test", &logger);
+        let outcome = volume::lock_with_policy(
+            &backend,
+            ctx,
+            &LockOptions::default(),
+            LabelLevel::High,
+            |_| Ok(()),
+        )
+        .expect("上锁失败");
+        assert_eq!(outcome.result, LockResult::Success);
+        assert_eq!(outcome.warnings, vec![Warning::ResolvedThroughReparsePoint]);
+
+        let ctx = OperationContext::new(&real.join("a.txt"), "S-1-5-21-test", &logger);
+        assert!(ctx.warnings.is_empty());
+    }
+
+    #[test]
+    #[cfg(not(feature = "winsec"))]
+    fn test_portable_surface_without_winsec() {
+        use std::path::PathBuf;
+
+        // 关闭 winsec 后后台任务、结果累计与分类仍可使用
+        let paths = vec![PathBuf::from("a"), PathBuf::from("b"), PathBuf::from("c")];
+        let handle = spawn_operation(paths, |path| {
+            if path == Path::new("c") {
+                Err(amberlock_types::AmberlockError::Unsupported)
+            } else {
+                Ok(LockResult::Skipped)
+            }
+        });
+        let result = handle.join().expect("后台任务失败");
+
+        assert_eq!(result.skipped_count, 2);
+        assert_eq!(result.failed_paths, vec!["c".to_string()]);
+        assert_eq!(result.outcome(), BatchOutcome::Partial);
+        println!("✅ 未启用 winsec 时纯逻辑可用");
+    }
+}
@@ -0,0 +1,244 @@
+//! 进程生命周期加固
+//!
+//! 工作线程正在批量写入时进程异常退出，缓冲区中的日志行与积压记录会丢失：
+//! - `install_panic_hook`: panic 时把消息与回溯写入诊断日志，刷新所有日志
+//!   （`amberlock_storage::flush_all_logs`），并尽力向操作日志追加一条
+//!   `PANIC_STATUS` 记录
+//! - `install_console_ctrl_handler`: 批处理/命令行模式下收到 Ctrl+C 或控制台关闭时
+//!   取消所有登记中的操作并刷新日志
+
+use crate::build_info::APP_VERSION;
+use crate::registry::ActiveOperations;
+use amberlock_storage::{OperationLog, flush_all_logs};
+use amberlock_types::{LabelLevel, LockRecord, OperationOrigin, ProtectMode, TargetKind};
+use std::any::Any;
+use std::backtrace::Backtrace;
+use std::io::Write;
+use std::panic::PanicHookInfo;
+use std::path::{Path, PathBuf};
+use std::sync::Arc;
+
+/// 进程 panic 记录的日志状态
+pub const PANIC_STATUS: &str = "process_panic";
+
+/// 构造进程 panic 记录
+///
+/// # 参数
+/// - `logger`: 操作日志（提供时间戳与 ID）
+/// - `user_sid`: 用户 SID
+/// - `message`: panic 消息
+/// - `details`: 发生位置与线程
+///
+/// # 注意
+/// 记录的路径为当前可执行文件，级别与模式无实际含义
+pub fn panic_record(
+    logger: &OperationLog,
+    user_sid: &str,
+    message: &str,
+    details: Option<String>,
+) -> LockRecord {
+    let exe = std::env::current_exe()
+        .map(|path| path.to_string_lossy().to_string())
+        .unwrap_or_default();
+    LockRecord {
+        id: logger.stamper().new_id(),
+        path: exe,
+        kind: TargetKind::File,
+        mode: ProtectMode::ReadOnly,
+        level_applied: LabelLevel::Medium,
+        time_utc: logger.stamper().now(),
+        seq_no: logger.stamper().next_seq(),
+        user_sid: user_sid.to_string(),
+        owner_before: None,
+        sddl_before: None,
+        sddl_after: None,
+        status: PANIC_STATUS.to_string(),
+        errors: vec![message.to_string()],
+        details,
+        authorized_by: None,
+        comment: None,
+        batch_id: None,
+        volume_root_acknowledged: false,
+        downgrade_reason: None,
+        origin: OperationOrigin::Unknown,
+        app_version: Some(APP_VERSION.to_string()),
+        file_id: None,
+        warnings: vec![],
+    }
+}
+
+/// 安装进程级 panic 钩子（应在 `main` 中尽早调用）
+///
+/// # 参数
+/// - `logger`: 写入 panic 记录的操作日志
+/// - `user_sid`: 用户 SID
+/// - `diagnostics_path`: 诊断日志路径（为 None 时不写回溯）
+///
+/// # 注意
+/// 处理完成后调用原有钩子，默认的 stderr 输出保持不变
+pub fn install_panic_hook(
+    logger: Arc<OperationLog>,
+    user_sid: String,
+    diagnostics_path: Option<PathBuf>,
+) {
+    let previous = std::panic::take_hook();
+    std::panic::set_hook(Box::new(move |info| {
+        handle_panic(&logger, &user_sid, diagnostics_path.as_deref(), info);
+        previous(info);
+    }));
+}
+
+fn handle_panic(
+    logger: &OperationLog,
+    user_sid: &str,
+    diagnostics_path: Option<&Path>,
+    info: &PanicHookInfo<'_>,
+) {
+    let message = panic_message(info.payload());
+    let thread = std::thread::current()
+        .name()
+        .unwrap_or("<unnamed>")
+        .to_string();
+    let location = info
+        .location()
+        .map(|l| format!("{}:{}:{}", l.file(), l.line(), l.column()))
+        .unwrap_or_else(|| "未知位置".to_string());
+    let details = format!("thread '{}' at {}", thread, location);
+
+    if let Some(path) = diagnostics_path {
+        let entry = format!(
+            "[{}] panic: {} ({})\n{}\n",
+            logger.stamper().now(),
+            message,
+            details,
+            Backtrace::force_capture()
+        );
+        let _ = std::fs::OpenOptions::new()
+            .create(true)
+            .append(true)
+            .open(path)
+            .and_then(|mut file| file.write_all(entry.as_bytes()));
+    }
+
+    flush_all_logs();
+    logger.append_on_exit(&panic_record(logger, user_sid, &message, Some(details)));
+}
+
+/// 提取 panic 消息
+fn panic_message(payload: &(dyn Any + Send)) -> String {
+    if let Some(message) = payload.downcast_ref::<&str>() {
+        message.to_string()
+    } else if let Some(message) = payload.downcast_ref::<String>() {
+        message.clone()
+    } else {
+        "未知 panic".to_string()
+    }
+}
+
+/// 取消所有登记中的操作并刷新日志（控制台关闭等退出路径）
+///
+/// # 返回
+/// 被请求取消的登记项数量
+pub fn shutdown_in_flight() -> usize {
+    let cancelled = ActiveOperations::global().cancel_all();
+    flush_all_logs();
+    cancelled
+}
+
+/// 安装控制台控制处理器（批处理/命令行模式）
+///
+/// Ctrl+C / Ctrl+Break 只取消进行中的操作并刷新日志，由批处理自行收尾退出；
+/// 关闭控制台、注销与关机在取消并刷新后交给系统默认处理
+#[cfg(feature = "winsec")]
+pub fn install_console_ctrl_handler() -> amberlock_types::Result<()> {
+    use windows::Win32::System::Console::SetConsoleCtrlHandler;
+
+    unsafe { SetConsoleCtrlHandler(Some(console_ctrl_handler), true) }.map_err(|e| {
+        amberlock_types::AmberlockError::Win32 {
+            code: e.code().0 as u32,
+            msg: format!("安装控制台处理器失败: {}", e),
+        }
+    })
+}
+
+#[cfg(feature = "winsec")]
+unsafe extern "system" fn console_ctrl_handler(ctrl_type: u32) -> windows::core::BOOL {
+    use windows::Win32::System::Console::{CTRL_BREAK_EVENT, CTRL_C_EVENT};
+
+    shutdown_in_flight();
+    windows::core::BOOL::from(ctrl_type == CTRL_C_EVENT || ctrl_type == CTRL_BREAK_EVENT)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use amberlock_storage::{NdjsonReader, NdjsonWriter};
+    use tempfile::TempDir;
+
+    #[test]
+    fn test_panic_hook_persists_in_flight_records() {
+        let temp_dir = TempDir::new().expect("创建临时目录失败");
+        let log_path = temp_dir.path().join("log.ndjson");
+        let diagnostics = temp_dir.path().join("diagnostics.log");
+        let buffered_path = temp_dir.path().join("buffered.ndjson");
+        let logger = Arc::new(OperationLog::open(&log_path).expect("打开日志失败"));
+        let buffered = Arc::new(NdjsonWriter::open_append(&buffered_path).unwrap());
+
+        install_panic_hook(
+            logger.clone(),
+            "S-1-5-21-test".to_string(),
+            Some(diagnostics.clone()),
+        );
+        let writer = buffered.clone();
+        let outcome = std::thread::Builder::new()
+            .name("panicking-worker".to_string())
+            .spawn(move || {
+                writer
+                    .write_record(&serde_json::json!({"status": "in_flight"}))
+                    .unwrap();
+                panic!("模拟批量操作中途崩溃");
+            })
+            .unwrap()
+            .join();
+        drop(std::panic::take_hook());
+        assert!(outcome.is_err());
+
+        // 写入器仍存活（未经 Drop 刷新），记录由钩子刷新到磁盘
+        let mut reader = NdjsonReader::open(&buffered_path).expect("打开日志失败");
+        assert_eq!(reader.count_records().unwrap(), 1);
+
+        let mut reader = NdjsonReader::open(&log_path).expect("打开日志失败");
+        let records: Vec<LockRecord> = reader
+            .read_last_n(100)
+            .unwrap()
+            .into_iter()
+            .map(|value| serde_json::from_value(value).unwrap())
+            .collect();
+        let panic_record = records
+            .iter()
+            .find(|r| r.status == PANIC_STATUS && r.errors[0].contains("模拟批量操作中途崩溃"))
+            .expect("应写入 panic 记录");
+        assert!(
+            panic_record
+                .details
+                .as_deref()
+                .unwrap()
+                .contains("panicking-worker")
+        );
+
+        let diagnostics = std::fs::read_to_string(&diagnostics).expect("读取诊断日志失败");
+        assert!(diagnostics.contains("模拟批量操作中途崩溃"));
+        drop(buffered);
+        println!("✅ panic 钩子刷新了缓冲区并写入 panic 记录");
+    }
+
+    #[test]
+    fn test_panic_message() {
+        let payload: Box<dyn Any + Send> = Box::new("静态消息");
+        assert_eq!(panic_message(payload.as_ref()), "静态消息");
+        let payload: Box<dyn Any + Send> = Box::new(format!("格式化 {}", 1));
+        assert_eq!(panic_message(payload.as_ref()), "格式化 1");
+        let payload: Box<dyn Any + Send> = Box::new(42);
+        assert_eq!(panic_message(payload.as_ref()), "未知 panic");
+    }
+}
@@ -0,0 +1,566 @@
+//! 移动检测
+//!
+//! 用户把受保护的目录剪切到另一个盘或重命名后，验证会把原路径全部报告为不存在，
+//! 而移动后的对象不在期望之内。`reconcile_moves` 把两者配对：
+//! - 标识层：上锁时记录的文件标识（卷序列号 + 文件索引）与扫描到的对象一致，
+//!   即同卷内的移动或重命名，置信度高
+//! - 启发式层：文件名（不区分大小写）、类型与级别相同，双方大小都已知时大小也相同，
+//!   且候选一一对应，置信度低。跨卷移动后文件标识改变，只能由这一层匹配
+//!
+//! 确认的移动由 `record_relocations` 写入 `relocated` 记录，
+//! `expected_from_log` 沿记录把期望转移到新路径。
+
+use crate::backend::LabelBackend;
+use crate::build_info::APP_VERSION;
+use crate::pathutil;
+use crate::trace::error_code;
+use crate::verify::ExpectedLabel;
+use amberlock_storage::OperationLog;
+use amberlock_types::{
+    AmberlockError, FileId, LabelLevel, LockRecord, OperationOrigin, Result, TargetKind,
+};
+use std::collections::{HashMap, HashSet};
+use std::fmt::{Display, Formatter};
+use std::path::Path;
+
+/// 移动记录的日志状态
+pub const RELOCATED_STATUS: &str = "relocated";
+/// 移动记录 `details` 的前缀（其后为原路径）
+const RELOCATED_PREFIX: &str = "relocated from ";
+
+/// 扫描到的带标签对象
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct LabeledObject {
+    /// 对象路径
+    pub path: String,
+    /// 对象类型
+    pub kind: TargetKind,
+    /// 当前的完整性级别
+    pub level: LabelLevel,
+    /// 当前的文件标识（无法读取时为 None）
+    pub file_id: Option<FileId>,
+}
+
+/// 配对的置信度
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum MoveConfidence {
+    /// 文件标识一致（同卷移动或重命名）
+    High,
+    /// 仅名称、类型、级别与大小一致（跨卷移动或缺少标识）
+    Low,
+}
+
+/// 一处推断的移动
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct MovePair {
+    /// 原来的期望
+    pub expected: ExpectedLabel,
+    /// 移动后的对象
+    pub found: LabeledObject,
+    /// 置信度
+    pub confidence: MoveConfidence,
+}
+
+impl MovePair {
+    /// 原路径
+    pub fn from(&self) -> &str {
+        &self.expected.path
+    }
+
+    /// 新路径
+    pub fn to(&self) -> &str {
+        &self.found.path
+    }
+}
+
+impl Display for MovePair {
+    fn fmt(&self, f: &mut Formatter<'_>) -> std::fmt::Result {
+        let confidence = match self.confidence {
+            MoveConfidence::High => "文件标识一致",
+            MoveConfidence::Low => "仅名称与大小一致，请确认",
+        };
+        write!(
+            f,
+            "{} 已移动到 {}（{}）",
+            self.from(),
+            self.to(),
+            confidence
+        )
+    }
+}
+
+/// 移动检测结果
+#[derive(Debug, Clone, Default, PartialEq, Eq)]
+pub struct MoveReport {
+    /// 推断的移动
+    pub moves: Vec<MovePair>,
+    /// 有多个候选、无法确定去向的原路径
+    pub ambiguous: Vec<String>,
+    /// 没有候选的原路径（仍视为不存在）
+    pub missing: Vec<String>,
+    /// 未配对、不在期望内的扫描对象
+    pub unmanaged: Vec<String>,
+}
+
+impl MoveReport {
+    /// 可直接采纳的移动（文件标识一致）
+    pub fn confirmed(&self) -> Vec<MovePair> {
+        self.moves
+            .iter()
+            .filter(|pair| pair.confidence == MoveConfidence::High)
+            .cloned()
+            .collect()
+    }
+}
+
+/// 把不存在的期望与期望之外的带标签对象配对
+///
+/// # 参数
+/// - `old_expected`: 期望列表（应限定在扫描覆盖的范围内）
+/// - `scan`: 当前带标签的对象
+///
+/// # 返回
+/// 期望中不在扫描结果里的路径视为不存在，扫描结果中不在期望里的对象作为候选；
+/// 先按文件标识配对，剩余的按名称与大小一一配对
+pub fn reconcile_moves(old_expected: &[ExpectedLabel], scan: &[LabeledObject]) -> MoveReport {
+    let scanned: HashSet<String> = scan.iter().map(|o| path_key(&o.path)).collect();
+    let expected: HashSet<String> = old_expected.iter().map(|e| path_key(&e.path)).collect();
+    let missing: Vec<&ExpectedLabel> = old_expected
+        .iter()
+        .filter(|e| !scanned.contains(&path_key(&e.path)))
+        .collect();
+    let mut candidates: Vec<Option<&LabeledObject>> = scan
+        .iter()
+        .filter(|o| !expected.contains(&path_key(&o.path)))
+        .map(Some)
+        .collect();
+
+    let mut report = MoveReport::default();
+
+    // 标识层
+    let mut remaining = Vec::new();
+    for entry in missing {
+        let matched = entry.file_id.and_then(|id| {
+            candidates.iter().position(|c| {
+                c.and_then(|c| c.file_id)
+                    .is_some_and(|found| found.same_file(&id))
+            })
+        });
+        match matched {
+            Some(index) => report.moves.push(MovePair {
+                expected: entry.clone(),
+                found: candidates[index].take().unwrap().clone(),
+                confidence: MoveConfidence::High,
+            }),
+            None => remaining.push(entry),
+        }
+    }
+
+    // 启发式层：只接受一一对应的候选
+    let matches: Vec<Vec<usize>> = remaining
+        .iter()
+        .map(|entry| {
+            candidates
+                .iter()
+                .enumerate()
+                .filter(|(_, c)| c.is_some_and(|c| resembles(entry, c)))
+                .map(|(index, _)| index)
+                .collect()
+        })
+        .collect();
+    let mut claims: HashMap<usize, usize> = HashMap::new();
+    for index in matches.iter().flatten() {
+        *claims.entry(*index).or_default() += 1;
+    }
+    for (entry, matched) in remaining.into_iter().zip(matches) {
+        match matched.as_slice() {
+            [] => report.missing.push(entry.path.clone()),
+            [index] if claims[index] == 1 => report.moves.push(MovePair {
+                expected: entry.clone(),
+                found: candidates[*index].take().unwrap().clone(),
+                confidence: MoveConfidence::Low,
+            }),
+            _ => report.ambiguous.push(entry.path.clone()),
+        }
+    }
+
+    report.unmanaged = candidates
+        .into_iter()
+        .flatten()
+        .map(|c| c.path.clone())
+        .collect();
+    report
+}
+
+/// 启发式匹配：名称、类型、级别相同，双方大小已知时大小相同（目录不比较大小）
+fn resembles(entry: &ExpectedLabel, found: &LabeledObject) -> bool {
+    if entry.kind != found.kind
+        || entry.level != found.level
+        || file_name(&entry.path) != file_name(&found.path)
+    {
+        return false;
+    }
+    match (entry.kind, entry.file_id, found.file_id) {
+        (TargetKind::File, Some(before), Some(after)) => before.size == after.size,
+        _ => true,
+    }
+}
+
+/// 路径比较键（规范化分隔符；Windows 路径不区分大小写）
+fn path_key(path: &str) -> String {
+    pathutil::normalized_str(path).to_lowercase()
+}
+
+/// 文件名（小写，同时接受两种分隔符）
+fn file_name(path: &str) -> String {
+    path.trim_end_matches(['\\', '/'])
+        .rsplit(['\\', '/'])
+        .next()
+        .unwrap_or_default()
+        .to_lowercase()
+}
+
+/// 扫描目录树中带显式标签（高于 Medium）的对象
+///
+/// # 参数
+/// - `backend`: 标签读取后端
+/// - `root`: 扫描根目录（包含根自身）
+///
+/// # 返回
+/// 带标签的对象及其文件标识；无法读取标签的对象被跳过
+pub fn scan_labeled_objects(backend: &dyn LabelBackend, root: &Path) -> Vec<LabeledObject> {
+    walkdir::WalkDir::new(root)
+        .into_iter()
+        .filter_map(|entry| entry.ok())
+        .filter_map(|entry| {
+            let path = entry.path().to_string_lossy().to_string();
+            let target = pathutil::canonical_path(entry.path());
+            let target = target.to_string_lossy();
+            let label = backend.get_label(&target).ok()?;
+            (label.level != LabelLevel::Medium).then(|| LabeledObject {
+                kind: if entry.file_type().is_dir() {
+                    TargetKind::Directory
+                } else {
+                    TargetKind::File
+                },
+                level: label.level,
+                file_id: backend.file_id(&target),
+                path,
+            })
+        })
+        .collect()
+}
+
+/// 为已确认的移动写入 `relocated` 记录
+///
+/// # 参数
+/// - `logger`: 操作日志
+/// - `user_sid`: 用户 SID
+/// - `moves`: 已确认的移动
+/// - `origin`: 确认移动的入口
+///
+/// # 返回
+/// 写入的记录数
+///
+/// # 注意
+/// 记录的路径为新路径，`details` 为 `relocated from <原路径>`；
+/// 之后 `expected_from_log` 在新路径上验证该对象
+pub fn record_relocations(
+    logger: &OperationLog,
+    user_sid: &str,
+    moves: &[MovePair],
+    origin: OperationOrigin,
+) -> Result<usize> {
+    for pair in moves {
+        logger.append(&LockRecord {
+            id: logger.stamper().new_id(),
+            path: pair.found.path.clone(),
+            kind: pair.found.kind,
+            mode: pair.expected.mode,
+            level_applied: pair.found.level,
+            time_utc: logger.stamper().now(),
+            seq_no: logger.stamper().next_seq(),
+            user_sid: user_sid.to_string(),
+            owner_before: None,
+            sddl_before: None,
+            sddl_after: None,
+            status: RELOCATED_STATUS.to_string(),
+            errors: vec![],
+            details: Some(format!("{}{}", RELOCATED_PREFIX, pair.from())),
+            authorized_by: None,
+            comment: None,
+            batch_id: None,
+            volume_root_acknowledged: false,
+            downgrade_reason: None,
+            origin,
+            app_version: Some(APP_VERSION.to_string()),
+            file_id: pair.found.file_id,
+            warnings: vec![],
+        })?;
+    }
+    logger.flush()?;
+    Ok(moves.len())
+}
+
+/// `relocated` 记录的原路径
+pub(crate) fn relocated_from(record: &LockRecord) -> Option<&str> {
+    record.details.as_deref()?.strip_prefix(RELOCATED_PREFIX)
+}
+
+/// 错误是否表示对象不存在（文件或路径不存在，含 HRESULT 形式）
+pub(crate) fn is_not_found(error: &AmberlockError) -> bool {
+    matches!(error_code(error), 2 | 3 | 0x8007_0002 | 0x8007_0003)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::backend::FakeBackend;
+    use crate::verify::expected_from_log;
+    use amberlock_types::ProtectMode;
+    use tempfile::TempDir;
+
+    fn id(volume_serial: u32, index: u64, size: u64) -> FileId {
+        FileId {
+            volume_serial,
+            index,
+            size,
+        }
+    }
+
+    fn expected(path: &str, file_id: Option<FileId>) -> ExpectedLabel {
+        ExpectedLabel {
+            path: path.to_string(),
+            kind: TargetKind::File,
+            mode: ProtectMode::ReadOnly,
+            level: LabelLevel::High,
+            file_id,
+        }
+    }
+
+    fn found(path: &str, file_id: Option<FileId>) -> LabeledObject {
+        LabeledObject {
+            path: path.to_string(),
+            kind: TargetKind::File,
+            level: LabelLevel::High,
+            file_id,
+        }
+    }
+
+    fn pairs(report: &MoveReport) -> Vec<(&str, &str, MoveConfidence)> {
+        report
+            .moves
+            .iter()
+            .map(|pair| (pair.from(), pair.to(), pair.confidence))
+            .collect()
+    }
+
+    #[test]
+    fn test_same_volume_rename_matches_by_id() {
+        let old = [
+            expected(r"C:\Projects\plan.docx", Some(id(7, 100, 2048))),
+            expected(r"C:\Projects\keep.txt", Some(id(7, 101, 10))),
+        ];
+        // 重命名后名称与大小都变了，仍按标识配对
+        let scan = [
+            found(r"C:\Projects\keep.txt", Some(id(7, 101, 10))),
+            found(r"C:\Archive\plan-final.docx", Some(id(7, 100, 4096))),
+        ];
+
+        let report = reconcile_moves(&old, &scan);
+        assert_eq!(
+            pairs(&report),
+            vec![(
+                r"C:\Projects\plan.docx",
+                r"C:\Archive\plan-final.docx",
+                MoveConfidence::High
+            )]
+        );
+        assert_eq!(report.confirmed().len(), 1);
+        assert!(report.missing.is_empty() && report.unmanaged.is_empty());
+        println!("✅ 同卷重命名按文件标识配对");
+    }
+
+    #[test]
+    fn test_cross_volume_copy_is_low_confidence() {
+        let old = [
+            expected(r"C:\Docs\report.docx", Some(id(7, 200, 512))),
+            expected(r"C:\Docs\notes.txt", Some(id(7, 201, 64))),
+            expected(r"C:\Docs\gone.txt", Some(id(7, 202, 1))),
+        ];
+        let scan = [
+            // 新卷上标识不同，名称与大小一致
+            found(r"E:\Backup\Report.DOCX", Some(id(9, 5, 512))),
+            // 同名但大小不同：不是同一个文件
+            found(r"E:\Backup\notes.txt", Some(id(9, 6, 65))),
+        ];
+
+        let report = reconcile_moves(&old, &scan);
+        assert_eq!(
+            pairs(&report),
+            vec![(
+                r"C:\Docs\report.docx",
+                r"E:\Backup\Report.DOCX",
+                MoveConfidence::Low
+            )]
+        );
+        assert!(report.confirmed().is_empty(), "低置信度的配对需要用户确认");
+        assert_eq!(
+            report.missing,
+            vec![r"C:\Docs\notes.txt", r"C:\Docs\gone.txt"]
+        );
+        assert_eq!(report.unmanaged, vec![r"E:\Backup\notes.txt"]);
+    }
+
+    #[test]
+    fn test_ambiguous_many_to_many() {
+        let old = [
+            expected(r"C:\A\config.ini", None),
+            expected(r"C:\B\config.ini", None),
+            expected(r"C:\C\unique.ini", None),
+        ];
+        let scan = [
+            found(r"D:\X\config.ini", None),
+            found(r"D:\Y\config.ini", None),
+            found(r"D:\Z\unique.ini", None),
+        ];
+
+        let report = reconcile_moves(&old, &scan);
+        assert_eq!(
+            pairs(&report),
+            vec![(r"C:\C\unique.ini", r"D:\Z\unique.ini", MoveConfidence::Low)]
+        );
+        assert_eq!(
+            report.ambiguous,
+            vec![r"C:\A\config.ini", r"C:\B\config.ini"]
+        );
+        assert_eq!(
+            report.unmanaged,
+            vec![r"D:\X\config.ini", r"D:\Y\config.ini"]
+        );
+
+        // 一个原路径对多个候选同样不配对
+        let report = reconcile_moves(&old[..1], &scan[..2]);
+        assert!(report.moves.is_empty());
+        assert_eq!(report.ambiguous, vec![r"C:\A\config.ini"]);
+    }
+
+    #[test]
+    fn test_relocation_chain_is_followed() {
+        let temp_dir = TempDir::new().expect("创建临时目录失败");
+        let log_path = temp_dir.path().join("log.ndjson");
+        let logger = OperationLog::open(&log_path).expect("打开日志失败");
+
+        let mut lock = LockRecord {
+            id: "1".to_string(),
+            path: r"C:\Data\a.txt".to_string(),
+            kind: TargetKind::File,
+            mode: ProtectMode::ReadOnly,
+            level_applied: LabelLevel::High,
+            time_utc: logger.stamper().now(),
+            seq_no: logger.stamper().next_seq(),
+            user_sid: "S-1-5-21-test".to_string(),
+            owner_before: None,
+            sddl_before: None,
+            sddl_after: None,
+            status: "success".to_string(),
+            errors: vec![],
+            details: None,
+            authorized_by: None,
+            comment: None,
+            batch_id: None,
+            volume_root_acknowledged: false,
+            downgrade_reason: None,
+            origin: OperationOrigin::GuiButton,
+            app_version: None,
+            file_id: Some(id(7, 1, 3)),
+            warnings: vec![],
+        };
+        logger.append(&lock).unwrap();
+        lock.path = r"C:\Data\b.txt".to_string();
+        logger.append(&lock).unwrap();
+        logger.flush().unwrap();
+
+        // a.txt 被重命名为 moved.txt，随后又被移动到另一卷
+        let first = reconcile_moves(
+            &expected_from_log(&log_path).unwrap(),
+            &[
+                found(r"C:\Data\b.txt", Some(id(7, 1, 3))),
+                found(r"C:\Data\moved.txt", Some(id(7, 1, 3))),
+            ],
+        );
+        // b.txt 仍在原处；标识相同的 moved.txt 只能是 a.txt
+        assert_eq!(first.confirmed().len(), 1);
+        record_relocations(
+            &logger,
+            "S-1-5-21-test",
+            &first.confirmed(),
+            OperationOrigin::GuiButton,
+        )
+        .expect("写入移动记录失败");
+
+        let second = reconcile_moves(
+            &expected_from_log(&log_path).unwrap(),
+            &[
+                found(r"C:\Data\b.txt", Some(id(7, 1, 3))),
+                found(r"E:\moved.txt", Some(id(9, 4, 3))),
+            ],
+        );
+        record_relocations(
+            &logger,
+            "S-1-5-21-test",
+            &second.moves,
+            OperationOrigin::GuiButton,
+        )
+        .expect("写入移动记录失败");
+
+        let paths: Vec<String> = expected_from_log(&log_path)
+            .unwrap()
+            .into_iter()
+            .map(|e| e.path)
+            .collect();
+        assert_eq!(paths, vec![r"C:\Data\b.txt", r"E:\moved.txt"]);
+        println!("✅ 期望沿 relocated 记录链转移到新路径");
+    }
+
+    #[test]
+    fn test_scan_and_fold_into_verification() {
+        use crate::verify::{VerifyOptions, VerifyScope, verify_protection_chunked};
+
+        let temp_dir = TempDir::new().expect("创建临时目录失败");
+        let moved_dir = temp_dir.path().join("moved");
+        std::fs::create_dir(&moved_dir).unwrap();
+        let moved = moved_dir.join("a.txt");
+        std::fs::write(&moved, b"abc").unwrap();
+        let moved_path = moved.to_string_lossy().to_string();
+        let old_path = temp_dir.path().join("a.txt").to_string_lossy().to_string();
+
+        let backend = FakeBackend::new();
+        backend.insert_label(&moved_path, LabelLevel::High);
+        backend.set_file_id(&moved_path, id(7, 1, 3));
+        backend.fail_next(crate::backend::LabelOp::Get, &old_path, 2);
+
+        let logger = OperationLog::open(temp_dir.path().join("log.ndjson")).unwrap();
+        let old = vec![expected(&old_path, Some(id(7, 1, 3)))];
+        let mut progress = verify_protection_chunked(
+            &backend,
+            &VerifyScope::Entries(old.clone()),
+            &logger,
+            "S-1-5-21-test",
+            &VerifyOptions::new(temp_dir.path().join("verify.json")),
+            None,
+        )
+        .expect("验证失败");
+        assert_eq!(progress.missing_paths, vec![old_path.clone()]);
+
+        let scan = scan_labeled_objects(&backend, temp_dir.path());
+        assert_eq!(scan, vec![found(&moved_path, Some(id(7, 1, 3)))]);
+
+        let report = reconcile_moves(&old, &scan);
+        progress.fold_moves(&report.confirmed());
+        assert!(progress.missing_paths.is_empty());
+        assert_eq!(progress.failed, 0);
+        assert_eq!(progress.moved.len(), 1);
+        assert!(progress.status_line().contains("1 个对象已移动"));
+    }
+}
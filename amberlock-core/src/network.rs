@@ -0,0 +1,264 @@
+//! 网络路径的失败诊断
+//!
+//! 网络共享上的标签操作失败时，拒绝访问/登录失败往往意味着 SMB 会话已过期，
+//! 需要重新登录共享，而不是提权。本模块在共享上的操作首次失败时探测该共享
+//! （结果按 `\\server\share` 缓存），把错误替换为带处理建议的
+//! `NetworkAuthRequired` / `NetworkUnreachable`。
+//!
+//! 探测在辅助线程中进行并有超时，本地路径从不探测，
+//! 一个共享不可达不会拖慢其他路径的处理。
+
+use crate::pathutil::unc_share;
+use crate::trace::error_code;
+use amberlock_types::{AmberlockError, NetworkFailure, NetworkProbe};
+use std::collections::HashMap;
+use std::path::Path;
+use std::sync::mpsc;
+use std::sync::{Mutex, OnceLock};
+use std::time::{Duration, Instant};
+
+/// 单次共享探测的最长等待时间
+pub const NETWORK_PROBE_TIMEOUT: Duration = Duration::from_secs(5);
+/// 探测结果的缓存时长（过期后重新探测，以便用户重新登录后恢复）
+pub const NETWORK_PROBE_TTL: Duration = Duration::from_secs(60);
+/// 探测超时时记录的错误码（ERROR_SEM_TIMEOUT）
+const PROBE_TIMEOUT_CODE: u32 = 121;
+
+/// 按共享缓存的探测结果
+#[derive(Debug)]
+pub struct ShareProbeCache {
+    ttl: Duration,
+    entries: Mutex<HashMap<String, (Instant, NetworkProbe)>>,
+}
+
+impl ShareProbeCache {
+    /// 创建缓存
+    pub fn new(ttl: Duration) -> Self {
+        Self {
+            ttl,
+            entries: Mutex::new(HashMap::new()),
+        }
+    }
+
+    /// 进程级缓存（`NETWORK_PROBE_TTL`）
+    pub fn global() -> &'static ShareProbeCache {
+        static GLOBAL: OnceLock<ShareProbeCache> = OnceLock::new();
+        GLOBAL.get_or_init(|| ShareProbeCache::new(NETWORK_PROBE_TTL))
+    }
+
+    /// 未过期的缓存结果
+    pub fn get(&self, share: &str) -> Option<NetworkProbe> {
+        let entries = self.entries.lock().expect("ShareProbeCache lock poisoned");
+        entries
+            .get(&share.to_lowercase())
+            .filter(|(at, _)| at.elapsed() < self.ttl)
+            .map(|(_, probe)| probe.clone())
+    }
+
+    /// 取缓存结果，没有时执行探测并缓存
+    ///
+    /// # 注意
+    /// 探测期间不持有锁；同一共享的并发失败可能各自探测一次
+    pub fn get_or_probe(
+        &self,
+        share: &str,
+        probe: impl FnOnce(&str) -> NetworkProbe,
+    ) -> NetworkProbe {
+        if let Some(cached) = self.get(share) {
+            return cached;
+        }
+        let result = probe(share);
+        self.entries
+            .lock()
+            .expect("ShareProbeCache lock poisoned")
+            .insert(share.to_lowercase(), (Instant::now(), result.clone()));
+        result
+    }
+
+    /// 丢弃共享的缓存结果（如用户已重新登录）
+    pub fn invalidate(&self, share: &str) {
+        self.entries
+            .lock()
+            .expect("ShareProbeCache lock poisoned")
+            .remove(&share.to_lowercase());
+    }
+}
+
+/// 在辅助线程中探测，超时后视为不可达
+///
+/// # 参数
+/// - `share`: 共享根
+/// - `timeout`: 最长等待时间
+/// - `probe`: 实际的探测函数
+pub fn probe_with_timeout(
+    share: &str,
+    timeout: Duration,
+    probe: impl FnOnce(&str) -> NetworkProbe + Send + 'static,
+) -> NetworkProbe {
+    let (tx, rx) = mpsc::channel();
+    let target = share.to_string();
+    // 超时后线程继续运行到探测结束，结果被丢弃
+    let spawned = std::thread::Builder::new()
+        .name("amberlock-net-probe".to_string())
+        .spawn(move || {
+            let _ = tx.send(probe(&target));
+        });
+
+    match spawned.ok().and_then(|_| rx.recv_timeout(timeout).ok()) {
+        Some(result) => result,
+        None => NetworkProbe {
+            share: share.to_string(),
+            reachable: false,
+            authenticated: false,
+            share_caps: None,
+            failure: Some((NetworkFailure::Unreachable, PROBE_TIMEOUT_CODE)),
+        },
+    }
+}
+
+/// 为网络路径上的失败给出更准确的错误
+///
+/// # 参数
+/// - `path`: 操作的路径
+/// - `error`: 原始错误
+/// - `cache`: 探测结果缓存
+/// - `probe`: 缓存未命中时的探测函数
+///
+/// # 返回
+/// 路径位于网络共享、错误码属于网络/身份验证类，且探测确认共享不可用时，
+/// 返回 `NetworkAuthRequired` 或 `NetworkUnreachable`；否则原样返回 `error`
+pub fn explain_failure(
+    path: &Path,
+    error: AmberlockError,
+    cache: &ShareProbeCache,
+    probe: impl FnOnce(&str) -> NetworkProbe,
+) -> AmberlockError {
+    let Some(share) = unc_share(path) else {
+        return error;
+    };
+    if NetworkFailure::classify(error_code(&error)) == NetworkFailure::Other {
+        return error;
+    }
+    cache
+        .get_or_probe(&share, probe)
+        .to_error()
+        .unwrap_or(error)
+}
+
+/// 探测路径所在的网络共享（使用进程级缓存与 `NETWORK_PROBE_TIMEOUT`）
+///
+/// # 返回
+/// 本地路径返回 None
+#[cfg(feature = "winsec")]
+pub fn probe_network_path(path: &Path) -> Option<NetworkProbe> {
+    let share = unc_share(path)?;
+    Some(ShareProbeCache::global().get_or_probe(&share, probe_share))
+}
+
+/// 使用全局缓存解释网络路径上的失败（见 `explain_failure`）
+#[cfg(feature = "winsec")]
+pub(crate) fn explain_with_probe(path: &Path, error: AmberlockError) -> AmberlockError {
+    explain_failure(path, error, ShareProbeCache::global(), probe_share)
+}
+
+#[cfg(feature = "winsec")]
+fn probe_share(share: &str) -> NetworkProbe {
+    probe_with_timeout(
+        share,
+        NETWORK_PROBE_TIMEOUT,
+        amberlock_winsec::probe_network_share,
+    )
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::cell::Cell;
+
+    fn failed_probe(share: &str, failure: NetworkFailure, code: u32) -> NetworkProbe {
+        NetworkProbe {
+            share: share.to_string(),
+            reachable: failure != NetworkFailure::Unreachable,
+            authenticated: false,
+            share_caps: None,
+            failure: Some((failure, code)),
+        }
+    }
+
+    fn denied() -> AmberlockError {
+        AmberlockError::Win32 {
+            code: 0x8007_0005,
+            msg: "拒绝访问".to_string(),
+        }
+    }
+
+    #[test]
+    fn test_expired_session_becomes_auth_required() {
+        let cache = ShareProbeCache::new(NETWORK_PROBE_TTL);
+        let probes = Cell::new(0);
+        let probe = |share: &str| {
+            probes.set(probes.get() + 1);
+            failed_probe(share, NetworkFailure::AuthRequired, 1326)
+        };
+
+        let error = explain_failure(Path::new(r"\\NAS\backup\a.txt"), denied(), &cache, probe);
+        assert!(
+            matches!(&error, AmberlockError::NetworkAuthRequired { share } if share == r"\\NAS\backup")
+        );
+        // 同一共享（不区分大小写）使用缓存结果
+        let error = explain_failure(Path::new(r"\\nas\Backup\b.txt"), denied(), &cache, probe);
+        assert!(matches!(error, AmberlockError::NetworkAuthRequired { .. }));
+        assert_eq!(probes.get(), 1);
+
+        cache.invalidate(r"\\nas\backup");
+        explain_failure(Path::new(r"\\nas\backup\c.txt"), denied(), &cache, probe);
+        assert_eq!(probes.get(), 2);
+        println!("✅ 网络共享会话过期时提示重新登录");
+    }
+
+    #[test]
+    fn test_local_and_unrelated_failures_are_not_probed() {
+        let cache = ShareProbeCache::new(NETWORK_PROBE_TTL);
+        let probe = |_: &str| -> NetworkProbe { panic!("不应探测") };
+
+        let error = explain_failure(Path::new(r"C:\data\a.txt"), denied(), &cache, probe);
+        assert!(matches!(error, AmberlockError::Win32 { .. }));
+        let error = explain_failure(
+            Path::new(r"\\nas\backup\a.txt"),
+            AmberlockError::Unsupported,
+            &cache,
+            probe,
+        );
+        assert!(matches!(error, AmberlockError::Unsupported));
+    }
+
+    #[test]
+    fn test_healthy_share_keeps_original_error() {
+        let cache = ShareProbeCache::new(NETWORK_PROBE_TTL);
+        let healthy = |share: &str| NetworkProbe {
+            share: share.to_string(),
+            reachable: true,
+            authenticated: true,
+            share_caps: None,
+            failure: None,
+        };
+        let error = explain_failure(Path::new(r"\\nas\backup\a.txt"), denied(), &cache, healthy);
+        assert!(matches!(error, AmberlockError::Win32 { .. }));
+    }
+
+    #[test]
+    fn test_probe_timeout_reports_unreachable() {
+        let probe = probe_with_timeout(r"\\slow\share", Duration::from_millis(20), |share| {
+            std::thread::sleep(Duration::from_millis(500));
+            failed_probe(share, NetworkFailure::AuthRequired, 5)
+        });
+        assert_eq!(
+            probe.failure,
+            Some((NetworkFailure::Unreachable, PROBE_TIMEOUT_CODE))
+        );
+        assert!(matches!(
+            probe.to_error(),
+            Some(AmberlockError::NetworkUnreachable { code: 121, .. })
+        ));
+    }
+}
@@ -1,8 +1,22 @@
-use crate::{BatchResult, LockOptions, LockResult, OperationContext};
-use amberlock_storage::NdjsonWriter;
+use crate::autotune;
+use crate::decommission::{self, UnprotectAll, UnprotectReport};
+use crate::dirsummary;
+use crate::backend::{LabelBackend, WinsecBackend, unlock_object};
+use crate::privileged::SystemBackend;
+use crate::progress::{CancelToken, ProgressTracker};
+use crate::trace::TracingBackend;
+use crate::registry::{ActiveOperations, OperationKind};
+use crate::revert::{BatchEntry, BatchRevert, batch_entries};
+use crate::rollback;
+use crate::throttle::{self, ThrottleConfig};
+use crate::{
+    BatchResult, LockOptions, LockOutcome, LockResult, OperationContext, accumulate_outcome,
+    network, pathutil, readonly, volume,
+};
+use amberlock_storage::OperationLog;
 use amberlock_types::*;
 use amberlock_winsec as winsec;
-use std::path::Path;
+use std::path::{Path, PathBuf};
 
 // ============================================================================
 // 任务 4.1：特权检查前置
@@ -60,95 +74,152 @@ fn check_unlock_privileges() -> Result<()> {
 /// - `user_sid`: 用户 SID
 /// - `logger`: 日志记录器
 ///
+/// # 返回
+/// 上锁结果及附带的警告（如重试后成功、未能写后验证）
+///
 /// # 注意
 /// 任务 4.3：只对路径本身操作，不递归处理文件夹内容
+pub fn process_lock_outcome(
+    path: &Path,
+    opts: &LockOptions,
+    effective_level: LabelLevel,
+    user_sid: &str,
+    logger: &OperationLog,
+) -> Result<LockOutcome> {
+    lock_with_details(path, opts, effective_level, user_sid, logger, None)
+}
+
+/// 单个对象上锁处理（丢弃警告）
+#[deprecated(note = "请改用 `process_lock_outcome`，该函数会丢弃操作附带的警告")]
 pub fn process_lock(
     path: &Path,
     opts: &LockOptions,
     effective_level: LabelLevel,
     user_sid: &str,
-    logger: &NdjsonWriter,
+    logger: &OperationLog,
 ) -> Result<LockResult> {
-    // 任务 4.1：前置特权检查
-    check_lock_privileges(effective_level)?;
-
-    let ctx = OperationContext::new(path, user_sid, logger);
-    let before = winsec::get_object_label(&ctx.path_str).ok();
-
-    // 执行上锁
-    let result = winsec::set_mandatory_label(&ctx.path_str, effective_level);
-
-    let outcome = match result {
-        Ok(_) => {
-            let after = winsec::get_object_label(&ctx.path_str).ok();
-            ctx.log_and_track(
-                opts.mode,
-                effective_level,
-                before.as_ref().map(|s| s.sddl.clone()),
-                after.as_ref().map(|s| s.sddl.clone()),
-                "success",
-                vec![],
-            );
-
-            if effective_level != opts.desired_level {
-                Ok(LockResult::Downgraded)
-            } else {
-                Ok(LockResult::Success)
-            }
-        }
-        Err(e) => {
-            ctx.log_and_track(
-                opts.mode,
-                effective_level,
-                before.as_ref().map(|s| s.sddl.clone()),
-                None,
-                "error",
-                vec![format!("{:?}", e)],
-            );
-            Err(AmberlockError::from(e))
-        }
+    process_lock_outcome(path, opts, effective_level, user_sid, logger)
+        .map(|outcome| outcome.result)
+}
+
+/// 单个对象上锁处理（附带审计信息）
+///
+/// 开启诊断跟踪时，对象的开始与结果同样写入跟踪（含调用后端之前的失败）
+pub(crate) fn lock_with_details(
+    path: &Path,
+    opts: &LockOptions,
+    effective_level: LabelLevel,
+    user_sid: &str,
+    logger: &OperationLog,
+    details: Option<&str>,
+) -> Result<LockOutcome> {
+    let Some(recorder) = opts.trace.as_deref() else {
+        return lock_and_log(path, opts, effective_level, user_sid, logger, details);
     };
+    recorder.begin_object(&pathutil::canonical_path(path).to_string_lossy());
+    let outcome = lock_and_log(path, opts, effective_level, user_sid, logger, details);
+    recorder.end_object(outcome.as_ref().map(|outcome| &outcome.result));
     outcome
 }
 
+fn lock_and_log(
+    path: &Path,
+    opts: &LockOptions,
+    effective_level: LabelLevel,
+    user_sid: &str,
+    logger: &OperationLog,
+    details: Option<&str>,
+) -> Result<LockOutcome> {
+    // 保留设备名无法作为普通文件操作，提前拒绝
+    pathutil::ensure_not_reserved(path)?;
+
+    let ctx = OperationContext::new(path, user_sid, logger)
+        .with_details(details)
+        .with_comment(opts.comment.as_deref())
+        .with_batch_id(opts.batch_id.as_deref())
+        .with_origin(opts.origin);
+    readonly::ensure_writable(&ctx, opts.mode, effective_level)?;
+
+    // 开启诊断跟踪时记录每次标签读写
+    let traced;
+    let backend: &dyn LabelBackend = match opts.trace.as_deref() {
+        Some(recorder) => {
+            traced = TracingBackend::new(&WinsecBackend, recorder);
+            &traced
+        }
+        None => &WinsecBackend,
+    };
+
+    // 卷根策略确定实际级别后，任务 4.1：前置特权检查
+    volume::lock_with_policy(backend, ctx, opts, effective_level, check_lock_privileges)
+}
+
 /// 单个对象解锁处理
 ///
+/// # 参数
+/// - `credential`: 校验通过的保险库凭据名称，写入 `LockRecord.authorized_by`（未授权时为 None）
+///
+/// # 返回
+/// 解锁结果及附带的警告
+///
 /// # 注意
-/// 任务 4.3：只对路径本身操作，不递归处理文件夹内容
-pub fn process_unlock(path: &Path, user_sid: &str, logger: &NdjsonWriter) -> Result<LockResult> {
+/// - 任务 4.3：只对路径本身操作，不递归处理文件夹内容
+/// - 本函数不校验密码，调用方须先通过 `amberlock_auth::verify_password_for` 校验
+pub fn process_unlock_outcome(
+    path: &Path,
+    user_sid: &str,
+    logger: &OperationLog,
+    credential: Option<&str>,
+) -> Result<LockOutcome> {
+    unlock_with_context(
+        OperationContext::new(path, user_sid, logger).with_authorized_by(credential),
+    )
+}
+
+/// 单个对象解锁处理（丢弃警告）
+#[deprecated(note = "请改用 `process_unlock_outcome`，该函数会丢弃操作附带的警告")]
+pub fn process_unlock(path: &Path, user_sid: &str, logger: &OperationLog) -> Result<LockResult> {
+    process_unlock_outcome(path, user_sid, logger, None).map(|outcome| outcome.result)
+}
+
+/// 单个对象解锁处理（记录授权凭据，丢弃警告）
+#[deprecated(note = "请改用 `process_unlock_outcome`，该函数会丢弃操作附带的警告")]
+pub fn process_unlock_authorized(
+    path: &Path,
+    user_sid: &str,
+    logger: &OperationLog,
+    credential: &str,
+) -> Result<LockResult> {
+    process_unlock_outcome(path, user_sid, logger, Some(credential)).map(|outcome| outcome.result)
+}
+
+/// 单个对象解锁处理（审计信息由调用方写入上下文）
+///
+/// # 参数
+/// - `ctx`: 操作上下文，附带审计信息、备注、批次与来源
+pub(crate) fn unlock_with_context(ctx: OperationContext<'_>) -> Result<LockOutcome> {
+    let path = Path::new(&ctx.path_str);
+    pathutil::ensure_not_reserved(path)?;
+
+    readonly::ensure_writable(&ctx, ProtectMode::ReadOnly, LabelLevel::Medium)?;
     // 任务 4.1：前置特权检查
     check_unlock_privileges()?;
 
-    let ctx = OperationContext::new(path, user_sid, logger);
-    let before = winsec::get_object_label(&ctx.path_str).ok();
-    let result = winsec::remove_mandatory_label(&ctx.path_str);
-
-    let outcome = match result {
-        Ok(_) => {
-            ctx.log_and_track(
-                ProtectMode::ReadOnly,
-                LabelLevel::Medium,
-                before.as_ref().map(|s| s.sddl.clone()),
-                None,
-                "unlocked",
-                vec![],
-            );
-            Ok(LockResult::Success)
-        }
-        Err(e) => {
-            ctx.log_and_track(
-                ProtectMode::ReadOnly,
-                LabelLevel::Medium,
-                before.as_ref().map(|s| s.sddl.clone()),
-                None,
-                "error",
-                vec![format!("{:?}", e)],
-            );
-            Err(AmberlockError::from(e))
-        }
-    };
+    let change = unlock_object(&WinsecBackend, &ctx.target);
+    let result = change
+        .result
+        .map_err(|e| network::explain_with_probe(path, e));
+    ctx.log_unlock(change.before.map(|s| s.sddl), result)
+}
 
-    outcome
+/// 检查特权后按批次撤销单个对象
+pub(crate) fn revert_with_privileges(
+    revert: &BatchRevert,
+    entry: &BatchEntry,
+    logger: &OperationLog,
+) -> Result<LockOutcome> {
+    check_unlock_privileges()?;
+    revert.revert_entry(&WinsecBackend, entry, logger)
 }
 
 // ============================================================================
@@ -165,10 +236,14 @@ pub fn process_unlock(path: &Path, user_sid: &str, logger: &NdjsonWriter) -> Res
 /// - `logger`: 日志记录器
 ///
 /// # 返回
-/// 批量操作结果统计
+/// - `Ok(BatchResult)`: 批量操作结果统计（被取消时为已处理部分，并按 `opts.on_cancel` 设置 `cancelled`）
+/// - `Err(ConflictingOperation)`: 与进行中的操作路径重叠且 `opts.wait_for_conflicts` 为 false
+/// - `Err(ReadOnlyMode)`: 处于只读审计模式（每个路径都写入被拒绝记录）
 ///
 /// # 行为
+/// - 开始前在全局 `ActiveOperations` 中登记，结束时注销
 /// - 对列表中的每个路径独立执行锁定操作
+/// - 最多 `opts.parallelism` 个对象并行处理，可移动/网络卷按 `opts.slow_volume_throttle` 节流
 /// - 单个路径失败不影响其他路径的处理
 /// - 所有错误都记录到日志，但不中断批量操作
 pub fn batch_process_lock(
@@ -176,65 +251,212 @@ pub fn batch_process_lock(
     opts: &LockOptions,
     effective_level: LabelLevel,
     user_sid: &str,
-    logger: &NdjsonWriter,
-) -> BatchResult {
+    logger: &OperationLog,
+) -> Result<BatchResult> {
+    // 空列表直接返回 Empty，不做任何检查或特权探测
+    if paths.is_empty() {
+        return Ok(BatchResult::default());
+    }
+    readonly::ensure_batch_writable(
+        paths,
+        opts.mode,
+        effective_level,
+        user_sid,
+        logger,
+        opts.comment.as_deref(),
+    )?;
+    let guard = ActiveOperations::global().register(
+        &to_roots(paths),
+        OperationKind::Lock,
+        CancelToken::new(),
+        opts.wait_for_conflicts,
+    )?;
+    let opts = opts.in_batch(logger);
+    let roots = to_roots(paths);
+
+    // 按卷节流并行处理，慢速卷不拖累同批次的本地磁盘
+    let mut result = throttle::run_throttled(
+        &roots,
+        &ThrottleConfig::for_lock(&opts),
+        guard.cancel_token(),
+        None,
+        |path| process_lock_outcome(path, &opts, effective_level, user_sid, logger),
+    );
+    autotune::log_tuning(&result, &opts, user_sid, logger);
+    dirsummary::log_dir_summary(&result, &opts, user_sid, logger);
+    rollback::settle_cancelled(
+        &mut result,
+        &roots,
+        &opts,
+        guard.cancel_token(),
+        &WinsecBackend,
+        user_sid,
+        logger,
+    );
+
+    Ok(result)
+}
+
+/// 批量解锁操作
+///
+/// # 参数
+/// - `paths`: 要解锁的路径列表
+/// - `user_sid`: 用户 SID
+/// - `logger`: 日志记录器
+/// - `comment`: 操作备注，写入每条记录
+/// - `origin`: 发起操作的入口，写入每条记录
+///
+/// # 返回
+/// - `Ok(BatchResult)`: 批量操作结果统计（被取消时为已处理部分）
+/// - `Err(ConflictingOperation)`: 与进行中的操作路径重叠（解锁不等待冲突）
+/// - `Err(ReadOnlyMode)`: 处于只读审计模式（每个路径都写入被拒绝记录）
+pub fn batch_process_unlock(
+    paths: &[impl AsRef<Path>],
+    user_sid: &str,
+    logger: &OperationLog,
+    comment: Option<&str>,
+    origin: OperationOrigin,
+) -> Result<BatchResult> {
+    if paths.is_empty() {
+        return Ok(BatchResult::default());
+    }
+    readonly::ensure_batch_writable(
+        paths,
+        ProtectMode::ReadOnly,
+        LabelLevel::Medium,
+        user_sid,
+        logger,
+        comment,
+    )?;
+    let guard = ActiveOperations::global().register(
+        &to_roots(paths),
+        OperationKind::Unlock,
+        CancelToken::new(),
+        false,
+    )?;
+    let batch_id = logger.stamper().new_id();
     let mut result = BatchResult {
         total_count: paths.len(),
         ..Default::default()
     };
 
     for path in paths {
-        match process_lock(path.as_ref(), opts, effective_level, user_sid, logger) {
-            Ok(LockResult::Success) => result.success_count += 1,
-            Ok(LockResult::Downgraded) => {
-                result.success_count += 1;
-                result.downgraded_count += 1;
-            }
-            Ok(LockResult::Skipped) => {}
-            Err(_) => result.failed_count += 1,
+        if guard.cancel_token().is_cancelled() {
+            break;
         }
+        let outcome = unlock_with_context(
+            OperationContext::new(path.as_ref(), user_sid, logger)
+                .with_comment(comment)
+                .with_batch_id(Some(&batch_id))
+                .with_origin(origin),
+        );
+        accumulate_outcome(&mut result, path.as_ref(), &outcome);
     }
 
-    result
+    Ok(result)
 }
 
-/// 批量解锁操作
+/// 按批次撤销上锁
 ///
 /// # 参数
-/// - `paths`: 要解锁的路径列表
+/// - `log_path`: 审计日志路径
+/// - `batch_id`: 要撤销的批次
 /// - `user_sid`: 用户 SID
 /// - `logger`: 日志记录器
+/// - `credential`: 校验通过的保险库凭据名称，写入 `LockRecord.authorized_by`
+/// - `origin`: 发起操作的入口，写入每条撤销记录
 ///
 /// # 返回
-/// 批量操作结果统计
-pub fn batch_process_unlock(
-    paths: &[impl AsRef<Path>],
+/// - `Ok(BatchResult)`: 批量统计，标签在批次之后已被改动的对象计为跳过
+/// - `Err(ConflictingOperation)`: 与进行中的操作路径重叠
+/// - `Err(ReadOnlyMode)`: 处于只读审计模式（每个路径都写入被拒绝记录）
+///
+/// # 注意
+/// 本函数不校验密码，调用方须先通过 `amberlock_auth::verify_password_for` 校验；
+/// 撤销记录使用新的批次 ID，`details` 为 `reverts batch <原批次>`
+pub fn unlock_batch(
+    log_path: &Path,
+    batch_id: &str,
     user_sid: &str,
-    logger: &NdjsonWriter,
-) -> BatchResult {
+    logger: &OperationLog,
+    credential: &str,
+    origin: OperationOrigin,
+) -> Result<BatchResult> {
+    let entries = batch_entries(log_path, batch_id)?;
+    if entries.is_empty() {
+        return Ok(BatchResult::default());
+    }
+    let paths: Vec<PathBuf> = entries.iter().map(|entry| entry.path.clone()).collect();
+    readonly::ensure_batch_writable(
+        &paths,
+        ProtectMode::ReadOnly,
+        LabelLevel::Medium,
+        user_sid,
+        logger,
+        None,
+    )?;
+    let guard = ActiveOperations::global().register(
+        &paths,
+        OperationKind::Unlock,
+        CancelToken::new(),
+        false,
+    )?;
+    let revert = BatchRevert::new(batch_id, user_sid, logger)
+        .with_authorized_by(Some(credential))
+        .with_origin(origin);
     let mut result = BatchResult {
-        total_count: paths.len(),
+        total_count: entries.len(),
         ..Default::default()
     };
 
-    for path in paths {
-        match process_unlock(path.as_ref(), user_sid, logger) {
-            Ok(LockResult::Success) => result.success_count += 1,
-            Ok(LockResult::Downgraded) => {
-                result.success_count += 1;
-                result.downgraded_count += 1;
-            }
-            Ok(LockResult::Skipped) => {}
-            Err(_) => result.failed_count += 1,
+    for entry in &entries {
+        if guard.cancel_token().is_cancelled() {
+            break;
         }
+        let outcome = revert_with_privileges(&revert, entry, logger);
+        accumulate_outcome(&mut result, &entry.path, &outcome);
     }
 
-    result
+    Ok(result)
+}
+
+/// 全部解除保护（停用）
+///
+/// 以普通权限移除标签，System 级对象与被拒绝的对象改在 SYSTEM 权限下移除，
+/// 结束后验证是否仍有对象带标签（详见 `decommission::unprotect_all`）
+///
+/// # 参数
+/// - `request`: 本次操作（来源、批次、授权凭据与入口）
+/// - `confirm_phrase`: 用户输入的确认短语（须为 `DECOMMISSION_CONFIRM_PHRASE`）
+/// - `logger`: 日志记录器
+/// - `progress`: 进度跟踪器
+///
+/// # 注意
+/// 本函数不校验密码，调用方须先通过 `amberlock_auth::verify_password_for` 校验
+pub fn unprotect_all_now(
+    request: &UnprotectAll,
+    confirm_phrase: &str,
+    logger: &OperationLog,
+    progress: &ProgressTracker,
+) -> Result<UnprotectReport> {
+    decommission::unprotect_all(
+        &WinsecBackend,
+        Some(&SystemBackend),
+        request,
+        confirm_phrase,
+        logger,
+        progress,
+    )
+}
+
+fn to_roots(paths: &[impl AsRef<Path>]) -> Vec<PathBuf> {
+    paths.iter().map(|p| p.as_ref().to_path_buf()).collect()
 }
 
 #[cfg(test)]
 mod tests {
     use super::*;
+    use crate::BatchOutcome;
     use std::fs::File;
     use tempfile::TempDir;
 
@@ -245,15 +467,85 @@ mod tests {
             failed_count: 1,
             downgraded_count: 2,
             total_count: 6,
+            ..Default::default()
         };
 
         let display = format!("{}", result);
         assert!(display.contains("成功 5 个"));
         assert!(display.contains("失败 1 个"));
         assert!(display.contains("降级 2 个"));
+        assert!(display.starts_with("部分失败"));
         println!("✅ 批量结果显示测试通过");
     }
 
+    #[test]
+    fn test_batch_outcome_classification() {
+        let batch = |success, failed, downgraded, skipped| BatchResult {
+            success_count: success,
+            failed_count: failed,
+            downgraded_count: downgraded,
+            skipped_count: skipped,
+            total_count: success + failed + skipped,
+            ..Default::default()
+        };
+
+        // (成功, 失败, 降级, 跳过) → (分类, 退出码)
+        let cases = [
+            ((0, 0, 0, 0), BatchOutcome::Empty, 3),
+            ((3, 0, 0, 0), BatchOutcome::AllSucceeded, 0),
+            ((3, 0, 3, 0), BatchOutcome::AllSucceeded, 0),
+            ((0, 0, 0, 2), BatchOutcome::AllSucceeded, 0),
+            ((1, 0, 0, 2), BatchOutcome::AllSucceeded, 0),
+            ((2, 1, 0, 0), BatchOutcome::Partial, 2),
+            ((2, 1, 2, 0), BatchOutcome::Partial, 2),
+            ((0, 1, 0, 1), BatchOutcome::Partial, 2),
+            ((0, 3, 0, 0), BatchOutcome::AllFailed, 1),
+        ];
+        for ((s, f, d, k), expected, code) in cases {
+            let outcome = batch(s, f, d, k).outcome();
+            assert_eq!(outcome, expected, "计数 {:?}", (s, f, d, k));
+            assert_eq!(outcome.exit_code(), code, "计数 {:?}", (s, f, d, k));
+        }
+
+        // 开始前即被取消：有总数但未处理任何对象
+        let cancelled = BatchResult {
+            total_count: 5,
+            ..Default::default()
+        };
+        assert_eq!(cancelled.outcome(), BatchOutcome::Empty);
+        println!("✅ 批量结果分类与退出码正常");
+    }
+
+    #[test]
+    fn test_empty_batch_returns_early() {
+        let temp_dir = TempDir::new().expect("创建临时目录失败");
+        let logger = OperationLog::open(temp_dir.path().join("test.log"))
+            .expect("创建日志失败");
+        let empty: [&Path; 0] = [];
+
+        let locked = batch_process_lock(
+            &empty,
+            &LockOptions::default(),
+            LabelLevel::High,
+            "S-1-5-21-test",
+            &logger,
+        )
+        .expect("空列表不应出错");
+        let unlocked = batch_process_unlock(
+            &empty,
+            "S-1-5-21-test",
+            &logger,
+            None,
+            OperationOrigin::GuiButton,
+        )
+        .expect("空列表不应出错");
+
+        assert_eq!(locked.outcome(), BatchOutcome::Empty);
+        assert_eq!(unlocked.outcome(), BatchOutcome::Empty);
+        assert!(!locked.to_string().contains("全部成功"));
+        println!("✅ 空列表直接返回 Empty");
+    }
+
     #[test]
     #[ignore] // 需要管理员权限
     fn test_batch_lock() {
@@ -267,7 +559,7 @@ mod tests {
         let paths = vec![file1, file2];
         let opts = LockOptions::default();
 
-        let logger = NdjsonWriter::open_append(temp_dir.path().join("test.log"))
+        let logger = OperationLog::open(temp_dir.path().join("test.log"))
             .expect("创建日志失败");
 
         let user_sid = winsec::read_user_sid().unwrap_or_default();
@@ -276,7 +568,8 @@ mod tests {
             winsec::probe_capability().unwrap().has_se_relabel,
         );
 
-        let result = batch_process_lock(&paths, &opts, effective_level, &user_sid, &logger);
+        let result = batch_process_lock(&paths, &opts, effective_level, &user_sid, &logger)
+            .expect("批量锁定不应冲突");
 
         println!("批量锁定结果: {}", result);
         assert_eq!(result.total_count, 2);
@@ -0,0 +1,442 @@
+//! 路径处理
+//!
+//! Win32 常规路径会被 `GetFullPathNameW` 规范化：名称末尾的 `.` 与空格被静默去除，
+//! 保留设备名（CON、NUL、COM1 等）被解释为设备。通过 `\\?\` 命名空间创建的
+//! `report. `、`aux.txt` 之类的对象因此无法用常规路径访问。
+//!
+//! 本模块负责：
+//! - 规范化调用方传入的分隔符（`/` 转为 `\`、折叠重复分隔符、去除多余的末尾分隔符）
+//! - 检测需要扩展长度前缀的路径，并在不做任何名称规范化的前提下转换为 `\\?\` 形式
+//! - 在操作开始前拒绝保留设备名
+//! - 检测经由联接或符号链接解析的路径（结果附带 `Warning::ResolvedThroughReparsePoint`）
+
+use amberlock_types::{AmberlockError, Result};
+use std::path::{Component, Path, PathBuf};
+
+/// 扩展长度路径前缀
+const EXTENDED_PREFIX: &str = r"\\?\";
+/// UNC 路径的扩展长度前缀
+const EXTENDED_UNC_PREFIX: &str = r"\\?\UNC\";
+
+/// 保留设备名（不区分大小写，带任意扩展名同样保留）
+const RESERVED_NAMES: [&str; 4] = ["CON", "PRN", "AUX", "NUL"];
+
+/// 路径最后一个组件是否为保留设备名
+///
+/// `nul`、`CON.txt`、`com1 .log`、`LPT9` 均视为保留名；
+/// `console`、`com10` 不是
+pub fn is_reserved_device_name(path: &Path) -> bool {
+    let Some(name) = path.file_name().map(|n| n.to_string_lossy()) else {
+        return false;
+    };
+
+    // 设备名判定只看第一个 `.` 之前的部分，且忽略其末尾空格
+    let stem = name.split('.').next().unwrap_or_default().trim_end();
+    let upper = stem.to_ascii_uppercase();
+
+    if RESERVED_NAMES.contains(&upper.as_str()) {
+        return true;
+    }
+    matches!(
+        upper.as_bytes(),
+        [b'C', b'O', b'M', b'0'..=b'9'] | [b'L', b'P', b'T', b'0'..=b'9']
+    )
+}
+
+/// 拒绝保留设备名
+///
+/// # 返回
+/// - `Err(ReservedName)`: 路径最后一个组件为保留设备名
+pub fn ensure_not_reserved(path: &Path) -> Result<()> {
+    if is_reserved_device_name(path) {
+        return Err(AmberlockError::ReservedName(
+            path.to_string_lossy().to_string(),
+        ));
+    }
+    Ok(())
+}
+
+/// 路径中是否有组件以 `.` 或空格结尾（`.`、`..` 除外）
+///
+/// 这类名称在常规 Win32 路径中会被截断，必须使用 `\\?\` 形式访问
+pub(crate) fn needs_extended_path(path: &Path) -> bool {
+    path.components().any(|c| match c {
+        Component::Normal(name) => {
+            let name = name.to_string_lossy();
+            name.ends_with('.') || name.ends_with(' ')
+        }
+        _ => false,
+    })
+}
+
+/// 转换为 `\\?\` 扩展长度形式
+///
+/// # 行为
+/// - 已是 `\\?\` 形式时原样返回
+/// - 相对路径先拼接当前目录
+/// - 仅按组件处理 `.` 与 `..`，不修改任何名称（保留末尾的点与空格）
+/// - `\\server\share\...` 转换为 `\\?\UNC\server\share\...`
+pub(crate) fn to_extended_path(path: &Path) -> PathBuf {
+    let raw = path.to_string_lossy();
+    if raw.starts_with(EXTENDED_PREFIX) {
+        return path.to_path_buf();
+    }
+
+    let absolute = if path.is_absolute() || raw.starts_with(r"\\") || raw.starts_with("//") {
+        path.to_path_buf()
+    } else {
+        std::env::current_dir()
+            .map(|cwd| cwd.join(path))
+            .unwrap_or_else(|_| path.to_path_buf())
+    };
+
+    let normalized = absolute.to_string_lossy().replace('/', "\\");
+    let (prefix, rest) = match normalized.strip_prefix(r"\\") {
+        Some(unc) => (EXTENDED_UNC_PREFIX, unc.to_string()),
+        None => (EXTENDED_PREFIX, normalized),
+    };
+
+    // 按组件折叠 `.` 与 `..`，第一个组件（盘符或服务器名）不可被弹出
+    let mut parts: Vec<&str> = Vec::new();
+    for (index, part) in rest.split('\\').enumerate() {
+        match part {
+            "" | "." if index > 0 => {}
+            ".." => {
+                if parts.len() > 1 {
+                    parts.pop();
+                }
+            }
+            _ => parts.push(part),
+        }
+    }
+
+    PathBuf::from(format!("{}{}", prefix, parts.join("\\")))
+}
+
+/// 按 Windows 语法规范化路径字符串
+///
+/// # 行为
+/// - `/` 转为 `\`，连续的分隔符折叠为一个（UNC 开头的 `\\` 除外）
+/// - 去除末尾分隔符；卷根保留（`D:\`），共享根统一为带一个末尾分隔符的 `\\server\share\`
+/// - `\\?\` 与 `\\.\` 路径不做名称处理，原样返回
+/// - 不处理 `.`、`..` 与大小写
+pub fn normalize_windows_path(raw: &str) -> String {
+    if raw.starts_with(EXTENDED_PREFIX) || raw.starts_with(r"\\.\") {
+        return raw.to_string();
+    }
+
+    let unified = raw.replace('/', "\\");
+    if let Some(rest) = unified.strip_prefix(r"\\") {
+        let parts = separated(rest);
+        return match parts.len() {
+            2 => format!(r"\\{}\{}\", parts[0], parts[1]),
+            _ => format!(r"\\{}", parts.join("\\")),
+        };
+    }
+
+    let (prefix, rest) = match unified.as_bytes() {
+        [drive, b':', ..] if drive.is_ascii_alphabetic() => unified.split_at(2),
+        _ => ("", unified.as_str()),
+    };
+    let root = if rest.starts_with('\\') { "\\" } else { "" };
+    format!("{}{}{}", prefix, root, separated(rest).join("\\"))
+}
+
+/// 按 `\` 拆分，丢弃空组件
+fn separated(rest: &str) -> Vec<&str> {
+    rest.split('\\').filter(|part| !part.is_empty()).collect()
+}
+
+/// 规范化调用方传入的路径
+///
+/// Windows 上（以及其他平台上带盘符或 `\` 的路径）按 [`normalize_windows_path`] 处理；
+/// 其他平台的本机路径只折叠重复的 `/` 并去除末尾的 `/`（根目录除外）
+pub fn normalize_path(path: &Path) -> PathBuf {
+    let raw = path.to_string_lossy();
+    let windows_syntax = cfg!(windows)
+        || raw.contains('\\')
+        || matches!(raw.as_bytes(), [drive, b':', ..] if drive.is_ascii_alphabetic());
+    if windows_syntax {
+        return PathBuf::from(normalize_windows_path(&raw));
+    }
+
+    let parts: Vec<&str> = raw.split('/').filter(|p| !p.is_empty()).collect();
+    let root = if raw.starts_with('/') { "/" } else { "" };
+    PathBuf::from(format!("{}{}", root, parts.join("/")))
+}
+
+/// 规范化日志或清单中的路径字符串，使规范化前后写入的记录可以互相匹配
+pub(crate) fn normalized_str(path: &str) -> String {
+    normalize_path(Path::new(path))
+        .to_string_lossy()
+        .into_owned()
+}
+
+/// 计算实际传给 Win32 API 的路径
+///
+/// 先按 [`normalize_path`] 规范化；含末尾点/空格的路径再转换为 `\\?\` 形式，
+/// 避免操作到被截断后的名称
+pub fn canonical_path(path: &Path) -> PathBuf {
+    let path = normalize_path(path);
+    if needs_extended_path(&path) {
+        to_extended_path(&path)
+    } else {
+        path
+    }
+}
+
+/// 路径是否为卷根
+///
+/// `C:`、`C:\`、`C:/`、`\\?\C:\` 与 `\\?\Volume{GUID}\` 视为卷根；
+/// 网络共享根不视为卷根
+pub fn is_volume_root(path: &Path) -> bool {
+    let raw = path.to_string_lossy().replace('/', "\\");
+    let rest = raw.strip_prefix(EXTENDED_PREFIX).unwrap_or(&raw);
+    let rest = rest.strip_suffix('\\').unwrap_or(rest);
+    match rest.as_bytes() {
+        [drive, b':'] => drive.is_ascii_alphabetic(),
+        _ => {
+            raw.starts_with(EXTENDED_PREFIX)
+                && rest.starts_with("Volume{")
+                && rest.ends_with('}')
+                && !rest.contains('\\')
+        }
+    }
+}
+
+/// 网络路径所在的共享根
+///
+/// `\\server\share\dir\file`、`//server/share` 与 `\\?\UNC\server\share\...`
+/// 均返回 `\\server\share`；本地路径与设备路径（`\\?\C:\`、`\\.\`）返回 None
+pub fn unc_share(path: &Path) -> Option<String> {
+    let raw = path.to_string_lossy().replace('/', "\\");
+    let rest = match raw.strip_prefix(EXTENDED_UNC_PREFIX) {
+        Some(rest) => rest,
+        None if raw.starts_with(EXTENDED_PREFIX) || raw.starts_with(r"\\.\") => return None,
+        None => raw.strip_prefix(r"\\")?,
+    };
+    let mut parts = rest.split('\\').filter(|part| !part.is_empty());
+    let server = parts.next()?;
+    let share = parts.next()?;
+    Some(format!(r"\\{}\{}", server, share))
+}
+
+/// 路径本身或任一上级目录是否为联接、符号链接等名称代理重解析点
+///
+/// 无法读取的组件视为普通对象；云文件占位符等非名称代理的重解析点不计入
+pub fn resolves_through_reparse_point(path: &Path) -> bool {
+    path.ancestors()
+        .filter(|ancestor| !ancestor.as_os_str().is_empty())
+        .any(|ancestor| {
+            std::fs::symlink_metadata(ancestor).is_ok_and(|meta| meta.file_type().is_symlink())
+        })
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_reserved_device_names() {
+        for name in [
+            "CON", "nul", "Aux.txt", "com1", "COM9.log", "lpt3", "prn .txt",
+        ] {
+            let path = Path::new("data").join(name);
+            assert!(is_reserved_device_name(&path), "{} 应为保留名", name);
+        }
+        for name in ["console", "com10", "nul_file", "a.con", "lpt"] {
+            let path = Path::new("data").join(name);
+            assert!(!is_reserved_device_name(&path), "{} 不应为保留名", name);
+        }
+        // 只检查最后一个组件
+        assert!(!is_reserved_device_name(&Path::new("CON").join("file.txt")));
+
+        assert!(matches!(
+            ensure_not_reserved(&Path::new("data").join("aux.txt")),
+            Err(AmberlockError::ReservedName(_))
+        ));
+        assert!(ensure_not_reserved(&Path::new("data").join("report.txt")).is_ok());
+        println!("✅ 保留设备名在校验层被拒绝");
+    }
+
+    #[test]
+    fn test_needs_extended_path() {
+        let base = Path::new("data");
+        assert!(needs_extended_path(&base.join("report. ")));
+        assert!(needs_extended_path(&base.join("dir.").join("a.txt")));
+        assert!(needs_extended_path(Path::new("trailing ")));
+        assert!(!needs_extended_path(&base.join(".").join("a.txt")));
+        assert!(!needs_extended_path(&Path::new("..").join("a.txt")));
+        assert!(!needs_extended_path(&base.join("a.txt")));
+    }
+
+    #[test]
+    fn test_to_extended_path_preserves_names() {
+        #[cfg(windows)]
+        {
+            assert_eq!(
+                to_extended_path(Path::new(r"C:\data\.\sub\..\report. ")),
+                PathBuf::from(r"\\?\C:\data\report. ")
+            );
+            assert_eq!(
+                to_extended_path(Path::new(r"\\server\share\a.")),
+                PathBuf::from(r"\\?\UNC\server\share\a.")
+            );
+            assert_eq!(
+                to_extended_path(Path::new(r"C:\..\a")),
+                PathBuf::from(r"\\?\C:\a")
+            );
+        }
+        assert_eq!(
+            to_extended_path(Path::new(r"\\?\C:\x\y ")),
+            PathBuf::from(r"\\?\C:\x\y ")
+        );
+        assert_eq!(
+            canonical_path(Path::new("plain.txt")),
+            PathBuf::from("plain.txt")
+        );
+    }
+
+    #[cfg(windows)]
+    #[test]
+    fn test_trailing_dot_and_space_names_are_reachable() {
+        let dir = tempfile::TempDir::new().expect("创建临时目录失败");
+
+        for name in ["report. ", "trailing.", "spaced "] {
+            let path = dir.path().join(name);
+            let extended = to_extended_path(&path);
+            std::fs::write(&extended, b"x").expect("通过扩展路径创建文件失败");
+
+            // 规范路径必须指向真实对象，而不是被截断后的名称
+            let canonical = canonical_path(&path);
+            assert_eq!(canonical, extended);
+            assert!(std::fs::metadata(&canonical).is_ok(), "{:?} 不可访问", name);
+            std::fs::remove_file(&canonical).expect("删除失败");
+        }
+        println!("✅ 末尾带点/空格的文件可通过规范路径访问");
+    }
+
+    #[test]
+    fn test_unc_share() {
+        let cases = [
+            (r"\\nas\backup\2024\report.docx", Some(r"\\nas\backup")),
+            (r"\\nas\backup", Some(r"\\nas\backup")),
+            ("//nas/backup/dir", Some(r"\\nas\backup")),
+            (r"\\?\UNC\nas\backup\a.txt", Some(r"\\nas\backup")),
+            (r"\\nas", None),
+            (r"\\?\C:\data", None),
+            (r"\\.\PhysicalDrive0", None),
+            (r"C:\data\a.txt", None),
+        ];
+        for (path, expected) in cases {
+            assert_eq!(unc_share(Path::new(path)).as_deref(), expected, "{}", path);
+        }
+    }
+
+    #[test]
+    fn test_is_volume_root() {
+        for path in [
+            "C:",
+            r"C:\",
+            "d:/",
+            r"\\?\C:\",
+            r"\\?\Volume{0b3c2f1a-8d4e-4c6b-9a2f-1e5d7c9b3a40}\",
+        ] {
+            assert!(is_volume_root(Path::new(path)), "{} 应为卷根", path);
+        }
+        for path in [
+            r"C:\Windows",
+            r"C:\data\",
+            r"\\nas\backup\",
+            r"\\?\UNC\nas\backup",
+            r"\\?\Volume{0b3c2f1a}\dir",
+            "C",
+            "",
+        ] {
+            assert!(!is_volume_root(Path::new(path)), "{} 不应为卷根", path);
+        }
+    }
+
+    #[test]
+    fn test_normalize_windows_path() {
+        let cases = [
+            (r"D:\data\file.txt\", r"D:\data\file.txt"),
+            ("D:/data//sub/file.txt", r"D:\data\sub\file.txt"),
+            (r"D:\data/\\sub\\", r"D:\data\sub"),
+            (r"data\a.txt\", r"data\a.txt"),
+            (r"\data\", r"\data"),
+            (r"\\nas\backup\\dir\a.txt\", r"\\nas\backup\dir\a.txt"),
+            ("//nas/backup/dir/", r"\\nas\backup\dir"),
+            (r"\\?\C:\x\y \", r"\\?\C:\x\y \"),
+            (r"\\.\PhysicalDrive0", r"\\.\PhysicalDrive0"),
+            ("", ""),
+        ];
+        for (raw, expected) in cases {
+            assert_eq!(normalize_windows_path(raw), expected, "{}", raw);
+        }
+        println!("✅ 混合分隔符与末尾分隔符被规范化");
+    }
+
+    #[test]
+    fn test_normalize_keeps_volume_and_share_roots() {
+        // 卷根必须保留末尾分隔符（`D:` 表示 D 盘的当前目录，不是根）
+        for raw in [r"D:\", "D:/", r"D:\\", "D://"] {
+            let normalized = normalize_windows_path(raw);
+            assert_eq!(normalized, r"D:\", "{}", raw);
+            assert!(is_volume_root(Path::new(&normalized)));
+        }
+        assert_eq!(normalize_windows_path("D:"), "D:");
+
+        // 共享根统一为带一个末尾分隔符的形式
+        for raw in [
+            r"\\nas\share",
+            r"\\nas\share\",
+            r"\\nas\share\\",
+            "//nas/share/",
+        ] {
+            assert_eq!(normalize_windows_path(raw), r"\\nas\share\", "{}", raw);
+        }
+        assert_eq!(
+            unc_share(Path::new(&normalize_windows_path(r"\\nas\share\"))).as_deref(),
+            Some(r"\\nas\share")
+        );
+        println!("✅ 卷根与共享根保留唯一的末尾分隔符");
+    }
+
+    #[test]
+    fn test_normalize_path_native() {
+        assert_eq!(
+            normalize_path(Path::new(r"D:\data\a.txt\")),
+            PathBuf::from(r"D:\data\a.txt")
+        );
+        #[cfg(unix)]
+        {
+            assert_eq!(
+                normalize_path(Path::new("/tmp//x/y/")),
+                PathBuf::from("/tmp/x/y")
+            );
+            assert_eq!(normalize_path(Path::new("/")), PathBuf::from("/"));
+        }
+        #[cfg(windows)]
+        assert_eq!(
+            canonical_path(Path::new("D:/data/report. /")),
+            PathBuf::from(r"\\?\D:\data\report. ")
+        );
+    }
+
+    #[cfg(unix)]
+    #[test]
+    fn test_resolves_through_reparse_point() {
+        let dir = tempfile::TempDir::new().expect("创建临时目录失败");
+        let real = dir.path().join("real");
+        std::fs::create_dir(&real).expect("创建目录失败");
+        std::fs::write(real.join("a.txt"), b"x").expect("创建文件失败");
+        let link = dir.path().join("link");
+        std::os::unix::fs::symlink(&real, &link).expect("创建符号链接失败");
+
+        assert!(!resolves_through_reparse_point(&real.join("a.txt")));
+        assert!(resolves_through_reparse_point(&link));
+        assert!(resolves_through_reparse_point(&link.join("a.txt")));
+        assert!(!resolves_through_reparse_point(&dir.path().join("missing")));
+    }
+}
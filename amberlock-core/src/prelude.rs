@@ -0,0 +1,150 @@
+//! 常用类型与函数的统一导入
+//!
+//! 嵌入 AmberLock 的程序只需依赖 `amberlock-core`：
+//! `use amberlock_core::prelude::*;` 即可完成"探测能力 → 计算有效级别 →
+//! 批量上锁/解锁 → 写入审计日志"的完整流程，无需再直接依赖
+//! `amberlock-types`、`amberlock-storage` 或 `amberlock-winsec`。
+//!
+//! # 注意
+//! 不导出 `amberlock_types::Result`，以免遮蔽标准库的 `Result`；
+//! 需要时请写 `Result<T, AmberlockError>`
+
+pub use crate::{
+    BatchFailure,
+    BatchOutcome,
+    BatchResult,
+    CancelToken,
+    LockOptions,
+    LockOutcome,
+    LockResult,
+    OperationHandle,
+    OperationKind,
+    ProgressSnapshot,
+    sanitize_comment,
+};
+#[cfg(feature = "winsec")]
+pub use crate::{
+    WinsecBackend,
+    batch_process_lock,
+    batch_process_unlock,
+    compute_effective_level,
+    probe_capability,
+    process_lock,
+    process_lock_outcome,
+    process_unlock,
+    process_unlock_outcome,
+    read_process_il,
+    read_user_sid,
+    spawn_batch_lock,
+    spawn_batch_unlock,
+};
+pub use amberlock_storage::OperationLog;
+pub use amberlock_types::{
+    AmberlockError,
+    CancelBehavior,
+    CapabilityProbe,
+    LabelLevel,
+    LockRecord,
+    ProtectMode,
+    Settings,
+    TargetKind,
+    Warning,
+    WarningKind,
+};
+
+#[cfg(test)]
+mod tests {
+    use std::path::Path;
+
+    /// 扫描 `src/*.rs` 中的顶层公开项与 `lib.rs` 的重导出，每行一项：
+    /// `模块: 类别 名称`，受特性控制的项附加 `[特性]`
+    fn public_api_summary() -> String {
+        let src = Path::new(env!("CARGO_MANIFEST_DIR")).join("src");
+        let mut files: Vec<_> = std::fs::read_dir(&src)
+            .expect("读取 src 目录失败")
+            .filter_map(|e| e.ok().map(|e| e.path()))
+            .filter(|p| p.extension().is_some_and(|ext| ext == "rs"))
+            .collect();
+        files.sort();
+
+        let mut lines = Vec::new();
+        for file in files {
+            let module = file.file_stem().unwrap().to_string_lossy().to_string();
+            let text = std::fs::read_to_string(&file).expect("读取源文件失败");
+            let mut feature: Option<String> = None;
+            let mut use_prefix: Option<String> = None;
+
+            for line in text.lines() {
+                if let Some(prefix) = &use_prefix {
+                    let item = line.trim().trim_end_matches(',');
+                    if item == "};" {
+                        use_prefix = None;
+                        feature = None;
+                    } else if !item.is_empty() {
+                        lines.push(entry(
+                            &module,
+                            "use",
+                            &format!("{}{}", prefix, item),
+                            &feature,
+                        ));
+                    }
+                    continue;
+                }
+                if let Some(rest) = line
+                    .strip_prefix("#[cfg(feature = \"")
+                    .and_then(|r| r.strip_suffix("\")]"))
+                {
+                    feature = Some(rest.to_string());
+                    continue;
+                }
+                if line.starts_with("#[") || line.starts_with("///") {
+                    continue;
+                }
+                if let Some(path) = line.strip_prefix("pub use ") {
+                    match path.strip_suffix("{") {
+                        Some(prefix) => use_prefix = Some(prefix.to_string()),
+                        None => {
+                            let path = path.trim_end_matches(';');
+                            lines.push(entry(&module, "use", path, &feature));
+                            feature = None;
+                        }
+                    }
+                    continue;
+                }
+                if let Some(item) = line.strip_prefix("pub ") {
+                    let item = item
+                        .strip_prefix("const ")
+                        .filter(|r| r.starts_with("fn "))
+                        .unwrap_or(item);
+                    let mut words = item.split_whitespace();
+                    let kind = words.next().unwrap_or_default();
+                    let name: String = words
+                        .next()
+                        .unwrap_or_default()
+                        .chars()
+                        .take_while(|c| c.is_alphanumeric() || *c == '_')
+                        .collect();
+                    lines.push(entry(&module, kind, &name, &feature));
+                }
+                feature = None;
+            }
+        }
+        lines.join("\n")
+    }
+
+    fn entry(module: &str, kind: &str, name: &str, feature: &Option<String>) -> String {
+        match feature {
+            Some(f) => format!("{}: {} {} [{}]", module, kind, name, f),
+            None => format!("{}: {} {}", module, kind, name),
+        }
+    }
+
+    /// 公开 API 清单必须与快照逐字一致；
+    /// 有意增删公开项时请同步更新 `testdata/public_api.txt`
+    #[test]
+    fn test_public_api_snapshot() {
+        let actual = public_api_summary();
+        let expected = include_str!("../testdata/public_api.txt");
+        assert_eq!(actual.trim_end(), expected.trim_end(), "公开 API 发生变化");
+    }
+}
@@ -0,0 +1,513 @@
+//! 保护预设
+//!
+//! 为不熟悉完整性级别与保护模式的用户提供几组经过验证的组合：
+//! 选择预设后，根目录（可默认为系统已知文件夹）按预设展开为待处理对象，
+//! 先预估数量供用户确认，再以预设的选项执行上锁。
+//!
+//! 预设定义与选项解析是纯数据；递归与排除复用 `glob` 模块的展开逻辑，
+//! 每条日志记录的 `details` 为 `preset: <预设名>`。
+
+use crate::LockOptions;
+use crate::glob::{ExpandOptions, expand_glob};
+use amberlock_types::{AmberlockError, LabelLevel, ParallelismMode, ProtectMode, Result};
+#[cfg(feature = "winsec")]
+use amberlock_types::OperationOrigin;
+use std::path::{Path, PathBuf};
+
+/// 预设展开的默认对象上限（超过时需要用户改用自定义设置）
+pub const PRESET_MAX_MATCHES: usize = 50_000;
+
+/// 系统已知文件夹
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum KnownFolder {
+    /// 文档
+    Documents,
+    /// 图片
+    Pictures,
+}
+
+/// 预设对应的上锁配置
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct LockProfile {
+    /// 保护模式
+    pub mode: ProtectMode,
+    /// 期望的完整性级别
+    pub level: LabelLevel,
+    /// 是否包含所有子目录
+    pub recursive: bool,
+    /// 排除模式（`**/name` 形式匹配任意层级）
+    pub exclude: Vec<String>,
+    /// 是否同时为目录本身设置标签
+    ///
+    /// 标签逐个对象显式写入（不依赖 ACE 继承），因此是否给目录上锁
+    /// 决定了目录下新建的文件能否继续创建
+    pub include_directories: bool,
+}
+
+/// 保护预设
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum ProtectionPreset {
+    /// 文档防篡改：只读，High，递归
+    DocumentsSafe,
+    /// 媒体归档：封印，High，递归，目录同样锁定
+    MediaArchive,
+    /// 源码仓库：只读，Medium，排除版本库与构建产物
+    SourceCodeRepo,
+    /// 自定义
+    Custom(LockProfile),
+}
+
+impl ProtectionPreset {
+    /// 内置预设（不含自定义），按界面展示顺序
+    pub fn builtin() -> [ProtectionPreset; 3] {
+        [
+            ProtectionPreset::DocumentsSafe,
+            ProtectionPreset::MediaArchive,
+            ProtectionPreset::SourceCodeRepo,
+        ]
+    }
+
+    /// 稳定名称（写入日志 `details`）
+    pub fn name(&self) -> &'static str {
+        match self {
+            ProtectionPreset::DocumentsSafe => "documents_safe",
+            ProtectionPreset::MediaArchive => "media_archive",
+            ProtectionPreset::SourceCodeRepo => "source_code_repo",
+            ProtectionPreset::Custom(_) => "custom",
+        }
+    }
+
+    /// 显示标题
+    pub fn title(&self) -> &'static str {
+        match self {
+            ProtectionPreset::DocumentsSafe => "📄 保护我的文档",
+            ProtectionPreset::MediaArchive => "🖼️ 归档照片与媒体",
+            ProtectionPreset::SourceCodeRepo => "💻 保护源码仓库",
+            ProtectionPreset::Custom(_) => "⚙️ 自定义",
+        }
+    }
+
+    /// 面向普通用户的说明
+    pub fn description(&self) -> &'static str {
+        match self {
+            ProtectionPreset::DocumentsSafe => {
+                "防止勒索软件等程序修改或加密“文档”中的文件，文件仍可正常打开查看；\
+                 文件夹本身不锁定，仍可保存新文档"
+            }
+            ProtectionPreset::MediaArchive => {
+                "适合不再修改的照片和视频：“图片”中的文件和文件夹都会被封印，\
+                 既不能修改也不能新增或删除"
+            }
+            ProtectionPreset::SourceCodeRepo => {
+                "防止代码文件被意外改写，自动跳过 .git、target、node_modules 等\
+                 需要频繁写入的目录"
+            }
+            ProtectionPreset::Custom(_) => "使用自定义的级别、模式与排除规则",
+        }
+    }
+
+    /// 未指定根目录时使用的已知文件夹
+    pub fn default_folder(&self) -> Option<KnownFolder> {
+        match self {
+            ProtectionPreset::DocumentsSafe => Some(KnownFolder::Documents),
+            ProtectionPreset::MediaArchive => Some(KnownFolder::Pictures),
+            ProtectionPreset::SourceCodeRepo | ProtectionPreset::Custom(_) => None,
+        }
+    }
+
+    /// 预设对应的上锁配置
+    pub fn profile(&self) -> LockProfile {
+        match self {
+            ProtectionPreset::DocumentsSafe => LockProfile {
+                mode: ProtectMode::ReadOnly,
+                level: LabelLevel::High,
+                recursive: true,
+                exclude: vec!["**/~$*".to_string(), "**/desktop.ini".to_string()],
+                include_directories: false,
+            },
+            ProtectionPreset::MediaArchive => LockProfile {
+                mode: ProtectMode::Seal,
+                level: LabelLevel::High,
+                recursive: true,
+                exclude: vec!["**/Thumbs.db".to_string(), "**/desktop.ini".to_string()],
+                include_directories: true,
+            },
+            ProtectionPreset::SourceCodeRepo => LockProfile {
+                mode: ProtectMode::ReadOnly,
+                level: LabelLevel::Medium,
+                recursive: true,
+                exclude: vec![
+                    "**/.git".to_string(),
+                    "**/target".to_string(),
+                    "**/node_modules".to_string(),
+                ],
+                include_directories: false,
+            },
+            ProtectionPreset::Custom(profile) => profile.clone(),
+        }
+    }
+}
+
+/// 预设解析后的执行计划（含预估的对象列表）
+#[derive(Debug, Clone)]
+pub struct PresetPlan {
+    /// 预设名称
+    pub preset_name: &'static str,
+    /// 根目录
+    pub root: PathBuf,
+    /// 预设配置
+    pub profile: LockProfile,
+    /// 将被上锁的对象
+    pub targets: Vec<PathBuf>,
+}
+
+impl PresetPlan {
+    /// 预估的对象数量
+    pub fn estimated_count(&self) -> usize {
+        self.targets.len()
+    }
+
+    /// 对应的批量上锁选项
+    pub fn lock_options(&self, parallelism: ParallelismMode) -> LockOptions {
+        LockOptions {
+            desired_level: self.profile.level,
+            mode: self.profile.mode,
+            parallelism,
+            collect_dir_summary: self.profile.recursive,
+            ..Default::default()
+        }
+    }
+
+    /// 写入日志记录的 `details`
+    pub fn details(&self) -> String {
+        format!("preset: {}", self.preset_name)
+    }
+
+    /// 确认前展示给用户的范围说明
+    pub fn summary(&self) -> String {
+        let mut text = format!(
+            "将{}保护 {} 中的 {} 个对象（级别 {:?}）",
+            match self.profile.mode {
+                ProtectMode::ReadOnly => "只读",
+                ProtectMode::Seal => "封印",
+            },
+            self.root.display(),
+            self.estimated_count(),
+            self.profile.level
+        );
+        if !self.profile.exclude.is_empty() {
+            text.push_str(&format!("，排除 {}", self.profile.exclude.join("、")));
+        }
+        text
+    }
+}
+
+/// 预设对应的展开模式与选项
+///
+/// # 参数
+/// - `profile`: 上锁配置
+/// - `root`: 根目录
+pub(crate) fn expand_options(profile: &LockProfile, root: &Path) -> (Vec<String>, ExpandOptions) {
+    let pattern = if profile.recursive { "**" } else { "*" };
+    let opts = ExpandOptions {
+        base_dir: Some(root.to_path_buf()),
+        follow_symlinks: false,
+        exclude: profile.exclude.clone(),
+        max_matches: PRESET_MAX_MATCHES,
+    };
+    (vec![pattern.to_string()], opts)
+}
+
+/// 解析预设并预估范围（不修改任何对象）
+///
+/// # 参数
+/// - `preset`: 保护预设
+/// - `root`: 根目录；为 None 时使用预设的已知文件夹
+///
+/// # 返回
+/// - `Ok(PresetPlan)`: 执行计划，`targets` 为将被上锁的对象
+/// - `Err(Unsupported)`: 未指定根目录且预设没有默认文件夹（或无法解析）
+/// - `Err(TooManyMatches)`: 对象数量超过 `PRESET_MAX_MATCHES`
+pub fn plan_preset(preset: &ProtectionPreset, root: Option<&Path>) -> Result<PresetPlan> {
+    let root = match root {
+        Some(root) => root.to_path_buf(),
+        None => preset
+            .default_folder()
+            .ok_or(AmberlockError::Unsupported)
+            .and_then(resolve_known_folder)?,
+    };
+
+    let profile = preset.profile();
+    let (patterns, opts) = expand_options(&profile, &root);
+    let targets = expand_glob(&patterns, &opts)?
+        .into_iter()
+        .filter(|path| profile.include_directories || !path.is_dir())
+        .collect();
+
+    Ok(PresetPlan {
+        preset_name: preset.name(),
+        root,
+        profile,
+        targets,
+    })
+}
+
+/// 按计划执行上锁
+///
+/// # 参数
+/// - `plan`: `plan_preset` 返回的执行计划
+/// - `effective_level`: 有效完整性级别
+/// - `user_sid`: 用户 SID
+/// - `logger`: 日志记录器
+/// - `origin`: 发起操作的入口，写入每条记录
+#[cfg(feature = "winsec")]
+pub fn apply_preset(
+    plan: &PresetPlan,
+    effective_level: LabelLevel,
+    user_sid: &str,
+    logger: &amberlock_storage::OperationLog,
+    origin: OperationOrigin,
+) -> Result<crate::BatchResult> {
+    let opts = LockOptions {
+        origin,
+        ..plan.lock_options(ParallelismMode::Fixed(1))
+    }
+    .in_batch(logger);
+    let details = plan.details();
+    let mut result = opts.batch_result(plan.targets.len());
+    for path in &plan.targets {
+        let outcome = crate::ops::lock_with_details(
+            path,
+            &opts,
+            effective_level,
+            user_sid,
+            logger,
+            Some(&details),
+        );
+        crate::accumulate_outcome(&mut result, path, &outcome);
+    }
+    crate::dirsummary::log_dir_summary(&result, &opts, user_sid, logger);
+    Ok(result)
+}
+
+/// 在后台线程中按计划执行上锁（在 `ActiveOperations` 中登记）
+///
+/// # 参数
+/// - `plan`: `plan_preset` 返回的执行计划
+/// - `effective_level`: 有效完整性级别
+/// - `user_sid`: 用户 SID
+/// - `logger`: 日志记录器
+/// - `wait_for_conflicts`: 存在冲突时是否等待
+/// - `origin`: 发起操作的入口，写入每条记录
+#[cfg(feature = "winsec")]
+pub fn spawn_preset(
+    plan: PresetPlan,
+    effective_level: LabelLevel,
+    user_sid: String,
+    logger: std::sync::Arc<amberlock_storage::OperationLog>,
+    wait_for_conflicts: bool,
+    origin: OperationOrigin,
+) -> crate::OperationHandle {
+    let opts = LockOptions {
+        origin,
+        ..plan.lock_options(ParallelismMode::Fixed(1))
+    }
+    .in_batch(&logger);
+    let details = plan.details();
+    crate::task::spawn_runner(
+        plan.targets,
+        Some((crate::OperationKind::Lock, wait_for_conflicts)),
+        move |paths, cancel, progress| {
+            let result = crate::task::run_sequential(
+                paths,
+                cancel,
+                progress,
+                opts.batch_result(paths.len()),
+                |path| {
+                    crate::ops::lock_with_details(
+                        path,
+                        &opts,
+                        effective_level,
+                        &user_sid,
+                        &logger,
+                        Some(&details),
+                    )
+                },
+            );
+            crate::dirsummary::log_dir_summary(&result, &opts, &user_sid, &logger);
+            result
+        },
+    )
+}
+
+/// 解析系统已知文件夹的路径
+#[cfg(feature = "winsec")]
+pub fn resolve_known_folder(folder: KnownFolder) -> Result<PathBuf> {
+    use windows::Win32::System::Com::CoTaskMemFree;
+    use windows::Win32::UI::Shell::{
+        FOLDERID_Documents, FOLDERID_Pictures, KF_FLAG_DEFAULT, SHGetKnownFolderPath,
+    };
+
+    let id = match folder {
+        KnownFolder::Documents => &FOLDERID_Documents,
+        KnownFolder::Pictures => &FOLDERID_Pictures,
+    };
+    unsafe {
+        let raw = SHGetKnownFolderPath(id, KF_FLAG_DEFAULT, None)?;
+        let path = raw.to_string();
+        CoTaskMemFree(Some(raw.0 as *const _));
+        path.map(PathBuf::from)
+            .map_err(|_| AmberlockError::Unsupported)
+    }
+}
+
+/// 未启用 `winsec` 时无法解析已知文件夹
+#[cfg(not(feature = "winsec"))]
+pub fn resolve_known_folder(_folder: KnownFolder) -> Result<PathBuf> {
+    Err(AmberlockError::Unsupported)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::fs::{self, File};
+    use tempfile::TempDir;
+
+    #[test]
+    fn test_builtin_presets_resolve_to_vetted_profiles() {
+        let expected = [
+            (
+                "documents_safe",
+                ProtectMode::ReadOnly,
+                LabelLevel::High,
+                false,
+                Some(KnownFolder::Documents),
+            ),
+            (
+                "media_archive",
+                ProtectMode::Seal,
+                LabelLevel::High,
+                true,
+                Some(KnownFolder::Pictures),
+            ),
+            (
+                "source_code_repo",
+                ProtectMode::ReadOnly,
+                LabelLevel::Medium,
+                false,
+                None,
+            ),
+        ];
+
+        for (preset, (name, mode, level, dirs, folder)) in
+            ProtectionPreset::builtin().iter().zip(expected)
+        {
+            let profile = preset.profile();
+            assert_eq!(preset.name(), name);
+            assert_eq!(profile.mode, mode, "{}", name);
+            assert_eq!(profile.level, level, "{}", name);
+            assert_eq!(profile.include_directories, dirs, "{}", name);
+            assert!(profile.recursive, "{}", name);
+            assert_eq!(preset.default_folder(), folder, "{}", name);
+            assert!(!preset.title().is_empty() && !preset.description().is_empty());
+            // 内置预设不使用 System 级别（需要 SeRelabelPrivilege）
+            assert_ne!(profile.level, LabelLevel::System);
+        }
+
+        let source = ProtectionPreset::SourceCodeRepo.profile();
+        for dir in [".git", "target", "node_modules"] {
+            assert!(source.exclude.contains(&format!("**/{}", dir)));
+        }
+        println!("✅ 内置预设配置正确");
+    }
+
+    #[test]
+    fn test_custom_preset_passes_profile_through() {
+        let profile = LockProfile {
+            mode: ProtectMode::Seal,
+            level: LabelLevel::System,
+            recursive: false,
+            exclude: vec!["**/*.tmp".to_string()],
+            include_directories: true,
+        };
+        let preset = ProtectionPreset::Custom(profile.clone());
+
+        assert_eq!(preset.profile(), profile);
+        assert_eq!(preset.name(), "custom");
+        assert_eq!(preset.default_folder(), None);
+        assert!(matches!(
+            plan_preset(&preset, None),
+            Err(AmberlockError::Unsupported)
+        ));
+
+        let (patterns, opts) = expand_options(&profile, Path::new("root"));
+        assert_eq!(patterns, vec!["*".to_string()]);
+        assert_eq!(opts.exclude, profile.exclude);
+        assert_eq!(opts.max_matches, PRESET_MAX_MATCHES);
+    }
+
+    #[test]
+    fn test_source_repo_plan_excludes_build_dirs() {
+        let dir = TempDir::new().expect("创建临时目录失败");
+        let root = dir.path();
+        for sub in [
+            "src/bin",
+            ".git/objects",
+            "target/debug",
+            "web/node_modules/x",
+        ] {
+            fs::create_dir_all(root.join(sub)).expect("创建目录失败");
+        }
+        for file in [
+            "Cargo.toml",
+            "src/main.rs",
+            "src/bin/tool.rs",
+            ".git/objects/abc",
+            "target/debug/app.exe",
+            "web/node_modules/x/index.js",
+            "web/app.js",
+        ] {
+            File::create(root.join(file)).expect("创建文件失败");
+        }
+
+        let plan = plan_preset(&ProtectionPreset::SourceCodeRepo, Some(root)).expect("解析失败");
+        let mut names: Vec<String> = plan
+            .targets
+            .iter()
+            .map(|p| {
+                p.strip_prefix(root)
+                    .unwrap()
+                    .to_string_lossy()
+                    .replace('\\', "/")
+            })
+            .collect();
+        names.sort();
+
+        assert_eq!(
+            names,
+            vec!["Cargo.toml", "src/bin/tool.rs", "src/main.rs", "web/app.js"]
+        );
+        assert_eq!(plan.estimated_count(), 4);
+        assert_eq!(plan.details(), "preset: source_code_repo");
+        assert!(plan.summary().contains("4 个对象"));
+        assert_eq!(
+            plan.lock_options(ParallelismMode::Fixed(2)).desired_level,
+            LabelLevel::Medium
+        );
+        println!("✅ 源码仓库预设排除版本库与构建目录");
+    }
+
+    #[test]
+    fn test_media_plan_includes_directories() {
+        let dir = TempDir::new().expect("创建临时目录失败");
+        let root = dir.path();
+        fs::create_dir_all(root.join("2024")).expect("创建目录失败");
+        File::create(root.join("2024/a.jpg")).expect("创建文件失败");
+        File::create(root.join("2024/Thumbs.db")).expect("创建文件失败");
+
+        let plan = plan_preset(&ProtectionPreset::MediaArchive, Some(root)).expect("解析失败");
+        assert!(plan.targets.iter().any(|p| p.ends_with("2024")));
+        assert!(plan.targets.iter().any(|p| p.ends_with("a.jpg")));
+        assert!(!plan.targets.iter().any(|p| p.ends_with("Thumbs.db")));
+    }
+}
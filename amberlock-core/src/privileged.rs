@@ -2,9 +2,11 @@
 //!
 //! 封装需要 SYSTEM 权限的高级操作
 
-use crate::{LockOptions, LockResult, OperationContext};
-use amberlock_storage::NdjsonWriter;
-use amberlock_types::{LabelLevel, Result};
+use crate::backend::{LabelBackend, ObjectLabel, WinsecBackend};
+use crate::repair::{self, RepairOptions, RepairReport};
+use crate::{LockOptions, LockResult, OperationContext, pathutil, readonly};
+use amberlock_storage::OperationLog;
+use amberlock_types::{LabelLevel, OperationOrigin, Result};
 use amberlock_winsec::{
     impersonate::with_system_privileges, remove_mandatory_label, set_mandatory_label,
     spawn_system_process, get_object_label,
@@ -26,18 +28,26 @@ pub fn force_lock(
     opts: &LockOptions,
     effective_level: LabelLevel,
     user_sid: &str,
-    logger: &NdjsonWriter,
+    logger: &OperationLog,
 ) -> Result<LockResult> {
+    pathutil::ensure_not_reserved(path)?;
+    readonly::ensure_writable(
+        &OperationContext::new(path, user_sid, logger),
+        opts.mode,
+        effective_level,
+    )?;
     with_system_privileges(|| {
-        let ctx = OperationContext::new(path, user_sid, logger);
-        let before = get_object_label(&ctx.path_str).ok();
+        let ctx = OperationContext::new(path, user_sid, logger)
+            .with_comment(opts.comment.as_deref())
+            .with_origin(opts.origin);
+        let before = get_object_label(&ctx.target).ok();
 
         // 直接调用 winsec 层 API，不经过 core 层检查
-        let result = set_mandatory_label(&ctx.path_str, effective_level);
+        let result = set_mandatory_label(&ctx.target, effective_level);
 
         match result {
             Ok(_) => {
-                let after = get_object_label(&ctx.path_str).ok();
+                let after = get_object_label(&ctx.target).ok();
                 ctx.log_and_track(
                     opts.mode,
                     effective_level,
@@ -74,13 +84,19 @@ pub fn force_lock(
 /// - 解锁被 SYSTEM 级保护的文件
 /// - 解锁权限损坏的文件
 /// - 修复无法正常解锁的对象
-pub fn force_unlock(path: &Path, user_sid: &str, logger: &NdjsonWriter) -> Result<LockResult> {
+pub fn force_unlock(path: &Path, user_sid: &str, logger: &OperationLog) -> Result<LockResult> {
+    pathutil::ensure_not_reserved(path)?;
+    readonly::ensure_writable(
+        &OperationContext::new(path, user_sid, logger),
+        amberlock_types::ProtectMode::ReadOnly,
+        LabelLevel::Medium,
+    )?;
     with_system_privileges(|| {
         let ctx = OperationContext::new(path, user_sid, logger);
-        let before = get_object_label(&ctx.path_str).ok();
+        let before = get_object_label(&ctx.target).ok();
 
         // 直接调用 winsec 层 API
-        let result = remove_mandatory_label(&ctx.path_str);
+        let result = remove_mandatory_label(&ctx.target);
 
         match result {
             Ok(_) => {
@@ -109,26 +125,53 @@ pub fn force_unlock(path: &Path, user_sid: &str, logger: &NdjsonWriter) -> Resul
     })
 }
 
-/// 修复文件权限
+/// 强制修复（SYSTEM 权限）
 ///
-/// 当文件的 DACL/SACL 损坏时，使用 SYSTEM 权限修复
+/// 当对象的标签、所有者或 DACL 损坏时，在 SYSTEM 权限下按选项逐步修复，
+/// 每一步的成败见返回的报告（详见 `repair::repair_object`）
 ///
 /// # 参数
-/// - `path`: 要修复的文件路径
+/// - `path`: 要修复的对象路径
+/// - `options`: 要执行的修复步骤
+/// - `user_sid`: 用户 SID（取得所有权时作为新所有者）
+/// - `logger`: 日志记录器
+/// - `origin`: 发起操作的入口
 ///
 /// # 返回
-/// - `Ok(())`: 修复成功
-/// - `Err`: 修复失败
-pub fn repair_file_permissions(path: &str) -> Result<()> {
+/// - `Ok(RepairReport)`: 修复报告（部分步骤失败同样返回 Ok）
+/// - `Err(ReadOnlyMode)`: 处于只读审计模式（写入被拒绝记录）
+/// - `Err`: 无法进入 SYSTEM 权限
+pub fn force_repair(
+    path: &Path,
+    options: &RepairOptions,
+    user_sid: &str,
+    logger: &OperationLog,
+    origin: OperationOrigin,
+) -> Result<RepairReport> {
     with_system_privileges(|| {
-        // 1. 移除现有标签
-        remove_mandatory_label(path)?;
+        repair::repair_object(&WinsecBackend, path, options, user_sid, logger, origin)
+    })
+}
+
+/// 在 SYSTEM 权限下读写标签的后端
+///
+/// 每次调用单独模拟 SYSTEM 令牌；用于全部解除保护时处理 System 级对象
+/// 与普通权限被拒绝的对象（见 `decommission` 模块）
+#[derive(Debug, Clone, Copy, Default)]
+pub struct SystemBackend;
 
-        // 2. 重新设置默认标签
-        set_mandatory_label(path, LabelLevel::High)?;
+impl LabelBackend for SystemBackend {
+    fn get_label(&self, target: &str) -> Result<ObjectLabel> {
+        with_system_privileges(|| WinsecBackend.get_label(target))
+    }
 
-        Ok(())
-    })
+    fn set_label(&self, target: &str, level: LabelLevel) -> Result<()> {
+        with_system_privileges(|| set_mandatory_label(target, level))
+    }
+
+    fn remove_label(&self, target: &str) -> Result<()> {
+        with_system_privileges(|| remove_mandatory_label(target))
+    }
 }
 
 /// 创建 SYSTEM 权限的维护进程
@@ -198,7 +241,7 @@ mod tests {
         let test_file = temp_dir.path().join("test_force.txt");
         File::create(&test_file).expect("创建测试文件失败");
 
-        let logger = NdjsonWriter::open_append(temp_dir.path().join("test.log"))
+        let logger = OperationLog::open(temp_dir.path().join("test.log"))
             .expect("创建日志失败");
 
         let user_sid = amberlock_winsec::read_user_sid().unwrap_or_default();
@@ -222,7 +265,7 @@ mod tests {
         let test_file = temp_dir.path().join("test_force_unlock.txt");
         File::create(&test_file).expect("创建测试文件失败");
 
-        let logger = NdjsonWriter::open_append(temp_dir.path().join("test.log"))
+        let logger = OperationLog::open(temp_dir.path().join("test.log"))
             .expect("创建日志失败");
 
         let user_sid = amberlock_winsec::read_user_sid().unwrap_or_default();
@@ -0,0 +1,596 @@
+//! 进度跟踪与取消
+//!
+//! `ProgressTracker` 由工作线程更新、UI 线程读取，计数使用原子变量，
+//! 最近完成时间戳保存在一个小型滑动窗口中（短暂持有的互斥锁），
+//! 读取方通过 `snapshot()` 获得某一时刻的一致视图。
+//!
+//! 总数未知时（边遍历边处理）使用 `indeterminate()` 创建跟踪器，
+//! 快照在调用 `update_total` 之前报告为不确定进度，而不是停在 0%。
+//!
+//! 进度只能拉取：工作线程从不调用调用方提供的回调，界面或其他订阅方
+//! 按自己的节奏轮询 `snapshot()`。读取方再慢也不会拖慢或阻塞工作线程，
+//! 因而无需在两者之间加入投递队列；最后一次快照总是精确的。
+
+use crate::autotune::ConcurrencyTrend;
+use amberlock_types::CancelBehavior;
+use std::collections::VecDeque;
+use std::sync::atomic::{AtomicBool, AtomicUsize, Ordering};
+use std::sync::{Arc, Mutex};
+use std::time::{Duration, Instant};
+
+/// 滑动窗口最多保留的完成时间戳数量
+pub(crate) const RATE_WINDOW_SIZE: usize = 64;
+/// 滑动窗口的时间跨度（更早的完成项不参与近期速度计算）
+pub(crate) const RATE_WINDOW_DURATION: Duration = Duration::from_secs(10);
+/// 按近期速度估算 ETA 所需的最少样本数，不足时回退到全程平均
+pub(crate) const MIN_RATE_SAMPLES: usize = 3;
+
+/// 取消令牌
+///
+/// 克隆后共享同一状态，任一副本调用 `cancel()` 后所有副本均可观察到
+#[derive(Debug, Clone, Default)]
+pub struct CancelToken {
+    cancelled: Arc<AtomicBool>,
+    /// 取消时为本次操作临时指定的处理方式（覆盖 `LockOptions.on_cancel`）
+    behavior: Arc<Mutex<Option<CancelBehavior>>>,
+}
+
+impl CancelToken {
+    /// 创建未取消的令牌
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// 请求取消（工作线程在处理下一个对象前检查）
+    pub fn cancel(&self) {
+        self.cancelled.store(true, Ordering::SeqCst);
+    }
+
+    /// 请求取消，并仅对本次操作指定已完成对象的处理方式
+    pub fn cancel_with(&self, behavior: CancelBehavior) {
+        *self.behavior.lock().unwrap() = Some(behavior);
+        self.cancel();
+    }
+
+    /// 是否已请求取消
+    pub fn is_cancelled(&self) -> bool {
+        self.cancelled.load(Ordering::SeqCst)
+    }
+
+    /// 取消时指定的处理方式（未指定时为 None，按操作选项处理）
+    pub fn requested_behavior(&self) -> Option<CancelBehavior> {
+        *self.behavior.lock().unwrap()
+    }
+}
+
+#[derive(Debug)]
+struct TrackerInner {
+    total: AtomicUsize,
+    indeterminate: AtomicBool,
+    completed: AtomicUsize,
+    failed: AtomicUsize,
+    started: Instant,
+    recent: Mutex<VecDeque<Instant>>,
+    /// 自动调整时的当前并发度与调整方向
+    concurrency: Mutex<Option<(usize, ConcurrencyTrend)>>,
+}
+
+/// 进度跟踪器
+#[derive(Debug, Clone)]
+pub struct ProgressTracker {
+    inner: Arc<TrackerInner>,
+}
+
+impl ProgressTracker {
+    /// 创建跟踪器
+    ///
+    /// # 参数
+    /// - `total`: 预计处理的对象总数
+    pub fn new(total: usize) -> Self {
+        Self::started_at(total, Instant::now())
+    }
+
+    /// 创建总数未知的跟踪器
+    ///
+    /// 调用 `update_total` 之前快照的 `indeterminate` 为 true
+    pub fn indeterminate() -> Self {
+        let tracker = Self::new(0);
+        tracker.inner.indeterminate.store(true, Ordering::SeqCst);
+        tracker
+    }
+
+    /// 以指定开始时间创建跟踪器（测试中配合 `record_at`/`snapshot_at` 模拟时钟）
+    fn started_at(total: usize, started: Instant) -> Self {
+        Self {
+            inner: Arc::new(TrackerInner {
+                total: AtomicUsize::new(total),
+                indeterminate: AtomicBool::new(false),
+                completed: AtomicUsize::new(0),
+                failed: AtomicUsize::new(0),
+                started,
+                recent: Mutex::new(VecDeque::with_capacity(RATE_WINDOW_SIZE)),
+                concurrency: Mutex::new(None),
+            }),
+        }
+    }
+
+    /// 更新对象总数（总数在处理过程中才确定时使用）
+    ///
+    /// # 注意
+    /// 总数不会小于已完成数量；调用后跟踪器不再处于不确定状态
+    pub fn update_total(&self, total: usize) {
+        let completed = self.inner.completed.load(Ordering::SeqCst);
+        self.inner.total.store(total.max(completed), Ordering::SeqCst);
+        self.inner.indeterminate.store(false, Ordering::SeqCst);
+    }
+
+    /// 记录一个对象处理完成
+    ///
+    /// # 参数
+    /// - `success`: 是否成功（失败同样计入已完成数）
+    pub fn record(&self, success: bool) {
+        self.record_at(success, Instant::now());
+    }
+
+    fn record_at(&self, success: bool, now: Instant) {
+        {
+            let mut recent = self.inner.recent.lock().unwrap();
+            if recent.len() == RATE_WINDOW_SIZE {
+                recent.pop_front();
+            }
+            recent.push_back(now);
+        }
+        if !success {
+            self.inner.failed.fetch_add(1, Ordering::SeqCst);
+        }
+        self.inner.completed.fetch_add(1, Ordering::SeqCst);
+    }
+
+    /// 更新自动调整的并发度（状态栏显示为 `并发: 12↑`）
+    pub fn set_concurrency(&self, concurrency: usize, trend: ConcurrencyTrend) {
+        *self.inner.concurrency.lock().unwrap() = Some((concurrency, trend));
+    }
+
+    /// 获取当前进度快照
+    pub fn snapshot(&self) -> ProgressSnapshot {
+        self.snapshot_at(Instant::now())
+    }
+
+    fn snapshot_at(&self, now: Instant) -> ProgressSnapshot {
+        // 只统计时间窗口内的完成项；停滞时窗口逐渐清空
+        let (recent_completed, recent_span) = {
+            let recent = self.inner.recent.lock().unwrap();
+            let in_window: Vec<&Instant> = recent
+                .iter()
+                .filter(|t| now.saturating_duration_since(**t) <= RATE_WINDOW_DURATION)
+                .collect();
+            match in_window.first() {
+                Some(oldest) => (in_window.len(), now.saturating_duration_since(**oldest)),
+                None => (0, Duration::ZERO),
+            }
+        };
+
+        // 先读完成数：总数与完成数并发更新时，快照中的总数不小于完成数
+        let completed = self.inner.completed.load(Ordering::SeqCst);
+        let indeterminate = self.inner.indeterminate.load(Ordering::SeqCst);
+        let total = self.inner.total.load(Ordering::SeqCst);
+
+        ProgressSnapshot {
+            total: if indeterminate { total } else { total.max(completed) },
+            indeterminate,
+            completed,
+            failed: self.inner.failed.load(Ordering::SeqCst),
+            elapsed: now.saturating_duration_since(self.inner.started),
+            recent_completed,
+            recent_span,
+            concurrency: *self.inner.concurrency.lock().unwrap(),
+        }
+    }
+}
+
+/// 某一时刻的进度快照
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct ProgressSnapshot {
+    /// 对象总数
+    pub total: usize,
+    /// 总数尚未确定（界面应显示不确定进度而非百分比）
+    pub indeterminate: bool,
+    /// 已完成数量（含失败）
+    pub completed: usize,
+    /// 失败数量
+    pub failed: usize,
+    /// 已用时间
+    pub elapsed: Duration,
+    /// 滑动窗口内的完成数量
+    pub recent_completed: usize,
+    /// 窗口内最早完成项距快照时刻的时长
+    pub recent_span: Duration,
+    /// 自动调整时的当前并发度与最近的调整方向（固定并发度时为 None）
+    pub concurrency: Option<(usize, ConcurrencyTrend)>,
+}
+
+impl ProgressSnapshot {
+    /// 完成比例（0.0 ~ 1.0，总数为 0 时视为已完成，总数未定时为 0.0）
+    pub fn fraction(&self) -> f64 {
+        if self.indeterminate {
+            0.0
+        } else if self.total == 0 {
+            1.0
+        } else {
+            (self.completed as f64 / self.total as f64).min(1.0)
+        }
+    }
+
+    /// 完成百分比（0.0 ~ 100.0）
+    pub fn percentage(&self) -> f64 {
+        self.fraction() * 100.0
+    }
+
+    /// 是否已全部完成（总数未定时始终为 false）
+    pub fn is_complete(&self) -> bool {
+        !self.indeterminate && self.completed >= self.total
+    }
+
+    /// 预计剩余时间（按全程平均速度估算）
+    ///
+    /// # 返回
+    /// 尚无完成项、总数未定、已全部完成或计算溢出时返回 None
+    pub fn eta(&self) -> Option<Duration> {
+        if self.completed == 0 || self.indeterminate || self.completed >= self.total {
+            return None;
+        }
+        let completed = u32::try_from(self.completed).ok()?;
+        let remaining = u32::try_from(self.total - self.completed).ok()?;
+        self.elapsed.checked_div(completed)?.checked_mul(remaining)
+    }
+
+    /// 近期速度（个/秒，按滑动窗口计算，窗口为空时为 0）
+    pub fn recent_rate(&self) -> f64 {
+        let span = self.recent_span.as_secs_f64();
+        if self.recent_completed == 0 || span <= 0.0 {
+            0.0
+        } else {
+            self.recent_completed as f64 / span
+        }
+    }
+
+    /// 预计剩余时间（按近期速度估算）
+    ///
+    /// # 返回
+    /// - 已全部完成时为 `Some(0)`
+    /// - 窗口样本不少于 `MIN_RATE_SAMPLES` 时按近期速度估算
+    /// - 样本不足时回退到全程平均（`eta()`）
+    /// - 窗口已清空（操作停滞）、尚无完成项或总数未定时返回 None
+    pub fn eta_smoothed(&self) -> Option<Duration> {
+        if self.indeterminate {
+            return None;
+        }
+        if self.is_complete() {
+            return Some(Duration::ZERO);
+        }
+        if self.recent_completed == 0 {
+            return None;
+        }
+        if self.recent_completed < MIN_RATE_SAMPLES {
+            return self.eta();
+        }
+        let rate = self.recent_rate();
+        if rate <= 0.0 {
+            return self.eta();
+        }
+        let remaining = self.total.saturating_sub(self.completed) as f64;
+        Some(Duration::from_secs_f64(remaining / rate))
+    }
+
+    /// 格式化为状态栏文本
+    pub fn format_status(&self) -> String {
+        self.format_with(self.eta(), None)
+    }
+
+    /// 格式化为包含近期速度的状态栏文本（使用平滑后的 ETA）
+    pub fn format_status_detailed(&self) -> String {
+        let rate = (self.recent_completed > 0).then(|| self.recent_rate());
+        self.format_with(self.eta_smoothed(), rate)
+    }
+
+    fn format_with(&self, eta: Option<Duration>, rate: Option<f64>) -> String {
+        let mut status = if self.indeterminate {
+            format!("⏳ 处理中，已完成 {} 个（正在统计总数…）", self.completed)
+        } else {
+            format!(
+                "⏳ 处理中 {}/{}（{:.0}%）",
+                self.completed,
+                self.total,
+                self.percentage()
+            )
+        };
+        if self.failed > 0 {
+            status.push_str(&format!("，失败 {} 个", self.failed));
+        }
+        if let Some(rate) = rate {
+            status.push_str(&format!("，{:.1} 个/秒", rate));
+        }
+        if let Some((concurrency, trend)) = self.concurrency {
+            status.push_str(&format!("，并发: {}{}", concurrency, trend));
+        }
+        match eta {
+            Some(eta) => status.push_str(&format!("，预计剩余 {} 秒", eta.as_secs())),
+            None if self.indeterminate => {}
+            None => status.push_str("，正在估算剩余时间…"),
+        }
+        status
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_snapshot_counts_and_eta() {
+        let tracker = ProgressTracker::new(4);
+        assert_eq!(tracker.snapshot().eta(), None);
+
+        tracker.record(true);
+        tracker.record(false);
+
+        let snapshot = tracker.snapshot();
+        assert_eq!(snapshot.completed, 2);
+        assert_eq!(snapshot.failed, 1);
+        assert!((snapshot.fraction() - 0.5).abs() < f64::EPSILON);
+        assert!(snapshot.eta().is_some());
+        assert!(!snapshot.is_complete());
+    }
+
+    #[test]
+    fn test_empty_operation_is_complete() {
+        let snapshot = ProgressTracker::new(0).snapshot();
+        assert!(snapshot.is_complete());
+        assert_eq!(snapshot.fraction(), 1.0);
+        assert!(snapshot.format_status().contains("100%"));
+    }
+
+    /// 在模拟时钟上按给定间隔记录完成项，返回最后一次完成的时刻
+    fn feed(
+        tracker: &ProgressTracker,
+        mut now: Instant,
+        count: usize,
+        interval: Duration,
+    ) -> Instant {
+        for _ in 0..count {
+            now += interval;
+            tracker.record_at(true, now);
+        }
+        now
+    }
+
+    /// 断言平滑 ETA 与按期望速度计算的结果相差不超过 `tolerance`（比例）
+    fn assert_eta_near(snapshot: &ProgressSnapshot, expected_rate: f64, tolerance: f64) {
+        let remaining = (snapshot.total - snapshot.completed) as f64;
+        let expected = remaining / expected_rate;
+        let actual = snapshot.eta_smoothed().expect("应有 ETA").as_secs_f64();
+        assert!(
+            (actual - expected).abs() <= expected * tolerance,
+            "平滑 ETA {:.1}s 偏离期望 {:.1}s",
+            actual,
+            expected
+        );
+    }
+
+    #[test]
+    fn test_eta_smoothed_tracks_slowdown() {
+        let start = Instant::now();
+        let tracker = ProgressTracker::started_at(1000, start);
+
+        // 前 100 个每个 10ms，随后 20 个每个 500ms
+        let now = feed(&tracker, start, 100, Duration::from_millis(10));
+        let now = feed(&tracker, now, 20, Duration::from_millis(500));
+
+        let snapshot = tracker.snapshot_at(now);
+        assert_eta_near(&snapshot, 2.0, 0.1);
+        // 全程平均明显过于乐观
+        assert!(snapshot.eta().unwrap() < snapshot.eta_smoothed().unwrap() / 2);
+        println!("✅ 变慢后：{}", snapshot.format_status_detailed());
+    }
+
+    #[test]
+    fn test_eta_smoothed_tracks_speedup() {
+        let start = Instant::now();
+        let tracker = ProgressTracker::started_at(1000, start);
+
+        // 前 20 个每个 1s（慢速网络共享），随后 200 个每个 10ms
+        let now = feed(&tracker, start, 20, Duration::from_secs(1));
+        let now = feed(&tracker, now, 200, Duration::from_millis(10));
+
+        let snapshot = tracker.snapshot_at(now);
+        assert_eta_near(&snapshot, 100.0, 0.1);
+        assert!(snapshot.eta().unwrap() > snapshot.eta_smoothed().unwrap() * 5);
+        println!("✅ 变快后：{}", snapshot.format_status_detailed());
+    }
+
+    #[test]
+    fn test_eta_smoothed_bursty() {
+        let start = Instant::now();
+        let tracker = ProgressTracker::started_at(1000, start);
+
+        // 每秒一批 10 个（批内几乎同时完成）
+        let mut now = start;
+        for _ in 0..12 {
+            now += Duration::from_secs(1);
+            feed(&tracker, now, 10, Duration::from_micros(1));
+        }
+
+        let snapshot = tracker.snapshot_at(now);
+        assert!((snapshot.recent_rate() - 10.0).abs() < 1.5);
+        assert_eta_near(&snapshot, 10.0, 0.15);
+    }
+
+    #[test]
+    fn test_stalled_operation_degrades_to_estimating() {
+        let start = Instant::now();
+        let tracker = ProgressTracker::started_at(10, start);
+        let now = feed(&tracker, start, 5, Duration::from_millis(100));
+
+        // 样本不足时回退到全程平均
+        let tracker_small = ProgressTracker::started_at(10, start);
+        feed(&tracker_small, start, 1, Duration::from_millis(100));
+        let small = tracker_small.snapshot_at(start + Duration::from_millis(100));
+        assert_eq!(small.eta_smoothed(), small.eta());
+
+        // 停滞超过窗口时长后窗口清空
+        let stalled = tracker.snapshot_at(now + RATE_WINDOW_DURATION * 2);
+        assert_eq!(stalled.recent_completed, 0);
+        assert_eq!(stalled.recent_rate(), 0.0);
+        assert_eq!(stalled.eta_smoothed(), None);
+        assert!(stalled.format_status_detailed().contains("正在估算"));
+
+        // 同一时刻完成多个对象（跨度为 0）不应除零，回退到全程平均
+        let instant = ProgressTracker::started_at(10, start);
+        feed(&instant, start, 5, Duration::ZERO);
+        let snapshot = instant.snapshot_at(start);
+        assert_eq!(snapshot.recent_rate(), 0.0);
+        assert_eq!(snapshot.eta_smoothed(), snapshot.eta());
+    }
+
+    #[test]
+    fn test_update_total_clamps_to_completed() {
+        let start = Instant::now();
+        let tracker = ProgressTracker::started_at(10, start);
+        let now = feed(&tracker, start, 6, Duration::from_secs(1));
+
+        // 总数缩小到已完成数以下时按已完成数计
+        tracker.update_total(4);
+        let snapshot = tracker.snapshot_at(now);
+        assert_eq!(snapshot.total, 6);
+        assert!(snapshot.is_complete());
+        assert_eq!(snapshot.percentage(), 100.0);
+        assert_eq!(snapshot.eta(), None);
+
+        tracker.update_total(12);
+        let snapshot = tracker.snapshot_at(now);
+        assert_eq!(snapshot.total, 12);
+        assert_eq!(snapshot.percentage(), 50.0);
+        assert_eq!(snapshot.eta(), Some(Duration::from_secs(6)));
+    }
+
+    #[test]
+    fn test_shrinking_total_race_never_exceeds_100_percent() {
+        // 完成数超过总数（总数未及时更新）时，快照按完成数饱和
+        let tracker = ProgressTracker::new(2);
+        for _ in 0..5 {
+            tracker.record(true);
+        }
+        let snapshot = tracker.snapshot();
+        assert_eq!(snapshot.total, 5);
+        assert!(snapshot.percentage() <= 100.0);
+        assert_eq!(snapshot.eta(), None);
+        assert!(snapshot.format_status().contains("5/5（100%）"));
+    }
+
+    #[test]
+    fn test_indeterminate_until_total_is_known() {
+        let tracker = ProgressTracker::indeterminate();
+        tracker.record(true);
+        tracker.record(true);
+
+        let snapshot = tracker.snapshot();
+        assert!(snapshot.indeterminate);
+        assert!(!snapshot.is_complete());
+        assert_eq!(snapshot.percentage(), 0.0);
+        assert_eq!(snapshot.eta(), None);
+        assert_eq!(snapshot.eta_smoothed(), None);
+        let status = snapshot.format_status_detailed();
+        assert!(status.contains("正在统计总数"), "{}", status);
+        assert!(!status.contains('%'));
+
+        tracker.update_total(4);
+        let snapshot = tracker.snapshot();
+        assert!(!snapshot.indeterminate);
+        assert_eq!(snapshot.percentage(), 50.0);
+        println!("✅ 总数确定前显示不确定进度");
+    }
+
+    #[test]
+    fn test_eta_edge_cases() {
+        let snapshot = |total, completed, elapsed| ProgressSnapshot {
+            total,
+            indeterminate: false,
+            completed,
+            failed: 0,
+            elapsed,
+            recent_completed: 0,
+            recent_span: Duration::ZERO,
+            concurrency: None,
+        };
+
+        assert_eq!(snapshot(10, 0, Duration::from_secs(5)).eta(), None);
+        assert_eq!(snapshot(10, 10, Duration::from_secs(5)).eta(), None);
+        assert_eq!(snapshot(10, 12, Duration::from_secs(5)).eta(), None);
+        assert_eq!(
+            snapshot(10, 5, Duration::from_secs(5)).eta(),
+            Some(Duration::from_secs(5))
+        );
+        // 极端耗时下乘法溢出时返回 None 而不是 panic
+        assert_eq!(snapshot(usize::MAX / 2, 1, Duration::MAX).eta(), None);
+    }
+
+    #[test]
+    fn test_slow_reader_does_not_block_workers() {
+        const ITEMS: usize = 20_000;
+        let tracker = ProgressTracker::new(ITEMS * 4);
+        let done = Arc::new(AtomicBool::new(false));
+
+        // 慢速读取方：每次读取后停顿，模拟繁忙的界面线程
+        let reader = {
+            let tracker = tracker.clone();
+            let done = done.clone();
+            std::thread::spawn(move || {
+                let mut reads = 0usize;
+                while !done.load(Ordering::SeqCst) {
+                    let _ = tracker.snapshot();
+                    reads += 1;
+                    std::thread::sleep(Duration::from_millis(50));
+                }
+                reads
+            })
+        };
+
+        let started = Instant::now();
+        std::thread::scope(|scope| {
+            for worker in 0..4 {
+                let tracker = &tracker;
+                scope.spawn(move || {
+                    for i in 0..ITEMS {
+                        tracker.record(worker != 0 || i % 10 != 0);
+                    }
+                });
+            }
+        });
+        let elapsed = started.elapsed();
+        done.store(true, Ordering::SeqCst);
+        let reads = reader.join().expect("读取线程异常");
+
+        // 工作线程只更新计数，耗时与读取方的停顿无关
+        assert!(
+            elapsed < Duration::from_secs(2),
+            "工作线程耗时 {:?}",
+            elapsed
+        );
+        let snapshot = tracker.snapshot();
+        assert_eq!(snapshot.completed, ITEMS * 4);
+        assert_eq!(snapshot.failed, ITEMS / 10);
+        assert!(snapshot.is_complete());
+        println!(
+            "✅ 慢速读取方（{} 次读取）不影响工作线程：{:?}",
+            reads, elapsed
+        );
+    }
+
+    #[test]
+    fn test_cancel_token_is_shared() {
+        let token = CancelToken::new();
+        let copy = token.clone();
+        copy.cancel();
+        assert!(token.is_cancelled());
+    }
+}
@@ -0,0 +1,195 @@
+//! 只读审计模式
+//!
+//! 开启后进程内所有修改标签的入口（上锁、解锁、批量、强制、修复、全部解除保护）都会被拒绝，
+//! 返回 `AmberlockError::ReadOnlyMode`；查询与检查类操作不受影响。
+//!
+//! 检查集中在 core 层各入口的开头，GUI 只负责禁用按钮与显示提示，
+//! 因此任何调用路径都无法绕过。每次被拒绝的尝试仍写入一条
+//! `blocked_read_only` 日志记录，便于追溯。
+
+use crate::OperationContext;
+use amberlock_storage::OperationLog;
+use amberlock_types::{AmberlockError, LabelLevel, ProtectMode, Result};
+use std::path::Path;
+use std::sync::atomic::{AtomicBool, Ordering};
+
+/// 被拒绝操作的日志状态
+pub const BLOCKED_STATUS: &str = "blocked_read_only";
+
+/// 进程级只读标志
+static READ_ONLY: AtomicBool = AtomicBool::new(false);
+
+/// 开启或关闭只读审计模式
+///
+/// # 注意
+/// 关闭前是否需要校验保险库密码由调用方决定
+pub fn set_read_only(enabled: bool) {
+    READ_ONLY.store(enabled, Ordering::SeqCst);
+}
+
+/// 当前是否处于只读审计模式
+pub fn is_read_only() -> bool {
+    READ_ONLY.load(Ordering::SeqCst)
+}
+
+/// 只读模式下拒绝单个对象的修改操作并记录
+///
+/// # 参数
+/// - `ctx`: 操作上下文（用于写入被拒绝记录）
+/// - `mode`: 请求的保护模式
+/// - `level`: 请求的完整性级别
+pub(crate) fn ensure_writable(
+    ctx: &OperationContext<'_>,
+    mode: ProtectMode,
+    level: LabelLevel,
+) -> Result<()> {
+    if !is_read_only() {
+        return Ok(());
+    }
+    ctx.log_and_track(
+        mode,
+        level,
+        None,
+        None,
+        BLOCKED_STATUS,
+        vec![AmberlockError::ReadOnlyMode.to_string()],
+    );
+    Err(AmberlockError::ReadOnlyMode)
+}
+
+/// 只读模式下拒绝批量修改操作，并为每个请求的路径写入记录
+pub(crate) fn ensure_batch_writable(
+    paths: &[impl AsRef<Path>],
+    mode: ProtectMode,
+    level: LabelLevel,
+    user_sid: &str,
+    logger: &OperationLog,
+    comment: Option<&str>,
+) -> Result<()> {
+    if !is_read_only() {
+        return Ok(());
+    }
+    for path in paths {
+        let ctx = OperationContext::new(path.as_ref(), user_sid, logger).with_comment(comment);
+        let _ = ensure_writable(&ctx, mode, level);
+    }
+    Err(AmberlockError::ReadOnlyMode)
+}
+
+#[cfg(all(test, feature = "winsec"))]
+mod tests {
+    use super::*;
+    use crate::backend::FakeBackend;
+    use crate::rollback::roll_back_batch;
+    use crate::seal::{apply_seal, preview_seal};
+    use crate::{
+        DECOMMISSION_CONFIRM_PHRASE, LockOptions, ProgressTracker, RepairOptions, UnprotectAll,
+        UnprotectScope, batch_process_lock, batch_process_unlock, force_lock, force_repair,
+        force_unlock, process_lock_outcome, process_unlock_outcome, unprotect_all_now,
+    };
+    use amberlock_storage::NdjsonReader;
+    use amberlock_types::OperationOrigin;
+    use std::fs::File;
+    use tempfile::TempDir;
+
+    /// 每次尝试都必须返回 ReadOnlyMode
+    fn assert_blocked<T: std::fmt::Debug>(result: Result<T>) {
+        assert!(
+            matches!(result, Err(AmberlockError::ReadOnlyMode)),
+            "期望 ReadOnlyMode，实际 {:?}",
+            result
+        );
+    }
+
+    #[test]
+    fn test_every_mutating_entry_point_is_blocked() {
+        let temp_dir = TempDir::new().expect("创建临时目录失败");
+        let target = temp_dir.path().join("target.txt");
+        File::create(&target).expect("创建文件失败");
+        let log_path = temp_dir.path().join("log.ndjson");
+        let logger = OperationLog::open(&log_path).expect("打开日志失败");
+        let opts = LockOptions::default();
+        let sid = "S-1-5-21-test";
+
+        let seal_backend = FakeBackend::new();
+        let plan = preview_seal(&seal_backend, &target.to_string_lossy(), LabelLevel::High)
+            .expect("预览失败");
+
+        set_read_only(true);
+        assert!(is_read_only());
+        assert_blocked(process_lock_outcome(
+            &target,
+            &opts,
+            LabelLevel::High,
+            sid,
+            &logger,
+        ));
+        assert_blocked(process_unlock_outcome(&target, sid, &logger, None));
+        assert_blocked(batch_process_lock(
+            &[&target, &target],
+            &opts,
+            LabelLevel::High,
+            sid,
+            &logger,
+        ));
+        assert_blocked(batch_process_unlock(
+            &[&target],
+            sid,
+            &logger,
+            Some("审计演练"),
+            OperationOrigin::GuiButton,
+        ));
+        assert_blocked(force_lock(&target, &opts, LabelLevel::System, sid, &logger));
+        assert_blocked(force_unlock(&target, sid, &logger));
+        assert_blocked(force_repair(
+            &target,
+            &RepairOptions {
+                target_level: Some(LabelLevel::High),
+                ..Default::default()
+            },
+            sid,
+            &logger,
+            OperationOrigin::GuiButton,
+        ));
+        assert_blocked(unprotect_all_now(
+            &UnprotectAll::new(UnprotectScope::FromLog, sid, "default", &logger),
+            DECOMMISSION_CONFIRM_PHRASE,
+            &logger,
+            &ProgressTracker::indeterminate(),
+        ));
+        assert_blocked(apply_seal(
+            &seal_backend,
+            &plan,
+            sid,
+            &logger,
+            OperationOrigin::GuiButton,
+        ));
+        assert_blocked(roll_back_batch(
+            &seal_backend,
+            "batch-1",
+            sid,
+            OperationOrigin::GuiButton,
+            &logger,
+        ));
+        set_read_only(false);
+
+        // 查询路径不受影响：日志可读，且每次被拒绝的尝试都有记录
+        logger.flush().unwrap();
+        let mut reader = NdjsonReader::open(&log_path).expect("打开日志失败");
+        let records = reader.read_last_n(100).expect("读取日志失败");
+        // 单个上锁/解锁 2 条 + 批量 3 条 + 强制 2 条 + 修复 1 条 + 封印 1 条
+        //（撤销的批次在日志中没有对象，不产生记录）
+        assert_eq!(records.len(), 9);
+        assert!(records.iter().all(|r| r["status"] == BLOCKED_STATUS));
+        // 批量入口的备注随被拒绝记录一并写入
+        assert_eq!(
+            records
+                .iter()
+                .filter(|r| r["comment"] == "审计演练")
+                .count(),
+            1
+        );
+        assert!(target.exists());
+        println!("✅ 只读审计模式拦截所有修改入口并留下记录");
+    }
+}
@@ -0,0 +1,402 @@
+//! 进行中操作登记表
+//!
+//! 批量/后台操作开始前在进程级登记表中登记其根路径。若已有操作的根路径
+//! 与新操作互为祖先/后代（或相同），两个遍历会在同一批对象上交错写入标签，
+//! 因此新操作要么立即以 `ConflictingOperation` 失败，要么等待冲突操作结束。
+
+use crate::progress::CancelToken;
+use amberlock_types::{AmberlockError, Result};
+use std::fmt::{Display, Formatter};
+use std::path::{Path, PathBuf};
+use std::sync::atomic::{AtomicU64, Ordering};
+use std::sync::{Condvar, Mutex, OnceLock};
+use std::time::{Duration, Instant};
+
+/// 操作类型
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum OperationKind {
+    /// 上锁
+    Lock,
+    /// 解锁
+    Unlock,
+}
+
+impl Display for OperationKind {
+    fn fmt(&self, f: &mut Formatter<'_>) -> std::fmt::Result {
+        match self {
+            OperationKind::Lock => write!(f, "上锁"),
+            OperationKind::Unlock => write!(f, "解锁"),
+        }
+    }
+}
+
+/// 已登记的操作
+#[derive(Debug, Clone)]
+pub struct ActiveOp {
+    id: u64,
+    /// 规范化后的根路径键（小写、`/` 分隔、以 `/` 结尾）
+    pub root_key: String,
+    /// 原始根路径（用于提示）
+    pub root: PathBuf,
+    /// 操作类型
+    pub kind: OperationKind,
+    /// 开始时间
+    pub started: Instant,
+    /// 该操作的取消令牌
+    pub cancel_token: CancelToken,
+}
+
+/// 进行中操作登记表
+#[derive(Debug, Default)]
+pub struct ActiveOperations {
+    ops: Mutex<Vec<ActiveOp>>,
+    changed: Condvar,
+    next_id: AtomicU64,
+}
+
+impl ActiveOperations {
+    /// 创建空登记表（测试或独立子系统使用）
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// 进程级全局登记表
+    pub fn global() -> &'static ActiveOperations {
+        static GLOBAL: OnceLock<ActiveOperations> = OnceLock::new();
+        GLOBAL.get_or_init(ActiveOperations::new)
+    }
+
+    /// 登记新操作
+    ///
+    /// # 参数
+    /// - `roots`: 操作涉及的根路径
+    /// - `kind`: 操作类型
+    /// - `cancel_token`: 该操作的取消令牌（其他操作可通过登记表取消它）
+    /// - `wait_for_conflicts`: 存在冲突时是否等待冲突操作结束
+    ///
+    /// # 返回
+    /// - `Ok(guard)`: 登记成功，guard 释放时自动注销
+    /// - `Err(ConflictingOperation)`: 存在冲突且不等待，或等待期间本操作被取消
+    pub fn register(
+        &self,
+        roots: &[PathBuf],
+        kind: OperationKind,
+        cancel_token: CancelToken,
+        wait_for_conflicts: bool,
+    ) -> Result<OperationGuard<'_>> {
+        let keys: Vec<String> = roots.iter().map(|r| root_key(r)).collect();
+        let mut ops = self.ops.lock().unwrap();
+
+        while let Some(conflict) = find_conflict_in(&ops, &keys) {
+            if !wait_for_conflicts || cancel_token.is_cancelled() {
+                return Err(AmberlockError::ConflictingOperation {
+                    other_root: conflict.root.to_string_lossy().to_string(),
+                    kind: conflict.kind.to_string(),
+                });
+            }
+            // 定时醒来以便响应本操作的取消
+            ops = self
+                .changed
+                .wait_timeout(ops, Duration::from_millis(100))
+                .unwrap()
+                .0;
+        }
+
+        let started = Instant::now();
+        let mut ids = Vec::with_capacity(roots.len());
+        for (root, root_key) in roots.iter().zip(keys) {
+            // 同一次登记中的重复根路径只保留一项
+            if ops[ops.len() - ids.len()..]
+                .iter()
+                .any(|op| op.root_key == root_key)
+            {
+                continue;
+            }
+            let id = self.next_id.fetch_add(1, Ordering::SeqCst);
+            ops.push(ActiveOp {
+                id,
+                root_key,
+                root: root.clone(),
+                kind,
+                started,
+                cancel_token: cancel_token.clone(),
+            });
+            ids.push(id);
+        }
+
+        Ok(OperationGuard {
+            registry: self,
+            ids,
+            cancel_token,
+        })
+    }
+
+    /// 查找与给定根路径冲突的进行中操作
+    pub fn find_conflict(&self, roots: &[PathBuf]) -> Option<ActiveOp> {
+        let keys: Vec<String> = roots.iter().map(|r| root_key(r)).collect();
+        find_conflict_in(&self.ops.lock().unwrap(), &keys)
+    }
+
+    /// 取消所有与给定根路径冲突的进行中操作
+    ///
+    /// # 返回
+    /// 被请求取消的登记项数量
+    pub fn cancel_conflicting(&self, roots: &[PathBuf]) -> usize {
+        let keys: Vec<String> = roots.iter().map(|r| root_key(r)).collect();
+        let ops = self.ops.lock().unwrap();
+
+        let mut count = 0;
+        for op in ops
+            .iter()
+            .filter(|op| keys.iter().any(|k| overlaps(k, &op.root_key)))
+        {
+            op.cancel_token.cancel();
+            count += 1;
+        }
+        count
+    }
+
+    /// 取消所有进行中的操作（进程退出前使用）
+    ///
+    /// # 返回
+    /// 被请求取消的登记项数量
+    pub fn cancel_all(&self) -> usize {
+        let ops = self.ops.lock().unwrap_or_else(|e| e.into_inner());
+        for op in ops.iter() {
+            op.cancel_token.cancel();
+        }
+        ops.len()
+    }
+
+    /// 当前登记的操作数量
+    pub fn active_count(&self) -> usize {
+        self.ops.lock().unwrap().len()
+    }
+
+    fn deregister(&self, ids: &[u64]) {
+        self.ops.lock().unwrap().retain(|op| !ids.contains(&op.id));
+        self.changed.notify_all();
+    }
+}
+
+/// 登记凭据，释放时注销对应登记项并唤醒等待者
+#[derive(Debug)]
+pub struct OperationGuard<'a> {
+    registry: &'a ActiveOperations,
+    ids: Vec<u64>,
+    cancel_token: CancelToken,
+}
+
+impl OperationGuard<'_> {
+    /// 本操作的取消令牌
+    pub fn cancel_token(&self) -> &CancelToken {
+        &self.cancel_token
+    }
+}
+
+impl Drop for OperationGuard<'_> {
+    fn drop(&mut self) {
+        self.registry.deregister(&self.ids);
+    }
+}
+
+fn find_conflict_in(ops: &[ActiveOp], keys: &[String]) -> Option<ActiveOp> {
+    ops.iter()
+        .find(|op| keys.iter().any(|k| overlaps(k, &op.root_key)))
+        .cloned()
+}
+
+/// 两个根路径键是否互为祖先/后代（或相同）
+fn overlaps(a: &str, b: &str) -> bool {
+    a.starts_with(b) || b.starts_with(a)
+}
+
+/// 计算根路径键
+///
+/// 路径存在时使用规范化路径（解析符号链接与 8.3 短名），否则使用绝对路径；
+/// 去除 `\\?\` 前缀并统一小写，末尾补 `/` 以保证按路径段比较前缀
+fn root_key(path: &Path) -> String {
+    let resolved = std::fs::canonicalize(path)
+        .or_else(|_| std::path::absolute(path))
+        .unwrap_or_else(|_| path.to_path_buf());
+
+    let mut key = resolved.to_string_lossy().replace('\\', "/").to_lowercase();
+    if let Some(rest) = key.strip_prefix("//?/unc/") {
+        key = format!("//{}", rest);
+    } else if let Some(rest) = key.strip_prefix("//?/") {
+        key = rest.to_string();
+    }
+    if !key.ends_with('/') {
+        key.push('/');
+    }
+    key
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::sync::Arc;
+    use std::sync::mpsc;
+
+    fn roots(paths: &[&str]) -> Vec<PathBuf> {
+        paths.iter().map(PathBuf::from).collect()
+    }
+
+    #[test]
+    fn test_ancestor_and_descendant_conflict() {
+        let registry = ActiveOperations::new();
+        let _guard = registry
+            .register(
+                &roots(&["C:\\Data"]),
+                OperationKind::Lock,
+                CancelToken::new(),
+                false,
+            )
+            .expect("首次登记应成功");
+
+        // 后代
+        let err = registry
+            .register(
+                &roots(&["C:\\Data\\Projects"]),
+                OperationKind::Unlock,
+                CancelToken::new(),
+                false,
+            )
+            .expect_err("后代路径应冲突");
+        match err {
+            AmberlockError::ConflictingOperation { other_root, kind } => {
+                assert_eq!(other_root, "C:\\Data");
+                assert_eq!(kind, "上锁");
+            }
+            other => panic!("期望 ConflictingOperation，实际为 {:?}", other),
+        }
+
+        // 祖先（大小写与分隔符不同）
+        assert!(registry.find_conflict(&roots(&["c:/"])).is_some());
+        assert!(registry.find_conflict(&roots(&["c:/data"])).is_some());
+    }
+
+    #[test]
+    fn test_sibling_prefix_is_not_conflict() {
+        let registry = ActiveOperations::new();
+        let _guard = registry
+            .register(
+                &roots(&["C:\\Data"]),
+                OperationKind::Lock,
+                CancelToken::new(),
+                false,
+            )
+            .expect("首次登记应成功");
+
+        // "C:\Data2" 与 "C:\Data" 字符串前缀相同但不是同一子树
+        assert!(registry.find_conflict(&roots(&["C:\\Data2"])).is_none());
+        assert!(
+            registry
+                .register(
+                    &roots(&["C:\\Other"]),
+                    OperationKind::Lock,
+                    CancelToken::new(),
+                    false
+                )
+                .is_ok()
+        );
+    }
+
+    #[test]
+    fn test_guard_drop_deregisters() {
+        let registry = ActiveOperations::new();
+        {
+            let _guard = registry
+                .register(
+                    &roots(&["C:\\A", "C:\\B"]),
+                    OperationKind::Lock,
+                    CancelToken::new(),
+                    false,
+                )
+                .expect("登记失败");
+            assert_eq!(registry.active_count(), 2);
+        }
+        assert_eq!(registry.active_count(), 0);
+        assert!(registry.find_conflict(&roots(&["C:\\A"])).is_none());
+    }
+
+    #[test]
+    fn test_wait_for_conflicts_blocks_until_release() {
+        let registry = Arc::new(ActiveOperations::new());
+        let guard = registry
+            .register(
+                &roots(&["C:\\Data"]),
+                OperationKind::Lock,
+                CancelToken::new(),
+                false,
+            )
+            .expect("登记失败");
+
+        let (tx, rx) = mpsc::channel();
+        let waiter = {
+            let registry = Arc::clone(&registry);
+            std::thread::spawn(move || {
+                let result = registry
+                    .register(
+                        &roots(&["C:\\Data\\Sub"]),
+                        OperationKind::Unlock,
+                        CancelToken::new(),
+                        true,
+                    )
+                    .map(|_| ());
+                tx.send(result).unwrap();
+            })
+        };
+
+        // 冲突操作未结束前等待方不应返回
+        assert!(rx.recv_timeout(Duration::from_millis(200)).is_err());
+
+        drop(guard);
+        let result = rx
+            .recv_timeout(Duration::from_secs(5))
+            .expect("释放后等待方应继续");
+        assert!(result.is_ok());
+        waiter.join().unwrap();
+    }
+
+    #[test]
+    fn test_cancel_conflicting_and_cancelled_waiter() {
+        let registry = ActiveOperations::new();
+        let running = CancelToken::new();
+        let _guard = registry
+            .register(
+                &roots(&["C:\\Data"]),
+                OperationKind::Lock,
+                running.clone(),
+                false,
+            )
+            .expect("登记失败");
+
+        assert_eq!(registry.cancel_conflicting(&roots(&["C:\\Data\\x"])), 1);
+        assert!(running.is_cancelled());
+
+        // 等待中的操作自身被取消时不再等待
+        let own = CancelToken::new();
+        own.cancel();
+        let err = registry
+            .register(&roots(&["C:\\Data"]), OperationKind::Lock, own, true)
+            .expect_err("已取消的等待方应返回冲突错误");
+        assert!(matches!(err, AmberlockError::ConflictingOperation { .. }));
+    }
+
+    #[test]
+    fn test_cancel_all() {
+        let registry = ActiveOperations::new();
+        let first = CancelToken::new();
+        let second = CancelToken::new();
+        let _a = registry
+            .register(&roots(&["C:\\A"]), OperationKind::Lock, first.clone(), false)
+            .expect("登记失败");
+        let _b = registry
+            .register(&roots(&["D:\\B"]), OperationKind::Unlock, second.clone(), false)
+            .expect("登记失败");
+
+        assert_eq!(registry.cancel_all(), 2);
+        assert!(first.is_cancelled() && second.is_cancelled());
+    }
+}
@@ -0,0 +1,556 @@
+//! 对象修复
+//!
+//! 对象的标签、所有者或 DACL 损坏时（文件列表显示"?"，或无法正常解锁），
+//! `repair_object` 按用户勾选的步骤逐一修复：
+//! 1. 取得所有权（所有者设为当前用户，之后才能改写 DACL）
+//! 2. 恢复 DACL 继承（清除受保护标志）
+//! 3. 移除强制标签，需要时重新设置为目标级别
+//!
+//! 每一步独立记录成败，某一步失败不影响后续步骤。修复前后分别读取完整描述符，
+//! 写入一条 `REPAIRED_STATUS` 记录（含两份 SDDL 与步骤明细），并返回 `RepairReport`
+//! 供界面逐项展示。需要 SYSTEM 权限时使用 `privileged::force_repair`。
+
+use crate::backend::LabelBackend;
+use crate::seal::Descriptor;
+use crate::{OperationContext, pathutil, readonly};
+use amberlock_storage::OperationLog;
+use amberlock_types::{LabelLevel, MaybeCompressed, OperationOrigin, ProtectMode, Result};
+use std::path::Path;
+
+/// 修复记录的状态
+pub const REPAIRED_STATUS: &str = "repaired";
+
+/// 修复选项
+#[derive(Debug, Clone, Default, PartialEq, Eq)]
+pub struct RepairOptions {
+    /// 重新设置的标签级别（None 不修改标签，Medium 只移除标签）
+    pub target_level: Option<LabelLevel>,
+    /// 恢复 DACL 继承
+    pub reset_dacl_inheritance: bool,
+    /// 将所有者设为当前用户
+    pub take_ownership: bool,
+}
+
+impl RepairOptions {
+    /// 按执行顺序展开的修复步骤
+    pub fn steps(&self) -> Vec<RepairStep> {
+        let mut steps = Vec::new();
+        if self.take_ownership {
+            steps.push(RepairStep::TakeOwnership);
+        }
+        if self.reset_dacl_inheritance {
+            steps.push(RepairStep::ResetDaclInheritance);
+        }
+        if let Some(level) = self.target_level {
+            steps.push(RepairStep::RemoveLabel);
+            if level != LabelLevel::Medium {
+                steps.push(RepairStep::SetLabel(level));
+            }
+        }
+        steps
+    }
+}
+
+/// 单个修复步骤
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum RepairStep {
+    /// 取得所有权
+    TakeOwnership,
+    /// 恢复 DACL 继承
+    ResetDaclInheritance,
+    /// 移除强制标签
+    RemoveLabel,
+    /// 设置强制标签
+    SetLabel(LabelLevel),
+}
+
+impl RepairStep {
+    /// 写入日志的稳定名称
+    pub fn key(&self) -> String {
+        match self {
+            RepairStep::TakeOwnership => "take_ownership".to_string(),
+            RepairStep::ResetDaclInheritance => "reset_dacl_inheritance".to_string(),
+            RepairStep::RemoveLabel => "remove_label".to_string(),
+            RepairStep::SetLabel(level) => format!("set_label({:?})", level),
+        }
+    }
+
+    /// 界面显示的描述
+    pub fn describe(&self) -> String {
+        match self {
+            RepairStep::TakeOwnership => "取得所有权".to_string(),
+            RepairStep::ResetDaclInheritance => "恢复 DACL 继承".to_string(),
+            RepairStep::RemoveLabel => "移除强制标签".to_string(),
+            RepairStep::SetLabel(level) => format!("设置强制标签为 {:?}", level),
+        }
+    }
+}
+
+/// 单个步骤的结果
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct StepOutcome {
+    pub step: RepairStep,
+    /// 失败时的错误描述
+    pub error: Option<String>,
+}
+
+/// 修复前或修复后读取到的对象状态
+#[derive(Debug, Clone, Default, PartialEq, Eq)]
+pub struct RepairState {
+    /// 完整安全描述符（读取失败为 None）
+    pub sddl: Option<String>,
+    /// 所有者
+    pub owner: Option<String>,
+    /// DACL 是否阻止继承
+    pub dacl_protected: Option<bool>,
+    /// 完整性级别
+    pub level: Option<LabelLevel>,
+    /// 读取失败的原因
+    pub error: Option<String>,
+}
+
+impl RepairState {
+    /// 通过后端读取对象状态（失败时记录原因，不返回错误）
+    pub fn capture(backend: &dyn LabelBackend, target: &str) -> Self {
+        let mut state = RepairState::default();
+        match backend.read_descriptor(target) {
+            Ok(sddl) => {
+                if let Ok(descriptor) = Descriptor::parse(&sddl) {
+                    state.owner = descriptor.owner.clone();
+                    state.dacl_protected = descriptor.dacl.as_ref().map(|d| d.flags.contains('P'));
+                }
+                state.sddl = Some(sddl);
+            }
+            Err(e) => state.error = Some(e.to_string()),
+        }
+        match backend.get_label(target) {
+            Ok(label) => state.level = Some(label.level),
+            Err(e) => {
+                state.error.get_or_insert_with(|| e.to_string());
+            }
+        }
+        state
+    }
+
+    /// 多行说明（描述符不可读时为失败原因）
+    pub fn explain(&self) -> Vec<String> {
+        match (&self.sddl, &self.error) {
+            (Some(sddl), _) => explain_sddl(sddl),
+            (None, Some(error)) => vec![format!("无法读取安全描述符：{}", error)],
+            (None, None) => vec!["无法读取安全描述符".to_string()],
+        }
+    }
+}
+
+/// 修复报告
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct RepairReport {
+    pub path: String,
+    pub before: RepairState,
+    pub after: RepairState,
+    /// 按执行顺序的步骤结果
+    pub steps: Vec<StepOutcome>,
+}
+
+impl RepairReport {
+    /// 所有步骤均成功
+    pub fn succeeded(&self) -> bool {
+        self.steps.iter().all(|outcome| outcome.error.is_none())
+    }
+
+    /// 失败的步骤
+    pub fn failed_steps(&self) -> impl Iterator<Item = &StepOutcome> {
+        self.steps.iter().filter(|outcome| outcome.error.is_some())
+    }
+
+    /// 写入 `LockRecord.details` 的步骤明细（如 `take_ownership=ok; remove_label=failed`）
+    pub fn details(&self) -> String {
+        self.steps
+            .iter()
+            .map(|outcome| {
+                let result = if outcome.error.is_none() {
+                    "ok"
+                } else {
+                    "failed"
+                };
+                format!("{}={}", outcome.step.key(), result)
+            })
+            .collect::<Vec<_>>()
+            .join("; ")
+    }
+
+    /// 结果对话框的多行文本：步骤结果与修复前后的状态说明
+    pub fn render_lines(&self) -> Vec<String> {
+        let mut lines = vec![format!("修复对象：{}", self.path)];
+        for outcome in &self.steps {
+            lines.push(match &outcome.error {
+                None => format!("✅ {}", outcome.step.describe()),
+                Some(error) => format!("❌ {}：{}", outcome.step.describe(), error),
+            });
+        }
+        lines.push(String::new());
+        lines.push("修复前：".to_string());
+        lines.extend(
+            self.before
+                .explain()
+                .into_iter()
+                .map(|line| format!("  {}", line)),
+        );
+        lines.push("修复后：".to_string());
+        lines.extend(
+            self.after
+                .explain()
+                .into_iter()
+                .map(|line| format!("  {}", line)),
+        );
+        lines
+    }
+}
+
+/// 说明日志记录中的 SDDL 字段（压缩字段先解压）
+pub fn explain_logged_sddl(field: &MaybeCompressed<String>) -> Vec<String> {
+    match field.expand() {
+        Ok(sddl) => explain_sddl(&sddl),
+        Err(e) => vec![e.to_string()],
+    }
+}
+
+/// 用通俗的语言说明安全描述符（所有者、DACL 继承、强制标签）
+pub fn explain_sddl(sddl: &str) -> Vec<String> {
+    let Ok(descriptor) = Descriptor::parse(sddl) else {
+        return vec![format!("无法解析的安全描述符：{}", sddl)];
+    };
+    let mut lines = Vec::new();
+    if let Some(owner) = &descriptor.owner {
+        lines.push(format!("所有者：{}", trustee_name(owner)));
+    }
+    match &descriptor.dacl {
+        Some(dacl) if dacl.flags.contains("NO_ACCESS_CONTROL") => {
+            lines.push("DACL：空（所有人完全控制）".to_string());
+        }
+        Some(dacl) => {
+            let denies = dacl.aces.iter().filter(|ace| ace.is_deny()).count();
+            let inheritance = if dacl.flags.contains('P') {
+                "已阻止继承"
+            } else {
+                "允许继承"
+            };
+            lines.push(format!(
+                "DACL：{} 条 ACE（其中 {} 条拒绝），{}",
+                dacl.aces.len(),
+                denies,
+                inheritance
+            ));
+        }
+        None => lines.push("DACL：未读取".to_string()),
+    }
+    let label = descriptor
+        .sacl
+        .iter()
+        .flat_map(|sacl| &sacl.aces)
+        .find(|ace| ace.ace_type == "ML");
+    lines.push(match label {
+        Some(ace) => format!(
+            "强制标签：{}（策略 {}）",
+            trustee_name(&ace.trustee),
+            ace.rights
+        ),
+        None => "强制标签：无（默认 Medium）".to_string(),
+    });
+    lines
+}
+
+/// 常见 SID 别名的显示名称
+fn trustee_name(trustee: &str) -> String {
+    let name = match trustee {
+        "BA" => "Administrators",
+        "SY" => "SYSTEM",
+        "BU" => "Users",
+        "WD" => "Everyone",
+        "AU" => "Authenticated Users",
+        "OW" => "所有者权限",
+        "ME" | "S-1-16-8192" => "Medium",
+        "HI" | "S-1-16-12288" => "High",
+        "SI" | "S-1-16-16384" => "System",
+        "LW" | "S-1-16-4096" => "Low",
+        _ => return trustee.to_string(),
+    };
+    name.to_string()
+}
+
+/// 按选项修复单个对象
+///
+/// # 参数
+/// - `backend`: 标签后端
+/// - `path`: 对象路径
+/// - `options`: 要执行的修复步骤
+/// - `user_sid`: 用户 SID（取得所有权时作为新所有者）
+/// - `logger`: 日志记录器
+/// - `origin`: 发起操作的入口
+///
+/// # 返回
+/// - `Ok(RepairReport)`: 修复报告（部分步骤失败同样返回 Ok，见 `succeeded`）
+/// - `Err(ReservedName)`: 保留设备名
+/// - `Err(ReadOnlyMode)`: 处于只读审计模式（写入被拒绝记录）
+///
+/// # 注意
+/// 不检查特权；需要 SYSTEM 权限时使用 `privileged::force_repair`
+pub fn repair_object(
+    backend: &dyn LabelBackend,
+    path: &Path,
+    options: &RepairOptions,
+    user_sid: &str,
+    logger: &OperationLog,
+    origin: OperationOrigin,
+) -> Result<RepairReport> {
+    pathutil::ensure_not_reserved(path)?;
+    let ctx = OperationContext::new(path, user_sid, logger).with_origin(origin);
+    let requested_level = options.target_level.unwrap_or(LabelLevel::Medium);
+    readonly::ensure_writable(&ctx, ProtectMode::ReadOnly, requested_level)?;
+
+    let before = RepairState::capture(backend, &ctx.target);
+    let steps = options
+        .steps()
+        .into_iter()
+        .map(|step| {
+            let result = match step {
+                RepairStep::TakeOwnership => backend.set_owner(&ctx.target, user_sid),
+                RepairStep::ResetDaclInheritance => backend.reset_dacl_inheritance(&ctx.target),
+                RepairStep::RemoveLabel => backend.remove_label(&ctx.target),
+                RepairStep::SetLabel(level) => backend.set_label(&ctx.target, level),
+            };
+            StepOutcome {
+                step,
+                error: result.err().map(|e| e.to_string()),
+            }
+        })
+        .collect();
+    let after = RepairState::capture(backend, &ctx.target);
+
+    let report = RepairReport {
+        path: ctx.path_str.clone(),
+        before,
+        after,
+        steps,
+    };
+    let mut errors: Vec<String> = report
+        .failed_steps()
+        .map(|outcome| {
+            format!(
+                "{}: {}",
+                outcome.step.key(),
+                outcome.error.as_deref().unwrap_or_default()
+            )
+        })
+        .collect();
+    if report.after.level.is_none() {
+        errors.push("无法读取修复后的标签".to_string());
+    }
+    let details = report.details();
+    ctx.with_details(Some(&details)).log_and_track(
+        ProtectMode::ReadOnly,
+        report
+            .after
+            .level
+            .or(report.before.level)
+            .unwrap_or(LabelLevel::Medium),
+        report.before.sddl.clone(),
+        report.after.sddl.clone(),
+        REPAIRED_STATUS,
+        errors,
+    );
+    Ok(report)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::backend::{FakeBackend, LabelOp};
+    use crate::inventory::ProtectionInventory;
+    use amberlock_storage::NdjsonReader;
+    use amberlock_types::LockRecord;
+    use tempfile::TempDir;
+
+    const TARGET: &str = r"D:\broken.txt";
+
+    fn last_record(log_path: &Path) -> LockRecord {
+        let mut values = NdjsonReader::open(log_path)
+            .expect("打开日志失败")
+            .read_last_n(1)
+            .expect("读取日志失败");
+        serde_json::from_value(values.remove(0)).expect("解析记录失败")
+    }
+
+    fn all_steps(level: LabelLevel) -> RepairOptions {
+        RepairOptions {
+            target_level: Some(level),
+            reset_dacl_inheritance: true,
+            take_ownership: true,
+        }
+    }
+
+    #[test]
+    fn test_step_sequencing() {
+        assert!(RepairOptions::default().steps().is_empty());
+        assert_eq!(
+            all_steps(LabelLevel::High).steps(),
+            vec![
+                RepairStep::TakeOwnership,
+                RepairStep::ResetDaclInheritance,
+                RepairStep::RemoveLabel,
+                RepairStep::SetLabel(LabelLevel::High),
+            ]
+        );
+        // 目标为 Medium 时只移除标签
+        assert_eq!(
+            RepairOptions {
+                target_level: Some(LabelLevel::Medium),
+                ..Default::default()
+            }
+            .steps(),
+            vec![RepairStep::RemoveLabel]
+        );
+        println!("✅ 修复步骤顺序测试通过");
+    }
+
+    #[test]
+    fn test_repair_captures_before_and_after() {
+        let temp_dir = TempDir::new().expect("创建临时目录失败");
+        let log_path = temp_dir.path().join("log.ndjson");
+        let logger = OperationLog::open(&log_path).expect("打开日志失败");
+        let backend = FakeBackend::new().with_label(TARGET, LabelLevel::System);
+        backend.set_owner(TARGET, "S-1-5-21-other").unwrap();
+
+        let report = repair_object(
+            &backend,
+            Path::new(TARGET),
+            &all_steps(LabelLevel::High),
+            "S-1-5-21-test",
+            &logger,
+            OperationOrigin::GuiButton,
+        )
+        .expect("修复不应返回错误");
+        logger.flush().unwrap();
+
+        assert!(report.succeeded());
+        assert_eq!(report.before.owner.as_deref(), Some("S-1-5-21-other"));
+        assert_eq!(report.before.dacl_protected, Some(true));
+        assert_eq!(report.before.level, Some(LabelLevel::System));
+        assert_eq!(report.after.owner.as_deref(), Some("S-1-5-21-test"));
+        assert_eq!(report.after.dacl_protected, Some(false));
+        assert_eq!(report.after.level, Some(LabelLevel::High));
+
+        let record = last_record(&log_path);
+        assert_eq!(record.status, REPAIRED_STATUS);
+        assert_eq!(record.level_applied, LabelLevel::High);
+        assert!(
+            record
+                .sddl_before_text()
+                .unwrap()
+                .contains("(ML;;NW;;;SI)")
+        );
+        assert!(
+            record
+                .sddl_after_text()
+                .unwrap()
+                .contains("(ML;;NW;;;HI)")
+        );
+        assert_eq!(
+            record.details.as_deref(),
+            Some(
+                "take_ownership=ok; reset_dacl_inheritance=ok; remove_label=ok; set_label(High)=ok"
+            )
+        );
+        assert!(record.errors.is_empty());
+
+        // 修复成功的记录更新保护清单
+        let mut inventory = ProtectionInventory::default();
+        inventory.apply(&record);
+        assert_eq!(inventory.len(), 1);
+
+        let lines = report.render_lines();
+        assert!(lines.iter().any(|l| l.contains("强制标签：System")));
+        assert!(lines.iter().any(|l| l.contains("强制标签：High")));
+        assert!(lines.iter().any(|l| l.contains("已阻止继承")));
+        println!("✅ 修复前后状态与日志记录测试通过");
+    }
+
+    #[test]
+    fn test_each_step_failure_is_tracked_independently() {
+        let steps = all_steps(LabelLevel::High).steps();
+        let ops = [
+            LabelOp::SetOwner,
+            LabelOp::ResetInheritance,
+            LabelOp::Remove,
+            LabelOp::Set,
+        ];
+        for (failing, op) in ops.into_iter().enumerate() {
+            let temp_dir = TempDir::new().expect("创建临时目录失败");
+            let log_path = temp_dir.path().join("log.ndjson");
+            let logger = OperationLog::open(&log_path).expect("打开日志失败");
+            let backend = FakeBackend::new().with_label(TARGET, LabelLevel::System);
+            backend.fail_next(op, TARGET, 5);
+
+            let report = repair_object(
+                &backend,
+                Path::new(TARGET),
+                &all_steps(LabelLevel::High),
+                "S-1-5-21-test",
+                &logger,
+                OperationOrigin::GuiButton,
+            )
+            .expect("修复不应返回错误");
+            logger.flush().unwrap();
+
+            // 只有注入失败的步骤失败，其余步骤照常执行
+            assert!(!report.succeeded());
+            let failed: Vec<_> = report.failed_steps().map(|o| o.step).collect();
+            assert_eq!(failed, vec![steps[failing]], "{:?}", op);
+            assert_eq!(report.steps.len(), steps.len());
+
+            let record = last_record(&log_path);
+            assert_eq!(record.status, REPAIRED_STATUS);
+            assert_eq!(record.errors.len(), 1, "{:?}", op);
+            assert!(record.errors[0].starts_with(&steps[failing].key()));
+            assert!(
+                record
+                    .details
+                    .as_deref()
+                    .unwrap()
+                    .contains(&format!("{}=failed", steps[failing].key()))
+            );
+
+            // 带错误的修复记录不更新保护清单
+            let mut inventory = ProtectionInventory::default();
+            inventory.apply(&record);
+            assert_eq!(inventory.len(), 0);
+        }
+        println!("✅ 各步骤失败独立记录测试通过");
+    }
+
+    #[test]
+    fn test_unreadable_before_state_is_reported() {
+        let temp_dir = TempDir::new().expect("创建临时目录失败");
+        let logger = OperationLog::open(temp_dir.path().join("log.ndjson")).expect("打开日志失败");
+        let backend = FakeBackend::new();
+        backend.fail_next(LabelOp::Get, TARGET, 5);
+
+        let report = repair_object(
+            &backend,
+            Path::new(TARGET),
+            &RepairOptions {
+                target_level: Some(LabelLevel::High),
+                ..Default::default()
+            },
+            "S-1-5-21-test",
+            &logger,
+            OperationOrigin::GuiButton,
+        )
+        .expect("修复不应返回错误");
+
+        assert!(report.before.sddl.is_none());
+        assert!(report.before.explain()[0].starts_with("无法读取安全描述符"));
+        assert_eq!(report.after.level, Some(LabelLevel::High));
+        println!("✅ 修复前状态不可读测试通过");
+    }
+}
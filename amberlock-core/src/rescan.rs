@@ -0,0 +1,440 @@
+//! 卷到达与系统恢复后的重新验证
+//!
+//! 可移动硬盘与网络共享断开期间，其上的受保护对象无法验证；重新连接后若要等到
+//! 下一次手动或计划扫描，标签被剥离的对象会在这段时间内无人发现。GUI 监听卷到达
+//! （`WM_DEVICECHANGE`/`DBT_DEVICEARRIVAL`）与系统恢复（`WM_POWERBROADCAST`）事件，
+//! 经本模块决定验证哪些清单根：
+//! - 卷到达：清单中位于该卷上的根
+//! - 系统恢复：所有网络共享根（睡眠期间连接已断开；本地卷的重新挂载另有到达事件）
+//!
+//! `RescanQueue` 对同一根的重复事件去抖，卷到达优先于系统恢复，两次验证之间至少
+//! 间隔 `RESCAN_MIN_GAP`；该卷上有进行中的操作时暂缓，操作结束后再开始。
+//! `rescan_root` 只验证该根下的对象，分块进行并在块之间暂停，漂移照常写入日志。
+
+use crate::backend::LabelBackend;
+use crate::inventory;
+use crate::progress::CancelToken;
+use crate::verify::{
+    ExpectedLabel, VerifyOptions, VerifyProgress, VerifyScope, verify_protection_chunked,
+};
+use amberlock_storage::OperationLog;
+use amberlock_types::Result;
+use std::collections::{BTreeSet, HashMap};
+use std::fmt::{Display, Formatter};
+use std::path::{Path, PathBuf};
+use std::time::{Duration, Instant};
+
+/// 同一根的重复事件在此时间内只排队一次
+pub const RESCAN_DEBOUNCE: Duration = Duration::from_secs(60);
+/// 两次重新验证开始之间的最短间隔
+pub const RESCAN_MIN_GAP: Duration = Duration::from_secs(10);
+/// 重新验证的每块路径数
+pub const RESCAN_CHUNK: usize = 200;
+/// 重新验证块之间的暂停
+pub const RESCAN_CHUNK_PAUSE: Duration = Duration::from_millis(200);
+
+/// 触发重新验证的系统事件
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum VolumeEvent {
+    /// 卷到达（卷根，如 `E:\`）
+    Arrival(String),
+    /// 系统从睡眠或休眠中恢复
+    Resume,
+}
+
+impl VolumeEvent {
+    /// 排队优先级
+    pub fn priority(&self) -> RescanPriority {
+        match self {
+            VolumeEvent::Arrival(_) => RescanPriority::Arrival,
+            VolumeEvent::Resume => RescanPriority::Resume,
+        }
+    }
+}
+
+/// 重新验证的优先级（值越小越先执行）
+#[derive(Debug, Clone, Copy, PartialEq, Eq, PartialOrd, Ord)]
+pub enum RescanPriority {
+    /// 卷到达
+    Arrival,
+    /// 系统恢复
+    Resume,
+}
+
+impl Display for RescanPriority {
+    fn fmt(&self, f: &mut Formatter<'_>) -> std::fmt::Result {
+        match self {
+            RescanPriority::Arrival => write!(f, "卷已连接"),
+            RescanPriority::Resume => write!(f, "系统已恢复"),
+        }
+    }
+}
+
+/// 事件涉及的清单根
+///
+/// # 参数
+/// - `roots`: 清单中的根（见 `ProtectionInventory::roots`）
+/// - `event`: 系统事件
+///
+/// # 返回
+/// 按清单顺序排列的根（卷根比较不区分大小写）
+pub fn roots_for_event(roots: &BTreeSet<String>, event: &VolumeEvent) -> Vec<String> {
+    roots
+        .iter()
+        .filter(|root| match event {
+            VolumeEvent::Arrival(volume) => root.eq_ignore_ascii_case(volume),
+            VolumeEvent::Resume => root.starts_with(r"\\"),
+        })
+        .cloned()
+        .collect()
+}
+
+/// 已排队的重新验证
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct RescanRequest {
+    /// 清单根
+    pub root: String,
+    /// 优先级
+    pub priority: RescanPriority,
+    /// 排队时间
+    pub queued_at: Instant,
+}
+
+/// 去抖、按优先级排序并限速的重新验证队列
+#[derive(Debug)]
+pub struct RescanQueue {
+    debounce: Duration,
+    min_gap: Duration,
+    pending: Vec<RescanRequest>,
+    last_queued: HashMap<String, Instant>,
+    last_started: Option<Instant>,
+}
+
+impl Default for RescanQueue {
+    fn default() -> Self {
+        Self::new(RESCAN_DEBOUNCE, RESCAN_MIN_GAP)
+    }
+}
+
+impl RescanQueue {
+    /// 创建队列
+    ///
+    /// # 参数
+    /// - `debounce`: 同一根的重复事件在此时间内只排队一次
+    /// - `min_gap`: 两次重新验证开始之间的最短间隔
+    pub fn new(debounce: Duration, min_gap: Duration) -> Self {
+        Self {
+            debounce,
+            min_gap,
+            pending: Vec::new(),
+            last_queued: HashMap::new(),
+            last_started: None,
+        }
+    }
+
+    /// 为事件涉及的根排队
+    ///
+    /// # 返回
+    /// 新排队的根数（去抖期内的重复事件与已在队列中的根不计）
+    ///
+    /// # 注意
+    /// 已在队列中的根遇到更高优先级的事件时提升优先级
+    pub fn push(&mut self, event: &VolumeEvent, roots: &[String], now: Instant) -> usize {
+        let priority = event.priority();
+        let mut queued = 0;
+        for root in roots {
+            let key = root.to_lowercase();
+            if let Some(pending) = self
+                .pending
+                .iter_mut()
+                .find(|p| p.root.to_lowercase() == key)
+            {
+                pending.priority = pending.priority.min(priority);
+                continue;
+            }
+            if self
+                .last_queued
+                .get(&key)
+                .is_some_and(|last| now.saturating_duration_since(*last) < self.debounce)
+            {
+                continue;
+            }
+            self.last_queued.insert(key, now);
+            self.pending.push(RescanRequest {
+                root: root.clone(),
+                priority,
+                queued_at: now,
+            });
+            queued += 1;
+        }
+        queued
+    }
+
+    /// 取出下一个可以开始的重新验证
+    ///
+    /// # 参数
+    /// - `now`: 当前时间
+    /// - `is_busy`: 该根上是否有进行中的操作（有则暂缓，留在队列中）
+    ///
+    /// # 返回
+    /// 优先级最高（同级先排队者优先）且不忙的请求；距上次开始不足最短间隔时为 None
+    pub fn next_ready(
+        &mut self,
+        now: Instant,
+        is_busy: impl Fn(&str) -> bool,
+    ) -> Option<RescanRequest> {
+        if self
+            .last_started
+            .is_some_and(|last| now.saturating_duration_since(last) < self.min_gap)
+        {
+            return None;
+        }
+        let index = self
+            .pending
+            .iter()
+            .enumerate()
+            .filter(|(_, request)| !is_busy(&request.root))
+            .min_by_key(|(_, request)| (request.priority, request.queued_at))
+            .map(|(index, _)| index)?;
+        self.last_started = Some(now);
+        Some(self.pending.remove(index))
+    }
+
+    /// 排队中的请求数
+    pub fn len(&self) -> usize {
+        self.pending.len()
+    }
+
+    /// 队列是否为空
+    pub fn is_empty(&self) -> bool {
+        self.pending.is_empty()
+    }
+}
+
+/// 位于指定根下的期望列表
+pub fn expected_under_root(expected: &[ExpectedLabel], root: &str) -> Vec<ExpectedLabel> {
+    expected
+        .iter()
+        .filter(|entry| inventory::root_of(&entry.path).eq_ignore_ascii_case(root))
+        .cloned()
+        .collect()
+}
+
+/// 根的重新验证状态文件（`{日志文件名}.rescan-{根}.json`，根中的非字母数字字符替换为 `_`）
+pub fn rescan_state_path(log_path: impl AsRef<Path>, root: &str) -> PathBuf {
+    let sanitized: String = root
+        .chars()
+        .map(|c| if c.is_ascii_alphanumeric() { c } else { '_' })
+        .collect();
+    let mut name = log_path.as_ref().as_os_str().to_os_string();
+    name.push(format!(".rescan-{}.json", sanitized));
+    PathBuf::from(name)
+}
+
+/// 分块重新验证一个根下的对象
+///
+/// # 参数
+/// - `backend`: 标签读取后端
+/// - `expected`: 清单的期望列表（只验证位于 `root` 下的对象）
+/// - `root`: 清单根
+/// - `log_path`: 日志路径（用于定位状态文件）
+/// - `logger`: 写入漂移记录的操作日志
+/// - `user_sid`: 用户 SID
+/// - `chunk_pause`: 块之间的暂停
+/// - `cancel`: 取消令牌（在块之间检查）
+///
+/// # 返回
+/// 汇总各块的进度（`drifted_paths` 为本次发现的全部漂移）
+#[allow(clippy::too_many_arguments)]
+pub fn rescan_root(
+    backend: &dyn LabelBackend,
+    expected: &[ExpectedLabel],
+    root: &str,
+    log_path: impl AsRef<Path>,
+    logger: &OperationLog,
+    user_sid: &str,
+    chunk_pause: Duration,
+    cancel: Option<&CancelToken>,
+) -> Result<VerifyProgress> {
+    let scope = VerifyScope::Entries(expected_under_root(expected, root));
+    let options = VerifyOptions {
+        chunk_size: RESCAN_CHUNK,
+        max_chunks: Some(1),
+        ..VerifyOptions::new(rescan_state_path(log_path, root))
+    };
+
+    let mut total = VerifyProgress::default();
+    loop {
+        let progress =
+            verify_protection_chunked(backend, &scope, logger, user_sid, &options, cancel)?;
+        total.total = progress.total;
+        total.completed = progress.completed;
+        total.checked += progress.checked;
+        total.skipped_unchanged += progress.skipped_unchanged;
+        total.failed += progress.failed;
+        total.missing_paths.extend(progress.missing_paths);
+        total.drifted_paths.extend(progress.drifted_paths);
+        total.pass_drifted = progress.pass_drifted;
+        total.pass_complete = progress.pass_complete;
+        if progress.pass_complete || cancel.is_some_and(CancelToken::is_cancelled) {
+            return Ok(total);
+        }
+        std::thread::sleep(chunk_pause);
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::backend::FakeBackend;
+    use amberlock_types::{LabelLevel, ProtectMode, TargetKind};
+    use tempfile::TempDir;
+
+    fn entry(path: &str, level: LabelLevel) -> ExpectedLabel {
+        ExpectedLabel {
+            path: path.to_string(),
+            kind: TargetKind::File,
+            mode: ProtectMode::ReadOnly,
+            level,
+            file_id: None,
+        }
+    }
+
+    fn fake_roots() -> BTreeSet<String> {
+        [r"C:\", r"E:\", r"\\nas\backup"]
+            .into_iter()
+            .map(String::from)
+            .collect()
+    }
+
+    #[test]
+    fn test_event_to_root_mapping() {
+        let roots = fake_roots();
+        assert_eq!(
+            roots_for_event(&roots, &VolumeEvent::Arrival(r"e:\".to_string())),
+            vec![r"E:\"]
+        );
+        // 清单中没有该卷
+        assert!(roots_for_event(&roots, &VolumeEvent::Arrival(r"F:\".to_string())).is_empty());
+        // 系统恢复只涉及网络共享
+        assert_eq!(
+            roots_for_event(&roots, &VolumeEvent::Resume),
+            vec![r"\\nas\backup"]
+        );
+        println!("✅ 事件到清单根的映射测试通过");
+    }
+
+    #[test]
+    fn test_debounce_and_priority() {
+        let start = Instant::now();
+        let mut queue = RescanQueue::new(Duration::from_secs(60), Duration::from_secs(10));
+        let arrival = VolumeEvent::Arrival(r"E:\".to_string());
+        let e = vec![r"E:\".to_string()];
+        let nas = vec![r"\\nas\backup".to_string()];
+
+        assert_eq!(queue.push(&VolumeEvent::Resume, &nas, start), 1);
+        assert_eq!(queue.push(&arrival, &e, start), 1);
+        // 同一卷反复到达：去抖期内不再排队
+        assert_eq!(queue.push(&arrival, &e, start + Duration::from_secs(1)), 0);
+        assert_eq!(queue.len(), 2);
+
+        // 卷到达优先于系统恢复，即使排队更晚
+        let first = queue
+            .next_ready(start, |_| false)
+            .expect("应有可执行的请求");
+        assert_eq!(first.root, r"E:\");
+        assert_eq!(first.priority, RescanPriority::Arrival);
+        // 两次开始之间须间隔最短时间
+        assert!(
+            queue
+                .next_ready(start + Duration::from_secs(5), |_| false)
+                .is_none()
+        );
+        let second = queue
+            .next_ready(start + Duration::from_secs(10), |_| false)
+            .expect("应有可执行的请求");
+        assert_eq!(second.root, r"\\nas\backup");
+        assert!(queue.is_empty());
+
+        // 去抖期过后可以再次排队
+        assert_eq!(queue.push(&arrival, &e, start + Duration::from_secs(61)), 1);
+        println!("✅ 去抖与优先级测试通过");
+    }
+
+    #[test]
+    fn test_busy_volume_is_deferred() {
+        let start = Instant::now();
+        let mut queue = RescanQueue::new(Duration::from_secs(60), Duration::ZERO);
+        queue.push(
+            &VolumeEvent::Arrival(r"E:\".to_string()),
+            &[r"E:\".to_string()],
+            start,
+        );
+
+        // 该卷上有进行中的操作：不开始，请求留在队列中
+        assert!(queue.next_ready(start, |root| root == r"E:\").is_none());
+        assert_eq!(queue.len(), 1);
+
+        // 队列中的根遇到更高优先级事件时提升优先级
+        let mut resumed = RescanQueue::new(Duration::from_secs(60), Duration::ZERO);
+        let nas = [r"\\nas\backup".to_string()];
+        resumed.push(&VolumeEvent::Resume, &nas, start);
+        assert_eq!(
+            resumed.push(&VolumeEvent::Arrival(nas[0].clone()), &nas, start),
+            0
+        );
+        assert_eq!(
+            resumed.next_ready(start, |_| false).unwrap().priority,
+            RescanPriority::Arrival
+        );
+
+        let request = queue
+            .next_ready(start + Duration::from_secs(1), |_| false)
+            .expect("操作结束后应开始");
+        assert_eq!(request.root, r"E:\");
+        println!("✅ 忙碌卷暂缓测试通过");
+    }
+
+    #[test]
+    fn test_rescan_only_checks_arrived_root() {
+        let temp_dir = TempDir::new().expect("创建临时目录失败");
+        let log_path = temp_dir.path().join("log.ndjson");
+        let logger = OperationLog::open(&log_path).expect("打开日志失败");
+        let backend = FakeBackend::new()
+            .with_label(r"E:\a.txt", LabelLevel::High)
+            .with_label(r"C:\c.txt", LabelLevel::Medium);
+        // E:\b.txt 的标签已被剥离
+        let expected: Vec<_> = (0..RESCAN_CHUNK)
+            .map(|i| {
+                let path = format!(r"E:\dir\{:04}.txt", i);
+                backend.insert_label(&path, LabelLevel::High);
+                entry(&path, LabelLevel::High)
+            })
+            .chain([
+                entry(r"E:\a.txt", LabelLevel::High),
+                entry(r"E:\b.txt", LabelLevel::High),
+                entry(r"C:\c.txt", LabelLevel::High),
+            ])
+            .collect();
+
+        let progress = rescan_root(
+            &backend,
+            &expected,
+            r"E:\",
+            &log_path,
+            &logger,
+            "S-1-5-21-test",
+            Duration::ZERO,
+            None,
+        )
+        .expect("重新验证失败");
+
+        // 跨越两块，且不涉及 C:\ 上的对象
+        assert!(progress.pass_complete);
+        assert_eq!(progress.total, RESCAN_CHUNK + 2);
+        assert_eq!(progress.checked, RESCAN_CHUNK + 2);
+        assert_eq!(progress.drifted_paths, vec![r"E:\b.txt"]);
+        assert!(rescan_state_path(&log_path, r"E:\").exists());
+        println!("✅ 只重新验证到达的卷：{}", progress);
+    }
+}
@@ -0,0 +1,285 @@
+//! 按批次撤销上锁
+//!
+//! 批量上锁后发现操作有误时，按日志中的 `batch_id` 找出该批次成功上锁的对象，
+//! 逐个解锁并写入新批次的记录（`details` 指向被撤销的批次）。
+//! 批次之后标签又被改动过的对象（当前标签与该批次写入的不一致）不解锁，
+//! 以 `LABEL_CHANGED_STATUS` 记录原因并计为跳过。
+
+use crate::backend::{LabelBackend, ObjectLabel};
+use crate::{LockOutcome, LockResult, OperationContext, pathutil, readonly};
+use amberlock_storage::OperationLog;
+use amberlock_storage::query::QueryBuilder;
+use amberlock_types::{LabelLevel, LockRecord, OperationOrigin, ProtectMode, Result};
+use std::collections::HashMap;
+use std::path::{Path, PathBuf};
+
+/// 标签在批次之后被改动、因此未撤销的对象的日志状态
+pub const LABEL_CHANGED_STATUS: &str = "skipped_label_changed";
+
+/// 批次中成功上锁的一个对象
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct BatchEntry {
+    /// 对象路径（与日志记录一致）
+    pub path: PathBuf,
+    /// 该批次写入的完整性级别
+    pub level: LabelLevel,
+    /// 该批次上锁后读取到的标签
+    pub sddl_after: Option<String>,
+}
+
+/// 查询批次中成功上锁的对象
+///
+/// # 参数
+/// - `log_path`: 审计日志路径
+/// - `batch_id`: 批次 ID
+///
+/// # 返回
+/// 按首次出现顺序去重的对象；同一路径有多条记录时以最后一条为准
+pub fn batch_entries(log_path: impl AsRef<Path>, batch_id: &str) -> Result<Vec<BatchEntry>> {
+    let records = QueryBuilder::new(log_path)
+        .filter_status("success")
+        .filter_custom("batch_id", batch_id)
+        .execute()?;
+
+    let mut entries: Vec<BatchEntry> = Vec::new();
+    let mut index: HashMap<PathBuf, usize> = HashMap::new();
+    for value in records {
+        let Ok(record) = serde_json::from_value::<LockRecord>(value) else {
+            continue;
+        };
+        let entry = BatchEntry {
+            sddl_after: record.sddl_after_text(),
+            path: PathBuf::from(record.path),
+            level: record.level_applied,
+        };
+        match index.get(&entry.path) {
+            Some(&i) => entries[i] = entry,
+            None => {
+                index.insert(entry.path.clone(), entries.len());
+                entries.push(entry);
+            }
+        }
+    }
+    Ok(entries)
+}
+
+/// 一次批次撤销操作
+#[derive(Debug, Clone)]
+pub struct BatchRevert {
+    /// 被撤销的批次
+    pub source_batch: String,
+    /// 本次撤销操作自身的批次 ID
+    pub batch_id: String,
+    /// 用户 SID
+    pub user_sid: String,
+    /// 授权本次撤销的保险库凭据名称
+    pub authorized_by: Option<String>,
+    /// 发起本次撤销的入口
+    pub origin: OperationOrigin,
+}
+
+impl BatchRevert {
+    /// 创建撤销操作（新批次 ID 由 `logger` 的记录 ID 来源生成）
+    pub fn new(source_batch: &str, user_sid: &str, logger: &OperationLog) -> Self {
+        Self {
+            source_batch: source_batch.to_string(),
+            batch_id: logger.stamper().new_id(),
+            user_sid: user_sid.to_string(),
+            authorized_by: None,
+            origin: OperationOrigin::Unknown,
+        }
+    }
+
+    /// 记录授权本次撤销的保险库凭据名称
+    pub fn with_authorized_by(mut self, credential: Option<&str>) -> Self {
+        self.authorized_by = credential.map(str::to_string);
+        self
+    }
+
+    /// 记录发起本次撤销的入口
+    pub fn with_origin(mut self, origin: OperationOrigin) -> Self {
+        self.origin = origin;
+        self
+    }
+
+    /// 写入撤销记录 `details` 的文本
+    pub fn details(&self) -> String {
+        format!("reverts batch {}", self.source_batch)
+    }
+
+    /// 撤销单个对象
+    ///
+    /// # 返回
+    /// - `Ok(Success)`: 已解锁
+    /// - `Ok(Skipped)`: 标签在批次之后已被改动（或无法读取），未解锁
+    /// - `Err`: 解锁失败
+    ///
+    /// # 注意
+    /// 不检查特权，调用方负责（见 `ops::unlock_batch`）
+    pub fn revert_entry(
+        &self,
+        backend: &dyn LabelBackend,
+        entry: &BatchEntry,
+        logger: &OperationLog,
+    ) -> Result<LockOutcome> {
+        pathutil::ensure_not_reserved(&entry.path)?;
+
+        let details = self.details();
+        let ctx = OperationContext::new(&entry.path, &self.user_sid, logger)
+            .with_details(Some(&details))
+            .with_authorized_by(self.authorized_by.as_deref())
+            .with_batch_id(Some(&self.batch_id))
+            .with_origin(self.origin);
+        readonly::ensure_writable(&ctx, ProtectMode::ReadOnly, LabelLevel::Medium)?;
+
+        let current = backend.get_label(&ctx.target).ok();
+        if let Some(reason) = label_changed(entry, current.as_ref()) {
+            ctx.log_and_track(
+                ProtectMode::ReadOnly,
+                LabelLevel::Medium,
+                current.map(|label| label.sddl),
+                None,
+                LABEL_CHANGED_STATUS,
+                vec![reason],
+            );
+            return Ok(ctx.outcome(LockResult::Skipped));
+        }
+
+        let result = backend.remove_label(&ctx.target);
+        ctx.log_unlock(current.map(|label| label.sddl), result)
+    }
+}
+
+/// 当前标签与批次写入的不一致时返回原因
+pub(crate) fn label_changed(entry: &BatchEntry, current: Option<&ObjectLabel>) -> Option<String> {
+    let Some(current) = current else {
+        return Some("无法读取当前标签".to_string());
+    };
+    let unchanged = match &entry.sddl_after {
+        Some(sddl) => *sddl == current.sddl,
+        None => current.level == entry.level,
+    };
+    (!unchanged).then(|| {
+        format!(
+            "标签在批次之后已被修改（批次写入 {:?}，当前 {:?}）",
+            entry.level, current.level
+        )
+    })
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::backend::{FakeBackend, lock_object};
+    use crate::{BatchResult, accumulate_outcome};
+    use amberlock_storage::{NdjsonReader, SequenceStamper};
+    use std::sync::Arc;
+    use tempfile::TempDir;
+
+    /// 按上锁流程写入一条批次记录
+    fn lock_in_batch(backend: &FakeBackend, logger: &OperationLog, path: &Path, batch_id: &str) {
+        let ctx =
+            OperationContext::new(path, "S-1-5-21-test", logger).with_batch_id(Some(batch_id));
+        let change = lock_object(backend, &ctx.target, LabelLevel::High);
+        assert!(change.result.is_ok());
+        ctx.log_and_track(
+            ProtectMode::ReadOnly,
+            LabelLevel::High,
+            change.before.map(|label| label.sddl),
+            change.after.map(|label| label.sddl),
+            "success",
+            vec![],
+        );
+    }
+
+    #[test]
+    fn test_unlock_batch_skips_labels_changed_since() {
+        let temp_dir = TempDir::new().expect("创建临时目录失败");
+        let log_path = temp_dir.path().join("log.ndjson");
+        let logger = OperationLog::open(&log_path)
+            .expect("打开日志失败")
+            .with_stamper(Arc::new(SequenceStamper::new("2025-01-01T00:00:00Z")));
+        let backend = FakeBackend::new();
+
+        let paths: Vec<PathBuf> = (0..4)
+            .map(|i| temp_dir.path().join(format!("file_{}.txt", i)))
+            .collect();
+        for path in &paths {
+            lock_in_batch(&backend, &logger, path, "batch-a");
+        }
+        // 其他批次的记录不受影响
+        let other = temp_dir.path().join("other.txt");
+        lock_in_batch(&backend, &logger, &other, "batch-b");
+        // 批次之后有人改动了其中一个对象的标签
+        let mutated = paths[2].to_string_lossy().to_string();
+        backend.insert_label(&mutated, LabelLevel::System);
+        logger.flush().unwrap();
+
+        let entries = batch_entries(&log_path, "batch-a").expect("查询批次失败");
+        assert_eq!(entries.len(), 4);
+
+        let revert = BatchRevert::new("batch-a", "S-1-5-21-test", &logger)
+            .with_authorized_by(Some("default"));
+        let mut result = BatchResult {
+            total_count: entries.len(),
+            ..Default::default()
+        };
+        for entry in &entries {
+            let outcome = revert.revert_entry(&backend, entry, &logger);
+            accumulate_outcome(&mut result, &entry.path, &outcome);
+        }
+        logger.flush().unwrap();
+
+        assert_eq!(result.success_count, 3);
+        assert_eq!(result.skipped_count, 1);
+        assert_eq!(result.failed_count, 0);
+        assert_eq!(backend.label_of(&mutated), Some(LabelLevel::System));
+        for path in paths.iter().filter(|p| p.to_string_lossy() != mutated) {
+            assert_eq!(backend.label_of(&path.to_string_lossy()), None);
+        }
+        assert_eq!(
+            backend.label_of(&other.to_string_lossy()),
+            Some(LabelLevel::High)
+        );
+
+        let mut reader = NdjsonReader::open(&log_path).expect("打开日志失败");
+        let records: Vec<LockRecord> = reader
+            .read_last_n(4)
+            .expect("读取日志失败")
+            .into_iter()
+            .map(|value| serde_json::from_value(value).unwrap())
+            .collect();
+        for record in &records {
+            assert_eq!(record.batch_id.as_deref(), Some(revert.batch_id.as_str()));
+            assert_eq!(record.details.as_deref(), Some("reverts batch batch-a"));
+            assert_eq!(record.authorized_by.as_deref(), Some("default"));
+        }
+        let skipped: Vec<_> = records
+            .iter()
+            .filter(|r| r.status == LABEL_CHANGED_STATUS)
+            .collect();
+        assert_eq!(skipped.len(), 1);
+        assert_eq!(skipped[0].path, mutated);
+        assert!(skipped[0].errors[0].contains("已被修改"));
+        assert_eq!(records.iter().filter(|r| r.status == "unlocked").count(), 3);
+        println!("✅ 按批次撤销：跳过已被改动的对象并关联原批次");
+    }
+
+    #[test]
+    fn test_batch_entries_deduplicates_paths() {
+        let temp_dir = TempDir::new().expect("创建临时目录失败");
+        let log_path = temp_dir.path().join("log.ndjson");
+        let logger = OperationLog::open(&log_path).expect("打开日志失败");
+        let backend = FakeBackend::new();
+        let path = temp_dir.path().join("a.txt");
+
+        lock_in_batch(&backend, &logger, &path, "batch-a");
+        lock_in_batch(&backend, &logger, &path, "batch-a");
+        logger.flush().unwrap();
+
+        let entries = batch_entries(&log_path, "batch-a").expect("查询批次失败");
+        assert_eq!(entries.len(), 1);
+        assert_eq!(entries[0].sddl_after.as_deref(), Some("S:(ML;;NW;;;HI)"));
+        assert!(batch_entries(&log_path, "missing").unwrap().is_empty());
+    }
+}
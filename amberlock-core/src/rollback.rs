@@ -0,0 +1,489 @@
+//! 批量上锁被取消时的处理
+//!
+//! 取消只停止处理剩余对象；已上锁的对象按 `LockOptions.on_cancel`
+//! （或取消时通过 `CancelToken::cancel_with` 临时指定的方式）处理：
+//! - `KeepCompleted`：保持上锁
+//! - `RollBackCompleted`：按日志中本批次的成功记录逆序解锁，
+//!   每个对象写入 `ROLLED_BACK_STATUS` 记录（与原批次同一 `batch_id`）；
+//!   标签在上锁之后又被改动的对象不解锁，以 `LABEL_CHANGED_STATUS` 记录
+//!
+//! 两种方式都会追加一条批次摘要记录，状态分别为 `CANCELLED_KEPT_STATUS`
+//! 与 `CANCELLED_ROLLED_BACK_STATUS`。
+//!
+//! 上锁记录总是写入上锁后的标签（`sddl_after`），审计日志本身即撤销所需的备份，
+//! 因此撤销不依赖额外的开关。
+
+use crate::backend::LabelBackend;
+use crate::progress::CancelToken;
+use crate::revert::{BatchEntry, LABEL_CHANGED_STATUS, batch_entries, label_changed};
+use crate::{BatchResult, LockOptions, MAX_FAILED_PATHS, OperationContext, pathutil, readonly};
+use amberlock_storage::OperationLog;
+use amberlock_types::{CancelBehavior, LabelLevel, OperationOrigin, ProtectMode, Result};
+use std::fmt::{Display, Formatter};
+use std::path::{Path, PathBuf};
+
+/// 取消后保留已完成对象时批次摘要记录的状态
+pub const CANCELLED_KEPT_STATUS: &str = "cancelled_kept";
+/// 取消后撤销已完成对象时批次摘要记录的状态
+pub const CANCELLED_ROLLED_BACK_STATUS: &str = "cancelled_rolled_back";
+/// 因批次被取消而解锁的对象的日志状态
+pub const ROLLED_BACK_STATUS: &str = "rolled_back";
+
+/// 撤销被取消批次的结果
+#[derive(Debug, Clone, Default, PartialEq, Eq)]
+pub struct RollbackResult {
+    /// 已解锁的对象数
+    pub rolled_back_count: usize,
+    /// 标签在上锁之后被改动、未解锁的对象数
+    pub skipped_count: usize,
+    /// 解锁失败的对象数
+    pub failed_count: usize,
+    /// 解锁失败的路径样本（最多 `MAX_FAILED_PATHS` 个）
+    pub failed_paths: Vec<String>,
+}
+
+impl RollbackResult {
+    /// 是否所有已完成对象都已恢复
+    pub fn is_complete(&self) -> bool {
+        self.skipped_count == 0 && self.failed_count == 0
+    }
+}
+
+impl Display for RollbackResult {
+    fn fmt(&self, f: &mut Formatter<'_>) -> std::fmt::Result {
+        write!(
+            f,
+            "已撤销 {} 个，跳过 {} 个，失败 {} 个",
+            self.rolled_back_count, self.skipped_count, self.failed_count
+        )
+    }
+}
+
+/// 被取消批次的处理结果（见 `BatchResult.cancelled`）
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct CancelledBatch {
+    /// 实际采用的处理方式
+    pub behavior: CancelBehavior,
+    /// 取消前已处理的对象数
+    pub processed: usize,
+    /// 撤销结果（保留已完成对象时为 None）
+    pub rollback: Option<RollbackResult>,
+}
+
+impl CancelledBatch {
+    /// 批次摘要记录的状态
+    pub fn status(&self) -> &'static str {
+        match self.behavior {
+            CancelBehavior::KeepCompleted => CANCELLED_KEPT_STATUS,
+            CancelBehavior::RollBackCompleted => CANCELLED_ROLLED_BACK_STATUS,
+        }
+    }
+}
+
+impl Display for CancelledBatch {
+    fn fmt(&self, f: &mut Formatter<'_>) -> std::fmt::Result {
+        write!(
+            f,
+            "已取消（处理了 {} 个对象），{}",
+            self.processed, self.behavior
+        )?;
+        if let Some(rollback) = &self.rollback {
+            write!(f, "：{}", rollback)?;
+        }
+        Ok(())
+    }
+}
+
+/// 按日志撤销批次中已上锁的对象（逆序，子对象先于父目录）
+///
+/// # 参数
+/// - `backend`: 标签读写后端
+/// - `batch_id`: 被取消的批次
+/// - `user_sid`: 用户 SID
+/// - `origin`: 发起操作的入口，写入每条记录
+/// - `logger`: 日志记录器（先刷新，确保读到本批次的全部记录）
+///
+/// # 注意
+/// - 撤销不响应取消令牌，一旦开始即处理完本批次的全部对象
+/// - 只读审计模式下不撤销任何对象，返回 `ReadOnlyMode`
+pub fn roll_back_batch(
+    backend: &dyn LabelBackend,
+    batch_id: &str,
+    user_sid: &str,
+    origin: OperationOrigin,
+    logger: &OperationLog,
+) -> Result<RollbackResult> {
+    logger.flush()?;
+    let entries = batch_entries(logger.path(), batch_id)?;
+    let paths: Vec<&Path> = entries.iter().map(|entry| entry.path.as_path()).collect();
+    readonly::ensure_batch_writable(
+        &paths,
+        ProtectMode::ReadOnly,
+        LabelLevel::Medium,
+        user_sid,
+        logger,
+        None,
+    )?;
+
+    let mut result = RollbackResult::default();
+    for entry in entries.iter().rev() {
+        match roll_back_entry(backend, entry, batch_id, user_sid, origin, logger) {
+            Ok(true) => result.rolled_back_count += 1,
+            Ok(false) => result.skipped_count += 1,
+            Err(_) => {
+                result.failed_count += 1;
+                if result.failed_paths.len() < MAX_FAILED_PATHS {
+                    result
+                        .failed_paths
+                        .push(entry.path.to_string_lossy().to_string());
+                }
+            }
+        }
+    }
+    Ok(result)
+}
+
+/// 撤销单个对象；返回是否已解锁（false 为标签已被改动而跳过）
+fn roll_back_entry(
+    backend: &dyn LabelBackend,
+    entry: &BatchEntry,
+    batch_id: &str,
+    user_sid: &str,
+    origin: OperationOrigin,
+    logger: &OperationLog,
+) -> Result<bool> {
+    pathutil::ensure_not_reserved(&entry.path)?;
+
+    let details = format!("rolls back cancelled batch {}", batch_id);
+    let ctx = OperationContext::new(&entry.path, user_sid, logger)
+        .with_details(Some(&details))
+        .with_batch_id(Some(batch_id))
+        .with_origin(origin);
+
+    let current = backend.get_label(&ctx.target).ok();
+    if let Some(reason) = label_changed(entry, current.as_ref()) {
+        ctx.log_and_track(
+            ProtectMode::ReadOnly,
+            LabelLevel::Medium,
+            current.map(|label| label.sddl),
+            None,
+            LABEL_CHANGED_STATUS,
+            vec![reason],
+        );
+        return Ok(false);
+    }
+
+    let sddl_before = current.map(|label| label.sddl);
+    match backend.remove_label(&ctx.target) {
+        Ok(()) => {
+            ctx.log_and_track(
+                ProtectMode::ReadOnly,
+                LabelLevel::Medium,
+                sddl_before,
+                None,
+                ROLLED_BACK_STATUS,
+                vec![],
+            );
+            Ok(true)
+        }
+        Err(e) => {
+            ctx.log_and_track(
+                ProtectMode::ReadOnly,
+                LabelLevel::Medium,
+                sddl_before,
+                None,
+                "error",
+                vec![format!("{:?}", e)],
+            );
+            Err(e)
+        }
+    }
+}
+
+/// 批量上锁结束后处理取消：按选项撤销已完成对象，并写入批次摘要记录
+///
+/// 未请求取消或所有对象均已处理时不做任何事；
+/// 否则设置 `result.cancelled`
+///
+/// # 参数
+/// - `result`: 批量上锁的统计
+/// - `paths`: 本批次的全部路径（摘要记录写在第一个路径上）
+/// - `opts`: 批量选项（须已通过 `in_batch` 分配批次 ID）
+/// - `cancel`: 本次操作的取消令牌
+#[cfg_attr(not(feature = "winsec"), allow(dead_code))]
+pub(crate) fn settle_cancelled(
+    result: &mut BatchResult,
+    paths: &[PathBuf],
+    opts: &LockOptions,
+    cancel: &CancelToken,
+    backend: &dyn LabelBackend,
+    user_sid: &str,
+    logger: &OperationLog,
+) {
+    let processed = result.success_count + result.failed_count + result.skipped_count;
+    if !cancel.is_cancelled() || processed >= result.total_count {
+        return;
+    }
+    let Some(first) = paths.first() else {
+        return;
+    };
+    let batch_id = opts.batch_id.as_deref().unwrap_or_default();
+
+    let behavior = cancel.requested_behavior().unwrap_or(opts.on_cancel);
+    let rollback = match behavior {
+        CancelBehavior::KeepCompleted => None,
+        CancelBehavior::RollBackCompleted => Some(
+            roll_back_batch(backend, batch_id, user_sid, opts.origin, logger).unwrap_or_else(
+                |_| RollbackResult {
+                    failed_count: result.success_count,
+                    ..Default::default()
+                },
+            ),
+        ),
+    };
+    let cancelled = CancelledBatch {
+        behavior,
+        processed,
+        rollback,
+    };
+
+    let details = format!("{}/{} {}", processed, result.total_count, cancelled);
+    OperationContext::new(first, user_sid, logger)
+        .with_details(Some(&details))
+        .with_comment(opts.comment.as_deref())
+        .with_batch_id(Some(batch_id))
+        .with_origin(opts.origin)
+        .log_and_track(
+            opts.mode,
+            opts.desired_level,
+            None,
+            None,
+            cancelled.status(),
+            vec![],
+        );
+    let _ = logger.flush();
+
+    result.cancelled = Some(cancelled);
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::accumulate_outcome;
+    use crate::backend::{FakeBackend, lock_object};
+    use amberlock_storage::{NdjsonReader, SequenceStamper};
+    use amberlock_types::LockRecord;
+    use std::path::Path;
+    use std::sync::Arc;
+    use tempfile::TempDir;
+
+    const USER_SID: &str = "S-1-5-21-test";
+
+    /// 逐个上锁，处理完 `cancel_after` 个对象后请求取消（模拟用户在固定位置点击取消）
+    fn run_until_cancelled(
+        backend: &FakeBackend,
+        paths: &[PathBuf],
+        opts: &LockOptions,
+        cancel: &CancelToken,
+        cancel_after: usize,
+        logger: &OperationLog,
+    ) -> BatchResult {
+        let mut result = BatchResult {
+            total_count: paths.len(),
+            ..Default::default()
+        };
+        for (i, path) in paths.iter().enumerate() {
+            if cancel.is_cancelled() {
+                break;
+            }
+            let ctx = OperationContext::new(path, USER_SID, logger)
+                .with_batch_id(opts.batch_id.as_deref());
+            let change = lock_object(backend, &ctx.target, opts.desired_level);
+            ctx.log_and_track(
+                opts.mode,
+                opts.desired_level,
+                change.before.map(|label| label.sddl),
+                change.after.map(|label| label.sddl),
+                "success",
+                vec![],
+            );
+            accumulate_outcome(
+                &mut result,
+                path,
+                &Ok(ctx.outcome(crate::LockResult::Success)),
+            );
+            if i + 1 == cancel_after {
+                cancel.cancel();
+            }
+        }
+        result
+    }
+
+    fn setup(temp_dir: &TempDir) -> (OperationLog, Vec<PathBuf>) {
+        let logger = OperationLog::open(temp_dir.path().join("log.ndjson"))
+            .expect("打开日志失败")
+            .with_stamper(Arc::new(SequenceStamper::new("2025-01-01T00:00:00Z")));
+        let paths = (0..5)
+            .map(|i| temp_dir.path().join(format!("file_{}.txt", i)))
+            .collect();
+        (logger, paths)
+    }
+
+    fn records(log_path: &Path) -> Vec<LockRecord> {
+        let mut reader = NdjsonReader::open(log_path).expect("打开日志失败");
+        reader
+            .read_last_n(100)
+            .expect("读取日志失败")
+            .into_iter()
+            .map(|value| serde_json::from_value(value).unwrap())
+            .collect()
+    }
+
+    fn label(backend: &FakeBackend, path: &Path) -> Option<LabelLevel> {
+        backend.label_of(&path.to_string_lossy())
+    }
+
+    #[test]
+    fn test_cancel_keeps_completed_by_default() {
+        let temp_dir = TempDir::new().expect("创建临时目录失败");
+        let (logger, paths) = setup(&temp_dir);
+        let backend = FakeBackend::new();
+        let opts = LockOptions::default().in_batch(&logger);
+        assert_eq!(opts.on_cancel, CancelBehavior::KeepCompleted);
+        let cancel = CancelToken::new();
+
+        let mut result = run_until_cancelled(&backend, &paths, &opts, &cancel, 2, &logger);
+        settle_cancelled(
+            &mut result,
+            &paths,
+            &opts,
+            &cancel,
+            &backend,
+            USER_SID,
+            &logger,
+        );
+
+        let cancelled = result.cancelled.as_ref().expect("应标记为已取消");
+        assert_eq!(cancelled.behavior, CancelBehavior::KeepCompleted);
+        assert_eq!(cancelled.processed, 2);
+        assert!(cancelled.rollback.is_none());
+        assert_eq!(label(&backend, &paths[0]), Some(LabelLevel::High));
+        assert_eq!(label(&backend, &paths[1]), Some(LabelLevel::High));
+        for path in &paths[2..] {
+            assert_eq!(label(&backend, path), None);
+        }
+
+        let records = records(&logger.path());
+        let statuses: Vec<&str> = records.iter().map(|r| r.status.as_str()).collect();
+        assert_eq!(statuses, ["success", "success", CANCELLED_KEPT_STATUS]);
+        assert_eq!(records[2].batch_id, opts.batch_id);
+        assert!(records[2].details.as_deref().unwrap().starts_with("2/5"));
+        println!("✅ 取消批次：默认保留已完成的对象");
+    }
+
+    #[test]
+    fn test_cancel_rolls_back_completed_in_reverse() {
+        let temp_dir = TempDir::new().expect("创建临时目录失败");
+        let (logger, paths) = setup(&temp_dir);
+        let backend = FakeBackend::new();
+        let opts = LockOptions {
+            on_cancel: CancelBehavior::RollBackCompleted,
+            ..Default::default()
+        }
+        .in_batch(&logger);
+        let cancel = CancelToken::new();
+
+        let mut result = run_until_cancelled(&backend, &paths, &opts, &cancel, 3, &logger);
+        // 取消前有人改动了已上锁的一个对象，撤销时保留其标签
+        backend.insert_label(&paths[1].to_string_lossy(), LabelLevel::System);
+        settle_cancelled(
+            &mut result,
+            &paths,
+            &opts,
+            &cancel,
+            &backend,
+            USER_SID,
+            &logger,
+        );
+
+        let cancelled = result.cancelled.as_ref().expect("应标记为已取消");
+        assert_eq!(cancelled.behavior, CancelBehavior::RollBackCompleted);
+        let rollback = cancelled.rollback.as_ref().expect("应包含撤销结果");
+        assert_eq!(rollback.rolled_back_count, 2);
+        assert_eq!(rollback.skipped_count, 1);
+        assert_eq!(rollback.failed_count, 0);
+        // 批次统计仍反映取消前的处理结果
+        assert_eq!(result.success_count, 3);
+        assert_eq!(result.total_count, 5);
+
+        assert_eq!(label(&backend, &paths[0]), None);
+        assert_eq!(label(&backend, &paths[1]), Some(LabelLevel::System));
+        for path in &paths[2..] {
+            assert_eq!(label(&backend, path), None);
+        }
+
+        let records = records(&logger.path());
+        let tail: Vec<(&str, &str)> = records[3..]
+            .iter()
+            .map(|r| (r.path.as_str(), r.status.as_str()))
+            .collect();
+        let name = |i: usize| paths[i].to_str().unwrap();
+        assert_eq!(
+            tail,
+            [
+                (name(2), ROLLED_BACK_STATUS),
+                (name(1), LABEL_CHANGED_STATUS),
+                (name(0), ROLLED_BACK_STATUS),
+                (name(0), CANCELLED_ROLLED_BACK_STATUS),
+            ]
+        );
+        for record in &records {
+            assert_eq!(record.batch_id, opts.batch_id);
+        }
+        println!("✅ 取消批次：逆序撤销已完成的对象");
+    }
+
+    #[test]
+    fn test_cancel_override_and_completed_batch() {
+        let temp_dir = TempDir::new().expect("创建临时目录失败");
+        let (logger, paths) = setup(&temp_dir);
+        let backend = FakeBackend::new();
+        let opts = LockOptions::default().in_batch(&logger);
+
+        let cancel = CancelToken::new();
+        let mut result = run_until_cancelled(&backend, &paths, &opts, &cancel, 0, &logger);
+        cancel.cancel_with(CancelBehavior::RollBackCompleted);
+        assert_eq!(result.success_count, 5);
+        // 全部处理完后才取消：不视为被取消的批次
+        settle_cancelled(
+            &mut result,
+            &paths,
+            &opts,
+            &cancel,
+            &backend,
+            USER_SID,
+            &logger,
+        );
+        assert!(result.cancelled.is_none());
+
+        // 取消时临时指定撤销，覆盖选项中的保留
+        let opts = LockOptions::default().in_batch(&logger);
+        let cancel = CancelToken::new();
+        let mut result = run_until_cancelled(&backend, &paths, &opts, &cancel, 1, &logger);
+        cancel.cancel_with(CancelBehavior::RollBackCompleted);
+        settle_cancelled(
+            &mut result,
+            &paths,
+            &opts,
+            &cancel,
+            &backend,
+            USER_SID,
+            &logger,
+        );
+        let cancelled = result.cancelled.expect("应标记为已取消");
+        assert_eq!(cancelled.behavior, CancelBehavior::RollBackCompleted);
+        assert_eq!(cancelled.rollback.unwrap().rolled_back_count, 1);
+        assert_eq!(label(&backend, &paths[0]), None);
+        println!("✅ 取消批次：取消时临时指定处理方式");
+    }
+}
@@ -0,0 +1,858 @@
+//! 计划任务：按本地时间自动上锁、解锁或验证
+//!
+//! 计划保存在 `Settings.schedule` 中，由 GUI 的后台线程定期检查，
+//! 或由 `amberlock schedule run-due`（供 Windows 任务计划程序调用）执行一次。
+//!
+//! # 时间规则
+//! - `19:00`：每天 19:00
+//! - `mon-fri 08:00`：周一至周五 08:00（范围可跨周末，如 `fri-mon`）
+//! - `sat,sun 22:30`：周六、周日 22:30
+//!
+//! # 到期判定
+//! 取当前时刻之前最近一次计划时间点，晚于上次执行时间时：
+//! - 距该时间点不超过 `SCHEDULE_GRACE`：到期
+//! - 已错过（如关机期间）且条目设置了 `catch_up`：补执行一次（多次错过只执行一次）
+//!
+//! 夏令时切换时，被跳过的本地时刻顺延到切换之后，重复出现的本地时刻只执行第一次。
+//!
+//! # 无人值守解锁
+//! 计划任务无法交互输入保险库密码，解锁条目须由用户明确设置 `allow_unattended_unlock`，
+//! 否则每次到期都写入一条 `SCHEDULE_SKIPPED_STATUS` 记录并跳过。
+
+use std::fmt::{Display, Formatter};
+use std::path::{Path, PathBuf};
+
+use amberlock_storage::{OperationLog, parse_utc};
+use amberlock_types::{
+    AmberlockError, LabelLevel, OperationOrigin, ProtectMode, Result, Schedule, ScheduleAction,
+    ScheduleEntry,
+};
+use time::format_description::well_known::Rfc3339;
+use time::{Date, Duration, OffsetDateTime, PrimitiveDateTime, Time, UtcOffset};
+
+use crate::backend::{LabelBackend, unlock_object};
+use crate::glob::{ExpandOptions, expand_glob};
+use crate::presets::PRESET_MAX_MATCHES;
+use crate::verify::DRIFT_STATUS;
+use crate::{
+    BatchFailure, BatchResult, LockOptions, LockOutcome, LockResult, MAX_FAILED_PATHS,
+    OperationContext, accumulate_outcome, pathutil, readonly, volume,
+};
+
+/// 计划时间点之后仍视为按时执行的时长（覆盖检查间隔与睡眠唤醒的延迟）
+pub const SCHEDULE_GRACE: Duration = Duration::minutes(5);
+
+/// 计划任务被跳过时写入的状态
+pub const SCHEDULE_SKIPPED_STATUS: &str = "schedule_skipped";
+
+/// 向前查找计划时间点的天数（覆盖每周一次的规则）
+const LOOKBACK_DAYS: usize = 8;
+
+const WEEKDAY_NAMES: [&str; 7] = ["mon", "tue", "wed", "thu", "fri", "sat", "sun"];
+
+/// 本地时区（按 UTC 时刻查询偏移，含夏令时）
+pub trait LocalZone {
+    /// `utc` 时刻的本地偏移
+    fn offset_at(&self, utc: OffsetDateTime) -> UtcOffset;
+}
+
+/// 固定偏移的时区（无夏令时）
+#[derive(Debug, Clone, Copy)]
+pub struct FixedZone(pub UtcOffset);
+
+impl LocalZone for FixedZone {
+    fn offset_at(&self, _utc: OffsetDateTime) -> UtcOffset {
+        self.0
+    }
+}
+
+/// 系统时区（通过 `SystemTimeToTzSpecificLocalTime` 换算，含夏令时）
+#[cfg(feature = "winsec")]
+#[derive(Debug, Clone, Copy, Default)]
+pub struct SystemZone;
+
+#[cfg(feature = "winsec")]
+impl LocalZone for SystemZone {
+    fn offset_at(&self, utc: OffsetDateTime) -> UtcOffset {
+        use windows::Win32::Foundation::SYSTEMTIME;
+        use windows::Win32::System::Time::SystemTimeToTzSpecificLocalTime;
+
+        let utc = utc.to_offset(UtcOffset::UTC);
+        let universal = SYSTEMTIME {
+            wYear: utc.year() as u16,
+            wMonth: u8::from(utc.month()) as u16,
+            wDayOfWeek: 0,
+            wDay: utc.day() as u16,
+            wHour: utc.hour() as u16,
+            wMinute: utc.minute() as u16,
+            wSecond: utc.second() as u16,
+            wMilliseconds: 0,
+        };
+        let mut local = SYSTEMTIME::default();
+        // SAFETY: 两个指针均指向有效的栈上结构；时区参数为 None 时使用当前时区
+        if unsafe { SystemTimeToTzSpecificLocalTime(None, &universal, &mut local) }.is_err() {
+            return UtcOffset::UTC;
+        }
+        let local = time::Month::try_from(local.wMonth as u8)
+            .ok()
+            .and_then(|month| {
+                Date::from_calendar_date(local.wYear as i32, month, local.wDay as u8).ok()
+            })
+            .and_then(|date| {
+                date.with_hms(local.wHour as u8, local.wMinute as u8, local.wSecond as u8)
+                    .ok()
+            });
+        let universal = utc
+            .date()
+            .with_hms(utc.hour(), utc.minute(), utc.second())
+            .ok();
+        match (local, universal) {
+            (Some(local), Some(universal)) => {
+                let seconds = (local.assume_utc() - universal.assume_utc()).whole_seconds();
+                UtcOffset::from_whole_seconds(seconds as i32).unwrap_or(UtcOffset::UTC)
+            }
+            _ => UtcOffset::UTC,
+        }
+    }
+}
+
+/// 解析后的时间规则
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct TimeSpec {
+    /// 适用的星期（位 0 为周一）
+    days: u8,
+    time: Time,
+}
+
+impl TimeSpec {
+    /// 解析时间规则（`HH:MM` 或 `<星期> HH:MM`，星期不区分大小写）
+    pub fn parse(spec: &str) -> Result<Self> {
+        let invalid = || AmberlockError::InvalidSchedule(spec.to_string());
+        let spec_lower = spec.trim().to_ascii_lowercase();
+        let tokens: Vec<&str> = spec_lower.split_whitespace().collect();
+        let (days, clock) = match tokens.as_slice() {
+            [clock] => (0b111_1111, *clock),
+            [days, clock] => (parse_days(days).ok_or_else(invalid)?, *clock),
+            _ => return Err(invalid()),
+        };
+        let (hour, minute) = clock.split_once(':').ok_or_else(invalid)?;
+        let hour: u8 = hour.parse().map_err(|_| invalid())?;
+        let minute: u8 = minute.parse().map_err(|_| invalid())?;
+        let time = Time::from_hms(hour, minute, 0).map_err(|_| invalid())?;
+        Ok(Self { days, time })
+    }
+
+    /// 是否适用于该日期
+    pub fn matches(&self, date: Date) -> bool {
+        self.days & (1 << date.weekday().number_days_from_monday()) != 0
+    }
+
+    /// 某个本地日期的计划时间点（不适用于该日期时为 None）
+    pub fn occurrence_on(&self, date: Date, zone: &dyn LocalZone) -> Option<OffsetDateTime> {
+        self.matches(date)
+            .then(|| resolve_local(PrimitiveDateTime::new(date, self.time), zone))
+    }
+
+    /// 不晚于 `now` 的最近一次计划时间点
+    pub fn last_occurrence(
+        &self,
+        now: OffsetDateTime,
+        zone: &dyn LocalZone,
+    ) -> Option<OffsetDateTime> {
+        let mut date = now.to_offset(zone.offset_at(now)).date();
+        for _ in 0..LOOKBACK_DAYS {
+            if let Some(occurrence) = self.occurrence_on(date, zone)
+                && occurrence <= now
+            {
+                return Some(occurrence);
+            }
+            date = date.previous_day()?;
+        }
+        None
+    }
+}
+
+/// 解析星期列表（`mon`、`mon-fri`、`sat,sun`，`*` 与 `daily` 表示每天）
+fn parse_days(text: &str) -> Option<u8> {
+    if text == "*" || text == "daily" {
+        return Some(0b111_1111);
+    }
+    let index = |name: &str| WEEKDAY_NAMES.iter().position(|day| *day == name);
+    let mut days = 0u8;
+    for part in text.split(',') {
+        match part.split_once('-') {
+            Some((from, to)) => {
+                let (from, to) = (index(from)?, index(to)?);
+                let mut day = from;
+                loop {
+                    days |= 1 << day;
+                    if day == to {
+                        break;
+                    }
+                    day = (day + 1) % 7;
+                }
+            }
+            None => days |= 1 << index(part)?,
+        }
+    }
+    Some(days)
+}
+
+/// 将本地时间换算为时刻
+///
+/// # 规则
+/// - 唯一对应：直接换算
+/// - 夏令时结束、本地时间出现两次：取较早的一次
+/// - 夏令时开始、本地时间不存在：按切换前的偏移换算（即顺延到切换之后）
+fn resolve_local(local: PrimitiveDateTime, zone: &dyn LocalZone) -> OffsetDateTime {
+    let naive = local.assume_utc();
+    let before = zone.offset_at(naive - Duration::DAY);
+    let after = zone.offset_at(naive + Duration::DAY);
+    [before, after]
+        .into_iter()
+        .map(|offset| (offset, local.assume_offset(offset)))
+        .filter(|(offset, instant)| zone.offset_at(*instant) == *offset)
+        .map(|(_, instant)| instant)
+        .min()
+        .unwrap_or_else(|| local.assume_offset(before))
+        .to_offset(UtcOffset::UTC)
+}
+
+/// 条目到期时返回对应的计划时间点
+///
+/// # 返回
+/// - `Ok(Some(时间点))`: 应执行
+/// - `Ok(None)`: 未到期、已执行过，或已错过且未设置 `catch_up`
+/// - `Err(InvalidSchedule)`: 时间规则无效
+///
+/// # 注意
+/// 从未执行过的条目只在时间点附近执行，不补执行添加之前的时间点
+pub fn due_occurrence(
+    entry: &ScheduleEntry,
+    now: OffsetDateTime,
+    zone: &dyn LocalZone,
+) -> Result<Option<OffsetDateTime>> {
+    let spec = TimeSpec::parse(&entry.at)?;
+    let Some(occurrence) = spec.last_occurrence(now, zone) else {
+        return Ok(None);
+    };
+    let last_run = entry.last_run_utc.as_deref().and_then(parse_utc);
+    if last_run.is_some_and(|last_run| last_run >= occurrence) {
+        return Ok(None);
+    }
+    let on_time = now - occurrence <= SCHEDULE_GRACE;
+    let catch_up = entry.catch_up && last_run.is_some();
+    Ok((on_time || catch_up).then_some(occurrence))
+}
+
+/// 到期条目的下标，按计划时间点排序（时间规则无效的条目不会到期）
+pub fn due_entries(
+    schedule: &Schedule,
+    now: OffsetDateTime,
+    zone: &dyn LocalZone,
+) -> Vec<(usize, OffsetDateTime)> {
+    let mut due: Vec<_> = schedule
+        .entries
+        .iter()
+        .enumerate()
+        .filter_map(|(index, entry)| {
+            due_occurrence(entry, now, zone)
+                .ok()
+                .flatten()
+                .map(|occurrence| (index, occurrence))
+        })
+        .collect();
+    due.sort_by_key(|(_, occurrence)| *occurrence);
+    due
+}
+
+/// 计划任务的执行方式
+///
+/// 生产环境使用 `WinsecExecutor`（标准批量入口），测试使用 `BackendExecutor` 与内存后端
+pub trait ScheduleExecutor {
+    /// 上锁（级别、模式、来源与批次取自 `opts`）
+    fn lock(&self, targets: &[PathBuf], opts: &LockOptions) -> Result<BatchResult>;
+    /// 解锁（来源、备注与批次取自 `opts`）
+    fn unlock(&self, targets: &[PathBuf], opts: &LockOptions) -> Result<BatchResult>;
+    /// 验证标签为 `opts.desired_level`，漂移的对象写入 `DRIFT_STATUS` 记录并计为失败
+    fn verify(&self, targets: &[PathBuf], opts: &LockOptions) -> Result<BatchResult>;
+}
+
+/// 逐个对象调用标签后端执行（不检查特权）
+pub struct BackendExecutor<'a> {
+    backend: &'a dyn LabelBackend,
+    logger: &'a OperationLog,
+    user_sid: &'a str,
+    effective_level: LabelLevel,
+}
+
+impl<'a> BackendExecutor<'a> {
+    pub fn new(
+        backend: &'a dyn LabelBackend,
+        logger: &'a OperationLog,
+        user_sid: &'a str,
+        effective_level: LabelLevel,
+    ) -> Self {
+        Self {
+            backend,
+            logger,
+            user_sid,
+            effective_level,
+        }
+    }
+
+    fn context(&self, path: &Path, opts: &LockOptions) -> OperationContext<'a> {
+        OperationContext::new(path, self.user_sid, self.logger)
+            .with_comment(opts.comment.as_deref())
+            .with_batch_id(opts.batch_id.as_deref())
+            .with_origin(opts.origin)
+    }
+
+    fn batch<O: Into<LockOutcome>>(
+        &self,
+        targets: &[PathBuf],
+        mut process: impl FnMut(&Path) -> Result<O>,
+    ) -> BatchResult {
+        let mut result = BatchResult {
+            total_count: targets.len(),
+            ..Default::default()
+        };
+        for path in targets {
+            let outcome = process(path).map(Into::into);
+            accumulate_outcome(&mut result, path, &outcome);
+        }
+        result
+    }
+}
+
+impl ScheduleExecutor for BackendExecutor<'_> {
+    fn lock(&self, targets: &[PathBuf], opts: &LockOptions) -> Result<BatchResult> {
+        Ok(self.batch(targets, |path| {
+            pathutil::ensure_not_reserved(path)?;
+            let ctx = self.context(path, opts);
+            readonly::ensure_writable(&ctx, opts.mode, self.effective_level)?;
+            volume::lock_with_policy(self.backend, ctx, opts, self.effective_level, |_| Ok(()))
+        }))
+    }
+
+    fn unlock(&self, targets: &[PathBuf], opts: &LockOptions) -> Result<BatchResult> {
+        Ok(self.batch(targets, |path| {
+            pathutil::ensure_not_reserved(path)?;
+            let ctx = self.context(path, opts);
+            readonly::ensure_writable(&ctx, ProtectMode::ReadOnly, LabelLevel::Medium)?;
+            let change = unlock_object(self.backend, &ctx.target);
+            ctx.log_unlock(change.before.map(|s| s.sddl), change.result)
+        }))
+    }
+
+    fn verify(&self, targets: &[PathBuf], opts: &LockOptions) -> Result<BatchResult> {
+        let mut drifted = Vec::new();
+        let mut result = self.batch(targets, |path| {
+            let ctx = self.context(path, opts);
+            let label = self.backend.get_label(&ctx.target)?;
+            if label.level != opts.desired_level {
+                let details = format!("expected {:?}, found {:?}", opts.desired_level, label.level);
+                ctx.with_details(Some(&details)).log_and_track(
+                    opts.mode,
+                    label.level,
+                    Some(label.sddl),
+                    None,
+                    DRIFT_STATUS,
+                    vec![],
+                );
+                drifted.push((path.to_path_buf(), details));
+            }
+            Ok(LockResult::Success)
+        });
+        // 漂移的对象计为失败，便于摘要与告警
+        for (path, details) in drifted {
+            result.success_count -= 1;
+            result.failed_count += 1;
+            if result.failed_paths.len() < MAX_FAILED_PATHS {
+                result.failed_paths.push(path.to_string_lossy().to_string());
+            }
+            if result.failures.len() < result.failure_cap {
+                result.failures.push(BatchFailure {
+                    path: path.to_string_lossy().to_string(),
+                    error: format!("标签漂移（{}）", details),
+                });
+            } else {
+                result.failures_omitted += 1;
+            }
+        }
+        Ok(result)
+    }
+}
+
+/// 通过标准批量入口执行（含特权检查、冲突登记与节流）
+#[cfg(feature = "winsec")]
+pub struct WinsecExecutor<'a> {
+    pub logger: &'a OperationLog,
+    pub user_sid: &'a str,
+    pub effective_level: LabelLevel,
+}
+
+#[cfg(feature = "winsec")]
+impl ScheduleExecutor for WinsecExecutor<'_> {
+    fn lock(&self, targets: &[PathBuf], opts: &LockOptions) -> Result<BatchResult> {
+        crate::ops::batch_process_lock(
+            targets,
+            opts,
+            self.effective_level,
+            self.user_sid,
+            self.logger,
+        )
+    }
+
+    fn unlock(&self, targets: &[PathBuf], opts: &LockOptions) -> Result<BatchResult> {
+        crate::ops::batch_process_unlock(
+            targets,
+            self.user_sid,
+            self.logger,
+            opts.comment.as_deref(),
+            opts.origin,
+        )
+    }
+
+    fn verify(&self, targets: &[PathBuf], opts: &LockOptions) -> Result<BatchResult> {
+        BackendExecutor::new(
+            &crate::backend::WinsecBackend,
+            self.logger,
+            self.user_sid,
+            self.effective_level,
+        )
+        .verify(targets, opts)
+    }
+}
+
+/// 单个条目的执行结果
+#[derive(Debug, Clone)]
+pub enum ScheduleOutcome {
+    /// 已执行
+    Ran(Box<BatchResult>),
+    /// 已跳过（原因）
+    Skipped(String),
+    /// 执行失败（错误描述）
+    Failed(String),
+}
+
+/// 一次到期执行的记录
+#[derive(Debug, Clone)]
+pub struct ScheduleRun {
+    pub path: String,
+    pub action: ScheduleAction,
+    /// 对应的计划时间点（UTC）
+    pub occurrence: OffsetDateTime,
+    pub outcome: ScheduleOutcome,
+}
+
+impl Display for ScheduleRun {
+    fn fmt(&self, f: &mut Formatter<'_>) -> std::fmt::Result {
+        let action = match self.action {
+            ScheduleAction::Lock => "上锁",
+            ScheduleAction::Unlock => "解锁",
+            ScheduleAction::Verify => "验证",
+        };
+        match &self.outcome {
+            ScheduleOutcome::Ran(result) => {
+                write!(f, "计划{} {}：{}", action, self.path, result)
+            }
+            ScheduleOutcome::Skipped(reason) => {
+                write!(f, "计划{} {} 已跳过：{}", action, self.path, reason)
+            }
+            ScheduleOutcome::Failed(error) => {
+                write!(f, "计划{} {} 失败：{}", action, self.path, error)
+            }
+        }
+    }
+}
+
+/// 执行所有到期条目并更新其执行时间
+///
+/// # 参数
+/// - `executor`: 执行方式
+/// - `schedule`: 计划任务（执行后更新 `last_run_utc`，调用方负责保存设置）
+/// - `now`: 当前时刻
+/// - `zone`: 本地时区
+/// - `logger`: 日志记录器（写入跳过记录）
+/// - `user_sid`: 用户 SID
+///
+/// # 注意
+/// - 按计划时间点先后执行，错过的上锁与解锁按原本的顺序补执行
+/// - 跳过与失败同样更新执行时间，避免每次检查都重试
+/// - 所有记录的来源均为 `OperationOrigin::Scheduler`
+pub fn run_due(
+    executor: &dyn ScheduleExecutor,
+    schedule: &mut Schedule,
+    now: OffsetDateTime,
+    zone: &dyn LocalZone,
+    logger: &OperationLog,
+    user_sid: &str,
+) -> Vec<ScheduleRun> {
+    let ran_at = now
+        .to_offset(UtcOffset::UTC)
+        .format(&Rfc3339)
+        .expect("UTC 时间总能格式化为 RFC 3339");
+    let mut runs = Vec::new();
+    for (index, occurrence) in due_entries(schedule, now, zone) {
+        let entry = &mut schedule.entries[index];
+        let opts = LockOptions {
+            desired_level: entry.level,
+            mode: entry.mode,
+            comment: Some(format!("计划任务 {}", entry.at)),
+            origin: OperationOrigin::Scheduler,
+            ..LockOptions::default()
+        }
+        .in_batch(logger);
+        let outcome = run_entry(executor, entry, &opts, logger, user_sid);
+        entry.last_run_utc = Some(ran_at.clone());
+        runs.push(ScheduleRun {
+            path: entry.path.clone(),
+            action: entry.action,
+            occurrence,
+            outcome,
+        });
+    }
+    runs
+}
+
+fn run_entry(
+    executor: &dyn ScheduleExecutor,
+    entry: &ScheduleEntry,
+    opts: &LockOptions,
+    logger: &OperationLog,
+    user_sid: &str,
+) -> ScheduleOutcome {
+    if entry.action == ScheduleAction::Unlock && !entry.allow_unattended_unlock {
+        let reason = "解锁需要保险库密码，计划任务无法交互输入（可为该条目允许无人值守解锁）";
+        OperationContext::new(Path::new(&entry.path), user_sid, logger)
+            .with_comment(opts.comment.as_deref())
+            .with_batch_id(opts.batch_id.as_deref())
+            .with_origin(opts.origin)
+            .log_and_track(
+                ProtectMode::ReadOnly,
+                LabelLevel::Medium,
+                None,
+                None,
+                SCHEDULE_SKIPPED_STATUS,
+                vec![reason.to_string()],
+            );
+        return ScheduleOutcome::Skipped(reason.to_string());
+    }
+
+    let result = schedule_targets(entry).and_then(|targets| match entry.action {
+        ScheduleAction::Lock => executor.lock(&targets, opts),
+        ScheduleAction::Unlock => executor.unlock(&targets, opts),
+        ScheduleAction::Verify => executor.verify(&targets, opts),
+    });
+    match result {
+        Ok(result) => ScheduleOutcome::Ran(Box::new(result)),
+        Err(e) => ScheduleOutcome::Failed(e.to_string()),
+    }
+}
+
+/// 条目的操作对象：路径本身，递归时加上文件夹内的全部对象
+fn schedule_targets(entry: &ScheduleEntry) -> Result<Vec<PathBuf>> {
+    let root = PathBuf::from(&entry.path);
+    let mut targets = vec![root.clone()];
+    if entry.recursive && root.is_dir() {
+        let opts = ExpandOptions {
+            base_dir: Some(root),
+            max_matches: PRESET_MAX_MATCHES,
+            ..ExpandOptions::default()
+        };
+        targets.extend(expand_glob(&["**".to_string()], &opts)?);
+    }
+    Ok(targets)
+}
+
+/// 以系统时区执行当前到期的条目（GUI 后台线程与 `schedule run-due` 共用）
+#[cfg(feature = "winsec")]
+pub fn run_due_now(
+    schedule: &mut Schedule,
+    logger: &OperationLog,
+    user_sid: &str,
+    effective_level: LabelLevel,
+) -> Vec<ScheduleRun> {
+    let executor = WinsecExecutor {
+        logger,
+        user_sid,
+        effective_level,
+    };
+    run_due(
+        &executor,
+        schedule,
+        OffsetDateTime::now_utc(),
+        &SystemZone,
+        logger,
+        user_sid,
+    )
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::backend::FakeBackend;
+    use amberlock_storage::NdjsonReader;
+    use amberlock_types::LockRecord;
+    use tempfile::TempDir;
+
+    /// 欧洲中部时区（2025 年）：3 月 30 日 01:00 UTC 进入夏令时，10 月 26 日 01:00 UTC 结束
+    struct CentralEurope;
+
+    impl LocalZone for CentralEurope {
+        fn offset_at(&self, utc: OffsetDateTime) -> UtcOffset {
+            let hours = if utc >= at("2025-03-30T01:00:00Z") && utc < at("2025-10-26T01:00:00Z") {
+                2
+            } else {
+                1
+            };
+            UtcOffset::from_hms(hours, 0, 0).unwrap()
+        }
+    }
+
+    fn at(utc: &str) -> OffsetDateTime {
+        parse_utc(utc).expect("测试时间格式无效")
+    }
+
+    fn date(utc: &str) -> Date {
+        at(&format!("{}T12:00:00Z", utc)).date()
+    }
+
+    fn entry(action: ScheduleAction, at: &str) -> ScheduleEntry {
+        ScheduleEntry {
+            path: r"D:\Finance".to_string(),
+            action,
+            at: at.to_string(),
+            recursive: false,
+            mode: ProtectMode::ReadOnly,
+            level: LabelLevel::High,
+            catch_up: false,
+            allow_unattended_unlock: false,
+            last_run_utc: None,
+        }
+    }
+
+    #[test]
+    fn test_time_spec_parsing() {
+        let daily = TimeSpec::parse("19:00").expect("应能解析");
+        let weekdays = TimeSpec::parse("Mon-Fri 08:00").expect("应能解析");
+        let wrap = TimeSpec::parse("fri-mon 22:30").expect("应能解析");
+        let list = TimeSpec::parse("sat,sun 9:05").expect("应能解析");
+
+        let monday = date("2025-06-02");
+        let saturday = date("2025-06-07");
+        let wednesday = date("2025-06-04");
+        assert!(daily.matches(wednesday));
+        assert!(weekdays.matches(monday) && !weekdays.matches(saturday));
+        assert!(wrap.matches(saturday) && wrap.matches(monday) && !wrap.matches(wednesday));
+        assert!(list.matches(saturday) && !list.matches(monday));
+        assert_eq!(list.time, Time::from_hms(9, 5, 0).unwrap());
+
+        for invalid in [
+            "",
+            "25:00",
+            "08:60",
+            "0800",
+            "someday 08:00",
+            "mon 08:00 extra",
+        ] {
+            assert!(
+                matches!(
+                    TimeSpec::parse(invalid),
+                    Err(AmberlockError::InvalidSchedule(_))
+                ),
+                "应拒绝 {:?}",
+                invalid
+            );
+        }
+
+        println!("✅ 时间规则解析测试通过");
+    }
+
+    #[test]
+    fn test_due_computation_across_dst() {
+        let zone = CentralEurope;
+        let spec = TimeSpec::parse("02:30").expect("应能解析");
+
+        // 冬令时：02:30 CET = 01:30 UTC
+        assert_eq!(
+            spec.occurrence_on(date("2025-03-29"), &zone),
+            Some(at("2025-03-29T01:30:00Z"))
+        );
+        // 夏令时开始当天 02:30 不存在，顺延到 03:30 CEST
+        assert_eq!(
+            spec.occurrence_on(date("2025-03-30"), &zone),
+            Some(at("2025-03-30T01:30:00Z"))
+        );
+        // 夏令时结束当天 02:30 出现两次，只取第一次（CEST）
+        let fall_back = at("2025-10-26T00:30:00Z");
+        assert_eq!(
+            spec.occurrence_on(date("2025-10-26"), &zone),
+            Some(fall_back)
+        );
+
+        // 第一次执行后，重复出现的 02:30 CET 不再到期
+        let mut task = entry(ScheduleAction::Lock, "02:30");
+        assert_eq!(
+            due_occurrence(&task, fall_back + Duration::minutes(1), &zone).unwrap(),
+            Some(fall_back)
+        );
+        task.last_run_utc = Some("2025-10-26T00:31:00Z".to_string());
+        assert_eq!(
+            due_occurrence(&task, at("2025-10-26T01:31:00Z"), &zone).unwrap(),
+            None
+        );
+
+        // 夏令时期间 19:00 本地 = 17:00 UTC
+        let evening = entry(ScheduleAction::Lock, "19:00");
+        assert_eq!(
+            due_occurrence(&evening, at("2025-07-01T17:02:00Z"), &zone).unwrap(),
+            Some(at("2025-07-01T17:00:00Z"))
+        );
+        assert_eq!(
+            due_occurrence(&evening, at("2025-07-01T16:59:00Z"), &zone).unwrap(),
+            None,
+            "时间点之前不应到期"
+        );
+
+        println!("✅ 夏令时到期计算测试通过");
+    }
+
+    #[test]
+    fn test_catch_up_runs_missed_window_once() {
+        let zone = FixedZone(UtcOffset::UTC);
+        let mut task = entry(ScheduleAction::Lock, "mon-fri 19:00");
+        task.last_run_utc = Some("2025-06-02T19:00:30Z".to_string());
+
+        // 周三 09:00 开机：周一之后错过了周二的 19:00
+        let startup = at("2025-06-04T09:00:00Z");
+        assert_eq!(
+            due_occurrence(&task, startup, &zone).unwrap(),
+            None,
+            "未设置补执行"
+        );
+
+        task.catch_up = true;
+        assert_eq!(
+            due_occurrence(&task, startup, &zone).unwrap(),
+            Some(at("2025-06-03T19:00:00Z")),
+            "多次错过只补执行最近一次"
+        );
+        task.last_run_utc = Some("2025-06-04T09:00:00Z".to_string());
+        assert_eq!(
+            due_occurrence(&task, startup + Duration::minutes(1), &zone).unwrap(),
+            None
+        );
+
+        // 从未执行过的条目不补执行添加之前的时间点
+        let mut fresh = entry(ScheduleAction::Lock, "19:00");
+        fresh.catch_up = true;
+        assert_eq!(due_occurrence(&fresh, startup, &zone).unwrap(), None);
+
+        // 周末不适用：周一早上补执行的是上周五
+        task.last_run_utc = Some("2025-06-05T19:01:00Z".to_string());
+        assert_eq!(
+            due_occurrence(&task, at("2025-06-09T07:00:00Z"), &zone).unwrap(),
+            Some(at("2025-06-06T19:00:00Z"))
+        );
+
+        println!("✅ 补执行测试通过");
+    }
+
+    #[test]
+    fn test_run_due_executes_in_order_and_skips_unattended_unlock() {
+        let temp_dir = TempDir::new().expect("创建临时目录失败");
+        let log_path = temp_dir.path().join("log.ndjson");
+        let logger = OperationLog::open(&log_path).expect("打开日志失败");
+        let backend = FakeBackend::new().with_label(r"D:\Reports", LabelLevel::Medium);
+        let executor = BackendExecutor::new(&backend, &logger, "S-1-5-21-test", LabelLevel::High);
+        let zone = FixedZone(UtcOffset::UTC);
+
+        let mut lock = entry(ScheduleAction::Lock, "19:00");
+        lock.last_run_utc = Some("2025-06-02T19:00:00Z".to_string());
+        lock.catch_up = true;
+        let mut unlock = entry(ScheduleAction::Unlock, "08:00");
+        unlock.last_run_utc = Some("2025-06-03T08:00:00Z".to_string());
+        unlock.catch_up = true;
+        let mut verify = entry(ScheduleAction::Verify, "08:05");
+        verify.path = r"D:\Reports".to_string();
+        let mut schedule = Schedule {
+            entries: vec![unlock, lock, verify],
+        };
+
+        let now = at("2025-06-04T08:06:00Z");
+        let runs = run_due(
+            &executor,
+            &mut schedule,
+            now,
+            &zone,
+            &logger,
+            "S-1-5-21-test",
+        );
+        logger.flush().unwrap();
+
+        // 先补执行昨晚的上锁，再处理今早的解锁与验证
+        let order: Vec<_> = runs.iter().map(|run| run.action).collect();
+        assert_eq!(
+            order,
+            vec![
+                ScheduleAction::Lock,
+                ScheduleAction::Unlock,
+                ScheduleAction::Verify
+            ]
+        );
+        assert!(matches!(&runs[0].outcome, ScheduleOutcome::Ran(r) if r.success_count == 1));
+        assert!(
+            matches!(runs[1].outcome, ScheduleOutcome::Skipped(_)),
+            "未允许无人值守解锁"
+        );
+        assert!(matches!(&runs[2].outcome, ScheduleOutcome::Ran(r) if r.failed_count == 1));
+        assert_eq!(
+            backend.label_of(r"D:\Finance"),
+            Some(LabelLevel::High),
+            "解锁应被跳过"
+        );
+        assert!(
+            schedule
+                .entries
+                .iter()
+                .all(|e| e.last_run_utc.as_deref() == Some("2025-06-04T08:06:00Z"))
+        );
+
+        let records: Vec<LockRecord> = NdjsonReader::open(&log_path)
+            .unwrap()
+            .read_last_n(usize::MAX)
+            .unwrap()
+            .into_iter()
+            .map(|v| serde_json::from_value(v).unwrap())
+            .collect();
+        let statuses: Vec<_> = records.iter().map(|r| r.status.as_str()).collect();
+        assert_eq!(
+            statuses,
+            vec!["success", SCHEDULE_SKIPPED_STATUS, DRIFT_STATUS]
+        );
+        assert!(
+            records
+                .iter()
+                .all(|r| r.origin == OperationOrigin::Scheduler)
+        );
+
+        // 明确允许后按时解锁；已执行的条目不再到期
+        schedule.entries[0].allow_unattended_unlock = true;
+        schedule.entries[0].last_run_utc = Some("2025-06-04T07:00:00Z".to_string());
+        let runs = run_due(
+            &executor,
+            &mut schedule,
+            now,
+            &zone,
+            &logger,
+            "S-1-5-21-test",
+        );
+        assert_eq!(runs.len(), 1);
+        assert!(matches!(&runs[0].outcome, ScheduleOutcome::Ran(r) if r.success_count == 1));
+        assert_eq!(backend.label_of(r"D:\Finance"), None);
+
+        println!("✅ 计划任务执行测试通过");
+    }
+}
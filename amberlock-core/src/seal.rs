@@ -0,0 +1,698 @@
+//! 封印模式变更预览
+//!
+//! 封印比只读更危险：安全描述符写错可能连所有者自己都无法访问。本模块在写入前
+//! 计算封印将要写入的完整安全描述符，并与当前描述符逐条比较 ACE：
+//! - `preview_seal` 读取当前描述符，生成 `SealPlan`（结构化 ACE 差异与写入后的 SDDL），
+//!   不修改对象
+//! - 写入时（`apply_seal` 或批量上锁时的 `LockOptions::seal_plans`）重新读取描述符，
+//!   与预览时的哈希不一致即返回 `PlanStale`，保证写入的正是用户确认过的内容
+//!
+//! 当前封印只写入强制标签（`NW` 策略），DACL 保持不变；解析与差异计算覆盖 DACL，
+//! 封印开始修改 DACL 后预览无需调整。
+
+use crate::backend::LabelBackend;
+use crate::pathutil;
+use crate::readonly;
+use crate::volume::lock_with_policy;
+use crate::{LockOptions, LockOutcome, OperationContext};
+use amberlock_storage::OperationLog;
+use amberlock_types::{AmberlockError, LabelLevel, OperationOrigin, ProtectMode, Result};
+use std::fmt::{Display, Formatter};
+use std::path::Path;
+
+/// 会影响当前用户的广泛主体（Everyone、Authenticated Users、Users、Interactive）
+const BROAD_TRUSTEES: [&str; 4] = ["WD", "AU", "BU", "IU"];
+
+/// 一条 ACE（SDDL 形式的各字段）
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Ace {
+    /// 类型（如 `A` 允许、`D` 拒绝、`ML` 强制标签）
+    pub ace_type: String,
+    /// 标志（如 `OICI`）
+    pub flags: String,
+    /// 访问权限（如 `FA`、`0x1200a9`、`NW`）
+    pub rights: String,
+    /// 对象类型 GUID
+    pub object_guid: String,
+    /// 继承对象类型 GUID
+    pub inherit_guid: String,
+    /// 主体（SID 或别名，如 `BA`）
+    pub trustee: String,
+    /// 条件表达式或资源属性等其余字段（原样保留）
+    pub extra: Option<String>,
+}
+
+impl Ace {
+    /// 解析括号内的 ACE 字符串（不含括号）
+    pub fn parse(text: &str) -> Result<Self> {
+        let mut fields = text.splitn(7, ';');
+        let mut next = || fields.next().map(str::to_string);
+        let (Some(ace_type), Some(flags), Some(rights), Some(object_guid), Some(inherit_guid)) =
+            (next(), next(), next(), next(), next())
+        else {
+            return Err(AmberlockError::InvalidLabel);
+        };
+        let Some(trustee) = next() else {
+            return Err(AmberlockError::InvalidLabel);
+        };
+        Ok(Self {
+            ace_type,
+            flags,
+            rights,
+            object_guid,
+            inherit_guid,
+            trustee,
+            extra: next(),
+        })
+    }
+
+    /// 渲染为 SDDL（含括号）
+    pub fn sddl(&self) -> String {
+        let mut text = format!(
+            "({};{};{};{};{};{}",
+            self.ace_type,
+            self.flags,
+            self.rights,
+            self.object_guid,
+            self.inherit_guid,
+            self.trustee
+        );
+        if let Some(extra) = &self.extra {
+            text.push(';');
+            text.push_str(extra);
+        }
+        text.push(')');
+        text
+    }
+
+    /// 是否为拒绝 ACE
+    pub fn is_deny(&self) -> bool {
+        matches!(self.ace_type.as_str(), "D" | "OD" | "XD")
+    }
+
+    /// 比较键：类型、主体与对象类型相同的 ACE 视为同一条目
+    ///
+    /// 强制标签只有一条，级别（主体）变化视为修改
+    fn key(&self) -> (&str, &str, &str, &str) {
+        let trustee = if self.ace_type == "ML" {
+            ""
+        } else {
+            &self.trustee
+        };
+        (
+            &self.ace_type,
+            trustee,
+            &self.object_guid,
+            &self.inherit_guid,
+        )
+    }
+}
+
+impl Display for Ace {
+    fn fmt(&self, f: &mut Formatter<'_>) -> std::fmt::Result {
+        let kind = match self.ace_type.as_str() {
+            "A" | "OA" | "XA" => "允许",
+            "D" | "OD" | "XD" => "拒绝",
+            "ML" => "强制标签",
+            "AU" | "OU" => "审核",
+            other => other,
+        };
+        write!(
+            f,
+            "{} {}：{}",
+            kind,
+            trustee_name(&self.trustee),
+            self.rights
+        )?;
+        if !self.flags.is_empty() {
+            write!(f, "（{}）", self.flags)?;
+        }
+        Ok(())
+    }
+}
+
+/// 常见 SID 别名的可读名称
+fn trustee_name(trustee: &str) -> &str {
+    match trustee {
+        "WD" => "Everyone",
+        "AU" => "Authenticated Users",
+        "BU" => "Users",
+        "BA" => "Administrators",
+        "SY" => "SYSTEM",
+        "IU" => "Interactive",
+        "CO" => "创建者所有者",
+        "OW" => "所有者权限",
+        "ME" => "Medium 完整性",
+        "HI" => "High 完整性",
+        "SI" => "System 完整性",
+        other => other,
+    }
+}
+
+/// 一个 ACL（`D:` 或 `S:` 段）
+#[derive(Debug, Clone, Default, PartialEq, Eq)]
+pub struct Acl {
+    /// 控制标志（如 `PAI`）
+    pub flags: String,
+    /// 按顺序排列的 ACE
+    pub aces: Vec<Ace>,
+}
+
+impl Acl {
+    fn parse(text: &str) -> Result<Self> {
+        let start = text.find('(').unwrap_or(text.len());
+        let mut acl = Acl {
+            flags: text[..start].to_string(),
+            aces: Vec::new(),
+        };
+        let mut depth = 0usize;
+        let mut begin = start;
+        for (i, c) in text.char_indices().skip_while(|(i, _)| *i < start) {
+            match c {
+                '(' => {
+                    if depth == 0 {
+                        begin = i + 1;
+                    }
+                    depth += 1;
+                }
+                ')' => {
+                    depth = depth.checked_sub(1).ok_or(AmberlockError::InvalidLabel)?;
+                    if depth == 0 {
+                        acl.aces.push(Ace::parse(&text[begin..i])?);
+                    }
+                }
+                _ if depth == 0 => return Err(AmberlockError::InvalidLabel),
+                _ => {}
+            }
+        }
+        if depth != 0 {
+            return Err(AmberlockError::InvalidLabel);
+        }
+        Ok(acl)
+    }
+
+    fn sddl(&self) -> String {
+        let mut text = self.flags.clone();
+        for ace in &self.aces {
+            text.push_str(&ace.sddl());
+        }
+        text
+    }
+}
+
+/// 解析后的安全描述符
+#[derive(Debug, Clone, Default, PartialEq, Eq)]
+pub struct Descriptor {
+    /// 所有者
+    pub owner: Option<String>,
+    /// 主要组
+    pub group: Option<String>,
+    /// DACL（描述符不含 `D:` 段时为 None）
+    pub dacl: Option<Acl>,
+    /// SACL（含强制标签；描述符不含 `S:` 段时为 None）
+    pub sacl: Option<Acl>,
+}
+
+impl Descriptor {
+    /// 解析 SDDL 字符串
+    ///
+    /// # 返回
+    /// 结构化的描述符；括号不匹配或 ACE 字段不足时返回 `InvalidLabel`
+    pub fn parse(sddl: &str) -> Result<Self> {
+        // 段标记为括号外的 `O:`、`G:`、`D:`、`S:`（SID 中不含冒号）
+        let mut marks = Vec::new();
+        let mut depth = 0usize;
+        let bytes = sddl.as_bytes();
+        for (i, &b) in bytes.iter().enumerate() {
+            match b {
+                b'(' => depth += 1,
+                b')' => depth = depth.saturating_sub(1),
+                b':' if depth == 0 && i > 0 && b"OGDS".contains(&bytes[i - 1]) => marks.push(i - 1),
+                _ => {}
+            }
+        }
+
+        let mut descriptor = Descriptor::default();
+        for (n, &mark) in marks.iter().enumerate() {
+            let end = marks.get(n + 1).copied().unwrap_or(sddl.len());
+            let body = &sddl[mark + 2..end];
+            match bytes[mark] {
+                b'O' => descriptor.owner = Some(body.to_string()),
+                b'G' => descriptor.group = Some(body.to_string()),
+                b'D' => descriptor.dacl = Some(Acl::parse(body)?),
+                _ => descriptor.sacl = Some(Acl::parse(body)?),
+            }
+        }
+        if !sddl.is_empty() && marks.first() != Some(&0) {
+            return Err(AmberlockError::InvalidLabel);
+        }
+        Ok(descriptor)
+    }
+
+    /// 渲染为 SDDL 字符串
+    pub fn sddl(&self) -> String {
+        let mut text = String::new();
+        if let Some(owner) = &self.owner {
+            text.push_str(&format!("O:{}", owner));
+        }
+        if let Some(group) = &self.group {
+            text.push_str(&format!("G:{}", group));
+        }
+        if let Some(dacl) = &self.dacl {
+            text.push_str(&format!("D:{}", dacl.sddl()));
+        }
+        if let Some(sacl) = &self.sacl {
+            text.push_str(&format!("S:{}", sacl.sddl()));
+        }
+        text
+    }
+}
+
+/// 单条 ACE 的变化
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum AceChange {
+    /// 新增
+    Added(Ace),
+    /// 移除
+    Removed(Ace),
+    /// 同一条目的权限或标志改变
+    Modified {
+        /// 修改前
+        before: Ace,
+        /// 修改后
+        after: Ace,
+    },
+}
+
+impl AceChange {
+    /// 变化后生效的 ACE（移除时为 None）
+    pub fn resulting(&self) -> Option<&Ace> {
+        match self {
+            AceChange::Added(ace) | AceChange::Modified { after: ace, .. } => Some(ace),
+            AceChange::Removed(_) => None,
+        }
+    }
+
+    /// 是否为影响指定用户的拒绝 ACE（新增或修改后）
+    ///
+    /// # 参数
+    /// - `user_sids`: 当前用户及其所属组的 SID；Everyone 等广泛主体总是计入
+    pub fn is_dangerous(&self, user_sids: &[&str]) -> bool {
+        self.resulting().is_some_and(|ace| {
+            ace.is_deny()
+                && (BROAD_TRUSTEES.contains(&ace.trustee.as_str())
+                    || user_sids.contains(&ace.trustee.as_str()))
+        })
+    }
+}
+
+impl Display for AceChange {
+    fn fmt(&self, f: &mut Formatter<'_>) -> std::fmt::Result {
+        match self {
+            AceChange::Added(ace) => write!(f, "+ {}", ace),
+            AceChange::Removed(ace) => write!(f, "- {}", ace),
+            AceChange::Modified { before, after } => write!(f, "~ {} → {}", before, after),
+        }
+    }
+}
+
+/// 比较两组 ACE
+///
+/// # 返回
+/// 按 `after` 顺序排列的新增与修改，随后是移除；类型、主体与对象类型相同的 ACE
+/// 按出现顺序一一配对
+pub fn diff_aces(before: &[Ace], after: &[Ace]) -> Vec<AceChange> {
+    let mut unmatched: Vec<Option<&Ace>> = before.iter().map(Some).collect();
+    let mut changes = Vec::new();
+    for ace in after {
+        let pair = unmatched
+            .iter_mut()
+            .find(|slot| slot.is_some_and(|old| old.key() == ace.key()))
+            .and_then(Option::take);
+        match pair {
+            Some(old) if old == ace => {}
+            Some(old) => changes.push(AceChange::Modified {
+                before: old.clone(),
+                after: ace.clone(),
+            }),
+            None => changes.push(AceChange::Added(ace.clone())),
+        }
+    }
+    changes.extend(
+        unmatched
+            .into_iter()
+            .flatten()
+            .cloned()
+            .map(AceChange::Removed),
+    );
+    changes
+}
+
+/// 封印计划（预览结果）
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct SealPlan {
+    /// 对象路径
+    pub path: String,
+    /// 封印写入的完整性级别
+    pub level: LabelLevel,
+    /// 预览时的安全描述符
+    pub original_sddl: String,
+    /// 预览时安全描述符的哈希（写入前据此检查是否被修改）
+    pub original_hash: u64,
+    /// 写入后的安全描述符
+    pub planned_sddl: String,
+    /// DACL 的变化
+    pub dacl: Vec<AceChange>,
+    /// SACL（强制标签）的变化
+    pub sacl: Vec<AceChange>,
+}
+
+impl SealPlan {
+    /// 全部变化中影响当前用户的拒绝 ACE
+    pub fn dangerous(&self, user_sids: &[&str]) -> Vec<&AceChange> {
+        self.dacl
+            .iter()
+            .chain(&self.sacl)
+            .filter(|change| change.is_dangerous(user_sids))
+            .collect()
+    }
+
+    /// 渲染为多行文本（用于确认对话框，危险条目以 ⚠️ 标出）
+    pub fn render_lines(&self, user_sids: &[&str]) -> Vec<String> {
+        let mut lines = vec![format!("{}（{:?}）", self.path, self.level)];
+        for (name, changes) in [("DACL", &self.dacl), ("SACL", &self.sacl)] {
+            if changes.is_empty() {
+                lines.push(format!("  {}: 不变", name));
+                continue;
+            }
+            lines.push(format!("  {}:", name));
+            for change in changes {
+                let marker = if change.is_dangerous(user_sids) {
+                    "⚠️ "
+                } else {
+                    ""
+                };
+                lines.push(format!("    {}{}", marker, change));
+            }
+        }
+        lines
+    }
+}
+
+/// 强制标签 ACE 的级别标记
+fn level_token(level: LabelLevel) -> &'static str {
+    match level {
+        LabelLevel::Medium => "ME",
+        LabelLevel::High => "HI",
+        LabelLevel::System => "SI",
+    }
+}
+
+/// 根据当前描述符计算封印计划（纯函数）
+///
+/// # 参数
+/// - `path`: 对象路径
+/// - `original_sddl`: 当前的安全描述符
+/// - `level`: 封印写入的完整性级别
+///
+/// # 返回
+/// 封印计划；SACL 中原有的强制标签被替换为 `(ML;;NW;;;级别)`，其他 ACE 与 DACL 不变
+pub fn plan_seal(path: &str, original_sddl: &str, level: LabelLevel) -> Result<SealPlan> {
+    let original = Descriptor::parse(original_sddl)?;
+
+    let mut planned = original.clone();
+    let sacl = planned.sacl.get_or_insert_with(Acl::default);
+    sacl.aces.retain(|ace| ace.ace_type != "ML");
+    sacl.aces.push(Ace {
+        ace_type: "ML".to_string(),
+        flags: String::new(),
+        rights: "NW".to_string(),
+        object_guid: String::new(),
+        inherit_guid: String::new(),
+        trustee: level_token(level).to_string(),
+        extra: None,
+    });
+
+    let aces = |acl: &Option<Acl>| acl.as_ref().map(|a| a.aces.clone()).unwrap_or_default();
+    Ok(SealPlan {
+        path: path.to_string(),
+        level,
+        original_sddl: original_sddl.to_string(),
+        original_hash: descriptor_hash(original_sddl),
+        planned_sddl: planned.sddl(),
+        dacl: diff_aces(&aces(&original.dacl), &aces(&planned.dacl)),
+        sacl: diff_aces(&aces(&original.sacl), &aces(&planned.sacl)),
+    })
+}
+
+/// 安全描述符的哈希（FNV-1a）
+fn descriptor_hash(sddl: &str) -> u64 {
+    sddl.bytes().fold(0xcbf2_9ce4_8422_2325, |hash, byte| {
+        (hash ^ byte as u64).wrapping_mul(0x0000_0100_0000_01b3)
+    })
+}
+
+/// 预览封印对象将写入的变更（不修改对象）
+///
+/// # 参数
+/// - `backend`: 标签后端
+/// - `path`: 对象路径
+/// - `level`: 封印写入的完整性级别
+pub fn preview_seal(backend: &dyn LabelBackend, path: &str, level: LabelLevel) -> Result<SealPlan> {
+    let target = pathutil::canonical_path(Path::new(path));
+    let sddl = backend.read_descriptor(&target.to_string_lossy())?;
+    plan_seal(path, &sddl, level)
+}
+
+/// 写入前确认计划仍然有效
+///
+/// # 返回
+/// 描述符自预览后被修改，或实际写入的级别与计划不同时返回 `PlanStale`
+pub(crate) fn check_plan(
+    backend: &dyn LabelBackend,
+    plan: &SealPlan,
+    target: &str,
+    level: LabelLevel,
+) -> Result<()> {
+    let current = backend.read_descriptor(target)?;
+    if plan.level != level || descriptor_hash(&current) != plan.original_hash {
+        return Err(AmberlockError::PlanStale {
+            path: plan.path.clone(),
+        });
+    }
+    Ok(())
+}
+
+/// 按已确认的计划封印单个对象
+///
+/// # 参数
+/// - `backend`: 标签后端
+/// - `plan`: `preview_seal` 返回并经用户确认的计划
+/// - `user_sid`: 用户 SID
+/// - `logger`: 操作日志
+/// - `origin`: 发起操作的入口
+///
+/// # 返回
+/// 与批量上锁相同的结果；描述符自预览后被修改时返回 `PlanStale` 且不写入
+///
+/// # 注意
+/// - 与单个对象上锁相同，保留设备名与只读审计模式下直接拒绝（后者写入被拒绝记录）
+/// - 不做前置特权检查（特权不足时写入本身会失败）；批量上锁通过
+///   `LockOptions::seal_plans` 使用同一校验
+pub fn apply_seal(
+    backend: &dyn LabelBackend,
+    plan: &SealPlan,
+    user_sid: &str,
+    logger: &OperationLog,
+    origin: OperationOrigin,
+) -> Result<LockOutcome> {
+    let opts = LockOptions {
+        desired_level: plan.level,
+        mode: ProtectMode::Seal,
+        origin,
+        seal_plans: vec![plan.clone()],
+        ..LockOptions::default()
+    };
+    let path = Path::new(&plan.path);
+    pathutil::ensure_not_reserved(path)?;
+
+    let ctx = OperationContext::new(path, user_sid, logger).with_origin(origin);
+    readonly::ensure_writable(&ctx, opts.mode, plan.level)?;
+    lock_with_policy(backend, ctx, &opts, plan.level, |_| Ok(()))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::LockResult;
+    use crate::backend::{DEFAULT_FAKE_DACL, FakeBackend};
+    use tempfile::TempDir;
+
+    const TARGET: &str = r"C:\Data\a.txt";
+
+    #[test]
+    fn test_parse_round_trip() {
+        let sddls = [
+            "O:BAG:SYD:PAI(A;;FA;;;SY)(A;OICI;0x1200a9;;;BU)S:(ML;;NW;;;HI)",
+            "O:S-1-5-21-1-2-3-1001G:S-1-5-21-1-2-3-513D:(D;;WD;;;S-1-5-21-1-2-3-1001)(A;;FA;;;BA)",
+            "D:AI(XA;;FX;;;WD;(@User.Project Any_of {1, 2}))",
+            "S:",
+        ];
+        for sddl in sddls {
+            let descriptor = Descriptor::parse(sddl).expect(sddl);
+            assert_eq!(descriptor.sddl(), sddl);
+        }
+
+        let descriptor = Descriptor::parse(sddls[1]).unwrap();
+        assert_eq!(descriptor.owner.as_deref(), Some("S-1-5-21-1-2-3-1001"));
+        assert_eq!(
+            descriptor.dacl.unwrap().aces[0].trustee,
+            "S-1-5-21-1-2-3-1001"
+        );
+
+        for invalid in ["D:(A;;FA)", "D:(A;;FA;;;SY", "D:P(A;;FA;;;SY)x", "BA"] {
+            assert!(Descriptor::parse(invalid).is_err(), "{}", invalid);
+        }
+    }
+
+    #[test]
+    fn test_diff_aces() {
+        let ace = |text: &str| Ace::parse(text).unwrap();
+        let before = [ace("A;;FA;;;SY"), ace("A;;FA;;;BU"), ace("A;;FR;;;WD")];
+        let after = [
+            ace("A;;FA;;;SY"),
+            ace("A;;FR;;;BU"),
+            ace("D;;FW;;;S-1-5-21-9"),
+        ];
+        let changes = diff_aces(&before, &after);
+        assert_eq!(
+            changes,
+            vec![
+                AceChange::Modified {
+                    before: ace("A;;FA;;;BU"),
+                    after: ace("A;;FR;;;BU"),
+                },
+                AceChange::Added(ace("D;;FW;;;S-1-5-21-9")),
+                AceChange::Removed(ace("A;;FR;;;WD")),
+            ]
+        );
+
+        // 拒绝当前用户或广泛主体的 ACE 是危险的；允许与移除不是
+        assert!(changes[1].is_dangerous(&["S-1-5-21-9"]));
+        assert!(!changes[1].is_dangerous(&["S-1-5-21-1"]));
+        assert!(AceChange::Added(ace("D;;FA;;;WD")).is_dangerous(&[]));
+        assert!(!changes[0].is_dangerous(&["S-1-5-21-9"]));
+        assert!(!AceChange::Removed(ace("D;;FA;;;WD")).is_dangerous(&[]));
+        assert!(diff_aces(&before, &before).is_empty());
+        println!("✅ ACE 差异与危险条目判定正确");
+    }
+
+    #[test]
+    fn test_plan_replaces_label_only() {
+        let sddl = "O:BAG:SYD:PAI(A;;FA;;;SY)(D;;FW;;;WD)S:AI(AU;SA;FA;;;WD)(ML;;NWNR;;;ME)";
+        let plan = plan_seal(TARGET, sddl, LabelLevel::High).unwrap();
+        assert!(plan.dacl.is_empty(), "封印不修改 DACL");
+        assert_eq!(
+            plan.planned_sddl,
+            "O:BAG:SYD:PAI(A;;FA;;;SY)(D;;FW;;;WD)S:AI(AU;SA;FA;;;WD)(ML;;NW;;;HI)"
+        );
+        assert_eq!(plan.sacl.len(), 1);
+        assert!(
+            matches!(&plan.sacl[0], AceChange::Modified { after, .. } if after.trustee == "HI")
+        );
+        // 原有的拒绝 ACE 没有变化，不计为危险条目
+        assert!(plan.dangerous(&[]).is_empty());
+
+        let lines = plan.render_lines(&[]);
+        assert!(lines.contains(&"  DACL: 不变".to_string()));
+        assert!(lines.iter().any(|l| l.contains("强制标签 High 完整性：NW")));
+
+        // 没有 SACL 时新增强制标签
+        let plan = plan_seal(TARGET, "O:BAD:(A;;FA;;;BA)", LabelLevel::System).unwrap();
+        assert_eq!(plan.planned_sddl, "O:BAD:(A;;FA;;;BA)S:(ML;;NW;;;SI)");
+        assert!(matches!(&plan.sacl[..], [AceChange::Added(_)]));
+    }
+
+    #[test]
+    fn test_apply_rejects_stale_plan() {
+        let temp_dir = TempDir::new().expect("创建临时目录失败");
+        let logger = OperationLog::open(temp_dir.path().join("log.ndjson")).unwrap();
+        let backend = FakeBackend::new();
+
+        let plan = preview_seal(&backend, TARGET, LabelLevel::High).expect("预览失败");
+        assert!(plan.original_sddl.contains(DEFAULT_FAKE_DACL));
+        assert_eq!(backend.label_of(TARGET), None, "预览不应修改对象");
+
+        // 预览之后 DACL 被其他进程修改
+        backend.set_dacl(TARGET, "D:(D;;FA;;;WD)");
+        let outcome = apply_seal(
+            &backend,
+            &plan,
+            "S-1-5-21-test",
+            &logger,
+            OperationOrigin::GuiButton,
+        );
+        assert!(matches!(outcome, Err(AmberlockError::PlanStale { .. })));
+        assert_eq!(backend.label_of(TARGET), None, "计划过期时不应写入");
+
+        // 重新预览后写入，写入结果与计划一致
+        let plan = preview_seal(&backend, TARGET, LabelLevel::High).unwrap();
+        let outcome = apply_seal(
+            &backend,
+            &plan,
+            "S-1-5-21-test",
+            &logger,
+            OperationOrigin::GuiButton,
+        );
+        assert_eq!(outcome.unwrap().result, LockResult::Success);
+        assert_eq!(backend.read_descriptor(TARGET).unwrap(), plan.planned_sddl);
+        println!("✅ 描述符在预览后被修改时放弃封印");
+    }
+
+    /// 需要管理员权限：真实对象上的预览、并发修改与写入
+    #[cfg(feature = "winsec")]
+    #[test]
+    #[ignore]
+    fn test_stale_plan_on_real_file() {
+        use crate::backend::WinsecBackend;
+
+        let temp_dir = TempDir::new().expect("创建临时目录失败");
+        let logger = OperationLog::open(temp_dir.path().join("log.ndjson")).unwrap();
+        let file = temp_dir.path().join("sealed.txt");
+        std::fs::write(&file, b"data").unwrap();
+        let path = file.to_string_lossy().to_string();
+
+        let plan = preview_seal(&WinsecBackend, &path, LabelLevel::High).expect("预览失败");
+
+        // 另一个线程在确认期间修改了标签
+        std::thread::scope(|scope| {
+            scope.spawn(|| {
+                amberlock_winsec::set_mandatory_label(&path, LabelLevel::Medium)
+                    .expect("并发修改失败（需要管理员权限）")
+            });
+        });
+        let outcome = apply_seal(
+            &WinsecBackend,
+            &plan,
+            "S-1-5-21-test",
+            &logger,
+            OperationOrigin::GuiButton,
+        );
+        assert!(matches!(outcome, Err(AmberlockError::PlanStale { .. })));
+
+        let plan = preview_seal(&WinsecBackend, &path, LabelLevel::High).unwrap();
+        apply_seal(
+            &WinsecBackend,
+            &plan,
+            "S-1-5-21-test",
+            &logger,
+            OperationOrigin::GuiButton,
+        )
+        .expect("封印失败");
+        assert_eq!(
+            amberlock_winsec::read_security_descriptor(&path).unwrap(),
+            plan.planned_sddl
+        );
+        amberlock_winsec::remove_mandatory_label(&path).expect("清理标签失败");
+    }
+}
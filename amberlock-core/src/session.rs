@@ -0,0 +1,532 @@
+//! 设置与保护清单的配对保存
+//!
+//! 退出时设置与保护清单分别写入两个文件，两次写入之间进程崩溃会让两者描述不同的
+//! 状态（设置已切换日志路径而清单仍对应旧日志，或反之），且此前没有任何检测。
+//!
+//! 每次退出保存生成新的 `SessionStamp`（序号递增），两个文件写入相同的标记：
+//! - 保存顺序固定为先设置、后清单（均为原子替换），崩溃最多让清单落后一步；
+//!   清单只是审计日志的缓存，落后时从日志重新推导即可恢复
+//! - 启动时 `reconcile_session` 比较两个标记，不同则按各自的保存时间取较新的一方：
+//!   清单较旧时从日志重建，设置较旧时保留设置并在启动报告中提示
+//!
+//! 运行期间的设置保存与清单保存沿用当前标记，不改变序号。
+
+use crate::inventory::InventoryTracker;
+use amberlock_storage::{RecordStamper, parse_utc, save_settings};
+use amberlock_types::{Result, SessionStamp, Settings};
+use std::fmt::{Display, Formatter};
+use std::path::Path;
+
+/// 启动时比较设置与保护清单标记的结论
+#[derive(Debug, Clone, Default, PartialEq, Eq)]
+pub enum SessionReconciliation {
+    /// 两者来自同一次保存（或都没有标记，如旧版本写入的文件）
+    #[default]
+    InSync,
+    /// 清单较旧：从审计日志重建清单
+    InventoryStale {
+        settings_epoch: Option<u64>,
+        inventory_epoch: Option<u64>,
+    },
+    /// 设置较旧：保留设置，沿用清单（清单已按日志长度校验）
+    SettingsStale {
+        settings_epoch: Option<u64>,
+        inventory_epoch: Option<u64>,
+    },
+}
+
+impl SessionReconciliation {
+    /// 两个标记是否一致
+    pub fn is_in_sync(&self) -> bool {
+        *self == SessionReconciliation::InSync
+    }
+
+    /// 两者相差的保存次数（任一方没有标记时为 None）
+    ///
+    /// 按固定顺序保存时崩溃只会相差 1；更大的差距说明文件被替换或从备份恢复
+    pub fn gap(&self) -> Option<u64> {
+        match self {
+            SessionReconciliation::InSync => Some(0),
+            SessionReconciliation::InventoryStale {
+                settings_epoch,
+                inventory_epoch,
+            }
+            | SessionReconciliation::SettingsStale {
+                settings_epoch,
+                inventory_epoch,
+            } => Some((*settings_epoch)?.abs_diff((*inventory_epoch)?)),
+        }
+    }
+}
+
+impl Display for SessionReconciliation {
+    fn fmt(&self, f: &mut Formatter<'_>) -> std::fmt::Result {
+        let epoch = |epoch: &Option<u64>| match epoch {
+            Some(epoch) => format!("第 {} 次保存", epoch),
+            None => "无保存标记".to_string(),
+        };
+        match self {
+            SessionReconciliation::InSync => write!(f, "设置与保护清单来自同一次保存")?,
+            SessionReconciliation::InventoryStale {
+                settings_epoch,
+                inventory_epoch,
+            } => write!(
+                f,
+                "保护清单（{}）落后于设置（{}），已从审计日志重建保护清单",
+                epoch(inventory_epoch),
+                epoch(settings_epoch)
+            )?,
+            SessionReconciliation::SettingsStale {
+                settings_epoch,
+                inventory_epoch,
+            } => write!(
+                f,
+                "设置（{}）落后于保护清单（{}），上次运行中的设置更改可能已丢失，请检查设置",
+                epoch(settings_epoch),
+                epoch(inventory_epoch)
+            )?,
+        }
+        match self.gap() {
+            Some(gap) if gap > 1 => {
+                write!(f, "（相差 {} 次保存，文件可能被替换或从备份恢复）", gap)
+            }
+            _ => Ok(()),
+        }
+    }
+}
+
+/// 比较设置与保护清单的标记
+///
+/// # 参数
+/// - `settings`: 设置中的标记
+/// - `inventory`: 保护清单附属文件中的标记
+///
+/// # 返回
+/// - 标记完全相同或都没有标记：`InSync`
+/// - 只有一方有标记：没有标记的一方较旧（旧版本写入、被删除后重新生成）
+/// - 都有标记：按保存时间取较新的一方；时间相同或无法解析时按序号
+pub fn reconcile_session(
+    settings: Option<&SessionStamp>,
+    inventory: Option<&SessionStamp>,
+) -> SessionReconciliation {
+    let settings_epoch = settings.map(|s| s.epoch);
+    let inventory_epoch = inventory.map(|s| s.epoch);
+    let settings_newer = match (settings, inventory) {
+        (None, None) => return SessionReconciliation::InSync,
+        (Some(s), Some(i)) if s == i => return SessionReconciliation::InSync,
+        (Some(_), None) => true,
+        (None, Some(_)) => false,
+        (Some(s), Some(i)) => match (parse_utc(&s.saved_utc), parse_utc(&i.saved_utc)) {
+            (Some(s_time), Some(i_time)) if s_time != i_time => s_time > i_time,
+            _ => s.epoch > i.epoch,
+        },
+    };
+
+    if settings_newer {
+        SessionReconciliation::InventoryStale {
+            settings_epoch,
+            inventory_epoch,
+        }
+    } else {
+        SessionReconciliation::SettingsStale {
+            settings_epoch,
+            inventory_epoch,
+        }
+    }
+}
+
+/// 下一次保存的标记（序号为两者中较大者加一）
+pub fn next_session_stamp(
+    settings: Option<&SessionStamp>,
+    inventory: Option<&SessionStamp>,
+    saved_utc: String,
+) -> SessionStamp {
+    let epoch = settings
+        .into_iter()
+        .chain(inventory)
+        .map(|stamp| stamp.epoch)
+        .max()
+        .unwrap_or(0)
+        + 1;
+    SessionStamp { epoch, saved_utc }
+}
+
+/// 退出保存的步骤（按执行顺序）
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+enum SaveStep {
+    Settings,
+    Inventory,
+}
+
+/// 先写设置、后写清单：崩溃时清单最多落后一步，可从日志恢复
+const SAVE_ORDER: [SaveStep; 2] = [SaveStep::Settings, SaveStep::Inventory];
+
+/// 退出时以新标记依次保存设置与保护清单
+///
+/// # 参数
+/// - `settings_path`: 设置文件路径
+/// - `settings`: 当前设置（`session` 更新为新标记）
+/// - `inventory`: 保护清单（应在日志刷新之后调用）
+/// - `stamper`: 保存时间来源
+///
+/// # 返回
+/// 本次保存的标记
+pub fn save_session_pair(
+    settings_path: impl AsRef<Path>,
+    settings: &mut Settings,
+    inventory: &InventoryTracker,
+    stamper: &dyn RecordStamper,
+) -> Result<SessionStamp> {
+    save_steps(settings_path, settings, inventory, stamper, &SAVE_ORDER)
+}
+
+fn save_steps(
+    settings_path: impl AsRef<Path>,
+    settings: &mut Settings,
+    inventory: &InventoryTracker,
+    stamper: &dyn RecordStamper,
+    steps: &[SaveStep],
+) -> Result<SessionStamp> {
+    let stamp = next_session_stamp(
+        settings.session.as_ref(),
+        inventory.session().as_ref(),
+        stamper.now(),
+    );
+    settings.session = Some(stamp.clone());
+    for step in steps {
+        match step {
+            SaveStep::Settings => save_settings(&settings_path, settings)?,
+            SaveStep::Inventory => inventory.persist_session(stamp.clone())?,
+        }
+    }
+    Ok(stamp)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use amberlock_storage::{FixedStamper, OperationLog, load_settings};
+    use amberlock_types::{LabelLevel, LockRecord, OperationOrigin, ProtectMode, TargetKind};
+    use std::path::PathBuf;
+    use std::sync::Arc;
+    use tempfile::TempDir;
+
+    fn stamp(epoch: u64, saved_utc: &str) -> SessionStamp {
+        SessionStamp {
+            epoch,
+            saved_utc: saved_utc.to_string(),
+        }
+    }
+
+    fn inventory_stale(settings: Option<u64>, inventory: Option<u64>) -> SessionReconciliation {
+        SessionReconciliation::InventoryStale {
+            settings_epoch: settings,
+            inventory_epoch: inventory,
+        }
+    }
+
+    fn settings_stale(settings: Option<u64>, inventory: Option<u64>) -> SessionReconciliation {
+        SessionReconciliation::SettingsStale {
+            settings_epoch: settings,
+            inventory_epoch: inventory,
+        }
+    }
+
+    #[test]
+    fn test_reconcile_decision_table() {
+        let t1 = "2025-03-01T10:00:00Z";
+        let t2 = "2025-03-02T10:00:00Z";
+        let cases = [
+            // 都没有标记（旧版本或首次运行）
+            (None, None, SessionReconciliation::InSync),
+            // 同一次保存
+            (
+                Some(stamp(3, t1)),
+                Some(stamp(3, t1)),
+                SessionReconciliation::InSync,
+            ),
+            // 写完设置后崩溃：清单落后一步
+            (
+                Some(stamp(4, t2)),
+                Some(stamp(3, t1)),
+                inventory_stale(Some(4), Some(3)),
+            ),
+            // 设置较旧（被替换为旧文件）
+            (
+                Some(stamp(3, t1)),
+                Some(stamp(4, t2)),
+                settings_stale(Some(3), Some(4)),
+            ),
+            // 清单来自旧版本或被删除后重建
+            (Some(stamp(2, t1)), None, inventory_stale(Some(2), None)),
+            // 设置被重置为默认值
+            (None, Some(stamp(2, t1)), settings_stale(None, Some(2))),
+            // 序号与时间矛盾时以保存时间为准
+            (
+                Some(stamp(9, t1)),
+                Some(stamp(2, t2)),
+                settings_stale(Some(9), Some(2)),
+            ),
+            (
+                Some(stamp(2, t2)),
+                Some(stamp(9, t1)),
+                inventory_stale(Some(2), Some(9)),
+            ),
+            // 时间相同或无法解析时按序号
+            (
+                Some(stamp(5, t1)),
+                Some(stamp(4, t1)),
+                inventory_stale(Some(5), Some(4)),
+            ),
+            (
+                Some(stamp(4, "坏时间")),
+                Some(stamp(5, t1)),
+                settings_stale(Some(4), Some(5)),
+            ),
+            // 同一序号、不同时间：两个进程各自保存过，取较新的一方
+            (
+                Some(stamp(6, t2)),
+                Some(stamp(6, t1)),
+                inventory_stale(Some(6), Some(6)),
+            ),
+            (
+                Some(stamp(6, t1)),
+                Some(stamp(6, t2)),
+                settings_stale(Some(6), Some(6)),
+            ),
+            // 同一序号、时间相同但格式不同（带偏移）：仍视为不同标记，按序号打平时取清单
+            (
+                Some(stamp(6, "2025-03-01T18:00:00+08:00")),
+                Some(stamp(6, t1)),
+                settings_stale(Some(6), Some(6)),
+            ),
+        ];
+
+        for (settings, inventory, expected) in cases {
+            assert_eq!(
+                reconcile_session(settings.as_ref(), inventory.as_ref()),
+                expected,
+                "设置 {:?}，清单 {:?}",
+                settings,
+                inventory
+            );
+        }
+        println!("✅ 标记比对决策表正确");
+    }
+
+    #[test]
+    fn test_gap_and_messages() {
+        assert_eq!(SessionReconciliation::InSync.gap(), Some(0));
+        assert_eq!(inventory_stale(Some(4), Some(3)).gap(), Some(1));
+        assert_eq!(settings_stale(Some(2), Some(7)).gap(), Some(5));
+        assert_eq!(inventory_stale(Some(2), None).gap(), None);
+
+        let one_step = inventory_stale(Some(4), Some(3)).to_string();
+        assert!(one_step.contains("已从审计日志重建"), "{}", one_step);
+        assert!(!one_step.contains("相差"), "{}", one_step);
+        let restored = settings_stale(Some(2), Some(7)).to_string();
+        assert!(restored.contains("相差 5 次保存"), "{}", restored);
+        assert!(
+            settings_stale(None, Some(1))
+                .to_string()
+                .contains("无保存标记")
+        );
+        println!("✅ 相差次数与说明正确");
+    }
+
+    #[test]
+    fn test_next_stamp_is_monotonic() {
+        let now = "2025-03-01T10:00:00Z".to_string();
+        assert_eq!(next_session_stamp(None, None, now.clone()).epoch, 1);
+        let a = stamp(3, "2025-01-01T00:00:00Z");
+        let b = stamp(7, "2024-01-01T00:00:00Z");
+        assert_eq!(next_session_stamp(Some(&a), Some(&b), now.clone()).epoch, 8);
+        assert_eq!(
+            next_session_stamp(Some(&b), None, now.clone()).saved_utc,
+            now
+        );
+        println!("✅ 保存序号单调递增");
+    }
+
+    // ---- 模拟退出保存在各步骤之间崩溃 ----
+
+    struct Sandbox {
+        _dir: TempDir,
+        log_path: PathBuf,
+        settings_path: PathBuf,
+    }
+
+    fn record(logger: &OperationLog, path: &str, status: &str) -> LockRecord {
+        LockRecord {
+            id: logger.stamper().new_id(),
+            path: path.to_string(),
+            kind: TargetKind::File,
+            mode: ProtectMode::ReadOnly,
+            level_applied: LabelLevel::High,
+            time_utc: logger.stamper().now(),
+            seq_no: logger.stamper().next_seq(),
+            user_sid: "S-1-5-21-test".to_string(),
+            owner_before: None,
+            sddl_before: None,
+            sddl_after: None,
+            status: status.to_string(),
+            errors: vec![],
+            details: None,
+            authorized_by: None,
+            comment: None,
+            batch_id: None,
+            volume_root_acknowledged: false,
+            downgrade_reason: None,
+            origin: OperationOrigin::GuiButton,
+            app_version: None,
+            file_id: None,
+            warnings: vec![],
+        }
+    }
+
+    fn settings_for(log_path: &Path) -> Settings {
+        serde_json::from_value(serde_json::json!({
+            "parallelism": 4,
+            "default_mode": "ReadOnly",
+            "default_level": "High",
+            "log_path": log_path,
+            "vault_path": log_path.with_file_name("vault.bin"),
+            "shell_integration": false,
+        }))
+        .expect("构造设置失败")
+    }
+
+    /// 启动：读取设置与保护清单并比对标记
+    fn start(sandbox: &Sandbox) -> (Settings, Arc<InventoryTracker>, SessionReconciliation) {
+        let mut settings = load_settings(&sandbox.settings_path).expect("读取设置失败");
+        let tracker = Arc::new(InventoryTracker::open(&sandbox.log_path).expect("打开清单失败"));
+        let outcome = tracker
+            .reconcile_session(&mut settings)
+            .expect("比对标记失败");
+        (settings, tracker, outcome)
+    }
+
+    /// 一次运行：记录若干操作后退出，保存执行到第 `steps` 步时“崩溃”
+    fn run_session(sandbox: &Sandbox, paths: &[&str], steps: usize, now: &str) {
+        let (mut settings, tracker, _) = start(sandbox);
+        let logger = OperationLog::open(&sandbox.log_path)
+            .expect("打开日志失败")
+            .with_observer(tracker.clone());
+        for path in paths {
+            logger.append(&record(&logger, path, "success")).unwrap();
+        }
+        logger.flush().unwrap();
+        let stamper = FixedStamper::new(now, "session");
+        save_steps(
+            &sandbox.settings_path,
+            &mut settings,
+            &tracker,
+            &stamper,
+            &SAVE_ORDER[..steps],
+        )
+        .expect("保存失败");
+    }
+
+    /// 完成一次完整保存（序号 1）后的沙盒
+    fn sandbox() -> Sandbox {
+        let dir = TempDir::new().expect("创建临时目录失败");
+        let log_path = dir.path().join("log.ndjson");
+        let settings_path = dir.path().join("settings.json");
+        save_settings(&settings_path, &settings_for(&log_path)).expect("写入设置失败");
+        let sandbox = Sandbox {
+            _dir: dir,
+            log_path,
+            settings_path,
+        };
+        run_session(
+            &sandbox,
+            &[r"C:\a.txt"],
+            SAVE_ORDER.len(),
+            "2025-03-01T10:00:00Z",
+        );
+        sandbox
+    }
+
+    #[test]
+    fn test_crash_before_any_write_stays_in_sync() {
+        let sandbox = sandbox();
+        run_session(&sandbox, &[r"C:\b.txt"], 0, "2025-03-02T10:00:00Z");
+
+        let (settings, tracker, outcome) = start(&sandbox);
+        assert_eq!(outcome, SessionReconciliation::InSync);
+        assert_eq!(settings.session.as_ref().map(|s| s.epoch), Some(1));
+        // 运行期间清单随日志刷新保存，内容仍是最新的
+        assert!(tracker.snapshot().get(r"C:\b.txt").is_some());
+        println!("✅ 保存前崩溃：两者仍为同一次保存");
+    }
+
+    #[test]
+    fn test_crash_after_settings_rebuilds_inventory() {
+        let sandbox = sandbox();
+        run_session(&sandbox, &[r"C:\b.txt"], 1, "2025-03-02T10:00:00Z");
+
+        let (settings, tracker, outcome) = start(&sandbox);
+        assert_eq!(outcome, inventory_stale(Some(2), Some(1)));
+        assert_eq!(outcome.gap(), Some(1));
+        assert_eq!(tracker.session_reconciliation(), outcome);
+        assert_eq!(
+            tracker.snapshot(),
+            crate::inventory::rebuild_inventory_from_log(&sandbox.log_path).unwrap()
+        );
+        // 重建后的清单沿用设置的标记并已保存，再次启动不再提示
+        assert_eq!(tracker.session(), settings.session);
+        drop(tracker);
+        let (_, _, again) = start(&sandbox);
+        assert_eq!(again, SessionReconciliation::InSync);
+        println!("✅ 写完设置后崩溃：清单从日志重建");
+    }
+
+    #[test]
+    fn test_complete_save_stays_in_sync() {
+        let sandbox = sandbox();
+        run_session(
+            &sandbox,
+            &[r"C:\b.txt"],
+            SAVE_ORDER.len(),
+            "2025-03-02T10:00:00Z",
+        );
+
+        let (settings, tracker, outcome) = start(&sandbox);
+        assert_eq!(outcome, SessionReconciliation::InSync);
+        assert_eq!(settings.session.as_ref().map(|s| s.epoch), Some(2));
+        assert_eq!(tracker.session(), settings.session);
+        println!("✅ 完整保存：两者序号一致");
+    }
+
+    #[test]
+    fn test_restored_settings_keep_inventory() {
+        let sandbox = sandbox();
+        let backup = std::fs::read(&sandbox.settings_path).unwrap();
+        run_session(
+            &sandbox,
+            &[r"C:\b.txt"],
+            SAVE_ORDER.len(),
+            "2025-03-02T10:00:00Z",
+        );
+        run_session(
+            &sandbox,
+            &[r"C:\c.txt"],
+            SAVE_ORDER.len(),
+            "2025-03-03T10:00:00Z",
+        );
+        // 设置文件被替换为第 1 次保存时的备份
+        std::fs::write(&sandbox.settings_path, backup).unwrap();
+
+        let (settings, tracker, outcome) = start(&sandbox);
+        assert_eq!(outcome, settings_stale(Some(1), Some(3)));
+        assert!(outcome.to_string().contains("相差 2 次保存"));
+        assert!(tracker.snapshot().get(r"C:\c.txt").is_some());
+        // 沿用清单的标记，下次保存序号继续递增
+        assert_eq!(settings.session, tracker.session());
+        run_session(&sandbox, &[], SAVE_ORDER.len(), "2025-03-04T10:00:00Z");
+        let (settings, _, outcome) = start(&sandbox);
+        assert_eq!(outcome, SessionReconciliation::InSync);
+        assert_eq!(settings.session.map(|s| s.epoch), Some(4));
+        println!("✅ 设置较旧：保留清单并提示");
+    }
+}
@@ -0,0 +1,702 @@
+//! 启动自检报告
+//!
+//! 汇总程序启动时的各项健康检查（能力探测、保险库、设置、日志可写性）
+//! 以及已加载的文件系统过滤驱动（供排查安全软件拦截）、保护清单摘要与日志副本状态，
+//! 生成结构化的 `StartupReport`，供 GUI 状态栏/系统状态面板渲染或测试断言。
+
+use crate::build_info::{APP_VERSION, version_string};
+use crate::inventory::{InventoryCheck, ProtectionInventory};
+use crate::schedule::TimeSpec;
+use crate::session::SessionReconciliation;
+use amberlock_storage::{DivergenceReport, SpoolStatus};
+use amberlock_types::{
+    CapabilityProbe, ParallelismMode, Result, Schedule, ScheduleAction, Settings,
+};
+use std::fmt::{Display, Formatter};
+use std::fs::OpenOptions;
+use std::path::Path;
+
+/// 问题严重程度
+#[derive(Debug, Clone, Copy, PartialEq, Eq, PartialOrd, Ord)]
+pub enum Severity {
+    /// 提示信息，不影响健康状态
+    Info,
+    /// 功能受限，程序可继续运行
+    Warning,
+    /// 核心功能不可用
+    Error,
+}
+
+/// 单项启动问题
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Issue {
+    /// 稳定标识符（供测试和日志匹配，不随文案变化）
+    pub id: &'static str,
+    /// 严重程度
+    pub severity: Severity,
+    /// 本地化描述
+    pub message: String,
+}
+
+impl Issue {
+    fn new(id: &'static str, severity: Severity, message: impl Into<String>) -> Self {
+        Self {
+            id,
+            severity,
+            message: message.into(),
+        }
+    }
+}
+
+impl Display for Issue {
+    fn fmt(&self, f: &mut Formatter<'_>) -> std::fmt::Result {
+        let icon = match self.severity {
+            Severity::Info => "ℹ️",
+            Severity::Warning => "⚠️",
+            Severity::Error => "❌",
+        };
+        write!(f, "{} {}", icon, self.message)
+    }
+}
+
+/// 整体健康状态
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum Health {
+    /// 一切正常
+    Ready,
+    /// 部分功能受限
+    Degraded(Vec<Issue>),
+    /// 核心功能不可用
+    Broken(Vec<Issue>),
+}
+
+/// 启动自检报告
+#[derive(Debug, Clone)]
+pub struct StartupReport {
+    /// 程序版本（含 git 提交与构建时间）
+    pub version: String,
+    /// 能力探测结果（探测失败时为 None）
+    pub capability: Option<CapabilityProbe>,
+    /// 保险库文件是否存在
+    pub vault_present: bool,
+    /// 日志文件是否可写
+    pub log_writable: bool,
+    /// 已加载的文件系统微筛选器（未收集或枚举失败时为 None）
+    pub filter_drivers: Option<Vec<String>>,
+    /// 保护清单摘要（未附加时为 None）
+    pub inventory: Option<String>,
+    /// 全部问题（含 Info 级别）
+    pub issues: Vec<Issue>,
+    /// 整体健康状态
+    pub health: Health,
+}
+
+impl StartupReport {
+    /// 按严重程度筛选问题
+    pub fn issues_with(&self, severity: Severity) -> impl Iterator<Item = &Issue> {
+        self.issues.iter().filter(move |i| i.severity == severity)
+    }
+
+    /// 检查是否包含指定 id 的问题
+    pub fn has_issue(&self, id: &str) -> bool {
+        self.issues.iter().any(|i| i.id == id)
+    }
+
+    /// 附加保护清单的摘要与抽样检查结果
+    ///
+    /// # 参数
+    /// - `inventory`: 保护清单
+    /// - `rebuilt`: 清单是否刚从审计日志重建
+    /// - `check`: 抽样检查结果
+    ///
+    /// # 注意
+    /// 抽样发现不一致时记为 Warning（清单或标签在 AmberLock 之外被修改，
+    /// 建议运行保护验证），并重新计算整体健康状态
+    pub fn attach_inventory(
+        &mut self,
+        inventory: &ProtectionInventory,
+        rebuilt: bool,
+        check: &InventoryCheck,
+    ) {
+        self.inventory = Some(inventory.summary_line());
+        if rebuilt {
+            self.issues.push(Issue::new(
+                "inventory.rebuilt",
+                Severity::Info,
+                "保护清单已从审计日志重建",
+            ));
+        }
+        if !check.is_consistent() {
+            self.issues.push(Issue::new(
+                "inventory.divergent",
+                Severity::Warning,
+                format!(
+                    "抽样检查的 {} 个对象中有 {} 个与保护清单不一致，建议运行保护验证",
+                    check.sampled,
+                    check.divergent.len()
+                ),
+            ));
+        }
+        self.health = classify_health(&self.issues);
+    }
+
+    /// 附加设置与保护清单的退出保存标记比对结论
+    ///
+    /// # 注意
+    /// 标记不一致（上次退出保存中断，或某个文件被替换）时记为 Warning，说明已做的恢复
+    pub fn attach_session(&mut self, reconciliation: &SessionReconciliation) {
+        let id = match reconciliation {
+            SessionReconciliation::InSync => return,
+            SessionReconciliation::InventoryStale { .. } => "session.inventory_stale",
+            SessionReconciliation::SettingsStale { .. } => "session.settings_stale",
+        };
+        self.issues.push(Issue::new(
+            id,
+            Severity::Warning,
+            reconciliation.to_string(),
+        ));
+        self.health = classify_health(&self.issues);
+    }
+
+    /// 附加日志副本的暂存状态与比较结果
+    ///
+    /// # 参数
+    /// - `spool`: 副本暂存状态
+    /// - `divergence`: 主日志与副本的比较结果（副本不可读时为 None）
+    ///
+    /// # 注意
+    /// 暂存溢出（副本永久缺失记录）与主日志疑似被删改记为 Warning；
+    /// 副本暂不可用、仍有暂存记录只是提示
+    pub fn attach_replica(&mut self, spool: &SpoolStatus, divergence: Option<&DivergenceReport>) {
+        if spool.overflowed > 0 {
+            self.issues.push(Issue::new(
+                "replica.spool_overflow",
+                Severity::Warning,
+                format!(
+                    "日志副本暂存已满，{} 条记录未能写入副本，副本已不完整",
+                    spool.overflowed
+                ),
+            ));
+        }
+        if spool.pending > 0 {
+            self.issues.push(Issue::new(
+                "replica.spooled",
+                Severity::Info,
+                format!("{} 条记录等待写入日志副本", spool.pending),
+            ));
+        }
+        match divergence {
+            None => self.issues.push(Issue::new(
+                "replica.unavailable",
+                Severity::Info,
+                "日志副本暂不可用，新记录将暂存",
+            )),
+            Some(report) if report.primary_tampered() => self.issues.push(Issue::new(
+                "replica.primary_divergent",
+                Severity::Warning,
+                format!(
+                    "操作日志与副本不一致（{}），日志可能被删改，可从副本恢复",
+                    report.summary_line()
+                ),
+            )),
+            Some(_) => {}
+        }
+        self.health = classify_health(&self.issues);
+    }
+
+    /// 附加计划任务的检查结果
+    ///
+    /// # 注意
+    /// 时间规则无效（条目永远不会执行）与允许无人值守解锁（不校验密码）记为 Warning
+    pub fn attach_schedule(&mut self, schedule: &Schedule) {
+        for entry in &schedule.entries {
+            if let Err(e) = TimeSpec::parse(&entry.at) {
+                self.issues.push(Issue::new(
+                    "schedule.invalid_spec",
+                    Severity::Warning,
+                    format!("计划任务 {} 不会执行：{}", entry.path, e),
+                ));
+            }
+            if entry.action == ScheduleAction::Unlock && entry.allow_unattended_unlock {
+                self.issues.push(Issue::new(
+                    "schedule.unattended_unlock",
+                    Severity::Warning,
+                    format!("计划任务将在不校验密码的情况下解锁 {}", entry.path),
+                ));
+            }
+        }
+        self.health = classify_health(&self.issues);
+    }
+
+    /// 渲染为单行状态文本（用于状态栏）
+    pub fn status_line(&self) -> String {
+        match &self.health {
+            Health::Ready => {
+                let il = self
+                    .capability
+                    .as_ref()
+                    .map(|c| format!("{:?}", c.caller_il))
+                    .unwrap_or_else(|| "未知".to_string());
+                let mut line = format!("✅ 就绪 - 完整性级别: {} | 版本: {}", il, APP_VERSION);
+                for issue in self.issues_with(Severity::Info) {
+                    line.push_str(" | ");
+                    line.push_str(&issue.to_string());
+                }
+                line
+            }
+            Health::Degraded(issues) | Health::Broken(issues) => issues
+                .iter()
+                .map(|i| i.to_string())
+                .collect::<Vec<_>>()
+                .join(" | "),
+        }
+    }
+
+    /// 渲染为多行文本（用于系统状态面板）
+    pub fn render_lines(&self) -> Vec<String> {
+        let mut lines = Vec::new();
+
+        lines.push(match &self.health {
+            Health::Ready => "✅ 系统状态：就绪".to_string(),
+            Health::Degraded(_) => "⚠️ 系统状态：功能受限".to_string(),
+            Health::Broken(_) => "❌ 系统状态：不可用".to_string(),
+        });
+        lines.push(format!("版本: {}", self.version));
+
+        match &self.capability {
+            Some(cap) => {
+                lines.push(format!("完整性级别: {:?}", cap.caller_il));
+                lines.push(format!(
+                    "SeSecurityPrivilege: {}",
+                    if cap.has_se_security { "可用" } else { "缺失" }
+                ));
+                lines.push(format!(
+                    "SeRelabelPrivilege: {}",
+                    if cap.has_se_relabel { "可用" } else { "缺失" }
+                ));
+            }
+            None => lines.push("能力探测: 失败".to_string()),
+        }
+
+        lines.push(format!(
+            "保险库: {}",
+            if self.vault_present { "已存在" } else { "未创建" }
+        ));
+        lines.push(format!(
+            "日志文件: {}",
+            if self.log_writable { "可写" } else { "不可写" }
+        ));
+        lines.push(match &self.filter_drivers {
+            Some(filters) => format!("文件系统过滤驱动: {}", filters.join(", ")),
+            None => "文件系统过滤驱动: 无法枚举（需要管理员权限）".to_string(),
+        });
+        if let Some(inventory) = &self.inventory {
+            lines.push(format!("保护清单: {}", inventory));
+        }
+
+        for issue in &self.issues {
+            lines.push(format!("[{}] {}", issue.id, issue));
+        }
+
+        lines
+    }
+}
+
+/// 收集启动自检报告
+///
+/// # 参数
+/// - `settings`: 当前应用程序设置
+///
+/// # 注意
+/// 实际执行能力探测（带缓存）并枚举过滤驱动，测试请使用 `gather_startup_report_with`
+#[cfg(feature = "winsec")]
+pub fn gather_startup_report(settings: &Settings) -> StartupReport {
+    StartupReport {
+        filter_drivers: amberlock_winsec::list_filter_drivers().ok(),
+        ..gather_startup_report_with(settings, amberlock_winsec::probe_capability())
+    }
+}
+
+/// 使用注入的能力探测结果收集启动自检报告
+///
+/// # 参数
+/// - `settings`: 当前应用程序设置
+/// - `capability`: 能力探测结果
+///
+/// # 分类规则
+/// - 任一 `Error` 级问题 → `Health::Broken`
+/// - 任一 `Warning` 级问题 → `Health::Degraded`
+/// - 仅有 `Info` 级问题或无问题 → `Health::Ready`
+pub fn gather_startup_report_with(
+    settings: &Settings,
+    capability: Result<CapabilityProbe>,
+) -> StartupReport {
+    let mut issues = Vec::new();
+
+    // 1. 能力探测
+    let capability = match capability {
+        Ok(cap) => {
+            if !cap.has_se_security {
+                issues.push(Issue::new(
+                    "capability.no_se_security",
+                    Severity::Warning,
+                    "缺少 SeSecurityPrivilege，功能受限",
+                ));
+            }
+            if !cap.has_se_relabel {
+                issues.push(Issue::new(
+                    "capability.no_se_relabel",
+                    Severity::Info,
+                    "无法设置 System 级，将自动降级为 High",
+                ));
+            }
+            Some(cap)
+        }
+        Err(e) => {
+            issues.push(Issue::new(
+                "capability.probe_failed",
+                Severity::Error,
+                format!("能力探测失败: {:?}", e),
+            ));
+            None
+        }
+    };
+
+    // 2. 设置检查
+    if settings.parallelism == ParallelismMode::Fixed(0) {
+        issues.push(Issue::new(
+            "settings.parallelism_zero",
+            Severity::Warning,
+            "设置中的并发度为 0，将按 1 处理",
+        ));
+    }
+    if settings.vault_path.trim().is_empty() {
+        issues.push(Issue::new(
+            "settings.vault_path_empty",
+            Severity::Warning,
+            "设置中未配置保险库路径",
+        ));
+    }
+
+    // 3. 保险库状态
+    let vault_present =
+        !settings.vault_path.trim().is_empty() && Path::new(&settings.vault_path).is_file();
+    if !vault_present && !settings.vault_path.trim().is_empty() {
+        issues.push(Issue::new(
+            "vault.missing",
+            Severity::Info,
+            "尚未创建密码保险库",
+        ));
+    }
+
+    // 4. 日志可写性
+    let log_writable = check_log_writable(&settings.log_path);
+    if !log_writable {
+        issues.push(Issue::new(
+            "log.not_writable",
+            Severity::Error,
+            format!("日志文件不可写: {}", settings.log_path),
+        ));
+    }
+
+    let health = classify_health(&issues);
+
+    StartupReport {
+        version: version_string(),
+        capability,
+        vault_present,
+        log_writable,
+        filter_drivers: None,
+        inventory: None,
+        issues,
+        health,
+    }
+}
+
+/// 检查日志文件能否以追加模式打开（不存在时创建）
+fn check_log_writable(log_path: &str) -> bool {
+    if log_path.trim().is_empty() {
+        return false;
+    }
+
+    OpenOptions::new()
+        .create(true)
+        .append(true)
+        .open(log_path)
+        .is_ok()
+}
+
+/// 根据问题列表计算整体健康状态
+fn classify_health(issues: &[Issue]) -> Health {
+    let blocking: Vec<Issue> = issues
+        .iter()
+        .filter(|i| i.severity >= Severity::Warning)
+        .cloned()
+        .collect();
+
+    if blocking.iter().any(|i| i.severity == Severity::Error) {
+        Health::Broken(blocking)
+    } else if !blocking.is_empty() {
+        Health::Degraded(blocking)
+    } else {
+        Health::Ready
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use amberlock_types::{AmberlockError, LabelLevel, ProtectMode};
+    use std::fs::File;
+    use tempfile::TempDir;
+
+    fn full_capability() -> CapabilityProbe {
+        CapabilityProbe {
+            caller_il: LabelLevel::High,
+            has_se_security: true,
+            has_se_relabel: true,
+            user_sid: "S-1-5-21-0".to_string(),
+        }
+    }
+
+    fn test_settings(dir: &TempDir) -> Settings {
+        let vault_path = dir.path().join("vault.bin");
+        File::create(&vault_path).expect("创建保险库文件失败");
+
+        Settings {
+            parallelism: ParallelismMode::Fixed(4),
+            default_mode: ProtectMode::ReadOnly,
+            default_level: LabelLevel::High,
+            log_path: dir.path().join("log.ndjson").to_string_lossy().to_string(),
+            vault_path: vault_path.to_string_lossy().to_string(),
+            shell_integration: false,
+            backoff_base_ms: 500,
+            backoff_pad_success: false,
+            notifications_enabled: true,
+            alert_webhook_url: None,
+            alert_min_failures: 1,
+            read_only: false,
+            check_updates: false,
+            update_manifest_url: None,
+            last_update_check: None,
+            slow_volume_max_concurrent: 2,
+            slow_volume_min_interval_ms: 5,
+            replica_path: None,
+            schedule: Schedule::default(),
+            compress_sddl: false,
+            theme: Default::default(),
+            allow_hello_unlock: false,
+            on_cancel: Default::default(),
+            session: None,
+        }
+    }
+
+    #[test]
+    fn test_ready_report() {
+        let dir = TempDir::new().expect("创建临时目录失败");
+        let report = gather_startup_report_with(&test_settings(&dir), Ok(full_capability()));
+
+        assert_eq!(report.health, Health::Ready);
+        assert!(report.issues.is_empty());
+        assert!(report.vault_present);
+        assert!(report.log_writable);
+        assert!(report.status_line().contains("就绪"));
+    }
+
+    #[test]
+    fn test_missing_relabel_is_info_only() {
+        let dir = TempDir::new().expect("创建临时目录失败");
+        let mut cap = full_capability();
+        cap.has_se_relabel = false;
+
+        let report = gather_startup_report_with(&test_settings(&dir), Ok(cap));
+
+        assert_eq!(report.health, Health::Ready);
+        assert!(report.has_issue("capability.no_se_relabel"));
+        assert!(report.status_line().contains("降级为 High"));
+    }
+
+    #[test]
+    fn test_missing_se_security_is_degraded() {
+        let dir = TempDir::new().expect("创建临时目录失败");
+        let mut cap = full_capability();
+        cap.has_se_security = false;
+
+        let report = gather_startup_report_with(&test_settings(&dir), Ok(cap));
+
+        match &report.health {
+            Health::Degraded(issues) => {
+                assert_eq!(issues.len(), 1);
+                assert_eq!(issues[0].id, "capability.no_se_security");
+            }
+            other => panic!("期望 Degraded，实际为 {:?}", other),
+        }
+    }
+
+    #[test]
+    fn test_probe_failure_is_broken() {
+        let dir = TempDir::new().expect("创建临时目录失败");
+        let report =
+            gather_startup_report_with(&test_settings(&dir), Err(AmberlockError::Unsupported));
+
+        assert!(matches!(report.health, Health::Broken(_)));
+        assert!(report.capability.is_none());
+        assert!(report.has_issue("capability.probe_failed"));
+    }
+
+    #[test]
+    fn test_unwritable_log_is_broken() {
+        let dir = TempDir::new().expect("创建临时目录失败");
+        let mut settings = test_settings(&dir);
+        // 日志路径指向一个目录，无法以文件方式打开
+        settings.log_path = dir.path().to_string_lossy().to_string();
+
+        let report = gather_startup_report_with(&settings, Ok(full_capability()));
+
+        assert!(!report.log_writable);
+        assert!(matches!(report.health, Health::Broken(_)));
+        assert!(report.has_issue("log.not_writable"));
+    }
+
+    #[test]
+    fn test_settings_and_vault_issues() {
+        let dir = TempDir::new().expect("创建临时目录失败");
+        let mut settings = test_settings(&dir);
+        settings.parallelism = ParallelismMode::Fixed(0);
+        settings.vault_path = dir.path().join("missing.bin").to_string_lossy().to_string();
+
+        let report = gather_startup_report_with(&settings, Ok(full_capability()));
+
+        assert!(!report.vault_present);
+        assert!(report.has_issue("vault.missing"));
+        assert!(report.has_issue("settings.parallelism_zero"));
+        assert!(matches!(report.health, Health::Degraded(_)));
+        assert!(report.render_lines().iter().any(|l| l.contains("保险库: 未创建")));
+    }
+
+    #[test]
+    fn test_filter_drivers_are_rendered_without_affecting_health() {
+        let dir = TempDir::new().expect("创建临时目录失败");
+        let mut report = gather_startup_report_with(&test_settings(&dir), Ok(full_capability()));
+        assert!(report.render_lines().iter().any(|l| l.contains("无法枚举")));
+
+        report.filter_drivers = Some(vec!["WdFilter".to_string(), "FileInfo".to_string()]);
+        assert_eq!(report.health, Health::Ready);
+        assert!(
+            report
+                .render_lines()
+                .contains(&"文件系统过滤驱动: WdFilter, FileInfo".to_string())
+        );
+    }
+
+    #[test]
+    fn test_inventory_divergence_degrades_health() {
+        let dir = TempDir::new().expect("创建临时目录失败");
+        let mut report = gather_startup_report_with(&test_settings(&dir), Ok(full_capability()));
+        let inventory = ProtectionInventory::default();
+
+        report.attach_inventory(&inventory, true, &InventoryCheck::default());
+        assert_eq!(report.health, Health::Ready, "重建只是提示");
+        assert!(report.has_issue("inventory.rebuilt"));
+        assert!(
+            report
+                .render_lines()
+                .contains(&"保护清单: 当前保护 0 个对象，分布在 0 个卷".to_string())
+        );
+
+        let check = InventoryCheck {
+            sampled: 20,
+            divergent: vec![r"C:\Data\a.txt".to_string()],
+            unreadable: 0,
+        };
+        report.attach_inventory(&inventory, false, &check);
+        assert!(report.has_issue("inventory.divergent"));
+        assert!(matches!(report.health, Health::Degraded(_)));
+        println!("✅ 保护清单不一致时启动报告提示重新验证");
+    }
+
+    #[test]
+    fn test_session_mismatch_degrades_health() {
+        let dir = TempDir::new().expect("创建临时目录失败");
+        let mut report = gather_startup_report_with(&test_settings(&dir), Ok(full_capability()));
+
+        report.attach_session(&SessionReconciliation::InSync);
+        assert_eq!(report.health, Health::Ready);
+
+        report.attach_session(&SessionReconciliation::InventoryStale {
+            settings_epoch: Some(4),
+            inventory_epoch: Some(3),
+        });
+        assert!(report.has_issue("session.inventory_stale"));
+        assert!(matches!(report.health, Health::Degraded(_)));
+        assert!(report.status_line().contains("已从审计日志重建保护清单"));
+        println!("✅ 设置与保护清单的保存标记不一致时启动报告告警");
+    }
+
+    #[test]
+    fn test_replica_overflow_and_divergence_degrade_health() {
+        let dir = TempDir::new().expect("创建临时目录失败");
+        let mut report = gather_startup_report_with(&test_settings(&dir), Ok(full_capability()));
+
+        // 副本只是落后：提示即可
+        let lagging = DivergenceReport {
+            extra_in_primary: vec!["a".to_string()],
+            ..Default::default()
+        };
+        let spool = SpoolStatus {
+            pending: 3,
+            overflowed: 0,
+        };
+        report.attach_replica(&spool, Some(&lagging));
+        assert_eq!(report.health, Health::Ready);
+        assert!(report.has_issue("replica.spooled"));
+
+        let tampered = DivergenceReport {
+            missing_from_primary: vec!["b".to_string()],
+            ..Default::default()
+        };
+        let overflowed = SpoolStatus {
+            pending: 0,
+            overflowed: 7,
+        };
+        report.attach_replica(&overflowed, Some(&tampered));
+        assert!(report.has_issue("replica.spool_overflow"));
+        assert!(report.has_issue("replica.primary_divergent"));
+        assert!(matches!(report.health, Health::Degraded(_)));
+        assert!(report.status_line().contains("7 条记录未能写入副本"));
+        println!("✅ 副本暂存溢出与日志删改在启动报告中告警");
+    }
+
+    #[test]
+    fn test_schedule_warnings() {
+        let dir = TempDir::new().expect("创建临时目录失败");
+        let mut report = gather_startup_report_with(&test_settings(&dir), Ok(full_capability()));
+        let entry = |action, at: &str, allow| amberlock_types::ScheduleEntry {
+            path: r"D:\Finance".to_string(),
+            action,
+            at: at.to_string(),
+            recursive: false,
+            mode: ProtectMode::ReadOnly,
+            level: LabelLevel::High,
+            catch_up: false,
+            allow_unattended_unlock: allow,
+            last_run_utc: None,
+        };
+
+        report.attach_schedule(&Schedule {
+            entries: vec![entry(ScheduleAction::Lock, "19:00", false)],
+        });
+        assert_eq!(report.health, Health::Ready);
+
+        report.attach_schedule(&Schedule {
+            entries: vec![
+                entry(ScheduleAction::Lock, "7pm", false),
+                entry(ScheduleAction::Unlock, "08:00", true),
+            ],
+        });
+        assert!(report.has_issue("schedule.invalid_spec"));
+        assert!(report.has_issue("schedule.unattended_unlock"));
+        assert!(matches!(report.health, Health::Degraded(_)));
+        println!("✅ 计划任务的无效规则与无人值守解锁在启动报告中告警");
+    }
+}
@@ -0,0 +1,461 @@
+//! 后台操作封装
+//!
+//! 核心库的操作全部是阻塞式的，本模块将其放到独立工作线程中执行，
+//! 并通过 `OperationHandle` 暴露取消、进度查询和等待结果的能力。
+//! 启用 `tokio` 特性后可通过 `into_future()` 在异步代码中等待，
+//! Win32 调用始终在专用线程上执行，不会阻塞异步执行器。
+
+#[cfg(feature = "winsec")]
+use crate::{LockOptions, OperationContext};
+#[cfg(feature = "winsec")]
+use crate::ops::{process_lock_outcome, revert_with_privileges, unlock_with_context};
+use crate::progress::{CancelToken, ProgressSnapshot, ProgressTracker};
+use crate::registry::{ActiveOperations, OperationKind};
+#[cfg(feature = "winsec")]
+use crate::backend::WinsecBackend;
+#[cfg(feature = "winsec")]
+use crate::revert::{BatchEntry, BatchRevert};
+#[cfg(feature = "winsec")]
+use crate::throttle::{ThrottleConfig, run_throttled};
+use crate::{BatchResult, LockOutcome, accumulate_outcome};
+#[cfg(feature = "winsec")]
+use amberlock_storage::OperationLog;
+use amberlock_types::{AmberlockError, CancelBehavior, Result};
+#[cfg(feature = "winsec")]
+use amberlock_types::{LabelLevel, OperationOrigin};
+use std::path::{Path, PathBuf};
+#[cfg(feature = "winsec")]
+use std::collections::HashMap;
+#[cfg(feature = "winsec")]
+use std::sync::Arc;
+use std::thread::JoinHandle;
+
+/// 后台操作句柄
+///
+/// # 注意
+/// 丢弃句柄不会终止工作线程；需要中止时请先调用 `cancel()`
+#[derive(Debug)]
+pub struct OperationHandle {
+    cancel: CancelToken,
+    progress: ProgressTracker,
+    worker: JoinHandle<Result<BatchResult>>,
+}
+
+impl OperationHandle {
+    /// 请求取消（当前对象处理完后停止）
+    pub fn cancel(&self) {
+        self.cancel.cancel();
+    }
+
+    /// 请求取消，并仅对本次操作指定已完成对象的处理方式（见 `CancelBehavior`）
+    pub fn cancel_with(&self, behavior: CancelBehavior) {
+        self.cancel.cancel_with(behavior);
+    }
+
+    /// 是否已请求取消
+    pub fn is_cancelled(&self) -> bool {
+        self.cancel.is_cancelled()
+    }
+
+    /// 获取取消令牌副本（供其他线程持有）
+    pub fn cancel_token(&self) -> CancelToken {
+        self.cancel.clone()
+    }
+
+    /// 获取当前进度快照
+    pub fn progress(&self) -> ProgressSnapshot {
+        self.progress.snapshot()
+    }
+
+    /// 工作线程是否已结束（结束后 `join()` 不会阻塞）
+    pub fn is_finished(&self) -> bool {
+        self.worker.is_finished()
+    }
+
+    /// 阻塞等待操作结束
+    ///
+    /// # 返回
+    /// 批量统计；被取消时返回取消前已处理部分的统计
+    pub fn join(self) -> Result<BatchResult> {
+        self.worker
+            .join()
+            .map_err(|_| AmberlockError::WorkerPanicked)?
+    }
+
+    /// 转换为 Future，工作线程结束时通过 oneshot 通道完成
+    #[cfg(feature = "tokio")]
+    pub fn into_future(self) -> impl std::future::Future<Output = Result<BatchResult>> {
+        let (tx, rx) = tokio::sync::oneshot::channel();
+        std::thread::spawn(move || {
+            let _ = tx.send(self.join());
+        });
+        async move { rx.await.map_err(|_| AmberlockError::WorkerPanicked)? }
+    }
+}
+
+/// 在后台线程中对路径列表逐个执行操作
+///
+/// # 参数
+/// - `paths`: 待处理路径
+/// - `op`: 单个对象的处理函数
+///
+/// # 行为
+/// - 每个对象处理前检查取消令牌，取消后立即返回已处理部分的统计
+/// - 单个对象失败不影响后续对象
+/// - 不在 `ActiveOperations` 中登记，需要冲突检测时请使用 `spawn_registered`
+pub fn spawn_operation<F, O>(paths: Vec<PathBuf>, op: F) -> OperationHandle
+where
+    F: FnMut(&Path) -> Result<O> + Send + 'static,
+    O: Into<LockOutcome>,
+{
+    spawn_worker(paths, None, op)
+}
+
+/// 在后台线程中执行操作，并在全局 `ActiveOperations` 中登记
+///
+/// # 参数
+/// - `paths`: 待处理路径（同时作为登记的根路径）
+/// - `kind`: 操作类型
+/// - `wait_for_conflicts`: 存在冲突时是否在工作线程中等待
+/// - `op`: 单个对象的处理函数
+///
+/// # 注意
+/// 登记在工作线程中进行，冲突错误通过 `join()` 返回；
+/// 等待期间调用 `cancel()` 会放弃等待
+pub fn spawn_registered<F, O>(
+    paths: Vec<PathBuf>,
+    kind: OperationKind,
+    wait_for_conflicts: bool,
+    op: F,
+) -> OperationHandle
+where
+    F: FnMut(&Path) -> Result<O> + Send + 'static,
+    O: Into<LockOutcome>,
+{
+    spawn_worker(paths, Some((kind, wait_for_conflicts)), op)
+}
+
+fn spawn_worker<F, O>(
+    paths: Vec<PathBuf>,
+    registration: Option<(OperationKind, bool)>,
+    mut op: F,
+) -> OperationHandle
+where
+    F: FnMut(&Path) -> Result<O> + Send + 'static,
+    O: Into<LockOutcome>,
+{
+    spawn_runner(paths, registration, move |paths, cancel, progress| {
+        let result = BatchResult {
+            total_count: paths.len(),
+            ..Default::default()
+        };
+        run_sequential(paths, cancel, progress, result, &mut op)
+    })
+}
+
+/// 逐个处理路径并累计到 `result`，取消后立即返回已处理部分
+pub(crate) fn run_sequential<F, O>(
+    paths: &[PathBuf],
+    cancel: &CancelToken,
+    progress: &ProgressTracker,
+    mut result: BatchResult,
+    mut op: F,
+) -> BatchResult
+where
+    F: FnMut(&Path) -> Result<O>,
+    O: Into<LockOutcome>,
+{
+    for path in paths {
+        if cancel.is_cancelled() {
+            break;
+        }
+        let outcome = op(path).map(Into::into);
+        accumulate_outcome(&mut result, path, &outcome);
+        progress.record(outcome.is_ok());
+    }
+
+    result
+}
+
+/// 在工作线程中登记并执行 `run`（逐个处理或按卷节流并行处理）
+pub(crate) fn spawn_runner<R>(
+    paths: Vec<PathBuf>,
+    registration: Option<(OperationKind, bool)>,
+    run: R,
+) -> OperationHandle
+where
+    R: FnOnce(&[PathBuf], &CancelToken, &ProgressTracker) -> BatchResult + Send + 'static,
+{
+    let cancel = CancelToken::new();
+    let progress = ProgressTracker::new(paths.len());
+
+    let worker = {
+        let cancel = cancel.clone();
+        let progress = progress.clone();
+        std::thread::spawn(move || {
+            let _guard = match registration {
+                Some((kind, wait)) => {
+                    Some(ActiveOperations::global().register(&paths, kind, cancel.clone(), wait)?)
+                }
+                None => None,
+            };
+
+            Ok(run(&paths, &cancel, &progress))
+        })
+    };
+
+    OperationHandle {
+        cancel,
+        progress,
+        worker,
+    }
+}
+
+/// 在后台线程中批量上锁
+///
+/// # 参数
+/// - `paths`: 要锁定的路径列表
+/// - `opts`: 锁定选项
+/// - `effective_level`: 有效完整性级别
+/// - `user_sid`: 用户 SID
+/// - `logger`: 日志记录器
+///
+/// # 注意
+/// 最多 `opts.parallelism` 个对象并行处理，可移动/网络卷按 `opts.slow_volume_throttle` 节流；
+/// 被取消时按 `opts.on_cancel`（或 `cancel_with` 指定的方式）处理已上锁的对象
+#[cfg(feature = "winsec")]
+pub fn spawn_batch_lock(
+    paths: Vec<PathBuf>,
+    opts: LockOptions,
+    effective_level: LabelLevel,
+    user_sid: String,
+    logger: Arc<OperationLog>,
+) -> OperationHandle {
+    let wait = opts.wait_for_conflicts;
+    let opts = opts.in_batch(&logger);
+    spawn_runner(
+        paths,
+        Some((OperationKind::Lock, wait)),
+        move |paths, cancel, progress| {
+            let mut result = run_throttled(
+                paths,
+                &ThrottleConfig::for_lock(&opts),
+                cancel,
+                Some(progress),
+                |path| process_lock_outcome(path, &opts, effective_level, &user_sid, &logger),
+            );
+            crate::autotune::log_tuning(&result, &opts, &user_sid, &logger);
+            crate::dirsummary::log_dir_summary(&result, &opts, &user_sid, &logger);
+            crate::rollback::settle_cancelled(
+                &mut result,
+                paths,
+                &opts,
+                cancel,
+                &WinsecBackend,
+                &user_sid,
+                &logger,
+            );
+            result
+        },
+    )
+}
+
+/// 在后台线程中批量解锁
+///
+/// # 参数
+/// - `paths`: 要解锁的路径列表
+/// - `user_sid`: 用户 SID
+/// - `logger`: 日志记录器
+/// - `wait_for_conflicts`: 存在冲突时是否等待
+/// - `comment`: 操作备注，写入每条记录
+/// - `origin`: 发起操作的入口，写入每条记录
+#[cfg(feature = "winsec")]
+pub fn spawn_batch_unlock(
+    paths: Vec<PathBuf>,
+    user_sid: String,
+    logger: Arc<OperationLog>,
+    wait_for_conflicts: bool,
+    comment: Option<String>,
+    origin: OperationOrigin,
+) -> OperationHandle {
+    let batch_id = logger.stamper().new_id();
+    spawn_registered(
+        paths,
+        OperationKind::Unlock,
+        wait_for_conflicts,
+        move |path| {
+            unlock_with_context(
+                OperationContext::new(path, &user_sid, &logger)
+                    .with_comment(comment.as_deref())
+                    .with_batch_id(Some(&batch_id))
+                    .with_origin(origin),
+            )
+        },
+    )
+}
+
+/// 在后台线程中按批次撤销上锁
+///
+/// # 参数
+/// - `entries`: `batch_entries` 查询到的对象
+/// - `revert`: 撤销操作（被撤销的批次、新批次 ID、授权凭据）
+/// - `logger`: 日志记录器
+/// - `wait_for_conflicts`: 存在冲突时是否等待
+///
+/// # 注意
+/// 调用方须先校验保险库密码，见 `ops::unlock_batch`
+#[cfg(feature = "winsec")]
+pub fn spawn_unlock_batch(
+    entries: Vec<BatchEntry>,
+    revert: BatchRevert,
+    logger: Arc<OperationLog>,
+    wait_for_conflicts: bool,
+) -> OperationHandle {
+    let paths = entries.iter().map(|entry| entry.path.clone()).collect();
+    let entries: HashMap<PathBuf, BatchEntry> = entries
+        .into_iter()
+        .map(|entry| (entry.path.clone(), entry))
+        .collect();
+    spawn_registered(paths, OperationKind::Unlock, wait_for_conflicts, move |path| {
+        let entry = entries.get(path).ok_or(AmberlockError::Unsupported)?;
+        revert_with_privileges(&revert, entry, &logger)
+    })
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::LockResult;
+    use std::sync::mpsc;
+    use std::time::Duration;
+
+    fn fake_paths(count: usize) -> Vec<PathBuf> {
+        (0..count)
+            .map(|i| PathBuf::from(format!("C:\\fake\\file_{}.txt", i)))
+            .collect()
+    }
+
+    #[test]
+    fn test_dry_run_batch_progress_is_monotonic() {
+        let handle = spawn_operation(fake_paths(20), |path| {
+            std::thread::sleep(Duration::from_millis(2));
+            if path.to_string_lossy().ends_with("_7.txt") {
+                Err(AmberlockError::Unsupported)
+            } else {
+                Ok(LockResult::Success)
+            }
+        });
+
+        let mut last = 0;
+        while !handle.is_finished() {
+            let snapshot = handle.progress();
+            assert!(snapshot.completed >= last, "进度不应回退");
+            assert_eq!(snapshot.total, 20);
+            last = snapshot.completed;
+            std::thread::sleep(Duration::from_millis(1));
+        }
+
+        let result = handle.join().expect("操作失败");
+        assert_eq!(result.total_count, 20);
+        assert_eq!(result.success_count, 19);
+        assert_eq!(result.failed_count, 1);
+        println!("✅ 进度单调递增，结果：{}", result);
+    }
+
+    #[test]
+    fn test_cancel_mid_run_returns_partial_result() {
+        // 第 3 个对象处理时通知测试线程，随后阻塞到取消发出
+        let (reached_tx, reached_rx) = mpsc::channel();
+        let (resume_tx, resume_rx) = mpsc::channel::<()>();
+
+        let mut processed = 0;
+        let handle = spawn_operation(fake_paths(10), move |_| {
+            processed += 1;
+            if processed == 3 {
+                reached_tx.send(()).unwrap();
+                resume_rx.recv().unwrap();
+            }
+            Ok(LockResult::Success)
+        });
+
+        reached_rx
+            .recv_timeout(Duration::from_secs(5))
+            .expect("工作线程未到达第 3 个对象");
+        handle.cancel();
+        assert!(handle.is_cancelled());
+        resume_tx.send(()).unwrap();
+
+        let result = handle.join().expect("取消后 join 不应失败");
+        assert_eq!(result.success_count, 3);
+        assert_eq!(result.total_count, 10);
+    }
+
+    #[test]
+    fn test_join_after_cancel_before_start_does_not_hang() {
+        let handle = spawn_operation(fake_paths(1000), |_| {
+            std::thread::sleep(Duration::from_millis(1));
+            Ok(LockResult::Success)
+        });
+        handle.cancel_token().cancel();
+
+        let progress_at_cancel = handle.progress().completed;
+        let result = handle.join().expect("操作失败");
+        // 最多多处理取消时正在进行的那一个对象
+        assert!(result.success_count <= progress_at_cancel + 1);
+        assert!(result.success_count < 1000);
+    }
+
+    #[test]
+    fn test_registered_operations_conflict_and_cancel() {
+        let root = PathBuf::from("C:\\task-registry-test");
+        let (started_tx, started_rx) = mpsc::channel();
+
+        // 慢操作：同一根路径下 1000 个对象，每个耗时 5ms
+        let mut notified = false;
+        let slow = spawn_registered(
+            vec![root.clone(); 1000],
+            OperationKind::Lock,
+            false,
+            move |_| {
+                if !notified {
+                    started_tx.send(()).unwrap();
+                    notified = true;
+                }
+                std::thread::sleep(Duration::from_millis(5));
+                Ok(LockResult::Success)
+            },
+        );
+        started_rx
+            .recv_timeout(Duration::from_secs(5))
+            .expect("慢操作未开始");
+
+        // 不等待：立即返回冲突错误
+        let child = vec![root.join("child")];
+        let rejected = spawn_registered(child.clone(), OperationKind::Unlock, false, |_| {
+            Ok(LockResult::Success)
+        });
+        assert!(matches!(
+            rejected.join(),
+            Err(AmberlockError::ConflictingOperation { .. })
+        ));
+
+        // 等待：取消冲突操作后继续执行
+        let waiting = spawn_registered(child.clone(), OperationKind::Unlock, true, |_| {
+            Ok(LockResult::Success)
+        });
+        assert_eq!(ActiveOperations::global().cancel_conflicting(&child), 1);
+
+        assert!(slow.join().is_ok());
+        let result = waiting.join().expect("等待方应在冲突结束后完成");
+        assert_eq!(result.success_count, 1);
+    }
+
+    #[test]
+    fn test_worker_panic_is_reported() {
+        let handle = spawn_operation(fake_paths(1), |_| -> Result<LockResult> {
+            panic!("模拟工作线程崩溃")
+        });
+
+        assert!(matches!(handle.join(), Err(AmberlockError::WorkerPanicked)));
+    }
+}
@@ -0,0 +1,618 @@
+//! 按卷节流
+//!
+//! 同一批次中的对象可能分布在速度差异很大的卷上：在移动硬盘或小型 NAS 上
+//! 全速处理会让设备长时间满负荷，而全局降低并发度又会拖慢同批次的本地 NVMe 卷。
+//! 本模块按对象所在的卷分组排队，每个卷有独立的并发上限与操作间隔：
+//! - 固定磁盘默认不限
+//! - 可移动/网络卷默认最多 2 个并发、两次操作开始之间至少间隔 5 ms
+//!
+//! 工作线程在所有卷之间轮询取任务，某个卷达到上限时继续处理其他卷，
+//! 不同卷互不阻塞。进度与统计在所有队列之间汇总。
+//!
+//! 并发度为自动调整时，每个卷的并发上限改由该卷的 `AutoTuner` 给出
+//! （操作间隔仍按上面的参数），每个对象完成后把耗时与结果送入调整器。
+
+#[cfg(any(feature = "winsec", test))]
+use crate::autotune::{AutoTuneConfig, AutoTuner, ConcurrencyTrend, SampleOutcome};
+#[cfg(any(feature = "winsec", test))]
+use crate::progress::{CancelToken, ProgressTracker};
+#[cfg(any(feature = "winsec", test))]
+use crate::{BatchResult, LockOutcome, accumulate_outcome};
+#[cfg(any(feature = "winsec", test))]
+use amberlock_types::Result;
+use amberlock_types::{Settings, VolumeKind};
+#[cfg(any(feature = "winsec", test))]
+use std::collections::{HashMap, VecDeque};
+#[cfg(any(feature = "winsec", test))]
+use std::path::{Path, PathBuf};
+#[cfg(any(feature = "winsec", test))]
+use std::sync::{Condvar, Mutex};
+#[cfg(any(feature = "winsec", test))]
+use std::time::{Duration, Instant};
+
+/// 单个卷的节流参数
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct VolumeThrottle {
+    /// 同一卷上同时处理的对象数上限（0 表示不限）
+    pub max_concurrent_per_volume: usize,
+    /// 同一卷上两次操作开始之间的最短间隔（毫秒）
+    pub min_interval_ms: u64,
+}
+
+impl VolumeThrottle {
+    /// 不限制
+    pub const UNLIMITED: VolumeThrottle = VolumeThrottle {
+        max_concurrent_per_volume: 0,
+        min_interval_ms: 0,
+    };
+
+    /// 可移动/网络卷的默认节流
+    pub const SLOW_VOLUME_DEFAULT: VolumeThrottle = VolumeThrottle {
+        max_concurrent_per_volume: 2,
+        min_interval_ms: 5,
+    };
+
+    /// 从设置中读取可移动/网络卷的节流参数
+    pub fn from_settings(settings: &Settings) -> Self {
+        Self {
+            max_concurrent_per_volume: settings.slow_volume_max_concurrent,
+            min_interval_ms: settings.slow_volume_min_interval_ms,
+        }
+    }
+
+    /// 按卷类型选择节流参数
+    ///
+    /// # 参数
+    /// - `kind`: 卷类型
+    /// - `slow_volume`: 可移动/网络卷使用的参数
+    pub fn for_kind(kind: VolumeKind, slow_volume: VolumeThrottle) -> Self {
+        match kind {
+            VolumeKind::Removable | VolumeKind::Network => slow_volume,
+            VolumeKind::Fixed | VolumeKind::Other => VolumeThrottle::UNLIMITED,
+        }
+    }
+
+    #[cfg(any(feature = "winsec", test))]
+    fn min_interval(&self) -> Duration {
+        Duration::from_millis(self.min_interval_ms)
+    }
+}
+
+/// 识别路径所在的卷
+#[cfg(any(feature = "winsec", test))]
+pub(crate) trait VolumeClassifier: Sync {
+    /// 返回卷根（分组键）与卷类型
+    fn classify(&self, path: &Path) -> (String, VolumeKind);
+}
+
+/// 通过 Win32 API 识别卷；失败时归入不限速的 `Other` 分组
+#[cfg(feature = "winsec")]
+pub(crate) struct SystemVolumeClassifier;
+
+#[cfg(feature = "winsec")]
+impl VolumeClassifier for SystemVolumeClassifier {
+    fn classify(&self, path: &Path) -> (String, VolumeKind) {
+        amberlock_winsec::volume_of(&path.to_string_lossy())
+            .map(|(root, kind)| (root.to_lowercase(), kind))
+            .unwrap_or_else(|_| (String::new(), VolumeKind::Other))
+    }
+}
+
+/// 时钟（测试中替换为虚拟时钟）
+#[cfg(any(feature = "winsec", test))]
+pub(crate) trait Clock: Sync {
+    fn now(&self) -> Instant;
+    fn sleep_until(&self, deadline: Instant);
+}
+
+/// 系统时钟
+#[cfg(any(feature = "winsec", test))]
+pub(crate) struct SystemClock;
+
+#[cfg(any(feature = "winsec", test))]
+impl Clock for SystemClock {
+    fn now(&self) -> Instant {
+        Instant::now()
+    }
+
+    fn sleep_until(&self, deadline: Instant) {
+        let now = Instant::now();
+        if deadline > now {
+            std::thread::sleep(deadline - now);
+        }
+    }
+}
+
+/// 一次节流运行的配置
+#[cfg(any(feature = "winsec", test))]
+pub(crate) struct ThrottleConfig<'a> {
+    /// 工作线程数
+    pub parallelism: usize,
+    /// 自动调整参数（为 None 时按 `slow_volume` 的固定上限）
+    pub auto: Option<AutoTuneConfig>,
+    /// 可移动/网络卷的节流参数
+    pub slow_volume: VolumeThrottle,
+    pub classifier: &'a dyn VolumeClassifier,
+    pub clock: &'a dyn Clock,
+    /// 按目录汇总的深度（为 None 时不汇总，见 `dirsummary` 模块）
+    pub dir_summary_depth: Option<usize>,
+}
+
+#[cfg(feature = "winsec")]
+impl ThrottleConfig<'static> {
+    /// 批量上锁使用的配置
+    ///
+    /// # 注意
+    /// 开启诊断跟踪时逐个处理，跟踪文件中的对象事件保持顺序
+    pub fn for_lock(opts: &crate::LockOptions) -> Self {
+        use amberlock_types::ParallelismMode;
+
+        let (parallelism, auto) = match (&opts.trace, opts.parallelism) {
+            (Some(_), _) => (1, None),
+            (None, ParallelismMode::Fixed(n)) => (n, None),
+            (None, ParallelismMode::Auto) => {
+                let auto = AutoTuneConfig::default();
+                (auto.max, Some(auto))
+            }
+        };
+        Self {
+            parallelism,
+            auto,
+            slow_volume: opts.slow_volume_throttle,
+            classifier: &SystemVolumeClassifier,
+            clock: &SystemClock,
+            dir_summary_depth: opts
+                .collect_dir_summary
+                .then_some(crate::DEFAULT_DIR_SUMMARY_DEPTH),
+        }
+    }
+}
+
+/// 单个卷的待处理队列
+#[cfg(any(feature = "winsec", test))]
+struct VolumeQueue {
+    root: String,
+    throttle: VolumeThrottle,
+    /// 自动调整时的并发度调整器
+    tuner: Option<AutoTuner>,
+    pending: VecDeque<usize>,
+    in_flight: usize,
+    /// 下一次操作最早的开始时间
+    next_start: Option<Instant>,
+}
+
+#[cfg(any(feature = "winsec", test))]
+impl VolumeQueue {
+    /// 当前的并发上限（0 表示不限）
+    fn limit(&self) -> usize {
+        match &self.tuner {
+            Some(tuner) => tuner.concurrency(),
+            None => self.throttle.max_concurrent_per_volume,
+        }
+    }
+}
+
+#[cfg(any(feature = "winsec", test))]
+struct Scheduler {
+    volumes: Vec<VolumeQueue>,
+    /// 轮询起点，避免总是优先处理第一个卷
+    cursor: usize,
+    remaining: usize,
+}
+
+/// 一个已分配的任务：卷序号、对象序号、开始时间
+#[cfg(any(feature = "winsec", test))]
+type Job = (usize, usize, Instant);
+
+#[cfg(any(feature = "winsec", test))]
+impl Scheduler {
+    fn new(paths: &[PathBuf], config: &ThrottleConfig<'_>) -> Self {
+        let mut index_of: HashMap<String, usize> = HashMap::new();
+        let mut volumes: Vec<VolumeQueue> = Vec::new();
+        for (item, path) in paths.iter().enumerate() {
+            let (root, kind) = config.classifier.classify(path);
+            let volume = *index_of.entry(root.clone()).or_insert_with(|| {
+                volumes.push(VolumeQueue {
+                    root,
+                    throttle: VolumeThrottle::for_kind(kind, config.slow_volume),
+                    tuner: config.auto.map(AutoTuner::new),
+                    pending: VecDeque::new(),
+                    in_flight: 0,
+                    next_start: None,
+                });
+                volumes.len() - 1
+            });
+            volumes[volume].pending.push_back(item);
+        }
+        Self {
+            volumes,
+            cursor: 0,
+            remaining: paths.len(),
+        }
+    }
+
+    /// 取下一个未达到并发上限的卷上的任务，并预留其开始时间
+    fn take(&mut self, now: Instant) -> Option<Job> {
+        let count = self.volumes.len();
+        for offset in 0..count {
+            let volume = (self.cursor + offset) % count;
+            let queue = &mut self.volumes[volume];
+            let limit = queue.limit();
+            if queue.pending.is_empty() || (limit != 0 && queue.in_flight >= limit) {
+                continue;
+            }
+            let item = queue.pending.pop_front()?;
+            let start = queue.next_start.map_or(now, |next| next.max(now));
+            queue.next_start = Some(start + queue.throttle.min_interval());
+            queue.in_flight += 1;
+            self.cursor = volume + 1;
+            self.remaining -= 1;
+            return Some((volume, item, start));
+        }
+        None
+    }
+
+    /// 对象完成：释放并发名额，自动调整时送入样本
+    ///
+    /// # 返回
+    /// 自动调整时为所有卷的并发度之和与本卷的调整方向
+    fn complete(
+        &mut self,
+        volume: usize,
+        latency: Duration,
+        outcome: SampleOutcome,
+    ) -> Option<(usize, ConcurrencyTrend)> {
+        let queue = &mut self.volumes[volume];
+        queue.in_flight -= 1;
+        let trend = {
+            let tuner = queue.tuner.as_mut()?;
+            tuner.record(latency, outcome);
+            tuner.trend()
+        };
+        let total = self
+            .volumes
+            .iter()
+            .filter_map(|queue| queue.tuner.as_ref())
+            .map(AutoTuner::concurrency)
+            .sum();
+        Some((total, trend))
+    }
+}
+
+/// 按卷节流并行处理路径列表
+///
+/// # 参数
+/// - `paths`: 待处理路径
+/// - `config`: 线程数、节流参数、卷识别与时钟
+/// - `cancel`: 取消后不再开始新的对象（进行中的对象处理完）
+/// - `progress`: 每个对象完成时更新
+/// - `op`: 单个对象的处理函数（在工作线程中并发调用）
+///
+/// # 返回
+/// 批量统计；`failed_paths` 等按完成顺序排列
+#[cfg(any(feature = "winsec", test))]
+pub(crate) fn run_throttled<F, O>(
+    paths: &[PathBuf],
+    config: &ThrottleConfig<'_>,
+    cancel: &CancelToken,
+    progress: Option<&ProgressTracker>,
+    op: F,
+) -> BatchResult
+where
+    F: Fn(&Path) -> Result<O> + Sync,
+    O: Into<LockOutcome>,
+{
+    let scheduler = Mutex::new(Scheduler::new(paths, config));
+    let released = Condvar::new();
+    let mut initial = BatchResult {
+        total_count: paths.len(),
+        ..Default::default()
+    };
+    if let Some(depth) = config.dir_summary_depth {
+        initial = initial.with_dir_summary(depth);
+    }
+    let result = Mutex::new(initial);
+
+    let worker = || loop {
+        let (volume, item, start) = {
+            let mut state = scheduler.lock().unwrap();
+            loop {
+                if cancel.is_cancelled() || state.remaining == 0 {
+                    return;
+                }
+                if let Some(job) = state.take(config.clock.now()) {
+                    break job;
+                }
+                state = released.wait(state).unwrap();
+            }
+        };
+
+        config.clock.sleep_until(start);
+        let path = paths[item].as_path();
+        let outcome = op(path).map(Into::into);
+        let latency = config.clock.now().saturating_duration_since(start);
+        accumulate_outcome(&mut result.lock().unwrap(), path, &outcome);
+        if let Some(progress) = progress {
+            progress.record(outcome.is_ok());
+        }
+
+        let tuned =
+            scheduler
+                .lock()
+                .unwrap()
+                .complete(volume, latency, SampleOutcome::of(&outcome));
+        if let (Some(progress), Some((concurrency, trend))) = (progress, tuned) {
+            progress.set_concurrency(concurrency, trend);
+        }
+        released.notify_all();
+    };
+
+    let threads = config.parallelism.clamp(1, paths.len().max(1));
+    std::thread::scope(|scope| {
+        for _ in 1..threads {
+            scope.spawn(worker);
+        }
+        worker();
+    });
+
+    let mut result = result.into_inner().unwrap();
+    result.tuning = scheduler
+        .into_inner()
+        .unwrap()
+        .volumes
+        .iter()
+        .filter_map(|queue| Some(queue.tuner.as_ref()?.summary(&queue.root)))
+        .collect();
+    result
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::LockResult;
+    use amberlock_types::AmberlockError;
+    use std::sync::atomic::{AtomicUsize, Ordering};
+
+    /// 按盘符识别卷：A、B 为可移动卷，其余为固定磁盘
+    struct FakeClassifier;
+
+    impl VolumeClassifier for FakeClassifier {
+        fn classify(&self, path: &Path) -> (String, VolumeKind) {
+            let root = path.to_string_lossy()[..3].to_string();
+            let kind = match &root[..1] {
+                "A" | "B" => VolumeKind::Removable,
+                _ => VolumeKind::Fixed,
+            };
+            (root, kind)
+        }
+    }
+
+    /// 虚拟时钟：`sleep_until` 直接把时间推进到截止时刻
+    struct FakeClock {
+        now: Mutex<Instant>,
+    }
+
+    impl FakeClock {
+        fn new() -> Self {
+            Self {
+                now: Mutex::new(Instant::now()),
+            }
+        }
+    }
+
+    impl Clock for FakeClock {
+        fn now(&self) -> Instant {
+            *self.now.lock().unwrap()
+        }
+
+        fn sleep_until(&self, deadline: Instant) {
+            let mut now = self.now.lock().unwrap();
+            *now = (*now).max(deadline);
+        }
+    }
+
+    /// 统计每个卷与全局的同时进行数及其峰值
+    #[derive(Default)]
+    struct CountingGate {
+        current: [AtomicUsize; 3],
+        peak: [AtomicUsize; 3],
+        total: AtomicUsize,
+        total_peak: AtomicUsize,
+    }
+
+    impl CountingGate {
+        fn run(&self, volume: usize) {
+            let now = self.current[volume].fetch_add(1, Ordering::SeqCst) + 1;
+            self.peak[volume].fetch_max(now, Ordering::SeqCst);
+            let total = self.total.fetch_add(1, Ordering::SeqCst) + 1;
+            self.total_peak.fetch_max(total, Ordering::SeqCst);
+            std::thread::sleep(Duration::from_millis(10));
+            self.total.fetch_sub(1, Ordering::SeqCst);
+            self.current[volume].fetch_sub(1, Ordering::SeqCst);
+        }
+    }
+
+    fn volume_index(path: &Path) -> usize {
+        match &path.to_string_lossy()[..1] {
+            "A" => 0,
+            "B" => 1,
+            _ => 2,
+        }
+    }
+
+    fn paths(drives: &[&str], per_drive: usize) -> Vec<PathBuf> {
+        (0..per_drive)
+            .flat_map(|i| {
+                drives
+                    .iter()
+                    .map(move |drive| PathBuf::from(format!(r"{}:\data\{}.txt", drive, i)))
+            })
+            .collect()
+    }
+
+    #[test]
+    fn test_per_volume_cap_with_independent_volumes() {
+        let gate = CountingGate::default();
+        let config = ThrottleConfig {
+            parallelism: 8,
+            slow_volume: VolumeThrottle {
+                max_concurrent_per_volume: 2,
+                min_interval_ms: 0,
+            },
+            auto: None,
+            classifier: &FakeClassifier,
+            clock: &SystemClock,
+            dir_summary_depth: None,
+        };
+        let paths = paths(&["A", "B", "C"], 12);
+        let progress = ProgressTracker::new(paths.len());
+
+        let result = run_throttled(
+            &paths,
+            &config,
+            &CancelToken::new(),
+            Some(&progress),
+            |path| {
+                gate.run(volume_index(path));
+                match path.to_string_lossy().ends_with(r"\0.txt") {
+                    true => Err(AmberlockError::Unsupported),
+                    false => Ok(LockResult::Success),
+                }
+            },
+        );
+
+        assert!(
+            gate.peak[0].load(Ordering::SeqCst) <= 2,
+            "卷 A 超过并发上限"
+        );
+        assert!(
+            gate.peak[1].load(Ordering::SeqCst) <= 2,
+            "卷 B 超过并发上限"
+        );
+        assert!(
+            gate.total_peak.load(Ordering::SeqCst) > 2,
+            "不同卷应能同时处理，总并发应超过单卷上限"
+        );
+        assert!(
+            gate.peak[2].load(Ordering::SeqCst) > 2,
+            "固定磁盘不应受可移动卷的上限约束"
+        );
+
+        assert_eq!(result.total_count, 36);
+        assert_eq!(result.success_count, 33);
+        assert_eq!(result.failed_count, 3);
+        let snapshot = progress.snapshot();
+        assert_eq!((snapshot.completed, snapshot.failed), (36, 3));
+        println!("✅ 按卷并发上限生效，不同卷互不阻塞");
+    }
+
+    #[test]
+    fn test_min_interval_pacing_with_fake_clock() {
+        let clock = FakeClock::new();
+        let origin = clock.now();
+        let config = ThrottleConfig {
+            parallelism: 1,
+            slow_volume: VolumeThrottle::SLOW_VOLUME_DEFAULT,
+            auto: None,
+            classifier: &FakeClassifier,
+            clock: &clock,
+            dir_summary_depth: None,
+        };
+        let starts = Mutex::new(Vec::new());
+        let paths = paths(&["A", "C"], 10);
+
+        run_throttled(&paths, &config, &CancelToken::new(), None, |path| {
+            starts
+                .lock()
+                .unwrap()
+                .push((volume_index(path), clock.now() - origin));
+            Ok(LockResult::Success)
+        });
+
+        let starts = starts.into_inner().unwrap();
+        let slow: Vec<Duration> = starts
+            .iter()
+            .filter(|(volume, _)| *volume == 0)
+            .map(|(_, at)| *at)
+            .collect();
+        assert_eq!(slow.len(), 10);
+        for pair in slow.windows(2) {
+            assert!(pair[1] - pair[0] >= Duration::from_millis(5), "间隔不足");
+        }
+        // 固定磁盘不等待：全部完成所需的虚拟时间只取决于可移动卷
+        assert_eq!(slow[9], Duration::from_millis(45));
+        assert_eq!(starts.last().unwrap().1, Duration::from_millis(45));
+    }
+
+    #[test]
+    fn test_auto_tuned_limits_per_volume() {
+        let gate = CountingGate::default();
+        let config = ThrottleConfig {
+            parallelism: 8,
+            slow_volume: VolumeThrottle::UNLIMITED,
+            auto: Some(AutoTuneConfig {
+                initial: 1,
+                max: 3,
+                window: 2,
+                ..Default::default()
+            }),
+            classifier: &FakeClassifier,
+            clock: &SystemClock,
+            dir_summary_depth: None,
+        };
+        let paths = paths(&["A", "C"], 20);
+        let progress = ProgressTracker::new(paths.len());
+
+        let result = run_throttled(
+            &paths,
+            &config,
+            &CancelToken::new(),
+            Some(&progress),
+            |path| {
+                gate.run(volume_index(path));
+                Ok(LockResult::Success)
+            },
+        );
+
+        // 调整器给出的上限即每个卷的并发上限
+        assert!(gate.peak[0].load(Ordering::SeqCst) <= 3);
+        assert!(gate.peak[2].load(Ordering::SeqCst) <= 3);
+        assert_eq!(result.success_count, 40);
+
+        let roots: Vec<&str> = result.tuning.iter().map(|t| t.root.as_str()).collect();
+        assert_eq!(roots, [r"A:\", r"C:\"]);
+        for tuning in &result.tuning {
+            assert_eq!(tuning.history[0].concurrency, 1);
+            assert!(tuning.peak > 1, "延迟稳定时应提高并发度");
+        }
+        let status = progress.snapshot().format_status_detailed();
+        assert!(status.contains("，并发: "), "{}", status);
+        println!("✅ 自动调整按卷限制并发度");
+    }
+
+    #[test]
+    fn test_cancel_stops_taking_new_items() {
+        let cancel = CancelToken::new();
+        let processed = AtomicUsize::new(0);
+        let config = ThrottleConfig {
+            parallelism: 2,
+            slow_volume: VolumeThrottle::SLOW_VOLUME_DEFAULT,
+            auto: None,
+            classifier: &FakeClassifier,
+            clock: &SystemClock,
+            dir_summary_depth: None,
+        };
+        let paths = paths(&["C"], 50);
+
+        let result = run_throttled(&paths, &config, &cancel, None, |_| {
+            if processed.fetch_add(1, Ordering::SeqCst) == 4 {
+                cancel.cancel();
+            }
+            Ok(LockResult::Success)
+        });
+
+        assert_eq!(result.total_count, 50);
+        assert!(result.success_count < 50);
+        assert_eq!(result.success_count, processed.load(Ordering::SeqCst));
+    }
+}
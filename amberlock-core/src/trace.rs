@@ -0,0 +1,773 @@
+//! 诊断跟踪的捕获与回放
+//!
+//! 用户反馈"锁定某棵目录树时行为异常"时，我们拿不到对方的目录结构。
+//! 为单次上锁操作开启跟踪后，每次标签读写调用（类型、路径、级别、错误码、耗时）
+//! 与每个对象的结果都写入跟踪 NDJSON。写入前路径逐段替换为稳定的哈希令牌
+//! （同一名称得到同一令牌），保留层级深度、扩展名与长度档位，
+//! 结构可供分析但名称不会泄露。
+//!
+//! `replay_trace` 用记录的结果配置 `FakeBackend`，按原顺序重新执行，
+//! 可在任意机器上确定性地复现顺序、进度与统计方面的问题。
+
+use crate::backend::{FakeBackend, LabelBackend, LabelOp, ObjectLabel, lock_object};
+use crate::progress::ProgressTracker;
+use crate::{BatchResult, LockResult, accumulate};
+use amberlock_storage::NdjsonWriter;
+use amberlock_types::{AmberlockError, FileId, LabelLevel, ProtectMode, Result};
+use serde::{Deserialize, Serialize};
+use std::collections::HashSet;
+use std::hash::BuildHasher;
+use std::path::{Path, PathBuf};
+use std::sync::atomic::{AtomicUsize, Ordering};
+use std::time::Instant;
+
+/// 跟踪文件格式版本
+pub const TRACE_VERSION: u32 = 1;
+
+/// 无法映射为 Win32 错误码的失败在跟踪中使用的错误码
+pub const UNKNOWN_ERROR_CODE: u32 = u32::MAX;
+
+/// 跟踪文件头（本次操作的参数）
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+pub struct TraceHeader {
+    /// 格式版本
+    pub version: u32,
+    /// 保护模式
+    pub mode: ProtectMode,
+    /// 期望的完整性级别
+    pub desired_level: LabelLevel,
+    /// 实际使用的完整性级别
+    pub effective_level: LabelLevel,
+    /// 对象总数
+    pub total: usize,
+}
+
+impl TraceHeader {
+    /// 创建当前版本的文件头
+    pub fn new(
+        mode: ProtectMode,
+        desired_level: LabelLevel,
+        effective_level: LabelLevel,
+        total: usize,
+    ) -> Self {
+        Self {
+            version: TRACE_VERSION,
+            mode,
+            desired_level,
+            effective_level,
+            total,
+        }
+    }
+}
+
+/// 跟踪文件中的一行
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(tag = "event", rename_all = "snake_case")]
+pub enum TraceEvent {
+    /// 文件头
+    Header(TraceHeader),
+    /// 开始处理一个对象
+    Object {
+        /// 对象序号（从 0 开始）
+        index: usize,
+        /// 匿名化后的目标路径
+        path: String,
+    },
+    /// 一次后端调用
+    Call {
+        /// 调用类型
+        op: LabelOp,
+        /// 匿名化后的目标路径
+        path: String,
+        /// 设置的级别，或读取成功时返回的级别
+        level: Option<LabelLevel>,
+        /// 错误码（0 表示成功）
+        code: u32,
+        /// 耗时（微秒）
+        duration_us: u64,
+    },
+    /// 对象处理结束
+    Outcome {
+        /// 对象序号
+        index: usize,
+        /// `success` / `downgraded` / `skipped` / `failed`
+        status: String,
+        /// 失败时的错误码（成功为 0）
+        code: u32,
+    },
+}
+
+/// 将错误映射为跟踪中记录的错误码
+pub fn error_code(error: &AmberlockError) -> u32 {
+    match error {
+        AmberlockError::Win32 { code, .. } => *code,
+        #[cfg(windows)]
+        AmberlockError::Win32Error(e) => e.code().0 as u32,
+        _ => UNKNOWN_ERROR_CODE,
+    }
+}
+
+fn replayed_error(code: u32) -> AmberlockError {
+    AmberlockError::Win32 {
+        code,
+        msg: format!("回放的错误 {}", code),
+    }
+}
+
+// ============================================================================
+// 匿名化
+// ============================================================================
+
+/// 路径匿名化
+///
+/// 每段名称（不区分大小写）经加盐哈希替换为 `L<档位>-<8 位十六进制>` 形式的令牌，
+/// 短小的字母数字扩展名原样保留。盘符、`\\?\` 前缀、`.` 与 `..` 不做替换。
+/// 同一跟踪内使用同一盐值，因此同一名称总是得到同一令牌
+#[derive(Debug, Clone, Copy)]
+pub struct Anonymizer {
+    salt: u64,
+}
+
+impl Anonymizer {
+    /// 使用指定盐值（测试中保证输出确定）
+    pub fn new(salt: u64) -> Self {
+        Self { salt }
+    }
+
+    /// 使用随机盐值，跟踪文件之间的令牌无法相互对照
+    pub fn random() -> Self {
+        Self::new(std::collections::hash_map::RandomState::new().hash_one(std::process::id()))
+    }
+
+    /// 匿名化完整路径（`\` 与 `/` 分隔符原样保留）
+    pub fn anonymize_path(&self, path: &str) -> String {
+        let mut out = String::with_capacity(path.len());
+        let mut previous = "";
+        for piece in path.split_inclusive(['\\', '/']) {
+            let component = piece.trim_end_matches(['\\', '/']);
+            out.push_str(&self.component_token(component, previous));
+            out.push_str(&piece[component.len()..]);
+            previous = component;
+        }
+        out
+    }
+
+    fn component_token(&self, component: &str, previous: &str) -> String {
+        let is_drive = component.len() == 2
+            && component.ends_with(':')
+            && component.as_bytes()[0].is_ascii_alphabetic();
+        let is_unc_marker = previous == "?" && component.eq_ignore_ascii_case("UNC");
+        if component.is_empty()
+            || matches!(component, "." | ".." | "?")
+            || is_drive
+            || is_unc_marker
+        {
+            return component.to_string();
+        }
+        self.anonymize_component(component)
+    }
+
+    /// 匿名化单段名称
+    pub fn anonymize_component(&self, name: &str) -> String {
+        let extension = Path::new(name)
+            .extension()
+            .and_then(|ext| ext.to_str())
+            .filter(|ext| {
+                (1..=5).contains(&ext.len())
+                    && ext.chars().all(|c| c.is_ascii_alphanumeric())
+                    && name.len() > ext.len() + 1
+            })
+            .map(|ext| format!(".{}", ext.to_ascii_lowercase()))
+            .unwrap_or_default();
+        let hash = fnv1a(self.salt, &name.to_lowercase());
+        format!(
+            "L{}-{:08x}{}",
+            length_bucket(name.chars().count()),
+            (hash >> 32) as u32 ^ hash as u32,
+            extension
+        )
+    }
+}
+
+/// 名称长度档位（不超过该值的最小 2 的幂，上限 64）
+fn length_bucket(len: usize) -> usize {
+    len.next_power_of_two().clamp(4, 64)
+}
+
+fn fnv1a(salt: u64, data: &str) -> u64 {
+    let mut hash = 0xcbf2_9ce4_8422_2325u64 ^ salt;
+    for byte in data.bytes() {
+        hash ^= u64::from(byte);
+        hash = hash.wrapping_mul(0x0100_0000_01b3);
+    }
+    hash
+}
+
+// ============================================================================
+// 捕获
+// ============================================================================
+
+/// 跟踪记录器
+///
+/// 写入失败不影响操作本身（跟踪仅用于诊断）；内部写入器自带互斥锁，可在线程间共享
+pub struct TraceRecorder {
+    path: PathBuf,
+    writer: NdjsonWriter,
+    anonymizer: Anonymizer,
+    objects: AtomicUsize,
+}
+
+impl std::fmt::Debug for TraceRecorder {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        f.debug_struct("TraceRecorder")
+            .field("path", &self.path)
+            .field("objects", &self.objects)
+            .finish()
+    }
+}
+
+impl TraceRecorder {
+    /// 创建跟踪文件并写入文件头（使用随机盐值）
+    pub fn create<P: AsRef<Path>>(path: P, header: TraceHeader) -> Result<Self> {
+        Self::with_anonymizer(path, header, Anonymizer::random())
+    }
+
+    /// 创建跟踪文件并写入文件头（指定匿名化器）
+    pub fn with_anonymizer<P: AsRef<Path>>(
+        path: P,
+        header: TraceHeader,
+        anonymizer: Anonymizer,
+    ) -> Result<Self> {
+        let writer = NdjsonWriter::open_append(&path)?;
+        writer.write_record(&TraceEvent::Header(header))?;
+        Ok(Self {
+            path: path.as_ref().to_path_buf(),
+            writer,
+            anonymizer,
+            objects: AtomicUsize::new(0),
+        })
+    }
+
+    /// 跟踪文件路径
+    pub fn path(&self) -> &Path {
+        &self.path
+    }
+
+    /// 记录开始处理一个对象
+    ///
+    /// # 参数
+    /// - `target`: 传给后端的目标路径（与后续调用中的路径一致）
+    pub fn begin_object(&self, target: &str) {
+        let index = self.objects.fetch_add(1, Ordering::SeqCst);
+        self.write(&TraceEvent::Object {
+            index,
+            path: self.anonymizer.anonymize_path(target),
+        });
+    }
+
+    /// 记录对象的处理结果
+    pub fn end_object(&self, outcome: std::result::Result<&LockResult, &AmberlockError>) {
+        let (status, code) = match outcome {
+            Ok(LockResult::Success) => ("success", 0),
+            Ok(LockResult::Downgraded) => ("downgraded", 0),
+            Ok(LockResult::Recovered) => ("recovered", 0),
+            Ok(LockResult::Skipped) => ("skipped", 0),
+            Err(e) => ("failed", error_code(e)),
+        };
+        self.write(&TraceEvent::Outcome {
+            index: self.objects.load(Ordering::SeqCst).saturating_sub(1),
+            status: status.to_string(),
+            code,
+        });
+    }
+
+    /// 记录一次后端调用
+    pub fn record_call(
+        &self,
+        op: LabelOp,
+        target: &str,
+        level: Option<LabelLevel>,
+        code: u32,
+        started: Instant,
+    ) {
+        self.write(&TraceEvent::Call {
+            op,
+            path: self.anonymizer.anonymize_path(target),
+            level,
+            code,
+            duration_us: started.elapsed().as_micros() as u64,
+        });
+    }
+
+    /// 刷新到磁盘
+    pub fn flush(&self) -> Result<()> {
+        Ok(self.writer.flush()?)
+    }
+
+    fn write(&self, event: &TraceEvent) {
+        let _ = self.writer.write_record(event);
+    }
+}
+
+/// 记录每次调用的后端包装
+///
+/// 不转发 `precheck_access`：跟踪期间被拒绝的写入不重试，回放的调用序列与记录一致；
+/// `file_id` 不影响调用序列，直接转发且不记录
+pub struct TracingBackend<'a> {
+    inner: &'a dyn LabelBackend,
+    recorder: &'a TraceRecorder,
+}
+
+impl<'a> TracingBackend<'a> {
+    /// 包装后端，调用经 `recorder` 记录
+    pub fn new(inner: &'a dyn LabelBackend, recorder: &'a TraceRecorder) -> Self {
+        Self { inner, recorder }
+    }
+
+    fn code_of<T>(result: &Result<T>) -> u32 {
+        result.as_ref().err().map_or(0, error_code)
+    }
+}
+
+impl LabelBackend for TracingBackend<'_> {
+    fn get_label(&self, target: &str) -> Result<ObjectLabel> {
+        let started = Instant::now();
+        let result = self.inner.get_label(target);
+        let level = result.as_ref().ok().map(|label| label.level);
+        self.recorder
+            .record_call(LabelOp::Get, target, level, Self::code_of(&result), started);
+        result
+    }
+
+    fn set_label(&self, target: &str, level: LabelLevel) -> Result<()> {
+        let started = Instant::now();
+        let result = self.inner.set_label(target, level);
+        self.recorder.record_call(
+            LabelOp::Set,
+            target,
+            Some(level),
+            Self::code_of(&result),
+            started,
+        );
+        result
+    }
+
+    fn remove_label(&self, target: &str) -> Result<()> {
+        let started = Instant::now();
+        let result = self.inner.remove_label(target);
+        self.recorder.record_call(
+            LabelOp::Remove,
+            target,
+            None,
+            Self::code_of(&result),
+            started,
+        );
+        result
+    }
+
+    fn file_id(&self, target: &str) -> Option<FileId> {
+        self.inner.file_id(target)
+    }
+
+    fn read_descriptor(&self, target: &str) -> Result<String> {
+        self.inner.read_descriptor(target)
+    }
+
+    fn reset_dacl_inheritance(&self, target: &str) -> Result<()> {
+        let started = Instant::now();
+        let result = self.inner.reset_dacl_inheritance(target);
+        self.recorder.record_call(
+            LabelOp::ResetInheritance,
+            target,
+            None,
+            Self::code_of(&result),
+            started,
+        );
+        result
+    }
+
+    fn set_owner(&self, target: &str, owner_sid: &str) -> Result<()> {
+        let started = Instant::now();
+        let result = self.inner.set_owner(target, owner_sid);
+        self.recorder.record_call(
+            LabelOp::SetOwner,
+            target,
+            None,
+            Self::code_of(&result),
+            started,
+        );
+        result
+    }
+}
+
+// ============================================================================
+// 回放
+// ============================================================================
+
+/// 单个对象的上锁结果（与 `ops::lock_with_details` 的判定一致）
+pub(crate) fn lock_outcome(
+    result: Result<()>,
+    effective_level: LabelLevel,
+    desired_level: LabelLevel,
+) -> Result<LockResult> {
+    result.map(|()| {
+        if effective_level != desired_level {
+            LockResult::Downgraded
+        } else {
+            LockResult::Success
+        }
+    })
+}
+
+/// 以指定后端逐个上锁（不写审计日志，供回放与测试使用）
+///
+/// # 参数
+/// - `backend`: 标签后端
+/// - `header`: 操作参数
+/// - `targets`: 目标路径
+/// - `recorder`: 提供时记录对象的开始与结果
+///
+/// # 返回
+/// 批量统计与每个对象处理后的进度（已完成数，失败数）
+pub fn run_lock_batch(
+    backend: &dyn LabelBackend,
+    header: &TraceHeader,
+    targets: &[String],
+    recorder: Option<&TraceRecorder>,
+) -> (BatchResult, Vec<(usize, usize)>) {
+    run_batch(backend, header, targets, recorder, |_| None)
+}
+
+fn run_batch(
+    backend: &dyn LabelBackend,
+    header: &TraceHeader,
+    targets: &[String],
+    recorder: Option<&TraceRecorder>,
+    mut preflight: impl FnMut(usize) -> Option<u32>,
+) -> (BatchResult, Vec<(usize, usize)>) {
+    let tracker = ProgressTracker::new(targets.len());
+    let mut result = BatchResult {
+        total_count: header.total,
+        ..Default::default()
+    };
+    let mut progress = Vec::with_capacity(targets.len());
+
+    for (index, target) in targets.iter().enumerate() {
+        if let Some(recorder) = recorder {
+            recorder.begin_object(target);
+        }
+        let outcome = match preflight(index) {
+            // 原操作在调用后端之前即失败（如保留设备名、特权不足）
+            Some(code) => Err(replayed_error(code)),
+            None => {
+                let change = lock_object(backend, target, header.effective_level);
+                lock_outcome(change.result, header.effective_level, header.desired_level)
+            }
+        };
+        if let Some(recorder) = recorder {
+            recorder.end_object(outcome.as_ref());
+        }
+        accumulate(&mut result, Path::new(target), &outcome);
+        tracker.record(outcome.is_ok());
+        let snapshot = tracker.snapshot();
+        progress.push((snapshot.completed, snapshot.failed));
+    }
+
+    (result, progress)
+}
+
+/// 回放报告
+#[derive(Debug)]
+pub struct ReplayReport {
+    /// 跟踪文件头
+    pub header: TraceHeader,
+    /// 回放得到的批量统计（路径为匿名化路径）
+    pub result: BatchResult,
+    /// 回放中每个对象处理后的进度（已完成数，失败数）
+    pub progress: Vec<(usize, usize)>,
+    /// 跟踪中记录的进度
+    pub recorded_progress: Vec<(usize, usize)>,
+    /// 回放中的后端调用数
+    pub calls: usize,
+    /// 回放调用序列与记录不一致之处
+    pub divergences: Vec<String>,
+}
+
+impl ReplayReport {
+    /// 回放是否完全复现了记录的调用序列与进度
+    pub fn reproduced(&self) -> bool {
+        self.divergences.is_empty() && self.progress == self.recorded_progress
+    }
+}
+
+/// 回放跟踪文件
+///
+/// # 参数
+/// - `trace_path`: 跟踪 NDJSON 文件
+///
+/// # 返回
+/// - `Ok(ReplayReport)`: 回放结果与差异
+/// - `Err(InvalidTrace)`: 文件无法读取、格式错误、缺少文件头或版本不受支持
+///
+/// # 注意
+/// 回放使用记录的结果配置 `FakeBackend`，不访问真实文件系统
+pub fn replay_trace(trace_path: &Path) -> Result<ReplayReport> {
+    let text = std::fs::read_to_string(trace_path)
+        .map_err(|e| AmberlockError::InvalidTrace(format!("读取失败: {}", e)))?;
+    let mut events = Vec::new();
+    for (number, line) in text.lines().enumerate() {
+        if line.trim().is_empty() {
+            continue;
+        }
+        let event: TraceEvent = serde_json::from_str(line)
+            .map_err(|e| AmberlockError::InvalidTrace(format!("第 {} 行: {}", number + 1, e)))?;
+        events.push(event);
+    }
+
+    let header = match events.first() {
+        Some(TraceEvent::Header(header)) if header.version == TRACE_VERSION => header.clone(),
+        Some(TraceEvent::Header(header)) => {
+            return Err(AmberlockError::InvalidTrace(format!(
+                "不支持的版本 {}",
+                header.version
+            )));
+        }
+        _ => return Err(AmberlockError::InvalidTrace("缺少文件头".to_string())),
+    };
+
+    let backend = FakeBackend::new();
+    let mut targets = Vec::new();
+    let mut recorded_calls = Vec::new();
+    let mut object_calls = Vec::new();
+    let mut preflight_failures = Vec::new();
+    let mut recorded_progress = Vec::new();
+    let mut seen = HashSet::new();
+    let (mut completed, mut failed) = (0, 0);
+
+    for event in &events[1..] {
+        match event {
+            TraceEvent::Header(_) => {}
+            TraceEvent::Object { path, .. } => {
+                targets.push(path.clone());
+                object_calls.push(0usize);
+                preflight_failures.push(None);
+            }
+            TraceEvent::Call {
+                op,
+                path,
+                level,
+                code,
+                ..
+            } => {
+                // 首次成功读取的级别即对象的初始标签
+                if *op == LabelOp::Get
+                    && *code == 0
+                    && seen.insert(path.clone())
+                    && let Some(level) = level
+                {
+                    backend.insert_label(path, *level);
+                }
+                backend.push_script(*op, path, (*code != 0).then_some(*code));
+                recorded_calls.push((*op, path.clone()));
+                if let Some(count) = object_calls.last_mut() {
+                    *count += 1;
+                }
+            }
+            TraceEvent::Outcome { status, code, .. } => {
+                completed += 1;
+                if status == "failed" {
+                    failed += 1;
+                    if object_calls.last() == Some(&0)
+                        && let Some(slot) = preflight_failures.last_mut()
+                    {
+                        *slot = Some(*code);
+                    }
+                }
+                recorded_progress.push((completed, failed));
+            }
+        }
+    }
+
+    let (result, progress) = run_batch(&backend, &header, &targets, None, |index| {
+        preflight_failures.get(index).copied().flatten()
+    });
+
+    let replayed_calls = backend.calls();
+    let mut divergences = Vec::new();
+    for (i, (recorded, replayed)) in recorded_calls.iter().zip(&replayed_calls).enumerate() {
+        if recorded != replayed {
+            divergences.push(format!(
+                "第 {} 次调用不一致：记录为 {:?} {}，回放为 {:?} {}",
+                i + 1,
+                recorded.0,
+                recorded.1,
+                replayed.0,
+                replayed.1
+            ));
+        }
+    }
+    if recorded_calls.len() != replayed_calls.len() {
+        divergences.push(format!(
+            "调用次数不一致：记录 {} 次，回放 {} 次",
+            recorded_calls.len(),
+            replayed_calls.len()
+        ));
+    }
+
+    Ok(ReplayReport {
+        header,
+        result,
+        progress,
+        recorded_progress,
+        calls: replayed_calls.len(),
+        divergences,
+    })
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use tempfile::TempDir;
+
+    const ORIGINAL_PATHS: [&str; 5] = [
+        "D:\\Projekt\\Quartalsbericht\\Gehaltsliste.xlsx",
+        "D:\\Projekt\\Quartalsbericht\\Notizen.txt",
+        "D:\\Projekt\\Privat\\Tagebuch.docx",
+        "\\\\?\\D:\\Projekt\\Privat\\trailing. ",
+        "\\\\nas-server\\Personal\\Vertrag.pdf",
+    ];
+
+    fn capture(
+        dir: &TempDir,
+        backend: &FakeBackend,
+    ) -> (PathBuf, BatchResult, Vec<(usize, usize)>) {
+        let trace_path = dir.path().join("trace.ndjson");
+        let header = TraceHeader::new(
+            ProtectMode::ReadOnly,
+            LabelLevel::System,
+            LabelLevel::High,
+            ORIGINAL_PATHS.len(),
+        );
+        let recorder =
+            TraceRecorder::with_anonymizer(&trace_path, header.clone(), Anonymizer::new(7))
+                .expect("创建跟踪文件失败");
+        let traced = TracingBackend::new(backend, &recorder);
+        let targets: Vec<String> = ORIGINAL_PATHS.iter().map(|p| p.to_string()).collect();
+        let (result, progress) = run_lock_batch(&traced, &header, &targets, Some(&recorder));
+        recorder.flush().unwrap();
+        (trace_path, result, progress)
+    }
+
+    #[test]
+    fn test_capture_anonymize_replay_round_trip() {
+        let dir = TempDir::new().expect("创建临时目录失败");
+        let backend = FakeBackend::new().with_label(ORIGINAL_PATHS[2], LabelLevel::High);
+        backend.fail_next(LabelOp::Set, ORIGINAL_PATHS[1], 5);
+        backend.fail_next(LabelOp::Get, ORIGINAL_PATHS[4], 53);
+        backend.fail_next(LabelOp::Set, ORIGINAL_PATHS[4], 53);
+
+        let (trace_path, captured, captured_progress) = capture(&dir, &backend);
+        assert_eq!(captured.failed_count, 2);
+        assert_eq!(captured.downgraded_count, 3);
+
+        let report = replay_trace(&trace_path).expect("回放失败");
+        assert!(
+            report.reproduced(),
+            "回放应完全复现: {:?}",
+            report.divergences
+        );
+        assert_eq!(report.progress, captured_progress);
+        assert_eq!(report.recorded_progress, captured_progress);
+        assert_eq!(report.calls, backend.calls().len());
+
+        let replayed = &report.result;
+        assert_eq!(replayed.success_count, captured.success_count);
+        assert_eq!(replayed.failed_count, captured.failed_count);
+        assert_eq!(replayed.downgraded_count, captured.downgraded_count);
+        assert_eq!(replayed.skipped_count, captured.skipped_count);
+        assert_eq!(replayed.total_count, captured.total_count);
+        assert_eq!(replayed.outcome(), captured.outcome());
+        let errors = |result: &BatchResult| {
+            result
+                .failures
+                .iter()
+                .map(|f| f.error.clone())
+                .collect::<Vec<_>>()
+        };
+        assert_eq!(errors(replayed), errors(&captured));
+        println!("✅ 捕获 → 匿名化 → 回放 复现了相同的统计与进度");
+    }
+
+    #[test]
+    fn test_trace_contains_no_original_names() {
+        let dir = TempDir::new().expect("创建临时目录失败");
+        let (trace_path, _, _) = capture(&dir, &FakeBackend::new());
+        let text = std::fs::read_to_string(&trace_path).unwrap();
+
+        for path in ORIGINAL_PATHS {
+            for component in path.split('\\') {
+                let stem = component.split('.').next().unwrap_or_default();
+                if stem.len() >= 3 {
+                    assert!(!text.contains(stem), "跟踪文件泄露了名称: {}", stem);
+                }
+            }
+        }
+        assert!(text.contains(".xlsx"), "扩展名应保留");
+        println!("✅ 跟踪文件中不含任何原始名称");
+    }
+
+    #[test]
+    fn test_anonymizer_preserves_structure() {
+        let anonymizer = Anonymizer::new(42);
+        let a = anonymizer.anonymize_path("C:\\Users\\Alice\\Documents\\report.docx");
+        let b = anonymizer.anonymize_path("C:\\Users\\ALICE\\Pictures");
+
+        let parts_a: Vec<&str> = a.split('\\').collect();
+        let parts_b: Vec<&str> = b.split('\\').collect();
+        assert_eq!(parts_a.len(), 5, "应保留层级深度");
+        assert_eq!(parts_a[0], "C:");
+        assert_eq!(parts_a[1], parts_b[1], "同一名称应得到同一令牌");
+        assert_eq!(parts_a[2], parts_b[2], "名称比较不区分大小写");
+        assert_ne!(parts_a[3], parts_b[3]);
+        assert!(parts_a[4].ends_with(".docx"));
+        assert!(
+            parts_a[2].starts_with("L8-"),
+            "Alice 属于长度档位 8: {}",
+            parts_a[2]
+        );
+
+        let verbatim = anonymizer.anonymize_path("\\\\?\\UNC\\server\\share");
+        assert!(verbatim.starts_with("\\\\?\\UNC\\L8-"));
+        assert_eq!(verbatim.split('\\').count(), 6);
+
+        // 不同盐值的令牌不同
+        assert_ne!(
+            Anonymizer::new(1).anonymize_component("Alice"),
+            Anonymizer::new(2).anonymize_component("Alice")
+        );
+    }
+
+    #[test]
+    fn test_replay_reports_preflight_failures_and_bad_header() {
+        let dir = TempDir::new().expect("创建临时目录失败");
+        let path = dir.path().join("trace.ndjson");
+        let recorder = TraceRecorder::with_anonymizer(
+            &path,
+            TraceHeader::new(ProtectMode::ReadOnly, LabelLevel::High, LabelLevel::High, 1),
+            Anonymizer::new(1),
+        )
+        .unwrap();
+        recorder.begin_object("C:\\NUL");
+        recorder.end_object(Err(&AmberlockError::ReservedName("C:\\NUL".into())));
+        recorder.flush().unwrap();
+
+        let report = replay_trace(&path).expect("回放失败");
+        assert!(report.reproduced());
+        assert_eq!(report.calls, 0, "前置失败的对象不应调用后端");
+        assert_eq!(report.result.failed_count, 1);
+
+        let bad = dir.path().join("bad.ndjson");
+        std::fs::write(&bad, "{\"event\":\"object\",\"index\":0,\"path\":\"x\"}\n").unwrap();
+        assert!(replay_trace(&bad).is_err());
+    }
+}
@@ -0,0 +1,621 @@
+//! 分块、可续传的保护验证（漂移扫描）
+//!
+//! 对大型目录树一次性重新读取所有标签需要数小时，中断后只能从头再来。
+//! `verify_protection_chunked` 按路径排序后分块读取标签，每处理完一块：
+//! - 把发现的漂移立即写入操作日志（状态为 `DRIFT_STATUS`）
+//! - 把游标与各路径的最近验证时间原子写入状态文件
+//!
+//! 下次调用从游标处继续，直到本轮覆盖全部路径；随后的调用开始新一轮。
+//! 设置 `only_changed_since` 后，上次验证后未被修改的文件直接跳过。
+
+use crate::backend::LabelBackend;
+use crate::build_info::APP_VERSION;
+use crate::inventory;
+use crate::moves::{self, MovePair};
+use crate::pathutil;
+use crate::progress::CancelToken;
+use amberlock_storage::{AtomicWriteOptions, OperationLog, atomic_write, parse_utc};
+use amberlock_types::{
+    AmberlockError, FileId, LabelLevel, LockRecord, OperationOrigin, ProtectMode, Result,
+    TargetKind,
+};
+use serde::{Deserialize, Serialize};
+use std::collections::{BTreeMap, HashSet};
+use std::fmt::{Display, Formatter};
+use std::path::{Path, PathBuf};
+use std::time::SystemTime;
+use time::OffsetDateTime;
+
+/// 漂移记录的日志状态
+pub const DRIFT_STATUS: &str = "drift_detected";
+/// 默认每块路径数
+pub const DEFAULT_VERIFY_CHUNK: usize = 1000;
+/// 状态文件格式版本
+pub const VERIFY_STATE_VERSION: u32 = 1;
+
+/// 一个应受保护的对象
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct ExpectedLabel {
+    /// 对象路径（与日志记录一致）
+    pub path: String,
+    /// 对象类型
+    pub kind: TargetKind,
+    /// 上锁时的保护模式
+    pub mode: ProtectMode,
+    /// 期望的完整性级别
+    pub level: LabelLevel,
+    /// 上锁时的文件标识（旧记录为 None，见 `moves` 模块）
+    pub file_id: Option<FileId>,
+}
+
+/// 验证范围
+#[derive(Debug, Clone)]
+pub enum VerifyScope {
+    /// 由审计日志推导：每个路径最后一次上锁成功且之后未解锁
+    Log(PathBuf),
+    /// 显式给出的对象列表
+    Entries(Vec<ExpectedLabel>),
+    /// 保护清单（附属文件不可用时从审计日志重建，见 `inventory` 模块）
+    Inventory(PathBuf),
+}
+
+impl VerifyScope {
+    /// 按路径排序、去重后的期望列表（同一路径以最后一项为准）
+    pub fn expected(&self) -> Result<Vec<ExpectedLabel>> {
+        let entries = match self {
+            VerifyScope::Log(log_path) => return expected_from_log(log_path),
+            VerifyScope::Inventory(log_path) => {
+                let path = inventory::inventory_path(log_path);
+                let (inventory, _) = inventory::load_or_rebuild(path, log_path)?;
+                return Ok(inventory.expected());
+            }
+            VerifyScope::Entries(entries) => entries.clone(),
+        };
+        let mut by_path: BTreeMap<String, ExpectedLabel> = BTreeMap::new();
+        for entry in entries {
+            by_path.insert(entry.path.clone(), entry);
+        }
+        Ok(by_path.into_values().collect())
+    }
+}
+
+/// 从审计日志推导应受保护的对象
+///
+/// # 返回
+/// 按路径排序的列表；路径的最后一条 `success` 记录给出期望级别，
+/// 其后出现 `unlocked` 记录的路径不再包含在内，
+/// `relocated` 记录把原路径的期望转移到新路径（见 `moves::record_relocations`）
+pub fn expected_from_log(log_path: impl AsRef<Path>) -> Result<Vec<ExpectedLabel>> {
+    Ok(inventory::rebuild_inventory_from_log(log_path)?.expected())
+}
+
+/// 分块验证选项
+#[derive(Debug, Clone)]
+pub struct VerifyOptions {
+    /// 每块路径数（每块结束后保存一次状态）
+    pub chunk_size: usize,
+    /// 状态文件路径
+    pub state_path: PathBuf,
+    /// 跳过未修改文件的时间下限（RFC 3339）
+    ///
+    /// 设置后，若路径的最近验证不早于该时间、且文件修改时间早于最近验证，
+    /// 则本轮跳过该路径；为 None 时每轮都读取全部路径
+    pub only_changed_since: Option<String>,
+    /// 本次调用最多处理的块数（为 None 时处理到本轮结束）
+    pub max_chunks: Option<usize>,
+}
+
+impl VerifyOptions {
+    /// 使用默认块大小创建选项
+    pub fn new(state_path: impl Into<PathBuf>) -> Self {
+        Self {
+            chunk_size: DEFAULT_VERIFY_CHUNK,
+            state_path: state_path.into(),
+            only_changed_since: None,
+            max_chunks: None,
+        }
+    }
+}
+
+/// 持久化的验证进度
+#[derive(Debug, Clone, Default, PartialEq, Eq, Serialize, Deserialize)]
+pub struct VerifyState {
+    /// 格式版本
+    pub version: u32,
+    /// 已完成的轮数
+    pub passes_completed: u64,
+    /// 本轮最后处理的路径（为 None 时从头开始）
+    pub cursor: Option<String>,
+    /// 本轮已发现的漂移数
+    pub pass_drifted: usize,
+    /// 各路径最近一次成功读取标签的时间（RFC 3339）
+    pub last_verified: BTreeMap<String, String>,
+}
+
+impl VerifyState {
+    /// 读取状态文件
+    ///
+    /// # 返回
+    /// 文件不存在时返回初始状态
+    pub fn load(path: &Path) -> Result<Self> {
+        let bytes = match std::fs::read(path) {
+            Ok(bytes) => bytes,
+            Err(e) if e.kind() == std::io::ErrorKind::NotFound => {
+                return Ok(Self {
+                    version: VERIFY_STATE_VERSION,
+                    ..Self::default()
+                });
+            }
+            Err(e) => return Err(AmberlockError::Storage(e.into())),
+        };
+        let state: Self =
+            serde_json::from_slice(&bytes).map_err(|e| AmberlockError::Storage(e.into()))?;
+        if state.version != VERIFY_STATE_VERSION {
+            let message = format!("不支持的验证状态版本: {}", state.version);
+            return Err(AmberlockError::Storage(
+                std::io::Error::new(std::io::ErrorKind::InvalidData, message).into(),
+            ));
+        }
+        Ok(state)
+    }
+
+    /// 原子写入状态文件
+    pub fn save(&self, path: &Path) -> Result<()> {
+        let bytes = serde_json::to_vec(self).map_err(|e| AmberlockError::Storage(e.into()))?;
+        atomic_write(path, &bytes, &AtomicWriteOptions::default())?;
+        Ok(())
+    }
+}
+
+/// 一次分块验证调用的结果
+#[derive(Debug, Clone, Default, PartialEq, Eq)]
+pub struct VerifyProgress {
+    /// 本轮的路径总数
+    pub total: usize,
+    /// 本轮已经过的路径数（含之前的调用）
+    pub completed: usize,
+    /// 本次调用读取了标签的路径数
+    pub checked: usize,
+    /// 本次调用因未修改而跳过的路径数
+    pub skipped_unchanged: usize,
+    /// 本次调用无法读取标签的路径数（含 `missing_paths`）
+    pub failed: usize,
+    /// 本次调用发现已不存在的路径（文件或路径不存在）
+    pub missing_paths: Vec<String>,
+    /// 已确认移动的对象（由 `fold_moves` 从 `missing_paths` 中移出）
+    pub moved: Vec<MovePair>,
+    /// 本次调用发现的漂移路径
+    pub drifted_paths: Vec<String>,
+    /// 本轮累计发现的漂移数
+    pub pass_drifted: usize,
+    /// 本次调用是否完成了一整轮
+    pub pass_complete: bool,
+}
+
+impl VerifyProgress {
+    /// 本轮完成比例（0.0 - 1.0，没有路径时为 1.0）
+    pub fn fraction(&self) -> f64 {
+        if self.total == 0 {
+            1.0
+        } else {
+            self.completed as f64 / self.total as f64
+        }
+    }
+
+    /// 把已确认的移动从“不存在”中移出，单独归类
+    ///
+    /// # 参数
+    /// - `moves`: 已确认的移动（如 `MoveReport::confirmed` 或用户接受的配对）
+    ///
+    /// # 注意
+    /// 原路径不在 `missing_paths` 中的配对被忽略
+    pub fn fold_moves(&mut self, moves: &[MovePair]) {
+        for pair in moves {
+            let Some(index) = self.missing_paths.iter().position(|p| *p == pair.from()) else {
+                continue;
+            };
+            self.missing_paths.remove(index);
+            self.failed = self.failed.saturating_sub(1);
+            self.moved.push(pair.clone());
+        }
+    }
+
+    /// 状态栏文本（如 `已验证 38%（可随时中断）`）
+    pub fn status_line(&self) -> String {
+        if self.pass_complete {
+            let moved = if self.moved.is_empty() {
+                String::new()
+            } else {
+                format!("，{} 个对象已移动", self.moved.len())
+            };
+            format!(
+                "验证完成：共 {} 个对象，发现 {} 处漂移{}",
+                self.total, self.pass_drifted, moved
+            )
+        } else {
+            format!("已验证 {:.0}%（可随时中断）", self.fraction() * 100.0)
+        }
+    }
+}
+
+impl Display for VerifyProgress {
+    fn fmt(&self, f: &mut Formatter<'_>) -> std::fmt::Result {
+        write!(f, "{}", self.status_line())
+    }
+}
+
+/// 分块验证受保护对象的标签，可中断并续传
+///
+/// # 参数
+/// - `backend`: 标签读取后端
+/// - `scope`: 验证范围
+/// - `logger`: 写入漂移记录的操作日志（同时提供验证时间）
+/// - `user_sid`: 用户 SID
+/// - `options`: 分块选项
+/// - `cancel`: 取消令牌（在块之间检查）
+///
+/// # 返回
+/// 本次调用的进度；状态文件已更新到最后一个完成的块
+///
+/// # 注意
+/// 漂移记录先于状态写入磁盘，进程恰好在两者之间退出时，
+/// 该块的漂移会在续传时再记录一次
+pub fn verify_protection_chunked(
+    backend: &dyn LabelBackend,
+    scope: &VerifyScope,
+    logger: &OperationLog,
+    user_sid: &str,
+    options: &VerifyOptions,
+    cancel: Option<&CancelToken>,
+) -> Result<VerifyProgress> {
+    let expected = scope.expected()?;
+    let mut state = VerifyState::load(&options.state_path)?;
+    let since = options.only_changed_since.as_deref().and_then(parse_utc);
+    let chunk_size = options.chunk_size.max(1);
+
+    let mut position = match &state.cursor {
+        Some(cursor) => expected.partition_point(|entry| entry.path.as_str() <= cursor.as_str()),
+        None => 0,
+    };
+    let mut progress = VerifyProgress {
+        total: expected.len(),
+        ..VerifyProgress::default()
+    };
+    let mut chunks = 0;
+
+    while position < expected.len() {
+        if cancel.is_some_and(CancelToken::is_cancelled)
+            || options.max_chunks.is_some_and(|max| chunks >= max)
+        {
+            break;
+        }
+
+        let end = (position + chunk_size).min(expected.len());
+        for entry in &expected[position..end] {
+            if since.is_some_and(|since| is_unchanged(&state, entry, since)) {
+                progress.skipped_unchanged += 1;
+                continue;
+            }
+            let target = pathutil::canonical_path(Path::new(&entry.path));
+            match backend.get_label(&target.to_string_lossy()) {
+                Ok(label) => {
+                    progress.checked += 1;
+                    let verified_at = logger.stamper().now();
+                    if label.level != entry.level {
+                        logger.append(&drift_record(logger, user_sid, entry, &label))?;
+                        progress.drifted_paths.push(entry.path.clone());
+                        state.pass_drifted += 1;
+                    }
+                    state.last_verified.insert(entry.path.clone(), verified_at);
+                }
+                Err(e) => {
+                    progress.failed += 1;
+                    if moves::is_not_found(&e) {
+                        progress.missing_paths.push(entry.path.clone());
+                    }
+                }
+            }
+        }
+
+        position = end;
+        chunks += 1;
+        state.cursor = Some(expected[end - 1].path.clone());
+        logger.flush()?;
+        state.save(&options.state_path)?;
+    }
+
+    progress.completed = position;
+    progress.pass_drifted = state.pass_drifted;
+    if position >= expected.len() {
+        finish_pass(&mut state, &expected);
+        state.save(&options.state_path)?;
+        progress.pass_complete = true;
+    }
+    Ok(progress)
+}
+
+/// 结束一轮：重置游标，清理已不在范围内的路径
+fn finish_pass(state: &mut VerifyState, expected: &[ExpectedLabel]) {
+    let current: HashSet<&str> = expected.iter().map(|e| e.path.as_str()).collect();
+    state
+        .last_verified
+        .retain(|path, _| current.contains(path.as_str()));
+    state.cursor = None;
+    state.pass_drifted = 0;
+    state.passes_completed += 1;
+}
+
+/// 上次验证后文件是否未被修改
+fn is_unchanged(state: &VerifyState, entry: &ExpectedLabel, since: OffsetDateTime) -> bool {
+    let Some(verified) = state
+        .last_verified
+        .get(&entry.path)
+        .and_then(|t| parse_utc(t))
+    else {
+        return false;
+    };
+    if verified < since {
+        return false;
+    }
+    match modified_time(Path::new(&entry.path)) {
+        Some(modified) => modified < verified,
+        None => false,
+    }
+}
+
+/// 文件修改时间（无法读取时为 None）
+fn modified_time(path: &Path) -> Option<OffsetDateTime> {
+    let modified: SystemTime = std::fs::metadata(path).ok()?.modified().ok()?;
+    Some(OffsetDateTime::from(modified))
+}
+
+/// 构造漂移记录
+fn drift_record(
+    logger: &OperationLog,
+    user_sid: &str,
+    entry: &ExpectedLabel,
+    found: &crate::backend::ObjectLabel,
+) -> LockRecord {
+    LockRecord {
+        id: logger.stamper().new_id(),
+        path: entry.path.clone(),
+        kind: entry.kind,
+        mode: entry.mode,
+        level_applied: found.level,
+        time_utc: logger.stamper().now(),
+        seq_no: logger.stamper().next_seq(),
+        user_sid: user_sid.to_string(),
+        owner_before: None,
+        sddl_before: None,
+        sddl_after: Some(found.sddl.clone().into()),
+        status: DRIFT_STATUS.to_string(),
+        errors: vec![],
+        details: Some(format!(
+            "expected {:?}, found {:?}",
+            entry.level, found.level
+        )),
+        authorized_by: None,
+        comment: None,
+        batch_id: None,
+        volume_root_acknowledged: false,
+        downgrade_reason: None,
+        origin: OperationOrigin::Verification,
+        app_version: Some(APP_VERSION.to_string()),
+        file_id: None,
+        warnings: vec![],
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::backend::{FakeBackend, LabelOp};
+    use amberlock_storage::{FixedStamper, NdjsonReader, SequenceStamper};
+    use std::collections::HashMap;
+    use std::sync::Arc;
+    use tempfile::TempDir;
+
+    fn entry(path: &str, level: LabelLevel) -> ExpectedLabel {
+        ExpectedLabel {
+            path: path.to_string(),
+            kind: TargetKind::File,
+            mode: ProtectMode::ReadOnly,
+            level,
+            file_id: None,
+        }
+    }
+
+    fn drift_records(log_path: &Path) -> Vec<LockRecord> {
+        let mut reader = NdjsonReader::open(log_path).expect("打开日志失败");
+        reader
+            .read_last_n(usize::MAX)
+            .unwrap()
+            .into_iter()
+            .map(|value| serde_json::from_value::<LockRecord>(value).unwrap())
+            .filter(|record| record.status == DRIFT_STATUS)
+            .collect()
+    }
+
+    #[test]
+    fn test_expected_from_log() {
+        let temp_dir = TempDir::new().expect("创建临时目录失败");
+        let log_path = temp_dir.path().join("log.ndjson");
+        let logger = OperationLog::open(&log_path).expect("打开日志失败");
+        let mut record = drift_record(
+            &logger,
+            "S-1-5-21-test",
+            &entry(r"C:\b.txt", LabelLevel::High),
+            &crate::backend::ObjectLabel {
+                sddl: String::new(),
+                level: LabelLevel::High,
+            },
+        );
+        for (path, status, level) in [
+            (r"C:\b.txt", "success", LabelLevel::High),
+            (r"C:\a.txt", "success", LabelLevel::High),
+            (r"C:\a.txt", "success", LabelLevel::System),
+            (r"C:\b.txt", "unlocked", LabelLevel::Medium),
+            (r"C:\c.txt", "error", LabelLevel::High),
+        ] {
+            record.path = path.to_string();
+            record.status = status.to_string();
+            record.level_applied = level;
+            logger.append(&record).unwrap();
+        }
+        logger.flush().unwrap();
+
+        let expected = expected_from_log(&log_path).expect("推导期望失败");
+        assert_eq!(expected, vec![entry(r"C:\a.txt", LabelLevel::System)]);
+    }
+
+    #[test]
+    fn test_interrupted_passes_verify_each_path_once() {
+        let temp_dir = TempDir::new().expect("创建临时目录失败");
+        let log_path = temp_dir.path().join("log.ndjson");
+        let logger = OperationLog::open(&log_path)
+            .expect("打开日志失败")
+            .with_stamper(Arc::new(SequenceStamper::new("2025-01-01T00:00:00Z")));
+        let backend = FakeBackend::new();
+        let mut entries = Vec::new();
+        for i in 0..25 {
+            let path = format!(r"C:\archive\{:02}.dat", i);
+            // 第 3、11、19 个对象的标签已被移除
+            if i % 8 != 3 {
+                backend.insert_label(&path, LabelLevel::High);
+            }
+            entries.push(entry(&path, LabelLevel::High));
+        }
+        backend.fail_next(LabelOp::Get, r"C:\archive\07.dat", 5);
+        let scope = VerifyScope::Entries(entries);
+        let options = VerifyOptions {
+            chunk_size: 4,
+            max_chunks: Some(2),
+            ..VerifyOptions::new(temp_dir.path().join("verify.json"))
+        };
+
+        let mut runs = Vec::new();
+        loop {
+            let progress = verify_protection_chunked(
+                &backend,
+                &scope,
+                &logger,
+                "S-1-5-21-test",
+                &options,
+                None,
+            )
+            .expect("验证失败");
+            let done = progress.pass_complete;
+            runs.push(progress);
+            if done {
+                break;
+            }
+        }
+
+        assert_eq!(runs.len(), 4, "25 个对象每次 8 个，应在第 4 次调用完成");
+        assert_eq!(runs[0].status_line(), "已验证 32%（可随时中断）");
+        assert!(runs.windows(2).all(|w| w[0].completed < w[1].completed));
+        let mut reads: HashMap<String, usize> = HashMap::new();
+        for (_, path) in backend.calls() {
+            *reads.entry(path).or_default() += 1;
+        }
+        assert_eq!(reads.len(), 25);
+        assert!(reads.values().all(|&n| n == 1), "每轮每个路径只应读取一次");
+        assert_eq!(runs.iter().map(|r| r.failed).sum::<usize>(), 1);
+
+        let drifted: Vec<String> = drift_records(&log_path)
+            .into_iter()
+            .map(|r| r.path)
+            .collect();
+        assert_eq!(
+            drifted,
+            vec![
+                r"C:\archive\03.dat".to_string(),
+                r"C:\archive\11.dat".to_string(),
+                r"C:\archive\19.dat".to_string(),
+            ]
+        );
+        assert_eq!(runs[3].pass_drifted, 3);
+
+        // 下一次调用开始新一轮
+        let state = VerifyState::load(&options.state_path).unwrap();
+        assert_eq!((state.passes_completed, state.cursor), (1, None));
+        let next =
+            verify_protection_chunked(&backend, &scope, &logger, "S-1-5-21-test", &options, None)
+                .unwrap();
+        assert_eq!(next.completed, 8);
+        println!("✅ 分块验证可中断续传，每个路径只验证一次，漂移只记录一次");
+    }
+
+    #[test]
+    fn test_cancel_stops_between_chunks() {
+        let temp_dir = TempDir::new().expect("创建临时目录失败");
+        let logger = OperationLog::open(temp_dir.path().join("log.ndjson")).unwrap();
+        let scope = VerifyScope::Entries(vec![entry(r"C:\a", LabelLevel::Medium)]);
+        let cancel = CancelToken::new();
+        cancel.cancel();
+
+        let progress = verify_protection_chunked(
+            &FakeBackend::new(),
+            &scope,
+            &logger,
+            "S-1-5-21-test",
+            &VerifyOptions::new(temp_dir.path().join("verify.json")),
+            Some(&cancel),
+        )
+        .unwrap();
+        assert_eq!((progress.completed, progress.pass_complete), (0, false));
+    }
+
+    #[test]
+    fn test_since_filter_skips_unmodified_files() {
+        let temp_dir = TempDir::new().expect("创建临时目录失败");
+        let logger = OperationLog::open(temp_dir.path().join("log.ndjson"))
+            .expect("打开日志失败")
+            .with_stamper(Arc::new(FixedStamper::new("2025-01-01T00:00:00Z", "id")));
+        let backend = FakeBackend::new();
+        let mut entries = Vec::new();
+        for name in ["changed.txt", "untouched.txt"] {
+            let path = temp_dir.path().join(name);
+            std::fs::write(&path, "data").unwrap();
+            backend.insert_label(&path.to_string_lossy(), LabelLevel::High);
+            entries.push(entry(&path.to_string_lossy(), LabelLevel::High));
+        }
+        let scope = VerifyScope::Entries(entries);
+        let options = VerifyOptions {
+            only_changed_since: Some("2024-06-01T00:00:00Z".to_string()),
+            ..VerifyOptions::new(temp_dir.path().join("verify.json"))
+        };
+
+        let first =
+            verify_protection_chunked(&backend, &scope, &logger, "S-1-5-21-test", &options, None)
+                .unwrap();
+        assert_eq!((first.checked, first.skipped_unchanged), (2, 0));
+
+        let set_modified = |name: &str, secs: u64| {
+            let file = std::fs::File::options()
+                .write(true)
+                .open(temp_dir.path().join(name))
+                .unwrap();
+            file.set_modified(SystemTime::UNIX_EPOCH + std::time::Duration::from_secs(secs))
+                .unwrap();
+        };
+        set_modified("changed.txt", 1_893_456_000); // 2030-01-01
+        set_modified("untouched.txt", 1_577_836_800); // 2020-01-01
+
+        let second =
+            verify_protection_chunked(&backend, &scope, &logger, "S-1-5-21-test", &options, None)
+                .unwrap();
+        assert_eq!((second.checked, second.skipped_unchanged), (1, 1));
+
+        // 最近验证早于时间下限时不跳过
+        let options = VerifyOptions {
+            only_changed_since: Some("2025-06-01T00:00:00Z".to_string()),
+            ..options
+        };
+        let third =
+            verify_protection_chunked(&backend, &scope, &logger, "S-1-5-21-test", &options, None)
+                .unwrap();
+        assert_eq!((third.checked, third.skipped_unchanged), (2, 0));
+        println!("✅ 未修改的文件在后续验证中被跳过");
+    }
+}
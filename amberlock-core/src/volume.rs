@@ -0,0 +1,396 @@
+//! 卷根策略
+//!
+//! 为卷根（`C:\` 等）设置完整性标签会影响整个卷上新建的对象，
+//! 可能导致系统更新、安装程序与系统服务失败。策略集中在 core 层，
+//! 单对象上锁（以及基于它的批量、通配符、预设入口）都经过这里：
+//! - 卷根只允许只读模式（仅 NW 策略）
+//! - 调用方必须在用户确认后设置 `LockOptions::acknowledged_volume_root`
+//! - 请求 System 级别时自动降为 High（`DowngradeReason::VolumeRootPolicy`）
+//!
+//! 确认与降级原因都写入日志记录，便于审计。
+
+use crate::backend::LabelBackend;
+use crate::interference::{self, INTERFERENCE_RETRY_DELAY, Interference};
+use crate::pathutil::is_volume_root;
+use crate::seal;
+use crate::{LockOptions, LockOutcome, LockResult, OperationContext};
+use amberlock_types::{AmberlockError, DowngradeReason, LabelLevel, ProtectMode, Result, Warning};
+use std::path::Path;
+
+/// 卷根策略的判定结果
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum VolumeRootPolicy {
+    /// 目标不是卷根，不受限制
+    NotRoot,
+    /// 目标是已确认的卷根，按 `level` 上锁
+    Root {
+        /// 实际使用的级别
+        level: LabelLevel,
+        /// 策略降级时的原因
+        downgrade: Option<DowngradeReason>,
+    },
+}
+
+impl VolumeRootPolicy {
+    /// 判定单个对象是否允许上锁
+    ///
+    /// # 参数
+    /// - `path`: 目标路径
+    /// - `mode`: 保护模式
+    /// - `level`: 有效完整性级别
+    /// - `acknowledged`: 用户是否已确认锁定卷根
+    ///
+    /// # 返回
+    /// - `Err(VolumeRootModeNotAllowed)`: 卷根使用了只读以外的模式
+    /// - `Err(VolumeRootNotAcknowledged)`: 卷根未经确认
+    pub fn evaluate(
+        path: &Path,
+        mode: ProtectMode,
+        level: LabelLevel,
+        acknowledged: bool,
+    ) -> Result<Self> {
+        if !is_volume_root(path) {
+            return Ok(VolumeRootPolicy::NotRoot);
+        }
+        let display = path.to_string_lossy().to_string();
+        if mode != ProtectMode::ReadOnly {
+            return Err(AmberlockError::VolumeRootModeNotAllowed(display));
+        }
+        if !acknowledged {
+            return Err(AmberlockError::VolumeRootNotAcknowledged(display));
+        }
+        Ok(match level {
+            LabelLevel::System => VolumeRootPolicy::Root {
+                level: LabelLevel::High,
+                downgrade: Some(DowngradeReason::VolumeRootPolicy),
+            },
+            _ => VolumeRootPolicy::Root {
+                level,
+                downgrade: None,
+            },
+        })
+    }
+
+    /// 实际使用的级别
+    pub fn level(self, requested: LabelLevel) -> LabelLevel {
+        match self {
+            VolumeRootPolicy::NotRoot => requested,
+            VolumeRootPolicy::Root { level, .. } => level,
+        }
+    }
+}
+
+/// 按卷根策略上锁单个对象并写入日志
+///
+/// # 参数
+/// - `backend`: 标签后端
+/// - `ctx`: 操作上下文
+/// - `opts`: 锁定选项
+/// - `effective_level`: 按特权计算出的有效级别
+/// - `check_privileges`: 按策略确定的级别检查特权
+///
+/// # 注意
+/// - 策略拒绝的对象写入一条 `error` 记录后返回错误，不调用后端
+/// - 写入被拒绝且权限预检通过时重试一次（见 `interference` 模块）
+/// - 重试后成功、写入后未能读回标签时，结果附带相应的警告
+pub(crate) fn lock_with_policy(
+    backend: &dyn LabelBackend,
+    ctx: OperationContext<'_>,
+    opts: &LockOptions,
+    effective_level: LabelLevel,
+    check_privileges: impl FnOnce(LabelLevel) -> Result<()>,
+) -> Result<LockOutcome> {
+    let policy = match VolumeRootPolicy::evaluate(
+        Path::new(&ctx.path_str),
+        opts.mode,
+        effective_level,
+        opts.acknowledged_volume_root,
+    ) {
+        Ok(policy) => policy,
+        Err(e) => {
+            ctx.log_and_track(
+                opts.mode,
+                effective_level,
+                None,
+                None,
+                "error",
+                vec![e.to_string()],
+            );
+            return Err(e);
+        }
+    };
+    let level = policy.level(effective_level);
+    check_privileges(level)?;
+
+    let downgrade = match policy {
+        VolumeRootPolicy::Root {
+            downgrade: Some(reason),
+            ..
+        } => Some(reason),
+        _ => (level != opts.desired_level).then_some(DowngradeReason::MissingRelabelPrivilege),
+    };
+    let ctx = ctx.with_policy(policy != VolumeRootPolicy::NotRoot, downgrade);
+
+    // 封印计划：描述符自预览后被修改时放弃写入
+    if let Some(plan) = opts.seal_plan_for(&ctx.path_str)
+        && let Err(e) = seal::check_plan(backend, plan, &ctx.target, level)
+    {
+        ctx.log_and_track(opts.mode, level, None, None, "error", vec![e.to_string()]);
+        return Err(e);
+    }
+
+    let (change, verdict) =
+        interference::lock_with_retry(backend, &ctx.target, level, INTERFERENCE_RETRY_DELAY);
+    match change.result {
+        Ok(()) => {
+            let recovered = verdict == Interference::Recovered;
+            let mut warnings = Vec::new();
+            if recovered {
+                warnings.push(Warning::RetriedAfterTransientError { attempts: 2 });
+            }
+            if change.after.is_none() {
+                warnings.push(Warning::VerificationSkipped);
+            }
+            let file_id = backend.file_id(&ctx.target);
+            let ctx = ctx.with_file_id(file_id).with_warnings(warnings);
+            ctx.log_and_track(
+                opts.mode,
+                level,
+                change.before.map(|s| s.sddl),
+                change.after.map(|s| s.sddl),
+                "success",
+                vec![],
+            );
+            // 同时降级时以降级为准
+            Ok(ctx.outcome(match downgrade {
+                Some(_) => LockResult::Downgraded,
+                None if recovered => LockResult::Recovered,
+                None => LockResult::Success,
+            }))
+        }
+        Err(e) => {
+            let e = interference::classify(&ctx.path_str, e, verdict);
+            // 网络共享上的失败先探测共享，会话过期时给出重新登录的建议
+            #[cfg(feature = "winsec")]
+            let e = crate::network::explain_with_probe(Path::new(&ctx.path_str), e);
+            ctx.log_and_track(
+                opts.mode,
+                level,
+                change.before.map(|s| s.sddl),
+                None,
+                "error",
+                vec![format!("{:?}", e)],
+            );
+            Err(e)
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::backend::{FakeBackend, LabelOp};
+    use amberlock_storage::{NdjsonReader, OperationLog};
+    use amberlock_types::LockRecord;
+    use tempfile::TempDir;
+
+    /// 期望的判定：拒绝时的错误，或（结果、实际级别、降级原因）
+    #[derive(Debug, PartialEq)]
+    enum Expected {
+        ModeRejected,
+        NotAcknowledged,
+        Locked(LockResult, LabelLevel, Option<DowngradeReason>),
+    }
+
+    fn expected(root: bool, mode: ProtectMode, level: LabelLevel, ack: bool) -> Expected {
+        match (root, mode, level) {
+            (true, ProtectMode::Seal, _) => Expected::ModeRejected,
+            (true, _, _) if !ack => Expected::NotAcknowledged,
+            (true, _, LabelLevel::System) => Expected::Locked(
+                LockResult::Downgraded,
+                LabelLevel::High,
+                Some(DowngradeReason::VolumeRootPolicy),
+            ),
+            _ => Expected::Locked(LockResult::Success, level, None),
+        }
+    }
+
+    #[test]
+    fn test_volume_root_policy_matrix() {
+        let temp_dir = TempDir::new().expect("创建临时目录失败");
+        let log_path = temp_dir.path().join("log.ndjson");
+        let logger = OperationLog::open(&log_path).expect("打开日志失败");
+
+        let mut cases = 0;
+        for root in [true, false] {
+            for mode in [ProtectMode::ReadOnly, ProtectMode::Seal] {
+                for level in [LabelLevel::High, LabelLevel::System] {
+                    for ack in [true, false] {
+                        let target = if root { r"D:\" } else { r"D:\data" };
+                        let backend = FakeBackend::new();
+                        let opts = LockOptions {
+                            desired_level: level,
+                            mode,
+                            acknowledged_volume_root: ack,
+                            ..LockOptions::default()
+                        };
+                        let ctx =
+                            OperationContext::new(Path::new(target), "S-1-5-21-test", &logger);
+                        let mut checked = None;
+                        let outcome = lock_with_policy(&backend, ctx, &opts, level, |level| {
+                            checked = Some(level);
+                            Ok(())
+                        });
+                        logger.flush().unwrap();
+                        let record: LockRecord = serde_json::from_value(
+                            NdjsonReader::open(&log_path)
+                                .unwrap()
+                                .read_last_n(1)
+                                .unwrap()
+                                .remove(0),
+                        )
+                        .unwrap();
+
+                        let case = format!("root={} {:?} {:?} ack={}", root, mode, level, ack);
+                        let actual = match &outcome {
+                            Err(AmberlockError::VolumeRootModeNotAllowed(_)) => {
+                                Expected::ModeRejected
+                            }
+                            Err(AmberlockError::VolumeRootNotAcknowledged(_)) => {
+                                Expected::NotAcknowledged
+                            }
+                            Ok(outcome) => Expected::Locked(
+                                outcome.result.clone(),
+                                backend.label_of(target).expect("应已设置标签"),
+                                record.downgrade_reason,
+                            ),
+                            Err(e) => panic!("{}: 意外的错误 {}", case, e),
+                        };
+                        let want = expected(root, mode, level, ack);
+                        assert_eq!(actual, want, "{}", case);
+
+                        match want {
+                            Expected::Locked(_, applied, reason) => {
+                                assert_eq!(
+                                    checked,
+                                    Some(applied),
+                                    "{}: 应按实际级别检查特权",
+                                    case
+                                );
+                                assert_eq!(record.status, "success", "{}", case);
+                                assert_eq!(record.level_applied, applied, "{}", case);
+                                assert_eq!(record.downgrade_reason, reason, "{}", case);
+                                assert_eq!(record.volume_root_acknowledged, root, "{}", case);
+                            }
+                            _ => {
+                                assert!(backend.calls().is_empty(), "{}: 不应调用后端", case);
+                                assert_eq!(checked, None, "{}", case);
+                                assert_eq!(record.status, "error", "{}", case);
+                                assert!(!record.volume_root_acknowledged, "{}", case);
+                            }
+                        }
+                        cases += 1;
+                    }
+                }
+            }
+        }
+        assert_eq!(cases, 16);
+        println!("✅ 卷根策略在所有组合下的判定与记录正确");
+    }
+
+    #[test]
+    fn test_missing_privilege_downgrade_is_recorded() {
+        let temp_dir = TempDir::new().expect("创建临时目录失败");
+        let log_path = temp_dir.path().join("log.ndjson");
+        let logger = OperationLog::open(&log_path).expect("打开日志失败");
+        let backend = FakeBackend::new();
+        let opts = LockOptions {
+            desired_level: LabelLevel::System,
+            ..LockOptions::default()
+        };
+
+        let ctx = OperationContext::new(Path::new(r"D:\data"), "S-1-5-21-test", &logger);
+        let outcome = lock_with_policy(&backend, ctx, &opts, LabelLevel::High, |_| Ok(()));
+        assert_eq!(outcome.unwrap().result, LockResult::Downgraded);
+
+        backend.fail_next(LabelOp::Set, r"D:\", 5);
+        let opts = LockOptions {
+            acknowledged_volume_root: true,
+            ..opts
+        };
+        let ctx = OperationContext::new(Path::new(r"D:\"), "S-1-5-21-test", &logger);
+        assert!(lock_with_policy(&backend, ctx, &opts, LabelLevel::System, |_| Ok(())).is_err());
+
+        logger.flush().unwrap();
+        let records = NdjsonReader::open(&log_path)
+            .unwrap()
+            .read_last_n(2)
+            .unwrap();
+        assert_eq!(records[0]["downgrade_reason"], "missing_relabel_privilege");
+        // 失败记录同样带有卷根确认与降级原因
+        assert_eq!(records[1]["status"], "error");
+        assert_eq!(records[1]["volume_root_acknowledged"], true);
+        assert_eq!(records[1]["downgrade_reason"], "volume_root_policy");
+    }
+
+    #[test]
+    fn test_trailing_and_mixed_separators_are_normalized() {
+        let temp_dir = TempDir::new().expect("创建临时目录失败");
+        let log_path = temp_dir.path().join("log.ndjson");
+        let logger = OperationLog::open(&log_path).expect("打开日志失败");
+        let opts = LockOptions::default();
+
+        for raw in [
+            r"D:\data\file.txt\",
+            "D:/data//file.txt",
+            r"D:\data/file.txt/",
+        ] {
+            let backend = FakeBackend::new();
+            let ctx = OperationContext::new(Path::new(raw), "S-1-5-21-test", &logger);
+            let outcome = lock_with_policy(&backend, ctx, &opts, LabelLevel::High, |_| Ok(()))
+                .expect("规范化后的路径应上锁成功");
+            assert_eq!(outcome.result, LockResult::Success, "{}", raw);
+            assert!(
+                backend
+                    .calls()
+                    .iter()
+                    .all(|(_, target)| target == r"D:\data\file.txt"),
+                "{}: 后端应收到规范化后的路径",
+                raw
+            );
+            assert_eq!(
+                backend.label_of(r"D:\data\file.txt"),
+                Some(LabelLevel::High)
+            );
+        }
+
+        // 卷根保留末尾分隔符，仍按卷根策略要求确认
+        for raw in ["D:/", r"D:\\"] {
+            let backend = FakeBackend::new();
+            let ctx = OperationContext::new(Path::new(raw), "S-1-5-21-test", &logger);
+            assert_eq!(ctx.path_str, r"D:\", "{}", raw);
+            assert!(matches!(
+                lock_with_policy(&backend, ctx, &opts, LabelLevel::High, |_| Ok(())),
+                Err(AmberlockError::VolumeRootNotAcknowledged(_))
+            ));
+        }
+
+        logger.flush().unwrap();
+        let records = NdjsonReader::open(&log_path)
+            .unwrap()
+            .read_last_n(5)
+            .unwrap();
+        let paths: Vec<&str> = records.iter().filter_map(|r| r["path"].as_str()).collect();
+        assert_eq!(
+            paths,
+            vec![
+                r"D:\data\file.txt",
+                r"D:\data\file.txt",
+                r"D:\data\file.txt",
+                r"D:\",
+                r"D:\"
+            ]
+        );
+        println!("✅ 末尾与混合分隔符在操作开始前被规范化");
+    }
+}
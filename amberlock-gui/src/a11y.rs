@@ -0,0 +1,346 @@
+//! 无障碍支持
+//!
+//! - 读屏播报：`set_status` 更新状态栏的同时写入 `announcement`，状态栏文字是
+//!   live region，读屏软件在完成、出错等状态变化时播报；进度刷新直接写
+//!   `status_text`，不经过这里，避免每次刷新都打断播报
+//! - 控件标签：`CONTROLS` 列出 `ui/main.slint` 中每个可交互控件的 `accessible-id`
+//!   与读屏标签，测试逐一核对，保证没有控件以“未标注”的形式发布
+//!
+//! 自动化覆盖不到的部分（实际朗读顺序、焦点框可见性）按 `docs/user_docs.md`
+//! “无障碍”一节中的 NVDA 检查清单人工验收。
+
+use crate::MainWindow;
+use slint::SharedString;
+
+/// 控件的读屏标签
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Label {
+    /// 固定文字（与 `.slint` 中的字面量一致）
+    Static(&'static str),
+    /// 由数据拼接而成，这里记录朗读内容的组成
+    Dynamic(&'static str),
+}
+
+/// 可交互控件：`accessible-id` → 读屏标签
+pub const CONTROLS: &[(&str, Label)] = &[
+    // 标题栏
+    ("system-status-button", Label::Static("系统状态")),
+    ("storage-button", Label::Static("存储占用")),
+    ("theme-button", Label::Static("切换主题")),
+    // 只读审计模式横幅
+    (
+        "exit-read-only-password",
+        Label::Static("保险库密码（退出只读模式）"),
+    ),
+    ("exit-read-only-button", Label::Static("退出只读模式")),
+    // 左侧边栏
+    ("add-files-button", Label::Static("添加文件")),
+    ("add-folders-button", Label::Static("添加文件夹")),
+    ("copy-paths-button", Label::Static("复制所选路径")),
+    ("sort-by-label-button", Label::Static("按保护状态排序")),
+    ("quick-protect-button", Label::Static("快速保护")),
+    ("log-filter-input", Label::Static("日志筛选")),
+    ("refresh-logs-button", Label::Static("刷新日志")),
+    ("copy-logs-button", Label::Static("复制所选日志")),
+    (
+        "revert-password-input",
+        Label::Static("保险库密码（撤销批次）"),
+    ),
+    // 文件列表
+    ("file-list", Label::Static("文件/目录列表")),
+    ("file-row", Label::Dynamic("路径，类型，保护状态")),
+    ("file-row-checkbox", Label::Dynamic("选择 路径，保护状态")),
+    ("file-row-repair", Label::Dynamic("修复对象 路径")),
+    // 锁定操作
+    ("mode-read-only", Label::Static("只读模式")),
+    ("mode-seal", Label::Static("封印模式")),
+    ("level-option", Label::Dynamic("完整性级别 级别名")),
+    ("comment-input", Label::Static("操作备注（可选）")),
+    ("unlock-password-input", Label::Static("保险库密码（解锁）")),
+    ("capture-trace-checkbox", Label::Static("捕获诊断跟踪")),
+    ("lock-button", Label::Static("应用上锁")),
+    ("unlock-button", Label::Static("解锁")),
+    (
+        "hello-unlock-button",
+        Label::Static("使用 Windows Hello 解锁"),
+    ),
+    ("cancel-button", Label::Static("取消操作")),
+    // 操作日志
+    ("log-list", Label::Static("操作日志")),
+    ("log-row", Label::Dynamic("动作 路径，级别，状态，时间")),
+    ("log-row-revert", Label::Static("撤销此批次")),
+    // 状态栏
+    ("status-text", Label::Dynamic("最近一次播报的状态")),
+    ("warnings-toggle", Label::Dynamic("N 类警告")),
+    ("dir-tree-toggle", Label::Static("按目录查看")),
+    ("notices-toggle", Label::Dynamic("N 条提示")),
+    ("about-button", Label::Static("关于 / 组件清单")),
+    // 浮动面板
+    ("system-status-close", Label::Static("关闭系统状态")),
+    ("storage-close", Label::Static("关闭存储占用")),
+    ("storage-item-checkbox", Label::Dynamic("清理类别 类别摘要")),
+    ("storage-cleanup-button", Label::Static("清理")),
+    ("decommission-toggle", Label::Static("高级：全部解除保护")),
+    (
+        "decommission-scope-button",
+        Label::Dynamic("解除保护范围 范围名称"),
+    ),
+    (
+        "decommission-password-input",
+        Label::Static("保险库密码（全部解除保护）"),
+    ),
+    ("decommission-phrase-input", Label::Static("确认短语")),
+    ("decommission-button", Label::Static("全部解除保护")),
+    ("warnings-close", Label::Static("关闭警告列表")),
+    ("dir-tree-close", Label::Static("关闭按目录汇总")),
+    ("dir-row-toggle", Label::Dynamic("目录汇总行")),
+    ("dir-row-logs", Label::Dynamic("查看日志 目录")),
+    ("notices-clear", Label::Static("清空提示")),
+    ("notices-close", Label::Static("关闭提示")),
+    ("about-close", Label::Static("关闭关于")),
+    ("component-search-input", Label::Static("搜索第三方组件")),
+    ("preset-close", Label::Static("关闭快速保护")),
+    ("preset-option", Label::Dynamic("预设 预设名称")),
+    ("preset-confirm-button", Label::Static("确认保护")),
+    ("repair-close", Label::Static("关闭修复对象")),
+    (
+        "repair-relabel-checkbox",
+        Label::Dynamic("重新设置标签（级别）"),
+    ),
+    (
+        "repair-inheritance-checkbox",
+        Label::Static("恢复 DACL 继承"),
+    ),
+    (
+        "repair-owner-checkbox",
+        Label::Static("取得所有权（所有者设为当前用户）"),
+    ),
+    ("repair-start-button", Label::Static("开始修复")),
+];
+
+/// 播报的紧急程度
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Urgency {
+    /// 等当前朗读结束后播报
+    Polite,
+    /// 立即打断当前朗读（错误）
+    Assertive,
+}
+
+/// 状态文字开头的符号 → 朗读时的前缀
+const SPOKEN_PREFIXES: &[(&str, &str)] = &[
+    ("✅", "完成："),
+    ("❌", "错误："),
+    ("⚠️", "警告："),
+    ("⚠", "警告："),
+];
+
+/// 状态文字的紧急程度（以 ❌ 开头的错误立即播报）
+pub fn urgency(status: &str) -> Urgency {
+    if status.trim_start().starts_with('❌') {
+        Urgency::Assertive
+    } else {
+        Urgency::Polite
+    }
+}
+
+/// 状态文字 → 朗读文字
+///
+/// 表示结果的符号换成文字前缀，其余开头的装饰符号（🔍、🗑 等）去掉，
+/// 以免读屏软件逐个朗读符号名称
+pub fn spoken_text(status: &str) -> String {
+    let status = status.trim();
+    for (symbol, prefix) in SPOKEN_PREFIXES {
+        if let Some(rest) = status.strip_prefix(symbol) {
+            return format!("{}{}", prefix, strip_symbols(rest));
+        }
+    }
+    strip_symbols(status).to_string()
+}
+
+/// 去掉开头的非文字符号（emoji、变体选择符）与空白
+fn strip_symbols(text: &str) -> &str {
+    text.trim_start_matches(|c: char| {
+        c.is_whitespace() || (!c.is_ascii() && !c.is_alphanumeric() && !is_cjk_punctuation(c))
+    })
+}
+
+/// 中文标点（保留，如开头的引号）
+fn is_cjk_punctuation(c: char) -> bool {
+    matches!(c, '\u{3000}'..='\u{303f}' | '\u{ff00}'..='\u{ffef}' | '“' | '”' | '‘' | '’')
+}
+
+/// 下一次写入 `announcement` 的文字
+///
+/// 文字与上一次相同时读屏软件不会重复播报，此时追加（或去掉）一个零宽空格，
+/// 使连续两次相同的结果都能被播报
+pub fn next_announcement(previous: &str, spoken: String) -> String {
+    if previous == spoken {
+        spoken + "\u{200b}"
+    } else {
+        spoken
+    }
+}
+
+/// 更新状态栏并向读屏软件播报
+pub fn set_status(app: &MainWindow, status: SharedString) {
+    let spoken = spoken_text(&status);
+    let announcement = next_announcement(&app.get_announcement(), spoken);
+    app.set_announcement_urgent(urgency(&status) == Urgency::Assertive);
+    app.set_announcement(announcement.into());
+    app.set_status_text(status);
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::collections::HashSet;
+
+    const MAIN_SLINT: &str = include_str!("../ui/main.slint");
+
+    /// 可交互的元素类型：实例须带 `accessible-id`，或以 `accessible-role: none` 交由外层组件暴露
+    const INTERACTIVE: &[&str] = &["Clickable", "ModernButton", "ModernCheckbox", "ModernInput"];
+
+    fn indent(line: &str) -> usize {
+        line.len() - line.trim_start().len()
+    }
+
+    /// 从第 `start` 行起，与该行缩进相同的属性行（即该元素自身的属性）
+    fn own_properties(lines: &[&str], start: usize) -> Vec<String> {
+        let depth = indent(lines[start]);
+        lines[start..]
+            .iter()
+            .take_while(|line| line.trim().is_empty() || indent(line) >= depth)
+            .filter(|line| !line.trim().is_empty() && indent(line) == depth)
+            .map(|line| line.trim().to_string())
+            .collect()
+    }
+
+    /// 属性值（去掉结尾的分号）
+    fn value_of<'a>(properties: &'a [String], name: &str) -> Option<&'a str> {
+        properties.iter().find_map(|line| {
+            line.strip_prefix(name)
+                .and_then(|rest| rest.strip_prefix(':'))
+                .map(|value| value.trim().trim_end_matches(';'))
+        })
+    }
+
+    /// 值是否为单个字符串字面量
+    fn is_literal(value: &str) -> bool {
+        value.len() >= 2
+            && value.starts_with('"')
+            && value.ends_with('"')
+            && !value[1..value.len() - 1].contains('"')
+    }
+
+    #[test]
+    fn test_every_interactive_element_is_mapped() {
+        let lines: Vec<&str> = MAIN_SLINT.lines().collect();
+        let mut unlabeled = Vec::new();
+        for (index, line) in lines.iter().enumerate() {
+            let trimmed = line.trim();
+            if trimmed.starts_with("component ") {
+                continue;
+            }
+            let Some(head) = trimmed.strip_suffix(" {") else {
+                continue;
+            };
+            let element = head.rsplit(' ').next().unwrap_or(head);
+            if !INTERACTIVE.contains(&element) {
+                continue;
+            }
+            let properties = own_properties(&lines, index + 1);
+            let delegated = value_of(&properties, "accessible-role") == Some("none");
+            if !delegated && value_of(&properties, "accessible-id").is_none() {
+                unlabeled.push(format!("第 {} 行：{}", index + 1, trimmed));
+            }
+        }
+        assert!(
+            unlabeled.is_empty(),
+            "未标注的控件：\n{}",
+            unlabeled.join("\n")
+        );
+        println!("✅ 所有可交互控件都带有 accessible-id");
+    }
+
+    #[test]
+    fn test_control_map_matches_slint() {
+        let lines: Vec<&str> = MAIN_SLINT.lines().collect();
+        let mut declared = HashSet::new();
+        for (index, line) in lines.iter().enumerate() {
+            let Some(id) = line.trim().strip_prefix("accessible-id: ") else {
+                continue;
+            };
+            let id = id.trim_end_matches(';').trim_matches('"');
+            assert!(declared.insert(id), "accessible-id 重复：{}", id);
+
+            let (_, label) = CONTROLS
+                .iter()
+                .find(|(known, _)| *known == id)
+                .unwrap_or_else(|| panic!("{} 未列入 CONTROLS", id));
+            // 读屏标签：accessible-label，按钮与复选框未设置时取 text / label
+            let properties = own_properties(&lines, index);
+            let value = ["accessible-label", "text", "label"]
+                .iter()
+                .find_map(|name| value_of(&properties, name))
+                .unwrap_or_else(|| panic!("{} 没有读屏标签", id));
+            match label {
+                Label::Static(text) => {
+                    assert_eq!(value, format!("\"{}\"", text), "{} 的标签不一致", id)
+                }
+                Label::Dynamic(_) => {
+                    assert!(!is_literal(value), "{} 的标签是固定文字：{}", id, value)
+                }
+            }
+        }
+
+        let mapped: HashSet<&str> = CONTROLS.iter().map(|(id, _)| *id).collect();
+        assert_eq!(mapped.len(), CONTROLS.len(), "CONTROLS 中有重复的 id");
+        let stale: Vec<_> = mapped.difference(&declared).collect();
+        assert!(stale.is_empty(), "界面中已不存在：{:?}", stale);
+        for (id, label) in CONTROLS {
+            let (Label::Static(text) | Label::Dynamic(text)) = label;
+            assert!(!text.trim().is_empty(), "{} 的标签为空", id);
+        }
+        println!("✅ {} 个控件的读屏标签与界面一致", CONTROLS.len());
+    }
+
+    #[test]
+    fn test_spoken_text() {
+        assert_eq!(
+            spoken_text("✅ 上锁完成: 10/10 成功"),
+            "完成：上锁完成: 10/10 成功"
+        );
+        assert_eq!(spoken_text("❌ 密码错误，未解锁"), "错误：密码错误，未解锁");
+        assert_eq!(spoken_text("⚠️ 未选择任何对象"), "警告：未选择任何对象");
+        assert_eq!(spoken_text("⚠ 1 类警告"), "警告：1 类警告");
+        assert_eq!(
+            spoken_text("🗑 已从列表移除 2 个对象"),
+            "已从列表移除 2 个对象"
+        );
+        assert_eq!(spoken_text("🔍 日志已过滤: \"a\""), "日志已过滤: \"a\"");
+        assert_eq!(spoken_text("准备就绪"), "准备就绪");
+        assert_eq!(spoken_text("“引号”开头"), "“引号”开头");
+        assert_eq!(spoken_text(""), "");
+
+        assert_eq!(urgency("❌ 清理失败"), Urgency::Assertive);
+        assert_eq!(urgency("⚠️ 已放弃锁定卷根"), Urgency::Polite);
+        assert_eq!(urgency("✅ 已退出只读审计模式"), Urgency::Polite);
+        println!("✅ 状态文字转换为朗读文字");
+    }
+
+    #[test]
+    fn test_repeated_announcement_still_changes() {
+        let first = next_announcement("", "完成：已刷新".to_string());
+        let second = next_announcement(&first, "完成：已刷新".to_string());
+        let third = next_announcement(&second, "完成：已刷新".to_string());
+        assert_ne!(first, second);
+        assert_ne!(second, third);
+        assert_eq!(first, third);
+        assert_eq!(
+            next_announcement(&first, "错误：失败".to_string()),
+            "错误：失败"
+        );
+        println!("✅ 连续相同的播报也会更新");
+    }
+}
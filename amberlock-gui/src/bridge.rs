@@ -1,8 +1,12 @@
 //! 文件对话框和参数转换工具函数
 //!
 
-use crate::{Level, Mode};
+use crate::{DirRow, Level, Mode, PresetItem, StorageItem};
+use amberlock_core::{BatchOutcome, BatchResult, DirSummary, ProtectionPreset};
+use amberlock_storage::query::Timeseries;
+use amberlock_storage::{ArtifactCategory, CleanupPlan, StorageReport};
 use amberlock_types::{LabelLevel, ProtectMode};
+use std::collections::HashSet;
 use std::path::PathBuf;
 
 /// 打开文件选择对话框
@@ -25,10 +29,100 @@ pub fn pick_folders_dialog() -> Option<Vec<PathBuf>> {
     Some(dirs)
 }
 
+/// 打开单个文件夹选择对话框（用于没有默认文件夹的预设）
+pub fn pick_folder_dialog(title: &str) -> Option<PathBuf> {
+    rfd::FileDialog::new().set_title(title).pick_folder()
+}
+
+/// 打开保存文件对话框
+pub fn save_file_dialog(title: &str, file_name: &str) -> Option<PathBuf> {
+    rfd::FileDialog::new()
+        .set_title(title)
+        .set_file_name(file_name)
+        .save_file()
+}
+
+/// 将保护预设转换为界面列表项
+pub fn preset_items(presets: &[ProtectionPreset]) -> Vec<PresetItem> {
+    presets
+        .iter()
+        .map(|preset| PresetItem {
+            title: preset.title().into(),
+            description: preset.description().into(),
+        })
+        .collect()
+}
+
+/// 存储占用报告 → 界面条目（有可释放文件的类别默认勾选）
+pub fn storage_items(report: &StorageReport) -> Vec<StorageItem> {
+    report
+        .categories
+        .iter()
+        .map(|usage| StorageItem {
+            key: usage.category.key().into(),
+            summary: usage.to_string().into(),
+            cleanable: usage.category.is_cleanable(),
+            checked: usage.reclaimable_files > 0,
+        })
+        .collect()
+}
+
+/// 目录汇总树 → 界面行（只展开 `expanded` 中的目录）
+pub fn dir_rows(summary: &DirSummary, expanded: &HashSet<String>) -> Vec<DirRow> {
+    fn push(node: &DirSummary, depth: i32, expanded: &HashSet<String>, rows: &mut Vec<DirRow>) {
+        let is_expanded = expanded.contains(&node.path);
+        rows.push(DirRow {
+            path: node.path.as_str().into(),
+            label: node.label().into(),
+            depth,
+            expandable: !node.children.is_empty(),
+            expanded: is_expanded,
+            failed: node.counts.failed > 0,
+        });
+        if is_expanded {
+            for child in &node.children {
+                push(child, depth + 1, expanded, rows);
+            }
+        }
+    }
+
+    let mut rows = Vec::new();
+    push(summary, 0, expanded, &mut rows);
+    rows
+}
+
+/// 按勾选的条目生成清理计划（各类别使用默认保留期）
+pub fn storage_plan(items: &[StorageItem]) -> CleanupPlan {
+    items
+        .iter()
+        .filter(|item| item.cleanable && item.checked)
+        .filter_map(|item| ArtifactCategory::from_key(&item.key))
+        .fold(CleanupPlan::default(), CleanupPlan::with_category)
+}
+
 /// 将路径添加到文件列表模型
-pub fn add_paths_to_model(paths: &[PathBuf], model: &crate::model::FileListModel) {
+///
+/// # 返回
+/// 生成的状态栏文本（包含被拒绝的保留设备名数量）
+pub fn add_paths_to_model(
+    paths: &[PathBuf],
+    model: &crate::model::FileListModel,
+    noun: &str,
+) -> String {
     // 委托给模型自身的添加方法
-    model.add_paths(paths);
+    let rejected = model.add_paths(paths);
+    let added = paths.len() - rejected.len();
+    if rejected.is_empty() {
+        format!("✅ 已添加 {} 个{}", added, noun)
+    } else {
+        format!(
+            "⚠️ 已添加 {} 个{}，忽略 {} 个保留设备名（如 {}）",
+            added,
+            noun,
+            rejected.len(),
+            rejected[0].display()
+        )
+    }
 }
 
 /// 将UI参数转换为Amberlock内部类型
@@ -48,4 +142,229 @@ pub fn convert_ui_params(mode: Mode, level: Level) -> (ProtectMode, LabelLevel)
     };
 
     (m, l)
-}
\ No newline at end of file
+}
+
+/// 将计数序列换算为迷你柱状图的相对高度（以最大值为 1.0）
+pub fn sparkline_heights(values: &[u64]) -> Vec<f32> {
+    let max = values.iter().copied().max().unwrap_or(0);
+    values
+        .iter()
+        .map(|&v| if max == 0 { 0.0 } else { v as f32 / max as f32 })
+        .collect()
+}
+
+/// 活动趋势卡片的说明文字
+///
+/// 桶按 UTC 自然日划分，说明中注明时区，避免与本地日期混淆
+pub fn activity_caption(series: &Timeseries) -> String {
+    let total: u64 = series
+        .buckets
+        .iter()
+        .map(|b| b.success + b.error + b.downgraded + b.unlocked)
+        .sum();
+    let failed: u64 = series.buckets.iter().map(|b| b.error).sum();
+    let mut caption = format!("共 {} 次操作，失败 {} 次（按 UTC 日期）", total, failed);
+    if series.unbucketed > 0 {
+        caption.push_str(&format!("，{} 条记录时间无效", series.unbucketed));
+    }
+    caption
+}
+
+/// 格式化批量操作结果（任务 7.1：清晰的错误提示）
+///
+/// 按 `BatchResult::outcome` 分类给出不同的状态栏文本，
+/// 空批次不会显示为成功
+pub fn format_batch_result(result: &BatchResult) -> String {
+    let extras = |result: &BatchResult| {
+        let mut extras = String::new();
+        if result.downgraded_count > 0 {
+            extras.push_str(&format!("，降级 {} 个", result.downgraded_count));
+        }
+        if result.skipped_count > 0 {
+            extras.push_str(&format!("，跳过 {} 个", result.skipped_count));
+        }
+        if result.has_warnings() {
+            extras.push_str(&format!("，{} 个有警告", result.warned_count));
+        }
+        extras
+    };
+
+    let summary = match result.outcome() {
+        BatchOutcome::Empty => "ℹ️ 没有可处理的对象：未匹配到任何路径".to_string(),
+        // 全部成功但部分对象附带警告时以琥珀色状态提示，详情见状态栏的警告列表
+        BatchOutcome::AllSucceeded if result.has_warnings() => format!(
+            "⚠️ 完成但有警告：完成 {} 个{}",
+            result.success_count,
+            extras(result)
+        ),
+        BatchOutcome::AllSucceeded => {
+            format!("✅ 操作成功：完成 {} 个{}", result.success_count, extras(result))
+        }
+        BatchOutcome::Partial => format!(
+            "⚠️ 操作部分失败：成功 {} 个，失败 {} 个{}",
+            result.success_count,
+            result.failed_count,
+            extras(result)
+        ),
+        BatchOutcome::AllFailed => format!("❌ 操作全部失败：失败 {} 个", result.failed_count),
+    };
+    // 疑似被安全软件拦截时附加提示，便于用户直接检查安全软件
+    match result.interference.summary_line() {
+        Some(line) => format!("{}（{}）", summary, line),
+        None => summary,
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_empty_batch_message_does_not_imply_success() {
+        let message = format_batch_result(&BatchResult::default());
+        assert!(!message.contains("成功"), "空批次提示不应包含“成功”：{}", message);
+        assert!(message.contains("没有可处理的对象"));
+
+        let all_failed = BatchResult {
+            failed_count: 2,
+            total_count: 2,
+            ..Default::default()
+        };
+        assert!(format_batch_result(&all_failed).starts_with("❌"));
+
+        let partial = BatchResult {
+            success_count: 1,
+            failed_count: 1,
+            downgraded_count: 1,
+            total_count: 2,
+            ..Default::default()
+        };
+        assert_eq!(
+            format_batch_result(&partial),
+            "⚠️ 操作部分失败：成功 1 个，失败 1 个，降级 1 个"
+        );
+
+        let mut recovered = BatchResult {
+            success_count: 1,
+            total_count: 1,
+            ..Default::default()
+        };
+        recovered
+            .interference
+            .observe(Ok(&amberlock_core::LockResult::Recovered));
+        assert_eq!(
+            format_batch_result(&recovered),
+            "✅ 操作成功：完成 1 个（1 次失败疑似被安全软件拦截）"
+        );
+        println!("✅ 批量结果提示按分类区分");
+    }
+
+    #[test]
+    fn test_succeeded_with_warnings_is_not_plain_success() {
+        let mut warned = BatchResult {
+            success_count: 3,
+            total_count: 3,
+            warned_count: 1,
+            ..Default::default()
+        };
+        warned
+            .warnings
+            .insert(amberlock_types::WarningKind::VerificationSkipped, 1);
+        assert_eq!(
+            format_batch_result(&warned),
+            "⚠️ 完成但有警告：完成 3 个，1 个有警告"
+        );
+        assert_eq!(
+            warned.warning_lines(),
+            vec!["未能读回标签，跳过了写后验证：1 次"]
+        );
+        println!("✅ 有警告的成功批次以琥珀色状态提示");
+    }
+
+    #[test]
+    fn test_storage_plan_uses_checked_cleanable_items() {
+        let item = |key: &str, cleanable: bool, checked: bool| StorageItem {
+            key: key.into(),
+            summary: Default::default(),
+            cleanable,
+            checked,
+        };
+        let plan = storage_plan(&[
+            item("failure_spill", true, true),
+            item("trace", true, false),
+            item("vault", false, true),
+            item("unknown", true, true),
+        ]);
+        assert_eq!(
+            plan.categories().collect::<Vec<_>>(),
+            vec![ArtifactCategory::FailureSpill]
+        );
+    }
+
+    #[test]
+    fn test_dir_rows_follow_expanded_nodes() {
+        let denied = amberlock_types::AmberlockError::Win32 {
+            code: 5,
+            msg: String::new(),
+        };
+        let mut result = BatchResult::default().with_dir_summary(8);
+        for path in [
+            r"D:\data\a\x\1.txt",
+            r"D:\data\a\x\2.txt",
+            r"D:\data\b\3.txt",
+        ] {
+            let dirs = result.dirs.as_mut().unwrap();
+            dirs.record(std::path::Path::new(path), Err(&denied));
+        }
+        let summary = result.dir_summary().expect("应有汇总");
+
+        let collapsed = dir_rows(&summary, &HashSet::new());
+        assert_eq!(collapsed.len(), 1);
+        assert!(collapsed[0].expandable && !collapsed[0].expanded);
+
+        let expanded: HashSet<String> = [r"D:\data", r"D:\data\a"].map(String::from).into();
+        let rows = dir_rows(&summary, &expanded);
+        let shape: Vec<(i32, &str)> = rows.iter().map(|r| (r.depth, r.path.as_str())).collect();
+        assert_eq!(
+            shape,
+            vec![
+                (0, r"D:\data"),
+                (1, r"D:\data\a"),
+                (2, r"D:\data\a\x"),
+                (1, r"D:\data\b"),
+            ]
+        );
+        assert_eq!(rows[1].label, r"a\ ✗ 2 个失败 — ACCESS_DENIED");
+        assert!(rows.iter().all(|r| r.failed));
+        println!("✅ 目录汇总按展开状态生成界面行");
+    }
+
+    #[test]
+    fn test_sparkline_heights_are_relative_to_max() {
+        assert_eq!(sparkline_heights(&[0, 2, 4]), vec![0.0, 0.5, 1.0]);
+        assert_eq!(sparkline_heights(&[0, 0]), vec![0.0, 0.0]);
+        assert!(sparkline_heights(&[]).is_empty());
+    }
+
+    #[test]
+    fn test_ui_mappings_cover_every_variant() {
+        // 界面可选的模式与级别覆盖全部内部取值
+        let modes: Vec<ProtectMode> = [Mode::ReadOnly, Mode::Seal]
+            .into_iter()
+            .map(|mode| convert_ui_params(mode, Level::High).0)
+            .collect();
+        assert_eq!(modes, ProtectMode::ALL);
+        let levels: Vec<LabelLevel> = [Level::Medium, Level::High, Level::System]
+            .into_iter()
+            .map(|level| convert_ui_params(Mode::ReadOnly, level).1)
+            .collect();
+        assert_eq!(levels, LabelLevel::ALL);
+
+        // 每个级别的徽标互不相同
+        let badges: std::collections::BTreeSet<&str> = LabelLevel::ALL
+            .into_iter()
+            .map(|level| crate::labels::LabelState::from_level(level).badge_text())
+            .collect();
+        assert_eq!(badges.len(), LabelLevel::ALL.len());
+    }
+}
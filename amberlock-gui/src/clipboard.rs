@@ -0,0 +1,243 @@
+//! 剪贴板支持
+//!
+//! 提供日志记录与文件路径的文本格式化函数，以及对系统剪贴板的薄封装。
+//! 剪贴板写入通过 `ClipboardSink` trait 进行，便于测试时替换。
+
+use crate::LogRow;
+use serde_json::{Value, json};
+use std::path::PathBuf;
+
+/// 剪贴板文本的换行符（Windows 应用约定使用 CRLF）
+const LINE_ENDING: &str = "\r\n";
+
+/// TSV 导出的列（与 LockRecord 字段名一致）
+const TSV_COLUMNS: [&str; 7] = [
+    "time_utc",
+    "status",
+    "level_applied",
+    "mode",
+    "path",
+    "user_sid",
+    "errors",
+];
+
+/// 剪贴板写入接口
+pub trait ClipboardSink {
+    /// 写入纯文本
+    fn set_text(&mut self, text: String) -> anyhow::Result<()>;
+}
+
+/// 系统剪贴板（基于 arboard）
+pub struct SystemClipboard;
+
+impl ClipboardSink for SystemClipboard {
+    fn set_text(&mut self, text: String) -> anyhow::Result<()> {
+        arboard::Clipboard::new()?.set_text(text)?;
+        Ok(())
+    }
+}
+
+/// 写入剪贴板并生成状态栏提示
+///
+/// # 参数
+/// - `sink`: 剪贴板实现
+/// - `text`: 待写入文本
+/// - `summary`: 成功时的描述（如 "3 条日志"）
+///
+/// # 注意
+/// 剪贴板被其他程序占用等失败情况只返回警告文本，不会 panic
+pub fn copy_with(sink: &mut dyn ClipboardSink, text: String, summary: &str) -> String {
+    match sink.set_text(text) {
+        Ok(()) => format!("📋 已复制 {}", summary),
+        Err(e) => format!("⚠️ 剪贴板不可用: {}", e),
+    }
+}
+
+/// 将界面日志行还原为原始记录
+///
+/// 优先使用行中保存的原始 JSON，缺失或无法解析时由显示字段重建
+pub fn rows_to_records(rows: &[LogRow]) -> Vec<Value> {
+    rows.iter()
+        .map(|row| {
+            serde_json::from_str(&row.raw).unwrap_or_else(|_| {
+                json!({
+                    "time_utc": row.time.as_str(),
+                    "status": row.status.as_str(),
+                    "level_applied": row.level.as_str(),
+                    "path": row.path.as_str(),
+                })
+            })
+        })
+        .collect()
+}
+
+/// 格式化为 TSV（含表头）
+///
+/// 字段中含制表符、换行或双引号时按 CSV 惯例加双引号包裹，
+/// 粘贴到 Excel 等表格程序时不会错列
+pub fn records_to_tsv(records: &[Value]) -> String {
+    let mut lines = Vec::with_capacity(records.len() + 1);
+    lines.push(TSV_COLUMNS.join("\t"));
+
+    for record in records {
+        let fields: Vec<String> = TSV_COLUMNS
+            .iter()
+            .map(|column| escape_tsv_field(&field_text(record.get(*column))))
+            .collect();
+        lines.push(fields.join("\t"));
+    }
+
+    lines.join(LINE_ENDING)
+}
+
+/// 格式化为带缩进的 JSON 数组
+pub fn records_to_json_pretty(records: &[Value]) -> String {
+    serde_json::to_string_pretty(records).unwrap_or_default()
+}
+
+/// 格式化路径列表（每行一个）
+pub fn paths_to_text(paths: &[PathBuf]) -> String {
+    paths
+        .iter()
+        .map(|p| p.to_string_lossy())
+        .collect::<Vec<_>>()
+        .join(LINE_ENDING)
+}
+
+/// 将 JSON 字段转换为单元格文本（数组以 "; " 连接）
+fn field_text(value: Option<&Value>) -> String {
+    match value {
+        None | Some(Value::Null) => String::new(),
+        Some(Value::String(s)) => s.clone(),
+        Some(Value::Array(items)) => items
+            .iter()
+            .map(|item| field_text(Some(item)))
+            .collect::<Vec<_>>()
+            .join("; "),
+        Some(other) => other.to_string(),
+    }
+}
+
+/// 转义单个 TSV 字段
+fn escape_tsv_field(field: &str) -> String {
+    if field.contains(['\t', '\n', '\r', '"']) {
+        format!("\"{}\"", field.replace('"', "\"\""))
+    } else {
+        field.to_string()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn sample_record(path: &str) -> Value {
+        json!({
+            "id": "1",
+            "path": path,
+            "kind": "File",
+            "mode": "ReadOnly",
+            "level_applied": "High",
+            "time_utc": "2025-01-01T00:00:00Z",
+            "user_sid": "S-1-5-21-0",
+            "status": "success",
+            "errors": ["e1", "e2"],
+        })
+    }
+
+    struct FakeClipboard {
+        text: Option<String>,
+        fail: bool,
+    }
+
+    impl ClipboardSink for FakeClipboard {
+        fn set_text(&mut self, text: String) -> anyhow::Result<()> {
+            if self.fail {
+                anyhow::bail!("剪贴板被占用");
+            }
+            self.text = Some(text);
+            Ok(())
+        }
+    }
+
+    #[test]
+    fn test_records_to_tsv() {
+        let tsv = records_to_tsv(&[sample_record(r"C:\Data\a.txt")]);
+        let lines: Vec<&str> = tsv.split(LINE_ENDING).collect();
+
+        assert_eq!(lines.len(), 2);
+        assert_eq!(lines[0], TSV_COLUMNS.join("\t"));
+        assert_eq!(
+            lines[1],
+            "2025-01-01T00:00:00Z\tsuccess\tHigh\tReadOnly\tC:\\Data\\a.txt\tS-1-5-21-0\te1; e2"
+        );
+    }
+
+    #[test]
+    fn test_tsv_escapes_embedded_tabs_and_newlines() {
+        let tsv = records_to_tsv(&[sample_record("C:\\odd\tname\n\"x\".txt")]);
+        let row = tsv.split(LINE_ENDING).nth(1).expect("缺少数据行");
+
+        assert!(row.contains("\"C:\\odd\tname\n\"\"x\"\".txt\""));
+        // 除转义字段内部外，列数保持不变
+        assert_eq!(row.replace("\tname", "").matches('\t').count(), 6);
+    }
+
+    #[test]
+    fn test_records_to_json_pretty_round_trip() {
+        let records = vec![sample_record("a"), sample_record("b")];
+        let text = records_to_json_pretty(&records);
+
+        assert!(text.contains("\n  "));
+        let parsed: Vec<Value> = serde_json::from_str(&text).expect("JSON 解析失败");
+        assert_eq!(parsed, records);
+    }
+
+    #[test]
+    fn test_paths_to_text() {
+        let paths = vec![PathBuf::from(r"C:\a.txt"), PathBuf::from(r"D:\b")];
+        assert_eq!(paths_to_text(&paths), "C:\\a.txt\r\nD:\\b");
+        assert_eq!(paths_to_text(&[]), "");
+    }
+
+    #[test]
+    fn test_rows_to_records_prefers_raw() {
+        let raw = sample_record("C:\\raw.txt");
+        let rows = vec![
+            LogRow {
+                path: "C:\\raw.txt".into(),
+                raw: raw.to_string().into(),
+                ..Default::default()
+            },
+            LogRow {
+                path: "C:\\display.txt".into(),
+                status: "error".into(),
+                ..Default::default()
+            },
+        ];
+
+        let records = rows_to_records(&rows);
+        assert_eq!(records[0], raw);
+        assert_eq!(records[1]["path"], "C:\\display.txt");
+        assert_eq!(records[1]["status"], "error");
+    }
+
+    #[test]
+    fn test_copy_failure_degrades_to_warning() {
+        let mut ok = FakeClipboard {
+            text: None,
+            fail: false,
+        };
+        let status = copy_with(&mut ok, "abc".to_string(), "1 条日志");
+        assert_eq!(ok.text.as_deref(), Some("abc"));
+        assert!(status.contains("已复制 1 条日志"));
+
+        let mut locked = FakeClipboard {
+            text: None,
+            fail: true,
+        };
+        let status = copy_with(&mut locked, "abc".to_string(), "1 条日志");
+        assert!(status.starts_with("⚠️"));
+        assert!(status.contains("剪贴板被占用"));
+    }
+}
@@ -0,0 +1,93 @@
+//! 窗口关闭请求的处理
+//!
+//! 批量操作进行中直接关闭窗口会在工作线程写到一半时结束进程。
+//! 此时拒绝关闭，询问用户是否取消操作：同意后请求取消，操作结束时再退出。
+
+use std::cell::Cell;
+
+/// 关闭请求的处理结果
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum CloseDecision {
+    /// 没有进行中的操作，直接关闭
+    Close,
+    /// 保持窗口，不做处理
+    Refuse,
+    /// 取消进行中的操作，结束后退出
+    CancelAndWait,
+}
+
+/// 窗口关闭闸门（仅在 UI 线程中使用）
+#[derive(Debug, Default)]
+pub struct CloseGate {
+    /// 已请求取消并等待操作结束后退出
+    close_pending: Cell<bool>,
+}
+
+impl CloseGate {
+    /// 处理一次关闭请求
+    ///
+    /// # 参数
+    /// - `in_progress`: 是否有进行中的操作
+    /// - `confirm_cancel`: 询问用户是否取消操作并退出（仅在需要时调用）
+    pub fn request_close(
+        &self,
+        in_progress: bool,
+        confirm_cancel: impl FnOnce() -> bool,
+    ) -> CloseDecision {
+        if !in_progress {
+            return CloseDecision::Close;
+        }
+        // 已在等待取消完成，不再重复询问
+        if self.close_pending.get() {
+            return CloseDecision::Refuse;
+        }
+        if confirm_cancel() {
+            self.close_pending.set(true);
+            CloseDecision::CancelAndWait
+        } else {
+            CloseDecision::Refuse
+        }
+    }
+
+    /// 操作结束时调用
+    ///
+    /// # 返回
+    /// 此前请求过取消并退出时返回 `true`（调用方应退出事件循环）
+    pub fn operation_finished(&self) -> bool {
+        self.close_pending.replace(false)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_idle_window_closes_without_asking() {
+        let gate = CloseGate::default();
+        let decision = gate.request_close(false, || panic!("空闲时不应询问"));
+        assert_eq!(decision, CloseDecision::Close);
+        assert!(!gate.operation_finished());
+    }
+
+    #[test]
+    fn test_close_during_operation_waits_for_cancel() {
+        let gate = CloseGate::default();
+
+        assert_eq!(gate.request_close(true, || false), CloseDecision::Refuse);
+        assert!(!gate.operation_finished(), "拒绝后操作结束不应退出");
+
+        assert_eq!(
+            gate.request_close(true, || true),
+            CloseDecision::CancelAndWait
+        );
+        // 等待期间再次关闭不重复询问
+        assert_eq!(
+            gate.request_close(true, || panic!("不应重复询问")),
+            CloseDecision::Refuse
+        );
+        assert!(gate.operation_finished());
+        assert!(!gate.operation_finished());
+        println!("✅ 操作进行中关闭窗口时先取消再退出");
+    }
+}
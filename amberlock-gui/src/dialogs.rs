@@ -0,0 +1,482 @@
+//! 错误与警告对话框协调
+//!
+//! 所有错误/警告对话框请求都经过 `DialogCoordinator`，避免失败的批量操作
+//! 弹出一连串模态框冻结界面：
+//! - 去重窗口内相同的（标题，正文）只显示一次
+//! - 合并窗口内同一标题的连续请求汇总为一个对话框
+//! - 同一时刻最多显示一个模态框，其余排队
+//! - 低严重级别（单个对象失败、一般警告）从不弹窗，只进入状态栏与提示面板
+//!
+//! 协调器是纯状态机，当前时间由调用方传入；实际弹窗在 `show_error_dialog` /
+//! `show_warning_dialog` 中完成。
+
+use std::collections::{HashMap, VecDeque};
+use std::time::{Duration, Instant};
+
+/// 默认去重窗口
+pub const DEFAULT_DEDUP_WINDOW: Duration = Duration::from_secs(30);
+
+/// 默认合并窗口（批量错误在此时间内到达时汇总显示）
+pub const DEFAULT_COALESCE_WINDOW: Duration = Duration::from_millis(500);
+
+/// 提示面板保留的最大条目数
+pub const DEFAULT_PANEL_CAPACITY: usize = 200;
+
+/// 对话框请求的严重级别
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+pub enum Severity {
+    /// 单个对象的失败
+    Item,
+    /// 一般警告
+    Warning,
+    /// 整个批量操作失败
+    Batch,
+    /// 致命错误（立即显示，不等待合并）
+    Fatal,
+}
+
+/// 请求的显示方式
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Route {
+    /// 状态栏 + 提示面板（非模态）
+    Panel,
+    /// 模态对话框
+    Modal,
+}
+
+/// 严重级别到显示方式的路由表
+pub const SEVERITY_ROUTES: &[(Severity, Route)] = &[
+    (Severity::Item, Route::Panel),
+    (Severity::Warning, Route::Panel),
+    (Severity::Batch, Route::Modal),
+    (Severity::Fatal, Route::Modal),
+];
+
+/// 查询严重级别的显示方式（路由表中缺失的级别按模态处理）
+pub fn route_for(severity: Severity) -> Route {
+    SEVERITY_ROUTES
+        .iter()
+        .find(|(s, _)| *s == severity)
+        .map(|(_, route)| *route)
+        .unwrap_or(Route::Modal)
+}
+
+/// 对话框请求
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct DialogRequest {
+    /// 严重级别
+    pub severity: Severity,
+    /// 标题
+    pub title: String,
+    /// 正文
+    pub message: String,
+}
+
+impl DialogRequest {
+    /// 创建请求
+    pub fn new(severity: Severity, title: impl Into<String>, message: impl Into<String>) -> Self {
+        Self {
+            severity,
+            title: title.into(),
+            message: message.into(),
+        }
+    }
+}
+
+/// 提交请求的处理结果
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Submitted {
+    /// 进入模态队列
+    Queued,
+    /// 并入尚未显示的同标题对话框
+    Coalesced,
+    /// 去重窗口内已出现过，丢弃
+    Duplicate,
+    /// 写入提示面板
+    Panel,
+}
+
+/// 待显示的模态对话框
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Dialog {
+    /// 严重级别
+    pub severity: Severity,
+    /// 标题
+    pub title: String,
+    /// 正文（合并多条时附带汇总说明）
+    pub message: String,
+}
+
+/// 提示面板条目
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct PanelEntry {
+    /// 标题
+    pub title: String,
+    /// 正文
+    pub message: String,
+    /// 连续重复次数
+    pub count: usize,
+}
+
+impl PanelEntry {
+    /// 面板中显示的单行文本
+    pub fn line(&self) -> String {
+        if self.count > 1 {
+            format!("{}：{}（×{}）", self.title, self.message, self.count)
+        } else {
+            format!("{}：{}", self.title, self.message)
+        }
+    }
+}
+
+/// 协调器参数
+#[derive(Debug, Clone)]
+pub struct DialogConfig {
+    /// 相同（标题，正文）的去重窗口
+    pub dedup_window: Duration,
+    /// 同标题请求的合并窗口
+    pub coalesce_window: Duration,
+    /// 提示面板保留的最大条目数
+    pub panel_capacity: usize,
+}
+
+impl Default for DialogConfig {
+    fn default() -> Self {
+        Self {
+            dedup_window: DEFAULT_DEDUP_WINDOW,
+            coalesce_window: DEFAULT_COALESCE_WINDOW,
+            panel_capacity: DEFAULT_PANEL_CAPACITY,
+        }
+    }
+}
+
+/// 排队中的模态对话框（可能合并了多个请求）
+#[derive(Debug)]
+struct Pending {
+    severity: Severity,
+    title: String,
+    message: String,
+    count: usize,
+    opened: Instant,
+}
+
+/// 对话框协调器
+#[derive(Debug, Default)]
+pub struct DialogCoordinator {
+    config: DialogConfig,
+    /// 最近显示过的（标题，正文）及首次出现时间
+    recent: HashMap<(String, String), Instant>,
+    pending: VecDeque<Pending>,
+    /// 是否有模态对话框正在显示
+    visible: bool,
+    panel: VecDeque<PanelEntry>,
+}
+
+impl DialogCoordinator {
+    /// 使用指定参数创建协调器
+    pub fn new(config: DialogConfig) -> Self {
+        Self {
+            config,
+            ..Default::default()
+        }
+    }
+
+    /// 提交对话框请求
+    pub fn submit(&mut self, request: DialogRequest, now: Instant) -> Submitted {
+        if route_for(request.severity) == Route::Panel {
+            self.push_panel(request);
+            return Submitted::Panel;
+        }
+
+        let window = self.config.dedup_window;
+        self.recent
+            .retain(|_, seen| now.saturating_duration_since(*seen) < window);
+        let key = (request.title.clone(), request.message.clone());
+        if self.recent.contains_key(&key) {
+            return Submitted::Duplicate;
+        }
+        self.recent.insert(key, now);
+
+        let coalesce = self.config.coalesce_window;
+        if let Some(group) = self.pending.iter_mut().find(|p| {
+            p.severity == request.severity
+                && p.title == request.title
+                && now.saturating_duration_since(p.opened) <= coalesce
+        }) {
+            group.count += 1;
+            return Submitted::Coalesced;
+        }
+
+        self.pending.push_back(Pending {
+            severity: request.severity,
+            title: request.title,
+            message: request.message,
+            count: 1,
+            opened: now,
+        });
+        Submitted::Queued
+    }
+
+    /// 取出下一个应显示的模态对话框
+    ///
+    /// 已有对话框显示时返回 None；非致命对话框在合并窗口结束后才释放，
+    /// 以便同一批次的后续错误并入汇总。调用方显示完毕后必须调用 `dismissed`
+    pub fn next_dialog(&mut self, now: Instant) -> Option<Dialog> {
+        if self.visible {
+            return None;
+        }
+        let front = self.pending.front()?;
+        if front.severity != Severity::Fatal
+            && now.saturating_duration_since(front.opened) < self.config.coalesce_window
+        {
+            return None;
+        }
+
+        let pending = self.pending.pop_front()?;
+        self.visible = true;
+        let message = if pending.count > 1 {
+            format!(
+                "{}\n\n此操作产生了 {} 个相似错误，已汇总",
+                pending.message, pending.count
+            )
+        } else {
+            pending.message
+        };
+        Some(Dialog {
+            severity: pending.severity,
+            title: pending.title,
+            message,
+        })
+    }
+
+    /// 当前模态对话框已关闭
+    pub fn dismissed(&mut self) {
+        self.visible = false;
+    }
+
+    /// 排队中的模态对话框数
+    pub fn queued(&self) -> usize {
+        self.pending.len()
+    }
+
+    /// 提示面板条目（最新的在后）
+    pub fn panel(&self) -> impl Iterator<Item = &PanelEntry> {
+        self.panel.iter()
+    }
+
+    /// 清空提示面板
+    pub fn clear_panel(&mut self) {
+        self.panel.clear();
+    }
+
+    /// 写入提示面板，与上一条相同时只累加次数
+    fn push_panel(&mut self, request: DialogRequest) {
+        if let Some(last) = self.panel.back_mut()
+            && last.title == request.title
+            && last.message == request.message
+        {
+            last.count += 1;
+            return;
+        }
+        if self.panel.len() >= self.config.panel_capacity {
+            self.panel.pop_front();
+        }
+        self.panel.push_back(PanelEntry {
+            title: request.title,
+            message: request.message,
+            count: 1,
+        });
+    }
+}
+
+/// 显示阻塞的错误对话框
+pub fn show_error_dialog(title: &str, message: &str) {
+    rfd::MessageDialog::new()
+        .set_title(title)
+        .set_level(rfd::MessageLevel::Error)
+        .set_description(message)
+        .set_buttons(rfd::MessageButtons::Ok)
+        .show();
+}
+
+/// 显示阻塞的警告对话框
+pub fn show_warning_dialog(title: &str, message: &str) {
+    rfd::MessageDialog::new()
+        .set_title(title)
+        .set_level(rfd::MessageLevel::Warning)
+        .set_description(message)
+        .set_buttons(rfd::MessageButtons::Ok)
+        .show();
+}
+
+/// 按严重级别显示协调器释放的对话框
+pub fn show_dialog(dialog: &Dialog) {
+    match dialog.severity {
+        Severity::Batch | Severity::Fatal => show_error_dialog(&dialog.title, &dialog.message),
+        Severity::Item | Severity::Warning => show_warning_dialog(&dialog.title, &dialog.message),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    /// 测试用时钟：从固定起点按毫秒推进
+    struct FakeClock {
+        start: Instant,
+        elapsed: Duration,
+    }
+
+    impl FakeClock {
+        fn new() -> Self {
+            Self {
+                start: Instant::now(),
+                elapsed: Duration::ZERO,
+            }
+        }
+
+        fn advance(&mut self, ms: u64) -> Instant {
+            self.elapsed += Duration::from_millis(ms);
+            self.now()
+        }
+
+        fn now(&self) -> Instant {
+            self.start + self.elapsed
+        }
+    }
+
+    fn batch(message: &str) -> DialogRequest {
+        DialogRequest::new(Severity::Batch, "上锁失败", message)
+    }
+
+    #[test]
+    fn test_routes_come_from_table() {
+        assert_eq!(route_for(Severity::Item), Route::Panel);
+        assert_eq!(route_for(Severity::Warning), Route::Panel);
+        assert_eq!(route_for(Severity::Batch), Route::Modal);
+        assert_eq!(route_for(Severity::Fatal), Route::Modal);
+    }
+
+    #[test]
+    fn test_burst_is_coalesced_into_one_summary() {
+        let mut clock = FakeClock::new();
+        let mut coordinator = DialogCoordinator::default();
+
+        assert_eq!(
+            coordinator.submit(batch("错误 0"), clock.now()),
+            Submitted::Queued
+        );
+        for i in 1..37 {
+            let now = clock.advance(10);
+            assert_eq!(
+                coordinator.submit(batch(&format!("错误 {}", i)), now),
+                Submitted::Coalesced
+            );
+        }
+
+        // 合并窗口未结束前不释放
+        assert!(coordinator.next_dialog(clock.now()).is_none());
+        let dialog = coordinator
+            .next_dialog(clock.advance(500))
+            .expect("合并窗口结束后应显示");
+        assert_eq!(dialog.title, "上锁失败");
+        assert!(dialog.message.starts_with("错误 0"));
+        assert!(
+            dialog
+                .message
+                .ends_with("此操作产生了 37 个相似错误，已汇总")
+        );
+        assert_eq!(coordinator.queued(), 0);
+    }
+
+    #[test]
+    fn test_identical_requests_are_deduplicated_within_window() {
+        let mut clock = FakeClock::new();
+        let mut coordinator = DialogCoordinator::default();
+
+        assert_eq!(
+            coordinator.submit(batch("磁盘已满"), clock.now()),
+            Submitted::Queued
+        );
+        let dialog = coordinator.next_dialog(clock.advance(600)).unwrap();
+        assert_eq!(dialog.message, "磁盘已满");
+        coordinator.dismissed();
+
+        let now = clock.advance(1_000);
+        assert_eq!(
+            coordinator.submit(batch("磁盘已满"), now),
+            Submitted::Duplicate
+        );
+        assert!(coordinator.next_dialog(clock.advance(600)).is_none());
+
+        // 去重窗口过后再次出现
+        let now = clock.advance(30_000);
+        assert_eq!(
+            coordinator.submit(batch("磁盘已满"), now),
+            Submitted::Queued
+        );
+    }
+
+    #[test]
+    fn test_only_one_modal_visible_at_a_time() {
+        let mut clock = FakeClock::new();
+        let mut coordinator = DialogCoordinator::default();
+
+        coordinator.submit(
+            DialogRequest::new(Severity::Fatal, "致命错误", "A"),
+            clock.now(),
+        );
+        coordinator.submit(
+            DialogRequest::new(Severity::Batch, "解锁失败", "B"),
+            clock.now(),
+        );
+
+        // 致命错误不等待合并窗口
+        let first = coordinator.next_dialog(clock.now()).unwrap();
+        assert_eq!(first.title, "致命错误");
+
+        // 第一个未关闭时后续请求排队
+        assert!(coordinator.next_dialog(clock.advance(1_000)).is_none());
+        assert_eq!(coordinator.queued(), 1);
+
+        coordinator.dismissed();
+        let second = coordinator.next_dialog(clock.now()).unwrap();
+        assert_eq!(second.title, "解锁失败");
+        assert!(coordinator.next_dialog(clock.now()).is_none());
+        coordinator.dismissed();
+        assert!(coordinator.next_dialog(clock.now()).is_none());
+    }
+
+    #[test]
+    fn test_low_severity_goes_to_panel_only() {
+        let clock = FakeClock::new();
+        let mut coordinator = DialogCoordinator::new(DialogConfig {
+            panel_capacity: 2,
+            ..Default::default()
+        });
+
+        for _ in 0..3 {
+            let request = DialogRequest::new(Severity::Item, "C:\\a.txt", "拒绝访问");
+            assert_eq!(coordinator.submit(request, clock.now()), Submitted::Panel);
+        }
+        coordinator.submit(
+            DialogRequest::new(Severity::Item, "C:\\b.txt", "拒绝访问"),
+            clock.now(),
+        );
+        coordinator.submit(
+            DialogRequest::new(Severity::Warning, "网络共享", "不支持标签"),
+            clock.now(),
+        );
+
+        assert!(coordinator.next_dialog(clock.now()).is_none());
+        let lines: Vec<_> = coordinator.panel().map(PanelEntry::line).collect();
+        assert_eq!(
+            lines,
+            vec!["C:\\b.txt：拒绝访问", "网络共享：不支持标签"],
+            "超出容量时丢弃最早的条目"
+        );
+
+        coordinator.clear_panel();
+        assert_eq!(coordinator.panel().count(), 0);
+    }
+}
@@ -0,0 +1,359 @@
+//! 解锁的二次验证（Windows Hello）
+//!
+//! 保险库密码仍是唯一的根凭据，Windows Hello（PIN、指纹、面部）只是确认“本人在场”的便捷方式：
+//! - 本次运行中至少输入过一次正确的保险库密码（证明保险库确实可用）之后，
+//!   启用 `allow_hello_unlock` 时可用 Windows Hello 代替密码打开解锁宽限期
+//! - 宽限期内解锁无需再次输入密码，过期后重新验证
+//! - 关闭只读审计模式、撤销批次等敏感操作始终需要输入保险库密码，不受宽限期与 Windows Hello 影响
+//!
+//! Windows Hello 不可用、被取消或验证失败时回退到输入密码。
+//!
+//! # 手动测试
+//! 1. 在"设置 → 账户 → 登录选项"中配置 Windows Hello PIN，配置文件中设置 `"allow_hello_unlock": true`
+//! 2. 启动后直接点击"Windows Hello"：提示需要先输入一次保险库密码，不弹出 Windows Hello
+//! 3. 输入密码解锁一次；5 分钟后再次解锁，点击"Windows Hello"，完成 PIN 验证后直接开始解锁
+//! 4. 在 Windows Hello 对话框中取消：提示改为输入密码，不开始解锁
+//! 5. 宽限期内退出只读审计模式：仍需输入保险库密码
+//! 6. 在未配置 Windows Hello 的账户上启动：不显示"Windows Hello"按钮
+
+use std::fmt::{Display, Formatter};
+use std::time::{Duration, Instant};
+
+/// 解锁宽限期：验证后在此期间内解锁无需再次输入密码
+pub const UNLOCK_GRACE: Duration = Duration::from_secs(5 * 60);
+
+/// 二次验证通过的凭证
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct Verified;
+
+/// 二次验证提供者
+pub trait AuthProvider: Send + Sync {
+    /// 显示名称（用于提示文字）
+    fn name(&self) -> &str;
+
+    /// 当前设备与账户是否可以使用
+    fn is_available(&self) -> bool;
+
+    /// 请求用户验证（阻塞直到用户完成或取消）
+    ///
+    /// # 参数
+    /// - `reason`: 显示在系统验证对话框中的说明
+    fn request_verification(&self, reason: &str) -> anyhow::Result<Verified>;
+}
+
+/// 需要保险库授权的操作
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum ProtectedAction {
+    /// 解锁选中的对象
+    Unlock,
+    /// 关闭只读审计模式
+    ExitReadOnly,
+    /// 撤销整批操作
+    RevertBatch,
+}
+
+impl ProtectedAction {
+    /// 是否可以使用解锁宽限期或 Windows Hello（其余操作始终需要输入密码）
+    pub fn allows_session(self) -> bool {
+        matches!(self, ProtectedAction::Unlock)
+    }
+}
+
+/// Windows Hello 未能代替密码的原因
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum HelloFallback {
+    /// 设置中未启用
+    Disabled,
+    /// 本次运行中尚未输入过保险库密码
+    PasswordNotYetEntered,
+    /// 设备或账户不支持
+    Unavailable,
+    /// 用户取消或验证失败
+    Failed(String),
+}
+
+impl Display for HelloFallback {
+    fn fmt(&self, f: &mut Formatter<'_>) -> std::fmt::Result {
+        match self {
+            HelloFallback::Disabled => write!(f, "未启用 Windows Hello 解锁，请输入保险库密码"),
+            HelloFallback::PasswordNotYetEntered => {
+                write!(
+                    f,
+                    "本次运行需要先输入一次保险库密码，之后才能使用 Windows Hello"
+                )
+            }
+            HelloFallback::Unavailable => write!(f, "Windows Hello 不可用，请输入保险库密码"),
+            HelloFallback::Failed(reason) => write!(f, "{}，请输入保险库密码", reason),
+        }
+    }
+}
+
+/// 本次运行的解锁授权状态
+#[derive(Debug, Clone)]
+pub struct UnlockSession {
+    grace: Duration,
+    /// 本次运行中是否输入过正确的保险库密码
+    password_verified: bool,
+    /// 宽限期结束时刻
+    open_until: Option<Instant>,
+}
+
+impl Default for UnlockSession {
+    fn default() -> Self {
+        Self::new(UNLOCK_GRACE)
+    }
+}
+
+impl UnlockSession {
+    /// 创建会话
+    ///
+    /// # 参数
+    /// - `grace`: 每次验证后宽限期的长度
+    pub fn new(grace: Duration) -> Self {
+        Self {
+            grace,
+            password_verified: false,
+            open_until: None,
+        }
+    }
+
+    /// 宽限期是否仍有效
+    pub fn is_open(&self, now: Instant) -> bool {
+        self.open_until.is_some_and(|until| now < until)
+    }
+
+    /// 本次运行中是否输入过正确的保险库密码
+    pub fn password_verified(&self) -> bool {
+        self.password_verified
+    }
+
+    /// 执行 `action` 是否可以不输入密码
+    pub fn skips_password(&self, action: ProtectedAction, now: Instant) -> bool {
+        action.allows_session() && self.is_open(now)
+    }
+
+    /// 记录一次正确的保险库密码并打开宽限期
+    pub fn record_password(&mut self, now: Instant) {
+        self.password_verified = true;
+        self.open_until = Some(now + self.grace);
+    }
+
+    /// 检查能否请求 Windows Hello 验证（不调用验证对话框）
+    pub fn hello_precheck(
+        &self,
+        allow_hello: bool,
+        provider: &dyn AuthProvider,
+    ) -> Result<(), HelloFallback> {
+        if !allow_hello {
+            return Err(HelloFallback::Disabled);
+        }
+        if !self.password_verified {
+            return Err(HelloFallback::PasswordNotYetEntered);
+        }
+        if !provider.is_available() {
+            return Err(HelloFallback::Unavailable);
+        }
+        Ok(())
+    }
+
+    /// 记录一次通过的 Windows Hello 验证并打开宽限期
+    ///
+    /// # 注意
+    /// 尚未输入过保险库密码时不打开宽限期（`Verified` 只能来自 `hello_precheck` 之后的验证）
+    pub fn record_hello(&mut self, _verified: Verified, now: Instant) -> Result<(), HelloFallback> {
+        if !self.password_verified {
+            return Err(HelloFallback::PasswordNotYetEntered);
+        }
+        self.open_until = Some(now + self.grace);
+        Ok(())
+    }
+
+    /// 用 Windows Hello 代替密码打开宽限期
+    ///
+    /// # 返回
+    /// - `Ok(())`: 验证通过，宽限期已打开
+    /// - `Err(fallback)`: 需要改为输入密码（未启用、尚未输入过密码、不可用或验证未通过）
+    pub fn try_hello(
+        &mut self,
+        allow_hello: bool,
+        provider: &dyn AuthProvider,
+        reason: &str,
+        now: Instant,
+    ) -> Result<(), HelloFallback> {
+        self.hello_precheck(allow_hello, provider)?;
+        let verified = provider
+            .request_verification(reason)
+            .map_err(|e| HelloFallback::Failed(e.to_string()))?;
+        self.record_hello(verified, now)
+    }
+}
+
+/// 基于 WinRT `UserConsentVerifier` 的 Windows Hello 验证
+#[derive(Debug, Default, Clone, Copy)]
+pub struct WindowsHelloProvider;
+
+impl AuthProvider for WindowsHelloProvider {
+    fn name(&self) -> &str {
+        "Windows Hello"
+    }
+
+    fn is_available(&self) -> bool {
+        winrt::check_availability()
+    }
+
+    fn request_verification(&self, reason: &str) -> anyhow::Result<Verified> {
+        winrt::request_verification(reason)
+    }
+}
+
+mod winrt {
+    use super::Verified;
+    use windows::Security::Credentials::UI::{
+        UserConsentVerificationResult, UserConsentVerifier, UserConsentVerifierAvailability,
+    };
+    use windows::core::HSTRING;
+
+    pub(super) fn check_availability() -> bool {
+        UserConsentVerifier::CheckAvailabilityAsync()
+            .and_then(|operation| operation.join())
+            .is_ok_and(|availability| availability == UserConsentVerifierAvailability::Available)
+    }
+
+    pub(super) fn request_verification(reason: &str) -> anyhow::Result<Verified> {
+        let result =
+            UserConsentVerifier::RequestVerificationAsync(&HSTRING::from(reason))?.join()?;
+        match result {
+            UserConsentVerificationResult::Verified => Ok(Verified),
+            UserConsentVerificationResult::Canceled => anyhow::bail!("已取消 Windows Hello 验证"),
+            UserConsentVerificationResult::RetriesExhausted => {
+                anyhow::bail!("Windows Hello 验证失败次数过多")
+            }
+            other => anyhow::bail!("Windows Hello 验证未通过（状态 {}）", other.0),
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::sync::atomic::{AtomicUsize, Ordering};
+
+    /// 按预设结果应答的验证提供者，统计验证请求次数
+    struct MockProvider {
+        available: bool,
+        accept: bool,
+        requests: AtomicUsize,
+    }
+
+    impl MockProvider {
+        fn new(available: bool, accept: bool) -> Self {
+            Self {
+                available,
+                accept,
+                requests: AtomicUsize::new(0),
+            }
+        }
+
+        fn requests(&self) -> usize {
+            self.requests.load(Ordering::SeqCst)
+        }
+    }
+
+    impl AuthProvider for MockProvider {
+        fn name(&self) -> &str {
+            "Mock"
+        }
+
+        fn is_available(&self) -> bool {
+            self.available
+        }
+
+        fn request_verification(&self, _reason: &str) -> anyhow::Result<Verified> {
+            self.requests.fetch_add(1, Ordering::SeqCst);
+            if self.accept {
+                Ok(Verified)
+            } else {
+                anyhow::bail!("已取消 Windows Hello 验证")
+            }
+        }
+    }
+
+    #[test]
+    fn test_availability_and_setting_gate_hello() {
+        let now = Instant::now();
+        let mut session = UnlockSession::default();
+        session.record_password(now);
+        session.open_until = None;
+
+        let unavailable = MockProvider::new(false, true);
+        assert_eq!(
+            session.try_hello(true, &unavailable, "解锁", now),
+            Err(HelloFallback::Unavailable)
+        );
+        let available = MockProvider::new(true, true);
+        assert_eq!(
+            session.try_hello(false, &available, "解锁", now),
+            Err(HelloFallback::Disabled)
+        );
+        assert_eq!(unavailable.requests() + available.requests(), 0);
+        assert!(!session.is_open(now));
+        println!("✅ 未启用或不可用时不请求 Windows Hello");
+    }
+
+    #[test]
+    fn test_hello_requires_password_once_per_session() {
+        let start = Instant::now();
+        let provider = MockProvider::new(true, true);
+        let mut session = UnlockSession::new(Duration::from_secs(60));
+
+        // 尚未输入过密码：不弹出验证，也不打开宽限期
+        assert_eq!(
+            session.try_hello(true, &provider, "解锁", start),
+            Err(HelloFallback::PasswordNotYetEntered)
+        );
+        assert_eq!(provider.requests(), 0);
+        assert_eq!(
+            session.record_hello(Verified, start),
+            Err(HelloFallback::PasswordNotYetEntered)
+        );
+        assert!(!session.skips_password(ProtectedAction::Unlock, start));
+
+        // 输入密码后宽限期内无需密码，过期后需要重新验证
+        session.record_password(start);
+        assert!(session.skips_password(ProtectedAction::Unlock, start + Duration::from_secs(59)));
+        let later = start + Duration::from_secs(120);
+        assert!(!session.skips_password(ProtectedAction::Unlock, later));
+
+        // 之后 Windows Hello 可以重新打开宽限期
+        assert_eq!(session.try_hello(true, &provider, "解锁", later), Ok(()));
+        assert_eq!(provider.requests(), 1);
+        assert!(session.skips_password(ProtectedAction::Unlock, later));
+
+        // 敏感操作始终需要密码
+        for action in [ProtectedAction::ExitReadOnly, ProtectedAction::RevertBatch] {
+            assert!(!session.skips_password(action, later));
+        }
+        println!("✅ Windows Hello 只在输入过密码后代替密码");
+    }
+
+    #[test]
+    fn test_failed_verification_falls_back_to_password() {
+        let start = Instant::now();
+        let provider = MockProvider::new(true, false);
+        let mut session = UnlockSession::new(Duration::from_secs(60));
+        session.record_password(start);
+        let later = start + Duration::from_secs(120);
+
+        let fallback = session
+            .try_hello(true, &provider, "解锁", later)
+            .expect_err("取消时应回退到密码");
+        assert!(matches!(fallback, HelloFallback::Failed(_)));
+        assert!(fallback.to_string().ends_with("请输入保险库密码"));
+        assert!(!session.is_open(later));
+
+        // 回退后输入密码照常打开宽限期
+        session.record_password(later);
+        assert!(session.skips_password(ProtectedAction::Unlock, later));
+        println!("✅ 验证失败时回退到输入密码");
+    }
+}
@@ -0,0 +1,231 @@
+//! 文件列表的标签状态
+//!
+//! 文件列表每行以徽标显示对象当前的完整性级别。标签在后台线程中按批读取，
+//! 结果经通道交还 UI 线程（见 `FileListModel::poll_label_results`），不阻塞界面。
+//! 目录显示目录对象自身的标签，不汇总子项。
+
+use amberlock_core::LabelBackend;
+use amberlock_storage::RecordStamper;
+use amberlock_types::LabelLevel;
+use std::path::{Path, PathBuf};
+use std::sync::mpsc::{self, Receiver, Sender};
+use std::sync::{Arc, Mutex};
+use std::time::Duration;
+
+/// 行的标签状态（整数值与 Slint `FileItem.label_state` 一致）
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default)]
+#[repr(i32)]
+pub enum LabelState {
+    /// 尚未读取（或操作后已失效）
+    #[default]
+    Pending = 0,
+    /// 未上锁（无显式标签或 Medium）
+    Unlocked = 1,
+    /// High 完整性级别
+    High = 2,
+    /// System 完整性级别
+    System = 3,
+    /// 目录内级别不一致（预留给后续的汇总功能）
+    Mixed = 4,
+    /// 无法读取
+    Unknown = 5,
+}
+
+impl LabelState {
+    /// 由完整性级别得到状态
+    pub fn from_level(level: LabelLevel) -> Self {
+        match level {
+            LabelLevel::Medium => Self::Unlocked,
+            LabelLevel::High => Self::High,
+            LabelLevel::System => Self::System,
+        }
+    }
+
+    /// 传给 Slint 的整数值
+    pub fn as_i32(self) -> i32 {
+        self as i32
+    }
+
+    /// 徽标文字（尚未读取时为空，不显示徽标）
+    pub fn badge_text(self) -> &'static str {
+        match self {
+            Self::Pending => "",
+            Self::Unlocked => "未上锁",
+            Self::High => "High",
+            Self::System => "System",
+            Self::Mixed => "混合",
+            Self::Unknown => "?",
+        }
+    }
+
+    /// 按保护状态排序的键：未受保护的对象在前
+    pub fn sort_key(self) -> u8 {
+        match self {
+            Self::Unlocked => 0,
+            Self::Unknown => 1,
+            Self::Pending => 2,
+            Self::Mixed => 3,
+            Self::High => 4,
+            Self::System => 5,
+        }
+    }
+}
+
+/// 读取对象的标签状态（读取失败为 `Unknown`）
+pub fn read_state(backend: &dyn LabelBackend, path: &Path) -> LabelState {
+    match backend.get_label(&path.to_string_lossy()) {
+        Ok(label) => LabelState::from_level(label.level),
+        Err(_) => LabelState::Unknown,
+    }
+}
+
+/// 一次标签读取请求
+#[derive(Debug, Clone)]
+pub struct LabelRequest {
+    /// 对象路径
+    pub path: PathBuf,
+    /// 请求发出时该行的版本（行失效后版本递增，旧结果被丢弃）
+    pub generation: u64,
+}
+
+/// 一次标签读取结果
+#[derive(Debug, Clone)]
+pub struct LabelReading {
+    /// 对象路径
+    pub path: PathBuf,
+    /// 对应请求的版本
+    pub generation: u64,
+    /// 读取到的状态
+    pub state: LabelState,
+    /// 读取时间（RFC 3339）
+    pub checked_at: String,
+}
+
+/// 后台标签读取线程
+///
+/// 线程在所有请求发送端被丢弃（即模型被释放）后退出
+pub struct LabelRefresher {
+    requests: Sender<Vec<LabelRequest>>,
+    results: Mutex<Receiver<Vec<LabelReading>>>,
+}
+
+impl LabelRefresher {
+    /// 启动读取线程
+    ///
+    /// # 参数
+    /// - `backend`: 标签读取后端
+    /// - `stamper`: 读取时间来源
+    pub fn spawn(backend: Arc<dyn LabelBackend>, stamper: Arc<dyn RecordStamper>) -> Self {
+        let (request_tx, request_rx) = mpsc::channel::<Vec<LabelRequest>>();
+        let (result_tx, result_rx) = mpsc::channel();
+
+        std::thread::Builder::new()
+            .name("amberlock-labels".to_string())
+            .spawn(move || {
+                for batch in request_rx {
+                    let readings: Vec<LabelReading> = batch
+                        .into_iter()
+                        .map(|request| LabelReading {
+                            state: read_state(backend.as_ref(), &request.path),
+                            checked_at: stamper.now(),
+                            path: request.path,
+                            generation: request.generation,
+                        })
+                        .collect();
+                    if result_tx.send(readings).is_err() {
+                        break;
+                    }
+                }
+            })
+            .expect("无法启动标签读取线程");
+
+        Self {
+            requests: request_tx,
+            results: Mutex::new(result_rx),
+        }
+    }
+
+    /// 提交一批读取请求
+    ///
+    /// # 返回
+    /// 读取线程已退出时返回 `false`
+    pub fn submit(&self, batch: Vec<LabelRequest>) -> bool {
+        self.requests.send(batch).is_ok()
+    }
+
+    /// 取出已完成的全部结果（不阻塞）
+    pub fn try_results(&self) -> Vec<LabelReading> {
+        let results = self.results.lock().expect("LabelRefresher lock poisoned");
+        results.try_iter().flatten().collect()
+    }
+
+    /// 等待下一批结果
+    pub fn wait_results(&self, timeout: Duration) -> Option<Vec<LabelReading>> {
+        let results = self.results.lock().expect("LabelRefresher lock poisoned");
+        results.recv_timeout(timeout).ok()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use amberlock_core::{FakeBackend, LabelOp};
+    use amberlock_storage::FixedStamper;
+
+    #[test]
+    fn test_refresher_reads_batches() {
+        let backend = FakeBackend::new().with_label(r"C:\locked.txt", LabelLevel::System);
+        backend.fail_next(LabelOp::Get, r"C:\denied.txt", 5);
+        let refresher = LabelRefresher::spawn(
+            Arc::new(backend),
+            Arc::new(FixedStamper::new("2025-01-01T00:00:00Z", "id")),
+        );
+
+        let requests = [r"C:\locked.txt", r"C:\plain.txt", r"C:\denied.txt"]
+            .into_iter()
+            .map(|path| LabelRequest {
+                path: PathBuf::from(path),
+                generation: 7,
+            })
+            .collect();
+        assert!(refresher.submit(requests));
+
+        let readings = refresher
+            .wait_results(Duration::from_secs(5))
+            .expect("未收到读取结果");
+        let states: Vec<LabelState> = readings.iter().map(|r| r.state).collect();
+        assert_eq!(
+            states,
+            vec![
+                LabelState::System,
+                LabelState::Unlocked,
+                LabelState::Unknown
+            ]
+        );
+        assert!(readings.iter().all(|r| r.generation == 7));
+        assert_eq!(readings[0].checked_at, "2025-01-01T00:00:00Z");
+        println!("✅ 后台按批读取标签，读取失败记为未知");
+    }
+
+    #[test]
+    fn test_sort_key_puts_unprotected_first() {
+        let mut states = vec![
+            LabelState::System,
+            LabelState::Pending,
+            LabelState::High,
+            LabelState::Unknown,
+            LabelState::Unlocked,
+        ];
+        states.sort_by_key(|state| state.sort_key());
+        assert_eq!(
+            states,
+            vec![
+                LabelState::Unlocked,
+                LabelState::Unknown,
+                LabelState::Pending,
+                LabelState::High,
+                LabelState::System,
+            ]
+        );
+    }
+}
@@ -1,4 +1,17 @@
 slint::include_modules!();
+pub mod a11y;
 pub mod bridge;
+pub mod clipboard;
+pub mod closing;
+pub mod dialogs;
+pub mod hello;
+pub mod labels;
 pub mod model;
+pub mod notify;
 pub mod privileged;
+pub mod shortcuts;
+pub mod theme;
+pub mod third_party;
+pub mod updates;
+pub mod vault;
+pub mod volumes;
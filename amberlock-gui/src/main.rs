@@ -1,17 +1,129 @@
 //! AmberLock 图形用户界面主应用程序模块
 //!
 
-use amberlock_core::{LockOptions, batch_process_lock, batch_process_unlock};
+use amberlock_core::{
+    ActiveOperations, AlertPolicy, AppController, BatchOutcome, BatchResult, BatchRevert,
+    DECOMMISSION_CONFIRM_PHRASE, DEFAULT_INVENTORY_SAMPLE, DirSummary, InventoryTracker,
+    LockOptions, OperationHandle, OperationKind, PresetPlan, ProgressTracker, ProtectionPreset,
+    RESCAN_CHUNK_PAUSE, RepairOptions, RescanQueue, ScheduleOutcome, SealPlan, ShareProbeCache,
+    StartupComponent, StartupEvent, StartupPhase, StartupReport, StartupTask, TraceHeader,
+    TraceRecorder, UnprotectAll, UnprotectReport, UnprotectScope, VolumeThrottle, WebhookSink,
+    WinsecBackend, batch_entries, can_lift_label, check_confirm_phrase, check_inventory,
+    deliver_alert, diagnose_log_blockage, flush_backlog_with_label_lifted, force_repair,
+    gather_startup_report, install_panic_hook, is_read_only, is_volume_root, plan_preset,
+    preview_seal, probe_network_path, relocate_log, rescan_root, roots_for_event, run_due_now,
+    sanitize_comment, save_session_pair, set_read_only, spawn_batch_lock, spawn_batch_unlock,
+    spawn_preset, spawn_unlock_batch, subtree_query, unc_share, unprotect_all_now,
+};
+use amberlock_auth::{BackoffConfig, DEFAULT_CREDENTIAL, SecretString};
 use amberlock_gui::{
-    MainWindow, bridge,
+    LogRow, MainWindow, Mode, ShortcutScope, a11y, bridge,
+    clipboard::{self, SystemClipboard},
+    closing::{CloseDecision, CloseGate},
+    dialogs::{self, DialogCoordinator, DialogRequest, Route, Severity},
+    hello::{AuthProvider, HelloFallback, ProtectedAction, UnlockSession, WindowsHelloProvider},
     model::{FileListModel, LogListModel},
+    notify::{self, Notifier, SystemNotifier},
+    shortcuts::{self, Command, Scope, ShortcutState},
+    theme::{self, RegistryThemeSource, SystemThemeSource},
+    third_party, updates, vault,
+    volumes::{DeviceNotificationWindow, VolumeEventSource},
+};
+use amberlock_storage::housekeeping::{
+    DIAGNOSTICS_FILE_NAME, REPLICA_SPOOL_FILE_NAME, SETTINGS_FILE_NAME, TRACE_FILE_PREFIX,
+};
+use amberlock_storage::query::{
+    Metric, QueryBuilder, TimeWindow, Timeseries, TimeseriesSpec, generate_timeseries,
+};
+use amberlock_storage::{
+    ArtifactCategory, CleanupPlan, ExportOptions, OperationLog, ReplicationSink, SpoolStatus,
+    SystemStamper, cleanup, compare_logs, export_log, flush_all_logs, has_field_syntax,
+    load_settings, parse_query, restore_from_replica, save_settings, storage_report,
 };
-use amberlock_storage::{NdjsonWriter, load_settings, save_settings};
 use amberlock_types::*;
 use amberlock_winsec::{compute_effective_level, read_user_sid, token};
-use slint::ComponentHandle;
-use std::path::PathBuf;
+use slint::{
+    CloseRequestResponse, ComponentHandle, Model, SharedString, Timer, TimerMode, VecModel,
+};
+use std::cell::{Cell, RefCell};
+use std::collections::HashSet;
+use std::path::{Path, PathBuf};
+use std::rc::Rc;
 use std::sync::{Arc, Mutex, RwLock};
+use std::time::{Duration, Instant, SystemTime};
+
+/// 强制只读审计模式的启动参数
+const READ_ONLY_FLAG: &str = "--read-only";
+
+/// 执行到期计划任务后退出的子命令（供 Windows 任务计划程序调用）
+const SCHEDULE_RUN_DUE_COMMAND: [&str; 2] = ["schedule", "run-due"];
+
+/// 导出操作日志后退出的子命令：`log export <输出文件> [--expand]`
+const LOG_EXPORT_COMMAND: [&str; 2] = ["log", "export"];
+
+/// 导出时将压缩的 SDDL 字段还原为普通字符串
+const EXPAND_FLAG: &str = "--expand";
+
+/// 输出最近的日志记录后退出的子命令：`log tail [--query 查询语句] [-n 条数]`
+const LOG_TAIL_COMMAND: [&str; 2] = ["log", "tail"];
+
+/// `log tail` 的查询语句参数（语法同界面日志筛选框）
+const QUERY_FLAG: &str = "--query";
+
+/// `log tail` 的条数参数
+const COUNT_FLAG: &str = "-n";
+
+/// `log tail` 默认输出的条数
+const LOG_TAIL_DEFAULT_COUNT: usize = 20;
+
+/// 输出数据目录占用报告后退出的子命令
+const STORAGE_REPORT_COMMAND: [&str; 2] = ["storage", "report"];
+
+/// 清理数据目录后退出的子命令：`storage cleanup [类别...] [--older-than-days N]`
+const STORAGE_CLEANUP_COMMAND: [&str; 2] = ["storage", "cleanup"];
+
+/// 清理时覆盖各类别默认保留期的天数
+const OLDER_THAN_DAYS_FLAG: &str = "--older-than-days";
+
+/// 停用本机前移除全部保护后退出的子命令：
+/// `unprotect-all --confirm-phrase 全部解除保护 [--scope inventory|log] [--scan 目录...]`
+const UNPROTECT_ALL_COMMAND: &str = "unprotect-all";
+
+/// `unprotect-all` 的确认短语参数（须为 `DECOMMISSION_CONFIRM_PHRASE`）
+const CONFIRM_PHRASE_FLAG: &str = "--confirm-phrase";
+
+/// `unprotect-all` 的目标来源参数
+const SCOPE_FLAG: &str = "--scope";
+
+/// `unprotect-all` 改为扫描目录（其后的参数均为目录）
+const SCAN_FLAG: &str = "--scan";
+
+/// 默认操作日志文件名
+const LOG_FILE_NAME: &str = "amberlock-log.ndjson";
+
+/// 活动趋势卡片显示的天数
+const DASHBOARD_DAYS: usize = 30;
+
+/// 文件列表标签状态的刷新间隔
+const LABEL_REFRESH_INTERVAL: Duration = Duration::from_millis(250);
+
+/// 封印确认对话框中逐条列出变更的对象数
+const SEAL_PREVIEW_DETAIL: usize = 5;
+
+/// 对话框队列的检查间隔
+const DIALOG_PUMP_INTERVAL: Duration = Duration::from_millis(200);
+
+/// 后台计划任务的检查间隔
+const SCHEDULE_CHECK_INTERVAL: Duration = Duration::from_secs(30);
+
+/// 重新验证线程等待卷事件的间隔（期间检查排队中的请求能否开始）
+const RESCAN_POLL_INTERVAL: Duration = Duration::from_secs(1);
+
+/// 启动阶段取回后台加载结果的间隔
+const STARTUP_POLL_INTERVAL: Duration = Duration::from_millis(50);
+
+/// 启动时显示的最近日志条数
+const STARTUP_LOG_TAIL: usize = 200;
 
 /// AmberLock GUI 应用程序的主入口点
 ///
@@ -20,20 +132,56 @@ use std::sync::{Arc, Mutex, RwLock};
 /// 2. 初始化数据模型和日志记录器
 /// 3. 设置用户界面初始状态
 /// 4. 绑定所有用户界面事件处理器
-/// 5. 检查并显示系统能力警告
+/// 5. 显示窗口，在后台依次加载启动自检、活动趋势与最近日志
 /// 6. 运行 GUI 主事件循环
 /// 7. 退出时保存当前设置
 ///
 ///
 fn main() -> anyhow::Result<()> {
+    let args: Vec<String> = std::env::args().skip(1).collect();
+    if args.iter().map(String::as_str).eq(SCHEDULE_RUN_DUE_COMMAND) {
+        return run_schedule_command();
+    }
+    if args.len() >= 2 && args[..2].iter().map(String::as_str).eq(LOG_EXPORT_COMMAND) {
+        return run_log_export_command(&args[2..]);
+    }
+    if args.len() >= 2 && args[..2].iter().map(String::as_str).eq(LOG_TAIL_COMMAND) {
+        return run_log_tail_command(&args[2..]);
+    }
+    if args.iter().map(String::as_str).eq(STORAGE_REPORT_COMMAND) {
+        let settings = load_settings(get_settings_path()?)?;
+        println!("{}", storage_report(&settings)?);
+        return Ok(());
+    }
+    if args.len() >= 2
+        && args[..2]
+            .iter()
+            .map(String::as_str)
+            .eq(STORAGE_CLEANUP_COMMAND)
+    {
+        return run_storage_cleanup_command(&args[2..]);
+    }
+    if args.first().map(String::as_str) == Some(UNPROTECT_ALL_COMMAND) {
+        return run_unprotect_all_command(&args[1..]);
+    }
+
     let app = MainWindow::new()?;
+    a11y::set_status(&app, "正在加载…".into());
 
     // 加载设置
     let settings = load_application_settings()?;
-    let (logger, file, log_model, user_sid, effective_level) =
+    let (logger, inventory, file, log_model, user_sid, effective_level) =
         initialize_application_models(&settings)?;
 
-    setup_initial_ui_state(&app, file.clone(), log_model.clone())?;
+    // panic 时写入诊断日志、刷新所有日志并记录 process_panic
+    let diagnostics_path =
+        Path::new(&settings.read().unwrap().log_path).with_file_name(DIAGNOSTICS_FILE_NAME);
+    install_panic_hook(logger.clone(), user_sid.clone(), Some(diagnostics_path));
+
+    setup_initial_ui_state(&app, file.clone())?;
+    apply_read_only_mode(&app, &settings.read().unwrap());
+    refresh_totp_required(&app, Path::new(&settings.read().unwrap().vault_path));
+    setup_theme(&app, settings.clone());
 
     // 绑定所有用户界面事件处理器
     setup_event_handlers(
@@ -41,19 +189,43 @@ fn main() -> anyhow::Result<()> {
         settings.clone(),
         logger.clone(),
         file.clone(),
-        log_model,
-        user_sid,
+        log_model.clone(),
+        user_sid.clone(),
         effective_level,
     )?;
 
-    // 显示能力警告和欢迎信息
-    show_startup_info(&app)?;
+    // 后台读取文件列表的标签状态
+    let _label_timer = start_label_refresh(file.clone());
+
+    // 先显示窗口，启动自检、活动趋势与最近日志在后台加载，超出预算时不再等待
+    app.show()?;
+    let _startup_timer = start_startup_phases(
+        &app,
+        &settings.read().unwrap(),
+        inventory.clone(),
+        log_model,
+    );
+
+    // 后台检查更新（已启用且距上次检查超过 24 小时）
+    start_update_check(&app, &settings);
+
+    // 后台执行计划任务（启动时先补执行错过的时间点）
+    start_scheduler(&app, &settings, logger.clone(), user_sid.clone(), effective_level);
+
+    // 卷到达或系统恢复后重新验证受影响的清单根
+    start_volume_watcher(&app, &settings, inventory.clone(), logger.clone(), user_sid);
 
     app.run()?;
+    flush_all_logs();
 
-    // 退出时保存设置
+    // 退出时以同一标记依次保存设置与保护清单
     let settings_path = get_settings_path()?;
-    save_settings(settings_path, &settings.read().unwrap())?;
+    save_session_pair(
+        settings_path,
+        &mut settings.write().unwrap(),
+        &inventory,
+        &SystemStamper,
+    )?;
 
     Ok(())
 }
@@ -80,16 +252,34 @@ fn load_application_settings() -> anyhow::Result<Arc<RwLock<Settings>>> {
 
 /// 创建默认应用程序设置
 fn create_default_settings() -> anyhow::Result<Arc<RwLock<Settings>>> {
-    let log_path = get_default_data_path("amberlock-log.ndjson")?;
+    let log_path = get_default_data_path(LOG_FILE_NAME)?;
     let vault_path = get_default_data_path("amberlock-vault.bin")?;
 
     Ok(Arc::new(RwLock::new(Settings {
-        parallelism: 4,
+        parallelism: ParallelismMode::Fixed(4),
         default_mode: ProtectMode::ReadOnly,
         default_level: LabelLevel::High,
         log_path,
         vault_path,
         shell_integration: false,
+        backoff_base_ms: 500,
+        backoff_pad_success: false,
+        notifications_enabled: true,
+        alert_webhook_url: None,
+        alert_min_failures: 1,
+        read_only: false,
+        check_updates: false,
+        update_manifest_url: None,
+        last_update_check: None,
+        slow_volume_max_concurrent: 2,
+        slow_volume_min_interval_ms: 5,
+        replica_path: None,
+        schedule: Schedule::default(),
+        compress_sddl: false,
+        theme: Theme::System,
+        allow_hello_unlock: false,
+        on_cancel: Default::default(),
+        session: None,
     })))
 }
 
@@ -100,7 +290,7 @@ fn create_default_settings() -> anyhow::Result<Arc<RwLock<Settings>>> {
 fn get_settings_path() -> anyhow::Result<PathBuf> {
     Ok(dirs::config_dir()
         .unwrap_or(std::env::current_dir()?)
-        .join("amberlock-settings.json"))
+        .join(SETTINGS_FILE_NAME))
 }
 
 /// 获取默认数据文件路径
@@ -121,19 +311,34 @@ fn get_default_data_path(filename: &str) -> anyhow::Result<String> {
 fn initialize_application_models(
     settings: &Arc<RwLock<Settings>>,
 ) -> anyhow::Result<(
-    Arc<Mutex<NdjsonWriter>>,
+    Arc<OperationLog>,
+    Arc<InventoryTracker>,
     Arc<Mutex<FileListModel>>,
     Arc<Mutex<LogListModel>>,
     String,
     LabelLevel,
 )> {
     // 以追加模式打开日志文件，如果文件不存在则创建
-    let log_path = { settings.read().unwrap().log_path.clone() };
+    let (log_path, replica_path, compress_sddl) = {
+        let s = settings.read().unwrap();
+        (s.log_path.clone(), s.replica_path.clone(), s.compress_sddl)
+    };
+
+    // 打开日志前检查副本：主日志缺失记录时询问是否恢复
+    if let Some(replica_path) = &replica_path {
+        offer_restore_from_replica(&log_path, replica_path);
+    }
 
-    let logger = Arc::new(Mutex::new(NdjsonWriter::open_append(&log_path)?));
+    let (logger, inventory) =
+        open_operation_log(&log_path, replica_path.as_deref(), compress_sddl)?;
+    // 上次退出保存中断时恢复（结论由启动报告显示）
+    inventory.reconcile_session(&mut settings.write().unwrap())?;
 
-    // 创建空的文件列表模型
-    let file_model = Arc::new(Mutex::new(FileListModel::default()));
+    // 创建空的文件列表模型（后台读取各行的标签状态）
+    let file_model = Arc::new(Mutex::new(
+        FileListModel::default()
+            .with_label_reader(Arc::new(WinsecBackend), Arc::new(SystemStamper)),
+    ));
 
     // 从日志文件加载日志列表模型
     let log_model = Arc::new(Mutex::new(LogListModel::open(&log_path)?));
@@ -142,7 +347,306 @@ fn initialize_application_models(
     let cap = token::probe_capability()?;
     let effective_level = compute_effective_level(LabelLevel::System, cap.has_se_relabel);
 
-    Ok((logger, file_model, log_model, user_sid, effective_level))
+    Ok((
+        logger,
+        inventory,
+        file_model,
+        log_model,
+        user_sid,
+        effective_level,
+    ))
+}
+
+/// 打开操作日志，挂接保护清单与日志副本
+fn open_operation_log(
+    log_path: &str,
+    replica_path: Option<&str>,
+    compress_sddl: bool,
+) -> anyhow::Result<(Arc<OperationLog>, Arc<InventoryTracker>)> {
+    // 保护清单跟随日志更新（附属文件不可用时从日志重建）
+    let inventory = Arc::new(InventoryTracker::open(log_path)?);
+    let mut logger = OperationLog::open(log_path)?
+        .with_observer(inventory.clone())
+        .with_sddl_compression(compress_sddl);
+    if let Some(replica_path) = replica_path {
+        let spool_path = get_default_data_path(REPLICA_SPOOL_FILE_NAME)?;
+        logger = logger.with_replica(Arc::new(ReplicationSink::open(replica_path, spool_path)));
+    }
+    Ok((Arc::new(logger), inventory))
+}
+
+/// `schedule run-due`：执行到期的计划任务后退出
+///
+/// # 注意
+/// 不创建窗口、不询问从副本恢复；每个条目的结果输出到标准输出，
+/// 有条目失败时以退出码 1 退出。与 GUI 后台计划任务二选一使用，避免同一时间点执行两次
+fn run_schedule_command() -> anyhow::Result<()> {
+    let settings_path = get_settings_path()?;
+    let mut settings = load_settings(&settings_path)?;
+    let (logger, inventory) = open_operation_log(
+        &settings.log_path,
+        settings.replica_path.as_deref(),
+        settings.compress_sddl,
+    )?;
+    let reconciliation = inventory.reconcile_session(&mut settings)?;
+    if !reconciliation.is_in_sync() {
+        println!("⚠️ {}", reconciliation);
+    }
+    let user_sid = read_user_sid()?;
+    let cap = token::probe_capability()?;
+    let effective_level = compute_effective_level(LabelLevel::System, cap.has_se_relabel);
+
+    let runs = run_due_now(&mut settings.schedule, &logger, &user_sid, effective_level);
+    for run in &runs {
+        println!("{}", run);
+    }
+    flush_all_logs();
+    save_session_pair(&settings_path, &mut settings, &inventory, &SystemStamper)?;
+
+    let failed = runs.iter().any(|run| match &run.outcome {
+        ScheduleOutcome::Ran(result) => result.failed_count > 0,
+        ScheduleOutcome::Skipped(_) => false,
+        ScheduleOutcome::Failed(_) => true,
+    });
+    if failed {
+        std::process::exit(1);
+    }
+    Ok(())
+}
+
+/// `log export <输出文件> [--expand]`：导出操作日志后退出
+///
+/// # 注意
+/// 默认原样保留压缩的 SDDL 字段；`--expand` 时还原为普通字符串，
+/// 供不认识压缩格式的外部工具读取
+fn run_log_export_command(args: &[String]) -> anyhow::Result<()> {
+    let expand = args.iter().any(|arg| arg == EXPAND_FLAG);
+    let Some(output) = args.iter().find(|arg| *arg != EXPAND_FLAG) else {
+        anyhow::bail!("用法: amberlock log export <输出文件> [{}]", EXPAND_FLAG);
+    };
+    let settings = load_settings(get_settings_path()?)?;
+    let stats = export_log(
+        Path::new(&settings.log_path),
+        Path::new(output),
+        &ExportOptions { expand },
+    )?;
+    println!(
+        "已导出 {} 条记录到 {}（还原 {} 个压缩字段，跳过 {} 行）",
+        stats.written, output, stats.expanded, stats.skipped
+    );
+    Ok(())
+}
+
+/// `log tail [--query 查询语句] [-n 条数]`：按时间顺序输出最近的匹配记录（每行一条 JSON）后退出
+///
+/// 查询语句解析失败时在语句下方标出出错的片段
+fn run_log_tail_command(args: &[String]) -> anyhow::Result<()> {
+    let usage = format!(
+        "用法: amberlock log tail [{} 查询语句] [{} 条数]",
+        QUERY_FLAG, COUNT_FLAG
+    );
+    let mut query = String::new();
+    let mut count = LOG_TAIL_DEFAULT_COUNT;
+    let mut args = args.iter();
+    while let Some(arg) = args.next() {
+        if arg == QUERY_FLAG {
+            query = args
+                .next()
+                .cloned()
+                .ok_or_else(|| anyhow::anyhow!("{}", usage))?;
+        } else if arg == COUNT_FLAG {
+            count = args
+                .next()
+                .and_then(|n| n.parse().ok())
+                .ok_or_else(|| anyhow::anyhow!("{}", usage))?;
+        } else {
+            anyhow::bail!("未知的参数: {}\n{}", arg, usage);
+        }
+    }
+
+    let parsed = parse_query(&query).map_err(|err| {
+        let span = err.char_span(&query);
+        anyhow::anyhow!(
+            "查询语句有误：{}\n  {}\n  {}{}",
+            err,
+            query,
+            " ".repeat(span.start),
+            "^".repeat(span.len().max(1))
+        )
+    })?;
+    let settings = load_settings(get_settings_path()?)?;
+    let records = QueryBuilder::new(&settings.log_path)
+        .filter_query(parsed)
+        .execute()?;
+    for record in &records[records.len().saturating_sub(count)..] {
+        println!("{}", record);
+    }
+    Ok(())
+}
+
+/// `storage cleanup [类别...] [--older-than-days N]`：清理数据目录后退出
+///
+/// 未指定类别时按报告的建议清理所有有过期文件的类别；
+/// 受保护的类别（保险库、设置、操作日志等）即使指定也不会清理
+fn run_storage_cleanup_command(args: &[String]) -> anyhow::Result<()> {
+    let usage = "用法: amberlock storage cleanup [类别...] [--older-than-days N]";
+    let mut categories = Vec::new();
+    let mut older_than = None;
+    let mut args = args.iter();
+    while let Some(arg) = args.next() {
+        if arg == OLDER_THAN_DAYS_FLAG {
+            let days: u64 = args
+                .next()
+                .and_then(|days| days.parse().ok())
+                .ok_or_else(|| anyhow::anyhow!("{}", usage))?;
+            older_than = Some(Duration::from_secs(days * 24 * 60 * 60));
+        } else {
+            let category = ArtifactCategory::from_key(arg)
+                .ok_or_else(|| anyhow::anyhow!("未知的类别: {}\n{}", arg, usage))?;
+            categories.push(category);
+        }
+    }
+
+    let settings = load_settings(get_settings_path()?)?;
+    if categories.is_empty() {
+        categories = storage_report(&settings)?
+            .recommended_plan()
+            .categories()
+            .collect();
+    }
+    let mut plan = CleanupPlan::default();
+    for category in categories {
+        plan = match older_than {
+            Some(max_age) => plan.with_category_older_than(category, max_age),
+            None => plan.with_category(category),
+        };
+    }
+    let result = cleanup(&settings, &plan)?;
+    println!("{}", result);
+    for (path, reason) in &result.failed {
+        println!("  {}: {}", path.display(), reason);
+    }
+    Ok(())
+}
+
+/// `unprotect-all --confirm-phrase 全部解除保护 [--scope inventory|log] [--scan 目录...]`：
+/// 移除全部保护并复查后退出
+///
+/// # 注意
+/// 保险库密码从标准输入的第一行读取，启用 TOTP 的保险库第二行为验证码；默认按保护清单收集目标。
+/// 处于只读审计模式时拒绝执行，复查发现残留或有对象失败时以退出码 1 退出
+fn run_unprotect_all_command(args: &[String]) -> anyhow::Result<()> {
+    let usage = format!(
+        "用法: amberlock {} {} {} [{} inventory|log] [{} 目录...]",
+        UNPROTECT_ALL_COMMAND,
+        CONFIRM_PHRASE_FLAG,
+        DECOMMISSION_CONFIRM_PHRASE,
+        SCOPE_FLAG,
+        SCAN_FLAG
+    );
+    let mut phrase = None;
+    let mut scope = UnprotectScope::FromInventory;
+    let mut args = args.iter();
+    while let Some(arg) = args.next() {
+        if arg == CONFIRM_PHRASE_FLAG {
+            phrase = Some(args.next().ok_or_else(|| anyhow::anyhow!("{}", usage))?);
+        } else if arg == SCOPE_FLAG {
+            scope = match args.next().map(String::as_str) {
+                Some("inventory") => UnprotectScope::FromInventory,
+                Some("log") => UnprotectScope::FromLog,
+                _ => anyhow::bail!("{}", usage),
+            };
+        } else if arg == SCAN_FLAG {
+            let roots: Vec<PathBuf> = args.by_ref().map(PathBuf::from).collect();
+            if roots.is_empty() {
+                anyhow::bail!("{}", usage);
+            }
+            scope = UnprotectScope::ScanRoots(roots);
+        } else {
+            anyhow::bail!("未知的参数: {}\n{}", arg, usage);
+        }
+    }
+    let Some(phrase) = phrase else {
+        anyhow::bail!("{}", usage);
+    };
+    check_confirm_phrase(phrase)?;
+
+    let settings = load_settings(get_settings_path()?)?;
+    set_read_only(settings.read_only);
+    let mut line = String::new();
+    std::io::stdin().read_line(&mut line)?;
+    line.truncate(line.trim_end_matches(['\r', '\n']).len());
+    let password = SecretString::new(line);
+    // 启用 TOTP 的保险库：第二行为验证码
+    let mut totp_code = String::new();
+    std::io::stdin().read_line(&mut totp_code)?;
+    if !vault::authorize_sensitive_action(
+        Path::new(&settings.vault_path),
+        &password,
+        Some(totp_code.as_str()),
+        &BackoffConfig::from_settings(&settings),
+    )? {
+        anyhow::bail!(vault::wrong_password_message(
+            Path::new(&settings.vault_path),
+            "密码或验证码错误，未解除保护"
+        ));
+    }
+
+    let (logger, _inventory) = open_operation_log(
+        &settings.log_path,
+        settings.replica_path.as_deref(),
+        settings.compress_sddl,
+    )?;
+    let user_sid = read_user_sid()?;
+    let request = UnprotectAll::new(scope, &user_sid, DEFAULT_CREDENTIAL, &logger)
+        .with_origin(OperationOrigin::CliBatch);
+    let report = unprotect_all_now(&request, phrase, &logger, &ProgressTracker::indeterminate())?;
+    flush_all_logs();
+
+    println!("{}", report);
+    println!("批次: {}", report.batch_id);
+    for object in &report.leftovers {
+        println!("  仍带标签: {}（{:?}）", object.path, object.level);
+    }
+    if !report.is_clean() {
+        std::process::exit(1);
+    }
+    Ok(())
+}
+
+/// 主日志缺失副本中的记录时，询问是否从副本恢复
+///
+/// # 注意
+/// 必须在打开操作日志之前调用：恢复会原子替换主日志文件
+fn offer_restore_from_replica(log_path: &str, replica_path: &str) {
+    let Ok(report) = compare_logs(log_path, replica_path) else {
+        return;
+    };
+    if report.missing_from_primary.is_empty() {
+        return;
+    }
+
+    let answer = rfd::MessageDialog::new()
+        .set_title("操作日志不完整")
+        .set_level(rfd::MessageLevel::Warning)
+        .set_description(format!(
+            "操作日志与副本 {} 不一致：{}。\n\n日志可能被删除或截断，是否从副本恢复缺失的记录？",
+            replica_path,
+            report.summary_line()
+        ))
+        .set_buttons(rfd::MessageButtons::YesNo)
+        .show();
+    if answer != rfd::MessageDialogResult::Yes {
+        return;
+    }
+    if let Err(e) = restore_from_replica(log_path, replica_path) {
+        rfd::MessageDialog::new()
+            .set_title("恢复失败")
+            .set_level(rfd::MessageLevel::Error)
+            .set_description(format!("无法从副本恢复操作日志: {}", e))
+            .set_buttons(rfd::MessageButtons::Ok)
+            .show();
+    }
 }
 
 /// 设置用户界面初始状态
@@ -152,18 +656,14 @@ fn initialize_application_models(
 fn setup_initial_ui_state(
     app: &MainWindow,
     file_model: Arc<Mutex<FileListModel>>,
-    log_model: Arc<Mutex<LogListModel>>,
 ) -> anyhow::Result<()> {
     // 获取当前用户的 Windows 安全标识符
     let sid = read_user_sid().unwrap_or_else(|_| "未知".to_string());
     app.set_user_sid(sid.into());
 
-    // 将文件列表模型快照绑定到 UI
+    // 将文件列表模型快照绑定到 UI（日志列表在启动阶段后台加载）
     app.set_files(file_model.lock().unwrap().to_model_rc());
 
-    // 将日志列表模型快照绑定到 UI（限制显示最近200条）
-    app.set_logs(log_model.lock().unwrap().to_model_rc(200));
-
     Ok(())
 }
 
@@ -172,7 +672,7 @@ fn setup_initial_ui_state(
 fn setup_event_handlers(
     app: &MainWindow,
     settings: Arc<RwLock<Settings>>,
-    logger: Arc<Mutex<NdjsonWriter>>,
+    logger: Arc<OperationLog>,
     file_model: Arc<Mutex<FileListModel>>,
     log_model: Arc<Mutex<LogListModel>>,
     user_sid: String,
@@ -180,6 +680,12 @@ fn setup_event_handlers(
 ) -> anyhow::Result<()> {
     setup_file_selection_handlers(app, file_model.clone());
     setup_log_refresh_handler(app, log_model.clone());
+    setup_clipboard_handlers(app, file_model.clone());
+    setup_file_removal_handlers(app, file_model.clone());
+    setup_shortcut_handler(app);
+
+    let active = ActiveOperation::new(file_model.clone(), DialogQueue::start(app));
+    let session = Arc::new(Mutex::new(UnlockSession::default()));
     setup_lock_handler(
         app,
         settings.clone(),
@@ -187,11 +693,248 @@ fn setup_event_handlers(
         file_model.clone(),
         effective_level,
         user_sid.clone(),
+        active.clone(),
+    );
+    setup_unlock_handler(
+        app,
+        settings.clone(),
+        logger.clone(),
+        file_model.clone(),
+        user_sid.clone(),
+        active.clone(),
+        session.clone(),
+    );
+    setup_hello_unlock_handler(app, settings.clone(), session);
+    setup_revert_handler(
+        app,
+        settings.clone(),
+        logger.clone(),
+        user_sid.clone(),
+        active.clone(),
+    );
+    setup_decommission_handler(
+        app,
+        settings.clone(),
+        logger.clone(),
+        user_sid.clone(),
+        active.clone(),
+    );
+    setup_change_password_handler(app, settings.clone());
+    setup_preset_handlers(
+        app,
+        settings.clone(),
+        logger.clone(),
+        effective_level,
+        user_sid.clone(),
+        active.clone(),
+    );
+    setup_repair_handler(
+        app,
+        settings.clone(),
+        logger.clone(),
+        file_model.clone(),
+        user_sid,
+        active.clone(),
     );
-    setup_unlock_handler(app, settings.clone(), logger.clone(), user_sid);
+    setup_cancel_handler(app, active.clone());
+    setup_dir_tree_handlers(app, active.dir_tree.clone());
+    setup_close_handler(app, active);
+    setup_storage_handlers(app, settings.clone());
+    setup_read_only_exit_handler(app, settings);
     Ok(())
 }
 
+/// 根据设置与启动参数开启只读审计模式（`--read-only` 优先）
+fn apply_read_only_mode(app: &MainWindow, settings: &Settings) {
+    let forced = std::env::args().skip(1).any(|arg| arg == READ_ONLY_FLAG);
+    let read_only = forced || settings.read_only;
+    set_read_only(read_only);
+    app.set_read_only(read_only);
+    app.set_read_only_forced(forced);
+}
+
+/// 按保险库是否需要验证码显示各处的验证码输入框（保险库无法读取时同样显示）
+///
+/// # 返回
+/// 是否显示验证码输入框
+fn refresh_totp_required(app: &MainWindow, vault_path: &Path) -> bool {
+    let required = vault::totp_required(vault_path).unwrap_or(true);
+    app.set_totp_required(required);
+    required
+}
+
+/// 应用主题设置并绑定主题切换
+///
+/// 选择“跟随系统”时，系统切换深浅色后界面随之切换；
+/// 选择后立即保存设置，下次启动沿用。系统关闭了动画时界面同样不播放动画，
+/// 启动、切换主题与系统设置变化时重新读取
+fn setup_theme(app: &MainWindow, settings: Arc<RwLock<Settings>>) {
+    let source: Arc<dyn SystemThemeSource> = Arc::new(RegistryThemeSource);
+    theme::apply_theme(
+        app,
+        settings.read().unwrap().theme,
+        source.apps_use_light_theme(),
+    );
+    theme::apply_motion(app, source.client_area_animation());
+
+    let app_weak = app.as_weak();
+    let choose_settings = settings.clone();
+    let choose_source = source.clone();
+    app.on_choose_theme(move |choice| {
+        let app = app_weak.unwrap();
+        let selected = theme::theme_from_choice(choice);
+        choose_settings.write().unwrap().theme = selected;
+        theme::apply_theme(&app, selected, choose_source.apps_use_light_theme());
+        theme::apply_motion(&app, choose_source.client_area_animation());
+
+        let saved = get_settings_path()
+            .and_then(|path| save_settings(path, &choose_settings.read().unwrap()));
+        if let Err(e) = saved {
+            a11y::set_status(&app, format!("⚠️ 主题已切换，但保存设置失败：{}", e).into());
+        }
+    });
+
+    let app_weak = app.as_weak();
+    let watch_source = source.clone();
+    let watched = source.watch(Box::new(move |apps_use_light| {
+        let settings = settings.clone();
+        let animation = watch_source.client_area_animation();
+        app_weak
+            .upgrade_in_event_loop(move |app| {
+                let selected = settings.read().unwrap().theme;
+                if selected == Theme::System {
+                    theme::apply_theme(&app, selected, apps_use_light);
+                }
+                theme::apply_motion(&app, animation);
+            })
+            .is_ok()
+    }));
+    if let Err(e) = watched {
+        a11y::set_status(app, format!("⚠️ 无法监听系统主题变化：{}", e).into());
+    }
+}
+
+/// 设置存储占用面板事件处理器
+///
+/// 打开面板时统计数据目录占用；清理前确认勾选的类别与可释放的空间，
+/// 完成后重新统计。只读审计模式下不可清理
+fn setup_storage_handlers(app: &MainWindow, settings: Arc<RwLock<Settings>>) {
+    let app_weak = app.as_weak();
+    let refresh_settings = settings.clone();
+    app.on_refresh_storage(move || {
+        let app = app_weak.unwrap();
+        render_storage_report(&app, &refresh_settings.read().unwrap());
+    });
+
+    let app_weak = app.as_weak();
+    app.on_toggle_storage_item(move |index| {
+        let app = app_weak.unwrap();
+        let items = app.get_storage_items();
+        if let Some(mut item) = usize::try_from(index).ok().and_then(|i| items.row_data(i)) {
+            item.checked = !item.checked;
+            items.set_row_data(index as usize, item);
+        }
+    });
+
+    let app_weak = app.as_weak();
+    app.on_cleanup_storage(move || {
+        let app = app_weak.unwrap();
+        if is_read_only() {
+            a11y::set_status(&app, "🔍 只读审计模式下不能清理数据目录".into());
+            return;
+        }
+        let items: Vec<_> = app.get_storage_items().iter().collect();
+        let plan = bridge::storage_plan(&items);
+        if plan.is_empty() {
+            a11y::set_status(&app, "⚠️ 未勾选可清理的类别".into());
+            return;
+        }
+
+        let settings = settings.read().unwrap().clone();
+        let labels: Vec<&str> = plan.categories().map(ArtifactCategory::label).collect();
+        let answer = rfd::MessageDialog::new()
+            .set_title("清理数据目录")
+            .set_level(rfd::MessageLevel::Warning)
+            .set_description(format!(
+                "将删除以下类别中超过保留期的文件：{}。\n\n是否继续？",
+                labels.join("、")
+            ))
+            .set_buttons(rfd::MessageButtons::YesNo)
+            .show();
+        if answer != rfd::MessageDialogResult::Yes {
+            return;
+        }
+
+        match cleanup(&settings, &plan) {
+            Ok(result) if result.failed.is_empty() => {
+                a11y::set_status(&app, format!("✅ {}", result).into())
+            }
+            Ok(result) => a11y::set_status(&app, format!("⚠️ {}", result).into()),
+            Err(e) => a11y::set_status(&app, format!("❌ 清理失败: {}", e).into()),
+        }
+        render_storage_report(&app, &settings);
+    });
+}
+
+/// 统计数据目录占用并显示在存储占用面板中
+fn render_storage_report(app: &MainWindow, settings: &Settings) {
+    match storage_report(settings) {
+        Ok(report) => {
+            let summary = report.to_string();
+            app.set_storage_summary(summary.lines().next().unwrap_or_default().into());
+            app.set_storage_items(VecModel::from_slice(&bridge::storage_items(&report)));
+        }
+        Err(e) => {
+            app.set_storage_summary(format!("❌ 无法统计数据目录: {}", e).into());
+            app.set_storage_items(VecModel::from_slice(&[]));
+        }
+    }
+}
+
+/// 设置退出只读审计模式事件处理器（存在保险库时需要密码）
+fn setup_read_only_exit_handler(app: &MainWindow, settings: Arc<RwLock<Settings>>) {
+    let app_weak = app.as_weak();
+
+    app.on_exit_read_only(move |password| {
+        let app = app_weak.unwrap();
+        let (vault_path, backoff) = {
+            let s = settings.read().unwrap();
+            (PathBuf::from(&s.vault_path), BackoffConfig::from_settings(&s))
+        };
+
+        let password = SecretString::from(password.as_str());
+        let totp_code = app.get_exit_totp_code();
+        app.set_exit_totp_code("".into());
+        let totp_required = refresh_totp_required(&app, &vault_path);
+        match vault::authorize_sensitive_action(
+            &vault_path,
+            &password,
+            Some(totp_code.as_str()),
+            &backoff,
+        ) {
+            Ok(true) => {
+                settings.write().unwrap().read_only = false;
+                set_read_only(false);
+                app.set_read_only(false);
+                a11y::set_status(&app, "✅ 已退出只读审计模式".into());
+            }
+            Ok(false) => a11y::set_status(
+                &app,
+                vault::wrong_password_message(
+                    &vault_path,
+                    if totp_required {
+                        "❌ 密码或验证码错误，仍处于只读审计模式"
+                    } else {
+                        "❌ 密码错误，仍处于只读审计模式"
+                    },
+                )
+                .into(),
+            ),
+            Err(e) => a11y::set_status(&app, format!("❌ 无法校验保险库: {}", e).into()),
+        }
+    });
+}
+
 /// 设置文件选择事件处理器
 ///
 /// 处理用户通过 UI 选择文件和文件夹的操作，将选择结果添加到文件列表模型。
@@ -207,12 +950,13 @@ fn setup_file_selection_handlers(app: &MainWindow, file_model: Arc<Mutex<FileLis
                 let app = app_weak.unwrap();
                 let mut fm = file_model.lock().unwrap();
                 // 将选择的路径添加到文件模型
-                bridge::add_paths_to_model(&paths, &mut *fm);
+                let status = bridge::add_paths_to_model(&paths, &mut *fm, "文件");
                 let rc = fm.to_model_rc();
                 drop(fm);
                 // 更新 UI 中的文件列表
                 app.set_files(rc);
-                app.set_status_text(format!("✅ 已添加 {} 个文件", paths.len()).into());
+                a11y::set_status(&app, status.into());
+                probe_added_shares(&app, &paths);
             }
         });
     }
@@ -228,186 +972,2195 @@ fn setup_file_selection_handlers(app: &MainWindow, file_model: Arc<Mutex<FileLis
 
                 let mut fm = file_model.lock().unwrap();
                 // 将选择的路径添加到文件模型
-                bridge::add_paths_to_model(&paths, &mut *fm);
+                let status = bridge::add_paths_to_model(&paths, &mut *fm, "文件夹");
                 let rc = fm.to_model_rc();
                 drop(fm);
 
                 // 更新 UI 中的文件列表
                 app.set_files(rc);
-                app.set_status_text(format!("✅ 已添加 {} 个文件夹", paths.len()).into());
+                a11y::set_status(&app, status.into());
+                probe_added_shares(&app, &paths);
             }
         });
     }
 }
 
-/// 设置日志刷新事件处理器
+/// 在后台探测新添加的网络路径所在的共享
 ///
-/// 处理用户刷新日志列表的请求，支持按查询字符串过滤日志条目。
-fn setup_log_refresh_handler(app: &MainWindow, log_model: Arc<Mutex<LogListModel>>) {
-    let app_weak = app.as_weak();
-
-    app.on_refresh_logs(move |query| {
-        let query = query.to_string();
-        let app = app_weak.unwrap();
-
-        let rows = log_model.lock().unwrap().to_filtered_model_rc(&query, 300);
-
-        // 更新 UI 中的日志列表
-        app.set_logs(rows);
+/// 每个共享只探测一次（结果由 `ShareProbeCache` 缓存）；
+/// 共享需要重新登录、不可达或不支持标签时在状态栏提示
+fn probe_added_shares(app: &MainWindow, paths: &[PathBuf]) {
+    let mut shares: Vec<String> = paths
+        .iter()
+        .filter_map(|path| unc_share(path))
+        .filter(|share| ShareProbeCache::global().get(share).is_none())
+        .collect();
+    shares.sort_by_key(|share| share.to_lowercase());
+    shares.dedup_by_key(|share| share.to_lowercase());
+    if shares.is_empty() {
+        return;
+    }
 
-        if query.is_empty() {
-            app.set_status_text("✅ 日志已刷新（显示全部）".into());
-        } else {
-            app.set_status_text(format!("🔍 日志已过滤: \"{}\"", query).into());
+    let app_weak = app.as_weak();
+    std::thread::spawn(move || {
+        for share in shares {
+            let Some(probe) = probe_network_path(Path::new(&share)) else {
+                continue;
+            };
+            let status = match (probe.to_error(), &probe.share_caps) {
+                (Some(error), _) => format!("⚠️ {}", error),
+                (None, Some(caps)) if !caps.persistent_acls => format!(
+                    "⚠️ 共享 {} 的文件系统（{}）不保存 ACL，无法设置完整性标签",
+                    share, caps.file_system
+                ),
+                _ => continue,
+            };
+            let _ =
+                app_weak.upgrade_in_event_loop(move |app| a11y::set_status(&app, status.into()));
         }
     });
 }
 
-/// 设置锁定操作事件处理器
+/// 定时提交并取回文件列表的标签读取
 ///
-fn setup_lock_handler(
-    app: &MainWindow,
-    settings: Arc<RwLock<Settings>>,
-    logger: Arc<Mutex<NdjsonWriter>>,
-    file_model: Arc<Mutex<FileListModel>>,
-    effective_level: LabelLevel,
-    user_sid: String,
-) {
-    let app_weak = app.as_weak();
+/// 读取在模型的后台线程中进行，定时器只在 UI 线程中分发请求与结果；
+/// 返回的定时器须在事件循环期间保持存活
+fn start_label_refresh(file_model: Arc<Mutex<FileListModel>>) -> Timer {
+    let timer = Timer::default();
+    timer.start(TimerMode::Repeated, LABEL_REFRESH_INTERVAL, move || {
+        let fm = file_model.lock().unwrap();
+        fm.poll_label_results();
+        fm.request_label_refresh();
+    });
+    timer
+}
 
-    app.on_request_lock(move |mode, level| {
-        let app = app_weak.unwrap();
+/// 设置文件列表的选中切换、排序与移除事件处理器
+fn setup_file_removal_handlers(app: &MainWindow, file_model: Arc<Mutex<FileListModel>>) {
+    // 切换文件行选中状态
+    {
+        let app_weak = app.as_weak();
+        let file_model = Arc::clone(&file_model);
+        app.on_toggle_file_selected(move |index| {
+            let index = index as usize;
+            let Some(selected) = file_model.lock().unwrap().toggle_selected(index) else {
+                return;
+            };
+            let files = app_weak.unwrap().get_files();
+            if let Some(mut row) = files.row_data(index) {
+                row.selected = selected;
+                files.set_row_data(index, row);
+            }
+        });
+    }
 
-        // 获取选中的路径
-        let selected_paths = file_model.lock().unwrap().selected_paths();
+    // 按保护状态排序
+    {
+        let file_model = Arc::clone(&file_model);
+        app.on_sort_files_by_label(move || {
+            file_model.lock().unwrap().sort_by_label_state();
+        });
+    }
 
-        // 检查是否有选中的项
+    // 关于 / 组件清单：按关键字筛选
+    {
+        let app_weak = app.as_weak();
+        app.on_search_components(move |query| {
+            let lines: Vec<SharedString> = third_party::search_components(&query)
+                .into_iter()
+                .map(SharedString::from)
+                .collect();
+            app_weak
+                .unwrap()
+                .set_component_lines(VecModel::from_slice(&lines));
+        });
+    }
+
+    // 移除所选对象
+    {
+        let app_weak = app.as_weak();
+        app.on_remove_selected_files(move || {
+            let app = app_weak.unwrap();
+            let fm = file_model.lock().unwrap();
+            let removed = fm.remove_selected();
+            let rc = fm.to_model_rc();
+            drop(fm);
+
+            if removed.is_empty() {
+                a11y::set_status(&app, "⚠️ 未选择任何对象".into());
+                return;
+            }
+            app.set_files(rc);
+            a11y::set_status(
+                &app,
+                format!("🗑 已从列表移除 {} 个对象", removed.len()).into(),
+            );
+        });
+    }
+}
+
+/// 设置键盘快捷键处理器
+///
+/// 按键经 `shortcuts::dispatch` 映射为命令，操作进行中、模态面板打开或只读模式下
+/// 不安全的命令不会执行，未处理的按键交还 UI 继续传递
+fn setup_shortcut_handler(app: &MainWindow) {
+    let app_weak = app.as_weak();
+
+    app.on_shortcut(move |key, control, shift, scope| {
+        let app = app_weak.unwrap();
+        let scope = match scope {
+            ShortcutScope::Global => Scope::Global,
+            ShortcutScope::Files => Scope::Files,
+            ShortcutScope::Logs => Scope::Logs,
+        };
+        let state = ShortcutState {
+            busy: app.get_busy(),
+            modal_open: app.get_modal_open(),
+            read_only: app.get_read_only(),
+        };
+
+        let Some(command) = shortcuts::dispatch(&key, control, shift, scope, state) else {
+            return false;
+        };
+        match command {
+            Command::AddFiles => app.invoke_pick_files(),
+            Command::AddFolders => app.invoke_pick_folders(),
+            Command::RemoveSelectedFiles => app.invoke_remove_selected_files(),
+            Command::CopyPaths => app.invoke_copy_paths(),
+            Command::CopyLogsTsv => app.invoke_copy_logs(false),
+            Command::CopyLogsJson => app.invoke_copy_logs(true),
+            Command::Lock => app.invoke_submit_lock(true),
+            Command::FocusUnlock => app.invoke_focus_unlock(),
+            Command::RefreshLogs => app.invoke_refresh_current_logs(),
+            Command::FocusLogFilter => app.invoke_focus_log_filter(),
+        }
+        true
+    });
+}
+
+/// 设置日志刷新事件处理器
+///
+/// 处理用户刷新日志列表的请求，支持按查询字符串过滤日志条目。
+fn setup_log_refresh_handler(app: &MainWindow, log_model: Arc<Mutex<LogListModel>>) {
+    let app_weak = app.as_weak();
+
+    app.on_refresh_logs(move |query| {
+        let query = query.to_string();
+        let app = app_weak.unwrap();
+
+        // 不含 `字段:取值` 语法时保持普通关键字过滤
+        if !has_field_syntax(&query) {
+            app.set_log_query_error("".into());
+            let rows = log_model.lock().unwrap().to_filtered_model_rc(&query, 300);
+
+            // 更新 UI 中的日志列表
+            app.set_logs(rows);
+
+            if query.is_empty() {
+                a11y::set_status(&app, "✅ 日志已刷新（显示全部）".into());
+            } else {
+                a11y::set_status(&app, format!("🔍 日志已过滤: \"{}\"", query).into());
+            }
+            return;
+        }
+
+        match parse_query(&query) {
+            Ok(parsed) => {
+                app.set_log_query_error("".into());
+                app.set_logs(log_model.lock().unwrap().to_query_model_rc(&parsed, 300));
+                a11y::set_status(&app, format!("🔍 日志已过滤: {}", parsed).into());
+            }
+            Err(err) => {
+                // 保留当前列表，选中出错的片段
+                app.set_log_query_error(err.to_string().into());
+                app.invoke_select_log_query(err.span.start as i32, err.span.end as i32);
+            }
+        }
+    });
+}
+
+/// 设置剪贴板相关事件处理器
+///
+/// 包括日志行选中切换、复制所选日志（TSV/JSON）与复制所选路径
+fn setup_clipboard_handlers(app: &MainWindow, file_model: Arc<Mutex<FileListModel>>) {
+    // 切换日志行选中状态
+    {
+        let app_weak = app.as_weak();
+        app.on_toggle_log_selected(move |index| {
+            let logs = app_weak.unwrap().get_logs();
+            let index = index as usize;
+            if let Some(mut row) = logs.row_data(index) {
+                row.selected = !row.selected;
+                logs.set_row_data(index, row);
+            }
+        });
+    }
+
+    // 复制所选日志
+    {
+        let app_weak = app.as_weak();
+        app.on_copy_logs(move |as_json| {
+            let app = app_weak.unwrap();
+            let rows: Vec<LogRow> = app.get_logs().iter().filter(|row| row.selected).collect();
+
+            if rows.is_empty() {
+                a11y::set_status(&app, "⚠️ 未选择任何日志".into());
+                return;
+            }
+
+            let records = clipboard::rows_to_records(&rows);
+            let text = if as_json {
+                clipboard::records_to_json_pretty(&records)
+            } else {
+                clipboard::records_to_tsv(&records)
+            };
+            let status = clipboard::copy_with(
+                &mut SystemClipboard,
+                text,
+                &format!("{} 条日志", records.len()),
+            );
+            a11y::set_status(&app, status.into());
+        });
+    }
+
+    // 复制所选路径
+    {
+        let app_weak = app.as_weak();
+        app.on_copy_paths(move || {
+            let app = app_weak.unwrap();
+            let paths = file_model.lock().unwrap().selected_paths();
+
+            if paths.is_empty() {
+                a11y::set_status(&app, "⚠️ 未选择任何对象".into());
+                return;
+            }
+
+            let status = clipboard::copy_with(
+                &mut SystemClipboard,
+                clipboard::paths_to_text(&paths),
+                &format!("{} 个路径", paths.len()),
+            );
+            a11y::set_status(&app, status.into());
+        });
+    }
+}
+
+/// 设置锁定操作事件处理器
+///
+/// 批量上锁在后台线程执行，进度与结果由 `ActiveOperation` 回写界面
+fn setup_lock_handler(
+    app: &MainWindow,
+    settings: Arc<RwLock<Settings>>,
+    logger: Arc<OperationLog>,
+    file_model: Arc<Mutex<FileListModel>>,
+    effective_level: LabelLevel,
+    user_sid: String,
+    active: ActiveOperation,
+) {
+    let app_weak = app.as_weak();
+
+    app.on_request_lock(move |mode, level, comment, capture_trace, via_shortcut| {
+        let app = app_weak.unwrap();
+
+        if active.is_running() {
+            a11y::set_status(&app, "⚠️ 已有操作正在进行，请等待完成或取消".into());
+            return;
+        }
+
+        // 获取选中的路径
+        let selected_paths = file_model.lock().unwrap().selected_paths();
+
+        // 检查是否有选中的项
         if selected_paths.is_empty() {
-            app.set_status_text("⚠️ 未选择任何对象".into());
+            a11y::set_status(&app, "⚠️ 未选择任何对象".into());
             return;
         }
 
         // 转换 UI 参数为核心库参数
         let (mode, level) = bridge::convert_ui_params(mode, level);
 
+        // 卷根只允许只读模式，且需要用户确认（core 层会再次校验）
+        let volume_roots: Vec<&PathBuf> = selected_paths
+            .iter()
+            .filter(|path| is_volume_root(path))
+            .collect();
+        let acknowledged_volume_root = !volume_roots.is_empty();
+        if acknowledged_volume_root {
+            if mode != ProtectMode::ReadOnly {
+                a11y::set_status(&app, "⚠️ 卷根只能以只读模式（仅 NW 策略）上锁".into());
+                return;
+            }
+            if !confirm_volume_root_lock(&volume_roots) {
+                a11y::set_status(&app, "⚠️ 已放弃锁定卷根".into());
+                return;
+            }
+        }
+
+        // 与进行中的操作路径重叠时询问是否取消对方
+        let Some(wait_for_conflicts) = resolve_conflicts(&selected_paths) else {
+            a11y::set_status(&app, "⚠️ 已放弃操作：与正在进行的操作冲突".into());
+            return;
+        };
+
+        // 封印前预览每个对象将写入的变更，确认后按计划写入
+        let seal_plans = if mode == ProtectMode::Seal {
+            let plans: Result<Vec<SealPlan>> = selected_paths
+                .iter()
+                .map(|path| preview_seal(&WinsecBackend, &path.to_string_lossy(), effective_level))
+                .collect();
+            let plans = match plans {
+                Ok(plans) => plans,
+                Err(e) => {
+                    a11y::set_status(&app, format!("❌ 无法预览封印变更: {}", e).into());
+                    return;
+                }
+            };
+            if !confirm_seal(&plans, &user_sid) {
+                a11y::set_status(&app, "⚠️ 已放弃封印".into());
+                return;
+            }
+            plans
+        } else {
+            Vec::new()
+        };
+
+        // 仅为本次操作捕获诊断跟踪，创建失败时照常执行
+        let trace = if capture_trace {
+            let header = TraceHeader::new(mode, level, effective_level, selected_paths.len());
+            match create_trace_recorder(&settings.read().unwrap().log_path, header) {
+                Ok(recorder) => Some(Arc::new(recorder)),
+                Err(e) => {
+                    a11y::set_status(&app, format!("⚠️ 无法创建诊断跟踪: {}", e).into());
+                    None
+                }
+            }
+        } else {
+            None
+        };
+        let trace_path = trace.as_ref().map(|recorder| recorder.path().to_path_buf());
+
         let opts = LockOptions {
             desired_level: level,
             mode,
             parallelism: { settings.read().unwrap().parallelism },
+            slow_volume_throttle: VolumeThrottle::from_settings(&settings.read().unwrap()),
+            wait_for_conflicts,
+            comment: sanitize_comment(&comment),
+            trace,
+            batch_id: None,
+            acknowledged_volume_root,
+            origin: if via_shortcut {
+                OperationOrigin::GuiShortcut
+            } else {
+                OperationOrigin::GuiButton
+            },
+            seal_plans,
+            on_cancel: settings.read().unwrap().on_cancel,
+            // 逐个选择的对象不是递归展开的，不按目录汇总
+            collect_dir_summary: false,
         };
 
-        // 批量操作
-        let batch_result = batch_process_lock(
-            &selected_paths,
-            &opts,
+        // 后台批量操作
+        active.set_touched_paths(selected_paths.clone());
+        active.set_cancel_behavior(Some(opts.on_cancel));
+        let handle = spawn_batch_lock(
+            selected_paths,
+            opts,
             effective_level,
-            &user_sid,
-            &logger.lock().unwrap(),
+            user_sid.clone(),
+            logger.clone(),
+        );
+        active.set_trace_path(trace_path);
+        active.watch(
+            &app,
+            handle,
+            OperationKind::Lock,
+            settings.clone(),
+            logger.clone(),
+            user_sid.clone(),
         );
-
-        // 显示详细的操作结果
-        let status = format_batch_result(&batch_result);
-        app.set_status_text(status.into());
-
-        // 刷新日志
-        refresh_logs_in_ui(&app, &settings);
     });
 }
 
+/// 在日志文件旁创建本次操作的诊断跟踪文件（`amberlock-trace-<时间戳>.ndjson`）
+fn create_trace_recorder(
+    log_path: &str,
+    header: TraceHeader,
+) -> amberlock_types::Result<TraceRecorder> {
+    let stamp = std::time::SystemTime::now()
+        .duration_since(std::time::UNIX_EPOCH)
+        .map(|d| d.as_secs())
+        .unwrap_or_default();
+    let path = Path::new(log_path).with_file_name(format!("{}{}.ndjson", TRACE_FILE_PREFIX, stamp));
+    TraceRecorder::create(path, header)
+}
+
 /// 设置解锁操作事件处理器
+///
+/// 存在保险库时需要密码；输入正确后打开解锁宽限期，宽限期内密码可留空。
+/// 密码在后台线程中校验，校验期间禁用解锁按钮
 fn setup_unlock_handler(
     app: &MainWindow,
     settings: Arc<RwLock<Settings>>,
-    logger: Arc<Mutex<NdjsonWriter>>,
+    logger: Arc<OperationLog>,
+    file_model: Arc<Mutex<FileListModel>>,
     user_sid: String,
+    active: ActiveOperation,
+    session: Arc<Mutex<UnlockSession>>,
 ) {
     let app_weak = app.as_weak();
+    let cooldown_timer = Rc::new(Timer::default());
 
-    app.on_request_unlock(move |_password| {
+    app.on_request_unlock(move |password, comment| {
         let app = app_weak.unwrap();
+        let password = SecretString::from(password.as_str());
+
+        if app.get_unlock_pending() {
+            a11y::set_status(&app, "⏳ 正在校验保险库密码，请稍候".into());
+            return;
+        }
+        if active.is_running() {
+            a11y::set_status(&app, "⚠️ 已有操作正在进行，请等待完成或取消".into());
+            return;
+        }
 
-        let selected_paths = FileListModel::selected_paths_static();
+        let selected_paths = file_model.lock().unwrap().selected_paths();
 
         if selected_paths.is_empty() {
-            app.set_status_text("⚠️ 未选择任何对象".into());
+            a11y::set_status(&app, "⚠️ 未选择任何对象".into());
+            return;
+        }
+
+        // 授权通过后开始后台批量操作
+        let start = {
+            let settings = settings.clone();
+            let logger = logger.clone();
+            let user_sid = user_sid.clone();
+            let active = active.clone();
+            move |app: &MainWindow| {
+                let Some(wait_for_conflicts) = resolve_conflicts(&selected_paths) else {
+                    a11y::set_status(app, "⚠️ 已放弃操作：与正在进行的操作冲突".into());
+                    return;
+                };
+
+                active.set_touched_paths(selected_paths.clone());
+                let handle = spawn_batch_unlock(
+                    selected_paths,
+                    user_sid.clone(),
+                    logger.clone(),
+                    wait_for_conflicts,
+                    sanitize_comment(&comment),
+                    OperationOrigin::GuiButton,
+                );
+                active.watch(
+                    app,
+                    handle,
+                    OperationKind::Unlock,
+                    settings,
+                    logger,
+                    user_sid,
+                );
+            }
+        };
+
+        if session
+            .lock()
+            .unwrap()
+            .skips_password(ProtectedAction::Unlock, Instant::now())
+        {
+            start(&app);
+            return;
+        }
+
+        let (vault_path, backoff) = {
+            let s = settings.read().unwrap();
+            (
+                PathBuf::from(&s.vault_path),
+                BackoffConfig::from_settings(&s),
+            )
+        };
+        if vault_path.exists() && password.is_empty() {
+            a11y::set_status(&app, "🔑 请输入保险库密码后解锁".into());
             return;
         }
 
-        // 批量操作
-        let batch_result =
-            batch_process_unlock(&selected_paths, &user_sid, &logger.lock().unwrap());
+        // 冷却期间禁用密码框并倒计时（失败记录的检查与登记在 authorize_sensitive_action 中）
+        let lockout = vault::LockoutStore::for_vault(&vault_path);
+        if lockout.remaining(SystemTime::now()).is_some() {
+            show_unlock_cooldown(&app, &cooldown_timer, lockout);
+            return;
+        }
 
-        // 显示批量操作结果
-        let status = format_batch_result(&batch_result);
-        app.set_status_text(status.into());
+        let totp_code = app.get_unlock_totp_code().to_string();
+        app.set_unlock_totp_code("".into());
+        let totp_required = refresh_totp_required(&app, &vault_path);
 
-        // 刷新日志
-        refresh_logs_in_ui(&app, &settings);
+        // Argon2id 计算在后台线程中进行，事件循环中等待结果，窗口保持响应
+        app.set_unlock_pending(true);
+        a11y::set_status(&app, "⏳ 正在校验保险库密码...".into());
+        let authorized = vault::authorize_sensitive_action_async(
+            vault_path.clone(),
+            password,
+            Some(totp_code),
+            backoff,
+        );
+        let session = session.clone();
+        let cooldown_timer = cooldown_timer.clone();
+        let app_weak = app.as_weak();
+        let spawned = slint::spawn_local(async move {
+            let authorized = authorized.await;
+            let Some(app) = app_weak.upgrade() else {
+                return;
+            };
+            app.set_unlock_pending(false);
+            match authorized {
+                Ok(true) => {
+                    session.lock().unwrap().record_password(Instant::now());
+                    start(&app);
+                }
+                Ok(false) => {
+                    if lockout.remaining(SystemTime::now()).is_some() {
+                        show_unlock_cooldown(&app, &cooldown_timer, lockout);
+                    } else {
+                        let message = if totp_required {
+                            "❌ 密码或验证码错误，未解锁"
+                        } else {
+                            "❌ 密码错误，未解锁"
+                        };
+                        a11y::set_status(
+                            &app,
+                            vault::wrong_password_message(&vault_path, message).into(),
+                        );
+                    }
+                }
+                Err(e) => a11y::set_status(&app, format!("❌ 无法校验保险库: {}", e).into()),
+            }
+        });
+        if let Err(e) = spawned {
+            app.set_unlock_pending(false);
+            a11y::set_status(&app, format!("❌ 无法校验保险库: {}", e).into());
+        }
     });
 }
 
-// === 启动信息显示 ===
+/// 解锁冷却期间禁用密码框，每秒在状态栏刷新剩余秒数，冷却结束后恢复
+fn show_unlock_cooldown(app: &MainWindow, timer: &Rc<Timer>, lockout: vault::LockoutStore) {
+    let Some(remaining) = lockout.remaining(SystemTime::now()) else {
+        return;
+    };
+    a11y::set_status(app, vault::cooldown_message(remaining).into());
+    app.set_unlock_cooldown(true);
 
-fn show_startup_info(app: &MainWindow) -> anyhow::Result<()> {
-    match token::probe_capability() {
-        Ok(report) => {
-            let mut warnings = Vec::new();
+    let app_weak = app.as_weak();
+    let weak_timer = Rc::downgrade(timer);
+    timer.start(TimerMode::Repeated, Duration::from_secs(1), move || {
+        let Some(app) = app_weak.upgrade() else {
+            return;
+        };
+        match lockout.remaining(SystemTime::now()) {
+            Some(remaining) => app.set_status_text(vault::cooldown_message(remaining).into()),
+            None => {
+                if let Some(timer) = weak_timer.upgrade() {
+                    timer.stop();
+                }
+                app.set_unlock_cooldown(false);
+                a11y::set_status(&app, "🔑 冷却结束，可以重新输入密码".into());
+            }
+        }
+    });
+}
+
+/// 设置 Windows Hello 解锁事件处理器
+///
+/// 验证在后台线程中进行（系统对话框会阻塞调用线程），通过后打开解锁宽限期并按普通解锁流程继续；
+/// 未启用、本次运行尚未输入过密码、不可用或验证未通过时提示改为输入密码
+fn setup_hello_unlock_handler(
+    app: &MainWindow,
+    settings: Arc<RwLock<Settings>>,
+    session: Arc<Mutex<UnlockSession>>,
+) {
+    let provider: Arc<dyn AuthProvider> = Arc::new(WindowsHelloProvider);
+    let allow_hello = settings.read().unwrap().allow_hello_unlock;
+    app.set_hello_available(allow_hello && provider.is_available());
+
+    let app_weak = app.as_weak();
+    app.on_request_unlock_with_hello(move |comment| {
+        let app = app_weak.unwrap();
+        let (allow_hello, vault_exists) = {
+            let s = settings.read().unwrap();
+            (s.allow_hello_unlock, Path::new(&s.vault_path).exists())
+        };
+
+        // 尚未设置保险库时解锁本就无需密码
+        if !vault_exists || session.lock().unwrap().is_open(Instant::now()) {
+            app.invoke_request_unlock(SharedString::new(), comment);
+            return;
+        }
+        if let Err(fallback) = session
+            .lock()
+            .unwrap()
+            .hello_precheck(allow_hello, provider.as_ref())
+        {
+            a11y::set_status(&app, format!("🔑 {}", fallback).into());
+            return;
+        }
+
+        a11y::set_status(&app, format!("🔑 等待 {} 验证...", provider.name()).into());
+        let provider = provider.clone();
+        let session = session.clone();
+        let app_weak = app.as_weak();
+        std::thread::spawn(move || {
+            let verified = provider.request_verification("确认解锁 AmberLock 保护的对象");
+            let _ = app_weak.upgrade_in_event_loop(move |app| {
+                let opened = verified
+                    .map_err(|e| HelloFallback::Failed(e.to_string()))
+                    .and_then(|verified| {
+                        session
+                            .lock()
+                            .unwrap()
+                            .record_hello(verified, Instant::now())
+                    });
+                match opened {
+                    Ok(()) => app.invoke_request_unlock(SharedString::new(), comment),
+                    Err(fallback) => a11y::set_status(&app, format!("🔑 {}", fallback).into()),
+                }
+            });
+        });
+    });
+}
+
+/// 设置撤销批次事件处理器
+///
+/// 校验保险库密码并确认对象数量后，在后台按批次撤销上锁
+fn setup_revert_handler(
+    app: &MainWindow,
+    settings: Arc<RwLock<Settings>>,
+    logger: Arc<OperationLog>,
+    user_sid: String,
+    active: ActiveOperation,
+) {
+    let app_weak = app.as_weak();
+
+    app.on_revert_batch(move |batch_id, password| {
+        let app = app_weak.unwrap();
+
+        if active.is_running() {
+            a11y::set_status(&app, "⚠️ 已有操作正在进行，请等待完成或取消".into());
+            return;
+        }
+        if app.get_read_only() {
+            a11y::set_status(&app, "🔍 只读审计模式下不能撤销批次".into());
+            return;
+        }
 
-            if !report.has_se_security {
-                warnings.push("⚠️ 缺少 SeSecurityPrivilege，功能受限");
+        let (log_path, vault_path, backoff) = {
+            let s = settings.read().unwrap();
+            (
+                s.log_path.clone(),
+                PathBuf::from(&s.vault_path),
+                BackoffConfig::from_settings(&s),
+            )
+        };
+        let password = SecretString::from(password.as_str());
+        let totp_code = app.get_revert_totp_code();
+        app.set_revert_totp_code("".into());
+        let totp_required = refresh_totp_required(&app, &vault_path);
+        match vault::authorize_sensitive_action(
+            &vault_path,
+            &password,
+            Some(totp_code.as_str()),
+            &backoff,
+        ) {
+            Ok(true) => {}
+            Ok(false) => {
+                let message = if totp_required {
+                    "❌ 密码或验证码错误，未撤销批次"
+                } else {
+                    "❌ 密码错误，未撤销批次"
+                };
+                a11y::set_status(
+                    &app,
+                    vault::wrong_password_message(&vault_path, message).into(),
+                );
+                return;
             }
+            Err(e) => {
+                a11y::set_status(&app, format!("❌ 无法校验保险库: {}", e).into());
+                return;
+            }
+        }
 
-            if !report.has_se_relabel {
-                warnings.push("ℹ️ 无法设置 System 级，将自动降级为 High");
+        let entries = match batch_entries(&log_path, &batch_id) {
+            Ok(entries) if entries.is_empty() => {
+                a11y::set_status(&app, "⚠️ 该批次没有可撤销的对象".into());
+                return;
             }
+            Ok(entries) => entries,
+            Err(e) => {
+                a11y::set_status(&app, format!("❌ 无法读取批次记录: {}", e).into());
+                return;
+            }
+        };
 
-            if warnings.is_empty() {
-                app.set_status_text(
-                    format!("✅ 就绪 - 完整性级别: {:?} | 版本: 2.0.0", report.caller_il).into(),
+        let answer = rfd::MessageDialog::new()
+            .set_title("撤销此批操作")
+            .set_level(rfd::MessageLevel::Warning)
+            .set_description(format!(
+                "将解锁批次 {} 中的 {} 个对象，批次之后标签已被修改的对象会被跳过。\n\n是否继续？",
+                batch_id,
+                entries.len()
+            ))
+            .set_buttons(rfd::MessageButtons::YesNo)
+            .show();
+        if answer != rfd::MessageDialogResult::Yes {
+            return;
+        }
+
+        let paths: Vec<PathBuf> = entries.iter().map(|entry| entry.path.clone()).collect();
+        let Some(wait_for_conflicts) = resolve_conflicts(&paths) else {
+            a11y::set_status(&app, "⚠️ 已放弃操作：与正在进行的操作冲突".into());
+            return;
+        };
+
+        let revert = BatchRevert::new(&batch_id, &user_sid, &logger)
+            .with_authorized_by(Some(DEFAULT_CREDENTIAL))
+            .with_origin(OperationOrigin::GuiButton);
+        active.set_touched_paths(paths);
+        let handle = spawn_unlock_batch(entries, revert, logger.clone(), wait_for_conflicts);
+        active.watch(
+            &app,
+            handle,
+            OperationKind::Unlock,
+            settings.clone(),
+            logger.clone(),
+            user_sid.clone(),
+        );
+    });
+}
+
+/// 设置全部解除保护事件处理器
+///
+/// 校验保险库密码与确认短语并再次确认后，在后台移除范围内全部对象的标签，
+/// 结束后显示复查结果，并可将本批次的日志记录导出留档
+fn setup_decommission_handler(
+    app: &MainWindow,
+    settings: Arc<RwLock<Settings>>,
+    logger: Arc<OperationLog>,
+    user_sid: String,
+    active: ActiveOperation,
+) {
+    let app_weak = app.as_weak();
+    let timer = Rc::new(Timer::default());
+
+    app.on_decommission(move |scope, password, phrase| {
+        let app = app_weak.unwrap();
+
+        if active.is_running() || app.get_busy() {
+            a11y::set_status(&app, "⚠️ 已有操作正在进行，请等待完成或取消".into());
+            return;
+        }
+        if app.get_read_only() {
+            a11y::set_status(&app, "🔍 只读审计模式下不能解除保护".into());
+            return;
+        }
+        if let Err(e) = check_confirm_phrase(&phrase) {
+            a11y::set_status(&app, format!("⚠️ {}", e).into());
+            return;
+        }
+
+        let (log_path, vault_path, backoff) = {
+            let s = settings.read().unwrap();
+            (
+                s.log_path.clone(),
+                PathBuf::from(&s.vault_path),
+                BackoffConfig::from_settings(&s),
+            )
+        };
+        let password = SecretString::from(password.as_str());
+        let totp_code = app.get_decommission_totp_code();
+        app.set_decommission_totp_code("".into());
+        let totp_required = refresh_totp_required(&app, &vault_path);
+        match vault::authorize_sensitive_action(
+            &vault_path,
+            &password,
+            Some(totp_code.as_str()),
+            &backoff,
+        ) {
+            Ok(true) => {}
+            Ok(false) => {
+                let message = if totp_required {
+                    "❌ 密码或验证码错误，未解除保护"
+                } else {
+                    "❌ 密码错误，未解除保护"
+                };
+                a11y::set_status(
+                    &app,
+                    vault::wrong_password_message(&vault_path, message).into(),
                 );
-            } else {
-                app.set_status_text(warnings.join(" | ").into());
+                return;
+            }
+            Err(e) => {
+                a11y::set_status(&app, format!("❌ 无法校验保险库: {}", e).into());
+                return;
             }
         }
-        Err(e) => {
-            app.set_status_text(format!("⚠️ 能力探测失败: {:?}", e).into());
+
+        let scope = match scope {
+            0 => UnprotectScope::FromInventory,
+            1 => UnprotectScope::FromLog,
+            _ => match bridge::pick_folders_dialog() {
+                Some(roots) => UnprotectScope::ScanRoots(roots),
+                None => return,
+            },
+        };
+        let answer = rfd::MessageDialog::new()
+            .set_title("全部解除保护")
+            .set_level(rfd::MessageLevel::Warning)
+            .set_description(format!(
+                "将移除{}中所有对象的保护标签（SYSTEM 级对象以 SYSTEM 权限移除），完成后逐一复查。\n\n此操作不可撤销，是否继续？",
+                scope
+            ))
+            .set_buttons(rfd::MessageButtons::YesNo)
+            .show();
+        if answer != rfd::MessageDialogResult::Yes {
+            return;
         }
-    }
 
-    Ok(())
+        let request = UnprotectAll::new(scope, &user_sid, DEFAULT_CREDENTIAL, &logger)
+            .with_origin(OperationOrigin::GuiButton);
+        let progress = ProgressTracker::indeterminate();
+        let finished: Arc<Mutex<Option<amberlock_types::Result<UnprotectReport>>>> =
+            Arc::default();
+        app.set_busy(true);
+        a11y::set_status(&app, "正在全部解除保护…".into());
+
+        let worker_logger = logger.clone();
+        let worker_progress = progress.clone();
+        let worker_slot = finished.clone();
+        let phrase = phrase.to_string();
+        std::thread::spawn(move || {
+            let outcome = unprotect_all_now(&request, &phrase, &worker_logger, &worker_progress);
+            *worker_slot.lock().unwrap() = Some(outcome);
+        });
+
+        // 工作线程结束后在 UI 线程显示结果（与 ActiveOperation 相同，定时器轮询）
+        let app_weak = app.as_weak();
+        let settings = settings.clone();
+        let poll_timer = Rc::downgrade(&timer);
+        timer.start(TimerMode::Repeated, Duration::from_millis(100), move || {
+            let Some(app) = app_weak.upgrade() else {
+                return;
+            };
+            let Some(outcome) = finished.lock().unwrap().take() else {
+                app.set_status_text(progress.format_status_detailed().into());
+                return;
+            };
+            if let Some(timer) = poll_timer.upgrade() {
+                timer.stop();
+            }
+            app.set_busy(false);
+            refresh_logs_in_ui(&app, &settings);
+            match outcome {
+                Ok(report) => show_decommission_report(&app, &report, &log_path),
+                Err(e) => a11y::set_status(&app, format!("❌ 解除保护失败: {}", e).into()),
+            }
+        });
+    });
 }
 
-// === 辅助函数 ===
+/// 设置修改保险库密码事件处理器
+///
+/// 两次输入的新密码一致后以原密码（启用 TOTP 时连同验证码）授权并写回，
+/// 读回确认新保险库能以新密码打开（见 `vault::change_vault_password`）
+fn setup_change_password_handler(app: &MainWindow, settings: Arc<RwLock<Settings>>) {
+    let app_weak = app.as_weak();
 
-/// 格式化批量操作结果（任务 7.1：清晰的错误提示）
-fn format_batch_result(result: &amberlock_core::BatchResult) -> String {
-    if result.failed_count == 0 {
-        if result.downgraded_count > 0 {
-            format!(
-                "✅ 操作成功：完成 {} 个（其中 {} 个已降级）",
-                result.success_count, result.downgraded_count
-            )
-        } else {
-            format!("✅ 操作成功：完成 {} 个", result.success_count)
+    app.on_change_vault_password(move |old_password, new_password, confirm| {
+        let app = app_weak.unwrap();
+
+        if app.get_busy() {
+            a11y::set_status(&app, "⚠️ 已有操作正在进行，请等待完成或取消".into());
+            return;
         }
-    } else {
-        format!(
-            "⚠️ 操作部分失败：成功 {} 个，失败 {} 个{}",
-            result.success_count,
-            result.failed_count,
-            if result.downgraded_count > 0 {
-                format!("，降级 {} 个", result.downgraded_count)
-            } else {
-                String::new()
+        if app.get_read_only() {
+            a11y::set_status(&app, "🔍 只读审计模式下不能修改保险库密码".into());
+            return;
+        }
+        if new_password != confirm {
+            a11y::set_status(&app, "⚠️ 两次输入的新密码不一致，未修改".into());
+            return;
+        }
+
+        let vault_path = PathBuf::from(&settings.read().unwrap().vault_path);
+        let old_password = SecretString::from(old_password.as_str());
+        let new_password = SecretString::from(new_password.as_str());
+        let totp_code = app.get_change_password_totp_code();
+        app.set_change_password_totp_code("".into());
+        let totp_required = refresh_totp_required(&app, &vault_path);
+        match vault::change_vault_password(
+            &vault_path,
+            &old_password,
+            Some(totp_code.as_str()),
+            &new_password,
+        ) {
+            Ok(()) => a11y::set_status(&app, "✅ 保险库密码已修改".into()),
+            Err(e) if vault::is_wrong_password(&e) => {
+                let message = if totp_required {
+                    "❌ 原密码或验证码错误，未修改"
+                } else {
+                    "❌ 原密码错误，未修改"
+                };
+                a11y::set_status(
+                    &app,
+                    vault::wrong_password_message(&vault_path, message).into(),
+                );
             }
-        )
-    }
+            Err(e) => a11y::set_status(&app, format!("❌ 修改保险库密码失败: {}", e).into()),
+        }
+    });
 }
 
+/// 显示全部解除保护的结果，询问是否导出本批次的日志记录
+fn show_decommission_report(app: &MainWindow, report: &UnprotectReport, log_path: &str) {
+    let (level, icon) = if report.is_clean() {
+        (rfd::MessageLevel::Info, "✅")
+    } else {
+        (rfd::MessageLevel::Warning, "⚠️")
+    };
+    a11y::set_status(app, format!("{} {}", icon, report).into());
+
+    let mut description = report.to_string();
+    for object in report.leftovers.iter().take(SEAL_PREVIEW_DETAIL) {
+        description.push_str(&format!("\n  {}（{:?}）", object.path, object.level));
+    }
+    if report.leftovers.len() > SEAL_PREVIEW_DETAIL {
+        description.push_str(&format!(
+            "\n  …另有 {} 个",
+            report.leftovers.len() - SEAL_PREVIEW_DETAIL
+        ));
+    }
+    description.push_str("\n\n是否导出本批次的日志记录留档？");
+    let answer = rfd::MessageDialog::new()
+        .set_title("全部解除保护")
+        .set_level(level)
+        .set_description(description)
+        .set_buttons(rfd::MessageButtons::YesNo)
+        .show();
+    if answer != rfd::MessageDialogResult::Yes {
+        return;
+    }
+
+    let file_name = format!("amberlock-decommission-{}.ndjson", report.batch_id);
+    let Some(output) = bridge::save_file_dialog("导出本批次记录", &file_name) else {
+        return;
+    };
+    match export_batch_records(log_path, &report.batch_id, &output) {
+        Ok(count) => a11y::set_status(
+            app,
+            format!("✅ 已导出 {} 条记录到 {}", count, output.display()).into(),
+        ),
+        Err(e) => a11y::set_status(app, format!("❌ 导出失败: {}", e).into()),
+    }
+}
+
+/// 将指定批次的日志记录按 NDJSON 写入文件，返回写入的条数
+fn export_batch_records(log_path: &str, batch_id: &str, output: &Path) -> anyhow::Result<usize> {
+    let records = QueryBuilder::new(log_path)
+        .filter_custom("batch_id", batch_id)
+        .execute()?;
+    let mut content = String::new();
+    for record in &records {
+        content.push_str(&record.to_string());
+        content.push('\n');
+    }
+    std::fs::write(output, content)?;
+    Ok(records.len())
+}
+
+/// 设置快速保护事件处理器
+///
+/// 选择预设时先解析根目录并预估对象数量（不修改任何对象），
+/// 用户确认后按预设在后台批量上锁
+fn setup_preset_handlers(
+    app: &MainWindow,
+    settings: Arc<RwLock<Settings>>,
+    logger: Arc<OperationLog>,
+    effective_level: LabelLevel,
+    user_sid: String,
+    active: ActiveOperation,
+) {
+    let presets = ProtectionPreset::builtin();
+    app.set_presets(VecModel::from_slice(&bridge::preset_items(&presets)));
+
+    let pending: Rc<RefCell<Option<PresetPlan>>> = Rc::default();
+
+    {
+        let app_weak = app.as_weak();
+        let pending = pending.clone();
+        app.on_preview_preset(move |index| {
+            let app = app_weak.unwrap();
+            *pending.borrow_mut() = None;
+            app.set_preset_ready(false);
+
+            let Some(preset) = usize::try_from(index).ok().and_then(|i| presets.get(i)) else {
+                return;
+            };
+
+            // 没有默认文件夹的预设由用户选择根目录
+            let root = match preset.default_folder() {
+                Some(_) => None,
+                None => match bridge::pick_folder_dialog("选择要保护的文件夹") {
+                    Some(root) => Some(root),
+                    None => {
+                        app.set_preset_preview("未选择文件夹".into());
+                        return;
+                    }
+                },
+            };
+
+            match plan_preset(preset, root.as_deref()) {
+                Ok(plan) if plan.targets.is_empty() => {
+                    app.set_preset_preview(
+                        format!("⚠️ {} 中没有需要保护的对象", plan.root.display()).into(),
+                    );
+                }
+                Ok(plan) => {
+                    app.set_preset_preview(plan.summary().into());
+                    app.set_preset_ready(true);
+                    *pending.borrow_mut() = Some(plan);
+                }
+                Err(e) => app.set_preset_preview(format!("❌ 无法解析预设: {}", e).into()),
+            }
+        });
+    }
+
+    let app_weak = app.as_weak();
+    app.on_confirm_preset(move || {
+        let app = app_weak.unwrap();
+        app.set_preset_ready(false);
+
+        let Some(plan) = pending.borrow_mut().take() else {
+            return;
+        };
+
+        if active.is_running() {
+            a11y::set_status(&app, "⚠️ 已有操作正在进行，请等待完成或取消".into());
+            return;
+        }
+
+        let Some(wait_for_conflicts) = resolve_conflicts(&plan.targets) else {
+            a11y::set_status(&app, "⚠️ 已放弃操作：与正在进行的操作冲突".into());
+            return;
+        };
+
+        active.set_touched_paths(plan.targets.clone());
+        let handle = spawn_preset(
+            plan,
+            effective_level,
+            user_sid.clone(),
+            logger.clone(),
+            wait_for_conflicts,
+            OperationOrigin::GuiButton,
+        );
+        active.watch(
+            &app,
+            handle,
+            OperationKind::Lock,
+            settings.clone(),
+            logger.clone(),
+            user_sid.clone(),
+        );
+    });
+}
+
+/// 设置对象修复事件处理器
+///
+/// 在 SYSTEM 权限下按勾选的步骤修复单个对象，完成后以对话框展示每一步的结果
+/// 与修复前后的状态对比
+fn setup_repair_handler(
+    app: &MainWindow,
+    settings: Arc<RwLock<Settings>>,
+    logger: Arc<OperationLog>,
+    file_model: Arc<Mutex<FileListModel>>,
+    user_sid: String,
+    active: ActiveOperation,
+) {
+    let app_weak = app.as_weak();
+    app.on_repair_object(
+        move |path, relabel, level, reset_inheritance, take_ownership| {
+            let app = app_weak.unwrap();
+
+            if active.is_running() {
+                a11y::set_status(&app, "⚠️ 已有操作正在进行，请等待完成或取消".into());
+                return;
+            }
+            if app.get_read_only() {
+                a11y::set_status(&app, "🔍 只读审计模式下不能修复对象".into());
+                return;
+            }
+
+            let path = PathBuf::from(path.as_str());
+            let (_, level) = bridge::convert_ui_params(Mode::ReadOnly, level);
+            let options = RepairOptions {
+                target_level: relabel.then_some(level),
+                reset_dacl_inheritance: reset_inheritance,
+                take_ownership,
+            };
+            let report = match force_repair(
+                &path,
+                &options,
+                &user_sid,
+                &logger,
+                OperationOrigin::GuiButton,
+            ) {
+                Ok(report) => report,
+                Err(e) => {
+                    a11y::set_status(&app, format!("❌ 修复失败: {}", e).into());
+                    return;
+                }
+            };
+            let _ = logger.flush();
+
+            let failed = report.failed_steps().count();
+            a11y::set_status(
+                &app,
+                if failed == 0 {
+                    format!("🔧 已修复 {}", path.display())
+                } else {
+                    format!("⚠️ 修复完成，{} 个步骤失败", failed)
+                }
+                .into(),
+            );
+            rfd::MessageDialog::new()
+                .set_title("修复结果")
+                .set_level(if failed == 0 {
+                    rfd::MessageLevel::Info
+                } else {
+                    rfd::MessageLevel::Warning
+                })
+                .set_description(report.render_lines().join("\n"))
+                .set_buttons(rfd::MessageButtons::Ok)
+                .show();
+
+            file_model.lock().unwrap().invalidate_labels(&[path]);
+            refresh_logs_in_ui(&app, &settings);
+        },
+    );
+}
+
+/// 设置取消操作事件处理器
+///
+/// 批量上锁先确认已完成对象的处理方式：显示设置中的方式，并允许仅对本次操作改选
+fn setup_cancel_handler(app: &MainWindow, active: ActiveOperation) {
+    let app_weak = app.as_weak();
+
+    app.on_cancel_operation(move || {
+        if !active.is_running() {
+            return;
+        }
+        match active.cancel_behavior.get() {
+            Some(configured) => {
+                let Some(behavior) = confirm_cancel_behavior(configured) else {
+                    return;
+                };
+                active.cancel_with(behavior);
+            }
+            None => active.cancel(),
+        }
+        a11y::set_status(&app_weak.unwrap(), "⏹ 正在取消…".into());
+    });
+}
+
+/// 取消批量上锁前确认已完成对象的处理方式
+///
+/// # 返回
+/// 用户选择的处理方式；选择继续操作时返回 None
+fn confirm_cancel_behavior(configured: CancelBehavior) -> Option<CancelBehavior> {
+    let keep = CancelBehavior::KeepCompleted.to_string();
+    let roll_back = CancelBehavior::RollBackCompleted.to_string();
+    let resume = "继续操作".to_string();
+    let answer = rfd::MessageDialog::new()
+        .set_title("取消批量上锁")
+        .set_level(rfd::MessageLevel::Warning)
+        .set_description(format!(
+            "取消后不再处理剩余对象。\n\n当前设置：{}。\n可仅对本次操作改选其他处理方式；撤销按审计日志逆序解锁，上锁后又被改动的对象保持不变。",
+            configured
+        ))
+        .set_buttons(rfd::MessageButtons::YesNoCancelCustom(
+            keep.clone(),
+            roll_back.clone(),
+            resume,
+        ))
+        .show();
+    match answer {
+        rfd::MessageDialogResult::Custom(label) if label == keep => {
+            Some(CancelBehavior::KeepCompleted)
+        }
+        rfd::MessageDialogResult::Custom(label) if label == roll_back => {
+            Some(CancelBehavior::RollBackCompleted)
+        }
+        _ => None,
+    }
+}
+
+/// 设置窗口关闭请求处理器
+///
+/// 操作进行中拒绝关闭，经用户确认后取消操作，操作结束时再退出
+fn setup_close_handler(app: &MainWindow, active: ActiveOperation) {
+    let app_weak = app.as_weak();
+
+    app.window().on_close_requested(move || {
+        let decision = active.close_gate.request_close(active.is_running(), || {
+            rfd::MessageDialog::new()
+                .set_title("操作进行中")
+                .set_level(rfd::MessageLevel::Warning)
+                .set_description("当前有批量操作正在进行，直接退出可能导致审计记录不完整。\n\n是否取消该操作并在其结束后退出？")
+                .set_buttons(rfd::MessageButtons::YesNo)
+                .show()
+                == rfd::MessageDialogResult::Yes
+        });
+        match decision {
+            CloseDecision::Close => CloseRequestResponse::HideWindow,
+            CloseDecision::Refuse => CloseRequestResponse::KeepWindowShown,
+            CloseDecision::CancelAndWait => {
+                active.cancel();
+                if let Some(app) = app_weak.upgrade() {
+                    a11y::set_status(&app, "⏹ 正在取消，操作结束后退出…".into());
+                }
+                CloseRequestResponse::KeepWindowShown
+            }
+        }
+    });
+}
+
+// === 后台操作 ===
+
+/// 锁定卷根前的二次确认
+///
+/// # 返回
+/// 用户确认继续时返回 `true`
+fn confirm_volume_root_lock(roots: &[&PathBuf]) -> bool {
+    let list = roots
+        .iter()
+        .map(|root| root.display().to_string())
+        .collect::<Vec<_>>()
+        .join("、");
+    let answer = rfd::MessageDialog::new()
+        .set_title("卷根保护警告")
+        .set_level(rfd::MessageLevel::Warning)
+        .set_description(format!(
+            "您正在尝试锁定卷根（{}），这可能导致：\n\n\
+             ❌ 系统更新失败\n❌ 应用程序无法安装\n❌ 系统服务异常\n\n\
+             卷根仅以只读模式（仅 NW 策略）上锁，请求 System 级别时将降为 High。\n\n\
+             确定要继续吗？",
+            list
+        ))
+        .set_buttons(rfd::MessageButtons::YesNo)
+        .show();
+    answer == rfd::MessageDialogResult::Yes
+}
+
+/// 封印前的变更确认
+///
+/// 列出前 `SEAL_PREVIEW_DETAIL` 个对象的 ACE 变化，拒绝当前用户访问的条目以 ⚠️ 标出
+///
+/// # 返回
+/// 用户确认继续时返回 `true`
+fn confirm_seal(plans: &[SealPlan], user_sid: &str) -> bool {
+    let user_sids = [user_sid];
+    let dangerous: usize = plans
+        .iter()
+        .map(|plan| plan.dangerous(&user_sids).len())
+        .sum();
+
+    let mut lines = Vec::new();
+    if dangerous > 0 {
+        lines.push(format!(
+            "⚠️ 有 {} 条变更会拒绝当前用户访问，封印后可能无法再打开这些对象！\n",
+            dangerous
+        ));
+    }
+    for plan in plans.iter().take(SEAL_PREVIEW_DETAIL) {
+        lines.extend(plan.render_lines(&user_sids));
+    }
+    if plans.len() > SEAL_PREVIEW_DETAIL {
+        lines.push(format!(
+            "……另有 {} 个对象",
+            plans.len() - SEAL_PREVIEW_DETAIL
+        ));
+    }
+    lines.push(
+        "\n写入前会再次读取安全描述符，预览之后被修改的对象将放弃封印。确定要继续吗？".to_string(),
+    );
+
+    let answer = rfd::MessageDialog::new()
+        .set_title("封印变更预览")
+        .set_level(if dangerous > 0 {
+            rfd::MessageLevel::Error
+        } else {
+            rfd::MessageLevel::Warning
+        })
+        .set_description(lines.join("\n"))
+        .set_buttons(rfd::MessageButtons::YesNo)
+        .show();
+    answer == rfd::MessageDialogResult::Yes
+}
+
+/// 检查与进行中操作的路径冲突
+///
+/// # 返回
+/// - `Some(false)`: 无冲突
+/// - `Some(true)`: 用户同意取消冲突操作，新操作应等待其结束
+/// - `None`: 用户放弃本次操作
+fn resolve_conflicts(paths: &[PathBuf]) -> Option<bool> {
+    let registry = ActiveOperations::global();
+    let Some(conflict) = registry.find_conflict(paths) else {
+        return Some(false);
+    };
+
+    let answer = rfd::MessageDialog::new()
+        .set_title("操作冲突")
+        .set_level(rfd::MessageLevel::Warning)
+        .set_description(format!(
+            "路径与正在进行的{}操作重叠：{}\n\n取消正在进行的操作并继续？",
+            conflict.kind,
+            conflict.root.display()
+        ))
+        .set_buttons(rfd::MessageButtons::YesNo)
+        .show();
+
+    if answer == rfd::MessageDialogResult::Yes {
+        registry.cancel_conflicting(paths);
+        Some(true)
+    } else {
+        None
+    }
+}
+
+/// 当前后台操作（同一时刻只允许一个批量操作）
+///
+/// 仅在 UI 线程中使用：定时器轮询句柄进度，工作线程结束后在 UI 线程 join 并显示结果
+#[derive(Clone)]
+struct ActiveOperation {
+    handle: Rc<RefCell<Option<OperationHandle>>>,
+    timer: Rc<Timer>,
+    notifier: Rc<RefCell<SystemNotifier>>,
+    /// 当前操作的诊断跟踪文件（结束时告知用户位置）
+    trace_path: Rc<RefCell<Option<PathBuf>>>,
+    /// 当前操作涉及的路径（结束时使文件列表中对应行的标签状态失效）
+    touched_paths: Rc<RefCell<Vec<PathBuf>>>,
+    /// 批量上锁被取消时对已完成对象的处理方式（其他操作为 None）
+    cancel_behavior: Rc<Cell<Option<CancelBehavior>>>,
+    file_model: Arc<Mutex<FileListModel>>,
+    /// 窗口关闭闸门（操作结束后按需退出）
+    close_gate: Rc<CloseGate>,
+    /// 操作结束时的错误对话框与提示面板
+    dialogs: DialogQueue,
+    /// 上一次操作的按目录汇总
+    dir_tree: DirTreeView,
+}
+
+impl ActiveOperation {
+    fn new(file_model: Arc<Mutex<FileListModel>>, dialogs: DialogQueue) -> Self {
+        Self {
+            handle: Rc::default(),
+            timer: Rc::default(),
+            notifier: Rc::default(),
+            trace_path: Rc::default(),
+            touched_paths: Rc::default(),
+            cancel_behavior: Rc::default(),
+            file_model,
+            close_gate: Rc::default(),
+            dialogs,
+            dir_tree: DirTreeView::default(),
+        }
+    }
+
+    /// 是否有操作正在进行
+    fn is_running(&self) -> bool {
+        self.handle.borrow().is_some()
+    }
+
+    /// 请求取消当前操作
+    fn cancel(&self) {
+        if let Some(handle) = self.handle.borrow().as_ref() {
+            handle.cancel();
+        }
+    }
+
+    /// 请求取消当前操作，并仅对本次操作指定已完成对象的处理方式
+    fn cancel_with(&self, behavior: CancelBehavior) {
+        if let Some(handle) = self.handle.borrow().as_ref() {
+            handle.cancel_with(behavior);
+        }
+    }
+
+    /// 记录当前操作被取消时的处理方式（仅批量上锁）
+    fn set_cancel_behavior(&self, behavior: Option<CancelBehavior>) {
+        self.cancel_behavior.set(behavior);
+    }
+
+    /// 记录当前操作的诊断跟踪文件
+    fn set_trace_path(&self, path: Option<PathBuf>) {
+        *self.trace_path.borrow_mut() = path;
+    }
+
+    /// 记录当前操作涉及的路径
+    fn set_touched_paths(&self, paths: Vec<PathBuf>) {
+        *self.touched_paths.borrow_mut() = paths;
+    }
+
+    /// 开始监视后台操作，结束后刷新状态栏与日志，窗口不在前台时发出完成通知，
+    /// 失败数量达到阈值时投递告警
+    fn watch(
+        &self,
+        app: &MainWindow,
+        handle: OperationHandle,
+        kind: OperationKind,
+        settings: Arc<RwLock<Settings>>,
+        logger: Arc<OperationLog>,
+        user_sid: String,
+    ) {
+        app.set_busy(true);
+        app.set_status_text(handle.progress().format_status_detailed().into());
+        app.set_warning_lines(VecModel::<SharedString>::from_slice(&[]));
+        *self.handle.borrow_mut() = Some(handle);
+
+        let app_weak = app.as_weak();
+        let slot = self.handle.clone();
+        let timer = Rc::downgrade(&self.timer);
+        let notifier = self.notifier.clone();
+        let trace_path = self.trace_path.clone();
+        let touched_paths = self.touched_paths.clone();
+        let cancel_behavior = self.cancel_behavior.clone();
+        let file_model = self.file_model.clone();
+        let close_gate = self.close_gate.clone();
+        let dialogs = self.dialogs.clone();
+        let dir_tree = self.dir_tree.clone();
+
+        self.timer
+            .start(TimerMode::Repeated, Duration::from_millis(100), move || {
+                let Some(app) = app_weak.upgrade() else {
+                    return;
+                };
+
+                if let Some(handle) = slot.borrow().as_ref()
+                    && !handle.is_finished()
+                {
+                    app.set_status_text(handle.progress().format_status_detailed().into());
+                    return;
+                }
+
+                if let Some(timer) = timer.upgrade() {
+                    timer.stop();
+                }
+                app.set_busy(false);
+
+                // 操作涉及的对象需要重新读取标签
+                let touched = std::mem::take(&mut *touched_paths.borrow_mut());
+                file_model.lock().unwrap().invalidate_labels(&touched);
+                cancel_behavior.set(None);
+
+                if let Some(handle) = slot.borrow_mut().take() {
+                    let cancelled = handle.is_cancelled();
+                    let outcome = handle.join();
+                    let status = match &outcome {
+                        Ok(result) if cancelled => match &result.cancelled {
+                            Some(summary) => {
+                                format!("⏹ {}；{}", summary, bridge::format_batch_result(result))
+                            }
+                            None => {
+                                format!("⏹ 操作已取消，{}", bridge::format_batch_result(result))
+                            }
+                        },
+                        Ok(result) => bridge::format_batch_result(result),
+                        Err(e) => format!("❌ 操作失败: {}", e),
+                    };
+                    // 按警告类型汇总，供状态栏展开查看
+                    let warning_lines = match &outcome {
+                        Ok(result) => result.warning_lines(),
+                        Err(_) => Vec::new(),
+                    };
+                    let warning_lines: Vec<SharedString> =
+                        warning_lines.into_iter().map(Into::into).collect();
+                    app.set_warning_lines(VecModel::from_slice(&warning_lines));
+                    dir_tree.show(
+                        &app,
+                        outcome.as_ref().ok().and_then(BatchResult::dir_summary),
+                    );
+                    let status = match trace_path.borrow_mut().take() {
+                        Some(path) => {
+                            format!("{}；🧭 诊断跟踪已保存到 {}", status, path.display())
+                        }
+                        None => status,
+                    };
+                    a11y::set_status(&app, status.into());
+
+                    let enabled = settings.read().unwrap().notifications_enabled;
+                    if let Some(notice) = notify::completion_notice(
+                        kind,
+                        outcome.as_ref(),
+                        cancelled,
+                        notify::app_in_foreground(),
+                        enabled,
+                    ) {
+                        let _ = notifier.borrow_mut().show(&notice);
+                    }
+
+                    dialogs.report_outcome(&app, kind, outcome.as_ref(), cancelled);
+
+                    if let Ok(result) = &outcome {
+                        dispatch_alert(kind, result, &settings.read().unwrap(), &logger, &user_sid);
+                    }
+                }
+
+                // 刷新日志（同时保存保护清单）
+                let _ = logger.flush();
+                refresh_logs_in_ui(&app, &settings);
+                check_audit_log(&app, &settings, &logger);
+
+                // 关闭窗口时请求过取消：操作已结束，现在退出
+                if close_gate.operation_finished() {
+                    let _ = logger.flush();
+                    let _ = slint::quit_event_loop();
+                }
+            });
+    }
+}
+
+/// 上一次操作的按目录汇总（仅在 UI 线程中使用）
+///
+/// 未开启汇总的操作结束后清空；默认只展开根目录
+#[derive(Clone, Default)]
+struct DirTreeView {
+    summary: Rc<RefCell<Option<DirSummary>>>,
+    expanded: Rc<RefCell<HashSet<String>>>,
+}
+
+impl DirTreeView {
+    /// 显示新的汇总（None 时隐藏）
+    fn show(&self, app: &MainWindow, summary: Option<DirSummary>) {
+        let mut expanded = self.expanded.borrow_mut();
+        expanded.clear();
+        if let Some(summary) = &summary {
+            expanded.insert(summary.path.clone());
+        }
+        drop(expanded);
+        *self.summary.borrow_mut() = summary;
+        self.render(app);
+    }
+
+    /// 展开或折叠目录
+    fn toggle(&self, app: &MainWindow, path: &str) {
+        {
+            let mut expanded = self.expanded.borrow_mut();
+            if !expanded.remove(path) {
+                expanded.insert(path.to_string());
+            }
+        }
+        self.render(app);
+    }
+
+    fn render(&self, app: &MainWindow) {
+        let rows = match self.summary.borrow().as_ref() {
+            Some(summary) => bridge::dir_rows(summary, &self.expanded.borrow()),
+            None => Vec::new(),
+        };
+        app.set_dir_rows(VecModel::from_slice(&rows));
+    }
+}
+
+/// 设置按目录汇总的展开与跳转处理器
+///
+/// 点击“查看日志”时以 `path:` 查询筛选该目录下的记录
+fn setup_dir_tree_handlers(app: &MainWindow, dir_tree: DirTreeView) {
+    {
+        let app_weak = app.as_weak();
+        app.on_toggle_dir_row(move |path| {
+            dir_tree.toggle(&app_weak.unwrap(), &path);
+        });
+    }
+
+    let app_weak = app.as_weak();
+    app.on_open_dir_logs(move |path| {
+        app_weak
+            .unwrap()
+            .invoke_show_log_query(subtree_query(&path).into());
+    });
+}
+
+/// 错误对话框队列（仅在 UI 线程中使用）
+///
+/// 请求经 `DialogCoordinator` 去重、合并后由定时器逐个显示，
+/// 低严重级别的请求只进入提示面板
+#[derive(Clone)]
+struct DialogQueue {
+    coordinator: Rc<RefCell<DialogCoordinator>>,
+    timer: Rc<Timer>,
+}
+
+impl DialogQueue {
+    /// 创建队列并开始定时显示，同时绑定提示面板的清空回调
+    fn start(app: &MainWindow) -> Self {
+        let queue = Self {
+            coordinator: Rc::default(),
+            timer: Rc::default(),
+        };
+
+        let coordinator = queue.coordinator.clone();
+        queue
+            .timer
+            .start(TimerMode::Repeated, DIALOG_PUMP_INTERVAL, move || {
+                // 显示期间不持有借用：模态框的消息循环会再次触发本定时器
+                let next = coordinator.borrow_mut().next_dialog(Instant::now());
+                if let Some(dialog) = next {
+                    dialogs::show_dialog(&dialog);
+                    coordinator.borrow_mut().dismissed();
+                }
+            });
+
+        let app_weak = app.as_weak();
+        let coordinator = queue.coordinator.clone();
+        app.on_clear_notices(move || {
+            coordinator.borrow_mut().clear_panel();
+            if let Some(app) = app_weak.upgrade() {
+                app.set_notice_lines(VecModel::<SharedString>::from_slice(&[]));
+            }
+        });
+
+        queue
+    }
+
+    /// 提交请求；写入提示面板时同步刷新界面
+    fn submit(&self, app: &MainWindow, request: DialogRequest) {
+        let route = dialogs::route_for(request.severity);
+        self.coordinator.borrow_mut().submit(request, Instant::now());
+        if route == Route::Panel {
+            self.sync_panel(app);
+        }
+    }
+
+    /// 将操作结果转为对话框请求
+    ///
+    /// 单个对象的失败只进入提示面板；整批失败或操作出错时才弹出模态框
+    fn report_outcome(
+        &self,
+        app: &MainWindow,
+        kind: OperationKind,
+        outcome: Result<&BatchResult, &AmberlockError>,
+        cancelled: bool,
+    ) {
+        let title = format!("{}失败", kind);
+        match outcome {
+            Ok(result) => {
+                {
+                    let mut coordinator = self.coordinator.borrow_mut();
+                    let now = Instant::now();
+                    for failure in &result.failures {
+                        coordinator.submit(
+                            DialogRequest::new(Severity::Item, &failure.path, &failure.error),
+                            now,
+                        );
+                    }
+                }
+                if !result.failures.is_empty() {
+                    self.sync_panel(app);
+                }
+                if !cancelled && result.outcome() == BatchOutcome::AllFailed {
+                    self.submit(
+                        app,
+                        DialogRequest::new(
+                            Severity::Batch,
+                            title,
+                            bridge::format_batch_result(result),
+                        ),
+                    );
+                }
+            }
+            Err(e) => self.submit(
+                app,
+                DialogRequest::new(Severity::Fatal, title, e.to_string()),
+            ),
+        }
+    }
+
+    /// 刷新提示面板
+    fn sync_panel(&self, app: &MainWindow) {
+        let lines: Vec<SharedString> = self
+            .coordinator
+            .borrow()
+            .panel()
+            .map(|entry| entry.line().into())
+            .collect();
+        app.set_notice_lines(VecModel::from_slice(&lines));
+    }
+}
+
+/// 审计日志持续写入失败时诊断原因并提供恢复方式
+///
+/// 日志目录被自身保护锁定（`AuditBlockedBySelfLock`）时弹窗询问：
+/// 更改日志位置，或在有权限时临时解除目录标签补写积压记录
+fn check_audit_log(app: &MainWindow, settings: &Arc<RwLock<Settings>>, logger: &OperationLog) {
+    if !logger.is_persistently_failing() {
+        return;
+    }
+    let pending = logger.backlog_len();
+    let Some(AmberlockError::AuditBlockedBySelfLock {
+        log_dir,
+        dir_level,
+        process_level,
+    }) = diagnose_log_blockage(logger)
+    else {
+        a11y::set_status(
+            app,
+            format!("⚠️ 审计日志写入失败，{} 条记录等待补写", pending).into(),
+        );
+        return;
+    };
+
+    const RELOCATE: &str = "更改日志位置";
+    const LIFT: &str = "临时解除并补写";
+    let can_lift = can_lift_label(dir_level);
+    let description = format!(
+        "日志目录 {} 的完整性级别为 {:?}，当前程序以 {:?} 运行，无法写入审计日志（{} 条记录等待补写）。\n\n{}",
+        log_dir,
+        dir_level,
+        process_level,
+        pending,
+        if can_lift {
+            "可以更改日志位置，或临时解除目录标签、补写后恢复。"
+        } else {
+            "请更改日志位置，或以管理员身份重新启动后补写。"
+        }
+    );
+    let buttons = if can_lift {
+        rfd::MessageButtons::YesNoCancelCustom(RELOCATE.into(), LIFT.into(), "稍后处理".into())
+    } else {
+        rfd::MessageButtons::OkCancelCustom(RELOCATE.into(), "稍后处理".into())
+    };
+    let answer = rfd::MessageDialog::new()
+        .set_title("审计日志被锁定")
+        .set_level(rfd::MessageLevel::Warning)
+        .set_description(description)
+        .set_buttons(buttons)
+        .show();
+
+    let status = match answer {
+        rfd::MessageDialogResult::Custom(choice) if choice == RELOCATE => {
+            let Some(dir) = bridge::pick_folder_dialog("选择新的日志目录") else {
+                return;
+            };
+            let new_path = dir.join(LOG_FILE_NAME);
+            let result = get_settings_path().map_err(AmberlockError::from).and_then(|path| {
+                relocate_log(logger, &mut settings.write().unwrap(), &path, &new_path)
+            });
+            match result {
+                Ok(n) => format!("✅ 日志已切换到 {}，补写 {} 条记录", new_path.display(), n),
+                Err(e) => format!("❌ 切换日志失败: {}", e),
+            }
+        }
+        rfd::MessageDialogResult::Custom(choice) if choice == LIFT => {
+            match flush_backlog_with_label_lifted(logger) {
+                Ok(n) => format!("✅ 已补写 {} 条记录，日志目录标签已恢复", n),
+                Err(e) => format!("❌ 补写失败: {}", e),
+            }
+        }
+        _ => return,
+    };
+    a11y::set_status(app, status.into());
+    refresh_logs_in_ui(app, settings);
+}
+
+/// 按设置阈值在后台线程投递批量操作告警
+///
+/// # 注意
+/// 投递失败只写入日志，不影响已显示的操作结果
+fn dispatch_alert(
+    kind: OperationKind,
+    result: &BatchResult,
+    settings: &Settings,
+    logger: &Arc<OperationLog>,
+    user_sid: &str,
+) {
+    let Some(url) = settings.alert_webhook_url.clone().filter(|u| !u.is_empty()) else {
+        return;
+    };
+    let Some(alert) = AlertPolicy::from_settings(settings).batch_alert(kind, result, None) else {
+        return;
+    };
+
+    let logger = Arc::clone(logger);
+    let user_sid = user_sid.to_string();
+    std::thread::spawn(move || {
+        deliver_alert(&WebhookSink::new(&url), &alert, &logger, &user_sid);
+    });
+}
+
+// === 更新检查 ===
+
+/// 在后台检查更新，有新版本时写入状态栏
+///
+/// # 注意
+/// 检查开始前即记录检查时间，网络失败同样计入 24 小时限制；
+/// 网络失败不提示，清单签名问题以警告提示
+fn start_update_check(app: &MainWindow, settings: &Arc<RwLock<Settings>>) {
+    let manifest_url = {
+        let mut settings = settings.write().unwrap();
+        let Some(url) = settings
+            .update_manifest_url
+            .clone()
+            .filter(|u| settings.check_updates && !u.is_empty())
+        else {
+            return;
+        };
+        let now = std::time::SystemTime::now()
+            .duration_since(std::time::UNIX_EPOCH)
+            .map(|d| d.as_secs())
+            .unwrap_or_default();
+        if !updates::should_check(settings.last_update_check, now) {
+            return;
+        }
+        settings.last_update_check = Some(now);
+        // 立即持久化，避免异常退出后重复检查
+        let _ = get_settings_path().and_then(|path| save_settings(path, &settings));
+        url
+    };
+
+    let app_weak = app.as_weak();
+    std::thread::spawn(move || {
+        let status = match updates::check_for_update(
+            &updates::HttpFetcher::new(),
+            &manifest_url,
+            &updates::embedded_key(),
+            env!("CARGO_PKG_VERSION"),
+        ) {
+            Ok(Some(notice)) => notice.status_line(),
+            Ok(None) => return,
+            Err(e) if e.is_loud() => format!("⚠️ 已拒绝更新清单：{}", e),
+            Err(e) => {
+                updates::debug_log(&e.to_string());
+                return;
+            }
+        };
+        let _ = app_weak.upgrade_in_event_loop(move |app| a11y::set_status(&app, status.into()));
+    });
+}
+
+/// 在后台线程中定期执行到期的计划任务
+///
+/// # 注意
+/// 执行后立即保存设置（记录各条目的执行时间），结果显示在状态栏；
+/// 只读审计模式下不执行
+fn start_scheduler(
+    app: &MainWindow,
+    settings: &Arc<RwLock<Settings>>,
+    logger: Arc<OperationLog>,
+    user_sid: String,
+    effective_level: LabelLevel,
+) {
+    let app_weak = app.as_weak();
+    let settings = settings.clone();
+    std::thread::spawn(move || {
+        loop {
+            let mut schedule = settings.read().unwrap().schedule.clone();
+            let runs = if is_read_only() {
+                Vec::new()
+            } else {
+                run_due_now(&mut schedule, &logger, &user_sid, effective_level)
+            };
+            if !runs.is_empty() {
+                let mut settings = settings.write().unwrap();
+                settings.schedule = schedule;
+                let _ = get_settings_path().and_then(|path| save_settings(path, &settings));
+            }
+            let status = runs
+                .iter()
+                .map(ToString::to_string)
+                .collect::<Vec<_>>()
+                .join(" | ");
+            let ui_settings = settings.clone();
+            let alive = app_weak.upgrade_in_event_loop(move |app| {
+                if !status.is_empty() {
+                    a11y::set_status(&app, status.into());
+                    refresh_logs_in_ui(&app, &ui_settings);
+                }
+            });
+            if alive.is_err() {
+                break;
+            }
+            std::thread::sleep(SCHEDULE_CHECK_INTERVAL);
+        }
+    });
+}
+
+/// 监听卷到达与系统恢复事件，重新验证受影响的清单根
+///
+/// 事件映射、去抖与排队见 `amberlock_core::rescan`；该根上有进行中的操作时暂缓。
+/// 发现漂移时刷新日志并弹出通知
+fn start_volume_watcher(
+    app: &MainWindow,
+    settings: &Arc<RwLock<Settings>>,
+    inventory: Arc<InventoryTracker>,
+    logger: Arc<OperationLog>,
+    user_sid: String,
+) {
+    let (sink, events) = std::sync::mpsc::channel();
+    if let Err(e) = DeviceNotificationWindow.start(sink) {
+        a11y::set_status(app, format!("⚠️ 无法监听卷连接事件：{}", e).into());
+        return;
+    }
+
+    let app_weak = app.as_weak();
+    let settings = settings.clone();
+    std::thread::spawn(move || {
+        let mut queue = RescanQueue::default();
+        loop {
+            match events.recv_timeout(RESCAN_POLL_INTERVAL) {
+                Ok(event) => {
+                    let roots = roots_for_event(&inventory.snapshot().roots(), &event);
+                    queue.push(&event, &roots, Instant::now());
+                }
+                Err(std::sync::mpsc::RecvTimeoutError::Timeout) => {}
+                Err(std::sync::mpsc::RecvTimeoutError::Disconnected) => break,
+            }
+
+            let Some(request) = queue.next_ready(Instant::now(), |root| {
+                ActiveOperations::global()
+                    .find_conflict(&[PathBuf::from(root)])
+                    .is_some()
+            }) else {
+                continue;
+            };
+
+            let (log_path, notifications_enabled) = {
+                let s = settings.read().unwrap();
+                (s.log_path.clone(), s.notifications_enabled)
+            };
+            let expected = inventory.snapshot().expected();
+            let (status, notice) = match rescan_root(
+                &WinsecBackend,
+                &expected,
+                &request.root,
+                &log_path,
+                &logger,
+                &user_sid,
+                RESCAN_CHUNK_PAUSE,
+                None,
+            ) {
+                Ok(progress) => (
+                    format!("🔄 {}，{}：{}", request.priority, request.root, progress),
+                    notify::drift_notice(&request.root, &progress, notifications_enabled),
+                ),
+                Err(e) => (format!("❌ 重新验证 {} 失败：{}", request.root, e), None),
+            };
+            let ui_settings = settings.clone();
+            let alive = app_weak.upgrade_in_event_loop(move |app| {
+                a11y::set_status(&app, status.into());
+                refresh_logs_in_ui(&app, &ui_settings);
+                if let Some(notice) = notice {
+                    let _ = SystemNotifier::default().show(&notice);
+                }
+            });
+            if alive.is_err() {
+                break;
+            }
+        }
+    });
+}
+
+// === 启动信息显示 ===
+
+/// 启动阶段在后台准备、回到 UI 线程后渲染的数据
+enum StartupData {
+    /// 启动自检报告与保险库信息（无法读取时附诊断结果）
+    Status(Box<StartupReport>, Vec<String>),
+    /// 活动趋势（日志无法读取时为 None）
+    Dashboard(Option<Timeseries>),
+    /// 最近的日志记录
+    LogTail(Vec<LogRow>),
+}
+
+/// 窗口显示后分阶段加载启动自检、活动趋势与最近日志
+///
+/// 组件按 `StartupComponent` 的顺序在后台运行，定时器在 UI 线程中取回结果并逐个渲染；
+/// 加载期间状态栏显示进度，全部完成后显示启动自检摘要。
+/// 返回的定时器须在事件循环期间保持存活
+fn start_startup_phases(
+    app: &MainWindow,
+    settings: &Settings,
+    inventory: Arc<InventoryTracker>,
+    log_model: Arc<Mutex<LogListModel>>,
+) -> Rc<Timer> {
+    let (tx, rx) = std::sync::mpsc::channel();
+    let status_settings = settings.clone();
+    let log_path = settings.log_path.clone();
+    let tail_model = log_model.lock().unwrap().clone();
+    let tasks = vec![
+        StartupTask::new(StartupComponent::Capability, move || {
+            let (report, vault_lines) = gather_startup_info(&status_settings, &inventory);
+            StartupData::Status(Box::new(report), vault_lines)
+        }),
+        StartupTask::new(StartupComponent::Settings, move || {
+            StartupData::Dashboard(load_dashboard(&log_path))
+        }),
+        StartupTask::new(StartupComponent::LogTail, move || {
+            StartupData::LogTail(
+                tail_model
+                    .snapshot(STARTUP_LOG_TAIL)
+                    .iter()
+                    .cloned()
+                    .collect(),
+            )
+        }),
+    ];
+
+    let mut controller = AppController::new();
+    if let Err(e) = controller.start(tasks, tx) {
+        a11y::set_status(app, format!("❌ 无法在后台加载启动信息: {}", e).into());
+    }
+
+    let timer = Rc::new(Timer::default());
+    let weak_timer = Rc::downgrade(&timer);
+    let app_weak = app.as_weak();
+    let mut summary: Option<String> = None;
+    timer.start(TimerMode::Repeated, STARTUP_POLL_INTERVAL, move || {
+        let Some(app) = app_weak.upgrade() else {
+            return;
+        };
+        let mut changed = false;
+        while let Ok(event) = rx.try_recv() {
+            changed = true;
+            controller.handle(&event);
+            let StartupEvent::Loaded {
+                component, data, ..
+            } = event
+            else {
+                continue;
+            };
+            match data {
+                Ok(StartupData::Status(report, vault_lines)) => {
+                    summary = Some(report.status_line());
+                    render_startup_report(&app, &report, vault_lines);
+                }
+                Ok(StartupData::Dashboard(series)) => render_dashboard(&app, series.as_ref()),
+                Ok(StartupData::LogTail(rows)) => app.set_logs(VecModel::from_slice(&rows)),
+                Err(e) => summary = Some(format!("⚠️ 加载{}失败: {}", component, e)),
+            }
+        }
+        if !changed {
+            return;
+        }
+        match controller.status_text() {
+            Some(text) => a11y::set_status(&app, text.into()),
+            None => {
+                if let Some(summary) = summary.take() {
+                    a11y::set_status(&app, summary.into());
+                }
+            }
+        }
+        if controller.phase() == StartupPhase::Ready
+            && let Some(timer) = weak_timer.upgrade()
+        {
+            timer.stop();
+        }
+    });
+    timer
+}
+
+/// 收集启动自检报告与保险库信息（在后台线程中调用）
+///
+/// 报告摘要写入状态栏（会被后续操作覆盖），完整内容写入
+/// 系统状态面板（通过标题栏 🩺 按钮查看），两者保持一致。
+fn gather_startup_info(
+    settings: &Settings,
+    inventory: &InventoryTracker,
+) -> (StartupReport, Vec<String>) {
+    let mut report = gather_startup_report(settings);
+    let snapshot = inventory.snapshot();
+    let seed = std::time::SystemTime::now()
+        .duration_since(std::time::UNIX_EPOCH)
+        .map(|d| d.as_nanos() as u64)
+        .unwrap_or_default();
+    let check = check_inventory(&snapshot, &WinsecBackend, DEFAULT_INVENTORY_SAMPLE, seed);
+    report.attach_inventory(&snapshot, inventory.rebuilt(), &check);
+    report.attach_session(&inventory.session_reconciliation());
+    if let Some(replica_path) = &settings.replica_path {
+        let spool = get_default_data_path(REPLICA_SPOOL_FILE_NAME)
+            .map(SpoolStatus::read)
+            .unwrap_or_default();
+        let divergence = compare_logs(&settings.log_path, replica_path).ok();
+        report.attach_replica(&spool, divergence.as_ref());
+    }
+    report.attach_schedule(&settings.schedule);
+    let vault_path = Path::new(&settings.vault_path);
+    let vault_lines = match vault::vault_info(vault_path) {
+        Ok(Some(metadata)) => vec![vault::format_vault_info(&metadata)],
+        Ok(None) => vec!["保险库尚未创建".to_string()],
+        Err(e) => std::iter::once(format!("⚠️ 无法读取保险库信息: {}", e))
+            .chain(
+                vault::diagnose(vault_path)
+                    .map(|diagnostics| vault::format_diagnostics(&diagnostics))
+                    .unwrap_or_default(),
+            )
+            .collect(),
+    };
+    (report, vault_lines)
+}
+
+/// 将启动自检报告渲染到状态栏与系统状态面板
+fn render_startup_report(app: &MainWindow, report: &StartupReport, vault_lines: Vec<String>) {
+    a11y::set_status(app, report.status_line().into());
+    app.set_version_text(report.version.clone().into());
+
+    let lines: Vec<SharedString> = report
+        .render_lines()
+        .into_iter()
+        .chain(vault_lines)
+        .map(SharedString::from)
+        .collect();
+    app.set_system_status_lines(VecModel::from_slice(&lines));
+}
+
+// === 辅助函数 ===
+
 /// 刷新日志显示
 fn refresh_logs_in_ui(app: &MainWindow, settings: &Arc<RwLock<Settings>>) {
     let log_path = { settings.read().unwrap().log_path.clone() };
     if let Ok(log_model) = LogListModel::open(&log_path) {
         app.set_logs(log_model.to_model_rc(200));
     }
+    refresh_dashboard(app, &log_path);
+}
+
+/// 刷新活动趋势卡片
+fn refresh_dashboard(app: &MainWindow, log_path: &str) {
+    render_dashboard(app, load_dashboard(log_path).as_ref());
+}
+
+/// 统计活动趋势（近 30 天，按 UTC 日期分桶；日志无法读取时为 None）
+fn load_dashboard(log_path: &str) -> Option<Timeseries> {
+    let spec = TimeseriesSpec {
+        window: TimeWindow::Day,
+        span: DASHBOARD_DAYS,
+        end: None,
+        metrics: &[Metric::Success, Metric::Error, Metric::Unlocked],
+    };
+    generate_timeseries(log_path, &spec).ok()
+}
+
+/// 将活动趋势渲染到卡片
+fn render_dashboard(app: &MainWindow, series: Option<&Timeseries>) {
+    let Some(series) = series else {
+        app.set_activity_caption("暂无日志".into());
+        return;
+    };
+
+    let totals: Vec<u64> = series
+        .buckets
+        .iter()
+        .map(|b| b.success + b.error + b.downgraded + b.unlocked)
+        .collect();
+    app.set_activity_bars(VecModel::from_slice(&bridge::sparkline_heights(&totals)));
+    app.set_failure_bars(VecModel::from_slice(&series.failure_rate()));
+    app.set_activity_caption(bridge::activity_caption(series).into());
 }
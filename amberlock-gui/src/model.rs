@@ -3,25 +3,59 @@
 //! 提供文件列表和日志列表的数据模型，用于在Slint UI中显示和管理数据。
 //! 模型负责数据的存储、转换和查询，并提供快照功能供UI组件绑定。
 
+use crate::labels::{LabelReading, LabelRefresher, LabelRequest, LabelState};
 use crate::{FileItem, LogRow};
-use amberlock_storage::NdjsonReader;
-use once_cell::sync::Lazy;
+use amberlock_core::{LabelBackend, is_reserved_device_name};
+use amberlock_storage::{ParsedQuery, RecordStamper, SegmentedReader, expand_compressed_fields};
+use amberlock_types::OperationOrigin;
 use slint::{
     Model, ModelNotify, ModelRc, ModelTracker, SharedString, SharedVector, ToSharedString, VecModel,
 };
 use std::path::{Path, PathBuf};
 use std::sync::{Arc, Mutex};
 
+/// 每次提交给后台读取线程的最大行数
+const LABEL_BATCH_SIZE: usize = 64;
+
 /// 文件列表项的内部表示结构
 ///
-/// 包含文件路径和选中状态，使用元组形式存储以减少内存开销。
-type FileEntry = (PathBuf, bool);
+/// 除路径和选中状态外，缓存最近一次读取到的标签状态。
+#[derive(Debug, Clone)]
+struct FileEntry {
+    path: PathBuf,
+    selected: bool,
+    label: LabelState,
+    /// 标签读取时间（尚未读取时为空）
+    checked_at: String,
+    /// 行版本，标签失效时递增，用于丢弃过期的读取结果
+    generation: u64,
+    /// 已提交读取、尚未收到结果
+    queued: bool,
+}
 
-/// 全局选中的文件路径快照
-///
-/// 用于在模型销毁后仍能访问选中的文件路径列表。
-/// 使用 `Lazy` 确保全局单例，`Mutex` 提供线程安全访问。
-static SELECTED_SNAPSHOT: Lazy<Mutex<Vec<PathBuf>>> = Lazy::new(|| Mutex::new(Vec::new()));
+impl FileEntry {
+    fn new(path: PathBuf) -> Self {
+        Self {
+            path,
+            selected: true,
+            label: LabelState::Pending,
+            checked_at: String::new(),
+            generation: 0,
+            queued: false,
+        }
+    }
+
+    fn to_item(&self) -> FileItem {
+        FileItem {
+            path: path_to_display_string(&self.path),
+            kind: file_kind_string(&self.path),
+            selected: self.selected,
+            il_text: self.label.badge_text().into(),
+            label_state: self.label.as_i32(),
+            label_checked_at: self.checked_at.to_shared_string(),
+        }
+    }
+}
 
 /// 文件列表模型
 ///
@@ -30,10 +64,11 @@ static SELECTED_SNAPSHOT: Lazy<Mutex<Vec<PathBuf>>> = Lazy::new(|| Mutex::new(Ve
 #[derive(Clone)]
 pub struct FileListModel {
     /// 内部数据存储，使用互斥锁保护并发访问
-    /// 元组包含：(文件路径, 是否选中)
     inner: Arc<Mutex<Vec<FileEntry>>>,
     /// 模型变更通知器，用于在数据变化时通知UI更新
     notify: Arc<ModelNotify>,
+    /// 后台标签读取线程（未配置时不读取标签）
+    labels: Option<Arc<LabelRefresher>>,
 }
 
 impl Default for FileListModel {
@@ -54,9 +89,24 @@ impl FileListModel {
         Self {
             inner: Arc::new(Mutex::new(Vec::new())),
             notify: Arc::new(ModelNotify::default()),
+            labels: None,
         }
     }
 
+    /// 启用后台标签读取
+    ///
+    /// # 参数
+    /// - `backend`: 标签读取后端（运行时为 `WinsecBackend`）
+    /// - `stamper`: 读取时间来源
+    pub fn with_label_reader(
+        mut self,
+        backend: Arc<dyn LabelBackend>,
+        stamper: Arc<dyn RecordStamper>,
+    ) -> Self {
+        self.labels = Some(Arc::new(LabelRefresher::spawn(backend, stamper)));
+        self
+    }
+
     /// 获取当前模型的快照，转换为Slint UI可用的格式
     ///
     /// # 返回值
@@ -68,7 +118,7 @@ impl FileListModel {
     ///
     /// - 该方法会获取内部锁，阻塞直到锁可用
     /// - 路径类型根据 `Path::is_dir()` 判断
-    /// - `il_text` 字段当前为空，未来可集成Windows安全描述符信息
+    /// - `il_text` / `label_state` 为缓存的标签状态，尚未读取时为空 / `Pending`
     ///
     /// # 示例
     ///
@@ -84,14 +134,8 @@ impl FileListModel {
         let mut snapshot = SharedVector::with_capacity(entries.len());
 
         // 将内部数据转换为UI需要的格式
-        for (path, selected) in entries.iter() {
-            let item = FileItem {
-                path: path_to_display_string(path),
-                kind: file_kind_string(path),
-                selected: *selected,
-                il_text: SharedString::from(""), // 预留字段，可用于显示Windows完整性级别
-            };
-            snapshot.push(item);
+        for entry in entries.iter() {
+            snapshot.push(entry.to_item());
         }
 
         snapshot
@@ -104,10 +148,14 @@ impl FileListModel {
     /// - `paths`: 要添加的路径切片，路径会自动克隆存储
     /// - `selected`: 添加的路径是否默认为选中状态（默认为`true`）
     ///
+    /// # 返回值
+    ///
+    /// 被拒绝的路径（保留设备名，如 `NUL`、`com1.txt`）
+    ///
     /// # 注意
     ///
     /// - 新添加的路径默认选中状态为`true`
-    /// - 添加后会更新全局选中快照并通知UI更新
+    /// - 添加后会通知UI更新
     /// - 不检查路径是否存在或重复，由调用方确保
     ///
     /// # 示例
@@ -115,25 +163,28 @@ impl FileListModel {
     /// ```rust
     /// model.add_paths(&[PathBuf::from("/tmp/file.txt")]);
     /// ```
-    pub fn add_paths(&self, paths: &[PathBuf]) {
+    pub fn add_paths(&self, paths: &[PathBuf]) -> Vec<PathBuf> {
         let mut entries = self.inner.lock().expect("FileListModel lock poisoned");
 
         // 记录添加前的行数，用于通知UI
         let old_len = entries.len();
 
-        // 添加所有路径，默认选中状态为true
+        // 添加所有路径，默认选中状态为true；保留设备名不入列表
+        let mut rejected = Vec::new();
         for path in paths {
-            entries.push((path.clone(), true));
+            if is_reserved_device_name(path) {
+                rejected.push(path.clone());
+            } else {
+                entries.push(FileEntry::new(path.clone()));
+            }
         }
 
-        // 更新全局选中快照
-        Self::update_selected_snapshot(&entries);
-
         // 通知UI新增了行
         let new_len = entries.len();
         if new_len > old_len {
-            self.notify.row_added(old_len, new_len - 1);
+            self.notify.row_added(old_len, new_len - old_len);
         }
+        rejected
     }
 
     /// 设置指定索引的选中状态
@@ -149,15 +200,14 @@ impl FileListModel {
     ///
     /// # 注意
     ///
-    /// 修改选中状态后会更新全局选中快照并通知UI更新
+    /// 修改选中状态后会通知UI更新
     pub fn set_selected(&self, index: usize, selected: bool) -> bool {
         let mut entries = self.inner.lock().expect("FileListModel lock poisoned");
 
-        if let Some((_, sel)) = entries.get_mut(index) {
+        if let Some(entry) = entries.get_mut(index) {
             // 检查选中状态是否实际改变
-            if *sel != selected {
-                *sel = selected;
-                Self::update_selected_snapshot(&entries);
+            if entry.selected != selected {
+                entry.selected = selected;
                 // 通知UI该行数据已更改
                 self.notify.row_changed(index);
                 true
@@ -182,10 +232,9 @@ impl FileListModel {
     pub fn toggle_selected(&self, index: usize) -> Option<bool> {
         let mut entries = self.inner.lock().expect("FileListModel lock poisoned");
 
-        if let Some((_, sel)) = entries.get_mut(index) {
-            *sel = !*sel;
-            let new_state = *sel;
-            Self::update_selected_snapshot(&entries);
+        if let Some(entry) = entries.get_mut(index) {
+            entry.selected = !entry.selected;
+            let new_state = entry.selected;
             // 通知UI该行数据已更改
             self.notify.row_changed(index);
             Some(new_state)
@@ -206,35 +255,53 @@ impl FileListModel {
     ///
     /// # 注意
     ///
-    /// 移除后会更新全局选中快照并通知UI更新
+    /// 移除后会通知UI更新
     pub fn remove_at(&self, index: usize) -> Option<PathBuf> {
         let mut entries = self.inner.lock().expect("FileListModel lock poisoned");
 
         if index < entries.len() {
             let removed = entries.remove(index);
-            Self::update_selected_snapshot(&entries);
             // 通知UI该行已被移除
-            self.notify.row_removed(index, index);
-            Some(removed.0)
+            self.notify.row_removed(index, 1);
+            Some(removed.path)
         } else {
             None
         }
     }
 
+    /// 移除所有选中的项
+    ///
+    /// # 返回值
+    ///
+    /// 被移除的路径（按原顺序）
+    pub fn remove_selected(&self) -> Vec<PathBuf> {
+        let mut entries = self.inner.lock().expect("FileListModel lock poisoned");
+
+        let mut removed = Vec::new();
+        // 从后向前移除，保证通知中的索引有效
+        for index in (0..entries.len()).rev() {
+            if entries[index].selected {
+                removed.push(entries.remove(index).path);
+                self.notify.row_removed(index, 1);
+            }
+        }
+        removed.reverse();
+        removed
+    }
+
     /// 清空模型中的所有项
     ///
     /// # 注意
     ///
-    /// 清空后会同时清空全局选中快照并通知UI更新
+    /// 清空后会通知UI更新
     pub fn clear(&self) {
         let mut entries = self.inner.lock().expect("FileListModel lock poisoned");
 
         if !entries.is_empty() {
             let old_len = entries.len();
             entries.clear();
-            Self::update_selected_snapshot(&entries);
             // 通知UI所有行已被移除
-            self.notify.row_removed(0, old_len - 1);
+            self.notify.row_removed(0, old_len);
         }
     }
 
@@ -246,60 +313,130 @@ impl FileListModel {
     ///
     /// # 注意
     ///
-    /// 该方法会获取内部锁，建议缓存结果而非频繁调用
+    /// 该方法会获取内部锁，建议缓存结果而非频繁调用。
+    /// 需要在工作线程中使用选中项时，应在派发时调用本方法取得副本并移入闭包
     pub fn selected_paths(&self) -> Vec<PathBuf> {
         let entries = self.inner.lock().expect("FileListModel lock poisoned");
 
         entries
             .iter()
-            .filter(|(_, selected)| *selected)
-            .map(|(path, _)| path.clone())
+            .filter(|entry| entry.selected)
+            .map(|entry| entry.path.clone())
             .collect()
     }
 
-    /// 获取全局选中快照的路径列表
+    /// 获取指定行缓存的标签状态
+    pub fn label_state(&self, index: usize) -> Option<LabelState> {
+        let entries = self.inner.lock().expect("FileListModel lock poisoned");
+        entries.get(index).map(|entry| entry.label)
+    }
+
+    /// 为尚未读取标签的行提交一批后台读取
     ///
     /// # 返回值
     ///
-    /// 全局选中快照的克隆
+    /// 本次提交的行数（未启用标签读取或没有待读取的行时为 0）
     ///
     /// # 注意
     ///
-    /// 这是一个静态方法，可以在不持有模型实例的情况下获取选中路径
-    /// 快照在每次选中状态变更时更新
-    pub fn selected_paths_static() -> Vec<PathBuf> {
-        SELECTED_SNAPSHOT
-            .lock()
-            .expect("SELECTED_SNAPSHOT lock poisoned")
-            .clone()
+    /// 每次最多提交 `LABEL_BATCH_SIZE` 行，已提交未返回的行不会重复提交
+    pub fn request_label_refresh(&self) -> usize {
+        let Some(labels) = &self.labels else {
+            return 0;
+        };
+        let mut entries = self.inner.lock().expect("FileListModel lock poisoned");
+
+        let mut batch = Vec::new();
+        for entry in entries
+            .iter_mut()
+            .filter(|entry| entry.label == LabelState::Pending && !entry.queued)
+            .take(LABEL_BATCH_SIZE)
+        {
+            entry.queued = true;
+            batch.push(LabelRequest {
+                path: entry.path.clone(),
+                generation: entry.generation,
+            });
+        }
+
+        let count = batch.len();
+        if count > 0 && !labels.submit(batch) {
+            return 0;
+        }
+        count
     }
 
-    /// 更新全局选中快照
+    /// 取回后台读取结果并更新对应行（在 UI 线程中调用）
+    ///
+    /// # 返回值
+    ///
+    /// 状态被更新的行数
+    pub fn poll_label_results(&self) -> usize {
+        match &self.labels {
+            Some(labels) => self.apply_label_readings(labels.try_results()),
+            None => 0,
+        }
+    }
+
+    /// 按路径应用读取结果，版本不一致（读取后行已失效）的结果被丢弃
+    fn apply_label_readings(&self, readings: Vec<LabelReading>) -> usize {
+        let mut entries = self.inner.lock().expect("FileListModel lock poisoned");
+
+        let mut updated = 0;
+        for reading in readings {
+            for (index, entry) in entries.iter_mut().enumerate() {
+                if entry.path != reading.path || entry.generation != reading.generation {
+                    continue;
+                }
+                entry.queued = false;
+                entry.label = reading.state;
+                entry.checked_at = reading.checked_at.clone();
+                self.notify.row_changed(index);
+                updated += 1;
+            }
+        }
+        updated
+    }
+
+    /// 使受操作影响的行的标签缓存失效，等待重新读取
     ///
     /// # 参数
     ///
-    /// - `entries`: 当前模型的完整条目列表
+    /// - `paths`: 上锁/解锁涉及的路径；位于这些路径之下的行同样失效
     ///
-    /// # 注意
+    /// # 返回值
     ///
-    /// 内部方法，由其他修改选中状态的方法调用
-    fn update_selected_snapshot(entries: &[FileEntry]) {
-        let selected_paths: Vec<PathBuf> = entries
-            .iter()
-            .filter(|(_, selected)| *selected)
-            .map(|(path, _)| path.clone())
-            .collect();
+    /// 失效的行数
+    pub fn invalidate_labels(&self, paths: &[PathBuf]) -> usize {
+        let mut entries = self.inner.lock().expect("FileListModel lock poisoned");
 
-        *SELECTED_SNAPSHOT
-            .lock()
-            .expect("SELECTED_SNAPSHOT lock poisoned") = selected_paths;
+        let mut invalidated = 0;
+        for (index, entry) in entries.iter_mut().enumerate() {
+            if !paths.iter().any(|path| entry.path.starts_with(path)) {
+                continue;
+            }
+            entry.generation += 1;
+            entry.queued = false;
+            entry.label = LabelState::Pending;
+            entry.checked_at.clear();
+            self.notify.row_changed(index);
+            invalidated += 1;
+        }
+        invalidated
+    }
+
+    /// 按保护状态排序，未受保护的对象在前（同一状态内保持原顺序）
+    pub fn sort_by_label_state(&self) {
+        let mut entries = self.inner.lock().expect("FileListModel lock poisoned");
+        entries.sort_by_key(|entry| entry.label.sort_key());
+        self.notify.reset();
     }
 
     /// 转换为 Slint UI 可用的 ModelRc
+    ///
+    /// 返回的模型与本模型共享数据，后台标签结果经 `row_changed` 直接反映到界面
     pub fn to_model_rc(&self) -> ModelRc<FileItem> {
-        let snapshot = self.snapshot();
-        let vec: Vec<FileItem> = snapshot.iter().cloned().collect();
-        VecModel::from_slice(&vec).into()
+        ModelRc::new(self.clone())
     }
 }
 
@@ -363,12 +500,7 @@ impl Model for FileListModel {
     fn row_data(&self, row: usize) -> Option<Self::Data> {
         let entries = self.inner.lock().expect("FileListModel lock poisoned");
 
-        entries.get(row).map(|(path, selected)| FileItem {
-            path: path_to_display_string(path),
-            kind: file_kind_string(path),
-            selected: *selected,
-            il_text: SharedString::from(""),
-        })
+        entries.get(row).map(FileEntry::to_item)
     }
 
     /// 设置指定行的数据
@@ -384,11 +516,10 @@ impl Model for FileListModel {
     fn set_row_data(&self, row: usize, data: Self::Data) {
         let mut entries = self.inner.lock().expect("FileListModel lock poisoned");
 
-        if let Some((_, selected)) = entries.get_mut(row) {
+        if let Some(entry) = entries.get_mut(row) {
             // 只更新选中状态，保持路径不变
-            if *selected != data.selected {
-                *selected = data.selected;
-                Self::update_selected_snapshot(&entries);
+            if entry.selected != data.selected {
+                entry.selected = data.selected;
             }
         }
     }
@@ -407,9 +538,10 @@ impl Model for FileListModel {
 ///
 /// 用于读取和显示Amberlock的NDJSON格式日志文件。
 /// 支持分页读取、过滤和转换为UI格式。
+/// 日志按日期轮转时，读取与过滤跨越全部分段（见`SegmentedReader`）。
 #[derive(Clone, Debug)]
 pub struct LogListModel {
-    /// 日志文件路径
+    /// 日志文件路径（按日期轮转时为未轮转的文件名，如`amberlock.ndjson`）
     path: String,
 }
 
@@ -447,7 +579,7 @@ impl LogListModel {
     /// # 注意
     ///
     /// - 如果文件打开或读取失败，返回空向量
-    /// - 使用`NdjsonReader::read_last_n`获取最新记录
+    /// - 使用`SegmentedReader::read_last_n`获取最新记录，可跨越多个日期分段
     /// - 字段缺失时使用空字符串替代
     pub fn snapshot(&self, limit: usize) -> SharedVector<LogRow> {
         self.read_and_map_logs(|reader| reader.read_last_n(limit), limit)
@@ -466,17 +598,32 @@ impl LogListModel {
     ///
     /// # 注意
     ///
-    /// - 查询逻辑由`NdjsonReader::filter`实现
+    /// - 查询逻辑由`SegmentedReader::filter`实现，从最早的分段向后扫描
     /// - 如果过滤失败，返回空向量
     pub fn filter_snapshot(&self, query: &str, limit: usize) -> SharedVector<LogRow> {
         self.read_and_map_logs(|reader| reader.filter(query, limit), limit)
     }
 
+    /// 获取匹配查询语句的日志快照
+    ///
+    /// # 参数
+    ///
+    /// - `query`: 由`parse_query`解析得到的查询语句
+    /// - `limit`: 最大返回记录数
+    ///
+    /// # 注意
+    ///
+    /// - 匹配逻辑由`SegmentedReader::filter_query`实现，从最早的分段向后扫描
+    /// - 如果读取失败，返回空向量
+    pub fn query_snapshot(&self, query: &ParsedQuery, limit: usize) -> SharedVector<LogRow> {
+        self.read_and_map_logs(|reader| reader.filter_query(query, limit), limit)
+    }
+
     /// 内部方法：读取日志并映射到UI格式
     ///
     /// # 参数
     ///
-    /// - `read_operation`: 读取操作闭包，接受`&mut NdjsonReader`，返回`anyhow::Result<Vec<serde_json::Value>>`
+    /// - `read_operation`: 读取操作闭包，接受`&SegmentedReader`，返回`anyhow::Result<Vec<serde_json::Value>>`
     /// - `capacity_hint`: 容量提示，用于预分配向量空间
     ///
     /// # 返回值
@@ -484,16 +631,16 @@ impl LogListModel {
     /// 映射后的日志记录向量
     fn read_and_map_logs<F>(&self, read_operation: F, capacity_hint: usize) -> SharedVector<LogRow>
     where
-        F: FnOnce(&mut NdjsonReader) -> anyhow::Result<Vec<serde_json::Value>>,
+        F: FnOnce(&SegmentedReader) -> anyhow::Result<Vec<serde_json::Value>>,
     {
-        // 尝试打开日志文件
-        let mut reader = match NdjsonReader::open(&self.path) {
+        // 列出日志的全部分段
+        let reader = match SegmentedReader::for_log_path(&self.path) {
             Ok(reader) => reader,
             Err(_) => return SharedVector::default(),
         };
 
         // 执行读取操作
-        let log_values = match read_operation(&mut reader) {
+        let log_values = match read_operation(&reader) {
             Ok(values) => values,
             Err(_) => return SharedVector::default(),
         };
@@ -518,8 +665,10 @@ impl LogListModel {
     ///
     /// # 返回值
     ///
-    /// 转换后的`LogRow`，缺失字段用空字符串填充
+    /// 转换后的`LogRow`，缺失字段用空字符串填充，压缩的 SDDL 字段已还原
     fn map_json_to_logrow(&self, value: &serde_json::Value) -> LogRow {
+        let mut value = value.clone();
+        expand_compressed_fields(&mut value);
         LogRow {
             time: value
                 .get("time_utc")
@@ -546,6 +695,29 @@ impl LogListModel {
                 .and_then(|v| v.as_str())
                 .unwrap_or("")
                 .into(),
+            selected: false,
+            comment: value
+                .get("comment")
+                .and_then(|v| v.as_str())
+                .unwrap_or("")
+                .into(),
+            raw: value.to_string().into(),
+            sddl: value
+                .get("sddl_after")
+                .and_then(|v| v.as_str())
+                .or_else(|| value.get("sddl_before").and_then(|v| v.as_str()))
+                .unwrap_or("")
+                .into(),
+            batch_id: value
+                .get("batch_id")
+                .and_then(|v| v.as_str())
+                .unwrap_or("")
+                .into(),
+            origin: value
+                .get("origin")
+                .and_then(|v| v.as_str())
+                .unwrap_or(OperationOrigin::Unknown.as_str())
+                .into(),
         }
     }
 
@@ -562,4 +734,300 @@ impl LogListModel {
         let vec: Vec<LogRow> = snapshot.iter().cloned().collect();
         VecModel::from_slice(&vec).into()
     }
+
+    /// 按查询语句过滤后转换为 ModelRc
+    pub fn to_query_model_rc(&self, query: &ParsedQuery, limit: usize) -> ModelRc<LogRow> {
+        let snapshot = self.query_snapshot(query, limit);
+        let vec: Vec<LogRow> = snapshot.iter().cloned().collect();
+        VecModel::from_slice(&vec).into()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use amberlock_core::{FakeBackend, LabelOp};
+    use amberlock_storage::FixedStamper;
+    use amberlock_types::LabelLevel;
+    use std::time::{Duration, Instant};
+
+    fn model_with_backend(backend: &Arc<FakeBackend>) -> FileListModel {
+        FileListModel::new().with_label_reader(
+            backend.clone(),
+            Arc::new(FixedStamper::new("2025-01-01T00:00:00Z", "id")),
+        )
+    }
+
+    /// 反复提交并取回标签读取，直到所有行都已读取
+    fn wait_for_labels(model: &FileListModel) {
+        let deadline = Instant::now() + Duration::from_secs(5);
+        while (0..model.row_count()).any(|i| model.label_state(i) == Some(LabelState::Pending)) {
+            assert!(Instant::now() < deadline, "等待标签读取超时");
+            model.request_label_refresh();
+            model.poll_label_results();
+            std::thread::sleep(Duration::from_millis(5));
+        }
+    }
+
+    fn get_calls(backend: &FakeBackend) -> usize {
+        backend
+            .calls()
+            .iter()
+            .filter(|(op, _)| *op == LabelOp::Get)
+            .count()
+    }
+
+    #[test]
+    fn test_selection_does_not_leak_between_models() {
+        let first = FileListModel::new();
+        let second = FileListModel::new();
+
+        first.add_paths(&[PathBuf::from(r"C:\a.txt"), PathBuf::from(r"C:\b.txt")]);
+        second.add_paths(&[PathBuf::from(r"D:\c.txt")]);
+        first.set_selected(1, false);
+
+        assert_eq!(first.selected_paths(), vec![PathBuf::from(r"C:\a.txt")]);
+        assert_eq!(second.selected_paths(), vec![PathBuf::from(r"D:\c.txt")]);
+
+        // 清空另一个实例不影响本实例的选中项
+        second.clear();
+        assert!(second.selected_paths().is_empty());
+        assert_eq!(first.selected_paths(), vec![PathBuf::from(r"C:\a.txt")]);
+        println!("✅ 两个模型的选中状态相互独立");
+    }
+
+    #[test]
+    fn test_clones_share_selection() {
+        let model = FileListModel::new();
+        let handle = model.clone();
+
+        model.add_paths(&[PathBuf::from(r"C:\a.txt")]);
+        handle.toggle_selected(0);
+
+        assert!(model.selected_paths().is_empty());
+    }
+
+    #[test]
+    fn test_remove_selected_keeps_unselected() {
+        let model = FileListModel::new();
+        model.add_paths(&[
+            PathBuf::from(r"C:\a.txt"),
+            PathBuf::from(r"C:\b.txt"),
+            PathBuf::from(r"C:\c.txt"),
+        ]);
+        model.set_selected(1, false);
+
+        let removed = model.remove_selected();
+        assert_eq!(
+            removed,
+            vec![PathBuf::from(r"C:\a.txt"), PathBuf::from(r"C:\c.txt")]
+        );
+        assert_eq!(model.row_count(), 1);
+        assert!(model.selected_paths().is_empty());
+    }
+
+    #[test]
+    fn test_reserved_device_names_are_rejected() {
+        let model = FileListModel::new();
+        let rejected = model.add_paths(&[
+            PathBuf::from(r"C:\data\report. "),
+            PathBuf::from(r"C:\data\NUL"),
+            PathBuf::from(r"C:\data\com1.txt"),
+        ]);
+
+        assert_eq!(
+            rejected,
+            vec![PathBuf::from(r"C:\data\NUL"), PathBuf::from(r"C:\data\com1.txt")]
+        );
+        assert_eq!(model.selected_paths(), vec![PathBuf::from(r"C:\data\report. ")]);
+        println!("✅ 保留设备名未进入文件列表");
+    }
+
+    #[test]
+    fn test_label_states_are_cached() {
+        let backend = Arc::new(FakeBackend::new().with_label(r"C:\a.txt", LabelLevel::High));
+        let model = model_with_backend(&backend);
+        model.add_paths(&[PathBuf::from(r"C:\a.txt"), PathBuf::from(r"C:\b.txt")]);
+
+        wait_for_labels(&model);
+        assert_eq!(model.label_state(0), Some(LabelState::High));
+        assert_eq!(model.label_state(1), Some(LabelState::Unlocked));
+        let row = model.row_data(0).unwrap();
+        assert_eq!(row.label_state, LabelState::High.as_i32());
+        assert_eq!(row.label_checked_at, "2025-01-01T00:00:00Z");
+
+        // 已读取的行不再重复读取
+        assert_eq!(model.request_label_refresh(), 0);
+        assert_eq!(get_calls(&backend), 2);
+        println!("✅ 标签状态读取后被缓存");
+    }
+
+    #[test]
+    fn test_operation_invalidates_label_state() {
+        let backend = Arc::new(FakeBackend::new());
+        let model = model_with_backend(&backend);
+        let dir = PathBuf::from(r"C:\data");
+        let file = dir.join("a.txt");
+        model.add_paths(&[dir.clone(), file.clone(), PathBuf::from(r"C:\other.txt")]);
+        wait_for_labels(&model);
+        assert_eq!(model.label_state(1), Some(LabelState::Unlocked));
+
+        // 上锁目录后，目录及其下的行失效并重新读取
+        backend.insert_label(&dir.to_string_lossy(), LabelLevel::High);
+        backend.insert_label(&file.to_string_lossy(), LabelLevel::High);
+        assert_eq!(model.invalidate_labels(&[dir]), 2);
+        assert_eq!(model.label_state(0), Some(LabelState::Pending));
+        assert_eq!(model.row_data(0).unwrap().label_checked_at, "");
+
+        wait_for_labels(&model);
+        assert_eq!(model.label_state(0), Some(LabelState::High));
+        assert_eq!(model.label_state(1), Some(LabelState::High));
+        assert_eq!(model.label_state(2), Some(LabelState::Unlocked));
+        assert_eq!(get_calls(&backend), 5);
+    }
+
+    #[test]
+    fn test_stale_readings_are_discarded() {
+        let model = FileListModel::new();
+        model.add_paths(&[PathBuf::from(r"C:\a.txt")]);
+        model.invalidate_labels(&[PathBuf::from(r"C:\a.txt")]);
+
+        // 失效前发出的读取（版本 0）不得覆盖失效后的状态
+        let stale = LabelReading {
+            path: PathBuf::from(r"C:\a.txt"),
+            generation: 0,
+            state: LabelState::Unlocked,
+            checked_at: "2025-01-01T00:00:00Z".to_string(),
+        };
+        assert_eq!(model.apply_label_readings(vec![stale]), 0);
+        assert_eq!(model.label_state(0), Some(LabelState::Pending));
+    }
+
+    #[test]
+    fn test_sort_by_label_state() {
+        let backend = Arc::new(
+            FakeBackend::new()
+                .with_label(r"C:\system.txt", LabelLevel::System)
+                .with_label(r"C:\high.txt", LabelLevel::High),
+        );
+        backend.fail_next(LabelOp::Get, r"C:\denied.txt", 5);
+        let model = model_with_backend(&backend);
+        model.add_paths(&[
+            PathBuf::from(r"C:\system.txt"),
+            PathBuf::from(r"C:\high.txt"),
+            PathBuf::from(r"C:\plain.txt"),
+            PathBuf::from(r"C:\denied.txt"),
+        ]);
+        wait_for_labels(&model);
+
+        model.sort_by_label_state();
+        let order: Vec<String> = (0..model.row_count())
+            .map(|i| model.row_data(i).unwrap().path.to_string())
+            .collect();
+        assert_eq!(
+            order,
+            vec![
+                r"C:\plain.txt",
+                r"C:\denied.txt",
+                r"C:\high.txt",
+                r"C:\system.txt"
+            ]
+        );
+        println!("✅ 未受保护的对象排在前面");
+    }
+
+    #[test]
+    fn test_unreadable_path_is_unknown() {
+        let backend = Arc::new(FakeBackend::new());
+        backend.fail_next(LabelOp::Get, r"C:\denied.txt", 5);
+        let model = model_with_backend(&backend);
+        model.add_paths(&[PathBuf::from(r"C:\denied.txt"), PathBuf::from(r"C:\ok.txt")]);
+
+        // 读取失败不会中断刷新，其余行照常读取
+        wait_for_labels(&model);
+        assert_eq!(model.label_state(0), Some(LabelState::Unknown));
+        assert_eq!(model.row_data(0).unwrap().il_text, "?");
+        assert_eq!(model.label_state(1), Some(LabelState::Unlocked));
+    }
+
+    #[test]
+    fn test_log_rows_expand_compressed_sddl() {
+        use amberlock_types::{MaybeCompressed, SDDL_COMPRESS_THRESHOLD};
+
+        let dir = tempfile::TempDir::new().expect("创建临时目录失败");
+        let log_path = dir.path().join("log.ndjson");
+        let fat = format!(
+            "O:BAG:SYD:PAI{}",
+            "(A;OICI;FA;;;S-1-5-21-1000-1001)".repeat(50)
+        );
+        let compressed = MaybeCompressed::compress(fat.clone(), SDDL_COMPRESS_THRESHOLD);
+        assert!(compressed.is_compressed());
+        let lines = [
+            serde_json::json!({"path": r"C:\a.txt", "sddl_after": compressed}),
+            serde_json::json!({"path": r"C:\b.txt", "sddl_before": "S:(ML;;NW;;;HI)"}),
+        ];
+        let text: String = lines.iter().map(|v| format!("{}\n", v)).collect();
+        std::fs::write(&log_path, text).unwrap();
+
+        let rows = LogListModel::open(log_path.to_str().unwrap())
+            .unwrap()
+            .snapshot(10);
+        assert_eq!(rows[0].sddl, fat.as_str());
+        assert!(
+            !rows[0].raw.contains("\"alg\""),
+            "复制的记录应为还原后的文本"
+        );
+        assert_eq!(rows[1].sddl, "S:(ML;;NW;;;HI)");
+        println!("✅ 日志列表还原压缩的 SDDL");
+    }
+
+    #[test]
+    fn test_query_snapshot_uses_parsed_query() {
+        let dir = tempfile::TempDir::new().expect("创建临时目录失败");
+        let log_path = dir.path().join("log.ndjson");
+        let lines = [
+            serde_json::json!({"path": r"D:\Finance\a.xlsx", "status": "error"}),
+            serde_json::json!({"path": r"D:\Finance\b.xlsx", "status": "success"}),
+            serde_json::json!({"path": r"D:\HR\c.docx", "status": "error"}),
+        ];
+        let text: String = lines.iter().map(|v| format!("{}\n", v)).collect();
+        std::fs::write(&log_path, text).unwrap();
+
+        let model = LogListModel::open(log_path.to_str().unwrap()).unwrap();
+        let query = amberlock_storage::parse_query("status:error path:Finance").unwrap();
+        let rows = model.query_snapshot(&query, 10);
+        assert_eq!(rows.len(), 1);
+        assert_eq!(rows[0].path, r"D:\Finance\a.xlsx");
+
+        // 普通关键字仍走旧的过滤路径
+        assert_eq!(model.filter_snapshot("finance", 10).len(), 2);
+        println!("✅ 日志列表按查询语句过滤");
+    }
+
+    #[test]
+    fn test_log_model_reads_across_daily_segments() {
+        let dir = tempfile::TempDir::new().expect("创建临时目录失败");
+        let log_path = dir.path().join("log.ndjson");
+        for (name, path, status) in [
+            ("log.ndjson", r"D:\a.txt", "error"),
+            ("log-2025-06-01.ndjson", r"D:\b.txt", "success"),
+            ("log-2025-06-02.ndjson", r"D:\c.txt", "error"),
+        ] {
+            let line = serde_json::json!({"path": path, "status": status});
+            std::fs::write(dir.path().join(name), format!("{}\n", line)).unwrap();
+        }
+
+        let model = LogListModel::open(log_path.to_str().unwrap()).unwrap();
+        let paths = |rows: SharedVector<LogRow>| -> Vec<String> {
+            rows.iter().map(|row| row.path.to_string()).collect()
+        };
+        assert_eq!(paths(model.snapshot(2)), [r"D:\b.txt", r"D:\c.txt"]);
+        assert_eq!(
+            paths(model.filter_snapshot("error", 10)),
+            [r"D:\a.txt", r"D:\c.txt"]
+        );
+        let query = amberlock_storage::parse_query("status:success").unwrap();
+        assert_eq!(paths(model.query_snapshot(&query, 10)), [r"D:\b.txt"]);
+        println!("✅ 日志列表跨日期分段读取与过滤");
+    }
 }
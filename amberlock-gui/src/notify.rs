@@ -0,0 +1,337 @@
+//! 操作完成通知
+//!
+//! 长时间运行的操作结束时，如果主窗口不在前台，则弹出系统托盘气泡通知；
+//! 托盘不可用时退化为闪烁任务栏按钮。
+//!
+//! 是否通知、通知内容由纯函数 `completion_notice` 决定，
+//! 实际的系统调用通过 `Notifier` trait 进行，便于测试时替换。
+
+use amberlock_core::{BatchResult, OperationKind, VerifyProgress};
+use amberlock_types::AmberlockError;
+
+/// 通知标题
+const NOTICE_TITLE: &str = "AmberLock";
+
+/// 待显示的通知
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Notice {
+    /// 标题
+    pub title: String,
+    /// 正文
+    pub body: String,
+}
+
+/// 通知显示接口
+pub trait Notifier {
+    /// 显示通知
+    fn show(&mut self, notice: &Notice) -> anyhow::Result<()>;
+}
+
+/// 决定操作结束时是否通知以及通知内容
+///
+/// # 参数
+/// - `kind`: 操作类型
+/// - `outcome`: 操作结果
+/// - `cancelled`: 操作是否被取消
+/// - `window_in_foreground`: 主窗口当前是否在前台
+/// - `enabled`: 设置中是否启用通知
+///
+/// # 返回
+/// 需要通知时返回通知内容；窗口在前台或通知被禁用时返回 None
+pub fn completion_notice(
+    kind: OperationKind,
+    outcome: Result<&BatchResult, &AmberlockError>,
+    cancelled: bool,
+    window_in_foreground: bool,
+    enabled: bool,
+) -> Option<Notice> {
+    if !enabled || window_in_foreground {
+        return None;
+    }
+
+    let body = match outcome {
+        Ok(result) => format!(
+            "{}{}：成功 {} / 失败 {}",
+            kind,
+            if cancelled { "已取消" } else { "完成" },
+            group_thousands(result.success_count),
+            group_thousands(result.failed_count)
+        ),
+        Err(e) => format!("{}失败：{}", kind, e),
+    };
+
+    Some(Notice {
+        title: NOTICE_TITLE.to_string(),
+        body,
+    })
+}
+
+/// 卷到达或系统恢复后的重新验证发现漂移时的通知
+///
+/// # 参数
+/// - `root`: 重新验证的清单根
+/// - `progress`: 重新验证结果
+/// - `enabled`: 设置中是否启用通知
+///
+/// # 返回
+/// 发现漂移时返回通知内容（无论窗口是否在前台）；未发现漂移或通知被禁用时返回 None
+pub fn drift_notice(root: &str, progress: &VerifyProgress, enabled: bool) -> Option<Notice> {
+    if !enabled || progress.drifted_paths.is_empty() {
+        return None;
+    }
+    Some(Notice {
+        title: NOTICE_TITLE.to_string(),
+        body: format!(
+            "{} 重新连接后发现 {} 个对象的保护级别已改变，详见操作日志",
+            root,
+            group_thousands(progress.drifted_paths.len())
+        ),
+    })
+}
+
+/// 千位分隔（83921 → "83,921"）
+fn group_thousands(n: usize) -> String {
+    let digits = n.to_string();
+    let mut out = String::with_capacity(digits.len() + digits.len() / 3);
+    for (i, c) in digits.chars().enumerate() {
+        if i > 0 && (digits.len() - i).is_multiple_of(3) {
+            out.push(',');
+        }
+        out.push(c);
+    }
+    out
+}
+
+/// 当前进程的窗口是否位于前台
+pub fn app_in_foreground() -> bool {
+    use windows::Win32::System::Threading::GetCurrentProcessId;
+    use windows::Win32::UI::WindowsAndMessaging::{GetForegroundWindow, GetWindowThreadProcessId};
+
+    unsafe {
+        let hwnd = GetForegroundWindow();
+        if hwnd.is_invalid() {
+            return false;
+        }
+        let mut pid = 0u32;
+        GetWindowThreadProcessId(hwnd, Some(&mut pid));
+        pid == GetCurrentProcessId()
+    }
+}
+
+/// 系统通知（托盘气泡，失败时闪烁任务栏）
+///
+/// # 注意
+/// 必须在 UI 线程上创建和使用，用于定位主窗口句柄
+#[derive(Default)]
+pub struct SystemNotifier {
+    /// 已添加托盘图标的窗口句柄，析构时移除图标
+    icon_owner: Option<windows::Win32::Foundation::HWND>,
+}
+
+impl Notifier for SystemNotifier {
+    fn show(&mut self, notice: &Notice) -> anyhow::Result<()> {
+        let hwnd = main_window_handle().ok_or_else(|| anyhow::anyhow!("未找到主窗口"))?;
+
+        if self.show_balloon(hwnd, notice) {
+            return Ok(());
+        }
+        flash_taskbar(hwnd);
+        Ok(())
+    }
+}
+
+impl SystemNotifier {
+    /// 通过托盘图标显示气泡通知
+    fn show_balloon(&mut self, hwnd: windows::Win32::Foundation::HWND, notice: &Notice) -> bool {
+        use windows::Win32::UI::Shell::{
+            NIF_ICON, NIF_INFO, NIF_TIP, NIIF_INFO, NIM_ADD, NIM_MODIFY, NOTIFYICONDATAW,
+            Shell_NotifyIconW,
+        };
+        use windows::Win32::UI::WindowsAndMessaging::{IDI_APPLICATION, LoadIconW};
+
+        let mut data = NOTIFYICONDATAW {
+            cbSize: std::mem::size_of::<NOTIFYICONDATAW>() as u32,
+            hWnd: hwnd,
+            uID: TRAY_ICON_ID,
+            uFlags: NIF_ICON | NIF_TIP | NIF_INFO,
+            dwInfoFlags: NIIF_INFO,
+            ..Default::default()
+        };
+        copy_wide(&mut data.szTip, NOTICE_TITLE);
+        copy_wide(&mut data.szInfoTitle, &notice.title);
+        copy_wide(&mut data.szInfo, &notice.body);
+
+        unsafe {
+            if let Ok(icon) = LoadIconW(None, IDI_APPLICATION) {
+                data.hIcon = icon;
+            }
+            let message = if self.icon_owner.is_some() {
+                NIM_MODIFY
+            } else {
+                NIM_ADD
+            };
+            let shown = Shell_NotifyIconW(message, &data).as_bool();
+            if shown {
+                self.icon_owner = Some(hwnd);
+            }
+            shown
+        }
+    }
+}
+
+impl Drop for SystemNotifier {
+    fn drop(&mut self) {
+        use windows::Win32::UI::Shell::{NIM_DELETE, NOTIFYICONDATAW, Shell_NotifyIconW};
+
+        if let Some(hwnd) = self.icon_owner.take() {
+            let data = NOTIFYICONDATAW {
+                cbSize: std::mem::size_of::<NOTIFYICONDATAW>() as u32,
+                hWnd: hwnd,
+                uID: TRAY_ICON_ID,
+                ..Default::default()
+            };
+            unsafe {
+                let _ = Shell_NotifyIconW(NIM_DELETE, &data);
+            }
+        }
+    }
+}
+
+/// 托盘图标 ID（每个窗口内唯一即可）
+const TRAY_ICON_ID: u32 = 1;
+
+/// 闪烁任务栏按钮直到窗口回到前台
+fn flash_taskbar(hwnd: windows::Win32::Foundation::HWND) {
+    use windows::Win32::UI::WindowsAndMessaging::{
+        FLASHW_ALL, FLASHW_TIMERNOFG, FLASHWINFO, FlashWindowEx,
+    };
+
+    let info = FLASHWINFO {
+        cbSize: std::mem::size_of::<FLASHWINFO>() as u32,
+        hwnd,
+        dwFlags: FLASHW_ALL | FLASHW_TIMERNOFG,
+        uCount: 0,
+        dwTimeout: 0,
+    };
+    unsafe {
+        let _ = FlashWindowEx(&info);
+    }
+}
+
+/// 查找当前 UI 线程上第一个可见的顶层窗口
+fn main_window_handle() -> Option<windows::Win32::Foundation::HWND> {
+    use windows::Win32::Foundation::{HWND, LPARAM};
+    use windows::Win32::System::Threading::GetCurrentThreadId;
+    use windows::Win32::UI::WindowsAndMessaging::{EnumThreadWindows, IsWindowVisible};
+    use windows::core::BOOL;
+
+    unsafe extern "system" fn find_visible(hwnd: HWND, lparam: LPARAM) -> BOOL {
+        unsafe {
+            if IsWindowVisible(hwnd).as_bool() {
+                *(lparam.0 as *mut Option<HWND>) = Some(hwnd);
+                return BOOL(0);
+            }
+        }
+        BOOL(1)
+    }
+
+    let mut found: Option<HWND> = None;
+    unsafe {
+        let _ = EnumThreadWindows(
+            GetCurrentThreadId(),
+            Some(find_visible),
+            LPARAM(&mut found as *mut Option<HWND> as isize),
+        );
+    }
+    found
+}
+
+/// 将字符串复制到定长 UTF-16 缓冲区（超长时截断并保留结尾 0）
+fn copy_wide(buffer: &mut [u16], text: &str) {
+    let max = buffer.len().saturating_sub(1);
+    for (slot, unit) in buffer.iter_mut().zip(text.encode_utf16().take(max)) {
+        *slot = unit;
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn result(success: usize, failed: usize) -> BatchResult {
+        BatchResult {
+            success_count: success,
+            failed_count: failed,
+            total_count: success + failed,
+            ..Default::default()
+        }
+    }
+
+    /// 记录通知而不真正显示
+    #[derive(Default)]
+    struct RecordingNotifier {
+        shown: Vec<Notice>,
+    }
+
+    impl Notifier for RecordingNotifier {
+        fn show(&mut self, notice: &Notice) -> anyhow::Result<()> {
+            self.shown.push(notice.clone());
+            Ok(())
+        }
+    }
+
+    #[test]
+    fn test_notice_only_when_in_background_and_enabled() {
+        let r = result(83921, 12);
+
+        assert!(completion_notice(OperationKind::Lock, Ok(&r), false, true, true).is_none());
+        assert!(completion_notice(OperationKind::Lock, Ok(&r), false, false, false).is_none());
+
+        let notice = completion_notice(OperationKind::Lock, Ok(&r), false, false, true)
+            .expect("后台完成时应通知");
+        assert_eq!(notice.body, "上锁完成：成功 83,921 / 失败 12");
+
+        let mut notifier = RecordingNotifier::default();
+        notifier.show(&notice).unwrap();
+        assert_eq!(notifier.shown, vec![notice]);
+    }
+
+    #[test]
+    fn test_notice_text_for_cancel_and_error() {
+        let r = result(5, 0);
+        let cancelled =
+            completion_notice(OperationKind::Unlock, Ok(&r), true, false, true).expect("应通知");
+        assert_eq!(cancelled.body, "解锁已取消：成功 5 / 失败 0");
+
+        let err = AmberlockError::WorkerPanicked;
+        let failed = completion_notice(OperationKind::Unlock, Err(&err), false, false, true)
+            .expect("应通知");
+        assert!(failed.body.starts_with("解锁失败："));
+    }
+
+    #[test]
+    fn test_group_thousands_and_copy_wide() {
+        assert_eq!(group_thousands(0), "0");
+        assert_eq!(group_thousands(999), "999");
+        assert_eq!(group_thousands(1000), "1,000");
+        assert_eq!(group_thousands(1234567), "1,234,567");
+
+        let mut buffer = [0u16; 4];
+        copy_wide(&mut buffer, "abcdef");
+        assert_eq!(buffer, [b'a' as u16, b'b' as u16, b'c' as u16, 0]);
+    }
+
+    #[test]
+    fn test_drift_notice_only_when_drift_found() {
+        let mut progress = VerifyProgress::default();
+        assert!(drift_notice(r"E:\", &progress, true).is_none());
+
+        progress.drifted_paths = vec![r"E:\a.txt".to_string(), r"E:\b.txt".to_string()];
+        assert!(drift_notice(r"E:\", &progress, false).is_none());
+        let notice = drift_notice(r"E:\", &progress, true).expect("应有通知");
+        assert_eq!(
+            notice.body,
+            "E:\\ 重新连接后发现 2 个对象的保护级别已改变，详见操作日志"
+        );
+    }
+}
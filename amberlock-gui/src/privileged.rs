@@ -2,8 +2,8 @@
 //!
 //! 封装需要 SYSTEM 权限的高级操作
 
-use amberlock_core::{process_lock, process_unlock, LockOptions, LockResult};
-use amberlock_storage::NdjsonWriter;
+use amberlock_core::{process_lock_outcome, process_unlock_outcome, LockOptions, LockResult};
+use amberlock_storage::OperationLog;
 use amberlock_types::{LabelLevel, Result};
 use amberlock_winsec::{
     impersonate::with_system_privileges,
@@ -18,11 +18,11 @@ use std::path::Path;
 pub fn force_unlock(
     path: &Path,
     user_sid: &str,
-    logger: &NdjsonWriter,
+    logger: &OperationLog,
 ) -> Result<LockResult> {
     // 在 SYSTEM 权限下执行解锁
     with_system_privileges(|| {
-        process_unlock(path, user_sid, logger)
+        process_unlock_outcome(path, user_sid, logger, None).map(|outcome| outcome.result)
     })
 }
 
@@ -35,10 +35,11 @@ pub fn force_lock(
     opts: &LockOptions,
     effective_level: LabelLevel,
     user_sid: &str,
-    logger: &NdjsonWriter,
+    logger: &OperationLog,
 ) -> Result<LockResult> {
     Ok(with_system_privileges(|| {
-        process_lock(path, opts, effective_level, user_sid, logger)
+        process_lock_outcome(path, opts, effective_level, user_sid, logger)
+            .map(|outcome| outcome.result)
     })?)
 }
 
@@ -0,0 +1,285 @@
+//! 键盘快捷键
+//!
+//! UI 只把按键事件（文本、修饰键、当前获得焦点的列表）原样转发过来，
+//! 按键到命令的映射与启用条件都在这里，以普通函数形式便于测试。
+//! 新增快捷键只需在 `SHORTCUTS` 中加一行。
+
+/// Slint 中 Delete 键的事件文本
+pub const KEY_DELETE: &str = "\u{7f}";
+/// Slint 中 F5 键的事件文本
+pub const KEY_F5: &str = "\u{f708}";
+
+/// 快捷键可触发的命令
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Command {
+    /// 添加文件（Ctrl+O）
+    AddFiles,
+    /// 添加文件夹（Ctrl+Shift+O）
+    AddFolders,
+    /// 从列表移除所选对象（文件列表中 Delete）
+    RemoveSelectedFiles,
+    /// 复制所选路径（文件列表中 Ctrl+C）
+    CopyPaths,
+    /// 复制所选日志为 TSV（日志列表中 Ctrl+C）
+    CopyLogsTsv,
+    /// 复制所选日志为 JSON（日志列表中 Ctrl+Shift+C）
+    CopyLogsJson,
+    /// 应用上锁（Ctrl+L）
+    Lock,
+    /// 聚焦解锁表单输入框（Ctrl+U）
+    FocusUnlock,
+    /// 刷新日志（F5）
+    RefreshLogs,
+    /// 聚焦日志筛选框（Ctrl+F）
+    FocusLogFilter,
+}
+
+/// 按键发生时获得焦点的区域（与 UI 中的 `ShortcutScope` 对应）
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Scope {
+    /// 任意位置
+    Global,
+    /// 文件列表
+    Files,
+    /// 日志列表
+    Logs,
+}
+
+/// 快捷键表中的一行
+#[derive(Debug, Clone, Copy)]
+pub struct Shortcut {
+    /// 按键文本（字母按小写书写）
+    pub key: &'static str,
+    /// 是否需要 Ctrl
+    pub control: bool,
+    /// 是否需要 Shift
+    pub shift: bool,
+    /// 生效区域；`Global` 表示任意位置
+    pub scope: Scope,
+    /// 触发的命令
+    pub command: Command,
+}
+
+const fn bind(
+    key: &'static str,
+    control: bool,
+    shift: bool,
+    scope: Scope,
+    command: Command,
+) -> Shortcut {
+    Shortcut {
+        key,
+        control,
+        shift,
+        scope,
+        command,
+    }
+}
+
+/// 快捷键表
+///
+/// 列表区域的条目优先于全局条目匹配，因此 Delete / Ctrl+C 作用于当前获得焦点的列表
+#[rustfmt::skip]
+pub const SHORTCUTS: &[Shortcut] = &[
+    bind("o", true, false, Scope::Global, Command::AddFiles),
+    bind("o", true, true, Scope::Global, Command::AddFolders),
+    bind("l", true, false, Scope::Global, Command::Lock),
+    bind("u", true, false, Scope::Global, Command::FocusUnlock),
+    bind("f", true, false, Scope::Global, Command::FocusLogFilter),
+    bind(KEY_F5, false, false, Scope::Global, Command::RefreshLogs),
+    bind(KEY_DELETE, false, false, Scope::Files, Command::RemoveSelectedFiles),
+    bind("c", true, false, Scope::Files, Command::CopyPaths),
+    bind("c", true, false, Scope::Logs, Command::CopyLogsTsv),
+    bind("c", true, true, Scope::Logs, Command::CopyLogsJson),
+];
+
+/// 查找按键对应的命令
+///
+/// # 参数
+/// - `key`: 按键事件文本（字母大小写均可）
+/// - `control` / `shift`: 修饰键状态，须与表项完全一致
+/// - `scope`: 当前获得焦点的区域
+///
+/// # 返回
+/// 未绑定时返回 None，UI 应继续传递该按键
+pub fn command_for(key: &str, control: bool, shift: bool, scope: Scope) -> Option<Command> {
+    let matches =
+        |s: &&Shortcut| s.key.eq_ignore_ascii_case(key) && s.control == control && s.shift == shift;
+    SHORTCUTS
+        .iter()
+        .filter(matches)
+        .find(|s| s.scope == scope)
+        .or_else(|| {
+            SHORTCUTS
+                .iter()
+                .filter(matches)
+                .find(|s| s.scope == Scope::Global)
+        })
+        .map(|s| s.command)
+}
+
+/// 判断快捷键是否可用时参考的界面状态
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq)]
+pub struct ShortcutState {
+    /// 有批量操作正在进行
+    pub busy: bool,
+    /// 有模态面板（如快速保护向导）打开
+    pub modal_open: bool,
+    /// 只读审计模式
+    pub read_only: bool,
+}
+
+/// 命令在当前状态下是否可用
+///
+/// # 规则
+/// - 模态面板打开时全部禁用
+/// - 操作进行中禁用会修改对象列表或发起新操作的命令
+/// - 只读审计模式禁用上锁与解锁表单
+pub fn is_enabled(command: Command, state: ShortcutState) -> bool {
+    if state.modal_open {
+        return false;
+    }
+    match command {
+        Command::AddFiles | Command::AddFolders | Command::RemoveSelectedFiles => !state.busy,
+        Command::Lock | Command::FocusUnlock => !state.busy && !state.read_only,
+        Command::CopyPaths
+        | Command::CopyLogsTsv
+        | Command::CopyLogsJson
+        | Command::RefreshLogs
+        | Command::FocusLogFilter => true,
+    }
+}
+
+/// 查找命令并检查是否可用
+///
+/// # 返回
+/// 可执行的命令；未绑定或当前不可用时返回 None
+pub fn dispatch(
+    key: &str,
+    control: bool,
+    shift: bool,
+    scope: Scope,
+    state: ShortcutState,
+) -> Option<Command> {
+    command_for(key, control, shift, scope).filter(|&command| is_enabled(command, state))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_command_for_global_shortcuts() {
+        let cases = [
+            ("o", true, false, Command::AddFiles),
+            ("O", true, true, Command::AddFolders),
+            ("l", true, false, Command::Lock),
+            ("u", true, false, Command::FocusUnlock),
+            ("f", true, false, Command::FocusLogFilter),
+            (KEY_F5, false, false, Command::RefreshLogs),
+        ];
+        for (key, control, shift, expected) in cases {
+            for scope in [Scope::Global, Scope::Files, Scope::Logs] {
+                assert_eq!(
+                    command_for(key, control, shift, scope),
+                    Some(expected),
+                    "{:?} 在 {:?} 中应映射为 {:?}",
+                    key,
+                    scope,
+                    expected
+                );
+            }
+        }
+        println!("✅ 全局快捷键映射正确");
+    }
+
+    #[test]
+    fn test_command_for_depends_on_focused_list() {
+        assert_eq!(
+            command_for(KEY_DELETE, false, false, Scope::Files),
+            Some(Command::RemoveSelectedFiles)
+        );
+        // 审计日志只追加，日志列表中 Delete 不做任何事
+        assert_eq!(command_for(KEY_DELETE, false, false, Scope::Logs), None);
+        assert_eq!(command_for(KEY_DELETE, false, false, Scope::Global), None);
+
+        assert_eq!(
+            command_for("c", true, false, Scope::Files),
+            Some(Command::CopyPaths)
+        );
+        assert_eq!(
+            command_for("c", true, false, Scope::Logs),
+            Some(Command::CopyLogsTsv)
+        );
+        assert_eq!(
+            command_for("C", true, true, Scope::Logs),
+            Some(Command::CopyLogsJson)
+        );
+        assert_eq!(command_for("c", true, false, Scope::Global), None);
+    }
+
+    #[test]
+    fn test_command_for_requires_exact_modifiers() {
+        assert_eq!(command_for("o", false, false, Scope::Global), None);
+        assert_eq!(command_for("l", true, true, Scope::Global), None);
+        assert_eq!(command_for(KEY_F5, true, false, Scope::Global), None);
+        assert_eq!(command_for("x", true, false, Scope::Global), None);
+    }
+
+    #[test]
+    fn test_shortcut_table_has_no_duplicates() {
+        for (i, a) in SHORTCUTS.iter().enumerate() {
+            for b in &SHORTCUTS[i + 1..] {
+                assert!(
+                    !(a.key == b.key
+                        && a.control == b.control
+                        && a.shift == b.shift
+                        && a.scope == b.scope),
+                    "快捷键重复: {:?} 与 {:?}",
+                    a.command,
+                    b.command
+                );
+            }
+        }
+    }
+
+    #[test]
+    fn test_is_enabled() {
+        let idle = ShortcutState::default();
+        let busy = ShortcutState { busy: true, ..idle };
+        let modal = ShortcutState {
+            modal_open: true,
+            ..idle
+        };
+        let read_only = ShortcutState {
+            read_only: true,
+            ..idle
+        };
+
+        for shortcut in SHORTCUTS {
+            assert!(is_enabled(shortcut.command, idle));
+            assert!(
+                !is_enabled(shortcut.command, modal),
+                "模态面板打开时应全部禁用"
+            );
+        }
+
+        assert!(!is_enabled(Command::Lock, busy));
+        assert!(!is_enabled(Command::AddFiles, busy));
+        assert!(!is_enabled(Command::RemoveSelectedFiles, busy));
+        assert!(is_enabled(Command::RefreshLogs, busy));
+        assert!(is_enabled(Command::CopyLogsTsv, busy));
+
+        assert!(!is_enabled(Command::Lock, read_only));
+        assert!(!is_enabled(Command::FocusUnlock, read_only));
+        assert!(is_enabled(Command::AddFiles, read_only));
+        assert!(is_enabled(Command::FocusLogFilter, read_only));
+
+        assert_eq!(dispatch("l", true, false, Scope::Global, busy), None);
+        assert_eq!(
+            dispatch("l", true, false, Scope::Global, idle),
+            Some(Command::Lock)
+        );
+        println!("✅ 快捷键启用条件正确");
+    }
+}
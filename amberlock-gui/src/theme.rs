@@ -0,0 +1,346 @@
+//! 界面主题
+//!
+//! 主题设置（`Settings::theme`）决定使用浅色还是深色调色板：
+//! - 跟随系统：读取 `HKCU\Software\Microsoft\Windows\CurrentVersion\Themes\Personalize`
+//!   下的 `AppsUseLightTheme`，并在后台监听其变化，系统切换时界面随之切换
+//! - 浅色 / 深色：固定使用对应调色板，忽略系统设置
+//!
+//! 读不到系统设置（值不存在、旧版本 Windows）时按浅色处理，与 Windows 默认一致。
+//! 切换只替换 `Palette` 全局的颜色，无需重建窗口。
+//!
+//! 系统关闭了“显示动画”（`SPI_GETCLIENTAREAANIMATION`）时设置 `Palette.reduce-motion`，
+//! 界面中的过渡与呼吸动画随之停用；读不到时保留动画。
+
+use crate::{MainWindow, Palette, PaletteColors, ThemeChoice};
+use amberlock_types::Theme;
+use slint::{Color, ComponentHandle};
+
+/// 系统深浅色设置的来源
+pub trait SystemThemeSource: Send + Sync {
+    /// 应用是否使用浅色主题（`None` 表示读不到系统设置）
+    fn apps_use_light_theme(&self) -> Option<bool>;
+
+    /// 系统是否开启界面动画（`None` 表示读不到系统设置）
+    fn client_area_animation(&self) -> Option<bool> {
+        None
+    }
+
+    /// 开始监听系统设置的变化
+    ///
+    /// # 注意
+    /// 监听在后台线程中进行，每次变化后以新值调用 `on_change`，
+    /// 其返回 `false`（窗口已关闭）时停止
+    fn watch(&self, on_change: Box<dyn Fn(Option<bool>) -> bool + Send>) -> anyhow::Result<()>;
+}
+
+/// 按主题设置与系统设置决定是否使用深色调色板
+pub fn resolve_dark(theme: Theme, apps_use_light: Option<bool>) -> bool {
+    match theme {
+        Theme::Light => false,
+        Theme::Dark => true,
+        Theme::System => apps_use_light == Some(false),
+    }
+}
+
+/// 按系统设置决定是否减少动画（仅在系统明确关闭动画时减少）
+pub fn resolve_reduce_motion(client_area_animation: Option<bool>) -> bool {
+    client_area_animation == Some(false)
+}
+
+/// 设置中的主题 → 界面中的主题选项
+pub fn theme_choice(theme: Theme) -> ThemeChoice {
+    match theme {
+        Theme::System => ThemeChoice::System,
+        Theme::Light => ThemeChoice::Light,
+        Theme::Dark => ThemeChoice::Dark,
+    }
+}
+
+/// 界面中的主题选项 → 设置中的主题
+pub fn theme_from_choice(choice: ThemeChoice) -> Theme {
+    match choice {
+        ThemeChoice::System => Theme::System,
+        ThemeChoice::Light => Theme::Light,
+        ThemeChoice::Dark => Theme::Dark,
+    }
+}
+
+/// 由 `0xRRGGBBAA` 构造颜色（与 `.slint` 中的 `#rrggbbaa` 写法一致）
+fn rgba(value: u32) -> Color {
+    let [r, g, b, a] = value.to_be_bytes();
+    Color::from_argb_u8(a, r, g, b)
+}
+
+/// 浅色或深色调色板
+///
+/// 强调色与状态色在两种主题下相同，深色值与 `main.slint` 中的默认值一致
+pub fn palette(dark: bool) -> PaletteColors {
+    let shared = PaletteColors {
+        text_on_accent: rgba(0xffffffff),
+        accent_primary: rgba(0x00a8ffff),
+        accent_hover: rgba(0x0096e6ff),
+        accent_secondary: rgba(0x7c4dffff),
+        accent_tint: rgba(0x00a8ff30),
+        secondary_tint: rgba(0x7c4dff30),
+        success: rgba(0x00c853ff),
+        success_tint: rgba(0x00c85330),
+        warning: rgba(0xffa726ff),
+        warning_tint: rgba(0xffa72630),
+        error: rgba(0xff5252ff),
+        error_tint: rgba(0xff525230),
+        ..Default::default()
+    };
+    if dark {
+        PaletteColors {
+            bg_primary: rgba(0x1a1a1aff),
+            bg_secondary: rgba(0x2a2a2aff),
+            bg_tertiary: rgba(0x353535ff),
+            bg_hover: rgba(0x404040ff),
+            text_primary: rgba(0xe8e8e8ff),
+            text_secondary: rgba(0xa8a8a8ff),
+            text_tertiary: rgba(0x707070ff),
+            border_color: rgba(0x404040ff),
+            divider_color: rgba(0x333333ff),
+            glass_bg: rgba(0x2a2a2a80),
+            glass_border: rgba(0xffffff20),
+            shadow_color: rgba(0x00000060),
+            ripple: rgba(0xffffff20),
+            ..shared
+        }
+    } else {
+        PaletteColors {
+            bg_primary: rgba(0xffffffff),
+            bg_secondary: rgba(0xf5f5f5ff),
+            bg_tertiary: rgba(0xe8e8e8ff),
+            bg_hover: rgba(0xe0e0e0ff),
+            text_primary: rgba(0x1a1a1aff),
+            text_secondary: rgba(0x666666ff),
+            text_tertiary: rgba(0x999999ff),
+            border_color: rgba(0xd0d0d0ff),
+            divider_color: rgba(0xe0e0e0ff),
+            glass_bg: rgba(0xffffff90),
+            glass_border: rgba(0x00000020),
+            shadow_color: rgba(0x00000020),
+            ripple: rgba(0x00000015),
+            ..shared
+        }
+    }
+}
+
+/// 按主题设置切换窗口的调色板与主题选项
+///
+/// # 返回值
+/// 是否使用了深色调色板
+pub fn apply_theme(app: &MainWindow, theme: Theme, apps_use_light: Option<bool>) -> bool {
+    let dark = resolve_dark(theme, apps_use_light);
+    let global = app.global::<Palette>();
+    global.set_colors(palette(dark));
+    global.set_is_dark(dark);
+    app.set_theme_choice(theme_choice(theme));
+    dark
+}
+
+/// 按系统动画设置开启或停用界面动画
+///
+/// # 返回值
+/// 是否减少了动画
+pub fn apply_motion(app: &MainWindow, client_area_animation: Option<bool>) -> bool {
+    let reduce = resolve_reduce_motion(client_area_animation);
+    app.global::<Palette>().set_reduce_motion(reduce);
+    reduce
+}
+
+/// 注册表实现的系统深浅色设置
+#[derive(Debug, Default, Clone, Copy)]
+pub struct RegistryThemeSource;
+
+impl SystemThemeSource for RegistryThemeSource {
+    fn apps_use_light_theme(&self) -> Option<bool> {
+        win32::read_apps_use_light_theme()
+    }
+
+    fn client_area_animation(&self) -> Option<bool> {
+        win32::read_client_area_animation()
+    }
+
+    fn watch(&self, on_change: Box<dyn Fn(Option<bool>) -> bool + Send>) -> anyhow::Result<()> {
+        let (ready_tx, ready_rx) = std::sync::mpsc::channel();
+        std::thread::Builder::new()
+            .name("amberlock-theme-watch".to_string())
+            .spawn(move || win32::watch_personalize_key(on_change, ready_tx))?;
+        ready_rx
+            .recv()
+            .map_err(|_| anyhow::anyhow!("主题监听线程意外退出"))?
+    }
+}
+
+mod win32 {
+    use std::sync::mpsc::Sender;
+    use windows::Win32::Foundation::ERROR_SUCCESS;
+    use windows::Win32::System::Registry::{
+        HKEY, HKEY_CURRENT_USER, KEY_NOTIFY, KEY_READ, REG_NOTIFY_CHANGE_LAST_SET, RegCloseKey,
+        RegNotifyChangeKeyValue, RegOpenKeyExW, RegQueryValueExW,
+    };
+    use windows::Win32::UI::WindowsAndMessaging::{
+        SPI_GETCLIENTAREAANIMATION, SYSTEM_PARAMETERS_INFO_UPDATE_FLAGS, SystemParametersInfoW,
+    };
+    use windows::core::{BOOL, w};
+
+    /// 打开个性化设置键
+    fn open_personalize_key() -> anyhow::Result<HKEY> {
+        let mut key = HKEY::default();
+        let status = unsafe {
+            RegOpenKeyExW(
+                HKEY_CURRENT_USER,
+                w!(r"Software\Microsoft\Windows\CurrentVersion\Themes\Personalize"),
+                None,
+                KEY_READ | KEY_NOTIFY,
+                &mut key,
+            )
+        };
+        if status != ERROR_SUCCESS {
+            return Err(windows::core::Error::from(status.to_hresult()).into());
+        }
+        Ok(key)
+    }
+
+    /// 读取 `AppsUseLightTheme`（DWORD，非零为浅色）
+    fn read_value(key: HKEY) -> Option<bool> {
+        let mut data = 0u32;
+        let mut size = std::mem::size_of::<u32>() as u32;
+        let status = unsafe {
+            RegQueryValueExW(
+                key,
+                w!("AppsUseLightTheme"),
+                None,
+                None,
+                Some(&mut data as *mut u32 as *mut u8),
+                Some(&mut size),
+            )
+        };
+        (status == ERROR_SUCCESS && size == std::mem::size_of::<u32>() as u32).then_some(data != 0)
+    }
+
+    pub(super) fn read_apps_use_light_theme() -> Option<bool> {
+        let key = open_personalize_key().ok()?;
+        let value = read_value(key);
+        unsafe {
+            let _ = RegCloseKey(key);
+        }
+        value
+    }
+
+    /// 读取“显示动画”设置
+    pub(super) fn read_client_area_animation() -> Option<bool> {
+        let mut enabled = BOOL::default();
+        unsafe {
+            SystemParametersInfoW(
+                SPI_GETCLIENTAREAANIMATION,
+                0,
+                Some(&mut enabled as *mut BOOL as *mut std::ffi::c_void),
+                SYSTEM_PARAMETERS_INFO_UPDATE_FLAGS(0),
+            )
+        }
+        .ok()?;
+        Some(enabled.as_bool())
+    }
+
+    /// 阻塞等待键值变化，每次变化后回调新值，回调返回 `false` 或监听失败时退出
+    pub(super) fn watch_personalize_key(
+        on_change: Box<dyn Fn(Option<bool>) -> bool + Send>,
+        ready: Sender<anyhow::Result<()>>,
+    ) {
+        let key = match open_personalize_key() {
+            Ok(key) => key,
+            Err(e) => {
+                let _ = ready.send(Err(e));
+                return;
+            }
+        };
+        let _ = ready.send(Ok(()));
+
+        loop {
+            let status = unsafe {
+                RegNotifyChangeKeyValue(key, false, REG_NOTIFY_CHANGE_LAST_SET, None, false)
+            };
+            if status != ERROR_SUCCESS || !on_change(read_value(key)) {
+                break;
+            }
+        }
+        unsafe {
+            let _ = RegCloseKey(key);
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::sync::Mutex;
+
+    /// 返回固定值的系统设置
+    struct FixedSource(Option<bool>);
+
+    impl SystemThemeSource for FixedSource {
+        fn apps_use_light_theme(&self) -> Option<bool> {
+            self.0
+        }
+
+        fn watch(&self, on_change: Box<dyn Fn(Option<bool>) -> bool + Send>) -> anyhow::Result<()> {
+            on_change(self.0);
+            Ok(())
+        }
+    }
+
+    #[test]
+    fn test_setting_resolves_to_palette() {
+        for system in [Some(true), Some(false), None] {
+            assert!(!resolve_dark(Theme::Light, system));
+            assert!(resolve_dark(Theme::Dark, system));
+        }
+        assert!(!resolve_dark(Theme::System, Some(true)));
+        assert!(resolve_dark(Theme::System, Some(false)));
+
+        let dark = palette(true);
+        let light = palette(false);
+        assert_eq!(dark.bg_primary, rgba(0x1a1a1aff));
+        assert_eq!(light.bg_primary, rgba(0xffffffff));
+        assert_ne!(dark.text_primary, light.text_primary);
+        assert_eq!(dark.accent_primary, light.accent_primary);
+        assert_eq!(dark.warning, light.warning);
+
+        for theme in [Theme::System, Theme::Light, Theme::Dark] {
+            assert_eq!(theme_from_choice(theme_choice(theme)), theme);
+        }
+        println!("✅ 主题设置正确映射到调色板");
+    }
+
+    #[test]
+    fn test_system_falls_back_to_light_when_unreadable() {
+        let source = FixedSource(None);
+        assert!(!resolve_dark(Theme::System, source.apps_use_light_theme()));
+
+        let seen = std::sync::Arc::new(Mutex::new(Vec::new()));
+        let sink = seen.clone();
+        source
+            .watch(Box::new(move |value| {
+                sink.lock()
+                    .unwrap()
+                    .push(resolve_dark(Theme::System, value));
+                true
+            }))
+            .expect("监听失败");
+        assert_eq!(*seen.lock().unwrap(), vec![false]);
+        println!("✅ 读不到系统设置时使用浅色");
+    }
+
+    #[test]
+    fn test_reduce_motion_only_when_animation_disabled() {
+        assert!(resolve_reduce_motion(Some(false)));
+        assert!(!resolve_reduce_motion(Some(true)));
+        assert!(!resolve_reduce_motion(
+            FixedSource(None).client_area_animation()
+        ));
+        println!("✅ 仅在系统关闭动画时减少动画");
+    }
+}
@@ -0,0 +1,105 @@
+//! 第三方组件清单
+//!
+//! 企业部署需要证明随 AmberLock 发布了哪些第三方组件及其许可证。清单在构建时
+//! 内嵌（`third_party.in` 由 `scripts/gen-third-party.sh` 根据 Cargo.lock 生成），
+//! 运行时不访问网络；单元测试在清单与 Cargo.lock 不一致时失败，避免清单过期。
+
+/// 单个第三方组件
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct Component {
+    /// crate 名称
+    pub name: &'static str,
+    /// 版本
+    pub version: &'static str,
+    /// 许可证（SPDX 表达式）
+    pub license: &'static str,
+}
+
+impl Component {
+    /// 清单中的一行（`名称 版本 — 许可证`）
+    pub fn line(&self) -> String {
+        format!("{} {} — {}", self.name, self.version, self.license)
+    }
+}
+
+/// 依赖树中的全部第三方组件（按名称排序，不含工作区自身的 crate）
+pub const THIRD_PARTY: &[Component] = &include!("third_party.in");
+
+/// 按关键字筛选组件清单
+///
+/// # 参数
+/// - `query`: 关键字（匹配名称或许可证，不区分大小写；空白时返回全部）
+///
+/// # 返回
+/// 匹配组件的清单行
+pub fn search_components(query: &str) -> Vec<String> {
+    let query = query.trim().to_lowercase();
+    THIRD_PARTY
+        .iter()
+        .filter(|c| {
+            query.is_empty()
+                || c.name.to_lowercase().contains(&query)
+                || c.license.to_lowercase().contains(&query)
+        })
+        .map(Component::line)
+        .collect()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    /// 从 Cargo.lock 解析第三方包（有 `source` 的包）的名称与版本
+    fn locked_packages() -> Vec<(String, String)> {
+        let lock = include_str!("../../Cargo.lock");
+        let mut packages = Vec::new();
+        for block in lock.split("[[package]]").skip(1) {
+            let field = |key: &str| {
+                block.lines().find_map(|line| {
+                    let value = line.strip_prefix(key)?.strip_prefix(" = ")?;
+                    Some(value.trim_matches('"').to_string())
+                })
+            };
+            if let (Some(name), Some(version), Some(_)) =
+                (field("name"), field("version"), field("source"))
+            {
+                packages.push((name, version));
+            }
+        }
+        packages.sort();
+        packages
+    }
+
+    #[test]
+    fn test_third_party_matches_cargo_lock() {
+        let mut embedded: Vec<(String, String)> = THIRD_PARTY
+            .iter()
+            .map(|c| (c.name.to_string(), c.version.to_string()))
+            .collect();
+        embedded.sort();
+
+        let locked = locked_packages();
+        assert!(!locked.is_empty(), "Cargo.lock 解析失败");
+        assert_eq!(
+            embedded, locked,
+            "组件清单与 Cargo.lock 不一致，请运行 scripts/gen-third-party.sh 重新生成"
+        );
+        println!("✅ 组件清单与 Cargo.lock 一致（{} 个组件）", embedded.len());
+    }
+
+    #[test]
+    fn test_search_components() {
+        assert_eq!(search_components("  ").len(), THIRD_PARTY.len());
+
+        let serde = search_components("SERDE");
+        assert!(serde.iter().any(|line| line.starts_with("serde ")));
+        assert!(
+            serde
+                .iter()
+                .all(|line| line.to_lowercase().contains("serde"))
+        );
+
+        assert!(!search_components("apache-2.0").is_empty());
+        assert!(search_components("no-such-component").is_empty());
+    }
+}
@@ -0,0 +1,415 @@
+//! 更新检查
+//!
+//! 启用 `check_updates` 后，启动时（每 24 小时最多一次）从 `update_manifest_url`
+//! 获取发布清单，并从 `{清单地址}.sig` 获取其分离式 Ed25519 签名（十六进制）。
+//! 签名使用内置公钥校验，通过后才比较版本号；有新版本时仅在状态栏提示，
+//! 不下载、不替换程序。
+//!
+//! 网络失败静默处理（仅在 `dev` 特性下输出调试信息）；
+//! 清单未签名或签名无效则明确提示并拒绝。
+//!
+//! HTTP 请求通过 `ManifestFetcher` trait 进行，便于测试时替换。
+
+use ed25519_dalek::{Signature, VerifyingKey};
+use semver::Version;
+use serde::Deserialize;
+use std::fmt;
+use std::time::Duration;
+
+/// 两次检查之间的最短间隔（秒）
+pub const UPDATE_CHECK_INTERVAL_SECS: u64 = 24 * 60 * 60;
+
+/// 发布清单签名公钥
+pub const UPDATE_PUBLIC_KEY: [u8; 32] = [
+    0x72, 0xdf, 0x7d, 0x22, 0x5d, 0xb9, 0xf4, 0x63, 0x60, 0xfd, 0xe3, 0x2e, 0xd6, 0x8b, 0xf9, 0x14,
+    0x0c, 0x6f, 0xfe, 0x6a, 0x29, 0x58, 0x32, 0x4a, 0x77, 0x7b, 0x24, 0x48, 0xa8, 0x08, 0xd7, 0xc3,
+];
+
+/// 发布清单
+#[derive(Debug, Clone, PartialEq, Eq, Deserialize)]
+pub struct UpdateManifest {
+    /// 最新版本号（语义化版本，可带 `v` 前缀）
+    pub version: String,
+    /// 发布说明地址
+    pub notes_url: String,
+}
+
+/// 有新版本时的提示
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct UpdateNotice {
+    /// 新版本号
+    pub version: Version,
+    /// 发布说明地址
+    pub notes_url: String,
+}
+
+impl UpdateNotice {
+    /// 状态栏提示文本
+    pub fn status_line(&self) -> String {
+        format!(
+            "🆕 AmberLock {} 已发布，详见 {}",
+            self.version, self.notes_url
+        )
+    }
+}
+
+/// 更新检查错误
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum UpdateCheckError {
+    /// 网络错误（静默处理）
+    Network(String),
+    /// 清单缺少签名
+    Unsigned,
+    /// 签名格式错误或校验失败
+    BadSignature,
+    /// 清单内容或版本号无效
+    InvalidManifest(String),
+}
+
+impl fmt::Display for UpdateCheckError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            Self::Network(e) => write!(f, "网络错误: {}", e),
+            Self::Unsigned => write!(f, "更新清单缺少签名"),
+            Self::BadSignature => write!(f, "更新清单签名无效"),
+            Self::InvalidManifest(e) => write!(f, "更新清单无效: {}", e),
+        }
+    }
+}
+
+impl std::error::Error for UpdateCheckError {}
+
+impl UpdateCheckError {
+    /// 是否需要向用户提示（网络错误不提示）
+    pub fn is_loud(&self) -> bool {
+        !matches!(self, Self::Network(_))
+    }
+}
+
+/// 清单获取接口
+pub trait ManifestFetcher {
+    /// 获取指定地址的完整内容
+    fn fetch(&self, url: &str) -> anyhow::Result<Vec<u8>>;
+}
+
+/// 基于 HTTPS 的清单获取
+pub struct HttpFetcher {
+    agent: ureq::Agent,
+}
+
+impl HttpFetcher {
+    /// 单次请求超时
+    const TIMEOUT: Duration = Duration::from_secs(10);
+    /// 清单与签名的最大字节数
+    const MAX_BYTES: u64 = 64 * 1024;
+
+    pub fn new() -> Self {
+        let mut builder = ureq::AgentBuilder::new().timeout(Self::TIMEOUT);
+        if let Ok(tls) = native_tls::TlsConnector::new() {
+            builder = builder.tls_connector(std::sync::Arc::new(tls));
+        }
+        Self {
+            agent: builder.build(),
+        }
+    }
+}
+
+impl Default for HttpFetcher {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+impl ManifestFetcher for HttpFetcher {
+    fn fetch(&self, url: &str) -> anyhow::Result<Vec<u8>> {
+        use std::io::Read;
+
+        let response = self.agent.get(url).call()?;
+        let mut bytes = Vec::new();
+        response
+            .into_reader()
+            .take(Self::MAX_BYTES)
+            .read_to_end(&mut bytes)?;
+        Ok(bytes)
+    }
+}
+
+/// 是否到了再次检查的时间
+///
+/// # 参数
+/// - `last_check`: 上次检查时间（UNIX 秒），从未检查时为 None
+/// - `now`: 当前时间（UNIX 秒）
+///
+/// # 注意
+/// 系统时钟回拨（`now` 早于上次检查）时视为需要检查
+pub fn should_check(last_check: Option<u64>, now: u64) -> bool {
+    match last_check {
+        None => true,
+        Some(last) if now < last => true,
+        Some(last) => now - last >= UPDATE_CHECK_INTERVAL_SECS,
+    }
+}
+
+/// 解析版本号（允许 `v` 前缀）
+pub fn parse_version(text: &str) -> Result<Version, UpdateCheckError> {
+    let text = text.trim();
+    let text = text.strip_prefix('v').unwrap_or(text);
+    Version::parse(text).map_err(|e| UpdateCheckError::InvalidManifest(e.to_string()))
+}
+
+/// 分离式签名地址
+pub fn signature_url(manifest_url: &str) -> String {
+    format!("{}.sig", manifest_url)
+}
+
+/// 校验清单签名并解析
+///
+/// # 参数
+/// - `manifest`: 清单原始字节（签名覆盖的内容）
+/// - `signature`: 十六进制签名文本（允许首尾空白）
+/// - `key`: 校验公钥
+///
+/// # 返回
+/// - `Err(Unsigned)`: 签名为空
+/// - `Err(BadSignature)`: 签名格式错误或与清单不匹配
+/// - `Err(InvalidManifest)`: 签名有效但清单无法解析
+pub fn verify_manifest(
+    manifest: &[u8],
+    signature: &str,
+    key: &VerifyingKey,
+) -> Result<UpdateManifest, UpdateCheckError> {
+    let signature = signature.trim();
+    if signature.is_empty() {
+        return Err(UpdateCheckError::Unsigned);
+    }
+
+    let bytes: [u8; 64] = decode_hex(signature)
+        .and_then(|b| b.try_into().ok())
+        .ok_or(UpdateCheckError::BadSignature)?;
+    key.verify_strict(manifest, &Signature::from_bytes(&bytes))
+        .map_err(|_| UpdateCheckError::BadSignature)?;
+
+    serde_json::from_slice(manifest).map_err(|e| UpdateCheckError::InvalidManifest(e.to_string()))
+}
+
+/// 检查是否有新版本
+///
+/// # 参数
+/// - `fetcher`: 清单获取接口
+/// - `manifest_url`: 清单地址
+/// - `key`: 签名校验公钥
+/// - `current`: 当前版本号
+///
+/// # 返回
+/// - `Ok(Some(notice))`: 清单版本高于当前版本
+/// - `Ok(None)`: 已是最新版本
+pub fn check_for_update(
+    fetcher: &dyn ManifestFetcher,
+    manifest_url: &str,
+    key: &VerifyingKey,
+    current: &str,
+) -> Result<Option<UpdateNotice>, UpdateCheckError> {
+    let manifest = fetcher
+        .fetch(manifest_url)
+        .map_err(|e| UpdateCheckError::Network(e.to_string()))?;
+    // 清单可达而签名缺失，按未签名处理
+    let signature = fetcher
+        .fetch(&signature_url(manifest_url))
+        .map_err(|_| UpdateCheckError::Unsigned)?;
+    let signature = String::from_utf8(signature).map_err(|_| UpdateCheckError::BadSignature)?;
+
+    let manifest = verify_manifest(&manifest, &signature, key)?;
+    let advertised = parse_version(&manifest.version)?;
+    let current = parse_version(current)?;
+
+    Ok((advertised > current).then_some(UpdateNotice {
+        version: advertised,
+        notes_url: manifest.notes_url,
+    }))
+}
+
+/// 内置的发布清单签名公钥
+pub fn embedded_key() -> VerifyingKey {
+    VerifyingKey::from_bytes(&UPDATE_PUBLIC_KEY).expect("内置公钥无效")
+}
+
+/// 调试输出（仅 `dev` 特性）
+pub fn debug_log(message: &str) {
+    if cfg!(feature = "dev") {
+        eprintln!("[updates] {}", message);
+    }
+}
+
+/// 十六进制解码
+fn decode_hex(text: &str) -> Option<Vec<u8>> {
+    if !text.len().is_multiple_of(2) {
+        return None;
+    }
+    (0..text.len())
+        .step_by(2)
+        .map(|i| u8::from_str_radix(text.get(i..i + 2)?, 16).ok())
+        .collect()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use ed25519_dalek::{Signer, SigningKey};
+    use std::collections::HashMap;
+
+    const MANIFEST_URL: &str = "https://example.com/amberlock/latest.json";
+
+    /// 测试用签名密钥（固定种子）
+    fn fixture_key() -> SigningKey {
+        SigningKey::from_bytes(&[7u8; 32])
+    }
+
+    fn fixture_manifest(version: &str) -> Vec<u8> {
+        format!(
+            r#"{{"version":"{}","notes_url":"https://example.com/releases/{}"}}"#,
+            version, version
+        )
+        .into_bytes()
+    }
+
+    fn sign(key: &SigningKey, manifest: &[u8]) -> String {
+        key.sign(manifest)
+            .to_bytes()
+            .iter()
+            .map(|b| format!("{:02x}", b))
+            .collect()
+    }
+
+    /// 按地址返回预置内容，未预置的地址视为网络错误
+    #[derive(Default)]
+    struct FakeFetcher {
+        responses: HashMap<String, Vec<u8>>,
+    }
+
+    impl FakeFetcher {
+        fn with(mut self, url: &str, body: impl Into<Vec<u8>>) -> Self {
+            self.responses.insert(url.to_string(), body.into());
+            self
+        }
+
+        fn signed(version: &str) -> Self {
+            let manifest = fixture_manifest(version);
+            let signature = sign(&fixture_key(), &manifest);
+            Self::default()
+                .with(MANIFEST_URL, manifest)
+                .with(&signature_url(MANIFEST_URL), signature)
+        }
+    }
+
+    impl ManifestFetcher for FakeFetcher {
+        fn fetch(&self, url: &str) -> anyhow::Result<Vec<u8>> {
+            self.responses
+                .get(url)
+                .cloned()
+                .ok_or_else(|| anyhow::anyhow!("连接超时"))
+        }
+    }
+
+    #[test]
+    fn test_version_comparison() {
+        let key = fixture_key().verifying_key();
+        let check = |advertised: &str, current: &str| {
+            check_for_update(
+                &FakeFetcher::signed(advertised),
+                MANIFEST_URL,
+                &key,
+                current,
+            )
+        };
+
+        let notice = check("0.2.0", "0.1.9")
+            .expect("检查失败")
+            .expect("应有新版本");
+        assert_eq!(notice.version, Version::new(0, 2, 0));
+        assert_eq!(notice.notes_url, "https://example.com/releases/0.2.0");
+
+        // 按语义化版本而非字符串比较
+        assert!(check("0.10.0", "0.9.0").unwrap().is_some());
+        assert!(check("v1.0.0", "1.0.0").unwrap().is_none());
+        assert!(check("0.1.0", "0.2.0").unwrap().is_none());
+        // 预发布版本低于正式版本
+        assert!(check("1.0.0-rc.1", "1.0.0").unwrap().is_none());
+        assert!(check("1.0.0", "1.0.0-rc.1").unwrap().is_some());
+
+        assert!(matches!(
+            check("latest", "0.1.0"),
+            Err(UpdateCheckError::InvalidManifest(_))
+        ));
+        println!("✅ 版本比较正常");
+    }
+
+    #[test]
+    fn test_throttling() {
+        let now = 1_700_000_000;
+        assert!(should_check(None, now));
+        assert!(!should_check(Some(now), now));
+        assert!(!should_check(
+            Some(now - UPDATE_CHECK_INTERVAL_SECS + 1),
+            now
+        ));
+        assert!(should_check(Some(now - UPDATE_CHECK_INTERVAL_SECS), now));
+        // 时钟回拨
+        assert!(should_check(Some(now + 3600), now));
+        println!("✅ 检查频率限制正常");
+    }
+
+    #[test]
+    fn test_signature_verification() {
+        let key = fixture_key().verifying_key();
+        let manifest = fixture_manifest("9.9.9");
+        let signature = sign(&fixture_key(), &manifest);
+
+        let parsed =
+            verify_manifest(&manifest, &format!("{}\n", signature), &key).expect("有效签名应通过");
+        assert_eq!(parsed.version, "9.9.9");
+
+        // 内容被篡改
+        let tampered = fixture_manifest("9.9.8");
+        assert_eq!(
+            verify_manifest(&tampered, &signature, &key),
+            Err(UpdateCheckError::BadSignature)
+        );
+
+        // 其他密钥签名
+        let other = SigningKey::from_bytes(&[9u8; 32]);
+        assert_eq!(
+            verify_manifest(&manifest, &sign(&other, &manifest), &key),
+            Err(UpdateCheckError::BadSignature)
+        );
+
+        // 格式错误
+        assert_eq!(
+            verify_manifest(&manifest, "not-hex", &key),
+            Err(UpdateCheckError::BadSignature)
+        );
+        assert_eq!(
+            verify_manifest(&manifest, "  ", &key),
+            Err(UpdateCheckError::Unsigned)
+        );
+
+        // 内置公钥可正常加载，且不接受测试密钥的签名
+        assert!(verify_manifest(&manifest, &signature, &embedded_key()).is_err());
+        println!("✅ 清单签名校验正常");
+    }
+
+    #[test]
+    fn test_fetch_failures() {
+        let key = fixture_key().verifying_key();
+
+        let offline = FakeFetcher::default();
+        let err = check_for_update(&offline, MANIFEST_URL, &key, "0.1.0").unwrap_err();
+        assert!(matches!(err, UpdateCheckError::Network(_)));
+        assert!(!err.is_loud(), "网络错误应静默");
+
+        let unsigned = FakeFetcher::default().with(MANIFEST_URL, fixture_manifest("9.9.9"));
+        let err = check_for_update(&unsigned, MANIFEST_URL, &key, "0.1.0").unwrap_err();
+        assert_eq!(err, UpdateCheckError::Unsigned);
+        assert!(err.is_loud(), "未签名清单应明确提示");
+        println!("✅ 获取失败分类正常");
+    }
+}
@@ -0,0 +1,1212 @@
+//! 保险库信息展示与敏感设置授权
+//!
+//! 读取保险库元数据用于状态面板显示；解锁、关闭只读审计模式、撤销批次等敏感操作统一经
+//! `authorize_sensitive_action` 校验保险库密码，保险库启用了 TOTP 时连同验证码一起校验。
+//! 创建保险库前评估密码强度，弱密码需用户确认后才创建。
+//! 连续输错 `HINT_AFTER_FAILURES` 次后，密码错误的提示中附带保险库记录的密码提示。
+//! 保险库无法读取时，状态面板逐项列出诊断结果，区分文件来自其他计算机与文件损坏。
+//!
+//! # 写入
+//! 保险库先写入临时文件再替换（见 `amberlock_storage::atomic`），写到一半崩溃不会留下截断的文件。
+//! 创建与修改密码时原文件轮换为备份（`{保险库}.bak` 最新，其次 `.bak.1`……共 `VAULT_BACKUPS` 个），
+//! 保险库损坏时用 `restore_vault_backup` 恢复；写回失败计数不轮换备份。
+//!
+//! # 解锁冷却
+//! 所有密码校验（解锁、退出只读模式、撤销批次、全部解除保护、密钥文件校验）另有共用的失败
+//! 记录文件（见 `LockoutStore`）：连续失败 `COOLDOWN_AFTER_FAILURES` 次后冷却 `UNLOCK_COOLDOWN`，
+//! 冷却期间不进行校验。
+
+use amberlock_auth::{
+    Backend, BackoffConfig, Check, DEFAULT_CREDENTIAL, DpapiScope, MIN_RECOMMENDED_SCORE,
+    PlainFormat, SecretString, StrengthReport, TrackedVerification, VaultDiagnostics,
+    VaultMetadata, VerifyOutcome, backend::check_structure, check_vault_opens, diagnose_vault,
+    dpapi, estimate_password_strength, get_lockout_state, vault_metadata, verify_password,
+    verify_password_async, verify_password_tracked, verify_password_with_keyfile, verify_totp,
+    verify_totp_async,
+};
+use amberlock_storage::atomic::{AtomicWriteOptions, atomic_write, backup_path};
+use amberlock_types::AmberlockError;
+use serde::{Deserialize, Serialize};
+use std::path::{Path, PathBuf};
+use std::time::{Duration, SystemTime};
+use zeroize::Zeroizing;
+
+/// 连续失败多少次后显示密码提示
+pub const HINT_AFTER_FAILURES: u32 = 2;
+/// 保留的保险库备份数
+pub const VAULT_BACKUPS: usize = 3;
+/// 界面解锁连续失败多少次后进入冷却
+pub const COOLDOWN_AFTER_FAILURES: usize = 5;
+/// 界面解锁的冷却时长
+pub const UNLOCK_COOLDOWN: Duration = Duration::from_secs(60);
+/// 保险库启用了 TOTP 而未填写验证码时给用户的提示
+pub const TOTP_CODE_REQUIRED: &str = "保险库已启用验证码，请同时输入 6 位验证码";
+
+/// 读取保险库元数据
+///
+/// # 返回
+/// - `Ok(Some(metadata))`: 读取成功
+/// - `Ok(None)`: 保险库文件不存在（尚未设置密码）
+/// - `Err`: 文件无法读取或保险库损坏
+pub fn vault_info(path: &Path) -> anyhow::Result<Option<VaultMetadata>> {
+    if !path.exists() {
+        return Ok(None);
+    }
+    let blob = std::fs::read(path)?;
+    Ok(Some(vault_metadata(&blob)?))
+}
+
+/// 诊断无法读取的保险库（不需要密码）
+///
+/// # 返回
+/// 保险库文件不存在或无法读取时为 None
+pub fn diagnose(path: &Path) -> Option<VaultDiagnostics> {
+    std::fs::read(path).ok().map(|blob| diagnose_vault(&blob))
+}
+
+/// 创建保险库并写入 `path`
+///
+/// # 参数
+/// - `confirm_weak`: 密码强度低于 `MIN_RECOMMENDED_SCORE` 时调用，传入强度报告；
+///   返回 `true` 表示用户确认仍使用该密码（通常以 `strength_message` 作为对话框正文）
+///
+/// # 返回
+/// - `Ok(Some(report))`: 已创建，附带强度报告
+/// - `Ok(None)`: 密码较弱且用户未确认，未创建
+/// - `Err`: 保险库已存在或无法写入
+pub fn create_vault(
+    path: &Path,
+    password: &SecretString,
+    confirm_weak: impl FnOnce(&StrengthReport) -> bool,
+) -> anyhow::Result<Option<StrengthReport>> {
+    if path.exists() {
+        anyhow::bail!("保险库已存在: {}", path.display());
+    }
+    let report = estimate_password_strength(password.as_str());
+    if report.score < MIN_RECOMMENDED_SCORE && !confirm_weak(&report) {
+        return Ok(None);
+    }
+    let blob = amberlock_auth::create_vault(password.as_str())?;
+    write_vault(path, &blob)?;
+    Ok(Some(report))
+}
+
+/// 修改 `default` 凭据的密码并写回 `path`
+///
+/// # 参数
+/// - `totp_code`: 保险库启用了 TOTP 时的当前验证码，空白按未填写处理
+///
+/// # 返回
+/// - `Ok(())`: 已修改，读回的新保险库能以新密码打开
+/// - `Err`: 原密码或验证码错误（计入失败次数）、需要验证码而未填写（`TOTP_CODE_REQUIRED`）、
+///   冷却或锁定中、保险库无法读取或写入，或读回校验失败（已恢复原保险库）
+///
+/// # 注意
+/// - 读回时未启用 TOTP 的保险库校验新密码；启用 TOTP（只凭密码的校验不通过）或密码后端
+///   的保险库确认能以新密码解密并解析（见 `amberlock_auth::check_vault_opens`）
+/// - 备份中的保险库仍可用旧密码打开，读回校验通过后删除全部备份
+pub fn change_vault_password(
+    path: &Path,
+    old_password: &SecretString,
+    totp_code: Option<&str>,
+    new_password: &SecretString,
+) -> anyhow::Result<()> {
+    let Some(blob) = read_unless_locked(path)? else {
+        anyhow::bail!("保险库不存在: {}", path.display());
+    };
+    let totp_code = require_totp_code(&blob, totp_code)?;
+    let changed = amberlock_auth::change_password(
+        &blob,
+        old_password.as_str(),
+        totp_code,
+        new_password.as_str(),
+    );
+    let updated = match changed {
+        Ok(updated) => updated,
+        Err(e @ (AmberlockError::AuthorizationFailed { .. } | AmberlockError::WrongPassword)) => {
+            record_attempt(path, VerifyOutcome::InvalidPassword);
+            // 与校验失败相同：失败计数原地写回，不轮换备份
+            if let AmberlockError::AuthorizationFailed { updated } = &e {
+                atomic_write(path, updated, &AtomicWriteOptions::default())?;
+            }
+            return Err(e.into());
+        }
+        Err(e) => return Err(e.into()),
+    };
+    record_attempt(path, VerifyOutcome::Valid);
+    write_vault(path, &updated)?;
+
+    let verified = std::fs::read(path)
+        .is_ok_and(|written| written == updated && opens_with(&written, new_password));
+    if !verified {
+        restore_vault_backup(path, Some(old_password))?;
+        anyhow::bail!("新保险库校验失败，已恢复原保险库");
+    }
+    for backup in backup_paths(path) {
+        match std::fs::remove_file(&backup) {
+            Err(e) if e.kind() != std::io::ErrorKind::NotFound => return Err(e.into()),
+            _ => {}
+        }
+    }
+    Ok(())
+}
+
+/// `change_vault_password` 的错误是否为原密码或验证码错误
+pub fn is_wrong_password(err: &anyhow::Error) -> bool {
+    matches!(
+        err.downcast_ref::<AmberlockError>(),
+        Some(AmberlockError::AuthorizationFailed { .. } | AmberlockError::WrongPassword)
+    )
+}
+
+/// 读回的保险库能否以 `password` 打开
+fn opens_with(blob: &[u8], password: &SecretString) -> bool {
+    match vault_metadata(blob) {
+        Ok(metadata) if !metadata.totp_enabled => {
+            verify_password(blob, password.as_str()).unwrap_or(false)
+        }
+        _ => check_vault_opens(blob, password.as_str()).is_ok(),
+    }
+}
+
+/// 原子写入保险库，原文件轮换为备份
+pub fn write_vault(path: &Path, blob: &[u8]) -> anyhow::Result<()> {
+    if path.exists() {
+        let backups = backup_paths(path);
+        for (newer, older) in backups.iter().zip(&backups[1..]).rev() {
+            if newer.exists() {
+                std::fs::rename(newer, older)?;
+            }
+        }
+    }
+    let options = AtomicWriteOptions {
+        backup: true,
+        ..AtomicWriteOptions::default()
+    };
+    atomic_write(path, blob, &options)
+}
+
+/// 以最新的可用备份替换保险库
+///
+/// 备份按从新到旧的顺序检查，跳过无法读取或解析的备份；备份文件保留。
+/// 密码后端的备份先核对结构（头部、随机数与密文长度，见 `backend::check_structure`），
+/// 提供 `password` 时还须能以它解密，截断或改动的备份都被跳过
+///
+/// # 参数
+/// - `password`: 备份所用的密码（如修改密码前的原密码）；不提供时密码后端的备份只核对结构
+///
+/// # 返回
+/// - `Ok(())`: 已恢复
+/// - `Err`: 没有可用的备份，或写入失败
+pub fn restore_vault_backup(path: &Path, password: Option<&SecretString>) -> anyhow::Result<()> {
+    for backup in backup_paths(path) {
+        let Ok(blob) = std::fs::read(&backup) else {
+            continue;
+        };
+        let usable = match Backend::of(&blob) {
+            Backend::Dpapi => vault_metadata(&blob).is_ok(),
+            Backend::Password => {
+                check_structure(&blob).is_ok()
+                    && password
+                        .is_none_or(|password| check_vault_opens(&blob, password.as_str()).is_ok())
+            }
+        };
+        if usable {
+            return atomic_write(path, &blob, &AtomicWriteOptions::default());
+        }
+    }
+    anyhow::bail!("没有可用的保险库备份: {}", path.display())
+}
+
+/// 备份路径，从新到旧（`{保险库}.bak`、`{保险库}.bak.1`……）
+fn backup_paths(path: &Path) -> Vec<PathBuf> {
+    let newest = backup_path(path);
+    std::iter::once(newest.clone())
+        .chain((1..VAULT_BACKUPS).map(|index| {
+            let mut name = newest.clone().into_os_string();
+            name.push(format!(".{}", index));
+            PathBuf::from(name)
+        }))
+        .collect()
+}
+
+/// 弱密码确认对话框的正文
+pub fn strength_message(report: &StrengthReport) -> String {
+    let mut text = format!(
+        "密码强度 {}/4（约 {:.0} 位熵），低于建议的 {}/4。",
+        report.score, report.entropy_bits, MIN_RECOMMENDED_SCORE
+    );
+    for warning in &report.warnings {
+        text.push_str("\n· ");
+        text.push_str(warning);
+    }
+    text.push_str("\n\n仍要使用此密码创建保险库吗？");
+    text
+}
+
+/// 校验是否允许执行敏感操作（解锁、关闭只读审计模式、撤销批次、全部解除保护）
+///
+/// # 参数
+/// - `totp_code`: 验证器应用显示的验证码，空白按未填写处理
+///
+/// # 返回
+/// - `Ok(true)`: 尚未设置保险库，或 `default` 凭据密码（启用 TOTP 时连同验证码）正确
+/// - `Ok(false)`: 密码或验证码错误
+/// - `Err`: 需要验证码而未填写（`TOTP_CODE_REQUIRED`），或保险库无法读取、已损坏、
+///   属于其他用户或其他计算机（错误文本即给用户的提示）
+///
+/// # 注意
+/// - 失败时按退避配置补齐延迟，验证码错误与密码错误同样计入失败次数
+/// - 连续失败次数记录在保险库文件中并写回，锁定期间直接返回"请在 N 秒后重试"的错误
+/// - 密码正确且保险库的 Argon2 参数低于当前默认值时，升级后的保险库同样写回
+/// - 密码以 `SecretString` 传入，调用方持有的副本在释放时清零
+pub fn authorize_sensitive_action(
+    path: &Path,
+    password: &SecretString,
+    totp_code: Option<&str>,
+    backoff: &BackoffConfig,
+) -> anyhow::Result<bool> {
+    let Some(blob) = read_unless_locked(path)? else {
+        return Ok(true);
+    };
+    let verification = match require_totp_code(&blob, totp_code)? {
+        Some(code) => verify_totp(&blob, password.as_str(), code, backoff)?,
+        None => verify_password_tracked(&blob, password.as_str(), backoff)?,
+    };
+    finish_authorization(path, verification)
+}
+
+/// 在后台线程中校验敏感操作授权（见 `authorize_sensitive_action`）
+///
+/// 读取保险库与写回失败计数在等待方线程上进行，Argon2id 计算与退避延迟在后台线程中进行；
+/// 界面线程上可在事件循环中等待，窗口不会卡住
+pub async fn authorize_sensitive_action_async(
+    path: PathBuf,
+    password: SecretString,
+    totp_code: Option<String>,
+    backoff: BackoffConfig,
+) -> anyhow::Result<bool> {
+    let Some(blob) = read_unless_locked(&path)? else {
+        return Ok(true);
+    };
+    let verification = match require_totp_code(&blob, totp_code.as_deref())? {
+        Some(code) => {
+            let code = code.to_string();
+            verify_totp_async(blob, password, code, backoff).await?
+        }
+        None => verify_password_async(blob, password, backoff).await?,
+    };
+    finish_authorization(&path, verification)
+}
+
+/// 保险库是否需要 TOTP 验证码（读取元数据，不需要密码）
+///
+/// # 返回
+/// - `Ok(true/false)`: 是否启用了 TOTP；尚未设置保险库时为 false
+/// - `Err`: 保险库无法读取（密码后端的保险库不解密读不出元数据，同样返回错误），
+///   调用方应按需要验证码处理
+pub fn totp_required(path: &Path) -> anyhow::Result<bool> {
+    Ok(vault_info(path)?.is_some_and(|metadata| metadata.totp_enabled))
+}
+
+/// 取出填写的验证码；保险库启用了 TOTP 而未填写时返回 `TOTP_CODE_REQUIRED` 错误
+///
+/// 读不出元数据的保险库（密码后端、已损坏等）未填写时只凭密码校验：
+/// 启用了 TOTP 的由 `amberlock_auth` 按密码错误拒绝，损坏的照常报告校验结果
+fn require_totp_code<'a>(
+    blob: &[u8],
+    totp_code: Option<&'a str>,
+) -> anyhow::Result<Option<&'a str>> {
+    let code = totp_code.map(str::trim).filter(|code| !code.is_empty());
+    if code.is_none() && vault_metadata(blob).is_ok_and(|metadata| metadata.totp_enabled) {
+        anyhow::bail!(TOTP_CODE_REQUIRED);
+    }
+    Ok(code)
+}
+
+/// 读取保险库字节
+///
+/// # 返回
+/// - `Ok(None)`: 尚未设置保险库
+/// - `Err`: 无法读取，或仍在锁定或冷却中（错误文本即给用户的提示）
+fn read_unless_locked(path: &Path) -> anyhow::Result<Option<Vec<u8>>> {
+    if !path.exists() {
+        return Ok(None);
+    }
+    if let Some(remaining) = LockoutStore::for_vault(path).remaining(SystemTime::now()) {
+        anyhow::bail!(cooldown_message(remaining));
+    }
+    let blob = std::fs::read(path)?;
+    if let Ok(lockout) = get_lockout_state(&blob)
+        && lockout.is_locked()
+    {
+        anyhow::bail!(lockout_message(lockout.remaining_secs()));
+    }
+    Ok(Some(blob))
+}
+
+/// 写回更新后的保险库，在失败记录中登记本次结果，并把校验结果转换为授权结果
+fn finish_authorization(path: &Path, verification: TrackedVerification) -> anyhow::Result<bool> {
+    record_attempt(path, verification.outcome);
+    if let Some(updated) = verification.blob {
+        // 失败计数写不回去时拒绝本次失败的尝试，否则重启即可绕过锁定；
+        // 成功时清零失败计数、升级参数只是善后，写入失败不影响授权
+        let written = atomic_write(path, &updated, &AtomicWriteOptions::default());
+        if !verification.outcome.is_valid() {
+            written?;
+        }
+    }
+    match verification.outcome {
+        VerifyOutcome::Valid => Ok(true),
+        VerifyOutcome::InvalidPassword => Ok(false),
+        outcome => Err(anyhow::anyhow!(outcome_message(outcome))),
+    }
+}
+
+/// 在失败记录中登记校验结果：通过时清除，密码错误时记一次失败，保险库不可用时不登记
+fn record_attempt(path: &Path, outcome: VerifyOutcome) {
+    let store = LockoutStore::for_vault(path);
+    match outcome {
+        VerifyOutcome::Valid => store.reset(),
+        VerifyOutcome::InvalidPassword => store.record_failure(SystemTime::now()),
+        _ => {}
+    }
+}
+
+/// 以密码与密钥文件校验保险库
+///
+/// # 返回
+/// - `Ok(true)`: 尚未设置保险库，或密码与密钥文件均正确
+/// - `Ok(false)`: 密码或密钥文件错误
+/// - `Err`: 保险库或密钥文件无法读取，保险库已损坏，或仍在冷却中
+///
+/// # 注意
+/// 失败时按退避配置补齐延迟，并与 `authorize_sensitive_action` 共用失败记录（见 `LockoutStore`）；
+/// 读入的密钥文件内容用完清零
+pub fn verify_vault_password_with_keyfile(
+    path: &Path,
+    password: &SecretString,
+    keyfile_path: &Path,
+    backoff: &BackoffConfig,
+) -> anyhow::Result<bool> {
+    let Some(blob) = read_unless_locked(path)? else {
+        return Ok(true);
+    };
+    let keyfile = Zeroizing::new(
+        std::fs::read(keyfile_path)
+            .map_err(|e| anyhow::anyhow!("无法读取密钥文件 {}: {}", keyfile_path.display(), e))?,
+    );
+    let matched = verify_password_with_keyfile(&blob, password.as_str(), &keyfile, backoff)?;
+    let outcome = if matched {
+        VerifyOutcome::Valid
+    } else {
+        VerifyOutcome::InvalidPassword
+    };
+    record_attempt(path, outcome);
+    Ok(matched)
+}
+
+/// 密码错误时给用户的提示
+///
+/// 保险库记录的连续失败次数达到 `HINT_AFTER_FAILURES` 且设置了密码提示时，
+/// 在 `message` 后附上提示；保险库无法读取时只返回 `message`
+pub fn wrong_password_message(path: &Path, message: &str) -> String {
+    let hint = vault_info(path).ok().flatten().and_then(|metadata| {
+        (metadata.lockout.failed_attempts >= HINT_AFTER_FAILURES)
+            .then_some(metadata.hint)
+            .flatten()
+    });
+    match hint {
+        Some(hint) => format!("{}（密码提示：{}）", message, hint),
+        None => message.to_string(),
+    }
+}
+
+/// 锁定期间给用户的提示
+pub fn lockout_message(remaining_secs: u64) -> String {
+    format!("密码错误次数过多，已锁定，请在 {} 秒后重试", remaining_secs)
+}
+
+/// 密码校验的失败记录（`authorize_sensitive_action` 等在校验前检查、校验后登记）
+///
+/// 保存在保险库旁的 `{保险库}.lockout` 中（以当前用户范围的 DPAPI 加密），记录连续失败的时间。
+/// 保险库内的失败计数在密码后端下无法写回，本记录不受影响，重启程序后冷却照常生效。
+///
+/// # 注意
+/// 记录文件损坏或无法解密时输出警告并视为没有失败记录，不会因此无法解锁；
+/// 写入失败同样只输出警告
+#[derive(Debug, Clone)]
+pub struct LockoutStore {
+    path: PathBuf,
+    threshold: usize,
+    cooldown: Duration,
+}
+
+/// 失败记录文件的内容
+#[derive(Debug, Default, Serialize, Deserialize)]
+struct LockoutRecord {
+    /// 连续失败的时间（Unix 秒，按先后排列）
+    failures: Vec<u64>,
+}
+
+impl LockoutStore {
+    /// 保险库 `vault_path` 的失败记录（默认阈值与冷却时长）
+    pub fn for_vault(vault_path: &Path) -> Self {
+        Self::new(vault_path, COOLDOWN_AFTER_FAILURES, UNLOCK_COOLDOWN)
+    }
+
+    /// 指定阈值与冷却时长
+    pub fn new(vault_path: &Path, threshold: usize, cooldown: Duration) -> Self {
+        let mut name = vault_path.as_os_str().to_os_string();
+        name.push(".lockout");
+        Self {
+            path: PathBuf::from(name),
+            threshold,
+            cooldown,
+        }
+    }
+
+    /// 剩余冷却时长（未在冷却中时为 None）
+    pub fn remaining(&self, now: SystemTime) -> Option<Duration> {
+        let record = self.load();
+        if record.failures.len() < self.threshold {
+            return None;
+        }
+        let last = SystemTime::UNIX_EPOCH + Duration::from_secs(*record.failures.last()?);
+        let elapsed = now.duration_since(last).unwrap_or_default();
+        self.cooldown.checked_sub(elapsed).filter(|d| !d.is_zero())
+    }
+
+    /// 记录一次失败（冷却结束后的失败重新计数）
+    pub fn record_failure(&self, now: SystemTime) {
+        let mut record = self.load();
+        if record.failures.len() >= self.threshold {
+            record.failures.clear();
+        }
+        let secs = now
+            .duration_since(SystemTime::UNIX_EPOCH)
+            .unwrap_or_default()
+            .as_secs();
+        record.failures.push(secs);
+        if let Err(e) = self.save(&record) {
+            eprintln!("[lockout] 无法写入失败记录 {}: {}", self.path.display(), e);
+        }
+    }
+
+    /// 校验通过后清除失败记录
+    pub fn reset(&self) {
+        match std::fs::remove_file(&self.path) {
+            Err(e) if e.kind() != std::io::ErrorKind::NotFound => {
+                eprintln!("[lockout] 无法清除失败记录 {}: {}", self.path.display(), e);
+            }
+            _ => {}
+        }
+    }
+
+    /// 读取失败记录（不存在、损坏或无法解密时为空）
+    fn load(&self) -> LockoutRecord {
+        let Ok(blob) = std::fs::read(&self.path) else {
+            return LockoutRecord::default();
+        };
+        let record = dpapi::unprotect(&blob, None)
+            .map_err(anyhow::Error::from)
+            .and_then(|plain| Ok(serde_json::from_slice(&plain)?));
+        record.unwrap_or_else(|e| {
+            eprintln!(
+                "[lockout] 失败记录 {} 无效，已忽略: {}",
+                self.path.display(),
+                e
+            );
+            LockoutRecord::default()
+        })
+    }
+
+    fn save(&self, record: &LockoutRecord) -> anyhow::Result<()> {
+        let plain = serde_json::to_vec(record)?;
+        let blob = dpapi::protect(&plain, DpapiScope::CurrentUser, None)?;
+        atomic_write(&self.path, &blob, &AtomicWriteOptions::default())
+    }
+}
+
+/// 冷却期间给用户的提示（剩余时长向上取整到秒）
+pub fn cooldown_message(remaining: Duration) -> String {
+    let secs = remaining.as_secs() + u64::from(remaining.subsec_nanos() > 0);
+    lockout_message(secs)
+}
+
+/// 保险库不可用时给用户的提示
+pub fn outcome_message(outcome: VerifyOutcome) -> &'static str {
+    match outcome {
+        VerifyOutcome::Valid => "密码正确",
+        VerifyOutcome::InvalidPassword => "密码错误",
+        VerifyOutcome::VaultCorrupted => "保险库文件已损坏或被篡改，请从备份恢复保险库文件",
+        VerifyOutcome::WrongUserOrMachine => {
+            "保险库属于其他用户或其他计算机，请以创建保险库的账户在原计算机上操作，或重新创建保险库"
+        }
+    }
+}
+
+/// 格式化为状态面板文本
+///
+/// 形如 "保险库创建于 2024-11-02，上次修改 2025-01-10，Argon2id m=19456,t=2,p=1"；
+/// 以本机范围加密时注明"本机共享"
+pub fn format_vault_info(metadata: &VaultMetadata) -> String {
+    let created = metadata
+        .created_at
+        .as_deref()
+        .map(date_part)
+        .unwrap_or("未知");
+    let changed = metadata
+        .last_changed_at
+        .as_deref()
+        .map(date_part)
+        .unwrap_or("未知");
+
+    let mut text = format!("保险库创建于 {}，上次修改 {}", created, changed);
+    if let Some(password_changed) = metadata.password_changed_at.as_deref() {
+        text.push_str(&format!("，密码修改于 {}", date_part(password_changed)));
+    }
+    let params = metadata
+        .params
+        .get(DEFAULT_CREDENTIAL)
+        .or_else(|| metadata.params.values().next());
+    if let Some(params) = params {
+        text.push_str(&format!("，Argon2id {}", params));
+    }
+    if metadata.params.len() > 1 {
+        text.push_str(&format!("（{} 个凭据）", metadata.params.len()));
+    }
+    if metadata.dpapi_scope == DpapiScope::LocalMachine {
+        text.push_str("，本机共享");
+    }
+    if metadata.totp_enabled {
+        text.push_str("，已启用验证码");
+    }
+    text
+}
+
+/// 格式化诊断结果为状态面板文本（每步一行，最后一行为结论）
+pub fn format_diagnostics(diagnostics: &VaultDiagnostics) -> Vec<String> {
+    let backend = match diagnostics.backend {
+        Backend::Dpapi => "DPAPI",
+        Backend::Password => "密码",
+    };
+    let mut lines = vec![
+        format!(
+            "文件长度 {} 字节，加密后端 {}",
+            diagnostics.blob_len, backend
+        ),
+        format!("解密：{}", check_text(&diagnostics.decryption)),
+    ];
+    if diagnostics.decryption.is_passed() {
+        let format = match diagnostics.format {
+            Some(PlainFormat::Binary) => "二进制",
+            Some(PlainFormat::Json) => "JSON",
+            None => "无法识别",
+        };
+        let version = diagnostics
+            .version
+            .map(|version| format!("，版本 {}", version))
+            .unwrap_or_default();
+        lines.push(format!("明文格式：{}{}", format, version));
+        lines.push(format!("解析：{}", check_text(&diagnostics.parsing)));
+    }
+    for (name, params) in &diagnostics.params {
+        lines.push(match params {
+            Ok(params) => format!("凭据 {}：Argon2id {}", name, params),
+            Err(reason) => format!("凭据 {}：参数无效（{}）", name, reason),
+        });
+    }
+    lines.push(match diagnostics.problem {
+        Some(problem) => format!("结论：{}", outcome_message(problem)),
+        None if diagnostics.backend == Backend::Password => {
+            "结论：密码加密的保险库需输入密码才能进一步检查".to_string()
+        }
+        None => "结论：未发现问题".to_string(),
+    });
+    lines
+}
+
+/// 单个诊断步骤的文本
+fn check_text(check: &Check) -> String {
+    match check {
+        Check::Passed => "通过".to_string(),
+        Check::Failed(reason) => format!("失败（{}）", reason),
+        Check::Skipped => "未执行".to_string(),
+    }
+}
+
+/// 取 RFC3339 时间戳的日期部分
+fn date_part(timestamp: &str) -> &str {
+    timestamp.get(..10).unwrap_or(timestamp)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use amberlock_auth::{Argon2Params, LockoutState};
+    use std::collections::BTreeMap;
+
+    #[test]
+    fn test_format_vault_info() {
+        let metadata = VaultMetadata {
+            version: 4,
+            created_at: Some("2024-11-02T08:00:00Z".to_string()),
+            last_changed_at: Some("2025-01-10T12:30:00.123Z".to_string()),
+            dpapi_scope: DpapiScope::CurrentUser,
+            lockout: LockoutState::default(),
+            params: BTreeMap::from([(
+                DEFAULT_CREDENTIAL.to_string(),
+                Argon2Params::CURRENT,
+            )]),
+            password_changed_at: Some("2024-12-01T09:00:00Z".to_string()),
+            hint: Some("猫的名字".to_string()),
+            totp_enabled: false,
+        };
+
+        assert_eq!(
+            format_vault_info(&metadata),
+            "保险库创建于 2024-11-02，上次修改 2025-01-10，密码修改于 2024-12-01，Argon2id m=19456,t=2,p=1"
+        );
+
+        let shared = VaultMetadata {
+            dpapi_scope: DpapiScope::LocalMachine,
+            ..metadata
+        };
+        assert!(format_vault_info(&shared).ends_with("，本机共享"));
+    }
+
+    #[test]
+    fn test_format_legacy_vault_without_timestamps() {
+        let metadata = VaultMetadata {
+            version: 1,
+            created_at: None,
+            last_changed_at: None,
+            dpapi_scope: DpapiScope::CurrentUser,
+            lockout: LockoutState::default(),
+            params: BTreeMap::new(),
+            password_changed_at: None,
+            hint: None,
+            totp_enabled: false,
+        };
+
+        assert_eq!(
+            format_vault_info(&metadata),
+            "保险库创建于 未知，上次修改 未知"
+        );
+    }
+
+    #[test]
+    fn test_hint_shown_after_repeated_failures() {
+        let dir = tempfile::TempDir::new().expect("创建临时目录失败");
+        let path = dir.path().join("vault.bin");
+        let blob = amberlock_auth::create_vault_with_hint("secret", Some("猫的名字"))
+            .expect("创建保险库失败");
+        std::fs::write(&path, blob).unwrap();
+        let backoff = BackoffConfig::default();
+        let wrong = SecretString::from("wrong");
+
+        // 第一次失败不显示提示，第二次起显示
+        assert!(!authorize_sensitive_action(&path, &wrong, None, &backoff).unwrap());
+        assert_eq!(wrong_password_message(&path, "❌ 密码错误"), "❌ 密码错误");
+        std::thread::sleep(amberlock_auth::lockout::BASE_LOCKOUT);
+        assert!(!authorize_sensitive_action(&path, &wrong, None, &backoff).unwrap());
+        assert_eq!(
+            wrong_password_message(&path, "❌ 密码错误"),
+            "❌ 密码错误（密码提示：猫的名字）"
+        );
+
+        // 未设置提示或保险库不存在时只有原文
+        let missing = dir.path().join("missing.bin");
+        assert_eq!(
+            wrong_password_message(&missing, "❌ 密码错误"),
+            "❌ 密码错误"
+        );
+        println!("✅ 连续输错两次后显示密码提示");
+    }
+
+    #[test]
+    fn test_format_diagnostics() {
+        let dir = tempfile::tempdir().unwrap();
+
+        // 被截断的文件：解密失败，归为损坏
+        let path = dir.path().join("vault.bin");
+        let blob = amberlock_auth::create_vault("secret").expect("创建保险库失败");
+        std::fs::write(&path, &blob[..blob.len() / 2]).unwrap();
+        let lines = format_diagnostics(&diagnose(&path).expect("诊断失败"));
+        assert_eq!(
+            lines[0],
+            format!("文件长度 {} 字节，加密后端 DPAPI", blob.len() / 2)
+        );
+        assert!(lines[1].starts_with("解密：失败"));
+        assert_eq!(
+            lines.last().unwrap(),
+            &format!("结论：{}", outcome_message(VerifyOutcome::VaultCorrupted))
+        );
+
+        // 完好的保险库
+        std::fs::write(&path, &blob).unwrap();
+        let lines = format_diagnostics(&diagnose(&path).expect("诊断失败"));
+        assert!(lines.contains(&"解析：通过".to_string()));
+        assert!(lines.contains(&format!(
+            "凭据 {}：Argon2id {}",
+            DEFAULT_CREDENTIAL,
+            Argon2Params::CURRENT
+        )));
+        assert_eq!(lines.last().unwrap(), "结论：未发现问题");
+
+        assert!(diagnose(&dir.path().join("missing.bin")).is_none());
+        println!("✅ 诊断结果逐项显示");
+    }
+
+    #[test]
+    fn test_missing_vault_is_none() {
+        let path = std::env::temp_dir().join("amberlock-missing-vault-test.bin");
+        let info = vault_info(&path).expect("读取失败");
+        assert!(info.is_none());
+    }
+
+    #[test]
+    fn test_create_vault_confirms_weak_password() {
+        let dir = tempfile::TempDir::new().expect("创建临时目录失败");
+        let path = dir.path().join("vault.bin");
+
+        // 弱密码：用户拒绝时不创建
+        let weak = SecretString::from("amberlock");
+        let mut shown = None;
+        let created = create_vault(&path, &weak, |report| {
+            shown = Some(strength_message(report));
+            false
+        })
+        .expect("创建保险库失败");
+        assert!(created.is_none());
+        assert!(!path.exists());
+        assert!(shown.expect("未请求确认").contains("默认密码"));
+
+        // 用户确认后照常创建
+        let report = create_vault(&path, &weak, |_| true)
+            .expect("创建保险库失败")
+            .expect("确认后应创建");
+        assert!(report.score < MIN_RECOMMENDED_SCORE);
+        let backoff = BackoffConfig::default();
+        assert!(authorize_sensitive_action(&path, &weak, None, &backoff).unwrap());
+
+        // 已存在时不覆盖
+        let strong = SecretString::from("correct horse battery staple");
+        assert!(create_vault(&path, &strong, |_| true).is_err());
+
+        // 强密码不询问
+        std::fs::remove_file(&path).unwrap();
+        let report = create_vault(&path, &strong, |_| panic!("强密码不应请求确认"))
+            .expect("创建保险库失败")
+            .expect("应创建");
+        assert!(report.score >= MIN_RECOMMENDED_SCORE);
+        println!("✅ 弱密码创建保险库前需确认");
+    }
+
+    #[test]
+    fn test_write_vault_rotates_backups() {
+        let dir = tempfile::TempDir::new().expect("创建临时目录失败");
+        let path = dir.path().join("vault.bin");
+
+        // 上次写入中途崩溃留下的临时文件不影响现有保险库与后续写入
+        let first = amberlock_auth::create_vault("first").expect("创建保险库失败");
+        write_vault(&path, &first).expect("写入失败");
+        let bogus = dir.path().join(format!(
+            ".vault.bin.{}.00000000deadbeef.tmp",
+            std::process::id()
+        ));
+        std::fs::write(&bogus, b"half written").unwrap();
+        assert!(vault_info(&path).expect("读取失败").is_some());
+        assert!(!backup_paths(&path)[0].exists());
+
+        let versions: Vec<Vec<u8>> = (0..VAULT_BACKUPS + 1)
+            .map(|i| amberlock_auth::create_vault(&format!("pw{}", i)).expect("创建保险库失败"))
+            .collect();
+        for blob in &versions {
+            write_vault(&path, blob).expect("写入失败");
+        }
+        assert_eq!(std::fs::read(&path).unwrap(), *versions.last().unwrap());
+
+        // 从新到旧依次是前几次写入的内容，更早的被轮换掉
+        let backups = backup_paths(&path);
+        assert_eq!(backups[0], dir.path().join("vault.bin.bak"));
+        for (index, backup) in backups.iter().enumerate() {
+            let expected = &versions[versions.len() - 2 - index];
+            assert_eq!(&std::fs::read(backup).unwrap(), expected);
+        }
+        assert!(
+            !dir.path()
+                .join(format!("vault.bin.bak.{}", VAULT_BACKUPS))
+                .exists()
+        );
+        println!("✅ 原子写入并轮换备份");
+    }
+
+    #[test]
+    fn test_restore_vault_backup() {
+        let dir = tempfile::TempDir::new().expect("创建临时目录失败");
+        let path = dir.path().join("vault.bin");
+        assert!(restore_vault_backup(&path, None).is_err());
+
+        let older = amberlock_auth::create_vault("older").expect("创建保险库失败");
+        let newer = amberlock_auth::create_vault("newer").expect("创建保险库失败");
+        for blob in [&older, &newer, &newer] {
+            write_vault(&path, blob).expect("写入失败");
+        }
+
+        // 保险库被截断：从最新的备份恢复
+        std::fs::write(&path, &newer[..newer.len() / 2]).unwrap();
+        assert!(vault_info(&path).is_err());
+        restore_vault_backup(&path, None).expect("恢复失败");
+        assert!(verify_password(&std::fs::read(&path).unwrap(), "newer").unwrap());
+
+        // 最新的备份同样损坏时跳过，使用更早的备份
+        let backups = backup_paths(&path);
+        std::fs::write(&path, b"garbage").unwrap();
+        std::fs::write(&backups[0], b"garbage").unwrap();
+        restore_vault_backup(&path, None).expect("恢复失败");
+        assert!(verify_password(&std::fs::read(&path).unwrap(), "older").unwrap());
+        assert!(backups[0].exists());
+
+        // 密码后端的备份：只有头部或被截断时跳过，结构完整才恢复
+        let sealed = password_backend_vault("older");
+        let unknown = [&amberlock_auth::backend::MAGIC[..], &[9], b"sealed"].concat();
+        let header_only = [&amberlock_auth::backend::MAGIC[..], &[1], b"sealed"].concat();
+        std::fs::write(&backups[0], &unknown).unwrap();
+        std::fs::write(&backups[1], &header_only).unwrap();
+        std::fs::write(&backups[2], b"garbage").unwrap();
+        assert!(restore_vault_backup(&path, None).is_err());
+        // 截断在随机数之后、密文不足一个认证标签
+        std::fs::write(&backups[1], &sealed[..64]).unwrap();
+        assert!(restore_vault_backup(&path, None).is_err());
+        std::fs::write(&backups[1], &sealed).unwrap();
+        restore_vault_backup(&path, None).expect("恢复失败");
+        assert_eq!(std::fs::read(&path).unwrap(), sealed);
+
+        // 提供密码时还须能解密：截去一半或只少最后一个字节（认证标签不完整）同样跳过
+        let password = SecretString::from("older");
+        for len in [sealed.len() / 2, sealed.len() - 1] {
+            std::fs::write(&backups[1], &sealed[..len]).unwrap();
+            assert!(restore_vault_backup(&path, Some(&password)).is_err());
+        }
+        std::fs::write(&backups[1], &sealed).unwrap();
+        restore_vault_backup(&path, Some(&password)).expect("恢复失败");
+        println!("✅ 从最新的可用备份恢复保险库");
+    }
+
+    /// 以密码后端加密的保险库（任何平台都可构造）
+    ///
+    /// 密码后端的格式为后端头部加导出文件，导出文件即以密码派生的密钥加密的保险库明文
+    fn password_backend_vault(password: &str) -> Vec<u8> {
+        let blob = amberlock_auth::create_vault(password).expect("创建保险库失败");
+        let portable = amberlock_auth::export_portable(&blob, password, None).expect("导出失败");
+        [&amberlock_auth::backend::MAGIC[..], &[1], &portable].concat()
+    }
+
+    #[test]
+    fn test_change_vault_password() {
+        let dir = tempfile::TempDir::new().expect("创建临时目录失败");
+        let path = dir.path().join("vault.bin");
+        let old = SecretString::from("correct horse battery staple");
+        let new = SecretString::from("another long passphrase");
+        create_vault(&path, &old, |_| true).expect("创建保险库失败");
+
+        // 原密码错误计入失败次数，保险库内容不变
+        let err = change_vault_password(&path, &new, None, &new).unwrap_err();
+        assert!(is_wrong_password(&err));
+        let blob = std::fs::read(&path).unwrap();
+        assert_eq!(get_lockout_state(&blob).unwrap().failed_attempts, 1);
+        assert!(backup_paths(&path).iter().all(|backup| !backup.exists()));
+        std::thread::sleep(amberlock_auth::lockout::BASE_LOCKOUT);
+
+        change_vault_password(&path, &old, None, &new).expect("修改密码失败");
+        let blob = std::fs::read(&path).unwrap();
+        assert!(verify_password(&blob, new.as_str()).unwrap());
+        assert!(!verify_password(&blob, old.as_str()).unwrap());
+
+        // 备份可用旧密码打开，校验通过后全部删除
+        assert!(backup_paths(&path).iter().all(|backup| !backup.exists()));
+        println!("✅ 修改密码后删除旧密码的备份");
+    }
+
+    #[test]
+    fn test_change_totp_vault_password() {
+        let dir = tempfile::TempDir::new().expect("创建临时目录失败");
+        let path = dir.path().join("vault.bin");
+        let old = SecretString::from("correct horse battery staple");
+        let new = SecretString::from("another long passphrase");
+        let blob = amberlock_auth::create_vault(old.as_str()).expect("创建保险库失败");
+        let (blob, secret) =
+            amberlock_auth::enable_totp(&blob, old.as_str(), None).expect("启用 TOTP 失败");
+        std::fs::write(&path, blob).unwrap();
+        let now = || {
+            SystemTime::now()
+                .duration_since(SystemTime::UNIX_EPOCH)
+                .unwrap()
+                .as_secs()
+        };
+
+        // 未填写验证码：提示输入，保险库不变
+        let err = change_vault_password(&path, &old, None, &new).unwrap_err();
+        assert_eq!(err.to_string(), TOTP_CODE_REQUIRED);
+
+        // 读回时只凭新密码的校验不通过，改为确认能以新密码打开
+        let code = secret.code_at(now());
+        change_vault_password(&path, &old, Some(&code), &new).expect("修改密码失败");
+        let blob = std::fs::read(&path).unwrap();
+        assert!(check_vault_opens(&blob, new.as_str()).is_ok());
+        let backoff = BackoffConfig::default();
+        let code = secret.code_at(now());
+        assert!(
+            verify_totp(&blob, new.as_str(), &code, &backoff)
+                .unwrap()
+                .outcome
+                .is_valid()
+        );
+        assert!(backup_paths(&path).iter().all(|backup| !backup.exists()));
+        println!("✅ 启用 TOTP 的保险库同样可以修改密码");
+    }
+
+    #[test]
+    fn test_lockout_store_cooldown() {
+        let dir = tempfile::TempDir::new().expect("创建临时目录失败");
+        let vault_path = dir.path().join("vault.bin");
+        let store = LockoutStore::new(&vault_path, 3, Duration::from_secs(60));
+        let start = SystemTime::UNIX_EPOCH + Duration::from_secs(1_700_000_000);
+        let at = |secs: u64| start + Duration::from_secs(secs);
+
+        // 未达阈值不冷却
+        assert_eq!(store.remaining(start), None);
+        store.record_failure(at(0));
+        store.record_failure(at(1));
+        assert_eq!(store.remaining(at(2)), None);
+        assert!(dir.path().join("vault.bin.lockout").exists());
+
+        // 达到阈值后从最后一次失败起冷却，重新打开记录同样生效
+        store.record_failure(at(2));
+        let reopened = LockoutStore::new(&vault_path, 3, Duration::from_secs(60));
+        assert_eq!(reopened.remaining(at(12)), Some(Duration::from_secs(50)));
+        assert_eq!(
+            cooldown_message(Duration::from_millis(49_200)),
+            lockout_message(50)
+        );
+
+        // 冷却结束后允许尝试，再次失败重新计数
+        assert_eq!(store.remaining(at(62)), None);
+        store.record_failure(at(63));
+        assert_eq!(store.remaining(at(64)), None);
+
+        // 成功后清除
+        store.reset();
+        assert!(!dir.path().join("vault.bin.lockout").exists());
+        store.reset();
+        println!("✅ 连续失败后冷却，冷却结束或成功后恢复");
+    }
+
+    #[test]
+    fn test_corrupted_lockout_file_fails_open() {
+        let dir = tempfile::TempDir::new().expect("创建临时目录失败");
+        let vault_path = dir.path().join("vault.bin");
+        let store = LockoutStore::new(&vault_path, 1, Duration::from_secs(60));
+        let now = SystemTime::now();
+        store.record_failure(now);
+        assert!(store.remaining(now).is_some());
+
+        // 损坏的记录视为没有失败记录，允许尝试
+        std::fs::write(dir.path().join("vault.bin.lockout"), b"garbage").unwrap();
+        assert_eq!(store.remaining(now), None);
+
+        // 可以解密但内容无效的记录同样如此
+        let blob = dpapi::protect(b"{\"failures\": \"x\"}", DpapiScope::CurrentUser, None)
+            .expect("加密失败");
+        std::fs::write(dir.path().join("vault.bin.lockout"), blob).unwrap();
+        assert_eq!(store.remaining(now), None);
+        store.record_failure(now);
+        assert!(store.remaining(now).is_some());
+        println!("✅ 失败记录损坏时不阻止解锁");
+    }
+
+    #[test]
+    fn test_sensitive_action_shares_lockout_store() {
+        let dir = tempfile::TempDir::new().expect("创建临时目录失败");
+        let path = dir.path().join("vault.bin");
+        let record = dir.path().join("vault.bin.lockout");
+        let blob = amberlock_auth::create_vault("secret").expect("创建保险库失败");
+        std::fs::write(&path, blob).unwrap();
+        let backoff = BackoffConfig::default();
+        let correct = SecretString::from("secret");
+
+        // 冷却期间即使密码正确也不校验
+        let store = LockoutStore::for_vault(&path);
+        for _ in 0..COOLDOWN_AFTER_FAILURES {
+            store.record_failure(SystemTime::now());
+        }
+        let err = authorize_sensitive_action(&path, &correct, None, &backoff).unwrap_err();
+        assert!(err.to_string().starts_with("密码错误次数过多"));
+        assert_eq!(
+            get_lockout_state(&std::fs::read(&path).unwrap())
+                .unwrap()
+                .failed_attempts,
+            0
+        );
+
+        // 密码错误登记一次失败，通过后清除
+        store.reset();
+        let wrong = SecretString::from("wrong");
+        assert!(!authorize_sensitive_action(&path, &wrong, None, &backoff).unwrap());
+        let single = LockoutStore::new(&path, 1, UNLOCK_COOLDOWN);
+        assert!(single.remaining(SystemTime::now()).is_some());
+        std::thread::sleep(amberlock_auth::lockout::BASE_LOCKOUT);
+        assert!(authorize_sensitive_action(&path, &correct, None, &backoff).unwrap());
+        assert!(!record.exists());
+        println!("✅ 敏感操作与界面解锁共用失败记录");
+    }
+
+    #[test]
+    fn test_unlock_persists_upgraded_params() {
+        let dir = tempfile::TempDir::new().expect("创建临时目录失败");
+        let path = dir.path().join("vault.bin");
+        let weak = amberlock_auth::Argon2Config {
+            mem_kib: amberlock_auth::vault::MIN_MEM_KIB,
+            time_cost: 1,
+            ..Default::default()
+        };
+        let blob =
+            amberlock_auth::create_vault_with_config("secret", &weak).expect("创建保险库失败");
+        std::fs::write(&path, blob).unwrap();
+
+        let correct = SecretString::from("secret");
+        assert!(
+            authorize_sensitive_action(&path, &correct, None, &BackoffConfig::default()).unwrap()
+        );
+        let metadata = vault_info(&path).unwrap().expect("保险库应存在");
+        assert_eq!(metadata.params[DEFAULT_CREDENTIAL], Argon2Params::CURRENT);
+        println!("✅ 解锁后写回升级参数的保险库");
+    }
+
+    #[test]
+    fn test_verify_with_keyfile() {
+        let dir = tempfile::TempDir::new().expect("创建临时目录失败");
+        let path = dir.path().join("vault.bin");
+        let keyfile = dir.path().join("amberlock.key");
+        let other = dir.path().join("other.key");
+        std::fs::write(&keyfile, b"usb-keyfile").unwrap();
+        std::fs::write(&other, b"other-keyfile").unwrap();
+        let blob = amberlock_auth::create_vault_with_keyfile("secret", b"usb-keyfile")
+            .expect("创建保险库失败");
+        std::fs::write(&path, blob).unwrap();
+        let backoff = BackoffConfig::default();
+        let correct = SecretString::from("secret");
+
+        assert!(verify_vault_password_with_keyfile(&path, &correct, &keyfile, &backoff).unwrap());
+        assert!(!verify_vault_password_with_keyfile(&path, &correct, &other, &backoff).unwrap());
+        let wrong = SecretString::from("wrong");
+        assert!(!verify_vault_password_with_keyfile(&path, &wrong, &keyfile, &backoff).unwrap());
+
+        // 密钥文件缺失：报错而非当作密码错误
+        let missing = dir.path().join("missing.key");
+        let err =
+            verify_vault_password_with_keyfile(&path, &correct, &missing, &backoff).unwrap_err();
+        assert!(err.to_string().starts_with("无法读取密钥文件"));
+        // 不带密钥文件的普通校验按密码错误处理
+        assert!(!authorize_sensitive_action(&path, &correct, None, &backoff).unwrap());
+        println!("✅ 以密码与密钥文件校验保险库");
+    }
+
+    #[test]
+    fn test_sensitive_action_requires_vault_password() {
+        let dir = tempfile::TempDir::new().expect("创建临时目录失败");
+        let path = dir.path().join("vault.bin");
+        let backoff = BackoffConfig::default();
+
+        // 未设置保险库时无需密码
+        assert!(
+            authorize_sensitive_action(&path, &SecretString::default(), None, &backoff).unwrap()
+        );
+
+        let blob = amberlock_auth::create_vault("secret").expect("创建保险库失败");
+        std::fs::write(&path, blob).unwrap();
+        let wrong = SecretString::from("wrong");
+        assert!(!authorize_sensitive_action(&path, &wrong, None, &backoff).unwrap());
+        let correct = SecretString::from("secret");
+
+        // 失败次数已写回文件，等锁定结束后密码正确即清零
+        let blob = std::fs::read(&path).unwrap();
+        assert_eq!(get_lockout_state(&blob).unwrap().failed_attempts, 1);
+        std::thread::sleep(amberlock_auth::lockout::BASE_LOCKOUT);
+        assert!(authorize_sensitive_action(&path, &correct, None, &backoff).unwrap());
+        let blob = std::fs::read(&path).unwrap();
+        assert_eq!(get_lockout_state(&blob).unwrap().failed_attempts, 0);
+
+        // 多次失败后仍在锁定中：即使密码正确也提示稍后重试
+        // 最近失败时间晚于当前时间时按整段锁定处理
+        let mut vault = amberlock_auth::VaultBlob::with_default("secret").unwrap();
+        vault.lockout = LockoutState {
+            failed_attempts: 5,
+            last_failure_at: Some("9999-12-31T00:00:00Z".to_string()),
+        };
+        let plain = vault.encode().unwrap();
+        let locked = amberlock_auth::dpapi::protect(&plain, DpapiScope::CurrentUser, None).unwrap();
+        std::fs::write(&path, locked).unwrap();
+        let err = authorize_sensitive_action(&path, &correct, None, &backoff).unwrap_err();
+        assert!(err.to_string().starts_with("密码错误次数过多"));
+
+        // 损坏的保险库给出恢复提示而非 DPAPI 原始错误
+        std::fs::write(&path, b"not a vault").unwrap();
+        let err = authorize_sensitive_action(&path, &correct, None, &backoff).unwrap_err();
+        assert_eq!(
+            err.to_string(),
+            outcome_message(VerifyOutcome::VaultCorrupted)
+        );
+        println!("✅ 敏感操作需要保险库密码");
+    }
+
+    #[test]
+    fn test_sensitive_action_requires_totp_code() {
+        let dir = tempfile::TempDir::new().expect("创建临时目录失败");
+        let path = dir.path().join("vault.bin");
+        let backoff = BackoffConfig::default();
+        let now = || {
+            SystemTime::now()
+                .duration_since(SystemTime::UNIX_EPOCH)
+                .unwrap()
+                .as_secs()
+        };
+        assert!(!totp_required(&path).unwrap());
+
+        let blob = amberlock_auth::create_vault("secret").expect("创建保险库失败");
+        let (blob, secret) =
+            amberlock_auth::enable_totp(&blob, "secret", None).expect("启用 TOTP 失败");
+        std::fs::write(&path, blob).unwrap();
+        assert!(totp_required(&path).unwrap());
+        let correct = SecretString::from("secret");
+        let failures = || {
+            get_lockout_state(&std::fs::read(&path).unwrap())
+                .unwrap()
+                .failed_attempts
+        };
+
+        // 未填写验证码：提示输入，不校验也不计入失败次数
+        for code in [None, Some("  ")] {
+            let err = authorize_sensitive_action(&path, &correct, code, &backoff).unwrap_err();
+            assert_eq!(err.to_string(), TOTP_CODE_REQUIRED);
+        }
+        assert_eq!(failures(), 0);
+
+        // 验证码错误与密码错误同样计入失败次数
+        let code = secret.code_at(now());
+        let wrong = if code == "000000" { "000001" } else { "000000" };
+        assert!(!authorize_sensitive_action(&path, &correct, Some(wrong), &backoff).unwrap());
+        assert_eq!(failures(), 1);
+        std::thread::sleep(amberlock_auth::lockout::BASE_LOCKOUT);
+        let code = secret.code_at(now());
+        assert!(authorize_sensitive_action(&path, &correct, Some(&code), &backoff).unwrap());
+        assert_eq!(failures(), 0);
+
+        // 读不出元数据时按需要验证码处理
+        std::fs::write(&path, b"not a vault").unwrap();
+        assert!(totp_required(&path).is_err());
+        println!("✅ 启用 TOTP 后敏感操作需要验证码");
+    }
+}
@@ -0,0 +1,183 @@
+//! 卷到达与系统恢复事件
+//!
+//! 在独立线程上创建一个隐藏的顶层窗口接收系统广播：
+//! - `WM_DEVICECHANGE` + `DBT_DEVICEARRIVAL`（卷设备）：可移动硬盘插入、网络驱动器映射
+//! - `WM_POWERBROADCAST` + `PBT_APMRESUMEAUTOMATIC`：系统从睡眠或休眠中恢复
+//!
+//! 仅消息窗口（`HWND_MESSAGE`）收不到这两类广播，因此使用不显示的顶层窗口；
+//! 顶层窗口默认即可收到电源广播，无需 `RegisterSuspendResumeNotification`。
+//! 事件经通道交给重新验证线程，映射与排队逻辑见 `amberlock_core::rescan`。
+//!
+//! # 手动测试
+//! 1. 锁定移动硬盘（如 `E:\`）上的若干文件后拔出硬盘
+//! 2. 在另一台电脑上移除其中一个文件的标签，再插回硬盘
+//! 3. 约 10 秒内状态栏提示重新验证结果，托盘气泡提示发现漂移，日志中出现 `drift_detected` 记录
+//! 4. 反复插拔同一硬盘：60 秒内只验证一次
+//! 5. 对 `E:\` 发起批量上锁的同时插入硬盘：上锁结束后才开始验证
+//! 6. 让系统睡眠后唤醒：只验证清单中的网络共享根
+
+use amberlock_core::VolumeEvent;
+use std::sync::mpsc::Sender;
+
+/// 卷事件来源
+pub trait VolumeEventSource {
+    /// 开始监听，事件发送到 `sink`
+    ///
+    /// # 注意
+    /// 监听在后台线程中进行，`sink` 的接收端断开后停止
+    fn start(&self, sink: Sender<VolumeEvent>) -> anyhow::Result<()>;
+}
+
+/// 由 `DEV_BROADCAST_VOLUME.dbcv_unitmask` 得到卷根（位 0 为 `A:\`）
+pub fn drive_roots_from_unitmask(mask: u32) -> Vec<String> {
+    (0..26u8)
+        .filter(|bit| mask & (1 << bit) != 0)
+        .map(|bit| format!("{}:\\", (b'A' + bit) as char))
+        .collect()
+}
+
+/// 隐藏窗口实现的系统广播监听
+#[derive(Debug, Default, Clone, Copy)]
+pub struct DeviceNotificationWindow;
+
+impl VolumeEventSource for DeviceNotificationWindow {
+    fn start(&self, sink: Sender<VolumeEvent>) -> anyhow::Result<()> {
+        let (ready_tx, ready_rx) = std::sync::mpsc::channel();
+        std::thread::Builder::new()
+            .name("amberlock-volume-events".to_string())
+            .spawn(move || win32::run_message_loop(sink, ready_tx))?;
+        ready_rx
+            .recv()
+            .map_err(|_| anyhow::anyhow!("卷事件监听线程意外退出"))?
+    }
+}
+
+mod win32 {
+    use super::drive_roots_from_unitmask;
+    use amberlock_core::VolumeEvent;
+    use std::cell::RefCell;
+    use std::sync::mpsc::Sender;
+    use windows::Win32::Foundation::{HWND, LPARAM, LRESULT, WPARAM};
+    use windows::Win32::System::LibraryLoader::GetModuleHandleW;
+    use windows::Win32::UI::WindowsAndMessaging::{
+        CreateWindowExW, DBT_DEVICEARRIVAL, DBT_DEVTYP_VOLUME, DEV_BROADCAST_HDR,
+        DEV_BROADCAST_VOLUME, DefWindowProcW, DispatchMessageW, GetMessageW, MSG,
+        PBT_APMRESUMEAUTOMATIC, PostQuitMessage, RegisterClassW, TranslateMessage, WINDOW_EX_STYLE,
+        WM_DEVICECHANGE, WM_POWERBROADCAST, WNDCLASSW, WS_OVERLAPPED,
+    };
+    use windows::core::w;
+
+    thread_local! {
+        /// 窗口过程把事件发送到这里（窗口与消息循环位于同一线程）
+        static SINK: RefCell<Option<Sender<VolumeEvent>>> = const { RefCell::new(None) };
+    }
+
+    /// 创建隐藏窗口并运行消息循环，直到接收端断开
+    pub(super) fn run_message_loop(sink: Sender<VolumeEvent>, ready: Sender<anyhow::Result<()>>) {
+        SINK.with(|cell| *cell.borrow_mut() = Some(sink));
+
+        let created = unsafe { create_window() };
+        let failed = created.is_err();
+        let _ = ready.send(created.map(|_| ()));
+        if failed {
+            return;
+        }
+
+        let mut msg = MSG::default();
+        unsafe {
+            while GetMessageW(&mut msg, None, 0, 0).as_bool() {
+                let _ = TranslateMessage(&msg);
+                DispatchMessageW(&msg);
+            }
+        }
+    }
+
+    /// 注册窗口类并创建不显示的顶层窗口
+    unsafe fn create_window() -> anyhow::Result<HWND> {
+        unsafe {
+            let instance = GetModuleHandleW(None)?;
+            let class_name = w!("AmberLockVolumeEvents");
+            let class = WNDCLASSW {
+                lpfnWndProc: Some(window_proc),
+                hInstance: instance.into(),
+                lpszClassName: class_name,
+                ..Default::default()
+            };
+            if RegisterClassW(&class) == 0 {
+                return Err(windows::core::Error::from_win32().into());
+            }
+            let hwnd = CreateWindowExW(
+                WINDOW_EX_STYLE::default(),
+                class_name,
+                w!("AmberLock"),
+                WS_OVERLAPPED,
+                0,
+                0,
+                0,
+                0,
+                None,
+                None,
+                Some(instance.into()),
+                None,
+            )?;
+            Ok(hwnd)
+        }
+    }
+
+    unsafe extern "system" fn window_proc(
+        hwnd: HWND,
+        msg: u32,
+        wparam: WPARAM,
+        lparam: LPARAM,
+    ) -> LRESULT {
+        let events = match msg {
+            WM_DEVICECHANGE if wparam.0 as u32 == DBT_DEVICEARRIVAL && lparam.0 != 0 => {
+                let header = unsafe { &*(lparam.0 as *const DEV_BROADCAST_HDR) };
+                if header.dbch_devicetype == DBT_DEVTYP_VOLUME {
+                    let volume = unsafe { &*(lparam.0 as *const DEV_BROADCAST_VOLUME) };
+                    drive_roots_from_unitmask(volume.dbcv_unitmask)
+                        .into_iter()
+                        .map(VolumeEvent::Arrival)
+                        .collect()
+                } else {
+                    Vec::new()
+                }
+            }
+            WM_POWERBROADCAST if wparam.0 as u32 == PBT_APMRESUMEAUTOMATIC => {
+                vec![VolumeEvent::Resume]
+            }
+            _ => Vec::new(),
+        };
+
+        for event in events {
+            let delivered = SINK.with(|cell| {
+                cell.borrow()
+                    .as_ref()
+                    .is_some_and(|sink| sink.send(event).is_ok())
+            });
+            if !delivered {
+                unsafe { PostQuitMessage(0) };
+                break;
+            }
+        }
+
+        unsafe { DefWindowProcW(hwnd, msg, wparam, lparam) }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_drive_roots_from_unitmask() {
+        assert!(drive_roots_from_unitmask(0).is_empty());
+        // 位 4 = E:，位 25 = Z:
+        assert_eq!(
+            drive_roots_from_unitmask((1 << 4) | (1 << 25)),
+            vec![r"E:\", r"Z:\"]
+        );
+        // 高位未使用
+        assert_eq!(drive_roots_from_unitmask(1 | (1 << 31)), vec![r"A:\"]);
+    }
+}
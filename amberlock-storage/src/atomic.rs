@@ -0,0 +1,318 @@
+//! 原子写入
+//!
+//! 先写入同目录下的唯一临时文件，再以一次替换操作换入目标文件。
+//! 临时文件名包含进程 ID 与随机数（`.{文件名}.{pid}.{随机}.tmp`），
+//! 多个线程或进程同时保存同一目标时互不覆盖对方的半成品，
+//! 目标文件始终是某一个写入者的完整内容。
+//!
+//! # 替换方式
+//! - Windows：`ReplaceFileW`，目标不存在或替换失败时退化为
+//!   `MoveFileExW(MOVEFILE_REPLACE_EXISTING)`
+//! - 其他平台：`rename`
+
+use anyhow::Result;
+use std::collections::hash_map::RandomState;
+use std::fs::{File, OpenOptions};
+use std::hash::{BuildHasher, Hasher};
+use std::io::Write;
+use std::path::{Path, PathBuf};
+use std::time::{Duration, SystemTime};
+
+/// 超过该时长的遗留临时文件在下次写入时清理
+pub const ORPHAN_TEMP_AGE: Duration = Duration::from_secs(60 * 60);
+
+/// 替换被并发写入者短暂占用时的重试次数
+const REPLACE_RETRIES: u32 = 20;
+/// 两次替换重试之间的等待
+const REPLACE_RETRY_DELAY: Duration = Duration::from_millis(5);
+
+/// 原子写入选项
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct AtomicWriteOptions {
+    /// 替换前将临时文件刷到磁盘
+    pub fsync: bool,
+    /// 替换前将现有目标复制为 `{目标}.bak`
+    pub backup: bool,
+}
+
+impl Default for AtomicWriteOptions {
+    fn default() -> Self {
+        Self {
+            fsync: true,
+            backup: false,
+        }
+    }
+}
+
+/// 原子写入文件
+///
+/// # 参数
+/// - `path`: 目标文件路径（父目录不存在时自动创建）
+/// - `bytes`: 完整文件内容
+/// - `options`: 写入选项
+///
+/// # 返回
+/// - `Ok(())`: 目标文件已替换为 `bytes`
+/// - `Err`: 写入或替换失败（临时文件已删除，目标保持原样）
+pub fn atomic_write<P: AsRef<Path>>(
+    path: P,
+    bytes: &[u8],
+    options: &AtomicWriteOptions,
+) -> Result<()> {
+    let path = path.as_ref();
+    let dir = parent_dir(path);
+    std::fs::create_dir_all(&dir)?;
+    cleanup_orphaned_temps(path, ORPHAN_TEMP_AGE);
+
+    if options.backup && path.exists() {
+        let (backup_temp, mut file) = create_temp(path)?;
+        let copied = std::fs::read(path).and_then(|existing| file.write_all(&existing));
+        drop(file);
+        if let Err(e) = copied
+            .map_err(anyhow::Error::from)
+            .and_then(|_| replace(&backup_path(path), &backup_temp))
+        {
+            let _ = std::fs::remove_file(&backup_temp);
+            return Err(e);
+        }
+    }
+
+    let (temp, mut file) = create_temp(path)?;
+    let written = file.write_all(bytes).and_then(|_| {
+        if options.fsync {
+            file.sync_all()
+        } else {
+            Ok(())
+        }
+    });
+    drop(file);
+
+    if let Err(e) = written
+        .map_err(anyhow::Error::from)
+        .and_then(|_| replace(path, &temp))
+    {
+        let _ = std::fs::remove_file(&temp);
+        return Err(e);
+    }
+    Ok(())
+}
+
+/// 备份文件路径（`{目标}.bak`）
+pub fn backup_path(path: &Path) -> PathBuf {
+    let mut name = path.file_name().unwrap_or_default().to_os_string();
+    name.push(".bak");
+    path.with_file_name(name)
+}
+
+/// 尽力清理目标文件遗留的临时文件
+///
+/// # 参数
+/// - `path`: 目标文件路径
+/// - `max_age`: 只删除修改时间早于该时长的临时文件（避免删除并发写入者的文件）
+pub fn cleanup_orphaned_temps(path: &Path, max_age: Duration) {
+    let prefix = temp_prefix(path);
+    let Ok(entries) = std::fs::read_dir(parent_dir(path)) else {
+        return;
+    };
+    let now = SystemTime::now();
+
+    for entry in entries.flatten() {
+        let name = entry.file_name();
+        let name = name.to_string_lossy();
+        if !name.starts_with(&prefix) || !name.ends_with(".tmp") {
+            continue;
+        }
+        let expired = entry
+            .metadata()
+            .and_then(|m| m.modified())
+            .ok()
+            .and_then(|modified| now.duration_since(modified).ok())
+            .is_some_and(|age| age >= max_age);
+        if expired {
+            let _ = std::fs::remove_file(entry.path());
+        }
+    }
+}
+
+/// 目标所在目录（相对文件名时为当前目录）
+fn parent_dir(path: &Path) -> PathBuf {
+    match path.parent() {
+        Some(parent) if !parent.as_os_str().is_empty() => parent.to_path_buf(),
+        _ => PathBuf::from("."),
+    }
+}
+
+/// 临时文件名前缀（`.{文件名}.`）
+fn temp_prefix(path: &Path) -> String {
+    format!(
+        ".{}.",
+        path.file_name().unwrap_or_default().to_string_lossy()
+    )
+}
+
+/// 在目标目录中独占创建唯一的临时文件
+fn create_temp(path: &Path) -> Result<(PathBuf, File)> {
+    let dir = parent_dir(path);
+    let prefix = temp_prefix(path);
+    let pid = std::process::id();
+
+    loop {
+        let mut hasher = RandomState::new().build_hasher();
+        hasher.write_u128(
+            SystemTime::now()
+                .duration_since(SystemTime::UNIX_EPOCH)
+                .unwrap_or_default()
+                .as_nanos(),
+        );
+        let temp = dir.join(format!("{}{}.{:016x}.tmp", prefix, pid, hasher.finish()));
+
+        match OpenOptions::new().write(true).create_new(true).open(&temp) {
+            Ok(file) => return Ok((temp, file)),
+            Err(e) if e.kind() == std::io::ErrorKind::AlreadyExists => continue,
+            Err(e) => return Err(e.into()),
+        }
+    }
+}
+
+/// 以临时文件替换目标（并发替换导致的短暂占用会重试）
+fn replace(target: &Path, temp: &Path) -> Result<()> {
+    let mut attempt = 0;
+    loop {
+        match replace_once(target, temp) {
+            Ok(()) => return Ok(()),
+            Err(_) if attempt < REPLACE_RETRIES => {
+                attempt += 1;
+                std::thread::sleep(REPLACE_RETRY_DELAY);
+            }
+            Err(e) => return Err(e.into()),
+        }
+    }
+}
+
+#[cfg(windows)]
+fn replace_once(target: &Path, temp: &Path) -> std::io::Result<()> {
+    use windows::Win32::Storage::FileSystem::{
+        MOVEFILE_REPLACE_EXISTING, MOVEFILE_WRITE_THROUGH, MoveFileExW, REPLACEFILE_WRITE_THROUGH,
+        ReplaceFileW,
+    };
+    use windows::core::{HSTRING, PCWSTR};
+
+    let target_w = HSTRING::from(target.as_os_str());
+    let temp_w = HSTRING::from(temp.as_os_str());
+
+    unsafe {
+        if target.exists()
+            && ReplaceFileW(
+                &target_w,
+                &temp_w,
+                PCWSTR::null(),
+                REPLACEFILE_WRITE_THROUGH,
+                None,
+                None,
+            )
+            .is_ok()
+        {
+            return Ok(());
+        }
+        MoveFileExW(
+            &temp_w,
+            &target_w,
+            MOVEFILE_REPLACE_EXISTING | MOVEFILE_WRITE_THROUGH,
+        )
+        .map_err(std::io::Error::other)
+    }
+}
+
+#[cfg(not(windows))]
+fn replace_once(target: &Path, temp: &Path) -> std::io::Result<()> {
+    std::fs::rename(temp, target)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::sync::Arc;
+    use tempfile::TempDir;
+
+    /// 目录中遗留的临时文件
+    fn stray_temps(dir: &Path) -> Vec<String> {
+        std::fs::read_dir(dir)
+            .unwrap()
+            .flatten()
+            .map(|e| e.file_name().to_string_lossy().to_string())
+            .filter(|name| name.ends_with(".tmp"))
+            .collect()
+    }
+
+    #[test]
+    fn test_write_and_backup() {
+        let dir = TempDir::new().expect("创建临时目录失败");
+        let path = dir.path().join("nested").join("settings.json");
+        let backup = AtomicWriteOptions {
+            fsync: false,
+            backup: true,
+        };
+
+        atomic_write(&path, b"first", &backup).expect("写入失败");
+        assert_eq!(std::fs::read(&path).unwrap(), b"first");
+        assert!(!backup_path(&path).exists(), "首次写入无需备份");
+
+        atomic_write(&path, b"second", &backup).expect("写入失败");
+        assert_eq!(std::fs::read(&path).unwrap(), b"second");
+        assert_eq!(std::fs::read(backup_path(&path)).unwrap(), b"first");
+        assert!(stray_temps(path.parent().unwrap()).is_empty());
+        println!("✅ 原子写入与备份正常");
+    }
+
+    #[test]
+    fn test_concurrent_writers_never_interleave() {
+        const WRITERS: usize = 16;
+        const ROUNDS: usize = 20;
+
+        let dir = TempDir::new().expect("创建临时目录失败");
+        let path = Arc::new(dir.path().join("state.json"));
+
+        // 每个写入者的负载足够大，半成品或交错必然可见
+        let payload = |writer: usize| vec![b'a' + writer as u8; 64 * 1024];
+
+        let handles: Vec<_> = (0..WRITERS)
+            .map(|writer| {
+                let path = Arc::clone(&path);
+                std::thread::spawn(move || {
+                    for _ in 0..ROUNDS {
+                        atomic_write(path.as_path(), &payload(writer), &Default::default())
+                            .expect("并发写入失败");
+                    }
+                })
+            })
+            .collect();
+        for handle in handles {
+            handle.join().unwrap();
+        }
+
+        let content = std::fs::read(path.as_path()).unwrap();
+        assert!(
+            (0..WRITERS).any(|writer| content == payload(writer)),
+            "最终内容不是任何一个写入者的完整负载"
+        );
+        assert!(stray_temps(dir.path()).is_empty(), "残留临时文件");
+        println!("✅ {} 个线程并发写入后内容完整", WRITERS);
+    }
+
+    #[test]
+    fn test_orphaned_temps_are_cleaned_only_when_old() {
+        let dir = TempDir::new().expect("创建临时目录失败");
+        let path = dir.path().join("settings.json");
+        let orphan = dir.path().join(".settings.json.1234.00000000deadbeef.tmp");
+        let unrelated = dir.path().join(".other.json.1234.00000000deadbeef.tmp");
+        std::fs::write(&orphan, b"half").unwrap();
+        std::fs::write(&unrelated, b"half").unwrap();
+
+        cleanup_orphaned_temps(&path, ORPHAN_TEMP_AGE);
+        assert!(orphan.exists(), "新近的临时文件可能属于并发写入者");
+
+        cleanup_orphaned_temps(&path, Duration::ZERO);
+        assert!(!orphan.exists());
+        assert!(unrelated.exists(), "不属于该目标的临时文件不应删除");
+    }
+}
@@ -0,0 +1,239 @@
+//! 日志导出
+//!
+//! 逐行复制操作日志到新文件。默认原样保留压缩的 SDDL 字段
+//! （`{"alg":"zstd","b64":"..."}`），与合并、副本恢复的行为一致；
+//! 指定 `expand` 时将其还原为普通字符串，便于其他工具直接读取。
+
+use amberlock_types::MaybeCompressed;
+use anyhow::Result;
+use serde_json::Value;
+use std::fs::File;
+use std::io::{BufRead, BufReader, BufWriter, Write};
+use std::path::Path;
+use tempfile::NamedTempFile;
+
+/// 可能以压缩形式写入的字段
+pub const COMPRESSIBLE_FIELDS: [&str; 2] = ["sddl_before", "sddl_after"];
+
+/// 导出选项
+#[derive(Debug, Clone, Default)]
+pub struct ExportOptions {
+    /// 将压缩字段还原为普通字符串（命令行 `--expand`）
+    pub expand: bool,
+}
+
+/// 导出统计
+#[derive(Debug, Clone, Default, PartialEq, Eq)]
+pub struct ExportStats {
+    /// 写入输出的记录数
+    pub written: usize,
+    /// 被还原的压缩字段数
+    pub expanded: usize,
+    /// 无法解析被跳过的行数
+    pub skipped: usize,
+}
+
+/// 导出操作日志
+///
+/// # 参数
+/// - `input`: 源日志文件
+/// - `output`: 输出文件路径（写入同目录临时文件后原子替换）
+/// - `options`: 导出选项
+///
+/// # 注意
+/// 不展开时不解析记录内容，空行以外的每一行都原样写出
+pub fn export_log(input: &Path, output: &Path, options: &ExportOptions) -> Result<ExportStats> {
+    let mut stats = ExportStats::default();
+    let reader = BufReader::new(File::open(input)?);
+
+    let parent = output
+        .parent()
+        .filter(|p| !p.as_os_str().is_empty())
+        .unwrap_or_else(|| Path::new("."));
+    let mut out = BufWriter::new(NamedTempFile::new_in(parent)?);
+
+    for line in reader.lines() {
+        let line = line?;
+        let line = line.trim();
+        if line.is_empty() {
+            continue;
+        }
+        if !options.expand {
+            writeln!(out, "{}", line)?;
+            stats.written += 1;
+            continue;
+        }
+        let Ok(mut value) = serde_json::from_str::<Value>(line) else {
+            stats.skipped += 1;
+            continue;
+        };
+        stats.expanded += expand_compressed_fields(&mut value);
+        writeln!(out, "{}", value)?;
+        stats.written += 1;
+    }
+
+    let file = out.into_inner().map_err(|e| e.into_error())?;
+    file.as_file().sync_all()?;
+    file.persist(output)?;
+
+    Ok(stats)
+}
+
+/// 将记录中的压缩字段还原为普通字符串
+///
+/// # 返回值
+/// 被还原的字段数（无法解压的字段保持原样）
+pub fn expand_compressed_fields(record: &mut Value) -> usize {
+    let Some(object) = record.as_object_mut() else {
+        return 0;
+    };
+    let mut expanded = 0;
+    for field in COMPRESSIBLE_FIELDS {
+        let Some(value) = object.get_mut(field) else {
+            continue;
+        };
+        if !value.is_object() {
+            continue;
+        }
+        let Ok(compressed) = serde_json::from_value::<MaybeCompressed<String>>(value.clone())
+        else {
+            continue;
+        };
+        if let Ok(text) = compressed.expand() {
+            *value = Value::String(text.into_owned());
+            expanded += 1;
+        }
+    }
+    expanded
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::query::QueryBuilder;
+    use crate::{OperationLog, RecordStamper};
+    use amberlock_types::{
+        LabelLevel, LockRecord, OperationOrigin, ProtectMode, SDDL_COMPRESS_THRESHOLD, TargetKind,
+    };
+    use tempfile::TempDir;
+
+    fn fat_sddl() -> String {
+        let ace = "(A;OICI;0x1301bf;;;S-1-5-21-1004336348-1177238915-682003330-1001)";
+        format!("O:BAG:SYD:PAI{}S:(ML;;NW;;;HI)", ace.repeat(100))
+    }
+
+    fn record(stamper: &dyn RecordStamper, path: &str, compress: bool) -> LockRecord {
+        let sddl = if compress {
+            MaybeCompressed::compress(fat_sddl(), SDDL_COMPRESS_THRESHOLD)
+        } else {
+            fat_sddl().into()
+        };
+        LockRecord {
+            id: stamper.new_id(),
+            path: path.to_string(),
+            kind: TargetKind::File,
+            mode: ProtectMode::ReadOnly,
+            level_applied: LabelLevel::High,
+            time_utc: stamper.now(),
+            seq_no: stamper.next_seq(),
+            user_sid: "S-1-5-21-test".to_string(),
+            owner_before: None,
+            sddl_before: Some("S:(ML;;NW;;;ME)".into()),
+            sddl_after: Some(sddl),
+            status: "success".to_string(),
+            errors: vec![],
+            details: None,
+            authorized_by: None,
+            comment: None,
+            batch_id: None,
+            volume_root_acknowledged: false,
+            downgrade_reason: None,
+            origin: OperationOrigin::Unknown,
+            app_version: None,
+            file_id: None,
+            warnings: vec![],
+        }
+    }
+
+    #[test]
+    fn test_compressed_fields_round_trip_through_query_and_export() {
+        let dir = TempDir::new().expect("创建临时目录失败");
+        let log_path = dir.path().join("log.ndjson");
+        let log = OperationLog::open(&log_path).expect("打开日志失败");
+        log.append(&record(log.stamper(), r"C:\a.txt", true))
+            .expect("写入失败");
+        log.append(&record(log.stamper(), r"C:\b.txt", false))
+            .expect("写入失败");
+        log.flush().expect("刷新失败");
+
+        // 查询后按类型反序列化：两种形式读出的文本相同
+        let rows = QueryBuilder::new(&log_path).execute().expect("查询失败");
+        let records: Vec<LockRecord> = rows
+            .into_iter()
+            .map(|v| serde_json::from_value(v).expect("应为 LockRecord"))
+            .collect();
+        assert!(records[0].sddl_after.as_ref().unwrap().is_compressed());
+        assert!(!records[1].sddl_after.as_ref().unwrap().is_compressed());
+        for record in &records {
+            assert_eq!(
+                record.sddl_after_text().as_deref(),
+                Some(fat_sddl().as_str())
+            );
+            assert_eq!(
+                record.sddl_before_text().as_deref(),
+                Some("S:(ML;;NW;;;ME)")
+            );
+        }
+
+        // 默认导出逐字节保留
+        let plain_out = dir.path().join("plain.ndjson");
+        let stats = export_log(&log_path, &plain_out, &ExportOptions::default()).expect("导出失败");
+        assert_eq!(stats.written, 2);
+        assert_eq!(stats.expanded, 0);
+        assert_eq!(
+            std::fs::read_to_string(&plain_out).unwrap(),
+            std::fs::read_to_string(&log_path).unwrap()
+        );
+
+        // 合并（含校验）同样原样保留
+        let merged = dir.path().join("merged.ndjson");
+        let stats = crate::merge_logs(
+            std::slice::from_ref(&log_path),
+            &merged,
+            &Default::default(),
+        )
+        .expect("合并失败");
+        assert_eq!(stats.written, 2);
+        assert_eq!(
+            std::fs::read_to_string(&merged).unwrap(),
+            std::fs::read_to_string(&log_path).unwrap()
+        );
+
+        // --expand 后不再包含压缩对象
+        let expanded_out = dir.path().join("expanded.ndjson");
+        let stats = export_log(&log_path, &expanded_out, &ExportOptions { expand: true })
+            .expect("导出失败");
+        assert_eq!(stats.written, 2);
+        assert_eq!(stats.expanded, 1);
+        let text = std::fs::read_to_string(&expanded_out).unwrap();
+        assert!(!text.contains("\"alg\""));
+        for line in text.lines() {
+            let value: Value = serde_json::from_str(line).unwrap();
+            assert_eq!(value["sddl_after"].as_str(), Some(fat_sddl().as_str()));
+        }
+        println!("✅ 压缩字段经查询与导出往返正确");
+    }
+
+    #[test]
+    fn test_expand_leaves_plain_and_unknown_fields() {
+        let mut value = serde_json::json!({
+            "sddl_before": "S:(ML;;NW;;;HI)",
+            "sddl_after": {"alg": "lz4", "b64": "AAAA"},
+            "details": {"alg": "zstd", "b64": "AAAA"},
+        });
+        let before = value.clone();
+        assert_eq!(expand_compressed_fields(&mut value), 0);
+        assert_eq!(value, before);
+        println!("✅ 无法解压的字段保持原样");
+    }
+}
@@ -0,0 +1,315 @@
+//! 由外部数据派生的文件名
+//!
+//! 失败明细旁路文件等附属文件的文件名包含批次 id、SID、路径片段等外部数据。
+//! 直接拼接可能产生含非法字符的名称、在很深的数据目录中超出 `MAX_PATH`，
+//! 或在截断后与其他输入重名。`safe_derived_filename` 保证生成的路径：
+//! - 只包含 `[A-Za-z0-9_-]`、组件分隔符 `.`、转义符 `%` 与哈希标记 `~`；
+//!   其余字符（含 `.`、`%`、`~` 本身）按 UTF-8 字节百分号编码，编码是单射
+//! - 过长的组件截断后追加原始内容的 64 位稳定哈希，整个名称放不下时对全部组件取哈希，
+//!   不同输入重名的概率可忽略，同一输入在任何进程与版本中结果相同
+//! - 第一个组件不是保留设备名（`CON`、`NUL`、`COM1` 等）
+//! - 绝对路径长度（UTF-16 码元）不超过调用方给出的上限，见 `path_limit`
+
+use crate::stats::stable_hash;
+use amberlock_types::AmberlockError;
+use anyhow::Result;
+use std::path::{Path, PathBuf};
+
+/// 常规 Win32 路径的最大长度（`MAX_PATH` 去掉结尾 NUL）
+pub const LEGACY_MAX_PATH: usize = 259;
+/// `\\?\` 扩展长度路径的最大长度
+pub const EXTENDED_MAX_PATH: usize = 32_767;
+/// 单个组件编码后的最大长度（超出时截断并追加哈希）
+pub const MAX_DERIVED_COMPONENT: usize = 64;
+/// NTFS 单个文件名的最大长度
+const MAX_FILE_NAME: usize = 255;
+/// 扩展长度前缀
+const EXTENDED_PREFIX: &str = r"\\?\";
+
+/// 保留设备名（`COM0`–`COM9`、`LPT0`–`LPT9` 另行判断）
+const RESERVED_NAMES: [&str; 4] = ["CON", "PRN", "AUX", "NUL"];
+
+/// 目录下派生文件的路径长度上限
+///
+/// 目录已是 `\\?\` 形式时按扩展长度计算，否则按 `MAX_PATH`
+pub fn path_limit(base_dir: &Path) -> usize {
+    if base_dir.to_string_lossy().starts_with(EXTENDED_PREFIX) {
+        EXTENDED_MAX_PATH
+    } else {
+        LEGACY_MAX_PATH
+    }
+}
+
+/// 由外部数据派生安全的文件路径
+///
+/// # 参数
+/// - `base_dir`: 文件所在目录（不要求存在；相对路径按当前目录计算长度）
+/// - `components`: 文件名组成部分，编码后以 `.` 连接，不能为空
+/// - `extension`: 扩展名（不含 `.`，只允许 ASCII 字母与数字）
+/// - `max_total_len`: 绝对路径的最大长度，通常为 `path_limit(base_dir)`
+///
+/// # 返回
+/// - `Ok(path)`: `base_dir` 下的文件路径
+/// - `Err`: 组成部分为空、扩展名无效、第一个组件为保留设备名（`ReservedName`），
+///   或目录本身过深以致最短的哈希名称也放不下
+pub fn safe_derived_filename(
+    base_dir: &Path,
+    components: &[&str],
+    extension: &str,
+    max_total_len: usize,
+) -> Result<PathBuf> {
+    if components.is_empty() || components.iter().any(|c| c.is_empty()) {
+        anyhow::bail!("派生文件名的组成部分不能为空");
+    }
+    if extension.is_empty() || !extension.chars().all(|c| c.is_ascii_alphanumeric()) {
+        anyhow::bail!("无效的扩展名: {:?}", extension);
+    }
+    if is_reserved(components[0]) {
+        return Err(AmberlockError::ReservedName(components[0].to_string()).into());
+    }
+
+    let parts: Vec<String> = components.iter().map(|c| encode_component(c)).collect();
+    let stem = parts.join(".");
+
+    let dir = std::path::absolute(base_dir).unwrap_or_else(|_| base_dir.to_path_buf());
+    let dir_text = dir.to_string_lossy();
+    let separator = usize::from(!dir_text.ends_with(['\\', '/']));
+    let budget = max_total_len
+        .saturating_sub(dir_text.encode_utf16().count() + separator)
+        .min(MAX_FILE_NAME);
+
+    if stem.len() + 1 + extension.len() <= budget {
+        return Ok(base_dir.join(format!("{}.{}", stem, extension)));
+    }
+
+    // 整个名称放不下：保留尽可能长的前缀，以全部组件的哈希区分
+    let suffix = format!("~{:016x}.{}", hash_components(components), extension);
+    if budget < suffix.len() + 1 {
+        anyhow::bail!(
+            "目录过深，无法在 {} 个字符内派生文件名: {}",
+            max_total_len,
+            base_dir.display()
+        );
+    }
+    let prefix = truncate_encoded(&stem, budget - suffix.len());
+    Ok(base_dir.join(format!("{}{}", prefix, suffix)))
+}
+
+/// 百分号编码单个组件，过长时截断并追加原始内容的哈希
+fn encode_component(component: &str) -> String {
+    let mut encoded = String::with_capacity(component.len());
+    for byte in component.bytes() {
+        if byte.is_ascii_alphanumeric() || byte == b'-' || byte == b'_' {
+            encoded.push(byte as char);
+        } else {
+            encoded.push_str(&format!("%{:02X}", byte));
+        }
+    }
+    if encoded.len() <= MAX_DERIVED_COMPONENT {
+        return encoded;
+    }
+    let hash = format!("~{:016x}", stable_hash(component.as_bytes()));
+    let prefix = truncate_encoded(&encoded, MAX_DERIVED_COMPONENT - hash.len());
+    format!("{}{}", prefix, hash)
+}
+
+/// 截断编码后的文本，不拆开 `%XX` 转义
+fn truncate_encoded(encoded: &str, max_len: usize) -> &str {
+    if encoded.len() <= max_len {
+        return encoded;
+    }
+    let mut cut = max_len;
+    if let Some(offset) = encoded[max_len.saturating_sub(2)..max_len].find('%') {
+        cut = max_len.saturating_sub(2) + offset;
+    }
+    &encoded[..cut]
+}
+
+/// 全部组件的稳定哈希（带长度前缀，组件边界不同的输入结果不同）
+fn hash_components(components: &[&str]) -> u64 {
+    let mut bytes = Vec::new();
+    for component in components {
+        bytes.extend_from_slice(&(component.len() as u64).to_le_bytes());
+        bytes.extend_from_slice(component.as_bytes());
+    }
+    stable_hash(&bytes)
+}
+
+/// 组件是否为保留设备名（不区分大小写，忽略末尾空格与扩展名）
+fn is_reserved(component: &str) -> bool {
+    let stem = component.split('.').next().unwrap_or_default().trim_end();
+    let upper = stem.to_ascii_uppercase();
+    RESERVED_NAMES.contains(&upper.as_str())
+        || matches!(
+            upper.as_bytes(),
+            [b'C', b'O', b'M', b'0'..=b'9'] | [b'L', b'P', b'T', b'0'..=b'9']
+        )
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::collections::HashMap;
+
+    /// 测试用确定性伪随机数（splitmix64）
+    fn next(state: &mut u64) -> u64 {
+        *state = state.wrapping_add(0x9e37_79b9_7f4a_7c15);
+        let mut z = *state;
+        z = (z ^ (z >> 30)).wrapping_mul(0xbf58_476d_1ce4_e5b9);
+        z = (z ^ (z >> 27)).wrapping_mul(0x94d0_49bb_1331_11eb);
+        z ^ (z >> 31)
+    }
+
+    /// 由易出问题的片段随机拼出组件
+    fn adversarial_component(state: &mut u64) -> String {
+        const PIECES: [&str; 16] = [
+            "S-1-5-21-1004336348-1177238915-682003330-1001",
+            "文档",
+            "🔒",
+            "e\u{301}",
+            "/",
+            "\\",
+            "..",
+            ".",
+            " ",
+            "%2E",
+            "~",
+            ":",
+            "*?\"<>|",
+            "\u{7}",
+            "CON",
+            "a",
+        ];
+        let count = 1 + next(state) % 24;
+        (0..count)
+            .map(|_| PIECES[(next(state) % PIECES.len() as u64) as usize])
+            .collect()
+    }
+
+    fn assert_valid(path: &Path, limit: usize) {
+        let name = path.file_name().unwrap().to_string_lossy().to_string();
+        assert!(
+            name.bytes()
+                .all(|b| b.is_ascii_alphanumeric() || b"-_.%~".contains(&b)),
+            "含非法字符: {}",
+            name
+        );
+        assert!(name.len() <= MAX_FILE_NAME);
+        assert!(!is_reserved(&name), "保留设备名: {}", name);
+        assert!(!name.ends_with('.') && !name.ends_with(' '));
+        let absolute = std::path::absolute(path).unwrap();
+        assert!(
+            absolute.to_string_lossy().encode_utf16().count() <= limit,
+            "路径过长: {}",
+            absolute.display()
+        );
+    }
+
+    #[test]
+    fn test_adversarial_components_are_valid_unique_and_stable() {
+        let dir = tempfile::TempDir::new().expect("创建临时目录失败");
+        let shallow = dir.path().to_path_buf();
+        // 模拟很深的数据目录，只留下几十个字符给文件名
+        let deep = shallow.join("d".repeat(LEGACY_MAX_PATH - shallow.as_os_str().len() - 60));
+
+        let mut state = 0x5eed;
+        let mut inputs: Vec<Vec<String>> = vec![
+            vec!["S-1-5-21-".to_string() + &"4294967295-".repeat(40)],
+            vec!["amberlock-log".to_string(), "failures-b1".to_string()],
+            vec!["a.b".to_string()],
+            vec!["a".to_string(), "b".to_string()],
+            vec!["a%2Eb".to_string()],
+            vec!["log".to_string(), "CON".to_string()],
+            vec!["..".to_string(), "/etc/passwd".to_string()],
+        ];
+        for _ in 0..500 {
+            let parts = 1 + next(&mut state) % 3;
+            inputs.push(
+                (0..parts)
+                    .map(|_| adversarial_component(&mut state))
+                    .collect(),
+            );
+        }
+        inputs.sort();
+        inputs.dedup();
+        inputs.retain(|parts| !is_reserved(&parts[0]));
+
+        for base in [&shallow, &deep] {
+            let limit = path_limit(base);
+            let mut seen: HashMap<PathBuf, &Vec<String>> = HashMap::new();
+            for parts in &inputs {
+                let components: Vec<&str> = parts.iter().map(String::as_str).collect();
+                let path = safe_derived_filename(base, &components, "ndjson", limit)
+                    .unwrap_or_else(|e| panic!("派生失败 {:?}: {}", parts, e));
+                assert_valid(&path, limit);
+                assert_eq!(path.parent(), Some(base.as_path()));
+                assert!(path.to_string_lossy().ends_with(".ndjson"));
+                // 同一输入结果相同
+                let again = safe_derived_filename(base, &components, "ndjson", limit).unwrap();
+                assert_eq!(path, again);
+                if let Some(previous) = seen.insert(path.clone(), parts) {
+                    panic!("{:?} 与 {:?} 重名: {}", previous, parts, path.display());
+                }
+            }
+        }
+
+        // 跨进程、跨版本稳定：固定输入的结果固定
+        let long_sid = "S-1-5-21-".to_string() + &"4294967295-".repeat(40);
+        let path = safe_derived_filename(&shallow, &[&long_sid], "ndjson", LEGACY_MAX_PATH)
+            .expect("派生失败");
+        assert_eq!(
+            path.file_name().unwrap().to_string_lossy(),
+            "S-1-5-21-4294967295-4294967295-4294967295-42949~efc7b0d35883dcd6.ndjson"
+        );
+        println!("✅ 对抗性输入生成的文件名合法、唯一且稳定");
+    }
+
+    #[test]
+    fn test_short_names_are_readable() {
+        let path = safe_derived_filename(
+            Path::new("logs"),
+            &["amberlock-log", "failures-b1"],
+            "ndjson",
+            LEGACY_MAX_PATH,
+        )
+        .expect("派生失败");
+        assert_eq!(
+            path,
+            Path::new("logs").join("amberlock-log.failures-b1.ndjson")
+        );
+
+        let path = safe_derived_filename(Path::new("logs"), &["a.b", "文"], "log", LEGACY_MAX_PATH)
+            .expect("派生失败");
+        assert_eq!(path.file_name().unwrap(), "a%2Eb.%E6%96%87.log");
+    }
+
+    #[test]
+    fn test_invalid_inputs_are_refused() {
+        let base = Path::new("data");
+        for reserved in ["CON", "nul", "Com1", "LPT9 ", "aux.txt"] {
+            let err = safe_derived_filename(base, &[reserved, "x"], "ndjson", LEGACY_MAX_PATH)
+                .expect_err("保留设备名应被拒绝");
+            assert!(matches!(
+                err.downcast_ref::<AmberlockError>(),
+                Some(AmberlockError::ReservedName(_))
+            ));
+        }
+        // 非首个组件中的保留名不影响设备名判定
+        assert!(safe_derived_filename(base, &["log", "CON"], "ndjson", LEGACY_MAX_PATH).is_ok());
+        assert!(safe_derived_filename(base, &["console"], "ndjson", LEGACY_MAX_PATH).is_ok());
+
+        assert!(safe_derived_filename(base, &[], "ndjson", LEGACY_MAX_PATH).is_err());
+        assert!(safe_derived_filename(base, &["a", ""], "ndjson", LEGACY_MAX_PATH).is_err());
+        assert!(safe_derived_filename(base, &["a"], "nd/json", LEGACY_MAX_PATH).is_err());
+        assert!(safe_derived_filename(base, &["a"], "", LEGACY_MAX_PATH).is_err());
+
+        // 目录本身就接近上限时拒绝
+        let deep = std::path::absolute(base)
+            .unwrap()
+            .join("d".repeat(LEGACY_MAX_PATH));
+        assert!(safe_derived_filename(&deep, &["a"], "ndjson", LEGACY_MAX_PATH).is_err());
+        // 扩展长度目录放宽上限
+        let verbatim = PathBuf::from(format!(r"\\?\C:\{}", "d".repeat(LEGACY_MAX_PATH)));
+        assert_eq!(path_limit(&verbatim), EXTENDED_MAX_PATH);
+    }
+}
@@ -0,0 +1,911 @@
+//! 数据目录占用报告与清理
+//!
+//! 操作日志、统计缓存、重新验证检查点、失败旁路文件、诊断跟踪、副本暂存等
+//! AmberLock 自有文件散落在数据目录中，长期运行后可能占用大量磁盘。
+//! `storage_report` 按已知文件模式登记表（`ARTIFACT_KINDS`，每个功能登记自己的文件）
+//! 归类并统计数据目录中的文件，按默认保留期估算可释放的空间；
+//! `cleanup` 执行用户确认过的 `CleanupPlan`。
+//!
+//! 保险库、设置、正在使用的操作日志、保护清单与副本暂存属于受保护文件，
+//! 任何清理计划都不会删除；不属于 AmberLock 的文件不会出现在报告中。
+
+use crate::atomic::ORPHAN_TEMP_AGE;
+use amberlock_types::Settings;
+use anyhow::Result;
+use std::fmt::{Display, Formatter};
+use std::path::{Path, PathBuf};
+use std::time::{Duration, SystemTime};
+use time::OffsetDateTime;
+
+/// 设置文件名（位于配置目录，可能与数据目录相同）
+pub const SETTINGS_FILE_NAME: &str = "amberlock-settings.json";
+
+/// 日志副本暂存文件名（位于数据目录，副本不可用时暂存记录）
+pub const REPLICA_SPOOL_FILE_NAME: &str = "amberlock-replica-spool.ndjson";
+
+/// 诊断日志文件名（与操作日志同目录，记录 panic 消息与回溯）
+pub const DIAGNOSTICS_FILE_NAME: &str = "amberlock-diagnostics.log";
+
+/// 诊断跟踪文件名前缀（`amberlock-trace-<时间戳>.ndjson`）
+pub const TRACE_FILE_PREFIX: &str = "amberlock-trace-";
+
+const DAY: Duration = Duration::from_secs(24 * 60 * 60);
+
+/// AmberLock 自有文件的类别
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+pub enum ArtifactCategory {
+    OperationLog,
+    Vault,
+    Settings,
+    Inventory,
+    ReplicaSpool,
+    Replica,
+    Backup,
+    Diagnostics,
+    StatsCache,
+    RescanState,
+    FailureSpill,
+    Trace,
+    OrphanTemp,
+}
+
+/// 类别的保留策略
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Retention {
+    /// 正在使用的数据，任何计划都不会删除
+    Protected,
+    /// 只报告占用，不提供清理
+    Keep,
+    /// 修改时间早于保留期的文件可清理
+    MaxAge(Duration),
+}
+
+/// 文件模式登记项
+pub struct ArtifactKind {
+    pub category: ArtifactCategory,
+    /// 命令行与界面使用的标识
+    pub key: &'static str,
+    pub label: &'static str,
+    /// 默认保留策略（`CleanupPlan` 可为 `MaxAge` 类别指定其他保留期）
+    pub retention: Retention,
+    matches: fn(&str, &DataLayout) -> bool,
+}
+
+/// 文件模式登记表（按顺序匹配，第一个匹配的类别生效）
+pub static ARTIFACT_KINDS: &[ArtifactKind] = &[
+    // 原子写入（`atomic`）：`.{目标}.{pid}.{随机}.tmp`，先于目标本身匹配
+    ArtifactKind {
+        category: ArtifactCategory::OrphanTemp,
+        key: "orphan_temp",
+        label: "遗留临时文件",
+        retention: Retention::MaxAge(ORPHAN_TEMP_AGE),
+        matches: |name, layout| {
+            temp_target(name).is_some_and(|target| layout.classify(target).is_some())
+        },
+    },
+    // 原子写入（`atomic`）：`{目标}.bak`
+    ArtifactKind {
+        category: ArtifactCategory::Backup,
+        key: "backup",
+        label: "备份文件",
+        retention: Retention::Keep,
+        matches: |name, layout| {
+            name.strip_suffix(".bak")
+                .is_some_and(|target| layout.classify(target).is_some())
+        },
+    },
+    ArtifactKind {
+        category: ArtifactCategory::OperationLog,
+        key: "operation_log",
+        label: "操作日志",
+        retention: Retention::Protected,
+        matches: |name, layout| name == layout.log_name,
+    },
+    ArtifactKind {
+        category: ArtifactCategory::Vault,
+        key: "vault",
+        label: "保险库",
+        retention: Retention::Protected,
+        matches: |name, layout| layout.vault_name.as_deref() == Some(name),
+    },
+    ArtifactKind {
+        category: ArtifactCategory::Settings,
+        key: "settings",
+        label: "设置",
+        retention: Retention::Protected,
+        matches: |name, _| name == SETTINGS_FILE_NAME,
+    },
+    // 保护清单：与 `amberlock_core::inventory_path` 一致
+    ArtifactKind {
+        category: ArtifactCategory::Inventory,
+        key: "inventory",
+        label: "保护清单",
+        retention: Retention::Protected,
+        matches: |name, layout| name == format!("{}.inventory.json", layout.log_name),
+    },
+    // 日志副本（`replica`）：暂存与溢出计数中是尚未补写到副本的记录
+    ArtifactKind {
+        category: ArtifactCategory::ReplicaSpool,
+        key: "replica_spool",
+        label: "副本暂存",
+        retention: Retention::Protected,
+        matches: |name, _| {
+            name.strip_prefix(REPLICA_SPOOL_FILE_NAME)
+                .is_some_and(|rest| rest.is_empty() || rest == ".overflow")
+        },
+    },
+    ArtifactKind {
+        category: ArtifactCategory::Diagnostics,
+        key: "diagnostics",
+        label: "诊断日志",
+        retention: Retention::Keep,
+        matches: |name, _| name == DIAGNOSTICS_FILE_NAME,
+    },
+    // 增量统计（`stats`）：删除后下次统计时重建
+    ArtifactKind {
+        category: ArtifactCategory::StatsCache,
+        key: "stats_cache",
+        label: "统计缓存",
+        retention: Retention::MaxAge(Duration::ZERO),
+        matches: |name, layout| name == format!("{}.stats.json", layout.log_name),
+    },
+    // 分块重新验证：与 `amberlock_core::rescan_state_path` 一致，删除后从头验证
+    ArtifactKind {
+        category: ArtifactCategory::RescanState,
+        key: "rescan_state",
+        label: "重新验证检查点",
+        retention: Retention::MaxAge(Duration::from_secs(7 * 24 * 60 * 60)),
+        matches: |name, layout| {
+            name.strip_prefix(&layout.log_name)
+                .and_then(|rest| rest.strip_prefix(".rescan-"))
+                .is_some_and(|rest| rest.ends_with(".json"))
+        },
+    },
+    // 失败旁路文件：与 `amberlock_core::FailureSpill::sidecar_path` 一致
+    ArtifactKind {
+        category: ArtifactCategory::FailureSpill,
+        key: "failure_spill",
+        label: "失败旁路文件",
+        retention: Retention::MaxAge(Duration::from_secs(30 * 24 * 60 * 60)),
+        matches: |name, layout| {
+            name.strip_prefix(&layout.log_stem)
+                .and_then(|rest| rest.strip_prefix(".failures-"))
+                .is_some_and(|rest| rest.ends_with(".ndjson"))
+        },
+    },
+    ArtifactKind {
+        category: ArtifactCategory::Trace,
+        key: "trace",
+        label: "诊断跟踪",
+        retention: Retention::MaxAge(Duration::from_secs(30 * 24 * 60 * 60)),
+        matches: |name, _| name.starts_with(TRACE_FILE_PREFIX) && name.ends_with(".ndjson"),
+    },
+];
+
+impl ArtifactCategory {
+    /// 登记项
+    pub fn kind(self) -> &'static ArtifactKind {
+        ARTIFACT_KINDS
+            .iter()
+            .find(|kind| kind.category == self)
+            .unwrap_or(&REPLICA_KIND)
+    }
+
+    /// 按标识查找类别（命令行参数）
+    pub fn from_key(key: &str) -> Option<Self> {
+        ARTIFACT_KINDS
+            .iter()
+            .chain(std::iter::once(&REPLICA_KIND))
+            .find(|kind| kind.key.eq_ignore_ascii_case(key))
+            .map(|kind| kind.category)
+    }
+
+    pub fn key(self) -> &'static str {
+        self.kind().key
+    }
+
+    pub fn label(self) -> &'static str {
+        self.kind().label
+    }
+
+    pub fn retention(self) -> Retention {
+        self.kind().retention
+    }
+
+    /// 是否可以加入清理计划
+    pub fn is_cleanable(self) -> bool {
+        matches!(self.retention(), Retention::MaxAge(_))
+    }
+}
+
+/// 日志副本（`Settings::replica_path`）：位于数据目录之外，不按文件名匹配，只报告占用
+static REPLICA_KIND: ArtifactKind = ArtifactKind {
+    category: ArtifactCategory::Replica,
+    key: "replica",
+    label: "日志副本",
+    retention: Retention::Keep,
+    matches: |_, _| false,
+};
+
+/// 由设置推导出的自有文件名
+struct DataLayout {
+    /// 操作日志文件名
+    log_name: String,
+    /// 操作日志文件名去掉扩展名（失败旁路文件的前缀）
+    log_stem: String,
+    /// 保险库文件名
+    vault_name: Option<String>,
+}
+
+impl DataLayout {
+    fn from_settings(settings: &Settings) -> Self {
+        let log_path = Path::new(&settings.log_path);
+        let file_name = |path: &Path| {
+            path.file_name()
+                .map(|name| name.to_string_lossy().to_string())
+        };
+        Self {
+            log_name: file_name(log_path).unwrap_or_default(),
+            log_stem: log_path
+                .file_stem()
+                .map(|stem| stem.to_string_lossy().to_string())
+                .unwrap_or_else(|| "amberlock".to_string()),
+            vault_name: file_name(Path::new(&settings.vault_path)),
+        }
+    }
+
+    /// 按登记表归类文件名（不属于 AmberLock 时为 `None`）
+    fn classify(&self, name: &str) -> Option<ArtifactCategory> {
+        if name.is_empty() {
+            return None;
+        }
+        ARTIFACT_KINDS
+            .iter()
+            .find(|kind| (kind.matches)(name, self))
+            .map(|kind| kind.category)
+    }
+}
+
+/// 临时文件名 `.{目标}.{pid}.{随机}.tmp` 中的目标文件名
+fn temp_target(name: &str) -> Option<&str> {
+    let inner = name.strip_prefix('.')?.strip_suffix(".tmp")?;
+    let mut parts = inner.rsplitn(3, '.');
+    let (_random, _pid) = (parts.next()?, parts.next()?);
+    parts.next()
+}
+
+/// 单个文件
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct ArtifactFile {
+    pub path: PathBuf,
+    pub size: u64,
+    /// 修改时间（读不到时为 `None`，不会被清理）
+    pub modified: Option<SystemTime>,
+}
+
+impl ArtifactFile {
+    /// 修改时间是否早于 `now - max_age`
+    fn older_than(&self, max_age: Duration, now: SystemTime) -> bool {
+        self.modified
+            .and_then(|modified| now.duration_since(modified).ok())
+            .is_some_and(|age| age >= max_age)
+    }
+}
+
+/// 一个类别的占用
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct CategoryUsage {
+    pub category: ArtifactCategory,
+    pub files: Vec<ArtifactFile>,
+    /// 按默认保留期可清理的文件数与字节数
+    pub reclaimable_files: usize,
+    pub reclaimable_bytes: u64,
+}
+
+impl CategoryUsage {
+    fn new(category: ArtifactCategory, files: Vec<ArtifactFile>, now: SystemTime) -> Self {
+        let (reclaimable_files, reclaimable_bytes) = match category.retention() {
+            Retention::MaxAge(max_age) => files
+                .iter()
+                .filter(|file| file.older_than(max_age, now))
+                .fold((0, 0), |(count, bytes), file| {
+                    (count + 1, bytes + file.size)
+                }),
+            Retention::Protected | Retention::Keep => (0, 0),
+        };
+        Self {
+            category,
+            files,
+            reclaimable_files,
+            reclaimable_bytes,
+        }
+    }
+
+    pub fn total_bytes(&self) -> u64 {
+        self.files.iter().map(|file| file.size).sum()
+    }
+
+    pub fn oldest(&self) -> Option<SystemTime> {
+        self.files.iter().filter_map(|file| file.modified).min()
+    }
+
+    pub fn newest(&self) -> Option<SystemTime> {
+        self.files.iter().filter_map(|file| file.modified).max()
+    }
+}
+
+impl Display for CategoryUsage {
+    /// 形如 "失败旁路文件：3 个，1.2 MB（2025-01-02 ~ 2025-03-01）；超过 30 天的 2 个共 800.0 KB 可清理"
+    fn fmt(&self, f: &mut Formatter<'_>) -> std::fmt::Result {
+        write!(
+            f,
+            "{}：{} 个，{}",
+            self.category.label(),
+            self.files.len(),
+            format_bytes(self.total_bytes())
+        )?;
+        match (self.oldest(), self.newest()) {
+            (Some(oldest), Some(newest)) if date(oldest) != date(newest) => {
+                write!(f, "（{} ~ {}）", date(oldest), date(newest))?
+            }
+            (_, Some(newest)) => write!(f, "（{}）", date(newest))?,
+            _ => {}
+        }
+        match self.category.retention() {
+            Retention::Protected => write!(f, "；正在使用，不会清理"),
+            Retention::Keep => Ok(()),
+            Retention::MaxAge(_) if self.reclaimable_files == 0 => Ok(()),
+            Retention::MaxAge(max_age) if max_age < DAY => write!(
+                f,
+                "；{} 个共 {} 可清理",
+                self.reclaimable_files,
+                format_bytes(self.reclaimable_bytes)
+            ),
+            Retention::MaxAge(max_age) => write!(
+                f,
+                "；超过 {} 天的 {} 个共 {} 可清理",
+                max_age.as_secs() / DAY.as_secs(),
+                self.reclaimable_files,
+                format_bytes(self.reclaimable_bytes)
+            ),
+        }
+    }
+}
+
+/// 数据目录占用报告
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct StorageReport {
+    /// 扫描过的目录
+    pub directories: Vec<PathBuf>,
+    /// 有文件的类别（按登记表顺序）
+    pub categories: Vec<CategoryUsage>,
+}
+
+impl StorageReport {
+    pub fn total_bytes(&self) -> u64 {
+        self.categories.iter().map(CategoryUsage::total_bytes).sum()
+    }
+
+    /// 按默认保留期可释放的字节数
+    pub fn reclaimable_bytes(&self) -> u64 {
+        self.categories
+            .iter()
+            .map(|usage| usage.reclaimable_bytes)
+            .sum()
+    }
+
+    pub fn category(&self, category: ArtifactCategory) -> Option<&CategoryUsage> {
+        self.categories
+            .iter()
+            .find(|usage| usage.category == category)
+    }
+
+    /// 按默认保留期清理所有有可释放文件的类别
+    pub fn recommended_plan(&self) -> CleanupPlan {
+        self.categories
+            .iter()
+            .filter(|usage| usage.reclaimable_files > 0)
+            .fold(CleanupPlan::default(), |plan, usage| {
+                plan.with_category(usage.category)
+            })
+    }
+}
+
+impl Display for StorageReport {
+    fn fmt(&self, f: &mut Formatter<'_>) -> std::fmt::Result {
+        write!(
+            f,
+            "AmberLock 数据共 {}，可释放 {}",
+            format_bytes(self.total_bytes()),
+            format_bytes(self.reclaimable_bytes())
+        )?;
+        for usage in &self.categories {
+            write!(f, "\n  {}", usage)?;
+        }
+        Ok(())
+    }
+}
+
+/// 清理计划（用户勾选的类别及其保留期）
+#[derive(Debug, Clone, Default, PartialEq, Eq)]
+pub struct CleanupPlan {
+    items: Vec<(ArtifactCategory, Duration)>,
+}
+
+impl CleanupPlan {
+    /// 按默认保留期清理该类别（不可清理的类别会在执行时被拒绝）
+    pub fn with_category(self, category: ArtifactCategory) -> Self {
+        let max_age = match category.retention() {
+            Retention::MaxAge(max_age) => max_age,
+            Retention::Protected | Retention::Keep => Duration::MAX,
+        };
+        self.with_category_older_than(category, max_age)
+    }
+
+    /// 清理该类别中修改时间早于 `max_age` 的文件
+    pub fn with_category_older_than(
+        mut self,
+        category: ArtifactCategory,
+        max_age: Duration,
+    ) -> Self {
+        self.items.retain(|(existing, _)| *existing != category);
+        self.items.push((category, max_age));
+        self
+    }
+
+    pub fn categories(&self) -> impl Iterator<Item = ArtifactCategory> + '_ {
+        self.items.iter().map(|(category, _)| *category)
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.items.is_empty()
+    }
+}
+
+/// 清理结果
+#[derive(Debug, Clone, Default, PartialEq, Eq)]
+pub struct CleanupResult {
+    /// 已删除的文件
+    pub removed: Vec<ArtifactFile>,
+    /// 删除失败的文件及原因
+    pub failed: Vec<(PathBuf, String)>,
+    /// 计划中被拒绝的受保护或仅报告的类别
+    pub refused: Vec<ArtifactCategory>,
+}
+
+impl CleanupResult {
+    pub fn freed_bytes(&self) -> u64 {
+        self.removed.iter().map(|file| file.size).sum()
+    }
+}
+
+impl Display for CleanupResult {
+    fn fmt(&self, f: &mut Formatter<'_>) -> std::fmt::Result {
+        write!(
+            f,
+            "已清理 {} 个文件，释放 {}",
+            self.removed.len(),
+            format_bytes(self.freed_bytes())
+        )?;
+        if !self.failed.is_empty() {
+            write!(f, "，{} 个删除失败", self.failed.len())?;
+        }
+        if !self.refused.is_empty() {
+            let labels: Vec<&str> = self.refused.iter().map(|c| c.label()).collect();
+            write!(f, "，已跳过不可清理的类别：{}", labels.join("、"))?;
+        }
+        Ok(())
+    }
+}
+
+/// 统计数据目录中 AmberLock 自有文件的占用
+///
+/// # 参数
+/// - `settings`: 按 `log_path`、`vault_path` 所在目录扫描，`replica_path` 单独计入
+///
+/// # 返回
+/// 各类别的文件、大小、时间范围与按默认保留期可释放的空间；
+/// 目录不存在或无法读取时对应类别为空
+pub fn storage_report(settings: &Settings) -> Result<StorageReport> {
+    storage_report_at(settings, SystemTime::now())
+}
+
+fn storage_report_at(settings: &Settings, now: SystemTime) -> Result<StorageReport> {
+    let layout = DataLayout::from_settings(settings);
+    let mut directories: Vec<PathBuf> = Vec::new();
+    for path in [&settings.log_path, &settings.vault_path] {
+        let dir = parent_dir(Path::new(path));
+        if !directories.contains(&dir) {
+            directories.push(dir);
+        }
+    }
+
+    let mut found: Vec<(ArtifactCategory, ArtifactFile)> = Vec::new();
+    for dir in &directories {
+        let Ok(entries) = std::fs::read_dir(dir) else {
+            continue;
+        };
+        for entry in entries.flatten() {
+            let Ok(metadata) = entry.metadata() else {
+                continue;
+            };
+            let name = entry.file_name();
+            let Some(category) = metadata
+                .is_file()
+                .then(|| layout.classify(&name.to_string_lossy()))
+                .flatten()
+            else {
+                continue;
+            };
+            found.push((
+                category,
+                ArtifactFile {
+                    path: entry.path(),
+                    size: metadata.len(),
+                    modified: metadata.modified().ok(),
+                },
+            ));
+        }
+    }
+    if let Some(replica) = settings.replica_path.as_deref().map(Path::new)
+        && let Ok(metadata) = std::fs::metadata(replica)
+    {
+        found.push((
+            ArtifactCategory::Replica,
+            ArtifactFile {
+                path: replica.to_path_buf(),
+                size: metadata.len(),
+                modified: metadata.modified().ok(),
+            },
+        ));
+    }
+
+    let categories = ARTIFACT_KINDS
+        .iter()
+        .chain(std::iter::once(&REPLICA_KIND))
+        .filter_map(|kind| {
+            let mut files: Vec<ArtifactFile> = found
+                .iter()
+                .filter(|(category, _)| *category == kind.category)
+                .map(|(_, file)| file.clone())
+                .collect();
+            files.sort_by(|a, b| a.path.cmp(&b.path));
+            (!files.is_empty()).then(|| CategoryUsage::new(kind.category, files, now))
+        })
+        .collect();
+    Ok(StorageReport {
+        directories,
+        categories,
+    })
+}
+
+/// 执行清理计划
+///
+/// # 参数
+/// - `settings`: 与 `storage_report` 相同
+/// - `plan`: 用户确认的类别与保留期
+///
+/// # 注意
+/// 执行前重新扫描，只删除此时仍归入计划类别且超过保留期的文件；
+/// 受保护与仅报告的类别记入 `refused`，其文件不会被删除。
+/// 单个文件删除失败不影响其他文件
+pub fn cleanup(settings: &Settings, plan: &CleanupPlan) -> Result<CleanupResult> {
+    cleanup_at(settings, plan, SystemTime::now())
+}
+
+fn cleanup_at(settings: &Settings, plan: &CleanupPlan, now: SystemTime) -> Result<CleanupResult> {
+    let report = storage_report_at(settings, now)?;
+    let mut result = CleanupResult::default();
+
+    for &(category, max_age) in &plan.items {
+        if !category.is_cleanable() {
+            result.refused.push(category);
+            continue;
+        }
+        let Some(usage) = report.category(category) else {
+            continue;
+        };
+        for file in usage
+            .files
+            .iter()
+            .filter(|file| file.older_than(max_age, now))
+        {
+            match std::fs::remove_file(&file.path) {
+                Ok(()) => result.removed.push(file.clone()),
+                Err(e) if e.kind() == std::io::ErrorKind::NotFound => {}
+                Err(e) => result.failed.push((file.path.clone(), e.to_string())),
+            }
+        }
+    }
+    Ok(result)
+}
+
+/// 所在目录（相对文件名时为当前目录）
+fn parent_dir(path: &Path) -> PathBuf {
+    match path.parent() {
+        Some(parent) if !parent.as_os_str().is_empty() => parent.to_path_buf(),
+        _ => PathBuf::from("."),
+    }
+}
+
+/// 修改时间的日期部分（UTC）
+fn date(time: SystemTime) -> String {
+    let time = OffsetDateTime::from(time);
+    format!(
+        "{:04}-{:02}-{:02}",
+        time.year(),
+        u8::from(time.month()),
+        time.day()
+    )
+}
+
+/// 以 B/KB/MB/GB 显示字节数
+pub fn format_bytes(bytes: u64) -> String {
+    const UNITS: [&str; 4] = ["KB", "MB", "GB", "TB"];
+    if bytes < 1024 {
+        return format!("{} B", bytes);
+    }
+    let mut value = bytes as f64 / 1024.0;
+    let mut unit = 0;
+    while value >= 1024.0 && unit < UNITS.len() - 1 {
+        value /= 1024.0;
+        unit += 1;
+    }
+    format!("{:.1} {}", value, UNITS[unit])
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::fs::File;
+    use tempfile::TempDir;
+
+    /// 以 `now` 为基准，创建修改时间为 `age_days` 天前、大小为 `size` 的文件
+    fn touch(dir: &Path, name: &str, size: usize, age_days: u64, now: SystemTime) -> PathBuf {
+        let path = dir.join(name);
+        std::fs::write(&path, vec![b'x'; size]).expect("写入失败");
+        File::options()
+            .write(true)
+            .open(&path)
+            .and_then(|file| file.set_modified(now - DAY * age_days as u32))
+            .expect("设置修改时间失败");
+        path
+    }
+
+    fn settings_in(dir: &Path) -> Settings {
+        let mut settings: Settings = serde_json::from_value(serde_json::json!({
+            "parallelism": 4,
+            "default_mode": "ReadOnly",
+            "default_level": "High",
+            "enable_nr_nx": false,
+            "log_path": dir.join("amberlock-log.ndjson").to_string_lossy(),
+            "vault_path": dir.join("amberlock-vault.bin").to_string_lossy(),
+            "shell_integration": false,
+        }))
+        .expect("构造设置失败");
+        settings.replica_path = Some(
+            dir.join("replica")
+                .join("amberlock-log.ndjson")
+                .to_string_lossy()
+                .to_string(),
+        );
+        settings
+    }
+
+    /// 每种自有文件各一份（另有无关文件），返回设置与当前时间
+    fn synthetic_data_dir(dir: &Path) -> (Settings, SystemTime) {
+        let now = SystemTime::now();
+        std::fs::create_dir_all(dir.join("replica")).unwrap();
+        touch(dir, "amberlock-log.ndjson", 4000, 90, now);
+        touch(dir, "amberlock-vault.bin", 300, 400, now);
+        touch(dir, SETTINGS_FILE_NAME, 200, 400, now);
+        touch(dir, "amberlock-settings.json.bak", 200, 400, now);
+        touch(dir, "amberlock-log.ndjson.inventory.json", 500, 60, now);
+        touch(dir, "amberlock-log.ndjson.stats.json", 100, 1, now);
+        touch(dir, "amberlock-log.ndjson.rescan-C__.json", 50, 20, now);
+        touch(dir, "amberlock-log.ndjson.rescan-D__.json", 70, 2, now);
+        touch(dir, "amberlock-log.failures-b1.ndjson", 1000, 45, now);
+        touch(dir, "amberlock-log.failures-b2.ndjson", 800, 3, now);
+        touch(dir, "amberlock-trace-1700000000.ndjson", 2000, 40, now);
+        touch(dir, REPLICA_SPOOL_FILE_NAME, 600, 100, now);
+        touch(dir, "amberlock-replica-spool.ndjson.overflow", 4, 100, now);
+        touch(dir, DIAGNOSTICS_FILE_NAME, 150, 100, now);
+        touch(
+            dir,
+            ".amberlock-settings.json.42.00ff00ff00ff00ff.tmp",
+            10,
+            1,
+            now,
+        );
+        touch(
+            dir.join("replica").as_path(),
+            "amberlock-log.ndjson",
+            3900,
+            90,
+            now,
+        );
+        // 与 AmberLock 无关的文件（如共用的 %APPDATA%）
+        touch(dir, "other-app.log", 9999, 400, now);
+        touch(dir, ".other.json.1.2.tmp", 10, 400, now);
+        (settings_in(dir), now)
+    }
+
+    #[test]
+    fn test_report_sizes_every_category() {
+        let dir = TempDir::new().unwrap();
+        let (settings, now) = synthetic_data_dir(dir.path());
+        let report = storage_report_at(&settings, now).expect("生成报告失败");
+
+        let count = |category| report.category(category).map_or(0, |u| u.files.len());
+        for category in [
+            ArtifactCategory::OperationLog,
+            ArtifactCategory::Vault,
+            ArtifactCategory::Settings,
+            ArtifactCategory::Backup,
+            ArtifactCategory::Inventory,
+            ArtifactCategory::StatsCache,
+            ArtifactCategory::Trace,
+            ArtifactCategory::Diagnostics,
+            ArtifactCategory::OrphanTemp,
+            ArtifactCategory::Replica,
+        ] {
+            assert_eq!(count(category), 1, "{:?}", category);
+        }
+        assert_eq!(count(ArtifactCategory::RescanState), 2);
+        assert_eq!(count(ArtifactCategory::FailureSpill), 2);
+        assert_eq!(count(ArtifactCategory::ReplicaSpool), 2);
+
+        // 无关文件不计入
+        let expected: u64 = 4000
+            + 300
+            + 200
+            + 200
+            + 500
+            + 100
+            + 50
+            + 70
+            + 1000
+            + 800
+            + 2000
+            + 600
+            + 4
+            + 150
+            + 10
+            + 3900;
+        assert_eq!(report.total_bytes(), expected);
+
+        let spills = report.category(ArtifactCategory::FailureSpill).unwrap();
+        assert_eq!(spills.total_bytes(), 1800);
+        assert_eq!(spills.oldest(), Some(now - DAY * 45));
+        assert_eq!(spills.newest(), Some(now - DAY * 3));
+        assert_eq!(
+            (spills.reclaimable_files, spills.reclaimable_bytes),
+            (1, 1000)
+        );
+        let line = spills.to_string();
+        assert!(line.starts_with("失败旁路文件：2 个，1.8 KB"), "{}", line);
+        assert!(
+            line.ends_with("超过 30 天的 1 个共 1000 B 可清理"),
+            "{}",
+            line
+        );
+
+        let rescan = report.category(ArtifactCategory::RescanState).unwrap();
+        assert_eq!(
+            (rescan.reclaimable_files, rescan.reclaimable_bytes),
+            (1, 50)
+        );
+        assert!(report.to_string().contains("操作日志：1 个，3.9 KB（"));
+        assert!(report.to_string().contains("正在使用，不会清理"));
+        println!("✅ 报告：\n{}", report);
+    }
+
+    #[test]
+    fn test_protected_artifacts_never_eligible() {
+        let dir = TempDir::new().unwrap();
+        let (settings, now) = synthetic_data_dir(dir.path());
+        let report = storage_report_at(&settings, now).unwrap();
+
+        for usage in &report.categories {
+            if !usage.category.is_cleanable() {
+                assert_eq!(usage.reclaimable_files, 0, "{:?}", usage.category);
+            }
+        }
+        let recommended = report.recommended_plan();
+        assert!(recommended.categories().all(ArtifactCategory::is_cleanable));
+
+        // 显式要求清理受保护类别（即使保留期为 0）也会被拒绝
+        let plan = [
+            ArtifactCategory::Vault,
+            ArtifactCategory::OperationLog,
+            ArtifactCategory::Inventory,
+            ArtifactCategory::ReplicaSpool,
+            ArtifactCategory::Settings,
+            ArtifactCategory::Replica,
+            ArtifactCategory::Backup,
+        ]
+        .into_iter()
+        .fold(CleanupPlan::default(), |plan, category| {
+            plan.with_category_older_than(category, Duration::ZERO)
+        });
+        let result = cleanup_at(&settings, &plan, now).expect("清理失败");
+        assert!(result.removed.is_empty());
+        assert_eq!(result.refused.len(), 7);
+        assert!(dir.path().join("amberlock-vault.bin").exists());
+        assert!(dir.path().join(REPLICA_SPOOL_FILE_NAME).exists());
+        assert!(dir.path().join("amberlock-log.ndjson").exists());
+        println!("✅ 受保护文件不会被清理：{}", result);
+    }
+
+    #[test]
+    fn test_recommended_plan_and_selective_cleanup() {
+        let dir = TempDir::new().unwrap();
+        let (settings, now) = synthetic_data_dir(dir.path());
+        let report = storage_report_at(&settings, now).unwrap();
+
+        let recommended: Vec<_> = report.recommended_plan().categories().collect();
+        assert_eq!(
+            recommended,
+            vec![
+                ArtifactCategory::OrphanTemp,
+                ArtifactCategory::StatsCache,
+                ArtifactCategory::RescanState,
+                ArtifactCategory::FailureSpill,
+                ArtifactCategory::Trace,
+            ]
+        );
+
+        // 只清理勾选的类别，并按指定保留期
+        let plan = CleanupPlan::default()
+            .with_category(ArtifactCategory::RescanState)
+            .with_category_older_than(ArtifactCategory::FailureSpill, DAY);
+        let result = cleanup_at(&settings, &plan, now).expect("清理失败");
+        assert_eq!(result.removed.len(), 3);
+        assert_eq!(result.freed_bytes(), 50 + 1000 + 800);
+        assert!(result.failed.is_empty() && result.refused.is_empty());
+        assert!(
+            !dir.path()
+                .join("amberlock-log.ndjson.rescan-C__.json")
+                .exists()
+        );
+        assert!(
+            dir.path()
+                .join("amberlock-log.ndjson.rescan-D__.json")
+                .exists()
+        );
+        assert!(
+            dir.path()
+                .join("amberlock-trace-1700000000.ndjson")
+                .exists()
+        );
+        assert!(dir.path().join("amberlock-log.ndjson.stats.json").exists());
+        assert!(dir.path().join("other-app.log").exists());
+
+        let after = storage_report_at(&settings, now).unwrap();
+        assert_eq!(after.category(ArtifactCategory::FailureSpill), None);
+        assert_eq!(
+            after.total_bytes(),
+            report.total_bytes() - result.freed_bytes()
+        );
+        assert_eq!(result.to_string(), "已清理 3 个文件，释放 1.8 KB");
+        println!("✅ 按计划选择性清理：{}", result);
+    }
+
+    #[test]
+    fn test_category_keys_and_sizes() {
+        for kind in ARTIFACT_KINDS {
+            assert_eq!(ArtifactCategory::from_key(kind.key), Some(kind.category));
+            assert_eq!(kind.category.key(), kind.key);
+        }
+        assert_eq!(
+            ArtifactCategory::from_key("replica"),
+            Some(ArtifactCategory::Replica)
+        );
+        assert_eq!(ArtifactCategory::from_key("vaults"), None);
+
+        assert_eq!(format_bytes(512), "512 B");
+        assert_eq!(format_bytes(85 * 1024 * 1024), "85.0 MB");
+        assert_eq!(format_bytes(1288490189), "1.2 GB");
+        println!("✅ 类别标识与大小格式");
+    }
+}
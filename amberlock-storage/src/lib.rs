@@ -3,10 +3,20 @@
 //! 提供基于 NDJSON (JSON Lines) 格式的日志持久化和设置管理。
 //!
 //! # 核心功能
-//! - **日志写入**：线程安全的追加写入，支持任意可序列化类型
+//! - **日志写入**：线程安全的追加写入；操作日志经 `OperationLog` 只接受 `LockRecord`
 //! - **日志读取**：支持尾部读取、关键字过滤、时间区间查询
 //! - **设置管理**：简单的 JSON 配置文件读写
 //! - **高级查询**：类 SQL 的复合条件查询和统计分析
+//! - **查询语句**：`status:error path:Finance after:2025-01-01` 形式的筛选语法，编译为高级查询条件
+//! - **增量统计**：统计结果缓存在日志旁的附属文件中，只解析新追加的记录
+//! - **日志合并**：多个日志文件按时间全局排序合并（支持外部排序）
+//! - **日志导出**：复制操作日志，可选将压缩的 SDDL 字段还原为普通字符串
+//! - **原子写入**：唯一临时文件 + 原子替换，供设置等小文件保存使用
+//! - **退出刷新**：进程异常退出前刷新所有存活日志的缓冲区与积压记录
+//! - **日志副本**：操作日志同步写入副本，按 id 比较主日志与副本并补回缺失记录
+//! - **派生文件名**：由批次 id、SID 等外部数据生成合法、唯一且长度受限的附属文件名
+//! - **占用与清理**：按类别统计数据目录中的自有文件，按保留期清理过期的附属文件
+//! - **按日轮转**：按 UTC 日期写入 `{名称}-YYYY-MM-DD.ndjson`，日期变化时自动切换文件，按日期列出各段
 //!
 //! # NDJSON 格式说明
 //! 每行一个完整的 JSON 对象，无需数组包装，适合流式追加和大文件处理。
@@ -17,15 +27,52 @@
 //! {"id":"uuid2","time_utc":"2025-01-01T01:00:00Z","status":"error"}
 //! ```
 
+pub mod atomic;
+pub mod export;
+pub mod filename;
+pub mod housekeeping;
+pub mod lifecycle;
+pub mod merge;
+pub mod oplog;
 pub mod query;
+pub mod querylang;
+pub mod replica;
+pub mod rotation;
+pub mod stamp;
+pub mod stats;
+
+pub use atomic::{AtomicWriteOptions, atomic_write};
+pub use export::{ExportOptions, ExportStats, expand_compressed_fields, export_log};
+pub use filename::{EXTENDED_MAX_PATH, LEGACY_MAX_PATH, path_limit, safe_derived_filename};
+pub use housekeeping::{
+    ArtifactCategory, CleanupPlan, CleanupResult, StorageReport, cleanup, format_bytes,
+    storage_report,
+};
+pub use lifecycle::{FlushSummary, flush_all_logs};
+pub use merge::{MergeOptions, MergeStats, merge_logs};
+pub use oplog::{OperationLog, PERSISTENT_FAILURE_THRESHOLD, RecordObserver};
+pub use querylang::{ParsedQuery, QUERY_FIELDS, QueryParseError, has_field_syntax, parse_query};
+pub use replica::{
+    DEFAULT_SPOOL_CAPACITY, DivergenceReport, ReplicationSink, SpoolStatus, compare_logs,
+    restore_from_replica,
+};
+pub use rotation::{Clock, RotationPolicy, SegmentedReader, list_log_segments, segment_file_name};
+pub use stamp::{FixedStamper, RecordStamper, SequenceStamper, SystemStamper, format_utc};
+pub use stats::{StatsCache, generate_statistics_cached, stats_cache_path};
 
 use amberlock_types::Settings;
-use anyhow::Result;
+use anyhow::{Context, Result};
 use parking_lot::Mutex;
+use serde::de::DeserializeOwned;
+use time::format_description::well_known::Rfc3339;
+use time::{OffsetDateTime, UtcOffset};
 use std::{
+    cmp::Ordering,
     fs::{File, OpenOptions},
-    io::{BufRead, BufReader, BufWriter, Seek, SeekFrom, Write},
+    io::{BufRead, BufReader, BufWriter, Read, Seek, SeekFrom, Write},
+    marker::PhantomData,
     path::Path,
+    sync::Arc,
 };
 
 // ================================
@@ -35,9 +82,13 @@ use std::{
 /// 线程安全的 NDJSON 日志写入器
 ///
 /// 支持多线程并发写入，自动追加模式，每条记录占据一行。
+/// 创建时登记到退出刷新登记表（见 `lifecycle::flush_all_logs`）。
+/// 以 `open_rotating` 打开时可按日期轮转（见 `rotation` 模块）。
 pub struct NdjsonWriter {
     /// 内部文件句柄，使用互斥锁保护并发访问
-    file: Mutex<BufWriter<File>>,
+    file: Arc<Mutex<BufWriter<File>>>,
+    /// 按日轮转的状态（`RotationPolicy::Daily` 时存在）
+    rotation: Option<rotation::DailyRotation>,
 }
 
 impl NdjsonWriter {
@@ -60,9 +111,61 @@ impl NdjsonWriter {
             .append(true) // 追加模式，不覆盖现有内容
             .open(path)?;
 
-        Ok(Self {
-            file: Mutex::new(BufWriter::new(file)),
-        })
+        Ok(Self::from_file(file))
+    }
+
+    /// 在目录中按轮转策略打开日志（使用系统时钟）
+    ///
+    /// # 参数
+    /// - `dir`: 日志目录（须已存在）
+    /// - `base_name`: 文件名前缀，如 `amberlock`
+    /// - `policy`: `Daily` 时写入 `amberlock-2025-06-01.ndjson`，`Never` 时写入 `amberlock.ndjson`
+    ///
+    /// # 示例
+    /// ```rust,no_run
+    /// # use amberlock_storage::{NdjsonWriter, RotationPolicy};
+    /// # fn main() -> anyhow::Result<()> {
+    /// let writer = NdjsonWriter::open_rotating("logs", "amberlock", RotationPolicy::Daily)?;
+    /// # Ok(())
+    /// # }
+    /// ```
+    pub fn open_rotating<P: AsRef<Path>>(
+        dir: P,
+        base_name: &str,
+        policy: RotationPolicy,
+    ) -> Result<Self> {
+        Self::open_rotating_with_clock(dir, base_name, policy, rotation::system_clock())
+    }
+
+    /// 在目录中按轮转策略打开日志，日期取自 `clock`（测试中可注入固定时钟）
+    pub fn open_rotating_with_clock<P: AsRef<Path>>(
+        dir: P,
+        base_name: &str,
+        policy: RotationPolicy,
+        clock: Clock,
+    ) -> Result<Self> {
+        let dir = dir.as_ref();
+        match policy {
+            RotationPolicy::Never => {
+                Self::open_append(dir.join(segment_file_name(base_name, None)))
+            }
+            RotationPolicy::Daily => {
+                let (rotation, path) = rotation::DailyRotation::new(dir, base_name, clock)?;
+                let mut writer = Self::from_file(rotation::open_append_file(&path)?);
+                writer.rotation = Some(rotation);
+                Ok(writer)
+            }
+        }
+    }
+
+    /// 使用已打开的文件创建写入器
+    pub(crate) fn from_file(file: File) -> Self {
+        let file = Arc::new(Mutex::new(BufWriter::new(file)));
+        lifecycle::register(&file);
+        Self {
+            file,
+            rotation: None,
+        }
     }
 
     /// 写入单条记录
@@ -77,6 +180,7 @@ impl NdjsonWriter {
     /// # 注意
     /// - 自动在每条记录后添加换行符
     /// - 不会自动刷新缓冲区，需要手动调用 `flush()` 或依赖析构
+    /// - 按日轮转时，日期变化后先刷新旧文件再写入新文件
     ///
     /// # 示例
     /// ```rust
@@ -89,6 +193,10 @@ impl NdjsonWriter {
         // 序列化为 JSON 字符串
         let json_line = serde_json::to_string(rec)?;
 
+        if let Some(rotation) = &self.rotation {
+            rotation.switch_if_needed(&mut guard)?;
+        }
+
         // 写入一行：JSON + 换行符
         writeln!(guard, "{}", json_line)?;
 
@@ -112,6 +220,29 @@ impl NdjsonWriter {
     }
 }
 
+impl NdjsonWriter {
+    /// 限时加锁写入并刷新一条记录（退出路径使用，拿不到锁时放弃）
+    pub(crate) fn write_on_exit<T: serde::Serialize>(&self, rec: &T) -> bool {
+        let Ok(json_line) = serde_json::to_string(rec) else {
+            return false;
+        };
+        self.file
+            .try_lock_for(lifecycle::EXIT_LOCK_TIMEOUT)
+            .is_some_and(|mut file| {
+                writeln!(file, "{}", json_line)
+                    .and_then(|_| file.flush())
+                    .is_ok()
+            })
+    }
+}
+
+impl lifecycle::ExitFlush for Mutex<BufWriter<File>> {
+    fn flush_on_exit(&self) -> bool {
+        self.try_lock_for(lifecycle::EXIT_LOCK_TIMEOUT)
+            .is_some_and(|mut file| file.flush().is_ok())
+    }
+}
+
 // 实现 Drop trait，确保程序退出时刷新缓冲区
 impl Drop for NdjsonWriter {
     fn drop(&mut self) {
@@ -124,6 +255,9 @@ impl Drop for NdjsonWriter {
 // NDJSON 读取器
 // ================================
 
+/// `read_last_n` 倒序读取时每块的大小
+const TAIL_BLOCK_SIZE: usize = 64 * 1024;
+
 /// NDJSON 日志读取器
 ///
 /// 提供日志查询、过滤和分页功能。
@@ -158,9 +292,10 @@ impl NdjsonReader {
     /// - `Err`: IO 错误或 JSON 解析错误
     ///
     /// # 实现策略
-    /// 1. 先读取全部行（对大文件性能较差，可优化为倒序读取）
-    /// 2. 取最后 N 行
-    /// 3. 解析为 JSON
+    /// 1. 从文件末尾按 `TAIL_BLOCK_SIZE` 分块倒序读取，直到凑齐 N 个非空行
+    /// 2. 只解码、解析这 N 行，读取量与文件大小无关
+    ///
+    /// 空行被跳过，行尾空白（含 `\r`）被去除，与 `read_all_lines` 的结果一致
     ///
     /// # 示例
     /// ```rust
@@ -168,14 +303,9 @@ impl NdjsonReader {
     /// let recent_logs = reader.read_last_n(100)?; // 最近 100 条
     /// ```
     pub fn read_last_n(&mut self, n: usize) -> Result<Vec<serde_json::Value>> {
-        // 读取所有行
-        let all_lines = self.read_all_lines()?;
+        let (lines, _) = self.tail_lines(n, TAIL_BLOCK_SIZE)?;
 
-        // 计算起始索引（避免负数）
-        let start_index = all_lines.len().saturating_sub(n);
-
-        // 取最后 N 行并解析
-        let result: Result<Vec<_>, _> = all_lines[start_index..]
+        let result: Result<Vec<_>, _> = lines
             .iter()
             .map(|line| serde_json::from_str(line))
             .collect();
@@ -191,11 +321,12 @@ impl NdjsonReader {
     ///
     /// # 返回
     /// - `Ok(Vec<serde_json::Value>)`: 匹配的记录列表
-    /// - `Err`: IO 错误或 JSON 解析错误
+    /// - `Err`: IO 错误
     ///
     /// # 匹配规则
-    /// - 对整行 JSON 文本进行子串匹配（包含路径、状态、错误信息等）
-    /// - 不区分大小写
+    /// - 对解码后的字段值进行子串匹配（默认字段见 `DEFAULT_FILTER_FIELDS`）
+    /// - 不区分大小写，路径中的反斜杠、中文等按原文匹配，无需考虑 JSON 转义
+    /// - 无法解析的行会被跳过
     /// - 从文件开头向后扫描，找到 `limit` 条后停止
     ///
     /// # 示例
@@ -207,17 +338,99 @@ impl NdjsonReader {
     /// let path_logs = reader.filter("C:\\Users\\test", 100)?;
     /// ```
     pub fn filter(&mut self, key_substr: &str, limit: usize) -> Result<Vec<serde_json::Value>> {
-        let all_lines = self.read_all_lines()?;
-        let key_lower = key_substr.to_lowercase();
+        self.filter_with(&FilterSpec::new(key_substr), limit)
+    }
 
-        let result: Result<Vec<_>, _> = all_lines
-            .iter()
-            .filter(|line| line.to_lowercase().contains(&key_lower))
+    /// 按关键字对原始行文本过滤（旧行为）
+    ///
+    /// 对整行 JSON 文本进行不区分大小写的子串匹配，会命中 JSON 转义后的内容
+    /// 和任意字段。仅用于需要兼容旧行为的场景。
+    pub fn filter_raw(&mut self, key_substr: &str, limit: usize) -> Result<Vec<serde_json::Value>> {
+        let mut spec = FilterSpec::new(key_substr);
+        spec.raw = true;
+        self.filter_with(&spec, limit)
+    }
+
+    /// 按过滤规格过滤日志记录
+    ///
+    /// # 参数
+    /// - `spec`: 过滤规格（关键字、匹配字段、大小写敏感、原始模式）
+    /// - `limit`: 最大返回记录数
+    ///
+    /// # 示例
+    /// ```rust,no_run
+    /// # use amberlock_storage::{FilterSpec, NdjsonReader};
+    /// # fn main() -> anyhow::Result<()> {
+    /// # let mut reader = NdjsonReader::open("logs/operations.ndjson")?;
+    /// let spec = FilterSpec {
+    ///     query: "denied".into(),
+    ///     fields: vec!["errors".into()],
+    ///     case_sensitive: false,
+    ///     raw: false,
+    /// };
+    /// let hits = reader.filter_with(&spec, 100)?;
+    /// # Ok(())
+    /// # }
+    /// ```
+    pub fn filter_with(
+        &mut self,
+        spec: &FilterSpec,
+        limit: usize,
+    ) -> Result<Vec<serde_json::Value>> {
+        let needle = spec.normalize(&spec.query);
+
+        if spec.raw {
+            // 原始模式匹配行文本，命中的行无法解析时返回错误
+            let mut records = self.records::<serde_json::Value>();
+            let mut hits = Vec::new();
+            while hits.len() < limit {
+                let Some(line) = records.next_line() else {
+                    break;
+                };
+                let line = line?;
+                if spec.normalize(line).contains(&needle) {
+                    hits.push(serde_json::from_str(line)?);
+                }
+            }
+            return Ok(hits);
+        }
+
+        self.iter_values()
+            .filter_map(skip_malformed)
+            .filter(|json| {
+                json.as_ref()
+                    .map_or(true, |json| spec.matches_value(json, &needle))
+            })
             .take(limit)
-            .map(|line| serde_json::from_str(line))
-            .collect();
+            .collect()
+    }
 
-        Ok(result?)
+    /// 按查询语句过滤日志记录
+    ///
+    /// # 参数
+    /// - `query`: 由 `parse_query` 解析得到的查询语句
+    /// - `limit`: 最大返回记录数
+    ///
+    /// # 示例
+    /// ```rust,no_run
+    /// # use amberlock_storage::{NdjsonReader, parse_query};
+    /// # fn main() -> anyhow::Result<()> {
+    /// # let mut reader = NdjsonReader::open("logs/operations.ndjson")?;
+    /// let query = parse_query("status:error after:2025-01-01")?;
+    /// let hits = reader.filter_query(&query, 100)?;
+    /// # Ok(())
+    /// # }
+    /// ```
+    pub fn filter_query(
+        &mut self,
+        query: &ParsedQuery,
+        limit: usize,
+    ) -> Result<Vec<serde_json::Value>> {
+        self.iter_values()
+            .filter_map(skip_malformed)
+            .filter(|json| json.as_ref().map_or(true, |json| query.matches(json)))
+            .take(limit)
+            .collect()
     }
 
     /// 按时间区间过滤日志（高级功能）
@@ -233,34 +446,27 @@ impl NdjsonReader {
     ///
     /// # 注意
     /// - 假设记录中包含 `time_utc` 字段
-    /// - 时间比较使用字符串字典序（ISO8601 格式支持）
+    /// - 时间经 `compare_utc` 比较，秒精度的旧记录与微秒精度的新记录可以混合
     pub fn filter_by_time_range(
         &mut self,
         start: &str,
         end: &str,
         limit: usize,
     ) -> Result<Vec<serde_json::Value>> {
-        let all_lines = self.read_all_lines()?;
+        let in_range = |json: &serde_json::Value| {
+            // 提取 time_utc 字段并进行时间范围判断
+            if let Some(time_utc) = json.get("time_utc").and_then(|v| v.as_str()) {
+                compare_utc(time_utc, start).is_ge() && compare_utc(time_utc, end).is_le()
+            } else {
+                false
+            }
+        };
 
-        let result: Result<Vec<_>, _> = all_lines
-            .iter()
-            .filter_map(|line| {
-                // 尝试解析 JSON
-                serde_json::from_str::<serde_json::Value>(line).ok()
-            })
-            .filter(|json| {
-                // 提取 time_utc 字段并进行时间范围判断
-                if let Some(time_utc) = json.get("time_utc").and_then(|v| v.as_str()) {
-                    time_utc >= start && time_utc <= end
-                } else {
-                    false
-                }
-            })
+        self.iter_values()
+            .filter_map(skip_malformed)
+            .filter(|json| json.as_ref().map_or(true, &in_range))
             .take(limit)
-            .map(|item| Ok(item))
-            .collect::<Result<Vec<_>>>();
-
-        Ok(result?)
+            .collect()
     }
 
     /// 按状态过滤日志（便捷方法）
@@ -278,31 +484,165 @@ impl NdjsonReader {
         status: &str,
         limit: usize,
     ) -> Result<Vec<serde_json::Value>> {
-        let all_lines = self.read_all_lines()?;
+        let has_status = |json: &serde_json::Value| {
+            json.get("status")
+                .and_then(|v| v.as_str())
+                .map(|s| s == status)
+                .unwrap_or(false)
+        };
 
-        let result: Result<Vec<_>, _> = all_lines
-            .iter()
-            .filter_map(|line| serde_json::from_str::<serde_json::Value>(line).ok())
-            .filter(|json| {
-                json.get("status")
-                    .and_then(|v| v.as_str())
-                    .map(|s| s == status)
-                    .unwrap_or(false)
-            })
+        self.iter_values()
+            .filter_map(skip_malformed)
+            .filter(|json| json.as_ref().map_or(true, &has_status))
             .take(limit)
-            .map(|item| Ok(item))
-            .collect::<Result<Vec<_>>>();
-
-        Ok(result?)
+            .collect()
     }
 
     /// 统计日志记录总数（行数）
     ///
     /// # 返回
-    /// - `Ok(usize)`: 记录总数
+    /// - `Ok(usize)`: 记录总数（无法解析的行同样计入）
     /// - `Err`: IO 错误
     pub fn count_records(&mut self) -> Result<usize> {
-        Ok(self.read_all_lines()?.len())
+        let mut count = 0;
+        for record in self.iter_records::<serde::de::IgnoredAny>() {
+            if let Some(Err(e)) = skip_malformed(record) {
+                return Err(e);
+            }
+            count += 1;
+        }
+        Ok(count)
+    }
+
+    /// 逐行读取并解析记录（不缓存整个文件）
+    ///
+    /// # 返回
+    /// 按文件顺序产出每个非空行的解析结果：
+    /// - 无法解析的行产出 `Err`（附带行号），之后的行照常读取
+    /// - IO 错误或非 UTF-8 内容产出 `Err` 后结束
+    ///
+    /// # 注意
+    /// 从文件开头读取，各行共用同一个缓冲区
+    ///
+    /// # 示例
+    /// ```rust,no_run
+    /// # use amberlock_storage::NdjsonReader;
+    /// # use amberlock_types::LockRecord;
+    /// # fn main() -> anyhow::Result<()> {
+    /// let mut reader = NdjsonReader::open("logs/operations.ndjson")?;
+    /// for record in reader.iter_records::<LockRecord>() {
+    ///     match record {
+    ///         Ok(record) => println!("{}", record.path),
+    ///         Err(e) => eprintln!("跳过: {}", e),
+    ///     }
+    /// }
+    /// # Ok(())
+    /// # }
+    /// ```
+    pub fn iter_records<T: DeserializeOwned>(&mut self) -> impl Iterator<Item = Result<T>> {
+        self.records()
+    }
+
+    /// 逐行读取并解析为 `serde_json::Value`（见 `iter_records`）
+    pub fn iter_values(&mut self) -> impl Iterator<Item = Result<serde_json::Value>> {
+        self.iter_records()
+    }
+
+    /// 内部方法：从文件开头逐行读取的记录迭代器
+    fn records<T: DeserializeOwned>(&mut self) -> RecordIter<'_, T> {
+        RecordIter {
+            file: &mut self.file,
+            buffer: String::new(),
+            line_no: 0,
+            rewound: false,
+            finished: false,
+            _record: PhantomData,
+        }
+    }
+
+    /// 内部方法：逐行遍历文件（不缓存整个文件）
+    ///
+    /// # 注意
+    /// - 会重置文件指针到开头
+    /// - 空行被跳过，行尾换行符已去除
+    pub(crate) fn for_each_line<F: FnMut(&str)>(&mut self, mut f: F) -> Result<()> {
+        self.file.seek(SeekFrom::Start(0))?;
+
+        let mut buffer = String::new();
+        loop {
+            buffer.clear();
+            if self.file.read_line(&mut buffer)? == 0 {
+                break;
+            }
+            let trimmed = buffer.trim_end();
+            if !trimmed.is_empty() {
+                f(trimmed);
+            }
+        }
+        Ok(())
+    }
+
+    /// 内部方法：从文件末尾倒序分块读取最后 `n` 个非空行
+    ///
+    /// # 返回
+    /// 按文件顺序排列的行（已去除行尾空白）与实际读取的字节数
+    ///
+    /// # 注意
+    /// - 只在换行符处切分，`\n` 不会出现在 UTF-8 多字节序列中，跨块的字符不会被截断
+    /// - 超过块大小的长行跨多个块读取；缺少结尾换行符的最后一行照常计入
+    /// - 选中的行不是合法 UTF-8 时返回错误（与 `read_all_lines` 相同）
+    fn tail_lines(&mut self, n: usize, block_size: usize) -> Result<(Vec<String>, u64)> {
+        if n == 0 {
+            return Ok((Vec::new(), 0));
+        }
+        let len = self.file.seek(SeekFrom::End(0))?;
+
+        // 倒序读到的块（最靠后的块在前）
+        let mut blocks: Vec<Vec<u8>> = Vec::new();
+        let mut pos = len;
+        let mut start = 0;
+        let mut found = 0;
+        // 当前行（最近一个换行符之后）是否含有非空白字符
+        let mut current_nonblank = false;
+
+        'scan: while pos > 0 {
+            let size = (block_size as u64).min(pos);
+            pos -= size;
+            let mut block = vec![0u8; size as usize];
+            self.file.seek(SeekFrom::Start(pos))?;
+            self.file.read_exact(&mut block)?;
+
+            for (offset, &byte) in block.iter().enumerate().rev() {
+                if byte == b'\n' {
+                    found += usize::from(current_nonblank);
+                    current_nonblank = false;
+                    if found == n {
+                        start = pos + offset as u64 + 1;
+                        blocks.push(block);
+                        break 'scan;
+                    }
+                } else if !byte.is_ascii_whitespace() {
+                    current_nonblank = true;
+                }
+            }
+            blocks.push(block);
+        }
+
+        let bytes_read = len - pos;
+        let mut tail = Vec::with_capacity(bytes_read as usize);
+        for block in blocks.iter().rev() {
+            tail.extend_from_slice(block);
+        }
+        let text = std::str::from_utf8(&tail[(start - pos) as usize..])?;
+
+        let lines: Vec<String> = text
+            .split('\n')
+            .map(str::trim_end)
+            .filter(|line| !line.is_empty())
+            .map(str::to_string)
+            .collect();
+        let skip = lines.len().saturating_sub(n);
+        Ok((lines.into_iter().skip(skip).collect(), bytes_read))
     }
 
     /// 内部方法：读取文件所有行
@@ -339,6 +679,195 @@ impl NdjsonReader {
     }
 }
 
+/// 逐行读取记录的迭代器（见 `NdjsonReader::iter_records`）
+struct RecordIter<'a, T> {
+    file: &'a mut BufReader<File>,
+    /// 各行共用的读取缓冲区
+    buffer: String,
+    /// 当前行的行号（从 1 开始）
+    line_no: usize,
+    /// 是否已回到文件开头
+    rewound: bool,
+    /// 遇到 IO 错误或读到文件末尾后不再读取
+    finished: bool,
+    _record: PhantomData<fn() -> T>,
+}
+
+impl<T> RecordIter<'_, T> {
+    /// 读取下一个非空行（已去除行尾空白）
+    fn next_line(&mut self) -> Option<Result<&str>> {
+        if self.finished {
+            return None;
+        }
+        if !self.rewound {
+            self.rewound = true;
+            if let Err(e) = self.file.seek(SeekFrom::Start(0)) {
+                self.finished = true;
+                return Some(Err(e.into()));
+            }
+        }
+        loop {
+            self.buffer.clear();
+            match self.file.read_line(&mut self.buffer) {
+                Ok(0) => {
+                    self.finished = true;
+                    return None;
+                }
+                Ok(_) => {
+                    self.line_no += 1;
+                    if !self.buffer.trim_end().is_empty() {
+                        return Some(Ok(self.buffer.trim_end()));
+                    }
+                }
+                Err(e) => {
+                    self.finished = true;
+                    return Some(Err(e.into()));
+                }
+            }
+        }
+    }
+}
+
+impl<T: DeserializeOwned> Iterator for RecordIter<'_, T> {
+    type Item = Result<T>;
+
+    fn next(&mut self) -> Option<Self::Item> {
+        let line = match self.next_line()? {
+            Ok(line) => line,
+            Err(e) => return Some(Err(e)),
+        };
+        let record = serde_json::from_str(line);
+        Some(record.with_context(|| format!("第 {} 行无法解析", self.line_no)))
+    }
+}
+
+/// 跳过无法解析的行，保留 IO 错误（供遇到坏行时继续扫描的查询使用）
+fn skip_malformed<T>(record: Result<T>) -> Option<Result<T>> {
+    match record {
+        Err(e) if e.downcast_ref::<serde_json::Error>().is_some() => None,
+        other => Some(other),
+    }
+}
+
+// ================================
+// 过滤规格
+// ================================
+
+/// 默认参与关键字匹配的字段
+pub const DEFAULT_FILTER_FIELDS: &[&str] = &["path", "status", "user_sid", "id", "errors", "comment"];
+
+/// 日志关键字过滤规格
+///
+/// 默认对解码后的字段值进行不区分大小写的子串匹配。
+#[derive(Debug, Clone)]
+pub struct FilterSpec {
+    /// 查询关键字
+    pub query: String,
+    /// 参与匹配的字段名（顶层字段）
+    pub fields: Vec<String>,
+    /// 是否区分大小写
+    pub case_sensitive: bool,
+    /// 是否对原始行文本匹配（旧行为，忽略 `fields`）
+    pub raw: bool,
+}
+
+impl FilterSpec {
+    /// 使用默认字段集创建不区分大小写的过滤规格
+    pub fn new(query: &str) -> Self {
+        Self {
+            query: query.to_string(),
+            fields: DEFAULT_FILTER_FIELDS.iter().map(|f| f.to_string()).collect(),
+            case_sensitive: false,
+            raw: false,
+        }
+    }
+
+    /// 检查单条记录是否匹配
+    pub fn matches(&self, record: &serde_json::Value) -> bool {
+        self.matches_value(record, &self.normalize(&self.query))
+    }
+
+    /// 内部方法：按大小写设置规范化文本
+    fn normalize(&self, text: &str) -> String {
+        if self.case_sensitive {
+            text.to_string()
+        } else {
+            text.to_lowercase()
+        }
+    }
+
+    /// 内部方法：使用已规范化的关键字匹配记录
+    fn matches_value(&self, record: &serde_json::Value, needle: &str) -> bool {
+        self.fields.iter().any(|field| {
+            record
+                .get(field)
+                .map(|v| self.value_contains(v, needle))
+                .unwrap_or(false)
+        })
+    }
+
+    /// 内部方法：递归检查 JSON 值中的字符串内容是否包含关键字
+    fn value_contains(&self, value: &serde_json::Value, needle: &str) -> bool {
+        use serde_json::Value;
+
+        match value {
+            Value::String(s) => self.normalize(s).contains(needle),
+            Value::Number(n) => n.to_string().contains(needle),
+            Value::Bool(b) => b.to_string().contains(needle),
+            Value::Array(items) => items.iter().any(|v| self.value_contains(v, needle)),
+            Value::Object(map) => map.values().any(|v| self.value_contains(v, needle)),
+            Value::Null => false,
+        }
+    }
+}
+
+// ================================
+// 时间工具
+// ================================
+
+/// 解析 RFC 3339 时间戳并归一化到 UTC
+///
+/// # 返回
+/// - `Some(OffsetDateTime)`: UTC 时间（带偏移的时间戳已换算）
+/// - `None`: 格式无效
+///
+/// # 注意
+/// 日志中的 `time_utc` 通常已是 `Z` 结尾，但迁移或手工编辑的记录可能带有时区偏移，
+/// 比较或排序前应统一经过本函数，而不是直接比较字符串
+pub fn parse_utc(s: &str) -> Option<OffsetDateTime> {
+    OffsetDateTime::parse(s.trim(), &Rfc3339)
+        .ok()
+        .map(|t| t.to_offset(UtcOffset::UTC))
+}
+
+/// 比较两个时间戳
+///
+/// 两者都能解析时按时刻比较，秒精度的旧值（`...08:00:00Z`）与微秒精度的新值
+/// （`...08:00:00.000000Z`）、带偏移的值可以混合；任一无法解析时退回字符串字典序
+pub fn compare_utc(a: &str, b: &str) -> Ordering {
+    match (parse_utc(a), parse_utc(b)) {
+        (Some(a), Some(b)) => a.cmp(&b),
+        _ => a.cmp(b),
+    }
+}
+
+/// 记录的排序键：UTC 时刻（纳秒，缺失或无法解析时为 None，排在最前）与进程内序号
+///
+/// 同一时刻的记录按 `seq_no` 区分先后；旧记录没有序号，按 0 处理。
+/// 序号只在同一进程内有意义，不同进程写入的记录之间仍只按时间排序
+pub(crate) fn record_order(record: &serde_json::Value) -> (Option<i128>, u64) {
+    let time = record
+        .get("time_utc")
+        .and_then(|v| v.as_str())
+        .and_then(parse_utc)
+        .map(|t| t.unix_timestamp_nanos());
+    let seq_no = record
+        .get("seq_no")
+        .and_then(|v| v.as_u64())
+        .unwrap_or_default();
+    (time, seq_no)
+}
+
 // ================================
 // 设置管理
 // ================================
@@ -375,26 +904,561 @@ pub fn load_settings<P: AsRef<Path>>(path: P) -> Result<Settings> {
 /// - `Err`: 文件写入失败或序列化错误
 ///
 /// # 注意
-/// - 会覆盖现有文件
-/// - 自动创建父目录（如果实现）
+/// - 通过 `atomic_write` 原子替换现有文件，并发保存不会产生半成品
+/// - 自动创建父目录
 ///
 /// # 示例
 /// ```rust
 /// let mut settings = load_settings("config.json")?;
-/// settings.parallelism = 8;
+/// settings.parallelism = ParallelismMode::Auto;
 /// save_settings("config.json", &settings)?;
 /// ```
 pub fn save_settings<P: AsRef<Path>>(path: P, s: &Settings) -> Result<()> {
-    // 如果父目录不存在，尝试创建（可选功能）
-    if let Some(parent) = path.as_ref().parent() {
-        std::fs::create_dir_all(parent)?;
+    // 使用漂亮的 JSON 格式（便于人工编辑）
+    let bytes = serde_json::to_vec_pretty(s)?;
+    atomic_write(path, &bytes, &AtomicWriteOptions::default())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use serde_json::json;
+    use tempfile::TempDir;
+
+    fn write_log(dir: &TempDir, records: &[serde_json::Value]) -> std::path::PathBuf {
+        let path = dir.path().join("test.ndjson");
+        let writer = NdjsonWriter::open_append(&path).expect("创建日志失败");
+        for record in records {
+            writer.write_record(record).expect("写入记录失败");
+        }
+        writer.flush().expect("刷新失败");
+        path
     }
 
-    let file = File::create(path)?;
-    let writer = BufWriter::new(file);
+    #[test]
+    fn test_filter_matches_backslash_path() {
+        let dir = TempDir::new().expect("创建临时目录失败");
+        let path = write_log(
+            &dir,
+            &[
+                json!({"id": "1", "path": "C:\\Users\\test\\a.txt", "status": "success"}),
+                json!({"id": "2", "path": "D:\\Other\\b.txt", "status": "success"}),
+            ],
+        );
 
-    // 使用漂亮的 JSON 格式（便于人工编辑）
-    serde_json::to_writer_pretty(writer, s)?;
+        let mut reader = NdjsonReader::open(&path).expect("打开日志失败");
+        let hits = reader.filter("c:\\users", 10).expect("过滤失败");
+
+        assert_eq!(hits.len(), 1);
+        assert_eq!(hits[0]["id"], "1");
+    }
+
+    #[test]
+    fn test_filter_matches_escaped_unicode() {
+        let dir = TempDir::new().expect("创建临时目录失败");
+        let path = dir.path().join("test.ndjson");
+        // 模拟其他写入方使用 \uXXXX 转义的中文路径
+        std::fs::write(
+            &path,
+            "{\"id\":\"1\",\"path\":\"D:\\\\\\u6587\\u6863\\\\a.txt\",\"status\":\"success\"}\n",
+        )
+        .expect("写入失败");
+
+        let mut reader = NdjsonReader::open(&path).expect("打开日志失败");
+        let hits = reader.filter("文档", 10).expect("过滤失败");
+
+        assert_eq!(hits.len(), 1);
+        assert_eq!(hits[0]["path"], "D:\\文档\\a.txt");
+    }
+
+    #[test]
+    fn test_filter_no_false_positive_on_path_substring() {
+        let dir = TempDir::new().expect("创建临时目录失败");
+        let path = write_log(
+            &dir,
+            &[
+                json!({"id": "1", "path": "D:\\Terrors\\a.txt", "status": "success"}),
+                json!({"id": "2", "path": "D:\\b.txt", "status": "error"}),
+            ],
+        );
+
+        let mut reader = NdjsonReader::open(&path).expect("打开日志失败");
+
+        // 解码模式只匹配 status/path 等字段的真实值
+        let hits = reader
+            .filter_with(
+                &FilterSpec {
+                    query: "error".into(),
+                    fields: vec!["status".into()],
+                    case_sensitive: false,
+                    raw: false,
+                },
+                10,
+            )
+            .expect("过滤失败");
+        assert_eq!(hits.len(), 1);
+        assert_eq!(hits[0]["id"], "2");
+
+        // 原始模式保留旧行为：路径中的 "Terrors" 也会命中
+        let raw_hits = reader.filter_raw("error", 10).expect("过滤失败");
+        assert_eq!(raw_hits.len(), 2);
+    }
+
+    #[test]
+    fn test_filter_case_sensitivity_and_errors_field() {
+        let dir = TempDir::new().expect("创建临时目录失败");
+        let path = write_log(
+            &dir,
+            &[json!({
+                "id": "1",
+                "path": "D:\\a.txt",
+                "status": "error",
+                "errors": ["Access Denied"]
+            })],
+        );
+
+        let mut reader = NdjsonReader::open(&path).expect("打开日志失败");
+        assert_eq!(reader.filter("access denied", 10).expect("过滤失败").len(), 1);
+
+        let mut spec = FilterSpec::new("access denied");
+        spec.case_sensitive = true;
+        assert!(reader.filter_with(&spec, 10).expect("过滤失败").is_empty());
+    }
 
-    Ok(())
+    #[test]
+    fn test_filter_matches_comment() {
+        let dir = TempDir::new().expect("创建临时目录失败");
+        let path = write_log(
+            &dir,
+            &[
+                json!({"id": "1", "path": "D:\\a.txt", "status": "success", "comment": "工单 INC-1234 发布冻结"}),
+                json!({"id": "2", "path": "D:\\b.txt", "status": "success"}),
+            ],
+        );
+
+        let mut reader = NdjsonReader::open(&path).expect("打开日志失败");
+        let hits = reader.filter("inc-1234", 10).expect("过滤失败");
+        assert_eq!(hits.len(), 1);
+        assert_eq!(hits[0]["id"], "1");
+
+        let hits = query::QueryBuilder::new(&path)
+            .filter_comment_contains("发布冻结")
+            .execute()
+            .expect("查询失败");
+        assert_eq!(hits.len(), 1);
+        assert_eq!(hits[0]["id"], "1");
+    }
+
+    #[test]
+    fn test_query_filter_origin() {
+        use amberlock_types::OperationOrigin;
+
+        let dir = TempDir::new().expect("创建临时目录失败");
+        let path = write_log(
+            &dir,
+            &[
+                json!({"id": "1", "status": "success", "origin": "gui_button"}),
+                json!({"id": "2", "status": "success", "origin": "gui_shortcut"}),
+                json!({"id": "3", "status": "success"}),
+            ],
+        );
+
+        let ids = |origin| -> Vec<String> {
+            query::QueryBuilder::new(&path)
+                .filter_origin(origin)
+                .execute()
+                .expect("查询失败")
+                .iter()
+                .map(|record| record["id"].as_str().unwrap().to_string())
+                .collect()
+        };
+        assert_eq!(ids(OperationOrigin::GuiShortcut), vec!["2"]);
+        assert_eq!(ids(OperationOrigin::Unknown), vec!["3"], "旧记录应视为未知来源");
+        assert!(ids(OperationOrigin::CliBatch).is_empty());
+    }
+
+    #[test]
+    fn test_query_filter_has_warning() {
+        use amberlock_types::WarningKind;
+
+        let dir = TempDir::new().expect("创建临时目录失败");
+        let path = write_log(
+            &dir,
+            &[
+                json!({"id": "1", "status": "success", "warnings": [
+                    {"kind": "retried_after_transient_error", "attempts": 2},
+                    {"kind": "verification_skipped"}
+                ]}),
+                json!({"id": "2", "status": "success", "warnings": [
+                    {"kind": "verification_skipped"}
+                ]}),
+                json!({"id": "3", "status": "success"}),
+            ],
+        );
+
+        let ids = |kind| -> Vec<String> {
+            query::QueryBuilder::new(&path)
+                .filter_has_warning(kind)
+                .execute()
+                .expect("查询失败")
+                .iter()
+                .map(|record| record["id"].as_str().unwrap().to_string())
+                .collect()
+        };
+        assert_eq!(ids(WarningKind::VerificationSkipped), vec!["1", "2"]);
+        assert_eq!(ids(WarningKind::RetriedAfterTransientError), vec!["1"]);
+        assert!(ids(WarningKind::StreamsNotProtected).is_empty());
+    }
+
+    /// 同一文件中混合旧版本的秒精度与新版本的微秒精度时间戳
+    fn write_mixed_precision_log(dir: &TempDir) -> std::path::PathBuf {
+        write_log(
+            dir,
+            &[
+                json!({"id": "old1", "time_utc": "2025-03-01T10:00:00Z"}),
+                json!({"id": "new1", "time_utc": "2025-03-01T10:00:00.000000Z", "seq_no": 1}),
+                json!({"id": "new2", "time_utc": "2025-03-01T10:00:00.500000Z", "seq_no": 2}),
+                json!({"id": "old2", "time_utc": "2025-03-01T10:00:01Z"}),
+                json!({"id": "new3", "time_utc": "2025-03-01T10:00:01.250000Z", "seq_no": 3}),
+            ],
+        )
+    }
+
+    fn ids(records: &[serde_json::Value]) -> Vec<&str> {
+        records
+            .iter()
+            .map(|record| record["id"].as_str().unwrap())
+            .collect()
+    }
+
+    #[test]
+    fn test_time_filters_mix_second_and_microsecond_precision() {
+        use query::QueryBuilder;
+
+        let dir = TempDir::new().expect("创建临时目录失败");
+        let path = write_mixed_precision_log(&dir);
+
+        // 按字典序比较时 "10:00:01.250000Z" < "10:00:01Z"，new3 会被误判为在区间内
+        let mut reader = NdjsonReader::open(&path).expect("打开日志失败");
+        let hits = reader
+            .filter_by_time_range("2025-03-01T10:00:00.400000Z", "2025-03-01T10:00:01Z", 100)
+            .expect("过滤失败");
+        assert_eq!(ids(&hits), vec!["new2", "old2"]);
+
+        // 同理 "10:00:00Z" > "10:00:00.999999Z"，old1 会被误判为在区间外
+        let hits = QueryBuilder::new(path.to_str().unwrap())
+            .filter_time_after("2025-03-01T10:00:00Z")
+            .filter_time_before("2025-03-01T10:00:00.999999Z")
+            .sort_asc()
+            .execute()
+            .expect("查询失败");
+        assert_eq!(ids(&hits), vec!["old1", "new1", "new2"]);
+
+        assert_eq!(
+            compare_utc("2025-03-01T10:00:00Z", "2025-03-01T10:00:00.000000Z"),
+            Ordering::Equal
+        );
+        assert_eq!(
+            compare_utc("2025-03-01T18:00:00+08:00", "2025-03-01T10:00:00.000001Z"),
+            Ordering::Less
+        );
+        println!("✅ 秒精度与微秒精度的时间戳混合时过滤正确");
+    }
+
+    #[test]
+    fn test_sort_breaks_ties_by_seq_no_across_pages() {
+        use query::QueryBuilder;
+
+        let dir = TempDir::new().expect("创建临时目录失败");
+        // 同一微秒内的 5 条记录，写入顺序与 id 顺序都与序号不一致
+        let path = write_log(
+            &dir,
+            &[3, 1, 5, 2, 4].map(|seq: u64| {
+                json!({
+                    "id": format!("{:x}", 0xff - seq),
+                    "time_utc": "2025-03-01T10:00:00.000000Z",
+                    "seq_no": seq,
+                })
+            }),
+        );
+
+        let page = |offset: usize, desc: bool| {
+            let query = QueryBuilder::new(path.to_str().unwrap())
+                .offset(offset)
+                .limit(2);
+            let query = if desc {
+                query.sort_desc()
+            } else {
+                query.sort_asc()
+            };
+            query
+                .execute()
+                .expect("查询失败")
+                .iter()
+                .map(|record| record["seq_no"].as_u64().unwrap())
+                .collect::<Vec<_>>()
+        };
+        let asc: Vec<u64> = [0, 2, 4].into_iter().flat_map(|o| page(o, false)).collect();
+        let desc: Vec<u64> = [0, 2, 4].into_iter().flat_map(|o| page(o, true)).collect();
+        assert_eq!(asc, vec![1, 2, 3, 4, 5]);
+        assert_eq!(desc, vec![5, 4, 3, 2, 1]);
+
+        // 混合精度：同一时刻没有序号的旧记录排在最前
+        let mixed_dir = TempDir::new().expect("创建临时目录失败");
+        let mixed = write_mixed_precision_log(&mixed_dir);
+        let sorted = QueryBuilder::new(mixed.to_str().unwrap())
+            .sort_asc()
+            .execute()
+            .expect("查询失败");
+        assert_eq!(ids(&sorted), vec!["old1", "new1", "new2", "old2", "new3"]);
+        println!("✅ 同一时刻的记录按序号分页，结果稳定");
+    }
+
+    #[test]
+    fn test_timeseries_buckets_mixed_precision() {
+        use query::{Metric, TimeWindow, TimeseriesSpec, generate_timeseries};
+
+        let dir = TempDir::new().expect("创建临时目录失败");
+        let path = write_log(
+            &dir,
+            &[
+                json!({"time_utc": "2025-03-01T23:59:59Z", "status": "success"}),
+                json!({"time_utc": "2025-03-01T23:59:59.999999Z", "status": "success"}),
+                json!({"time_utc": "2025-03-02T00:00:00Z", "status": "success"}),
+                json!({"time_utc": "2025-03-02T00:00:00.000001Z", "status": "error"}),
+            ],
+        );
+        let spec = TimeseriesSpec {
+            window: TimeWindow::Day,
+            span: 2,
+            end: Some(utc("2025-03-02T12:00:00.000000Z")),
+            metrics: &[Metric::Success, Metric::Error],
+        };
+        let series = generate_timeseries(&path, &spec).expect("统计失败");
+        assert_eq!(series.series(Metric::Success), Some(vec![2, 1]));
+        assert_eq!(series.series(Metric::Error), Some(vec![0, 1]));
+        assert_eq!(series.unbucketed, 0);
+        println!("✅ 混合精度的时间戳按时刻分桶");
+    }
+
+    fn utc(s: &str) -> OffsetDateTime {
+        OffsetDateTime::parse(s, &Rfc3339).expect("时间格式错误")
+    }
+
+    #[test]
+    fn test_timeseries_daily_buckets() {
+        use query::{Metric, TimeWindow, TimeseriesSpec, generate_timeseries};
+
+        let dir = TempDir::new().expect("创建临时目录失败");
+        let path = write_log(
+            &dir,
+            &[
+                // 第 1 天：2 成功 1 失败
+                json!({"time_utc": "2025-03-01T00:00:00Z", "status": "success"}),
+                json!({"time_utc": "2025-03-01T12:30:00Z", "status": "success"}),
+                json!({"time_utc": "2025-03-01T23:59:59Z", "status": "error"}),
+                // 第 2 天无记录
+                // 第 3 天：1 解锁 1 失败（带时区偏移，换算为 UTC 后仍在 3 日）
+                json!({"time_utc": "2025-03-03T08:00:00+08:00", "status": "unlocked"}),
+                json!({"time_utc": "2025-03-03T10:00:00Z", "status": "error"}),
+                // 窗口之外
+                json!({"time_utc": "2025-02-20T10:00:00Z", "status": "success"}),
+                // 时间戳异常
+                json!({"time_utc": "昨天下午", "status": "success"}),
+                json!({"status": "error"}),
+            ],
+        );
+        std::fs::OpenOptions::new()
+            .append(true)
+            .open(&path)
+            .and_then(|mut f| f.write_all(b"{not json\n"))
+            .expect("追加损坏行失败");
+
+        let spec = TimeseriesSpec {
+            window: TimeWindow::Day,
+            span: 4,
+            end: Some(utc("2025-03-04T06:00:00Z")),
+            metrics: &[Metric::Success, Metric::Error],
+        };
+        let series = generate_timeseries(&path, &spec).expect("统计失败");
+
+        assert_eq!(series.buckets.len(), 4);
+        assert_eq!(series.buckets[0].start, utc("2025-03-01T00:00:00Z"));
+        assert_eq!(series.buckets[3].start, utc("2025-03-04T00:00:00Z"));
+        assert_eq!(series.series(Metric::Success), Some(vec![2, 0, 0, 0]));
+        assert_eq!(series.series(Metric::Error), Some(vec![1, 0, 1, 0]));
+        assert_eq!(series.series(Metric::Unlocked), None);
+        assert_eq!(series.buckets[2].unlocked, 1);
+        assert_eq!(series.unbucketed, 3);
+        assert_eq!(series.out_of_range, 1);
+
+        let rate = series.failure_rate();
+        assert!((rate[0] - 1.0 / 3.0).abs() < 1e-6);
+        assert_eq!(rate[1], 0.0);
+        assert_eq!(rate[2], 0.5);
+        println!("✅ 按日分桶正确，空桶已补齐");
+    }
+
+    #[test]
+    fn test_timeseries_hour_boundary_goes_to_later_bucket() {
+        use query::{Metric, TimeWindow, TimeseriesSpec, generate_timeseries};
+
+        let dir = TempDir::new().expect("创建临时目录失败");
+        let path = write_log(
+            &dir,
+            &[
+                json!({"time_utc": "2025-03-01T09:59:59Z", "status": "success"}),
+                json!({"time_utc": "2025-03-01T10:00:00Z", "status": "success"}),
+            ],
+        );
+
+        let spec = TimeseriesSpec {
+            window: TimeWindow::Hour,
+            span: 3,
+            end: Some(utc("2025-03-01T11:15:00Z")),
+            metrics: &[Metric::Success],
+        };
+        let series = generate_timeseries(&path, &spec).expect("统计失败");
+        assert_eq!(series.series(Metric::Success), Some(vec![1, 1, 0]));
+    }
+
+    /// 旧实现：读取全部行后取最后 N 行
+    fn read_last_n_full(path: &Path, n: usize) -> Vec<String> {
+        let mut reader = NdjsonReader::open(path).expect("打开日志失败");
+        let lines = reader.read_all_lines().expect("读取失败");
+        let start = lines.len().saturating_sub(n);
+        lines[start..].to_vec()
+    }
+
+    #[test]
+    fn test_read_last_n_matches_full_read_on_large_file() {
+        let dir = TempDir::new().expect("创建临时目录失败");
+        let path = dir.path().join("large.ndjson");
+        let content: String = (0..100_000)
+            .map(|i| format!("{{\"id\":\"{0}\",\"path\":\"C:\\\\数据\\\\{0}.txt\"}}\n", i))
+            .collect();
+        std::fs::write(&path, &content).expect("写入日志失败");
+
+        let mut reader = NdjsonReader::open(&path).expect("打开日志失败");
+        for n in [0, 1, 200, 99_999, 100_000, 150_000] {
+            let expected = read_last_n_full(&path, n);
+            let (lines, _) = reader.tail_lines(n, TAIL_BLOCK_SIZE).expect("读取失败");
+            assert_eq!(lines, expected, "n = {}", n);
+        }
+
+        let recent = reader.read_last_n(200).expect("读取失败");
+        assert_eq!(recent.len(), 200);
+        assert_eq!(recent[0]["id"], "99800");
+        assert_eq!(recent[199]["id"], "99999");
+    }
+
+    #[test]
+    fn test_read_last_n_reads_only_the_tail() {
+        let dir = TempDir::new().expect("创建临时目录失败");
+        let path = dir.path().join("large.ndjson");
+        let content: String = (0..100_000)
+            .map(|i| format!("{{\"id\":\"{}\",\"status\":\"success\"}}\n", i))
+            .collect();
+        std::fs::write(&path, &content).expect("写入日志失败");
+
+        let mut reader = NdjsonReader::open(&path).expect("打开日志失败");
+        let (lines, bytes_read) = reader.tail_lines(200, TAIL_BLOCK_SIZE).expect("读取失败");
+        assert_eq!(lines.len(), 200);
+        // 200 行约 7 KB，只需读取末尾一块，而整个文件约 3 MB
+        assert_eq!(bytes_read, TAIL_BLOCK_SIZE as u64);
+        assert!(bytes_read * 40 < content.len() as u64);
+    }
+
+    #[test]
+    fn test_read_last_n_edge_cases() {
+        let dir = TempDir::new().expect("创建临时目录失败");
+        let path = dir.path().join("edge.ndjson");
+        let long = format!("{{\"id\":\"long\",\"comment\":\"{}\"}}", "长".repeat(100));
+        let cases = [
+            String::new(),
+            "\n\n".to_string(),
+            "{\"id\":\"1\"}".to_string(),
+            "{\"id\":\"1\"}\n{\"id\":\"2\"}".to_string(),
+            "{\"id\":\"1\"}\r\n\r\n{\"id\":\"2\"}\r\n  \n".to_string(),
+            format!("{}\n{{\"id\":\"中文\"}}\n{}\n", long, long),
+            format!("\n\n{}", long),
+        ];
+        for content in &cases {
+            std::fs::write(&path, content).expect("写入日志失败");
+            let mut reader = NdjsonReader::open(&path).expect("打开日志失败");
+            // 块大小从 1 字节到超过文件大小：覆盖长行跨块与多字节字符跨块
+            for block_size in (1..=24).chain([1024]) {
+                for n in 0..=4 {
+                    let (lines, _) = reader.tail_lines(n, block_size).expect("读取失败");
+                    assert_eq!(
+                        lines,
+                        read_last_n_full(&path, n),
+                        "内容 {:?}，块大小 {}，n = {}",
+                        content,
+                        block_size,
+                        n
+                    );
+                }
+            }
+        }
+    }
+
+    #[test]
+    fn test_iter_records_continues_after_malformed_line() {
+        #[derive(serde::Deserialize)]
+        struct Row {
+            id: String,
+            status: String,
+        }
+
+        let dir = TempDir::new().expect("创建临时目录失败");
+        let path = dir.path().join("mixed.ndjson");
+        std::fs::write(
+            &path,
+            "{\"id\":\"1\",\"status\":\"success\"}\n\
+             {\"id\":\"2\",\"status\":\n\
+             \n\
+             {\"id\":\"3\",\"status\":\"error\"}",
+        )
+        .expect("写入日志失败");
+
+        let mut reader = NdjsonReader::open(&path).expect("打开日志失败");
+        let records: Vec<Result<Row>> = reader.iter_records().collect();
+        assert_eq!(records.len(), 3);
+        assert_eq!(records[0].as_ref().expect("第 1 行应可解析").id, "1");
+        let error = records[1].as_ref().err().expect("第 2 行应无法解析");
+        assert!(error.to_string().contains("第 2 行"), "{}", error);
+        let last = records[2].as_ref().expect("第 4 行应可解析");
+        assert_eq!((last.id.as_str(), last.status.as_str()), ("3", "error"));
+
+        // 迭代器每次从文件开头读取
+        let ids: Vec<_> = reader
+            .iter_values()
+            .filter_map(|record| record.ok())
+            .map(|json| json["id"].clone())
+            .collect();
+        assert_eq!(ids, [json!("1"), json!("3")]);
+
+        // 查询跳过坏行，计数仍计入坏行
+        let errors = reader.filter_by_status("error", 10).expect("过滤失败");
+        assert_eq!(errors.len(), 1);
+        assert_eq!(reader.filter("success", 10).expect("过滤失败").len(), 1);
+        assert_eq!(reader.count_records().expect("计数失败"), 3);
+    }
+
+    #[test]
+    fn test_iter_records_stops_on_invalid_utf8() {
+        let dir = TempDir::new().expect("创建临时目录失败");
+        let path = dir.path().join("binary.ndjson");
+        std::fs::write(&path, b"{\"id\":\"1\"}\n\xff\xfe\n{\"id\":\"3\"}\n").expect("写入日志失败");
+
+        let mut reader = NdjsonReader::open(&path).expect("打开日志失败");
+        let records: Vec<_> = reader.iter_values().collect();
+        assert_eq!(records.len(), 2);
+        assert!(records[0].is_ok());
+        assert!(records[1].is_err());
+        // 读取错误不能当作坏行跳过
+        assert!(reader.filter_by_status("success", 10).is_err());
+        assert!(reader.count_records().is_err());
+    }
 }
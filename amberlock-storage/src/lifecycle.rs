@@ -0,0 +1,109 @@
+//! 进程退出时的日志刷新
+//!
+//! `NdjsonWriter` 与 `OperationLog` 创建时在进程级登记表中登记自身（弱引用，
+//! 不影响正常释放）。进程即将异常退出（panic、控制台关闭等）时调用
+//! `flush_all_logs`，把仍在缓冲区中的行和积压记录尽力写入磁盘。
+//!
+//! 退出路径上可能恰好由出错线程持有写入锁，因此这里只限时尝试加锁，
+//! 拿不到锁的日志跳过，不会死锁。
+
+use parking_lot::Mutex;
+use std::sync::{Arc, Weak};
+use std::time::Duration;
+
+/// 退出时尝试获取日志写入锁的最长等待时间
+pub(crate) const EXIT_LOCK_TIMEOUT: Duration = Duration::from_millis(200);
+
+/// 可在进程退出前刷新的日志
+pub(crate) trait ExitFlush: Send + Sync {
+    /// 尽力刷新
+    ///
+    /// # 返回
+    /// 全部内容已写入磁盘时返回 `true`
+    fn flush_on_exit(&self) -> bool;
+}
+
+/// 退出刷新登记表
+static REGISTRY: Mutex<Vec<Weak<dyn ExitFlush>>> = Mutex::new(Vec::new());
+
+/// 登记需要在退出时刷新的日志
+pub(crate) fn register<T: ExitFlush + 'static>(target: &Arc<T>) {
+    let target: Arc<dyn ExitFlush> = target.clone();
+    let mut registry = REGISTRY.lock();
+    registry.retain(|entry| entry.strong_count() > 0);
+    registry.push(Arc::downgrade(&target));
+}
+
+/// 退出刷新的结果
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq)]
+pub struct FlushSummary {
+    /// 已完整刷新的日志数
+    pub flushed: usize,
+    /// 未能刷新的日志数（写入失败或锁被占用）
+    pub failed: usize,
+}
+
+/// 刷新所有仍然存活的日志
+///
+/// # 注意
+/// 供 panic 钩子、控制台关闭处理等退出路径调用；
+/// 登记表本身被占用时直接返回空结果
+pub fn flush_all_logs() -> FlushSummary {
+    let targets: Vec<Arc<dyn ExitFlush>> = match REGISTRY.try_lock_for(EXIT_LOCK_TIMEOUT) {
+        Some(mut registry) => {
+            registry.retain(|entry| entry.strong_count() > 0);
+            registry.iter().filter_map(Weak::upgrade).collect()
+        }
+        None => return FlushSummary::default(),
+    };
+
+    let mut summary = FlushSummary::default();
+    for target in targets {
+        if target.flush_on_exit() {
+            summary.flushed += 1;
+        } else {
+            summary.failed += 1;
+        }
+    }
+    summary
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::{NdjsonReader, NdjsonWriter};
+    use tempfile::TempDir;
+
+    #[test]
+    fn test_flush_all_logs_writes_buffered_lines() {
+        let dir = TempDir::new().expect("创建临时目录失败");
+        let path = dir.path().join("buffered.ndjson");
+        let writer = NdjsonWriter::open_append(&path).expect("打开日志失败");
+
+        writer
+            .write_record(&serde_json::json!({"status": "success"}))
+            .expect("写入失败");
+        assert_eq!(
+            std::fs::read_to_string(&path).unwrap(),
+            "",
+            "写入应仍在缓冲区中"
+        );
+
+        let summary = flush_all_logs();
+        assert!(summary.flushed >= 1);
+        let mut reader = NdjsonReader::open(&path).expect("打开日志失败");
+        assert_eq!(reader.count_records().unwrap(), 1);
+        println!("✅ 退出刷新写出了缓冲区中的记录");
+    }
+
+    #[test]
+    fn test_dropped_logs_leave_registry() {
+        let dir = TempDir::new().expect("创建临时目录失败");
+        let writer = NdjsonWriter::open_append(dir.path().join("a.ndjson")).unwrap();
+        let held = Arc::downgrade(&writer.file);
+
+        drop(writer);
+        assert!(held.upgrade().is_none(), "登记表不应阻止写入器释放");
+        flush_all_logs();
+    }
+}
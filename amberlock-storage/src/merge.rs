@@ -0,0 +1,427 @@
+//! 日志合并
+//!
+//! 将多个 NDJSON 日志文件（按用户拆分的日志、轮转分段、迁移前后的机器）
+//! 按 `(time_utc, seq_no, id)` 全局排序后合并为一个文件。
+//!
+//! # 实现
+//! - 输入逐行流式读取，累计数据量超过 `spill_threshold_bytes` 时将已排序的
+//!   批次溢写到临时文件，最后对所有批次做 k 路归并
+//! - 时间戳经 `parse_utc` 归一化到 UTC 后再比较，带时区偏移的记录、秒精度与微秒精度的记录也能正确排序
+//! - 同一时刻的记录按 `seq_no` 排序（同一进程写入的记录保持写入顺序），
+//!   序号也相同（不同进程、没有序号的旧记录）时才按 id 排序，保证结果确定
+//! - 输出先写入同目录临时文件，完成后原子替换目标文件
+
+use crate::record_order;
+use amberlock_types::LockRecord;
+use anyhow::Result;
+use serde_json::Value;
+use std::cmp::Reverse;
+use std::collections::{BinaryHeap, HashSet};
+use std::fs::File;
+use std::io::{BufRead, BufReader, BufWriter, Lines, Write};
+use std::path::{Path, PathBuf};
+use tempfile::{NamedTempFile, TempDir};
+
+/// 默认溢写阈值（64 MiB）
+pub const DEFAULT_SPILL_THRESHOLD: usize = 64 * 1024 * 1024;
+
+/// 合并选项
+#[derive(Debug, Clone)]
+pub struct MergeOptions {
+    /// 按记录 id 去重，保留排序后最先出现的一条
+    pub dedupe_by_id: bool,
+    /// 要求每行都能解析为 `LockRecord`，否则跳过
+    pub validate: bool,
+    /// 内存中缓存的行数据超过该字节数时溢写到临时文件
+    pub spill_threshold_bytes: usize,
+}
+
+impl Default for MergeOptions {
+    fn default() -> Self {
+        Self {
+            dedupe_by_id: true,
+            validate: true,
+            spill_threshold_bytes: DEFAULT_SPILL_THRESHOLD,
+        }
+    }
+}
+
+/// 合并统计
+#[derive(Debug, Clone, Default, PartialEq, Eq)]
+pub struct MergeStats {
+    /// 成功解析的记录数
+    pub read: usize,
+    /// 写入输出的记录数
+    pub written: usize,
+    /// 因 id 重复被丢弃的记录数
+    pub deduped: usize,
+    /// 无法解析（或未通过校验）被跳过的行数
+    pub skipped: usize,
+    /// 溢写到临时文件的已排序批次数（0 表示全程在内存中完成）
+    pub spilled_runs: usize,
+}
+
+/// 排序键：UTC 纳秒时间戳（无法解析时为 None，排在最前）、进程内序号与记录 id
+#[derive(Debug, Clone, PartialEq, Eq, PartialOrd, Ord)]
+struct SortKey {
+    time: Option<i128>,
+    seq_no: u64,
+    id: String,
+}
+
+/// 待合并的一行
+#[derive(Debug, PartialEq, Eq, PartialOrd, Ord)]
+struct Entry {
+    key: SortKey,
+    line: String,
+}
+
+/// 合并多个日志文件
+///
+/// # 参数
+/// - `inputs`: 输入文件列表
+/// - `output`: 输出文件路径（可以与某个输入相同，所有输入读完后才会替换）
+/// - `options`: 合并选项
+///
+/// # 返回
+/// - `Ok(MergeStats)`: 读取/写入/去重/跳过的数量
+/// - `Err`: 输入无法打开或输出无法写入
+pub fn merge_logs(inputs: &[PathBuf], output: &Path, options: &MergeOptions) -> Result<MergeStats> {
+    let mut stats = MergeStats::default();
+    let spill_dir = TempDir::new()?;
+    let mut runs: Vec<PathBuf> = Vec::new();
+    let mut buffer: Vec<Entry> = Vec::new();
+    let mut buffered_bytes = 0usize;
+
+    for input in inputs {
+        let reader = BufReader::new(File::open(input)?);
+        for line in reader.lines() {
+            let line = line?;
+            let line = line.trim();
+            if line.is_empty() {
+                continue;
+            }
+            let Some(key) = parse_line(line, options.validate) else {
+                stats.skipped += 1;
+                continue;
+            };
+
+            stats.read += 1;
+            buffered_bytes += line.len();
+            buffer.push(Entry {
+                key,
+                line: line.to_string(),
+            });
+
+            if buffered_bytes >= options.spill_threshold_bytes {
+                runs.push(spill_run(&spill_dir, runs.len(), &mut buffer)?);
+                buffered_bytes = 0;
+            }
+        }
+    }
+    stats.spilled_runs = runs.len();
+
+    let parent = output
+        .parent()
+        .filter(|p| !p.as_os_str().is_empty())
+        .unwrap_or_else(|| Path::new("."));
+    let mut out = BufWriter::new(NamedTempFile::new_in(parent)?);
+    let mut seen: HashSet<String> = HashSet::new();
+
+    let mut emit = |entry: Entry, stats: &mut MergeStats| -> Result<()> {
+        if options.dedupe_by_id && !seen.insert(entry.key.id) {
+            stats.deduped += 1;
+            return Ok(());
+        }
+        writeln!(out, "{}", entry.line)?;
+        stats.written += 1;
+        Ok(())
+    };
+
+    if runs.is_empty() {
+        buffer.sort();
+        for entry in buffer {
+            emit(entry, &mut stats)?;
+        }
+    } else {
+        if !buffer.is_empty() {
+            runs.push(spill_run(&spill_dir, runs.len(), &mut buffer)?);
+        }
+        let mut sources = runs
+            .iter()
+            .map(|run| Ok(BufReader::new(File::open(run)?).lines()))
+            .collect::<Result<Vec<_>>>()?;
+
+        // k 路归并：堆中保存每个批次的当前行
+        let mut heap = BinaryHeap::new();
+        for (index, source) in sources.iter_mut().enumerate() {
+            if let Some(entry) = next_entry(source)? {
+                heap.push(Reverse((entry, index)));
+            }
+        }
+        while let Some(Reverse((entry, index))) = heap.pop() {
+            if let Some(next) = next_entry(&mut sources[index])? {
+                heap.push(Reverse((next, index)));
+            }
+            emit(entry, &mut stats)?;
+        }
+    }
+
+    let file = out.into_inner().map_err(|e| e.into_error())?;
+    file.as_file().sync_all()?;
+    file.persist(output)?;
+
+    Ok(stats)
+}
+
+/// 解析一行并计算排序键
+///
+/// # 返回
+/// - `None`: 不是 JSON 对象，或启用校验时不是合法的 `LockRecord`
+fn parse_line(line: &str, validate: bool) -> Option<SortKey> {
+    let value: Value = serde_json::from_str(line).ok()?;
+    if !value.is_object() {
+        return None;
+    }
+    if validate && serde_json::from_value::<LockRecord>(value.clone()).is_err() {
+        return None;
+    }
+    Some(sort_key(&value))
+}
+
+fn sort_key(value: &Value) -> SortKey {
+    let (time, seq_no) = record_order(value);
+    let id = value
+        .get("id")
+        .and_then(Value::as_str)
+        .unwrap_or_default()
+        .to_string();
+    SortKey { time, seq_no, id }
+}
+
+/// 将缓冲区排序后写入临时文件并清空缓冲区
+fn spill_run(dir: &TempDir, index: usize, buffer: &mut Vec<Entry>) -> Result<PathBuf> {
+    buffer.sort();
+    let path = dir.path().join(format!("run-{}.ndjson", index));
+    let mut writer = BufWriter::new(File::create(&path)?);
+    for entry in buffer.drain(..) {
+        writeln!(writer, "{}", entry.line)?;
+    }
+    writer.flush()?;
+    Ok(path)
+}
+
+/// 从批次文件读取下一行（批次中的行均已通过解析）
+fn next_entry(source: &mut Lines<BufReader<File>>) -> Result<Option<Entry>> {
+    match source.next() {
+        Some(line) => {
+            let line = line?;
+            let value: Value = serde_json::from_str(&line)?;
+            Ok(Some(Entry {
+                key: sort_key(&value),
+                line,
+            }))
+        }
+        None => Ok(None),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::NdjsonReader;
+    use serde_json::json;
+
+    fn record(id: &str, time_utc: &str) -> Value {
+        json!({
+            "id": id,
+            "path": format!("C:\\Data\\{}.txt", id),
+            "kind": "File",
+            "mode": "ReadOnly",
+            "level_applied": "High",
+            "time_utc": time_utc,
+            "user_sid": "S-1-5-21-0",
+            "owner_before": null,
+            "sddl_before": null,
+            "sddl_after": null,
+            "status": "success",
+            "errors": [],
+        })
+    }
+
+    fn write_file(dir: &TempDir, name: &str, lines: &[String]) -> PathBuf {
+        let path = dir.path().join(name);
+        std::fs::write(&path, lines.join("\n") + "\n").expect("写入失败");
+        path
+    }
+
+    fn read_ids(path: &Path) -> Vec<String> {
+        NdjsonReader::open(path)
+            .expect("打开日志失败")
+            .read_last_n(usize::MAX)
+            .expect("读取失败")
+            .iter()
+            .map(|v| v["id"].as_str().unwrap().to_string())
+            .collect()
+    }
+
+    #[test]
+    fn test_merge_overlapping_time_ranges() {
+        let dir = TempDir::new().expect("创建临时目录失败");
+        let a = write_file(
+            &dir,
+            "a.ndjson",
+            &[
+                record("a1", "2025-01-01T00:00:00Z").to_string(),
+                record("a2", "2025-01-01T02:00:00Z").to_string(),
+                "not json".to_string(),
+            ],
+        );
+        // 带时区偏移：09:00+08:00 即 01:00Z，应排在 a1 与 a2 之间
+        let b = write_file(
+            &dir,
+            "b.ndjson",
+            &[
+                record("b1", "2025-01-01T09:00:00+08:00").to_string(),
+                record("b2", "2025-01-01T03:00:00Z").to_string(),
+                json!({"id": "x", "time_utc": "2025-01-01T00:30:00Z"}).to_string(),
+            ],
+        );
+        let output = dir.path().join("merged.ndjson");
+
+        let stats = merge_logs(&[a, b], &output, &MergeOptions::default()).expect("合并失败");
+
+        assert_eq!(read_ids(&output), vec!["a1", "b1", "a2", "b2"]);
+        assert_eq!(stats.read, 4);
+        assert_eq!(stats.written, 4);
+        assert_eq!(stats.skipped, 2);
+        assert_eq!(stats.spilled_runs, 0);
+        println!("✅ 合并统计：{:?}", stats);
+    }
+
+    #[test]
+    fn test_merge_orders_same_instant_by_seq_no() {
+        let dir = TempDir::new().expect("创建临时目录失败");
+        let with_seq = |id: &str, time_utc: &str, seq_no: u64| {
+            let mut value = record(id, time_utc);
+            value["seq_no"] = seq_no.into();
+            value.to_string()
+        };
+        // 同一微秒写入的两条记录：id 顺序与写入顺序相反
+        let a = write_file(
+            &dir,
+            "a.ndjson",
+            &[
+                with_seq("zz", "2025-01-01T00:00:00.000000Z", 1),
+                with_seq("aa", "2025-01-01T00:00:00.000000Z", 2),
+            ],
+        );
+        // 旧版本写入的秒精度记录：没有序号，与上面同一时刻
+        let b = write_file(
+            &dir,
+            "b.ndjson",
+            &[
+                record("mm", "2025-01-01T00:00:00Z").to_string(),
+                record("bb", "2025-01-01T00:00:00.5Z").to_string(),
+            ],
+        );
+        let output = dir.path().join("merged.ndjson");
+
+        merge_logs(&[a, b], &output, &MergeOptions::default()).expect("合并失败");
+        assert_eq!(read_ids(&output), vec!["mm", "zz", "aa", "bb"]);
+        println!("✅ 同一时刻的记录按序号合并");
+    }
+
+    #[test]
+    fn test_merge_dedupes_ids_across_files() {
+        let dir = TempDir::new().expect("创建临时目录失败");
+        let line = record("same", "2025-01-01T00:00:00Z").to_string();
+        let a = write_file(&dir, "a.ndjson", std::slice::from_ref(&line));
+        let b = write_file(
+            &dir,
+            "b.ndjson",
+            &[line, record("other", "2025-01-02T00:00:00Z").to_string()],
+        );
+        let output = dir.path().join("merged.ndjson");
+
+        let stats = merge_logs(&[a.clone(), b.clone()], &output, &MergeOptions::default())
+            .expect("合并失败");
+        assert_eq!(read_ids(&output), vec!["same", "other"]);
+        assert_eq!(stats.deduped, 1);
+
+        let keep_all = MergeOptions {
+            dedupe_by_id: false,
+            ..Default::default()
+        };
+        let stats = merge_logs(&[a, b], &output, &keep_all).expect("合并失败");
+        assert_eq!(stats.written, 3);
+        assert_eq!(stats.deduped, 0);
+    }
+
+    #[test]
+    fn test_merge_spills_large_input() {
+        let dir = TempDir::new().expect("创建临时目录失败");
+
+        // 三个文件各 700 条，时间交错且文件内部乱序
+        let inputs: Vec<PathBuf> = (0..3)
+            .map(|file| {
+                let lines: Vec<String> = (0..700)
+                    .rev()
+                    .map(|i| {
+                        let n = i * 3 + file;
+                        let time = format!(
+                            "2025-01-01T{:02}:{:02}:{:02}Z",
+                            n / 3600,
+                            (n / 60) % 60,
+                            n % 60
+                        );
+                        record(&format!("r{:05}", n), &time).to_string()
+                    })
+                    .collect();
+                write_file(&dir, &format!("{}.ndjson", file), &lines)
+            })
+            .collect();
+        let output = dir.path().join("merged.ndjson");
+
+        let options = MergeOptions {
+            spill_threshold_bytes: 16 * 1024,
+            ..Default::default()
+        };
+        let stats = merge_logs(&inputs, &output, &options).expect("合并失败");
+
+        assert!(stats.spilled_runs > 1, "应触发溢写：{:?}", stats);
+        assert_eq!(stats.written, 2100);
+        let ids = read_ids(&output);
+        let mut expected = ids.clone();
+        expected.sort();
+        assert_eq!(ids, expected);
+    }
+
+    #[test]
+    fn test_remerge_is_idempotent() {
+        let dir = TempDir::new().expect("创建临时目录失败");
+        let a = write_file(
+            &dir,
+            "a.ndjson",
+            &[
+                record("2", "2025-01-01T00:00:00Z").to_string(),
+                record("1", "2025-01-01T00:00:00Z").to_string(),
+                record("3", "2024-12-31T23:00:00-02:00").to_string(),
+            ],
+        );
+        let first = dir.path().join("first.ndjson");
+        merge_logs(&[a], &first, &MergeOptions::default()).expect("合并失败");
+
+        // 输出与输入为同一文件时也应原样保持
+        let before = std::fs::read(&first).unwrap();
+        let stats = merge_logs(
+            std::slice::from_ref(&first),
+            &first,
+            &MergeOptions::default(),
+        )
+        .expect("再次合并失败");
+        assert_eq!(std::fs::read(&first).unwrap(), before);
+        assert_eq!(stats.deduped, 0);
+        assert_eq!(read_ids(&first), vec!["1", "2", "3"]);
+    }
+}
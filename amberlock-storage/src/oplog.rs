@@ -0,0 +1,414 @@
+//! 操作日志
+//!
+//! 操作日志文件只应包含 `LockRecord`，查询与统计代码依赖这一点。
+//! `OperationLog` 是写入该文件的唯一入口，只接受 `LockRecord`，
+//! 由编译器保证不会混入任意 JSON。通用 NDJSON 写入（检查点、快照等）
+//! 仍直接使用 `NdjsonWriter`。
+//!
+//! 日志同时携带记录的时间戳与 ID 来源（`RecordStamper`），核心库构造
+//! 记录时从这里获取，测试可通过 `with_stamper` 注入确定性实现。
+//!
+//! 需要跟随记录维护内存状态的组件（如保护清单）可通过 `with_observer`
+//! 登记 `RecordObserver`，每条追加的记录与每次成功刷新都会通知它。
+//!
+//! 配置副本（`with_replica`）后，每条追加的记录同时写入副本；副本的失败
+//! 只在副本内部暂存处理，不影响主日志写入的结果。
+//!
+//! 写入失败的记录保留在内存积压队列中，连续失败达到
+//! `PERSISTENT_FAILURE_THRESHOLD` 后由上层诊断原因（如日志目录被自身
+//! 的保护锁定），修复后通过 `retry_backlog` 或 `relocate` 补写。
+//! 进程异常退出前 `flush_all_logs` 也会尝试补写积压记录。
+
+use crate::NdjsonWriter;
+use crate::lifecycle::{self, EXIT_LOCK_TIMEOUT, ExitFlush};
+use crate::replica::ReplicationSink;
+use crate::stamp::{RecordStamper, SystemStamper};
+use amberlock_types::LockRecord;
+use anyhow::Result;
+use parking_lot::{Mutex, RwLock};
+use std::path::{Path, PathBuf};
+use std::sync::Arc;
+use std::sync::atomic::{AtomicBool, AtomicUsize, Ordering};
+
+/// 连续写入失败达到该次数视为持续失败
+pub const PERSISTENT_FAILURE_THRESHOLD: usize = 3;
+
+/// 追加记录的观察者
+pub trait RecordObserver: Send + Sync {
+    /// 一条记录已追加（写入失败、进入积压队列的记录同样通知）
+    fn observe(&self, record: &LockRecord);
+
+    /// 日志已刷新到磁盘（观察者可在此保存自身状态）
+    fn flushed(&self) {}
+}
+
+/// 类型化的操作日志写入器
+///
+/// 内部的 `NdjsonWriter` 自带互斥锁，可直接通过 `Arc<OperationLog>` 在线程间共享
+pub struct OperationLog {
+    path: RwLock<PathBuf>,
+    state: Arc<LogState>,
+    stamper: Arc<dyn RecordStamper>,
+    observer: Option<Arc<dyn RecordObserver>>,
+    replica: Option<Arc<ReplicationSink>>,
+    consecutive_failures: AtomicUsize,
+    compress_sddl: AtomicBool,
+}
+
+/// 写入器与积压队列（登记到退出刷新登记表）
+struct LogState {
+    writer: RwLock<NdjsonWriter>,
+    backlog: Mutex<Vec<LockRecord>>,
+}
+
+impl ExitFlush for LogState {
+    fn flush_on_exit(&self) -> bool {
+        let Some(writer) = self.writer.try_read_for(EXIT_LOCK_TIMEOUT) else {
+            return false;
+        };
+        let Some(mut backlog) = self.backlog.try_lock_for(EXIT_LOCK_TIMEOUT) else {
+            return false;
+        };
+        let written = backlog
+            .iter()
+            .try_for_each(|record| writer.write_record(record))
+            .and_then(|_| writer.flush());
+        if written.is_ok() {
+            backlog.clear();
+        }
+        written.is_ok()
+    }
+}
+
+impl OperationLog {
+    /// 以追加模式打开操作日志
+    ///
+    /// # 参数
+    /// - `path`: 日志文件路径，如果不存在会自动创建
+    pub fn open<P: AsRef<Path>>(path: P) -> Result<Self> {
+        let state = Arc::new(LogState {
+            writer: RwLock::new(NdjsonWriter::open_append(&path)?),
+            backlog: Mutex::new(Vec::new()),
+        });
+        lifecycle::register(&state);
+        Ok(Self {
+            path: RwLock::new(path.as_ref().to_path_buf()),
+            state,
+            stamper: Arc::new(SystemStamper),
+            observer: None,
+            replica: None,
+            consecutive_failures: AtomicUsize::new(0),
+            compress_sddl: AtomicBool::new(false),
+        })
+    }
+
+    /// 替换记录的时间戳与 ID 来源（默认为 `SystemStamper`）
+    pub fn with_stamper(mut self, stamper: Arc<dyn RecordStamper>) -> Self {
+        self.stamper = stamper;
+        self
+    }
+
+    /// 登记追加记录的观察者（替换已有的观察者）
+    pub fn with_observer(mut self, observer: Arc<dyn RecordObserver>) -> Self {
+        self.observer = Some(observer);
+        self
+    }
+
+    /// 挂接日志副本（替换已有的副本）
+    pub fn with_replica(mut self, replica: Arc<ReplicationSink>) -> Self {
+        self.replica = Some(replica);
+        self
+    }
+
+    /// 启用或关闭 SDDL 字段压缩（对应 `Settings.compress_sddl`）
+    pub fn with_sddl_compression(self, enabled: bool) -> Self {
+        self.set_sddl_compression(enabled);
+        self
+    }
+
+    /// 运行时切换 SDDL 字段压缩（之后写入的记录生效）
+    pub fn set_sddl_compression(&self, enabled: bool) {
+        self.compress_sddl.store(enabled, Ordering::Relaxed);
+    }
+
+    /// 写入记录时是否压缩较长的 SDDL 字段（由 `OperationContext` 读取）
+    pub fn compresses_sddl(&self) -> bool {
+        self.compress_sddl.load(Ordering::Relaxed)
+    }
+
+    /// 已挂接的日志副本
+    pub fn replica(&self) -> Option<&ReplicationSink> {
+        self.replica.as_deref()
+    }
+
+    /// 记录的时间戳与 ID 来源
+    pub fn stamper(&self) -> &dyn RecordStamper {
+        self.stamper.as_ref()
+    }
+
+    /// 当前日志文件路径
+    pub fn path(&self) -> PathBuf {
+        self.path.read().clone()
+    }
+
+    /// 追加一条操作记录（写入后立即刷新，确保失败能被及时发现）
+    ///
+    /// # 返回
+    /// - `Ok(())`: 写入成功
+    /// - `Err`: 序列化失败或 IO 错误；记录保留在积压队列中等待补写
+    ///
+    /// # 注意
+    /// 主日志写入失败的记录同样写入副本；副本失败不影响返回值
+    pub fn append(&self, record: &LockRecord) -> Result<()> {
+        let written = {
+            let writer = self.state.writer.read();
+            writer.write_record(record).and_then(|_| writer.flush())
+        };
+        if let Some(observer) = &self.observer {
+            observer.observe(record);
+        }
+        if let Some(replica) = &self.replica {
+            replica.mirror(record);
+        }
+        match written {
+            Ok(()) => {
+                self.consecutive_failures.store(0, Ordering::SeqCst);
+                Ok(())
+            }
+            Err(e) => {
+                self.state.backlog.lock().push(record.clone());
+                self.consecutive_failures.fetch_add(1, Ordering::SeqCst);
+                Err(e)
+            }
+        }
+    }
+
+    /// 进程退出路径上追加一条记录（如 panic 记录）
+    ///
+    /// # 返回
+    /// 已写入并刷新时返回 `true`；写入锁被占用或写入失败时放弃，不阻塞退出
+    ///
+    /// # 注意
+    /// 不写入副本：副本可能位于响应缓慢的网络共享上
+    pub fn append_on_exit(&self, record: &LockRecord) -> bool {
+        self.state
+            .writer
+            .try_read_for(EXIT_LOCK_TIMEOUT)
+            .is_some_and(|writer| writer.write_on_exit(record))
+    }
+
+    /// 强制刷新缓冲区到磁盘
+    pub fn flush(&self) -> Result<()> {
+        self.state.writer.read().flush()?;
+        if let Some(observer) = &self.observer {
+            observer.flushed();
+        }
+        Ok(())
+    }
+
+    /// 连续写入失败次数（成功写入后清零）
+    pub fn consecutive_failures(&self) -> usize {
+        self.consecutive_failures.load(Ordering::SeqCst)
+    }
+
+    /// 是否处于持续写入失败状态
+    pub fn is_persistently_failing(&self) -> bool {
+        self.consecutive_failures() >= PERSISTENT_FAILURE_THRESHOLD
+    }
+
+    /// 等待补写的记录数
+    pub fn backlog_len(&self) -> usize {
+        self.state.backlog.lock().len()
+    }
+
+    /// 重新打开当前日志文件并补写积压记录
+    ///
+    /// # 返回
+    /// - `Ok(n)`: 补写的记录数
+    /// - `Err`: 仍无法写入，积压记录保持不变
+    pub fn retry_backlog(&self) -> Result<usize> {
+        let path = self.path();
+        self.relocate(path)
+    }
+
+    /// 将日志切换到新的路径并补写积压记录
+    ///
+    /// # 参数
+    /// - `new_path`: 新日志文件路径（不存在时创建）
+    ///
+    /// # 返回
+    /// - `Ok(n)`: 补写的记录数
+    /// - `Err`: 新路径无法写入，原写入器与积压记录保持不变
+    pub fn relocate<P: AsRef<Path>>(&self, new_path: P) -> Result<usize> {
+        let writer = NdjsonWriter::open_append(&new_path)?;
+
+        let mut backlog = self.state.backlog.lock();
+        for record in backlog.iter() {
+            writer.write_record(record)?;
+        }
+        writer.flush()?;
+
+        let written = backlog.len();
+        backlog.clear();
+        *self.state.writer.write() = writer;
+        *self.path.write() = new_path.as_ref().to_path_buf();
+        self.consecutive_failures.store(0, Ordering::SeqCst);
+        Ok(written)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::NdjsonReader;
+    use crate::stamp::SequenceStamper;
+    use amberlock_types::{LabelLevel, OperationOrigin, ProtectMode, TargetKind};
+    use tempfile::TempDir;
+
+    fn sample_record(log: &OperationLog) -> LockRecord {
+        LockRecord {
+            id: log.stamper().new_id(),
+            path: "C:\\Data\\a.txt".to_string(),
+            kind: TargetKind::File,
+            mode: ProtectMode::ReadOnly,
+            level_applied: LabelLevel::High,
+            time_utc: log.stamper().now(),
+            seq_no: log.stamper().next_seq(),
+            user_sid: "S-1-5-21-0".to_string(),
+            owner_before: None,
+            sddl_before: None,
+            sddl_after: None,
+            status: "success".to_string(),
+            errors: vec![],
+            details: None,
+            authorized_by: None,
+            comment: None,
+            batch_id: None,
+            volume_root_acknowledged: false,
+            downgrade_reason: None,
+            origin: OperationOrigin::Unknown,
+            app_version: None,
+            file_id: None,
+            warnings: vec![],
+        }
+    }
+
+    #[test]
+    fn test_append_round_trip() {
+        let dir = TempDir::new().expect("创建临时目录失败");
+        let path = dir.path().join("operations.ndjson");
+
+        let log = OperationLog::open(&path)
+            .expect("打开操作日志失败")
+            .with_stamper(Arc::new(SequenceStamper::new("2025-01-01T00:00:00Z")));
+        log.append(&sample_record(&log)).expect("写入失败");
+        log.append(&sample_record(&log)).expect("写入失败");
+        log.flush().expect("刷新失败");
+
+        let mut reader = NdjsonReader::open(&path).expect("打开日志失败");
+        let records: Vec<LockRecord> = reader
+            .read_last_n(10)
+            .expect("读取失败")
+            .into_iter()
+            .map(|v| serde_json::from_value(v).expect("应为 LockRecord"))
+            .collect();
+
+        assert_eq!(records.len(), 2);
+        assert_eq!(records[0].id, "00000000-0000-4000-8000-000000000001");
+        assert_eq!(records[1].id, "00000000-0000-4000-8000-000000000002");
+        assert_eq!(records[1].time_utc, "2025-01-01T00:00:01.000000Z");
+        assert_eq!((records[0].seq_no, records[1].seq_no), (1, 2));
+        println!("✅ 操作日志只包含 LockRecord");
+    }
+
+    #[test]
+    fn test_failed_writes_are_kept_and_replayed_on_relocate() {
+        let dir = TempDir::new().expect("创建临时目录失败");
+        let path = dir.path().join("operations.ndjson");
+        let log = OperationLog::open(&path)
+            .expect("打开操作日志失败")
+            .with_stamper(Arc::new(SequenceStamper::new("2025-01-01T00:00:00Z")));
+
+        // 模拟写入失败：替换为无法写入的写入器（只读打开的文件）
+        std::fs::write(dir.path().join("readonly.ndjson"), b"").unwrap();
+        let readonly = std::fs::File::open(dir.path().join("readonly.ndjson")).unwrap();
+        *log.state.writer.write() = NdjsonWriter::from_file(readonly);
+
+        for _ in 0..PERSISTENT_FAILURE_THRESHOLD {
+            assert!(log.append(&sample_record(&log)).is_err());
+        }
+        assert!(log.is_persistently_failing());
+        assert_eq!(log.backlog_len(), PERSISTENT_FAILURE_THRESHOLD);
+
+        let new_path = dir.path().join("moved").join("operations.ndjson");
+        assert!(log.relocate(&new_path).is_err(), "目录不存在时应失败且不丢弃积压");
+        assert_eq!(log.backlog_len(), PERSISTENT_FAILURE_THRESHOLD);
+
+        std::fs::create_dir_all(new_path.parent().unwrap()).unwrap();
+        assert_eq!(log.relocate(&new_path).expect("切换失败"), PERSISTENT_FAILURE_THRESHOLD);
+        assert_eq!(log.backlog_len(), 0);
+        assert_eq!(log.consecutive_failures(), 0);
+        assert_eq!(log.path(), new_path);
+
+        log.append(&sample_record(&log)).expect("切换后写入失败");
+        let mut reader = NdjsonReader::open(&new_path).expect("打开日志失败");
+        assert_eq!(reader.count_records().unwrap(), PERSISTENT_FAILURE_THRESHOLD + 1);
+        println!("✅ 写入失败的记录在切换日志后补写");
+    }
+
+    #[test]
+    fn test_exit_flush_writes_backlog() {
+        let dir = TempDir::new().expect("创建临时目录失败");
+        let path = dir.path().join("operations.ndjson");
+        let log = OperationLog::open(&path).expect("打开操作日志失败");
+
+        std::fs::write(dir.path().join("readonly.ndjson"), b"").unwrap();
+        let readonly = std::fs::File::open(dir.path().join("readonly.ndjson")).unwrap();
+        let original = std::mem::replace(
+            &mut *log.state.writer.write(),
+            NdjsonWriter::from_file(readonly),
+        );
+        assert!(log.append(&sample_record(&log)).is_err());
+        assert_eq!(log.backlog_len(), 1);
+
+        // 写入恢复后，退出刷新补写积压记录
+        *log.state.writer.write() = original;
+        crate::flush_all_logs();
+        assert_eq!(log.backlog_len(), 0);
+        assert!(log.append_on_exit(&sample_record(&log)));
+
+        let mut reader = NdjsonReader::open(&path).expect("打开日志失败");
+        assert_eq!(reader.count_records().unwrap(), 2);
+        println!("✅ 退出刷新补写了积压记录");
+    }
+
+    #[test]
+    fn test_observer_sees_every_append() {
+        struct Counter(AtomicUsize, AtomicUsize);
+        impl RecordObserver for Counter {
+            fn observe(&self, _record: &LockRecord) {
+                self.0.fetch_add(1, Ordering::SeqCst);
+            }
+            fn flushed(&self) {
+                self.1.fetch_add(1, Ordering::SeqCst);
+            }
+        }
+
+        let dir = TempDir::new().expect("创建临时目录失败");
+        let counter = Arc::new(Counter(AtomicUsize::new(0), AtomicUsize::new(0)));
+        let log = OperationLog::open(dir.path().join("operations.ndjson"))
+            .expect("打开操作日志失败")
+            .with_observer(counter.clone());
+        log.append(&sample_record(&log)).expect("写入失败");
+        log.flush().expect("刷新失败");
+        assert_eq!(counter.1.load(Ordering::SeqCst), 1);
+
+        std::fs::write(dir.path().join("readonly.ndjson"), b"").unwrap();
+        let readonly = std::fs::File::open(dir.path().join("readonly.ndjson")).unwrap();
+        *log.state.writer.write() = NdjsonWriter::from_file(readonly);
+        assert!(log.append(&sample_record(&log)).is_err());
+
+        assert_eq!(counter.0.load(Ordering::SeqCst), 2, "写入失败的记录也应通知观察者");
+        println!("✅ 观察者收到每条追加的记录");
+    }
+}
@@ -5,10 +5,17 @@
 //! - 分页和游标
 //! - 排序（正序/倒序）
 //! - 聚合统计
+//! - 按时间窗口分桶的时间序列统计
 
-use crate::NdjsonReader;
+use crate::querylang::ParsedQuery;
+use crate::stats::StatsCache;
+use crate::{FilterSpec, NdjsonReader, compare_utc, parse_utc, record_order};
+use amberlock_types::{OperationOrigin, WarningKind};
 use serde_json::Value;
+use std::cmp::Reverse;
+use std::collections::BTreeMap;
 use std::path::Path;
+use time::{Duration, OffsetDateTime};
 
 /// 查询构建器
 ///
@@ -32,11 +39,13 @@ pub struct QueryBuilder {
 
 /// 过滤条件
 #[derive(Debug, Clone)]
-enum Filter {
+pub(crate) enum Filter {
     /// 状态等于某值
     StatusEquals(String),
     /// 路径包含子串
     PathContains(String),
+    /// 操作备注包含子串
+    CommentContains(String),
     /// 时间晚于某时刻
     TimeAfter(String),
     /// 时间早于某时刻
@@ -45,8 +54,16 @@ enum Filter {
     UserSidEquals(String),
     /// 完整性级别等于某值
     LevelEquals(String),
+    /// 发起入口等于某值（无该字段的旧记录视为 Unknown）
+    OriginEquals(OperationOrigin),
+    /// `warnings` 中含有某类警告
+    HasWarning(WarningKind),
     /// 自定义字段匹配
     CustomField { field: String, value: String },
+    /// 解码字段关键字匹配（与 `NdjsonReader::filter_with` 相同）
+    Text(FilterSpec),
+    /// 查询语句（见 `querylang`）
+    Query(ParsedQuery),
 }
 
 /// 排序顺序
@@ -84,6 +101,12 @@ impl QueryBuilder {
         self
     }
 
+    /// 按操作备注关键字过滤（无备注的记录不匹配）
+    pub fn filter_comment_contains(mut self, substr: &str) -> Self {
+        self.filters.push(Filter::CommentContains(substr.to_string()));
+        self
+    }
+
     /// 过滤时间晚于某时刻的记录
     pub fn filter_time_after(mut self, time: &str) -> Self {
         self.filters.push(Filter::TimeAfter(time.to_string()));
@@ -108,6 +131,18 @@ impl QueryBuilder {
         self
     }
 
+    /// 按发起入口过滤（`Unknown` 匹配未记录来源的旧记录）
+    pub fn filter_origin(mut self, origin: OperationOrigin) -> Self {
+        self.filters.push(Filter::OriginEquals(origin));
+        self
+    }
+
+    /// 按警告类型过滤（只返回带有该类警告的记录）
+    pub fn filter_has_warning(mut self, kind: WarningKind) -> Self {
+        self.filters.push(Filter::HasWarning(kind));
+        self
+    }
+
     /// 自定义字段过滤
     pub fn filter_custom(mut self, field: &str, value: &str) -> Self {
         self.filters.push(Filter::CustomField {
@@ -117,6 +152,12 @@ impl QueryBuilder {
         self
     }
 
+    /// 按查询语句过滤（见 `querylang::parse_query`）
+    pub fn filter_query(mut self, query: ParsedQuery) -> Self {
+        self.filters.push(Filter::Query(query));
+        self
+    }
+
     /// 设置按时间倒序排序
     pub fn sort_desc(mut self) -> Self {
         self.sort_order = SortOrder::Desc;
@@ -160,21 +201,10 @@ impl QueryBuilder {
 
         // 排序
         let mut sorted = filtered;
+        // 按 UTC 时刻排序，同一时刻按 seq_no，保证分页边界稳定
         match self.sort_order {
-            SortOrder::Asc => {
-                sorted.sort_by(|a, b| {
-                    let time_a = a.get("time_utc").and_then(|v| v.as_str()).unwrap_or("");
-                    let time_b = b.get("time_utc").and_then(|v| v.as_str()).unwrap_or("");
-                    time_a.cmp(time_b)
-                });
-            }
-            SortOrder::Desc => {
-                sorted.sort_by(|a, b| {
-                    let time_a = a.get("time_utc").and_then(|v| v.as_str()).unwrap_or("");
-                    let time_b = b.get("time_utc").and_then(|v| v.as_str()).unwrap_or("");
-                    time_b.cmp(time_a)
-                });
-            }
+            SortOrder::Asc => sorted.sort_by_cached_key(record_order),
+            SortOrder::Desc => sorted.sort_by_cached_key(|record| Reverse(record_order(record))),
             SortOrder::None => {}
         }
 
@@ -200,7 +230,14 @@ impl QueryBuilder {
 
     /// 内部方法：检查单个过滤器
     fn check_filter(&self, record: &Value, filter: &Filter) -> bool {
-        match filter {
+        filter.matches(record)
+    }
+}
+
+impl Filter {
+    /// 检查记录是否满足本条件
+    pub(crate) fn matches(&self, record: &Value) -> bool {
+        match self {
             Filter::StatusEquals(status) => record
                 .get("status")
                 .and_then(|v| v.as_str())
@@ -211,15 +248,20 @@ impl QueryBuilder {
                 .and_then(|v| v.as_str())
                 .map(|s| s.contains(substr.as_str()))
                 .unwrap_or(false),
+            Filter::CommentContains(substr) => record
+                .get("comment")
+                .and_then(|v| v.as_str())
+                .map(|s| s.contains(substr.as_str()))
+                .unwrap_or(false),
             Filter::TimeAfter(time) => record
                 .get("time_utc")
                 .and_then(|v| v.as_str())
-                .map(|s| s >= time.as_str())
+                .map(|s| compare_utc(s, time).is_ge())
                 .unwrap_or(false),
             Filter::TimeBefore(time) => record
                 .get("time_utc")
                 .and_then(|v| v.as_str())
-                .map(|s| s <= time.as_str())
+                .map(|s| compare_utc(s, time).is_le())
                 .unwrap_or(false),
             Filter::UserSidEquals(sid) => record
                 .get("user_sid")
@@ -231,17 +273,32 @@ impl QueryBuilder {
                 .and_then(|v| v.as_str())
                 .map(|s| s == level)
                 .unwrap_or(false),
+            Filter::OriginEquals(origin) => record
+                .get("origin")
+                .and_then(|v| v.as_str())
+                .unwrap_or(OperationOrigin::Unknown.as_str())
+                == origin.as_str(),
+            Filter::HasWarning(kind) => record
+                .get("warnings")
+                .and_then(|v| v.as_array())
+                .is_some_and(|warnings| {
+                    warnings
+                        .iter()
+                        .any(|w| w.get("kind").and_then(|k| k.as_str()) == Some(kind.as_str()))
+                }),
             Filter::CustomField { field, value } => record
                 .get(field)
                 .and_then(|v| v.as_str())
                 .map(|s| s == value)
                 .unwrap_or(false),
+            Filter::Text(spec) => spec.matches(record),
+            Filter::Query(query) => query.matches(record),
         }
     }
 }
 
 /// 日志统计信息
-#[derive(Debug, Clone)]
+#[derive(Debug, Clone, PartialEq, Eq)]
 pub struct LogStatistics {
     /// 总记录数
     pub total_count: usize,
@@ -255,6 +312,12 @@ pub struct LogStatistics {
     pub unique_users: usize,
     /// 唯一路径数量
     pub unique_paths: usize,
+    /// `unique_users` 超过精确计数上限，为近似值
+    pub unique_users_approximate: bool,
+    /// `unique_paths` 超过精确计数上限，为近似值
+    pub unique_paths_approximate: bool,
+    /// 按发起入口分类的记录数（键为 `OperationOrigin::as_str`）
+    pub by_origin: BTreeMap<String, usize>,
 }
 
 /// 生成日志统计信息
@@ -266,50 +329,250 @@ pub struct LogStatistics {
 /// println!("成功率: {:.2}%",
 ///     stats.success_count as f64 / stats.total_count as f64 * 100.0);
 /// ```
+///
+/// # 注意
+/// 每次都解析整个日志；频繁调用时请使用 `stats::generate_statistics_cached`
 pub fn generate_statistics<P: AsRef<Path>>(file_path: P) -> anyhow::Result<LogStatistics> {
     let mut reader = NdjsonReader::open(file_path)?;
-    let all_lines = reader.read_all_lines()?;
-
-    let mut stats = LogStatistics {
-        total_count: 0,
-        success_count: 0,
-        error_count: 0,
-        pending_count: 0,
-        unique_users: 0,
-        unique_paths: 0,
-    };
-
-    let mut users = std::collections::HashSet::new();
-    let mut paths = std::collections::HashSet::new();
-
-    for line in all_lines {
-        if let Ok(record) = serde_json::from_str::<Value>(&line) {
-            stats.total_count += 1;
-
-            // 统计状态
-            if let Some(status) = record.get("status").and_then(|v| v.as_str()) {
-                match status {
-                    "success" => stats.success_count += 1,
-                    "error" => stats.error_count += 1,
-                    "pending" => stats.pending_count += 1,
-                    _ => {}
-                }
-            }
+    let mut cache = StatsCache::default();
 
-            // 收集唯一用户
-            if let Some(user_sid) = record.get("user_sid").and_then(|v| v.as_str()) {
-                users.insert(user_sid.to_string());
-            }
+    reader.for_each_line(|line| {
+        if let Ok(record) = serde_json::from_str::<Value>(line) {
+            cache.fold_record(&record);
+        }
+    })?;
 
-            // 收集唯一路径
-            if let Some(path) = record.get("path").and_then(|v| v.as_str()) {
-                paths.insert(path.to_string());
-            }
+    Ok(cache.statistics())
+}
+
+// ================================
+// 时间序列统计
+// ================================
+
+/// 记录状态分类（对应日志 `status` 字段）
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum RecordStatus {
+    /// 上锁成功（`success`）
+    Success,
+    /// 操作失败（`error`）
+    Error,
+    /// 以低于期望的级别完成（`downgraded`）
+    Downgraded,
+    /// 解锁成功（`unlocked`）
+    Unlocked,
+    /// 其他状态（如 `pending`、`alert_failed`）
+    Other,
+}
+
+impl RecordStatus {
+    /// 从 `status` 字段解析
+    pub fn parse(status: &str) -> Self {
+        match status {
+            "success" => RecordStatus::Success,
+            "error" => RecordStatus::Error,
+            "downgraded" => RecordStatus::Downgraded,
+            "unlocked" => RecordStatus::Unlocked,
+            _ => RecordStatus::Other,
+        }
+    }
+}
+
+/// 时间序列分桶窗口
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum TimeWindow {
+    /// 按 UTC 自然日
+    Day,
+    /// 按 UTC 整点小时
+    Hour,
+}
+
+impl TimeWindow {
+    /// 窗口长度
+    pub fn duration(self) -> Duration {
+        match self {
+            TimeWindow::Day => Duration::days(1),
+            TimeWindow::Hour => Duration::hours(1),
         }
     }
 
-    stats.unique_users = users.len();
-    stats.unique_paths = paths.len();
+    /// 时间点所在桶的起始时间（UTC）
+    pub fn floor(self, time: OffsetDateTime) -> OffsetDateTime {
+        let time = time.to_offset(time::UtcOffset::UTC);
+        let hour = match self {
+            TimeWindow::Day => 0,
+            TimeWindow::Hour => time.hour(),
+        };
+        time.replace_time(time::Time::from_hms(hour, 0, 0).expect("整点时间有效"))
+    }
+}
+
+/// 时间序列指标
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Metric {
+    /// 成功数
+    Success,
+    /// 失败数
+    Error,
+    /// 降级数
+    Downgraded,
+    /// 解锁数
+    Unlocked,
+}
+
+/// 时间序列统计规格
+#[derive(Debug, Clone, Copy)]
+pub struct TimeseriesSpec<'a> {
+    /// 分桶窗口
+    pub window: TimeWindow,
+    /// 桶数量（以 `end` 所在的桶为最后一个）
+    pub span: usize,
+    /// 统计截止时间（None 表示当前时间）
+    pub end: Option<OffsetDateTime>,
+    /// 需要输出的指标
+    pub metrics: &'a [Metric],
+}
+
+/// 单个时间桶的计数
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct Bucket {
+    /// 桶起始时间（UTC，含）
+    pub start: OffsetDateTime,
+    /// 成功数
+    pub success: u64,
+    /// 失败数
+    pub error: u64,
+    /// 降级数
+    pub downgraded: u64,
+    /// 解锁数
+    pub unlocked: u64,
+}
+
+impl Bucket {
+    fn empty(start: OffsetDateTime) -> Self {
+        Self {
+            start,
+            success: 0,
+            error: 0,
+            downgraded: 0,
+            unlocked: 0,
+        }
+    }
+
+    /// 指定指标的计数
+    pub fn get(&self, metric: Metric) -> u64 {
+        match metric {
+            Metric::Success => self.success,
+            Metric::Error => self.error,
+            Metric::Downgraded => self.downgraded,
+            Metric::Unlocked => self.unlocked,
+        }
+    }
+}
 
-    Ok(stats)
+/// 时间序列统计结果
+#[derive(Debug, Clone)]
+pub struct Timeseries {
+    /// 分桶窗口
+    pub window: TimeWindow,
+    /// 请求的指标
+    pub metrics: Vec<Metric>,
+    /// 按时间升序排列的桶（无记录的桶计数为 0）
+    pub buckets: Vec<Bucket>,
+    /// 缺少 `time_utc`、时间戳无法解析或整行不是合法 JSON 的记录数
+    pub unbucketed: u64,
+    /// 时间早于第一个桶或晚于最后一个桶的记录数
+    pub out_of_range: u64,
+}
+
+impl Timeseries {
+    /// 指定指标的序列；未在规格中请求时返回 None
+    pub fn series(&self, metric: Metric) -> Option<Vec<u64>> {
+        self.metrics
+            .contains(&metric)
+            .then(|| self.buckets.iter().map(|b| b.get(metric)).collect())
+    }
+
+    /// 各桶失败率：失败 / (成功 + 失败 + 降级 + 解锁)，空桶为 0
+    pub fn failure_rate(&self) -> Vec<f32> {
+        self.buckets
+            .iter()
+            .map(|b| {
+                let total = b.success + b.error + b.downgraded + b.unlocked;
+                if total == 0 {
+                    0.0
+                } else {
+                    b.error as f32 / total as f32
+                }
+            })
+            .collect()
+    }
+}
+
+/// 按时间窗口生成日志统计序列（单次流式遍历）
+///
+/// # 参数
+/// - `file_path`: 日志文件路径
+/// - `spec`: 分桶规格
+///
+/// # 规则
+/// - 桶按 UTC 对齐：`Day` 为 00:00:00Z 起的自然日，`Hour` 为整点
+/// - 每个桶为左闭右开区间，恰好落在边界上的记录归入后一个桶
+/// - 带时区偏移的时间戳先换算为 UTC 再分桶，界面显示时可再转换为本地时间
+/// - 无记录的桶同样输出（计数为 0），保证图表没有缺口
+/// - 时间戳缺失或无法解析的记录计入 `unbucketed`，不会被静默丢弃
+pub fn generate_timeseries<P: AsRef<Path>>(
+    file_path: P,
+    spec: &TimeseriesSpec,
+) -> anyhow::Result<Timeseries> {
+    let step = spec.window.duration();
+    let end = spec.end.unwrap_or_else(OffsetDateTime::now_utc);
+    let last = spec.window.floor(end);
+    let first = last - step * (spec.span.saturating_sub(1) as i32);
+
+    let mut buckets: Vec<Bucket> = (0..spec.span)
+        .map(|i| Bucket::empty(first + step * i as i32))
+        .collect();
+    let mut unbucketed = 0;
+    let mut out_of_range = 0;
+
+    let mut reader = NdjsonReader::open(file_path)?;
+    reader.for_each_line(|line| {
+        let Ok(record) = serde_json::from_str::<Value>(line) else {
+            unbucketed += 1;
+            return;
+        };
+        let Some(time) = record
+            .get("time_utc")
+            .and_then(|v| v.as_str())
+            .and_then(parse_utc)
+        else {
+            unbucketed += 1;
+            return;
+        };
+
+        let start = spec.window.floor(time);
+        if spec.span == 0 || start < first || start > last {
+            out_of_range += 1;
+            return;
+        }
+        let index = ((start - first).whole_seconds() / step.whole_seconds()) as usize;
+        let bucket = &mut buckets[index];
+
+        let status = record.get("status").and_then(|v| v.as_str()).unwrap_or("");
+        match RecordStatus::parse(status) {
+            RecordStatus::Success => bucket.success += 1,
+            RecordStatus::Error => bucket.error += 1,
+            RecordStatus::Downgraded => bucket.downgraded += 1,
+            RecordStatus::Unlocked => bucket.unlocked += 1,
+            RecordStatus::Other => {}
+        }
+    })?;
+
+    Ok(Timeseries {
+        window: spec.window,
+        metrics: spec.metrics.to_vec(),
+        buckets,
+        unbucketed,
+        out_of_range,
+    })
 }
@@ -0,0 +1,813 @@
+//! 日志查询语句
+//!
+//! 供界面日志筛选框与 `log tail --query` 使用的简短查询语法，编译为
+//! `QueryBuilder` 的过滤条件：
+//!
+//! ```text
+//! status:error path:Finance after:2025-01-01
+//! level:high OR -origin:scheduler "年度 报表"
+//! ```
+//!
+//! - `字段:取值`：字段见 [`QUERY_FIELDS`]，取值含空格时用双引号包裹
+//! - 不带字段的关键字：对解码后的字段做不区分大小写的子串匹配（同 `NdjsonReader::filter`）
+//! - 相邻条件同时满足（AND），`OR` 连接备选条件组，AND 优先于 OR
+//! - 条件前加 `-` 表示取反
+//!
+//! 解析错误带有出错位置（字节区间），界面据此选中出错的片段。
+
+use crate::query::Filter;
+use crate::{FilterSpec, format_utc};
+use amberlock_types::{LabelLevel, OperationOrigin, WarningKind};
+use serde_json::Value;
+use std::fmt;
+use std::ops::Range;
+use time::{Date, Duration, Month, OffsetDateTime};
+
+/// 查询语句支持的字段
+pub const QUERY_FIELDS: &[&str] = &[
+    "status",
+    "path",
+    "user",
+    "level",
+    "origin",
+    "batch",
+    "after",
+    "before",
+    "has-warning",
+];
+
+/// 连接备选条件组的关键字（须大写）
+const OR_KEYWORD: &str = "OR";
+
+/// 解析后的查询语句
+///
+/// 由 `OR` 分隔的若干条件组构成，组内条件同时满足；空语句匹配所有记录。
+#[derive(Debug, Clone, Default)]
+pub struct ParsedQuery {
+    groups: Vec<Vec<QueryTerm>>,
+}
+
+/// 单个条件
+#[derive(Debug, Clone)]
+pub(crate) struct QueryTerm {
+    /// 用户写了 `-` 前缀
+    negated: bool,
+    /// 字段名（不带字段的关键字为 None）
+    field: Option<&'static str>,
+    /// 规范化后的取值（用于显示）
+    value: String,
+    /// 编译得到的过滤条件
+    filter: Filter,
+    /// 过滤条件的结果需取反（如仅日期的 `before` 编译为“不晚于次日零点”）
+    inverted: bool,
+}
+
+/// 查询语句解析错误
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct QueryParseError {
+    /// 错误说明
+    pub message: String,
+    /// 出错片段在输入中的字节区间
+    pub span: Range<usize>,
+}
+
+impl ParsedQuery {
+    /// 语句中没有任何条件
+    pub fn is_empty(&self) -> bool {
+        self.groups.is_empty()
+    }
+
+    /// 检查单条记录是否匹配
+    pub fn matches(&self, record: &Value) -> bool {
+        self.is_empty()
+            || self
+                .groups
+                .iter()
+                .any(|group| group.iter().all(|term| term.matches(record)))
+    }
+}
+
+impl fmt::Display for ParsedQuery {
+    /// 规范化的语句文本（字段名小写、取值按需加引号）
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        for (i, group) in self.groups.iter().enumerate() {
+            if i > 0 {
+                write!(f, " {} ", OR_KEYWORD)?;
+            }
+            for (j, term) in group.iter().enumerate() {
+                if j > 0 {
+                    write!(f, " ")?;
+                }
+                write!(f, "{}", term)?;
+            }
+        }
+        Ok(())
+    }
+}
+
+impl QueryTerm {
+    fn matches(&self, record: &Value) -> bool {
+        self.filter.matches(record) != (self.negated != self.inverted)
+    }
+}
+
+impl fmt::Display for QueryTerm {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        if self.negated {
+            write!(f, "-")?;
+        }
+        if let Some(field) = self.field {
+            write!(f, "{}:", field)?;
+        }
+        let needs_quotes = self.value.is_empty()
+            || self.value.chars().any(char::is_whitespace)
+            || (self.field.is_none()
+                && (self.value == OR_KEYWORD
+                    || self.value.starts_with('-')
+                    || field_prefix(&self.value).is_some()));
+        if needs_quotes {
+            write!(f, "\"{}\"", self.value)
+        } else {
+            write!(f, "{}", self.value)
+        }
+    }
+}
+
+impl QueryParseError {
+    fn new(message: impl Into<String>, span: Range<usize>) -> Self {
+        Self {
+            message: message.into(),
+            span,
+        }
+    }
+
+    /// 出错片段在输入中的字符区间（用于在终端中标出位置）
+    pub fn char_span(&self, input: &str) -> Range<usize> {
+        let start = input[..self.span.start].chars().count();
+        let len = input[self.span.clone()].chars().count();
+        start..start + len
+    }
+}
+
+impl fmt::Display for QueryParseError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "{}", self.message)
+    }
+}
+
+impl std::error::Error for QueryParseError {}
+
+/// 输入是否使用了 `字段:取值` 语法
+///
+/// 不含字段语法的输入由界面按普通关键字过滤处理，保持旧的筛选行为。
+pub fn has_field_syntax(input: &str) -> bool {
+    input.split_whitespace().any(|word| {
+        let word = word.strip_prefix('-').unwrap_or(word);
+        field_prefix(word).is_some()
+    })
+}
+
+/// 解析查询语句
+///
+/// # 错误
+/// 未知字段、缺少取值、引号未闭合、`OR` 两侧缺少条件、取值无效（级别、来源、
+/// 警告类型、日期）时返回错误，`span` 指向出错的片段
+///
+/// # 示例
+/// ```rust,no_run
+/// # use amberlock_storage::parse_query;
+/// # use amberlock_storage::query::QueryBuilder;
+/// # fn main() -> anyhow::Result<()> {
+/// # let log_path = "logs/operations.ndjson";
+/// let query = parse_query("status:error path:\"My Docs\" OR level:high")?;
+/// let hits = QueryBuilder::new(log_path).filter_query(query).execute()?;
+/// # Ok(())
+/// # }
+/// ```
+pub fn parse_query(input: &str) -> Result<ParsedQuery, QueryParseError> {
+    let mut groups: Vec<Vec<QueryTerm>> = Vec::new();
+    let mut current = Vec::new();
+    let mut pending_or: Option<Range<usize>> = None;
+
+    for token in tokenize(input)? {
+        match token {
+            Token::Or(span) => {
+                if current.is_empty() {
+                    return Err(QueryParseError::new("OR 前缺少条件", span));
+                }
+                groups.push(std::mem::take(&mut current));
+                pending_or = Some(span);
+            }
+            Token::Term(raw) => {
+                current.push(compile_term(input, raw)?);
+                pending_or = None;
+            }
+        }
+    }
+
+    if let Some(span) = pending_or {
+        return Err(QueryParseError::new("OR 后缺少条件", span));
+    }
+    if !current.is_empty() {
+        groups.push(current);
+    }
+    Ok(ParsedQuery { groups })
+}
+
+/// 词法单元
+enum Token {
+    /// `OR` 关键字
+    Or(Range<usize>),
+    /// 条件
+    Term(RawTerm),
+}
+
+/// 尚未编译的条件
+struct RawTerm {
+    negated: bool,
+    /// 字段名及其字节区间
+    field: Option<(String, Range<usize>)>,
+    value: String,
+    /// 整个条件（含 `-` 前缀）的字节区间
+    span: Range<usize>,
+}
+
+/// 若 `text` 以 `字段名:` 开头，返回字段名的字节长度
+///
+/// 字段名至少两个字符，只含 ASCII 字母与 `-`，因此 `C:\Users` 仍视为普通关键字。
+fn field_prefix(text: &str) -> Option<usize> {
+    let len = text
+        .find(|c: char| !(c.is_ascii_alphabetic() || c == '-'))
+        .unwrap_or(text.len());
+    (len >= 2 && text[len..].starts_with(':')).then_some(len)
+}
+
+/// 从 `start` 处的引号开始读取带引号的取值，返回（取值，结束位置）
+fn read_quoted(input: &str, start: usize) -> Result<(String, usize), QueryParseError> {
+    let content_start = start + 1;
+    match input[content_start..].find('"') {
+        Some(len) => Ok((
+            input[content_start..content_start + len].to_string(),
+            content_start + len + 1,
+        )),
+        None => Err(QueryParseError::new("引号未闭合", start..input.len())),
+    }
+}
+
+/// 从 `start` 处读取到下一个空白为止，返回（取值，结束位置）
+fn read_word(input: &str, start: usize) -> (String, usize) {
+    let end = input[start..]
+        .find(char::is_whitespace)
+        .map_or(input.len(), |len| start + len);
+    (input[start..end].to_string(), end)
+}
+
+fn tokenize(input: &str) -> Result<Vec<Token>, QueryParseError> {
+    let mut tokens = Vec::new();
+    let mut pos = 0;
+
+    while let Some(skip) = input[pos..].find(|c: char| !c.is_whitespace()) {
+        pos += skip;
+        let start = pos;
+        let rest = &input[pos..];
+
+        // 单独的 `-` 或 `-` 后紧跟空白时按普通关键字处理
+        let negated =
+            rest.starts_with('-') && rest[1..].chars().next().is_some_and(|c| !c.is_whitespace());
+        let body = if negated { start + 1 } else { start };
+
+        let field = field_prefix(&input[body..]).map(|len| {
+            (
+                input[body..body + len].to_ascii_lowercase(),
+                body..body + len,
+            )
+        });
+        let value_start = match &field {
+            Some((_, name)) => name.end + 1,
+            None => body,
+        };
+
+        let (value, end) = if input[value_start..].starts_with('"') {
+            read_quoted(input, value_start)?
+        } else {
+            let (word, end) = read_word(input, value_start);
+            if !negated && field.is_none() && word == OR_KEYWORD {
+                tokens.push(Token::Or(start..end));
+                pos = end;
+                continue;
+            }
+            (word, end)
+        };
+
+        tokens.push(Token::Term(RawTerm {
+            negated,
+            field,
+            value,
+            span: start..end,
+        }));
+        pos = end;
+    }
+
+    Ok(tokens)
+}
+
+fn compile_term(input: &str, raw: RawTerm) -> Result<QueryTerm, QueryParseError> {
+    let Some((name, name_span)) = raw.field else {
+        return Ok(QueryTerm {
+            negated: raw.negated,
+            field: None,
+            filter: Filter::Text(FilterSpec::new(&raw.value)),
+            value: raw.value,
+            inverted: false,
+        });
+    };
+
+    let Some(&field) = QUERY_FIELDS.iter().find(|f| **f == name) else {
+        return Err(QueryParseError::new(
+            format!(
+                "未知字段“{}”，可用字段：{}",
+                &input[name_span.clone()],
+                QUERY_FIELDS.join("、")
+            ),
+            name_span,
+        ));
+    };
+
+    let value = raw.value.trim();
+    if value.is_empty() {
+        return Err(QueryParseError::new(
+            format!("字段 {} 缺少取值", field),
+            raw.span,
+        ));
+    }
+    let invalid = |expected: String| {
+        QueryParseError::new(
+            format!("{} 的取值“{}”无效，应为 {}", field, value, expected),
+            raw.span.clone(),
+        )
+    };
+
+    let (filter, value, inverted) = match field {
+        "status" => {
+            let status = value.to_lowercase();
+            (Filter::StatusEquals(status.clone()), status, false)
+        }
+        "path" => (
+            Filter::PathContains(value.to_string()),
+            value.to_string(),
+            false,
+        ),
+        "user" => (
+            Filter::UserSidEquals(value.to_string()),
+            value.to_string(),
+            false,
+        ),
+        "batch" => (
+            Filter::CustomField {
+                field: "batch_id".to_string(),
+                value: value.to_string(),
+            },
+            value.to_string(),
+            false,
+        ),
+        "level" => {
+            let name = LabelLevel::ALL
+                .iter()
+                .map(level_name)
+                .find(|name| name.eq_ignore_ascii_case(value))
+                .ok_or_else(|| {
+                    invalid(
+                        LabelLevel::ALL
+                            .iter()
+                            .map(level_name)
+                            .collect::<Vec<_>>()
+                            .join("、"),
+                    )
+                })?;
+            (
+                Filter::LevelEquals(name.clone()),
+                name.to_lowercase(),
+                false,
+            )
+        }
+        "origin" => {
+            let origin = OperationOrigin::ALL
+                .into_iter()
+                .find(|o| o.as_str().eq_ignore_ascii_case(value))
+                .ok_or_else(|| invalid(OperationOrigin::ALL.map(|o| o.as_str()).join("、")))?;
+            (
+                Filter::OriginEquals(origin),
+                origin.as_str().to_string(),
+                false,
+            )
+        }
+        "has-warning" => {
+            let kind = WarningKind::ALL
+                .into_iter()
+                .find(|k| k.as_str().eq_ignore_ascii_case(value))
+                .ok_or_else(|| invalid(WarningKind::ALL.map(|k| k.as_str()).join("、")))?;
+            (Filter::HasWarning(kind), kind.as_str().to_string(), false)
+        }
+        "after" => {
+            let bound = parse_bound(value).ok_or_else(|| invalid(DATE_EXPECTED.to_string()))?;
+            (
+                Filter::TimeAfter(format_utc(bound.start)),
+                value.to_string(),
+                false,
+            )
+        }
+        "before" => match parse_bound(value).ok_or_else(|| invalid(DATE_EXPECTED.to_string()))? {
+            // 仅日期：包含当天全天，即“早于次日零点”
+            Bound {
+                date_only: true,
+                start,
+            } => (
+                Filter::TimeAfter(format_utc(start + Duration::DAY)),
+                value.to_string(),
+                true,
+            ),
+            Bound { start, .. } => (
+                Filter::TimeBefore(format_utc(start)),
+                value.to_string(),
+                false,
+            ),
+        },
+        _ => unreachable!("QUERY_FIELDS 中的每个字段都有对应分支"),
+    };
+
+    Ok(QueryTerm {
+        negated: raw.negated,
+        field: Some(field),
+        value,
+        filter,
+        inverted,
+    })
+}
+
+/// 日期取值的格式说明
+const DATE_EXPECTED: &str = "YYYY-MM-DD 或 RFC 3339 时间（如 2025-01-01T08:00:00Z）";
+
+/// 日期或时间取值
+struct Bound {
+    /// 对应的 UTC 时刻（仅日期时为当天零点）
+    start: OffsetDateTime,
+    date_only: bool,
+}
+
+/// 解析 `YYYY-MM-DD` 或 RFC 3339 时间（带偏移的时间换算为 UTC）
+fn parse_bound(value: &str) -> Option<Bound> {
+    if let Some(time) = crate::parse_utc(value) {
+        return Some(Bound {
+            start: time,
+            date_only: false,
+        });
+    }
+
+    let mut parts = value.splitn(3, '-');
+    let year = parts.next()?;
+    let month = parts.next()?;
+    let day = parts.next()?;
+    if year.len() != 4 || month.len() != 2 || day.len() != 2 {
+        return None;
+    }
+    let date = Date::from_calendar_date(
+        year.parse().ok()?,
+        Month::try_from(month.parse::<u8>().ok()?).ok()?,
+        day.parse().ok()?,
+    )
+    .ok()?;
+    Some(Bound {
+        start: date.midnight().assume_utc(),
+        date_only: true,
+    })
+}
+
+/// 日志中 `level_applied` 的字符串形式
+fn level_name(level: &LabelLevel) -> String {
+    serde_json::to_value(level)
+        .ok()
+        .and_then(|v| v.as_str().map(str::to_string))
+        .unwrap_or_default()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::query::QueryBuilder;
+    use crate::{NdjsonReader, NdjsonWriter};
+    use serde_json::json;
+    use tempfile::TempDir;
+
+    fn parse(input: &str) -> ParsedQuery {
+        parse_query(input).expect("解析失败")
+    }
+
+    fn parse_err(input: &str) -> QueryParseError {
+        parse_query(input).expect_err("应当解析失败")
+    }
+
+    #[test]
+    fn test_parse_normalizes_terms() {
+        assert_eq!(
+            parse("Status:Error path:Finance level:HIGH origin:GUI_BUTTON").to_string(),
+            "status:error path:Finance level:high origin:gui_button"
+        );
+        assert_eq!(
+            parse("  has-warning:streams_not_protected   batch:b1  ").to_string(),
+            "has-warning:streams_not_protected batch:b1"
+        );
+        assert!(parse("").is_empty());
+        assert!(parse("   ").is_empty());
+        println!("✅ 字段名与取值规范化正确");
+    }
+
+    #[test]
+    fn test_parse_and_binds_tighter_than_or() {
+        let query = parse("status:error path:A OR status:success -path:B OR denied");
+        assert_eq!(
+            query.to_string(),
+            "status:error path:A OR status:success -path:B OR denied"
+        );
+        assert_eq!(query.groups.len(), 3);
+        assert_eq!(query.groups[0].len(), 2);
+        assert_eq!(query.groups[1].len(), 2);
+        assert!(query.groups[1][1].negated);
+
+        let error_in_a = json!({"status": "error", "path": "A\\x"});
+        let error_in_c = json!({"status": "error", "path": "C\\x"});
+        let success_in_b = json!({"status": "success", "path": "B\\x"});
+        let success_in_c = json!({"status": "success", "path": "C\\x"});
+        let denied = json!({"status": "error", "path": "C\\x", "errors": ["Access denied"]});
+        assert!(query.matches(&error_in_a));
+        assert!(!query.matches(&error_in_c));
+        assert!(!query.matches(&success_in_b));
+        assert!(query.matches(&success_in_c));
+        assert!(query.matches(&denied));
+        println!("✅ AND 优先于 OR");
+    }
+
+    #[test]
+    fn test_parse_quoted_values() {
+        let query = parse("path:\"D:\\My Docs\" \"年度 报表\" -\"OR\"");
+        assert_eq!(
+            query.to_string(),
+            "path:\"D:\\My Docs\" \"年度 报表\" -\"OR\""
+        );
+        let group = &query.groups[0];
+        assert_eq!(group.len(), 3);
+        assert_eq!(group[0].value, "D:\\My Docs");
+        assert_eq!(group[1].field, None);
+        assert_eq!(group[1].value, "年度 报表");
+        assert!(group[2].negated);
+        assert_eq!(group[2].value, "OR");
+
+        // 引号内的 OR 与字段语法都是普通文本
+        let quoted = parse("\"a OR b\" \"status:error\"");
+        assert_eq!(quoted.groups.len(), 1);
+        assert!(quoted.groups[0].iter().all(|term| term.field.is_none()));
+        println!("✅ 引号取值解析正确");
+    }
+
+    #[test]
+    fn test_bare_terms_use_decoded_substring_search() {
+        let query = parse("c:\\users -Temp");
+        let record = json!({"path": "C:\\Users\\test\\a.txt", "status": "success"});
+        let temp = json!({"path": "C:\\Users\\temp\\a.txt", "status": "success"});
+        assert!(query.matches(&record));
+        assert!(!query.matches(&temp));
+
+        // 单独的 `-` 是普通关键字
+        let dash = parse("- x");
+        assert_eq!(dash.groups[0].len(), 2);
+        assert!(!dash.groups[0][0].negated);
+        assert_eq!(dash.groups[0][0].value, "-");
+        println!("✅ 普通关键字按解码字段匹配");
+    }
+
+    #[test]
+    fn test_unicode_values_and_spans() {
+        let query = parse("path:文档 财务");
+        let record = json!({"path": "D:\\文档\\财务.xlsx", "status": "success"});
+        assert!(query.matches(&record));
+
+        let input = "财务 foo:bar";
+        let err = parse_err(input);
+        assert_eq!(&input[err.span.clone()], "foo");
+        assert_eq!(err.char_span(input), 3..6);
+        println!("✅ Unicode 取值与错误位置正确");
+    }
+
+    #[test]
+    fn test_unknown_field_reports_field_span() {
+        let input = "status:error stauts:ok";
+        let err = parse_err(input);
+        assert_eq!(&input[err.span.clone()], "stauts");
+        assert!(err.message.contains("stauts"));
+        assert!(err.message.contains("has-warning"));
+
+        let negated = parse_err("-Foo:bar");
+        assert_eq!(negated.span, 1..4);
+        println!("✅ 未知字段报告字段位置");
+    }
+
+    #[test]
+    fn test_empty_values_are_rejected() {
+        for input in ["status:", "path:\"\"", "x -user:", "batch:\"   \""] {
+            let err = parse_err(input);
+            assert!(err.message.contains("缺少取值"), "{}: {}", input, err);
+            assert_eq!(err.span.end, input.len(), "{}", input);
+        }
+        println!("✅ 空取值被拒绝");
+    }
+
+    #[test]
+    fn test_unterminated_quote_and_misplaced_or() {
+        let input = "status:error path:\"My Docs";
+        let err = parse_err(input);
+        assert!(err.message.contains("引号"));
+        assert_eq!(&input[err.span.clone()], "\"My Docs");
+
+        let err = parse_err("OR status:error");
+        assert_eq!(err.span, 0..2);
+        let err = parse_err("status:error OR");
+        assert_eq!(err.span, 13..15);
+        let err = parse_err("a OR OR b");
+        assert_eq!(err.span, 5..7);
+
+        // 小写 or 是普通关键字
+        assert_eq!(parse("a or b").groups[0].len(), 3);
+        println!("✅ 引号未闭合与 OR 位置错误被拒绝");
+    }
+
+    #[test]
+    fn test_invalid_enum_and_date_values() {
+        let err = parse_err("level:extreme");
+        assert!(err.message.contains("Medium"));
+        assert_eq!(err.span, 0..13);
+        assert!(parse_err("origin:mouse").message.contains("gui_button"));
+        assert!(
+            parse_err("has-warning:yes")
+                .message
+                .contains("placeholders_skipped")
+        );
+        for input in ["after:yesterday", "before:2025-13-01", "after:2025-1-1"] {
+            assert!(parse_err(input).message.contains("YYYY-MM-DD"), "{}", input);
+        }
+        println!("✅ 无效取值被拒绝");
+    }
+
+    #[test]
+    fn test_date_bounds() {
+        let query = parse("after:2025-01-01 before:2025-01-31");
+        let inside = json!({"time_utc": "2025-01-31T23:59:59Z"});
+        let first = json!({"time_utc": "2025-01-01T00:00:00Z"});
+        let next_day = json!({"time_utc": "2025-02-01T00:00:00Z"});
+        let earlier = json!({"time_utc": "2024-12-31T23:59:59Z"});
+        assert!(query.matches(&inside));
+        assert!(query.matches(&first));
+        assert!(!query.matches(&next_day));
+        assert!(!query.matches(&earlier));
+
+        // 带偏移的时间换算为 UTC
+        let offset = parse("after:2025-01-01T08:00:00+08:00");
+        assert!(offset.matches(&first));
+        assert!(!offset.matches(&earlier));
+
+        // 取反的仅日期 before 等价于“晚于当天”
+        let negated = parse("-before:2025-01-31");
+        assert!(!negated.matches(&inside));
+        assert!(negated.matches(&next_day));
+        println!("✅ 日期区间包含边界当天");
+    }
+
+    #[test]
+    fn test_has_field_syntax() {
+        assert!(has_field_syntax("status:error"));
+        assert!(has_field_syntax("foo -path:x"));
+        assert!(has_field_syntax("typo:x"));
+        assert!(!has_field_syntax("C:\\Users\\test"));
+        assert!(!has_field_syntax("denied OR error"));
+        assert!(!has_field_syntax("财务:报表"));
+        assert!(!has_field_syntax(""));
+        println!("✅ 字段语法检测正确");
+    }
+
+    fn write_fixture(dir: &TempDir) -> std::path::PathBuf {
+        let path = dir.path().join("fixture.ndjson");
+        let writer = NdjsonWriter::open_append(&path).expect("创建日志失败");
+        let records = [
+            json!({"id": "1", "time_utc": "2025-01-01T08:00:00Z", "path": "D:\\Finance\\q1.xlsx",
+                   "status": "success", "user_sid": "S-1-5-21-1", "level_applied": "High",
+                   "origin": "gui_button", "batch_id": "b1"}),
+            json!({"id": "2", "time_utc": "2025-01-02T09:00:00Z", "path": "D:\\Finance\\q2.xlsx",
+                   "status": "error", "user_sid": "S-1-5-21-1", "level_applied": "High",
+                   "origin": "gui_button", "batch_id": "b1", "errors": ["Access denied"]}),
+            json!({"id": "3", "time_utc": "2025-01-15T10:00:00Z", "path": "D:\\HR\\名单.docx",
+                   "status": "success", "user_sid": "S-1-5-21-2", "level_applied": "Medium",
+                   "origin": "scheduler", "batch_id": "b2",
+                   "warnings": [{"kind": "streams_not_protected"}]}),
+            json!({"id": "4", "time_utc": "2025-02-01T00:00:00Z", "path": "D:\\Finance\\q3.xlsx",
+                   "status": "unlocked", "user_sid": "S-1-5-21-2", "level_applied": "System",
+                   "batch_id": "b3"}),
+            json!({"id": "5", "time_utc": "2025-02-03T12:00:00Z", "path": "D:\\HR\\合同.pdf",
+                   "status": "error", "user_sid": "S-1-5-21-1", "level_applied": "Medium",
+                   "origin": "cli_batch", "batch_id": "b4",
+                   "warnings": [{"kind": "retried_after_transient_error"}]}),
+        ];
+        for record in &records {
+            writer.write_record(record).expect("写入记录失败");
+        }
+        writer.flush().expect("刷新失败");
+        path
+    }
+
+    fn ids(records: &[Value]) -> Vec<String> {
+        records
+            .iter()
+            .map(|r| r["id"].as_str().unwrap_or_default().to_string())
+            .collect()
+    }
+
+    fn run_query(path: &std::path::Path, input: &str) -> Vec<String> {
+        ids(&QueryBuilder::new(path)
+            .filter_query(parse(input))
+            .execute()
+            .expect("查询失败"))
+    }
+
+    #[test]
+    fn test_parsed_queries_match_query_builder_chains() {
+        let dir = TempDir::new().expect("创建临时目录失败");
+        let path = write_fixture(&dir);
+
+        let cases: Vec<(&str, QueryBuilder)> = vec![
+            (
+                "status:error path:Finance",
+                QueryBuilder::new(&path)
+                    .filter_status("error")
+                    .filter_path_contains("Finance"),
+            ),
+            (
+                "user:S-1-5-21-1 level:high",
+                QueryBuilder::new(&path)
+                    .filter_user_sid("S-1-5-21-1")
+                    .filter_level("High"),
+            ),
+            (
+                "origin:unknown",
+                QueryBuilder::new(&path).filter_origin(OperationOrigin::Unknown),
+            ),
+            (
+                "batch:b1",
+                QueryBuilder::new(&path).filter_custom("batch_id", "b1"),
+            ),
+            (
+                "has-warning:streams_not_protected",
+                QueryBuilder::new(&path).filter_has_warning(WarningKind::StreamsNotProtected),
+            ),
+            (
+                "after:2025-01-02T09:00:00Z before:2025-02-01T00:00:00Z",
+                QueryBuilder::new(&path)
+                    .filter_time_after("2025-01-02T09:00:00Z")
+                    .filter_time_before("2025-02-01T00:00:00Z"),
+            ),
+            (
+                "after:2025-01-15 level:medium",
+                QueryBuilder::new(&path)
+                    .filter_time_after("2025-01-15T00:00:00Z")
+                    .filter_level("Medium"),
+            ),
+        ];
+
+        for (input, builder) in cases {
+            let expected = ids(&builder.execute().expect("查询失败"));
+            assert!(!expected.is_empty(), "{} 的对照结果不应为空", input);
+            assert_eq!(run_query(&path, input), expected, "{}", input);
+        }
+        println!("✅ 查询语句与手写 QueryBuilder 结果一致");
+    }
+
+    #[test]
+    fn test_or_and_negation_over_fixture_log() {
+        let dir = TempDir::new().expect("创建临时目录失败");
+        let path = write_fixture(&dir);
+
+        assert_eq!(
+            run_query(&path, "status:error OR status:unlocked"),
+            vec!["2", "4", "5"]
+        );
+        assert_eq!(
+            run_query(&path, "path:Finance -status:success"),
+            vec!["2", "4"]
+        );
+        assert_eq!(run_query(&path, "before:2025-01-02"), vec!["1", "2"]);
+        assert_eq!(run_query(&path, "合同 OR 名单"), vec!["3", "5"]);
+        assert_eq!(run_query(&path, "denied"), vec!["2"]);
+
+        // 与普通关键字过滤的结果一致
+        let mut reader = NdjsonReader::open(&path).expect("打开日志失败");
+        let plain = reader.filter("finance", 100).expect("过滤失败");
+        assert_eq!(run_query(&path, "finance"), ids(&plain));
+        println!("✅ OR 与取反在日志上结果正确");
+    }
+}
@@ -0,0 +1,705 @@
+//! 操作日志副本
+//!
+//! 审计日志可能被有文件访问权限的用户或恶意程序删除、截断。
+//! `ReplicationSink` 把每条追加到主日志的记录同步写入副本（可位于其他卷
+//! 或 UNC 共享），写入后立即刷新，与主日志的持久化语义一致。
+//!
+//! 副本暂时不可用时，记录进入数据目录下的有界暂存文件，副本恢复后按原顺序
+//! 补写；暂存已满时丢弃新记录并持久化溢出计数，由启动报告醒目提示。
+//! 副本的任何失败都只影响副本本身，不会阻塞或导致主日志写入失败。
+//!
+//! `compare_logs` 按记录 id 比较主日志与副本，找出主日志缺失、多出或内容
+//! 不一致的记录；`restore_from_replica` 从副本补回主日志缺失的记录。
+
+use crate::atomic::{AtomicWriteOptions, atomic_write};
+use amberlock_types::LockRecord;
+use anyhow::Result;
+use parking_lot::Mutex;
+use serde_json::Value;
+use std::collections::{HashMap, HashSet};
+use std::fs::{File, OpenOptions};
+use std::io::{BufRead, BufReader, Write};
+use std::path::{Path, PathBuf};
+
+/// 暂存文件默认最多保留的记录数
+pub const DEFAULT_SPOOL_CAPACITY: usize = 100_000;
+
+/// 暂存文件与溢出计数文件
+struct Spool {
+    path: PathBuf,
+    /// 暂存中的记录数
+    len: usize,
+    capacity: usize,
+}
+
+/// 副本写入状态
+struct ReplicaState {
+    /// 已打开的副本文件（写入失败后丢弃，下次重新打开）
+    file: Option<File>,
+    spool: Spool,
+}
+
+/// 操作日志副本
+///
+/// 由 `OperationLog::with_replica` 挂接；所有方法都不返回错误，
+/// 副本或暂存不可用时静默降级
+pub struct ReplicationSink {
+    replica_path: PathBuf,
+    state: Mutex<ReplicaState>,
+}
+
+/// 暂存状态（启动报告使用）
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq)]
+pub struct SpoolStatus {
+    /// 等待补写到副本的记录数
+    pub pending: usize,
+    /// 暂存已满而丢弃的记录数（副本因此永久缺失这些记录）
+    pub overflowed: u64,
+}
+
+impl SpoolStatus {
+    /// 读取暂存文件的状态（文件不存在时为空）
+    pub fn read<P: AsRef<Path>>(spool_path: P) -> Self {
+        Self {
+            pending: count_lines(spool_path.as_ref()),
+            overflowed: read_overflow(spool_path.as_ref()),
+        }
+    }
+}
+
+impl ReplicationSink {
+    /// 创建副本写入器
+    ///
+    /// # 参数
+    /// - `replica_path`: 副本日志路径（此时不可用也可以创建）
+    /// - `spool_path`: 暂存文件路径（应位于本地数据目录）
+    pub fn open<P: AsRef<Path>, Q: AsRef<Path>>(replica_path: P, spool_path: Q) -> Self {
+        Self::with_capacity(replica_path, spool_path, DEFAULT_SPOOL_CAPACITY)
+    }
+
+    /// 创建副本写入器并指定暂存容量
+    pub fn with_capacity<P: AsRef<Path>, Q: AsRef<Path>>(
+        replica_path: P,
+        spool_path: Q,
+        capacity: usize,
+    ) -> Self {
+        let spool_path = spool_path.as_ref().to_path_buf();
+        Self {
+            replica_path: replica_path.as_ref().to_path_buf(),
+            state: Mutex::new(ReplicaState {
+                file: None,
+                spool: Spool {
+                    len: count_lines(&spool_path),
+                    path: spool_path,
+                    capacity,
+                },
+            }),
+        }
+    }
+
+    /// 副本日志路径
+    pub fn replica_path(&self) -> &Path {
+        &self.replica_path
+    }
+
+    /// 当前暂存状态
+    pub fn spool_status(&self) -> SpoolStatus {
+        let state = self.state.lock();
+        SpoolStatus {
+            pending: state.spool.len,
+            overflowed: read_overflow(&state.spool.path),
+        }
+    }
+
+    /// 将一条记录写入副本
+    ///
+    /// 有暂存记录时先尝试补写暂存，保证副本中的顺序与主日志一致；
+    /// 副本不可用时记录进入暂存
+    pub fn mirror(&self, record: &LockRecord) {
+        let Ok(line) = serde_json::to_string(record) else {
+            return;
+        };
+        let mut state = self.state.lock();
+        if state.spool.len > 0 && self.drain_locked(&mut state).is_err() {
+            state.spool.push(&line);
+            return;
+        }
+        if self
+            .write_locked(&mut state, std::slice::from_ref(&line))
+            .is_err()
+        {
+            state.spool.push(&line);
+        }
+    }
+
+    /// 将暂存记录补写到副本
+    ///
+    /// # 返回
+    /// - `Ok(n)`: 补写的记录数
+    /// - `Err`: 副本仍不可用（未写入的记录保留在暂存中）
+    pub fn drain(&self) -> Result<usize> {
+        let mut state = self.state.lock();
+        self.drain_locked(&mut state)
+    }
+
+    /// 清除溢出计数（确认副本已重新同步后调用）
+    pub fn clear_overflow(&self) -> Result<()> {
+        let state = self.state.lock();
+        match std::fs::remove_file(overflow_path(&state.spool.path)) {
+            Err(e) if e.kind() != std::io::ErrorKind::NotFound => Err(e.into()),
+            _ => Ok(()),
+        }
+    }
+
+    /// 补写暂存记录（调用方已持有状态锁）
+    fn drain_locked(&self, state: &mut ReplicaState) -> Result<usize> {
+        if state.spool.len == 0 {
+            return Ok(0);
+        }
+        let lines = read_lines(&state.spool.path)?;
+        let written = self.write_locked(state, &lines);
+        let done = match &written {
+            Ok(()) => lines.len(),
+            Err((done, _)) => *done,
+        };
+        state.spool.replace(&lines[done..])?;
+        match written {
+            Ok(()) => Ok(done),
+            Err((_, e)) => Err(e),
+        }
+    }
+
+    /// 逐行写入副本并刷新
+    ///
+    /// # 返回
+    /// 失败时返回已写入的行数与错误，副本文件句柄被丢弃以便下次重新打开
+    fn write_locked(
+        &self,
+        state: &mut ReplicaState,
+        lines: &[String],
+    ) -> std::result::Result<(), (usize, anyhow::Error)> {
+        if state.file.is_none() {
+            let file = OpenOptions::new()
+                .create(true)
+                .append(true)
+                .open(&self.replica_path)
+                .map_err(|e| (0, e.into()))?;
+            state.file = Some(file);
+        }
+        let file = state.file.as_mut().expect("副本文件已打开");
+        for (done, line) in lines.iter().enumerate() {
+            let mut bytes = Vec::with_capacity(line.len() + 1);
+            bytes.extend_from_slice(line.as_bytes());
+            bytes.push(b'\n');
+            if let Err(e) = file.write_all(&bytes).and_then(|_| file.flush()) {
+                state.file = None;
+                return Err((done, e.into()));
+            }
+        }
+        Ok(())
+    }
+}
+
+impl Spool {
+    /// 追加一行；已满时丢弃并累加溢出计数
+    fn push(&mut self, line: &str) {
+        if self.len >= self.capacity {
+            let overflowed = read_overflow(&self.path) + 1;
+            let _ = std::fs::write(overflow_path(&self.path), overflowed.to_string());
+            return;
+        }
+        let appended = OpenOptions::new()
+            .create(true)
+            .append(true)
+            .open(&self.path)
+            .and_then(|mut file| writeln!(file, "{}", line).and_then(|_| file.flush()));
+        if appended.is_ok() {
+            self.len += 1;
+        } else {
+            // 暂存本身不可写：按溢出处理，至少留下计数
+            let overflowed = read_overflow(&self.path) + 1;
+            let _ = std::fs::write(overflow_path(&self.path), overflowed.to_string());
+        }
+    }
+
+    /// 用剩余行替换暂存内容
+    fn replace(&mut self, remaining: &[String]) -> Result<()> {
+        if remaining.is_empty() {
+            File::create(&self.path)?;
+        } else {
+            let mut bytes = remaining.join("\n").into_bytes();
+            bytes.push(b'\n');
+            atomic_write(&self.path, &bytes, &AtomicWriteOptions::default())?;
+        }
+        self.len = remaining.len();
+        Ok(())
+    }
+}
+
+/// 溢出计数文件路径（`{暂存}.overflow`）
+fn overflow_path(spool_path: &Path) -> PathBuf {
+    let mut name = spool_path.file_name().unwrap_or_default().to_os_string();
+    name.push(".overflow");
+    spool_path.with_file_name(name)
+}
+
+/// 读取溢出计数（文件不存在或无效时为 0）
+fn read_overflow(spool_path: &Path) -> u64 {
+    std::fs::read_to_string(overflow_path(spool_path))
+        .ok()
+        .and_then(|s| s.trim().parse().ok())
+        .unwrap_or(0)
+}
+
+/// 读取全部非空行
+fn read_lines(path: &Path) -> Result<Vec<String>> {
+    let mut lines = Vec::new();
+    for line in BufReader::new(File::open(path)?).lines() {
+        let line = line?;
+        let trimmed = line.trim_end();
+        if !trimmed.is_empty() {
+            lines.push(trimmed.to_string());
+        }
+    }
+    Ok(lines)
+}
+
+/// 统计非空行数（文件不存在时为 0）
+fn count_lines(path: &Path) -> usize {
+    read_lines(path).map(|lines| lines.len()).unwrap_or(0)
+}
+
+// ================================
+// 主日志与副本比较
+// ================================
+
+/// 主日志与副本的差异（均为记录 id，按出现顺序）
+#[derive(Debug, Clone, Default, PartialEq, Eq)]
+pub struct DivergenceReport {
+    /// 副本中有、主日志中没有的记录（主日志被删除或截断）
+    pub missing_from_primary: Vec<String>,
+    /// 主日志中有、副本中没有的记录（副本落后或暂存溢出）
+    pub extra_in_primary: Vec<String>,
+    /// 两边内容不一致的记录（主日志或副本被修改）
+    pub mismatched: Vec<String>,
+    /// 主日志中无法解析或缺少 id 的行数
+    pub unreadable_primary: usize,
+}
+
+impl DivergenceReport {
+    /// 两边完全一致
+    pub fn is_consistent(&self) -> bool {
+        self.missing_from_primary.is_empty()
+            && self.extra_in_primary.is_empty()
+            && self.mismatched.is_empty()
+            && self.unreadable_primary == 0
+    }
+
+    /// 主日志疑似被删改（缺失、不一致或出现无法解析的行）
+    ///
+    /// 仅主日志多出记录时不算：副本可能只是尚未同步
+    pub fn primary_tampered(&self) -> bool {
+        !self.missing_from_primary.is_empty()
+            || !self.mismatched.is_empty()
+            || self.unreadable_primary > 0
+    }
+
+    /// 单行摘要
+    pub fn summary_line(&self) -> String {
+        format!(
+            "主日志缺失 {} 条、多出 {} 条、内容不一致 {} 条、无法解析 {} 行",
+            self.missing_from_primary.len(),
+            self.extra_in_primary.len(),
+            self.mismatched.len(),
+            self.unreadable_primary
+        )
+    }
+}
+
+/// 按 id 读取的日志：(id, 原始行, 解析后的值)
+struct IndexedLog {
+    entries: Vec<(String, String, Value)>,
+    unreadable: usize,
+}
+
+impl IndexedLog {
+    /// 读取日志（文件不存在视为空日志）
+    fn read(path: &Path) -> Result<Self> {
+        let lines = match read_lines(path) {
+            Ok(lines) => lines,
+            Err(e)
+                if e.downcast_ref::<std::io::Error>()
+                    .is_some_and(|e| e.kind() == std::io::ErrorKind::NotFound) =>
+            {
+                Vec::new()
+            }
+            Err(e) => return Err(e),
+        };
+
+        let mut entries = Vec::with_capacity(lines.len());
+        let mut unreadable = 0;
+        for line in lines {
+            let parsed = serde_json::from_str::<Value>(&line).ok().and_then(|value| {
+                let id = value.get("id")?.as_str()?.to_string();
+                Some((id, value))
+            });
+            match parsed {
+                Some((id, value)) => entries.push((id, line, value)),
+                None => unreadable += 1,
+            }
+        }
+        Ok(Self {
+            entries,
+            unreadable,
+        })
+    }
+
+    /// id → 解析后的值（重复 id 取第一条）
+    fn by_id(&self) -> HashMap<&str, &Value> {
+        let mut map = HashMap::with_capacity(self.entries.len());
+        for (id, _, value) in &self.entries {
+            map.entry(id.as_str()).or_insert(value);
+        }
+        map
+    }
+}
+
+/// 按记录 id 比较主日志与副本
+///
+/// # 参数
+/// - `primary`: 主日志路径
+/// - `replica`: 副本路径
+///
+/// # 返回
+/// - `Ok(DivergenceReport)`: 差异报告（文件不存在视为空日志）
+/// - `Err`: 读取失败
+///
+/// # 注意
+/// 记录按解析后的 JSON 值比较，字段顺序与空白差异不算不一致
+pub fn compare_logs<P: AsRef<Path>, Q: AsRef<Path>>(
+    primary: P,
+    replica: Q,
+) -> Result<DivergenceReport> {
+    let primary = IndexedLog::read(primary.as_ref())?;
+    let replica = IndexedLog::read(replica.as_ref())?;
+    let primary_ids = primary.by_id();
+    let replica_ids = replica.by_id();
+
+    let mut report = DivergenceReport {
+        unreadable_primary: primary.unreadable,
+        ..Default::default()
+    };
+    let mut seen = HashSet::new();
+    for (id, _, value) in &replica.entries {
+        if !seen.insert(id.as_str()) {
+            continue;
+        }
+        match primary_ids.get(id.as_str()) {
+            None => report.missing_from_primary.push(id.clone()),
+            Some(primary_value) if *primary_value != value => report.mismatched.push(id.clone()),
+            Some(_) => {}
+        }
+    }
+    let mut seen = HashSet::new();
+    for (id, _, _) in &primary.entries {
+        if seen.insert(id.as_str()) && !replica_ids.contains_key(id.as_str()) {
+            report.extra_in_primary.push(id.clone());
+        }
+    }
+    Ok(report)
+}
+
+/// 从副本补回主日志缺失的记录
+///
+/// # 参数
+/// - `primary`: 主日志路径
+/// - `replica`: 副本路径
+///
+/// # 返回
+/// - `Ok(n)`: 补回的记录数（没有缺失时为 0，主日志不被改写）
+/// - `Err`: 读取或写入失败（主日志保持原样）
+///
+/// # 注意
+/// - 结果按副本中的顺序排列，主日志独有的记录紧跟在它在主日志中的前一条记录之后
+/// - 内容不一致的记录保留主日志的版本，只在 `compare_logs` 中报告
+/// - 主日志被原子替换，调用前应关闭正在写入该文件的 `OperationLog`
+pub fn restore_from_replica<P: AsRef<Path>, Q: AsRef<Path>>(
+    primary: P,
+    replica: Q,
+) -> Result<usize> {
+    let primary_path = primary.as_ref();
+    let primary = IndexedLog::read(primary_path)?;
+    let replica = IndexedLog::read(replica.as_ref())?;
+    let primary_ids = primary.by_id();
+    let replica_ids = replica.by_id();
+
+    let mut restored = 0;
+    let mut seen = HashSet::new();
+    for (id, _, _) in &replica.entries {
+        if seen.insert(id.as_str()) && !primary_ids.contains_key(id.as_str()) {
+            restored += 1;
+        }
+    }
+    if restored == 0 {
+        return Ok(0);
+    }
+
+    // 主日志独有的记录按其前一条记录（None 表示文件开头）分组
+    let mut followers: HashMap<Option<&str>, Vec<(&str, &str)>> = HashMap::new();
+    let mut previous: Option<&str> = None;
+    for (id, line, _) in &primary.entries {
+        if !replica_ids.contains_key(id.as_str()) {
+            followers
+                .entry(previous)
+                .or_default()
+                .push((id.as_str(), line.as_str()));
+        }
+        previous = Some(id.as_str());
+    }
+    let primary_lines: HashMap<&str, &str> = primary
+        .entries
+        .iter()
+        .map(|(id, line, _)| (id.as_str(), line.as_str()))
+        .collect();
+
+    let mut output: Vec<&str> = Vec::new();
+    emit_followers(None, &mut followers, &mut output);
+    let mut seen = HashSet::new();
+    for (id, line, _) in &replica.entries {
+        if !seen.insert(id.as_str()) {
+            continue;
+        }
+        output.push(primary_lines.get(id.as_str()).copied().unwrap_or(line));
+        emit_followers(Some(id.as_str()), &mut followers, &mut output);
+    }
+
+    let mut bytes = output.join("\n").into_bytes();
+    bytes.push(b'\n');
+    atomic_write(primary_path, &bytes, &AtomicWriteOptions::default())?;
+    Ok(restored)
+}
+
+/// 输出跟在指定记录之后的主日志独有记录（递归处理连续的独有记录）
+fn emit_followers<'a>(
+    after: Option<&'a str>,
+    followers: &mut HashMap<Option<&'a str>, Vec<(&'a str, &'a str)>>,
+    output: &mut Vec<&'a str>,
+) {
+    let Some(group) = followers.remove(&after) else {
+        return;
+    };
+    for (id, line) in group {
+        output.push(line);
+        emit_followers(Some(id), followers, output);
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::OperationLog;
+    use crate::stamp::{RecordStamper, SequenceStamper};
+    use amberlock_types::{LabelLevel, OperationOrigin, ProtectMode, TargetKind};
+    use std::sync::Arc;
+    use tempfile::TempDir;
+
+    fn sample_record(stamper: &dyn RecordStamper, path: &str) -> LockRecord {
+        LockRecord {
+            id: stamper.new_id(),
+            path: path.to_string(),
+            kind: TargetKind::File,
+            mode: ProtectMode::ReadOnly,
+            level_applied: LabelLevel::High,
+            time_utc: stamper.now(),
+            seq_no: stamper.next_seq(),
+            user_sid: "S-1-5-21-0".to_string(),
+            owner_before: None,
+            sddl_before: None,
+            sddl_after: None,
+            status: "success".to_string(),
+            errors: vec![],
+            details: None,
+            authorized_by: None,
+            comment: None,
+            batch_id: None,
+            volume_root_acknowledged: false,
+            downgrade_reason: None,
+            origin: OperationOrigin::Unknown,
+            app_version: None,
+            file_id: None,
+            warnings: vec![],
+        }
+    }
+
+    fn ids(path: &Path) -> Vec<String> {
+        IndexedLog::read(path)
+            .unwrap()
+            .entries
+            .into_iter()
+            .map(|(id, _, _)| id)
+            .collect()
+    }
+
+    /// 写入 n 条记录到带副本的主日志，返回 (主日志, 副本, 暂存) 路径
+    fn replicated_log(dir: &TempDir, n: usize) -> (PathBuf, PathBuf, PathBuf) {
+        let primary = dir.path().join("log.ndjson");
+        let replica = dir.path().join("replica.ndjson");
+        let spool = dir.path().join("spool.ndjson");
+        let sink = Arc::new(ReplicationSink::open(&replica, &spool));
+        let log = OperationLog::open(&primary)
+            .unwrap()
+            .with_stamper(Arc::new(SequenceStamper::new("2025-01-01T00:00:00Z")))
+            .with_replica(sink);
+        for i in 0..n {
+            let record = sample_record(log.stamper(), &format!("C:\\Data\\{}.txt", i));
+            log.append(&record).unwrap();
+        }
+        (primary, replica, spool)
+    }
+
+    #[test]
+    fn test_every_append_is_mirrored() {
+        let dir = TempDir::new().expect("创建临时目录失败");
+        let (primary, replica, spool) = replicated_log(&dir, 3);
+
+        assert_eq!(ids(&primary), ids(&replica));
+        assert_eq!(ids(&replica).len(), 3);
+        assert_eq!(SpoolStatus::read(&spool), SpoolStatus::default());
+        assert!(compare_logs(&primary, &replica).unwrap().is_consistent());
+        println!("✅ 每条记录都写入了副本");
+    }
+
+    #[test]
+    fn test_outage_is_spooled_and_drained_in_order() {
+        let dir = TempDir::new().expect("创建临时目录失败");
+        let primary = dir.path().join("log.ndjson");
+        // 副本目录不存在，模拟共享不可达
+        let replica = dir.path().join("share").join("replica.ndjson");
+        let spool = dir.path().join("spool.ndjson");
+        let sink = Arc::new(ReplicationSink::open(&replica, &spool));
+        let log = OperationLog::open(&primary)
+            .unwrap()
+            .with_stamper(Arc::new(SequenceStamper::new("2025-01-01T00:00:00Z")))
+            .with_replica(sink.clone());
+
+        for i in 0..3 {
+            let record = sample_record(log.stamper(), &format!("C:\\Data\\{}.txt", i));
+            log.append(&record).expect("副本不可用不应影响主日志");
+        }
+        assert_eq!(sink.spool_status().pending, 3);
+        assert_eq!(SpoolStatus::read(&spool).pending, 3);
+        assert!(sink.drain().is_err());
+
+        // 副本恢复后，下一次追加先补写暂存，再写入新记录
+        std::fs::create_dir_all(replica.parent().unwrap()).unwrap();
+        log.append(&sample_record(log.stamper(), "C:\\Data\\3.txt"))
+            .unwrap();
+        assert_eq!(sink.spool_status().pending, 0);
+        assert_eq!(ids(&primary), ids(&replica), "补写后顺序与主日志一致");
+        assert_eq!(ids(&replica).len(), 4);
+        println!("✅ 副本中断期间的记录在恢复后按序补写");
+    }
+
+    #[test]
+    fn test_spool_overflow_is_counted() {
+        let dir = TempDir::new().expect("创建临时目录失败");
+        let replica = dir.path().join("missing").join("replica.ndjson");
+        let spool = dir.path().join("spool.ndjson");
+        let stamper = SequenceStamper::new("2025-01-01T00:00:00Z");
+        let sink = ReplicationSink::with_capacity(&replica, &spool, 2);
+
+        for i in 0..5 {
+            sink.mirror(&sample_record(&stamper, &format!("C:\\{}.txt", i)));
+        }
+        let status = SpoolStatus::read(&spool);
+        assert_eq!(status.pending, 2);
+        assert_eq!(status.overflowed, 3);
+
+        // 重新打开后计数仍在
+        let reopened = ReplicationSink::with_capacity(&replica, &spool, 2);
+        assert_eq!(reopened.spool_status(), status);
+        reopened.clear_overflow().unwrap();
+        assert_eq!(reopened.spool_status().overflowed, 0);
+    }
+
+    #[test]
+    fn test_divergence_detects_deletion_truncation_and_edits() {
+        let dir = TempDir::new().expect("创建临时目录失败");
+        let (primary, replica, _) = replicated_log(&dir, 5);
+        let all = ids(&replica);
+        let lines = read_lines(&primary).unwrap();
+
+        // 删除中间一条
+        let mut deleted = lines.clone();
+        deleted.remove(1);
+        std::fs::write(&primary, deleted.join("\n") + "\n").unwrap();
+        let report = compare_logs(&primary, &replica).unwrap();
+        assert_eq!(report.missing_from_primary, vec![all[1].clone()]);
+        assert!(report.primary_tampered());
+
+        // 截断：只保留前两条，最后一行被截成半行
+        let truncated = format!("{}\n{}\n{}", lines[0], lines[1], &lines[2][..10]);
+        std::fs::write(&primary, truncated).unwrap();
+        let report = compare_logs(&primary, &replica).unwrap();
+        assert_eq!(report.missing_from_primary, all[2..].to_vec());
+        assert_eq!(report.unreadable_primary, 1);
+
+        // 修改一条记录的状态
+        let mut edited = lines.clone();
+        edited[3] = edited[3].replace("\"success\"", "\"error\"");
+        std::fs::write(&primary, edited.join("\n") + "\n").unwrap();
+        let report = compare_logs(&primary, &replica).unwrap();
+        assert_eq!(report.mismatched, vec![all[3].clone()]);
+        assert!(report.missing_from_primary.is_empty());
+
+        // 副本落后只算主日志多出，不算篡改
+        std::fs::write(&primary, lines.join("\n") + "\n").unwrap();
+        let lagging = read_lines(&replica).unwrap()[..3].join("\n") + "\n";
+        std::fs::write(&replica, lagging).unwrap();
+        let report = compare_logs(&primary, &replica).unwrap();
+        assert_eq!(report.extra_in_primary, all[3..].to_vec());
+        assert!(!report.primary_tampered());
+        println!("✅ 比较可以识别删除、截断与修改");
+    }
+
+    #[test]
+    fn test_restore_reconstructs_missing_records_in_order() {
+        let dir = TempDir::new().expect("创建临时目录失败");
+        let (primary, replica, _) = replicated_log(&dir, 5);
+        let all = ids(&replica);
+        let lines = read_lines(&primary).unwrap();
+
+        // 主日志丢失第 1、4 条，并有一条副本中没有的记录（副本中断期间写入）
+        let stamper = SequenceStamper::new("2030-01-01T00:00:00Z");
+        let mut local = sample_record(&stamper, "C:\\local.txt");
+        local.id = "local-only".to_string();
+        let local_only = serde_json::to_string(&local).unwrap();
+        let damaged = [
+            lines[0].clone(),
+            lines[2].clone(),
+            local_only,
+            lines[3].clone(),
+        ];
+        std::fs::write(&primary, damaged.join("\n") + "\n").unwrap();
+
+        assert_eq!(restore_from_replica(&primary, &replica).unwrap(), 2);
+        assert_eq!(
+            ids(&primary),
+            vec![
+                all[0].clone(),
+                all[1].clone(),
+                all[2].clone(),
+                "local-only".to_string(),
+                all[3].clone(),
+                all[4].clone(),
+            ]
+        );
+        let report = compare_logs(&primary, &replica).unwrap();
+        assert!(report.missing_from_primary.is_empty());
+        assert_eq!(report.extra_in_primary.len(), 1);
+
+        // 没有缺失时不改写主日志
+        assert_eq!(restore_from_replica(&primary, &replica).unwrap(), 0);
+        println!("✅ 从副本补回了主日志缺失的记录");
+    }
+}
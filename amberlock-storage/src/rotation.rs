@@ -0,0 +1,490 @@
+//! 按日期轮转日志
+//!
+//! `RotationPolicy::Daily` 的写入器按 UTC 日期写入 `{base_name}-YYYY-MM-DD.ndjson`，
+//! 每次写入前比较当前日期，日期变化时刷新旧文件并切换到新文件，便于按天归档。
+//! 读取时用 `list_log_segments` 按日期列出各段，依次读取即可跨天查询；
+//! `SegmentedReader` 把各段当作一个日志读取（最近记录、关键字过滤、查询语句）。
+//!
+//! # 规则
+//! - 日期取自时钟返回的 RFC 3339 时间戳的前 10 个字符（默认时钟与记录时间戳同源，见 `stamp`）
+//! - `RotationPolicy::Never` 写入单个文件 `{base_name}.ndjson`
+//! - 列出分段时，未轮转的 `{base_name}.ndjson` 排在最前，其余按日期升序
+//! - `SegmentedReader::for_log_path` 以日志路径的文件名（去掉扩展名）为 `base_name`；
+//!   扩展名不是 `.ndjson` 时只读取该文件本身
+//!
+//! # 注意
+//! - 只在写入时切换文件，跨过零点但没有新记录时不会创建当天的文件
+//! - 退出刷新路径（`flush_all_logs`）不切换文件，写入当前文件
+
+use crate::querylang::ParsedQuery;
+use crate::stamp::format_utc;
+use crate::{FilterSpec, NdjsonReader};
+use anyhow::{Result, bail};
+use parking_lot::Mutex;
+use std::fs::{File, OpenOptions};
+use std::io::{BufWriter, Write};
+use std::path::{Path, PathBuf};
+use std::sync::Arc;
+use time::OffsetDateTime;
+
+/// 日志分段的扩展名
+const SEGMENT_EXTENSION: &str = "ndjson";
+
+/// 时钟：返回当前 UTC 时间（RFC 3339，如 `2025-06-01T08:00:00.000000Z`）
+pub type Clock = Arc<dyn Fn() -> String + Send + Sync>;
+
+/// 日志轮转策略
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq)]
+pub enum RotationPolicy {
+    /// 不轮转，始终写入 `{base_name}.ndjson`
+    #[default]
+    Never,
+    /// 按 UTC 日期轮转，写入 `{base_name}-YYYY-MM-DD.ndjson`
+    Daily,
+}
+
+/// 系统时钟（与 `SystemStamper` 的时间戳格式相同）
+pub fn system_clock() -> Clock {
+    Arc::new(|| format_utc(OffsetDateTime::now_utc()))
+}
+
+/// 日志分段的文件名
+///
+/// # 参数
+/// - `date`: `YYYY-MM-DD`，为 None 时是未轮转的单个文件
+pub fn segment_file_name(base_name: &str, date: Option<&str>) -> String {
+    match date {
+        Some(date) => format!("{}-{}.{}", base_name, date, SEGMENT_EXTENSION),
+        None => format!("{}.{}", base_name, SEGMENT_EXTENSION),
+    }
+}
+
+/// 按时间顺序列出目录下 `base_name` 的日志分段
+///
+/// # 返回
+/// - `Ok(paths)`: 未轮转的单个文件在前，按日期命名的分段按日期升序；目录不存在时为空
+/// - `Err`: 目录无法读取
+///
+/// # 示例
+/// ```rust,no_run
+/// # use amberlock_storage::{NdjsonReader, list_log_segments};
+/// # fn main() -> anyhow::Result<()> {
+/// for path in list_log_segments("logs", "amberlock")? {
+///     let mut reader = NdjsonReader::open(&path)?;
+///     for record in reader.iter_values() { /* ... */ }
+/// }
+/// # Ok(())
+/// # }
+/// ```
+pub fn list_log_segments<P: AsRef<Path>>(dir: P, base_name: &str) -> Result<Vec<PathBuf>> {
+    let entries = match std::fs::read_dir(dir.as_ref()) {
+        Ok(entries) => entries,
+        Err(e) if e.kind() == std::io::ErrorKind::NotFound => return Ok(Vec::new()),
+        Err(e) => return Err(e.into()),
+    };
+
+    let single = segment_file_name(base_name, None);
+    // 排序键：未轮转的文件日期为 None，排在所有日期之前
+    let mut segments: Vec<(Option<String>, PathBuf)> = Vec::new();
+    for entry in entries {
+        let entry = entry?;
+        if !entry.file_type()?.is_file() {
+            continue;
+        }
+        let name = entry.file_name();
+        let Some(name) = name.to_str() else {
+            continue;
+        };
+        if name == single {
+            segments.push((None, entry.path()));
+        } else if let Some(date) = segment_date(name, base_name) {
+            segments.push((Some(date.to_string()), entry.path()));
+        }
+    }
+    segments.sort();
+    Ok(segments.into_iter().map(|(_, path)| path).collect())
+}
+
+/// 从分段文件名中取出日期（不是 `base_name` 的分段时为 None）
+fn segment_date<'a>(name: &'a str, base_name: &str) -> Option<&'a str> {
+    let date = name
+        .strip_prefix(base_name)?
+        .strip_prefix('-')?
+        .strip_suffix(SEGMENT_EXTENSION)?
+        .strip_suffix('.')?;
+    is_date(date).then_some(date)
+}
+
+/// 是否为 `YYYY-MM-DD` 形式
+fn is_date(text: &str) -> bool {
+    let bytes = text.as_bytes();
+    bytes.len() == 10
+        && bytes.iter().enumerate().all(|(i, b)| match i {
+            4 | 7 => *b == b'-',
+            _ => b.is_ascii_digit(),
+        })
+}
+
+/// 按时间顺序读取日志的全部分段
+///
+/// 各段依次读取，结果与把各段首尾相接成一个文件后用 `NdjsonReader` 读取相同。
+/// 每次读取时才打开分段，创建后新增的当天分段需重新创建读取器才会读到。
+#[derive(Debug, Clone)]
+pub struct SegmentedReader {
+    segments: Vec<PathBuf>,
+}
+
+impl SegmentedReader {
+    /// 读取目录下 `base_name` 的全部分段（见 `list_log_segments`）
+    pub fn open<P: AsRef<Path>>(dir: P, base_name: &str) -> Result<Self> {
+        Ok(Self {
+            segments: list_log_segments(dir, base_name)?,
+        })
+    }
+
+    /// 读取日志路径对应的全部分段
+    ///
+    /// # 示例
+    /// ```rust,no_run
+    /// # use amberlock_storage::SegmentedReader;
+    /// # fn main() -> anyhow::Result<()> {
+    /// // 读取 amberlock.ndjson 与 amberlock-YYYY-MM-DD.ndjson
+    /// let reader = SegmentedReader::for_log_path("logs/amberlock.ndjson")?;
+    /// let recent = reader.read_last_n(100)?;
+    /// # Ok(())
+    /// # }
+    /// ```
+    pub fn for_log_path<P: AsRef<Path>>(path: P) -> Result<Self> {
+        let path = path.as_ref();
+        let base_name = path
+            .file_stem()
+            .and_then(|stem| stem.to_str())
+            .filter(|_| path.extension().is_some_and(|ext| ext == SEGMENT_EXTENSION));
+        match base_name {
+            Some(base_name) => {
+                let dir = path.parent().filter(|dir| !dir.as_os_str().is_empty());
+                Self::open(dir.unwrap_or(Path::new(".")), base_name)
+            }
+            None => Ok(Self {
+                segments: vec![path.to_path_buf()],
+            }),
+        }
+    }
+
+    /// 参与读取的分段（按时间顺序）
+    pub fn segments(&self) -> &[PathBuf] {
+        &self.segments
+    }
+
+    /// 读取最后 N 条记录（按时间顺序，最新在后）
+    ///
+    /// 从最新的分段向前读取，凑齐 N 条后不再打开更早的分段
+    pub fn read_last_n(&self, n: usize) -> Result<Vec<serde_json::Value>> {
+        let mut chunks = Vec::new();
+        let mut remaining = n;
+        for path in self.segments.iter().rev() {
+            if remaining == 0 {
+                break;
+            }
+            let chunk = NdjsonReader::open(path)?.read_last_n(remaining)?;
+            remaining -= chunk.len();
+            chunks.push(chunk);
+        }
+        Ok(chunks.into_iter().rev().flatten().collect())
+    }
+
+    /// 按关键字过滤（见 `NdjsonReader::filter`），从最早的分段向后扫描
+    pub fn filter(&self, key_substr: &str, limit: usize) -> Result<Vec<serde_json::Value>> {
+        self.filter_with(&FilterSpec::new(key_substr), limit)
+    }
+
+    /// 按过滤规格过滤（见 `NdjsonReader::filter_with`）
+    pub fn filter_with(&self, spec: &FilterSpec, limit: usize) -> Result<Vec<serde_json::Value>> {
+        self.collect(limit, |reader, remaining| {
+            reader.filter_with(spec, remaining)
+        })
+    }
+
+    /// 按查询语句过滤（见 `NdjsonReader::filter_query`）
+    pub fn filter_query(
+        &self,
+        query: &ParsedQuery,
+        limit: usize,
+    ) -> Result<Vec<serde_json::Value>> {
+        self.collect(limit, |reader, remaining| {
+            reader.filter_query(query, remaining)
+        })
+    }
+
+    /// 内部方法：依次在各分段上执行读取，凑齐 `limit` 条后停止
+    fn collect<F>(&self, limit: usize, mut read: F) -> Result<Vec<serde_json::Value>>
+    where
+        F: FnMut(&mut NdjsonReader, usize) -> Result<Vec<serde_json::Value>>,
+    {
+        let mut out = Vec::new();
+        for path in &self.segments {
+            if out.len() >= limit {
+                break;
+            }
+            let mut reader = NdjsonReader::open(path)?;
+            out.extend(read(&mut reader, limit - out.len())?);
+        }
+        Ok(out)
+    }
+}
+
+/// 以追加模式打开（不存在时创建）
+pub(crate) fn open_append_file(path: &Path) -> Result<File> {
+    Ok(OpenOptions::new().create(true).append(true).open(path)?)
+}
+
+/// 按日轮转的状态（由 `NdjsonWriter` 持有）
+pub(crate) struct DailyRotation {
+    dir: PathBuf,
+    base_name: String,
+    clock: Clock,
+    /// 当前写入的分段日期
+    date: Mutex<String>,
+}
+
+impl DailyRotation {
+    /// 按时钟的当前日期创建，并返回当天分段的路径
+    pub(crate) fn new(dir: &Path, base_name: &str, clock: Clock) -> Result<(Self, PathBuf)> {
+        let rotation = Self {
+            dir: dir.to_path_buf(),
+            base_name: base_name.to_string(),
+            clock,
+            date: Mutex::new(String::new()),
+        };
+        let date = rotation.today()?;
+        let path = rotation.segment_path(&date);
+        *rotation.date.lock() = date;
+        Ok((rotation, path))
+    }
+
+    /// 日期变化时刷新并切换到当天的分段（调用方持有写入锁）
+    pub(crate) fn switch_if_needed(&self, file: &mut BufWriter<File>) -> Result<()> {
+        let today = self.today()?;
+        let mut date = self.date.lock();
+        if *date != today {
+            file.flush()?;
+            *file = BufWriter::new(open_append_file(&self.segment_path(&today))?);
+            *date = today;
+        }
+        Ok(())
+    }
+
+    /// 时钟当前的 UTC 日期
+    fn today(&self) -> Result<String> {
+        let now = (self.clock)();
+        match now.get(..10) {
+            Some(date) if is_date(date) => Ok(date.to_string()),
+            _ => bail!("时钟返回的时间戳无法取得日期: {:?}", now),
+        }
+    }
+
+    fn segment_path(&self, date: &str) -> PathBuf {
+        self.dir
+            .join(segment_file_name(&self.base_name, Some(date)))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::{NdjsonReader, NdjsonWriter};
+    use serde_json::json;
+    use tempfile::TempDir;
+
+    /// 可由测试拨动的时钟
+    fn manual_clock(start: &str) -> (Clock, Arc<Mutex<String>>) {
+        let now = Arc::new(Mutex::new(start.to_string()));
+        let shared = now.clone();
+        (Arc::new(move || shared.lock().clone()), now)
+    }
+
+    fn ids(path: &Path) -> Vec<serde_json::Value> {
+        let mut reader = NdjsonReader::open(path).expect("打开日志失败");
+        reader
+            .iter_values()
+            .map(|record| record.expect("记录无法解析")["id"].clone())
+            .collect()
+    }
+
+    #[test]
+    fn test_daily_rotation_switches_files_when_date_changes() {
+        let dir = TempDir::new().expect("创建临时目录失败");
+        let (clock, now) = manual_clock("2025-06-01T23:59:59.000000Z");
+        let writer = NdjsonWriter::open_rotating_with_clock(
+            dir.path(),
+            "amberlock",
+            RotationPolicy::Daily,
+            clock,
+        )
+        .expect("创建日志失败");
+
+        writer.write_record(&json!({"id": "1"})).expect("写入失败");
+        writer.write_record(&json!({"id": "2"})).expect("写入失败");
+        *now.lock() = "2025-06-02T00:00:00.000001Z".to_string();
+        writer.write_record(&json!({"id": "3"})).expect("写入失败");
+        // 跨过多天：中间没有记录的日期不产生文件
+        *now.lock() = "2025-06-05T12:00:00.000000Z".to_string();
+        writer.write_record(&json!({"id": "4"})).expect("写入失败");
+        writer.flush().expect("刷新失败");
+
+        let day = |date| dir.path().join(segment_file_name("amberlock", Some(date)));
+        assert_eq!(ids(&day("2025-06-01")), [json!("1"), json!("2")]);
+        assert_eq!(ids(&day("2025-06-02")), [json!("3")]);
+        assert_eq!(ids(&day("2025-06-05")), [json!("4")]);
+
+        let segments = list_log_segments(dir.path(), "amberlock").expect("列出分段失败");
+        assert_eq!(
+            segments,
+            [day("2025-06-01"), day("2025-06-02"), day("2025-06-05")]
+        );
+        let all: Vec<_> = segments.iter().flat_map(|path| ids(path)).collect();
+        assert_eq!(all, [json!("1"), json!("2"), json!("3"), json!("4")]);
+    }
+
+    #[test]
+    fn test_daily_rotation_appends_to_existing_segment() {
+        let dir = TempDir::new().expect("创建临时目录失败");
+        for id in ["1", "2"] {
+            let (clock, _) = manual_clock("2025-06-01T08:00:00.000000Z");
+            let writer = NdjsonWriter::open_rotating_with_clock(
+                dir.path(),
+                "amberlock",
+                RotationPolicy::Daily,
+                clock,
+            )
+            .expect("创建日志失败");
+            writer.write_record(&json!({"id": id})).expect("写入失败");
+        }
+        let path = dir.path().join("amberlock-2025-06-01.ndjson");
+        assert_eq!(ids(&path), [json!("1"), json!("2")]);
+
+        let (clock, _) = manual_clock("not a timestamp");
+        assert!(
+            NdjsonWriter::open_rotating_with_clock(
+                dir.path(),
+                "amberlock",
+                RotationPolicy::Daily,
+                clock
+            )
+            .is_err()
+        );
+    }
+
+    #[test]
+    fn test_list_log_segments_order_and_filtering() {
+        let dir = TempDir::new().expect("创建临时目录失败");
+        for name in [
+            "amberlock-2025-06-10.ndjson",
+            "amberlock-2024-12-31.ndjson",
+            "amberlock.ndjson",
+            "amberlock-2025-06-01.ndjson",
+            // 以下均不是 amberlock 的分段
+            "amberlock-2025-6-1.ndjson",
+            "amberlock-2025-06-01.ndjson.bak",
+            "amberlock-audit-2025-06-01.ndjson",
+            "other-2025-06-01.ndjson",
+        ] {
+            std::fs::write(dir.path().join(name), "").expect("写入文件失败");
+        }
+        std::fs::create_dir(dir.path().join("amberlock-2025-07-01.ndjson")).expect("创建目录失败");
+
+        let names: Vec<_> = list_log_segments(dir.path(), "amberlock")
+            .expect("列出分段失败")
+            .iter()
+            .map(|path| path.file_name().unwrap().to_string_lossy().into_owned())
+            .collect();
+        assert_eq!(
+            names,
+            [
+                "amberlock.ndjson",
+                "amberlock-2024-12-31.ndjson",
+                "amberlock-2025-06-01.ndjson",
+                "amberlock-2025-06-10.ndjson",
+            ]
+        );
+        assert!(
+            list_log_segments(dir.path().join("missing"), "amberlock")
+                .expect("目录不存在时应返回空列表")
+                .is_empty()
+        );
+    }
+
+    #[test]
+    fn test_segmented_reader_spans_days() {
+        let dir = TempDir::new().expect("创建临时目录失败");
+        let (clock, now) = manual_clock("2025-06-01T08:00:00.000000Z");
+        let writer = NdjsonWriter::open_rotating_with_clock(
+            dir.path(),
+            "amberlock",
+            RotationPolicy::Daily,
+            clock,
+        )
+        .expect("创建日志失败");
+        for (date, id, status) in [
+            ("2025-06-01", "1", "error"),
+            ("2025-06-01", "2", "success"),
+            ("2025-06-02", "3", "error"),
+            ("2025-06-03", "4", "success"),
+            ("2025-06-03", "5", "error"),
+        ] {
+            *now.lock() = format!("{}T08:00:00.000000Z", date);
+            writer
+                .write_record(&json!({"id": id, "status": status}))
+                .expect("写入失败");
+        }
+        writer.flush().expect("刷新失败");
+        // 轮转前写入的单个文件排在最前
+        std::fs::write(
+            dir.path().join("amberlock.ndjson"),
+            "{\"id\":\"0\",\"status\":\"error\"}\n",
+        )
+        .expect("写入文件失败");
+
+        let reader = SegmentedReader::for_log_path(dir.path().join("amberlock.ndjson"))
+            .expect("列出分段失败");
+        assert_eq!(reader.segments().len(), 4);
+        let ids = |records: Vec<serde_json::Value>| -> Vec<serde_json::Value> {
+            records
+                .into_iter()
+                .map(|record| record["id"].clone())
+                .collect()
+        };
+
+        assert_eq!(
+            ids(reader.read_last_n(4).expect("读取失败")),
+            [json!("2"), json!("3"), json!("4"), json!("5")]
+        );
+        assert_eq!(ids(reader.read_last_n(100).expect("读取失败")).len(), 6);
+        assert_eq!(
+            ids(reader.filter("error", 3).expect("过滤失败")),
+            [json!("0"), json!("1"), json!("3")]
+        );
+        let query = crate::parse_query("status:success").expect("解析查询失败");
+        assert_eq!(
+            ids(reader.filter_query(&query, 10).expect("过滤失败")),
+            [json!("2"), json!("4")]
+        );
+
+        // 扩展名不是 .ndjson 时只读取该文件
+        let other = dir.path().join("amberlock.log");
+        std::fs::write(&other, "{\"id\":\"x\"}\n").expect("写入文件失败");
+        let reader = SegmentedReader::for_log_path(&other).expect("打开失败");
+        assert_eq!(reader.segments(), [other]);
+        assert_eq!(ids(reader.read_last_n(10).expect("读取失败")), [json!("x")]);
+    }
+
+    #[test]
+    fn test_never_policy_writes_single_file() {
+        let dir = TempDir::new().expect("创建临时目录失败");
+        let writer = NdjsonWriter::open_rotating(dir.path(), "amberlock", RotationPolicy::Never)
+            .expect("创建日志失败");
+        writer.write_record(&json!({"id": "1"})).expect("写入失败");
+        writer.flush().expect("刷新失败");
+        assert_eq!(ids(&dir.path().join("amberlock.ndjson")), [json!("1")]);
+    }
+}
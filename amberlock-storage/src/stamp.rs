@@ -0,0 +1,208 @@
+//! 记录时间戳与 ID 来源
+//!
+//! 构造 `LockRecord` 时不直接调用系统时钟和随机 UUID，而是通过
+//! `RecordStamper` 获取，测试中可替换为确定性实现以便逐字节比较日志。
+//!
+//! # 顺序
+//! - 时间戳固定为微秒精度（`2025-01-01T08:00:00.123456Z`），同格式的值按字典序比较即按时间比较；
+//!   旧版本写入的秒精度值（`2025-01-01T08:00:00Z`）与之混合时须经 `compare_utc` 比较
+//! - 同一微秒内的记录按 `seq_no` 排序：进程内单调递增，进程启动时从 1 开始，
+//!   因此只能区分同一进程写入的记录；不同进程写入的记录之间仍只能按时间戳排序
+
+use std::sync::atomic::{AtomicU64, Ordering};
+use time::format_description::well_known::Rfc3339;
+use time::{Duration, OffsetDateTime};
+use uuid::Uuid;
+
+/// 记录时间戳与 ID 来源
+pub trait RecordStamper: Send + Sync {
+    /// 当前 UTC 时间（RFC 3339，微秒精度）
+    fn now(&self) -> String;
+    /// 新记录 ID
+    fn new_id(&self) -> String;
+    /// 新记录的进程内序号（从 1 开始递增）
+    fn next_seq(&self) -> u64;
+}
+
+/// 进程内的记录序号（所有 `SystemStamper` 共享）
+static SEQ_NO: AtomicU64 = AtomicU64::new(0);
+
+/// 格式化为日志使用的时间戳：UTC、固定 6 位小数秒、`Z` 结尾
+///
+/// 宽度固定，同格式的时间戳按字典序比较即按时间比较
+pub fn format_utc(time: OffsetDateTime) -> String {
+    let time = time.to_offset(time::UtcOffset::UTC);
+    format!(
+        "{:04}-{:02}-{:02}T{:02}:{:02}:{:02}.{:06}Z",
+        time.year(),
+        u8::from(time.month()),
+        time.day(),
+        time.hour(),
+        time.minute(),
+        time.second(),
+        time.microsecond()
+    )
+}
+
+/// 系统时钟 + 随机 UUID v4 + 进程内序号（默认实现）
+#[derive(Debug, Clone, Copy, Default)]
+pub struct SystemStamper;
+
+impl RecordStamper for SystemStamper {
+    fn now(&self) -> String {
+        format_utc(OffsetDateTime::now_utc())
+    }
+
+    fn new_id(&self) -> String {
+        Uuid::new_v4().to_string()
+    }
+
+    fn next_seq(&self) -> u64 {
+        SEQ_NO.fetch_add(1, Ordering::Relaxed) + 1
+    }
+}
+
+/// 固定时间戳与 ID（测试用）
+#[derive(Debug, Clone)]
+pub struct FixedStamper {
+    /// 每次返回的时间戳
+    pub time: String,
+    /// 每次返回的 ID
+    pub id: String,
+    /// 每次返回的序号
+    pub seq_no: u64,
+}
+
+impl FixedStamper {
+    /// 创建固定来源（序号固定为 1）
+    pub fn new(time: &str, id: &str) -> Self {
+        Self {
+            time: time.to_string(),
+            id: id.to_string(),
+            seq_no: 1,
+        }
+    }
+}
+
+impl RecordStamper for FixedStamper {
+    fn now(&self) -> String {
+        self.time.clone()
+    }
+
+    fn new_id(&self) -> String {
+        self.id.clone()
+    }
+
+    fn next_seq(&self) -> u64 {
+        self.seq_no
+    }
+}
+
+/// 递增的时间戳与 ID（测试用）
+///
+/// 第 n 次调用 `now()` 返回 `base + n 秒`；第 n 次调用 `new_id()` 返回
+/// UUID 形式的 `00000000-0000-4000-8000-{n:012x}`，`next_seq()` 返回 n（n 从 1 开始）
+#[derive(Debug)]
+pub struct SequenceStamper {
+    base: OffsetDateTime,
+    ticks: AtomicU64,
+    ids: AtomicU64,
+    seqs: AtomicU64,
+}
+
+impl SequenceStamper {
+    /// # 参数
+    /// - `base`: 起始时间（RFC 3339）
+    ///
+    /// # 注意
+    /// 起始时间格式错误时 panic（仅用于测试）
+    pub fn new(base: &str) -> Self {
+        Self {
+            base: OffsetDateTime::parse(base, &Rfc3339).expect("起始时间必须为 RFC 3339"),
+            ticks: AtomicU64::new(0),
+            ids: AtomicU64::new(0),
+            seqs: AtomicU64::new(0),
+        }
+    }
+}
+
+impl RecordStamper for SequenceStamper {
+    fn now(&self) -> String {
+        let n = self.ticks.fetch_add(1, Ordering::SeqCst);
+        format_utc(self.base + Duration::seconds(n as i64))
+    }
+
+    fn new_id(&self) -> String {
+        let n = self.ids.fetch_add(1, Ordering::SeqCst) + 1;
+        format!("00000000-0000-4000-8000-{:012x}", n)
+    }
+
+    fn next_seq(&self) -> u64 {
+        self.seqs.fetch_add(1, Ordering::SeqCst) + 1
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_sequence_stamper_is_deterministic() {
+        let stamper = SequenceStamper::new("2025-01-01T00:00:00Z");
+        assert_eq!(stamper.now(), "2025-01-01T00:00:00.000000Z");
+        assert_eq!(stamper.now(), "2025-01-01T00:00:01.000000Z");
+        assert_eq!(stamper.new_id(), "00000000-0000-4000-8000-000000000001");
+        assert_eq!(stamper.new_id(), "00000000-0000-4000-8000-000000000002");
+        assert_eq!(stamper.next_seq(), 1);
+        assert_eq!(stamper.next_seq(), 2);
+
+        let system = SystemStamper;
+        assert_ne!(system.new_id(), system.new_id());
+        assert!(OffsetDateTime::parse(&system.now(), &Rfc3339).is_ok());
+    }
+
+    #[test]
+    fn test_timestamps_have_fixed_width_and_sortable_sequence() {
+        let times = [
+            "2025-01-01T00:00:00Z",
+            "2025-01-01T00:00:00.5Z",
+            "2025-01-01T08:00:00.000001+08:00",
+            "2025-12-31T23:59:59.999999999Z",
+        ];
+        let formatted: Vec<String> = times
+            .iter()
+            .map(|t| format_utc(OffsetDateTime::parse(t, &Rfc3339).unwrap()))
+            .collect();
+        assert_eq!(
+            formatted,
+            [
+                "2025-01-01T00:00:00.000000Z",
+                "2025-01-01T00:00:00.500000Z",
+                "2025-01-01T00:00:00.000001Z",
+                "2025-12-31T23:59:59.999999Z",
+            ]
+        );
+        assert!(formatted.iter().all(|t| t.len() == formatted[0].len()));
+
+        // 多线程并发取号也不会重复，且同一线程内严格递增
+        let system = SystemStamper;
+        let handles: Vec<_> = (0..4)
+            .map(|_| {
+                std::thread::spawn(move || {
+                    let seqs: Vec<u64> = (0..1000).map(|_| system.next_seq()).collect();
+                    assert!(seqs.windows(2).all(|w| w[0] < w[1]));
+                    seqs
+                })
+            })
+            .collect();
+        let mut all: Vec<u64> = handles
+            .into_iter()
+            .flat_map(|h| h.join().expect("取号线程崩溃"))
+            .collect();
+        all.sort_unstable();
+        all.dedup();
+        assert_eq!(all.len(), 4000);
+        assert!(all[0] >= 1);
+        println!("✅ 时间戳宽度固定，序号进程内唯一递增");
+    }
+}
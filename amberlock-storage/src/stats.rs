@@ -0,0 +1,500 @@
+//! 增量日志统计
+//!
+//! `query::generate_statistics` 每次调用都要解析整个日志，日志增长到 GB 级后
+//! 代价很高，而两次调用之间通常只追加了少量记录。本模块把聚合计数与已统计到的
+//! 字节偏移保存在日志旁的 JSON 附属文件中，之后只解析新追加的部分。
+//!
+//! # 指纹
+//! 附属文件记录偏移之前最后 4 KB 内容的哈希。日志被轮转、截断或压缩后
+//! （长度小于偏移或哈希不一致），丢弃缓存并完整重新统计。
+//!
+//! # 去重计数
+//! 唯一用户/路径数先按精确集合统计（只保存 64 位哈希），超过
+//! `DISTINCT_EXACT_CAP` 后转为 HyperLogLog 近似计数（误差约 1.6%），
+//! 内存与附属文件大小都有上限。是否为近似值见 `LogStatistics`。
+
+use crate::atomic::{AtomicWriteOptions, atomic_write};
+use crate::query::LogStatistics;
+use amberlock_types::OperationOrigin;
+use anyhow::Result;
+use serde::{Deserialize, Serialize};
+use serde_json::Value;
+use std::collections::{BTreeMap, BTreeSet};
+use std::fs::File;
+use std::io::{BufRead, BufReader, Read, Seek, SeekFrom};
+use std::path::{Path, PathBuf};
+
+/// 附属文件格式版本（版本不一致时完整重新统计）
+pub const STATS_CACHE_VERSION: u32 = 2;
+/// 精确去重的上限，超过后转为近似计数
+pub const DISTINCT_EXACT_CAP: usize = 10_000;
+/// 指纹覆盖的尾部字节数
+pub const FINGERPRINT_TAIL_BYTES: u64 = 4096;
+
+/// HyperLogLog 精度（寄存器数为 2^12）
+const HLL_PRECISION: u32 = 12;
+const HLL_REGISTERS: usize = 1 << HLL_PRECISION;
+
+/// 有上限的去重计数器
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(tag = "kind", rename_all = "snake_case")]
+pub enum DistinctCounter {
+    /// 精确计数（保存值的哈希）
+    Exact { hashes: BTreeSet<u64> },
+    /// HyperLogLog 近似计数
+    Approximate { registers: Vec<u8> },
+}
+
+impl Default for DistinctCounter {
+    fn default() -> Self {
+        DistinctCounter::Exact {
+            hashes: BTreeSet::new(),
+        }
+    }
+}
+
+impl DistinctCounter {
+    /// 记录一个值
+    pub fn insert(&mut self, value: &str) {
+        let hash = stable_hash(value.as_bytes());
+        match self {
+            DistinctCounter::Exact { hashes } => {
+                hashes.insert(hash);
+                if hashes.len() > DISTINCT_EXACT_CAP {
+                    let mut registers = vec![0u8; HLL_REGISTERS];
+                    for &hash in hashes.iter() {
+                        hll_add(&mut registers, hash);
+                    }
+                    *self = DistinctCounter::Approximate { registers };
+                }
+            }
+            DistinctCounter::Approximate { registers } => hll_add(registers, hash),
+        }
+    }
+
+    /// 不同值的数量（近似计数时为估计值）
+    pub fn count(&self) -> usize {
+        match self {
+            DistinctCounter::Exact { hashes } => hashes.len(),
+            DistinctCounter::Approximate { registers } => hll_estimate(registers),
+        }
+    }
+
+    /// 是否已转为近似计数
+    pub fn is_approximate(&self) -> bool {
+        matches!(self, DistinctCounter::Approximate { .. })
+    }
+}
+
+/// 持久化的统计缓存
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+pub struct StatsCache {
+    /// 格式版本
+    pub version: u32,
+    /// 已统计到的字节偏移（总是位于完整行之后）
+    pub offset: u64,
+    /// 偏移之前最后 `FINGERPRINT_TAIL_BYTES` 字节的哈希
+    pub tail_hash: u64,
+    /// 总记录数
+    pub total_count: usize,
+    /// 成功操作数
+    pub success_count: usize,
+    /// 失败操作数
+    pub error_count: usize,
+    /// 待处理操作数
+    pub pending_count: usize,
+    /// 唯一用户 SID
+    pub users: DistinctCounter,
+    /// 唯一路径
+    pub paths: DistinctCounter,
+    /// 按发起入口分类的记录数（无来源的旧记录计入 `unknown`）
+    pub by_origin: BTreeMap<String, usize>,
+}
+
+impl Default for StatsCache {
+    fn default() -> Self {
+        Self {
+            version: STATS_CACHE_VERSION,
+            offset: 0,
+            tail_hash: stable_hash(&[]),
+            total_count: 0,
+            success_count: 0,
+            error_count: 0,
+            pending_count: 0,
+            users: DistinctCounter::default(),
+            paths: DistinctCounter::default(),
+            by_origin: BTreeMap::new(),
+        }
+    }
+}
+
+impl StatsCache {
+    /// 读取附属文件
+    ///
+    /// # 返回
+    /// 文件不存在、损坏或版本不一致时返回 None
+    pub fn load<P: AsRef<Path>>(path: P) -> Option<Self> {
+        let bytes = std::fs::read(path).ok()?;
+        serde_json::from_slice::<StatsCache>(&bytes)
+            .ok()
+            .filter(|cache| cache.version == STATS_CACHE_VERSION)
+    }
+
+    /// 原子写入附属文件
+    pub fn save<P: AsRef<Path>>(&self, path: P) -> Result<()> {
+        let options = AtomicWriteOptions {
+            fsync: false,
+            ..Default::default()
+        };
+        atomic_write(path, &serde_json::to_vec(self)?, &options)
+    }
+
+    /// 将一条记录计入统计
+    pub fn fold_record(&mut self, record: &Value) {
+        self.total_count += 1;
+
+        match record.get("status").and_then(|v| v.as_str()) {
+            Some("success") => self.success_count += 1,
+            Some("error") => self.error_count += 1,
+            Some("pending") => self.pending_count += 1,
+            _ => {}
+        }
+        if let Some(user_sid) = record.get("user_sid").and_then(|v| v.as_str()) {
+            self.users.insert(user_sid);
+        }
+        if let Some(path) = record.get("path").and_then(|v| v.as_str()) {
+            self.paths.insert(path);
+        }
+        let origin = record
+            .get("origin")
+            .and_then(|v| v.as_str())
+            .unwrap_or(OperationOrigin::Unknown.as_str());
+        *self.by_origin.entry(origin.to_string()).or_default() += 1;
+    }
+
+    /// 当前的统计结果
+    pub fn statistics(&self) -> LogStatistics {
+        LogStatistics {
+            total_count: self.total_count,
+            success_count: self.success_count,
+            error_count: self.error_count,
+            pending_count: self.pending_count,
+            unique_users: self.users.count(),
+            unique_paths: self.paths.count(),
+            unique_users_approximate: self.users.is_approximate(),
+            unique_paths_approximate: self.paths.is_approximate(),
+            by_origin: self.by_origin.clone(),
+        }
+    }
+
+    /// 缓存是否仍对应该日志的前 `offset` 字节
+    fn matches(&self, file: &mut File, len: u64) -> bool {
+        self.offset <= len && tail_hash(file, self.offset).ok() == Some(self.tail_hash)
+    }
+}
+
+/// 日志的默认附属文件路径（`{日志文件名}.stats.json`）
+pub fn stats_cache_path<P: AsRef<Path>>(log_path: P) -> PathBuf {
+    let mut name = log_path.as_ref().as_os_str().to_os_string();
+    name.push(".stats.json");
+    PathBuf::from(name)
+}
+
+/// 增量生成日志统计信息
+///
+/// # 参数
+/// - `log_path`: 日志文件路径
+/// - `cache_path`: 附属文件路径（通常为 `stats_cache_path(log_path)`）
+///
+/// # 返回
+/// 与 `generate_statistics` 相同的统计结果
+///
+/// # 注意
+/// - 缓存有效时只解析偏移之后新追加的字节；日志被轮转、截断或压缩时完整重新统计
+/// - 末尾尚未写完（没有换行符）的行留待下次统计
+/// - 附属文件写入失败不影响本次结果
+pub fn generate_statistics_cached<P: AsRef<Path>, Q: AsRef<Path>>(
+    log_path: P,
+    cache_path: Q,
+) -> Result<LogStatistics> {
+    let mut file = File::open(log_path)?;
+    let len = file.metadata()?.len();
+    let cached = StatsCache::load(&cache_path).filter(|cache| cache.matches(&mut file, len));
+    let rescan = cached.is_none();
+    let mut cache = cached.unwrap_or_default();
+    let start = cache.offset;
+
+    file.seek(SeekFrom::Start(cache.offset))?;
+    let mut reader = BufReader::new(file);
+    let mut line = Vec::new();
+    loop {
+        line.clear();
+        let read = reader.read_until(b'\n', &mut line)?;
+        if read == 0 || line.last() != Some(&b'\n') {
+            break;
+        }
+        cache.offset += read as u64;
+        if let Ok(record) = serde_json::from_slice::<Value>(&line) {
+            cache.fold_record(&record);
+        }
+    }
+
+    if rescan || cache.offset != start {
+        cache.tail_hash = tail_hash(reader.get_mut(), cache.offset)?;
+        let _ = cache.save(&cache_path);
+    }
+    Ok(cache.statistics())
+}
+
+/// `end` 之前最后 `FINGERPRINT_TAIL_BYTES` 字节的哈希
+fn tail_hash(file: &mut File, end: u64) -> Result<u64> {
+    let start = end.saturating_sub(FINGERPRINT_TAIL_BYTES);
+    file.seek(SeekFrom::Start(start))?;
+    let mut tail = vec![0u8; (end - start) as usize];
+    file.read_exact(&mut tail)?;
+    Ok(stable_hash(&tail))
+}
+
+/// 跨版本稳定的 64 位哈希（FNV-1a 加 splitmix64 混合）
+///
+/// 结果会写入附属文件，不能使用每个进程随机化的 `DefaultHasher`
+pub(crate) fn stable_hash(bytes: &[u8]) -> u64 {
+    let mut hash: u64 = 0xcbf2_9ce4_8422_2325;
+    for &byte in bytes {
+        hash ^= byte as u64;
+        hash = hash.wrapping_mul(0x0000_0100_0000_01b3);
+    }
+    hash = (hash ^ (hash >> 30)).wrapping_mul(0xbf58_476d_1ce4_e5b9);
+    hash = (hash ^ (hash >> 27)).wrapping_mul(0x94d0_49bb_1331_11eb);
+    hash ^ (hash >> 31)
+}
+
+fn hll_add(registers: &mut [u8], hash: u64) {
+    let index = (hash >> (64 - HLL_PRECISION)) as usize;
+    let rank = ((hash << HLL_PRECISION).leading_zeros() + 1).min(64 - HLL_PRECISION + 1) as u8;
+    if registers[index] < rank {
+        registers[index] = rank;
+    }
+}
+
+fn hll_estimate(registers: &[u8]) -> usize {
+    let m = registers.len() as f64;
+    let alpha = 0.7213 / (1.0 + 1.079 / m);
+    let sum: f64 = registers.iter().map(|&r| 2f64.powi(-(r as i32))).sum();
+    let estimate = alpha * m * m / sum;
+
+    // 小基数时使用线性计数修正
+    let zeros = registers.iter().filter(|&&r| r == 0).count();
+    if estimate <= 2.5 * m && zeros > 0 {
+        (m * (m / zeros as f64).ln()).round() as usize
+    } else {
+        estimate.round() as usize
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::query::generate_statistics;
+    use serde_json::json;
+    use std::io::Write;
+    use tempfile::TempDir;
+
+    fn record(i: usize, status: &str) -> String {
+        json!({
+            "id": format!("r{}", i),
+            "path": format!("C:\\Data\\{}.txt", i % 37),
+            "time_utc": "2025-01-01T00:00:00Z",
+            "user_sid": format!("S-1-5-21-{}", i % 3),
+            "status": status,
+        })
+        .to_string()
+    }
+
+    fn append(path: &Path, lines: &[String]) {
+        let mut file = std::fs::OpenOptions::new()
+            .create(true)
+            .append(true)
+            .open(path)
+            .expect("打开日志失败");
+        for line in lines {
+            writeln!(file, "{}", line).expect("写入失败");
+        }
+    }
+
+    fn records(range: std::ops::Range<usize>) -> Vec<String> {
+        let statuses = ["success", "error", "pending", "unlocked"];
+        range.map(|i| record(i, statuses[i % 4])).collect()
+    }
+
+    #[test]
+    fn test_appended_records_match_full_scan() {
+        let dir = TempDir::new().expect("创建临时目录失败");
+        let log = dir.path().join("log.ndjson");
+        let cache_path = stats_cache_path(&log);
+
+        append(&log, &records(0..100));
+        let stats = generate_statistics_cached(&log, &cache_path).unwrap();
+        assert_eq!(stats, generate_statistics(&log).unwrap());
+
+        append(&log, &records(100..150));
+        append(&log, &["not json".to_string(), String::new()]);
+        let stats = generate_statistics_cached(&log, &cache_path).unwrap();
+        assert_eq!(stats, generate_statistics(&log).unwrap());
+        assert_eq!(stats.total_count, 150);
+        assert_eq!(stats.unique_users, 3);
+        assert_eq!(stats.unique_paths, 37);
+
+        let cache = StatsCache::load(&cache_path).expect("应已写入附属文件");
+        assert_eq!(cache.offset, std::fs::metadata(&log).unwrap().len());
+        println!("✅ 增量统计与完整统计一致");
+    }
+
+    #[test]
+    fn test_only_appended_bytes_are_parsed() {
+        let dir = TempDir::new().expect("创建临时目录失败");
+        let log = dir.path().join("log.ndjson");
+        let cache_path = stats_cache_path(&log);
+
+        append(&log, &records(0..10));
+        generate_statistics_cached(&log, &cache_path).unwrap();
+
+        // 篡改计数但保持指纹：若重新统计，篡改会被覆盖
+        let mut cache = StatsCache::load(&cache_path).unwrap();
+        cache.total_count += 1000;
+        cache.save(&cache_path).unwrap();
+
+        append(&log, &records(10..15));
+        let stats = generate_statistics_cached(&log, &cache_path).unwrap();
+        assert_eq!(
+            stats.total_count, 1015,
+            "缓存有效时不应重新解析已统计的部分"
+        );
+    }
+
+    #[test]
+    fn test_partial_trailing_line_waits_for_newline() {
+        let dir = TempDir::new().expect("创建临时目录失败");
+        let log = dir.path().join("log.ndjson");
+        let cache_path = stats_cache_path(&log);
+
+        append(&log, &records(0..3));
+        let line = record(3, "success");
+        let (head, tail) = line.split_at(10);
+        std::fs::OpenOptions::new()
+            .append(true)
+            .open(&log)
+            .unwrap()
+            .write_all(head.as_bytes())
+            .unwrap();
+        assert_eq!(
+            generate_statistics_cached(&log, &cache_path)
+                .unwrap()
+                .total_count,
+            3
+        );
+
+        append(&log, &[tail.to_string()]);
+        let stats = generate_statistics_cached(&log, &cache_path).unwrap();
+        assert_eq!(stats.total_count, 4);
+        assert_eq!(stats, generate_statistics(&log).unwrap());
+    }
+
+    #[test]
+    fn test_truncation_and_rotation_force_rescan() {
+        let dir = TempDir::new().expect("创建临时目录失败");
+        let log = dir.path().join("log.ndjson");
+        let cache_path = stats_cache_path(&log);
+
+        append(&log, &records(0..200));
+        generate_statistics_cached(&log, &cache_path).unwrap();
+
+        // 截断（压缩）为更短的内容
+        std::fs::write(&log, "").unwrap();
+        append(&log, &records(0..20));
+        let stats = generate_statistics_cached(&log, &cache_path).unwrap();
+        assert_eq!(stats, generate_statistics(&log).unwrap());
+        assert_eq!(stats.total_count, 20);
+
+        // 轮转后新日志比缓存偏移更长，但内容不同
+        std::fs::rename(&log, dir.path().join("log.ndjson.1")).unwrap();
+        append(&log, &records(1000..1300));
+        let stats = generate_statistics_cached(&log, &cache_path).unwrap();
+        assert_eq!(stats, generate_statistics(&log).unwrap());
+        assert_eq!(stats.total_count, 300);
+
+        // 附属文件损坏时同样重新统计
+        std::fs::write(&cache_path, "{broken").unwrap();
+        let stats = generate_statistics_cached(&log, &cache_path).unwrap();
+        assert_eq!(stats.total_count, 300);
+        println!("✅ 轮转、截断与损坏的缓存都会触发完整统计");
+    }
+
+    #[test]
+    fn test_origin_breakdown() {
+        let dir = TempDir::new().expect("创建临时目录失败");
+        let log = dir.path().join("log.ndjson");
+        let cache_path = stats_cache_path(&log);
+
+        let with_origin = |i: usize, origin: &str| {
+            json!({"id": format!("o{}", i), "status": "success", "origin": origin}).to_string()
+        };
+        append(&log, &records(0..3));
+        append(&log, &[with_origin(0, "gui_button"), with_origin(1, "gui_shortcut")]);
+        generate_statistics_cached(&log, &cache_path).unwrap();
+        append(&log, &[with_origin(2, "gui_button")]);
+
+        let stats = generate_statistics_cached(&log, &cache_path).unwrap();
+        assert_eq!(stats, generate_statistics(&log).unwrap());
+        let counts: Vec<(&str, usize)> = stats
+            .by_origin
+            .iter()
+            .map(|(origin, count)| (origin.as_str(), *count))
+            .collect();
+        assert_eq!(
+            counts,
+            vec![("gui_button", 2), ("gui_shortcut", 1), ("unknown", 3)],
+            "无来源的旧记录应计入 unknown"
+        );
+    }
+
+    #[test]
+    fn test_approximate_cardinality_bounds() {
+        let dir = TempDir::new().expect("创建临时目录失败");
+        let log = dir.path().join("log.ndjson");
+        let cache_path = stats_cache_path(&log);
+
+        let distinct = 30_000;
+        let lines: Vec<String> = (0..distinct)
+            .map(|i| {
+                json!({
+                    "path": format!("D:\\Archive\\{}\\file-{}.bin", i / 100, i),
+                    "user_sid": format!("S-1-5-21-{}", i % 5),
+                    "status": "success",
+                })
+                .to_string()
+            })
+            .collect();
+        append(&log, &lines[..distinct / 2]);
+        generate_statistics_cached(&log, &cache_path).unwrap();
+        append(&log, &lines[distinct / 2..]);
+        // 重复的路径不应增加计数
+        append(&log, &lines[..1000]);
+
+        let stats = generate_statistics_cached(&log, &cache_path).unwrap();
+        assert_eq!(stats, generate_statistics(&log).unwrap());
+        assert!(stats.unique_paths_approximate, "超过上限后应为近似值");
+        assert!(!stats.unique_users_approximate);
+        assert_eq!(stats.unique_users, 5);
+        let error = (stats.unique_paths as f64 - distinct as f64).abs() / distinct as f64;
+        assert!(error < 0.05, "近似误差过大: {}", stats.unique_paths);
+
+        let mut counter = DistinctCounter::default();
+        for i in 0..200_000 {
+            counter.insert(&format!("value-{}", i));
+        }
+        let error = (counter.count() as f64 - 200_000.0).abs() / 200_000.0;
+        assert!(error < 0.05, "近似误差过大: {}", counter.count());
+        println!("✅ 去重计数在上限内精确、超过上限后误差可控");
+    }
+}
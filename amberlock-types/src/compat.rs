@@ -0,0 +1,352 @@
+//! 序列化兼容性测试
+//!
+//! - 每个公开的 serde 类型经 serde_json 往返后保持不变
+//! - `testdata/golden` 中的夹具代表历史版本写入的 JSON，必须始终可以读取；
+//!   每类夹具中编号最大的一份还必须与当前序列化结果一致（流程见该目录的 README）
+//! - 每个枚举的 `ALL` 覆盖全部变体，字符串形式与界面文字覆盖每个变体
+
+use super::*;
+use serde::de::DeserializeOwned;
+use serde_json::{Value, json};
+use std::collections::{BTreeMap, BTreeSet};
+use std::fmt::Debug;
+use std::path::Path;
+
+/// 断言 `ALL` 按声明顺序列出了全部变体
+///
+/// 展开为一个穷尽的 match：新增变体而未更新此处时编译失败，
+/// 更新了此处而未更新 `ALL` 时测试失败
+macro_rules! assert_all_variants {
+    ($ty:ident { $($variant:ident),+ $(,)? }) => {{
+        let _exhaustive = |value: $ty| match value {
+            $($ty::$variant => ()),+
+        };
+        assert_eq!(
+            $ty::ALL.to_vec(),
+            vec![$($ty::$variant),+],
+            concat!("`", stringify!($ty), "::ALL` 与变体列表不一致")
+        );
+    }};
+}
+
+/// 序列化后再读取，断言与原值相等，返回序列化的 JSON
+fn round_trip<T>(value: &T) -> Value
+where
+    T: Serialize + DeserializeOwned + PartialEq + Debug,
+{
+    let json = serde_json::to_value(value).expect("序列化失败");
+    let back: T = serde_json::from_value(json.clone()).expect("读取序列化结果失败");
+    assert_eq!(&back, value, "往返后不一致: {}", json);
+    json
+}
+
+/// 读取 JSON 再序列化（用于没有实现 `PartialEq` 的类型）
+fn reserialize<T: Serialize + DeserializeOwned>(value: &Value) -> serde_json::Result<Value> {
+    let typed: T = serde_json::from_value(value.clone())?;
+    Ok(serde_json::to_value(typed).expect("序列化失败"))
+}
+
+/// 枚举全部变体的字符串形式，断言往返一致且互不相同
+fn enum_values<T>(all: &[T]) -> Vec<Value>
+where
+    T: Serialize + DeserializeOwned + PartialEq + Debug,
+{
+    let values: Vec<Value> = all.iter().map(round_trip).collect();
+    let distinct: BTreeSet<String> = values.iter().map(Value::to_string).collect();
+    assert_eq!(distinct.len(), values.len(), "序列化形式重复: {:?}", values);
+    values
+}
+
+/// 当前版本的 `enums` 夹具内容
+fn current_enums() -> Value {
+    json!({
+        "TargetKind": enum_values(&TargetKind::ALL),
+        "ProtectMode": enum_values(&ProtectMode::ALL),
+        "LabelLevel": enum_values(&LabelLevel::ALL),
+        "DowngradeReason": enum_values(&DowngradeReason::ALL),
+        "WarningKind": enum_values(&WarningKind::ALL),
+        "OperationOrigin": enum_values(&OperationOrigin::ALL),
+        "Theme": enum_values(&Theme::ALL),
+        "CancelBehavior": enum_values(&CancelBehavior::ALL),
+        "ScheduleAction": enum_values(&ScheduleAction::ALL),
+    })
+}
+
+/// 按夹具类型读取并再序列化
+fn reserialize_fixture(kind: &str, value: &Value) -> serde_json::Result<Value> {
+    match kind {
+        "lock_record" => reserialize::<LockRecord>(value),
+        "settings" => reserialize::<Settings>(value),
+        "enums" => {
+            let mut out = serde_json::Map::new();
+            for (name, values) in value.as_object().expect("enums 夹具应为对象") {
+                let values = values.as_array().expect("枚举取值应为数组");
+                let read = values
+                    .iter()
+                    .map(|value| match name.as_str() {
+                        "TargetKind" => reserialize::<TargetKind>(value),
+                        "ProtectMode" => reserialize::<ProtectMode>(value),
+                        "LabelLevel" => reserialize::<LabelLevel>(value),
+                        "DowngradeReason" => reserialize::<DowngradeReason>(value),
+                        "WarningKind" => reserialize::<WarningKind>(value),
+                        "OperationOrigin" => reserialize::<OperationOrigin>(value),
+                        "Theme" => reserialize::<Theme>(value),
+                        "CancelBehavior" => reserialize::<CancelBehavior>(value),
+                        "ScheduleAction" => reserialize::<ScheduleAction>(value),
+                        other => panic!("enums 夹具中有未登记的类型 {}", other),
+                    })
+                    .collect::<serde_json::Result<Vec<_>>>()?;
+                out.insert(name.clone(), Value::Array(read));
+            }
+            Ok(Value::Object(out))
+        }
+        other => panic!("未登记的夹具类型 {}（见 reserialize_fixture）", other),
+    }
+}
+
+/// 夹具标签：`vN` 为版本号，`future` 等其他标签为 None
+fn fixture_version(tag: &str) -> Option<u32> {
+    tag.strip_prefix('v')?.parse().ok()
+}
+
+/// 读取全部夹具：类型 → [(标签, 内容)]
+fn fixtures() -> BTreeMap<String, Vec<(String, Value)>> {
+    let dir = Path::new(env!("CARGO_MANIFEST_DIR")).join("testdata/golden");
+    let mut fixtures: BTreeMap<String, Vec<(String, Value)>> = BTreeMap::new();
+    for entry in std::fs::read_dir(&dir).expect("读取夹具目录失败") {
+        let path = entry.expect("读取夹具目录失败").path();
+        if path.extension().is_none_or(|ext| ext != "json") {
+            continue;
+        }
+        let stem = path.file_stem().unwrap().to_string_lossy().to_string();
+        let (kind, tag) = stem
+            .split_once('.')
+            .unwrap_or_else(|| panic!("夹具文件名应为 <类型>.<标签>.json: {}", path.display()));
+        let text = std::fs::read_to_string(&path).expect("读取夹具失败");
+        let value: Value = serde_json::from_str(&text)
+            .unwrap_or_else(|e| panic!("夹具不是有效的 JSON {}: {}", path.display(), e));
+        fixtures
+            .entry(kind.to_string())
+            .or_default()
+            .push((tag.to_string(), value));
+    }
+    fixtures
+}
+
+#[test]
+fn test_all_constants_list_every_variant() {
+    assert_all_variants!(TargetKind { File, Directory });
+    assert_all_variants!(ProtectMode { ReadOnly, Seal });
+    assert_all_variants!(LabelLevel {
+        Medium,
+        High,
+        System
+    });
+    assert_all_variants!(DowngradeReason {
+        MissingRelabelPrivilege,
+        VolumeRootPolicy,
+        Unknown,
+    });
+    assert_all_variants!(WarningKind {
+        StreamsNotProtected,
+        ResolvedThroughReparsePoint,
+        RetriedAfterTransientError,
+        VerificationSkipped,
+        PlaceholdersSkipped,
+        Unknown,
+    });
+    assert_all_variants!(OperationOrigin {
+        GuiButton,
+        GuiShortcut,
+        ShellContextMenu,
+        CliBatch,
+        Broker,
+        SelfTest,
+        Verification,
+        Scheduler,
+        Unknown,
+    });
+    assert_all_variants!(Theme {
+        System,
+        Light,
+        Dark
+    });
+    assert_all_variants!(CancelBehavior {
+        KeepCompleted,
+        RollBackCompleted,
+    });
+    assert_all_variants!(ScheduleAction {
+        Lock,
+        Unlock,
+        Verify
+    });
+    println!("✅ 各枚举的 ALL 覆盖全部变体");
+}
+
+#[test]
+fn test_string_forms_and_labels_cover_every_variant() {
+    // 字符串形式与序列化一致
+    for kind in WarningKind::ALL {
+        assert_eq!(round_trip(&kind), json!(kind.as_str()));
+    }
+    for origin in OperationOrigin::ALL {
+        assert_eq!(round_trip(&origin), json!(origin.as_str()));
+    }
+
+    // 界面文字非空且互不相同
+    let labels: BTreeSet<&str> = WarningKind::ALL.iter().map(WarningKind::label).collect();
+    assert_eq!(labels.len(), WarningKind::ALL.len());
+    assert!(!labels.contains(""));
+    let texts: BTreeSet<String> = CancelBehavior::ALL
+        .iter()
+        .map(ToString::to_string)
+        .collect();
+    assert_eq!(texts.len(), CancelBehavior::ALL.len());
+
+    // 每种警告类型都有对应的 Warning，且序列化的标签与 as_str 一致
+    for kind in WarningKind::ALL {
+        let sample = match kind {
+            WarningKind::StreamsNotProtected => Warning::StreamsNotProtected { count: 2 },
+            WarningKind::ResolvedThroughReparsePoint => Warning::ResolvedThroughReparsePoint,
+            WarningKind::RetriedAfterTransientError => {
+                Warning::RetriedAfterTransientError { attempts: 3 }
+            }
+            WarningKind::VerificationSkipped => Warning::VerificationSkipped,
+            WarningKind::PlaceholdersSkipped => Warning::PlaceholdersSkipped { count: 5 },
+            WarningKind::Unknown => Warning::Unknown,
+        };
+        assert_eq!(sample.kind(), kind);
+        assert_eq!(round_trip(&sample)["kind"], json!(kind.as_str()));
+        assert!(!sample.to_string().is_empty());
+    }
+    println!("✅ 字符串形式与界面文字覆盖全部变体");
+}
+
+#[test]
+fn test_public_types_round_trip() {
+    for mode in [
+        ParallelismMode::Fixed(1),
+        ParallelismMode::Fixed(16),
+        ParallelismMode::Auto,
+    ] {
+        round_trip(&mode);
+    }
+    round_trip(&FileId {
+        volume_serial: 0xDEAD_BEEF,
+        index: 1 << 48 | 21,
+        size: 4096,
+    });
+    round_trip(&MaybeCompressed::Plain("S:(ML;;NW;;;HI)".to_string()));
+    round_trip(&SessionStamp {
+        epoch: 42,
+        saved_utc: "2025-03-14T18:30:00Z".to_string(),
+    });
+    let ace = "(A;OICI;0x1200a9;;;S-1-5-21-1004336348-1177238915-682003330-1001)";
+    let fat = format!("O:BAG:SYD:PAI{}S:(ML;;NW;;;HI)", ace.repeat(8));
+    let compressed = MaybeCompressed::compress(fat, SDDL_COMPRESS_THRESHOLD);
+    assert!(compressed.is_compressed());
+    round_trip(&compressed);
+
+    // 没有实现 PartialEq 的结构体按 JSON 比较
+    for (kind, fixtures) in fixtures() {
+        for (tag, value) in fixtures {
+            let once = reserialize_fixture(&kind, &value).expect("读取夹具失败");
+            let twice = reserialize_fixture(&kind, &once).expect("读取序列化结果失败");
+            assert_eq!(once, twice, "{}.{} 往返后不一致", kind, tag);
+        }
+    }
+    println!("✅ 公开类型往返一致");
+}
+
+#[test]
+fn test_every_fixture_still_deserializes() {
+    let fixtures = fixtures();
+    for kind in ["lock_record", "settings", "enums"] {
+        assert!(fixtures.contains_key(kind), "缺少 {} 夹具", kind);
+    }
+    for (kind, fixtures) in &fixtures {
+        for (tag, value) in fixtures {
+            if let Err(e) = reserialize_fixture(kind, value) {
+                panic!(
+                    "{}.{} 无法读取（旧版本写入的数据必须保持可读）: {}",
+                    kind, tag, e
+                );
+            }
+        }
+    }
+
+    // 只有必填字段的旧记录与设置按缺省值补齐
+    let legacy: LockRecord = serde_json::from_value(
+        fixtures["lock_record"]
+            .iter()
+            .find(|(t, _)| t == "v0")
+            .unwrap()
+            .1
+            .clone(),
+    )
+    .unwrap();
+    assert_eq!(legacy.origin, OperationOrigin::Unknown);
+    assert!(legacy.warnings.is_empty() && legacy.batch_id.is_none());
+    assert_eq!(legacy.seq_no, 0, "旧记录没有序号");
+    let settings: Settings = serde_json::from_value(
+        fixtures["settings"]
+            .iter()
+            .find(|(t, _)| t == "v0")
+            .unwrap()
+            .1
+            .clone(),
+    )
+    .unwrap();
+    assert_eq!(settings.backoff_base_ms, 500);
+    assert!(settings.notifications_enabled);
+    assert_eq!(settings.on_cancel, CancelBehavior::KeepCompleted);
+    println!("✅ 全部历史夹具可以读取");
+}
+
+#[test]
+fn test_newest_fixture_matches_current_serialization() {
+    for (kind, fixtures) in fixtures() {
+        let Some((tag, newest)) = fixtures
+            .iter()
+            .filter_map(|(tag, value)| fixture_version(tag).map(|v| (v, tag, value)))
+            .max_by_key(|(version, _, _)| *version)
+            .map(|(_, tag, value)| (tag, value))
+        else {
+            continue;
+        };
+        let current = match kind.as_str() {
+            "enums" => current_enums(),
+            _ => reserialize_fixture(&kind, newest).unwrap(),
+        };
+        assert_eq!(
+            &current, newest,
+            "{}.{}.json 与当前序列化结果不同：按 testdata/golden/README.md 新增下一版本的夹具",
+            kind, tag
+        );
+    }
+    println!("✅ 最新夹具与当前序列化一致");
+}
+
+#[test]
+fn test_future_values_degrade_to_unknown() {
+    let fixtures = fixtures();
+    let (_, future) = fixtures["lock_record"]
+        .iter()
+        .find(|(tag, _)| tag == "future")
+        .expect("缺少 lock_record.future.json");
+    let record: LockRecord =
+        serde_json::from_value(future.clone()).expect("较新版本的记录应可读取");
+    assert_eq!(record.origin, OperationOrigin::Unknown);
+    assert_eq!(record.downgrade_reason, Some(DowngradeReason::Unknown));
+    assert_eq!(record.warnings, vec![Warning::Unknown]);
+    assert_eq!(record.status, "some_future_status");
+
+    assert_eq!(
+        serde_json::from_str::<WarningKind>(r#""some_future_warning""#).unwrap(),
+        WarningKind::Unknown
+    );
+    // 核心枚举不降级：无法解释的级别或模式必须报错，而不是被当作其他值
+    assert!(serde_json::from_str::<LabelLevel>(r#""Untrusted""#).is_err());
+    assert!(serde_json::from_str::<ProtectMode>(r#""Encrypt""#).is_err());
+    println!("✅ 较新版本写入的未知取值降级为 Unknown");
+}
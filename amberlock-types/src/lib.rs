@@ -1,4 +1,7 @@
+use base64::Engine;
+use base64::engine::general_purpose::STANDARD as BASE64;
 use serde::{Deserialize, Serialize};
+use std::borrow::Cow;
 use thiserror::Error;
 
 #[derive(Debug, Clone, Copy, Serialize, Deserialize, PartialEq, Eq)]
@@ -7,12 +10,22 @@ pub enum TargetKind {
     Directory,
 }
 
+impl TargetKind {
+    /// 全部变体（按声明顺序）
+    pub const ALL: [TargetKind; 2] = [TargetKind::File, TargetKind::Directory];
+}
+
 #[derive(Debug, Clone, Copy, Serialize, Deserialize, PartialEq, Eq)]
 pub enum ProtectMode {
     ReadOnly,
     Seal,
 }
 
+impl ProtectMode {
+    /// 全部变体（按声明顺序）
+    pub const ALL: [ProtectMode; 2] = [ProtectMode::ReadOnly, ProtectMode::Seal];
+}
+
 #[derive(Debug, Clone, Copy, PartialEq, Eq, PartialOrd, Ord, Serialize, Deserialize)]
 pub enum LabelLevel {
     Medium,
@@ -20,6 +33,11 @@ pub enum LabelLevel {
     System,
 }
 
+impl LabelLevel {
+    /// 全部变体（按声明顺序）
+    pub const ALL: [LabelLevel; 3] = [LabelLevel::Medium, LabelLevel::High, LabelLevel::System];
+}
+
 /// 能力探测报告
 #[derive(Debug, Clone)]
 pub struct CapabilityProbe {
@@ -33,6 +51,312 @@ pub struct CapabilityProbe {
     pub user_sid: String,
 }
 
+/// 网络共享访问失败的类别
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum NetworkFailure {
+    /// 服务器名称无法解析
+    NameResolution,
+    /// 服务器或共享不可达（网络、防火墙、SMB 服务）
+    Unreachable,
+    /// 会话过期或凭据无效，需要重新登录共享
+    AuthRequired,
+    /// 其他错误
+    Other,
+}
+
+impl NetworkFailure {
+    /// 按 Win32 错误码分类（同时接受 `HRESULT_FROM_WIN32` 形式）
+    ///
+    /// # 注意
+    /// 仅用于网络路径：本地路径上的拒绝访问通常是特权问题，不应提示重新登录共享
+    pub fn classify(code: u32) -> Self {
+        let code = if code & 0xFFFF_0000 == 0x8007_0000 {
+            code & 0xFFFF
+        } else {
+            code
+        };
+        match code {
+            // WSAHOST_NOT_FOUND、WSANO_DATA、ERROR_INVALID_NETNAME
+            11001 | 11004 | 1214 => NetworkFailure::NameResolution,
+            // ERROR_REM_NOT_LIST、ERROR_BAD_NETPATH、ERROR_UNEXP_NET_ERR、
+            // ERROR_NETNAME_DELETED、ERROR_BAD_NET_NAME、ERROR_SEM_TIMEOUT、
+            // ERROR_NO_NET_OR_BAD_PATH、ERROR_CONNECTION_REFUSED、
+            // ERROR_NETWORK_UNREACHABLE、ERROR_HOST_UNREACHABLE、WSAETIMEDOUT、WSAECONNREFUSED
+            51 | 53 | 59 | 64 | 67 | 121 | 1203 | 1225 | 1231 | 1232 | 10060 | 10061 => {
+                NetworkFailure::Unreachable
+            }
+            // ERROR_ACCESS_DENIED、ERROR_SESSION_CREDENTIAL_CONFLICT、ERROR_NOT_AUTHENTICATED、
+            // ERROR_NO_LOGON_SERVERS、ERROR_LOGON_FAILURE、ERROR_ACCOUNT_RESTRICTION、
+            // ERROR_PASSWORD_EXPIRED、ERROR_ACCOUNT_DISABLED、ERROR_ACCOUNT_EXPIRED、
+            // ERROR_PASSWORD_MUST_CHANGE、ERROR_ACCOUNT_LOCKED_OUT
+            5 | 1219 | 1244 | 1311 | 1326 | 1327 | 1330 | 1331 | 1793 | 1907 | 1909 => {
+                NetworkFailure::AuthRequired
+            }
+            _ => NetworkFailure::Other,
+        }
+    }
+}
+
+/// 共享的文件系统能力
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct ShareCaps {
+    /// 文件系统名称（如 `NTFS`）
+    pub file_system: String,
+    /// 是否保存 ACL（不保存时无法写入完整性标签）
+    pub persistent_acls: bool,
+}
+
+/// 网络共享探测结果
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct NetworkProbe {
+    /// 共享根（`\\server\share`）
+    pub share: String,
+    /// 服务器可达
+    pub reachable: bool,
+    /// 已通过身份验证，可以读取共享元数据
+    pub authenticated: bool,
+    /// 共享的文件系统能力（未通过验证时为 None）
+    pub share_caps: Option<ShareCaps>,
+    /// 失败类别与错误码（成功时为 None）
+    pub failure: Option<(NetworkFailure, u32)>,
+}
+
+impl NetworkProbe {
+    /// 将探测失败转换为带处理建议的错误
+    ///
+    /// # 返回
+    /// 探测成功或失败原因与网络无关时返回 None
+    pub fn to_error(&self) -> Option<AmberlockError> {
+        let share = self.share.clone();
+        match self.failure? {
+            (NetworkFailure::AuthRequired, _) => {
+                Some(AmberlockError::NetworkAuthRequired { share })
+            }
+            (NetworkFailure::NameResolution | NetworkFailure::Unreachable, code) => {
+                Some(AmberlockError::NetworkUnreachable { share, code })
+            }
+            (NetworkFailure::Other, _) => None,
+        }
+    }
+}
+
+/// 卷的类型（决定批量操作的默认节流）
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+pub enum VolumeKind {
+    /// 本地固定磁盘
+    Fixed,
+    /// 可移动磁盘（U 盘、移动硬盘）
+    Removable,
+    /// 网络共享
+    Network,
+    /// 其他或无法识别
+    Other,
+}
+
+/// 文件标识（上锁时记录，用于识别被移动或重命名的对象）
+///
+/// 同一卷内的移动与重命名保持 `volume_serial` + `index` 不变；跨卷移动后标识改变
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+pub struct FileId {
+    /// 卷序列号
+    pub volume_serial: u32,
+    /// 卷内的文件索引
+    pub index: u64,
+    /// 记录时的文件大小（字节，目录为 0）
+    pub size: u64,
+}
+
+impl FileId {
+    /// 是否为同一个文件（比较卷序列号与文件索引，不比较大小）
+    pub fn same_file(&self, other: &FileId) -> bool {
+        self.volume_serial == other.volume_serial && self.index == other.index
+    }
+}
+
+/// 实际级别低于请求级别的原因
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub enum DowngradeReason {
+    /// 缺少 SeRelabelPrivilege，System 降为 High
+    MissingRelabelPrivilege,
+    /// 卷根策略不允许 System 级别，降为 High
+    VolumeRootPolicy,
+    /// 较新版本写入的未知原因
+    #[serde(other)]
+    Unknown,
+}
+
+impl DowngradeReason {
+    /// 全部变体（按声明顺序）
+    pub const ALL: [DowngradeReason; 3] = [
+        DowngradeReason::MissingRelabelPrivilege,
+        DowngradeReason::VolumeRootPolicy,
+        DowngradeReason::Unknown,
+    ];
+}
+
+/// 操作成功但需要用户知晓的情况（不影响 `status`）
+///
+/// 序列化为带 `kind` 标签的对象，如 `{"kind":"retried_after_transient_error","attempts":2}`
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(tag = "kind", rename_all = "snake_case")]
+pub enum Warning {
+    /// 存在未受保护的备用数据流
+    StreamsNotProtected { count: usize },
+    /// 路径经由重解析点（联接或符号链接）解析
+    ResolvedThroughReparsePoint,
+    /// 遇到暂时性错误，重试后成功（`attempts` 为总尝试次数）
+    RetriedAfterTransientError { attempts: u32 },
+    /// 写入后未能读回标签，跳过了写后验证
+    VerificationSkipped,
+    /// 封存目录内的云占位符被跳过
+    PlaceholdersSkipped { count: usize },
+    /// 较新版本写入的未知类型
+    #[serde(other)]
+    Unknown,
+}
+
+impl Warning {
+    /// 警告类型
+    pub fn kind(&self) -> WarningKind {
+        match self {
+            Warning::StreamsNotProtected { .. } => WarningKind::StreamsNotProtected,
+            Warning::ResolvedThroughReparsePoint => WarningKind::ResolvedThroughReparsePoint,
+            Warning::RetriedAfterTransientError { .. } => WarningKind::RetriedAfterTransientError,
+            Warning::VerificationSkipped => WarningKind::VerificationSkipped,
+            Warning::PlaceholdersSkipped { .. } => WarningKind::PlaceholdersSkipped,
+            Warning::Unknown => WarningKind::Unknown,
+        }
+    }
+}
+
+impl std::fmt::Display for Warning {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self {
+            Warning::StreamsNotProtected { count } => {
+                write!(f, "{} 个备用数据流未受保护", count)
+            }
+            Warning::RetriedAfterTransientError { attempts } => {
+                write!(f, "遇到暂时性错误，共尝试 {} 次后成功", attempts)
+            }
+            Warning::PlaceholdersSkipped { count } => {
+                write!(f, "跳过了 {} 个云占位符", count)
+            }
+            other => f.write_str(other.kind().label()),
+        }
+    }
+}
+
+/// 警告类型（按类型统计与查询）
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash, PartialOrd, Ord, Serialize, Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub enum WarningKind {
+    StreamsNotProtected,
+    ResolvedThroughReparsePoint,
+    RetriedAfterTransientError,
+    VerificationSkipped,
+    PlaceholdersSkipped,
+    /// 较新版本写入的未知类型
+    #[serde(other)]
+    Unknown,
+}
+
+impl WarningKind {
+    /// 全部变体（按声明顺序）
+    pub const ALL: [WarningKind; 6] = [
+        WarningKind::StreamsNotProtected,
+        WarningKind::ResolvedThroughReparsePoint,
+        WarningKind::RetriedAfterTransientError,
+        WarningKind::VerificationSkipped,
+        WarningKind::PlaceholdersSkipped,
+        WarningKind::Unknown,
+    ];
+
+    /// 日志中 `kind` 字段的字符串形式
+    pub fn as_str(&self) -> &'static str {
+        match self {
+            WarningKind::StreamsNotProtected => "streams_not_protected",
+            WarningKind::ResolvedThroughReparsePoint => "resolved_through_reparse_point",
+            WarningKind::RetriedAfterTransientError => "retried_after_transient_error",
+            WarningKind::VerificationSkipped => "verification_skipped",
+            WarningKind::PlaceholdersSkipped => "placeholders_skipped",
+            WarningKind::Unknown => "unknown",
+        }
+    }
+
+    /// 界面显示的说明
+    pub fn label(&self) -> &'static str {
+        match self {
+            WarningKind::StreamsNotProtected => "备用数据流未受保护",
+            WarningKind::ResolvedThroughReparsePoint => "路径经由联接或符号链接解析",
+            WarningKind::RetriedAfterTransientError => "遇到暂时性错误，重试后成功",
+            WarningKind::VerificationSkipped => "未能读回标签，跳过了写后验证",
+            WarningKind::PlaceholdersSkipped => "跳过了云占位符",
+            WarningKind::Unknown => "未知警告",
+        }
+    }
+}
+
+/// 操作的发起入口（审计时区分用户操作与内部流程）
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash, Default, Serialize, Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub enum OperationOrigin {
+    /// 图形界面按钮
+    GuiButton,
+    /// 图形界面快捷键
+    GuiShortcut,
+    /// 资源管理器右键菜单
+    ShellContextMenu,
+    /// 命令行批处理（stdin）
+    CliBatch,
+    /// 提权代理服务
+    Broker,
+    /// 自检
+    SelfTest,
+    /// 保护验证（漂移记录等）
+    Verification,
+    /// 计划任务（后台调度或 `schedule run-due`）
+    Scheduler,
+    /// 未知来源（旧记录缺省值，或较新版本写入的未知入口）
+    #[default]
+    #[serde(other)]
+    Unknown,
+}
+
+impl OperationOrigin {
+    /// 全部变体（按声明顺序）
+    pub const ALL: [OperationOrigin; 9] = [
+        OperationOrigin::GuiButton,
+        OperationOrigin::GuiShortcut,
+        OperationOrigin::ShellContextMenu,
+        OperationOrigin::CliBatch,
+        OperationOrigin::Broker,
+        OperationOrigin::SelfTest,
+        OperationOrigin::Verification,
+        OperationOrigin::Scheduler,
+        OperationOrigin::Unknown,
+    ];
+
+    /// 日志中的字符串形式
+    pub fn as_str(&self) -> &'static str {
+        match self {
+            OperationOrigin::GuiButton => "gui_button",
+            OperationOrigin::GuiShortcut => "gui_shortcut",
+            OperationOrigin::ShellContextMenu => "shell_context_menu",
+            OperationOrigin::CliBatch => "cli_batch",
+            OperationOrigin::Broker => "broker",
+            OperationOrigin::SelfTest => "self_test",
+            OperationOrigin::Verification => "verification",
+            OperationOrigin::Scheduler => "scheduler",
+            OperationOrigin::Unknown => "unknown",
+        }
+    }
+
+    fn is_unknown(&self) -> bool {
+        *self == OperationOrigin::Unknown
+    }
+}
+
 #[derive(Debug, Clone, Serialize, Deserialize)]
 pub struct LockRecord {
     pub id: String,
@@ -40,23 +364,437 @@ pub struct LockRecord {
     pub kind: TargetKind,
     pub mode: ProtectMode,
     pub level_applied: LabelLevel,
+    /// 写入时间（UTC，RFC 3339；新记录为微秒精度，旧记录为秒精度）
     pub time_utc: String,
+    /// 进程内单调递增的序号，区分同一时刻写入的记录（旧记录缺省为 0，为 0 时不写入）
+    #[serde(default, skip_serializing_if = "is_zero")]
+    pub seq_no: u64,
     pub user_sid: String,
     pub owner_before: Option<String>,
-    pub sddl_before: Option<String>,
-    pub sddl_after: Option<String>,
+    /// 操作前的 SDDL（启用 `compress_sddl` 时较长的值以压缩形式写入）
+    pub sddl_before: Option<MaybeCompressed<String>>,
+    /// 操作后的 SDDL（同上）
+    pub sddl_after: Option<MaybeCompressed<String>>,
     pub status: String,
     pub errors: Vec<String>,
+    /// 附加审计信息（如通配符来源模式），旧记录缺省为 None
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub details: Option<String>,
+    /// 授权本次操作的保险库凭据名称（仅名称，不含任何密码信息）
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub authorized_by: Option<String>,
+    /// 用户填写的操作备注（工单号、申请来源等），旧记录缺省为 None
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub comment: Option<String>,
+    /// 所属批次 ID（同一次批量操作的记录相同），旧记录与单对象操作为 None
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub batch_id: Option<String>,
+    /// 目标为卷根且用户已确认风险（非卷根操作不写入）
+    #[serde(default, skip_serializing_if = "is_false")]
+    pub volume_root_acknowledged: bool,
+    /// 降级原因（未降级时不写入）
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub downgrade_reason: Option<DowngradeReason>,
+    /// 操作的发起入口（旧记录缺省为 Unknown，未知时不写入）
+    #[serde(default, skip_serializing_if = "OperationOrigin::is_unknown")]
+    pub origin: OperationOrigin,
+    /// 写入记录的程序版本（`<包版本>+<git 提交>`），旧记录缺省为 None
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub app_version: Option<String>,
+    /// 上锁时的文件标识（仅成功上锁的记录，旧记录与无法读取时为 None）
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub file_id: Option<FileId>,
+    /// 操作成功但附带的警告（没有警告时不写入）
+    #[serde(default, skip_serializing_if = "Vec::is_empty")]
+    pub warnings: Vec<Warning>,
+}
+
+impl LockRecord {
+    /// 操作前的 SDDL 文本（压缩字段自动解压，无法解压时为 None）
+    pub fn sddl_before_text(&self) -> Option<String> {
+        expand_field(self.sddl_before.as_ref())
+    }
+
+    /// 操作后的 SDDL 文本（压缩字段自动解压，无法解压时为 None）
+    pub fn sddl_after_text(&self) -> Option<String> {
+        expand_field(self.sddl_after.as_ref())
+    }
+}
+
+/// 超过该长度（字节）的 SDDL 才尝试压缩
+pub const SDDL_COMPRESS_THRESHOLD: usize = 256;
+
+/// 压缩字段使用的算法标识
+pub const COMPRESSION_ALG_ZSTD: &str = "zstd";
+
+/// zstd 压缩级别（日志写入在操作路径上，取默认级别）
+const ZSTD_LEVEL: i32 = 3;
+
+/// 压缩后的日志字段，序列化为 `{"alg":"zstd","b64":"..."}`
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+pub struct CompressedField {
+    pub alg: String,
+    pub b64: String,
+}
+
+impl CompressedField {
+    /// 解码并解压为原始文本
+    pub fn decode(&self) -> Result<String> {
+        if self.alg != COMPRESSION_ALG_ZSTD {
+            return Err(AmberlockError::CompressedField(format!(
+                "不支持的压缩算法 {}",
+                self.alg
+            )));
+        }
+        let bytes = BASE64
+            .decode(&self.b64)
+            .map_err(|e| AmberlockError::CompressedField(e.to_string()))?;
+        let raw = zstd::decode_all(bytes.as_slice())
+            .map_err(|e| AmberlockError::CompressedField(e.to_string()))?;
+        String::from_utf8(raw).map_err(|e| AmberlockError::CompressedField(e.to_string()))
+    }
+}
+
+/// 可能经过压缩的日志字段
+///
+/// 未压缩时序列化为普通字符串，与旧记录格式相同；
+/// 压缩时序列化为 `CompressedField` 对象。读取方应通过 `expand` 取得文本。
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(untagged)]
+pub enum MaybeCompressed<T> {
+    Plain(T),
+    Compressed(CompressedField),
+}
+
+impl MaybeCompressed<String> {
+    /// 长度超过 `threshold` 时尝试压缩
+    ///
+    /// 压缩后（含包装对象）不比原文短时保留原文
+    pub fn compress(text: String, threshold: usize) -> Self {
+        if text.len() <= threshold {
+            return Self::Plain(text);
+        }
+        let Ok(bytes) = zstd::encode_all(text.as_bytes(), ZSTD_LEVEL) else {
+            return Self::Plain(text);
+        };
+        let field = CompressedField {
+            alg: COMPRESSION_ALG_ZSTD.to_string(),
+            b64: BASE64.encode(bytes),
+        };
+        // `{"alg":"zstd","b64":""}` 与原文两侧引号的长度差
+        let wrapper = r#"{"alg":"","b64":""}"#.len() + field.alg.len() - 2;
+        if field.b64.len() + wrapper < text.len() {
+            Self::Compressed(field)
+        } else {
+            Self::Plain(text)
+        }
+    }
+
+    /// 取得原始文本（压缩字段在此解压）
+    pub fn expand(&self) -> Result<Cow<'_, str>> {
+        match self {
+            Self::Plain(text) => Ok(Cow::Borrowed(text)),
+            Self::Compressed(field) => field.decode().map(Cow::Owned),
+        }
+    }
+
+    pub fn is_compressed(&self) -> bool {
+        matches!(self, Self::Compressed(_))
+    }
+}
+
+impl From<String> for MaybeCompressed<String> {
+    fn from(text: String) -> Self {
+        Self::Plain(text)
+    }
+}
+
+impl From<&str> for MaybeCompressed<String> {
+    fn from(text: &str) -> Self {
+        Self::Plain(text.to_string())
+    }
+}
+
+/// 读取可能压缩的可选字段；无法解压时为 None
+pub fn expand_field(field: Option<&MaybeCompressed<String>>) -> Option<String> {
+    field
+        .and_then(|value| value.expand().ok())
+        .map(Cow::into_owned)
+}
+
+fn is_zero(value: &u64) -> bool {
+    *value == 0
+}
+
+fn is_false(value: &bool) -> bool {
+    !*value
 }
 
 #[derive(Debug, Clone, Serialize, Deserialize)]
 pub struct Settings {
-    pub parallelism: usize,
+    /// 并发度：固定值（如 `4`）或 `"auto"`（按实测延迟自动调整）
+    pub parallelism: ParallelismMode,
     pub default_mode: ProtectMode,
     pub default_level: LabelLevel,
     pub log_path: String,
     pub vault_path: String,
     pub shell_integration: bool,
+    /// 密码校验失败后的基础延迟（毫秒），旧设置文件缺省为 500
+    #[serde(default = "default_backoff_base_ms")]
+    pub backoff_base_ms: u64,
+    /// 校验成功时同样补齐延迟，使成功与失败耗时接近（高安全部署）
+    #[serde(default)]
+    pub backoff_pad_success: bool,
+    /// 主窗口不在前台时，长时间操作完成后弹出系统通知
+    #[serde(default = "default_true")]
+    pub notifications_enabled: bool,
+    /// 告警 Webhook 地址（为空时不发送告警）
+    #[serde(default)]
+    pub alert_webhook_url: Option<String>,
+    /// 失败或漂移数量达到该值时发送告警
+    #[serde(default = "default_alert_min_failures")]
+    pub alert_min_failures: usize,
+    /// 只读审计模式：禁止所有修改操作（`--read-only` 启动参数优先）
+    #[serde(default)]
+    pub read_only: bool,
+    /// 启动时检查更新（默认关闭）
+    #[serde(default)]
+    pub check_updates: bool,
+    /// 发布清单地址
+    #[serde(default)]
+    pub update_manifest_url: Option<String>,
+    /// 上次检查更新的时间（UNIX 秒）
+    #[serde(default)]
+    pub last_update_check: Option<u64>,
+    /// 可移动/网络卷上同时处理的对象数上限（0 表示不限）
+    #[serde(default = "default_slow_volume_max_concurrent")]
+    pub slow_volume_max_concurrent: usize,
+    /// 可移动/网络卷上两次操作开始之间的最短间隔（毫秒）
+    #[serde(default = "default_slow_volume_min_interval_ms")]
+    pub slow_volume_min_interval_ms: u64,
+    /// 操作日志副本路径（可位于其他卷或 UNC 共享；为空时不复制）
+    #[serde(default)]
+    pub replica_path: Option<String>,
+    /// 计划任务（按时间自动上锁、解锁或验证）
+    #[serde(default)]
+    pub schedule: Schedule,
+    /// 以 zstd 压缩写入较长的 SDDL 字段（减小递归操作的日志体积）
+    #[serde(default)]
+    pub compress_sddl: bool,
+    /// 界面主题（默认跟随系统）
+    #[serde(default)]
+    pub theme: Theme,
+    /// 允许在本次运行输入过保险库密码后用 Windows Hello 代替密码解锁
+    #[serde(default)]
+    pub allow_hello_unlock: bool,
+    /// 取消批量上锁时对已完成对象的处理方式（默认保留）
+    #[serde(default)]
+    pub on_cancel: CancelBehavior,
+    /// 最近一次退出保存的标记（与保护清单中的标记比对，检测两者是否来自同一次保存）
+    #[serde(default)]
+    pub session: Option<SessionStamp>,
+}
+
+/// 退出时设置与保护清单一同保存的标记
+///
+/// 两个文件写入相同的标记；启动时标记不同说明上次保存中断或文件被替换
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+pub struct SessionStamp {
+    /// 保存序号（每次退出保存递增）
+    pub epoch: u64,
+    /// 保存时间（RFC 3339）
+    pub saved_utc: String,
+}
+
+/// 界面主题
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub enum Theme {
+    /// 跟随 Windows“应用模式”（浅色/深色）
+    #[default]
+    System,
+    Light,
+    Dark,
+}
+
+impl Theme {
+    /// 全部变体（按声明顺序）
+    pub const ALL: [Theme; 3] = [Theme::System, Theme::Light, Theme::Dark];
+}
+
+/// 并发度设置
+///
+/// 序列化为数字（固定并发度，兼容旧设置文件）或字符串 `"auto"`
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(try_from = "ParallelismRepr", into = "ParallelismRepr")]
+pub enum ParallelismMode {
+    /// 固定的工作线程数
+    Fixed(usize),
+    /// 从保守的并发度开始，按每个卷的实测延迟与暂时性错误率自动增减
+    Auto,
+}
+
+impl Default for ParallelismMode {
+    fn default() -> Self {
+        ParallelismMode::Fixed(4)
+    }
+}
+
+impl ParallelismMode {
+    /// 是否为自动调整
+    pub fn is_auto(self) -> bool {
+        self == ParallelismMode::Auto
+    }
+}
+
+impl std::fmt::Display for ParallelismMode {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self {
+            ParallelismMode::Fixed(n) => write!(f, "{}", n),
+            ParallelismMode::Auto => write!(f, "自动"),
+        }
+    }
+}
+
+#[derive(Serialize, Deserialize)]
+#[serde(untagged)]
+enum ParallelismRepr {
+    Fixed(usize),
+    Named(String),
+}
+
+impl TryFrom<ParallelismRepr> for ParallelismMode {
+    type Error = String;
+
+    fn try_from(repr: ParallelismRepr) -> std::result::Result<Self, Self::Error> {
+        match repr {
+            ParallelismRepr::Fixed(n) => Ok(ParallelismMode::Fixed(n)),
+            ParallelismRepr::Named(name) if name.eq_ignore_ascii_case("auto") => {
+                Ok(ParallelismMode::Auto)
+            }
+            ParallelismRepr::Named(name) => {
+                Err(format!("无效的并发度: {}（应为正整数或 \"auto\"）", name))
+            }
+        }
+    }
+}
+
+impl From<ParallelismMode> for ParallelismRepr {
+    fn from(mode: ParallelismMode) -> Self {
+        match mode {
+            ParallelismMode::Fixed(n) => ParallelismRepr::Fixed(n),
+            ParallelismMode::Auto => ParallelismRepr::Named("auto".to_string()),
+        }
+    }
+}
+
+/// 取消批量上锁时对已完成对象的处理方式
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub enum CancelBehavior {
+    /// 只停止处理剩余对象，已上锁的对象保持上锁
+    #[default]
+    KeepCompleted,
+    /// 停止后按日志撤销本批次已上锁的对象
+    RollBackCompleted,
+}
+
+impl CancelBehavior {
+    /// 全部变体（按声明顺序）
+    pub const ALL: [CancelBehavior; 2] = [
+        CancelBehavior::KeepCompleted,
+        CancelBehavior::RollBackCompleted,
+    ];
+}
+
+impl std::fmt::Display for CancelBehavior {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self {
+            CancelBehavior::KeepCompleted => write!(f, "保留已完成的对象"),
+            CancelBehavior::RollBackCompleted => write!(f, "撤销已完成的对象"),
+        }
+    }
+}
+
+/// 计划任务列表
+#[derive(Debug, Clone, Default, Serialize, Deserialize)]
+pub struct Schedule {
+    #[serde(default)]
+    pub entries: Vec<ScheduleEntry>,
+}
+
+/// 计划任务的动作
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub enum ScheduleAction {
+    Lock,
+    Unlock,
+    Verify,
+}
+
+impl ScheduleAction {
+    /// 全部变体（按声明顺序）
+    pub const ALL: [ScheduleAction; 3] = [
+        ScheduleAction::Lock,
+        ScheduleAction::Unlock,
+        ScheduleAction::Verify,
+    ];
+}
+
+/// 单个计划任务
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct ScheduleEntry {
+    /// 目标文件或文件夹
+    pub path: String,
+    pub action: ScheduleAction,
+    /// 本地时间规则，如 `19:00`、`mon-fri 08:00`、`sat,sun 22:30`
+    pub at: String,
+    /// 文件夹是否包含全部子对象
+    #[serde(default)]
+    pub recursive: bool,
+    /// 上锁模式（解锁时忽略）
+    #[serde(default = "default_schedule_mode")]
+    pub mode: ProtectMode,
+    /// 上锁级别；验证时为期望级别（解锁时忽略）
+    #[serde(default = "default_schedule_level")]
+    pub level: LabelLevel,
+    /// 错过的时间点（如关机期间）在下次启动时补执行一次
+    #[serde(default)]
+    pub catch_up: bool,
+    /// 允许无人值守解锁（不校验保险库密码，需用户明确选择）
+    #[serde(default)]
+    pub allow_unattended_unlock: bool,
+    /// 上次执行（或跳过）的时间（RFC 3339，UTC）
+    #[serde(default)]
+    pub last_run_utc: Option<String>,
+}
+
+fn default_schedule_mode() -> ProtectMode {
+    ProtectMode::ReadOnly
+}
+
+fn default_schedule_level() -> LabelLevel {
+    LabelLevel::High
+}
+
+fn default_true() -> bool {
+    true
+}
+
+fn default_backoff_base_ms() -> u64 {
+    500
+}
+
+fn default_alert_min_failures() -> usize {
+    1
+}
+
+fn default_slow_volume_max_concurrent() -> usize {
+    2
+}
+
+fn default_slow_volume_min_interval_ms() -> u64 {
+    5
 }
 
 /// AmberLock 错误类型
@@ -77,11 +815,281 @@ pub enum AmberlockError {
     #[error("无效的标签或 SDDL")]
     InvalidLabel,
 
+    #[cfg(windows)]
     #[error("Windows API 错误: {0}")]
     Win32Error(#[from] windows::core::Error),
 
     #[error("需要提权执行")]
     ElevationRequired,
+
+    #[error("无效的通配符模式: {0}")]
+    InvalidPattern(String),
+
+    #[error("匹配数量超过上限 {limit}，请缩小通配符范围")]
+    TooManyMatches { limit: usize },
+
+    #[error("后台操作线程异常终止")]
+    WorkerPanicked,
+
+    #[error("与正在进行的{kind}操作冲突: {other_root}")]
+    ConflictingOperation { other_root: String, kind: String },
+
+    #[error("保险库数据损坏: {0}")]
+    VaultCorrupted(String),
+
+    #[error("保险库已损坏或被篡改，请从备份恢复: {0}")]
+    VaultTampered(String),
+
+    #[error("保险库属于其他用户或其他计算机: {0}")]
+    VaultWrongUserOrMachine(String),
+
+    #[error("保险库凭据 {name} 的 Argon2 参数不一致: {reason}")]
+    InconsistentVaultParams { name: String, reason: String },
+
+    #[error("不安全的 Argon2 参数: {0}")]
+    UnsafeArgon2Config(String),
+
+    #[error("密码错误")]
+    WrongPassword,
+
+    #[error("密码错误次数过多，请在 {remaining_secs} 秒后重试")]
+    LockedOut { remaining_secs: u64 },
+
+    #[error("密码或验证码错误")]
+    AuthorizationFailed { updated: Vec<u8> },
+
+    #[error("恢复密钥格式无效: {0}")]
+    InvalidRecoveryKey(String),
+
+    #[error("恢复密钥错误")]
+    WrongRecoveryKey,
+
+    #[error("保险库未设置恢复密钥")]
+    RecoveryNotConfigured,
+
+    #[error("无效的密钥文件: {0}")]
+    InvalidKeyfile(String),
+
+    #[error("此保险库以密码加密（非 DPAPI），需要输入密码才能读取")]
+    PasswordRequired,
+
+    #[error("以密码加密（非 DPAPI）的保险库不支持{0}")]
+    UnsupportedOnPasswordBackend(&'static str),
+
+    #[error("无效的密码提示: {0}")]
+    InvalidHint(String),
+
+    #[error("导出文件格式无效: {0}")]
+    PortableFormat(String),
+
+    #[error("无法解密导出文件：密码错误或文件已损坏")]
+    PortableDecryptFailed,
+
+    #[error("凭据不存在: {0}")]
+    CredentialNotFound(String),
+
+    #[error("凭据已存在: {0}")]
+    CredentialExists(String),
+
+    #[error("无效的凭据名称: {0}")]
+    InvalidCredentialName(String),
+
+    #[error("不能删除最后一个凭据")]
+    LastCredential,
+
+    #[error("不能删除最后一个管理员凭据")]
+    LastAdmin,
+
+    #[error("保留设备名不能作为操作目标: {0}")]
+    ReservedName(String),
+
+    #[error("告警投递失败: {0}")]
+    AlertDelivery(String),
+
+    #[error("只读审计模式下禁止修改操作")]
+    ReadOnlyMode,
+
+    #[error("操作日志目录 {log_dir} 被设置为 {dir_level:?} 完整性级别，当前进程为 {process_level:?}，无法写入审计日志")]
+    AuditBlockedBySelfLock {
+        log_dir: String,
+        dir_level: LabelLevel,
+        process_level: LabelLevel,
+    },
+
+    #[error("无效的诊断跟踪文件: {0}")]
+    InvalidTrace(String),
+
+    #[error("令牌信息格式无效: {0}")]
+    MalformedTokenInfo(String),
+
+    #[error("卷根 {0} 只能以只读模式（仅 NW 策略）上锁")]
+    VolumeRootModeNotAllowed(String),
+
+    #[error("锁定卷根 {0} 需要先确认风险")]
+    VolumeRootNotAcknowledged(String),
+
+    #[error("确认短语不一致，请完整输入“{0}”")]
+    ConfirmPhraseMismatch(&'static str),
+
+    #[error("网络共享 {share} 需要重新登录：请在资源管理器中打开该共享并输入凭据，或执行 `net use {share}` 后重试")]
+    NetworkAuthRequired { share: String },
+
+    #[error("无法连接网络共享 {share}（错误 {code}）：请检查网络连接与服务器名称，并在资源管理器中确认能打开该共享")]
+    NetworkUnreachable { share: String, code: u32 },
+
+    #[error("写入 {path} 的标签被拒绝（错误 {code}），但特权与访问权限预检均已通过，疑似被安全软件（文件系统过滤驱动）拦截：请将 AmberLock 加入安全软件的信任列表后重试")]
+    SuspectedFilterInterference { path: String, code: u32 },
+
+    #[error("{path} 的安全描述符在预览之后已被修改，为避免写入未经确认的变更已放弃封印，请重新预览")]
+    PlanStale { path: String },
+
+    #[error("无效的计划时间: {0}（示例：19:00、mon-fri 08:00）")]
+    InvalidSchedule(String),
+
+    #[error("无法还原压缩的日志字段: {0}")]
+    CompressedField(String),
 }
 
-pub type Result<T> = std::result::Result<T, AmberlockError>;
\ No newline at end of file
+pub type Result<T> = std::result::Result<T, AmberlockError>;
+#[cfg(test)]
+mod compat;
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_network_failure_classification() {
+        let table = [
+            (11001, NetworkFailure::NameResolution),
+            (53, NetworkFailure::Unreachable),
+            (67, NetworkFailure::Unreachable),
+            (1231, NetworkFailure::Unreachable),
+            (0x8007_04CF, NetworkFailure::Unreachable),
+            (5, NetworkFailure::AuthRequired),
+            (0x8007_0005, NetworkFailure::AuthRequired),
+            (1326, NetworkFailure::AuthRequired),
+            (1219, NetworkFailure::AuthRequired),
+            (2, NetworkFailure::Other),
+            (0x8000_4005, NetworkFailure::Other),
+        ];
+        for (code, expected) in table {
+            assert_eq!(
+                NetworkFailure::classify(code),
+                expected,
+                "错误码 {:#x}",
+                code
+            );
+        }
+    }
+
+    #[test]
+    fn test_network_probe_error() {
+        let mut probe = NetworkProbe {
+            share: r"\\nas\backup".to_string(),
+            reachable: true,
+            authenticated: false,
+            share_caps: None,
+            failure: Some((NetworkFailure::AuthRequired, 1326)),
+        };
+        let error = probe.to_error().expect("应转换为网络错误");
+        assert!(matches!(error, AmberlockError::NetworkAuthRequired { .. }));
+        assert!(error.to_string().contains(r"net use \\nas\backup"));
+
+        probe.failure = Some((NetworkFailure::NameResolution, 11001));
+        assert!(matches!(
+            probe.to_error(),
+            Some(AmberlockError::NetworkUnreachable { code: 11001, .. })
+        ));
+        probe.failure = None;
+        assert!(probe.to_error().is_none());
+    }
+
+    #[test]
+    fn test_sddl_compression() {
+        let ace = "(A;OICI;0x1200a9;;;S-1-5-21-1004336348-1177238915-682003330-1001)";
+        let fat = format!("O:BAG:SYD:PAI{}S:(ML;;NW;;;HI)", ace.repeat(200));
+
+        let field = MaybeCompressed::compress(fat.clone(), SDDL_COMPRESS_THRESHOLD);
+        assert!(field.is_compressed(), "重复的 ACE 应被压缩");
+        assert_eq!(field.expand().expect("解压失败"), fat);
+
+        // 短于阈值的不压缩
+        let short =
+            MaybeCompressed::compress("S:(ML;;NW;;;HI)".to_string(), SDDL_COMPRESS_THRESHOLD);
+        assert_eq!(short, MaybeCompressed::Plain("S:(ML;;NW;;;HI)".to_string()));
+
+        // 压缩后不更短的保留原文
+        let mut state = 0x9e37_79b9_7f4a_7c15u64;
+        let noisy: String = (0..600)
+            .map(|_| {
+                state ^= state << 13;
+                state ^= state >> 7;
+                state ^= state << 17;
+                char::from(b'!' + (state % 90) as u8)
+            })
+            .collect();
+        assert!(!MaybeCompressed::compress(noisy.clone(), 0).is_compressed());
+
+        // 未知算法与损坏的数据报错
+        let unknown = MaybeCompressed::<String>::Compressed(CompressedField {
+            alg: "lz4".to_string(),
+            b64: String::new(),
+        });
+        assert!(matches!(
+            unknown.expand(),
+            Err(AmberlockError::CompressedField(_))
+        ));
+        assert_eq!(expand_field(Some(&unknown)), None);
+        println!("✅ SDDL 字段压缩与还原正确");
+    }
+
+    #[test]
+    fn test_warning_serialization() {
+        let warning = Warning::RetriedAfterTransientError { attempts: 2 };
+        let json = serde_json::to_string(&warning).unwrap();
+        assert_eq!(
+            json,
+            r#"{"kind":"retried_after_transient_error","attempts":2}"#
+        );
+        assert_eq!(serde_json::from_str::<Warning>(&json).unwrap(), warning);
+        assert_eq!(
+            serde_json::to_string(&Warning::VerificationSkipped).unwrap(),
+            r#"{"kind":"verification_skipped"}"#
+        );
+
+        // 每种类型的 as_str 与序列化的标签一致
+        let samples = [
+            Warning::StreamsNotProtected { count: 1 },
+            Warning::ResolvedThroughReparsePoint,
+            warning,
+            Warning::VerificationSkipped,
+            Warning::PlaceholdersSkipped { count: 3 },
+        ];
+        for sample in &samples {
+            let value = serde_json::to_value(sample).unwrap();
+            assert_eq!(value["kind"].as_str(), Some(sample.kind().as_str()));
+        }
+
+        // 较新版本写入的未知类型不影响读取
+        let unknown: Warning =
+            serde_json::from_str(r#"{"kind":"future_thing","extra":1}"#).unwrap();
+        assert_eq!(unknown.kind(), WarningKind::Unknown);
+        println!("✅ 警告序列化格式正确");
+    }
+
+    #[test]
+    fn test_parallelism_mode_accepts_number_or_auto() {
+        // 旧设置文件中的数字仍按固定并发度读取
+        let fixed: ParallelismMode = serde_json::from_str("8").unwrap();
+        assert_eq!(fixed, ParallelismMode::Fixed(8));
+        assert_eq!(serde_json::to_string(&fixed).unwrap(), "8");
+
+        let auto: ParallelismMode = serde_json::from_str(r#""Auto""#).unwrap();
+        assert_eq!(auto, ParallelismMode::Auto);
+        assert_eq!(serde_json::to_string(&auto).unwrap(), r#""auto""#);
+
+        assert!(serde_json::from_str::<ParallelismMode>(r#""fast""#).is_err());
+        println!("✅ 并发度设置兼容数字与 auto");
+    }
+}
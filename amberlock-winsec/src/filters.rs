@@ -0,0 +1,129 @@
+//! 安全软件干扰诊断
+//!
+//! 终端安全产品通常以文件系统微筛选器（minifilter）的形式拦截 SACL 写入。
+//! 本模块提供两项诊断：
+//! - 枚举已加载的微筛选器，供启动报告展示
+//! - 预检当前进程能否以写入标签所需的访问权限打开对象
+
+use crate::HandleGuard;
+use crate::impersonate::with_privilege;
+use amberlock_types::{AmberlockError, Result};
+use windows::Win32::Foundation::{ERROR_NO_MORE_ITEMS, HANDLE};
+use windows::Win32::Storage::FileSystem::{
+    CreateFileW, FILE_FLAG_BACKUP_SEMANTICS, FILE_SHARE_DELETE, FILE_SHARE_READ,
+    FILE_SHARE_WRITE, OPEN_EXISTING, WRITE_OWNER,
+};
+use windows::Win32::Storage::InstallableFileSystems::{
+    FILTER_FULL_INFORMATION, FilterFindClose, FilterFindFirst, FilterFindNext,
+    FilterFullInformation,
+};
+use windows::Win32::System::SystemServices::ACCESS_SYSTEM_SECURITY;
+use windows::core::PCWSTR;
+
+/// 单条筛选器信息的缓冲区大小（`u64` 个数，保证对齐）
+const FILTER_INFO_BUFFER_WORDS: usize = 128;
+
+/// 枚举已加载的文件系统微筛选器
+///
+/// # 返回
+/// 筛选器名称（按系统返回的顺序，通常包括 `FileInfo`、`WdFilter` 等内置筛选器）
+///
+/// # 注意
+/// 需要管理员权限，普通权限下返回拒绝访问错误
+pub fn list_filter_drivers() -> Result<Vec<String>> {
+    let mut buffer = vec![0u64; FILTER_INFO_BUFFER_WORDS];
+    let size = (buffer.len() * size_of::<u64>()) as u32;
+    let mut returned = 0u32;
+    let mut find = HANDLE::default();
+    let mut names = Vec::new();
+
+    unsafe {
+        FilterFindFirst(
+            FilterFullInformation,
+            buffer.as_mut_ptr().cast(),
+            size,
+            &mut returned,
+            &mut find,
+        )
+    }
+    .map_err(|e| AmberlockError::Win32 {
+        code: e.code().0 as u32,
+        msg: format!("枚举文件系统筛选器失败: {}", e),
+    })?;
+
+    loop {
+        names.push(filter_name(&buffer));
+        let next = unsafe {
+            FilterFindNext(
+                find,
+                FilterFullInformation,
+                buffer.as_mut_ptr().cast(),
+                size,
+                &mut returned,
+            )
+        };
+        if let Err(e) = next {
+            let _ = unsafe { FilterFindClose(find) };
+            if e.code() == ERROR_NO_MORE_ITEMS.to_hresult() {
+                return Ok(names);
+            }
+            return Err(AmberlockError::Win32 {
+                code: e.code().0 as u32,
+                msg: format!("枚举文件系统筛选器失败: {}", e),
+            });
+        }
+    }
+}
+
+/// 从 `FILTER_FULL_INFORMATION` 缓冲区读取筛选器名称
+fn filter_name(buffer: &[u64]) -> String {
+    let info = buffer.as_ptr().cast::<FILTER_FULL_INFORMATION>();
+    unsafe {
+        let len = (*info).FilterNameLength as usize / size_of::<u16>();
+        let name = std::ptr::addr_of!((*info).FilterNameBuffer).cast::<u16>();
+        String::from_utf16_lossy(std::slice::from_raw_parts(name, len))
+    }
+}
+
+/// 预检能否以写入标签所需的访问权限打开对象
+///
+/// # 参数
+/// - `path`: 对象路径
+///
+/// # 返回
+/// 启用 SeSecurityPrivilege 后能以 `WRITE_OWNER | ACCESS_SYSTEM_SECURITY` 打开对象时为 true
+///
+/// # 注意
+/// 打开失败（包括缺少特权）一律返回 false，调用方据此保守地视为真实的权限错误
+pub fn probe_label_access(path: &str) -> bool {
+    let wide_path: Vec<u16> = path.encode_utf16().chain(Some(0)).collect();
+    with_privilege("SeSecurityPrivilege", || {
+        let handle = unsafe {
+            CreateFileW(
+                PCWSTR(wide_path.as_ptr()),
+                WRITE_OWNER.0 | ACCESS_SYSTEM_SECURITY,
+                FILE_SHARE_READ | FILE_SHARE_WRITE | FILE_SHARE_DELETE,
+                None,
+                OPEN_EXISTING,
+                FILE_FLAG_BACKUP_SEMANTICS,
+                None,
+            )
+        }?;
+        let _guard = HandleGuard(handle);
+        Ok(())
+    })
+    .is_ok()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    #[ignore]
+    fn test_lists_builtin_filters() {
+        let filters = list_filter_drivers().expect("枚举筛选器失败（需要管理员权限）");
+        assert!(!filters.is_empty(), "系统至少加载了内置筛选器");
+        println!("✅ 已加载的筛选器: {}", filters.join(", "));
+    }
+}
@@ -1,14 +1,16 @@
 use crate::HandleGuard;
 use amberlock_types::{AmberlockError, LabelLevel, Result};
 use std::mem::{size_of, zeroed};
+use std::ops::ControlFlow;
 use windows::{
     Win32::Foundation::{CloseHandle, HANDLE, LUID},
     Win32::Security::{
         AdjustTokenPrivileges, DuplicateTokenEx, GetTokenInformation, ImpersonateLoggedOnUser,
         LUID_AND_ATTRIBUTES, LookupPrivilegeValueW, RevertToSelf, SE_PRIVILEGE_ENABLED,
-        SecurityImpersonation, SetTokenInformation, TOKEN_ADJUST_PRIVILEGES, TOKEN_ALL_ACCESS,
-        TOKEN_DUPLICATE, TOKEN_IMPERSONATE, TOKEN_PRIVILEGES, TOKEN_PRIVILEGES_ATTRIBUTES,
-        TOKEN_QUERY, TokenIntegrityLevel, TokenPrimary, TokenSessionId,
+        SecurityImpersonation, SetTokenInformation, TOKEN_ACCESS_MASK, TOKEN_ADJUST_PRIVILEGES,
+        TOKEN_ALL_ACCESS, TOKEN_DUPLICATE, TOKEN_IMPERSONATE, TOKEN_PRIVILEGES,
+        TOKEN_PRIVILEGES_ATTRIBUTES, TOKEN_QUERY, TokenIntegrityLevel, TokenPrimary,
+        TokenSessionId,
     },
     Win32::System::{
         Diagnostics::ToolHelp::{
@@ -34,12 +36,30 @@ const SYSTEM_PROCESS_CANDIDATES: &[&str] = &[
     "wininit.exe",  // Windows 启动进程
 ];
 
+/// 窃取令牌时打开进程令牌所需的权限
+const STEAL_TOKEN_ACCESS: TOKEN_ACCESS_MASK =
+    TOKEN_ACCESS_MASK(TOKEN_DUPLICATE.0 | TOKEN_QUERY.0 | TOKEN_IMPERSONATE.0);
+
 /// 进程信息结构体
-#[derive(Debug, Clone)]
 struct ProcessInfo {
+    /// 进程 ID
     pid: u32,
+    /// 可执行文件名（见 `exe_name_from_entry`）
     name: String,
-    token: HANDLE,
+    /// 进程令牌：枚举时不打开；`open` 打开失败（进程已退出、无权访问）时为 None，
+    /// 随结构体释放关闭
+    token: Option<HandleGuard>,
+}
+
+impl ProcessInfo {
+    /// 以指定权限打开进程令牌
+    fn open(pid: u32, name: &str, access: TOKEN_ACCESS_MASK) -> Self {
+        Self {
+            pid,
+            name: name.to_string(),
+            token: open_process_token(pid, access),
+        }
+    }
 }
 
 /// 令牌窃取上下文
@@ -64,14 +84,27 @@ impl ImpersonationContext {
 
             let session_id = WTSGetActiveConsoleSessionId();
 
-            // 遍历候选进程
+            // 遍历候选进程：同名进程（如 csrss.exe）逐个尝试后才换下一个名称
             for process_name in SYSTEM_PROCESS_CANDIDATES {
-                match steal_token_from_process(process_name, session_id) {
-                    Ok(token) => {
-                        return Ok(Self { token, session_id });
-                    }
+                let pids = match get_pids_by_name(process_name) {
+                    Ok(pids) => pids,
                     Err(e) => {
                         eprintln!("尝试从 {} 窃取令牌失败: {:?}", process_name, e);
+                        continue;
+                    }
+                };
+                for pid in pids {
+                    let process = ProcessInfo::open(pid, process_name, STEAL_TOKEN_ACCESS);
+                    match steal_token_from_process(&process, session_id) {
+                        Ok(token) => {
+                            return Ok(Self { token, session_id });
+                        }
+                        Err(e) => {
+                            eprintln!(
+                                "尝试从 {}（PID {}）窃取令牌失败: {:?}",
+                                process_name, pid, e
+                            );
+                        }
                     }
                 }
             }
@@ -347,20 +380,18 @@ where
 }
 
 /// 从指定进程窃取令牌
-fn steal_token_from_process(process_name: &str, session_id: u32) -> Result<HANDLE> {
+///
+/// `process.token` 须以 `STEAL_TOKEN_ACCESS` 打开；为 None 时返回错误
+fn steal_token_from_process(process: &ProcessInfo, session_id: u32) -> Result<HANDLE> {
     unsafe {
-        let pid = get_pid_by_name(process_name)?;
-        let mut h_token = HANDLE::default();
-
-        let h_process = OpenProcess(PROCESS_QUERY_INFORMATION, false, pid)?;
-        let _process_guard = HandleGuard(h_process);
-
-        OpenProcessToken(
-            h_process,
-            TOKEN_DUPLICATE | TOKEN_QUERY | TOKEN_IMPERSONATE,
-            &mut h_token,
-        )?;
-        let _token_guard = HandleGuard(h_token);
+        let Some(token) = &process.token else {
+            return Err(AmberlockError::Win32 {
+                code: 0,
+                msg: format!("无法打开进程 {}（PID {}）的令牌", process.name, process.pid),
+            });
+        };
+        let h_token = token.0;
+        let process_name = &process.name;
 
         let mut h_dup_token = HANDLE::default();
         DuplicateTokenEx(
@@ -435,8 +466,39 @@ fn set_token_session_id(token: HANDLE, session_id: u32) -> Result<()> {
     }
 }
 
-/// 获取进程ID通过名称
-fn get_pid_by_name(name: &str) -> Result<u32> {
+/// 以指定权限打开进程令牌（进程已退出或无权访问时为 None）
+fn open_process_token(pid: u32, access: TOKEN_ACCESS_MASK) -> Option<HandleGuard> {
+    unsafe {
+        let h_process = OpenProcess(PROCESS_QUERY_INFORMATION, false, pid).ok()?;
+        let _process_guard = HandleGuard(h_process);
+
+        let mut h_token = HANDLE::default();
+        OpenProcessToken(h_process, access, &mut h_token).ok()?;
+        Some(HandleGuard(h_token))
+    }
+}
+
+/// 从进程快照条目中提取可执行文件名
+///
+/// `szExeFile` 在第一个 NUL 处结束，之后的内容忽略；没有 NUL 时取整个缓冲区，
+/// 无效的 UTF-16 替换为 U+FFFD
+fn exe_name_from_entry(entry: &PROCESSENTRY32W) -> String {
+    let name = &entry.szExeFile;
+    let len = name.iter().position(|&c| c == 0).unwrap_or(name.len());
+    String::from_utf16_lossy(&name[..len])
+}
+
+/// 遍历系统进程快照
+///
+/// # 参数
+/// - `visit`: 对每个进程调用（`token` 为 None），返回 `ControlFlow::Break` 时提前结束
+///
+/// # 返回
+/// 已访问的进程数
+fn enumerate_processes<F>(mut visit: F) -> Result<usize>
+where
+    F: FnMut(&ProcessInfo) -> ControlFlow<()>,
+{
     unsafe {
         let snapshot = CreateToolhelp32Snapshot(TH32CS_SNAPPROCESS, 0)?;
         let _snapshot_guard = HandleGuard(snapshot);
@@ -452,20 +514,17 @@ fn get_pid_by_name(name: &str) -> Result<u32> {
             });
         }
 
-        let mut checked_count = 0usize;
+        let mut visited = 0usize;
         loop {
-            let proc_name = String::from_utf16_lossy(
-                &entry.szExeFile[..entry
-                    .szExeFile
-                    .iter()
-                    .position(|&c| c == 0)
-                    .unwrap_or(entry.szExeFile.len())],
-            );
-
-            checked_count += 1;
-
-            if proc_name.eq_ignore_ascii_case(name) {
-                return Ok(entry.th32ProcessID);
+            let process = ProcessInfo {
+                pid: entry.th32ProcessID,
+                name: exe_name_from_entry(&entry),
+                token: None,
+            };
+            visited += 1;
+
+            if visit(&process).is_break() {
+                break;
             }
 
             // 处理遍历结束
@@ -474,11 +533,27 @@ fn get_pid_by_name(name: &str) -> Result<u32> {
             }
         }
 
-        Err(AmberlockError::Win32 {
+        Ok(visited)
+    }
+}
+
+/// 按名称查找全部同名进程的 ID（不区分大小写，按快照顺序）
+fn get_pids_by_name(name: &str) -> Result<Vec<u32>> {
+    let mut pids = Vec::new();
+    let checked_count = enumerate_processes(|process| {
+        if process.name.eq_ignore_ascii_case(name) {
+            pids.push(process.pid);
+        }
+        ControlFlow::Continue(())
+    })?;
+
+    if pids.is_empty() {
+        return Err(AmberlockError::Win32 {
             code: 0,
             msg: format!("未找到进程 {}（已检查 {} 个进程）", name, checked_count),
-        })
+        });
     }
+    Ok(pids)
 }
 
 /// 一步到位创建 SYSTEM 进程
@@ -537,6 +612,102 @@ mod tests {
         }
     }
 
+    /// 构造 `szExeFile` 为指定内容（其余为 0）的快照条目
+    fn entry_with_name(units: &[u16]) -> PROCESSENTRY32W {
+        let mut entry: PROCESSENTRY32W = unsafe { zeroed() };
+        entry.szExeFile[..units.len()].copy_from_slice(units);
+        entry
+    }
+
+    fn utf16(text: &str) -> Vec<u16> {
+        text.encode_utf16().collect()
+    }
+
+    #[test]
+    fn test_exe_name_from_entry() {
+        assert_eq!(
+            exe_name_from_entry(&entry_with_name(&utf16("lsass.exe"))),
+            "lsass.exe"
+        );
+        assert_eq!(exe_name_from_entry(&entry_with_name(&[])), "");
+
+        // NUL 之后的残留内容忽略
+        let mut embedded = utf16("csrss.exe");
+        embedded.push(0);
+        embedded.extend(utf16("garbage"));
+        assert_eq!(
+            exe_name_from_entry(&entry_with_name(&embedded)),
+            "csrss.exe"
+        );
+
+        // 缓冲区写满、没有 NUL 结尾
+        let capacity = entry_with_name(&[]).szExeFile.len();
+        let full = vec![u16::from(b'a'); capacity];
+        assert_eq!(
+            exe_name_from_entry(&entry_with_name(&full)),
+            "a".repeat(capacity)
+        );
+
+        // 未配对的代理项替换为 U+FFFD，不会失败
+        let mut lone = utf16("x");
+        lone.push(0xD800);
+        assert_eq!(exe_name_from_entry(&entry_with_name(&lone)), "x\u{FFFD}");
+        println!("✅ 进程名解析处理各种缓冲区");
+    }
+
+    #[test]
+    fn test_enumerate_processes_stops_on_break() {
+        let mut seen = Vec::new();
+        let visited = enumerate_processes(|process| {
+            seen.push(process.pid);
+            if seen.len() == 3 {
+                ControlFlow::Break(())
+            } else {
+                ControlFlow::Continue(())
+            }
+        })
+        .expect("进程枚举失败");
+        assert_eq!(visited, 3);
+        assert_eq!(seen.len(), 3);
+
+        // 完整遍历包含当前进程
+        let own = std::process::id();
+        let mut found = false;
+        let total = enumerate_processes(|process| {
+            found |= process.pid == own;
+            assert!(process.token.is_none());
+            ControlFlow::Continue(())
+        })
+        .expect("进程枚举失败");
+        assert!(found && total > 3);
+        println!("✅ 进程枚举可提前结束");
+    }
+
+    #[test]
+    fn test_get_pids_by_name_returns_every_instance() {
+        let mut children: Vec<_> = (0..2)
+            .map(|_| {
+                std::process::Command::new("cmd.exe")
+                    .args(["/c", "ping -n 5 127.0.0.1 >nul"])
+                    .spawn()
+                    .expect("启动子进程失败")
+            })
+            .collect();
+
+        let pids = get_pids_by_name("CMD.EXE").expect("应找到 cmd.exe");
+        for child in &children {
+            assert!(pids.contains(&child.id()), "缺少 PID {}", child.id());
+        }
+        for child in &mut children {
+            let _ = child.kill();
+            let _ = child.wait();
+        }
+
+        let err = get_pids_by_name("amberlock-no-such-process.exe").expect_err("不应找到");
+        assert!(err.to_string().contains("未找到进程"));
+        println!("✅ 按名称返回全部同名进程");
+    }
+
     #[test]
     #[cfg_attr(not(target_os = "windows"), ignore)]
     fn test_privilege_guard() {
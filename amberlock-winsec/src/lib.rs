@@ -1,11 +1,19 @@
 #![cfg(target_os = "windows")]
+pub mod filters;
 pub mod impersonate;
+pub mod network;
 mod sddl;
 mod setlabel;
 pub mod token;
+pub mod volume;
 
 use windows::Win32::Foundation::{CloseHandle, HANDLE};
 // 导出核心功能
+pub use filters::{
+    list_filter_drivers,
+    probe_label_access,
+};
+
 pub use impersonate::{
     spawn_system_process,
     with_system_privileges,
@@ -15,6 +23,14 @@ pub use impersonate::{
     PrivilegeGuard,
 };
 
+pub use network::probe_network_share;
+
+pub use sddl::{
+    read_security_descriptor,
+    reset_dacl_inheritance,
+    set_object_owner,
+};
+
 pub use setlabel::{
     SddlLabel,
     compute_effective_level,
@@ -31,6 +47,11 @@ pub use token::{
     read_user_sid,
 };
 
+pub use volume::{
+    file_id_of,
+    volume_of,
+};
+
 
 /// 特权类型枚举
 #[derive(Debug, Clone, Copy)]
@@ -0,0 +1,150 @@
+//! 网络共享探测
+//!
+//! SMB 会话过期时，共享上的每次标签操作都以拒绝访问或登录失败结束，
+//! 而"以管理员身份运行"的通用建议并不能解决问题。本模块按以下顺序探测共享：
+//! 1. 解析服务器名称
+//! 2. 连接 SMB 端口（445）
+//! 3. 读取共享根的卷信息，按错误码区分身份验证失败与其他错误
+//!
+//! 探测是阻塞的；需要超时与缓存时请使用 `amberlock_core::network`。
+
+use amberlock_types::{NetworkFailure, NetworkProbe, ShareCaps};
+use std::net::{TcpStream, ToSocketAddrs};
+use std::time::Duration;
+use windows::Win32::Storage::FileSystem::GetVolumeInformationW;
+use windows::Win32::System::SystemServices::FILE_PERSISTENT_ACLS;
+use windows::core::PCWSTR;
+
+/// SMB 端口
+const SMB_PORT: u16 = 445;
+/// 单个地址的 TCP 连接超时
+const CONNECT_TIMEOUT: Duration = Duration::from_secs(3);
+/// 名称解析失败时记录的错误码（WSAHOST_NOT_FOUND）
+const NAME_NOT_FOUND_CODE: u32 = 11001;
+/// 所有地址都无法连接且没有系统错误码时记录的错误码（ERROR_HOST_UNREACHABLE）
+const HOST_UNREACHABLE_CODE: u32 = 1232;
+
+/// 探测网络共享
+///
+/// # 参数
+/// - `share`: 共享根（`\\server\share`）
+///
+/// # 返回
+/// 探测结果；成功时包含共享的文件系统能力
+pub fn probe_network_share(share: &str) -> NetworkProbe {
+    let mut probe = NetworkProbe {
+        share: share.to_string(),
+        reachable: false,
+        authenticated: false,
+        share_caps: None,
+        failure: None,
+    };
+
+    let server = share
+        .trim_start_matches('\\')
+        .split('\\')
+        .next()
+        .unwrap_or_default();
+    let addrs: Vec<_> = match (server, SMB_PORT).to_socket_addrs() {
+        Ok(addrs) => addrs.collect(),
+        Err(_) => Vec::new(),
+    };
+    if addrs.is_empty() {
+        probe.failure = Some((NetworkFailure::NameResolution, NAME_NOT_FOUND_CODE));
+        return probe;
+    }
+
+    let mut connect_code = HOST_UNREACHABLE_CODE;
+    let mut connected = false;
+    for addr in &addrs {
+        match TcpStream::connect_timeout(addr, CONNECT_TIMEOUT) {
+            Ok(_) => {
+                connected = true;
+                break;
+            }
+            Err(e) => {
+                if let Some(code) = e.raw_os_error() {
+                    connect_code = code as u32;
+                }
+            }
+        }
+    }
+    if !connected {
+        probe.failure = Some((NetworkFailure::Unreachable, connect_code));
+        return probe;
+    }
+    probe.reachable = true;
+
+    match share_caps(share) {
+        Ok(caps) => {
+            probe.authenticated = true;
+            probe.share_caps = Some(caps);
+        }
+        Err(code) => {
+            let failure = NetworkFailure::classify(code);
+            probe.reachable = !matches!(
+                failure,
+                NetworkFailure::NameResolution | NetworkFailure::Unreachable
+            );
+            probe.failure = Some((failure, code));
+        }
+    }
+    probe
+}
+
+/// 读取共享根的文件系统能力
+///
+/// # 返回
+/// 失败时返回 Win32 错误码
+fn share_caps(share: &str) -> Result<ShareCaps, u32> {
+    let root = format!("{}\\", share.trim_end_matches('\\'));
+    let wide_root: Vec<u16> = root.encode_utf16().chain(Some(0)).collect();
+    let mut flags = 0u32;
+    let mut fs_name = [0u16; 64];
+
+    unsafe {
+        GetVolumeInformationW(
+            PCWSTR(wide_root.as_ptr()),
+            None,
+            None,
+            None,
+            Some(&mut flags),
+            Some(&mut fs_name),
+        )
+    }
+    .map_err(|e| e.code().0 as u32)?;
+
+    let len = fs_name
+        .iter()
+        .position(|&c| c == 0)
+        .unwrap_or(fs_name.len());
+    Ok(ShareCaps {
+        file_system: String::from_utf16_lossy(&fs_name[..len]),
+        persistent_acls: flags & FILE_PERSISTENT_ACLS != 0,
+    })
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    /// 需要本机开启文件共享（默认管理共享 `C$`）
+    #[test]
+    #[ignore]
+    fn test_probe_loopback_share() {
+        let probe = probe_network_share(r"\\localhost\C$");
+        println!("{:?}", probe);
+        assert!(probe.reachable);
+    }
+
+    #[test]
+    #[ignore]
+    fn test_probe_unresolvable_server() {
+        let probe = probe_network_share(r"\\amberlock-no-such-host.invalid\share");
+        assert_eq!(
+            probe.failure,
+            Some((NetworkFailure::NameResolution, NAME_NOT_FOUND_CODE))
+        );
+        assert!(!probe.reachable && !probe.authenticated);
+    }
+}
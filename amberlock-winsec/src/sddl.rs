@@ -4,11 +4,15 @@ use amberlock_types::{AmberlockError, LabelLevel, Result};
 use windows::Win32::{
     Foundation::{HLOCAL, LocalFree},
     Security::Authorization::{
-        ConvertSecurityDescriptorToStringSecurityDescriptorW,
+        ConvertSecurityDescriptorToStringSecurityDescriptorW, ConvertStringSidToSidW,
         ConvertStringSecurityDescriptorToSecurityDescriptorW, GetNamedSecurityInfoW,
         SE_FILE_OBJECT, SetNamedSecurityInfoW,
     },
-    Security::{LABEL_SECURITY_INFORMATION, PSECURITY_DESCRIPTOR, SACL_SECURITY_INFORMATION},
+    Security::{
+        DACL_SECURITY_INFORMATION, GROUP_SECURITY_INFORMATION, LABEL_SECURITY_INFORMATION,
+        OWNER_SECURITY_INFORMATION, PSECURITY_DESCRIPTOR, PSID, SACL_SECURITY_INFORMATION,
+        UNPROTECTED_DACL_SECURITY_INFORMATION,
+    },
     System::SystemServices::SECURITY_DESCRIPTOR_REVISION,
 };
 use windows::core::PWSTR;
@@ -104,6 +108,64 @@ pub fn read_ml_from_object(path: &str) -> Result<(Option<LabelLevel>, String)> {
     }
 }
 
+/// 读取对象的安全描述符（所有者、组、DACL 与 Mandatory Label）
+///
+/// # 参数
+/// - `path`: 文件/目录路径
+///
+/// # 返回
+/// 安全描述符的 SDDL 字符串
+///
+/// # 注意
+/// 只需要 READ_CONTROL，不读取审核 ACE，因此不需要 SeSecurityPrivilege
+pub fn read_security_descriptor(path: &str) -> Result<String> {
+    let info = OWNER_SECURITY_INFORMATION
+        | GROUP_SECURITY_INFORMATION
+        | DACL_SECURITY_INFORMATION
+        | LABEL_SECURITY_INFORMATION;
+    unsafe {
+        let wide_path: Vec<u16> = path.encode_utf16().chain(Some(0)).collect();
+
+        let mut sd_ptr: PSECURITY_DESCRIPTOR = PSECURITY_DESCRIPTOR::default();
+        GetNamedSecurityInfoW(
+            PWSTR(wide_path.as_ptr() as *mut _),
+            SE_FILE_OBJECT,
+            info,
+            None,
+            None,
+            None,
+            None,
+            &mut sd_ptr,
+        )
+            .ok()
+            .map_err(|e| AmberlockError::Win32 {
+                code: e.code().0 as u32,
+                msg: format!("获取对象 {} 的安全描述符失败: {}", path, e),
+            })?;
+
+        let mut sddl_ptr = PWSTR::null();
+        let converted = ConvertSecurityDescriptorToStringSecurityDescriptorW(
+            sd_ptr,
+            SECURITY_DESCRIPTOR_REVISION,
+            info,
+            &mut sddl_ptr,
+            None,
+        );
+        LocalFree(Some(HLOCAL(sd_ptr.0)));
+        converted.map_err(|e| AmberlockError::Win32 {
+            code: e.code().0 as u32,
+            msg: format!("安全描述符转换为 SDDL 失败: {}", e),
+        })?;
+
+        let sddl_string = sddl_ptr.to_string().map_err(|e| AmberlockError::Win32 {
+            code: 0,
+            msg: format!("SDDL 包含无效的 UTF-16: {}", e),
+        });
+        LocalFree(Some(HLOCAL(sddl_ptr.0 as *mut _)));
+        sddl_string
+    }
+}
+
 /// 清除对象中的 Mandatory Label
 ///
 /// # 参数
@@ -156,6 +218,97 @@ pub fn clear_ml_on_object(path: &str) -> Result<()> {
     }
 }
 
+/// 恢复对象 DACL 的继承（清除受保护标志，保留显式 ACE）
+///
+/// # 参数
+/// - `path`: 文件/目录路径
+///
+/// # 注意
+/// 需要 WRITE_DAC 权限；清除后父目录的可继承 ACE 重新传播到对象
+pub fn reset_dacl_inheritance(path: &str) -> Result<()> {
+    unsafe {
+        let wide_path: Vec<u16> = path.encode_utf16().chain(Some(0)).collect();
+
+        let mut sd_ptr: PSECURITY_DESCRIPTOR = PSECURITY_DESCRIPTOR::default();
+        let mut dacl_ptr = std::ptr::null_mut();
+        GetNamedSecurityInfoW(
+            PWSTR(wide_path.as_ptr() as *mut _),
+            SE_FILE_OBJECT,
+            DACL_SECURITY_INFORMATION,
+            None,
+            None,
+            Some(&mut dacl_ptr),
+            None,
+            &mut sd_ptr,
+        )
+            .ok()
+            .map_err(|e| AmberlockError::Win32 {
+                code: e.code().0 as u32,
+                msg: format!("获取对象 {} 的 DACL 失败: {}", path, e),
+            })?;
+
+        // 以原 DACL 重新写入并标记为不受保护
+        let result = SetNamedSecurityInfoW(
+            PWSTR(wide_path.as_ptr() as *mut _),
+            SE_FILE_OBJECT,
+            DACL_SECURITY_INFORMATION | UNPROTECTED_DACL_SECURITY_INFORMATION,
+            None,
+            None,
+            Some(dacl_ptr as *const _),
+            None,
+        )
+            .ok()
+            .map_err(|e| AmberlockError::Win32 {
+                code: e.code().0 as u32,
+                msg: format!("恢复对象 {} 的 DACL 继承失败: {}", path, e),
+            });
+
+        LocalFree(Some(HLOCAL(sd_ptr.0)));
+        result
+    }
+}
+
+/// 设置对象的所有者
+///
+/// # 参数
+/// - `path`: 文件/目录路径
+/// - `owner_sid`: 新所有者的 SID 字符串
+///
+/// # 注意
+/// 设为其他主体需要 SeRestorePrivilege，取得所有权需要 WRITE_OWNER 或 SeTakeOwnershipPrivilege
+pub fn set_object_owner(path: &str, owner_sid: &str) -> Result<()> {
+    unsafe {
+        let wide_path: Vec<u16> = path.encode_utf16().chain(Some(0)).collect();
+        let wide_sid: Vec<u16> = owner_sid.encode_utf16().chain(Some(0)).collect();
+
+        let mut sid = PSID::default();
+        ConvertStringSidToSidW(PWSTR(wide_sid.as_ptr() as *mut _), &mut sid).map_err(|e| {
+            AmberlockError::Win32 {
+                code: e.code().0 as u32,
+                msg: format!("无效的 SID {}: {}", owner_sid, e),
+            }
+        })?;
+
+        let result = SetNamedSecurityInfoW(
+            PWSTR(wide_path.as_ptr() as *mut _),
+            SE_FILE_OBJECT,
+            OWNER_SECURITY_INFORMATION,
+            Some(sid),
+            None,
+            None,
+            None,
+        )
+            .ok()
+            .map_err(|e| AmberlockError::Win32 {
+                code: e.code().0 as u32,
+                msg: format!("设置对象 {} 的所有者失败: {}", path, e),
+            });
+
+        LocalFree(Some(HLOCAL(sid.0)));
+        result
+    }
+}
+
 /// 从 SDDL 字符串解析 ML 信息
 ///
 /// # 参数
@@ -11,10 +11,14 @@ use crate::HandleGuard;
 use once_cell::sync::Lazy;
 use std::sync::Mutex;
 use windows::Win32::Security::TOKEN_QUERY;
+use std::ffi::c_void;
 use windows::Win32::{
-    Foundation::{LocalFree, HANDLE, HLOCAL},
+    Foundation::{ERROR_BAD_LENGTH, ERROR_INSUFFICIENT_BUFFER, HANDLE, HLOCAL, LocalFree},
     Security::Authorization::ConvertSidToStringSidW,
-    Security::{GetTokenInformation, TokenIntegrityLevel, TokenUser},
+    Security::{
+        GetLengthSid, GetTokenInformation, IsValidSid, TOKEN_INFORMATION_CLASS,
+        TOKEN_MANDATORY_LABEL, TOKEN_USER, TokenIntegrityLevel, TokenUser,
+    },
     System::Threading::{GetCurrentProcess, OpenProcessToken},
 };
 
@@ -22,102 +26,157 @@ use windows::Win32::{
 /// 全局能力探测缓存
 static CAPABILITY_CACHE: Lazy<Mutex<Option<CapabilityProbe>>> = Lazy::new(|| Mutex::new(None));
 
-/// 读取当前进程的完整性级别
-pub fn read_process_il() -> Result<LabelLevel> {
-    unsafe {
-        let mut token_handle = HANDLE::default();
-        OpenProcessToken(GetCurrentProcess(), TOKEN_QUERY, &mut token_handle).map_err(|e| {
-            AmberlockError::Win32 {
-                code: e.code().0 as u32,
-                msg: format!("打开进程令牌失败: {}", e),
+/// 令牌信息查询的最大尝试次数（两次调用之间所需长度可能变化）
+const MAX_QUERY_ATTEMPTS: usize = 4;
+
+/// SID 固定头部长度：Revision(1) + SubAuthorityCount(1) + IdentifierAuthority(6)
+const SID_HEADER_LEN: usize = 8;
+
+/// 查询令牌信息，返回原始缓冲区
+///
+/// 先以空缓冲区查询所需长度，再分配缓冲区查询；
+/// 两次调用之间所需长度变大时按新长度重试。
+///
+/// # 参数
+/// - `token`: 具有 `TOKEN_QUERY` 权限的令牌句柄
+/// - `class`: 信息类别
+///
+/// # 返回
+/// 长度等于实际写入长度的缓冲区
+///
+/// # 注意
+/// 只有 `ERROR_INSUFFICIENT_BUFFER` / `ERROR_BAD_LENGTH` 会触发重试，
+/// 其他错误（如无效句柄）直接返回 `Win32`
+pub fn query_token_info(token: HANDLE, class: TOKEN_INFORMATION_CLASS) -> Result<Vec<u8>> {
+    let mut size = 0u32;
+    for _ in 0..MAX_QUERY_ATTEMPTS {
+        let mut buffer = vec![0u8; size as usize];
+        let mut needed = 0u32;
+        let ptr = (!buffer.is_empty()).then_some(buffer.as_mut_ptr() as *mut c_void);
+        let result = unsafe { GetTokenInformation(token, class, ptr, size, &mut needed) };
+        match result {
+            Ok(()) => {
+                buffer.truncate(needed as usize);
+                return Ok(buffer);
             }
-        })?;
+            Err(e) if is_buffer_too_small(&e) && needed > size => size = needed,
+            Err(e) => {
+                return Err(AmberlockError::Win32 {
+                    code: e.code().0 as u32,
+                    msg: format!("查询令牌信息失败: {}", e),
+                });
+            }
+        }
+    }
+    Err(AmberlockError::Win32 {
+        code: ERROR_INSUFFICIENT_BUFFER.0,
+        msg: format!("令牌信息长度持续变化，{} 次尝试后放弃", MAX_QUERY_ATTEMPTS),
+    })
+}
 
-        let _guard = HandleGuard(token_handle);
+fn is_buffer_too_small(e: &windows::core::Error) -> bool {
+    e.code() == ERROR_INSUFFICIENT_BUFFER.to_hresult() || e.code() == ERROR_BAD_LENGTH.to_hresult()
+}
 
-        let mut return_length = 0u32;
-        let _ = GetTokenInformation(
-            token_handle,
-            TokenIntegrityLevel,
-            None,
-            0,
-            &mut return_length,
-        );
+/// 校验缓冲区至少容纳 `needed` 字节
+fn check_len(buf_len: usize, needed: usize, what: &str) -> Result<()> {
+    if buf_len < needed {
+        return Err(AmberlockError::MalformedTokenInfo(format!(
+            "{} 需要 {} 字节，实际 {} 字节",
+            what, needed, buf_len
+        )));
+    }
+    Ok(())
+}
 
-        // 分配缓冲区并再次查询
-        let mut buffer = vec![0u8; return_length as usize];
-        GetTokenInformation(
-            token_handle,
-            TokenIntegrityLevel,
-            Some(buffer.as_mut_ptr() as *mut _),
-            return_length,
-            &mut return_length,
-        )
-            .map_err(|e| AmberlockError::Win32 {
-                code: e.code().0 as u32,
-                msg: format!("获取令牌完整性级别失败: {}", e),
-            })?;
-
-        // 解析 TOKEN_MANDATORY_LABEL 结构
-        // 结构布局：Label (SID_AND_ATTRIBUTES) -> Sid (PSID) -> SubAuthority[...]
-        let label_ptr = buffer.as_ptr() as *const windows::Win32::Security::TOKEN_MANDATORY_LABEL;
-        let sid = (*label_ptr).Label.Sid;
-
-        // 获取 SubAuthority 数组（最后一个元素是 RID）
-        use windows::Win32::Security::{GetSidSubAuthority, GetSidSubAuthorityCount};
-        let sub_auth_count = *GetSidSubAuthorityCount(sid);
-        let rid_ptr = GetSidSubAuthority(sid, (sub_auth_count - 1) as u32);
-        let rid = *rid_ptr;
-
-        // 根据 RID 映射到完整性级别
-        // 标准映射：0x1000=Low, 0x2000=Medium, 0x3000=High, 0x4000=System
-        match rid {
-            0x2000 => Ok(LabelLevel::Medium),
-            0x3000 => Ok(LabelLevel::High),
-            0x4000..=0x5000 => Ok(LabelLevel::System),
-            _ => Ok(LabelLevel::Medium),
-        }
+/// 按子授权数量计算 SID 长度
+fn sid_length(sub_authority_count: u8) -> usize {
+    SID_HEADER_LEN + 4 * sub_authority_count as usize
+}
+
+/// 定位缓冲区内的 SID
+///
+/// # 返回
+/// SID 起止位置完整落在缓冲区内时返回其字节切片
+fn sid_bytes(buffer: &[u8], sid_addr: usize) -> Result<&[u8]> {
+    let out_of_range = || AmberlockError::MalformedTokenInfo("SID 超出缓冲区范围".to_string());
+    let offset = sid_addr
+        .checked_sub(buffer.as_ptr() as usize)
+        .ok_or_else(out_of_range)?;
+    let header = offset
+        .checked_add(SID_HEADER_LEN)
+        .and_then(|end| buffer.get(offset..end))
+        .ok_or_else(out_of_range)?;
+    offset
+        .checked_add(sid_length(header[1]))
+        .and_then(|end| buffer.get(offset..end))
+        .ok_or_else(out_of_range)
+}
+
+/// 读取 SID 的最后一个子授权（完整性 SID 的 RID）
+fn last_sub_authority(sid: &[u8]) -> Result<u32> {
+    let count = *sid
+        .get(1)
+        .ok_or_else(|| AmberlockError::MalformedTokenInfo("SID 头部不完整".to_string()))?;
+    if count == 0 {
+        return Err(AmberlockError::MalformedTokenInfo(
+            "完整性 SID 没有子授权".to_string(),
+        ));
     }
+    let start = SID_HEADER_LEN + 4 * (count as usize - 1);
+    sid.get(start..start + 4)
+        .map(|bytes| u32::from_le_bytes([bytes[0], bytes[1], bytes[2], bytes[3]]))
+        .ok_or_else(|| AmberlockError::MalformedTokenInfo("SID 子授权超出范围".to_string()))
 }
 
-/// 读取当前用户的 SID 字符串
-pub fn read_user_sid() -> Result<String> {
-    unsafe {
-        // 打开当前进程令牌
-        let mut token_handle = HANDLE::default();
-        OpenProcessToken(GetCurrentProcess(), TOKEN_QUERY, &mut token_handle).map_err(|e| {
-            AmberlockError::Win32 {
-                code: e.code().0 as u32,
-                msg: format!("打开进程令牌失败: {}", e),
-            }
-        })?;
+/// 根据 RID 映射到完整性级别
+///
+/// 标准映射：0x1000=Low, 0x2000=Medium, 0x3000=High, 0x4000=System
+fn level_from_rid(rid: u32) -> LabelLevel {
+    match rid {
+        0x2000 => LabelLevel::Medium,
+        0x3000 => LabelLevel::High,
+        0x4000..=0x5000 => LabelLevel::System,
+        _ => LabelLevel::Medium,
+    }
+}
+
+/// 读取令牌的完整性级别
+fn token_integrity_level(token: HANDLE) -> Result<LabelLevel> {
+    let buffer = query_token_info(token, TokenIntegrityLevel)?;
+    check_len(
+        buffer.len(),
+        size_of::<TOKEN_MANDATORY_LABEL>(),
+        "TOKEN_MANDATORY_LABEL",
+    )?;
+
+    // 长度已校验；Vec<u8> 不保证对齐，按非对齐读取
+    let label =
+        unsafe { std::ptr::read_unaligned(buffer.as_ptr() as *const TOKEN_MANDATORY_LABEL) };
+    let sid = sid_bytes(&buffer, label.Label.Sid.0 as usize)?;
+    last_sub_authority(sid).map(level_from_rid)
+}
+
+/// 读取令牌的用户 SID 字符串
+fn token_user_sid(token: HANDLE) -> Result<String> {
+    let buffer = query_token_info(token, TokenUser)?;
+    check_len(buffer.len(), size_of::<TOKEN_USER>(), "TOKEN_USER")?;
 
-        let _guard = HandleGuard(token_handle);
-
-        // 查询用户信息
-        let mut return_length = 0u32;
-        let _ = GetTokenInformation(token_handle, TokenUser, None, 0, &mut return_length);
-
-        let mut buffer = vec![0u8; return_length as usize];
-        GetTokenInformation(
-            token_handle,
-            TokenUser,
-            Some(buffer.as_mut_ptr() as *mut _),
-            return_length,
-            &mut return_length,
-        )
-            .map_err(|e| AmberlockError::Win32 {
-                code: e.code().0 as u32,
-                msg: format!("获取令牌用户信息失败: {}", e),
-            })?;
-
-        // 解析 TOKEN_USER 结构
-        let token_user_ptr = buffer.as_ptr() as *const windows::Win32::Security::TOKEN_USER;
-        let sid = (*token_user_ptr).User.Sid;
+    let user = unsafe { std::ptr::read_unaligned(buffer.as_ptr() as *const TOKEN_USER) };
+    let psid = user.User.Sid;
+    let sid = sid_bytes(&buffer, psid.0 as usize)?;
+
+    unsafe {
+        // SID 头部已在缓冲区内，GetLengthSid 只读取头部
+        if !IsValidSid(psid).as_bool() || GetLengthSid(psid) as usize != sid.len() {
+            return Err(AmberlockError::MalformedTokenInfo(
+                "令牌用户 SID 无效".to_string(),
+            ));
+        }
 
         // 转换 SID 为字符串
         let mut sid_string = windows::core::PWSTR::null();
-        ConvertSidToStringSidW(sid, &mut sid_string).map_err(|e| AmberlockError::Win32 {
+        ConvertSidToStringSidW(psid, &mut sid_string).map_err(|e| AmberlockError::Win32 {
             code: e.code().0 as u32,
             msg: format!("转换 SID 为字符串失败: {}", e),
         })?;
@@ -126,15 +185,39 @@ pub fn read_user_sid() -> Result<String> {
         let result = sid_string.to_string().map_err(|e| AmberlockError::Win32 {
             code: 0,
             msg: format!("SID 包含无效的 UTF-16: {}", e),
-        })?;
+        });
 
         // 释放 Windows 分配的字符串
-        LocalFree(Some(HLOCAL(sid_string.0 as *mut core::ffi::c_void)));
+        LocalFree(Some(HLOCAL(sid_string.0 as *mut c_void)));
 
-        Ok(result)
+        result
     }
 }
 
+/// 打开当前进程令牌（仅查询权限）
+fn open_process_token() -> Result<HandleGuard> {
+    let mut token_handle = HANDLE::default();
+    unsafe { OpenProcessToken(GetCurrentProcess(), TOKEN_QUERY, &mut token_handle) }.map_err(
+        |e| AmberlockError::Win32 {
+            code: e.code().0 as u32,
+            msg: format!("打开进程令牌失败: {}", e),
+        },
+    )?;
+    Ok(HandleGuard(token_handle))
+}
+
+/// 读取当前进程的完整性级别
+pub fn read_process_il() -> Result<LabelLevel> {
+    let token = open_process_token()?;
+    token_integrity_level(token.0)
+}
+
+/// 读取当前用户的 SID 字符串
+pub fn read_user_sid() -> Result<String> {
+    let token = open_process_token()?;
+    token_user_sid(token.0)
+}
+
 /// 系统能力探测（带缓存）
 ///
 /// # 返回
@@ -211,6 +294,62 @@ mod tests {
         }
     }
 
+    #[test]
+    fn test_query_token_info_invalid_handle() {
+        let result = query_token_info(HANDLE::default(), TokenUser);
+        assert!(
+            matches!(result, Err(AmberlockError::Win32 { .. })),
+            "无效句柄应返回 Win32 错误"
+        );
+        assert!(token_integrity_level(HANDLE::default()).is_err());
+        assert!(token_user_sid(HANDLE::default()).is_err());
+        println!("✅ 无效令牌句柄返回错误而非未定义行为");
+    }
+
+    #[test]
+    fn test_check_len() {
+        assert!(check_len(16, 16, "TOKEN_USER").is_ok());
+        assert!(matches!(
+            check_len(0, 16, "TOKEN_USER"),
+            Err(AmberlockError::MalformedTokenInfo(_))
+        ));
+        assert!(check_len(15, 16, "TOKEN_USER").is_err());
+    }
+
+    #[test]
+    fn test_sid_bounds() {
+        // S-1-16-12288（High 完整性），前置 4 字节模拟结构体头部
+        let mut buffer = vec![0u8; 4];
+        buffer.extend_from_slice(&[1, 1, 0, 0, 0, 0, 0, 16]);
+        buffer.extend_from_slice(&0x3000u32.to_le_bytes());
+        let base = buffer.as_ptr() as usize;
+
+        let sid = sid_bytes(&buffer, base + 4).expect("SID 应在缓冲区内");
+        assert_eq!(sid.len(), sid_length(1));
+        assert_eq!(last_sub_authority(sid).unwrap(), 0x3000);
+        assert_eq!(level_from_rid(0x3000), LabelLevel::High);
+
+        // 指针在缓冲区之前 / 头部越界 / 子授权越界
+        assert!(sid_bytes(&buffer, base.wrapping_sub(1)).is_err());
+        assert!(sid_bytes(&buffer, base + buffer.len() - 4).is_err());
+        buffer[5] = 2;
+        let base = buffer.as_ptr() as usize;
+        assert!(sid_bytes(&buffer, base + 4).is_err());
+
+        // 没有子授权
+        assert!(last_sub_authority(&[1, 0, 0, 0, 0, 0, 0, 16]).is_err());
+        assert!(last_sub_authority(&[1, 2, 0, 0, 0, 0, 0, 16, 0, 0, 0, 0]).is_err());
+        println!("✅ SID 边界检查正确");
+    }
+
+    #[test]
+    fn test_level_from_rid() {
+        assert_eq!(level_from_rid(0x2000), LabelLevel::Medium);
+        assert_eq!(level_from_rid(0x4000), LabelLevel::System);
+        assert_eq!(level_from_rid(0x5000), LabelLevel::System);
+        assert_eq!(level_from_rid(0x1000), LabelLevel::Medium);
+    }
+
     #[test]
     fn test_capability_probe() {
         match probe_capability() {
@@ -0,0 +1,116 @@
+//! 卷识别
+//!
+//! 批量操作按对象所在的卷分组节流，需要知道每个路径的卷根与卷类型；
+//! 移动检测需要对象的文件标识（卷序列号 + 文件索引）。
+
+use crate::HandleGuard;
+use amberlock_types::{AmberlockError, FileId, Result, VolumeKind};
+use windows::Win32::Storage::FileSystem::{
+    BY_HANDLE_FILE_INFORMATION, CreateFileW, FILE_FLAG_BACKUP_SEMANTICS, FILE_READ_ATTRIBUTES,
+    FILE_SHARE_DELETE, FILE_SHARE_READ, FILE_SHARE_WRITE, GetDriveTypeW,
+    GetFileInformationByHandle, GetVolumePathNameW, OPEN_EXISTING,
+};
+use windows::Win32::System::WindowsProgramming::{DRIVE_FIXED, DRIVE_REMOTE, DRIVE_REMOVABLE};
+use windows::core::PCWSTR;
+
+/// 卷根路径的最大长度（含 `\\?\Volume{GUID}\` 形式与挂载点）
+const VOLUME_PATH_CAPACITY: usize = 1024;
+
+/// 识别路径所在的卷
+///
+/// # 参数
+/// - `path`: 目标路径（不要求存在）
+///
+/// # 返回
+/// 卷根路径（以 `\` 结尾，如 `C:\`、`\\server\share\`）与卷类型
+pub fn volume_of(path: &str) -> Result<(String, VolumeKind)> {
+    let wide_path: Vec<u16> = path.encode_utf16().chain(Some(0)).collect();
+    let mut root = [0u16; VOLUME_PATH_CAPACITY];
+
+    unsafe { GetVolumePathNameW(PCWSTR(wide_path.as_ptr()), &mut root) }.map_err(|e| {
+        AmberlockError::Win32 {
+            code: e.code().0 as u32,
+            msg: format!("获取卷根失败: {}", e),
+        }
+    })?;
+
+    let len = root.iter().position(|&c| c == 0).unwrap_or(root.len());
+    let kind = match unsafe { GetDriveTypeW(PCWSTR(root.as_ptr())) } {
+        DRIVE_FIXED => VolumeKind::Fixed,
+        DRIVE_REMOVABLE => VolumeKind::Removable,
+        DRIVE_REMOTE => VolumeKind::Network,
+        _ => VolumeKind::Other,
+    };
+    Ok((String::from_utf16_lossy(&root[..len]), kind))
+}
+
+/// 读取对象的文件标识
+///
+/// # 参数
+/// - `path`: 文件或目录路径
+///
+/// # 返回
+/// 卷序列号、卷内文件索引与当前大小
+///
+/// # 注意
+/// 只请求 `FILE_READ_ATTRIBUTES`，不与其他进程的打开方式冲突
+pub fn file_id_of(path: &str) -> Result<FileId> {
+    let wide_path: Vec<u16> = path.encode_utf16().chain(Some(0)).collect();
+    let to_error = |e: windows::core::Error| AmberlockError::Win32 {
+        code: e.code().0 as u32,
+        msg: format!("读取文件标识失败: {}", e),
+    };
+
+    let handle = unsafe {
+        CreateFileW(
+            PCWSTR(wide_path.as_ptr()),
+            FILE_READ_ATTRIBUTES.0,
+            FILE_SHARE_READ | FILE_SHARE_WRITE | FILE_SHARE_DELETE,
+            None,
+            OPEN_EXISTING,
+            FILE_FLAG_BACKUP_SEMANTICS,
+            None,
+        )
+    }
+    .map_err(to_error)?;
+    let _guard = HandleGuard(handle);
+
+    let mut info = BY_HANDLE_FILE_INFORMATION::default();
+    unsafe { GetFileInformationByHandle(handle, &mut info) }.map_err(to_error)?;
+    Ok(FileId {
+        volume_serial: info.dwVolumeSerialNumber,
+        index: (u64::from(info.nFileIndexHigh) << 32) | u64::from(info.nFileIndexLow),
+        size: (u64::from(info.nFileSizeHigh) << 32) | u64::from(info.nFileSizeLow),
+    })
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    #[ignore]
+    fn test_system_drive_is_fixed() {
+        let (root, kind) = volume_of(r"C:\Windows\notepad.exe").expect("识别卷失败");
+        assert_eq!(root.to_uppercase(), r"C:\");
+        assert_eq!(kind, VolumeKind::Fixed);
+    }
+
+    #[test]
+    #[ignore]
+    fn test_rename_keeps_file_id() {
+        let dir = std::env::temp_dir().join("amberlock-file-id-test");
+        std::fs::create_dir_all(&dir).expect("创建目录失败");
+        let before = dir.join("before.txt");
+        let after = dir.join("after.txt");
+        std::fs::write(&before, b"amberlock").expect("写入失败");
+
+        let original = file_id_of(&before.to_string_lossy()).expect("读取标识失败");
+        std::fs::rename(&before, &after).expect("重命名失败");
+        let renamed = file_id_of(&after.to_string_lossy()).expect("读取标识失败");
+
+        assert!(original.same_file(&renamed), "同卷重命名应保持文件标识");
+        assert_eq!(renamed.size, 9);
+        let _ = std::fs::remove_dir_all(&dir);
+    }
+}
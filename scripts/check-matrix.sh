@@ -0,0 +1,35 @@
+#!/usr/bin/env bash
+# 特性/平台构建矩阵
+#
+# 在 Linux 上验证无需 windows-rs 的 crate 可以独立构建和测试；
+# 在 Windows（或安装了 x86_64-pc-windows-gnu 目标）时额外检查完整工作区。
+#
+# 用法: scripts/check-matrix.sh [额外的 cargo 参数，如 --offline]
+set -euo pipefail
+
+cd "$(dirname "$0")/.."
+
+LINUX_TARGET=x86_64-unknown-linux-gnu
+WINDOWS_TARGET=x86_64-pc-windows-gnu
+
+run() {
+    echo "==> cargo $*"
+    cargo "$@"
+}
+
+# 可移植部分：storage/types 无需任何特性调整，auth 退化为纯 Argon2，core 关闭 winsec
+run check -p amberlock-types --target "$LINUX_TARGET" "$@"
+run check -p amberlock-storage --target "$LINUX_TARGET" "$@"
+run check -p amberlock-auth --target "$LINUX_TARGET" "$@"
+run check -p amberlock-core --no-default-features --target "$LINUX_TARGET" "$@"
+run test --lib -p amberlock-types -p amberlock-storage -p amberlock-auth --target "$LINUX_TARGET" "$@"
+run test --lib -p amberlock-core --no-default-features --target "$LINUX_TARGET" "$@"
+
+# 完整工作区（默认特性）与 Windows 上关闭 winsec/dpapi 的组合
+if rustup target list --installed 2>/dev/null | grep -q "$WINDOWS_TARGET"; then
+    run check --workspace --all-targets --target "$WINDOWS_TARGET" "$@"
+    run check -p amberlock-core --no-default-features --all-targets --target "$WINDOWS_TARGET" "$@"
+    run check -p amberlock-auth --no-default-features --all-targets --target "$WINDOWS_TARGET" "$@"
+else
+    echo "==> 未安装 $WINDOWS_TARGET，跳过 Windows 组合"
+fi
@@ -0,0 +1,26 @@
+#!/usr/bin/env bash
+# 重新生成 GUI 内嵌的第三方组件清单（amberlock-gui/src/third_party.in）
+#
+# 依赖树变化（Cargo.lock 更新）后运行；amberlock-gui 的单元测试会在清单
+# 与 Cargo.lock 不一致时失败。需要 jq。
+#
+# 用法: scripts/gen-third-party.sh [额外的 cargo 参数，如 --offline]
+set -euo pipefail
+
+cd "$(dirname "$0")/.."
+
+OUT=amberlock-gui/src/third_party.in
+
+{
+    echo "// 由 scripts/gen-third-party.sh 根据 Cargo.lock 生成，请勿手动编辑"
+    echo "["
+    cargo metadata --format-version 1 --locked "$@" |
+        jq -r '.packages
+            | map(select(.source != null))
+            | sort_by(.name, .version)
+            | .[]
+            | "    Component { name: \(.name | @json), version: \(.version | @json), license: \((.license // "未声明") | @json) },"'
+    echo "]"
+} > "$OUT"
+
+echo "==> 已写入 $OUT（$(grep -c 'Component {' "$OUT") 个组件）"